@@ -87,6 +87,10 @@ nodeagent:
     hostname: "${NODE_NAME}"
     platform: "$(uname -s)"
     architecture: "${ARCH}"
+  log_forwarding:
+    enabled: false
+    target: "journald"
+    syslog_address: "127.0.0.1:514"
 EOF
 
 # Create systemd service file
@@ -4,15 +4,23 @@
 */
 use if_addrs::{get_if_addrs, Interface};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
-use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 // Global config instance
 static NODEAGENT_CONFIG: OnceLock<Config> = OnceLock::new();
 
+// File the config was loaded from, with the contents applied so far
+static CONFIG_SOURCE: OnceLock<Mutex<Source>> = OnceLock::new();
+
+/// Interval between checks of the config file by [`watch`]
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
@@ -20,6 +28,9 @@ pub enum ConfigError {
 
     #[error("Failed to parse YAML: {0}")]
     YamlError(#[from] serde_yaml::Error),
+
+    #[error("Config was not loaded from a file")]
+    NoSource,
 }
 
 #[derive(Debug, Deserialize, Clone, Default, PartialEq)]
@@ -35,6 +46,38 @@ pub struct SystemConfig {
     pub architecture: String,
 }
 
+/// Where the output of the containers is forwarded, see [`crate::logforward`]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LogForwardingConfig {
+    pub enabled: bool,
+    /// `journald` or `syslog`
+    pub target: String,
+    /// UDP address of the syslog server
+    pub syslog_address: String,
+}
+
+impl Default for LogForwardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: "journald".to_string(),
+            syslog_address: "127.0.0.1:514".to_string(),
+        }
+    }
+}
+
+/// Taint the node registers with, see [`NodeAgentConfig::taints`]
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct TaintConfig {
+    pub key: String,
+    #[serde(default)]
+    pub value: String,
+    /// `NoSchedule` or `PreferNoSchedule`
+    #[serde(default = "default_taint_effect")]
+    pub effect: String,
+}
+
 #[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct NodeAgentConfig {
     #[serde(default = "default_node_name")]
@@ -52,6 +95,14 @@ pub struct NodeAgentConfig {
     pub system: SystemConfig,
     #[serde(default = "default_yaml_storage")]
     pub yaml_storage: String,
+    #[serde(default)]
+    pub log_forwarding: LogForwardingConfig,
+    /// Labels models select the node by, e.g. `gpu: "true"`
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Taints keeping the models that do not tolerate them off the node
+    #[serde(default)]
+    pub taints: Vec<TaintConfig>,
 }
 
 fn default_node_name() -> String {
@@ -69,6 +120,10 @@ fn default_node_role() -> String {
     "nodeagent".to_string()
 }
 
+fn default_taint_effect() -> String {
+    "NoSchedule".to_string()
+}
+
 fn default_yaml_storage() -> String {
     "/etc/piccolo/yaml".to_string()
 }
@@ -121,6 +176,19 @@ impl Config {
         self.nodeagent.yaml_storage.clone()
     }
 
+    /// Taints of the node as registered with the API server
+    pub fn get_taints(&self) -> Vec<common::nodeagent::fromapiserver::Taint> {
+        self.nodeagent
+            .taints
+            .iter()
+            .map(|taint| common::nodeagent::fromapiserver::Taint {
+                key: taint.key.clone(),
+                value: taint.value.clone(),
+                effect: taint.effect.clone(),
+            })
+            .collect()
+    }
+
     // Get or initialize the global config
     pub fn get() -> &'static Config {
         NODEAGENT_CONFIG.get().unwrap_or_else(|| {
@@ -136,6 +204,139 @@ impl Config {
     }
 }
 
+struct Source {
+    path: PathBuf,
+    config: Config,
+}
+
+/// Outcome of a reload of the config file
+#[derive(Debug, Default, PartialEq)]
+pub struct ReloadReport {
+    /// Runtime node configuration keys updated from the file
+    pub applied: Vec<String>,
+    /// Rejected runtime keys with the reason they were rejected
+    pub failed: HashMap<String, String>,
+    /// Changed settings that keep their value until the agent restarts
+    pub restart_required: Vec<String>,
+}
+
+impl Config {
+    /// Changes from `self` to `new`
+    ///
+    /// `log_level` and `metrics.collection_interval` are returned as updates
+    /// of the runtime node configuration, the other changed settings by name
+    /// as needing a restart.
+    pub fn changes(&self, new: &Config) -> (HashMap<String, String>, Vec<String>) {
+        let (old, new) = (&self.nodeagent, &new.nodeagent);
+        let mut runtime = HashMap::new();
+        if old.log_level != new.log_level {
+            runtime.insert("log_level".to_string(), new.log_level.clone());
+        }
+        if old.metrics.collection_interval != new.metrics.collection_interval {
+            runtime.insert(
+                "monitoring_interval".to_string(),
+                new.metrics.collection_interval.to_string(),
+            );
+        }
+        let restart_required = [
+            ("node_name", old.node_name != new.node_name),
+            ("node_type", old.node_type != new.node_type),
+            ("node_role", old.node_role != new.node_role),
+            ("master_ip", old.master_ip != new.master_ip),
+            ("node_ip", old.node_ip != new.node_ip),
+            ("grpc_port", old.grpc_port != new.grpc_port),
+            (
+                "metrics.batch_size",
+                old.metrics.batch_size != new.metrics.batch_size,
+            ),
+            ("system", old.system != new.system),
+            ("yaml_storage", old.yaml_storage != new.yaml_storage),
+            ("log_forwarding", old.log_forwarding != new.log_forwarding),
+            ("labels", old.labels != new.labels),
+            ("taints", old.taints != new.taints),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name.to_string())
+        .collect();
+        (runtime, restart_required)
+    }
+}
+
+/// Remember the file the config was loaded from, for [`reload`] and [`watch`]
+pub fn set_source(path: PathBuf, config: Config) {
+    let _ = CONFIG_SOURCE.set(Mutex::new(Source { path, config }));
+}
+
+/// Re-read the config file and apply the settings that can change at runtime
+pub fn reload() -> Result<ReloadReport, ConfigError> {
+    let mut source = CONFIG_SOURCE
+        .get()
+        .ok_or(ConfigError::NoSource)?
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let new = Config::load(&source.path)?;
+    let (runtime, restart_required) = source.config.changes(&new);
+    let (result, saved) = crate::node_config::update(&runtime);
+    if let Err(e) = saved {
+        eprintln!("Reloaded configuration not persisted: {}", e);
+    }
+
+    // Settings that need a restart are reported again by later reloads
+    if result.applied.iter().any(|key| key == "log_level") {
+        source.config.nodeagent.log_level = new.nodeagent.log_level;
+    }
+    if result
+        .applied
+        .iter()
+        .any(|key| key == "monitoring_interval")
+    {
+        source.config.nodeagent.metrics.collection_interval =
+            new.nodeagent.metrics.collection_interval;
+    }
+    Ok(ReloadReport {
+        applied: result.applied,
+        failed: result.failed,
+        restart_required,
+    })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reload the config whenever its file changes
+pub async fn watch() {
+    let Some(path) = CONFIG_SOURCE.get().map(|source| {
+        source
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .path
+            .clone()
+    }) else {
+        return;
+    };
+    let mut last = modified(&path);
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        let current = modified(&path);
+        if current == last {
+            continue;
+        }
+        last = current;
+        match reload() {
+            Ok(report) => println!(
+                "Reloaded {}: applied {:?}, rejected {:?}, restart required for {:?}",
+                path.display(),
+                report.applied,
+                report.failed,
+                report.restart_required
+            ),
+            Err(e) => eprintln!("Configuration not reloaded: {}", e),
+        }
+    }
+}
+
 // Helper function to get network interfaces
 fn get_network_interfaces() -> Result<Vec<Interface>, std::io::Error> {
     get_if_addrs()
@@ -201,4 +402,63 @@ mod tests {
         };
         assert!(!config.get_host_ip().is_empty());
     }
+
+    #[test]
+    fn test_labels_and_taints() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+nodeagent:
+  master_ip: 127.0.0.1
+  grpc_port: 47004
+  log_level: info
+  metrics:
+    collection_interval: 5
+    batch_size: 50
+  system:
+    hostname: node-1
+    platform: linux
+    architecture: x86_64
+  labels:
+    gpu: "true"
+  taints:
+    - key: safety-domain
+      value: ASIL-B
+    - key: dedicated
+      effect: PreferNoSchedule
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.nodeagent.labels["gpu"], "true");
+        let taints = config.get_taints();
+        assert_eq!(taints.len(), 2);
+        assert_eq!(
+            (taints[0].key.as_str(), taints[0].value.as_str()),
+            ("safety-domain", "ASIL-B")
+        );
+        assert_eq!(taints[0].effect, "NoSchedule");
+        assert_eq!(taints[1].effect, "PreferNoSchedule");
+
+        let mut relabelled = config.clone();
+        relabelled.nodeagent.labels.clear();
+        assert_eq!(config.changes(&relabelled).1, vec!["labels"]);
+    }
+
+    #[test]
+    fn test_changes_split_runtime_and_restart_settings() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.nodeagent.log_level = "debug".to_string();
+        new.nodeagent.metrics.collection_interval = 10;
+        new.nodeagent.grpc_port = 47004;
+        new.nodeagent.system.platform = "linux".to_string();
+
+        let (runtime, restart_required) = old.changes(&new);
+        assert_eq!(runtime["log_level"], "debug");
+        assert_eq!(runtime["monitoring_interval"], "10");
+        assert_eq!(restart_required, vec!["grpc_port", "system"]);
+
+        let (runtime, restart_required) = new.changes(&new);
+        assert!(runtime.is_empty());
+        assert!(restart_required.is_empty());
+    }
 }
@@ -2,9 +2,29 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
-use common::nodeagent::fromactioncontroller::{HandleWorkloadRequest, HandleWorkloadResponse};
+use common::correlation::LABEL_CORRELATION;
+use common::nodeagent::fromactioncontroller::{
+    HandleWorkloadRequest, HandleWorkloadResponse, PrefetchImagesRequest, PrefetchImagesResponse,
+};
+use common::spec::k8s::Pod;
+use std::collections::HashMap;
 use tonic::{Request, Response, Status};
 
+/// Pod whose containers are labelled with the correlation ID of the request
+fn with_correlation_label(pod: &str, correlation_id: &str) -> String {
+    if correlation_id.is_empty() {
+        return pod.to_string();
+    }
+    let Ok(mut parsed) = serde_yaml::from_str::<Pod>(pod) else {
+        return pod.to_string();
+    };
+    parsed.add_labels(HashMap::from([(
+        LABEL_CORRELATION.to_string(),
+        correlation_id.to_string(),
+    )]));
+    serde_yaml::to_string(&parsed).unwrap_or_else(|_| pod.to_string())
+}
+
 pub async fn handle_workload(
     request: Request<HandleWorkloadRequest>,
 ) -> Result<Response<HandleWorkloadResponse>, Status> {
@@ -13,11 +33,12 @@ pub async fn handle_workload(
     // TODO - Currently, just create a test nginx container for development.
     //        Need to implement actual workload handling logic.
     let req = request.into_inner();
-    match crate::runtime::podman::handle_workload(req.workload_command, &req.pod).await {
+    let pod = with_correlation_label(&req.pod, &req.correlation_id);
+    match crate::runtime::podman::handle_workload(req.workload_command, &pod).await {
         Ok(_) => {
             println!(
-                "Workload handle {} successfully",
-                req.workload_command.to_string()
+                "Workload handle {} successfully [{}]",
+                req.workload_command, req.correlation_id
             );
             let response = HandleWorkloadResponse {
                 status: true,
@@ -33,3 +54,42 @@ pub async fn handle_workload(
         }
     }
 }
+
+/// Pull the images of models the ActionController is about to launch
+pub async fn prefetch_images(
+    request: Request<PrefetchImagesRequest>,
+) -> Result<Response<PrefetchImagesResponse>, Status> {
+    let req = request.into_inner();
+    let response = crate::image::prefetch(&req.images).await;
+    println!(
+        "Prefetched {} image(s), {} cached, {} failed [{}]",
+        response.pulled.len(),
+        response.cached.len(),
+        response.failed.len(),
+        req.correlation_id
+    );
+    Ok(Response::new(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_correlation_label() {
+        let pod = r#"
+apiVersion: v1
+kind: Pod
+metadata:
+  name: hello
+spec:
+  containers:
+    - name: hello
+      image: hello
+"#;
+        assert_eq!(with_correlation_label(pod, ""), pod);
+
+        let labelled: Pod = serde_yaml::from_str(&with_correlation_label(pod, "cid-1")).unwrap();
+        assert_eq!(labelled.get_labels()[LABEL_CORRELATION], "cid-1");
+    }
+}
@@ -2,13 +2,38 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
+use crate::runtime::podman::logs::{self, Demuxer, LogOptions};
+use common::auth::Role;
 use common::nodeagent::fromapiserver::{
-    ConfigRequest, ConfigResponse, HandleYamlRequest, HandleYamlResponse, HeartbeatRequest,
-    HeartbeatResponse, NodeRegistrationRequest, NodeRegistrationResponse, StatusAck, StatusReport,
+    ConfigRequest, ConfigResponse, ContainerExecRequest, ContainerExecResponse, ContainerLogChunk,
+    ContainerLogsRequest, HandleYamlRequest, HandleYamlResponse, HeartbeatRequest,
+    HeartbeatResponse, ListImagesRequest, ListImagesResponse, NodeRegistrationRequest,
+    NodeRegistrationResponse, PruneImagesRequest, PruneImagesResponse, ReloadConfigRequest,
+    ReloadConfigResponse, StatusAck, StatusReport,
 };
+use common::version::{ApiVersionRequest, ApiVersionResponse};
+use hyper::body::HttpBody;
+use std::pin::Pin;
 use tokio::sync::mpsc;
 use tonic::{Request, Response, Status};
 
+/// RPCs of the NodeAgent reported by `NegotiateApiVersion`
+const CAPABILITIES: &[&str] = &[
+    "HandleYaml",
+    "RegisterNode",
+    "ReportStatus",
+    "Heartbeat",
+    "ReceiveConfig",
+    "ReloadConfig",
+    "NegotiateApiVersion",
+    "GetContainerLogs",
+    "ExecInContainer",
+    "ListImages",
+    "PruneImages",
+    "HandleWorkload",
+    "PrefetchImages",
+];
+
 /// Handle a yaml request from API-Server
 ///
 /// Receives a yaml from API-Server and forwards it to the NodeAgent manager for processing.
@@ -48,7 +73,10 @@ pub async fn register_node(
         message: "Node registration processed".to_string(),
         cluster_token: "node-token".to_string(),
         cluster_config: Some(common::nodeagent::fromapiserver::ClusterConfig {
-            master_endpoint: format!("http://{}:47098", master_ip),
+            master_endpoint: common::address::connect_server_at(
+                common::address::Service::ApiServerGrpc,
+                &master_ip,
+            ),
             heartbeat_interval: 30,
             settings: std::collections::HashMap::new(),
         }),
@@ -89,7 +117,10 @@ pub async fn heartbeat(
     let response = HeartbeatResponse {
         ack: true,
         updated_config: Some(common::nodeagent::fromapiserver::ClusterConfig {
-            master_endpoint: format!("http://{}:47098", master_ip),
+            master_endpoint: common::address::connect_server_at(
+                common::address::Service::ApiServerGrpc,
+                &master_ip,
+            ),
             heartbeat_interval: 30,
             settings: std::collections::HashMap::new(),
         }),
@@ -99,23 +130,247 @@ pub async fn heartbeat(
 }
 
 /// Receive configuration updates from API server
+///
+/// Applies every recognised key to the runtime node configuration and reports
+/// which keys were applied and which were rejected.
 pub async fn receive_config(
     request: Request<ConfigRequest>,
 ) -> Result<Response<ConfigResponse>, Status> {
     println!("Processing ReceiveConfig request");
     let req = request.into_inner();
-
-    // TODO: Apply configuration changes
     println!("Received config with {} settings", req.config.len());
 
+    let (result, saved) = crate::node_config::update(&req.config);
+
+    let message = match (&saved, result.failed.is_empty()) {
+        (Err(e), _) => format!("Configuration applied but not persisted: {}", e),
+        (Ok(()), true) => "Configuration applied successfully".to_string(),
+        (Ok(()), false) => format!(
+            "Applied {} setting(s), rejected {}",
+            result.applied.len(),
+            result.failed.len()
+        ),
+    };
+
     let response = ConfigResponse {
-        applied: true,
-        message: "Configuration applied successfully".to_string(),
+        applied: result.failed.is_empty(),
+        message,
+        applied_keys: result.applied,
+        failed_keys: result.failed,
     };
 
     Ok(Response::new(response))
 }
 
+/// Re-read the configuration file and apply the runtime settings it changed
+pub async fn reload_config(
+    request: Request<ReloadConfigRequest>,
+) -> Result<Response<ReloadConfigResponse>, Status> {
+    println!("Processing ReloadConfig request");
+    common::auth::authorize(&request, "ReloadConfig", Role::Admin)?;
+    let report = crate::config::reload().map_err(|e| Status::failed_precondition(e.to_string()))?;
+    Ok(Response::new(ReloadConfigResponse {
+        applied_keys: report.applied,
+        failed_keys: report.failed,
+        restart_required: report.restart_required,
+    }))
+}
+
+/// Agree on the API version with API-Server and list the RPCs of the NodeAgent
+pub async fn negotiate_api_version(
+    request: Request<ApiVersionRequest>,
+) -> Result<Response<ApiVersionResponse>, Status> {
+    let req = request.into_inner();
+    println!(
+        "{} negotiates API versions {} to {}",
+        req.component, req.min_api_version, req.max_api_version
+    );
+    common::version::respond("nodeagent", &req, CAPABILITIES).map(Response::new)
+}
+
+/// Check a pod or container name before it is put in a Podman API path
+///
+/// Pod names are model names, with the namespace joined by a dot and the
+/// replica or standby suffix, so only letters, digits, dashes and dots are
+/// accepted.
+#[allow(clippy::result_large_err)]
+fn validate_name(what: &str, name: &str) -> Result<(), Status> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && !name.starts_with(['-', '.'])
+        && !name.contains("..");
+    if !valid {
+        return Err(Status::invalid_argument(format!(
+            "invalid {} name '{}'",
+            what, name
+        )));
+    }
+    Ok(())
+}
+
+/// Chunks of the logs of a container sent to API-Server
+pub type ContainerLogStream =
+    Pin<Box<dyn futures::Stream<Item = Result<ContainerLogChunk, Status>> + Send>>;
+
+/// Stream the logs of a container of a Pod to API-Server
+///
+/// Logs are read from podman while the chunks are sent, a followed log ends
+/// when API-Server cancels the request.
+pub async fn get_container_logs(
+    request: Request<ContainerLogsRequest>,
+) -> Result<Response<ContainerLogStream>, Status> {
+    let req = request.into_inner();
+    if req.pod.is_empty() || req.container.is_empty() {
+        return Err(Status::invalid_argument("pod and container are required"));
+    }
+    validate_name("pod", &req.pod)?;
+    validate_name("container", &req.container)?;
+    let container_name = format!("{}_{}", req.pod, req.container);
+    println!("Reading logs of container {}", container_name);
+
+    let options = LogOptions {
+        tail: req.tail,
+        since: req.since,
+        follow: req.follow,
+    };
+    let mut body = logs::open(&container_name, &options)
+        .await
+        .map_err(Status::not_found)?;
+
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut demuxer = Demuxer::default();
+        while let Some(bytes) = body.data().await {
+            let frames = match bytes {
+                Ok(bytes) => demuxer.push(&bytes),
+                Err(e) => {
+                    let _ = tx.send(Err(Status::unavailable(e.to_string()))).await;
+                    return;
+                }
+            };
+            for (stream, data) in frames {
+                let chunk = ContainerLogChunk {
+                    stream: stream.into(),
+                    data,
+                };
+                if tx.send(Ok(chunk)).await.is_err() {
+                    // API-Server stopped reading
+                    return;
+                }
+            }
+        }
+        if let Some((stream, data)) = demuxer.finish() {
+            let chunk = ContainerLogChunk {
+                stream: stream.into(),
+                data,
+            };
+            let _ = tx.send(Ok(chunk)).await;
+        }
+    });
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    Ok(Response::new(Box::pin(stream)))
+}
+
+/// Wait for a diagnostic command when the request sets no timeout
+const EXEC_TIMEOUT_SECS: u32 = 30;
+
+/// Run a one-shot diagnostic command in a container for API-Server
+pub async fn exec_in_container(
+    request: Request<ContainerExecRequest>,
+) -> Result<Response<ContainerExecResponse>, Status> {
+    // Diagnostics of the ApiServer, gated to admins there, and canary probes
+    let principal = common::auth::authorize(&request, "ExecInContainer", Role::Operator)?;
+    common::auth::require_caller(
+        principal.as_ref(),
+        "ExecInContainer",
+        &["apiserver", "actioncontroller"],
+    )?;
+    let caller = principal.map_or_else(|| "anonymous".to_string(), |p| p.name);
+    let req = request.into_inner();
+    if req.pod.is_empty() || req.container.is_empty() || req.command.is_empty() {
+        return Err(Status::invalid_argument(
+            "pod, container and command are required",
+        ));
+    }
+    validate_name("pod", &req.pod)?;
+    validate_name("container", &req.container)?;
+    let container_name = format!("{}_{}", req.pod, req.container);
+    common::auth::audit_granted(
+        "ExecInContainer",
+        &caller,
+        &format!("{:?} in container {}", req.command, container_name),
+    );
+
+    let timeout = match req.timeout_seconds {
+        0 => EXEC_TIMEOUT_SECS,
+        secs => secs,
+    };
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(timeout.into()),
+        crate::runtime::podman::container::exec_output(&container_name, &req.command),
+    )
+    .await
+    .map_err(|_| Status::deadline_exceeded(format!("Command did not exit within {}s", timeout)))?;
+
+    match result {
+        Ok(output) => {
+            common::auth::audit_granted(
+                "ExecInContainer",
+                &caller,
+                &format!(
+                    "{} in container {} exited with {}",
+                    req.command[0], container_name, output.exit_code
+                ),
+            );
+            Ok(Response::new(ContainerExecResponse {
+                stdout: output.stdout,
+                stderr: output.stderr,
+                exit_code: output.exit_code,
+            }))
+        }
+        Err(e) => Err(Status::failed_precondition(format!(
+            "Failed to exec in {}: {}",
+            container_name, e
+        ))),
+    }
+}
+
+/// List the images cached on this node
+pub async fn list_images(
+    _request: Request<ListImagesRequest>,
+) -> Result<Response<ListImagesResponse>, Status> {
+    match crate::image::list().await {
+        Ok(images) => Ok(Response::new(ListImagesResponse { images })),
+        Err(e) => Err(Status::unavailable(format!("Failed to list images: {}", e))),
+    }
+}
+
+/// Remove the cached images no container uses
+pub async fn prune_images(
+    request: Request<PruneImagesRequest>,
+) -> Result<Response<PruneImagesResponse>, Status> {
+    let req = request.into_inner();
+    match crate::image::prune(&req).await {
+        Ok(response) => {
+            println!(
+                "Pruned {} image(s), {} bytes{}",
+                response.removed.len(),
+                response.reclaimed_bytes,
+                if req.dry_run { " (dry run)" } else { "" }
+            );
+            Ok(Response::new(response))
+        }
+        Err(e) => Err(Status::unavailable(format!(
+            "Failed to prune images: {}",
+            e
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::grpc::receiver::{NodeAgentConnection, NodeAgentReceiver};
@@ -170,6 +425,97 @@ spec:
   terminationGracePeriodSeconds: 0
 "#;
 
+    #[tokio::test]
+    async fn test_exec_in_container_is_restricted_to_components() {
+        use common::auth::{Principal, Role};
+        use common::nodeagent::fromapiserver::ContainerExecRequest;
+
+        let request = |caller: Option<(&str, Role)>| {
+            let mut request = Request::new(ContainerExecRequest {
+                pod: "hellow".to_string(),
+                container: "hellow".to_string(),
+                command: vec!["id".to_string()],
+                // Claimed by the client, never trusted
+                caller: "apiserver".to_string(),
+                ..Default::default()
+            });
+            if let Some((name, role)) = caller {
+                request.extensions_mut().insert(Principal {
+                    name: name.to_string(),
+                    role,
+                    namespaces: vec![],
+                });
+            }
+            request
+        };
+        let settings = common::setting::AuthSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        common::auth::scope(settings, async {
+            let anonymous = super::exec_in_container(request(None)).await;
+            assert_eq!(anonymous.unwrap_err().code(), tonic::Code::Unauthenticated);
+            let viewer = super::exec_in_container(request(Some(("apiserver", Role::ReadOnly))));
+            assert_eq!(
+                viewer.await.unwrap_err().code(),
+                tonic::Code::PermissionDenied
+            );
+            let other = super::exec_in_container(request(Some(("dashboard", Role::Operator))));
+            assert_eq!(
+                other.await.unwrap_err().code(),
+                tonic::Code::PermissionDenied
+            );
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_validate_name() {
+        use super::validate_name;
+
+        assert!(validate_name("pod", "team-a.hellow-core-standby").is_ok());
+        assert!(validate_name("container", "Hellow2").is_ok());
+        for name in [
+            "../images",
+            "hellow/json",
+            "hellow?follow=true",
+            ".hidden",
+            "-x",
+        ] {
+            assert_eq!(
+                validate_name("pod", name).unwrap_err().code(),
+                tonic::Code::InvalidArgument
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_requires_an_admin() {
+        use common::auth::{Principal, Role};
+        use common::nodeagent::fromapiserver::ReloadConfigRequest;
+
+        let request = |role: Role| {
+            let mut request = Request::new(ReloadConfigRequest {});
+            request.extensions_mut().insert(Principal {
+                name: "apiserver".to_string(),
+                role,
+                namespaces: vec![],
+            });
+            request
+        };
+        let settings = common::setting::AuthSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        common::auth::scope(settings, async {
+            let anonymous = super::reload_config(Request::new(ReloadConfigRequest {})).await;
+            assert_eq!(anonymous.unwrap_err().code(), tonic::Code::Unauthenticated);
+            let operator = super::reload_config(request(Role::Operator)).await;
+            assert_eq!(operator.unwrap_err().code(), tonic::Code::PermissionDenied);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_handle_yaml_with_valid_artifact_yaml() {
         let (tx, mut rx) = mpsc::channel(1);
@@ -319,7 +665,7 @@ spec:
         );
 
         let mut config_map = std::collections::HashMap::new();
-        config_map.insert("key".to_string(), "value".to_string());
+        config_map.insert("log_level".to_string(), "info".to_string());
 
         let request = ConfigRequest {
             config: config_map,
@@ -334,5 +680,31 @@ spec:
             .into_inner();
         assert!(response.applied);
         assert_eq!(response.message, "Configuration applied successfully");
+        assert_eq!(response.applied_keys, vec!["log_level".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_receive_config_reports_failed_keys() {
+        let (tx, _rx) = mpsc::channel(1);
+        let receiver = NodeAgentReceiver::new(
+            tx,
+            "test-node".to_string(),
+            "test-host".to_string(),
+            "192.168.1.100".to_string(),
+        );
+
+        let mut config_map = std::collections::HashMap::new();
+        config_map.insert("key".to_string(), "value".to_string());
+        config_map.insert("monitoring_interval".to_string(), "abc".to_string());
+
+        let response = receiver
+            .receive_config(Request::new(ConfigRequest { config: config_map }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!response.applied);
+        assert!(response.applied_keys.is_empty());
+        assert!(response.failed_keys.contains_key("key"));
+        assert!(response.failed_keys.contains_key("monitoring_interval"));
     }
 }
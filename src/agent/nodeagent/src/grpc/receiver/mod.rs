@@ -8,13 +8,19 @@ pub mod apiserver;
 
 use common::nodeagent::node_agent_connection_server::NodeAgentConnection;
 use common::nodeagent::{
-    fromactioncontroller::{HandleWorkloadRequest, HandleWorkloadResponse},
+    fromactioncontroller::{
+        HandleWorkloadRequest, HandleWorkloadResponse, PrefetchImagesRequest,
+        PrefetchImagesResponse,
+    },
     fromapiserver::{
-        ConfigRequest, ConfigResponse, HandleYamlRequest, HandleYamlResponse, HeartbeatRequest,
-        HeartbeatResponse, NodeRegistrationRequest, NodeRegistrationResponse, StatusAck,
-        StatusReport,
+        ConfigRequest, ConfigResponse, ContainerExecRequest, ContainerExecResponse,
+        ContainerLogsRequest, HandleYamlRequest, HandleYamlResponse, HeartbeatRequest,
+        HeartbeatResponse, ListImagesRequest, ListImagesResponse, NodeRegistrationRequest,
+        NodeRegistrationResponse, PruneImagesRequest, PruneImagesResponse, ReloadConfigRequest,
+        ReloadConfigResponse, StatusAck, StatusReport,
     },
 };
+use common::version::{ApiVersionRequest, ApiVersionResponse};
 use tokio::sync::mpsc;
 use tonic::{Request, Response, Status};
 
@@ -88,12 +94,70 @@ impl NodeAgentConnection for NodeAgentReceiver {
         apiserver::receive_config(request).await
     }
 
+    /// Re-read the configuration file of the NodeAgent
+    async fn reload_config(
+        &self,
+        request: Request<ReloadConfigRequest>,
+    ) -> Result<Response<ReloadConfigResponse>, Status> {
+        apiserver::reload_config(request).await
+    }
+
+    /// Agree on the API version with API-Server
+    async fn negotiate_api_version(
+        &self,
+        request: Request<ApiVersionRequest>,
+    ) -> Result<Response<ApiVersionResponse>, Status> {
+        apiserver::negotiate_api_version(request).await
+    }
+
+    type GetContainerLogsStream = apiserver::ContainerLogStream;
+
+    /// Stream the logs of a container to API-Server
+    async fn get_container_logs(
+        &self,
+        request: Request<ContainerLogsRequest>,
+    ) -> Result<Response<Self::GetContainerLogsStream>, Status> {
+        apiserver::get_container_logs(request).await
+    }
+
+    /// Run a diagnostic command in a container for API-Server
+    async fn exec_in_container(
+        &self,
+        request: Request<ContainerExecRequest>,
+    ) -> Result<Response<ContainerExecResponse>, Status> {
+        apiserver::exec_in_container(request).await
+    }
+
+    /// List the images cached on this node for API-Server
+    async fn list_images(
+        &self,
+        request: Request<ListImagesRequest>,
+    ) -> Result<Response<ListImagesResponse>, Status> {
+        apiserver::list_images(request).await
+    }
+
+    /// Remove the cached images no container uses
+    async fn prune_images(
+        &self,
+        request: Request<PruneImagesRequest>,
+    ) -> Result<Response<PruneImagesResponse>, Status> {
+        apiserver::prune_images(request).await
+    }
+
     async fn handle_workload(
         &self,
         request: Request<HandleWorkloadRequest>,
     ) -> Result<Response<HandleWorkloadResponse>, Status> {
         actioncontroller::handle_workload(request).await
     }
+
+    /// Pull the images of models ahead of their launch
+    async fn prefetch_images(
+        &self,
+        request: Request<PrefetchImagesRequest>,
+    ) -> Result<Response<PrefetchImagesResponse>, Status> {
+        actioncontroller::prefetch_images(request).await
+    }
 }
 
 /*
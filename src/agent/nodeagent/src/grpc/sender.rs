@@ -18,20 +18,32 @@ use tonic::{Request, Status};
 
 /// Sender for making gRPC requests to Monitoring Server
 #[derive(Clone, Default)]
-pub struct NodeAgentSender {}
+pub struct NodeAgentSender {
+    /// Address of the API server, the one of the master node when not set
+    api_server: Option<String>,
+}
 
 impl NodeAgentSender {
+    fn api_server(&self) -> String {
+        self.api_server.clone().unwrap_or_else(|| {
+            let master_ip = &crate::config::Config::get().nodeagent.master_ip;
+            common::address::connect_server_at(common::address::Service::ApiServerGrpc, master_ip)
+        })
+    }
+
     /// Trigger an action for a scenario
     pub async fn trigger_action(
         &mut self,
         action: Action,
     ) -> Result<tonic::Response<Response>, Status> {
         let addr = common::statemanager::connect_server();
-        let client = StateManagerConnectionClient::connect(addr).await;
+        let client = common::grpc::channel(&addr)
+            .await
+            .map(StateManagerConnectionClient::new);
         match client {
             Ok(mut client) => {
                 // Send the action
-                client.send_action(Request::new(action)).await
+                client.send_action(common::auth::request(action)).await
             }
             Err(e) => {
                 // Handle connection error
@@ -50,7 +62,10 @@ impl NodeAgentSender {
     ) -> Result<tonic::Response<SendContainerListResponse>, Status> {
         let config = crate::config::Config::get();
         let master_ip = config.nodeagent.master_ip.clone();
-        let addr = format!("http://{}:47003", master_ip);
+        let addr = common::address::connect_server_at(
+            common::address::Service::MonitoringServer,
+            &master_ip,
+        );
 
         let client = MonitoringServerConnectionClient::connect(addr).await;
 
@@ -75,7 +90,10 @@ impl NodeAgentSender {
     ) -> Result<tonic::Response<common::monitoringserver::SendNodeInfoResponse>, Status> {
         let config = crate::config::Config::get();
         let master_ip = config.nodeagent.master_ip.clone();
-        let addr = format!("http://{}:47003", master_ip);
+        let addr = common::address::connect_server_at(
+            common::address::Service::MonitoringServer,
+            &master_ip,
+        );
 
         let client = MonitoringServerConnectionClient::connect(addr).await;
 
@@ -92,15 +110,18 @@ impl NodeAgentSender {
     ) -> Result<tonic::Response<SendContainerListResponse>, Status> {
         let config = crate::config::Config::get();
         let master_ip = config.nodeagent.master_ip.clone();
-        let addr = format!("http://{}:47006", master_ip);
+        let addr =
+            common::address::connect_server_at(common::address::Service::StateManager, &master_ip);
 
-        let client = StateManagerConnectionClient::connect(addr).await;
+        let client = common::grpc::channel(&addr)
+            .await
+            .map(StateManagerConnectionClient::new);
 
         match client {
             Ok(mut client) => {
                 // Send the changed container list
                 client
-                    .send_changed_container_list(Request::new(container_list))
+                    .send_changed_container_list(common::auth::request(container_list))
                     .await
             }
             Err(e) => {
@@ -115,16 +136,16 @@ impl NodeAgentSender {
         &mut self,
         registration_request: NodeRegistrationRequest,
     ) -> Result<tonic::Response<NodeRegistrationResponse>, Status> {
-        let config = crate::config::Config::get();
-        let master_ip = config.nodeagent.master_ip.clone();
-        let addr = format!("http://{}:47098", master_ip);
+        let addr = self.api_server();
 
-        let client = ApiServerConnectionClient::connect(addr).await;
+        let client = common::grpc::channel(&addr)
+            .await
+            .map(ApiServerConnectionClient::new);
 
         match client {
             Ok(mut client) => {
                 client
-                    .register_node(Request::new(registration_request))
+                    .register_node(common::auth::request(registration_request))
                     .await
             }
             Err(e) => Err(Status::unknown(format!(
@@ -134,31 +155,54 @@ impl NodeAgentSender {
         }
     }
 
+    /// Agree on the API version with the API server
+    ///
+    /// An API server older than versioning speaks version 1.
+    pub async fn negotiate_api_version(&mut self) -> Result<u32, Status> {
+        let addr = self.api_server();
+
+        let client = common::grpc::channel(&addr)
+            .await
+            .map(ApiServerConnectionClient::new);
+
+        match client {
+            Ok(mut client) => common::version::negotiated(
+                client
+                    .negotiate_api_version(common::auth::request(common::version::request(
+                        "nodeagent",
+                    )))
+                    .await
+                    .map(|response| response.into_inner()),
+            ),
+            Err(e) => Err(Status::unknown(format!(
+                "Failed to connect to API server: {}",
+                e
+            ))),
+        }
+    }
+
     /// Send heartbeat to the API server
     pub async fn send_heartbeat(
         &mut self,
-        _heartbeat_request: HeartbeatRequest,
+        heartbeat_request: HeartbeatRequest,
     ) -> Result<tonic::Response<HeartbeatResponse>, Status> {
-        // For NodeAgent sender, we use the NodeAgent service to send heartbeat
-        // This is a local operation that would be handled by the node's own receiver
-        // In practice, heartbeats are typically sent from NodeAgent to API server
-        // but this implementation allows for local heartbeat processing
-
-        // TODO: Implement heartbeat sending to API server if needed
-        // For now, return a success response
-        // Use master_ip from config
-        let config = crate::config::Config::get();
-        let master_ip = config.nodeagent.master_ip.clone();
-        let master_endpoint = format!("http://{}:47098", master_ip);
-
-        Ok(tonic::Response::new(HeartbeatResponse {
-            ack: true,
-            updated_config: Some(common::nodeagent::fromapiserver::ClusterConfig {
-                master_endpoint,
-                heartbeat_interval: 30,
-                settings: std::collections::HashMap::new(),
-            }),
-        }))
+        let addr = self.api_server();
+
+        let client = common::grpc::channel(&addr)
+            .await
+            .map(ApiServerConnectionClient::new);
+
+        match client {
+            Ok(mut client) => {
+                client
+                    .heartbeat(common::auth::request(heartbeat_request))
+                    .await
+            }
+            Err(e) => Err(Status::unknown(format!(
+                "Failed to connect to API server: {}",
+                e
+            ))),
+        }
     }
 
     /// Send status report to the API server
@@ -166,31 +210,143 @@ impl NodeAgentSender {
         &mut self,
         status_report: StatusReport,
     ) -> Result<tonic::Response<StatusAck>, Status> {
-        // Similar to heartbeat, this is typically a NodeAgent operation
-        // TODO: Implement status reporting to API server if needed
+        let addr = self.api_server();
 
-        println!("Sending status report for node: {}", status_report.node_id);
+        let client = common::grpc::channel(&addr)
+            .await
+            .map(ApiServerConnectionClient::new);
 
-        Ok(tonic::Response::new(StatusAck {
-            received: true,
-            message: "Status report sent successfully".to_string(),
-        }))
+        match client {
+            Ok(mut client) => {
+                client
+                    .report_status(common::auth::request(status_report))
+                    .await
+            }
+            Err(e) => Err(Status::unknown(format!(
+                "Failed to connect to API server: {}",
+                e
+            ))),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::grpc::sender::NodeAgentSender;
+    use common::apiserver::api_server_connection_server::{
+        ApiServerConnection, ApiServerConnectionServer,
+    };
+    use common::apiserver::{
+        GetNodeRequest, GetNodeResponse, GetNodesRequest, GetNodesResponse, GetTopologyRequest,
+        GetTopologyResponse, UpdateTopologyRequest, UpdateTopologyResponse,
+    };
     use common::monitoringserver::{
         ContainerList, NodeInfo, SendContainerListResponse, SendNodeInfoResponse,
     };
+    use common::nodeagent::fromapiserver::ClusterConfig;
     use common::nodeagent::fromapiserver::{
         HeartbeatRequest, HeartbeatResponse, NodeRegistrationRequest, NodeRegistrationResponse,
         StatusAck, StatusReport,
     };
     use common::statemanager::{Action, Response as SMResponse};
+    use common::version::{ApiVersionRequest, ApiVersionResponse};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
     use tonic::{Request, Response, Status};
 
+    /// API server answering heartbeats and status reports
+    struct FakeApiServer;
+
+    #[tonic::async_trait]
+    impl ApiServerConnection for FakeApiServer {
+        async fn get_nodes(
+            &self,
+            _request: Request<GetNodesRequest>,
+        ) -> Result<Response<GetNodesResponse>, Status> {
+            Err(Status::unimplemented("get_nodes"))
+        }
+
+        async fn get_node(
+            &self,
+            _request: Request<GetNodeRequest>,
+        ) -> Result<Response<GetNodeResponse>, Status> {
+            Err(Status::unimplemented("get_node"))
+        }
+
+        async fn register_node(
+            &self,
+            _request: Request<NodeRegistrationRequest>,
+        ) -> Result<Response<NodeRegistrationResponse>, Status> {
+            Err(Status::unimplemented("register_node"))
+        }
+
+        async fn heartbeat(
+            &self,
+            _request: Request<HeartbeatRequest>,
+        ) -> Result<Response<HeartbeatResponse>, Status> {
+            Ok(Response::new(HeartbeatResponse {
+                ack: true,
+                updated_config: Some(ClusterConfig {
+                    master_endpoint: String::new(),
+                    heartbeat_interval: 30,
+                    settings: Default::default(),
+                }),
+            }))
+        }
+
+        async fn report_status(
+            &self,
+            _request: Request<StatusReport>,
+        ) -> Result<Response<StatusAck>, Status> {
+            Ok(Response::new(StatusAck {
+                received: true,
+                message: "Status report sent".to_string(),
+            }))
+        }
+
+        async fn get_topology(
+            &self,
+            _request: Request<GetTopologyRequest>,
+        ) -> Result<Response<GetTopologyResponse>, Status> {
+            Err(Status::unimplemented("get_topology"))
+        }
+
+        async fn update_topology(
+            &self,
+            _request: Request<UpdateTopologyRequest>,
+        ) -> Result<Response<UpdateTopologyResponse>, Status> {
+            Err(Status::unimplemented("update_topology"))
+        }
+
+        async fn negotiate_api_version(
+            &self,
+            _request: Request<ApiVersionRequest>,
+        ) -> Result<Response<ApiVersionResponse>, Status> {
+            Err(Status::unimplemented("negotiate_api_version"))
+        }
+    }
+
+    /// Sender talking to a `FakeApiServer` on a local port
+    async fn sender_with_api_server() -> NodeAgentSender {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = TcpListenerStream::new(listener);
+
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(ApiServerConnectionServer::new(FakeApiServer))
+                .serve_with_incoming(stream)
+                .await
+                .unwrap();
+        });
+
+        // Delay to allow the server to start
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        NodeAgentSender {
+            api_server: Some(format!("http://{}", addr)),
+        }
+    }
+
     #[tokio::test]
     async fn test_trigger_action_success() {
         let mut sender = NodeAgentSender::default();
@@ -266,7 +422,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_heartbeat_returns_success() {
-        let mut sender = NodeAgentSender::default();
+        let mut sender = sender_with_api_server().await;
 
         let req = HeartbeatRequest::default();
         let result = sender.send_heartbeat(req).await;
@@ -278,7 +434,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_status_report_returns_success() {
-        let mut sender = NodeAgentSender::default();
+        let mut sender = sender_with_api_server().await;
 
         let req = StatusReport::default();
         let result = sender.send_status_report(req).await;
@@ -332,7 +488,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_heartbeat_multiple_calls() {
-        let mut sender = NodeAgentSender::default();
+        let mut sender = sender_with_api_server().await;
 
         let req = HeartbeatRequest::default();
         let result1 = sender.send_heartbeat(req.clone()).await;
@@ -343,7 +499,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_status_report_multiple_calls() {
-        let mut sender = NodeAgentSender::default();
+        let mut sender = sender_with_api_server().await;
 
         let req = StatusReport::default();
         let result1 = sender.send_status_report(req.clone()).await;
@@ -0,0 +1,358 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Images cached on this node
+//!
+//! Containers are created from the images podman keeps locally, an image
+//! missing from it is pulled first. The ActionController prefetches the
+//! images of the models it is about to launch on the node, so that the
+//! launch does not wait for the pull. The NodeAgent remembers when it pulled
+//! an image, whether it was prefetched and when a container was last
+//! created from it. API-Server lists the cached images with their digests,
+//! and prunes those no container uses to free the disk.
+//!
+//! What the NodeAgent remembers is kept in memory only, images pulled
+//! before its start are listed without pull or use times.
+
+use crate::runtime::podman::{container, delete, get, PODMAN_API_VERSION};
+use common::nodeagent::fromactioncontroller::PrefetchImagesResponse;
+use common::nodeagent::fromapiserver::{CachedImage, PruneImagesRequest, PruneImagesResponse};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// What the NodeAgent did with an image, by the reference Pods use
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Usage {
+    pulled_at_ms: i64,
+    last_used_ms: i64,
+    prefetched: bool,
+}
+
+static USAGE: OnceLock<Mutex<HashMap<String, Usage>>> = OnceLock::new();
+
+fn with_usage<R>(f: impl FnOnce(&mut HashMap<String, Usage>) -> R) -> R {
+    let mut usage = USAGE
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    f(&mut usage)
+}
+
+/// Reference with the `latest` tag when it names neither a tag nor a digest
+fn with_tag(reference: &str) -> String {
+    let name = reference.rsplit('/').next().unwrap_or(reference);
+    if reference.contains('@') || name.contains(':') {
+        reference.to_string()
+    } else {
+        format!("{}:latest", reference)
+    }
+}
+
+/// Reference in full, e.g. `docker.io/library/nginx:latest` for `nginx`
+///
+/// A reference names its registry when its first component has a dot or a
+/// port or is `localhost`, Docker Hub otherwise, where images without a user
+/// are under `library/`. A digest stands for the tag it is given with.
+fn normalize(reference: &str) -> String {
+    let (name, digest) = match reference.split_once('@') {
+        Some((name, digest)) => (name, Some(digest)),
+        None => (reference, None),
+    };
+    let (path, tag) = match name.rsplit_once(':') {
+        Some((path, tag)) if !tag.contains('/') => (path, Some(tag)),
+        _ => (name, None),
+    };
+    let path = match path.split_once('/') {
+        Some((registry, _)) if registry.contains(['.', ':']) || registry == "localhost" => {
+            path.to_string()
+        }
+        _ => format!("{}/{}", DEFAULT_REGISTRY, path),
+    };
+    let path = match path.strip_prefix(&format!("{}/", DEFAULT_REGISTRY)) {
+        Some(repository) if !repository.contains('/') => {
+            format!("{}/library/{}", DEFAULT_REGISTRY, repository)
+        }
+        _ => path,
+    };
+    match digest {
+        Some(digest) => format!("{}@{}", path, digest),
+        None => format!("{}:{}", path, tag.unwrap_or("latest")),
+    }
+}
+
+/// Registry of the references that name none
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Whether a tag or digest of the runtime, e.g.
+/// `docker.io/library/nginx:latest`, is the image a Pod names with
+/// `reference`, e.g. `nginx`, `docker.io/nginx` or `nginx@sha256:...`
+///
+/// Short names also match images of other registries, podman resolves them
+/// with its search registries.
+pub fn matches(name: &str, reference: &str) -> bool {
+    let short = with_tag(reference);
+    name == short
+        || name.ends_with(&format!("/{}", short))
+        || normalize(name) == normalize(reference)
+}
+
+/// Whether an image is cached
+pub async fn is_cached(image: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let images = runtime_images().await?;
+    Ok(images
+        .iter()
+        .any(|entry| names(entry).any(|name| matches(name, image))))
+}
+
+/// Pull an image unless it is cached, and tell whether it was pulled
+async fn pull_if_missing(image: &str, prefetch: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    if is_cached(image).await? {
+        return Ok(false);
+    }
+    container::pull_image(image).await?;
+    if !is_cached(image).await? {
+        return Err(format!("image {} is not cached after its pull", image).into());
+    }
+    with_usage(|usage| {
+        let entry = usage.entry(image.to_string()).or_default();
+        entry.pulled_at_ms = common::clock::now_ms();
+        entry.prefetched = prefetch;
+    });
+    Ok(true)
+}
+
+/// Make an image available to create a container from
+pub async fn ensure(image: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if pull_if_missing(image, false).await? {
+        println!("Image {} pulled", image);
+    }
+    with_usage(|usage| {
+        usage.entry(image.to_string()).or_default().last_used_ms = common::clock::now_ms();
+    });
+    Ok(())
+}
+
+/// Pull the images of models about to be launched on this node
+pub async fn prefetch(images: &[String]) -> PrefetchImagesResponse {
+    let mut response = PrefetchImagesResponse::default();
+    for image in images {
+        match pull_if_missing(image, true).await {
+            Ok(true) => response.pulled.push(image.clone()),
+            Ok(false) => response.cached.push(image.clone()),
+            Err(e) => {
+                response.failed.insert(image.clone(), e.to_string());
+            }
+        }
+    }
+    response
+}
+
+async fn runtime_images() -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+    let body = get(&format!("{}/images/json", PODMAN_API_VERSION)).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+fn tags(image: &serde_json::Value) -> impl Iterator<Item = &str> {
+    image["RepoTags"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag.as_str())
+}
+
+/// Tags and digests an image of the runtime is referenced by
+fn names(image: &serde_json::Value) -> impl Iterator<Item = &str> {
+    let digests = image["RepoDigests"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|digest| digest.as_str());
+    tags(image).chain(digests)
+}
+
+/// Cached images from the image list of podman and what the NodeAgent did
+/// with them
+fn cached_images(images: &[serde_json::Value], usage: &HashMap<String, Usage>) -> Vec<CachedImage> {
+    let mut cached: Vec<CachedImage> = images
+        .iter()
+        .map(|image| {
+            let id = image["Id"].as_str().unwrap_or_default().to_string();
+            let mut entry = CachedImage {
+                image: tags(image).next().unwrap_or(&id).to_string(),
+                digest: image["Digest"].as_str().unwrap_or_default().to_string(),
+                size_bytes: image["Size"].as_i64().unwrap_or_default(),
+                in_use: image["Containers"].as_i64().unwrap_or_default() > 0,
+                id,
+                ..Default::default()
+            };
+            for (reference, used) in usage {
+                if names(image).any(|name| matches(name, reference)) {
+                    entry.pulled_at_ms = entry.pulled_at_ms.max(used.pulled_at_ms);
+                    entry.last_used_ms = entry.last_used_ms.max(used.last_used_ms);
+                    entry.prefetched |= used.prefetched;
+                }
+            }
+            entry
+        })
+        .collect();
+    cached.sort_by(|a, b| a.image.cmp(&b.image));
+    cached
+}
+
+/// Images cached on this node
+pub async fn list() -> Result<Vec<CachedImage>, Box<dyn std::error::Error>> {
+    let images = runtime_images().await?;
+    Ok(with_usage(|usage| cached_images(&images, usage)))
+}
+
+/// Images a prune request removes: unused, not used or pulled within
+/// `unused_for_secs`, and among the requested images if any
+fn prune_candidates<'a>(
+    images: &'a [CachedImage],
+    request: &PruneImagesRequest,
+    now_ms: i64,
+) -> Vec<&'a CachedImage> {
+    let unused_for_ms = request.unused_for_secs.saturating_mul(1000) as i64;
+    images
+        .iter()
+        .filter(|image| !image.in_use)
+        .filter(|image| {
+            request.images.is_empty()
+                || request
+                    .images
+                    .iter()
+                    .any(|requested| matches(&image.image, requested) || *requested == image.id)
+        })
+        .filter(|image| {
+            let last_activity = image.last_used_ms.max(image.pulled_at_ms);
+            last_activity == 0 || now_ms.saturating_sub(last_activity) >= unused_for_ms
+        })
+        .collect()
+}
+
+/// Remove cached images no container uses
+pub async fn prune(
+    request: &PruneImagesRequest,
+) -> Result<PruneImagesResponse, Box<dyn std::error::Error>> {
+    let images = list().await?;
+    let mut response = PruneImagesResponse::default();
+    for image in prune_candidates(&images, request, common::clock::now_ms()) {
+        if !request.dry_run {
+            let path = format!("{}/images/{}", PODMAN_API_VERSION, image.id);
+            let body = delete(&path).await?;
+            let result: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            if let Some(cause) = result["cause"].as_str() {
+                response
+                    .failed
+                    .insert(image.image.clone(), cause.to_string());
+                continue;
+            }
+            with_usage(|usage| usage.retain(|reference, _| !matches(&image.image, reference)));
+        }
+        response.removed.push(image.image.clone());
+        response.reclaimed_bytes += image.size_bytes;
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("docker.io/library/nginx:latest", "nginx"));
+        assert!(matches("docker.io/library/nginx:1.25", "nginx:1.25"));
+        assert!(matches("localhost:5000/app:latest", "localhost:5000/app"));
+        assert!(!matches("docker.io/library/nginx:1.25", "nginx"));
+        assert!(!matches("docker.io/library/my-nginx:latest", "nginx"));
+
+        // Docker Hub references in full and digests of the image
+        assert!(matches("docker.io/library/nginx:latest", "docker.io/nginx"));
+        assert!(matches(
+            "docker.io/library/nginx:1.25",
+            "docker.io/library/nginx:1.25"
+        ));
+        assert!(matches("docker.io/grafana/agent:latest", "grafana/agent"));
+        assert!(matches(
+            "docker.io/library/nginx@sha256:aa",
+            "nginx@sha256:aa"
+        ));
+        assert!(matches(
+            "docker.io/library/nginx@sha256:aa",
+            "docker.io/nginx:1.25@sha256:aa"
+        ));
+        assert!(!matches(
+            "docker.io/library/nginx@sha256:aa",
+            "nginx@sha256:bb"
+        ));
+        assert!(!matches("docker.io/library/nginx:latest", "quay.io/nginx"));
+        assert_eq!(normalize("localhost:5000/app"), "localhost:5000/app:latest");
+    }
+
+    #[test]
+    fn test_cached_images_and_prune_candidates() {
+        let images = vec![
+            json!({"Id": "id-nginx", "RepoTags": ["docker.io/library/nginx:latest"],
+                   "Digest": "sha256:aa", "Size": 100, "Containers": 1}),
+            json!({"Id": "id-app", "RepoTags": ["localhost/app:1"],
+                   "RepoDigests": ["localhost/app@sha256:bb"],
+                   "Digest": "sha256:bb", "Size": 200, "Containers": 0}),
+            json!({"Id": "id-old", "RepoTags": null, "Size": 300, "Containers": 0}),
+        ];
+        let usage = HashMap::from([
+            (
+                "localhost/app:1".to_string(),
+                Usage {
+                    pulled_at_ms: 1_000,
+                    last_used_ms: 0,
+                    prefetched: true,
+                },
+            ),
+            (
+                "localhost/app@sha256:bb".to_string(),
+                Usage {
+                    pulled_at_ms: 0,
+                    last_used_ms: 2_000,
+                    prefetched: false,
+                },
+            ),
+        ]);
+        let cached = cached_images(&images, &usage);
+        let names: Vec<&str> = cached.iter().map(|i| i.image.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "docker.io/library/nginx:latest",
+                "id-old",
+                "localhost/app:1"
+            ]
+        );
+        assert!(cached[0].in_use);
+        assert_eq!(cached[2].digest, "sha256:bb");
+        assert!(cached[2].prefetched);
+        assert_eq!(cached[2].pulled_at_ms, 1_000);
+        assert_eq!(cached[2].last_used_ms, 2_000);
+
+        let prune = |request: PruneImagesRequest, now_ms: i64| -> Vec<String> {
+            prune_candidates(&cached, &request, now_ms)
+                .iter()
+                .map(|i| i.id.clone())
+                .collect()
+        };
+        // Images in use are kept, and those pulled too recently
+        let recent = PruneImagesRequest {
+            unused_for_secs: 60,
+            ..Default::default()
+        };
+        assert_eq!(prune(recent.clone(), 30_000), vec!["id-old"]);
+        assert_eq!(prune(recent, 62_000), vec!["id-old", "id-app"]);
+        let only_app = PruneImagesRequest {
+            images: vec!["localhost/app:1".to_string(), "nginx".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(prune(only_app, 0), vec!["id-app"]);
+    }
+}
@@ -0,0 +1,353 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Forwarding of the container output to journald or syslog
+//!
+//! With `log_forwarding.enabled` set, every running container gathered from
+//! podman gets its log stream followed, and each line is sent to the node's
+//! journald or to the syslog server at `log_forwarding.syslog_address`. The
+//! lines are tagged with the container and the model, package and scenario
+//! labels of its Pod, so the log collection of the vehicle can filter them
+//! like the output of any other service. Lines of stderr are sent with the
+//! error priority, lines of stdout with the informational one.
+
+use crate::config::LogForwardingConfig;
+use crate::runtime::podman::logs::{self, Demuxer, LogOptions};
+use common::logd;
+use common::monitoringserver::ContainerInfo;
+use common::nodeagent::fromapiserver::LogStream;
+use common::spec::k8s::pod::{LABEL_MODEL, LABEL_PACKAGE, LABEL_SCENARIO};
+use hyper::body::HttpBody;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+/// Socket of the native journald protocol
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Syslog facility of user-level messages
+const FACILITY_USER: u8 = 1;
+
+/// Structured data id of the tags in a syslog message
+const SD_ID: &str = "piccolo@32473";
+
+/// Longest syslog APP-NAME
+const APP_NAME_MAX: usize = 48;
+
+/// How long output without a final newline waits for the rest of its line
+const PARTIAL_LINE_WAIT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Containers whose logs are being forwarded, by id
+static ATTACHED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn attached() -> &'static Mutex<HashSet<String>> {
+    ATTACHED.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Where a line of output comes from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags {
+    pub container: String,
+    pub model: String,
+    pub package: String,
+    pub scenario: String,
+}
+
+impl Tags {
+    pub fn of(container: &ContainerInfo) -> Self {
+        let label = |key: &str| container.annotation.get(key).cloned().unwrap_or_default();
+        Self {
+            container: container
+                .names
+                .first()
+                .map(|name| name.trim_start_matches('/').to_string())
+                .unwrap_or_else(|| container.id.clone()),
+            model: label(LABEL_MODEL),
+            package: label(LABEL_PACKAGE),
+            scenario: label(LABEL_SCENARIO),
+        }
+    }
+}
+
+/// Syslog severity of a line of a stream
+fn severity(stream: LogStream) -> u8 {
+    match stream {
+        LogStream::Stderr => 3,
+        _ => 6,
+    }
+}
+
+/// Entry of the native journald protocol
+pub fn journald_entry(tags: &Tags, stream: LogStream, line: &str) -> String {
+    let mut entry = format!(
+        "MESSAGE={}\nPRIORITY={}\nSYSLOG_IDENTIFIER={}\n",
+        line,
+        severity(stream),
+        tags.container
+    );
+    for (field, value) in [
+        ("PICCOLO_MODEL", &tags.model),
+        ("PICCOLO_PACKAGE", &tags.package),
+        ("PICCOLO_SCENARIO", &tags.scenario),
+    ] {
+        if !value.is_empty() {
+            entry.push_str(&format!("{}={}\n", field, value));
+        }
+    }
+    entry
+}
+
+/// Escape a value of RFC 5424 structured data
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(']', "\\]")
+}
+
+/// RFC 5424 message, the receiver stamps the time
+pub fn syslog_message(tags: &Tags, stream: LogStream, line: &str, host: &str) -> String {
+    let app: String = tags
+        .container
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(APP_NAME_MAX)
+        .collect();
+    format!(
+        "<{}>1 - {} {} - - [{} model=\"{}\" package=\"{}\" scenario=\"{}\"] {}",
+        FACILITY_USER * 8 + severity(stream),
+        if host.is_empty() { "-" } else { host },
+        if app.is_empty() { "-" } else { &app },
+        SD_ID,
+        escape(&tags.model),
+        escape(&tags.package),
+        escape(&tags.scenario),
+        line
+    )
+}
+
+/// Splits the output of each stream into lines
+#[derive(Default)]
+struct Lines {
+    partial: HashMap<i32, Vec<u8>>,
+}
+
+impl Lines {
+    /// Add output of a stream and take its complete lines
+    fn push(&mut self, stream: LogStream, data: &[u8]) -> Vec<String> {
+        let buffer = self.partial.entry(stream as i32).or_default();
+        buffer.extend_from_slice(data);
+        let mut lines = Vec::new();
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            lines.push(line.trim_end_matches('\r').to_string());
+        }
+        lines
+    }
+
+    /// Take the output left without a final newline
+    fn flush(&mut self) -> Vec<(LogStream, String)> {
+        self.partial
+            .drain()
+            .filter(|(_, rest)| !rest.is_empty())
+            .filter_map(|(stream, rest)| {
+                let stream = LogStream::try_from(stream).ok()?;
+                Some((stream, String::from_utf8_lossy(&rest).to_string()))
+            })
+            .collect()
+    }
+}
+
+enum Sink {
+    Journald(tokio::net::UnixDatagram),
+    Syslog {
+        socket: tokio::net::UdpSocket,
+        address: String,
+        host: String,
+    },
+}
+
+impl Sink {
+    async fn open(config: &LogForwardingConfig) -> Result<Self, String> {
+        match config.target.as_str() {
+            "journald" => tokio::net::UnixDatagram::unbound()
+                .map(Sink::Journald)
+                .map_err(|e| e.to_string()),
+            "syslog" => Ok(Sink::Syslog {
+                socket: tokio::net::UdpSocket::bind("0.0.0.0:0")
+                    .await
+                    .map_err(|e| e.to_string())?,
+                address: config.syslog_address.clone(),
+                host: crate::config::Config::get().get_node_name(),
+            }),
+            other => Err(format!("unknown log forwarding target '{}'", other)),
+        }
+    }
+
+    async fn send(&self, tags: &Tags, stream: LogStream, line: &str) -> std::io::Result<()> {
+        match self {
+            Sink::Journald(socket) => {
+                let entry = journald_entry(tags, stream, line);
+                socket.send_to(entry.as_bytes(), JOURNALD_SOCKET).await?;
+            }
+            Sink::Syslog {
+                socket,
+                address,
+                host,
+            } => {
+                let message = syslog_message(tags, stream, line, host);
+                socket.send_to(message.as_bytes(), address.as_str()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Follow the logs of a container until it stops
+///
+/// Output left without a final newline is sent once no more output follows
+/// within [`PARTIAL_LINE_WAIT`], and when the stream ends, also when it
+/// breaks off with an error.
+async fn follow(config: &LogForwardingConfig, id: &str, tags: &Tags) -> Result<(), String> {
+    let sink = Sink::open(config).await?;
+    let options = LogOptions {
+        since: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64),
+        follow: true,
+        ..Default::default()
+    };
+    let mut body = logs::open(id, &options).await?;
+
+    let (mut demuxer, mut lines) = (Demuxer::default(), Lines::default());
+    let mut result = Ok(());
+    loop {
+        let bytes = match tokio::time::timeout(PARTIAL_LINE_WAIT, body.data()).await {
+            Ok(Some(Ok(bytes))) => bytes,
+            Ok(Some(Err(e))) => {
+                result = Err(e.to_string());
+                break;
+            }
+            Ok(None) => break,
+            Err(_) => {
+                for (stream, line) in lines.flush() {
+                    let _ = sink.send(tags, stream, &line).await;
+                }
+                continue;
+            }
+        };
+        for (stream, data) in demuxer.push(&bytes) {
+            for line in lines.push(stream, &data) {
+                // A full journal or an unreachable server drops the line
+                let _ = sink.send(tags, stream, &line).await;
+            }
+        }
+    }
+    if let Some((stream, data)) = demuxer.finish() {
+        for line in lines.push(stream, &data) {
+            let _ = sink.send(tags, stream, &line).await;
+        }
+    }
+    for (stream, line) in lines.flush() {
+        let _ = sink.send(tags, stream, &line).await;
+    }
+    result
+}
+
+/// Start forwarding the logs of the running containers not forwarded yet
+pub fn attach(containers: &[ContainerInfo]) {
+    let config = &crate::config::Config::get().nodeagent.log_forwarding;
+    if !config.enabled {
+        return;
+    }
+    for container in containers {
+        if container.state.get("Status").map(String::as_str) != Some("running") {
+            continue;
+        }
+        let newly_attached = attached()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(container.id.clone());
+        if !newly_attached {
+            continue;
+        }
+
+        let (config, id, tags) = (config.clone(), container.id.clone(), Tags::of(container));
+        tokio::spawn(async move {
+            if let Err(e) = follow(&config, &id, &tags).await {
+                logd!(4, "Log forwarding of {} stopped: {}", tags.container, e);
+            }
+            // Attached again with the next container list while still running
+            attached()
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags() -> Tags {
+        Tags {
+            container: "helloworld-core_helloworld".to_string(),
+            model: "helloworld-core".to_string(),
+            package: "helloworld".to_string(),
+            scenario: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_tags_of_container() {
+        let mut container = ContainerInfo {
+            id: "abc".to_string(),
+            names: vec!["/helloworld-core_helloworld".to_string()],
+            ..Default::default()
+        };
+        container
+            .annotation
+            .insert(LABEL_MODEL.to_string(), "helloworld-core".to_string());
+        container
+            .annotation
+            .insert(LABEL_PACKAGE.to_string(), "helloworld".to_string());
+        assert_eq!(Tags::of(&container), tags());
+    }
+
+    #[test]
+    fn test_journald_entry() {
+        assert_eq!(
+            journald_entry(&tags(), LogStream::Stderr, "oops"),
+            "MESSAGE=oops\nPRIORITY=3\nSYSLOG_IDENTIFIER=helloworld-core_helloworld\n\
+             PICCOLO_MODEL=helloworld-core\nPICCOLO_PACKAGE=helloworld\n"
+        );
+    }
+
+    #[test]
+    fn test_syslog_message() {
+        let mut tags = tags();
+        tags.scenario = "team/\"odd\"]".to_string();
+        assert_eq!(
+            syslog_message(&tags, LogStream::Stdout, "hello", "HPC"),
+            "<14>1 - HPC helloworld-core_helloworld - - [piccolo@32473 \
+             model=\"helloworld-core\" package=\"helloworld\" \
+             scenario=\"team/\\\"odd\\\"\\]\"] hello"
+        );
+    }
+
+    #[test]
+    fn test_lines_per_stream() {
+        let mut lines = Lines::default();
+        assert!(lines.push(LogStream::Stdout, b"hel").is_empty());
+        assert_eq!(lines.push(LogStream::Stderr, b"err\r\n"), vec!["err"]);
+        assert_eq!(
+            lines.push(LogStream::Stdout, b"lo\nworld\nrest"),
+            vec!["hello", "world"]
+        );
+        assert_eq!(lines.flush(), vec![(LogStream::Stdout, "rest".to_string())]);
+        assert!(lines.flush().is_empty());
+        assert_eq!(lines.push(LogStream::Stdout, b"!\n"), vec!["!"]);
+    }
+}
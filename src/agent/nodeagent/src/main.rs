@@ -12,9 +12,15 @@ use common::nodeagent::fromapiserver::{HandleYamlRequest, NodeRegistrationReques
 use std::path::PathBuf;
 pub mod config;
 pub mod grpc;
+pub mod image;
+pub mod logforward;
 pub mod manager;
+pub mod node_config;
+pub mod probe;
 pub mod resource;
+pub mod restart;
 pub mod runtime;
+pub mod termination;
 
 use common::nodeagent::node_agent_connection_server::NodeAgentConnectionServer;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
@@ -33,6 +39,7 @@ async fn launch_manager(
     match manager.initialize().await {
         Ok(_) => {
             println!("NodeAgentManager successfully initialized");
+            common::health::set_started();
             // Add registration with API server
             let mut sender = grpc::sender::NodeAgentSender::default();
 
@@ -42,12 +49,20 @@ async fn launch_manager(
             // node_id를 node_name과 동일하게 설정 (IP 주소 제거)
             let node_id = node_name.clone();
 
+            let containers = resource::container::inspect(hostname.clone())
+                .await
+                .unwrap_or_default();
+            let count = manager::running_containers(&containers).len() as u32;
+            let usage = tokio::task::spawn_blocking(move || resource::nodeinfo::node_usage(count))
+                .await
+                .ok();
+
             let registration_request = NodeRegistrationRequest {
                 node_id: node_id.clone(),
                 hostname: hostname.clone(),
                 ip_address: host_ip.clone(),
                 metadata: std::collections::HashMap::new(),
-                resources: None,
+                resources: Some(resource::nodeinfo::resource_info()),
                 node_type: match config.nodeagent.node_type.as_str() {
                     "cloud" => 1,   // NodeType::Cloud as i32
                     "vehicle" => 2, // NodeType::Vehicle as i32
@@ -59,8 +74,18 @@ async fn launch_manager(
                     "bluechi" => 3,   // NodeRole::Bluechi as i32
                     _ => 0,           // NodeRole::Unspecified as i32
                 },
+                api_version: common::version::API_VERSION,
+                usage,
+                labels: config.nodeagent.labels.clone(),
+                taints: config.get_taints(),
             };
 
+            // Agree on the API version before the first versioned request
+            match sender.negotiate_api_version().await {
+                Ok(version) => println!("API version {} agreed with API server", version),
+                Err(e) => eprintln!("Failed to negotiate the API version: {:?}", e),
+            }
+
             // Register with API server
             match sender.register_with_api_server(registration_request).await {
                 Ok(_) => println!("Successfully registered with API server"),
@@ -80,6 +105,7 @@ async fn launch_manager(
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap()
                             .as_secs() as i64, // Cast to i64
+                        api_version: common::version::API_VERSION,
                     };
                     // Fix: call on instance, not static method
                     if let Err(e) = sender_clone.send_heartbeat(heartbeat_request).await {
@@ -103,8 +129,6 @@ async fn launch_manager(
 ///
 /// Sets up the gRPC service and starts listening for incoming requests.
 async fn initialize(tx_grpc: Sender<HandleYamlRequest>, hostname: String, config: config::Config) {
-    use tonic::transport::Server;
-
     // Use IP address from config file
     let host_ip = config.get_host_ip();
     let node_name = config.get_node_name();
@@ -137,8 +161,19 @@ async fn initialize(tx_grpc: Sender<HandleYamlRequest>, hostname: String, config
         config.nodeagent.master_ip, config.nodeagent.grpc_port
     );
 
-    let _ = Server::builder()
-        .add_service(NodeAgentConnectionServer::new(server))
+    let mut builder = match common::tls::server() {
+        Ok(builder) => builder,
+        Err(e) => {
+            println!("NodeAgent gRPC server not started: {}", e);
+            return;
+        }
+    };
+    let _ = builder
+        .add_service(NodeAgentConnectionServer::with_interceptor(
+            server,
+            common::auth::authenticate,
+        ))
+        .add_service(common::health::grpc_service())
         .serve(addr)
         .await;
 }
@@ -158,6 +193,8 @@ struct Args {
 #[cfg(not(feature = "tarpaulin_include"))]
 #[tokio::main]
 async fn main() {
+    let _ = common::logd::logger::init_async_logger("nodeagent").await;
+
     // Parse command line arguments
     let args = Args::parse();
 
@@ -180,6 +217,11 @@ async fn main() {
 
     // Set global config for other parts of the application
     config::Config::set_global(app_config.clone());
+    config::set_source(args.config.clone(), app_config.clone());
+    tokio::spawn(config::watch());
+
+    // Restore configuration previously pushed by the API server
+    println!("Runtime node configuration: {:?}", node_config::get());
 
     let mut hostname = app_config.get_hostname();
     if hostname.is_empty() || hostname == "$(hostname)" {
@@ -195,6 +237,12 @@ async fn main() {
     }
     println!("Starting NodeAgent on host: {}", hostname);
 
+    common::health::init("nodeagent", false);
+    common::address::validate_or_exit("nodeagent");
+    tokio::spawn(common::health::serve(
+        common::nodeagent::open_health_server(&app_config.get_host_ip()),
+    ));
+
     let (tx_grpc, rx_grpc) = channel::<HandleYamlRequest>(100);
     let mgr = launch_manager(rx_grpc, hostname.clone(), app_config.clone());
     let grpc = initialize(tx_grpc, hostname, app_config);
@@ -266,6 +314,10 @@ mod tests {
                 "bluechi" => 3,
                 _ => 0,
             },
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: config.nodeagent.labels.clone(),
+            taints: config.get_taints(),
         };
         assert_eq!(registration_request.node_id, node_name);
         assert_eq!(registration_request.ip_address, host_ip);
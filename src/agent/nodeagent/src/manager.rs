@@ -78,7 +78,11 @@ impl NodeAgentManager {
         let mut previous_container_list = Vec::new();
 
         loop {
-            let container_list = inspect(self.hostname.clone()).await.unwrap_or_default();
+            let mut container_list = inspect(self.hostname.clone()).await.unwrap_or_default();
+            crate::probe::annotate(&mut container_list);
+            crate::restart::enforce(&mut container_list).await;
+            crate::logforward::attach(&container_list);
+            crate::termination::report(&mut container_list);
             let node = self.hostname.clone();
 
             // Send the container info to the monitoring server
@@ -88,6 +92,7 @@ impl NodeAgentManager {
                     .send_container_list(ContainerList {
                         node_name: node.clone(),
                         containers: container_list.clone(),
+                        api_version: common::version::API_VERSION,
                     })
                     .await
                 {
@@ -112,6 +117,7 @@ impl NodeAgentManager {
                     .send_changed_container_list(ContainerList {
                         node_name: node.clone(),
                         containers: container_list,
+                        api_version: common::version::API_VERSION,
                     })
                     .await
                 {
@@ -119,7 +125,61 @@ impl NodeAgentManager {
                 }
             }
 
-            sleep(Duration::from_secs(1)).await;
+            sleep(Duration::from_secs(
+                crate::node_config::get().monitoring_interval,
+            ))
+            .await;
+        }
+    }
+
+    /// Background task: Periodically reports the load of the node to the API server.
+    ///
+    /// The API server keeps the last report with the node for the placement and
+    /// admission of models in the ActionController.
+    async fn report_status_loop(&self) {
+        use crate::resource::container::inspect;
+        use common::nodeagent::fromapiserver::{NodeStatus, StatusReport};
+        use tokio::time::{sleep, Duration};
+
+        let node_id = crate::config::Config::get().get_node_name();
+        loop {
+            let containers = inspect(self.hostname.clone()).await.unwrap_or_default();
+            let active_containers = running_containers(&containers);
+            let count = active_containers.len() as u32;
+            match tokio::task::spawn_blocking(move || crate::resource::nodeinfo::node_usage(count))
+                .await
+            {
+                Ok(usage) => {
+                    let report = StatusReport {
+                        node_id: node_id.clone(),
+                        status: NodeStatus::Ready.into(),
+                        metrics: std::collections::HashMap::new(),
+                        active_containers,
+                        timestamp: usage.timestamp,
+                        usage: Some(usage),
+                    };
+                    let mut sender = self.sender.lock().await;
+                    if let Err(e) = sender.send_status_report(report).await {
+                        eprintln!("[NodeAgent] Error sending status report: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("[NodeAgent] Error sampling node usage: {}", e),
+            }
+
+            sleep(Duration::from_secs(
+                crate::node_config::get().monitoring_interval,
+            ))
+            .await;
+        }
+    }
+
+    /// Background task: Runs the readiness and liveness probes that are due.
+    ///
+    /// Their results reach the StateManager with the next container list.
+    async fn probe_loop(&self) {
+        loop {
+            crate::probe::run_due().await;
+            tokio::time::sleep(crate::probe::TICK).await;
         }
     }
 
@@ -176,7 +236,10 @@ impl NodeAgentManager {
                 node_info.arch,
                 node_info.ip
             );
-            sleep(Duration::from_secs(1)).await;
+            sleep(Duration::from_secs(
+                crate::node_config::get().monitoring_interval,
+            ))
+            .await;
         }
     }
 
@@ -201,12 +264,44 @@ impl NodeAgentManager {
         let nodeinfo_task = tokio::spawn(async move {
             nodeinfo_manager.gather_node_info_loop().await;
         });
-        let _ = tokio::try_join!(grpc_processor, container_gatherer, nodeinfo_task);
+        let probe_manager = Arc::clone(&arc_self);
+        let probe_task = tokio::spawn(async move {
+            probe_manager.probe_loop().await;
+        });
+        let status_manager = Arc::clone(&arc_self);
+        let status_task = tokio::spawn(async move {
+            status_manager.report_status_loop().await;
+        });
+        let _ = tokio::try_join!(
+            grpc_processor,
+            container_gatherer,
+            nodeinfo_task,
+            probe_task,
+            status_task
+        );
         println!("NodeAgentManager stopped");
         Ok(())
     }
 }
 
+/// Names of the running containers, for the status report of the node
+pub fn running_containers(containers: &[ContainerInfo]) -> Vec<String> {
+    containers
+        .iter()
+        .filter(|c| {
+            c.state
+                .get("Running")
+                .is_some_and(|running| running == "true")
+        })
+        .map(|c| {
+            c.names
+                .first()
+                .map(|name| name.trim_start_matches('/').to_string())
+                .unwrap_or_else(|| c.id.clone())
+        })
+        .collect()
+}
+
 fn containers_equal_except_stats<'a>(a: &'a [ContainerInfo], b: &'a [ContainerInfo]) -> bool {
     if a.len() != b.len() {
         return false;
@@ -323,6 +418,25 @@ spec:
         ));
     }
 
+    #[test]
+    fn test_running_containers() {
+        let container = |id: &str, names: Vec<String>, running: &str| ContainerInfo {
+            id: id.to_string(),
+            names,
+            state: HashMap::from([("Running".to_string(), running.to_string())]),
+            ..Default::default()
+        };
+        let containers = vec![
+            container("id1", vec!["/radar".to_string()], "true"),
+            container("id2", vec!["camera".to_string()], "false"),
+            container("id3", vec![], "true"),
+        ];
+        assert_eq!(
+            super::running_containers(&containers),
+            vec!["radar".to_string(), "id3".to_string()]
+        );
+    }
+
     #[tokio::test]
     async fn test_new_creates_instance_with_correct_hostname() {
         let (_tx, rx) = mpsc::channel(1);
@@ -0,0 +1,276 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Runtime node configuration pushed by the API server
+//!
+//! Unlike the static `config::Config` loaded at startup, these values can be
+//! changed through `ReceiveConfig` while the agent is running. Every accepted
+//! update is written to disk so that it survives a restart.
+//!
+//! `log_level` replaces the `logging.level` setting of the agent, the
+//! resource limits apply to the containers whose spec sets none.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+/// Location of the persisted runtime configuration
+const NODE_CONFIG_PATH: &str = "/etc/piccolo/nodeagent-runtime.yaml";
+
+const KEY_LOG_LEVEL: &str = "log_level";
+const KEY_MONITORING_INTERVAL: &str = "monitoring_interval";
+const KEY_RUNTIME_SOCKET: &str = "runtime_socket";
+const KEY_CPU_LIMIT: &str = "resource_limits.cpu";
+const KEY_MEMORY_LIMIT: &str = "resource_limits.memory_mb";
+
+const LOG_LEVELS: [&str; 5] = ["debug", "info", "warn", "error", "fatal"];
+
+static NODE_CONFIG: OnceLock<RwLock<NodeConfig>> = OnceLock::new();
+
+/// Default resource limits applied to workloads on this node
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// CPU limit in cores
+    pub cpu: Option<f64>,
+    /// Memory limit in MiB
+    pub memory_mb: Option<u64>,
+}
+
+/// Node configuration that can be changed at runtime
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NodeConfig {
+    pub log_level: String,
+    /// Seconds between container and node info collections
+    pub monitoring_interval: u64,
+    /// Unix socket of the container runtime API
+    pub runtime_socket: String,
+    pub resource_limits: ResourceLimits,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            log_level: "info".to_string(),
+            monitoring_interval: 1,
+            runtime_socket: "/var/run/podman/podman.sock".to_string(),
+            resource_limits: ResourceLimits::default(),
+        }
+    }
+}
+
+/// Outcome of applying a configuration map
+#[derive(Debug, Default, PartialEq)]
+pub struct ApplyResult {
+    pub applied: Vec<String>,
+    /// Rejected keys with the reason they were rejected
+    pub failed: HashMap<String, String>,
+}
+
+impl NodeConfig {
+    /// Apply every known key of `settings`, leaving the rest untouched
+    pub fn apply(&mut self, settings: &HashMap<String, String>) -> ApplyResult {
+        let mut result = ApplyResult::default();
+
+        // Sort keys so the applied list is deterministic
+        let mut keys: Vec<&String> = settings.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            match self.apply_one(key, &settings[key]) {
+                Ok(()) => result.applied.push(key.clone()),
+                Err(reason) => {
+                    result.failed.insert(key.clone(), reason);
+                }
+            }
+        }
+        result
+    }
+
+    fn apply_one(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match key {
+            KEY_LOG_LEVEL => {
+                let level = value.to_lowercase();
+                if !LOG_LEVELS.contains(&level.as_str()) {
+                    return Err(format!("unknown log level '{}'", value));
+                }
+                self.log_level = level;
+            }
+            KEY_MONITORING_INTERVAL => {
+                let interval: u64 = value
+                    .parse()
+                    .map_err(|e| format!("invalid interval '{}': {}", value, e))?;
+                if interval == 0 {
+                    return Err("interval must be at least 1 second".to_string());
+                }
+                self.monitoring_interval = interval;
+            }
+            KEY_RUNTIME_SOCKET => {
+                if !value.starts_with('/') {
+                    return Err(format!("socket path '{}' must be absolute", value));
+                }
+                self.runtime_socket = value.to_string();
+            }
+            KEY_CPU_LIMIT => {
+                let cpu: f64 = value
+                    .parse()
+                    .map_err(|e| format!("invalid cpu limit '{}': {}", value, e))?;
+                if cpu <= 0.0 {
+                    return Err("cpu limit must be positive".to_string());
+                }
+                self.resource_limits.cpu = Some(cpu);
+            }
+            KEY_MEMORY_LIMIT => {
+                let memory: u64 = value
+                    .parse()
+                    .map_err(|e| format!("invalid memory limit '{}': {}", value, e))?;
+                if memory == 0 {
+                    return Err("memory limit must be positive".to_string());
+                }
+                self.resource_limits.memory_mb = Some(memory);
+            }
+            _ => return Err("unknown configuration key".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Lowest `logd!` level code of `log_level`
+    pub fn min_log_level(&self) -> i32 {
+        common::setting::LoggingSettings {
+            level: self.log_level.clone(),
+        }
+        .min_level()
+    }
+
+    /// Read a persisted configuration, falling back to defaults
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_yaml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the configuration so that it is restored on restart
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let contents = serde_yaml::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+fn persist_path() -> PathBuf {
+    if cfg!(test) {
+        std::env::temp_dir().join("nodeagent-runtime.yaml")
+    } else {
+        PathBuf::from(NODE_CONFIG_PATH)
+    }
+}
+
+fn global() -> &'static RwLock<NodeConfig> {
+    NODE_CONFIG.get_or_init(|| {
+        let config = NodeConfig::load(persist_path());
+        // The default level leaves the logging setting in effect
+        if persist_path().exists() {
+            common::logd::logger::set_min_level(Some(config.min_log_level()));
+        }
+        RwLock::new(config)
+    })
+}
+
+/// Snapshot of the current runtime configuration
+pub fn get() -> NodeConfig {
+    global().read().unwrap().clone()
+}
+
+/// Apply a configuration update and persist the result
+///
+/// Keys that fail validation are reported and leave the previous value in
+/// place. A persistence failure is returned as an error after the in-memory
+/// configuration has already been updated.
+pub fn update(settings: &HashMap<String, String>) -> (ApplyResult, Result<(), String>) {
+    let mut config = global().write().unwrap();
+    let result = config.apply(settings);
+    if result.applied.iter().any(|key| key == KEY_LOG_LEVEL) {
+        common::logd::logger::set_min_level(Some(config.min_log_level()));
+    }
+    let saved = if result.applied.is_empty() {
+        Ok(())
+    } else {
+        config.save(persist_path())
+    };
+    (result, saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_valid_keys() {
+        let mut config = NodeConfig::default();
+        let result = config.apply(&settings(&[
+            ("log_level", "DEBUG"),
+            ("monitoring_interval", "5"),
+            ("runtime_socket", "/run/user/1000/podman/podman.sock"),
+            ("resource_limits.cpu", "1.5"),
+            ("resource_limits.memory_mb", "512"),
+        ]));
+
+        assert!(result.failed.is_empty());
+        assert_eq!(result.applied.len(), 5);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.monitoring_interval, 5);
+        assert_eq!(config.runtime_socket, "/run/user/1000/podman/podman.sock");
+        assert_eq!(config.resource_limits.cpu, Some(1.5));
+        assert_eq!(config.resource_limits.memory_mb, Some(512));
+    }
+
+    #[test]
+    fn test_apply_reports_invalid_keys() {
+        let mut config = NodeConfig::default();
+        let result = config.apply(&settings(&[
+            ("log_level", "verbose"),
+            ("monitoring_interval", "0"),
+            ("runtime_socket", "relative.sock"),
+            ("unknown", "value"),
+            ("resource_limits.memory_mb", "1024"),
+        ]));
+
+        assert_eq!(
+            result.applied,
+            vec!["resource_limits.memory_mb".to_string()]
+        );
+        assert_eq!(result.failed.len(), 4);
+        assert_eq!(config.log_level, NodeConfig::default().log_level);
+        assert_eq!(config.monitoring_interval, 1);
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let path = std::env::temp_dir().join("nodeagent-runtime-roundtrip.yaml");
+        let mut config = NodeConfig::default();
+        config.apply(&settings(&[("monitoring_interval", "7")]));
+
+        config.save(&path).unwrap();
+        assert_eq!(NodeConfig::load(&path), config);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_missing_file_returns_default() {
+        let config = NodeConfig::load("/nonexistent/path/nodeagent-runtime.yaml");
+        assert_eq!(config, NodeConfig::default());
+    }
+}
@@ -0,0 +1,331 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Readiness and liveness probes of the containers started by this node
+//!
+//! The probes of a Pod are registered when its containers are started and
+//! run in the background at their own period. Their results are added to
+//! the state of the containers sent to the StateManager:
+//! * `Ready` is `false` until the readiness probe passes, and again once it
+//!   fails `failureThreshold` times in a row
+//! * `Live` is `false` once the liveness probe fails `failureThreshold`
+//!   times in a row, until it passes again
+//!
+//! Containers without probes get neither key. Probe results are kept in
+//! memory only, after a restart of the NodeAgent the containers of already
+//! running Pods are reported without them.
+
+use common::monitoringserver::ContainerInfo;
+use common::spec::k8s::pod::{Probe, ProbeHandler};
+use common::spec::k8s::Pod;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::time::{Duration, Instant};
+
+/// Interval at which probes are checked for being due
+pub const TICK: Duration = Duration::from_secs(1);
+
+static PROBES: OnceLock<Mutex<HashMap<String, ContainerProbes>>> = OnceLock::new();
+
+fn probes() -> &'static Mutex<HashMap<String, ContainerProbes>> {
+    PROBES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ProbeKind {
+    Readiness,
+    Liveness,
+}
+
+/// Probes of a single container
+#[derive(Default)]
+struct ContainerProbes {
+    readiness: Option<ProbeState>,
+    liveness: Option<ProbeState>,
+}
+
+/// A probe and its results so far
+struct ProbeState {
+    probe: Probe,
+    kind: ProbeKind,
+    next_run: Instant,
+    failures: u32,
+    passing: bool,
+}
+
+impl ProbeState {
+    fn new(probe: Probe, kind: ProbeKind, now: Instant) -> Self {
+        Self {
+            next_run: now + probe.initial_delay(),
+            probe,
+            kind,
+            failures: 0,
+            // A container is live until proven otherwise, ready once proven
+            passing: kind == ProbeKind::Liveness,
+        }
+    }
+
+    fn record(&mut self, passed: bool, now: Instant) {
+        self.next_run = now + self.probe.period();
+        if passed {
+            self.failures = 0;
+            self.passing = true;
+        } else {
+            self.failures += 1;
+            if self.failures >= self.probe.failure_threshold() {
+                self.passing = false;
+            }
+        }
+    }
+}
+
+/// Register the probes of the containers of a Pod, resetting their results
+pub fn register(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pod: Pod = serde_yaml::from_str(pod_yaml)?;
+    let now = Instant::now();
+    let mut registry = probes().lock().unwrap();
+    for container in &pod.get_spec().containers {
+        let name = container_name(&pod.get_name(), container.get_name());
+        let container_probes = ContainerProbes {
+            readiness: container
+                .readiness_probe()
+                .map(|p| ProbeState::new(p.clone(), ProbeKind::Readiness, now)),
+            liveness: container
+                .liveness_probe()
+                .map(|p| ProbeState::new(p.clone(), ProbeKind::Liveness, now)),
+        };
+        if container_probes.readiness.is_some() || container_probes.liveness.is_some() {
+            registry.insert(name, container_probes);
+        } else {
+            registry.remove(&name);
+        }
+    }
+    Ok(())
+}
+
+/// Forget the probes of the containers of a Pod
+pub fn unregister(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pod: Pod = serde_yaml::from_str(pod_yaml)?;
+    let mut registry = probes().lock().unwrap();
+    for container in &pod.get_spec().containers {
+        registry.remove(&container_name(&pod.get_name(), container.get_name()));
+    }
+    Ok(())
+}
+
+/// Restart the probes of a restarted container, as if its Pod was started
+pub fn reset(container_name: &str) {
+    let now = Instant::now();
+    if let Some(probes) = probes().lock().unwrap().get_mut(container_name) {
+        for state in [&mut probes.readiness, &mut probes.liveness]
+            .into_iter()
+            .flatten()
+        {
+            *state = ProbeState::new(state.probe.clone(), state.kind, now);
+        }
+    }
+}
+
+/// Name podman knows a container of a Pod by
+fn container_name(pod_name: &str, container: &str) -> String {
+    format!("{}_{}", pod_name, container)
+}
+
+/// Run every probe that is due and record its result
+pub async fn run_due() {
+    let now = Instant::now();
+    let due: Vec<(String, ProbeKind, Probe)> = {
+        let registry = probes().lock().unwrap();
+        registry
+            .iter()
+            .flat_map(|(name, probes)| {
+                [&probes.readiness, &probes.liveness]
+                    .into_iter()
+                    .flatten()
+                    .filter(|state| state.next_run <= now)
+                    .map(|state| (name.clone(), state.kind, state.probe.clone()))
+            })
+            .collect()
+    };
+
+    let results =
+        futures::future::join_all(due.into_iter().map(|(name, kind, probe)| async move {
+            let passed = run(&name, &probe).await;
+            (name, kind, passed)
+        }))
+        .await;
+
+    let now = Instant::now();
+    let mut registry = probes().lock().unwrap();
+    for (name, kind, passed) in results {
+        // The Pod may have been stopped while its probe ran
+        let Some(probes) = registry.get_mut(&name) else {
+            continue;
+        };
+        let state = match kind {
+            ProbeKind::Readiness => probes.readiness.as_mut(),
+            ProbeKind::Liveness => probes.liveness.as_mut(),
+        };
+        if let Some(state) = state {
+            if !passed {
+                println!("[Probe] {:?} probe of {} failed", kind, name);
+            }
+            state.record(passed, now);
+        }
+    }
+}
+
+/// Run a probe against a container, a probe that times out fails
+async fn run(container_name: &str, probe: &Probe) -> bool {
+    let check = async {
+        match probe.handler() {
+            Some(ProbeHandler::Exec(cmd)) => {
+                matches!(
+                    crate::runtime::podman::container::exec(container_name, cmd).await,
+                    Ok(0)
+                )
+            }
+            Some(ProbeHandler::Tcp { host, port }) => {
+                tokio::net::TcpStream::connect((host, port)).await.is_ok()
+            }
+            Some(ProbeHandler::Http { host, port, path }) => http_get(host, port, path).await,
+            None => true,
+        }
+    };
+    tokio::time::timeout(probe.timeout(), check)
+        .await
+        .unwrap_or(false)
+}
+
+/// Whether a GET of `path` answers with a 2xx or 3xx status
+async fn http_get(host: &str, port: u16, path: &str) -> bool {
+    let Ok(uri) = format!("http://{}:{}{}", host, port, path).parse::<hyper::Uri>() else {
+        return false;
+    };
+    match hyper::Client::new().get(uri).await {
+        Ok(response) => response.status().is_success() || response.status().is_redirection(),
+        Err(_) => false,
+    }
+}
+
+/// Add the probe results to the state of the containers
+pub fn annotate(containers: &mut [ContainerInfo]) {
+    let registry = probes().lock().unwrap();
+    for container in containers {
+        let Some(probes) = container
+            .names
+            .first()
+            .and_then(|name| registry.get(name.trim_start_matches('/')))
+        else {
+            continue;
+        };
+        for (key, state) in [("Ready", &probes.readiness), ("Live", &probes.liveness)] {
+            if let Some(state) = state {
+                container
+                    .state
+                    .insert(key.to_string(), state.passing.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POD: &str = r#"
+apiVersion: v1
+kind: Pod
+metadata:
+  name: probed
+spec:
+  containers:
+    - name: server
+      image: server
+      readinessProbe:
+        tcpSocket: {port: 8080}
+        failureThreshold: 2
+      livenessProbe:
+        exec: {command: [check]}
+        initialDelaySeconds: 30
+    - name: sidecar
+      image: sidecar
+"#;
+
+    fn probe(yaml: &str) -> Probe {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_readiness_needs_a_pass_and_failure_threshold_to_drop() {
+        let now = Instant::now();
+        let mut state = ProbeState::new(
+            probe("{tcpSocket: {port: 1}, failureThreshold: 2}"),
+            ProbeKind::Readiness,
+            now,
+        );
+        assert!(!state.passing);
+        state.record(true, now);
+        assert!(state.passing);
+        state.record(false, now);
+        assert!(state.passing);
+        state.record(false, now);
+        assert!(!state.passing);
+        assert_eq!(state.next_run, now + Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_liveness_passes_until_failure_threshold() {
+        let now = Instant::now();
+        let mut state = ProbeState::new(
+            probe("{tcpSocket: {port: 1}, initialDelaySeconds: 5}"),
+            ProbeKind::Liveness,
+            now,
+        );
+        assert!(state.passing);
+        assert_eq!(state.next_run, now + Duration::from_secs(5));
+        for _ in 0..2 {
+            state.record(false, now);
+        }
+        assert!(state.passing);
+        state.record(false, now);
+        assert!(!state.passing);
+        state.record(true, now);
+        assert!(state.passing);
+    }
+
+    #[test]
+    fn test_annotate_registered_containers() {
+        register(POD).unwrap();
+        let container = |name: &str| ContainerInfo {
+            id: name.to_string(),
+            names: vec![name.to_string()],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), "running".to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::new(),
+            stats: HashMap::new(),
+        };
+        let mut containers = vec![container("probed_server"), container("probed_sidecar")];
+        annotate(&mut containers);
+        assert_eq!(containers[0].state["Ready"], "false");
+        assert_eq!(containers[0].state["Live"], "true");
+        assert!(!containers[1].state.contains_key("Ready"));
+
+        unregister(POD).unwrap();
+        let mut containers = vec![container("probed_server")];
+        annotate(&mut containers);
+        assert!(!containers[0].state.contains_key("Ready"));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let tcp = probe(&format!("{{tcpSocket: {{port: {}}}}}", port));
+        assert!(run("c", &tcp).await);
+        drop(listener);
+        assert!(!run("c", &tcp).await);
+    }
+}
@@ -5,6 +5,7 @@
 use super::{Container, ContainerError, ContainerInspect, ContainerStats};
 use crate::runtime::podman::get;
 use common::monitoringserver::ContainerInfo;
+use common::spec::k8s::pod::{model_of, LABEL_MODEL};
 use futures::future::try_join_all;
 use std::collections::HashMap;
 
@@ -64,6 +65,8 @@ pub async fn inspect(hostname: String) -> std::result::Result<Vec<ContainerInfo>
                 println!("Container {} is not running, stats unavailable.", id);
                 stats_map.insert("Status".to_string(), "StatsUnavailable".to_string());
             }
+            // The restarts and OOM kills by this node are added by crate::restart
+            stats_map.insert("RestartCount".to_string(), inspect.RestartCount.to_string());
             let mut state_map = HashMap::new();
             state_map.insert("Status".to_string(), inspect.State.Status);
             state_map.insert("Running".to_string(), inspect.State.Running.to_string());
@@ -108,14 +111,25 @@ pub async fn inspect(hostname: String) -> std::result::Result<Vec<ContainerInfo>
             config_map.insert("Image".to_string(), inspect.Config.Image.clone());
             config_map.insert("WorkingDir".to_string(), inspect.Config.WorkingDir);
 
-            let annotation_map = if let Some(ann_map) = inspect.Config.Annotations {
+            let mut annotation_map = if let Some(ann_map) = inspect.Config.Annotations {
                 ann_map.clone()
             } else {
                 HashMap::new()
             };
+            // Labels carry the model and package of the container
+            for (key, value) in inspect.Config.Labels.unwrap_or_default() {
+                annotation_map.entry(key).or_insert(value);
+            }
+            // Model containers created before the piccolo annotations were set
+            let names = vec![inspect.Name];
+            if !annotation_map.contains_key(LABEL_MODEL) {
+                if let Some(model) = model_of(&annotation_map) {
+                    annotation_map.insert(LABEL_MODEL.to_string(), model);
+                }
+            }
             Ok::<ContainerInfo, ContainerError>(ContainerInfo {
                 id: inspect.Id,
-                names: vec![inspect.Name],
+                names,
                 image: inspect.Config.Image.clone(),
                 state: state_map,
                 config: config_map,
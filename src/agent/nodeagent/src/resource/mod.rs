@@ -65,6 +65,9 @@ pub struct ContainerInspect {
     pub Name: String,
     pub State: ContainerState,
     pub Config: ContainerConfig,
+    /// Restarts by the restart policy of podman
+    #[serde(default)]
+    pub RestartCount: u32,
 }
 
 #[allow(non_snake_case, unused)]
@@ -3,9 +3,10 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 use super::NodeInfo;
+use common::nodeagent::fromapiserver::{NodeUsage, ResourceInfo};
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
-use sysinfo::{Networks, System};
+use sysinfo::{Disks, Networks, System};
 
 // Static storage for previous IO/network values for delta calculation
 type PrevIoType = Option<(u64, u64, u64, u64)>;
@@ -101,6 +102,58 @@ pub fn extract_node_info_delta() -> NodeInfo {
     }
 }
 
+/// Capacity of the node, reported when registering with the API server
+///
+/// The ActionController admits launches on the node against it.
+pub fn resource_info() -> ResourceInfo {
+    let sys = System::new_all();
+    let disk_bytes: u64 = Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| disk.total_space())
+        .sum();
+
+    ResourceInfo {
+        cpu_cores: sys.cpus().len() as i32,
+        memory_mb: (sys.total_memory() >> 20) as i64,
+        disk_gb: (disk_bytes >> 30) as i64,
+        architecture: System::cpu_arch(),
+        os_version: System::long_os_version().unwrap_or_else(|| "Unknown".to_string()),
+    }
+}
+
+/// Load of the node, reported when registering and in the status reports
+///
+/// The ActionController places relocated models on the least loaded nodes
+/// and admits launches against the free memory. Blocks for the CPU sampling
+/// interval.
+pub fn node_usage(container_count: u32) -> NodeUsage {
+    let mut sys = System::new_all();
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_cpu_usage();
+
+    let cpu_count = sys.cpus().len();
+    let cpu_load = if cpu_count > 0 {
+        sys.cpus().iter().map(|cpu| cpu.cpu_usage()).sum::<f32>() / cpu_count as f32
+    } else {
+        0.0
+    };
+    let disk_used: u64 = Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| disk.total_space().saturating_sub(disk.available_space()))
+        .sum();
+
+    NodeUsage {
+        cpu_load,
+        free_memory_mb: (sys.available_memory() >> 20) as i64,
+        disk_used_gb: (disk_used >> 30) as i64,
+        container_count,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default(),
+    }
+}
+
 /// Returns the first non-loopback IPv4 address as a String, or None if not found.
 fn get_local_ip() -> Option<String> {
     use std::net::UdpSocket;
@@ -158,4 +211,21 @@ mod tests {
         assert!(info.mem_usage >= 0.0 && info.mem_usage <= 100.0);
         // Removed always-true u64 >= 0 assertions
     }
+
+    #[test]
+    fn test_resource_info() {
+        let info = resource_info();
+        assert!(info.cpu_cores > 0);
+        assert!(info.memory_mb > 0);
+        assert!(!info.architecture.is_empty());
+    }
+
+    #[test]
+    fn test_node_usage() {
+        let usage = node_usage(3);
+        assert!(usage.cpu_load >= 0.0 && usage.cpu_load <= 100.0);
+        assert!(usage.free_memory_mb > 0);
+        assert_eq!(usage.container_count, 3);
+        assert!(usage.timestamp > 0);
+    }
 }
@@ -0,0 +1,305 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Restart policy of the Pods started by this node
+//!
+//! Every container list gathered from podman is checked against the
+//! `restartPolicy` of the Pods: stopped containers are restarted, with a
+//! back-off doubling from [`BACKOFF_BASE`] between restarts of the same
+//! container. A container failing its liveness probe is restarted like a
+//! dead one. Restarts are counted per container since its Pod was started,
+//! once a container needs more than `maxRestarts` it is left stopped and
+//! reported with `CrashLoopBackOff`, which the StateManager escalates to
+//! the state of its model.
+//!
+//! The restarts and the OOM kills of a container are added to its stats as
+//! "RestartCount" and "OOMKillCount", for the StateManager to find the
+//! containers that crash loop while they are still restarted.
+
+use common::monitoringserver::ContainerInfo;
+use common::spec::k8s::pod::RestartPolicy;
+use common::spec::k8s::Pod;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::time::{Duration, Instant};
+
+/// Wait before the second restart of a container, doubled for every restart
+pub const BACKOFF_BASE: Duration = Duration::from_secs(10);
+
+/// Longest wait between restarts of a container
+const BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+static PODS: OnceLock<Mutex<HashMap<String, PodRestarts>>> = OnceLock::new();
+
+fn pods() -> &'static Mutex<HashMap<String, PodRestarts>> {
+    PODS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Restart policy of a Pod and the restarts of its containers
+struct PodRestarts {
+    policy: RestartPolicy,
+    max_restarts: u32,
+    containers: HashMap<String, ContainerRestarts>,
+}
+
+#[derive(Default)]
+struct ContainerRestarts {
+    count: u32,
+    next_allowed: Option<Instant>,
+    oom_kills: u32,
+    /// When the container was last OOM killed, a kill is counted once
+    oom_killed_at: Option<String>,
+}
+
+impl ContainerRestarts {
+    /// Count the OOM kill a container stopped with, if it is a new one
+    fn record_oom_kill(&mut self, state: &HashMap<String, String>) {
+        if state.get("OOMKilled").is_none_or(|v| v != "true") {
+            return;
+        }
+        let finished_at = state.get("FinishedAt").cloned().unwrap_or_default();
+        if self.oom_killed_at.as_ref() != Some(&finished_at) {
+            self.oom_kills += 1;
+            self.oom_killed_at = Some(finished_at);
+        }
+    }
+}
+
+/// What to do with a container that is checked
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Keep,
+    Restart,
+    Wait,
+    CrashLoop,
+}
+
+impl PodRestarts {
+    fn verdict(&self, container: &str, state: &HashMap<String, String>, now: Instant) -> Verdict {
+        if !needs_restart(self.policy, state) {
+            return Verdict::Keep;
+        }
+        let restarts = self.containers.get(container);
+        let count = restarts.map_or(0, |r| r.count);
+        if count >= self.max_restarts {
+            return Verdict::CrashLoop;
+        }
+        match restarts.and_then(|r| r.next_allowed) {
+            Some(next_allowed) if now < next_allowed => Verdict::Wait,
+            _ => Verdict::Restart,
+        }
+    }
+
+    fn record_restart(&mut self, container: &str, now: Instant) {
+        let restarts = self.containers.entry(container.to_string()).or_default();
+        restarts.count += 1;
+        let backoff = BACKOFF_BASE.saturating_mul(1 << (restarts.count - 1).min(16));
+        restarts.next_allowed = Some(now + backoff.min(BACKOFF_MAX));
+    }
+}
+
+/// Whether a container has stopped in a way its restart policy covers
+fn needs_restart(policy: RestartPolicy, state: &HashMap<String, String>) -> bool {
+    let status = state.get("Status").map(String::as_str).unwrap_or_default();
+    let failed = status == "dead"
+        || state.get("OOMKilled").is_some_and(|v| v == "true")
+        || (status == "running" && state.get("Live").is_some_and(|v| v == "false"))
+        || (status == "exited" && state.get("ExitCode").is_some_and(|v| v != "0"));
+    match policy {
+        RestartPolicy::Always => failed || status == "exited",
+        RestartPolicy::OnFailure => failed,
+        RestartPolicy::Never => false,
+    }
+}
+
+/// Register the restart policy of a Pod, resetting its restart counts
+pub fn register(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pod: Pod = serde_yaml::from_str(pod_yaml)?;
+    let spec = pod.get_spec();
+    pods().lock().unwrap().insert(
+        pod.get_name(),
+        PodRestarts {
+            policy: spec.restart_policy(),
+            max_restarts: spec.max_restarts(),
+            containers: HashMap::new(),
+        },
+    );
+    Ok(())
+}
+
+/// Forget a Pod, its containers are stopped on purpose
+pub fn unregister(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pod: Pod = serde_yaml::from_str(pod_yaml)?;
+    pods().lock().unwrap().remove(&pod.get_name());
+    Ok(())
+}
+
+/// Pod a container was created for, by its `{pod}_{container}` name
+fn pod_of(container_name: &str, pods: &HashMap<String, PodRestarts>) -> Option<String> {
+    pods.keys()
+        .filter(|pod| {
+            container_name
+                .strip_prefix(pod.as_str())
+                .is_some_and(|rest| rest.starts_with('_'))
+        })
+        .max_by_key(|pod| pod.len())
+        .cloned()
+}
+
+/// Restart the stopped containers per their restart policy and add the
+/// restart and OOM kill counts to their stats
+pub async fn enforce(containers: &mut [ContainerInfo]) {
+    let now = Instant::now();
+    for container in containers {
+        let Some(name) = container
+            .names
+            .first()
+            .map(|name| name.trim_start_matches('/').to_string())
+        else {
+            continue;
+        };
+
+        let verdict = {
+            let pods = pods().lock().unwrap();
+            let Some(pod) = pod_of(&name, &pods) else {
+                continue;
+            };
+            pods[&pod].verdict(&name, &container.state, now)
+        };
+
+        match verdict {
+            Verdict::Restart => {
+                println!("[Restart] Restarting stopped container {}", name);
+                if let Err(e) = crate::runtime::podman::container::restart_container(&name).await {
+                    println!("[Restart] Failed to restart container {}: {}", name, e);
+                }
+                crate::probe::reset(&name);
+                let mut pods = pods().lock().unwrap();
+                if let Some(pod) = pod_of(&name, &pods) {
+                    if let Some(pod) = pods.get_mut(&pod) {
+                        pod.record_restart(&name, now);
+                    }
+                }
+            }
+            Verdict::CrashLoop => {
+                container
+                    .state
+                    .insert("CrashLoopBackOff".to_string(), "true".to_string());
+            }
+            Verdict::Keep | Verdict::Wait => {}
+        }
+
+        let mut pods = pods().lock().unwrap();
+        if let Some(pod) = pod_of(&name, &pods) {
+            if let Some(pod) = pods.get_mut(&pod) {
+                let restarts = pod.containers.entry(name).or_default();
+                restarts.record_oom_kill(&container.state);
+                // Restarts by podman are counted by podman
+                let podman_restarts: u32 = container
+                    .stats
+                    .get("RestartCount")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_default();
+                container.stats.insert(
+                    "RestartCount".to_string(),
+                    (podman_restarts + restarts.count).to_string(),
+                );
+                container
+                    .stats
+                    .insert("OOMKillCount".to_string(), restarts.oom_kills.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn pod(policy: RestartPolicy, max_restarts: u32) -> PodRestarts {
+        PodRestarts {
+            policy,
+            max_restarts,
+            containers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_needs_restart_per_policy() {
+        let completed = state(&[("Status", "exited"), ("ExitCode", "0")]);
+        let failed = state(&[("Status", "exited"), ("ExitCode", "1")]);
+        let not_live = state(&[("Status", "running"), ("Live", "false")]);
+        let running = state(&[("Status", "running"), ("Live", "true")]);
+
+        assert!(needs_restart(RestartPolicy::Always, &completed));
+        assert!(!needs_restart(RestartPolicy::OnFailure, &completed));
+        assert!(needs_restart(RestartPolicy::OnFailure, &failed));
+        assert!(needs_restart(RestartPolicy::OnFailure, &not_live));
+        assert!(!needs_restart(RestartPolicy::Never, &failed));
+        assert!(!needs_restart(RestartPolicy::Always, &running));
+    }
+
+    #[test]
+    fn test_restarts_back_off_until_crash_loop() {
+        let now = Instant::now();
+        let dead = state(&[("Status", "dead")]);
+        let mut pod = pod(RestartPolicy::Always, 2);
+
+        assert_eq!(pod.verdict("p_c", &dead, now), Verdict::Restart);
+        pod.record_restart("p_c", now);
+        assert_eq!(pod.verdict("p_c", &dead, now), Verdict::Wait);
+        let later = now + BACKOFF_BASE;
+        assert_eq!(pod.verdict("p_c", &dead, later), Verdict::Restart);
+        pod.record_restart("p_c", later);
+        assert_eq!(
+            pod.containers["p_c"].next_allowed,
+            Some(later + BACKOFF_BASE * 2)
+        );
+        assert_eq!(pod.verdict("p_c", &dead, later), Verdict::CrashLoop);
+        // Other containers of the pod have their own count
+        assert_eq!(pod.verdict("p_d", &dead, later), Verdict::Restart);
+    }
+
+    #[test]
+    fn test_oom_kills_are_counted_once() {
+        let mut restarts = ContainerRestarts::default();
+        let killed = state(&[
+            ("Status", "exited"),
+            ("OOMKilled", "true"),
+            ("FinishedAt", "2024-01-01T00:00:00Z"),
+        ]);
+        restarts.record_oom_kill(&killed);
+        restarts.record_oom_kill(&killed);
+        assert_eq!(restarts.oom_kills, 1);
+
+        restarts.record_oom_kill(&state(&[("Status", "running"), ("OOMKilled", "false")]));
+        assert_eq!(restarts.oom_kills, 1);
+
+        let killed_again = state(&[
+            ("Status", "exited"),
+            ("OOMKilled", "true"),
+            ("FinishedAt", "2024-01-01T00:01:00Z"),
+        ]);
+        restarts.record_oom_kill(&killed_again);
+        assert_eq!(restarts.oom_kills, 2);
+    }
+
+    #[test]
+    fn test_pod_of_picks_the_longest_prefix() {
+        let pods = HashMap::from([
+            ("app".to_string(), pod(RestartPolicy::Always, 1)),
+            ("app_v2".to_string(), pod(RestartPolicy::Always, 1)),
+        ]);
+        assert_eq!(pod_of("app_main", &pods), Some("app".to_string()));
+        assert_eq!(pod_of("app_v2_main", &pods), Some("app_v2".to_string()));
+        assert_eq!(pod_of("application_main", &pods), None);
+    }
+}
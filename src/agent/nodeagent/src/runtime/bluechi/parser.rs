@@ -74,7 +74,8 @@ pub async fn get_complete_model(
                 if model.get_name() == model_name {
                     if let Some(volume_name) = mi.get_resources().get_volume() {
                         let key = format!("Volume/{}", volume_name);
-                        let volume_str: String = common::etcd::get(&key).await?;
+                        let volume_str: String =
+                            common::etcd::get(&key).await.map_err(common::etcd::error)?;
                         let volume: Volume = serde_yaml::from_str(&volume_str)?;
 
                         if let Some(volume_spec) = volume.get_spec() {
@@ -86,7 +87,8 @@ pub async fn get_complete_model(
                     }
                     if let Some(network_name) = mi.get_resources().get_network() {
                         let key = format!("Network/{}", network_name);
-                        let network_str = common::etcd::get(&key).await?;
+                        let network_str =
+                            common::etcd::get(&key).await.map_err(common::etcd::error)?;
                         let network: Network = serde_yaml::from_str(&network_str)?;
 
                         if let Some(_network_spec) = network.get_spec() {
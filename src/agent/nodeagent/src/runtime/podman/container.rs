@@ -4,10 +4,14 @@
 */
 
 use super::{get, post};
+use common::nodeagent::fromapiserver::LogStream;
 use hyper::Body;
 use serde_json::json;
+use std::collections::HashMap;
 
-const PODMAN_API_VERSION: &str = "/v4.0.0/libpod";
+use super::PODMAN_API_VERSION;
+use crate::termination::{Termination, POLL_INTERVAL};
+use tokio::time::{sleep, Duration, Instant};
 
 /// Parse Pod YAML and extract pod name and spec
 fn parse_pod(pod_yaml: &str) -> Result<(String, serde_json::Value), Box<dyn std::error::Error>> {
@@ -18,6 +22,26 @@ fn parse_pod(pod_yaml: &str) -> Result<(String, serde_json::Value), Box<dyn std:
     Ok((pod_name, spec))
 }
 
+/// Labels and annotations of the Pod, given to each of its containers
+///
+/// The piccolo annotations naming the model, package and scenario are
+/// injected over those of the Pod.
+fn parse_metadata(pod_yaml: &str) -> Result<PodMetadata, Box<dyn std::error::Error>> {
+    let pod = serde_yaml::from_str::<common::spec::k8s::Pod>(pod_yaml)?;
+    let mut annotations = pod.get_annotations();
+    annotations.extend(pod.piccolo_annotations());
+    Ok(PodMetadata {
+        labels: pod.get_labels(),
+        annotations,
+    })
+}
+
+/// Metadata propagated from the Pod to its containers
+struct PodMetadata {
+    labels: HashMap<String, String>,
+    annotations: HashMap<String, String>,
+}
+
 /// Get container names from pod spec
 fn get_container_names(
     pod_name: &str,
@@ -121,12 +145,78 @@ fn build_command(container: &serde_json::Value) -> Vec<String> {
         .unwrap_or_default()
 }
 
+/// CFS period the CPU limit of a container is enforced over, in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Build the libpod resource limits of a container from its requests and limits
+///
+/// CPU requests become relative shares, CPU limits a quota of the CFS period.
+/// A container without a CPU or memory limit gets the one of `defaults`, the
+/// limits of the node configuration, unless it requests more. Returns `None`
+/// for containers without resources.
+fn build_resource_limits(
+    container: &serde_json::Value,
+    defaults: &crate::node_config::ResourceLimits,
+) -> Result<Option<serde_json::Value>, Box<dyn std::error::Error>> {
+    let container: common::spec::k8s::pod::Container = serde_json::from_value(container.clone())?;
+    let mut limits = container
+        .resource_limits()
+        .map_err(|e| format!("container '{}': {}", container.get_name(), e))?;
+    if limits.cpu_limit_millis.is_none() {
+        limits.cpu_limit_millis = defaults
+            .cpu
+            .map(|cores| (cores * 1000.0).round() as u64)
+            .filter(|limit| limits.cpu_request_millis.is_none_or(|r| r <= *limit));
+    }
+    if limits.memory_limit_bytes.is_none() {
+        limits.memory_limit_bytes = defaults
+            .memory_mb
+            .map(|mb| mb << 20)
+            .filter(|limit| limits.memory_request_bytes.is_none_or(|r| r <= *limit));
+    }
+
+    let mut resources = serde_json::Map::new();
+    let mut cpu = serde_json::Map::new();
+    if let Some(millis) = limits.cpu_request_millis {
+        // Same conversion as the kubelet: 1024 shares per core, at least 2
+        cpu.insert("shares".to_string(), json!((millis * 1024 / 1000).max(2)));
+    }
+    if let Some(millis) = limits.cpu_limit_millis {
+        cpu.insert("period".to_string(), json!(CPU_PERIOD_US));
+        cpu.insert(
+            "quota".to_string(),
+            json!((millis * CPU_PERIOD_US / 1000).max(1000)),
+        );
+    }
+    if !cpu.is_empty() {
+        resources.insert("cpu".to_string(), json!(cpu));
+    }
+
+    let mut memory = serde_json::Map::new();
+    if let Some(bytes) = limits.memory_request_bytes {
+        memory.insert("reservation".to_string(), json!(bytes));
+    }
+    if let Some(bytes) = limits.memory_limit_bytes {
+        memory.insert("limit".to_string(), json!(bytes));
+    }
+    if !memory.is_empty() {
+        resources.insert("memory".to_string(), json!(memory));
+    }
+
+    if let Some(pids) = limits.pids_limit {
+        resources.insert("pids".to_string(), json!({ "limit": pids }));
+    }
+
+    Ok((!resources.is_empty()).then(|| json!(resources)))
+}
+
 /// Create container from spec
 async fn create_container(
     pod_name: &str,
     container: &serde_json::Value,
     spec: &serde_json::Value,
     host_network: bool,
+    metadata: &PodMetadata,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let image = container["image"]
         .as_str()
@@ -135,12 +225,8 @@ async fn create_container(
         .as_str()
         .ok_or("Container name field not found")?;
 
-    // Check if image exists, pull if not
-    if !image_exists(image).await? {
-        println!("Image {} not found locally, pulling...", image);
-        pull_image(image).await?;
-        println!("Image {} pulled successfully", image);
-    }
+    // Pull the image unless it is cached, see crate::image
+    crate::image::ensure(image).await?;
 
     // Build container creation request
     let mut create_body = json!({
@@ -154,6 +240,19 @@ async fn create_container(
         create_body["HostConfig"] = host_config;
     }
 
+    // Enforce the CPU, memory and process limits of the container
+    let defaults = crate::node_config::get().resource_limits;
+    if let Some(resource_limits) = build_resource_limits(container, &defaults)? {
+        create_body["resource_limits"] = resource_limits;
+    }
+
+    // Labels and annotations let the containers be traced back to their
+    // model and package
+    if !metadata.labels.is_empty() {
+        create_body["Labels"] = json!(metadata.labels);
+    }
+    create_body["Annotations"] = json!(metadata.annotations);
+
     // Add environment variables
     let env_vars = build_env_vars(container);
     if !env_vars.is_empty() {
@@ -182,11 +281,16 @@ async fn create_container(
 
 pub async fn start(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
     let (pod_name, spec) = parse_pod(pod_yaml)?;
+    let metadata = parse_metadata(pod_yaml)?;
     let host_network = spec["hostNetwork"].as_bool().unwrap_or(false);
 
+    // Volumes must exist before containers binding them are created
+    super::volume::provision(&pod_name, &spec).await?;
+
     if let Some(containers) = spec["containers"].as_array() {
         for container in containers.iter() {
-            let container_id = create_container(&pod_name, container, &spec, host_network).await?;
+            let container_id =
+                create_container(&pod_name, container, &spec, host_network, &metadata).await?;
 
             // Start the container
             println!("Starting container: {}", container_id);
@@ -200,19 +304,90 @@ pub async fn start(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Seconds the containers of a Pod have to exit after SIGTERM
+fn grace_period(pod_yaml: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let pod = serde_yaml::from_str::<common::spec::k8s::Pod>(pod_yaml)?;
+    Ok(pod.get_spec().termination_grace_period_seconds())
+}
+
+/// Send a signal to the main process of a container
+async fn kill(container_name: &str, signal: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let kill_path = format!(
+        "{}/containers/{}/kill?signal={}",
+        PODMAN_API_VERSION, container_name, signal
+    );
+    post(&kill_path, Body::empty()).await?;
+    Ok(())
+}
+
+/// Whether a container still runs, `false` once it is gone
+async fn is_running(container_name: &str) -> bool {
+    crate::resource::container::get_inspect(container_name)
+        .await
+        .is_ok_and(|inspect| inspect.State.Running)
+}
+
+/// Wait for a container sent SIGTERM to exit, and SIGKILL it if it still
+/// runs at `deadline`
+async fn wait_for_exit(container_name: &str, deadline: Instant) -> Result<Termination, String> {
+    while is_running(container_name).await {
+        if Instant::now() >= deadline {
+            kill(container_name, "SIGKILL")
+                .await
+                .map_err(|e| e.to_string())?;
+            return Ok(Termination::Forced);
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+    Ok(Termination::Graceful)
+}
+
 pub async fn stop(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
     let (pod_name, spec) = parse_pod(pod_yaml)?;
     let container_names = get_container_names(&pod_name, &spec)?;
-
-    for full_container_name in container_names {
-        // Stop the container
-        println!("Stopping container: {}", full_container_name);
-        let stop_path = format!(
-            "{}/containers/{}/stop",
-            PODMAN_API_VERSION, full_container_name
+    let grace_period = grace_period(pod_yaml)?;
+
+    // Every container gets SIGTERM before any is waited for, so that their
+    // grace periods run at the same time
+    let mut signalled = Vec::new();
+    for full_container_name in &container_names {
+        println!(
+            "Stopping container: {} (grace period {}s)",
+            full_container_name, grace_period
         );
-        match post(&stop_path, Body::empty()).await {
-            Ok(_) => println!("Container {} stopped successfully", full_container_name),
+        // The error is not Send, it must not be held over the next request
+        let sent = kill(full_container_name, "SIGTERM")
+            .await
+            .map_err(|e| e.to_string());
+        signalled.push(sent);
+    }
+    let deadline = Instant::now() + Duration::from_secs(grace_period);
+    let terminations = futures::future::join_all(container_names.iter().zip(signalled).map(
+        |(full_container_name, sent)| async move {
+            sent?;
+            wait_for_exit(full_container_name, deadline).await
+        },
+    ))
+    .await;
+
+    for (full_container_name, terminated) in container_names.into_iter().zip(terminations) {
+        match terminated {
+            Ok(termination) => {
+                println!(
+                    "Container {} stopped: {}",
+                    full_container_name,
+                    termination.as_str()
+                );
+                // The last state is reported after the container is removed
+                if let Ok(inspect) =
+                    crate::resource::container::get_inspect(&full_container_name).await
+                {
+                    crate::termination::record(crate::termination::last_state(
+                        inspect,
+                        termination,
+                    ));
+                }
+            }
             Err(e) => println!(
                 "Warning: Failed to stop container {}: {}",
                 full_container_name, e
@@ -234,19 +409,22 @@ pub async fn stop(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    super::volume::release(&pod_name, &spec).await;
+
     Ok(())
 }
 
 pub async fn restart(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
     let (pod_name, spec) = parse_pod(pod_yaml)?;
     let container_names = get_container_names(&pod_name, &spec)?;
+    let grace_period = grace_period(pod_yaml)?;
 
     for full_container_name in container_names {
-        // Use Podman's restart API endpoint
+        // Use Podman's restart API endpoint, which kills after the grace period
         println!("Restarting container: {}", full_container_name);
         let restart_path = format!(
-            "{}/containers/{}/restart",
-            PODMAN_API_VERSION, full_container_name
+            "{}/containers/{}/restart?t={}",
+            PODMAN_API_VERSION, full_container_name, grace_period
         );
         match post(&restart_path, Body::empty()).await {
             Ok(_) => println!("Container {} restarted successfully", full_container_name),
@@ -267,22 +445,131 @@ pub async fn restart(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Check if an image exists locally
-pub async fn image_exists(image_name: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    let path = "/v4.0.0/libpod/images/json";
-
-    let result = get(path).await?;
-    let images: Vec<serde_json::Value> = serde_json::from_slice(&result)?;
-    for image in images {
-        if let Some(repo_tags) = image["RepoTags"].as_array() {
-            for tag in repo_tags {
-                if tag.as_str() == Some(image_name) {
-                    return Ok(true);
-                }
-            }
+/// Freeze the containers of a Pod, keeping them created and their memory
+///
+/// A paused Pod is a warm standby, [`unpause`] resumes it where it stopped.
+pub async fn pause(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (pod_name, spec) = parse_pod(pod_yaml)?;
+    let container_names = get_container_names(&pod_name, &spec)?;
+    for full_container_name in container_names {
+        println!("Pausing container: {}", full_container_name);
+        let pause_path = format!(
+            "{}/containers/{}/pause",
+            PODMAN_API_VERSION, full_container_name
+        );
+        post(&pause_path, Body::empty()).await?;
+    }
+    Ok(())
+}
+
+/// Resume the containers of a Pod paused by [`pause`]
+pub async fn unpause(pod_yaml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (pod_name, spec) = parse_pod(pod_yaml)?;
+    let container_names = get_container_names(&pod_name, &spec)?;
+    for full_container_name in container_names {
+        println!("Unpausing container: {}", full_container_name);
+        let unpause_path = format!(
+            "{}/containers/{}/unpause",
+            PODMAN_API_VERSION, full_container_name
+        );
+        post(&unpause_path, Body::empty()).await?;
+    }
+    Ok(())
+}
+
+/// Restart a single container, keeping the other containers of its Pod
+pub async fn restart_container(container_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let restart_path = format!(
+        "{}/containers/{}/restart",
+        PODMAN_API_VERSION, container_name
+    );
+    post(&restart_path, Body::empty()).await?;
+    Ok(())
+}
+
+/// Interval between reads of the state of a command run by [`exec`]
+const EXEC_POLL_MS: u64 = 100;
+
+/// Output of a command run by [`exec_output`]
+#[derive(Debug, Default, PartialEq)]
+pub struct ExecOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: i64,
+}
+
+/// Create a command in a running container, returning the ID of the exec
+async fn create_exec(
+    container_name: &str,
+    cmd: &[String],
+    attach: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let create_path = format!("{}/containers/{}/exec", PODMAN_API_VERSION, container_name);
+    let create_body = json!({ "Cmd": cmd, "AttachStdout": attach, "AttachStderr": attach });
+    let response = post(&create_path, Body::from(create_body.to_string())).await?;
+    let created: serde_json::Value = serde_json::from_slice(&response)?;
+    Ok(created["Id"]
+        .as_str()
+        .ok_or_else(|| format!("Failed to exec in {}: {}", container_name, created))?
+        .to_string())
+}
+
+/// Wait for a started command to exit and take its exit code
+async fn wait_exec(container_name: &str, exec_id: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    let inspect_path = format!("{}/exec/{}/json", PODMAN_API_VERSION, exec_id);
+    loop {
+        let inspect: serde_json::Value = serde_json::from_slice(&get(&inspect_path).await?)?;
+        if inspect["Running"].as_bool() == Some(false) {
+            return inspect["ExitCode"]
+                .as_i64()
+                .ok_or_else(|| format!("No exit code for exec in {}", container_name).into());
         }
+        tokio::time::sleep(std::time::Duration::from_millis(EXEC_POLL_MS)).await;
     }
-    Ok(false)
+}
+
+/// Run a command in a running container and wait for its exit code
+pub async fn exec(container_name: &str, cmd: &[String]) -> Result<i64, Box<dyn std::error::Error>> {
+    let exec_id = create_exec(container_name, cmd, false).await?;
+
+    let start_path = format!("{}/exec/{}/start", PODMAN_API_VERSION, exec_id);
+    post(
+        &start_path,
+        Body::from(json!({ "Detach": true }).to_string()),
+    )
+    .await?;
+
+    wait_exec(container_name, &exec_id).await
+}
+
+/// Run a command in a running container and collect its output
+///
+/// The start of an attached exec answers once the command has exited, with
+/// the output framed like the logs of the container.
+pub async fn exec_output(
+    container_name: &str,
+    cmd: &[String],
+) -> Result<ExecOutput, Box<dyn std::error::Error>> {
+    let exec_id = create_exec(container_name, cmd, true).await?;
+
+    let start_path = format!("{}/exec/{}/start", PODMAN_API_VERSION, exec_id);
+    let body = post(
+        &start_path,
+        Body::from(json!({ "Detach": false, "Tty": false }).to_string()),
+    )
+    .await?;
+
+    let mut output = ExecOutput::default();
+    let mut demuxer = super::logs::Demuxer::default();
+    let frames = demuxer.push(&body).into_iter().chain(demuxer.finish());
+    for (stream, data) in frames {
+        match stream {
+            LogStream::Stderr => output.stderr.extend(data),
+            LogStream::Stdout => output.stdout.extend(data),
+        }
+    }
+    output.exit_code = wait_exec(container_name, &exec_id).await?;
+    Ok(output)
 }
 
 /// Pull an image from a registry
@@ -291,3 +578,86 @@ pub async fn pull_image(image_name: &str) -> Result<(), Box<dyn std::error::Erro
     post(&path, Body::empty()).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_config::ResourceLimits;
+
+    #[test]
+    fn test_build_resource_limits() {
+        let container = json!({
+            "name": "hello",
+            "image": "hello",
+            "resources": {
+                "requests": { "cpu": "250m", "memory": "64Mi" },
+                "limits": { "cpu": "1500m", "memory": "128Mi", "pids": "32" }
+            }
+        });
+        let none = ResourceLimits::default();
+        assert_eq!(
+            build_resource_limits(&container, &none).unwrap(),
+            Some(json!({
+                "cpu": { "shares": 256, "period": 100_000, "quota": 150_000 },
+                "memory": { "reservation": 64 << 20, "limit": 128 << 20 },
+                "pids": { "limit": 32 }
+            }))
+        );
+
+        let unbounded = json!({ "name": "hello", "image": "hello" });
+        assert_eq!(build_resource_limits(&unbounded, &none).unwrap(), None);
+
+        // The limits of the node apply to containers without their own
+        let defaults = ResourceLimits {
+            cpu: Some(0.5),
+            memory_mb: Some(256),
+        };
+        assert_eq!(
+            build_resource_limits(&unbounded, &defaults).unwrap(),
+            Some(json!({
+                "cpu": { "period": 100_000, "quota": 50_000 },
+                "memory": { "limit": 256 << 20 }
+            }))
+        );
+        assert_eq!(
+            build_resource_limits(&container, &defaults)
+                .unwrap()
+                .unwrap()["memory"]["limit"],
+            json!(128 << 20)
+        );
+
+        let malformed = json!({
+            "name": "hello",
+            "image": "hello",
+            "resources": { "limits": { "memory": "lots" } }
+        });
+        assert!(build_resource_limits(&malformed, &none).is_err());
+    }
+    #[test]
+    fn test_parse_metadata_injects_piccolo_annotations() {
+        use common::spec::k8s::pod::{LABEL_MODEL, LABEL_PACKAGE};
+        let pod = r#"
+apiVersion: v1
+kind: Pod
+metadata:
+  name: hello-core
+  labels:
+    io.piccolo.model: hello-core
+    io.piccolo.package: hello
+  annotations:
+    io.piccolo.model: stale
+    io.piccolo.annotations.package-network: default
+spec:
+  containers:
+    - name: hello
+      image: hello
+"#;
+        let metadata = parse_metadata(pod).unwrap();
+        assert_eq!(metadata.annotations[LABEL_MODEL], "hello-core");
+        assert_eq!(metadata.annotations[LABEL_PACKAGE], "hello");
+        assert_eq!(
+            metadata.annotations["io.piccolo.annotations.package-network"],
+            "default"
+        );
+    }
+}
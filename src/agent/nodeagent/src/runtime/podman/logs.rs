@@ -0,0 +1,187 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Logs of the containers, read from the podman logs endpoint
+//!
+//! Podman sends the logs of a container without a terminal as frames of an
+//! 8 byte header (stream type, three zero bytes and the big endian length of
+//! the data) followed by the data, which [`Demuxer`] splits again. Output of
+//! a container with a terminal is not framed and passed on as stdout.
+
+use super::{get_response, PODMAN_API_VERSION};
+use common::nodeagent::fromapiserver::LogStream;
+use hyper::Body;
+
+/// Length of the header of a log frame
+const HEADER_LEN: usize = 8;
+
+/// Which logs of a container to read
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LogOptions {
+    /// Number of lines from the end, 0 for all of them
+    pub tail: i64,
+    /// Unix time in seconds of the oldest line, 0 for no limit
+    pub since: i64,
+    /// Keep the body open for new lines
+    pub follow: bool,
+}
+
+impl LogOptions {
+    fn query(&self) -> String {
+        let mut query = String::from("stdout=true&stderr=true");
+        if self.tail > 0 {
+            query.push_str(&format!("&tail={}", self.tail));
+        }
+        if self.since > 0 {
+            query.push_str(&format!("&since={}", self.since));
+        }
+        if self.follow {
+            query.push_str("&follow=true");
+        }
+        query
+    }
+}
+
+/// Open the logs of a container, the body streams the framed output
+pub async fn open(container_name: &str, options: &LogOptions) -> Result<Body, String> {
+    let path = format!(
+        "{}/containers/{}/logs?{}",
+        PODMAN_API_VERSION,
+        container_name,
+        options.query()
+    );
+    let response = get_response(&path).await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        return Ok(response.into_body());
+    }
+
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .unwrap_or_default();
+    let message = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|error| error["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(&body).to_string());
+    Err(format!(
+        "Failed to read logs of {} ({}): {}",
+        container_name, status, message
+    ))
+}
+
+/// Splits the body of the logs endpoint into the data of each stream
+#[derive(Default)]
+pub struct Demuxer {
+    buffer: Vec<u8>,
+    /// Whether the body is framed, known once a header could be read
+    framed: Option<bool>,
+}
+
+impl Demuxer {
+    /// Add the next bytes of the body and take the complete frames
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<(LogStream, Vec<u8>)> {
+        if self.framed == Some(false) {
+            return vec![(LogStream::Stdout, bytes.to_vec())];
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        let mut frames = Vec::new();
+        while self.buffer.len() >= HEADER_LEN {
+            let stream = match self.buffer[..4] {
+                [0 | 1, 0, 0, 0] => LogStream::Stdout,
+                [2, 0, 0, 0] => LogStream::Stderr,
+                _ => {
+                    // Not a header: the container has a terminal
+                    self.framed = Some(false);
+                    frames.push((LogStream::Stdout, std::mem::take(&mut self.buffer)));
+                    return frames;
+                }
+            };
+            self.framed = Some(true);
+            let len = u32::from_be_bytes([
+                self.buffer[4],
+                self.buffer[5],
+                self.buffer[6],
+                self.buffer[7],
+            ]) as usize;
+            if self.buffer.len() < HEADER_LEN + len {
+                break;
+            }
+            let data = self.buffer[HEADER_LEN..HEADER_LEN + len].to_vec();
+            self.buffer.drain(..HEADER_LEN + len);
+            frames.push((stream, data));
+        }
+        frames
+    }
+
+    /// Bytes left at the end of the body, output of a terminal shorter than
+    /// a header
+    pub fn finish(self) -> Option<(LogStream, Vec<u8>)> {
+        (self.framed.is_none() && !self.buffer.is_empty())
+            .then_some((LogStream::Stdout, self.buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(stream: u8, data: &str) -> Vec<u8> {
+        let mut bytes = vec![stream, 0, 0, 0];
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(data.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_demux_frames_split_across_chunks() {
+        let mut body = frame(1, "hello\n");
+        body.extend(frame(2, "oops\n"));
+        let (first, second) = body.split_at(10);
+
+        let mut demuxer = Demuxer::default();
+        assert!(demuxer.push(first).is_empty());
+        assert_eq!(
+            demuxer.push(second),
+            vec![
+                (LogStream::Stdout, b"hello\n".to_vec()),
+                (LogStream::Stderr, b"oops\n".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_demux_passes_unframed_output_as_stdout() {
+        let mut demuxer = Demuxer::default();
+        assert_eq!(
+            demuxer.push(b"plain output\n"),
+            vec![(LogStream::Stdout, b"plain output\n".to_vec())]
+        );
+        assert_eq!(
+            demuxer.push(&frame(2, "x")),
+            vec![(LogStream::Stdout, frame(2, "x"))]
+        );
+
+        let mut demuxer = Demuxer::default();
+        assert!(demuxer.push(b"ok\n").is_empty());
+        assert_eq!(
+            demuxer.finish(),
+            Some((LogStream::Stdout, b"ok\n".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_log_options_query() {
+        assert_eq!(LogOptions::default().query(), "stdout=true&stderr=true");
+        let options = LogOptions {
+            tail: 100,
+            since: 1700000000,
+            follow: true,
+        };
+        assert_eq!(
+            options.query(),
+            "stdout=true&stderr=true&tail=100&since=1700000000&follow=true"
+        );
+    }
+}
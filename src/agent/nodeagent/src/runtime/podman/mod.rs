@@ -4,41 +4,52 @@
 */
 
 pub mod container;
+pub mod logs;
+pub mod volume;
 
 use common::nodeagent::fromactioncontroller::WorkloadCommand;
 use hyper::{Body, Client, Method, Request, Uri};
 use hyperlocal::{UnixConnector, Uri as UnixUri};
 
+pub const PODMAN_API_VERSION: &str = "/v4.0.0/libpod";
+
 pub async fn get(path: &str) -> Result<hyper::body::Bytes, hyper::Error> {
     let connector = UnixConnector;
     let client = Client::builder().build::<_, Body>(connector);
 
-    // Modify this if you want to run without root authorization
-    // or if you have a different socket path.
+    // The socket path comes from the runtime node configuration.
     // For example, if you run Podman as root, you might use:
-    // let socket = "/var/run/podman/podman.sock";
+    //   /var/run/podman/podman.sock
     // Or if you run it as a user, you might use:
-    // let socket = "/run/user/1000/podman/podman.sock
-    let socket = "/var/run/podman/podman.sock";
-    // let socket = "/var/run/podman/podman.sock";
+    //   /run/user/1000/podman/podman.sock
+    let socket = crate::node_config::get().runtime_socket;
     let uri: Uri = UnixUri::new(socket, path).into();
 
     let res = client.get(uri).await?;
     hyper::body::to_bytes(res).await
 }
 
+/// GET whose body is read as it arrives, for endpoints that stream
+pub async fn get_response(path: &str) -> Result<hyper::Response<Body>, hyper::Error> {
+    let connector = UnixConnector;
+    let client = Client::builder().build::<_, Body>(connector);
+
+    let socket = crate::node_config::get().runtime_socket;
+    let uri: Uri = UnixUri::new(socket, path).into();
+
+    client.get(uri).await
+}
+
 pub async fn post(path: &str, body: Body) -> Result<hyper::body::Bytes, hyper::Error> {
     let connector = UnixConnector;
     let client = Client::builder().build::<_, Body>(connector);
 
-    // Modify this if you want to run without root authorization
-    // or if you have a different socket path.
+    // The socket path comes from the runtime node configuration.
     // For example, if you run Podman as root, you might use:
-    // let socket = "/var/run/podman/podman.sock";
+    //   /var/run/podman/podman.sock
     // Or if you run it as a user, you might use:
-    // let socket = "/run/user/1000/podman/podman.sock
-    let socket = "/var/run/podman/podman.sock";
-    // let socket = "/var/run/podman/podman.sock";
+    //   /run/user/1000/podman/podman.sock
+    let socket = crate::node_config::get().runtime_socket;
     // let path = "/v4.0.0/libpod/containers/{name}/start";
     let uri: Uri = UnixUri::new(socket, path).into();
 
@@ -56,7 +67,7 @@ pub async fn delete(path: &str) -> Result<hyper::body::Bytes, hyper::Error> {
     let connector = UnixConnector;
     let client = Client::builder().build::<_, Body>(connector);
 
-    let socket = "/var/run/podman/podman.sock";
+    let socket = crate::node_config::get().runtime_socket;
     let uri: Uri = UnixUri::new(socket, path).into();
 
     let req = Request::builder()
@@ -77,12 +88,29 @@ pub async fn handle_workload(command: i32, pod: &str) -> Result<(), Box<dyn std:
     match command {
         x if x == WorkloadCommand::Start as i32 => {
             container::start(pod).await?;
+            crate::probe::register(pod)?;
+            crate::restart::register(pod)?;
         }
         x if x == WorkloadCommand::Stop as i32 => {
+            crate::restart::unregister(pod)?;
+            crate::probe::unregister(pod)?;
             container::stop(pod).await?;
         }
         x if x == WorkloadCommand::Restart as i32 => {
             container::restart(pod).await?;
+            crate::probe::register(pod)?;
+            crate::restart::register(pod)?;
+        }
+        // A paused standby is neither probed nor restarted until it is resumed
+        x if x == WorkloadCommand::Pause as i32 => {
+            crate::restart::unregister(pod)?;
+            crate::probe::unregister(pod)?;
+            container::pause(pod).await?;
+        }
+        x if x == WorkloadCommand::Unpause as i32 => {
+            container::unpause(pod).await?;
+            crate::probe::register(pod)?;
+            crate::restart::register(pod)?;
         }
         _ => {
             // Do nothing for unimplemented commands
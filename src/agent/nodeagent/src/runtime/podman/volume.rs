@@ -0,0 +1,293 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Provisioning of the volumes a model mounts
+//!
+//! A `hostPath` volume with an absolute path is bound from the host, any
+//! other path names a podman volume. Before the containers of a model are
+//! created every volume is made available, and the model is recorded as a
+//! user of it in etcd under `/volume/{node}/{source}/users/{model}`. When the
+//! last model using a volume stops, the volume is removed again if this
+//! NodeAgent created it.
+
+use super::{delete, get, post, PODMAN_API_VERSION};
+use hyper::Body;
+use serde_json::json;
+use std::path::Path;
+
+/// Where the data of a volume lives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VolumeSource {
+    /// Directory on the host, bound into the containers
+    HostPath(String),
+    /// Volume managed by podman
+    Named(String),
+}
+
+/// Record of a provisioned volume kept in etcd
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct VolumeRecord {
+    source: String,
+    /// Whether provisioning created the volume, only then cleanup removes it
+    created: bool,
+}
+
+impl VolumeSource {
+    /// Classify the `hostPath.path` of a pod volume
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let path = path.trim();
+        if path.is_empty() {
+            return Err("volume path is empty".to_string());
+        }
+        if path.starts_with('/') {
+            if path.split('/').any(|segment| segment == "..") {
+                return Err(format!("host path {} must not contain '..'", path));
+            }
+            Ok(VolumeSource::HostPath(path.to_string()))
+        } else if path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            Ok(VolumeSource::Named(path.to_string()))
+        } else {
+            Err(format!("invalid volume name {}", path))
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            VolumeSource::HostPath(path) | VolumeSource::Named(path) => path,
+        }
+    }
+
+    /// etcd key of the volume record, with the source escaped into one segment
+    fn key(&self, node_name: &str) -> String {
+        let escaped = self.as_str().replace('%', "%25").replace('/', "%2F");
+        format!("/volume/{}/{}", node_name, escaped)
+    }
+}
+
+/// Volume sources referenced by a pod spec
+fn pod_volume_sources(spec: &serde_json::Value) -> Result<Vec<VolumeSource>, String> {
+    let mut sources = Vec::new();
+    for volume in spec["volumes"].as_array().into_iter().flatten() {
+        let name = volume["name"].as_str().unwrap_or("");
+        let path = volume["hostPath"]["path"]
+            .as_str()
+            .ok_or_else(|| format!("volume {} has no hostPath", name))?;
+        let source =
+            VolumeSource::from_path(path).map_err(|e| format!("volume {}: {}", name, e))?;
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    Ok(sources)
+}
+
+/// Create a host directory if it does not exist
+///
+/// ### Returns
+/// * `Ok(true)` - the directory was created
+/// * `Ok(false)` - the directory already existed
+/// * `Err(String)` - the path exists but is not a directory, or creation failed
+fn ensure_host_path(path: &str) -> Result<bool, String> {
+    let path = Path::new(path);
+    if path.exists() {
+        if path.is_dir() {
+            return Ok(false);
+        }
+        return Err(format!("host path {} is not a directory", path.display()));
+    }
+    std::fs::create_dir_all(path)
+        .map_err(|e| format!("failed to create host path {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+/// Create a podman volume if it does not exist
+///
+/// ### Returns
+/// * `Ok(true)` - the volume was created
+/// * `Ok(false)` - the volume already existed
+async fn ensure_named_volume(name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let inspect_path = format!("{}/volumes/{}/json", PODMAN_API_VERSION, name);
+    let inspect: serde_json::Value = serde_json::from_slice(&get(&inspect_path).await?)?;
+    if inspect["Name"].as_str() == Some(name) {
+        return Ok(false);
+    }
+
+    let create_path = format!("{}/volumes/create", PODMAN_API_VERSION);
+    let body = json!({ "Name": name });
+    let created: serde_json::Value =
+        serde_json::from_slice(&post(&create_path, Body::from(body.to_string())).await?)?;
+    if created["Name"].as_str() != Some(name) {
+        return Err(format!("failed to create volume {}: {}", name, created).into());
+    }
+    Ok(true)
+}
+
+/// Provision every volume of a pod and register the model as their user
+pub async fn provision(
+    model_name: &str,
+    spec: &serde_json::Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let node_name = crate::config::Config::get().get_node_name();
+    for source in pod_volume_sources(spec)? {
+        let created = match &source {
+            VolumeSource::HostPath(path) => ensure_host_path(path)?,
+            VolumeSource::Named(name) => ensure_named_volume(name).await?,
+        };
+        if created {
+            println!("Provisioned volume {}", source.as_str());
+        }
+
+        let key = source.key(&node_name);
+        // Keep the original record so a volume created earlier is still cleaned up
+        if common::etcd::get(&key).await.is_err() {
+            let record = VolumeRecord {
+                source: source.as_str().to_string(),
+                created,
+            };
+            common::etcd::put(&key, &serde_json::to_string(&record)?).await?;
+        }
+        common::etcd::put(&format!("{}/users/{}", key, model_name), model_name).await?;
+    }
+    Ok(())
+}
+
+/// Unregister the model from the volumes of a pod and remove unused ones
+///
+/// Failures are reported but do not stop the release of other volumes.
+pub async fn release(model_name: &str, spec: &serde_json::Value) {
+    let node_name = crate::config::Config::get().get_node_name();
+    let sources = match pod_volume_sources(spec) {
+        Ok(sources) => sources,
+        Err(e) => {
+            println!("Warning: Failed to read volumes of {}: {}", model_name, e);
+            return;
+        }
+    };
+
+    for source in sources {
+        let key = source.key(&node_name);
+        if let Err(e) = common::etcd::delete(&format!("{}/users/{}", key, model_name)).await {
+            println!(
+                "Warning: Failed to release volume {}: {}",
+                source.as_str(),
+                e
+            );
+            continue;
+        }
+        // Keep the volume unless it is known to be unused
+        match common::etcd::get_all_with_prefix(&format!("{}/users/", key)).await {
+            Ok(users) if users.is_empty() => {}
+            _ => continue,
+        }
+
+        let created = common::etcd::get(&key)
+            .await
+            .ok()
+            .and_then(|value| serde_json::from_str::<VolumeRecord>(&value).ok())
+            .is_some_and(|record| record.created);
+        if created {
+            if let Err(e) = remove(&source).await {
+                println!(
+                    "Warning: Failed to remove volume {}: {}",
+                    source.as_str(),
+                    e
+                );
+                continue;
+            }
+            println!("Removed unused volume {}", source.as_str());
+        }
+        let _ = common::etcd::delete(&key).await;
+    }
+}
+
+/// Remove a volume that is no longer used
+///
+/// Host directories are only removed when empty so that no data is lost.
+async fn remove(source: &VolumeSource) -> Result<(), Box<dyn std::error::Error>> {
+    match source {
+        VolumeSource::HostPath(path) => match std::fs::remove_dir(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        },
+        VolumeSource::Named(name) => {
+            let path = format!("{}/volumes/{}", PODMAN_API_VERSION, name);
+            delete(&path).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_source_from_path() {
+        assert_eq!(
+            VolumeSource::from_path("/var/lib/app"),
+            Ok(VolumeSource::HostPath("/var/lib/app".to_string()))
+        );
+        assert_eq!(
+            VolumeSource::from_path("app-data"),
+            Ok(VolumeSource::Named("app-data".to_string()))
+        );
+        assert!(VolumeSource::from_path("").is_err());
+        assert!(VolumeSource::from_path("/var/../etc").is_err());
+        assert!(VolumeSource::from_path("data/sub").is_err());
+    }
+
+    #[test]
+    fn test_volume_key_escapes_path() {
+        let source = VolumeSource::HostPath("/var/lib/app".to_string());
+        assert_eq!(source.key("node1"), "/volume/node1/%2Fvar%2Flib%2Fapp");
+        let named = VolumeSource::Named("app-data".to_string());
+        assert_eq!(named.key("node1"), "/volume/node1/app-data");
+    }
+
+    #[test]
+    fn test_pod_volume_sources() {
+        let spec = json!({
+            "volumes": [
+                { "name": "a", "hostPath": { "path": "/tmp/a" } },
+                { "name": "b", "hostPath": { "path": "/tmp/a" } },
+                { "name": "c", "hostPath": { "path": "cache" } }
+            ]
+        });
+        assert_eq!(
+            pod_volume_sources(&spec).unwrap(),
+            vec![
+                VolumeSource::HostPath("/tmp/a".to_string()),
+                VolumeSource::Named("cache".to_string())
+            ]
+        );
+        assert!(pod_volume_sources(&json!({})).unwrap().is_empty());
+        assert!(pod_volume_sources(&json!({ "volumes": [{ "name": "x" }] })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_host_path_provision_and_remove() {
+        let dir = std::env::temp_dir().join(format!("nodeagent-volume-{}", std::process::id()));
+        let path = dir.to_str().unwrap().to_string();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(ensure_host_path(&path), Ok(true));
+        assert_eq!(ensure_host_path(&path), Ok(false));
+
+        let file = dir.join("file");
+        std::fs::write(&file, "data").unwrap();
+        assert!(ensure_host_path(file.to_str().unwrap()).is_err());
+
+        // A directory with data is kept
+        let source = VolumeSource::HostPath(path.clone());
+        assert!(remove(&source).await.is_err());
+        std::fs::remove_file(&file).unwrap();
+        assert!(remove(&source).await.is_ok());
+        assert!(!dir.exists());
+        assert!(remove(&source).await.is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Termination of the containers stopped by this node
+//!
+//! A container is stopped with SIGTERM and given the
+//! `terminationGracePeriodSeconds` of its Pod to exit, 30 seconds when not
+//! set, before it is killed with SIGKILL. The container is removed right
+//! after, so its last state is added once to the next container list with
+//! "Termination" set to "Graceful" or "Forced", for the StateManager to tell
+//! a workload that shut down cleanly from one that had to be killed.
+
+use crate::resource::ContainerInspect;
+use common::monitoringserver::ContainerInfo;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::time::Duration;
+
+/// Interval between checks whether a container has exited after SIGTERM
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How a stopped container ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    /// The container exited within its grace period
+    Graceful,
+    /// The container was killed when its grace period ran out
+    Forced,
+}
+
+impl Termination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Termination::Graceful => "Graceful",
+            Termination::Forced => "Forced",
+        }
+    }
+}
+
+static TERMINATED: OnceLock<Mutex<Vec<ContainerInfo>>> = OnceLock::new();
+
+/// Last states of the stopped containers not reported yet
+fn terminated() -> &'static Mutex<Vec<ContainerInfo>> {
+    TERMINATED.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Last state of a stopped container, as reported by its inspection
+pub fn last_state(inspect: ContainerInspect, termination: Termination) -> ContainerInfo {
+    let mut state = HashMap::new();
+    state.insert("Status".to_string(), inspect.State.Status);
+    state.insert("Running".to_string(), inspect.State.Running.to_string());
+    state.insert("OOMKilled".to_string(), inspect.State.OOMKilled.to_string());
+    state.insert("ExitCode".to_string(), inspect.State.ExitCode.to_string());
+    state.insert("FinishedAt".to_string(), inspect.State.FinishedAt);
+    state.insert("Termination".to_string(), termination.as_str().to_string());
+
+    let mut annotation = inspect.Config.Annotations.unwrap_or_default();
+    for (key, value) in inspect.Config.Labels.unwrap_or_default() {
+        annotation.entry(key).or_insert(value);
+    }
+    ContainerInfo {
+        id: inspect.Id,
+        names: vec![inspect.Name],
+        image: inspect.Config.Image,
+        state,
+        config: HashMap::new(),
+        annotation,
+        stats: HashMap::new(),
+    }
+}
+
+/// Keep the last state of a stopped container for the next container list
+pub fn record(container: ContainerInfo) {
+    terminated()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(container);
+}
+
+/// Add the last states of the containers stopped since the previous list
+///
+/// A container still listed, e.g. because its removal failed, is reported
+/// as listed with its termination added to its state.
+pub fn report(containers: &mut Vec<ContainerInfo>) {
+    let stopped: Vec<ContainerInfo> = terminated()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+        .collect();
+    for last in stopped {
+        match containers.iter_mut().find(|c| c.id == last.id) {
+            Some(listed) => {
+                if let Some(termination) = last.state.get("Termination") {
+                    listed
+                        .state
+                        .insert("Termination".to_string(), termination.clone());
+                }
+            }
+            None => containers.push(last),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(id: &str, status: &str) -> ContainerInfo {
+        ContainerInfo {
+            id: id.to_string(),
+            names: vec![format!("pod-{}_c", id)],
+            image: "image-1".to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::new(),
+            stats: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_stopped_containers_are_reported_once() {
+        let mut forced = container("term-forced", "exited");
+        forced.state.insert(
+            "Termination".to_string(),
+            Termination::Forced.as_str().to_string(),
+        );
+        let mut graceful = container("term-graceful", "exited");
+        graceful.state.insert(
+            "Termination".to_string(),
+            Termination::Graceful.as_str().to_string(),
+        );
+        record(forced);
+        record(graceful);
+
+        let mut list = vec![container("term-graceful", "exited")];
+        report(&mut list);
+        let termination = |list: &[ContainerInfo], id: &str| {
+            list.iter()
+                .find(|c| c.id == id)
+                .and_then(|c| c.state.get("Termination").cloned())
+        };
+        assert_eq!(termination(&list, "term-forced").as_deref(), Some("Forced"));
+        assert_eq!(
+            termination(&list, "term-graceful").as_deref(),
+            Some("Graceful")
+        );
+        assert_eq!(list.len(), 2);
+
+        let mut next = Vec::new();
+        report(&mut next);
+        assert!(next.iter().all(|c| !c.id.starts_with("term-")));
+    }
+}
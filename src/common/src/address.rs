@@ -0,0 +1,405 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Addresses of the servers the components open and connect to
+//!
+//! Every server is a [`Service`] with a default port. The `addresses`
+//! section of settings.yaml changes where they are:
+//!
+//! ```yaml
+//! addresses:
+//!   bind_ip: 0.0.0.0
+//!   ports:
+//!     statemanager: 48006
+//!   hosts:
+//!     pharos: 192.168.10.2
+//! ```
+//!
+//! Servers are opened on `bind_ip` and reached at `host.ip`, external
+//! services at their entry of `hosts`, both `host.ip` by default. Servers
+//! of the NodeAgent run on every node and are reached at the IP of the node.
+//!
+//! Components call [`validate_or_exit`] before they open their servers, so
+//! that settings under which two services share a host and port, or name an
+//! unknown service, stop the component at once instead of failing a bind.
+//!
+//! Pharos used to be reached at port 47006, the port of the StateManager,
+//! and defaults to 47008 so that both fit on the master node. A Pharos still
+//! listening on 47006 is reached with `ports: { pharos: 47006 }` and, as the
+//! port is taken on the master node, its own entry of `hosts`.
+
+use crate::setting::Settings;
+use std::net::IpAddr;
+
+/// A server opened by a component or an external service they connect to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Service {
+    ActionController,
+    ActionControllerHealth,
+    ApiServerRest,
+    ApiServerGrpc,
+    FilterGateway,
+    FilterGatewayHealth,
+    /// REST signal injector of test builds
+    FilterGatewayInjector,
+    MonitoringServer,
+    MonitoringServerHealth,
+    NodeAgent,
+    NodeAgentHealth,
+    PolicyManager,
+    PolicyManagerHealth,
+    StateManager,
+    StateManagerHealth,
+    RocksDbService,
+    Timpani,
+    Pharos,
+}
+
+impl Service {
+    pub const ALL: [Service; 18] = [
+        Service::ActionController,
+        Service::ActionControllerHealth,
+        Service::ApiServerRest,
+        Service::ApiServerGrpc,
+        Service::FilterGateway,
+        Service::FilterGatewayHealth,
+        Service::FilterGatewayInjector,
+        Service::MonitoringServer,
+        Service::MonitoringServerHealth,
+        Service::NodeAgent,
+        Service::NodeAgentHealth,
+        Service::PolicyManager,
+        Service::PolicyManagerHealth,
+        Service::StateManager,
+        Service::StateManagerHealth,
+        Service::RocksDbService,
+        Service::Timpani,
+        Service::Pharos,
+    ];
+
+    /// Name of the service in the `addresses` settings
+    pub fn name(&self) -> &'static str {
+        match self {
+            Service::ActionController => "actioncontroller",
+            Service::ActionControllerHealth => "actioncontroller-health",
+            Service::ApiServerRest => "apiserver-rest",
+            Service::ApiServerGrpc => "apiserver-grpc",
+            Service::FilterGateway => "filtergateway",
+            Service::FilterGatewayHealth => "filtergateway-health",
+            Service::FilterGatewayInjector => "filtergateway-injector",
+            Service::MonitoringServer => "monitoringserver",
+            Service::MonitoringServerHealth => "monitoringserver-health",
+            Service::NodeAgent => "nodeagent",
+            Service::NodeAgentHealth => "nodeagent-health",
+            Service::PolicyManager => "policymanager",
+            Service::PolicyManagerHealth => "policymanager-health",
+            Service::StateManager => "statemanager",
+            Service::StateManagerHealth => "statemanager-health",
+            Service::RocksDbService => "rocksdbservice",
+            Service::Timpani => "timpani",
+            Service::Pharos => "pharos",
+        }
+    }
+
+    /// Service of a name in the `addresses` settings
+    pub fn from_name(name: &str) -> Option<Service> {
+        Service::ALL
+            .into_iter()
+            .find(|service| service.name() == name)
+    }
+
+    /// Port of the service when the settings do not list one
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Service::ActionController => 47001,
+            Service::ActionControllerHealth => 47101,
+            Service::ApiServerRest => 47099,
+            Service::ApiServerGrpc => 47098,
+            Service::FilterGateway => 47002,
+            Service::FilterGatewayHealth => 47102,
+            Service::FilterGatewayInjector => 47202,
+            Service::MonitoringServer => 47003,
+            Service::MonitoringServerHealth => 47103,
+            Service::NodeAgent => 47004,
+            Service::NodeAgentHealth => 47104,
+            Service::PolicyManager => 47005,
+            Service::PolicyManagerHealth => 47105,
+            Service::StateManager => 47006,
+            Service::StateManagerHealth => 47106,
+            Service::RocksDbService => 47007,
+            Service::Timpani => 50052,
+            // Was 47006, which the StateManager listens on
+            Service::Pharos => 47008,
+        }
+    }
+
+    /// Whether clients reach the service through [`crate::tls::scheme`]
+    fn tls(&self) -> bool {
+        matches!(
+            self,
+            Service::ActionController
+                | Service::ApiServerGrpc
+                | Service::NodeAgent
+                | Service::StateManager
+        )
+    }
+
+    /// Whether the service is run outside of Piccolo
+    pub fn external(&self) -> bool {
+        matches!(self, Service::Timpani | Service::Pharos)
+    }
+
+    fn port_in(&self, settings: &Settings) -> u16 {
+        settings
+            .addresses
+            .ports
+            .get(self.name())
+            .copied()
+            .unwrap_or_else(|| self.default_port())
+    }
+
+    /// Host the service is reached at, for services not run per node
+    fn host_in<'a>(&self, settings: &'a Settings) -> &'a str {
+        if self.external() {
+            if let Some(host) = settings.addresses.hosts.get(self.name()) {
+                return host;
+            }
+        }
+        &settings.host.ip
+    }
+
+    fn url(&self, host: &str, port: u16) -> String {
+        let scheme = if self.tls() {
+            crate::tls::scheme()
+        } else {
+            "http"
+        };
+        format!("{scheme}://{host}:{port}")
+    }
+}
+
+fn bind_ip(settings: &Settings) -> &str {
+    if settings.addresses.bind_ip.is_empty() {
+        &settings.host.ip
+    } else {
+        &settings.addresses.bind_ip
+    }
+}
+
+/// Port of a service
+pub fn port(service: Service) -> u16 {
+    service.port_in(crate::setting::get_config())
+}
+
+/// `ip:port` address a component opens the server of a service on
+pub fn open_server(service: Service) -> String {
+    let settings = crate::setting::get_config();
+    format!("{}:{}", bind_ip(settings), service.port_in(settings))
+}
+
+/// `ip:port` address the server of a service opens on a node
+pub fn open_server_at(service: Service, node_ip: &str) -> String {
+    format!("{}:{}", node_ip, port(service))
+}
+
+/// URL clients reach a service at
+pub fn connect_server(service: Service) -> String {
+    let settings = crate::setting::get_config();
+    service.url(service.host_in(settings), service.port_in(settings))
+}
+
+/// URL clients reach the server of a service on a node at
+pub fn connect_server_at(service: Service, node_ip: &str) -> String {
+    service.url(node_ip, port(service))
+}
+
+/// Check the addresses of the current settings
+///
+/// ### Returns
+/// * `Err(String)` - the first unknown service, invalid address or pair of
+///   services sharing a host and port
+pub fn validate() -> Result<(), String> {
+    validate_in(crate::setting::get_config())
+}
+
+/// Check the addresses of the current settings, stopping the component with
+/// exit code 1 when they are invalid
+///
+/// The error goes to stderr as well as to logd, since the logger of the
+/// component may not be up yet and the log service validates too.
+pub fn validate_or_exit(component: &str) {
+    if let Err(e) = validate() {
+        eprintln!("{component}: invalid server addresses: {e}");
+        crate::logd!(6, "{component}: invalid server addresses: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// A host and port a service is opened on
+struct Endpoint<'a> {
+    service: Service,
+    host: &'a str,
+    port: u16,
+    /// Opened on this host, where `0.0.0.0` takes every IP of the host
+    local: bool,
+}
+
+impl Endpoint<'_> {
+    fn conflicts_with(&self, other: &Endpoint) -> bool {
+        self.port == other.port
+            && (self.host == other.host
+                || (self.local
+                    && other.local
+                    && (unspecified(self.host) || unspecified(other.host))))
+    }
+}
+
+fn unspecified(host: &str) -> bool {
+    host.parse::<IpAddr>().is_ok_and(|ip| ip.is_unspecified())
+}
+
+fn validate_in(settings: &Settings) -> Result<(), String> {
+    let addresses = &settings.addresses;
+    for name in addresses.ports.keys().chain(addresses.hosts.keys()) {
+        if Service::from_name(name).is_none() {
+            return Err(format!("unknown service '{}' in addresses", name));
+        }
+    }
+    if let Some(name) = addresses
+        .hosts
+        .keys()
+        .find(|name| Service::from_name(name).is_some_and(|service| !service.external()))
+    {
+        return Err(format!(
+            "addresses.hosts.{} is not an external service",
+            name
+        ));
+    }
+    if !addresses.bind_ip.is_empty() && addresses.bind_ip.parse::<IpAddr>().is_err() {
+        return Err(format!("invalid addresses.bind_ip '{}'", addresses.bind_ip));
+    }
+
+    let bind_ip = bind_ip(settings);
+    let mut endpoints: Vec<Endpoint> = Vec::new();
+    for service in Service::ALL {
+        let port = service.port_in(settings);
+        if port == 0 {
+            return Err(format!("addresses.ports.{} is 0", service.name()));
+        }
+        let host = if service.external() {
+            service.host_in(settings)
+        } else {
+            bind_ip
+        };
+        let local = !service.external() || host == settings.host.ip || host == bind_ip;
+        let endpoint = Endpoint {
+            service,
+            host,
+            port,
+            local,
+        };
+        if let Some(other) = endpoints
+            .iter()
+            .find(|other| other.conflicts_with(&endpoint))
+        {
+            return Err(format!(
+                "{} and {} are both at {}:{}",
+                other.service.name(),
+                service.name(),
+                other.host,
+                port
+            ));
+        }
+        endpoints.push(endpoint);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(yaml: &str) -> Settings {
+        let base = "host:\n  name: HPC\n  ip: 192.168.10.1\n  type: bluechi\n  role: master\n";
+        config::Config::builder()
+            .add_source(config::File::from_str(
+                &format!("{base}{yaml}"),
+                config::FileFormat::Yaml,
+            ))
+            .build()
+            .and_then(|source| source.try_deserialize())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_service_names() {
+        for service in Service::ALL {
+            assert_eq!(Service::from_name(service.name()), Some(service));
+        }
+        assert_eq!(Service::from_name("bluechi"), None);
+    }
+
+    #[test]
+    fn test_default_addresses_are_unique() {
+        let settings = settings("");
+        assert_eq!(validate_in(&settings), Ok(()));
+        assert_eq!(
+            format!(
+                "{}:{}",
+                bind_ip(&settings),
+                Service::StateManager.port_in(&settings)
+            ),
+            "192.168.10.1:47006"
+        );
+        assert_eq!(Service::Pharos.host_in(&settings), "192.168.10.1");
+    }
+
+    #[test]
+    fn test_overridden_addresses() {
+        let settings = settings(
+            "addresses:\n  bind_ip: 0.0.0.0\n  ports:\n    pharos: 47106\n  hosts:\n    pharos: 192.168.10.2\n",
+        );
+        // Pharos shares the port of a health server, on another host
+        assert_eq!(validate_in(&settings), Ok(()));
+        assert_eq!(bind_ip(&settings), "0.0.0.0");
+        assert_eq!(Service::Pharos.host_in(&settings), "192.168.10.2");
+        assert_eq!(Service::Pharos.port_in(&settings), 47106);
+        assert_eq!(Service::StateManager.host_in(&settings), "192.168.10.1");
+    }
+
+    #[test]
+    fn test_conflicting_addresses_are_rejected() {
+        assert_eq!(
+            validate_in(&settings(
+                "addresses:\n  ports:\n    policymanager: 47001\n"
+            )),
+            Err("actioncontroller and policymanager are both at 192.168.10.1:47001".to_string())
+        );
+        // Opened on every IP, an external service of the host conflicts too
+        assert_eq!(
+            validate_in(&settings(
+                "addresses:\n  bind_ip: 0.0.0.0\n  ports:\n    timpani: 47003\n"
+            )),
+            Err("monitoringserver and timpani are both at 0.0.0.0:47003".to_string())
+        );
+        assert_eq!(
+            validate_in(&settings("addresses:\n  ports:\n    scheduler: 1\n")),
+            Err("unknown service 'scheduler' in addresses".to_string())
+        );
+        assert!(validate_in(&settings(
+            "addresses:\n  hosts:\n    statemanager: 192.168.10.2\n"
+        ))
+        .unwrap_err()
+        .contains("not an external service"));
+        assert!(validate_in(&settings("addresses:\n  bind_ip: any\n"))
+            .unwrap_err()
+            .contains("bind_ip"));
+        assert!(
+            validate_in(&settings("addresses:\n  ports:\n    nodeagent: 0\n"))
+                .unwrap_err()
+                .contains("is 0")
+        );
+    }
+}
@@ -0,0 +1,462 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Authentication and authorization of API callers
+//!
+//! When the `auth` section of settings.yaml is enabled, every caller of the
+//! StateManager and ApiServer APIs must identify itself, either with a static
+//! token sent as `authorization: Bearer <token>` or with the SPIFFE-style
+//! identity of its TLS client certificate. Both map to a [`Principal`], a
+//! component name with a [`Role`].
+//!
+//! [`authenticate`] is installed as tonic interceptor and resolves the
+//! principal of each request. Handlers then check the role their RPC needs
+//! with [`authorize`], and the namespace of the resources it acts on with
+//! [`check_namespace`]. Every denied attempt is written to the audit log.
+//!
+//! Clients attach the token of their own component with [`request`]. Tests
+//! run handlers under [`scope`] to check them with auth enabled.
+
+use crate::setting::{AuthSettings, PrincipalSettings};
+use tonic::metadata::MetadataValue;
+use tonic::{Request, Status};
+
+/// Access level of a caller, each role includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Queries only
+    ReadOnly,
+    /// Queries and state changes
+    Operator,
+    /// Everything, including transitions reserved to specific components
+    Admin,
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "read-only" | "readonly" => Ok(Role::ReadOnly),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role '{}'", other)),
+        }
+    }
+}
+
+/// Authenticated caller of an API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Component name, e.g. "actioncontroller"
+    pub name: String,
+    pub role: Role,
+    /// Namespaces the caller may act on, all of them when empty
+    pub namespaces: Vec<String>,
+}
+
+impl Principal {
+    /// Whether the caller may act on resources of a namespace
+    ///
+    /// Admins may act on every namespace.
+    pub fn can_access(&self, namespace: &str) -> bool {
+        self.role == Role::Admin
+            || self.namespaces.is_empty()
+            || self.namespaces.iter().any(|allowed| allowed == namespace)
+    }
+}
+
+impl From<&PrincipalSettings> for Principal {
+    /// An unknown role grants read-only access
+    fn from(settings: &PrincipalSettings) -> Self {
+        Self {
+            name: settings.name.clone(),
+            role: settings.role.parse().unwrap_or(Role::ReadOnly),
+            namespaces: settings.namespaces.clone(),
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Auth settings of the current task, see [`scope`]
+    static SCOPED: AuthSettings;
+}
+
+/// Run `f` with the auth settings of the current task
+fn with_settings<R>(f: impl FnOnce(&AuthSettings) -> R) -> R {
+    let mut f = Some(f);
+    SCOPED
+        .try_with(|settings| (f.take().unwrap())(settings))
+        .unwrap_or_else(|_| (f.take().unwrap())(&crate::setting::get_config().auth))
+}
+
+/// Run a future with `settings` in place of the auth settings of
+/// settings.yaml, e.g. for tests of the checks of a handler
+pub async fn scope<F: std::future::Future>(settings: AuthSettings, f: F) -> F::Output {
+    SCOPED.scope(settings, f).await
+}
+
+/// Principal of a static token
+pub fn principal_for_token(settings: &AuthSettings, token: &str) -> Option<Principal> {
+    settings.tokens.get(token).map(Principal::from)
+}
+
+/// Principal of a DER client certificate carrying a configured identity
+fn principal_for_certificate(settings: &AuthSettings, der: &[u8]) -> Option<Principal> {
+    settings
+        .identities
+        .iter()
+        .find(|(identity, _)| crate::tls::has_uri_san(der, identity))
+        .map(|(_, principal)| Principal::from(principal))
+}
+
+/// Token of an `authorization: Bearer <token>` header value
+pub fn bearer_token(value: &str) -> Option<&str> {
+    value
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Resolve the principal of a gRPC request
+fn resolve<T>(settings: &AuthSettings, request: &Request<T>) -> Option<Principal> {
+    if let Some(token) = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token)
+    {
+        return principal_for_token(settings, token);
+    }
+    request
+        .peer_certs()
+        .and_then(|certs| certs.first().map(|leaf| leaf.to_vec()))
+        .and_then(|leaf| principal_for_certificate(settings, &leaf))
+}
+
+/// Interceptor attaching the principal of the caller to the request
+///
+/// ### Returns
+/// * `Err(Status::unauthenticated)` - authentication is enabled and the
+///   request carries neither a known token nor a known identity
+#[allow(clippy::result_large_err)]
+pub fn authenticate(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let Some(principal) =
+        with_settings(|settings| settings.enabled.then(|| resolve(settings, &request)))
+    else {
+        return Ok(request);
+    };
+    match principal {
+        Some(principal) => {
+            request.extensions_mut().insert(principal);
+            Ok(request)
+        }
+        None => {
+            let peer = request
+                .remote_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|| "unknown peer".to_string());
+            audit_denied("authenticate", &peer, "no valid token or identity");
+            Err(Status::unauthenticated("Missing or invalid credentials"))
+        }
+    }
+}
+
+/// Check that the caller of an RPC has at least the given role
+///
+/// ### Parameters
+/// * `request` - request passed through [`authenticate`]
+/// * `rpc` - name of the RPC for the audit log
+/// * `role` - lowest role allowed to call it
+/// ### Returns
+/// * `Ok(None)` - authentication is disabled
+/// * `Ok(Some(Principal))` - the caller is allowed
+/// * `Err(Status::permission_denied)` - the caller's role is too low
+#[allow(clippy::result_large_err)]
+pub fn authorize<T>(
+    request: &Request<T>,
+    rpc: &str,
+    role: Role,
+) -> Result<Option<Principal>, Status> {
+    if !with_settings(|settings| settings.enabled) {
+        return Ok(None);
+    }
+    check_role(request.extensions().get::<Principal>(), rpc, role).map(Some)
+}
+
+/// Check the role of a resolved principal, `None` for an anonymous caller
+#[allow(clippy::result_large_err)]
+pub fn check_role(
+    principal: Option<&Principal>,
+    rpc: &str,
+    role: Role,
+) -> Result<Principal, Status> {
+    match principal {
+        Some(principal) if principal.role >= role => Ok(principal.clone()),
+        Some(principal) => {
+            audit_denied(rpc, &principal.name, &format!("requires {:?}", role));
+            Err(Status::permission_denied(format!(
+                "{} requires the {:?} role",
+                rpc, role
+            )))
+        }
+        None => {
+            audit_denied(rpc, "anonymous", "not authenticated");
+            Err(Status::unauthenticated("Missing or invalid credentials"))
+        }
+    }
+}
+
+/// Check that an RPC is called by one of the named components
+///
+/// Admins may always call it. Passes when authentication is disabled.
+#[allow(clippy::result_large_err)]
+pub fn require_caller(
+    principal: Option<&Principal>,
+    rpc: &str,
+    allowed: &[&str],
+) -> Result<(), Status> {
+    match principal {
+        None => Ok(()),
+        Some(p) if p.role == Role::Admin || allowed.contains(&p.name.as_str()) => Ok(()),
+        Some(p) => {
+            audit_denied(rpc, &p.name, &format!("only {:?} may call it", allowed));
+            Err(Status::permission_denied(format!(
+                "{} is restricted to {}",
+                rpc,
+                allowed.join(", ")
+            )))
+        }
+    }
+}
+
+/// Check that the caller of an RPC may act on a namespace
+///
+/// Passes when authentication is disabled.
+#[allow(clippy::result_large_err)]
+pub fn check_namespace(
+    principal: Option<&Principal>,
+    rpc: &str,
+    namespace: &str,
+) -> Result<(), Status> {
+    match principal {
+        Some(p) if !p.can_access(namespace) => {
+            audit_denied(
+                rpc,
+                &p.name,
+                &format!("no access to namespace {}", namespace),
+            );
+            Err(Status::permission_denied(format!(
+                "{} is not allowed in namespace {}",
+                p.name, namespace
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Record a denied request in the audit log
+pub fn audit_denied(rpc: &str, caller: &str, reason: &str) {
+    crate::logd!(5, "[AUDIT] denied {} for {}: {}", rpc, caller, reason);
+}
+
+/// Record an allowed request that acts on a workload in the audit log
+pub fn audit_granted(rpc: &str, caller: &str, detail: &str) {
+    crate::logd!(3, "[AUDIT] granted {} for {}: {}", rpc, caller, detail);
+}
+
+/// gRPC request carrying the token of this component
+///
+/// Without a configured token the request is sent without credentials, as
+/// with `Request::new`.
+pub fn request<T>(message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    let token = with_settings(|settings| settings.token.clone());
+    if !token.is_empty() {
+        match MetadataValue::try_from(format!("Bearer {}", token)) {
+            Ok(value) => {
+                request.metadata_mut().insert("authorization", value);
+            }
+            Err(_) => crate::logd!(5, "Configured auth token is not a valid header value"),
+        }
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn principal(name: &str, role: &str) -> PrincipalSettings {
+        PrincipalSettings {
+            name: name.to_string(),
+            role: role.to_string(),
+            namespaces: Vec::new(),
+        }
+    }
+
+    /// Self-signed DER certificate with URI SANs, and optionally the
+    /// encoding of a URI SAN in the subject common name
+    fn certificate(uris: &[&str], common_name: Option<&str>) -> Vec<u8> {
+        let mut params = rcgen::CertificateParams::default();
+        params.subject_alt_names = uris
+            .iter()
+            .map(|uri| rcgen::SanType::URI(rcgen::Ia5String::try_from(*uri).unwrap()))
+            .collect();
+        if let Some(uri) = common_name {
+            // U+0086 is encoded as C2 86, followed by the length and the URI
+            let name = format!("\u{86}{}{}", char::from(uri.len() as u8), uri);
+            params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, rcgen::DnValue::Utf8String(name));
+        }
+        let key = rcgen::KeyPair::generate().unwrap();
+        params.self_signed(&key).unwrap().der().to_vec()
+    }
+
+    fn auth_settings() -> AuthSettings {
+        AuthSettings {
+            enabled: true,
+            token: String::new(),
+            tokens: HashMap::from([
+                (
+                    "ac-token".to_string(),
+                    principal("actioncontroller", "operator"),
+                ),
+                ("viewer".to_string(), principal("dashboard", "read-only")),
+            ]),
+            identities: HashMap::from([(
+                "spiffe://piccolo/apiserver".to_string(),
+                principal("apiserver", "admin"),
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_role_order_and_parse() {
+        assert!(Role::Admin > Role::Operator && Role::Operator > Role::ReadOnly);
+        assert_eq!("read_only".parse(), Ok(Role::ReadOnly));
+        assert_eq!("Operator".parse(), Ok(Role::Operator));
+        assert!("root".parse::<Role>().is_err());
+        assert_eq!(
+            Principal::from(&principal("x", "root")).role,
+            Role::ReadOnly
+        );
+    }
+
+    #[test]
+    fn test_resolve_token_and_identity() {
+        let settings = auth_settings();
+
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer ac-token".parse().unwrap());
+        assert_eq!(
+            resolve(&settings, &request),
+            Some(Principal {
+                name: "actioncontroller".to_string(),
+                role: Role::Operator,
+                namespaces: Vec::new(),
+            })
+        );
+
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer wrong".parse().unwrap());
+        assert_eq!(resolve(&settings, &request), None);
+        assert_eq!(resolve(&settings, &Request::new(())), None);
+
+        let uri = "spiffe://piccolo/apiserver";
+        assert_eq!(
+            principal_for_certificate(&settings, &certificate(&[uri], None)).map(|p| p.role),
+            Some(Role::Admin)
+        );
+        // The identity outside the SubjectAltName grants nothing
+        let der = certificate(&["spiffe://piccolo/nodeagent"], Some(uri));
+        let mut encoded = vec![0x86, uri.len() as u8];
+        encoded.extend_from_slice(uri.as_bytes());
+        assert!(der.windows(encoded.len()).any(|window| window == encoded));
+        assert_eq!(principal_for_certificate(&settings, &der), None);
+    }
+
+    #[tokio::test]
+    async fn test_role_and_caller_checks() {
+        let operator = principal_for_token(&auth_settings(), "ac-token");
+        let viewer = principal_for_token(&auth_settings(), "viewer");
+
+        assert!(check_role(operator.as_ref(), "SendStateChange", Role::Operator).is_ok());
+        let denied = check_role(viewer.as_ref(), "SendStateChange", Role::Operator);
+        assert_eq!(denied.unwrap_err().code(), tonic::Code::PermissionDenied);
+        let anonymous = check_role(None, "GetVehicleMode", Role::ReadOnly);
+        assert_eq!(anonymous.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        assert!(require_caller(operator.as_ref(), "Package", &["actioncontroller"]).is_ok());
+        assert!(require_caller(viewer.as_ref(), "Package", &["actioncontroller"]).is_err());
+        assert!(require_caller(None, "Package", &["actioncontroller"]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_namespace_checks() {
+        let team = Principal {
+            name: "team-a-ci".to_string(),
+            role: Role::Operator,
+            namespaces: vec!["team-a".to_string()],
+        };
+        assert!(check_namespace(Some(&team), "apply", "team-a").is_ok());
+        let denied = check_namespace(Some(&team), "apply", "team-b");
+        assert_eq!(denied.unwrap_err().code(), tonic::Code::PermissionDenied);
+
+        let admin = Principal {
+            role: Role::Admin,
+            ..team.clone()
+        };
+        assert!(check_namespace(Some(&admin), "apply", "team-b").is_ok());
+        let any = Principal {
+            namespaces: Vec::new(),
+            ..team
+        };
+        assert!(check_namespace(Some(&any), "apply", "team-b").is_ok());
+        assert!(check_namespace(None, "apply", "team-b").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_scoped_settings_apply_to_the_task_only() {
+        let denied = scope(auth_settings(), async {
+            authorize(&Request::new(()), "Any", Role::ReadOnly).unwrap_err()
+        })
+        .await;
+        assert_eq!(denied.code(), tonic::Code::Unauthenticated);
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", MetadataValue::from_static("Bearer viewer"));
+        let authenticated = scope(auth_settings(), async { authenticate(request) })
+            .await
+            .unwrap();
+        assert_eq!(
+            authenticated.extensions().get::<Principal>().unwrap().name,
+            "dashboard"
+        );
+        assert_eq!(
+            authorize(&Request::new(()), "Any", Role::Admin).unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disabled_auth_passes_requests() {
+        assert!(authenticate(Request::new(())).is_ok());
+        assert_eq!(
+            authorize(&Request::new(()), "Any", Role::Admin).unwrap(),
+            None
+        );
+        assert!(request(()).metadata().get("authorization").is_none());
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+    }
+}
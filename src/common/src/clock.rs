@@ -0,0 +1,156 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Source of the current time
+//!
+//! Components take timestamps and measure durations through the [`Clock`]
+//! installed with [`set`], the [`SystemClock`] unless a test installs a
+//! [`MockClock`] to control time. Timestamps are wall-clock nanoseconds
+//! since the Unix epoch, which can be persisted and replayed, and never go
+//! backwards within a process even when the system time is stepped back.
+//! Durations are measured with the monotonic [`Instant`] of the clock.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Wall and monotonic time
+pub trait Clock: Send + Sync {
+    /// Nanoseconds since the Unix epoch
+    fn now_ns(&self) -> i64;
+    /// Monotonic point in time for measuring durations
+    fn instant(&self) -> Instant;
+}
+
+/// Clock of the operating system
+#[derive(Debug, Default)]
+pub struct SystemClock {
+    /// Last timestamp returned, later ones never go below it
+    last_ns: AtomicI64,
+}
+
+impl Clock for SystemClock {
+    fn now_ns(&self) -> i64 {
+        let system_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as i64;
+        let previous = self.last_ns.fetch_max(system_ns, Ordering::Relaxed);
+        system_ns.max(previous)
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to, for deterministic tests
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    start_ns: i64,
+    /// Nanoseconds advanced since `start`
+    elapsed_ns: AtomicI64,
+}
+
+impl MockClock {
+    /// Clock standing at `start_ns` nanoseconds since the Unix epoch
+    pub fn new(start_ns: i64) -> Self {
+        Self {
+            start: Instant::now(),
+            start_ns,
+            elapsed_ns: AtomicI64::new(0),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        self.elapsed_ns
+            .fetch_add(by.as_nanos() as i64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ns(&self) -> i64 {
+        self.start_ns + self.elapsed_ns.load(Ordering::Relaxed)
+    }
+
+    fn instant(&self) -> Instant {
+        self.start + Duration::from_nanos(self.elapsed_ns.load(Ordering::Relaxed) as u64)
+    }
+}
+
+fn installed() -> &'static RwLock<Arc<dyn Clock>> {
+    static CLOCK: OnceLock<RwLock<Arc<dyn Clock>>> = OnceLock::new();
+    CLOCK.get_or_init(|| RwLock::new(Arc::new(SystemClock::default())))
+}
+
+/// Install the clock of the process, e.g. a [`MockClock`] in a test
+pub fn set(clock: Arc<dyn Clock>) {
+    *installed().write().unwrap_or_else(|e| e.into_inner()) = clock;
+}
+
+/// Go back to the [`SystemClock`]
+pub fn reset() {
+    set(Arc::new(SystemClock::default()));
+}
+
+/// The installed clock
+pub fn get() -> Arc<dyn Clock> {
+    installed()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Current timestamp in nanoseconds since the Unix epoch
+pub fn now_ns() -> i64 {
+    get().now_ns()
+}
+
+/// Current timestamp in milliseconds since the Unix epoch
+pub fn now_ms() -> i64 {
+    now_ns() / 1_000_000
+}
+
+/// Current time as a UTC date, for formatting and calendars
+pub fn now_utc() -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp_nanos(now_ns())
+}
+
+/// Current monotonic point in time
+pub fn instant() -> Instant {
+    get().instant()
+}
+
+/// Time between two timestamps of [`now_ns`], zero if `to` is earlier
+pub fn elapsed(from_ns: i64, to_ns: i64) -> Duration {
+    Duration::from_nanos(to_ns.saturating_sub(from_ns).max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock_never_goes_back() {
+        let clock = SystemClock::default();
+        let future = clock.now_ns() + 3_600_000_000_000;
+        clock.last_ns.store(future, Ordering::Relaxed);
+        assert!(clock.now_ns() >= future);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1_000);
+        let start = clock.instant();
+        assert_eq!(clock.now_ns(), 1_000);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(clock.now_ns(), 5_001_000);
+        assert_eq!(clock.instant() - start, Duration::from_millis(5));
+        assert_eq!(elapsed(1_000, clock.now_ns()), Duration::from_millis(5));
+        assert_eq!(elapsed(clock.now_ns(), 1_000), Duration::ZERO);
+    }
+}
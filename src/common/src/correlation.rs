@@ -0,0 +1,106 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Correlation IDs tracing one scenario activation across components
+//!
+//! The component where an activation starts creates a correlation ID and
+//! sends it along with its requests: in `StateChange`, in the ActionController
+//! requests and in the NodeAgent workload requests, which give it to the
+//! containers as the [`LABEL_CORRELATION`] label. A receiver runs its handling
+//! in [`scope`], so that every `logd!` of the handling and every request it
+//! sends carry the same ID.
+//!
+//! Transition IDs identify a single state change and are still unique per
+//! change; [`transition_id`] creates them in one format for all components.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Label giving containers the correlation ID of the activation that created them
+pub const LABEL_CORRELATION: &str = "io.piccolo.correlation-id";
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+fn unique_suffix() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}", nanos, sequence)
+}
+
+/// New correlation ID
+pub fn new_id() -> String {
+    format!("cid-{}", unique_suffix())
+}
+
+/// The received correlation ID, or a new one if the sender gave none
+pub fn or_new(id: &str) -> String {
+    if id.is_empty() {
+        new_id()
+    } else {
+        id.to_string()
+    }
+}
+
+/// New transition ID of a state change sent by `component`
+pub fn transition_id(component: &str) -> String {
+    format!("{}-{}", component, unique_suffix())
+}
+
+/// Correlation ID of the running task, if it runs in a [`scope`]
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// Correlation ID to send with a request, empty outside a [`scope`]
+pub fn current_or_empty() -> String {
+    current().unwrap_or_default()
+}
+
+/// Run `future` with `id` as the correlation ID of its task
+///
+/// An empty `id` runs it without correlation ID. Tasks spawned by `future` do
+/// not inherit the ID.
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    if id.is_empty() {
+        future.await
+    } else {
+        CURRENT.scope(id, future).await
+    }
+}
+
+//Unit Test Cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_unique() {
+        assert_ne!(new_id(), new_id());
+        assert!(new_id().starts_with("cid-"));
+        let transition = transition_id("apiserver");
+        assert!(transition.starts_with("apiserver-"));
+        assert_ne!(transition, transition_id("apiserver"));
+        assert_eq!(or_new("cid-1"), "cid-1");
+        assert!(or_new("").starts_with("cid-"));
+    }
+
+    #[tokio::test]
+    async fn test_scope_sets_current_id() {
+        assert_eq!(current(), None);
+        assert_eq!(current_or_empty(), "");
+        let inner = scope("cid-test".to_string(), async { current() }).await;
+        assert_eq!(inner.as_deref(), Some("cid-test"));
+        assert_eq!(current(), None);
+        assert_eq!(scope(String::new(), async { current() }).await, None);
+    }
+}
@@ -2,20 +2,253 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
-pub type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>;
 
-// TODO - add custom error message types
-/*
-pub struct Error {
-    msg: Msg,
+//! Errors of the fallible paths shared by the components
+//!
+//! An [`Error`] tells the category of a failure, so that callers can branch
+//! on it, e.g. retry when etcd is unavailable but not when an artifact is
+//! invalid. Every category has the [`ErrorCode`] of the StateManager API and
+//! a gRPC status code, an error crosses gRPC as a [`Status`] and comes back
+//! with its category.
+//!
+//! Errors of parsers and I/O convert with `?`, a message alone is an
+//! internal error. The category of any other failure is given where it is
+//! known, e.g. a failed etcd operation converts with
+//! [`crate::etcd::error`].
+
+use crate::statemanager::ErrorCode;
+use tonic::{Code, Status};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Failure of an operation, by category
+///
+/// The message is shown as is, the category is given by [`Error::code`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum Error {
+    /// The request or artifact is malformed
+    #[error("{0}")]
+    InvalidRequest(String),
+    /// The resource does not exist
+    #[error("{0}")]
+    NotFound(String),
+    /// The resource cannot go to the requested state
+    #[error("{0}")]
+    InvalidStateTransition(String),
+    /// The resource is not in a state allowing the operation
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("{0}")]
+    Timeout(String),
+    /// etcd or another component cannot be reached
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    /// Another component failed the operation
+    #[error("{0}")]
+    DependencyFailed(String),
+    #[error("{0}")]
+    RecoveryFailed(String),
+    #[error("{0}")]
+    Internal(String),
 }
 
-struct Msg {
-    kind: ErrorKind,
-    desc: Box<std::error::Error+Send+Sync>,
+impl Error {
+    /// Code of the category in the StateManager API
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            Error::NotFound(_) => ErrorCode::ResourceNotFound,
+            Error::InvalidStateTransition(_) => ErrorCode::InvalidStateTransition,
+            Error::PreconditionFailed(_) => ErrorCode::PreconditionFailed,
+            Error::Timeout(_) => ErrorCode::Timeout,
+            Error::Unavailable(_) => ErrorCode::ResourceUnavailable,
+            Error::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            Error::DependencyFailed(_) => ErrorCode::DependencyFailed,
+            Error::RecoveryFailed(_) => ErrorCode::RecoveryFailed,
+            Error::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+
+    /// Error of a category of the StateManager API
+    pub fn with_code(code: ErrorCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match code {
+            ErrorCode::InvalidRequest => Error::InvalidRequest(message),
+            ErrorCode::ResourceNotFound => Error::NotFound(message),
+            ErrorCode::InvalidStateTransition => Error::InvalidStateTransition(message),
+            ErrorCode::PreconditionFailed => Error::PreconditionFailed(message),
+            ErrorCode::Timeout => Error::Timeout(message),
+            ErrorCode::ResourceUnavailable => Error::Unavailable(message),
+            ErrorCode::PermissionDenied => Error::PermissionDenied(message),
+            ErrorCode::DependencyFailed => Error::DependencyFailed(message),
+            ErrorCode::RecoveryFailed => Error::RecoveryFailed(message),
+            ErrorCode::Success | ErrorCode::Unspecified | ErrorCode::InternalError => {
+                Error::Internal(message)
+            }
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::InvalidRequest(message)
+            | Error::NotFound(message)
+            | Error::InvalidStateTransition(message)
+            | Error::PreconditionFailed(message)
+            | Error::Timeout(message)
+            | Error::Unavailable(message)
+            | Error::PermissionDenied(message)
+            | Error::DependencyFailed(message)
+            | Error::RecoveryFailed(message)
+            | Error::Internal(message) => message,
+        }
+    }
+
+    /// Whether the operation may succeed when tried again later
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::Timeout(_) | Error::Unavailable(_))
+    }
+
+    /// gRPC code of the category
+    pub fn grpc_code(&self) -> Code {
+        match self {
+            Error::InvalidRequest(_) => Code::InvalidArgument,
+            Error::NotFound(_) => Code::NotFound,
+            Error::InvalidStateTransition(_) | Error::PreconditionFailed(_) => {
+                Code::FailedPrecondition
+            }
+            Error::Timeout(_) => Code::DeadlineExceeded,
+            Error::Unavailable(_) => Code::Unavailable,
+            Error::PermissionDenied(_) => Code::PermissionDenied,
+            Error::DependencyFailed(_) => Code::Aborted,
+            Error::RecoveryFailed(_) | Error::Internal(_) => Code::Internal,
+        }
+    }
 }
 
-pub enum Errorkind {
-    ...
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Internal(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::from(message.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Error::InvalidRequest(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::InvalidRequest(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Error::NotFound(e.to_string()),
+            std::io::ErrorKind::PermissionDenied => Error::PermissionDenied(e.to_string()),
+            std::io::ErrorKind::TimedOut => Error::Timeout(e.to_string()),
+            _ => Error::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<std::net::AddrParseError> for Error {
+    fn from(e: std::net::AddrParseError) -> Self {
+        Error::InvalidRequest(e.to_string())
+    }
+}
+
+impl From<tonic::transport::Error> for Error {
+    fn from(e: tonic::transport::Error) -> Self {
+        Error::Unavailable(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Error::Internal(e.to_string())
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        Error::Internal(e.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for Error {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        Error::Internal(e.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Error::Internal(e.to_string())
+    }
+}
+
+/// The category of a status of a peer
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        let message = status.message().to_string();
+        match status.code() {
+            Code::InvalidArgument => Error::InvalidRequest(message),
+            Code::NotFound => Error::NotFound(message),
+            Code::FailedPrecondition => Error::PreconditionFailed(message),
+            Code::DeadlineExceeded => Error::Timeout(message),
+            Code::Unavailable => Error::Unavailable(message),
+            Code::PermissionDenied | Code::Unauthenticated => Error::PermissionDenied(message),
+            Code::Aborted => Error::DependencyFailed(message),
+            _ => Error::Internal(message),
+        }
+    }
+}
+
+impl From<Error> for Status {
+    fn from(e: Error) -> Self {
+        Status::new(e.grpc_code(), e.message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_are_internal_errors() {
+        let unavailable = Error::from("etcd unavailable: no endpoint answered".to_string());
+        assert_eq!(unavailable.code(), ErrorCode::InternalError);
+        assert!(!unavailable.is_transient());
+        assert_eq!(Error::from("disk full").code(), ErrorCode::InternalError);
+        assert_eq!(Error::from("disk full").to_string(), "disk full");
+
+        let parse = serde_yaml::from_str::<u32>("not a number").unwrap_err();
+        assert_eq!(Error::from(parse).code(), ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn test_category_crosses_grpc() {
+        let sent = Error::NotFound("Scenario/helloworld".to_string());
+        let status = Status::from(sent.clone());
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(Error::from(status), sent);
+
+        let denied = Error::with_code(ErrorCode::PermissionDenied, "no role");
+        assert_eq!(denied, Error::PermissionDenied("no role".to_string()));
+        assert_eq!(
+            Error::with_code(ErrorCode::Unspecified, "?").code(),
+            ErrorCode::InternalError
+        );
+    }
 }
-*/
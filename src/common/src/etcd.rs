@@ -3,308 +3,770 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+//! Client of the RocksDB service that stores the artifacts and states
+//!
+//! The service may run on several endpoints, configured in the `etcd`
+//! section of settings.yaml or as comma separated `ROCKSDB_SERVICE_URL`.
+//! Each operation is sent to the endpoint that answered last and moves on to
+//! the next one when an endpoint is unreachable or does not answer within
+//! `timeout_ms`. When no endpoint answers, the round is repeated with
+//! backoff up to `retry_attempts` times before the operation fails with an
+//! error for which [`is_unavailable`] holds.
+//!
+//! The operations go through the [`KeyValueStore`] trait, so that tests can
+//! run against an [`InMemoryStore`] instead of the service.
+
+use crate::error::Error;
+use crate::grpc::retry::RetryPolicy;
 use crate::logd;
 use crate::rocksdbservice::{
-    rocks_db_service_client::RocksDbServiceClient, BatchPutRequest, DeleteRequest,
-    GetByPrefixRequest, GetRequest, HealthRequest, KeyValue, PutRequest,
+    rocks_db_service_client::RocksDbServiceClient, BatchPutRequest, CompareAndSwapRequest,
+    DeleteRequest, GetByPrefixRequest, GetRequest, HealthRequest, KeyValue, PutRequest,
 };
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::{Code, Status};
 
 lazy_static::lazy_static! {
-    static ref ROCKSDB_SERVICE_URL: String = {
-        std::env::var("ROCKSDB_SERVICE_URL")
-            .unwrap_or_else(|_| "http://localhost:47007".to_string())
+    static ref ENDPOINTS: Vec<String> = {
+        let configured = match std::env::var("ROCKSDB_SERVICE_URL") {
+            Ok(urls) => urls.split(',').map(|url| url.trim().to_string()).collect(),
+            Err(_) => crate::setting::get_config().etcd.endpoints.clone(),
+        };
+        let endpoints: Vec<String> = configured.into_iter().filter(|url| !url.is_empty()).collect();
+        if endpoints.is_empty() {
+            vec!["http://localhost:47007".to_string()]
+        } else {
+            endpoints
+        }
     };
 }
 
+/// Index of the endpoint that answered last
+static PREFERRED: AtomicUsize = AtomicUsize::new(0);
+
 const DEV: bool = false;
 
-/// Put a key-value pair into the gRPC RocksDB service
-pub async fn put(key: &str, value: &str) -> Result<(), String> {
-    if DEV {
-        logd!(
-            1,
-            "[RocksDB] Putting key '{}' to service: {}",
-            key,
-            *ROCKSDB_SERVICE_URL
-        );
+/// Prefix of the error of an operation no endpoint answered
+const UNAVAILABLE: &str = "etcd unavailable";
+
+/// Error of a get for a key that is not stored
+pub const NOT_FOUND: &str = "Key not found";
+
+/// Whether an operation failed because no endpoint could be reached, as
+/// opposed to an error returned by the service
+pub fn is_unavailable(error: &str) -> bool {
+    error.starts_with(UNAVAILABLE)
+}
+
+/// Error of a failed operation, for callers returning [`crate::Result`]
+///
+/// An unreachable service is [`Error::Unavailable`] and a key that is not
+/// stored [`Error::NotFound`], so that callers can retry the former.
+pub fn error(message: String) -> Error {
+    if is_unavailable(&message) {
+        Error::Unavailable(message)
+    } else if message == NOT_FOUND {
+        Error::NotFound(message)
+    } else {
+        Error::Internal(message)
     }
+}
 
-    match RocksDbServiceClient::connect(ROCKSDB_SERVICE_URL.clone()).await {
-        Ok(mut client) => {
-            let request = tonic::Request::new(PutRequest {
-                key: key.to_string(),
-                value: value.to_string(),
-            });
+/// Whether the endpoint failed rather than the operation
+fn is_endpoint_failure(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable | Code::Unknown | Code::DeadlineExceeded
+    )
+}
+
+/// Run an operation on the endpoints, failing over and retrying as configured
+async fn call<T, F, Fut>(operation: &str, request: F) -> Result<T, String>
+where
+    F: FnMut(RocksDbServiceClient<Channel>) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let settings = &crate::setting::get_config().etcd;
+    let policy = RetryPolicy {
+        attempts: settings.retry_attempts.max(1),
+        initial_backoff: Duration::from_millis(settings.initial_backoff_ms),
+        max_backoff: Duration::from_millis(settings.max_backoff_ms),
+        deadline: Duration::from_millis(settings.timeout_ms),
+    };
+    call_on(&ENDPOINTS, &PREFERRED, &policy, operation, request).await
+}
 
-            match client.put(request).await {
+async fn call_on<T, F, Fut>(
+    endpoints: &[String],
+    preferred: &AtomicUsize,
+    policy: &RetryPolicy,
+    operation: &str,
+    mut request: F,
+) -> Result<T, String>
+where
+    F: FnMut(RocksDbServiceClient<Channel>) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let mut last_error = String::new();
+    for attempt in 1..=policy.attempts {
+        let first = preferred.load(Ordering::Relaxed) % endpoints.len();
+        for offset in 0..endpoints.len() {
+            let index = (first + offset) % endpoints.len();
+            let addr = &endpoints[index];
+            let result = match crate::grpc::channel(addr).await {
+                Ok(channel) => {
+                    let client = RocksDbServiceClient::new(channel);
+                    match tokio::time::timeout(policy.deadline, request(client)).await {
+                        Ok(result) => result,
+                        Err(_) => Err(Status::deadline_exceeded(format!(
+                            "no response within {:?}",
+                            policy.deadline
+                        ))),
+                    }
+                }
+                Err(status) => Err(status),
+            };
+
+            match result {
                 Ok(response) => {
-                    let put_response = response.into_inner();
-                    if put_response.success {
-                        Ok(())
-                    } else {
-                        let error_msg = put_response.error;
-                        logd!(5, "[RocksDB] Put failed: {}", error_msg);
-                        Err(error_msg)
+                    if index != first {
+                        logd!(4, "[RocksDB] {} failed over to {}", operation, addr);
                     }
+                    preferred.store(index, Ordering::Relaxed);
+                    return Ok(response);
                 }
-                Err(e) => {
-                    let error_msg = format!("gRPC request failed: {}", e);
+                Err(status) if is_endpoint_failure(&status) => {
+                    let status = crate::grpc::release_on_failure(addr, status).await;
+                    last_error = format!("{}: {}", addr, status.message());
+                }
+                Err(status) => {
+                    let error_msg = format!("gRPC request failed: {}", status);
                     logd!(5, "[RocksDB] {}", error_msg);
-                    Err(error_msg)
+                    return Err(error_msg);
                 }
             }
         }
-        Err(e) => {
-            let error_msg = format!("Failed to create client: {}", e);
-            logd!(5, "[RocksDB] {}", error_msg);
-            Err(error_msg)
+        if attempt < policy.attempts {
+            tokio::time::sleep(policy.backoff(attempt)).await;
         }
     }
+
+    let error_msg = format!("{} for {}: {}", UNAVAILABLE, operation, last_error);
+    logd!(5, "[RocksDB] {}", error_msg);
+    Err(error_msg)
 }
 
-/// Get a value by key from the gRPC RocksDB service
-pub async fn get(key: &str) -> Result<String, String> {
-    if DEV {
-        logd!(
-            1,
-            "[RocksDB] Getting key '{}' from service: {}",
-            key,
-            *ROCKSDB_SERVICE_URL
-        );
+/// Operations of the key-value service
+///
+/// The RocksDB service is used unless another store is set with
+/// [`set_store`]. Unit tests that read or write keys call
+/// [`use_in_memory_store`] first, so that they pass without a running
+/// service.
+#[tonic::async_trait]
+pub trait KeyValueStore: Send + Sync {
+    async fn put(&self, key: &str, value: &str) -> Result<(), String>;
+    /// Value of a key, `Key not found` if it is not stored
+    async fn get(&self, key: &str) -> Result<String, String>;
+    /// All pairs whose key starts with `prefix`, ordered by key
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String>;
+    /// Up to `limit` pairs whose key starts with `prefix` and comes after
+    /// `after`, ordered by key, all of them when `limit` is 0
+    async fn get_page_with_prefix(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>, String>;
+    async fn delete(&self, key: &str) -> Result<(), String>;
+    async fn batch_put(&self, items: Vec<(String, String)>) -> Result<(), String>;
+    /// Put `value` only if `key` holds `expected`, or is not stored when
+    /// `expected` is `None`, and return whether it was written
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&str>,
+        value: &str,
+    ) -> Result<bool, String>;
+    async fn health_check(&self) -> Result<bool, String>;
+}
+
+/// The gRPC RocksDB service on the configured endpoints
+pub struct RocksDbService;
+
+#[tonic::async_trait]
+impl KeyValueStore for RocksDbService {
+    /// Put a key-value pair into the gRPC RocksDB service
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        if DEV {
+            logd!(1, "[RocksDB] Putting key '{}' to service", key);
+        }
+
+        let put_response = call("put", |mut client| async move {
+            let request = tonic::Request::new(PutRequest {
+                key: key.to_string(),
+                value: value.to_string(),
+            });
+            client.put(request).await
+        })
+        .await?
+        .into_inner();
+
+        if put_response.success {
+            Ok(())
+        } else {
+            let error_msg = put_response.error;
+            logd!(5, "[RocksDB] Put failed: {}", error_msg);
+            Err(error_msg)
+        }
     }
 
-    match RocksDbServiceClient::connect(ROCKSDB_SERVICE_URL.clone()).await {
-        Ok(mut client) => {
+    /// Get a value by key from the gRPC RocksDB service
+    async fn get(&self, key: &str) -> Result<String, String> {
+        if DEV {
+            logd!(1, "[RocksDB] Getting key '{}' from service", key);
+        }
+
+        let get_response = call("get", |mut client| async move {
             let request = tonic::Request::new(GetRequest {
                 key: key.to_string(),
             });
+            client.get(request).await
+        })
+        .await?
+        .into_inner();
 
-            match client.get(request).await {
-                Ok(response) => {
-                    let get_response = response.into_inner();
-                    if get_response.success {
-                        if DEV {
-                            logd!(
-                                1,
-                                "[RocksDB] Successfully retrieved key: {} (value length: {})",
-                                key,
-                                get_response.value.len()
-                            );
-                        }
-                        Ok(get_response.value)
-                    } else {
-                        logd!(5, "[RocksDB] Key not found: {}", key);
-                        Err("Key not found".to_string())
-                    }
-                }
-                Err(e) => {
-                    let error_msg = format!("gRPC request failed: {}", e);
-                    logd!(5, "[RocksDB] {}", error_msg);
-                    Err(error_msg)
-                }
+        if get_response.success {
+            if DEV {
+                logd!(
+                    1,
+                    "[RocksDB] Successfully retrieved key: {} (value length: {})",
+                    key,
+                    get_response.value.len()
+                );
             }
-        }
-        Err(e) => {
-            let error_msg = format!("Failed to create client: {}", e);
-            logd!(5, "[RocksDB] {}", error_msg);
-            Err(error_msg)
+            Ok(get_response.value)
+        } else {
+            logd!(5, "[RocksDB] Key not found: {}", key);
+            Err(NOT_FOUND.to_string())
         }
     }
-}
 
-/// Get all key-value pairs with the specified prefix using gRPC RocksDB service
-pub async fn get_all_with_prefix(prefix: &str) -> Result<Vec<(String, String)>, String> {
-    if DEV {
-        logd!(
-            1,
-            "[RocksDB] Getting all keys with prefix '{}' from service: {}",
-            prefix,
-            *ROCKSDB_SERVICE_URL
-        );
+    /// Get all key-value pairs with the specified prefix using gRPC RocksDB service
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        self.get_page_with_prefix(prefix, None, 0).await
     }
 
-    match RocksDbServiceClient::connect(ROCKSDB_SERVICE_URL.clone()).await {
-        Ok(mut client) => {
+    /// Get one page of the key-value pairs with the specified prefix using
+    /// gRPC RocksDB service, which reads no further than the page
+    async fn get_page_with_prefix(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>, String> {
+        if DEV {
+            logd!(1, "[RocksDB] Getting all keys with prefix '{}'", prefix);
+        }
+
+        let get_response = call("get_by_prefix", |mut client| async move {
             let request = tonic::Request::new(GetByPrefixRequest {
                 prefix: prefix.to_string(),
-                limit: 0, // 0 means no limit
+                limit: i32::try_from(limit).unwrap_or(i32::MAX), // 0 means no limit
+                start_after: after.unwrap_or_default().to_string(),
             });
+            client.get_by_prefix(request).await
+        })
+        .await?
+        .into_inner();
 
-            match client.get_by_prefix(request).await {
-                Ok(response) => {
-                    let get_response = response.into_inner();
-                    if get_response.error.is_empty() {
-                        let result: Vec<(String, String)> = get_response
-                            .pairs
-                            .into_iter()
-                            .map(|kv| (kv.key, kv.value))
-                            .collect();
-                        if DEV {
-                            logd!(
-                                1,
-                                "[RocksDB] Successfully retrieved {} keys with prefix '{}'",
-                                result.len(),
-                                prefix
-                            );
-                        }
-                        Ok(result)
-                    } else {
-                        logd!(5, "[RocksDB] Error from service: {}", get_response.error);
-                        Err(get_response.error)
-                    }
-                }
-                Err(e) => {
-                    let error_msg = format!("gRPC request failed: {}", e);
-                    logd!(5, "[RocksDB] {}", error_msg);
-                    Err(error_msg)
-                }
+        if get_response.error.is_empty() {
+            let result: Vec<(String, String)> = get_response
+                .pairs
+                .into_iter()
+                .map(|kv| (kv.key, kv.value))
+                .collect();
+            if DEV {
+                logd!(
+                    1,
+                    "[RocksDB] Successfully retrieved {} keys with prefix '{}'",
+                    result.len(),
+                    prefix
+                );
             }
-        }
-        Err(e) => {
-            let error_msg = format!("Failed to create client: {}", e);
-            logd!(5, "[RocksDB] {}", error_msg);
-            Err(error_msg)
+            Ok(result)
+        } else {
+            logd!(5, "[RocksDB] Error from service: {}", get_response.error);
+            Err(get_response.error)
         }
     }
-}
 
-/// Delete a key from the gRPC RocksDB service
-pub async fn delete(key: &str) -> Result<(), String> {
-    if DEV {
-        logd!(
-            1,
-            "[RocksDB] Deleting key '{}' from service: {}",
-            key,
-            *ROCKSDB_SERVICE_URL
-        );
-    }
+    /// Delete a key from the gRPC RocksDB service
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        if DEV {
+            logd!(1, "[RocksDB] Deleting key '{}' from service", key);
+        }
 
-    match RocksDbServiceClient::connect(ROCKSDB_SERVICE_URL.clone()).await {
-        Ok(mut client) => {
+        let delete_response = call("delete", |mut client| async move {
             let request = tonic::Request::new(DeleteRequest {
                 key: key.to_string(),
             });
+            client.delete(request).await
+        })
+        .await?
+        .into_inner();
 
-            match client.delete(request).await {
-                Ok(response) => {
-                    let delete_response = response.into_inner();
-                    if delete_response.success {
-                        if DEV {
-                            logd!(1, "[RocksDB] Successfully deleted key: {}", key);
-                        }
-                        Ok(())
-                    } else {
-                        let error_msg = delete_response.error;
-                        logd!(5, "[RocksDB] Delete failed: {}", error_msg);
-                        Err(error_msg)
-                    }
-                }
-                Err(e) => {
-                    let error_msg = format!("gRPC request failed: {}", e);
-                    logd!(5, "[RocksDB] {}", error_msg);
-                    Err(error_msg)
-                }
+        if delete_response.success {
+            if DEV {
+                logd!(1, "[RocksDB] Successfully deleted key: {}", key);
             }
-        }
-        Err(e) => {
-            let error_msg = format!("Failed to create client: {}", e);
-            logd!(5, "[RocksDB] {}", error_msg);
+            Ok(())
+        } else {
+            let error_msg = delete_response.error;
+            logd!(5, "[RocksDB] Delete failed: {}", error_msg);
             Err(error_msg)
         }
     }
-}
 
-/// Batch put operation to store multiple key-value pairs using gRPC RocksDB service
-pub async fn batch_put(items: Vec<(String, String)>) -> Result<(), String> {
-    if DEV {
-        logd!(
-            1,
-            "[RocksDB] Batch putting {} items to service: {}",
-            items.len(),
-            *ROCKSDB_SERVICE_URL
-        );
-    }
+    /// Batch put operation to store multiple key-value pairs using gRPC RocksDB service
+    async fn batch_put(&self, items: Vec<(String, String)>) -> Result<(), String> {
+        if DEV {
+            logd!(
+                1,
+                "[RocksDB] Batch putting {} items to service",
+                items.len()
+            );
+        }
 
-    match RocksDbServiceClient::connect(ROCKSDB_SERVICE_URL.clone()).await {
-        Ok(mut client) => {
-            let pairs: Vec<KeyValue> = items
-                .into_iter()
-                .map(|(key, value)| KeyValue { key, value })
-                .collect();
+        let pairs: Vec<KeyValue> = items
+            .into_iter()
+            .map(|(key, value)| KeyValue { key, value })
+            .collect();
+        let pairs = &pairs;
 
-            let request = tonic::Request::new(BatchPutRequest { pairs });
+        let batch_response = call("batch_put", |mut client| async move {
+            let request = tonic::Request::new(BatchPutRequest {
+                pairs: pairs.clone(),
+            });
+            client.batch_put(request).await
+        })
+        .await?
+        .into_inner();
 
-            match client.batch_put(request).await {
-                Ok(response) => {
-                    let batch_response = response.into_inner();
-                    if batch_response.success {
-                        if DEV {
-                            logd!(
-                                1,
-                                "[RocksDB] Successfully stored {} items in batch",
-                                batch_response.processed_count
-                            );
-                        }
-                        Ok(())
-                    } else {
-                        let error_msg = batch_response.error;
-                        logd!(5, "[RocksDB] Batch put failed: {}", error_msg);
-                        Err(error_msg)
-                    }
-                }
-                Err(e) => {
-                    let error_msg = format!("gRPC request failed: {}", e);
-                    logd!(5, "[RocksDB] {}", error_msg);
-                    Err(error_msg)
-                }
+        if batch_response.success {
+            if DEV {
+                logd!(
+                    1,
+                    "[RocksDB] Successfully stored {} items in batch",
+                    batch_response.processed_count
+                );
             }
+            Ok(())
+        } else {
+            let error_msg = batch_response.error;
+            logd!(5, "[RocksDB] Batch put failed: {}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Conditional put of a key-value pair into the gRPC RocksDB service
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&str>,
+        value: &str,
+    ) -> Result<bool, String> {
+        if DEV {
+            logd!(1, "[RocksDB] Swapping key '{}' in service", key);
         }
-        Err(e) => {
-            let error_msg = format!("Failed to create client: {}", e);
-            logd!(5, "[RocksDB] {}", error_msg);
+
+        let swap_response = call("compare_and_swap", |mut client| async move {
+            let request = tonic::Request::new(CompareAndSwapRequest {
+                key: key.to_string(),
+                expected: expected.map(str::to_string),
+                value: value.to_string(),
+            });
+            client.compare_and_swap(request).await
+        })
+        .await?
+        .into_inner();
+
+        if swap_response.error.is_empty() {
+            Ok(swap_response.swapped)
+        } else {
+            let error_msg = swap_response.error;
+            logd!(5, "[RocksDB] Compare-and-swap failed: {}", error_msg);
             Err(error_msg)
         }
     }
+
+    /// Health check for the gRPC RocksDB service
+    async fn health_check(&self) -> Result<bool, String> {
+        let health_response = call("health", |mut client| async move {
+            client.health(tonic::Request::new(HealthRequest {})).await
+        })
+        .await?
+        .into_inner();
+
+        if DEV {
+            logd!(
+                1,
+                "[RocksDB] Health check result: {}",
+                health_response.status
+            );
+        }
+        Ok(health_response.status == "healthy")
+    }
 }
 
-/// Health check for the gRPC RocksDB service
-pub async fn health_check() -> Result<bool, String> {
-    if DEV {
-        logd!(
-            1,
-            "[RocksDB] Health check for service: {}",
-            *ROCKSDB_SERVICE_URL
-        );
+/// Key-value pairs kept in memory, for tests
+///
+/// Keys are validated like the RocksDB service does.
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    pairs: Mutex<BTreeMap<String, String>>,
+}
+
+/// Check a written key against the rules of the RocksDB service
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        Err("Key cannot be empty".to_string())
+    } else if key.len() > 1024 {
+        Err("Key exceeds maximum allowed length of 1024 characters".to_string())
+    } else if key.contains(['<', '>', '?', '{', '}']) {
+        Err("Key contains invalid special characters".to_string())
+    } else {
+        Ok(())
     }
+}
 
-    match RocksDbServiceClient::connect(ROCKSDB_SERVICE_URL.clone()).await {
-        Ok(mut client) => {
-            let request = tonic::Request::new(HealthRequest {});
+fn require(value: &str, what: &str) -> Result<(), String> {
+    if value.is_empty() {
+        Err(format!("{} cannot be empty", what))
+    } else {
+        Ok(())
+    }
+}
 
-            match client.health(request).await {
-                Ok(response) => {
-                    let health_response = response.into_inner();
-                    let is_healthy = health_response.status == "healthy";
-                    if DEV {
-                        logd!(
-                            1,
-                            "[RocksDB] Health check result: {}",
-                            health_response.status
-                        );
-                    }
-                    Ok(is_healthy)
-                }
-                Err(e) => {
-                    let error_msg = format!("Health check failed: {}", e);
-                    logd!(5, "[RocksDB] {}", error_msg);
-                    Err(error_msg)
-                }
-            }
+impl InMemoryStore {
+    fn with_pairs<R>(&self, f: impl FnOnce(&mut BTreeMap<String, String>) -> R) -> R {
+        let mut pairs = self.pairs.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut pairs)
+    }
+}
+
+#[tonic::async_trait]
+impl KeyValueStore for InMemoryStore {
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        validate_key(key)?;
+        self.with_pairs(|pairs| pairs.insert(key.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        require(key, "Key")?;
+        self.with_pairs(|pairs| pairs.get(key).cloned())
+            .ok_or_else(|| NOT_FOUND.to_string())
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        self.get_page_with_prefix(prefix, None, 0).await
+    }
+
+    async fn get_page_with_prefix(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>, String> {
+        require(prefix, "Prefix")?;
+        let start = match after {
+            Some(after) if after >= prefix => Bound::Excluded(after.to_string()),
+            _ => Bound::Included(prefix.to_string()),
+        };
+        Ok(self.with_pairs(|pairs| {
+            pairs
+                .range((start, Bound::Unbounded))
+                .take_while(|(key, _)| key.starts_with(prefix))
+                .take(if limit == 0 { usize::MAX } else { limit })
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        require(key, "Key")?;
+        self.with_pairs(|pairs| pairs.remove(key));
+        Ok(())
+    }
+
+    async fn batch_put(&self, items: Vec<(String, String)>) -> Result<(), String> {
+        for (key, _) in &items {
+            validate_key(key).map_err(|_| format!("Invalid key: {}", key))?;
         }
-        Err(e) => {
-            let error_msg = format!("Failed to create client: {}", e);
-            logd!(5, "[RocksDB] {}", error_msg);
-            Err(error_msg)
+        self.with_pairs(|pairs| pairs.extend(items));
+        Ok(())
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&str>,
+        value: &str,
+    ) -> Result<bool, String> {
+        validate_key(key).map_err(|_| format!("Invalid key: {}", key))?;
+        Ok(self.with_pairs(|pairs| {
+            if pairs.get(key).map(String::as_str) != expected {
+                return false;
+            }
+            pairs.insert(key.to_string(), value.to_string());
+            true
+        }))
+    }
+
+    async fn health_check(&self) -> Result<bool, String> {
+        Ok(true)
+    }
+}
+
+/// Store of the process, the RocksDB service until another one is set
+static STORE: RwLock<Option<Arc<dyn KeyValueStore>>> = RwLock::new(None);
+
+/// Whether [`STORE`] holds the store set by [`use_in_memory_store`]
+static IN_MEMORY: AtomicBool = AtomicBool::new(false);
+
+/// Use `store` for every later operation instead of the RocksDB service
+pub fn set_store(store: Arc<dyn KeyValueStore>) {
+    let mut current = STORE.write().unwrap_or_else(|e| e.into_inner());
+    *current = Some(store);
+    IN_MEMORY.store(false, Ordering::SeqCst);
+}
+
+/// Keep the keys of this process in an [`InMemoryStore`], for unit tests
+///
+/// The store is shared by every test of the process, later calls keep it
+/// and its keys.
+pub fn use_in_memory_store() {
+    let mut store = STORE.write().unwrap_or_else(|e| e.into_inner());
+    if !IN_MEMORY.swap(true, Ordering::SeqCst) {
+        *store = Some(Arc::new(InMemoryStore::default()));
+    }
+}
+
+fn store() -> Arc<dyn KeyValueStore> {
+    if let Some(store) = STORE.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return store.clone();
+    }
+    STORE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(|| Arc::new(RocksDbService))
+        .clone()
+}
+
+/// Put a key-value pair into the store
+pub async fn put(key: &str, value: &str) -> Result<(), String> {
+    store().put(key, value).await
+}
+
+/// Get a value by key from the store
+pub async fn get(key: &str) -> Result<String, String> {
+    store().get(key).await
+}
+
+/// Get all key-value pairs with the specified prefix from the store
+pub async fn get_all_with_prefix(prefix: &str) -> Result<Vec<(String, String)>, String> {
+    store().get_all_with_prefix(prefix).await
+}
+
+/// Get up to `limit` key-value pairs with the specified prefix whose key
+/// comes after `after` from the store, all of them when `limit` is 0
+pub async fn get_page_with_prefix(
+    prefix: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<Vec<(String, String)>, String> {
+    store().get_page_with_prefix(prefix, after, limit).await
+}
+
+/// Delete a key from the store
+pub async fn delete(key: &str) -> Result<(), String> {
+    store().delete(key).await
+}
+
+/// Batch put operation to store multiple key-value pairs
+pub async fn batch_put(items: Vec<(String, String)>) -> Result<(), String> {
+    store().batch_put(items).await
+}
+
+/// Put a key-value pair into the store if the key still holds `expected`
+///
+/// `None` expects the key not to be stored. Returns whether it was written.
+pub async fn compare_and_swap(
+    key: &str,
+    expected: Option<&str>,
+    value: &str,
+) -> Result<bool, String> {
+    store().compare_and_swap(key, expected, value).await
+}
+
+/// Health check of the store
+pub async fn health_check() -> Result<bool, String> {
+    store().health_check().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+            deadline: Duration::from_millis(500),
         }
     }
+
+    /// Endpoints that cannot be connected, nothing listens on port 1
+    fn unreachable(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| format!("http://127.0.0.{}:1", i + 1))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_endpoints_fail_as_unavailable() {
+        let endpoints = unreachable(2);
+        let preferred = AtomicUsize::new(0);
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), String> =
+            call_on(&endpoints, &preferred, &policy(2), "get", |_| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(is_unavailable(&err), "{}", err);
+        assert!(err.contains("127.0.0.2:1"));
+        assert!(matches!(error(err), Error::Unavailable(_)));
+        // No request is sent without a connection
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_service_errors_are_not_retried() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let _ = tonic::transport::Server::builder()
+                .add_service(crate::health::grpc_service())
+                .serve_with_incoming(
+                    tonic::transport::server::TcpIncoming::from_listener(listener, true, None)
+                        .unwrap(),
+                )
+                .await;
+        });
+
+        // The reachable endpoint is used after the unreachable one and kept
+        let mut endpoints = unreachable(1);
+        endpoints.push(addr);
+        let preferred = AtomicUsize::new(0);
+        let calls = AtomicU32::new(0);
+
+        let result = call_on(&endpoints, &preferred, &policy(3), "put", |_| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Status::invalid_argument("bad key"))
+        })
+        .await;
+        assert!(!is_unavailable(&result.unwrap_err()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let result = call_on(&endpoints, &preferred, &policy(3), "put", |_| async {
+            Ok::<_, Status>(())
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(preferred.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_behaves_like_the_service() {
+        let store = InMemoryStore::default();
+        store.put("/model/a/state", "Running").await.unwrap();
+        store
+            .batch_put(vec![
+                ("/model/b/state".to_string(), "Dead".to_string()),
+                ("/package/p/state".to_string(), "error".to_string()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(store.get("/model/a/state").await.unwrap(), "Running");
+        assert_eq!(
+            store.get_all_with_prefix("/model/").await.unwrap(),
+            vec![
+                ("/model/a/state".to_string(), "Running".to_string()),
+                ("/model/b/state".to_string(), "Dead".to_string()),
+            ]
+        );
+
+        let keys = |pairs: Vec<(String, String)>| -> Vec<String> {
+            pairs.into_iter().map(|(key, _)| key).collect()
+        };
+        let first = store.get_page_with_prefix("/", None, 2).await.unwrap();
+        assert_eq!(keys(first), vec!["/model/a/state", "/model/b/state"]);
+        let next = store.get_page_with_prefix("/", Some("/model/b/state"), 2);
+        assert_eq!(keys(next.await.unwrap()), vec!["/package/p/state"]);
+        let next = store.get_page_with_prefix("/model/", Some("/a"), 0);
+        assert_eq!(next.await.unwrap().len(), 2);
+
+        store.delete("/model/a/state").await.unwrap();
+        let err = store.get("/model/a/state").await.unwrap_err();
+        assert_eq!(err, NOT_FOUND);
+        assert!(matches!(error(err), Error::NotFound(_)));
+        assert!(store.put("", "value").await.is_err());
+        assert!(store.put(&"k".repeat(1025), "value").await.is_err());
+        assert!(store.delete("").await.is_err());
+        assert!(store.get_all_with_prefix("").await.is_err());
+        assert!(store.health_check().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_compare_and_swap() {
+        let store = InMemoryStore::default();
+        assert!(store.compare_and_swap("/lease", None, "a").await.unwrap());
+        // Already stored
+        assert!(!store.compare_and_swap("/lease", None, "b").await.unwrap());
+        assert!(!store
+            .compare_and_swap("/lease", Some("b"), "c")
+            .await
+            .unwrap());
+        assert!(store
+            .compare_and_swap("/lease", Some("a"), "c")
+            .await
+            .unwrap());
+        assert_eq!(store.get("/lease").await.unwrap(), "c");
+        assert!(store.compare_and_swap("", None, "a").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_is_kept_once_selected() {
+        use_in_memory_store();
+        put("/etcd-test/key", "value").await.unwrap();
+        use_in_memory_store();
+        assert_eq!(get("/etcd-test/key").await.unwrap(), "value");
+
+        set_store(Arc::new(InMemoryStore::default()));
+        assert!(get("/etcd-test/key").await.is_err());
+        use_in_memory_store();
+        assert!(get("/etcd-test/key").await.is_err());
+    }
 }
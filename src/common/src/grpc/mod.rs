@@ -0,0 +1,137 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Shared gRPC channels
+//!
+//! Connecting for every call adds a TCP and HTTP/2 handshake to each request.
+//! Instead, one channel per server address is created on first use and
+//! shared by every client of the process; a tonic channel is cheap to clone
+//! and multiplexes concurrent requests.
+//!
+//! Channels send HTTP/2 keep-alive pings so that a dead connection is noticed
+//! while idle, and tonic reconnects a broken connection on the next request.
+//! A channel whose call failed at the transport level is additionally dropped
+//! with [`release_on_failure`], so the next call starts from a fresh connection.
+//!
+//! Servers addressed with `https://` are connected with the TLS settings of
+//! `common::tls`.
+//!
+//! Calls that should survive short outages go through [`retry::call`], which
+//! adds deadlines, backoff and a circuit breaker per server.
+
+pub mod outbox;
+pub mod retry;
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+/// Time allowed to establish a new connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval of HTTP/2 keep-alive pings
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Time to wait for a ping acknowledgement before the connection is closed
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    static ref CHANNELS: Mutex<HashMap<String, Channel>> = Mutex::new(HashMap::new());
+}
+
+/// Shared channel to a gRPC server, connecting on first use
+///
+/// ### Parameters
+/// * `addr` - server URI, e.g. `http://127.0.0.1:47001`
+/// ### Returns
+/// * `Ok(Channel)` - the pooled channel, clone it freely
+/// * `Err(Status)` - `unavailable` if the server cannot be reached
+pub async fn channel(addr: &str) -> Result<Channel, Status> {
+    if let Some(channel) = CHANNELS.lock().await.get(addr) {
+        return Ok(channel.clone());
+    }
+
+    // Connect without holding the lock so other servers are not blocked
+    let mut endpoint = Endpoint::from_shared(addr.to_string())
+        .map_err(|e| Status::invalid_argument(format!("Invalid address {}: {}", addr, e)))?;
+    if addr.starts_with("https://") {
+        let config = crate::tls::client_config()
+            .map_err(Status::unavailable)?
+            .ok_or_else(|| Status::unavailable(format!("TLS is not configured for {}", addr)))?;
+        endpoint = endpoint
+            .tls_config(config)
+            .map_err(|e| Status::unavailable(format!("Invalid TLS configuration: {}", e)))?;
+    }
+    let channel = endpoint
+        .connect_timeout(CONNECT_TIMEOUT)
+        .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+        .keep_alive_timeout(KEEP_ALIVE_TIMEOUT)
+        .keep_alive_while_idle(true)
+        .connect()
+        .await
+        .map_err(|e| Status::unavailable(format!("Failed to connect to {}: {}", addr, e)))?;
+
+    // Another task may have connected meanwhile, keep the first channel
+    Ok(CHANNELS
+        .lock()
+        .await
+        .entry(addr.to_string())
+        .or_insert(channel)
+        .clone())
+}
+
+/// Drop the pooled channel of a server, the next call reconnects
+pub async fn invalidate(addr: &str) {
+    CHANNELS.lock().await.remove(addr);
+}
+
+/// Whether a failed call indicates a broken connection rather than an
+/// error returned by the server
+fn is_transport_failure(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::Unknown)
+}
+
+/// Drop the pooled channel of a server if a call failed at the transport level
+///
+/// Returns the status unchanged for the caller to propagate.
+pub async fn release_on_failure(addr: &str, status: Status) -> Status {
+    if is_transport_failure(&status) {
+        invalidate(addr).await;
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_channel_unreachable_and_invalid_address() {
+        let err = channel("http://127.0.0.1:1").await.unwrap_err();
+        assert_eq!(err.code(), Code::Unavailable);
+        assert!(!CHANNELS.lock().await.contains_key("http://127.0.0.1:1"));
+
+        let err = channel("not a uri").await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_pooled_channel_is_reused_until_released() {
+        let addr = "http://127.0.0.1:2";
+        let lazy = Endpoint::from_static("http://127.0.0.1:2").connect_lazy();
+        CHANNELS.lock().await.insert(addr.to_string(), lazy);
+
+        // The pooled channel is returned without connecting
+        assert!(channel(addr).await.is_ok());
+
+        // Server errors keep the channel, transport errors drop it
+        let status = release_on_failure(addr, Status::not_found("missing")).await;
+        assert_eq!(status.code(), Code::NotFound);
+        assert!(CHANNELS.lock().await.contains_key(addr));
+        release_on_failure(addr, Status::unavailable("connection reset")).await;
+        assert!(!CHANNELS.lock().await.contains_key(addr));
+        assert!(channel(addr).await.is_err());
+    }
+}
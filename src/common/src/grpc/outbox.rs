@@ -0,0 +1,525 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Persistent outbox of the StateChanges sent to the StateManager
+//!
+//! A StateChange that cannot be delivered because the StateManager is down
+//! or unreachable, after the retries of [`super::retry::call`], is stored in
+//! etcd under `/outbox/<component>/<sequence>` and answered as queued. The
+//! stored changes are delivered in the order they were sent by
+//! [`Outbox::run`], and a new change queues behind them until they are
+//! through. Changes survive a restart of the sending component.
+//!
+//! Deliveries run without holding the queue, so a sender never waits for
+//! the retries of another one. Only one flush delivers at a time.
+//!
+//! Delivery is at-least-once: a change that reached the StateManager but
+//! whose response was lost is delivered again, with its original transition
+//! id. A change the StateManager rejects is dropped from the outbox, only
+//! transport failures keep it queued. A change still failing with
+//! `Code::Unknown` after [`MAX_UNKNOWN_ATTEMPTS`] flushes is moved to
+//! `/outbox-dead/<component>/<sequence>`, so that it does not hold back the
+//! changes behind it. Its attempts are counted from the start of the
+//! component.
+
+use crate::logd;
+use crate::statemanager::{ErrorCode, StateChange, StateChangeResponse};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tonic::{Code, Response, Status};
+
+/// Interval between deliveries of the stored StateChanges
+pub const FLUSH_INTERVAL_SECS: u64 = 5;
+
+/// Flushes a stored StateChange may fail with `Code::Unknown` before it is
+/// moved to the dead letters
+pub const MAX_UNKNOWN_ATTEMPTS: u32 = 10;
+
+/// StateChanges of a component waiting for the StateManager
+pub struct Outbox {
+    component: String,
+    queue: Mutex<Queue>,
+    /// Held by the flush delivering the stored changes
+    flushing: Mutex<()>,
+}
+
+#[derive(Default)]
+struct Queue {
+    /// Whether the changes stored by an earlier run were read
+    loaded: bool,
+    /// Sequence number of the next stored change, starting from the time
+    /// the outbox was created
+    next: u64,
+    /// Stored changes, oldest first
+    changes: VecDeque<Stored>,
+}
+
+/// A stored StateChange
+struct Stored {
+    key: String,
+    state_change: StateChange,
+    /// Flushes that failed with `Code::Unknown`
+    unknown_attempts: u32,
+}
+
+impl Outbox {
+    /// Outbox of the StateChanges sent by `component`
+    pub fn new(component: &str) -> Self {
+        Self {
+            component: component.to_string(),
+            queue: Mutex::new(Queue {
+                next: crate::clock::now_ns().max(0) as u64,
+                ..Default::default()
+            }),
+            flushing: Mutex::new(()),
+        }
+    }
+
+    /// Deliver a StateChange with `deliver`, storing it if it cannot be
+    ///
+    /// A change sent while earlier ones are stored is stored behind them
+    /// without a delivery.
+    ///
+    /// ### Returns
+    /// * `Ok(Response)` - response of the StateManager, or a response with
+    ///   `ERROR_CODE_RESOURCE_UNAVAILABLE` if the change was queued
+    /// * `Err(Status)` - error of the StateManager, or the transport failure
+    ///   if the change could not be stored either
+    pub async fn send<F, Fut>(
+        &self,
+        state_change: StateChange,
+        deliver: F,
+    ) -> Result<Response<StateChangeResponse>, Status>
+    where
+        F: Fn(StateChange) -> Fut,
+        Fut: Future<Output = Result<Response<StateChangeResponse>, Status>>,
+    {
+        {
+            let mut queue = self.queue.lock().await;
+            self.load(&mut queue).await;
+            if !queue.changes.is_empty() {
+                let status = Status::unavailable(format!(
+                    "{} earlier StateChanges are not delivered yet",
+                    queue.changes.len()
+                ));
+                return self.store(&mut queue, state_change, status).await;
+            }
+        }
+
+        let status = match deliver(state_change.clone()).await {
+            Err(status) if is_undelivered(&status) => status,
+            result => return result,
+        };
+        let mut queue = self.queue.lock().await;
+        self.store(&mut queue, state_change, status).await
+    }
+
+    /// Store a change that was not delivered for `status`
+    async fn store(
+        &self,
+        queue: &mut Queue,
+        state_change: StateChange,
+        status: Status,
+    ) -> Result<Response<StateChangeResponse>, Status> {
+        let key = key(&self.component, queue.next);
+        let value = serde_json::to_string(&state_change)
+            .map_err(|e| Status::internal(format!("StateChange not stored: {}", e)))?;
+        if let Err(e) = crate::etcd::put(&key, &value).await {
+            logd!(
+                5,
+                "StateChange {} of {} lost, not stored: {}",
+                state_change.transition_id,
+                state_change.resource_name,
+                e
+            );
+            return Err(status);
+        }
+        logd!(
+            4,
+            "StateChange {} of {} queued: {}",
+            state_change.transition_id,
+            state_change.resource_name,
+            status.message()
+        );
+        queue.next += 1;
+        let response = queued(&state_change, &status);
+        queue.changes.push_back(Stored {
+            key,
+            state_change,
+            unknown_attempts: 0,
+        });
+        Ok(Response::new(response))
+    }
+
+    /// Deliver the stored StateChanges in order with `deliver`
+    ///
+    /// Stops at the first change that cannot be delivered and returns the
+    /// number of changes still stored.
+    pub async fn flush<F, Fut>(&self, deliver: F) -> usize
+    where
+        F: Fn(StateChange) -> Fut,
+        Fut: Future<Output = Result<Response<StateChangeResponse>, Status>>,
+    {
+        let _flushing = self.flushing.lock().await;
+        loop {
+            // Only the flush removes changes, the front stays until it is done
+            let (key, state_change) = {
+                let mut queue = self.queue.lock().await;
+                self.load(&mut queue).await;
+                match queue.changes.front() {
+                    Some(front) => (front.key.clone(), front.state_change.clone()),
+                    None => return 0,
+                }
+            };
+            let result = deliver(state_change.clone()).await;
+
+            let mut queue = self.queue.lock().await;
+            match result {
+                Err(status) if is_undelivered(&status) => {
+                    let Some(front) = queue.changes.front_mut() else {
+                        return 0;
+                    };
+                    if status.code() == Code::Unknown {
+                        front.unknown_attempts += 1;
+                    }
+                    if front.unknown_attempts < MAX_UNKNOWN_ATTEMPTS {
+                        return queue.changes.len();
+                    }
+                    self.dead_letter(&key, &state_change, &status).await;
+                }
+                Err(status) => logd!(
+                    4,
+                    "Queued StateChange {} of {} rejected: {}",
+                    state_change.transition_id,
+                    state_change.resource_name,
+                    status.message()
+                ),
+                Ok(_) => logd!(
+                    2,
+                    "Queued StateChange {} of {} delivered",
+                    state_change.transition_id,
+                    state_change.resource_name
+                ),
+            }
+            if let Err(e) = crate::etcd::delete(&key).await {
+                logd!(4, "Delivered StateChange {} not removed: {}", key, e);
+            }
+            queue.changes.pop_front();
+        }
+    }
+
+    /// Deliver the stored StateChanges every `interval`
+    pub async fn run<F, Fut>(&self, interval: Duration, deliver: F)
+    where
+        F: Fn(StateChange) -> Fut,
+        Fut: Future<Output = Result<Response<StateChangeResponse>, Status>>,
+    {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let remaining = self.flush(&deliver).await;
+            if remaining > 0 {
+                logd!(3, "{} StateChanges of {} queued", remaining, self.component);
+            }
+        }
+    }
+
+    /// Keep a change that keeps failing with `Code::Unknown` aside
+    async fn dead_letter(&self, key: &str, state_change: &StateChange, status: &Status) {
+        let sequence = sequence(&self.component, key).unwrap_or_default();
+        let dead_key = dead_key(&self.component, sequence);
+        let stored = match serde_json::to_string(state_change) {
+            Ok(value) => crate::etcd::put(&dead_key, &value).await,
+            Err(e) => Err(e.to_string()),
+        };
+        match stored {
+            Ok(()) => logd!(
+                5,
+                "Queued StateChange {} of {} moved to {} after {} failures: {}",
+                state_change.transition_id,
+                state_change.resource_name,
+                dead_key,
+                MAX_UNKNOWN_ATTEMPTS,
+                status.message()
+            ),
+            Err(e) => logd!(
+                5,
+                "Queued StateChange {} of {} dropped after {} failures, not kept: {}",
+                state_change.transition_id,
+                state_change.resource_name,
+                MAX_UNKNOWN_ATTEMPTS,
+                e
+            ),
+        }
+    }
+
+    /// Read the changes stored by an earlier run, once
+    async fn load(&self, queue: &mut Queue) {
+        if queue.loaded {
+            return;
+        }
+        let stored = match crate::etcd::get_all_with_prefix(&prefix(&self.component)).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                logd!(4, "Outbox of {} not read: {}", self.component, e);
+                return;
+            }
+        };
+        let mut changes: Vec<(u64, String, StateChange)> = Vec::new();
+        for (key, value) in stored {
+            let Some(sequence) = sequence(&self.component, &key) else {
+                continue;
+            };
+            match serde_json::from_str(&value) {
+                Ok(state_change) => changes.push((sequence, key, state_change)),
+                Err(e) => {
+                    logd!(4, "Dropping unreadable StateChange {}: {}", key, e);
+                    let _ = crate::etcd::delete(&key).await;
+                }
+            }
+        }
+        changes.sort_by_key(|(sequence, _, _)| *sequence);
+
+        // Changes queued before the outbox could be read are among the stored
+        // ones, sequences of a run start after those of earlier runs
+        if let Some((sequence, _, _)) = changes.last() {
+            queue.next = queue.next.max(sequence + 1);
+        }
+        queue.changes = changes
+            .into_iter()
+            .map(|(_, key, state_change)| Stored {
+                key,
+                state_change,
+                unknown_attempts: 0,
+            })
+            .collect();
+        queue.loaded = true;
+        if !queue.changes.is_empty() {
+            logd!(
+                3,
+                "{} StateChanges of {} to deliver",
+                queue.changes.len(),
+                self.component
+            );
+        }
+    }
+}
+
+fn prefix(component: &str) -> String {
+    format!("/outbox/{}/", component)
+}
+
+/// Key of a stored change, zero padded so that keys sort by sequence
+fn key(component: &str, sequence: u64) -> String {
+    format!("{}{:020}", prefix(component), sequence)
+}
+
+/// Key of a change moved to the dead letters
+fn dead_key(component: &str, sequence: u64) -> String {
+    format!("/outbox-dead/{}/{:020}", component, sequence)
+}
+
+fn sequence(component: &str, key: &str) -> Option<u64> {
+    key.strip_prefix(&prefix(component))?.parse().ok()
+}
+
+/// Whether a call failed before the StateManager could handle it
+fn is_undelivered(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable | Code::Unknown | Code::DeadlineExceeded
+    )
+}
+
+/// Response to a StateChange stored for a later delivery
+fn queued(state_change: &StateChange, status: &Status) -> StateChangeResponse {
+    StateChangeResponse {
+        message: format!(
+            "StateChange of {} queued for delivery",
+            state_change.resource_name
+        ),
+        transition_id: state_change.transition_id.clone(),
+        timestamp_ns: crate::clock::now_ns(),
+        error_code: ErrorCode::ResourceUnavailable as i32,
+        error_details: status.message().to_string(),
+        metadata: state_change.metadata.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+    use std::sync::{Arc, Mutex as StdMutex};
+
+    #[test]
+    fn test_keys_sort_by_sequence() {
+        let keys: Vec<String> = [10, 2, 1].iter().map(|s| key("apiserver", *s)).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(
+            sorted,
+            vec![keys[2].clone(), keys[1].clone(), keys[0].clone()]
+        );
+        assert_eq!(sequence("apiserver", &keys[0]), Some(10));
+        assert_eq!(sequence("actioncontroller", &keys[0]), None);
+        assert_eq!(sequence("apiserver", "/outbox/apiserver/x"), None);
+    }
+
+    #[test]
+    fn test_undelivered_and_queued() {
+        assert!(is_undelivered(&Status::unavailable("down")));
+        assert!(is_undelivered(&Status::deadline_exceeded("slow")));
+        assert!(!is_undelivered(&Status::invalid_argument("bad")));
+
+        let state_change = StateChange {
+            resource_name: "scenario".to_string(),
+            transition_id: "apiserver-1".to_string(),
+            ..Default::default()
+        };
+        let response = queued(&state_change, &Status::unavailable("down"));
+        assert_eq!(response.transition_id, "apiserver-1");
+        assert_eq!(response.error_code, ErrorCode::ResourceUnavailable as i32);
+        assert_eq!(response.error_details, "down");
+    }
+
+    fn change(id: &str) -> StateChange {
+        StateChange {
+            resource_name: "scenario".to_string(),
+            transition_id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Delivery recording the transition ids it is called with and
+    /// answering with `answer`
+    fn delivery(
+        delivered: &Arc<StdMutex<Vec<String>>>,
+        answer: Option<Code>,
+    ) -> impl Fn(StateChange) -> Ready<Result<Response<StateChangeResponse>, Status>> {
+        let delivered = delivered.clone();
+        move |state_change: StateChange| {
+            delivered
+                .lock()
+                .unwrap()
+                .push(state_change.transition_id.clone());
+            ready(match answer {
+                Some(code) => Err(Status::new(code, "failed")),
+                None => Ok(Response::new(StateChangeResponse {
+                    transition_id: state_change.transition_id,
+                    ..Default::default()
+                })),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_delivers_or_queues_in_order() {
+        crate::etcd::use_in_memory_store();
+        let outbox = Outbox::new("outbox-order");
+        let delivered = Arc::new(StdMutex::new(Vec::new()));
+
+        let response = outbox
+            .send(change("1"), delivery(&delivered, None))
+            .await
+            .unwrap();
+        assert_eq!(response.get_ref().error_code, ErrorCode::Success as i32);
+
+        let response = outbox
+            .send(change("2"), delivery(&delivered, Some(Code::Unavailable)))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.get_ref().error_code,
+            ErrorCode::ResourceUnavailable as i32
+        );
+
+        // Queued behind the undelivered change without being sent
+        outbox
+            .send(change("3"), delivery(&delivered, None))
+            .await
+            .unwrap();
+        assert_eq!(*delivered.lock().unwrap(), ["1", "2"]);
+
+        assert_eq!(
+            outbox
+                .flush(delivery(&delivered, Some(Code::DeadlineExceeded)))
+                .await,
+            2
+        );
+        assert_eq!(outbox.flush(delivery(&delivered, None)).await, 0);
+        assert_eq!(*delivered.lock().unwrap(), ["1", "2", "2", "2", "3"]);
+        let stored = crate::etcd::get_all_with_prefix(&prefix("outbox-order"))
+            .await
+            .unwrap();
+        assert!(stored.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_send_is_not_queued() {
+        crate::etcd::use_in_memory_store();
+        let outbox = Outbox::new("outbox-rejected");
+        let delivered = Arc::new(StdMutex::new(Vec::new()));
+
+        let result = outbox
+            .send(
+                change("1"),
+                delivery(&delivered, Some(Code::InvalidArgument)),
+            )
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(outbox.flush(delivery(&delivered, None)).await, 0);
+        assert_eq!(*delivered.lock().unwrap(), ["1"]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_failures_are_dead_lettered() {
+        crate::etcd::use_in_memory_store();
+        let outbox = Outbox::new("outbox-dead");
+        let delivered = Arc::new(StdMutex::new(Vec::new()));
+        for id in ["1", "2"] {
+            outbox
+                .send(change(id), delivery(&delivered, Some(Code::Unknown)))
+                .await
+                .unwrap();
+        }
+
+        for _ in 1..MAX_UNKNOWN_ATTEMPTS {
+            assert_eq!(
+                outbox
+                    .flush(delivery(&delivered, Some(Code::Unknown)))
+                    .await,
+                2
+            );
+        }
+        // The last failure moves the head aside and lets the next one through
+        let answer = Arc::new(StdMutex::new(Vec::new()));
+        let unknown_then_ok = {
+            let answer = answer.clone();
+            move |state_change: StateChange| {
+                answer
+                    .lock()
+                    .unwrap()
+                    .push(state_change.transition_id.clone());
+                ready(if state_change.transition_id == "1" {
+                    Err(Status::unknown("failed"))
+                } else {
+                    Ok(Response::new(StateChangeResponse::default()))
+                })
+            }
+        };
+        assert_eq!(outbox.flush(unknown_then_ok).await, 0);
+        assert_eq!(*answer.lock().unwrap(), ["1", "2"]);
+
+        let dead = crate::etcd::get_all_with_prefix("/outbox-dead/outbox-dead/")
+            .await
+            .unwrap();
+        assert_eq!(dead.len(), 1);
+        let kept: StateChange = serde_json::from_str(&dead[0].1).unwrap();
+        assert_eq!(kept.transition_id, "1");
+    }
+}
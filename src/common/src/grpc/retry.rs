@@ -0,0 +1,486 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Retries and circuit breaking for calls between components
+//!
+//! [`call`] runs a request on the pooled channel of a server with a deadline,
+//! and retries it with exponential backoff while the failure is transient:
+//! the server is unreachable, did not answer in time or is overloaded.
+//! Errors returned by the server itself are passed through unchanged.
+//!
+//! Every server has a circuit breaker. After `breaker_failure_threshold`
+//! consecutive transport failures it opens and calls fail immediately with
+//! `unavailable` for `breaker_open_secs`. The next call is then let through
+//! as a probe, closing the breaker on success and opening it again on failure.
+//! A probe cancelled before its outcome is known leaves the breaker open with
+//! its period elapsed, so that the next call probes again.
+//! [`breaker_stats`] exposes the breakers for metrics and health reporting.
+
+use super::{channel, is_transport_failure, release_on_failure};
+use crate::setting::GrpcSettings;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tonic::transport::Channel;
+use tonic::{Code, Status};
+
+/// Attempts, backoff and deadline of a call
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, at least one
+    pub attempts: u32,
+    /// Delay before the second attempt, doubled for each further attempt
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between attempts
+    pub max_backoff: Duration,
+    /// Time allowed for a single attempt
+    pub deadline: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy configured in the `grpc` section of the settings
+    pub fn from_settings() -> Self {
+        Self::from(&crate::setting::get_config().grpc)
+    }
+
+    /// Delay after the given failed attempt, counting from 1
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl From<&GrpcSettings> for RetryPolicy {
+    fn from(settings: &GrpcSettings) -> Self {
+        Self {
+            attempts: settings.retry_attempts.max(1),
+            initial_backoff: Duration::from_millis(settings.initial_backoff_ms),
+            max_backoff: Duration::from_millis(settings.max_backoff_ms),
+            deadline: Duration::from_millis(settings.deadline_ms),
+        }
+    }
+}
+
+/// State of the circuit breaker of a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BreakerState {
+    /// Calls pass through
+    Closed,
+    /// Calls fail immediately until the open period ends
+    Open,
+    /// A single probe call is in flight
+    HalfOpen,
+}
+
+/// Snapshot of the circuit breaker of a server
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BreakerStats {
+    pub addr: String,
+    pub state: BreakerState,
+    /// Transport failures since the last successful call
+    pub consecutive_failures: u32,
+    /// Number of times the breaker opened
+    pub trips: u64,
+    /// Calls refused while the breaker was open
+    pub rejected: u64,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trips: u64,
+    rejected: u64,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            trips: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Whether a call may be sent now, `Some(true)` for the probe of a
+    /// half-open breaker
+    fn allow(&mut self, open_period: Duration) -> Option<bool> {
+        match self.state {
+            BreakerState::Closed => Some(false),
+            BreakerState::Open if self.opened_at.is_some_and(|t| t.elapsed() >= open_period) => {
+                self.state = BreakerState::HalfOpen;
+                Some(true)
+            }
+            BreakerState::Open | BreakerState::HalfOpen => {
+                self.rejected += 1;
+                None
+            }
+        }
+    }
+
+    /// Give the probe slot back, the period it was opened for has elapsed
+    fn on_probe_cancelled(&mut self) {
+        if self.state == BreakerState::HalfOpen {
+            self.state = BreakerState::Open;
+        }
+    }
+
+    fn on_success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    /// Record a transport failure, returns whether the breaker opened
+    fn on_failure(&mut self, threshold: u32) -> bool {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let trip = match self.state {
+            BreakerState::HalfOpen => true,
+            BreakerState::Closed => self.consecutive_failures >= threshold.max(1),
+            BreakerState::Open => false,
+        };
+        if trip {
+            self.state = BreakerState::Open;
+            self.opened_at = Some(Instant::now());
+            self.trips += 1;
+        }
+        trip
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref BREAKERS: Mutex<HashMap<String, Breaker>> = Mutex::new(HashMap::new());
+}
+
+/// Run `f` against the breaker of a server, creating it on first use
+fn with_breaker<R>(addr: &str, f: impl FnOnce(&mut Breaker) -> R) -> R {
+    let mut breakers = BREAKERS.lock().unwrap_or_else(|e| e.into_inner());
+    f(breakers
+        .entry(addr.to_string())
+        .or_insert_with(Breaker::new))
+}
+
+/// Probe let through the half-open breaker of a server
+///
+/// Dropped before [`Probe::done`], e.g. when the call is cancelled, it gives
+/// the probe slot back instead of leaving the breaker half-open.
+struct Probe<'a> {
+    addr: Option<&'a str>,
+}
+
+impl Probe<'_> {
+    /// The outcome of the probe is recorded by the caller
+    fn done(mut self) {
+        self.addr = None;
+    }
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if let Some(addr) = self.addr {
+            with_breaker(addr, Breaker::on_probe_cancelled);
+        }
+    }
+}
+
+/// Snapshot of every circuit breaker of this process, sorted by address
+pub fn breaker_stats() -> Vec<BreakerStats> {
+    let breakers = BREAKERS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut stats: Vec<BreakerStats> = breakers
+        .iter()
+        .map(|(addr, breaker)| BreakerStats {
+            addr: addr.clone(),
+            state: breaker.state,
+            consecutive_failures: breaker.consecutive_failures,
+            trips: breaker.trips,
+            rejected: breaker.rejected,
+        })
+        .collect();
+    stats.sort_by(|a, b| a.addr.cmp(&b.addr));
+    stats
+}
+
+/// Whether a failed attempt may succeed when sent again
+pub fn is_retryable(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted
+    )
+}
+
+/// Whether a failed attempt counts against the circuit breaker
+fn is_breaker_failure(status: &Status) -> bool {
+    is_transport_failure(status) || status.code() == Code::DeadlineExceeded
+}
+
+/// Call a server with the configured retry policy and circuit breaker
+///
+/// ### Parameters
+/// * `addr` - server URI, e.g. `http://127.0.0.1:47006`
+/// * `request` - sends one attempt over the given channel, called again
+///   for every retry so it must be safe to repeat
+/// ### Returns
+/// * `Ok(T)` - response of the first successful attempt
+/// * `Err(Status)` - error of the last attempt, `unavailable` if the
+///   breaker is open or `deadline_exceeded` if an attempt timed out
+pub async fn call<T, F, Fut>(addr: &str, request: F) -> Result<T, Status>
+where
+    F: FnMut(Channel) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let settings = &crate::setting::get_config().grpc;
+    call_with(
+        addr,
+        &RetryPolicy::from_settings(),
+        settings.breaker_failure_threshold,
+        Duration::from_secs(settings.breaker_open_secs),
+        request,
+    )
+    .await
+}
+
+/// [`call`] with an explicit policy and breaker parameters
+pub async fn call_with<T, F, Fut>(
+    addr: &str,
+    policy: &RetryPolicy,
+    failure_threshold: u32,
+    open_period: Duration,
+    mut request: F,
+) -> Result<T, Status>
+where
+    F: FnMut(Channel) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let attempts = policy.attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let probe = match with_breaker(addr, |b| b.allow(open_period)) {
+            Some(probe) => Probe {
+                addr: probe.then_some(addr),
+            },
+            None => {
+                return Err(Status::unavailable(format!(
+                    "Circuit breaker open for {}",
+                    addr
+                )))
+            }
+        };
+
+        let result = match channel(addr).await {
+            Ok(channel) => match tokio::time::timeout(policy.deadline, request(channel)).await {
+                Ok(result) => result,
+                Err(_) => Err(Status::deadline_exceeded(format!(
+                    "No response from {} within {:?}",
+                    addr, policy.deadline
+                ))),
+            },
+            Err(status) => Err(status),
+        };
+        probe.done();
+
+        let status = match result {
+            Ok(response) => {
+                with_breaker(addr, Breaker::on_success);
+                return Ok(response);
+            }
+            Err(status) => status,
+        };
+
+        if is_breaker_failure(&status) {
+            if with_breaker(addr, |b| b.on_failure(failure_threshold)) {
+                crate::logd!(
+                    5,
+                    "Circuit breaker for {} opened after {}",
+                    addr,
+                    status.message()
+                );
+            }
+        } else {
+            // The server answered, so the connection itself is healthy
+            with_breaker(addr, Breaker::on_success);
+        }
+        let status = release_on_failure(addr, status).await;
+
+        if !is_retryable(&status) || attempt >= attempts {
+            return Err(status);
+        }
+        crate::logd!(
+            3,
+            "Call to {} failed (attempt {}/{}): {}",
+            addr,
+            attempt,
+            attempts,
+            status.message()
+        );
+        tokio::time::sleep(policy.backoff(attempt)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tonic::transport::Endpoint;
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            deadline: Duration::from_millis(50),
+        }
+    }
+
+    async fn pool_lazy_channel(addr: &'static str) {
+        super::super::CHANNELS
+            .lock()
+            .await
+            .insert(addr.to_string(), Endpoint::from_static(addr).connect_lazy());
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = policy(5);
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(3), Duration::from_millis(4));
+        assert_eq!(policy.backoff(10), Duration::from_millis(4));
+    }
+
+    #[tokio::test]
+    async fn test_call_retries_transient_failures_only() {
+        let addr = "http://127.0.0.1:3";
+        pool_lazy_channel(addr).await;
+
+        // An overloaded server is retried until an attempt succeeds
+        let calls = AtomicU32::new(0);
+        let result = call_with(addr, &policy(3), 10, Duration::from_secs(30), |_| {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n < 2 {
+                    Err(Status::resource_exhausted("queue full"))
+                } else {
+                    Ok(n)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+
+        // Errors of the server are returned at once
+        let calls = AtomicU32::new(0);
+        let result: Result<(), Status> =
+            call_with(addr, &policy(3), 10, Duration::from_secs(30), |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err(Status::invalid_argument("bad")) }
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A slow attempt hits the deadline
+        let result: Result<(), Status> =
+            call_with(addr, &policy(1), 10, Duration::from_secs(30), |_| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::DeadlineExceeded);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_and_recovers() {
+        let addr = "http://127.0.0.1:4";
+        let open_period = Duration::from_millis(20);
+        let state = || {
+            breaker_stats()
+                .into_iter()
+                .find(|s| s.addr == addr)
+                .unwrap()
+        };
+
+        // Two failed calls reach the threshold and open the breaker
+        for _ in 0..2 {
+            pool_lazy_channel(addr).await;
+            let result: Result<(), Status> =
+                call_with(addr, &policy(1), 2, open_period, |_| async {
+                    Err(Status::unavailable("down"))
+                })
+                .await;
+            assert!(result.is_err());
+        }
+        assert_eq!(state().state, BreakerState::Open);
+        assert_eq!(state().trips, 1);
+
+        // Calls are refused without reaching the server
+        let calls = AtomicU32::new(0);
+        let result: Result<(), Status> = call_with(addr, &policy(1), 2, open_period, |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        })
+        .await;
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(state().rejected, 1);
+
+        // After the open period a successful probe closes it again
+        tokio::time::sleep(open_period).await;
+        pool_lazy_channel(addr).await;
+        let result: Result<(), Status> =
+            call_with(addr, &policy(1), 2, open_period, |_| async { Ok(()) }).await;
+        assert!(result.is_ok());
+        assert_eq!(state().state, BreakerState::Closed);
+        assert_eq!(state().consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_probe_releases_the_breaker() {
+        let addr = "http://127.0.0.1:5";
+        let open_period = Duration::from_millis(20);
+        let policy = policy(1);
+        let state = || {
+            breaker_stats()
+                .into_iter()
+                .find(|s| s.addr == addr)
+                .unwrap()
+                .state
+        };
+
+        pool_lazy_channel(addr).await;
+        let result: Result<(), Status> = call_with(addr, &policy, 1, open_period, |_| async {
+            Err(Status::unavailable("down"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(state(), BreakerState::Open);
+
+        // The probe is cancelled while it waits for the server
+        tokio::time::sleep(open_period).await;
+        pool_lazy_channel(addr).await;
+        let probe = call_with(addr, &policy, 1, open_period, |_| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<_, Status>(())
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(10), probe)
+            .await
+            .is_err());
+        assert_eq!(state(), BreakerState::Open);
+
+        // The next call probes again
+        pool_lazy_channel(addr).await;
+        let result: Result<(), Status> =
+            call_with(addr, &policy, 1, open_period, |_| async { Ok(()) }).await;
+        assert!(result.is_ok());
+        assert_eq!(state(), BreakerState::Closed);
+    }
+}
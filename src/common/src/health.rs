@@ -0,0 +1,362 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Liveness and readiness of a component
+//!
+//! A component calls [`init`] once, marks itself [`set_started`] when it
+//! is able to do its work, and reports on its processing loops:
+//! * [`busy`] while a loop handles an item; a loop busy for longer than
+//!   `health.stall_secs` is stalled
+//! * [`closed`] when the channel a loop reads is gone and it stopped
+//!
+//! The component is live while none of its loops is closed or stalled. It is
+//! ready when it is live, started, and, if it needs etcd, etcd answers. The
+//! dependencies awaited by [`crate::startup`] are reported with
+//! [`set_dependency`] and must be available too.
+//!
+//! The state is served with the standard gRPC health checking protocol
+//! through [`grpc_service`], and over HTTP as `/healthz` and `/readyz`
+//! through [`router`] or [`serve`], both `200 OK` or
+//! `503 Service Unavailable` with a JSON [`Report`].
+
+use axum::{http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tonic_health::pb::health_server::HealthServer;
+use tonic_health::ServingStatus;
+
+/// State of a processing loop
+#[derive(Debug, Default)]
+struct LoopState {
+    busy_since: Option<Instant>,
+    closed: bool,
+}
+
+/// Health of this component
+struct Health {
+    component: String,
+    needs_etcd: bool,
+    started: bool,
+    loops: HashMap<String, LoopState>,
+    dependencies: HashMap<String, Check>,
+}
+
+fn health() -> &'static Mutex<Health> {
+    static HEALTH: OnceLock<Mutex<Health>> = OnceLock::new();
+    HEALTH.get_or_init(|| {
+        Mutex::new(Health {
+            component: String::new(),
+            needs_etcd: false,
+            started: false,
+            loops: HashMap::new(),
+            dependencies: HashMap::new(),
+        })
+    })
+}
+
+fn with_health<R>(f: impl FnOnce(&mut Health) -> R) -> R {
+    let mut health = health().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut health)
+}
+
+/// Name the component and whether its readiness depends on etcd
+pub fn init(component: &str, needs_etcd: bool) {
+    with_health(|h| {
+        h.component = component.to_string();
+        h.needs_etcd = needs_etcd;
+    });
+}
+
+/// Mark the component as able to serve requests
+pub fn set_started() {
+    with_health(|h| h.started = true);
+}
+
+/// Record whether a dependency is available
+///
+/// Returns whether this changed its availability, which is the case for the
+/// first report of a dependency.
+pub fn set_dependency(name: &str, ok: bool, detail: &str) -> bool {
+    with_health(|h| {
+        let check = Check {
+            name: format!("dependency:{}", name),
+            ok,
+            detail: detail.to_string(),
+        };
+        h.dependencies
+            .insert(name.to_string(), check)
+            .is_none_or(|previous| previous.ok != ok)
+    })
+}
+
+/// Marks a processing loop busy until dropped
+pub struct BusyGuard {
+    name: String,
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        with_health(|h| {
+            if let Some(state) = h.loops.get_mut(&self.name) {
+                state.busy_since = None;
+            }
+        });
+    }
+}
+
+/// Mark a processing loop busy with one item
+pub fn busy(name: &str) -> BusyGuard {
+    with_health(|h| {
+        h.loops.entry(name.to_string()).or_default().busy_since = Some(Instant::now());
+    });
+    BusyGuard {
+        name: name.to_string(),
+    }
+}
+
+/// Mark a processing loop as stopped because its channel closed
+pub fn closed(name: &str) {
+    with_health(|h| h.loops.entry(name.to_string()).or_default().closed = true);
+}
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Liveness and readiness with the checks behind them
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Report {
+    pub component: String,
+    pub live: bool,
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Checks of the processing loops, they decide liveness
+fn loop_checks(loops: &HashMap<String, LoopState>, stall: Duration, now: Instant) -> Vec<Check> {
+    let mut checks: Vec<Check> = loops
+        .iter()
+        .map(|(name, state)| {
+            let busy_for = state.busy_since.map(|since| now.duration_since(since));
+            let (ok, detail) = match busy_for {
+                _ if state.closed => (false, "channel closed".to_string()),
+                Some(busy_for) if busy_for > stall => {
+                    (false, format!("stalled for {}s", busy_for.as_secs()))
+                }
+                Some(_) => (true, "busy".to_string()),
+                None => (true, "idle".to_string()),
+            };
+            Check {
+                name: format!("loop:{}", name),
+                ok,
+                detail,
+            }
+        })
+        .collect();
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
+    checks
+}
+
+/// Evaluate the health of the component
+pub async fn report() -> Report {
+    let settings = &crate::setting::get_config().health;
+    let (component, needs_etcd, started, mut checks, mut dependencies) = with_health(|h| {
+        (
+            h.component.clone(),
+            h.needs_etcd,
+            h.started,
+            loop_checks(
+                &h.loops,
+                Duration::from_secs(settings.stall_secs),
+                Instant::now(),
+            ),
+            h.dependencies.values().cloned().collect::<Vec<_>>(),
+        )
+    });
+    let live = checks.iter().all(|c| c.ok);
+
+    checks.push(Check {
+        name: "started".to_string(),
+        ok: started,
+        detail: if started { "running" } else { "starting" }.to_string(),
+    });
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+    checks.extend(dependencies);
+    if needs_etcd {
+        let timeout = Duration::from_millis(settings.etcd_timeout_ms);
+        let (ok, detail) = match tokio::time::timeout(timeout, crate::etcd::health_check()).await {
+            Ok(Ok(true)) => (true, "reachable".to_string()),
+            Ok(Ok(false)) => (false, "unhealthy".to_string()),
+            Ok(Err(e)) => (false, e),
+            Err(_) => (false, "timed out".to_string()),
+        };
+        checks.push(Check {
+            name: "etcd".to_string(),
+            ok,
+            detail,
+        });
+    }
+    let ready = checks.iter().all(|c| c.ok);
+
+    Report {
+        component,
+        live,
+        ready,
+        checks,
+    }
+}
+
+/// gRPC health service kept up to date with [`report`]
+///
+/// The overall status (empty service name) is `SERVING` while the component
+/// is ready.
+pub fn grpc_service() -> HealthServer<impl tonic_health::pb::health_server::Health> {
+    let (mut reporter, service) = tonic_health::server::health_reporter();
+    tokio::spawn(async move {
+        let interval_secs = crate::setting::get_config().health.interval_secs.max(1);
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            let status = if report().await.ready {
+                ServingStatus::Serving
+            } else {
+                ServingStatus::NotServing
+            };
+            reporter.set_service_status("", status).await;
+        }
+    });
+    service
+}
+
+async fn respond(ok: impl Fn(&Report) -> bool) -> impl IntoResponse {
+    let report = report().await;
+    let code = if ok(&report) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(report))
+}
+
+/// Routes of `/healthz` (liveness) and `/readyz` (readiness)
+pub fn router() -> Router {
+    Router::new()
+        .route("/healthz", get(|| respond(|r| r.live)))
+        .route("/readyz", get(|| respond(|r| r.ready)))
+}
+
+/// Serve [`router`] on its own HTTP listener
+///
+/// Does nothing when `health.http_enabled` is off.
+pub async fn serve(addr: String) {
+    serve_with(addr, Router::new()).await
+}
+
+/// Like [`serve`], with further routes of the component such as `/metrics`
+pub async fn serve_with(addr: String, routes: Router) {
+    if !crate::setting::get_config().health.http_enabled {
+        return;
+    }
+    match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => {
+            crate::logd!(3, "Health endpoints listening on {}", addr);
+            if let Err(e) = axum::serve(listener, router().merge(routes)).await {
+                crate::logd!(5, "Health endpoints stopped: {}", e);
+            }
+        }
+        Err(e) => crate::logd!(5, "Failed to bind health endpoints on {}: {}", addr, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_loop_checks() {
+        let now = Instant::now();
+        let loops = HashMap::from([
+            ("idle".to_string(), LoopState::default()),
+            (
+                "working".to_string(),
+                LoopState {
+                    busy_since: Some(now - Duration::from_secs(1)),
+                    closed: false,
+                },
+            ),
+            (
+                "hung".to_string(),
+                LoopState {
+                    busy_since: Some(now - Duration::from_secs(120)),
+                    closed: false,
+                },
+            ),
+            (
+                "gone".to_string(),
+                LoopState {
+                    busy_since: None,
+                    closed: true,
+                },
+            ),
+        ]);
+
+        let checks = loop_checks(&loops, Duration::from_secs(60), now);
+        let status: Vec<(&str, bool, &str)> = checks
+            .iter()
+            .map(|c| (c.name.as_str(), c.ok, c.detail.as_str()))
+            .collect();
+        assert_eq!(
+            status,
+            vec![
+                ("loop:gone", false, "channel closed"),
+                ("loop:hung", false, "stalled for 120s"),
+                ("loop:idle", true, "idle"),
+                ("loop:working", true, "busy"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_busy_guard_and_endpoints() {
+        init("test", false);
+        {
+            let _busy = busy("work");
+            assert!(with_health(|h| h.loops["work"].busy_since.is_some()));
+        }
+        assert!(with_health(|h| h.loops["work"].busy_since.is_none()));
+
+        let status = |uri: &'static str| async move {
+            let request = axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            router().oneshot(request).await.unwrap().status()
+        };
+
+        // Live but not ready before the component started
+        assert_eq!(status("/healthz").await, StatusCode::OK);
+        assert_eq!(status("/readyz").await, StatusCode::SERVICE_UNAVAILABLE);
+        set_started();
+        assert_eq!(status("/readyz").await, StatusCode::OK);
+
+        // Not ready while a dependency awaited at startup is missing
+        assert!(set_dependency("test-db", false, "connection refused"));
+        assert!(!set_dependency("test-db", false, "connection refused"));
+        assert_eq!(status("/readyz").await, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(set_dependency("test-db", true, "available"));
+        assert_eq!(status("/readyz").await, StatusCode::OK);
+
+        closed("work");
+        assert_eq!(status("/healthz").await, StatusCode::SERVICE_UNAVAILABLE);
+        let report = report().await;
+        assert!(!report.live && !report.ready);
+    }
+}
@@ -4,46 +4,42 @@
  */
 pub use crate::error::Result;
 
+pub mod address;
+pub mod auth;
+pub mod clock;
+pub mod correlation;
 pub mod error;
 pub mod etcd;
+pub mod grpc;
+pub mod health;
+pub mod namespace;
 pub mod setting;
 pub mod spec;
+pub mod startup;
+pub mod state_mapping;
+pub mod tls;
+pub mod version;
 
 // gRPC protobuf module for RocksDB service
 pub mod rocksdbservice {
     include!("generated/rocksdbservice.rs");
 }
 
-fn open_server(port: u16) -> String {
-    format!("{}:{}", crate::setting::get_config().host.ip, port)
-}
-
-// // guest 서버 함수 수정: 이제 항상 호스트 서버 주소 반환
-// fn open_guest_server(port: u16) -> String {
-//     // 항상 호스트 서버 주소 반환
-//     open_server(port)
-// }
-
-fn connect_server(port: u16) -> String {
-    format!("http://{}:{}", crate::setting::get_config().host.ip, port)
-}
-
-// guest 서버 연결 함수 수정: 이제 항상 호스트 서버 주소 반환
-//using rust build in _ to below code , as it was never used anywhere to prevent warnings
-fn _connect_guest_server(port: u16) -> String {
-    // 항상 호스트 서버 주소 반환
-    connect_server(port)
-}
+use crate::address::{connect_server, open_server, Service};
 
 pub mod actioncontroller {
     include!("generated/actioncontroller.rs");
 
     pub fn open_server() -> String {
-        super::open_server(47001)
+        super::open_server(super::Service::ActionController)
+    }
+
+    pub fn open_health_server() -> String {
+        super::open_server(super::Service::ActionControllerHealth)
     }
 
     pub fn connect_server() -> String {
-        super::connect_server(47001)
+        super::connect_server(super::Service::ActionController)
     }
 }
 
@@ -51,15 +47,15 @@ pub mod apiserver {
     include!("generated/apiserver.rs");
 
     pub fn open_rest_server() -> String {
-        super::open_server(47099)
+        super::open_server(super::Service::ApiServerRest)
     }
 
     pub fn open_grpc_server() -> String {
-        super::open_server(47098)
+        super::open_server(super::Service::ApiServerGrpc)
     }
 
     pub fn connect_grpc_server() -> String {
-        super::connect_server(47098)
+        super::connect_server(super::Service::ApiServerGrpc)
     }
 }
 
@@ -67,11 +63,20 @@ pub mod filtergateway {
     include!("generated/filtergateway.rs");
 
     pub fn open_server() -> String {
-        super::open_server(47002)
+        super::open_server(super::Service::FilterGateway)
+    }
+
+    pub fn open_health_server() -> String {
+        super::open_server(super::Service::FilterGatewayHealth)
+    }
+
+    /// REST address of the signal injector of test builds
+    pub fn open_injector_server() -> String {
+        super::open_server(super::Service::FilterGatewayInjector)
     }
 
     pub fn connect_server() -> String {
-        super::connect_server(47002)
+        super::connect_server(super::Service::FilterGateway)
     }
 }
 
@@ -79,11 +84,15 @@ pub mod monitoringserver {
     include!("generated/monitoringserver.rs");
 
     pub fn open_server() -> String {
-        super::open_server(47003)
+        super::open_server(super::Service::MonitoringServer)
+    }
+
+    pub fn open_health_server() -> String {
+        super::open_server(super::Service::MonitoringServerHealth)
     }
 
     pub fn connect_server() -> String {
-        super::connect_server(47003)
+        super::connect_server(super::Service::MonitoringServer)
     }
 }
 
@@ -94,10 +103,14 @@ pub mod nodeagent {
         include!("generated/nodeagent.fromactioncontroller.rs");
 
         pub fn connect_server(node_ip: &str) -> String {
-            format!("http://{node_ip}:47004")
+            crate::address::connect_server_at(crate::address::Service::NodeAgent, node_ip)
         }
     }
 
+    pub fn open_health_server(node_ip: &str) -> String {
+        super::address::open_server_at(super::Service::NodeAgentHealth, node_ip)
+    }
+
     pub mod fromapiserver {
         include!("generated/nodeagent.fromapiserver.rs");
     }
@@ -107,11 +120,15 @@ pub mod policymanager {
     include!("generated/policymanager.rs");
 
     pub fn open_server() -> String {
-        super::open_server(47005)
+        super::open_server(super::Service::PolicyManager)
+    }
+
+    pub fn open_health_server() -> String {
+        super::open_server(super::Service::PolicyManagerHealth)
     }
 
     pub fn connect_server() -> String {
-        super::connect_server(47005)
+        super::connect_server(super::Service::PolicyManager)
     }
 }
 
@@ -119,11 +136,44 @@ pub mod statemanager {
     include!("generated/statemanager.rs");
 
     pub fn open_server() -> String {
-        super::open_server(47006)
+        super::open_server(super::Service::StateManager)
+    }
+
+    pub fn open_health_server() -> String {
+        super::open_server(super::Service::StateManagerHealth)
     }
 
     pub fn connect_server() -> String {
-        super::connect_server(47006)
+        super::connect_server(super::Service::StateManager)
+    }
+
+    /// Sub-state of a package whose canary update ended with a promotion
+    pub const CANARY_PROMOTED: &str = "canary promoted";
+
+    /// Sub-state of a package whose canary update ended with a rollback,
+    /// followed by the reason
+    pub const CANARY_ROLLED_BACK: &str = "canary rolled back";
+
+    impl VehicleMode {
+        /// Lowercase mode name used in scenario specs and DDS signals
+        pub fn name(&self) -> &'static str {
+            match self {
+                VehicleMode::Unspecified => "unspecified",
+                VehicleMode::Driving => "driving",
+                VehicleMode::Parked => "parked",
+                VehicleMode::Charging => "charging",
+            }
+        }
+
+        /// Parse a mode name, ignoring case
+        pub fn from_name(name: &str) -> Option<Self> {
+            match name.trim().to_lowercase().as_str() {
+                "driving" => Some(VehicleMode::Driving),
+                "parked" => Some(VehicleMode::Parked),
+                "charging" => Some(VehicleMode::Charging),
+                _ => None,
+            }
+        }
     }
 }
 
@@ -133,14 +183,21 @@ pub mod external {
     pub mod timpani {
         include!("generated/schedinfo.v1.rs");
         pub fn connect_timpani_server() -> String {
-            format!("http://{}:{}", crate::setting::get_config().host.ip, 50052)
+            crate::address::connect_server(crate::address::Service::Timpani)
+        }
+
+        /// Whether release time <= runtime <= deadline <= period holds for a task
+        pub fn validate_task_constraints(task: &TaskInfo) -> bool {
+            task.release_time <= task.runtime
+                && task.runtime <= task.deadline
+                && task.deadline <= task.period
         }
     }
 
     pub mod pharos {
         include!("generated/pharos.api.v1.rs");
         pub fn connect_pharos_server() -> String {
-            format!("http://{}:{}", crate::setting::get_config().host.ip, 47006)
+            crate::address::connect_server(crate::address::Service::Pharos)
         }
     }
 }
@@ -231,4 +288,23 @@ mod tests {
         };
         assert_eq!(result, "Invalid port"); // Assert that the result indicates an invalid port
     }
+
+    #[test]
+    fn test_vehicle_mode_names() {
+        use crate::statemanager::VehicleMode;
+
+        for mode in [
+            VehicleMode::Driving,
+            VehicleMode::Parked,
+            VehicleMode::Charging,
+        ] {
+            assert_eq!(VehicleMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(
+            VehicleMode::from_name(" Parked "),
+            Some(VehicleMode::Parked)
+        );
+        assert_eq!(VehicleMode::from_name("unspecified"), None);
+        assert_eq!(VehicleMode::from_name("towing"), None);
+    }
 }
@@ -5,6 +5,7 @@
 use bytes::BytesMut;
 use prost::Message;
 use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 use tokio::net::UnixDatagram;
@@ -137,11 +138,43 @@ pub async fn init_async_logger(tag: &str) -> std::io::Result<()> {
 /// * `level` - Severity level code.
 /// * `message` - Formatted log message.
 pub async fn log(level: i32, message: String) {
+    if !enabled(level) {
+        return;
+    }
+    let message = with_correlation(message);
     if let Err(err) = enqueue(level, message).await {
         crate::logd!(6, "logger enqueue failed: {err}");
     }
 }
 
+/// Lowest level logged by this process, 0 to follow `logging.level`
+static MIN_LEVEL: AtomicI32 = AtomicI32::new(0);
+
+/// Log from `level` on in this process, whatever `logging.level` says
+///
+/// `None` follows the setting again.
+pub fn set_min_level(level: Option<i32>) {
+    MIN_LEVEL.store(level.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Whether messages of a level pass the `logging.level` setting, or the
+/// level set by [`set_min_level`]
+fn enabled(level: i32) -> bool {
+    let min_level = match MIN_LEVEL.load(Ordering::Relaxed) {
+        0 => crate::setting::get_config().logging.min_level(),
+        min_level => min_level,
+    };
+    level >= min_level
+}
+
+/// Prefix a message with the correlation ID of the calling task, if any.
+fn with_correlation(message: String) -> String {
+    match crate::correlation::current() {
+        Some(id) => format!("[{}] {}", id, message),
+        None => message,
+    }
+}
+
 /// Fire-and-forget API for synchronous call sites. Spawns a task on the
 /// current Tokio runtime (if any) to enqueue the log message.
 ///
@@ -149,8 +182,12 @@ pub async fn log(level: i32, message: String) {
 /// * `level` - Severity level code.
 /// * `message` - Formatted log message.
 pub fn log_nowait(level: i32, message: String) {
+    if !enabled(level) {
+        return;
+    }
     match Handle::try_current() {
         Ok(handle) => {
+            let message = with_correlation(message);
             handle.spawn(async move {
                 if let Err(err) = enqueue(level, message).await {
                     crate::logd!(6, "logger enqueue failed: {err}");
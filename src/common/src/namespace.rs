@@ -0,0 +1,164 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Namespaces of artifacts and their states
+//!
+//! An artifact belongs to the namespace of its `metadata.namespace`, the
+//! [`DEFAULT`] namespace when it has none. Components refer to a resource by
+//! its qualified name, `{namespace}/{name}`, so that the existing key
+//! formats give `Scenario/{namespace}/{name}` for an artifact and
+//! `/model/{namespace}/{name}/state` for a state. Resources of the default
+//! namespace keep their plain name and the keys they had before namespaces.
+//!
+//! References between artifacts, such as the package of a scenario or the
+//! models of a package, are resolved in the namespace of the referring
+//! artifact with [`qualify`]. Namespaces and names are DNS labels, checked
+//! with [`validate`] and [`validate_name`], so a qualified name splits back
+//! into the namespace and the name it was made of.
+
+/// Namespace of artifacts without `metadata.namespace`
+pub const DEFAULT: &str = "default";
+
+/// Separator of the namespace and the name in a qualified name
+pub const SEPARATOR: char = '/';
+
+/// Longest namespace or name, as a DNS label
+const MAX_LENGTH: usize = 63;
+
+/// Qualified name of a resource of a namespace
+pub fn qualify(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() || namespace == DEFAULT {
+        name.to_string()
+    } else {
+        format!("{}{}{}", namespace, SEPARATOR, name)
+    }
+}
+
+/// Namespace and plain name of a qualified name
+pub fn split(qualified: &str) -> (&str, &str) {
+    qualified
+        .split_once(SEPARATOR)
+        .unwrap_or((DEFAULT, qualified))
+}
+
+/// Namespace of a qualified name
+pub fn of(qualified: &str) -> &str {
+    split(qualified).0
+}
+
+/// Qualified name of `name` referenced from the resource `qualified`
+///
+/// References never leave the namespace of the referring resource.
+pub fn sibling(qualified: &str, name: &str) -> String {
+    qualify(of(qualified), name)
+}
+
+/// Name of the Pod of a model, usable as a container name
+///
+/// Container names cannot contain the separator, the namespace is joined
+/// with a dot instead.
+pub fn pod_name(qualified: &str) -> String {
+    match qualified.split_once(SEPARATOR) {
+        Some((namespace, name)) => format!("{}.{}", namespace, name),
+        None => qualified.to_string(),
+    }
+}
+
+/// Check that a namespace is a lowercase DNS label
+pub fn validate(namespace: &str) -> Result<(), String> {
+    check_label("namespace", namespace, "lowercase letters", |c| {
+        c.is_ascii_lowercase()
+    })
+}
+
+/// Check that the name of a resource is a DNS label
+///
+/// Unlike namespaces, names may use uppercase letters. Double dashes are
+/// reserved for the names of replicas, see
+/// [`crate::spec::k8s::pod::replica_name`].
+pub fn validate_name(name: &str) -> Result<(), String> {
+    check_label("name", name, "letters", |c| c.is_ascii_alphabetic())?;
+    if name.contains(crate::spec::k8s::pod::REPLICA_SEPARATOR) {
+        return Err(format!(
+            "name '{}' must not contain '{}', it is reserved for replicas",
+            name,
+            crate::spec::k8s::pod::REPLICA_SEPARATOR
+        ));
+    }
+    Ok(())
+}
+
+fn check_label(
+    what: &str,
+    label: &str,
+    letters: &str,
+    letter: fn(char) -> bool,
+) -> Result<(), String> {
+    if label.is_empty() || label.len() > MAX_LENGTH {
+        return Err(format!(
+            "{} '{}' must have 1 to {} characters",
+            what, label, MAX_LENGTH
+        ));
+    }
+    if !label
+        .chars()
+        .all(|c| letter(c) || c.is_ascii_digit() || c == '-')
+        || label.starts_with('-')
+        || label.ends_with('-')
+    {
+        return Err(format!(
+            "{} '{}' must consist of {}, digits and inner dashes",
+            what, label, letters
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qualify_and_split() {
+        assert_eq!(qualify(DEFAULT, "hello"), "hello");
+        assert_eq!(qualify("", "hello"), "hello");
+        assert_eq!(qualify("team-a", "hello"), "team-a/hello");
+        assert_eq!(split("team-a/hello"), ("team-a", "hello"));
+        assert_eq!(split("hello"), (DEFAULT, "hello"));
+        assert_eq!(of("team-a/hello"), "team-a");
+        assert_eq!(sibling("team-a/hello", "hello-core"), "team-a/hello-core");
+        assert_eq!(sibling("hello", "hello-core"), "hello-core");
+        assert_eq!(pod_name("team-a/hello-core"), "team-a.hello-core");
+        assert_eq!(pod_name("hello-core"), "hello-core");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("team-a").is_ok());
+        assert!(validate(DEFAULT).is_ok());
+        assert!(validate("").is_err());
+        assert!(validate("Team").is_err());
+        assert!(validate("team/a").is_err());
+        assert!(validate("-team").is_err());
+        assert!(validate(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("hello-core").is_ok());
+        assert!(validate_name("Battery").is_ok());
+        assert!(validate_name("").is_err());
+        assert_eq!(
+            validate_name("team-b/hello"),
+            Err("name 'team-b/hello' must consist of letters, digits and inner dashes".to_string())
+        );
+        assert!(validate_name("hello_core").is_err());
+        assert!(validate_name("hello.core").is_err());
+        assert!(validate_name("hello-").is_err());
+        assert!(validate_name(&"a".repeat(64)).is_err());
+        // Reserved for replicas
+        assert!(validate_name("hello--2").is_err());
+    }
+}
@@ -2,16 +2,108 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
-use serde::Deserialize;
-use std::sync::OnceLock;
-static SETTINGS: OnceLock<Settings> = OnceLock::new();
 
-#[derive(Deserialize)]
+//! Settings of the Piccolo components, read from `/etc/piccolo/settings.yaml`
+//!
+//! The settings can be reloaded while a component runs, by [`watch`] when
+//! the file changes or by [`reload`] on request. Only the settings read at
+//! use time, listed in [`HOT_RELOADABLE`], take the new value; the others
+//! keep the value the component started with and are reported as needing a
+//! restart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// File the settings are read from
+pub const SETTINGS_PATH: &str = "/etc/piccolo/settings.yaml";
+
+/// Settings applied by a reload, as `section` or `section.field`
+///
+/// Everything else, such as addresses, channel sizes and storage backends,
+/// is only read at startup.
+pub const HOT_RELOADABLE: [&str; 31] = [
+    "logging",
+    "policy",
+    "placement",
+    "auth",
+    "tls.allowed_peers",
+    "grpc.retry_attempts",
+    "grpc.initial_backoff_ms",
+    "grpc.max_backoff_ms",
+    "grpc.deadline_ms",
+    "etcd.timeout_ms",
+    "etcd.retry_attempts",
+    "etcd.initial_backoff_ms",
+    "etcd.max_backoff_ms",
+    "health.stall_secs",
+    "health.etcd_timeout_ms",
+    "statemanager.state_timeouts",
+    "statemanager.timeout_check_interval",
+    "statemanager.write_flush_interval_ms",
+    "statemanager.schedule_refresh_secs",
+    "statemanager.drift_check_interval_secs",
+    "statemanager.drift_auto_correct",
+    "statemanager.hooks",
+    "statemanager.timing_budgets_ms",
+    "statemanager.rate_limit_per_sec",
+    "statemanager.rate_limit_burst",
+    "statemanager.crash_loop_restarts",
+    "statemanager.crash_loop_oom_kills",
+    "statemanager.crash_loop_window_secs",
+    "statemanager.failure_grace_secs",
+    "artifact.max_documents",
+    "artifact.max_document_bytes",
+];
+
+/// Interval between checks of the settings file by [`watch`]
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+static SETTINGS: OnceLock<RwLock<&'static Settings>> = OnceLock::new();
+
+/// Reloads applied since startup
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Settings {
     pub host: HostSettings,
+    #[serde(default)]
+    pub liveness: LivenessSettings,
+    #[serde(default)]
+    pub policy: PolicySettings,
+    #[serde(default)]
+    pub placement: PlacementSettings,
+    #[serde(default)]
+    pub statemanager: StateManagerSettings,
+    #[serde(default)]
+    pub grpc: GrpcSettings,
+    #[serde(default)]
+    pub tls: TlsSettings,
+    #[serde(default)]
+    pub auth: AuthSettings,
+    #[serde(default)]
+    pub health: HealthSettings,
+    #[serde(default)]
+    pub startup: StartupSettings,
+    #[serde(default)]
+    pub gc: GcSettings,
+    #[serde(default)]
+    pub etcd: EtcdSettings,
+    #[serde(default)]
+    pub telemetry: TelemetrySettings,
+    #[serde(default)]
+    pub logging: LoggingSettings,
+    #[serde(default)]
+    pub filtergateway: FilterGatewaySettings,
+    #[serde(default)]
+    pub artifact: ArtifactSettings,
+    #[serde(default)]
+    pub addresses: AddressSettings,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct HostSettings {
     pub name: String,
     pub ip: String,
@@ -19,30 +111,723 @@ pub struct HostSettings {
     pub role: String,
 }
 
-fn parse_settings_yaml() -> Settings {
-    let default_settings: Settings = Settings {
+/// Node liveness tracking parameters used by the apiserver
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct LivenessSettings {
+    /// Expected interval between NodeAgent heartbeats, in seconds
+    pub heartbeat_interval: u64,
+    /// Number of consecutive missed heartbeats before a node is NotReady
+    pub miss_threshold: u32,
+}
+
+impl Default for LivenessSettings {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: 30,
+            miss_threshold: 3,
+        }
+    }
+}
+
+/// Limits enforced by the policymanager before a scenario is allowed
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct PolicySettings {
+    /// Highest ASIL level this platform may run: QM, A, B, C or D
+    pub max_asil_level: String,
+    /// CPU available to a single scenario in millicores, 0 for no limit
+    pub cpu_budget_millis: u64,
+    /// Memory available to a single scenario in MiB, 0 for no limit
+    pub memory_budget_mb: u64,
+}
+
+impl Default for PolicySettings {
+    fn default() -> Self {
+        Self {
+            max_asil_level: String::from("D"),
+            cpu_budget_millis: 0,
+            memory_budget_mb: 0,
+        }
+    }
+}
+
+/// Node labels models may be placed by
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct PlacementSettings {
+    /// Keys of the labels models may select, nodes registering with other
+    /// keys are warned about
+    pub label_keys: Vec<String>,
+}
+
+impl Default for PlacementSettings {
+    fn default() -> Self {
+        Self {
+            label_keys: ["gpu", "safety-domain", "zone", "arch", "os"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl PlacementSettings {
+    /// The keys that are not among the known `label_keys`
+    pub fn unknown_keys<'a>(&self, keys: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+        let mut unknown: Vec<String> = keys
+            .into_iter()
+            .filter(|key| !self.label_keys.contains(key))
+            .cloned()
+            .collect();
+        unknown.sort();
+        unknown
+    }
+}
+
+/// Channel sizing and state watchdog parameters of the statemanager
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct StateManagerSettings {
+    /// Buffered ContainerList snapshots from NodeAgents
+    pub container_buffer: usize,
+    /// What to do with a ContainerList when the buffer is full: block, reject or drop_oldest
+    pub container_overflow: String,
+    /// Buffered StateChange requests
+    pub state_change_buffer: usize,
+    /// What to do with a StateChange when the buffer is full: block, reject or drop_oldest
+    pub state_change_overflow: String,
+    /// Buffered single container state updates
+    pub container_update_buffer: usize,
+    /// What to do with a container update when the buffer is full: block, reject or drop_oldest
+    pub container_update_overflow: String,
+    /// Seconds a resource may stay in a transitional state before it times out,
+    /// keyed by resource type then state name, e.g. `scenario: { satisfied: 30 }`
+    pub state_timeouts: HashMap<String, HashMap<String, u64>>,
+    /// Interval between stuck-state checks, in seconds
+    pub timeout_check_interval: u64,
+    /// Writes kept in memory while etcd is unavailable
+    pub write_buffer: usize,
+    /// Interval between attempts to flush buffered writes, in milliseconds
+    pub write_flush_interval_ms: u64,
+    /// Milliseconds after a state write during which further writes of the
+    /// state are coalesced into one, 0 writes every change at once
+    pub write_coalesce_window_ms: u64,
+    /// Seconds a written state is trusted to be unchanged in storage, 0
+    /// writes every state even when unchanged
+    pub write_cache_ttl_secs: u64,
+    /// Where states are kept: etcd, or embedded for a local database
+    pub storage_backend: String,
+    /// Directory of the embedded database
+    pub storage_path: String,
+    /// Interval between reloads of scheduled scenarios, in seconds
+    pub schedule_refresh_secs: u64,
+    /// Interval between drift checks of desired and actual states, in seconds, 0 disables them
+    pub drift_check_interval_secs: u64,
+    /// Whether a periodic drift check asks the ActionController to reconcile drifted scenarios
+    pub drift_auto_correct: bool,
+    /// External endpoints notified of state transitions
+    pub hooks: Vec<HookSettings>,
+    /// Hook deliveries kept for retry while their endpoint is unreachable
+    pub hook_retry_buffer: usize,
+    /// Total attempts of a hook delivery before it is dropped
+    pub hook_retry_attempts: u32,
+    /// Milliseconds allowed from the receipt of a StateChange to the
+    /// persistence of the new state, keyed by resource type then ASIL level
+    /// of the owning scenario, e.g. `package: { qm: 1000, d: 50 }`
+    pub timing_budgets_ms: HashMap<String, HashMap<String, u64>>,
+    /// File the processed StateChange and ContainerList messages are recorded
+    /// to for a later replay, empty to record nothing
+    pub record_path: String,
+    /// Whether instances sharing one etcd elect a leader, only the leader
+    /// processes requests and writes states
+    pub leader_election: bool,
+    /// Seconds a leader stays elected without renewing its lease
+    pub lease_ttl_secs: u64,
+    /// Name of this instance in the election, `{hostname}-{pid}` when empty
+    pub instance_id: String,
+    /// Failed StateChanges kept in the dead-letter queue before an alert is raised
+    pub dead_letter_alert_threshold: usize,
+    /// Requests per second a peer may send to the StateManager, 0 for no limit
+    pub rate_limit_per_sec: f64,
+    /// Requests a peer may send at once above its rate
+    pub rate_limit_burst: u32,
+    /// Restarts of a container within the crash loop window that make it
+    /// crash loop, 0 leaves it to the restart limit of the NodeAgent
+    pub crash_loop_restarts: u32,
+    /// OOM kills of a container within the crash loop window that make it
+    /// crash loop, 0 for no limit
+    pub crash_loop_oom_kills: u32,
+    /// Window the restarts and OOM kills of a container are counted in, in seconds
+    pub crash_loop_window_secs: u64,
+    /// Seconds a container failing transiently has to recover before its
+    /// model is dead, 0 makes every failure count at once
+    pub failure_grace_secs: u64,
+    /// etcd key of transition tables overriding the built-in ones
+    pub transition_table_key: String,
+    /// YAML or TOML file of transition tables, used when the key is not set
+    pub transition_table_path: String,
+    /// States read at once when the packages of changed models are evaluated
+    pub cascade_concurrency: usize,
+    /// File of the key the safety event log is hashed with, kept outside the
+    /// storage, empty to hash without a key
+    pub safety_log_key_path: String,
+    /// Safety event log records kept, older ones are pruned, 0 keeps all
+    pub safety_log_retention: u64,
+}
+
+impl Default for StateManagerSettings {
+    fn default() -> Self {
+        Self {
+            container_buffer: 100,
+            container_overflow: String::from("drop_oldest"),
+            state_change_buffer: 100,
+            state_change_overflow: String::from("reject"),
+            container_update_buffer: 100,
+            container_update_overflow: String::from("reject"),
+            state_timeouts: HashMap::from([
+                (
+                    String::from("scenario"),
+                    HashMap::from([
+                        (String::from("satisfied"), 30),
+                        (String::from("allowed"), 120),
+                    ]),
+                ),
+                (
+                    String::from("model"),
+                    HashMap::from([(String::from("created"), 120)]),
+                ),
+                (
+                    String::from("network"),
+                    HashMap::from([(String::from("requested"), 60)]),
+                ),
+            ]),
+            timeout_check_interval: 5,
+            write_buffer: 1000,
+            write_flush_interval_ms: 1000,
+            write_coalesce_window_ms: 200,
+            write_cache_ttl_secs: 30,
+            storage_backend: String::from("etcd"),
+            storage_path: String::from("/var/lib/piccolo/statemanager"),
+            schedule_refresh_secs: 10,
+            drift_check_interval_secs: 60,
+            drift_auto_correct: false,
+            hooks: Vec::new(),
+            hook_retry_buffer: 100,
+            hook_retry_attempts: 5,
+            timing_budgets_ms: ["scenario", "package", "model"]
+                .into_iter()
+                .map(|resource_type| {
+                    (
+                        String::from(resource_type),
+                        HashMap::from([
+                            (String::from("qm"), 1000),
+                            (String::from("a"), 500),
+                            (String::from("b"), 200),
+                            (String::from("c"), 100),
+                            (String::from("d"), 50),
+                        ]),
+                    )
+                })
+                .collect(),
+            record_path: String::new(),
+            leader_election: false,
+            lease_ttl_secs: 10,
+            instance_id: String::new(),
+            dead_letter_alert_threshold: 20,
+            rate_limit_per_sec: 50.0,
+            rate_limit_burst: 100,
+            crash_loop_restarts: 5,
+            crash_loop_oom_kills: 3,
+            crash_loop_window_secs: 600,
+            failure_grace_secs: 10,
+            transition_table_key: String::from("/statemanager/transitions"),
+            transition_table_path: String::from("/etc/piccolo/transitions.yaml"),
+            cascade_concurrency: 8,
+            safety_log_key_path: String::new(),
+            safety_log_retention: 100_000,
+        }
+    }
+}
+
+/// Webhook or MQTT topic notified of selected state transitions
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HookSettings {
+    pub name: String,
+    /// Transitions as `{resource type}:{new state}`, either part may be `*`;
+    /// package errors, denied scenarios and crash looping models when empty
+    pub events: Vec<String>,
+    /// Names of the resources notified, all of them when empty
+    pub resources: Vec<String>,
+    /// URL the payload is posted to
+    pub webhook: Option<String>,
+    /// MQTT broker as `host:port` the payload is published to
+    pub mqtt_broker: Option<String>,
+    pub mqtt_topic: String,
+    /// Payload with `{resource_type}`, `{resource_name}`, `{from_state}`,
+    /// `{to_state}`, `{transition_id}` and `{timestamp}` placeholders, a JSON
+    /// object of these fields when not set. The placeholders of a JSON
+    /// template are only filled in its strings, escaped.
+    pub template: Option<String>,
+}
+
+/// Export of state history to a fleet backend by the statemanager
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    /// URL batches are posted to, export is disabled when empty
+    pub endpoint: String,
+    /// Encoding of the batches: protobuf (gzip compressed) or json
+    pub format: String,
+    /// Interval between uploads, in seconds
+    pub interval_secs: u64,
+    /// Records kept for the next batch, older ones are dropped beyond it
+    pub batch_size: usize,
+    /// Directory batches are spooled to while the backend is unreachable
+    pub spool_path: String,
+    /// Spooled batches kept, older ones are deleted beyond it
+    pub spool_limit: usize,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            format: String::from("protobuf"),
+            interval_secs: 60,
+            batch_size: 1000,
+            spool_path: String::from("/var/lib/piccolo/telemetry"),
+            spool_limit: 100,
+        }
+    }
+}
+
+/// Retry and circuit breaker parameters of calls between components
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct GrpcSettings {
+    /// Total attempts of a call whose server is unreachable or overloaded
+    pub retry_attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled for each further retry
+    pub initial_backoff_ms: u64,
+    /// Upper bound of the delay between retries, in milliseconds
+    pub max_backoff_ms: u64,
+    /// Time allowed for a single attempt, in milliseconds
+    pub deadline_ms: u64,
+    /// Consecutive transport failures after which calls to a server are refused
+    pub breaker_failure_threshold: u32,
+    /// Seconds calls stay refused before a probe call is let through
+    pub breaker_open_secs: u64,
+}
+
+impl Default for GrpcSettings {
+    fn default() -> Self {
+        Self {
+            retry_attempts: 3,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 2000,
+            deadline_ms: 5000,
+            breaker_failure_threshold: 5,
+            breaker_open_secs: 30,
+        }
+    }
+}
+
+/// TLS of the gRPC connections between components
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct TlsSettings {
+    /// disabled, permissive or strict
+    pub mode: String,
+    /// PEM file of the CA that signs every component certificate
+    pub ca_cert: String,
+    /// PEM certificate of this component
+    pub cert: String,
+    /// PEM private key of this component
+    pub key: String,
+    /// Name the server certificates are issued for
+    pub server_name: String,
+    /// SPIFFE-style identities accepted from clients, empty to accept any
+    /// certificate signed by the CA
+    pub allowed_peers: Vec<String>,
+}
+
+impl Default for TlsSettings {
+    fn default() -> Self {
+        Self {
+            mode: String::from("disabled"),
+            ca_cert: String::from("/etc/piccolo/tls/ca.pem"),
+            cert: String::from("/etc/piccolo/tls/cert.pem"),
+            key: String::from("/etc/piccolo/tls/key.pem"),
+            server_name: String::from("piccolo"),
+            allowed_peers: Vec::new(),
+        }
+    }
+}
+
+/// Callers allowed to use the StateManager and ApiServer APIs
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Whether callers must authenticate
+    pub enabled: bool,
+    /// Token this component presents to the servers it calls
+    pub token: String,
+    /// Principals of static tokens, keyed by token
+    pub tokens: HashMap<String, PrincipalSettings>,
+    /// Principals of TLS client certificates, keyed by SPIFFE-style identity
+    pub identities: HashMap<String, PrincipalSettings>,
+}
+
+/// Component name and role of an authenticated caller
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PrincipalSettings {
+    pub name: String,
+    /// read-only, operator or admin
+    pub role: String,
+    /// Namespaces the caller may act on, all of them when empty
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+/// Endpoints and retry parameters of the etcd (RocksDB service) client
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct EtcdSettings {
+    /// Service URIs tried in order, the `ROCKSDB_SERVICE_URL` environment
+    /// variable (comma separated) takes precedence
+    pub endpoints: Vec<String>,
+    /// Time allowed for one operation on one endpoint, in milliseconds
+    pub timeout_ms: u64,
+    /// Rounds over all endpoints before an operation fails
+    pub retry_attempts: u32,
+    /// Delay before the second round in milliseconds, doubled for each further round
+    pub initial_backoff_ms: u64,
+    /// Upper bound of the delay between rounds, in milliseconds
+    pub max_backoff_ms: u64,
+}
+
+impl Default for EtcdSettings {
+    fn default() -> Self {
+        Self {
+            endpoints: vec![String::from("http://localhost:47007")],
+            timeout_ms: 2000,
+            retry_attempts: 2,
+            initial_backoff_ms: 50,
+            max_backoff_ms: 1000,
+        }
+    }
+}
+
+/// Health reporting of long-running components
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct HealthSettings {
+    /// Whether components serve `/healthz` and `/readyz` over HTTP
+    pub http_enabled: bool,
+    /// Seconds between updates of the gRPC health status
+    pub interval_secs: u64,
+    /// Seconds a processing loop may spend on one item before it is stalled
+    pub stall_secs: u64,
+    /// Milliseconds to wait for the etcd check
+    pub etcd_timeout_ms: u64,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self {
+            http_enabled: true,
+            interval_secs: 5,
+            stall_secs: 60,
+            etcd_timeout_ms: 1000,
+        }
+    }
+}
+
+/// Wait of a component for its dependencies before it serves requests
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct StartupSettings {
+    /// Delay before the second check of a dependency in milliseconds, doubled for each further check
+    pub initial_backoff_ms: u64,
+    /// Upper bound of the delay between checks, in milliseconds
+    pub max_backoff_ms: u64,
+    /// Seconds to wait for the dependencies, 0 to wait until they are available
+    pub timeout_secs: u64,
+    /// Start in degraded mode when the wait times out instead of exiting
+    pub degraded_on_timeout: bool,
+}
+
+impl Default for StartupSettings {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 200,
+            max_backoff_ms: 5000,
+            timeout_secs: 60,
+            degraded_on_timeout: false,
+        }
+    }
+}
+
+/// Garbage collection of etcd keys left behind by removed artifacts
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct GcSettings {
+    pub enabled: bool,
+    /// Seconds between two collections
+    pub interval_secs: u64,
+    /// Seconds a key must stay orphaned before it is deleted
+    pub grace_secs: u64,
+}
+
+impl Default for GcSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 600,
+            grace_secs: 3600,
+        }
+    }
+}
+
+/// Messages written to the log
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct LoggingSettings {
+    /// Lowest level logged: verbose, debug, info, warn, error or fatal
+    pub level: String,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: String::from("verbose"),
+        }
+    }
+}
+
+impl LoggingSettings {
+    /// Lowest `logd!` level code logged, every level when unknown
+    pub fn min_level(&self) -> i32 {
+        match self.level.to_lowercase().as_str() {
+            "debug" => 2,
+            "info" => 3,
+            "warn" => 4,
+            "error" => 5,
+            "fatal" => 6,
+            _ => 1,
+        }
+    }
+}
+
+/// Vehicle buses the filtergateway receives condition signals from
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct FilterGatewaySettings {
+    /// SocketCAN interface frames of CAN operands are read from
+    pub can_interface: String,
+    /// UDP `address:port` SOME/IP messages of SOME/IP operands are received on
+    pub someip_address: String,
+    /// Whether `PushSignal` calls are accepted, for tests and simulators
+    pub push_signals: bool,
+}
+
+impl Default for FilterGatewaySettings {
+    fn default() -> Self {
+        Self {
+            can_interface: String::from("can0"),
+            someip_address: String::from("0.0.0.0:30490"),
+            push_signals: false,
+        }
+    }
+}
+
+/// Limits of the artifacts applied through the ApiServer
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct ArtifactSettings {
+    /// Largest request body, in bytes, also of a decompressed bulk tarball
+    pub max_body_bytes: usize,
+    /// Most artifact documents in a request
+    pub max_documents: usize,
+    /// Largest artifact document, in bytes
+    pub max_document_bytes: usize,
+}
+
+impl Default for ArtifactSettings {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            max_documents: 64,
+            max_document_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// Addresses of the servers, see [`crate::address`]
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AddressSettings {
+    /// IP the servers of the components are opened on, `host.ip` when empty
+    pub bind_ip: String,
+    /// Port of a service by name, e.g. `statemanager` or `pharos`
+    pub ports: HashMap<String, u16>,
+    /// Host an external service (`timpani`, `pharos`) is reached at,
+    /// `host.ip` when not listed
+    pub hosts: HashMap<String, String>,
+}
+
+fn default_settings() -> Settings {
+    Settings {
         host: HostSettings {
             name: String::from("HPC"),
             ip: String::from("0.0.0.0"),
             r#type: String::from("nodeagent"),
             role: String::from("master"),
         },
-    };
+        liveness: LivenessSettings::default(),
+        policy: PolicySettings::default(),
+        placement: PlacementSettings::default(),
+        statemanager: StateManagerSettings::default(),
+        grpc: GrpcSettings::default(),
+        tls: TlsSettings::default(),
+        auth: AuthSettings::default(),
+        health: HealthSettings::default(),
+        startup: StartupSettings::default(),
+        gc: GcSettings::default(),
+        etcd: EtcdSettings::default(),
+        telemetry: TelemetrySettings::default(),
+        logging: LoggingSettings::default(),
+        filtergateway: FilterGatewaySettings::default(),
+        artifact: ArtifactSettings::default(),
+        addresses: AddressSettings::default(),
+    }
+}
 
-    let settings = config::Config::builder()
-        .add_source(config::File::with_name("/etc/piccolo/settings.yaml"))
-        .build();
+/// Read the settings of a file, without falling back to the defaults
+fn read_settings(path: &str) -> Result<Settings, String> {
+    config::Config::builder()
+        .add_source(config::File::with_name(path))
+        .build()
+        .and_then(|settings| settings.try_deserialize::<Settings>())
+        .map_err(|e| format!("Cannot read {}: {}", path, e))
+}
 
-    match settings {
-        Ok(result) => result
-            .try_deserialize::<Settings>()
-            .unwrap_or(default_settings),
-        Err(_) => default_settings,
-    }
+fn parse_settings_yaml() -> Settings {
+    read_settings(SETTINGS_PATH).unwrap_or_else(|_| default_settings())
 }
 
+fn settings() -> &'static RwLock<&'static Settings> {
+    SETTINGS.get_or_init(|| RwLock::new(Box::leak(Box::new(parse_settings_yaml()))))
+}
+
+/// Current settings
+///
+/// The reference stays valid after a reload, but keeps the values it was
+/// taken with: call this at use time instead of keeping the result.
 pub fn get_config() -> &'static Settings {
-    SETTINGS.get_or_init(parse_settings_yaml)
+    *settings().read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Number of reloads applied since startup, for components caching settings
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// Outcome of a reload, as `section.field` paths of the changed settings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReloadReport {
+    /// Settings that took their new value
+    pub applied: Vec<String>,
+    /// Settings that keep their old value until the component restarts
+    pub restart_required: Vec<String>,
+}
+
+fn is_hot_reloadable(path: &str) -> bool {
+    HOT_RELOADABLE
+        .iter()
+        .any(|hot| path == *hot || path.starts_with(&format!("{}.", hot)))
+}
+
+/// Settings from `current` updated with the hot-reloadable settings of `new`
+pub fn merge(current: &Settings, new: &Settings) -> Result<(Settings, ReloadReport), String> {
+    let mut merged = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    let new = serde_json::to_value(new).map_err(|e| e.to_string())?;
+    let mut report = ReloadReport::default();
+    if let (Some(merged_sections), Some(new_sections)) = (merged.as_object_mut(), new.as_object()) {
+        for (section, new_section) in new_sections {
+            let Some(fields) = merged_sections
+                .get_mut(section)
+                .and_then(|section| section.as_object_mut())
+            else {
+                continue;
+            };
+            for (field, value) in new_section.as_object().into_iter().flatten() {
+                if fields.get(field) == Some(value) {
+                    continue;
+                }
+                let path = format!("{}.{}", section, field);
+                if is_hot_reloadable(&path) {
+                    fields.insert(field.clone(), value.clone());
+                    report.applied.push(path);
+                } else {
+                    report.restart_required.push(path);
+                }
+            }
+        }
+    }
+    report.applied.sort();
+    report.restart_required.sort();
+    let merged = serde_json::from_value(merged).map_err(|e| e.to_string())?;
+    Ok((merged, report))
+}
+
+/// Re-read the settings file and apply its hot-reloadable settings
+///
+/// The current settings are kept when the file cannot be read.
+pub fn reload() -> Result<ReloadReport, String> {
+    let new = read_settings(SETTINGS_PATH)?;
+    let report = {
+        let mut current = settings().write().unwrap_or_else(|e| e.into_inner());
+        let (merged, report) = merge(&current, &new)?;
+        if !report.applied.is_empty() {
+            // Earlier settings may still be referenced, they are leaked
+            *current = Box::leak(Box::new(merged));
+            GENERATION.fetch_add(1, Ordering::AcqRel);
+        }
+        report
+    };
+    if !report.applied.is_empty() {
+        crate::logd!(3, "Settings reloaded: {}", report.applied.join(", "));
+    }
+    if !report.restart_required.is_empty() {
+        crate::logd!(
+            4,
+            "Settings changed but need a restart: {}",
+            report.restart_required.join(", ")
+        );
+    }
+    Ok(report)
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reload the settings whenever the settings file changes
+pub async fn watch() {
+    let mut last = modified(SETTINGS_PATH);
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        let current = modified(SETTINGS_PATH);
+        if current == last {
+            continue;
+        }
+        last = current;
+        if let Err(e) = reload() {
+            crate::logd!(4, "Settings not reloaded: {}", e);
+        }
+    }
 }
 
 //Unit Test Cases
@@ -62,6 +847,194 @@ mod tests {
 
     // Guest 설정 테스트 제거
 
+    // Test default liveness parameters when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_liveness() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.liveness.heartbeat_interval, 30);
+        assert_eq!(settings.liveness.miss_threshold, 3);
+    }
+
+    // Test default policy limits when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_policy() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.policy.max_asil_level, "D");
+        assert_eq!(settings.policy.cpu_budget_millis, 0);
+        assert_eq!(settings.policy.memory_budget_mb, 0);
+    }
+
+    // Test default statemanager channel settings when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_statemanager() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.statemanager.container_buffer, 100);
+        assert_eq!(settings.statemanager.container_overflow, "drop_oldest");
+        assert_eq!(settings.statemanager.state_change_buffer, 100);
+        assert_eq!(settings.statemanager.state_change_overflow, "reject");
+        assert_eq!(settings.statemanager.container_update_buffer, 100);
+        assert_eq!(settings.statemanager.container_update_overflow, "reject");
+        assert_eq!(
+            settings.statemanager.state_timeouts["scenario"]["satisfied"],
+            30
+        );
+        assert_eq!(
+            settings.statemanager.state_timeouts["model"]["created"],
+            120
+        );
+        assert_eq!(settings.statemanager.timeout_check_interval, 5);
+        assert_eq!(settings.statemanager.write_buffer, 1000);
+        assert_eq!(settings.statemanager.write_flush_interval_ms, 1000);
+        assert_eq!(settings.statemanager.write_coalesce_window_ms, 200);
+        assert_eq!(settings.statemanager.write_cache_ttl_secs, 30);
+        assert_eq!(settings.statemanager.storage_backend, "etcd");
+        assert_eq!(
+            settings.statemanager.storage_path,
+            "/var/lib/piccolo/statemanager"
+        );
+        assert_eq!(settings.statemanager.schedule_refresh_secs, 10);
+        assert_eq!(settings.statemanager.drift_check_interval_secs, 60);
+        assert!(!settings.statemanager.drift_auto_correct);
+        assert!(settings.statemanager.hooks.is_empty());
+        assert_eq!(settings.statemanager.hook_retry_buffer, 100);
+        assert_eq!(settings.statemanager.hook_retry_attempts, 5);
+        assert_eq!(
+            settings.statemanager.timing_budgets_ms["package"]["qm"],
+            1000
+        );
+        assert_eq!(settings.statemanager.timing_budgets_ms["model"]["d"], 50);
+        assert!(settings.statemanager.record_path.is_empty());
+        assert!(!settings.statemanager.leader_election);
+        assert_eq!(settings.statemanager.lease_ttl_secs, 10);
+        assert_eq!(settings.statemanager.dead_letter_alert_threshold, 20);
+        assert_eq!(settings.statemanager.rate_limit_per_sec, 50.0);
+        assert_eq!(settings.statemanager.rate_limit_burst, 100);
+        assert_eq!(settings.statemanager.crash_loop_restarts, 5);
+        assert_eq!(settings.statemanager.crash_loop_oom_kills, 3);
+        assert_eq!(settings.statemanager.crash_loop_window_secs, 600);
+        assert_eq!(settings.statemanager.failure_grace_secs, 10);
+        assert_eq!(
+            settings.statemanager.transition_table_key,
+            "/statemanager/transitions"
+        );
+        assert_eq!(
+            settings.statemanager.transition_table_path,
+            "/etc/piccolo/transitions.yaml"
+        );
+        assert_eq!(settings.statemanager.cascade_concurrency, 8);
+        assert!(settings.statemanager.safety_log_key_path.is_empty());
+        assert_eq!(settings.statemanager.safety_log_retention, 100_000);
+    }
+
+    // Test default retry and circuit breaker settings when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_grpc() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.grpc.retry_attempts, 3);
+        assert_eq!(settings.grpc.initial_backoff_ms, 100);
+        assert_eq!(settings.grpc.max_backoff_ms, 2000);
+        assert_eq!(settings.grpc.deadline_ms, 5000);
+        assert_eq!(settings.grpc.breaker_failure_threshold, 5);
+        assert_eq!(settings.grpc.breaker_open_secs, 30);
+    }
+
+    // Test that telemetry export is disabled when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_telemetry() {
+        let settings = parse_settings_yaml();
+        assert!(settings.telemetry.endpoint.is_empty());
+        assert_eq!(settings.telemetry.format, "protobuf");
+        assert_eq!(settings.telemetry.interval_secs, 60);
+        assert_eq!(settings.telemetry.spool_limit, 100);
+    }
+
+    // Test default placement label keys when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_placement() {
+        let settings = parse_settings_yaml();
+        let keys = ["gpu".to_string(), "vendor".to_string(), "zone".to_string()];
+        assert_eq!(settings.placement.unknown_keys(&keys), vec!["vendor"]);
+    }
+
+    // Test default vehicle bus settings when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_filtergateway() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.filtergateway.can_interface, "can0");
+        assert_eq!(settings.filtergateway.someip_address, "0.0.0.0:30490");
+        assert!(!settings.filtergateway.push_signals);
+    }
+
+    // Test that TLS is disabled when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_tls() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.tls.mode, "disabled");
+        assert_eq!(settings.tls.server_name, "piccolo");
+        assert!(settings.tls.allowed_peers.is_empty());
+    }
+
+    // Test that authentication is disabled when the section is omitted
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_auth() {
+        let settings = parse_settings_yaml();
+        assert!(!settings.auth.enabled);
+        assert!(settings.auth.token.is_empty());
+        assert!(settings.auth.tokens.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_health() {
+        let settings = parse_settings_yaml();
+        assert!(settings.health.http_enabled);
+        assert_eq!(settings.health.interval_secs, 5);
+        assert_eq!(settings.health.stall_secs, 60);
+        assert_eq!(settings.health.etcd_timeout_ms, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_startup() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.startup.initial_backoff_ms, 200);
+        assert_eq!(settings.startup.max_backoff_ms, 5000);
+        assert_eq!(settings.startup.timeout_secs, 60);
+        assert!(!settings.startup.degraded_on_timeout);
+    }
+
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_gc() {
+        let settings = parse_settings_yaml();
+        assert!(settings.gc.enabled);
+        assert_eq!(settings.gc.interval_secs, 600);
+        assert_eq!(settings.gc.grace_secs, 3600);
+    }
+
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_artifact() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.artifact.max_body_bytes, 1024 * 1024);
+        assert_eq!(settings.artifact.max_documents, 64);
+        assert_eq!(settings.artifact.max_document_bytes, 256 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_addresses() {
+        let settings = parse_settings_yaml();
+        assert!(settings.addresses.bind_ip.is_empty());
+        assert!(settings.addresses.ports.is_empty());
+        assert!(settings.addresses.hosts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_settings_yaml_default_etcd() {
+        let settings = parse_settings_yaml();
+        assert_eq!(settings.etcd.endpoints, vec!["http://localhost:47007"]);
+        assert_eq!(settings.etcd.timeout_ms, 2000);
+        assert_eq!(settings.etcd.retry_attempts, 2);
+        assert_eq!(settings.etcd.initial_backoff_ms, 50);
+        assert_eq!(settings.etcd.max_backoff_ms, 1000);
+    }
+
     // Test lazy initialization of configuration
     #[tokio::test]
     async fn test_get_config_lazy_initialization() {
@@ -149,4 +1122,56 @@ mod tests {
     }
 
     // Guest 관련 테스트 제거
+
+    // Test that a reload only applies the hot-reloadable settings
+    #[test]
+    fn test_merge_applies_hot_settings_only() {
+        let current = default_settings();
+        let mut new = default_settings();
+        new.logging.level = String::from("warn");
+        new.statemanager.timeout_check_interval = 1;
+        new.statemanager
+            .state_timeouts
+            .entry(String::from("model"))
+            .or_default()
+            .insert(String::from("created"), 10);
+        new.statemanager.container_buffer = 500;
+        new.host.ip = String::from("10.0.0.1");
+
+        let (merged, report) = merge(&current, &new).unwrap();
+        assert_eq!(
+            report.applied,
+            vec![
+                "logging.level",
+                "statemanager.state_timeouts",
+                "statemanager.timeout_check_interval"
+            ]
+        );
+        assert_eq!(
+            report.restart_required,
+            vec!["host.ip", "statemanager.container_buffer"]
+        );
+        assert_eq!(merged.logging.min_level(), 4);
+        assert_eq!(merged.statemanager.timeout_check_interval, 1);
+        assert_eq!(merged.statemanager.state_timeouts["model"]["created"], 10);
+        assert_eq!(merged.statemanager.container_buffer, 100);
+        assert_eq!(merged.host.ip, "0.0.0.0");
+
+        let (_, unchanged) = merge(&merged, &merged).unwrap();
+        assert_eq!(unchanged, ReloadReport::default());
+    }
+
+    #[test]
+    fn test_logging_min_level() {
+        assert_eq!(default_settings().logging.min_level(), 1);
+        let level = |level: &str| {
+            LoggingSettings {
+                level: String::from(level),
+            }
+            .min_level()
+        };
+        assert_eq!(level("Info"), 3);
+        assert_eq!(level("fatal"), 6);
+        assert_eq!(level("loud"), 1);
+    }
 }
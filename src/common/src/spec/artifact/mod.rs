@@ -14,6 +14,14 @@ use serde::{Deserialize, Serialize};
 
 pub trait Artifact {
     fn get_name(&self) -> String;
+
+    /// Namespace of `metadata.namespace`, the default namespace without it
+    fn get_namespace(&self) -> String;
+
+    /// Name the artifact and its state are stored under, see [`crate::namespace`]
+    fn get_qualified_name(&self) -> String {
+        crate::namespace::qualify(&self.get_namespace(), &self.get_name())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
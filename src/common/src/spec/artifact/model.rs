@@ -11,6 +11,10 @@ impl Artifact for Model {
     fn get_name(&self) -> String {
         self.metadata.name.clone()
     }
+
+    fn get_namespace(&self) -> String {
+        self.metadata.get_namespace()
+    }
 }
 
 impl Model {
@@ -21,6 +25,14 @@ impl Model {
     pub fn get_podspec(&self) -> ModelSpec {
         self.spec.clone()
     }
+
+    pub fn get_labels(&self) -> std::collections::HashMap<String, String> {
+        self.metadata.get_labels()
+    }
+
+    pub fn get_annotations(&self) -> std::collections::HashMap<String, String> {
+        self.metadata.get_annotations()
+    }
 }
 
 //Unit Test Cases
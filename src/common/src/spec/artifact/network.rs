@@ -9,6 +9,10 @@ impl Artifact for Network {
     fn get_name(&self) -> String {
         self.metadata.name.clone()
     }
+
+    fn get_namespace(&self) -> String {
+        self.metadata.get_namespace()
+    }
 }
 
 impl Network {
@@ -41,6 +45,7 @@ mod tests {
             kind: "Network".to_string(),
             metadata: MetaData {
                 name: name.to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
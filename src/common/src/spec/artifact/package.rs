@@ -2,18 +2,52 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
+use super::scenario::DesiredState;
 use super::Artifact;
 use super::Package;
+use crate::external::timpani::{validate_task_constraints, SchedPolicy, TaskInfo};
 
 impl Artifact for Package {
     fn get_name(&self) -> String {
         self.metadata.name.clone()
     }
+
+    fn get_namespace(&self) -> String {
+        self.metadata.get_namespace()
+    }
 }
 
 impl Package {
-    pub fn get_models(&self) -> &Vec<ModelInfo> {
-        &self.spec.models
+    /// Models of the package, with their names and the volumes and networks
+    /// they use qualified with the namespace of the package
+    pub fn get_models(&self) -> Vec<ModelInfo> {
+        let namespace = self.get_namespace();
+        self.spec
+            .models
+            .iter()
+            .map(|model| model.qualified(&namespace))
+            .collect()
+    }
+
+    /// Replicas of the models of the package, see [`ModelInfo::get_replicas`]
+    pub fn get_replicas(&self) -> Vec<ModelInfo> {
+        self.get_models()
+            .iter()
+            .flat_map(ModelInfo::get_replicas)
+            .collect()
+    }
+
+    pub fn get_labels(&self) -> std::collections::HashMap<String, String> {
+        self.metadata.get_labels()
+    }
+
+    pub fn get_strategy(&self) -> &UpdateStrategy {
+        &self.spec.strategy
+    }
+
+    /// State the models are kept in once deployed, if declared
+    pub fn get_desired_state(&self) -> Option<DesiredState> {
+        self.spec.desired_state
     }
 }
 
@@ -21,6 +55,68 @@ impl Package {
 pub struct PackageSpec {
     pattern: Vec<Pattern>,
     models: Vec<ModelInfo>,
+    #[serde(default)]
+    strategy: UpdateStrategy,
+    #[serde(default, rename = "desiredState")]
+    desired_state: Option<DesiredState>,
+}
+
+/// How an update replaces the running models of a package
+#[derive(Clone, Debug, serde::Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct UpdateStrategy {
+    pub r#type: UpdateStrategyType,
+    /// Seconds an updated model may take to run before the update fails
+    pub health_timeout: u64,
+    /// Evaluation of the canary, used by the `canary` type only
+    pub canary: CanarySpec,
+}
+
+impl Default for UpdateStrategy {
+    fn default() -> Self {
+        Self {
+            r#type: UpdateStrategyType::Recreate,
+            health_timeout: 60,
+            canary: CanarySpec::default(),
+        }
+    }
+}
+
+/// How long a canary runs and what it takes to promote it
+#[derive(Clone, Debug, serde::Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CanarySpec {
+    /// Seconds the canary runs before it is promoted
+    pub bake_time: u64,
+    /// Restarts of a canary model tolerated during the bake time
+    pub max_restarts: u32,
+    /// Command run in the first container of every canary, it fails the
+    /// canary on a non-zero exit
+    pub probe: Vec<String>,
+}
+
+impl Default for CanarySpec {
+    fn default() -> Self {
+        Self {
+            bake_time: 30,
+            max_restarts: 0,
+            probe: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateStrategyType {
+    /// Restart every model at once
+    #[default]
+    Recreate,
+    /// Restart one model at a time, each one running before the next
+    Rolling,
+    /// Run a copy of every model, then move the models over and retire the copies
+    BlueGreen,
+    /// Run a copy of every model for a bake time, then promote or roll it back
+    Canary,
 }
 
 #[derive(Debug, serde::Deserialize, PartialEq)]
@@ -28,11 +124,23 @@ struct Pattern {
     r#type: String,
 }
 
-#[derive(Debug, serde::Deserialize, PartialEq)]
+#[derive(Clone, Debug, serde::Deserialize, PartialEq)]
 pub struct ModelInfo {
     name: String,
     node: String,
     resources: Resource,
+    /// Instances of the model, each on its own Pod
+    #[serde(default)]
+    replicas: Option<u32>,
+    /// Further nodes the replicas are spread over, after `node`
+    #[serde(default)]
+    nodes: Vec<String>,
+    /// Node a paused standby of the model waits on, to take over from it
+    #[serde(default)]
+    standby: Option<String>,
+    /// Replica this model stands for, see [`ModelInfo::get_replicas`]
+    #[serde(skip)]
+    replica: Option<u32>,
 }
 
 impl ModelInfo {
@@ -47,13 +155,108 @@ impl ModelInfo {
     pub fn get_resources(&self) -> Resource {
         self.resources.clone()
     }
+
+    /// Node of the warm standby of the model, if declared
+    pub fn get_standby_node(&self) -> Option<String> {
+        self.standby.clone()
+    }
+
+    /// Name of the Pod of the warm standby of the model
+    pub fn get_standby_pod_name(&self) -> String {
+        crate::spec::k8s::pod::standby_name(&self.get_pod_name())
+    }
+
+    /// Number of instances of the model, 1 unless declared
+    pub fn get_replica_count(&self) -> u32 {
+        self.replicas.unwrap_or(1)
+    }
+
+    /// The model once per replica, placed on the node of the replica
+    ///
+    /// Replicas go to `node` and `nodes` in turn. A model without replicas
+    /// is returned as it is.
+    pub fn get_replicas(&self) -> Vec<ModelInfo> {
+        let count = self.get_replica_count();
+        if count == 1 {
+            return vec![self.clone()];
+        }
+        let nodes: Vec<&String> = std::iter::once(&self.node).chain(&self.nodes).collect();
+        (0..count)
+            .map(|index| ModelInfo {
+                node: nodes[index as usize % nodes.len()].clone(),
+                replicas: Some(1),
+                nodes: Vec::new(),
+                standby: None,
+                replica: Some(index),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Index of the replica returned by [`ModelInfo::get_replicas`], `None`
+    /// for a model without replicas
+    pub fn get_replica(&self) -> Option<u32> {
+        self.replica
+    }
+
+    /// Name of the Pod of the model, or of its replica
+    pub fn get_pod_name(&self) -> String {
+        match self.replica {
+            Some(index) => crate::spec::k8s::pod::replica_name(&self.name, index),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Check the declared replicas
+    pub fn validate_replicas(&self) -> Result<(), String> {
+        if self.replicas == Some(0) {
+            return Err("replicas must be at least 1".to_string());
+        }
+        if !self.nodes.is_empty() && self.get_replica_count() == 1 {
+            return Err("nodes are only used by a model with replicas".to_string());
+        }
+        Ok(())
+    }
+
+    /// Check the declared standby
+    ///
+    /// A standby takes over a single instance, on a node of its own.
+    pub fn validate_standby(&self) -> Result<(), String> {
+        let Some(standby) = &self.standby else {
+            return Ok(());
+        };
+        if *standby == self.node {
+            return Err(format!(
+                "standby must be on another node than {}",
+                self.node
+            ));
+        }
+        if self.get_replica_count() > 1 {
+            return Err("a model with replicas cannot have a standby".to_string());
+        }
+        Ok(())
+    }
+
+    /// The model with its references resolved in a namespace
+    fn qualified(&self, namespace: &str) -> ModelInfo {
+        let qualify = |name: &String| crate::namespace::qualify(namespace, name);
+        ModelInfo {
+            name: qualify(&self.name),
+            resources: Resource {
+                volume: self.resources.volume.as_ref().map(qualify),
+                network: self.resources.network.as_ref().map(qualify),
+                realtime: self.resources.realtime.clone(),
+            },
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize, PartialEq)]
 pub struct Resource {
     volume: Option<String>,
     network: Option<String>,
-    realtime: Option<bool>,
+    realtime: Option<Realtime>,
 }
 
 impl Resource {
@@ -64,7 +267,221 @@ impl Resource {
         self.network.clone()
     }
     pub fn get_realtime(&self) -> Option<bool> {
-        self.realtime
+        self.realtime.as_ref().map(|realtime| match realtime {
+            Realtime::Enabled(enabled) => *enabled,
+            Realtime::Scheduled(_) => true,
+        })
+    }
+
+    /// Scheduling parameters of a realtime model, `None` if it is not realtime
+    pub fn get_realtime_spec(&self) -> Option<RealtimeSpec> {
+        match self.realtime.as_ref()? {
+            Realtime::Enabled(true) => Some(RealtimeSpec::default()),
+            Realtime::Enabled(false) => None,
+            Realtime::Scheduled(spec) => Some(spec.clone()),
+        }
+    }
+}
+
+/// Realtime scheduling of a model
+///
+/// `realtime: true` schedules the model with the default parameters, a block
+/// sets them explicitly.
+#[derive(Clone, Debug, serde::Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Realtime {
+    Enabled(bool),
+    Scheduled(RealtimeSpec),
+}
+
+/// Scheduling parameters given to Timpani, times in microseconds
+#[derive(Clone, Debug, serde::Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RealtimeSpec {
+    /// `normal`, `fifo` or `rr`
+    pub policy: String,
+    pub priority: i32,
+    pub period: i32,
+    pub runtime: i32,
+    pub deadline: i32,
+    pub release_time: i32,
+    /// Bit mask of the CPUs the task may run on
+    pub cpu_affinity: u64,
+    /// Deadline misses tolerated before Timpani reports a fault
+    pub max_dmiss: i32,
+}
+
+impl Default for RealtimeSpec {
+    fn default() -> Self {
+        Self {
+            policy: "fifo".to_string(),
+            priority: 50,
+            period: 10000,
+            runtime: 5000,
+            deadline: 10000,
+            release_time: 0,
+            cpu_affinity: 7,
+            max_dmiss: 3,
+        }
+    }
+}
+
+impl RealtimeSpec {
+    pub fn get_policy(&self) -> Option<SchedPolicy> {
+        match self.policy.to_lowercase().as_str() {
+            "normal" => Some(SchedPolicy::Normal),
+            "fifo" => Some(SchedPolicy::Fifo),
+            "rr" => Some(SchedPolicy::Rr),
+            _ => None,
+        }
+    }
+
+    /// Timpani task of the model's process on a node
+    pub fn to_task_info(&self, name: &str, node_id: &str) -> TaskInfo {
+        TaskInfo {
+            name: name.to_string(),
+            priority: self.priority,
+            policy: self.get_policy().unwrap_or(SchedPolicy::Normal) as i32,
+            cpu_affinity: self.cpu_affinity,
+            period: self.period,
+            release_time: self.release_time,
+            runtime: self.runtime,
+            deadline: self.deadline,
+            node_id: node_id.to_string(),
+            max_dmiss: self.max_dmiss,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let policy = self
+            .get_policy()
+            .ok_or(format!("unknown scheduling policy '{}'", self.policy))?;
+        let priorities = match policy {
+            SchedPolicy::Normal => 0..=0,
+            SchedPolicy::Fifo | SchedPolicy::Rr => 1..=99,
+        };
+        if !priorities.contains(&self.priority) {
+            return Err(format!(
+                "priority {} is out of range {:?} for policy '{}'",
+                self.priority, priorities, self.policy
+            ));
+        }
+        if self.cpu_affinity == 0 {
+            return Err("cpuAffinity must select at least one CPU".to_string());
+        }
+        if self.period <= 0 || self.release_time < 0 || self.max_dmiss < 0 {
+            return Err(
+                "period must be positive, releaseTime and maxDmiss not negative".to_string(),
+            );
+        }
+        if !validate_task_constraints(&self.to_task_info("", "")) {
+            return Err(format!(
+                "releaseTime {} <= runtime {} <= deadline {} <= period {} does not hold",
+                self.release_time, self.runtime, self.deadline, self.period
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Pods of a package a re-apply added, changed or removed
+///
+/// The ApiServer compares the Pods and nodes of the models of a package
+/// with the ones stored before it is applied again and keeps the result
+/// under [`PackageDiff::key`], for the next update of the package to
+/// restart only the Pods that changed.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct PackageDiff {
+    pub package: String,
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+impl PackageDiff {
+    /// Compare the Pods of a package, by Pod name with their node and yaml
+    pub fn between(
+        package: &str,
+        previous: &std::collections::BTreeMap<String, (String, String)>,
+        next: &std::collections::BTreeMap<String, (String, String)>,
+    ) -> Self {
+        let mut diff = PackageDiff {
+            package: package.to_string(),
+            ..Default::default()
+        };
+        for (pod, deployed) in next {
+            match previous.get(pod) {
+                None => diff.added.push(pod.clone()),
+                Some(stored) if stored != deployed => diff.changed.push(pod.clone()),
+                Some(_) => diff.unchanged.push(pod.clone()),
+            }
+        }
+        diff.removed = previous
+            .keys()
+            .filter(|pod| !next.contains_key(*pod))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Diff of this apply followed by `later`, for an apply that comes
+    /// before the update of the previous one took its diff
+    ///
+    /// Pods either apply added or changed are redeployed, Pods added by
+    /// this apply stay added, and Pods added then removed again are left
+    /// out, as they were never deployed.
+    pub fn then(&self, later: &PackageDiff) -> Self {
+        let mut diff = PackageDiff {
+            package: later.package.clone(),
+            ..Default::default()
+        };
+        let was_added = |pod: &String| self.added.contains(pod);
+        for pod in &later.added {
+            if self.removed.contains(pod) {
+                diff.changed.push(pod.clone());
+            } else {
+                diff.added.push(pod.clone());
+            }
+        }
+        for pod in later.changed.iter().chain(&later.unchanged) {
+            if was_added(pod) {
+                diff.added.push(pod.clone());
+            } else if later.changed.contains(pod) || self.changed.contains(pod) {
+                diff.changed.push(pod.clone());
+            } else {
+                diff.unchanged.push(pod.clone());
+            }
+        }
+        diff.removed = later
+            .removed
+            .iter()
+            .filter(|pod| !was_added(pod))
+            .chain(self.removed.iter().filter(|pod| !later.added.contains(pod)))
+            .cloned()
+            .collect();
+        for pods in [
+            &mut diff.added,
+            &mut diff.changed,
+            &mut diff.removed,
+            &mut diff.unchanged,
+        ] {
+            pods.sort();
+        }
+        diff
+    }
+
+    /// Key the diff of the last apply of a package is stored under
+    pub fn key(package: &str) -> String {
+        format!("/package/{}/diff", package)
+    }
+
+    /// Whether an update restarts the Pod of `pod_name`
+    pub fn redeploys(&self, pod_name: &str) -> bool {
+        self.added
+            .iter()
+            .chain(&self.changed)
+            .any(|pod| pod == pod_name)
     }
 }
 
@@ -98,6 +515,7 @@ mod tests {
             kind: "Package".to_string(),
             metadata: MetaData {
                 name: "test-package".to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
@@ -119,6 +537,10 @@ mod tests {
                             network: Some("net1".to_string()),
                             realtime: None,
                         },
+                        replicas: None,
+                        nodes: Vec::new(),
+                        standby: None,
+                        replica: None,
                     },
                     ModelInfo {
                         name: "model2".to_string(),
@@ -128,8 +550,14 @@ mod tests {
                             network: None,
                             realtime: None,
                         },
+                        replicas: None,
+                        nodes: Vec::new(),
+                        standby: None,
+                        replica: None,
                     },
                 ],
+                strategy: UpdateStrategy::default(),
+                desired_state: None,
             },
             status: Some(PackageStatus {
                 status: vec![
@@ -171,6 +599,10 @@ mod tests {
                 network: Some("test-net".to_string()),
                 realtime: None,
             },
+            replicas: None,
+            nodes: Vec::new(),
+            standby: None,
+            replica: None,
         };
 
         assert_eq!(model.get_name(), "test-model");
@@ -181,6 +613,60 @@ mod tests {
         assert_eq!(resources.get_network(), Some("test-net".to_string()));
     }
 
+    #[test]
+    fn test_replicas() {
+        let model: ModelInfo = serde_yaml::from_str(
+            "{name: web, node: HPC, replicas: 3, nodes: [ZONE], resources: {}}",
+        )
+        .unwrap();
+        assert!(model.validate_replicas().is_ok());
+        let replicas = model.get_replicas();
+        let placed: Vec<(String, String)> = replicas
+            .iter()
+            .map(|replica| (replica.get_pod_name(), replica.get_node()))
+            .collect();
+        assert_eq!(
+            placed,
+            [("web", "HPC"), ("web--1", "ZONE"), ("web--2", "HPC")]
+                .map(|(pod, node)| (pod.to_string(), node.to_string()))
+        );
+        assert!(replicas.iter().all(|replica| replica.get_name() == "web"));
+        assert_eq!(replicas[1].get_replica(), Some(1));
+        assert_eq!(replicas[1].get_replicas(), vec![replicas[1].clone()]);
+
+        let single: ModelInfo =
+            serde_yaml::from_str("{name: web, node: HPC, resources: {}}").unwrap();
+        assert_eq!(single.get_replicas(), vec![single.clone()]);
+        assert_eq!(single.get_replica(), None);
+        assert_eq!(single.get_pod_name(), "web");
+
+        for invalid in [
+            "{name: web, node: HPC, replicas: 0, resources: {}}",
+            "{name: web, node: HPC, nodes: [ZONE], resources: {}}",
+        ] {
+            let model: ModelInfo = serde_yaml::from_str(invalid).unwrap();
+            assert!(model.validate_replicas().is_err(), "{} is valid", invalid);
+        }
+    }
+
+    #[test]
+    fn test_standby() {
+        let model: ModelInfo =
+            serde_yaml::from_str("{name: web, node: HPC, standby: ZONE, resources: {}}").unwrap();
+        assert!(model.validate_standby().is_ok());
+        assert_eq!(model.get_standby_node(), Some("ZONE".to_string()));
+        assert_eq!(model.get_standby_pod_name(), "web-standby");
+        assert_eq!(model.get_replicas(), vec![model.clone()]);
+
+        for invalid in [
+            "{name: web, node: HPC, standby: HPC, resources: {}}",
+            "{name: web, node: HPC, standby: ZONE, replicas: 2, resources: {}}",
+        ] {
+            let model: ModelInfo = serde_yaml::from_str(invalid).unwrap();
+            assert!(model.validate_standby().is_err(), "{} is valid", invalid);
+        }
+    }
+
     #[test]
     fn test_resource_methods() {
         let resource_with_both = Resource {
@@ -215,6 +701,69 @@ mod tests {
         assert_eq!(resource_with_nothing.get_network(), None);
     }
 
+    #[test]
+    fn test_realtime_spec() {
+        let resource = |realtime: &str| -> Resource {
+            serde_yaml::from_str(&format!("realtime: {}", realtime)).unwrap()
+        };
+
+        assert_eq!(resource("false").get_realtime_spec(), None);
+        assert_eq!(
+            resource("true").get_realtime_spec(),
+            Some(RealtimeSpec::default())
+        );
+
+        let custom = resource("{policy: rr, priority: 80, period: 20000, runtime: 2000, deadline: 4000, cpuAffinity: 2}");
+        assert_eq!(custom.get_realtime(), Some(true));
+        let spec = custom.get_realtime_spec().unwrap();
+        assert!(spec.validate().is_ok());
+        let task = spec.to_task_info("sensor", "HPC");
+        assert_eq!(task.policy, SchedPolicy::Rr as i32);
+        assert_eq!(task.priority, 80);
+        assert_eq!(task.period, 20000);
+        assert_eq!(task.cpu_affinity, 2);
+        assert_eq!(task.max_dmiss, 3);
+        assert_eq!(task.node_id, "HPC");
+
+        for invalid in [
+            "{policy: edf}",
+            "{priority: 0}",
+            "{policy: normal}",
+            "{cpuAffinity: 0}",
+            "{runtime: 20000}",
+        ] {
+            let spec = resource(invalid).get_realtime_spec().unwrap();
+            assert!(spec.validate().is_err(), "{} is valid", invalid);
+        }
+    }
+
+    #[test]
+    fn test_update_strategy() {
+        let spec = |strategy: &str| -> PackageSpec {
+            serde_yaml::from_str(&format!("{{pattern: [], models: []{}}}", strategy)).unwrap()
+        };
+
+        assert_eq!(spec("").strategy, UpdateStrategy::default());
+        let rolling = spec(", strategy: {type: rolling}").strategy;
+        assert_eq!(rolling.r#type, UpdateStrategyType::Rolling);
+        assert_eq!(rolling.health_timeout, 60);
+        let blue_green = spec(", strategy: {type: blueGreen, healthTimeout: 5}").strategy;
+        assert_eq!(blue_green.r#type, UpdateStrategyType::BlueGreen);
+        assert_eq!(blue_green.health_timeout, 5);
+
+        let canary =
+            spec(", strategy: {type: canary, canary: {bakeTime: 10, probe: [check, -q]}}").strategy;
+        assert_eq!(canary.r#type, UpdateStrategyType::Canary);
+        assert_eq!(canary.canary.bake_time, 10);
+        assert_eq!(canary.canary.max_restarts, 0);
+        assert_eq!(canary.canary.probe, vec!["check", "-q"]);
+
+        let unknown = "{pattern: [], models: [], strategy: {type: staged}}";
+        assert!(serde_yaml::from_str::<PackageSpec>(unknown).is_err());
+        let typo = "{pattern: [], models: [], strategy: {canary: {bakeTme: 1}}}";
+        assert!(serde_yaml::from_str::<PackageSpec>(typo).is_err());
+    }
+
     #[test]
     fn test_package_without_status() {
         let package = Package {
@@ -222,12 +771,15 @@ mod tests {
             kind: "Package".to_string(),
             metadata: MetaData {
                 name: "no-status-package".to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
             spec: PackageSpec {
                 pattern: vec![],
                 models: vec![],
+                strategy: UpdateStrategy::default(),
+                desired_state: None,
             },
             status: None,
         };
@@ -243,12 +795,15 @@ mod tests {
             kind: "Package".to_string(),
             metadata: MetaData {
                 name: "empty-package".to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
             spec: PackageSpec {
                 pattern: vec![],
                 models: vec![],
+                strategy: UpdateStrategy::default(),
+                desired_state: None,
             },
             status: None,
         };
@@ -268,4 +823,59 @@ mod tests {
         assert_eq!(none, ModelStatusState::None);
         assert_eq!(error, ModelStatusState::Error);
     }
+
+    #[test]
+    fn test_package_diff_between() {
+        use std::collections::BTreeMap;
+        let pods = |entries: &[(&str, &str, &str)]| -> BTreeMap<String, (String, String)> {
+            entries
+                .iter()
+                .map(|(pod, node, yaml)| (pod.to_string(), (node.to_string(), yaml.to_string())))
+                .collect()
+        };
+        let previous = pods(&[
+            ("core", "HPC", "image: v1"),
+            ("moved", "HPC", "image: v1"),
+            ("kept", "HPC", "image: v1"),
+            ("gone", "HPC", "image: v1"),
+        ]);
+        let next = pods(&[
+            ("core", "HPC", "image: v2"),
+            ("moved", "ZONE", "image: v1"),
+            ("kept", "HPC", "image: v1"),
+            ("new", "HPC", "image: v1"),
+        ]);
+
+        let diff = PackageDiff::between("pkg", &previous, &next);
+        assert_eq!(diff.package, "pkg");
+        assert_eq!(diff.added, ["new"]);
+        assert_eq!(diff.changed, ["core", "moved"]);
+        assert_eq!(diff.removed, ["gone"]);
+        assert_eq!(diff.unchanged, ["kept"]);
+        assert!(diff.redeploys("new") && diff.redeploys("core"));
+        assert!(!diff.redeploys("kept") && !diff.redeploys("gone"));
+        assert_eq!(PackageDiff::key("pkg"), "/package/pkg/diff");
+    }
+
+    #[test]
+    fn test_package_diff_then() {
+        let diff = |added: &[&str], changed: &[&str], removed: &[&str], unchanged: &[&str]| {
+            let names = |pods: &[&str]| pods.iter().map(|pod| pod.to_string()).collect();
+            PackageDiff {
+                package: "pkg".to_string(),
+                added: names(added),
+                changed: names(changed),
+                removed: names(removed),
+                unchanged: names(unchanged),
+            }
+        };
+        let first = diff(&["new", "short"], &["a"], &["gone"], &["b", "c"]);
+        let second = diff(&["gone"], &["b"], &["short"], &["a", "c", "new"]);
+
+        let merged = first.then(&second);
+        assert_eq!(merged.added, ["new"]);
+        assert_eq!(merged.changed, ["a", "b", "gone"]);
+        assert!(merged.removed.is_empty());
+        assert_eq!(merged.unchanged, ["c"]);
+    }
 }
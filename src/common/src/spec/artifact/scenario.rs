@@ -4,11 +4,16 @@
 */
 use super::Artifact;
 use super::Scenario;
+use crate::statemanager::{ModelState, PackageState, VehicleMode};
 
 impl Artifact for Scenario {
     fn get_name(&self) -> String {
         self.metadata.name.clone()
     }
+
+    fn get_namespace(&self) -> String {
+        self.metadata.get_namespace()
+    }
 }
 
 impl Scenario {
@@ -20,9 +25,107 @@ impl Scenario {
         self.spec.action.clone()
     }
 
+    /// Package of the scenario, qualified with the namespace of the scenario
     pub fn get_targets(&self) -> String {
+        crate::namespace::qualify(&self.get_namespace(), &self.spec.target)
+    }
+
+    /// Package of the scenario as written, without the namespace
+    pub fn get_target_name(&self) -> String {
         self.spec.target.clone()
     }
+
+    /// State the models of the package are kept in, if declared
+    pub fn get_desired_state(&self) -> Option<DesiredState> {
+        self.spec.desired_state
+    }
+
+    /// Time based trigger of the scenario, if any
+    pub fn get_schedule(&self) -> Option<Schedule> {
+        self.spec.schedule.clone()
+    }
+
+    /// Vehicle modes the scenario may run in, empty if it may run in any mode
+    pub fn get_allowed_modes(&self) -> Vec<String> {
+        self.spec.allowed_modes.clone().unwrap_or_default()
+    }
+
+    /// Check whether the scenario may run in the given vehicle mode
+    pub fn is_allowed_in_mode(&self, mode: VehicleMode) -> bool {
+        match &self.spec.allowed_modes {
+            None => true,
+            Some(modes) => modes
+                .iter()
+                .any(|allowed| VehicleMode::from_name(allowed) == Some(mode)),
+        }
+    }
+
+    /// Exclusion groups of the scenario, qualified with its namespace
+    ///
+    /// Of the scenarios sharing a group, only one may run at a time.
+    pub fn get_exclusion_groups(&self) -> Vec<String> {
+        let namespace = self.get_namespace();
+        self.spec
+            .exclusion_groups
+            .iter()
+            .map(|group| crate::namespace::qualify(&namespace, group))
+            .collect()
+    }
+
+    /// Times the scenario may start in, any time if empty
+    pub fn get_activation_windows(&self) -> &[ActivationWindow] {
+        &self.spec.activation_windows
+    }
+
+    /// Check whether the scenario may start at the given time
+    pub fn is_in_activation_window(&self, at: chrono::DateTime<chrono::Utc>) -> bool {
+        self.spec.activation_windows.is_empty()
+            || self
+                .spec
+                .activation_windows
+                .iter()
+                .any(|window| window.contains(at))
+    }
+
+    /// Check that the scenario spec is well formed
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(condition) = &self.spec.condition {
+            condition.validate()?;
+        }
+        if let Some(schedule) = &self.spec.schedule {
+            schedule.validate()?;
+        }
+        if let Some(group) = self
+            .spec
+            .exclusion_groups
+            .iter()
+            .find(|group| group.trim().is_empty())
+        {
+            return Err(format!("invalid exclusion group '{}'", group));
+        }
+        for window in &self.spec.activation_windows {
+            window.validate()?;
+        }
+        if let Some(modes) = &self.spec.allowed_modes {
+            if modes.is_empty() {
+                return Err("allowedModes must list at least one mode".to_string());
+            }
+            if let Some(unknown) = modes.iter().find(|m| VehicleMode::from_name(m).is_none()) {
+                return Err(format!(
+                    "unknown vehicle mode '{}' in allowedModes",
+                    unknown
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_annotation(&self, key: &str) -> Option<String> {
+        self.metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(key).cloned())
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
@@ -30,6 +133,234 @@ pub struct ScenarioSpec {
     condition: Option<Condition>,
     action: String,
     target: String,
+    /// Vehicle modes the scenario may run in, any mode if omitted
+    #[serde(
+        default,
+        rename = "allowedModes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    allowed_modes: Option<Vec<String>>,
+    /// Time based trigger, in addition to or instead of `condition`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schedule: Option<Schedule>,
+    /// State of the package once the action completed, overrides the
+    /// desired state of the package
+    #[serde(
+        default,
+        rename = "desiredState",
+        skip_serializing_if = "Option::is_none"
+    )]
+    desired_state: Option<DesiredState>,
+    /// Groups of scenarios of which only one may run at a time
+    #[serde(
+        default,
+        rename = "exclusionGroups",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    exclusion_groups: Vec<String>,
+    /// Times the scenario may start in, any time if empty
+    #[serde(
+        default,
+        rename = "activationWindows",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    activation_windows: Vec<ActivationWindow>,
+}
+
+/// Time of day a scenario may start in, in UTC
+///
+/// The window runs from `start` to `end`, both `HH:MM`, on the given `days`
+/// (`mon` to `sun`) or on every day if none is given. A window whose `end`
+/// is before its `start` runs over midnight into the next day.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ActivationWindow {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    days: Vec<String>,
+    start: String,
+    end: String,
+}
+
+impl ActivationWindow {
+    pub fn new(days: &[&str], start: &str, end: &str) -> Self {
+        Self {
+            days: days.iter().map(|day| day.to_string()).collect(),
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    fn parse_time(time: &str) -> Result<chrono::NaiveTime, String> {
+        chrono::NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| {
+            format!(
+                "invalid time '{}' in activationWindows, expected HH:MM",
+                time
+            )
+        })
+    }
+
+    fn parse_days(&self) -> Result<Vec<chrono::Weekday>, String> {
+        self.days
+            .iter()
+            .map(|day| {
+                day.parse::<chrono::Weekday>()
+                    .map_err(|_| format!("unknown day '{}' in activationWindows", day))
+            })
+            .collect()
+    }
+
+    /// Check that the window is well formed
+    pub fn validate(&self) -> Result<(), String> {
+        let start = Self::parse_time(&self.start)?;
+        let end = Self::parse_time(&self.end)?;
+        if start == end {
+            return Err(format!(
+                "activation window {}-{} is empty",
+                self.start, self.end
+            ));
+        }
+        self.parse_days().map(|_| ())
+    }
+
+    /// Check whether `at` falls in the window, never for a malformed window
+    pub fn contains(&self, at: chrono::DateTime<chrono::Utc>) -> bool {
+        use chrono::{Datelike, Timelike};
+        let (Ok(start), Ok(end), Ok(days)) = (
+            Self::parse_time(&self.start),
+            Self::parse_time(&self.end),
+            self.parse_days(),
+        ) else {
+            return false;
+        };
+        let time = chrono::NaiveTime::from_hms_opt(at.hour(), at.minute(), at.second())
+            .unwrap_or_default();
+        let on = |day: chrono::Weekday| days.is_empty() || days.contains(&day);
+        let today = at.weekday();
+        if start < end {
+            on(today) && start <= time && time < end
+        } else {
+            // Before the end it is the part of the window of the day before
+            (on(today) && start <= time) || (on(today.pred()) && time < end)
+        }
+    }
+}
+
+/// State the models of a package should be kept in
+///
+/// Drift detection compares the models with it and reconciliation brings
+/// them back to it.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum DesiredState {
+    Running,
+    Stopped,
+}
+
+impl DesiredState {
+    /// Desired state implied by the action of a scenario without a declared
+    /// one, `None` for actions that leave the models as they are
+    pub fn from_action(action: &str) -> Option<Self> {
+        match action {
+            "launch" | "update" | "rollback" => Some(DesiredState::Running),
+            "terminate" => Some(DesiredState::Stopped),
+            _ => None,
+        }
+    }
+
+    /// Desired state of each model
+    pub fn model_state(&self) -> ModelState {
+        match self {
+            DesiredState::Running => ModelState::Running,
+            DesiredState::Stopped => ModelState::Exited,
+        }
+    }
+
+    /// Desired state of the package
+    pub fn package_state(&self) -> PackageState {
+        match self {
+            DesiredState::Running => PackageState::Running,
+            DesiredState::Stopped => PackageState::Exited,
+        }
+    }
+}
+
+/// Time based trigger of a scenario
+///
+/// Exactly one of the fields is set. Cron expressions are evaluated in UTC
+/// and may have 5 fields (from minutes) or 6 and 7 fields (from seconds, to
+/// years).
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cron: Option<String>,
+    /// Seconds after the scenario is registered to trigger it once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delay_secs: Option<u64>,
+    /// Seconds between triggers, starting when the scenario is registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interval_secs: Option<u64>,
+}
+
+/// Parsed [`Schedule`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    Cron(Box<cron::Schedule>),
+    Delay(std::time::Duration),
+    Interval(std::time::Duration),
+}
+
+impl Schedule {
+    pub fn cron(expression: &str) -> Self {
+        Self {
+            cron: Some(expression.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn delay(secs: u64) -> Self {
+        Self {
+            delay_secs: Some(secs),
+            ..Default::default()
+        }
+    }
+
+    pub fn interval(secs: u64) -> Self {
+        Self {
+            interval_secs: Some(secs),
+            ..Default::default()
+        }
+    }
+
+    /// Parse the schedule
+    ///
+    /// ### Returns
+    /// * `Err(String)` - description of the problem if it is malformed
+    pub fn trigger(&self) -> Result<Trigger, String> {
+        match (&self.cron, self.delay_secs, self.interval_secs) {
+            (Some(expression), None, None) => {
+                // The cron crate counts from seconds, a 5 field expression from minutes
+                let full = if expression.split_whitespace().count() == 5 {
+                    format!("0 {}", expression)
+                } else {
+                    expression.clone()
+                };
+                full.parse::<cron::Schedule>()
+                    .map(|schedule| Trigger::Cron(Box::new(schedule)))
+                    .map_err(|e| format!("invalid cron expression '{}': {}", expression, e))
+            }
+            (None, Some(secs), None) => Ok(Trigger::Delay(std::time::Duration::from_secs(secs))),
+            (None, None, Some(0)) => Err("intervalSecs must be positive".to_string()),
+            (None, None, Some(secs)) => Ok(Trigger::Interval(std::time::Duration::from_secs(secs))),
+            _ => {
+                Err("schedule must set exactly one of cron, delaySecs and intervalSecs".to_string())
+            }
+        }
+    }
+
+    /// Check that the schedule is well formed
+    pub fn validate(&self) -> Result<(), String> {
+        self.trigger().map(|_| ())
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
@@ -48,11 +379,45 @@ enum ScenarioState {
     Completed,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+/// Comparison operators accepted in `express`
+pub const EQUALITY_OPERATORS: [&str; 2] = ["eq", "ne"];
+/// Operators whose `value` is a numeric threshold
+pub const NUMERIC_OPERATORS: [&str; 4] = ["lt", "le", "gt", "ge"];
+/// Operators matching part of a text value
+pub const STRING_OPERATORS: [&str; 3] = ["contains", "startswith", "endswith"];
+
+/// Deepest allowed nesting of allOf/anyOf/not blocks
+const MAX_CONDITION_DEPTH: usize = 8;
+
+/// Scenario condition
+///
+/// A condition is either a comparison (`express`, `value`, `operands`) or
+/// exactly one of the composite blocks `allOf`, `anyOf` and `not`.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct Condition {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     express: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     value: String,
-    operands: Operand,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operands: Option<Operand>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    all_of: Option<Vec<Condition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    any_of: Option<Vec<Condition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not: Option<Box<Condition>>,
+}
+
+/// Shape of a condition node
+#[derive(Debug, PartialEq)]
+pub enum ConditionKind<'a> {
+    /// Leaf comparing an operand against `value`
+    Compare,
+    AllOf(&'a [Condition]),
+    AnyOf(&'a [Condition]),
+    Not(&'a Condition),
 }
 
 impl Condition {
@@ -65,11 +430,114 @@ impl Condition {
     }
 
     pub fn get_operand_value(&self) -> String {
-        self.operands.value.clone()
+        self.operands
+            .as_ref()
+            .map(|o| o.value.clone())
+            .unwrap_or_default()
     }
 
     pub fn get_operand_name(&self) -> String {
-        self.operands.name.clone()
+        self.operands
+            .as_ref()
+            .map(|o| o.name.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get_operand_type(&self) -> String {
+        self.operands
+            .as_ref()
+            .map(|o| o.r#type.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn kind(&self) -> ConditionKind<'_> {
+        if let Some(children) = &self.all_of {
+            ConditionKind::AllOf(children)
+        } else if let Some(children) = &self.any_of {
+            ConditionKind::AnyOf(children)
+        } else if let Some(child) = &self.not {
+            ConditionKind::Not(child)
+        } else {
+            ConditionKind::Compare
+        }
+    }
+
+    /// Check that the condition is well formed
+    ///
+    /// ### Returns
+    /// * `Err(String)` - description of the first problem found
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_at(1)
+    }
+
+    fn validate_at(&self, depth: usize) -> Result<(), String> {
+        if depth > MAX_CONDITION_DEPTH {
+            return Err(format!(
+                "condition is nested deeper than {} levels",
+                MAX_CONDITION_DEPTH
+            ));
+        }
+
+        let blocks = [
+            self.all_of.is_some(),
+            self.any_of.is_some(),
+            self.not.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count();
+        let is_compare = !self.express.is_empty() || self.operands.is_some();
+        if blocks > 1 || (blocks == 1 && is_compare) {
+            return Err(
+                "condition must be exactly one of a comparison, allOf, anyOf or not".to_string(),
+            );
+        }
+
+        match self.kind() {
+            ConditionKind::Compare => self.validate_compare(),
+            ConditionKind::AllOf(children) | ConditionKind::AnyOf(children) => {
+                if children.is_empty() {
+                    return Err("allOf/anyOf must contain at least one condition".to_string());
+                }
+                children
+                    .iter()
+                    .try_for_each(|child| child.validate_at(depth + 1))
+            }
+            ConditionKind::Not(child) => child.validate_at(depth + 1),
+        }
+    }
+
+    fn validate_compare(&self) -> Result<(), String> {
+        let operand = self
+            .operands
+            .as_ref()
+            .ok_or_else(|| "comparison is missing operands".to_string())?;
+        for (field, content) in [
+            ("type", &operand.r#type),
+            ("name", &operand.name),
+            ("value", &operand.value),
+        ] {
+            if content.trim().is_empty() {
+                return Err(format!("operand {} must not be empty", field));
+            }
+        }
+
+        let express = self.express.to_lowercase();
+        if NUMERIC_OPERATORS.contains(&express.as_str()) {
+            self.value.trim().parse::<f64>().map_err(|_| {
+                format!(
+                    "threshold '{}' of '{}' is not a number",
+                    self.value, self.express
+                )
+            })?;
+        } else if STRING_OPERATORS.contains(&express.as_str()) {
+            if self.value.is_empty() {
+                return Err(format!("'{}' needs a non-empty value", self.express));
+            }
+        } else if !EQUALITY_OPERATORS.contains(&express.as_str()) {
+            return Err(format!("unknown expression '{}'", self.express));
+        }
+        Ok(())
     }
 }
 
@@ -92,6 +560,7 @@ mod tests {
             kind: "Scenario".to_string(),
             metadata: MetaData {
                 name: "test-scenario".to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
@@ -99,14 +568,20 @@ mod tests {
                 condition: Some(Condition {
                     express: "eq".to_string(),
                     value: "ready".to_string(),
-                    operands: Operand {
+                    operands: Some(Operand {
                         r#type: "pod".to_string(),
                         name: "test-pod".to_string(),
                         value: "status".to_string(),
-                    },
+                    }),
+                    ..Default::default()
                 }),
                 action: "start".to_string(),
                 target: "model-1".to_string(),
+                allowed_modes: None,
+                schedule: None,
+                desired_state: None,
+                exclusion_groups: Vec::new(),
+                activation_windows: Vec::new(),
             },
             status: Some(ScenarioStatus {
                 state: ScenarioState::None,
@@ -129,6 +604,7 @@ mod tests {
         assert_eq!(conditions.get_value(), "ready");
         assert_eq!(conditions.get_operand_name(), "test-pod");
         assert_eq!(conditions.get_operand_value(), "status");
+        assert_eq!(conditions.get_operand_type(), "pod");
     }
 
     #[test]
@@ -137,6 +613,24 @@ mod tests {
         assert_eq!(scenario.get_actions(), "start");
     }
 
+    #[test]
+    fn test_get_annotation() {
+        let mut scenario = create_test_scenario();
+        assert_eq!(
+            scenario.get_annotation("io.piccolo.annotations.asil-level"),
+            None
+        );
+
+        scenario.metadata.annotations = Some(std::collections::HashMap::from([(
+            "io.piccolo.annotations.asil-level".to_string(),
+            "B".to_string(),
+        )]));
+        assert_eq!(
+            scenario.get_annotation("io.piccolo.annotations.asil-level"),
+            Some("B".to_string())
+        );
+    }
+
     #[test]
     fn test_get_targets() {
         let scenario = create_test_scenario();
@@ -150,6 +644,7 @@ mod tests {
             kind: "Scenario".to_string(),
             metadata: MetaData {
                 name: "no-condition-scenario".to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
@@ -157,6 +652,11 @@ mod tests {
                 condition: None,
                 action: "stop".to_string(),
                 target: "model-2".to_string(),
+                allowed_modes: None,
+                schedule: None,
+                desired_state: None,
+                exclusion_groups: Vec::new(),
+                activation_windows: Vec::new(),
             },
             status: None,
         };
@@ -217,14 +717,20 @@ mod tests {
             condition: Some(Condition {
                 express: "gt".to_string(),
                 value: "5".to_string(),
-                operands: Operand {
+                operands: Some(Operand {
                     r#type: "metric".to_string(),
                     name: "cpu_usage".to_string(),
                     value: "value".to_string(),
-                },
+                }),
+                ..Default::default()
             }),
             action: "scale".to_string(),
             target: "deployment".to_string(),
+            allowed_modes: Some(vec!["parked".to_string()]),
+            schedule: Some(Schedule::interval(60)),
+            desired_state: None,
+            exclusion_groups: vec!["diagnostics".to_string()],
+            activation_windows: vec![ActivationWindow::new(&["sat"], "22:00", "06:00")],
         };
 
         let serialized = serde_json::to_string(&spec).unwrap();
@@ -238,14 +744,243 @@ mod tests {
         let condition = Condition {
             express: "lt".to_string(),
             value: "10".to_string(),
-            operands: Operand {
+            operands: Some(Operand {
                 r#type: "metric".to_string(),
                 name: "memory_usage".to_string(),
                 value: "value".to_string(),
-            },
+            }),
+            ..Default::default()
         };
 
         let cloned = condition.clone();
         assert_eq!(condition, cloned);
     }
+
+    fn parse_condition(yaml: &str) -> Condition {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_parse_composite_condition() {
+        let condition = parse_condition(
+            r#"
+allOf:
+  - express: eq
+    value: parking
+    operands:
+      type: DDS
+      name: gear
+      value: /rt/piccolo/Gear_State
+  - not:
+      anyOf:
+        - express: lt
+          value: "20.5"
+          operands:
+            type: DDS
+            name: soc
+            value: /rt/piccolo/Battery
+        - express: contains
+          value: fault
+          operands:
+            type: DDS
+            name: status
+            value: /rt/piccolo/Battery
+"#,
+        );
+
+        assert!(condition.validate().is_ok());
+        let children = match condition.kind() {
+            ConditionKind::AllOf(children) => children,
+            other => panic!("unexpected kind {:?}", other),
+        };
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].kind(), ConditionKind::Compare);
+        assert_eq!(children[0].get_operand_type(), "DDS");
+        assert!(matches!(children[1].kind(), ConditionKind::Not(_)));
+
+        // Composite blocks survive a round trip with their camelCase names
+        let serialized = serde_yaml::to_string(&condition).unwrap();
+        assert!(serialized.contains("allOf"));
+        assert_eq!(parse_condition(&serialized), condition);
+    }
+
+    #[test]
+    fn test_validate_rejects_malformed_conditions() {
+        let operands = "operands: {type: DDS, name: soc, value: /rt/piccolo/Battery}";
+        let invalid = [
+            format!("{{express: between, value: '1', {operands}}}"),
+            format!("{{express: gt, value: high, {operands}}}"),
+            format!("{{express: contains, value: '', {operands}}}"),
+            "{express: eq, value: '1'}".to_string(),
+            "{express: eq, value: '1', operands: {type: DDS, name: '', value: topic}}"
+                .to_string(),
+            "{allOf: []}".to_string(),
+            format!("{{express: eq, value: '1', {operands}, not: {{express: eq, value: '2', {operands}}}}}"),
+        ];
+
+        for yaml in invalid {
+            assert!(
+                parse_condition(&yaml).validate().is_err(),
+                "expected invalid: {}",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_deep_nesting() {
+        let mut yaml =
+            "{express: eq, value: '1', operands: {type: DDS, name: a, value: b}}".to_string();
+        for _ in 0..MAX_CONDITION_DEPTH {
+            yaml = format!("{{not: {}}}", yaml);
+        }
+        assert!(parse_condition(&yaml).validate().is_err());
+    }
+
+    #[test]
+    fn test_allowed_modes() {
+        let mut scenario = create_test_scenario();
+        assert!(scenario.get_allowed_modes().is_empty());
+        assert!(scenario.is_allowed_in_mode(VehicleMode::Driving));
+
+        scenario.spec.allowed_modes = Some(vec!["Parked".to_string(), "charging".to_string()]);
+        assert!(scenario.validate().is_ok());
+        assert!(scenario.is_allowed_in_mode(VehicleMode::Parked));
+        assert!(scenario.is_allowed_in_mode(VehicleMode::Charging));
+        assert!(!scenario.is_allowed_in_mode(VehicleMode::Driving));
+        assert!(!scenario.is_allowed_in_mode(VehicleMode::Unspecified));
+
+        scenario.spec.allowed_modes = Some(vec!["towing".to_string()]);
+        assert!(scenario.validate().is_err());
+        scenario.spec.allowed_modes = Some(vec![]);
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn test_exclusion_groups_and_activation_windows() {
+        let mut scenario: Scenario = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: diagnostics
+  namespace: fleet
+spec:
+  action: launch
+  target: diag
+  exclusionGroups:
+    - vehicle-bus
+  activationWindows:
+    - days: [mon, tue]
+      start: "08:00"
+      end: "18:00"
+    - days: [sat]
+      start: "22:00"
+      end: "06:00"
+"#,
+        )
+        .unwrap();
+        assert!(scenario.validate().is_ok());
+        assert_eq!(scenario.get_exclusion_groups(), vec!["fleet/vehicle-bus"]);
+
+        let at = |s: &str| s.parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+        // 2024-01-01 is a Monday
+        assert!(scenario.is_in_activation_window(at("2024-01-01T08:00:00Z")));
+        assert!(!scenario.is_in_activation_window(at("2024-01-01T18:00:00Z")));
+        assert!(!scenario.is_in_activation_window(at("2024-01-03T12:00:00Z")));
+        // The saturday window runs into sunday morning
+        assert!(scenario.is_in_activation_window(at("2024-01-06T23:30:00Z")));
+        assert!(scenario.is_in_activation_window(at("2024-01-07T05:59:00Z")));
+        assert!(!scenario.is_in_activation_window(at("2024-01-06T05:00:00Z")));
+
+        scenario.spec.activation_windows = vec![ActivationWindow::new(&[], "9:00", "25:00")];
+        assert!(scenario.validate().is_err());
+        scenario.spec.activation_windows =
+            vec![ActivationWindow::new(&["someday"], "09:00", "10:00")];
+        assert!(scenario.validate().is_err());
+        scenario.spec.activation_windows = vec![ActivationWindow::new(&[], "09:00", "09:00")];
+        assert!(scenario.validate().is_err());
+        scenario.spec.activation_windows.clear();
+        assert!(scenario.is_in_activation_window(at("2024-01-03T12:00:00Z")));
+        scenario.spec.exclusion_groups = vec![" ".to_string()];
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_allowed_modes() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: ota-update
+spec:
+  action: update
+  target: ota
+  allowedModes:
+    - parked
+    - charging
+"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.get_allowed_modes(), vec!["parked", "charging"]);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let spec: ScenarioSpec = serde_yaml::from_str(
+            "action: launch\ntarget: helloworld\nschedule:\n  cron: '*/5 * * * *'\n",
+        )
+        .unwrap();
+        let schedule = spec.schedule.unwrap();
+        assert!(matches!(schedule.trigger(), Ok(Trigger::Cron(_))));
+        assert!(Schedule::cron("0 0 12 * * Mon-Fri *").validate().is_ok());
+        assert_eq!(
+            Schedule::delay(30).trigger(),
+            Ok(Trigger::Delay(std::time::Duration::from_secs(30)))
+        );
+
+        let invalid = [
+            Schedule::cron("every minute"),
+            Schedule::interval(0),
+            Schedule::default(),
+            Schedule {
+                delay_secs: Some(1),
+                interval_secs: Some(1),
+                ..Default::default()
+            },
+        ];
+        for schedule in invalid {
+            assert!(
+                schedule.validate().is_err(),
+                "expected invalid: {:?}",
+                schedule
+            );
+        }
+
+        let mut scenario = create_test_scenario();
+        scenario.spec.schedule = Some(Schedule::interval(0));
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn test_desired_state() {
+        let spec: ScenarioSpec =
+            serde_yaml::from_str("action: launch\ntarget: helloworld\ndesiredState: stopped\n")
+                .unwrap();
+        assert_eq!(spec.desired_state, Some(DesiredState::Stopped));
+        assert_eq!(DesiredState::Stopped.model_state(), ModelState::Exited);
+
+        let scenario = create_test_scenario();
+        assert_eq!(scenario.get_desired_state(), None);
+        assert_eq!(
+            DesiredState::from_action("update"),
+            Some(DesiredState::Running)
+        );
+        assert_eq!(
+            DesiredState::from_action("terminate"),
+            Some(DesiredState::Stopped)
+        );
+        assert_eq!(DesiredState::from_action("start"), None);
+    }
 }
@@ -9,6 +9,10 @@ impl Artifact for Volume {
     fn get_name(&self) -> String {
         self.metadata.name.clone()
     }
+
+    fn get_namespace(&self) -> String {
+        self.metadata.get_namespace()
+    }
 }
 
 impl Volume {
@@ -43,6 +47,7 @@ mod tests {
                 name: String::from("test-volume"),                   // Valid name
                 annotations: Some(std::collections::HashMap::new()), // Empty annotations
                 labels: Some(std::collections::HashMap::new()),      // Empty labels
+                namespace: None,
             },
             spec: None, // No spec provided
         };
@@ -59,6 +64,7 @@ mod tests {
                 name: String::from(""),                              // Empty name
                 annotations: Some(std::collections::HashMap::new()), // Empty annotations
                 labels: Some(std::collections::HashMap::new()),      // Empty labels
+                namespace: None,
             },
             spec: None, // No spec provided
         };
@@ -75,6 +81,7 @@ mod tests {
                 name: String::from("test-volume"),                   // Valid name
                 annotations: Some(std::collections::HashMap::new()), // Empty annotations
                 labels: Some(std::collections::HashMap::new()),      // Empty labels
+                namespace: None,
             },
             spec: None, // No spec provided
         };
@@ -94,6 +101,7 @@ mod tests {
                 name: String::from("test-volume"),                   // Valid name
                 annotations: Some(std::collections::HashMap::new()), // Empty annotations
                 labels: Some(std::collections::HashMap::new()),      // Empty labels
+                namespace: None,
             },
             spec: Some(volume_spec.clone()), // Spec provided
         };
@@ -137,6 +145,7 @@ mod tests {
                 name: String::from("test-volume"),                   // Valid name
                 annotations: Some(std::collections::HashMap::new()), // Empty annotations
                 labels: Some(std::collections::HashMap::new()),      // Empty labels
+                namespace: None,
             },
             spec: None, // No spec provided
         };
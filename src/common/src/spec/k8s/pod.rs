@@ -1,8 +1,25 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::Pod;
-use crate::spec::artifact::Model;
+use crate::nodeagent::fromapiserver::Taint;
+use crate::spec::artifact::{Artifact, Model};
 use crate::spec::MetaData;
+use std::collections::HashMap;
+
+/// Label and container annotation naming the Model a Pod belongs to
+pub const LABEL_MODEL: &str = "io.piccolo.model";
+/// Label and container annotation naming the Package a Pod was deployed with
+pub const LABEL_PACKAGE: &str = "io.piccolo.package";
+/// Label and container annotation naming the Scenario a Pod was applied with
+pub const LABEL_SCENARIO: &str = "io.piccolo.scenario";
+/// Label and container annotation numbering the replica of a replicated Model
+pub const LABEL_REPLICA: &str = "io.piccolo.replica";
+/// Label and container annotation marking the warm standby of a Model
+pub const LABEL_STANDBY: &str = "io.piccolo.standby";
+/// Container annotation giving the restart policy of a Pod that declares one
+pub const ANNOTATION_RESTART_POLICY: &str = "io.piccolo.restart-policy";
+/// Annotations naming the Model of a container before [`LABEL_MODEL`]
+pub const LEGACY_MODEL_KEYS: [&str; 2] = ["model", "pullpiri.model"];
 
 impl Pod {
     pub fn new(name: &str, podspec: PodSpec) -> Pod {
@@ -11,6 +28,7 @@ impl Pod {
             kind: String::from("Pod"),
             metadata: MetaData {
                 name: name.to_string(),
+                namespace: None,
                 labels: None,
                 annotations: None,
             },
@@ -21,11 +39,162 @@ impl Pod {
     pub fn get_name(&self) -> String {
         self.metadata.name.clone()
     }
+
+    pub fn get_spec(&self) -> &PodSpec {
+        &self.spec
+    }
+
+    pub fn get_labels(&self) -> HashMap<String, String> {
+        self.metadata.get_labels()
+    }
+
+    pub fn get_annotations(&self) -> HashMap<String, String> {
+        self.metadata.get_annotations()
+    }
+
+    /// Give the Pod another name, its containers then report it as their Model
+    pub fn rename(&mut self, name: &str) {
+        self.metadata.name = name.to_string();
+        self.metadata
+            .labels
+            .get_or_insert_with(HashMap::new)
+            .insert(LABEL_MODEL.to_string(), name.to_string());
+    }
+
+    /// Make the Pod the replica `index` of its Model
+    ///
+    /// The Pod is named after the replica and labelled with its index, its
+    /// containers keep reporting the Model.
+    pub fn set_replica(&mut self, index: u32) {
+        self.metadata.name = replica_name(&self.metadata.name, index);
+        self.metadata
+            .labels
+            .get_or_insert_with(HashMap::new)
+            .insert(LABEL_REPLICA.to_string(), index.to_string());
+    }
+
+    /// Make the Pod the warm standby of its Model
+    ///
+    /// The Pod is named after the standby and labelled as one, its
+    /// containers keep reporting the Model.
+    pub fn set_standby(&mut self) {
+        self.metadata.name = standby_name(&self.metadata.name);
+        self.metadata
+            .labels
+            .get_or_insert_with(HashMap::new)
+            .insert(LABEL_STANDBY.to_string(), "true".to_string());
+    }
+
+    /// Make the warm standby of its Model run the Model
+    ///
+    /// The Pod is named after the Model again and no longer labelled as a
+    /// standby, the reverse of [`Pod::set_standby`].
+    pub fn set_primary(&mut self) {
+        if let Some(base) = standby_base(&self.metadata.name) {
+            self.metadata.name = base.to_string();
+        }
+        if let Some(labels) = self.metadata.labels.as_mut() {
+            labels.remove(LABEL_STANDBY);
+        }
+    }
+
+    /// Add labels that are not set yet, existing ones are kept
+    pub fn add_labels(&mut self, labels: HashMap<String, String>) {
+        let current = self.metadata.labels.get_or_insert_with(HashMap::new);
+        for (key, value) in labels {
+            current.entry(key).or_insert(value);
+        }
+    }
+
+    /// Piccolo annotations nodeagent sets on the containers of the Pod
+    ///
+    /// Model, Package, Scenario, replica and standby are given when the Pod
+    /// is labelled with them, so only the containers of a Pod created from
+    /// a Model name one. The restart policy is given when the Pod declares
+    /// one.
+    pub fn piccolo_annotations(&self) -> HashMap<String, String> {
+        let labels = self.get_labels();
+        let mut annotations = HashMap::new();
+        for key in [
+            LABEL_MODEL,
+            LABEL_PACKAGE,
+            LABEL_SCENARIO,
+            LABEL_REPLICA,
+            LABEL_STANDBY,
+        ] {
+            if let Some(value) = labels.get(key) {
+                annotations.insert(key.to_string(), value.clone());
+            }
+        }
+        if let Some(policy) = self.spec.restartPolicy {
+            annotations.insert(
+                ANNOTATION_RESTART_POLICY.to_string(),
+                policy.as_str().to_string(),
+            );
+        }
+        annotations
+    }
+}
+
+/// Separator of a replica name and its index, names may not contain it
+pub const REPLICA_SEPARATOR: &str = "--";
+
+/// Name of the replica `index` of a Model or Pod, the first replica keeps
+/// the name
+pub fn replica_name(name: &str, index: u32) -> String {
+    match index {
+        0 => name.to_string(),
+        _ => format!("{}{}{}", name, REPLICA_SEPARATOR, index),
+    }
+}
+
+/// Name of the warm standby of a Model or Pod
+pub fn standby_name(name: &str) -> String {
+    format!("{}{}", name, STANDBY_SUFFIX)
 }
 
+/// Model or Pod a standby name of [`standby_name`] stands for
+pub fn standby_base(name: &str) -> Option<&str> {
+    name.strip_suffix(STANDBY_SUFFIX)
+        .filter(|base| !base.is_empty())
+}
+
+const STANDBY_SUFFIX: &str = "-standby";
+
+/// Model or Pod a replica name of [`replica_name`] may stand for
+pub fn replica_base(name: &str) -> Option<&str> {
+    let (base, index) = name.rsplit_once(REPLICA_SEPARATOR)?;
+    (!base.is_empty() && !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+        .then_some(base)
+}
+
+/// Model of a container from its annotations
+///
+/// [`LABEL_MODEL`] is authoritative. Containers created before it was set
+/// fall back to the [`LEGACY_MODEL_KEYS`]. A container without any of them
+/// belongs to no Model, whatever its name.
+pub fn model_of(annotations: &HashMap<String, String>) -> Option<String> {
+    std::iter::once(LABEL_MODEL)
+        .chain(LEGACY_MODEL_KEYS)
+        .find_map(|key| annotations.get(key))
+        .cloned()
+}
+
+/// The Pod carries the labels and annotations of the Model, plus
+/// [`LABEL_MODEL`] with the qualified name of the Model so its containers
+/// can be traced back to it
 impl From<Model> for Pod {
     fn from(model: Model) -> Self {
-        Pod::new(&model.get_name(), model.get_podspec())
+        let qualified = model.get_qualified_name();
+        let mut pod = Pod::new(&crate::namespace::pod_name(&qualified), model.get_podspec());
+        let mut labels = model.get_labels();
+        labels.insert(LABEL_MODEL.to_string(), qualified);
+        pod.metadata.labels = Some(labels);
+        let annotations = model.get_annotations();
+        if !annotations.is_empty() {
+            pod.metadata.annotations = Some(annotations);
+        }
+        pod
     }
 }
 
@@ -35,11 +204,46 @@ pub struct PodSpec {
     pub containers: Vec<Container>,
     pub volumes: Option<Vec<Volume>>,
     initContainers: Option<Vec<Container>>,
-    restartPolicy: Option<String>,
+    restartPolicy: Option<RestartPolicy>,
+    maxRestarts: Option<u32>,
     terminationGracePeriodSeconds: Option<i32>,
     hostIPC: Option<bool>,
     runtimeClassName: Option<String>,
     securityContext: Option<PodSecurityContext>,
+    nodeSelector: Option<HashMap<String, String>>,
+    tolerations: Option<Vec<Toleration>>,
+}
+
+/// When the NodeAgent restarts the containers of a Pod that stopped
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub enum RestartPolicy {
+    /// Restart containers whenever they stop
+    #[default]
+    Always,
+    /// Restart containers that fail, not those exiting with code 0
+    OnFailure,
+    /// Leave stopped containers as they are
+    Never,
+}
+
+impl RestartPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestartPolicy::Always => "Always",
+            RestartPolicy::OnFailure => "OnFailure",
+            RestartPolicy::Never => "Never",
+        }
+    }
+
+    /// Policy of [`RestartPolicy::as_str`], `None` for an unknown one
+    pub fn from_str_name(name: &str) -> Option<Self> {
+        match name {
+            "Always" => Some(RestartPolicy::Always),
+            "OnFailure" => Some(RestartPolicy::OnFailure),
+            "Never" => Some(RestartPolicy::Never),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
@@ -53,6 +257,159 @@ pub struct Container {
     workingDir: Option<String>,
     resources: Option<Resources>,
     securityContext: Option<SecurityContext>,
+    readinessProbe: Option<Probe>,
+    livenessProbe: Option<Probe>,
+}
+
+/// Effect of a taint that keeps the models not tolerating it off a node
+pub const TAINT_NO_SCHEDULE: &str = "NoSchedule";
+/// Effect of a taint that only makes a node the last choice for relocations
+pub const TAINT_PREFER_NO_SCHEDULE: &str = "PreferNoSchedule";
+
+/// Taint of a node a model may be placed on despite it
+///
+/// An `Exists` toleration matches any value of its key, or every taint
+/// without a key. A toleration without effect matches every effect.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct Toleration {
+    key: Option<String>,
+    operator: Option<TolerationOperator>,
+    value: Option<String>,
+    effect: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub enum TolerationOperator {
+    #[default]
+    Equal,
+    Exists,
+}
+
+impl Toleration {
+    pub fn tolerates(&self, taint: &Taint) -> bool {
+        if self.effect.as_deref().is_some_and(|e| e != taint.effect) {
+            return false;
+        }
+        match (self.operator.unwrap_or_default(), self.key.as_deref()) {
+            (TolerationOperator::Exists, None) => true,
+            (TolerationOperator::Exists, Some(key)) => key == taint.key,
+            (TolerationOperator::Equal, key) => {
+                key == Some(taint.key.as_str())
+                    && self.value.as_deref().unwrap_or_default() == taint.value
+            }
+        }
+    }
+}
+
+/// Check of a container run by the NodeAgent, with exactly one handler
+///
+/// A readiness probe keeps the model created until it passes, a liveness
+/// probe reports the container dead once it fails `failureThreshold` times
+/// in a row.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct Probe {
+    exec: Option<ExecAction>,
+    tcpSocket: Option<TcpSocketAction>,
+    httpGet: Option<HttpGetAction>,
+    initialDelaySeconds: Option<u64>,
+    periodSeconds: Option<u64>,
+    timeoutSeconds: Option<u64>,
+    failureThreshold: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ExecAction {
+    command: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct TcpSocketAction {
+    host: Option<String>,
+    port: u16,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct HttpGetAction {
+    host: Option<String>,
+    port: u16,
+    path: Option<String>,
+}
+
+/// What a probe checks, hosts default to the node itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeHandler<'a> {
+    /// Command run in the container, passes on exit code 0
+    Exec(&'a [String]),
+    /// Passes when a connection is accepted
+    Tcp { host: &'a str, port: u16 },
+    /// Passes on a 2xx or 3xx response
+    Http {
+        host: &'a str,
+        port: u16,
+        path: &'a str,
+    },
+}
+
+impl Probe {
+    pub fn handler(&self) -> Option<ProbeHandler<'_>> {
+        const LOCALHOST: &str = "127.0.0.1";
+        if let Some(exec) = &self.exec {
+            return Some(ProbeHandler::Exec(&exec.command));
+        }
+        if let Some(tcp) = &self.tcpSocket {
+            return Some(ProbeHandler::Tcp {
+                host: tcp.host.as_deref().unwrap_or(LOCALHOST),
+                port: tcp.port,
+            });
+        }
+        self.httpGet.as_ref().map(|http| ProbeHandler::Http {
+            host: http.host.as_deref().unwrap_or(LOCALHOST),
+            port: http.port,
+            path: http.path.as_deref().unwrap_or("/"),
+        })
+    }
+
+    pub fn initial_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.initialDelaySeconds.unwrap_or(0))
+    }
+
+    pub fn period(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.periodSeconds.unwrap_or(10))
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeoutSeconds.unwrap_or(1))
+    }
+
+    pub fn failure_threshold(&self) -> u32 {
+        self.failureThreshold.unwrap_or(3)
+    }
+
+    /// Check that exactly one handler is set and the timings can be run
+    pub fn validate(&self) -> Result<(), String> {
+        let handlers = [
+            self.exec.is_some(),
+            self.tcpSocket.is_some(),
+            self.httpGet.is_some(),
+        ];
+        if handlers.iter().filter(|set| **set).count() != 1 {
+            return Err("probe needs exactly one of exec, tcpSocket or httpGet".to_string());
+        }
+        match self.handler() {
+            Some(ProbeHandler::Exec([])) => return Err("probe command is empty".to_string()),
+            Some(ProbeHandler::Tcp { port: 0, .. }) | Some(ProbeHandler::Http { port: 0, .. }) => {
+                return Err("probe port must be greater than 0".to_string())
+            }
+            _ => {}
+        }
+        if self.periodSeconds == Some(0) || self.timeoutSeconds == Some(0) {
+            return Err("probe period and timeout must be greater than 0".to_string());
+        }
+        if self.failureThreshold == Some(0) {
+            return Err("probe failureThreshold must be greater than 0".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
@@ -106,6 +463,7 @@ pub struct Requests {
 pub struct Limits {
     cpu: Option<String>,
     memory: Option<String>,
+    pids: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
@@ -131,9 +489,266 @@ impl PodSpec {
             .map(|container| container.image.as_str())
     }
 
+    /// Images of the init containers then the containers, each once
+    pub fn get_images(&self) -> Vec<&str> {
+        let init_containers = self.initContainers.iter().flatten();
+        let mut images: Vec<&str> = Vec::new();
+        for container in init_containers.chain(&self.containers) {
+            if !images.contains(&container.image.as_str()) {
+                images.push(&container.image);
+            }
+        }
+        images
+    }
+
     pub fn get_volume(&mut self) -> &Option<Vec<Volume>> {
         &self.volumes
     }
+
+    pub fn restart_policy(&self) -> RestartPolicy {
+        self.restartPolicy.unwrap_or_default()
+    }
+
+    /// Restarts of a container before its model is reported as crash looping
+    pub fn max_restarts(&self) -> u32 {
+        self.maxRestarts.unwrap_or(5)
+    }
+
+    /// Seconds a stopped container has to exit after SIGTERM before SIGKILL
+    pub fn termination_grace_period_seconds(&self) -> u64 {
+        self.terminationGracePeriodSeconds
+            .map_or(30, |seconds| seconds.max(0) as u64)
+    }
+
+    /// Labels a node needs to have for the Pod to be placed on it
+    pub fn node_selector(&self) -> HashMap<String, String> {
+        self.nodeSelector.clone().unwrap_or_default()
+    }
+
+    /// Why the Pod may not be placed on a node with these labels and taints
+    ///
+    /// The node has to carry every label of the `nodeSelector`, and every
+    /// `NoSchedule` taint of the node has to be tolerated.
+    pub fn placement_error(
+        &self,
+        labels: &HashMap<String, String>,
+        taints: &[Taint],
+    ) -> Option<String> {
+        for (key, value) in self.nodeSelector.iter().flatten() {
+            if labels.get(key) != Some(value) {
+                return Some(format!("node lacks label {}={}", key, value));
+            }
+        }
+        let tolerations = self.tolerations.as_deref().unwrap_or_default();
+        taints
+            .iter()
+            .filter(|taint| taint.effect.is_empty() || taint.effect == TAINT_NO_SCHEDULE)
+            .find(|taint| !tolerations.iter().any(|t| t.tolerates(taint)))
+            .map(|taint| format!("taint {}={} is not tolerated", taint.key, taint.value))
+    }
+
+    /// Whether a node with these taints is a last choice for the Pod, for a
+    /// `PreferNoSchedule` taint it does not tolerate
+    pub fn avoids(&self, taints: &[Taint]) -> bool {
+        let tolerations = self.tolerations.as_deref().unwrap_or_default();
+        taints
+            .iter()
+            .filter(|taint| taint.effect == TAINT_PREFER_NO_SCHEDULE)
+            .any(|taint| !tolerations.iter().any(|t| t.tolerates(taint)))
+    }
+
+    /// Total CPU requested by all containers, in millicores
+    ///
+    /// A container without requests is accounted with its limits.
+    pub fn cpu_request_millis(&self) -> u64 {
+        self.containers
+            .iter()
+            .filter_map(|c| c.requested(|r| r.cpu.as_deref(), |l| l.cpu.as_deref()))
+            .filter_map(parse_cpu_millis)
+            .sum()
+    }
+
+    /// Total memory requested by all containers, in MiB
+    ///
+    /// A container without requests is accounted with its limits.
+    pub fn memory_request_mb(&self) -> u64 {
+        self.containers
+            .iter()
+            .filter_map(|c| c.requested(|r| r.memory.as_deref(), |l| l.memory.as_deref()))
+            .filter_map(parse_memory_mb)
+            .sum()
+    }
+
+    /// Check that the probes of every container can be run
+    pub fn validate_probes(&self) -> Result<(), String> {
+        for container in &self.containers {
+            for (kind, probe) in [
+                ("readiness", &container.readinessProbe),
+                ("liveness", &container.livenessProbe),
+            ] {
+                if let Some(probe) = probe {
+                    probe
+                        .validate()
+                        .map_err(|e| format!("container '{}' {}: {}", container.name, kind, e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the resource quantities of every container are well formed
+    pub fn validate_resources(&self) -> Result<(), String> {
+        let init_containers = self.initContainers.iter().flatten();
+        for container in self.containers.iter().chain(init_containers) {
+            container
+                .resource_limits()
+                .map_err(|e| format!("container '{}': {}", container.name, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parsed resource requests and limits of a container
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    pub cpu_request_millis: Option<u64>,
+    pub cpu_limit_millis: Option<u64>,
+    pub memory_request_bytes: Option<u64>,
+    pub memory_limit_bytes: Option<u64>,
+    pub pids_limit: Option<u64>,
+}
+
+impl Container {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn readiness_probe(&self) -> Option<&Probe> {
+        self.readinessProbe.as_ref()
+    }
+
+    pub fn liveness_probe(&self) -> Option<&Probe> {
+        self.livenessProbe.as_ref()
+    }
+
+    /// Requests and limits of the container
+    ///
+    /// Fails if a quantity cannot be parsed, a limit is 0 or a request is
+    /// larger than its limit.
+    pub fn resource_limits(&self) -> Result<ResourceLimits, String> {
+        let Some(resources) = &self.resources else {
+            return Ok(ResourceLimits::default());
+        };
+        let requests = resources.requests.as_ref();
+        let limits = resources.limits.as_ref();
+
+        fn parse(
+            name: &str,
+            quantity: Option<&String>,
+            parser: fn(&str) -> Option<u64>,
+        ) -> Result<Option<u64>, String> {
+            quantity
+                .map(|q| parser(q).ok_or(format!("invalid {} quantity '{}'", name, q)))
+                .transpose()
+        }
+
+        let parsed = ResourceLimits {
+            cpu_request_millis: parse(
+                "cpu",
+                requests.and_then(|r| r.cpu.as_ref()),
+                parse_cpu_millis,
+            )?,
+            cpu_limit_millis: parse("cpu", limits.and_then(|l| l.cpu.as_ref()), parse_cpu_millis)?,
+            memory_request_bytes: parse(
+                "memory",
+                requests.and_then(|r| r.memory.as_ref()),
+                parse_memory_bytes,
+            )?,
+            memory_limit_bytes: parse(
+                "memory",
+                limits.and_then(|l| l.memory.as_ref()),
+                parse_memory_bytes,
+            )?,
+            pids_limit: parse("pids", limits.and_then(|l| l.pids.as_ref()), |q| {
+                q.trim().parse().ok()
+            })?,
+        };
+
+        for (name, request, limit) in [
+            ("cpu", parsed.cpu_request_millis, parsed.cpu_limit_millis),
+            (
+                "memory",
+                parsed.memory_request_bytes,
+                parsed.memory_limit_bytes,
+            ),
+        ] {
+            match (request, limit) {
+                (_, Some(0)) => return Err(format!("{} limit must be greater than 0", name)),
+                (Some(request), Some(limit)) if request > limit => {
+                    return Err(format!("{} request is larger than its limit", name))
+                }
+                _ => {}
+            }
+        }
+        if parsed.pids_limit == Some(0) {
+            return Err("pids limit must be greater than 0".to_string());
+        }
+        Ok(parsed)
+    }
+
+    fn requested<'a>(
+        &'a self,
+        request: impl Fn(&'a Requests) -> Option<&'a str>,
+        limit: impl Fn(&'a Limits) -> Option<&'a str>,
+    ) -> Option<&'a str> {
+        let resources = self.resources.as_ref()?;
+        resources
+            .requests
+            .as_ref()
+            .and_then(request)
+            .or_else(|| resources.limits.as_ref().and_then(limit))
+    }
+}
+
+/// Parse a Kubernetes CPU quantity ("500m", "1.5") into millicores
+pub fn parse_cpu_millis(quantity: &str) -> Option<u64> {
+    let quantity = quantity.trim();
+    match quantity.strip_suffix('m') {
+        Some(millis) => millis.parse().ok(),
+        None => quantity
+            .parse::<f64>()
+            .ok()
+            .filter(|cores| *cores >= 0.0)
+            .map(|cores| (cores * 1000.0).round() as u64),
+    }
+}
+
+/// Parse a Kubernetes memory quantity ("256Mi", "1Gi", "512M") into MiB, rounding up
+pub fn parse_memory_mb(quantity: &str) -> Option<u64> {
+    parse_memory_bytes(quantity).map(|bytes| bytes.div_ceil(1 << 20))
+}
+
+/// Parse a Kubernetes memory quantity ("256Mi", "1Gi", "512M") into bytes
+pub fn parse_memory_bytes(quantity: &str) -> Option<u64> {
+    const UNITS: [(&str, u64); 6] = [
+        ("Ki", 1 << 10),
+        ("Mi", 1 << 20),
+        ("Gi", 1 << 30),
+        ("K", 1_000),
+        ("M", 1_000_000),
+        ("G", 1_000_000_000),
+    ];
+
+    let quantity = quantity.trim();
+    let (number, multiplier) = UNITS
+        .iter()
+        .find_map(|(suffix, multiplier)| {
+            quantity
+                .strip_suffix(suffix)
+                .map(|number| (number, *multiplier))
+        })
+        .unwrap_or((quantity, 1));
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
 }
 
 //Unit Test Cases
@@ -141,6 +756,138 @@ impl PodSpec {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pod_from_model_keeps_metadata() {
+        let model: Model = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Model
+metadata:
+  name: hello-core
+  labels:
+    app: hello
+  annotations:
+    io.piccolo.annotations.package-network: default
+spec:
+  containers:
+    - name: hello
+      image: hello
+"#,
+        )
+        .unwrap();
+
+        let mut pod = Pod::from(model);
+        assert_eq!(pod.get_labels()[LABEL_MODEL], "hello-core");
+        assert_eq!(pod.get_labels()["app"], "hello");
+        assert_eq!(
+            pod.get_annotations()["io.piccolo.annotations.package-network"],
+            "default"
+        );
+
+        pod.add_labels(HashMap::from([
+            ("app".to_string(), "package".to_string()),
+            (LABEL_PACKAGE.to_string(), "hello".to_string()),
+        ]));
+        assert_eq!(pod.get_labels()["app"], "hello");
+        assert_eq!(pod.get_labels()[LABEL_PACKAGE], "hello");
+
+        pod.rename("hello-core-green");
+        assert_eq!(pod.get_name(), "hello-core-green");
+        assert_eq!(pod.get_labels()[LABEL_MODEL], "hello-core-green");
+        assert_eq!(pod.get_labels()["app"], "hello");
+    }
+
+    #[test]
+    fn test_piccolo_annotations_and_model_of() {
+        // A Pod not created from a Model names none
+        let mut pod = Pod::new("bare", serde_yaml::from_str("containers: []").unwrap());
+        assert!(pod.piccolo_annotations().is_empty());
+
+        pod.add_labels(HashMap::from([
+            (LABEL_MODEL.to_string(), "team-a/hello-core".to_string()),
+            (LABEL_PACKAGE.to_string(), "team-a/hello".to_string()),
+            ("app".to_string(), "hello".to_string()),
+        ]));
+        let annotations = pod.piccolo_annotations();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[LABEL_MODEL], "team-a/hello-core");
+        assert_eq!(annotations[LABEL_PACKAGE], "team-a/hello");
+
+        assert_eq!(model_of(&annotations).as_deref(), Some("team-a/hello-core"));
+        let legacy = HashMap::from([("pullpiri.model".to_string(), "legacy".to_string())]);
+        assert_eq!(model_of(&legacy).as_deref(), Some("legacy"));
+        assert_eq!(model_of(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_replicas_keep_their_model() {
+        let mut pod = Pod::new(
+            "hello-core",
+            serde_yaml::from_str("containers: []").unwrap(),
+        );
+        pod.add_labels(HashMap::from([(
+            LABEL_MODEL.to_string(),
+            "hello-core".to_string(),
+        )]));
+        pod.set_replica(2);
+        assert_eq!(pod.get_name(), "hello-core--2");
+        let annotations = pod.piccolo_annotations();
+        assert_eq!(annotations[LABEL_MODEL], "hello-core");
+        assert_eq!(annotations[LABEL_REPLICA], "2");
+
+        assert_eq!(replica_name("hello-core", 0), "hello-core");
+        assert_eq!(replica_base("hello-core--2"), Some("hello-core"));
+        assert_eq!(replica_base("hello-core"), None);
+        // A model named like a replica of another one is not a replica
+        assert_eq!(replica_base("hello-core-2"), None);
+        assert_eq!(replica_base("hello--"), None);
+        assert_eq!(replica_base("--2"), None);
+    }
+
+    #[test]
+    fn test_restart_policy_annotation() {
+        let pod = Pod::new("bare", serde_yaml::from_str("containers: []").unwrap());
+        assert!(!pod
+            .piccolo_annotations()
+            .contains_key(ANNOTATION_RESTART_POLICY));
+
+        let pod = Pod::new(
+            "never",
+            serde_yaml::from_str("{containers: [], restartPolicy: Never}").unwrap(),
+        );
+        let policy = &pod.piccolo_annotations()[ANNOTATION_RESTART_POLICY];
+        assert_eq!(
+            RestartPolicy::from_str_name(policy),
+            Some(RestartPolicy::Never)
+        );
+        assert_eq!(RestartPolicy::from_str_name("Sometimes"), None);
+    }
+
+    #[test]
+    fn test_standby_keeps_its_model() {
+        let mut pod = Pod::new(
+            "hello-core",
+            serde_yaml::from_str("containers: []").unwrap(),
+        );
+        pod.add_labels(HashMap::from([(
+            LABEL_MODEL.to_string(),
+            "hello-core".to_string(),
+        )]));
+        pod.set_standby();
+        assert_eq!(pod.get_name(), standby_name("hello-core"));
+        let annotations = pod.piccolo_annotations();
+        assert_eq!(annotations[LABEL_MODEL], "hello-core");
+        assert_eq!(annotations[LABEL_STANDBY], "true");
+        assert_eq!(standby_base("hello-core-standby"), Some("hello-core"));
+        assert_eq!(standby_base("-standby"), None);
+
+        pod.set_primary();
+        assert_eq!(pod.get_name(), "hello-core");
+        let annotations = pod.piccolo_annotations();
+        assert_eq!(annotations[LABEL_MODEL], "hello-core");
+        assert!(!annotations.contains_key(LABEL_STANDBY));
+    }
+
     // Positive Test: Validate that `get_image` returns the image of the first container
     // when multiple containers are present in the PodSpec.
     #[tokio::test]
@@ -155,6 +902,8 @@ mod tests {
             workingDir: None,
             resources: None,
             securityContext: None,
+            readinessProbe: None,
+            livenessProbe: None,
         };
         let container2 = Container {
             name: String::from("container-2"),
@@ -166,6 +915,8 @@ mod tests {
             workingDir: None,
             resources: None,
             securityContext: None,
+            readinessProbe: None,
+            livenessProbe: None,
         };
         let podspec = PodSpec {
             hostNetwork: None,
@@ -173,14 +924,26 @@ mod tests {
             volumes: None,
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(podspec.get_image(), Some("image-1"));
     }
 
+    #[test]
+    fn test_get_images() {
+        let podspec: PodSpec = serde_yaml::from_str(
+            "initContainers:\n  - name: init\n    image: busybox\ncontainers:\n  - name: a\n    image: app:1\n  - name: b\n    image: busybox\n",
+        )
+        .unwrap();
+        assert_eq!(podspec.get_images(), vec!["busybox", "app:1"]);
+    }
+
     // Negative Test: Validate that `get_image` returns `None` when no containers are present.
     #[tokio::test]
     async fn test_get_image_with_no_containers() {
@@ -190,10 +953,13 @@ mod tests {
             volumes: None,
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(podspec.get_image(), None);
     }
@@ -212,6 +978,8 @@ mod tests {
             workingDir: None,
             resources: None,
             securityContext: None,
+            readinessProbe: None,
+            livenessProbe: None,
         };
         let podspec = PodSpec {
             hostNetwork: None,
@@ -219,10 +987,13 @@ mod tests {
             volumes: None,
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(podspec.get_image(), Some(""));
     }
@@ -249,10 +1020,13 @@ mod tests {
             volumes: Some(vec![volume1, volume2]),
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(
             podspec.get_volume(),
@@ -282,10 +1056,13 @@ mod tests {
             volumes: None,
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(podspec.get_volume(), &None);
     }
@@ -299,10 +1076,13 @@ mod tests {
             volumes: Some(vec![]),
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(podspec.get_volume(), &Some(vec![]));
     }
@@ -322,10 +1102,13 @@ mod tests {
             volumes: Some(vec![volume]),
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(
             podspec.get_volume(),
@@ -352,6 +1135,8 @@ mod tests {
             workingDir: None,
             resources: None,
             securityContext: None,
+            readinessProbe: None,
+            livenessProbe: None,
         };
         let podspec = PodSpec {
             hostNetwork: None,
@@ -359,11 +1144,236 @@ mod tests {
             volumes: None,
             initContainers: None,
             restartPolicy: None,
+            maxRestarts: None,
             terminationGracePeriodSeconds: None,
             hostIPC: None,
             runtimeClassName: None,
             securityContext: None,
+            nodeSelector: None,
+            tolerations: None,
         };
         assert_eq!(podspec.get_image(), Some("special:image@tag"));
     }
+
+    #[test]
+    fn test_parse_resource_quantities() {
+        assert_eq!(parse_cpu_millis("500m"), Some(500));
+        assert_eq!(parse_cpu_millis("1.5"), Some(1500));
+        assert_eq!(parse_cpu_millis("two"), None);
+        assert_eq!(parse_memory_mb("256Mi"), Some(256));
+        assert_eq!(parse_memory_mb("1Gi"), Some(1024));
+        assert_eq!(parse_memory_mb("1M"), Some(1));
+        assert_eq!(parse_memory_mb("lots"), None);
+    }
+
+    #[test]
+    fn test_resource_requests_fall_back_to_limits() {
+        let podspec: PodSpec = serde_yaml::from_str(
+            r#"
+containers:
+  - name: requested
+    image: image-1
+    resources:
+      requests:
+        cpu: 250m
+        memory: 128Mi
+      limits:
+        cpu: "1"
+        memory: 1Gi
+  - name: limited
+    image: image-2
+    resources:
+      limits:
+        cpu: 500m
+        memory: 64Mi
+  - name: unbounded
+    image: image-3
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(podspec.cpu_request_millis(), 750);
+        assert_eq!(podspec.memory_request_mb(), 192);
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let podspec: PodSpec = serde_yaml::from_str(
+            r#"
+containers:
+  - name: limited
+    image: image-1
+    resources:
+      requests:
+        cpu: 250m
+        memory: 128Mi
+      limits:
+        cpu: "1"
+        memory: 1Gi
+        pids: "64"
+  - name: unbounded
+    image: image-2
+"#,
+        )
+        .unwrap();
+        assert!(podspec.validate_resources().is_ok());
+        assert_eq!(
+            podspec.containers[0].resource_limits(),
+            Ok(ResourceLimits {
+                cpu_request_millis: Some(250),
+                cpu_limit_millis: Some(1000),
+                memory_request_bytes: Some(128 << 20),
+                memory_limit_bytes: Some(1 << 30),
+                pids_limit: Some(64),
+            })
+        );
+        assert_eq!(
+            podspec.containers[1].resource_limits(),
+            Ok(ResourceLimits::default())
+        );
+
+        for resources in [
+            "{limits: {memory: lots}}",
+            "{limits: {cpu: \"0\"}}",
+            "{limits: {pids: \"-1\"}}",
+            "{requests: {cpu: \"2\"}, limits: {cpu: \"1\"}}",
+        ] {
+            let podspec: PodSpec = serde_yaml::from_str(&format!(
+                "containers:\n  - name: bad\n    image: image-1\n    resources: {}\n",
+                resources
+            ))
+            .unwrap();
+            let error = podspec.validate_resources().unwrap_err();
+            assert!(error.starts_with("container 'bad'"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_probes() {
+        let podspec: PodSpec = serde_yaml::from_str(
+            r#"
+containers:
+  - name: server
+    image: image-1
+    readinessProbe:
+      httpGet: {port: 8080, path: /ready}
+      periodSeconds: 2
+    livenessProbe:
+      exec: {command: [cat, /tmp/alive]}
+      initialDelaySeconds: 5
+"#,
+        )
+        .unwrap();
+        assert!(podspec.validate_probes().is_ok());
+        let readiness = podspec.containers[0].readiness_probe().unwrap();
+        assert_eq!(
+            readiness.handler(),
+            Some(ProbeHandler::Http {
+                host: "127.0.0.1",
+                port: 8080,
+                path: "/ready"
+            })
+        );
+        assert_eq!(readiness.period().as_secs(), 2);
+        assert_eq!(readiness.failure_threshold(), 3);
+        let liveness = podspec.containers[0].liveness_probe().unwrap();
+        assert_eq!(liveness.initial_delay().as_secs(), 5);
+
+        for probe in [
+            "{}",
+            "{exec: {command: [sh]}, tcpSocket: {port: 1}}",
+            "{exec: {command: []}}",
+            "{tcpSocket: {port: 0}}",
+            "{tcpSocket: {port: 1}, periodSeconds: 0}",
+            "{httpGet: {port: 1}, failureThreshold: 0}",
+        ] {
+            let podspec: PodSpec = serde_yaml::from_str(&format!(
+                "containers:\n  - name: bad\n    image: image-1\n    livenessProbe: {}\n",
+                probe
+            ))
+            .unwrap();
+            let error = podspec.validate_probes().unwrap_err();
+            assert!(error.starts_with("container 'bad' liveness"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_restart_policy() {
+        let podspec = |extra: &str| {
+            serde_yaml::from_str::<PodSpec>(&format!(
+                "containers:\n  - name: c\n    image: image-1\n{}",
+                extra
+            ))
+        };
+        let default = podspec("").unwrap();
+        assert_eq!(default.restart_policy(), RestartPolicy::Always);
+        assert_eq!(default.max_restarts(), 5);
+        assert_eq!(default.termination_grace_period_seconds(), 30);
+        let on_failure = podspec("restartPolicy: OnFailure\nmaxRestarts: 2\n").unwrap();
+        assert_eq!(on_failure.restart_policy(), RestartPolicy::OnFailure);
+        assert_eq!(on_failure.max_restarts(), 2);
+        assert!(podspec("restartPolicy: Sometimes\n").is_err());
+        let grace = |seconds: &str| {
+            podspec(&format!("terminationGracePeriodSeconds: {}\n", seconds))
+                .unwrap()
+                .termination_grace_period_seconds()
+        };
+        assert_eq!(grace("5"), 5);
+        assert_eq!(grace("0"), 0);
+        assert_eq!(grace("-1"), 0);
+    }
+
+    #[test]
+    fn test_placement_error() {
+        let podspec: PodSpec = serde_yaml::from_str(
+            r#"
+containers:
+  - name: c
+    image: image-1
+nodeSelector:
+  gpu: "true"
+tolerations:
+  - key: safety-domain
+    operator: Equal
+    value: ASIL-B
+    effect: NoSchedule
+"#,
+        )
+        .unwrap();
+        let taint = |key: &str, value: &str, effect: &str| Taint {
+            key: key.to_string(),
+            value: value.to_string(),
+            effect: effect.to_string(),
+        };
+        let gpu = HashMap::from([("gpu".to_string(), "true".to_string())]);
+
+        assert_eq!(podspec.placement_error(&gpu, &[]), None);
+        assert_eq!(
+            podspec.placement_error(&HashMap::new(), &[]).as_deref(),
+            Some("node lacks label gpu=true")
+        );
+        let asil_b = taint("safety-domain", "ASIL-B", TAINT_NO_SCHEDULE);
+        assert_eq!(podspec.placement_error(&gpu, &[asil_b]), None);
+        let asil_d = taint("safety-domain", "ASIL-D", TAINT_NO_SCHEDULE);
+        assert_eq!(
+            podspec.placement_error(&gpu, &[asil_d]).as_deref(),
+            Some("taint safety-domain=ASIL-D is not tolerated")
+        );
+        let preferred = taint("dedicated", "infotainment", TAINT_PREFER_NO_SCHEDULE);
+        let preferred = [preferred];
+        assert_eq!(podspec.placement_error(&gpu, &preferred), None);
+        assert!(podspec.avoids(&preferred));
+        assert!(!podspec.avoids(&[]));
+
+        let any: Toleration = serde_yaml::from_str("operator: Exists").unwrap();
+        assert!(any.tolerates(&taint("dedicated", "x", TAINT_NO_SCHEDULE)));
+        let key_only: Toleration = serde_yaml::from_str(
+            "key: gpu
+operator: Exists
+effect: PreferNoSchedule",
+        )
+        .unwrap();
+        assert!(key_only.tolerates(&taint("gpu", "x", TAINT_PREFER_NO_SCHEDULE)));
+        assert!(!key_only.tolerates(&taint("gpu", "x", TAINT_NO_SCHEDULE)));
+    }
 }
@@ -13,10 +13,30 @@ use std::collections::HashMap;
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 struct MetaData {
     name: String,
+    /// Namespace of an artifact, see [`crate::namespace`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
     labels: Option<HashMap<String, String>>,
     annotations: Option<HashMap<String, String>>,
 }
 
+impl MetaData {
+    fn get_namespace(&self) -> String {
+        self.namespace
+            .clone()
+            .filter(|namespace| !namespace.is_empty())
+            .unwrap_or_else(|| crate::namespace::DEFAULT.to_string())
+    }
+
+    fn get_labels(&self) -> HashMap<String, String> {
+        self.labels.clone().unwrap_or_default()
+    }
+
+    fn get_annotations(&self) -> HashMap<String, String> {
+        self.annotations.clone().unwrap_or_default()
+    }
+}
+
 //Unit Test Cases
 #[cfg(test)]
 mod tests {
@@ -29,6 +49,7 @@ mod tests {
         // Positive test: Creating a valid MetaData instance
         let metadata = MetaData {
             name: String::from("TestObject"),
+            namespace: None,
             labels: Some(HashMap::from([
                 (String::from("key1"), String::from("value1")),
                 (String::from("key2"), String::from("value2")),
@@ -49,6 +70,7 @@ mod tests {
         // Positive test: Serialization of MetaData to JSON
         let metadata = MetaData {
             name: String::from("TestObject"),
+            namespace: None,
             labels: Some(HashMap::from([(
                 String::from("key1"),
                 String::from("value1"),
@@ -82,6 +104,7 @@ mod tests {
         // Positive test: Equality between two MetaData instances
         let metadata1 = MetaData {
             name: String::from("TestObject"),
+            namespace: None,
             labels: Some(HashMap::from([(
                 String::from("key1"),
                 String::from("value1"),
@@ -91,6 +114,7 @@ mod tests {
 
         let metadata2 = MetaData {
             name: String::from("TestObject"),
+            namespace: None,
             labels: Some(HashMap::from([(
                 String::from("key1"),
                 String::from("value1"),
@@ -106,6 +130,7 @@ mod tests {
         // Positive test: MetaData with optional fields as None
         let metadata = MetaData {
             name: String::from("TestObject"),
+            namespace: None,
             labels: None,
             annotations: None,
         };
@@ -145,6 +170,7 @@ mod tests {
         // Negative test: Creating MetaData with an empty name
         let metadata = MetaData {
             name: String::from(""),
+            namespace: None,
             labels: None,
             annotations: None,
         };
@@ -159,6 +185,7 @@ mod tests {
         // Negative test: Attempting to serialize invalid MetaData (e.g., invalid types)
         let metadata = MetaData {
             name: String::from("TestObject"),
+            namespace: None,
             labels: Some(HashMap::from([(
                 String::from("key1"),
                 String::from("value1"),
@@ -0,0 +1,266 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Startup gate of a component
+//!
+//! Components started together would otherwise open their gRPC listeners and
+//! fail on every request until etcd is up. A component calls [`wait`] with
+//! the [`Dependency`]s it needs before it opens its listeners. Each
+//! dependency is checked with a backoff doubling from
+//! `startup.initial_backoff_ms` up to `startup.max_backoff_ms`, and every
+//! change between unavailable and available is logged and shown in the
+//! readiness checks of [`crate::health`].
+//!
+//! When the dependencies are not all available within
+//! `startup.timeout_secs`, [`wait`] fails, unless
+//! `startup.degraded_on_timeout` is set: the component then starts in
+//! degraded mode and the missing dependencies keep being checked in the
+//! background.
+
+use crate::setting::StartupSettings;
+use std::time::{Duration, Instant};
+
+/// Something a component needs before it serves requests
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dependency {
+    /// The key-value store behind `common::etcd`
+    Etcd,
+    /// A TCP endpoint such as the gRPC server of another component
+    Endpoint { name: String, addr: String },
+}
+
+impl Dependency {
+    /// Name of the dependency in logs and readiness checks
+    pub fn name(&self) -> &str {
+        match self {
+            Dependency::Etcd => "etcd",
+            Dependency::Endpoint { name, .. } => name,
+        }
+    }
+
+    /// Check once whether the dependency is available
+    pub async fn check(&self) -> Result<(), String> {
+        let timeout = Duration::from_millis(crate::setting::get_config().health.etcd_timeout_ms);
+        match self {
+            Dependency::Etcd => {
+                match tokio::time::timeout(timeout, crate::etcd::health_check()).await {
+                    Ok(Ok(true)) => Ok(()),
+                    Ok(Ok(false)) => Err("unhealthy".to_string()),
+                    Ok(Err(e)) => Err(e),
+                    Err(_) => Err("timed out".to_string()),
+                }
+            }
+            Dependency::Endpoint { addr, .. } => {
+                let addr = addr
+                    .trim_start_matches("http://")
+                    .trim_start_matches("https://");
+                match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
+                    Ok(Ok(_)) => Ok(()),
+                    Ok(Err(e)) => Err(e.to_string()),
+                    Err(_) => Err("timed out".to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// How a component started
+#[derive(Debug, Clone, PartialEq)]
+pub enum Startup {
+    /// Every dependency was available
+    Ready,
+    /// The wait timed out, with the names of the missing dependencies
+    Degraded(Vec<String>),
+}
+
+/// Delay after the `attempt`th failed check, from 1
+fn backoff(settings: &StartupSettings, attempt: u32) -> Duration {
+    Duration::from_millis(settings.initial_backoff_ms)
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(Duration::from_millis(settings.max_backoff_ms))
+}
+
+/// Records whether a dependency is available, returns whether that changed
+type Report = fn(&str, bool, &str) -> bool;
+
+/// Wait for the dependencies of a component with the startup settings
+///
+/// ### Returns
+/// * `Ok(Startup)` - whether the component may start normally or degraded
+/// * `Err(String)` - the missing dependencies, when the wait timed out and
+///   degraded mode is off
+pub async fn wait(component: &str, dependencies: &[Dependency]) -> Result<Startup, String> {
+    let settings = crate::setting::get_config().startup.clone();
+    gate(
+        component,
+        dependencies,
+        &settings,
+        crate::health::set_dependency,
+    )
+    .await
+}
+
+async fn gate(
+    component: &str,
+    dependencies: &[Dependency],
+    settings: &StartupSettings,
+    report: Report,
+) -> Result<Startup, String> {
+    let started = Instant::now();
+    let deadline =
+        (settings.timeout_secs > 0).then(|| started + Duration::from_secs(settings.timeout_secs));
+    let mut missing: Vec<Dependency> = dependencies.to_vec();
+    let mut attempt = 0;
+    loop {
+        missing = check_all(component, missing, report).await;
+        if missing.is_empty() {
+            crate::logd!(
+                3,
+                "{} dependencies available after {}ms",
+                component,
+                started.elapsed().as_millis()
+            );
+            return Ok(Startup::Ready);
+        }
+
+        attempt += 1;
+        let delay = backoff(settings, attempt);
+        if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+            break;
+        }
+        tokio::time::sleep(delay).await;
+    }
+
+    let names: Vec<String> = missing.iter().map(|d| d.name().to_string()).collect();
+    if !settings.degraded_on_timeout {
+        return Err(format!(
+            "{} gave up waiting for {} after {}s",
+            component,
+            names.join(", "),
+            settings.timeout_secs
+        ));
+    }
+    crate::logd!(
+        5,
+        "{} starts in degraded mode without {}",
+        component,
+        names.join(", ")
+    );
+    tokio::spawn(watch(
+        component.to_string(),
+        missing,
+        settings.clone(),
+        report,
+    ));
+    Ok(Startup::Degraded(names))
+}
+
+/// Keep checking the dependencies missing after a degraded start
+async fn watch(
+    component: String,
+    mut missing: Vec<Dependency>,
+    settings: StartupSettings,
+    report: Report,
+) {
+    let mut attempt = 0;
+    while !missing.is_empty() {
+        attempt += 1;
+        tokio::time::sleep(backoff(&settings, attempt)).await;
+        missing = check_all(&component, missing, report).await;
+    }
+    crate::logd!(3, "{} left degraded mode", component);
+}
+
+/// Check the dependencies not available yet, returns those still missing
+async fn check_all(
+    component: &str,
+    dependencies: Vec<Dependency>,
+    report: Report,
+) -> Vec<Dependency> {
+    let mut missing = Vec::new();
+    for dependency in dependencies {
+        match dependency.check().await {
+            Ok(()) => {
+                if report(dependency.name(), true, "available") {
+                    crate::logd!(3, "{}: {} is available", component, dependency.name());
+                }
+            }
+            Err(e) => {
+                if report(dependency.name(), false, &e) {
+                    crate::logd!(4, "{}: waiting for {}: {}", component, dependency.name(), e);
+                }
+                missing.push(dependency);
+            }
+        }
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(timeout_secs: u64, degraded_on_timeout: bool) -> StartupSettings {
+        StartupSettings {
+            initial_backoff_ms: 10,
+            max_backoff_ms: 40,
+            timeout_secs,
+            degraded_on_timeout,
+        }
+    }
+
+    /// Keeps the health of the component out of the tests
+    fn report(_name: &str, _ok: bool, _detail: &str) -> bool {
+        true
+    }
+
+    /// Address nothing listens on
+    async fn closed_port() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let settings = settings(1, false);
+        assert_eq!(backoff(&settings, 1), Duration::from_millis(10));
+        assert_eq!(backoff(&settings, 2), Duration::from_millis(20));
+        assert_eq!(backoff(&settings, 3), Duration::from_millis(40));
+        assert_eq!(backoff(&settings, 40), Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_available_endpoint() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = Dependency::Endpoint {
+            name: "test-server".to_string(),
+            addr: format!("http://{}", listener.local_addr().unwrap()),
+        };
+        let startup = gate("test", &[endpoint], &settings(1, false), report).await;
+        assert_eq!(startup, Ok(Startup::Ready));
+    }
+
+    #[tokio::test]
+    async fn test_wait_times_out_or_degrades() {
+        let endpoint = Dependency::Endpoint {
+            name: "missing-server".to_string(),
+            addr: closed_port().await,
+        };
+        let failed = gate(
+            "test",
+            std::slice::from_ref(&endpoint),
+            &settings(1, false),
+            report,
+        )
+        .await;
+        assert!(failed.unwrap_err().contains("missing-server"));
+
+        let degraded = gate("test", &[endpoint], &settings(1, true), report).await;
+        assert_eq!(
+            degraded,
+            Ok(Startup::Degraded(vec!["missing-server".to_string()]))
+        );
+    }
+}
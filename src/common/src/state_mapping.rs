@@ -0,0 +1,284 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Single mapping between resource states and their names
+//!
+//! States travel as proto enums, as short names in StateChange requests
+//! ("waiting") and as strings in etcd, where models are stored by short name
+//! ("Running") and packages and scenarios by proto name ("PACKAGE_STATE_RUNNING").
+//! Every component converts through this module so that a state name is
+//! always read back as the same state, and an unrecognized name is reported
+//! as such instead of silently becoming another state.
+//!
+//! Models and packages used to be stored with a Kubernetes-like state set
+//! ("Pending", "Succeeded", "Failed", ...). [`StateName::migrate`] reads
+//! those names as the LLD state they stood for, so that states persisted by
+//! an older version are not lost; new requests must use the current names.
+
+use crate::statemanager::{
+    ModelState, NetworkState, NodeState, PackageState, ResourceType, ScenarioState,
+};
+
+/// Conversion of a state enum from and to its names
+pub trait StateName: Sized + Copy {
+    /// Short name, e.g. "Running"
+    fn name(&self) -> &'static str;
+
+    /// Parse a short name in any case or a proto enum name
+    ///
+    /// ### Returns
+    /// * `None` - the name is unknown or the unspecified state
+    fn parse(value: &str) -> Option<Self>;
+
+    /// Parse a stored name, also accepting the names of the former state set
+    ///
+    /// ### Returns
+    /// * `None` - the name is neither current nor a former one
+    fn migrate(value: &str) -> Option<Self> {
+        Self::parse(value)
+    }
+}
+
+/// Upper case name without the proto prefix and word separators
+fn normalize(value: &str, prefix: &str) -> String {
+    let normalized = value.trim().to_ascii_uppercase().replace('-', "_");
+    normalized
+        .strip_prefix(prefix)
+        .unwrap_or(&normalized)
+        .replace('_', "")
+}
+
+macro_rules! state_names {
+    ($state:ident, $prefix:literal, [$($variant:ident => $name:literal),+ $(,)?]
+        $(, legacy [$($old:literal => $new:ident),+ $(,)?])?) => {
+        impl StateName for $state {
+            fn name(&self) -> &'static str {
+                match self {
+                    $state::Unspecified => "Unspecified",
+                    $($state::$variant => $name,)+
+                }
+            }
+
+            fn parse(value: &str) -> Option<Self> {
+                let short = normalize(value, $prefix);
+                $(
+                    if short.eq_ignore_ascii_case($name) {
+                        return Some($state::$variant);
+                    }
+                )+
+                None
+            }
+
+            $(
+                fn migrate(value: &str) -> Option<Self> {
+                    if let Some(state) = Self::parse(value) {
+                        return Some(state);
+                    }
+                    let short = normalize(value, $prefix);
+                    $(
+                        if short.eq_ignore_ascii_case($old) {
+                            return Some($state::$new);
+                        }
+                    )+
+                    None
+                }
+            )?
+        }
+    };
+}
+
+state_names!(ScenarioState, "SCENARIO_STATE_", [
+    Idle => "Idle",
+    Waiting => "Waiting",
+    Satisfied => "Satisfied",
+    Allowed => "Allowed",
+    Denied => "Denied",
+    Completed => "Completed",
+    Pending => "Pending",
+]);
+
+state_names!(PackageState, "PACKAGE_STATE_", [
+    Idle => "Idle",
+    Paused => "Paused",
+    Exited => "Exited",
+    Degraded => "Degraded",
+    Error => "Error",
+    Running => "Running",
+    Updating => "Updating",
+], legacy [
+    "Initializing" => Idle,
+    "Pending" => Idle,
+    "Stopped" => Exited,
+    "Succeeded" => Exited,
+    "Failed" => Error,
+]);
+
+state_names!(ModelState, "MODEL_STATE_", [
+    Created => "Created",
+    Paused => "Paused",
+    Exited => "Exited",
+    Dead => "Dead",
+    Running => "Running",
+    CrashLoopBackOff => "CrashLoopBackOff",
+    Degraded => "Degraded",
+], legacy [
+    "Pending" => Created,
+    "ContainerCreating" => Created,
+    "Succeeded" => Exited,
+    "Completed" => Exited,
+    "Stopped" => Exited,
+    "Failed" => Dead,
+]);
+
+state_names!(NetworkState, "NETWORK_STATE_", [
+    Requested => "Requested",
+    Ready => "Ready",
+    Failed => "Failed",
+]);
+
+state_names!(NodeState, "NODE_STATE_", [
+    Schedulable => "Schedulable",
+    Cordoned => "Cordoned",
+    Draining => "Draining",
+    Drained => "Drained",
+]);
+
+/// Parse the state of a resource of the given type into its proto value
+///
+/// Resource types without states yield `None`.
+pub fn parse_state(resource_type: ResourceType, value: &str) -> Option<i32> {
+    match resource_type {
+        ResourceType::Scenario => ScenarioState::parse(value).map(|s| s as i32),
+        ResourceType::Package => PackageState::parse(value).map(|s| s as i32),
+        ResourceType::Model => ModelState::parse(value).map(|s| s as i32),
+        ResourceType::Network => NetworkState::parse(value).map(|s| s as i32),
+        ResourceType::Node => NodeState::parse(value).map(|s| s as i32),
+        _ => None,
+    }
+}
+
+/// Short name of the proto state value of a resource of the given type
+pub fn state_name(resource_type: ResourceType, state: i32) -> Option<&'static str> {
+    match resource_type {
+        ResourceType::Scenario => ScenarioState::try_from(state).ok().map(|s| s.name()),
+        ResourceType::Package => PackageState::try_from(state).ok().map(|s| s.name()),
+        ResourceType::Model => ModelState::try_from(state).ok().map(|s| s.name()),
+        ResourceType::Network => NetworkState::try_from(state).ok().map(|s| s.name()),
+        ResourceType::Node => NodeState::try_from(state).ok().map(|s| s.name()),
+        _ => None,
+    }
+}
+
+/// Proto enum name of the state value of a resource of the given type
+pub fn proto_state_name(resource_type: ResourceType, state: i32) -> Option<&'static str> {
+    match resource_type {
+        ResourceType::Scenario => ScenarioState::try_from(state).ok().map(|s| s.as_str_name()),
+        ResourceType::Package => PackageState::try_from(state).ok().map(|s| s.as_str_name()),
+        ResourceType::Model => ModelState::try_from(state).ok().map(|s| s.as_str_name()),
+        ResourceType::Network => NetworkState::try_from(state).ok().map(|s| s.as_str_name()),
+        ResourceType::Node => NodeState::try_from(state).ok().map(|s| s.as_str_name()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accepts_short_and_proto_names() {
+        assert_eq!(ModelState::parse("Running"), Some(ModelState::Running));
+        assert_eq!(ModelState::parse(" paused "), Some(ModelState::Paused));
+        assert_eq!(
+            ModelState::parse("MODEL_STATE_DEAD"),
+            Some(ModelState::Dead)
+        );
+        assert_eq!(PackageState::parse("exited"), Some(PackageState::Exited));
+        assert_eq!(
+            PackageState::parse("PACKAGE_STATE_DEGRADED"),
+            Some(PackageState::Degraded)
+        );
+        assert_eq!(
+            ScenarioState::parse("Satisfied"),
+            Some(ScenarioState::Satisfied)
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_and_unspecified() {
+        assert_eq!(ModelState::parse("Unknown"), None);
+        assert_eq!(ModelState::parse("MODEL_STATE_UNSPECIFIED"), None);
+        assert_eq!(PackageState::parse(""), None);
+        // Names of other resource types are not accepted
+        assert_eq!(PackageState::parse("Created"), None);
+        assert_eq!(ModelState::parse("PACKAGE_STATE_RUNNING"), None);
+    }
+
+    #[test]
+    fn test_migrate_reads_former_names() {
+        assert_eq!(ModelState::migrate("Pending"), Some(ModelState::Created));
+        assert_eq!(
+            ModelState::migrate("MODEL_STATE_SUCCEEDED"),
+            Some(ModelState::Exited)
+        );
+        assert_eq!(ModelState::migrate("failed"), Some(ModelState::Dead));
+        assert_eq!(ModelState::migrate("Running"), Some(ModelState::Running));
+        assert_eq!(
+            PackageState::migrate("PACKAGE_STATE_INITIALIZING"),
+            Some(PackageState::Idle)
+        );
+        assert_eq!(PackageState::migrate("Failed"), Some(PackageState::Error));
+        // Unknown never stood for a particular state
+        assert_eq!(ModelState::migrate("Unknown"), None);
+        // Former names are not accepted from new requests
+        assert_eq!(ModelState::parse("Pending"), None);
+        assert_eq!(ScenarioState::migrate("Failed"), None);
+    }
+
+    #[test]
+    fn test_names_roundtrip() {
+        for state in [
+            PackageState::Idle,
+            PackageState::Paused,
+            PackageState::Exited,
+            PackageState::Degraded,
+            PackageState::Error,
+            PackageState::Running,
+            PackageState::Updating,
+        ] {
+            assert_eq!(PackageState::parse(state.name()), Some(state));
+            assert_eq!(PackageState::parse(state.as_str_name()), Some(state));
+        }
+        assert_eq!(
+            ModelState::parse(ModelState::CrashLoopBackOff.as_str_name()),
+            Some(ModelState::CrashLoopBackOff)
+        );
+        assert_eq!(
+            parse_state(ResourceType::Model, "exited"),
+            Some(ModelState::Exited as i32)
+        );
+        assert_eq!(
+            state_name(ResourceType::Scenario, ScenarioState::Denied as i32),
+            Some("Denied")
+        );
+        assert_eq!(
+            parse_state(ResourceType::Network, "ready"),
+            Some(NetworkState::Ready as i32)
+        );
+        assert_eq!(
+            proto_state_name(ResourceType::Network, NetworkState::Failed as i32),
+            Some("NETWORK_STATE_FAILED")
+        );
+        assert_eq!(
+            parse_state(ResourceType::Node, "cordoned"),
+            Some(NodeState::Cordoned as i32)
+        );
+        assert_eq!(
+            proto_state_name(ResourceType::Node, NodeState::Drained as i32),
+            Some("NODE_STATE_DRAINED")
+        );
+        assert_eq!(parse_state(ResourceType::Volume, "Idle"), None);
+    }
+}
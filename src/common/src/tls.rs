@@ -0,0 +1,272 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! TLS for gRPC between components
+//!
+//! The `tls` section of settings.yaml selects one of three modes:
+//! * `disabled` - plaintext gRPC, the default
+//! * `permissive` - TLS when the certificate files can be loaded, plaintext
+//!   otherwise; servers accept clients without a certificate. For development.
+//! * `strict` - mutual TLS; every client must present a certificate signed by
+//!   the configured CA, and nothing falls back to plaintext
+//!
+//! The StateManager, ActionController, ApiServer and NodeAgent servers are
+//! built with [`server`], and their clients reach them through the `https`
+//! addresses of [`scheme`], for which `common::grpc::channel` applies
+//! [`client_config`]. When `allowed_peers` lists SPIFFE-style identities
+//! (`spiffe://piccolo/statemanager`), servers only accept clients whose
+//! certificate carries one of them as URI SAN.
+
+use crate::setting::TlsSettings;
+use std::sync::OnceLock;
+use tonic::service::interceptor::InterceptorLayer;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+use tonic::{Request, Status};
+use tower::layer::util::{Identity as NoLayer, Stack};
+
+/// How components protect their gRPC connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    Disabled,
+    Permissive,
+    Strict,
+}
+
+impl std::str::FromStr for TlsMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "disabled" | "off" => Ok(TlsMode::Disabled),
+            "permissive" => Ok(TlsMode::Permissive),
+            "strict" => Ok(TlsMode::Strict),
+            other => Err(format!("unknown TLS mode '{}'", other)),
+        }
+    }
+}
+
+/// Certificates loaded from the configured files
+struct TlsMaterial {
+    mode: TlsMode,
+    ca: Certificate,
+    identity: Identity,
+    server_name: String,
+}
+
+/// Interceptor checking the identity of TLS clients
+pub type PeerCheck = fn(Request<()>) -> Result<Request<()>, Status>;
+
+/// gRPC server builder with TLS and peer checks applied
+pub type Server = tonic::transport::Server<Stack<InterceptorLayer<PeerCheck>, NoLayer>>;
+
+/// Load the certificates for the configured mode
+///
+/// ### Returns
+/// * `Ok(None)` - plaintext, either disabled or a permissive fallback
+/// * `Ok(Some(_))` - TLS is used
+/// * `Err(String)` - strict mode without usable certificates
+fn load(settings: &TlsSettings) -> Result<Option<TlsMaterial>, String> {
+    let mode: TlsMode = settings.mode.parse()?;
+    if mode == TlsMode::Disabled {
+        return Ok(None);
+    }
+
+    let read =
+        |path: &str| std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e));
+    let material = (|| {
+        Ok::<_, String>(TlsMaterial {
+            mode,
+            ca: Certificate::from_pem(read(&settings.ca_cert)?),
+            identity: Identity::from_pem(read(&settings.cert)?, read(&settings.key)?),
+            server_name: settings.server_name.clone(),
+        })
+    })();
+
+    match (material, mode) {
+        (Ok(material), _) => Ok(Some(material)),
+        (Err(e), TlsMode::Permissive) => {
+            crate::logd!(4, "TLS unavailable, falling back to plaintext gRPC: {}", e);
+            Ok(None)
+        }
+        (Err(e), _) => Err(e),
+    }
+}
+
+fn material() -> &'static Result<Option<TlsMaterial>, String> {
+    static MATERIAL: OnceLock<Result<Option<TlsMaterial>, String>> = OnceLock::new();
+    MATERIAL.get_or_init(|| load(&crate::setting::get_config().tls))
+}
+
+/// URI scheme of the TLS-capable servers, `https` unless plaintext is used
+pub fn scheme() -> &'static str {
+    match material() {
+        Ok(None) => "http",
+        _ => "https",
+    }
+}
+
+/// TLS configuration of a gRPC server
+fn server_config(material: &TlsMaterial) -> ServerTlsConfig {
+    ServerTlsConfig::new()
+        .identity(material.identity.clone())
+        .client_ca_root(material.ca.clone())
+        .client_auth_optional(material.mode == TlsMode::Permissive)
+}
+
+/// TLS configuration of a gRPC client, `None` for plaintext
+pub fn client_config() -> Result<Option<ClientTlsConfig>, String> {
+    match material() {
+        Ok(Some(material)) => Ok(Some(
+            ClientTlsConfig::new()
+                .ca_certificate(material.ca.clone())
+                .identity(material.identity.clone())
+                .domain_name(material.server_name.clone()),
+        )),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e.clone()),
+    }
+}
+
+/// Builder of a gRPC server using the configured TLS mode
+///
+/// ### Returns
+/// * `Err(String)` - strict mode without usable certificates, the server
+///   must not start in plaintext
+pub fn server() -> Result<Server, String> {
+    let mut builder = tonic::transport::Server::builder();
+    if let Some(material) = material().as_ref().map_err(Clone::clone)? {
+        builder = builder
+            .tls_config(server_config(material))
+            .map_err(|e| format!("invalid TLS configuration: {}", e))?;
+    }
+    Ok(builder.layer(tonic::service::interceptor(authorize_peer as PeerCheck)))
+}
+
+/// Reject clients whose certificate does not carry an allowed identity
+#[allow(clippy::result_large_err)]
+fn authorize_peer(request: Request<()>) -> Result<Request<()>, Status> {
+    let allowed = &crate::setting::get_config().tls.allowed_peers;
+    let mode = match material() {
+        Ok(Some(material)) => material.mode,
+        _ => return Ok(request),
+    };
+    if allowed.is_empty() {
+        return Ok(request);
+    }
+
+    let authorized = request
+        .peer_certs()
+        .and_then(|certs| certs.first().map(|leaf| leaf.to_vec()))
+        .is_some_and(|leaf| allowed.iter().any(|id| has_uri_san(&leaf, id)));
+    if authorized {
+        Ok(request)
+    } else if mode == TlsMode::Permissive {
+        crate::logd!(4, "Accepting gRPC client without an allowed identity");
+        Ok(request)
+    } else {
+        Err(Status::permission_denied("Client identity is not allowed"))
+    }
+}
+
+/// Whether a DER certificate has the URI as subject alternative name
+///
+/// Only the URI entries of the SubjectAltName extension count, the same
+/// bytes elsewhere in the certificate do not.
+pub(crate) fn has_uri_san(der: &[u8], uri: &str) -> bool {
+    use x509_parser::extensions::GeneralName;
+
+    let Ok((_, certificate)) = x509_parser::parse_x509_certificate(der) else {
+        return false;
+    };
+    match certificate.subject_alternative_name() {
+        Ok(Some(san)) => san
+            .value
+            .general_names
+            .iter()
+            .any(|name| matches!(name, GeneralName::URI(value) if *value == uri)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(mode: &str) -> TlsSettings {
+        TlsSettings {
+            mode: mode.to_string(),
+            ca_cert: "/nonexistent/ca.pem".to_string(),
+            cert: "/nonexistent/cert.pem".to_string(),
+            key: "/nonexistent/key.pem".to_string(),
+            ..TlsSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_tls_mode_from_str() {
+        assert_eq!("".parse(), Ok(TlsMode::Disabled));
+        assert_eq!("Permissive".parse(), Ok(TlsMode::Permissive));
+        assert_eq!("strict".parse(), Ok(TlsMode::Strict));
+        assert!("mutual".parse::<TlsMode>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_falls_back_only_in_permissive_mode() {
+        assert!(matches!(load(&settings("disabled")), Ok(None)));
+        assert!(matches!(load(&settings("permissive")), Ok(None)));
+        let err = load(&settings("strict")).err().unwrap();
+        assert!(err.contains("/nonexistent/ca.pem"));
+        assert!(load(&settings("bogus")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_settings_use_plaintext() {
+        assert_eq!(scheme(), "http");
+        assert!(client_config().unwrap().is_none());
+        assert!(server().is_ok());
+        assert!(authorize_peer(Request::new(())).is_ok());
+    }
+
+    /// Self-signed DER certificate with the given URI SANs and extensions
+    fn certificate(uris: &[&str], extensions: Vec<rcgen::CustomExtension>) -> Vec<u8> {
+        let mut params = rcgen::CertificateParams::default();
+        params.subject_alt_names = uris
+            .iter()
+            .map(|uri| rcgen::SanType::URI(rcgen::Ia5String::try_from(*uri).unwrap()))
+            .collect();
+        params.custom_extensions = extensions;
+        let key = rcgen::KeyPair::generate().unwrap();
+        params.self_signed(&key).unwrap().der().to_vec()
+    }
+
+    #[test]
+    fn test_has_uri_san() {
+        let uri = "spiffe://piccolo/statemanager";
+        let der = certificate(&[uri], Vec::new());
+
+        assert!(has_uri_san(&der, uri));
+        assert!(!has_uri_san(&der, "spiffe://piccolo/nodeagent"));
+        // A prefix of the identity is not a match
+        assert!(!has_uri_san(&der, "spiffe://piccolo/state"));
+        assert!(!has_uri_san(b"not a certificate", uri));
+    }
+
+    #[test]
+    fn test_has_uri_san_ignores_bytes_outside_the_san() {
+        let uri = "spiffe://piccolo/apiserver";
+        // The encoding of a URI SAN, smuggled in another extension
+        let mut smuggled = vec![0x86, uri.len() as u8];
+        smuggled.extend_from_slice(uri.as_bytes());
+        let extension =
+            rcgen::CustomExtension::from_oid_content(&[1, 3, 6, 1, 4, 1, 99999, 1], smuggled);
+        let der = certificate(&["spiffe://piccolo/nodeagent"], vec![extension]);
+
+        assert!(der
+            .windows(uri.len())
+            .any(|window| window == uri.as_bytes()));
+        assert!(!has_uri_san(&der, uri));
+        assert!(has_uri_san(&der, "spiffe://piccolo/nodeagent"));
+    }
+}
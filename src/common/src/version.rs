@@ -0,0 +1,268 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! API versions of the messages exchanged with NodeAgents
+//!
+//! NodeAgents are updated after the servers, so a server meets agents one
+//! version behind it. The versions so far:
+//! * 1 - messages sent before versioning, with an `api_version` of 0. The
+//!   agent reports the model of a container only in a legacy `model` or
+//!   `pullpiri.model` annotation.
+//! * 2 - `api_version` in ContainerList, NodeRegistrationRequest and
+//!   HeartbeatRequest, container labels reported with the annotations and the
+//!   `NegotiateApiVersion` RPC.
+//!
+//! Servers accept the last two versions, from [`MIN_API_VERSION`] to
+//! [`API_VERSION`], and translate an older message with the `upgrade_*`
+//! function of its type before handling it. A newer message is handled as
+//! it is, protobuf skips the fields it does not know.
+
+use crate::monitoringserver::ContainerList;
+use crate::nodeagent::fromapiserver::{HeartbeatRequest, NodeRegistrationRequest};
+use crate::spec::k8s::pod::{LABEL_MODEL, LEGACY_MODEL_KEYS};
+use tonic::{Code, Status};
+
+include!("generated/version.rs");
+
+/// Version of the messages of this build
+pub const API_VERSION: u32 = 2;
+
+/// Oldest version servers accept
+pub const MIN_API_VERSION: u32 = 1;
+
+/// Metadata of a registered node with the version its agent speaks
+pub const METADATA_API_VERSION: &str = "api_version";
+
+/// Version of a message, messages without one are version 1
+pub fn of(api_version: u32) -> u32 {
+    api_version.max(1)
+}
+
+/// Check the version of a request, returns the version to handle it as
+#[allow(clippy::result_large_err)]
+pub fn accept(rpc: &str, api_version: u32) -> Result<u32, Status> {
+    let version = of(api_version);
+    if version < MIN_API_VERSION {
+        return Err(Status::failed_precondition(format!(
+            "{} of API version {} is not supported, the oldest is {}",
+            rpc, version, MIN_API_VERSION
+        )));
+    }
+    Ok(version.min(API_VERSION))
+}
+
+/// Highest version spoken by this build and a peer speaking `min..=max`
+pub fn negotiate(min: u32, max: u32) -> Option<u32> {
+    let version = of(max).min(API_VERSION);
+    (version >= of(min).max(MIN_API_VERSION)).then_some(version)
+}
+
+/// Answer of a server to a `NegotiateApiVersion` request
+///
+/// `capabilities` are the RPCs the server implements.
+#[allow(clippy::result_large_err)]
+pub fn respond(
+    component: &str,
+    request: &ApiVersionRequest,
+    capabilities: &[&str],
+) -> Result<ApiVersionResponse, Status> {
+    let api_version =
+        negotiate(request.min_api_version, request.max_api_version).ok_or_else(|| {
+            Status::failed_precondition(format!(
+                "{} speaks API versions {} to {}, {} speaks {} to {}",
+                request.component,
+                request.min_api_version,
+                request.max_api_version,
+                component,
+                MIN_API_VERSION,
+                API_VERSION
+            ))
+        })?;
+    Ok(ApiVersionResponse {
+        api_version,
+        min_api_version: MIN_API_VERSION,
+        max_api_version: API_VERSION,
+        component: component.to_string(),
+        capabilities: capabilities.iter().map(|rpc| rpc.to_string()).collect(),
+    })
+}
+
+/// `NegotiateApiVersion` request of a client
+pub fn request(component: &str) -> ApiVersionRequest {
+    ApiVersionRequest {
+        component: component.to_string(),
+        min_api_version: MIN_API_VERSION,
+        max_api_version: API_VERSION,
+    }
+}
+
+/// Version agreed with a server from its answer to `NegotiateApiVersion`
+///
+/// A server older than versioning does not implement the RPC, it speaks
+/// version 1.
+#[allow(clippy::result_large_err)]
+pub fn negotiated(answer: Result<ApiVersionResponse, Status>) -> Result<u32, Status> {
+    match answer {
+        Ok(response) => Ok(response.api_version),
+        Err(status) if status.code() == Code::Unimplemented => Ok(1),
+        Err(status) => Err(status),
+    }
+}
+
+/// Translate a ContainerList to the current version
+pub fn upgrade_container_list(list: &mut ContainerList, version: u32) {
+    if version < 2 {
+        for container in &mut list.containers {
+            if container.annotation.contains_key(LABEL_MODEL) {
+                continue;
+            }
+            let model = LEGACY_MODEL_KEYS
+                .iter()
+                .find_map(|key| container.annotation.get(*key))
+                .cloned();
+            if let Some(model) = model {
+                container.annotation.insert(LABEL_MODEL.to_string(), model);
+            }
+        }
+    }
+    list.api_version = API_VERSION;
+}
+
+/// Translate a NodeRegistrationRequest to the current version
+///
+/// The version the agent speaks is kept in the metadata of the node.
+pub fn upgrade_registration(request: &mut NodeRegistrationRequest, version: u32) {
+    request.api_version = version;
+    request
+        .metadata
+        .insert(METADATA_API_VERSION.to_string(), version.to_string());
+}
+
+/// Translate a HeartbeatRequest to the current version
+pub fn upgrade_heartbeat(request: &mut HeartbeatRequest, version: u32) {
+    request.api_version = version;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitoringserver::ContainerInfo;
+    use prost::Message;
+    use std::collections::HashMap;
+
+    /// ContainerList as sent by agents of version 1
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct ContainerListV1 {
+        #[prost(string, tag = "1")]
+        node_name: String,
+        #[prost(message, repeated, tag = "2")]
+        containers: Vec<ContainerInfo>,
+    }
+
+    /// HeartbeatRequest as sent by agents of version 1
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct HeartbeatRequestV1 {
+        #[prost(string, tag = "1")]
+        node_id: String,
+        #[prost(int64, tag = "2")]
+        timestamp: i64,
+    }
+
+    fn legacy_container() -> ContainerInfo {
+        ContainerInfo {
+            id: "c1".to_string(),
+            names: vec!["hello-core".to_string()],
+            annotation: HashMap::from([("model".to_string(), "hello-core".to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(1, 2), Some(2));
+        // Older peer
+        assert_eq!(negotiate(0, 1), Some(1));
+        assert_eq!(negotiate(0, 0), Some(1));
+        // Newer peer that still speaks this version
+        assert_eq!(negotiate(2, 3), Some(2));
+        // Newer peer that no longer does
+        assert_eq!(negotiate(3, 4), None);
+
+        let response = respond("statemanager", &request("nodeagent"), &["SendStateChange"]);
+        let response = response.unwrap();
+        assert_eq!(response.api_version, API_VERSION);
+        assert_eq!(response.capabilities, vec!["SendStateChange"]);
+        assert_eq!(negotiated(Ok(response)).unwrap(), API_VERSION);
+        assert_eq!(negotiated(Err(Status::unimplemented("no rpc"))).unwrap(), 1);
+        assert!(negotiated(Err(Status::unavailable("down"))).is_err());
+    }
+
+    #[test]
+    fn test_accept() {
+        assert_eq!(accept("ContainerList", 0).unwrap(), 1);
+        assert_eq!(accept("ContainerList", 2).unwrap(), 2);
+        assert_eq!(accept("ContainerList", 7).unwrap(), API_VERSION);
+    }
+
+    #[test]
+    fn test_container_list_of_version_1_is_upgraded() {
+        let sent = ContainerListV1 {
+            node_name: "node-a".to_string(),
+            containers: vec![legacy_container()],
+        };
+        let mut received = ContainerList::decode(sent.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(received.api_version, 0);
+        assert_eq!(received.node_name, "node-a");
+
+        let version = accept("ContainerList", received.api_version).unwrap();
+        upgrade_container_list(&mut received, version);
+        assert_eq!(received.api_version, API_VERSION);
+        assert_eq!(received.containers[0].annotation[LABEL_MODEL], "hello-core");
+    }
+
+    #[test]
+    fn test_container_list_of_version_2_is_kept() {
+        let mut container = legacy_container();
+        container
+            .annotation
+            .insert(LABEL_MODEL.to_string(), "team-a/hello-core".to_string());
+        let mut list = ContainerList {
+            node_name: "node-a".to_string(),
+            containers: vec![container],
+            api_version: 2,
+        };
+        upgrade_container_list(&mut list, 2);
+        assert_eq!(
+            list.containers[0].annotation[LABEL_MODEL],
+            "team-a/hello-core"
+        );
+    }
+
+    #[test]
+    fn test_version_2_is_readable_by_version_1() {
+        let sent = ContainerList {
+            node_name: "node-a".to_string(),
+            containers: vec![legacy_container()],
+            api_version: API_VERSION,
+        };
+        let received = ContainerListV1::decode(sent.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(received.node_name, "node-a");
+        assert_eq!(received.containers, sent.containers);
+
+        let sent = HeartbeatRequest {
+            node_id: "node-a".to_string(),
+            timestamp: 42,
+            api_version: API_VERSION,
+        };
+        let received = HeartbeatRequestV1::decode(sent.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(received.node_id, "node-a");
+        assert_eq!(received.timestamp, 42);
+
+        let mut upgraded = HeartbeatRequest::decode(received.encode_to_vec().as_slice()).unwrap();
+        let version = accept("Heartbeat", upgraded.api_version).unwrap();
+        upgrade_heartbeat(&mut upgraded, version);
+        assert_eq!(upgraded.api_version, 1);
+    }
+}
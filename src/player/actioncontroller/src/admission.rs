@@ -0,0 +1,401 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Admission control of launches against node capacity
+//!
+//! NodeAgents report the CPU cores and memory of their node when they
+//! register. Models request CPU and memory in the container `resources` of
+//! their pod spec. Before a launch, update or rollback runs, the requests of
+//! the package's models are checked against the capacity of each node minus
+//! what the models already admitted there reserve:
+//! * a model whose request exceeds the whole capacity of its node is denied
+//! * a package that does not fit the remaining capacity is queued and
+//!   admitted when a terminated scenario releases its reservations
+//!
+//! A launch, update or rollback that fails after it was admitted puts back
+//! the reservations its models had before, see [`Admission::restore`].
+//!
+//! Nodes without reported capacity and models without requests are not
+//! limited.
+//!
+//! NodeAgents also report the load of their node periodically. A package
+//! whose new models request more memory than a node reports free is queued
+//! as well, and models relocated off a node go to the least loaded nodes
+//! first. Reports older than [`USAGE_MAX_AGE_SECS`] are not used.
+
+use common::nodeagent::fromapiserver::NodeUsage;
+use std::collections::{HashMap, VecDeque};
+
+/// Age in seconds after which the reported load of a node is not used
+pub const USAGE_MAX_AGE_SECS: i64 = 60;
+
+/// CPU and memory of a node or requested by a model
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Resources {
+    pub cpu_millis: u64,
+    pub memory_mb: u64,
+}
+
+impl Resources {
+    fn is_zero(&self) -> bool {
+        self.cpu_millis == 0 && self.memory_mb == 0
+    }
+
+    fn add(&mut self, other: Resources) {
+        self.cpu_millis += other.cpu_millis;
+        self.memory_mb += other.memory_mb;
+    }
+
+    /// Name of the first resource in which `self` exceeds `capacity`
+    ///
+    /// A capacity of 0 is unknown and not limited.
+    fn exceeds(&self, capacity: &Resources) -> Option<String> {
+        if capacity.cpu_millis > 0 && self.cpu_millis > capacity.cpu_millis {
+            return Some(format!(
+                "cpu {}m > {}m",
+                self.cpu_millis, capacity.cpu_millis
+            ));
+        }
+        if capacity.memory_mb > 0 && self.memory_mb > capacity.memory_mb {
+            return Some(format!(
+                "memory {}Mi > {}Mi",
+                self.memory_mb, capacity.memory_mb
+            ));
+        }
+        None
+    }
+}
+
+/// Resources a model requests on its node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Demand {
+    pub model: String,
+    pub node: String,
+    pub request: Resources,
+}
+
+/// Outcome of an admission check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    Admit,
+    /// The package fits its nodes once other models release resources
+    Queue(String),
+    /// A model can never fit its node
+    Deny(String),
+}
+
+/// Reservations of models before they were admitted again, `None` for the
+/// models that had none
+pub type Previous = Vec<(String, Option<Demand>)>;
+
+/// Reservations of admitted models and launches waiting for capacity
+#[derive(Debug, Default)]
+pub struct Admission {
+    reservations: HashMap<String, Demand>,
+    /// Scenarios with the transition that queued their action
+    queued: VecDeque<(String, String)>,
+}
+
+impl Admission {
+    /// Check the demands of a package against the capacity of its nodes
+    ///
+    /// Reservations of the package's own models are not counted, so that an
+    /// update or a relaunch does not compete with itself.
+    pub fn check(&self, demands: &[Demand], capacities: &HashMap<String, Resources>) -> Decision {
+        for demand in demands {
+            let Some(capacity) = capacities.get(&demand.node) else {
+                continue;
+            };
+            if let Some(exceeded) = demand.request.exceeds(capacity) {
+                return Decision::Deny(format!(
+                    "model '{}' requests more than node '{}' has: {}",
+                    demand.model, demand.node, exceeded
+                ));
+            }
+        }
+
+        let mut requested: HashMap<&str, Resources> = HashMap::new();
+        for demand in demands {
+            requested
+                .entry(demand.node.as_str())
+                .or_default()
+                .add(demand.request);
+        }
+        for (node, request) in requested {
+            let Some(capacity) = capacities.get(node) else {
+                continue;
+            };
+            let mut total = request;
+            for reservation in self.reservations.values() {
+                if reservation.node == node && !demands.iter().any(|d| d.model == reservation.model)
+                {
+                    total.add(reservation.request);
+                }
+            }
+            if let Some(exceeded) = total.exceeds(capacity) {
+                return Decision::Queue(format!(
+                    "node '{}' has not enough free capacity: {}",
+                    node, exceeded
+                ));
+            }
+        }
+        Decision::Admit
+    }
+
+    /// Check the demands of a package against the free memory its nodes report
+    ///
+    /// Models reserved on their node run there already and are part of the
+    /// reported usage, so only the other models are counted.
+    pub fn check_free_memory(
+        &self,
+        demands: &[Demand],
+        free_memory: &HashMap<String, u64>,
+    ) -> Decision {
+        let mut requested: HashMap<&str, u64> = HashMap::new();
+        for demand in demands {
+            let running = self
+                .reservations
+                .get(&demand.model)
+                .is_some_and(|reservation| reservation.node == demand.node);
+            if !running {
+                *requested.entry(demand.node.as_str()).or_default() += demand.request.memory_mb;
+            }
+        }
+        for (node, memory_mb) in requested {
+            match free_memory.get(node) {
+                Some(free) if memory_mb > *free => {
+                    return Decision::Queue(format!(
+                        "node '{}' reports {}Mi free memory, {}Mi requested",
+                        node, free, memory_mb
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Decision::Admit
+    }
+
+    /// Reserve the resources of admitted models
+    ///
+    /// Returns the reservations they replace, to restore if the admitted
+    /// action fails.
+    pub fn reserve(&mut self, demands: Vec<Demand>) -> Previous {
+        let mut previous = Vec::new();
+        for demand in demands {
+            let model = demand.model.clone();
+            let replaced = if demand.request.is_zero() {
+                self.reservations.remove(&model)
+            } else {
+                self.reservations.insert(model.clone(), demand)
+            };
+            previous.push((model, replaced));
+        }
+        previous
+    }
+
+    /// Put back the reservations replaced by an admitted action that failed
+    pub fn restore(&mut self, previous: Previous) {
+        for (model, demand) in previous {
+            match demand {
+                Some(demand) => self.reservations.insert(model, demand),
+                None => self.reservations.remove(&model),
+            };
+        }
+    }
+
+    /// Release the resources of stopped models
+    pub fn release(&mut self, models: &[String]) {
+        for model in models {
+            self.reservations.remove(model);
+        }
+    }
+
+    /// Queue a scenario until resources are released, once
+    ///
+    /// The scenario keeps the transition it was first queued by, whose
+    /// action the outcome is reported for once it runs.
+    pub fn enqueue(&mut self, scenario: &str, transition_id: &str) {
+        if !self.queued.iter().any(|(queued, _)| queued == scenario) {
+            self.queued
+                .push_back((scenario.to_string(), transition_id.to_string()));
+        }
+    }
+
+    /// Queued scenarios with their transitions in arrival order, emptying
+    /// the queue
+    pub fn take_queued(&mut self) -> Vec<(String, String)> {
+        self.queued.drain(..).collect()
+    }
+}
+
+/// Unix time in seconds, to tell the age of a reported usage
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Usage of a node if it was reported recently enough to rely on
+pub fn current(usage: Option<NodeUsage>, now: i64) -> Option<NodeUsage> {
+    usage.filter(|usage| now - usage.timestamp <= USAGE_MAX_AGE_SECS)
+}
+
+/// Nodes from the least to the most loaded
+///
+/// Nodes are ordered by CPU load, then by their number of containers. Nodes
+/// without a current usage come last, in their given order.
+pub fn by_load(nodes: Vec<(String, Option<NodeUsage>)>) -> Vec<String> {
+    let mut known = Vec::new();
+    let mut unknown = Vec::new();
+    for (node, usage) in nodes {
+        match usage {
+            Some(usage) => known.push((node, usage)),
+            None => unknown.push(node),
+        }
+    }
+    known.sort_by(|(_, a), (_, b)| {
+        a.cpu_load
+            .total_cmp(&b.cpu_load)
+            .then(a.container_count.cmp(&b.container_count))
+    });
+    known
+        .into_iter()
+        .map(|(node, _)| node)
+        .chain(unknown)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demand(model: &str, node: &str, cpu_millis: u64, memory_mb: u64) -> Demand {
+        Demand {
+            model: model.to_string(),
+            node: node.to_string(),
+            request: Resources {
+                cpu_millis,
+                memory_mb,
+            },
+        }
+    }
+
+    fn capacities() -> HashMap<String, Resources> {
+        HashMap::from([(
+            "HPC".to_string(),
+            Resources {
+                cpu_millis: 2000,
+                memory_mb: 1024,
+            },
+        )])
+    }
+
+    #[test]
+    fn test_check_denies_models_larger_than_their_node() {
+        let admission = Admission::default();
+        let decision = admission.check(&[demand("big", "HPC", 500, 2048)], &capacities());
+        assert!(matches!(decision, Decision::Deny(reason) if reason.contains("memory")));
+
+        // Unknown nodes are not limited
+        let decision = admission.check(&[demand("big", "ZONE", 8000, 8192)], &capacities());
+        assert_eq!(decision, Decision::Admit);
+    }
+
+    #[test]
+    fn test_check_queues_until_reservations_are_released() {
+        let mut admission = Admission::default();
+        let first = vec![demand("a", "HPC", 1500, 512)];
+        assert_eq!(admission.check(&first, &capacities()), Decision::Admit);
+        admission.reserve(first.clone());
+
+        let second = vec![demand("b", "HPC", 1000, 256)];
+        assert!(matches!(
+            admission.check(&second, &capacities()),
+            Decision::Queue(_)
+        ));
+
+        // Updating the admitted model does not compete with its own reservation
+        assert_eq!(admission.check(&first, &capacities()), Decision::Admit);
+
+        admission.enqueue("second", "t-1");
+        admission.enqueue("second", "t-2");
+        admission.release(&["a".to_string()]);
+        assert_eq!(
+            admission.take_queued(),
+            vec![("second".to_string(), "t-1".to_string())]
+        );
+        assert!(admission.take_queued().is_empty());
+        assert_eq!(admission.check(&second, &capacities()), Decision::Admit);
+    }
+
+    #[test]
+    fn test_failed_actions_restore_the_previous_reservations() {
+        let mut admission = Admission::default();
+        let second = vec![demand("b", "HPC", 1000, 256)];
+
+        // A failed launch frees what it reserved
+        let previous = admission.reserve(vec![demand("a", "HPC", 1500, 512)]);
+        admission.restore(previous);
+        assert_eq!(admission.check(&second, &capacities()), Decision::Admit);
+
+        // A failed update keeps what the running model reserved before
+        admission.reserve(vec![demand("a", "HPC", 500, 128)]);
+        let previous = admission.reserve(vec![demand("a", "HPC", 1500, 512)]);
+        admission.restore(previous);
+        assert_eq!(admission.check(&second, &capacities()), Decision::Admit);
+        let larger = vec![demand("b", "HPC", 1600, 256)];
+        assert!(matches!(
+            admission.check(&larger, &capacities()),
+            Decision::Queue(_)
+        ));
+    }
+
+    #[test]
+    fn test_check_free_memory_counts_new_models_only() {
+        let mut admission = Admission::default();
+        let free = HashMap::from([("HPC".to_string(), 512)]);
+        let package = vec![demand("a", "HPC", 0, 400), demand("b", "HPC", 0, 200)];
+        assert!(matches!(
+            admission.check_free_memory(&package, &free),
+            Decision::Queue(reason) if reason.contains("512Mi")
+        ));
+
+        // The memory of a running model is part of the reported usage
+        admission.reserve(vec![demand("a", "HPC", 0, 400)]);
+        assert_eq!(
+            admission.check_free_memory(&package, &free),
+            Decision::Admit
+        );
+        // Nodes without a report are not limited
+        let elsewhere = vec![demand("c", "ZONE", 0, 4096)];
+        assert_eq!(
+            admission.check_free_memory(&elsewhere, &free),
+            Decision::Admit
+        );
+    }
+
+    #[test]
+    fn test_by_load_orders_least_loaded_first() {
+        let usage = |cpu_load: f32, container_count: u32| {
+            Some(NodeUsage {
+                cpu_load,
+                container_count,
+                timestamp: 100,
+                ..Default::default()
+            })
+        };
+        let nodes = vec![
+            ("unknown".to_string(), None),
+            ("busy".to_string(), usage(80.0, 2)),
+            ("crowded".to_string(), usage(10.0, 9)),
+            ("idle".to_string(), usage(10.0, 1)),
+        ];
+        assert_eq!(by_load(nodes), vec!["idle", "crowded", "busy", "unknown"]);
+
+        assert!(current(usage(1.0, 0), 100 + USAGE_MAX_AGE_SECS).is_some());
+        assert!(current(usage(1.0, 0), 101 + USAGE_MAX_AGE_SECS).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Records of the models that are supposed to run on each node
+//!
+//! A launched, updated or rolled back model is recorded under
+//! `Autostart/{node}/{model}` with the scenario that started it, and a
+//! terminated model loses its record. A model moved to another node takes
+//! its record along. When a node registers again after a reboot, ApiServer
+//! asks for the models recorded for the node to be started again.
+
+use serde::{Deserialize, Serialize};
+
+/// Key prefix of the records
+pub const PREFIX: &str = "Autostart";
+
+/// Why a model is supposed to run on a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Scenario whose action started the model
+    pub scenario: String,
+}
+
+fn key(node: &str, model: &str) -> String {
+    format!("{}/{}/{}", PREFIX, node, model)
+}
+
+/// Model of a record key of `node`, model names may contain '/'
+fn model_of<'a>(node: &str, key: &'a str) -> Option<&'a str> {
+    key.strip_prefix(PREFIX)?
+        .strip_prefix('/')?
+        .strip_prefix(node)?
+        .strip_prefix('/')
+        .filter(|model| !model.is_empty())
+}
+
+/// Record that `model` is supposed to run on `node`
+pub async fn record(node: &str, model: &str, scenario: &str) -> Result<(), String> {
+    let record = Record {
+        scenario: scenario.to_string(),
+    };
+    let value = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+    common::etcd::put(&key(node, model), &value).await
+}
+
+/// Drop the record of `model` on `node`
+pub async fn forget(node: &str, model: &str) -> Result<(), String> {
+    common::etcd::delete(&key(node, model)).await
+}
+
+/// Move the record of `model` from `from` to `to`, if it has one
+pub async fn moved(from: &str, to: &str, model: &str) -> Result<(), String> {
+    let Ok(value) = common::etcd::get(&key(from, model)).await else {
+        return Ok(());
+    };
+    common::etcd::put(&key(to, model), &value).await?;
+    forget(from, model).await
+}
+
+/// Models recorded for `node` with their records, sorted by model
+pub async fn for_node(node: &str) -> Result<Vec<(String, Record)>, String> {
+    let kvs = common::etcd::get_all_with_prefix(&format!("{}/{}/", PREFIX, node)).await?;
+    Ok(parse(node, kvs))
+}
+
+fn parse(node: &str, kvs: Vec<(String, String)>) -> Vec<(String, Record)> {
+    let mut records: Vec<(String, Record)> = kvs
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let model = model_of(node, &key)?.to_string();
+            match serde_json::from_str(&value) {
+                Ok(record) => Some((model, record)),
+                Err(e) => {
+                    common::logd!(4, "Ignoring autostart record {}: {}", key, e);
+                    None
+                }
+            }
+        })
+        .collect();
+    records.sort_by(|a, b| a.0.cmp(&b.0));
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_records_of_a_node() {
+        let kvs = vec![
+            (
+                key("HPC", "team/radar"),
+                r#"{"scenario":"team/s"}"#.to_string(),
+            ),
+            (key("HPC", "camera"), r#"{"scenario":"s"}"#.to_string()),
+            (key("HPC", "broken"), "not json".to_string()),
+            // Another node sharing the prefix
+            (key("HPC2", "lidar"), r#"{"scenario":"s"}"#.to_string()),
+        ];
+        let records = parse("HPC", kvs);
+        assert_eq!(
+            records,
+            vec![
+                (
+                    "camera".to_string(),
+                    Record {
+                        scenario: "s".to_string()
+                    }
+                ),
+                (
+                    "team/radar".to_string(),
+                    Record {
+                        scenario: "team/s".to_string()
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -7,7 +7,6 @@ pub mod sender;
 
 use common::logd;
 use std::sync::Arc;
-use tonic::transport::Server;
 
 /// Initialize the gRPC communication system for ActionController
 ///
@@ -23,6 +22,7 @@ use tonic::transport::Server;
 ///
 /// Returns an error if:
 /// - Server address binding fails
+/// - Strict TLS is configured but the certificates cannot be loaded
 /// - Client connection establishment fails
 pub async fn init(manager: crate::manager::ActionControllerManager) -> common::Result<()> {
     let arc_manager = Arc::new(manager);
@@ -31,9 +31,11 @@ pub async fn init(manager: crate::manager::ActionControllerManager) -> common::R
     let addr = common::actioncontroller::open_server().parse()?;
     logd!(1, "Starting gRPC server on {}", addr);
 
+    let mut server = common::tls::server()?;
     tokio::spawn(async move {
-        if let Err(e) = Server::builder()
+        if let Err(e) = server
             .add_service(grpc_server.into_service())
+            .add_service(common::health::grpc_service())
             .serve(addr)
             .await
         {
@@ -11,10 +11,14 @@ use common::actioncontroller::{
     action_controller_connection_server::{
         ActionControllerConnection, ActionControllerConnectionServer,
     },
-    CompleteNetworkSettingRequest, CompleteNetworkSettingResponse, PodStatus as ActionStatus,
-    ReconcileRequest, ReconcileResponse, TriggerActionRequest, TriggerActionResponse,
+    AutostartNodeRequest, AutostartNodeResponse, CompleteNetworkSettingRequest,
+    CompleteNetworkSettingResponse, DrainNodeRequest, DrainNodeResponse, NetworkStatus,
+    PodStatus as ActionStatus, RebalanceRequest, RebalanceResponse, ReconcileRequest,
+    ReconcileResponse, RelocateNodeModelsRequest, RelocateNodeModelsResponse, TriggerActionRequest,
+    TriggerActionResponse,
 };
 use common::logd;
+use common::statemanager::ResourceType;
 
 /// Receiver for handling incoming gRPC requests for ActionController
 ///
@@ -78,8 +82,15 @@ impl ActionControllerConnection for ActionControllerReceiver {
 
         logd!(1, "trigger_action in grpc receiver");
 
-        let scenario_name = request.into_inner().scenario_name;
-        logd!(2, "trigger_action scenario: {}", scenario_name);
+        let request = request.into_inner();
+        let scenario_name = request.scenario_name;
+        let correlation_id = common::correlation::or_new(&request.correlation_id);
+        logd!(
+            2,
+            "trigger_action scenario: {} [{}]",
+            scenario_name,
+            correlation_id
+        );
 
         logd!(
             1,
@@ -100,7 +111,8 @@ impl ActionControllerConnection for ActionControllerReceiver {
         );
 
         logd!(1, "   🎯 Processing scenario actions...");
-        let result = match self.manager.trigger_manager_action(&scenario_name).await {
+        let action = self.manager.trigger_manager_action(&scenario_name);
+        let result = match common::correlation::scope(correlation_id, action).await {
             Ok(_) => Ok(Response::new(TriggerActionResponse {
                 status: 0,
                 desc: "Action triggered successfully".to_string(),
@@ -109,6 +121,14 @@ impl ActionControllerConnection for ActionControllerReceiver {
                 let err_msg = e.to_string();
                 let grpc_status = if err_msg.contains("Invalid scenario name") {
                     Status::invalid_argument(err_msg)
+                } else if err_msg.contains("denied by policy") {
+                    Status::permission_denied(err_msg)
+                } else if err_msg.contains("Operation in progress") {
+                    Status::aborted(err_msg)
+                } else if err_msg.contains("Too many operations waiting") {
+                    Status::unavailable(err_msg)
+                } else if err_msg.contains("not permitted in vehicle mode") {
+                    Status::failed_precondition(err_msg)
                 } else if err_msg.contains("not found") {
                     Status::not_found(err_msg)
                 } else if err_msg.contains("Failed to parse") {
@@ -158,11 +178,24 @@ impl ActionControllerConnection for ActionControllerReceiver {
             }));
         }
 
-        match self
-            .manager
-            .reconcile_do(scenario_name, current, desired)
-            .await
-        {
+        // A failed package names its failing models, only they are restarted
+        let reconcile = async {
+            if req.resource_type == ResourceType::Package as i32 && !req.package_name.is_empty() {
+                self.manager
+                    .reconcile_package(
+                        &scenario_name,
+                        &req.package_name,
+                        &req.failing_models,
+                        &req.reason,
+                    )
+                    .await
+            } else {
+                self.manager
+                    .reconcile_do(scenario_name, current, desired)
+                    .await
+            }
+        };
+        match common::correlation::scope(req.correlation_id, reconcile).await {
             Ok(_) => Ok(Response::new(ReconcileResponse {
                 status: 0, // Success
                 desc: "Reconciliation completed successfully".to_string(),
@@ -171,6 +204,9 @@ impl ActionControllerConnection for ActionControllerReceiver {
             // and propagate it. This allows gRPC clients to receive a proper error status.
             Err(e) => {
                 logd!(5, "Reconciliation failed: {:?}", e); // Log the error for debugging
+                if matches!(e, common::error::Error::PreconditionFailed(_)) {
+                    return Err(Status::aborted(format!("Failed to reconcile: {}", e)));
+                }
                 Err(Status::internal(format!("Failed to reconcile: {}", e)))
             }
         }
@@ -186,9 +222,200 @@ impl ActionControllerConnection for ActionControllerReceiver {
             req.request_id, req.network_status, req.pod_status, req.details
         );
 
-        let response = CompleteNetworkSettingResponse { acknowledged: true };
+        let success = req.network_status == NetworkStatus::Ok as i32;
+        let acknowledged = match self
+            .manager
+            .complete_network_setting(&req.request_id, success, &req.details)
+            .await
+        {
+            Ok(()) => true,
+            Err(e) => {
+                logd!(4, "Failed to complete network setting: {}", e);
+                false
+            }
+        };
+
+        let response = CompleteNetworkSettingResponse { acknowledged };
         Ok(Response::new(response))
     }
+
+    /// Handle relocation requests from ApiServer when a node stops responding
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - gRPC request containing the failed node and healthy candidates
+    ///
+    /// # Returns
+    ///
+    /// * `Response<RelocateNodeModelsResponse>` - relocated model names
+    /// * `Status` - gRPC status error if the relocation fails
+    async fn relocate_node_models(
+        &self,
+        request: Request<RelocateNodeModelsRequest>,
+    ) -> Result<Response<RelocateNodeModelsResponse>, Status> {
+        let req = request.into_inner();
+        logd!(
+            3,
+            "relocate_node_models: failed_node={}, healthy_nodes={:?}",
+            req.failed_node,
+            req.healthy_nodes
+        );
+
+        match self
+            .manager
+            .relocate_node_models(&req.failed_node, &req.healthy_nodes)
+            .await
+        {
+            Ok(relocated_models) => Ok(Response::new(RelocateNodeModelsResponse {
+                status: 0,
+                desc: format!("Relocated {} model(s)", relocated_models.len()),
+                relocated_models,
+            })),
+            Err(e) => {
+                logd!(5, "Relocation failed: {:?}", e);
+                Err(Status::failed_precondition(format!(
+                    "Failed to relocate models: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Handle drain requests from ApiServer for a cordoned node
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - gRPC request containing the node and the nodes that may take over its models
+    ///
+    /// # Returns
+    ///
+    /// * `Response<DrainNodeResponse>` - relocated models and models left on the node
+    /// * `Status` - gRPC status error if no target node is available
+    async fn drain_node(
+        &self,
+        request: Request<DrainNodeRequest>,
+    ) -> Result<Response<DrainNodeResponse>, Status> {
+        let req = request.into_inner();
+        logd!(
+            3,
+            "drain_node: node={}, target_nodes={:?}",
+            req.node,
+            req.target_nodes
+        );
+
+        match self.manager.drain_node(&req.node, &req.target_nodes).await {
+            Ok((relocated_models, failed_models)) => Ok(Response::new(DrainNodeResponse {
+                status: if failed_models.is_empty() { 0 } else { 1 },
+                desc: format!(
+                    "Relocated {} model(s), {} left on the node",
+                    relocated_models.len(),
+                    failed_models.len()
+                ),
+                relocated_models,
+                failed_models,
+            })),
+            Err(e) => {
+                logd!(5, "Drain failed: {:?}", e);
+                Err(Status::failed_precondition(format!(
+                    "Failed to drain node: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Handle autostart requests from ApiServer for a node that registered again
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - gRPC request containing the node
+    ///
+    /// # Returns
+    ///
+    /// * `Response<AutostartNodeResponse>` - started models and models that could not be started
+    /// * `Status` - gRPC status error if the records of the node cannot be read
+    async fn autostart_node(
+        &self,
+        request: Request<AutostartNodeRequest>,
+    ) -> Result<Response<AutostartNodeResponse>, Status> {
+        let req = request.into_inner();
+        logd!(3, "autostart_node: node={}", req.node);
+        if req.node.trim().is_empty() {
+            return Err(Status::invalid_argument("node is required"));
+        }
+
+        match self.manager.autostart_node(&req.node).await {
+            Ok((started_models, failed_models)) => Ok(Response::new(AutostartNodeResponse {
+                status: if failed_models.is_empty() { 0 } else { 1 },
+                desc: format!(
+                    "Started {} model(s), {} failed",
+                    started_models.len(),
+                    failed_models.len()
+                ),
+                started_models,
+                failed_models,
+            })),
+            Err(e) => {
+                logd!(5, "Autostart failed: {:?}", e);
+                Err(Status::unavailable(format!(
+                    "Failed to autostart node: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Handle rebalance requests from ApiServer
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - gRPC request containing the nodes to spread the models over
+    ///
+    /// # Returns
+    ///
+    /// * `Response<RebalanceResponse>` - planned or done moves and the failed ones
+    /// * `Status` - gRPC status error if no node is schedulable
+    async fn rebalance(
+        &self,
+        request: Request<RebalanceRequest>,
+    ) -> Result<Response<RebalanceResponse>, Status> {
+        let req = request.into_inner();
+        logd!(
+            3,
+            "rebalance: nodes={:?}, dry_run={}, max_moves={}",
+            req.nodes,
+            req.dry_run,
+            req.max_moves
+        );
+
+        match self
+            .manager
+            .rebalance(&req.nodes, req.dry_run, req.max_moves as usize)
+            .await
+        {
+            Ok((moves, failed_moves)) => Ok(Response::new(RebalanceResponse {
+                status: if failed_moves.is_empty() { 0 } else { 1 },
+                desc: if req.dry_run {
+                    format!("Planned {} move(s)", moves.len())
+                } else {
+                    format!(
+                        "Moved {} model(s), {} failed",
+                        moves.len() - failed_moves.len(),
+                        failed_moves.len()
+                    )
+                },
+                moves,
+                failed_moves,
+            })),
+            Err(e) => {
+                logd!(5, "Rebalance failed: {:?}", e);
+                Err(Status::failed_precondition(format!(
+                    "Failed to rebalance: {}",
+                    e
+                )))
+            }
+        }
+    }
 }
 
 fn i32_to_status(value: i32) -> ActionStatus {
@@ -284,11 +511,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_action_failure() {
+        common::etcd::use_in_memory_store();
         let manager = Arc::new(ActionControllerManager::new());
         let receiver = ActionControllerReceiver::new(manager.clone());
 
         let request = Request::new(TriggerActionRequest {
             scenario_name: "invalid_scenario".to_string(),
+            correlation_id: String::new(),
         });
 
         let response = receiver.trigger_action(request).await.unwrap_err();
@@ -297,6 +526,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_reconcile_when_states_equal() {
+        common::etcd::use_in_memory_store();
         let manager = Arc::new(ActionControllerManager::new());
         let receiver = ActionControllerReceiver::new(manager.clone());
 
@@ -304,6 +534,7 @@ mod tests {
             scenario_name: "test_scenario".to_string(),
             current: 3, // RUNNING
             desired: 3, // RUNNING
+            ..Default::default()
         });
 
         let response = receiver.reconcile(request).await.unwrap();
@@ -316,6 +547,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_action_success() {
+        common::etcd::use_in_memory_store();
         let scenario_yaml = r#"
         apiVersion: v1
         kind: Scenario
@@ -361,6 +593,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_reconcile_failure_invalid_scenario() {
+        common::etcd::use_in_memory_store();
         let manager = Arc::new(ActionControllerManager::new());
         let receiver = ActionControllerReceiver::new(manager.clone());
 
@@ -368,6 +601,7 @@ mod tests {
             scenario_name: "invalid_scenario".to_string(),
             current: 0,
             desired: 3,
+            ..Default::default()
         });
 
         let response = receiver.reconcile(request).await.unwrap_err();
@@ -376,6 +610,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_scenario_state_management_workflow() {
+        common::etcd::use_in_memory_store();
         // Setup test scenario in ETCD
         let scenario_yaml = r#"
         apiVersion: v1
@@ -446,4 +681,62 @@ mod tests {
         let receiver = ActionControllerReceiver::new(manager);
         let _service = receiver.into_service();
     }
+
+    #[tokio::test]
+    async fn test_drain_node_without_target_nodes() {
+        common::etcd::use_in_memory_store();
+        let manager = Arc::new(ActionControllerManager::new());
+        let receiver = ActionControllerReceiver::new(manager);
+
+        let request = Request::new(DrainNodeRequest {
+            node: "drained-node".to_string(),
+            target_nodes: vec![],
+        });
+        let status = receiver.drain_node(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_without_nodes() {
+        common::etcd::use_in_memory_store();
+        let manager = Arc::new(ActionControllerManager::new());
+        let receiver = ActionControllerReceiver::new(manager);
+
+        let request = Request::new(RebalanceRequest {
+            nodes: vec![],
+            dry_run: true,
+            max_moves: 0,
+        });
+        let status = receiver.rebalance(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn test_relocate_node_models_without_healthy_nodes() {
+        common::etcd::use_in_memory_store();
+        let manager = Arc::new(ActionControllerManager::new());
+        let receiver = ActionControllerReceiver::new(manager);
+
+        let request = Request::new(RelocateNodeModelsRequest {
+            failed_node: "failed-node".to_string(),
+            healthy_nodes: vec![],
+        });
+        let result = receiver.relocate_node_models(request).await;
+
+        let status = result.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn test_autostart_node_requires_node() {
+        common::etcd::use_in_memory_store();
+        let manager = Arc::new(ActionControllerManager::new());
+        let receiver = ActionControllerReceiver::new(manager);
+
+        let request = Request::new(AutostartNodeRequest {
+            node: " ".to_string(),
+        });
+        let status = receiver.autostart_node(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
 }
@@ -1,20 +1,49 @@
 use common::nodeagent::fromactioncontroller::{
-    connect_server, HandleWorkloadRequest, HandleWorkloadResponse,
+    connect_server, HandleWorkloadRequest, HandleWorkloadResponse, PrefetchImagesRequest,
+    PrefetchImagesResponse,
 };
+use common::nodeagent::fromapiserver::{ContainerExecRequest, ContainerExecResponse};
 use common::nodeagent::node_agent_connection_client::NodeAgentConnectionClient;
-use tonic::{Request, Status};
+use tonic::Status;
 
 pub async fn send_workload_handle_request(
     addr: &str,
     request: HandleWorkloadRequest,
 ) -> Result<HandleWorkloadResponse, Status> {
-    let mut client = NodeAgentConnectionClient::connect(connect_server(&addr))
-        .await
-        .unwrap();
+    let addr = connect_server(addr);
+    let mut client = NodeAgentConnectionClient::new(common::grpc::channel(&addr).await?);
+
+    match client.handle_workload(common::auth::request(request)).await {
+        Ok(response) => Ok(response.into_inner()),
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+    }
+}
+
+pub async fn send_prefetch_images_request(
+    addr: &str,
+    request: PrefetchImagesRequest,
+) -> Result<PrefetchImagesResponse, Status> {
+    let addr = connect_server(addr);
+    let mut client = NodeAgentConnectionClient::new(common::grpc::channel(&addr).await?);
 
-    let response = client
-        .handle_workload(Request::new(request))
-        .await?
-        .into_inner();
-    Ok(response)
+    match client.prefetch_images(common::auth::request(request)).await {
+        Ok(response) => Ok(response.into_inner()),
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+    }
+}
+
+pub async fn send_exec_request(
+    addr: &str,
+    request: ContainerExecRequest,
+) -> Result<ContainerExecResponse, Status> {
+    let addr = connect_server(addr);
+    let mut client = NodeAgentConnectionClient::new(common::grpc::channel(&addr).await?);
+
+    match client
+        .exec_in_container(common::auth::request(request))
+        .await
+    {
+        Ok(response) => Ok(response.into_inner()),
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+    }
 }
@@ -8,7 +8,8 @@
 use common::external::pharos::{
     connect_pharos_server,
     pharos_network_service_connection_client::PharosNetworkServiceConnectionClient,
-    RequestNetworkPodRequest, RequestNetworkPodResponse,
+    ReleaseNetworkPodRequest, ReleaseNetworkPodResponse, RequestNetworkPodRequest,
+    RequestNetworkPodResponse,
 };
 
 use common::logd;
@@ -36,8 +37,38 @@ pub async fn request_network_pod(
         pod_name,
         network_yamls,
     };
-    let mut client = PharosNetworkServiceConnectionClient::connect(connect_pharos_server())
-        .await
-        .unwrap();
-    client.request_network_pod(Request::new(request)).await
+    let addr = connect_pharos_server();
+    let mut client = PharosNetworkServiceConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.request_network_pod(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
+}
+
+/// Send request to Pharos to tear down the network of a pod
+///
+/// ### Parameters
+/// * `node_yaml` - YAML representation of the node
+/// * `pod_name` - Name of the pod
+/// * `network_yamls` - Map of network configurations the pod was set up with
+/// ### Returns
+/// * `Result<Response<ReleaseNetworkPodResponse>, Status>` - Response from Pharos
+/// ### Description
+/// Connects to Pharos service and removes the network configuration of a pod
+pub async fn release_network_pod(
+    node_yaml: String,
+    pod_name: String,
+    network_yamls: String,
+) -> Result<Response<ReleaseNetworkPodResponse>, Status> {
+    let request = ReleaseNetworkPodRequest {
+        node_yaml,
+        pod_name,
+        network_yamls,
+    };
+    let addr = connect_pharos_server();
+    let mut client = PharosNetworkServiceConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.release_network_pod(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
 }
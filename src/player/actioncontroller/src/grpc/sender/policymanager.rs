@@ -19,7 +19,8 @@ use common::Result;
 ///
 /// # Returns
 ///
-/// * `Ok(())` if the policy check passes
+/// * `Ok(transition_id)` if the policy check passes, with the transition
+///   that moved the scenario to allowed
 /// * `Err(...)` if the policy check fails or the request fails
 ///
 /// # Errors
@@ -28,22 +29,27 @@ use common::Result;
 /// - The connection to PolicyManager is not established
 /// - The gRPC request fails (e.g., PolicyManager returns a gRPC Status error)
 /// - The policy check fails (application-level failure indicated by gRPC Status)
-#[allow(dead_code)]
-pub async fn check_policy(scenario_name: String) -> Result<()> {
+pub async fn check_policy(scenario_name: String) -> Result<String> {
     // Change return type
     if scenario_name.trim().is_empty() {
         return Err("Invalid scenario name: cannot be empty".into());
     }
 
     let addr = common::policymanager::connect_server();
-    let mut client = PolicyManagerConnectionClient::connect(addr)
-        .await
-        .map_err(|e| format!("Failed to connect to PolicyManager: {}", e))?;
+    let mut client = PolicyManagerConnectionClient::new(
+        common::grpc::channel(&addr)
+            .await
+            .map_err(|e| format!("Failed to connect to PolicyManager: {}", e.message()))?,
+    );
 
     let request = tonic::Request::new(CheckPolicyRequest {
         scenario_name: scenario_name.clone(),
+        correlation_id: common::correlation::current_or_empty(),
     }); // Clone scenario_name if needed later for error messages
-    let response = client.check_policy(request).await?;
+    let response = match client.check_policy(request).await {
+        Ok(response) => response,
+        Err(status) => return Err(common::grpc::release_on_failure(&addr, status).await.into()),
+    };
     let response_inner = response.into_inner();
 
     // Check application-level status from the response payload *only if* the gRPC call was successful
@@ -54,7 +60,7 @@ pub async fn check_policy(scenario_name: String) -> Result<()> {
             scenario_name,
             response_inner.desc
         );
-        Ok(()) // Policy passed
+        Ok(response_inner.transition_id) // Policy passed
     } else {
         // This block would only be reached if the server sent a successful gRPC status (OK)
         // but included an application-level error code (non-0 status) in the payload.
@@ -13,11 +13,15 @@
 //! confirmations, and error conditions back to the StateManager for proper resource
 //! state tracking and recovery management.
 
+use common::grpc::outbox::Outbox;
 use common::statemanager::{
-    connect_server, state_manager_connection_client::StateManagerConnectionClient, ResourceType,
-    StateChange, StateChangeResponse,
+    connect_server, state_manager_connection_client::StateManagerConnectionClient, ActionResult,
+    ActionResultResponse, GetVehicleModeRequest, ResourceType, StateChange, StateChangeResponse,
+    VehicleModeResponse,
 };
-use tonic::{Request, Status};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tonic::Status;
 
 /// StateManager gRPC client for ActionController component.
 ///
@@ -94,28 +98,30 @@ impl StateManagerSender {
     /// # Errors
     /// * `Status::unknown` - Connection establishment failed (network, service unavailable, etc.)
     ///
-    /// # Future Enhancements
-    /// - Add connection health checking and automatic reconnection
-    /// - Implement exponential backoff for connection retries
-    /// - Add connection pooling for high-throughput scenarios
+    /// The channel is shared through the process-wide pool in `common::grpc`,
+    /// so new senders do not open new connections.
     async fn ensure_connected(&mut self) -> Result<(), Status> {
         if self.client.is_none() {
-            match StateManagerConnectionClient::connect(connect_server()).await {
-                Ok(client) => {
-                    self.client = Some(client);
-                    Ok(())
-                }
-                Err(e) => Err(Status::unknown(format!(
-                    "Failed to connect to StateManager: {}",
-                    e
-                ))),
-            }
+            let channel = common::grpc::channel(&connect_server()).await?;
+            self.client = Some(StateManagerConnectionClient::new(channel));
+            Ok(())
         } else {
             // Connection already exists and ready for use
             Ok(())
         }
     }
 
+    /// Drops the connection after a transport failure so the next request reconnects
+    async fn release_on_failure<T>(&mut self, result: Result<T, Status>) -> Result<T, Status> {
+        match result {
+            Err(status) => {
+                self.client = None;
+                Err(common::grpc::release_on_failure(&connect_server(), status).await)
+            }
+            ok => ok,
+        }
+    }
+
     /// Sends a state change message to the StateManager service.
     ///
     /// This is the primary method for communicating action execution results from the
@@ -123,11 +129,17 @@ impl StateManagerSender {
     /// including connection management, request transmission, and response processing.
     ///
     /// # Request Processing Flow
-    /// 1. Ensure gRPC connection is established and ready
+    /// 1. Take the pooled gRPC channel to the StateManager
     /// 2. Create gRPC request wrapper with StateChange message
-    /// 3. Send request to StateManager via gRPC
+    /// 3. Send request to StateManager via gRPC, retrying transient failures
+    ///    with backoff through `common::grpc::retry`
     /// 4. Receive and return StateChangeResponse with tracking information
     ///
+    /// A change the StateManager cannot be reached for is stored in the outbox
+    /// of `common::grpc::outbox` and delivered by [`run_outbox`] once it is
+    /// back, later changes queue behind it. The response of a queued change
+    /// carries `ERROR_CODE_RESOURCE_UNAVAILABLE`.
+    ///
     /// # Arguments
     /// * `state_change` - Complete StateChange message containing:
     ///   - Resource identification (type enum and name)
@@ -142,8 +154,8 @@ impl StateManagerSender {
     ///   - Error codes and details if applicable
     ///
     /// # Errors
-    /// * `Status::unknown` - Connection failure or client not connected
-    /// * `Status::unavailable` - StateManager service unavailable
+    /// * `Status::unavailable` - StateManager service unavailable after all retries,
+    ///   or its circuit breaker is open
     /// * `Status::invalid_argument` - Malformed StateChange message
     /// * `Status::deadline_exceeded` - Request timeout (ASIL timing violation)
     ///
@@ -197,14 +209,51 @@ impl StateManagerSender {
         &mut self,
         state_change: StateChange,
     ) -> Result<tonic::Response<StateChangeResponse>, Status> {
-        // Ensure we have an active gRPC connection before sending
+        outbox().send(state_change, deliver).await
+    }
+
+    /// Queries the current vehicle operational mode from the StateManager.
+    ///
+    /// # Returns
+    /// * `Result<tonic::Response<VehicleModeResponse>, Status>` - Current mode, unspecified
+    ///   while no mode source has reported one
+    ///
+    /// # Errors
+    /// * `Status::unknown` - Connection failure or client not connected
+    pub async fn get_vehicle_mode(
+        &mut self,
+    ) -> Result<tonic::Response<VehicleModeResponse>, Status> {
         self.ensure_connected().await?;
 
         if let Some(client) = &mut self.client {
-            // Send the state change message via gRPC
-            client.send_state_change(Request::new(state_change)).await
+            let result = client
+                .get_vehicle_mode(common::auth::request(GetVehicleModeRequest {}))
+                .await;
+            self.release_on_failure(result).await
+        } else {
+            Err(Status::unknown("Client not connected"))
+        }
+    }
+
+    /// Reports the outcome of an action to the StateManager, which moves the
+    /// resource on to the state that follows it.
+    ///
+    /// Sent once, a repeated result would queue the transition after it twice.
+    ///
+    /// # Errors
+    /// * `Status::unknown` - Connection failure or client not connected
+    pub async fn report_action_result(
+        &mut self,
+        result: ActionResult,
+    ) -> Result<tonic::Response<ActionResultResponse>, Status> {
+        self.ensure_connected().await?;
+
+        if let Some(client) = &mut self.client {
+            let response = client
+                .report_action_result(common::auth::request(result))
+                .await;
+            self.release_on_failure(response).await
         } else {
-            // This should never happen due to ensure_connected, but provide safety fallback
             Err(Status::unknown("Client not connected"))
         }
     }
@@ -256,6 +305,10 @@ impl StateManagerSender {
             transition_id: transition_id.to_string(),
             timestamp_ns: timestamp,
             source: "actioncontroller".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
@@ -308,6 +361,10 @@ impl StateManagerSender {
             transition_id: format!("error-{}", transition_id), // Unique ID for error transition
             timestamp_ns: timestamp,
             source: "actioncontroller".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
@@ -360,12 +417,40 @@ impl StateManagerSender {
             transition_id: format!("recovery-{}", recovery_id),
             timestamp_ns: timestamp,
             source: "actioncontroller".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
     }
 }
 
+/// Outbox of the StateChanges of the ActionController
+fn outbox() -> &'static Outbox {
+    static OUTBOX: OnceLock<Outbox> = OnceLock::new();
+    OUTBOX.get_or_init(|| Outbox::new("actioncontroller"))
+}
+
+/// Send a StateChange to the StateManager, retrying transient failures
+async fn deliver(
+    state_change: StateChange,
+) -> Result<tonic::Response<StateChangeResponse>, Status> {
+    let state_change = &state_change;
+    common::grpc::retry::call(&connect_server(), |channel| async move {
+        StateManagerConnectionClient::new(channel)
+            .send_state_change(common::auth::request(state_change.clone()))
+            .await
+    })
+    .await
+}
+
+/// Deliver the StateChanges queued while the StateManager was unreachable
+pub async fn run_outbox(interval: Duration) {
+    outbox().run(interval, deliver).await
+}
+
 // ========================================
 // UNIT TESTS
 // ========================================
@@ -408,9 +493,13 @@ mod tests {
             resource_name: "brake-control-package".to_string(),
             current_state: "updating".to_string(),
             target_state: "running".to_string(),
-            transition_id: format!("update-complete-{}", timestamp),
+            transition_id: common::correlation::transition_id("actioncontroller"),
             timestamp_ns: timestamp,
             source: "actioncontroller".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         // Send the message and verify successful response
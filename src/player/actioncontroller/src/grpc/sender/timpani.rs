@@ -4,50 +4,199 @@
  */
 
 //! Running gRPC message sending to timpani
+//!
+//! Timpani keeps the scheduling parameters of realtime workloads in memory.
+//! Every SchedInfo sent to it is also kept here, so that stopped workloads
+//! release their reservations and a restarted Timpani gets the full table
+//! again from the periodic resync.
 use common::external::timpani::{
-    connect_timpani_server, sched_info_service_client::SchedInfoServiceClient, Response, SchedInfo,
-    SchedPolicy, TaskInfo,
+    connect_timpani_server, sched_info_service_client::SchedInfoServiceClient,
+    validate_task_constraints, SchedInfo, SchedInfoRemoval, TaskInfo,
 };
 use common::logd;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Attempts of a request before Timpani is considered unreachable
+const SCHED_ATTEMPTS: u32 = 3;
+/// Delay before the next attempt, multiplied by the attempts made
+const SCHED_RETRY_DELAY_MS: u64 = 500;
+/// Interval of the resync of the full sched table
+pub const SCHED_RESYNC_INTERVAL_SECS: u64 = 60;
+
+/// SchedInfo of every registered workload by workload id
+static SCHED_TABLE: Mutex<BTreeMap<String, SchedInfo>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone)]
+enum SchedRequest {
+    Add(SchedInfo),
+    Update(SchedInfo),
+    Remove(SchedInfoRemoval),
+}
 
-pub async fn add_sched_info(workload_id: String, task_name: &str, node_id: &str) {
-    logd!(1, "Connecting to Timpani server ....");
-    let mut client = SchedInfoServiceClient::connect(connect_timpani_server())
-        .await
-        .unwrap();
+impl SchedRequest {
+    fn rpc(&self) -> &'static str {
+        match self {
+            SchedRequest::Add(_) => "add_sched_info",
+            SchedRequest::Update(_) => "update_sched_info",
+            SchedRequest::Remove(_) => "remove_sched_info",
+        }
+    }
 
-    let request = SchedInfo {
-        workload_id: workload_id,
-        tasks: vec![TaskInfo {
-            name: task_name.to_string(),
-            priority: 50,
-            policy: SchedPolicy::Fifo as i32,
-            cpu_affinity: 7,
-            period: 10000, // 10 miliseconds
-            release_time: 0,
-            runtime: 5000,   // 5 miliseconds
-            deadline: 10000, // 10 miliseconds
-            node_id: node_id.to_string(),
-            max_dmiss: 3,
-        }],
+    fn workload_id(&self) -> &str {
+        match self {
+            SchedRequest::Add(info) | SchedRequest::Update(info) => &info.workload_id,
+            SchedRequest::Remove(removal) => &removal.workload_id,
+        }
+    }
+}
+
+/// Register the scheduling parameters of a workload's task with Timpani
+pub async fn add_sched_info(workload_id: String, task: TaskInfo) -> bool {
+    match remember(workload_id, task) {
+        Some(info) => send(SchedRequest::Add(info)).await,
+        None => false,
+    }
+}
+
+/// Replace the scheduling parameters of an updated workload
+pub async fn update_sched_info(workload_id: String, task: TaskInfo) -> bool {
+    match remember(workload_id, task) {
+        Some(info) => send(SchedRequest::Update(info)).await,
+        None => false,
+    }
+}
+
+/// Release the reservation of a stopped workload
+pub async fn remove_sched_info(workload_id: String) -> bool {
+    forget(&workload_id);
+    send(SchedRequest::Remove(SchedInfoRemoval { workload_id })).await
+}
+
+/// Send the whole sched table again, returning the workloads Timpani accepted
+///
+/// `UpdateSchedInfo` adds unknown workloads, so a restarted Timpani gets back
+/// every reservation.
+pub async fn resync_sched_info() -> usize {
+    let mut accepted = 0;
+    for info in table() {
+        if send(SchedRequest::Update(info)).await {
+            accepted += 1;
+        }
+    }
+    accepted
+}
+
+/// Resync the sched table with Timpani periodically
+pub async fn run_resync(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let total = table().len();
+        if total == 0 {
+            continue;
+        }
+        let accepted = resync_sched_info().await;
+        logd!(
+            if accepted == total { 1 } else { 4 },
+            "[resync_sched_info] {}/{} workloads resynced",
+            accepted,
+            total
+        );
+    }
+}
+
+/// Keep the SchedInfo of a workload, `None` if the task timing is infeasible
+fn remember(workload_id: String, task: TaskInfo) -> Option<SchedInfo> {
+    if !validate_task_constraints(&task) {
+        logd!(5, "[sched_info] infeasible timing for task {:?}", task);
+        return None;
+    }
+    let info = SchedInfo {
+        workload_id: workload_id.clone(),
+        tasks: vec![task],
     };
+    SCHED_TABLE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(workload_id, info.clone());
+    Some(info)
+}
 
-    let response: Result<Response, tonic::Status> =
-        client.add_sched_info(request).await.map(|r| r.into_inner());
+fn forget(workload_id: &str) {
+    SCHED_TABLE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(workload_id);
+}
 
-    match response {
-        Ok(res) => {
-            logd!(3, "[add_sched_info] RESPONSE={:?}", res);
+fn table() -> Vec<SchedInfo> {
+    SCHED_TABLE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .cloned()
+        .collect()
+}
+
+/// Send a request to Timpani, retrying failures and non-zero statuses
+async fn send(request: SchedRequest) -> bool {
+    let addr = connect_timpani_server();
+    for attempt in 1..=SCHED_ATTEMPTS {
+        let result = match common::grpc::channel(&addr).await {
+            Ok(channel) => {
+                let mut client = SchedInfoServiceClient::new(channel);
+                let response = match request.clone() {
+                    SchedRequest::Add(info) => client.add_sched_info(info).await,
+                    SchedRequest::Update(info) => client.update_sched_info(info).await,
+                    SchedRequest::Remove(removal) => client.remove_sched_info(removal).await,
+                };
+                match response.map(|r| r.into_inner()) {
+                    Ok(res) if res.status == 0 => Ok(()),
+                    Ok(res) => Err(format!("status {}", res.status)),
+                    Err(e) => Err(format!(
+                        "{:?}",
+                        common::grpc::release_on_failure(&addr, e).await
+                    )),
+                }
+            }
+            Err(e) => Err(format!("{:?}", e)),
+        };
+
+        match result {
+            Ok(()) => {
+                logd!(3, "[{}] {} accepted", request.rpc(), request.workload_id());
+                return true;
+            }
+            Err(e) => logd!(
+                4,
+                "[{}] attempt {}/{} for {} failed: {}",
+                request.rpc(),
+                attempt,
+                SCHED_ATTEMPTS,
+                request.workload_id(),
+                e
+            ),
         }
-        Err(e) => {
-            logd!(5, "[add_sched_info] ERROR={:?}", e);
+        if attempt < SCHED_ATTEMPTS {
+            tokio::time::sleep(Duration::from_millis(SCHED_RETRY_DELAY_MS * attempt as u64)).await;
         }
     }
+    logd!(
+        5,
+        "[{}] giving up on {}, the resync sends it again",
+        request.rpc(),
+        request.workload_id()
+    );
+    false
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::external::timpani::SchedPolicy;
 
     // ==================== Direct Function Call Tests ====================
 
@@ -527,6 +676,26 @@ mod tests {
         assert!(validate_sched_info(&sched_info));
     }
 
+    #[tokio::test]
+    async fn test_sched_table_keeps_feasible_tasks() {
+        let task = create_timpani_test_request().tasks.remove(0);
+        let info = remember("table_test".to_string(), task.clone()).unwrap();
+        assert_eq!(info.tasks, vec![task.clone()]);
+        assert!(table().iter().any(|info| info.workload_id == "table_test"));
+
+        let infeasible = TaskInfo {
+            runtime: task.period + 1,
+            ..task
+        };
+        assert!(remember("table_test_infeasible".to_string(), infeasible).is_none());
+        assert!(!table()
+            .iter()
+            .any(|info| info.workload_id == "table_test_infeasible"));
+
+        forget("table_test");
+        assert!(!table().iter().any(|info| info.workload_id == "table_test"));
+    }
+
     // Helper function to create default TimPani test request
     pub fn create_timpani_test_request() -> SchedInfo {
         SchedInfo {
@@ -546,13 +715,6 @@ mod tests {
         }
     }
 
-    // Helper function to validate task constraints
-    pub fn validate_task_constraints(task: &TaskInfo) -> bool {
-        task.release_time <= task.runtime
-            && task.runtime <= task.deadline
-            && task.deadline <= task.period
-    }
-
     // Helper function to validate SchedInfo
     pub fn validate_sched_info(sched_info: &SchedInfo) -> bool {
         !sched_info.workload_id.is_empty() && sched_info.tasks.iter().all(validate_task_constraints)
@@ -4,11 +4,18 @@
 */
 use common::logd;
 use common::logd::logger;
+use common::startup::Dependency;
 use std::error::Error;
 
+mod admission;
+mod autostart;
 mod grpc;
 mod manager;
+mod network;
+mod operation;
+mod rebalance;
 mod runtime;
+mod update;
 
 /// Initialize the ActionController component
 ///
@@ -67,9 +74,31 @@ async fn initialize(skip_grpc: bool) -> Result<(), Box<dyn Error>> {
 async fn main() -> Result<(), Box<dyn Error>> {
     let _ = logger::init_async_logger("actioncontroller").await;
     logd!(1, "initiailize action controller");
+    common::health::init("actioncontroller", true);
+    common::address::validate_or_exit("actioncontroller");
+    tokio::spawn(common::health::serve(
+        common::actioncontroller::open_health_server(),
+    ));
+    common::startup::wait("actioncontroller", &[Dependency::Etcd]).await?;
 
     // Initialize the controller
     initialize(false).await?;
+    common::health::set_started();
+
+    // Give a restarted Timpani the scheduling parameters back
+    tokio::spawn(grpc::sender::timpani::run_resync(
+        std::time::Duration::from_secs(grpc::sender::timpani::SCHED_RESYNC_INTERVAL_SECS),
+    ));
+
+    // Tear down the networks Pharos could not release before
+    tokio::spawn(network::run_retry(std::time::Duration::from_secs(
+        network::TEARDOWN_RETRY_INTERVAL_SECS,
+    )));
+
+    // Deliver the StateChanges queued while the StateManager was unreachable
+    tokio::spawn(grpc::sender::statemanager::run_outbox(
+        std::time::Duration::from_secs(common::grpc::outbox::FLUSH_INTERVAL_SECS),
+    ));
 
     // TODO: Set up gRPC server
 
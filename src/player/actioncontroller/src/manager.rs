@@ -4,13 +4,26 @@
 */
 use std::{collections::HashMap, thread, time::Duration};
 
+use crate::admission::{Admission, Decision, Demand, Previous, Resources};
 use crate::grpc::sender::pharos::request_network_pod;
 use crate::grpc::sender::statemanager::StateManagerSender;
+use crate::network::{Phase, Status as NetworkStatus};
+use crate::operation::Operations;
+use crate::update::{self, CanaryBake, HealthGate};
 use common::logd;
 use common::{
-    actioncontroller::PodStatus as Status,
-    spec::artifact::{package::ModelInfo, Model, Package, Scenario},
-    statemanager::{ResourceType, StateChange},
+    actioncontroller::{ModelMove, PodStatus as Status},
+    error::Error,
+    spec::artifact::{
+        package::{ModelInfo, PackageDiff, RealtimeSpec, UpdateStrategy, UpdateStrategyType},
+        Artifact, Model, Package, Scenario,
+    },
+    spec::k8s::pod::PodSpec,
+    spec::k8s::Pod,
+    statemanager::{
+        ActionResult, DenialReason, ResourceType, StateChange, VehicleMode, CANARY_PROMOTED,
+        CANARY_ROLLED_BACK,
+    },
     Result,
 };
 
@@ -24,10 +37,31 @@ const ETCD_NODE_PREFIX: &str = "Node";
 const ETCD_NODES_PREFIX: &str = "nodes";
 const ETCD_CLUSTER_NODES_PREFIX: &str = "cluster/nodes";
 
+// Actions gated by the scenario's allowed vehicle modes
+const MODE_GATED_ACTIONS: [&str; 2] = ["launch", "update"];
+
+// Actions admitted against node capacity before they run
+const ADMITTED_ACTIONS: [&str; 3] = ["launch", "update", "rollback"];
+
 // Node types
 const NODE_TYPE_NODEAGENT: &str = "nodeagent";
 const NODE_ROLE_NODEAGENT: i32 = 2;
 
+// Attempts to set up the network of a model before it is marked failed
+const NETWORK_SETUP_ATTEMPTS: u32 = 3;
+const NETWORK_RETRY_DELAY_MS: u64 = 500;
+
+/// Network setup accepted by Pharos and waiting for its completion
+#[derive(Debug, Clone, PartialEq)]
+struct NetworkRequest {
+    model_name: String,
+    node_yaml: String,
+    pod_name: String,
+    network_yaml: String,
+    /// Setup requests sent for the model so far
+    attempts: u32,
+}
+
 /// Manager for coordinating scenario actions and workload operations
 ///
 /// Responsible for:
@@ -40,7 +74,12 @@ pub struct ActionControllerManager {
     pub nodeagent_nodes: Vec<String>,
     /// StateManager sender for scenario state changes
     state_sender: StateManagerSender,
-    // Add other fields as needed
+    /// Pending network setups by Pharos request id
+    network_requests: tokio::sync::Mutex<HashMap<String, NetworkRequest>>,
+    /// Resources reserved on nodes and launches waiting for capacity
+    admission: tokio::sync::Mutex<Admission>,
+    /// Operations running or waiting on scenarios and packages
+    operations: Operations,
 }
 #[allow(dead_code)]
 impl ActionControllerManager {
@@ -58,6 +97,9 @@ impl ActionControllerManager {
         Self {
             nodeagent_nodes: Vec::new(),
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         }
     }
 
@@ -121,7 +163,10 @@ impl ActionControllerManager {
             logd!(2, "Using host IP from settings.yaml: {}", config.host.ip);
             Ok(config.host.ip.clone())
         } else {
-            Err(format!("No IP found for node '{}'", node_name).into())
+            Err(Error::NotFound(format!(
+                "No IP found for node '{}'",
+                node_name
+            )))
         }
     }
 
@@ -132,15 +177,18 @@ impl ActionControllerManager {
             logd!(2, "Using role from settings.yaml for node '{}'", node_name);
             Ok(NODE_TYPE_NODEAGENT.to_string())
         } else {
-            Err(format!("No details found for node '{}'", node_name).into())
+            Err(Error::NotFound(format!(
+                "No details found for node '{}'",
+                node_name
+            )))
         }
     }
 
-    /// Load node roles for all models in a package
+    /// Load node roles for all models in a package, every replica included
     async fn load_node_roles(&self, package: &Package) -> HashMap<String, String> {
         let mut node_roles = HashMap::new();
 
-        for mi in package.get_models() {
+        for mi in package.get_replicas() {
             let model_node = mi.get_node();
             if node_roles.contains_key(&model_node) {
                 continue;
@@ -218,32 +266,78 @@ impl ActionControllerManager {
     ) -> Result<()> {
         let model_name = model_info.get_name();
         let model_node = model_info.get_node();
-        let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, model_name)).await?;
+        let pod_name = model_info.get_pod_name();
+        let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, pod_name)).await?;
 
         match action {
             "launch" => {
                 self.start_workload(&pod, &model_node, node_type).await?;
+                if let Some(standby_node) = model_info.get_standby_node() {
+                    self.launch_standby(&model_info.get_standby_pod_name(), &standby_node)
+                        .await;
+                }
 
-                if network_str.is_some() && node_str.is_some() {
-                    request_network_pod(
-                        node_str.clone().unwrap(),
-                        scenario_name.to_string(),
-                        network_str.clone().unwrap(),
+                if let (Some(network_yaml), Some(node_yaml)) = (network_str, node_str) {
+                    let request = NetworkRequest {
+                        model_name: model_name.clone(),
+                        node_yaml: node_yaml.clone(),
+                        pod_name: scenario_name.to_string(),
+                        network_yaml: network_yaml.clone(),
+                        attempts: 0,
+                    };
+                    self.notify_resource_state(
+                        ResourceType::Network,
+                        &model_name,
+                        "unspecified",
+                        "requested",
                     )
-                    .await
-                    .map_err(|e| {
-                        format!("Failed to request network pod for '{}': {}", model_name, e)
-                    })?;
+                    .await;
+                    let record = crate::network::Record {
+                        phase: Phase::Setup,
+                        status: NetworkStatus::Requested,
+                        node_yaml: request.node_yaml.clone(),
+                        pod_name: request.pod_name.clone(),
+                        network_yaml: request.network_yaml.clone(),
+                        teardown_attempts: 0,
+                    };
+                    if let Err(e) = crate::network::put(&model_name, &record).await {
+                        logd!(4, "Network of model '{}' not recorded: {}", model_name, e);
+                    }
+                    self.send_network_request(request).await?;
+                }
+
+                if let Some(realtime) = model_info.get_resources().get_realtime_spec() {
+                    self.handle_realtime_sched(action, model_info, &model_node, &realtime)
+                        .await?;
                 }
             }
             "terminate" => {
                 self.stop_workload(&pod, &model_node, node_type).await?;
+                if let Some(standby_node) = model_info.get_standby_node() {
+                    let standby_pod = model_info.get_standby_pod_name();
+                    if let Err(e) = self.stop_model_on_node(&standby_pod, &standby_node).await {
+                        logd!(
+                            4,
+                            "Standby '{}' not stopped on '{}': {}",
+                            standby_pod,
+                            standby_node,
+                            e
+                        );
+                    }
+                }
+
+                if model_info.get_resources().get_realtime().unwrap_or(false) {
+                    crate::grpc::sender::timpani::remove_sched_info(model_name.clone()).await;
+                }
+                self.tear_down_network(&model_name, scenario_name, network_str, node_str)
+                    .await;
             }
             "update" | "rollback" => {
                 self.restart_workload(&pod, &model_node, node_type).await?;
 
-                if model_info.get_resources().get_realtime().unwrap_or(false) {
-                    self.handle_realtime_sched(model_info, &model_node).await?;
+                if let Some(realtime) = model_info.get_resources().get_realtime_spec() {
+                    self.handle_realtime_sched(action, model_info, &model_node, &realtime)
+                        .await?;
                 }
             }
             _ => {
@@ -251,44 +345,320 @@ impl ActionControllerManager {
             }
         }
 
+        let recorded = match action {
+            "launch" | "update" | "rollback" => {
+                crate::autostart::record(&model_node, &pod_name, scenario_name).await
+            }
+            "terminate" => crate::autostart::forget(&model_node, &pod_name).await,
+            _ => Ok(()),
+        };
+        if let Err(e) = recorded {
+            logd!(
+                4,
+                "Autostart record of model '{}' not updated: {}",
+                model_name,
+                e
+            );
+        }
+
         Ok(())
     }
 
     /// Handle realtime scheduling for a model
-    async fn handle_realtime_sched(&self, model_info: &ModelInfo, model_node: &str) -> Result<()> {
+    ///
+    /// A launch registers the task of the model with Timpani, an update or a
+    /// rollback replaces its scheduling parameters.
+    async fn handle_realtime_sched(
+        &self,
+        action: &str,
+        model_info: &ModelInfo,
+        model_node: &str,
+        realtime: &RealtimeSpec,
+    ) -> Result<()> {
         let model_str =
-            common::etcd::get(&format!("{}/{}", ETCD_MODEL_PREFIX, model_info.get_name())).await?;
+            common::etcd::get(&format!("{}/{}", ETCD_MODEL_PREFIX, model_info.get_name()))
+                .await
+                .map_err(common::etcd::error)?;
         let model: Model = serde_yaml::from_str(&model_str)?;
 
         if let Some(command) = model.get_podspec().containers[0].command.clone() {
             if let Some(task_name) = command.last() {
-                crate::grpc::sender::timpani::add_sched_info(
-                    model_info.get_name(),
-                    task_name,
-                    model_node,
+                let task = realtime.to_task_info(task_name, model_node);
+                if action == "launch" {
+                    crate::grpc::sender::timpani::add_sched_info(model_info.get_name(), task).await;
+                } else {
+                    crate::grpc::sender::timpani::update_sched_info(model_info.get_name(), task)
+                        .await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Current vehicle mode from StateManager, unspecified if it cannot be queried
+    async fn current_vehicle_mode(&self) -> VehicleMode {
+        match self.state_sender.clone().get_vehicle_mode().await {
+            Ok(response) => VehicleMode::try_from(response.into_inner().mode)
+                .unwrap_or(VehicleMode::Unspecified),
+            Err(e) => {
+                logd!(4, "Failed to query vehicle mode from StateManager: {:?}", e);
+                VehicleMode::Unspecified
+            }
+        }
+    }
+
+    /// Have Pharos tear down the network of a terminated model
+    ///
+    /// Models whose network was not recorded, e.g. launched by an earlier
+    /// version, are torn down with the yamls of their scenario. A failed
+    /// teardown is retried by [`crate::network::run_retry`] until it is
+    /// given up, see [`crate::network::tear_down`].
+    async fn tear_down_network(
+        &self,
+        model_name: &str,
+        scenario_name: &str,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+    ) {
+        if crate::network::get(model_name).await.is_none() {
+            let (Some(network_yaml), Some(node_yaml)) = (network_str, node_str) else {
+                return;
+            };
+            let record = crate::network::Record {
+                phase: Phase::Setup,
+                status: NetworkStatus::Done,
+                node_yaml: node_yaml.clone(),
+                pod_name: scenario_name.to_string(),
+                network_yaml: network_yaml.clone(),
+                teardown_attempts: 0,
+            };
+            if let Err(e) = crate::network::put(model_name, &record).await {
+                logd!(4, "Network of model '{}' not torn down: {}", model_name, e);
+                return;
+            }
+        }
+        if let Err(e) = crate::network::tear_down(model_name).await {
+            logd!(
+                4,
+                "Network record of model '{}' not updated: {}",
+                model_name,
+                e
+            );
+        }
+    }
+
+    /// Record the outcome of the network setup of a model
+    async fn record_network_setup(&self, model_name: &str, status: NetworkStatus) {
+        if let Err(e) = crate::network::set(model_name, Phase::Setup, status).await {
+            logd!(
+                4,
+                "Network record of model '{}' not updated: {}",
+                model_name,
+                e
+            );
+        }
+    }
+
+    /// Request the network of a model from Pharos, retrying rejected requests
+    ///
+    /// An accepted request is kept until Pharos completes it. When every
+    /// attempt fails the network is reported failed to StateManager.
+    async fn send_network_request(&self, mut request: NetworkRequest) -> Result<()> {
+        loop {
+            request.attempts += 1;
+            let error = match request_network_pod(
+                request.node_yaml.clone(),
+                request.pod_name.clone(),
+                request.network_yaml.clone(),
+            )
+            .await
+            {
+                Ok(response) => {
+                    let response = response.into_inner();
+                    if response.accepted {
+                        logd!(
+                            2,
+                            "Network setup for model '{}' accepted as request {}",
+                            request.model_name,
+                            response.request_id
+                        );
+                        self.network_requests
+                            .lock()
+                            .await
+                            .insert(response.request_id, request);
+                        return Ok(());
+                    }
+                    response.message
+                }
+                Err(e) => e.to_string(),
+            };
+
+            logd!(
+                4,
+                "Network setup for model '{}' failed (attempt {}/{}): {}",
+                request.model_name,
+                request.attempts,
+                NETWORK_SETUP_ATTEMPTS,
+                error
+            );
+            if request.attempts >= NETWORK_SETUP_ATTEMPTS {
+                self.notify_resource_state(
+                    ResourceType::Network,
+                    &request.model_name,
+                    "requested",
+                    "failed",
                 )
                 .await;
+                self.record_network_setup(&request.model_name, NetworkStatus::Failed)
+                    .await;
+                return Err(Error::DependencyFailed(format!(
+                    "Failed to request network pod for '{}': {}",
+                    request.model_name, error
+                )));
             }
+            tokio::time::sleep(Duration::from_millis(
+                NETWORK_RETRY_DELAY_MS * request.attempts as u64,
+            ))
+            .await;
         }
+    }
 
-        Ok(())
+    /// Handle the completion of a network setup reported by Pharos
+    ///
+    /// A successful setup marks the network of the model ready. A failed one
+    /// is requested again until the attempts are used up.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the request was known
+    /// * `Err(...)` if the request id is unknown or the retry failed
+    pub async fn complete_network_setting(
+        &self,
+        request_id: &str,
+        success: bool,
+        details: &str,
+    ) -> Result<()> {
+        let request = self
+            .network_requests
+            .lock()
+            .await
+            .remove(request_id)
+            .ok_or_else(|| format!("Unknown network request '{}'", request_id))?;
+
+        if success {
+            self.notify_resource_state(
+                ResourceType::Network,
+                &request.model_name,
+                "requested",
+                "ready",
+            )
+            .await;
+            self.record_network_setup(&request.model_name, NetworkStatus::Done)
+                .await;
+            return Ok(());
+        }
+
+        logd!(
+            4,
+            "Network setup of model '{}' failed: {}",
+            request.model_name,
+            details
+        );
+        if request.attempts >= NETWORK_SETUP_ATTEMPTS {
+            self.notify_resource_state(
+                ResourceType::Network,
+                &request.model_name,
+                "requested",
+                "failed",
+            )
+            .await;
+            self.record_network_setup(&request.model_name, NetworkStatus::Failed)
+                .await;
+            return Ok(());
+        }
+        self.send_network_request(request).await
     }
 
     /// Send state change notification to StateManager
     async fn notify_state_change(&self, scenario_name: &str, current: &str, target: &str) {
+        self.notify_resource_state(ResourceType::Scenario, scenario_name, current, target)
+            .await;
+    }
+
+    /// Send a state change of any resource to StateManager
+    async fn notify_resource_state(
+        &self,
+        resource_type: ResourceType,
+        resource_name: &str,
+        current: &str,
+        target: &str,
+    ) {
+        self.send_resource_state(resource_type, resource_name, current, target, "", None)
+            .await;
+    }
+
+    /// Report a scenario refused by an admission check
+    async fn notify_denial(&self, scenario_name: &str, current: &str, rule: &str, reason: &str) {
+        let denial = DenialReason {
+            rule: rule.to_string(),
+            message: reason.to_string(),
+            source: "actioncontroller".to_string(),
+            timestamp_ns: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as i64),
+        };
+        self.send_resource_state(
+            ResourceType::Scenario,
+            scenario_name,
+            current,
+            "denied",
+            "",
+            Some(denial),
+        )
+        .await;
+    }
+
+    /// Report the step of a package update as sub-state of updating
+    async fn notify_update_progress(&self, package_name: &str, step: &str) {
+        self.send_resource_state(
+            ResourceType::Package,
+            package_name,
+            "updating",
+            "updating",
+            step,
+            None,
+        )
+        .await;
+    }
+
+    /// Send a state change with the sub-state reached within `target`
+    async fn send_resource_state(
+        &self,
+        resource_type: ResourceType,
+        resource_name: &str,
+        current: &str,
+        target: &str,
+        sub_state: &str,
+        denial: Option<DenialReason>,
+    ) {
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_nanos() as i64;
 
         let state_change = StateChange {
-            resource_type: ResourceType::Scenario as i32,
-            resource_name: scenario_name.to_string(),
+            resource_type: resource_type as i32,
+            resource_name: resource_name.to_string(),
             current_state: current.to_string(),
             target_state: target.to_string(),
-            transition_id: format!("actioncontroller-processing-complete-{}", timestamp),
+            transition_id: common::correlation::transition_id("actioncontroller"),
             timestamp_ns: timestamp,
             source: "actioncontroller".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: sub_state.to_string(),
+            denial,
+            metadata: Default::default(),
         };
 
         if let Err(e) = self
@@ -305,8 +675,9 @@ impl ActionControllerManager {
         } else {
             logd!(
                 3,
-                "  ✅ Successfully notified StateManager: scenario {}, {} → {}",
-                scenario_name,
+                "  ✅ Successfully notified StateManager: {:?} {}, {} → {}",
+                resource_type,
+                resource_name,
                 current,
                 target
             );
@@ -326,14 +697,20 @@ impl ActionControllerManager {
                 "start" => crate::runtime::nodeagent::start_workload(pod, node_name).await?,
                 "stop" => crate::runtime::nodeagent::stop_workload(pod, node_name).await?,
                 "restart" => crate::runtime::nodeagent::restart_workload(pod, node_name).await?,
-                _ => return Err(format!("Unknown operation '{}'", operation).into()),
+                "pause" => crate::runtime::nodeagent::pause_workload(pod, node_name).await?,
+                "unpause" => crate::runtime::nodeagent::unpause_workload(pod, node_name).await?,
+                _ => {
+                    return Err(Error::InvalidRequest(format!(
+                        "Unknown operation '{}'",
+                        operation
+                    )))
+                }
             },
             _ => {
-                return Err(format!(
+                return Err(Error::InvalidRequest(format!(
                     "Unsupported node type '{}' for workload '{}' on node '{}'",
                     node_type, pod, node_name
-                )
-                .into());
+                )));
             }
         }
         Ok(())
@@ -363,16 +740,246 @@ impl ActionControllerManager {
         logd!(2, "trigger_manager_action in manager {:?}", scenario_name);
 
         if scenario_name.trim().is_empty() {
-            return Err(format!("Scenario '{}' is invalid: cannot be empty", scenario_name).into());
+            return Err(Error::InvalidRequest(format!(
+                "Scenario '{}' is invalid: cannot be empty",
+                scenario_name
+            )));
         }
 
         let (scenario, package, network_str, node_str) =
             self.get_scenario_resources(scenario_name).await?;
+
+        // Mode restricted launches and updates wait for a permitted vehicle mode.
+        // The scenario stays satisfied so it can be triggered again later.
+        if MODE_GATED_ACTIONS.contains(&scenario.get_actions().as_str())
+            && !scenario.get_allowed_modes().is_empty()
+        {
+            let mode = self.current_vehicle_mode().await;
+            check_vehicle_mode(&scenario, mode)?;
+        }
+
+        // PolicyManager moves the scenario to allowed or denied
+        let transition_id =
+            crate::grpc::sender::policymanager::check_policy(scenario_name.to_string())
+                .await
+                .map_err(|e| format!("Scenario '{}' denied by policy: {}", scenario_name, e))?;
+
+        self.run_scenario_action(
+            scenario_name,
+            &transition_id,
+            &scenario,
+            &package,
+            &network_str,
+            &node_str,
+            "allowed",
+        )
+        .await?;
+
+        // Terminated models may have made room for queued launches
+        if scenario.get_actions() == "terminate" {
+            self.admit_queued().await;
+        }
+        Ok(())
+    }
+
+    /// Run the action of an allowed scenario on every model of its package
+    ///
+    /// The action waits for the operations running on the scenario or its
+    /// package, see [`crate::operation`]. One that is rejected as in progress
+    /// is reported to StateManager as rejected, the scenario is left to the
+    /// running operation. One turned away because too many operations wait
+    /// is reported as failed, so that the scenario is denied. Launches, updates and rollbacks are admitted
+    /// against the capacity of their nodes first, see [`crate::admission`].
+    /// The scenario moves from `from_state` to pending while it waits for
+    /// capacity, or to denied if it cannot fit. Once the action ran its
+    /// outcome is reported to StateManager with `transition_id`, the
+    /// transition to allowed that queued the action, and StateManager
+    /// completes the scenario or denies it.
+    async fn run_scenario_action(
+        &self,
+        scenario_name: &str,
+        transition_id: &str,
+        scenario: &Scenario,
+        package: &Package,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+        from_state: &str,
+    ) -> Result<()> {
         let action = scenario.get_actions();
-        let node_roles = self.load_node_roles(&package).await;
+        let resources = crate::operation::resources(scenario_name, &package.get_qualified_name());
+        let operation = format!("{} {}", action, scenario_name);
+        for resource in &resources {
+            let queued = self.operations.of(resource);
+            if !queued.is_empty() {
+                logd!(2, "'{}' waits for {:?} on {}", operation, queued, resource);
+            }
+        }
+        let _operation = match self.operations.acquire(&resources, &operation).await {
+            Ok(guard) => guard,
+            Err(e) => {
+                logd!(4, "Scenario '{}' not run: {}", scenario_name, e);
+                if matches!(e, Error::PreconditionFailed(_)) {
+                    self.report_rejected_action(
+                        scenario_name,
+                        transition_id,
+                        &action,
+                        &e.to_string(),
+                    )
+                    .await;
+                } else {
+                    self.report_action_result(
+                        scenario_name,
+                        transition_id,
+                        &action,
+                        Some(e.to_string()),
+                    )
+                    .await;
+                }
+                return Err(e);
+            }
+        };
 
-        for mi in package.get_models() {
-            let model_name = mi.get_name();
+        let previous = if ADMITTED_ACTIONS.contains(&action.as_str()) {
+            match self
+                .admit(scenario_name, transition_id, package, from_state)
+                .await?
+            {
+                Some(previous) => Some(previous),
+                None => return Ok(()),
+            }
+        } else {
+            None
+        };
+
+        let error = self
+            .execute_scenario_action(scenario_name, &action, package, network_str, node_str)
+            .await
+            .err()
+            .map(|e| e.to_string());
+        if let (Some(_), Some(previous)) = (&error, previous) {
+            logd!(
+                3,
+                "Scenario '{}' failed to {}, its reservations are released",
+                scenario_name,
+                action
+            );
+            self.admission.lock().await.restore(previous);
+        }
+        self.report_action_result(scenario_name, transition_id, &action, error.clone())
+            .await;
+        match error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Report the outcome of the action of a scenario to StateManager
+    async fn report_action_result(
+        &self,
+        scenario_name: &str,
+        transition_id: &str,
+        action: &str,
+        error: Option<String>,
+    ) {
+        let result = action_result(scenario_name, transition_id, action, error, false);
+        self.send_action_result(result).await;
+    }
+
+    /// Report an action that did not run because of another operation
+    async fn report_rejected_action(
+        &self,
+        scenario_name: &str,
+        transition_id: &str,
+        action: &str,
+        reason: &str,
+    ) {
+        let result = action_result(
+            scenario_name,
+            transition_id,
+            action,
+            Some(reason.to_string()),
+            true,
+        );
+        self.send_action_result(result).await;
+    }
+
+    async fn send_action_result(&self, result: ActionResult) {
+        let (action, scenario_name) = (result.action.clone(), result.resource_name.clone());
+        let outcome = match (result.success, result.rejected) {
+            (true, _) => "succeeded",
+            (false, true) => "rejected",
+            (false, false) => "failed",
+        };
+        match self.state_sender.clone().report_action_result(result).await {
+            Ok(_) => logd!(
+                3,
+                "  ✅ Reported action '{}' of scenario '{}' as {}",
+                action,
+                scenario_name,
+                outcome
+            ),
+            Err(e) => logd!(
+                5,
+                "  ❌ Failed to report the result of action '{}' of scenario '{}': {:?}",
+                action,
+                scenario_name,
+                e
+            ),
+        }
+    }
+
+    /// Execute an admitted action on every model of the package
+    ///
+    /// An update after the package was applied again only restarts the
+    /// models the apply added or changed, see [`PackageDiff`].
+    async fn execute_scenario_action(
+        &self,
+        scenario_name: &str,
+        action: &str,
+        package: &Package,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+    ) -> Result<()> {
+        let node_roles = self.load_node_roles(package).await;
+        let diff = take_package_diff(&package.get_qualified_name()).await;
+        let replicas = match diff {
+            Some(diff) if action == "update" => {
+                logd!(
+                    2,
+                    "Updating {} added and {} changed model(s) of package '{}', {} unchanged",
+                    diff.added.len(),
+                    diff.changed.len(),
+                    diff.package,
+                    diff.unchanged.len()
+                );
+                package
+                    .get_replicas()
+                    .into_iter()
+                    .filter(|mi| diff.redeploys(&mi.get_pod_name()))
+                    .collect()
+            }
+            _ => package.get_replicas(),
+        };
+
+        if ADMITTED_ACTIONS.contains(&action) {
+            self.prefetch_images(&replicas, &node_roles).await;
+        }
+
+        if action == "update" && package.get_strategy().r#type != UpdateStrategyType::Recreate {
+            return self
+                .update_package(
+                    scenario_name,
+                    package,
+                    &replicas,
+                    &node_roles,
+                    network_str,
+                    node_str,
+                )
+                .await;
+        }
+
+        for mi in &replicas {
+            let model_name = mi.get_pod_name();
             let model_node = mi.get_node();
 
             let node_type = match node_roles.get(&model_node) {
@@ -394,106 +1001,1552 @@ impl ActionControllerManager {
                 action
             );
 
-            self.execute_model_action(
-                &action,
-                &mi,
-                node_type,
-                scenario_name,
-                &network_str,
-                &node_str,
-            )
-            .await
-            .map_err(|e| {
-                format!(
-                    "Failed to execute action '{}' on model '{}': {}",
-                    action, model_name, e
-                )
-            })?;
+            self.execute_model_action(action, mi, node_type, scenario_name, network_str, node_str)
+                .await
+                .map_err(|e| {
+                    format!(
+                        "Failed to execute action '{}' on model '{}': {}",
+                        action, model_name, e
+                    )
+                })?;
         }
 
-        self.notify_state_change(scenario_name, "allowed", "completed")
-            .await;
+        if action == "terminate" {
+            let models: Vec<String> = package
+                .get_replicas()
+                .iter()
+                .map(|m| m.get_pod_name())
+                .collect();
+            self.admission.lock().await.release(&models);
+        }
 
         Ok(())
     }
 
-    /// Reconciles current and desired states for a scenario
-    ///
-    /// Compares the current state with the desired state for a given scenario
-    /// and performs the necessary actions to align them.
-    ///
-    /// # Arguments
+    /// Update `model_infos`, models of a package, with its update strategy
     ///
-    /// * `scenario_name` - Name of the scenario
-    /// * `current` - Current state value
-    /// * `desired` - Desired state value
-    ///
-    /// # Returns
-    ///
-    /// * `Ok(())` if the reconciliation was successful
-    /// * `Err(...)` if the reconciliation failed
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    /// - The scenario does not exist
-    /// - The reconciliation action fails
-    pub async fn reconcile_do(
+    /// The package is reported as updating with the step in progress, then
+    /// as running once every model runs its new version, or as error with
+    /// the failed step, see [`crate::update`]. A canary update also ends in
+    /// running when its canary is rolled back, with the outcome as sub-state. Models on nodes of unknown
+    /// role are skipped like in any other action.
+    async fn update_package(
         &self,
-        scenario_name: String,
-        current: Status,
-        desired: Status,
+        scenario_name: &str,
+        package: &Package,
+        model_infos: &[ModelInfo],
+        node_roles: &HashMap<String, String>,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
     ) -> Result<()> {
-        if current == desired {
-            return Ok(());
-        }
+        let package_name = package.get_qualified_name();
+        let strategy = package.get_strategy();
+        let gate = HealthGate::for_strategy(strategy);
+        let models: Vec<(&ModelInfo, &str)> = model_infos
+            .iter()
+            .filter_map(|mi| match node_roles.get(&mi.get_node()) {
+                Some(role) => Some((mi, role.as_str())),
+                None => {
+                    logd!(4, "Warning: Node '{}' is not configured or cannot determine its role. Skipping update.", mi.get_node());
+                    None
+                }
+            })
+            .collect();
 
-        if matches!(current, Status::None | Status::Failed | Status::Unknown) {
-            return Err(format!(
-                "Invalid current status: {:?}. Cannot reconcile from this state",
-                current
-            )
-            .into());
+        let current = common::etcd::get(&format!("/package/{}/state", package_name))
+            .await
+            .unwrap_or_else(|_| "idle".to_string());
+        self.send_resource_state(
+            ResourceType::Package,
+            &package_name,
+            &current,
+            "updating",
+            &format!("{:?} update of {} model(s)", strategy.r#type, models.len()),
+            None,
+        )
+        .await;
+
+        let result = match strategy.r#type {
+            UpdateStrategyType::BlueGreen => self
+                .blue_green_update(
+                    &package_name,
+                    &models,
+                    &gate,
+                    scenario_name,
+                    network_str,
+                    node_str,
+                )
+                .await
+                .map(|()| String::new()),
+            UpdateStrategyType::Canary => {
+                self.canary_update(
+                    &package_name,
+                    &models,
+                    strategy,
+                    scenario_name,
+                    network_str,
+                    node_str,
+                )
+                .await
+            }
+            _ => self
+                .rolling_update(
+                    &package_name,
+                    &models,
+                    &gate,
+                    scenario_name,
+                    network_str,
+                    node_str,
+                )
+                .await
+                .map(|()| String::new()),
         }
-
-        if matches!(desired, Status::None | Status::Failed | Status::Unknown) {
-            return Err(format!(
-                "Invalid desired status: {:?}. Cannot set this as target state",
-                desired
-            )
-            .into());
+        // Only the message outlives the update, the error is not Send
+        .map_err(|e| e.to_string());
+
+        match &result {
+            Ok(outcome) => {
+                self.send_resource_state(
+                    ResourceType::Package,
+                    &package_name,
+                    "updating",
+                    "running",
+                    outcome,
+                    None,
+                )
+                .await
+            }
+            Err(e) => {
+                logd!(4, "Update of package '{}' failed: {}", package_name, e);
+                self.send_resource_state(
+                    ResourceType::Package,
+                    &package_name,
+                    "updating",
+                    "error",
+                    e,
+                    None,
+                )
+                .await
+            }
         }
+        result.map(|_| ()).map_err(Into::into)
+    }
 
-        let etcd_scenario_key: String = format!("scenario/{}", scenario_name);
-        let scenario_str = common::etcd::get(&etcd_scenario_key).await?;
-        let scenario: Scenario = serde_yaml::from_str(&scenario_str)?;
+    /// Update models one at a time, each one running before the next
+    async fn rolling_update(
+        &self,
+        package_name: &str,
+        models: &[(&ModelInfo, &str)],
+        gate: &HealthGate,
+        scenario_name: &str,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+    ) -> Result<()> {
+        for (index, (mi, node_type)) in models.iter().enumerate() {
+            let model_name = mi.get_name();
+            let step = update::rolling_step(index + 1, models.len(), &mi.get_pod_name());
+            self.notify_update_progress(package_name, &step).await;
+
+            self.execute_model_action(
+                "update",
+                mi,
+                node_type,
+                scenario_name,
+                network_str,
+                node_str,
+            )
+            .await
+            .map_err(|e| format!("{}: {}", step, e))?;
+            gate.wait(&model_name, || update::model_state(&model_name))
+                .await
+                .map_err(|e| format!("{}: {}", step, e))?;
+        }
+        Ok(())
+    }
+
+    /// Run copies of the new models, switch the models once all copies run
+    /// and retire the copies
+    ///
+    /// The copies are retired whether the update succeeds or not. If one of
+    /// them does not run, the models are not touched at all.
+    async fn blue_green_update(
+        &self,
+        package_name: &str,
+        models: &[(&ModelInfo, &str)],
+        gate: &HealthGate,
+        scenario_name: &str,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+    ) -> Result<()> {
+        let mut greens = Vec::new();
+        let result = async {
+            self.notify_update_progress(package_name, update::LAUNCHING_GREEN)
+                .await;
+            self.launch_copies(models, update::GREEN_SUFFIX, gate, &mut greens)
+                .await?;
+
+            self.notify_update_progress(package_name, update::SWITCHING)
+                .await;
+            self.switch_models(models, gate, scenario_name, network_str, node_str)
+                .await
+        }
+        .await
+        .map_err(|e| e.to_string());
+
+        self.notify_update_progress(package_name, update::RETIRING_GREEN)
+            .await;
+        self.retire_copies(&greens).await;
+        result.map_err(Into::into)
+    }
+
+    /// Run copies of the new models for a bake time, then promote them by
+    /// switching the models or roll them back
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(sub_state)` the outcome of the canary, promoted or rolled back
+    /// * `Err(...)` if the canary could not be evaluated or promoted
+    async fn canary_update(
+        &self,
+        package_name: &str,
+        models: &[(&ModelInfo, &str)],
+        strategy: &UpdateStrategy,
+        scenario_name: &str,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+    ) -> Result<String> {
+        let gate = &HealthGate::for_strategy(strategy);
+        let bake = CanaryBake::for_strategy(strategy);
+        let canaries: Vec<String> = models
+            .iter()
+            .map(|(mi, _)| update::copy_name(&mi.get_pod_name(), update::CANARY_SUFFIX))
+            .collect();
+        let mut launched = Vec::new();
+
+        self.notify_update_progress(package_name, update::LAUNCHING_CANARY)
+            .await;
+        let launch = self
+            .launch_copies(models, update::CANARY_SUFFIX, gate, &mut launched)
+            .await
+            .map_err(|e| e.to_string());
+        let verdict = match launch {
+            Ok(()) => {
+                self.notify_update_progress(package_name, &bake.sub_state())
+                    .await;
+                match launched
+                    .iter()
+                    .map(|(copy, node, _)| update::probe_target(copy, node))
+                    .collect::<Result<Vec<_>>>()
+                {
+                    Ok(targets) => bake
+                        .evaluate(
+                            &canaries,
+                            |name| async move { update::model_state(&name).await },
+                            || {
+                                update::run_probe(
+                                    &strategy.canary.probe,
+                                    &targets,
+                                    crate::runtime::nodeagent::exec_in_workload,
+                                )
+                            },
+                        )
+                        .await
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        let result = match verdict {
+            Ok(()) => {
+                self.notify_update_progress(package_name, update::PROMOTING)
+                    .await;
+                self.switch_models(models, gate, scenario_name, network_str, node_str)
+                    .await
+                    .map(|()| CANARY_PROMOTED.to_string())
+                    .map_err(|e| e.to_string())
+            }
+            Err(reason) => {
+                logd!(
+                    4,
+                    "Canary of package '{}' rolled back: {}",
+                    package_name,
+                    reason
+                );
+                self.notify_update_progress(package_name, update::ROLLING_BACK)
+                    .await;
+                Ok(format!("{}: {}", CANARY_ROLLED_BACK, reason))
+            }
+        };
+
+        self.retire_copies(&launched).await;
+        result.map_err(Into::into)
+    }
+
+    /// Launch a copy of every model under its name with `suffix` and wait
+    /// for all copies to run
+    ///
+    /// Every launched copy is added to `launched`, also when a later one
+    /// fails, so that the caller can retire them.
+    async fn launch_copies<'a>(
+        &self,
+        models: &[(&ModelInfo, &'a str)],
+        suffix: &str,
+        gate: &HealthGate,
+        launched: &mut Vec<(String, String, &'a str)>,
+    ) -> Result<()> {
+        for (mi, node_type) in models {
+            let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, mi.get_pod_name()))
+                .await
+                .map_err(common::etcd::error)?;
+            let copy = update::copy_pod(&pod, suffix)?;
+            self.start_workload(&copy, &mi.get_node(), node_type)
+                .await?;
+            launched.push((copy, mi.get_node(), *node_type));
+        }
+        for (mi, _) in models {
+            let copy_name = update::copy_name(&mi.get_pod_name(), suffix);
+            gate.wait(&copy_name, || update::model_state(&copy_name))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Stop the copies launched during an update, failures are only logged
+    async fn retire_copies(&self, launched: &[(String, String, &str)]) {
+        for (copy, node, node_type) in launched {
+            if let Err(e) = self.stop_workload(copy, node, node_type).await {
+                logd!(4, "Failed to retire model copy on node '{}': {}", node, e);
+            }
+        }
+    }
+
+    /// Update every model to its new version and wait for all of them to run
+    async fn switch_models(
+        &self,
+        models: &[(&ModelInfo, &str)],
+        gate: &HealthGate,
+        scenario_name: &str,
+        network_str: &Option<String>,
+        node_str: &Option<String>,
+    ) -> Result<()> {
+        for (mi, node_type) in models {
+            self.execute_model_action(
+                "update",
+                mi,
+                node_type,
+                scenario_name,
+                network_str,
+                node_str,
+            )
+            .await?;
+        }
+        for (mi, _) in models {
+            let model_name = mi.get_name();
+            gate.wait(&model_name, || update::model_state(&model_name))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Admit the models of a package on their nodes
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(previous))` if the models fit and their resources were
+    ///   reserved, with the reservations they replaced
+    /// * `Ok(None)` if the scenario was queued until resources are released
+    /// * `Err(...)` if a model can never fit its node
+    async fn admit(
+        &self,
+        scenario_name: &str,
+        transition_id: &str,
+        package: &Package,
+        from_state: &str,
+    ) -> Result<Option<Previous>> {
+        let demands = self.package_demands(package).await;
+        let capacities = self.node_capacities(package).await;
+        let free_memory = self.node_free_memory(package).await;
+        let cordoned = self.cordoned_nodes(package).await;
+        let misplaced = self.misplaced_model(package).await;
+
+        let mut admission = self.admission.lock().await;
+        let (rule, decision) = match (cordoned.first(), misplaced) {
+            (Some(node), _) => (
+                "node-cordoned",
+                Decision::Deny(format!("node '{}' is cordoned", node)),
+            ),
+            (None, Some(reason)) => ("node-placement", Decision::Deny(reason)),
+            (None, None) => match admission.check(&demands, &capacities) {
+                Decision::Admit => (
+                    "admission",
+                    admission.check_free_memory(&demands, &free_memory),
+                ),
+                decision => ("admission", decision),
+            },
+        };
+        match decision {
+            Decision::Admit => Ok(Some(admission.reserve(demands))),
+            Decision::Queue(reason) => {
+                logd!(
+                    3,
+                    "Scenario '{}' waits for capacity: {}",
+                    scenario_name,
+                    reason
+                );
+                admission.enqueue(scenario_name, transition_id);
+                drop(admission);
+                if from_state != "pending" {
+                    self.notify_state_change(scenario_name, from_state, "pending")
+                        .await;
+                }
+                Ok(None)
+            }
+            Decision::Deny(reason) => {
+                drop(admission);
+                logd!(
+                    4,
+                    "Scenario '{}' denied admission: {}",
+                    scenario_name,
+                    reason
+                );
+                self.notify_denial(scenario_name, from_state, rule, &reason)
+                    .await;
+                Err(Error::PreconditionFailed(format!(
+                    "Scenario '{}' denied admission: {}",
+                    scenario_name, reason
+                )))
+            }
+        }
+    }
+
+    /// Retry the scenarios waiting for capacity, in arrival order
+    async fn admit_queued(&self) {
+        let queued = self.admission.lock().await.take_queued();
+        for (scenario_name, transition_id) in queued {
+            let resources = self
+                .get_scenario_resources(&scenario_name)
+                .await
+                .map_err(|e| e.to_string());
+            let result = match resources {
+                Ok((scenario, package, network_str, node_str)) => self
+                    .run_scenario_action(
+                        &scenario_name,
+                        &transition_id,
+                        &scenario,
+                        &package,
+                        &network_str,
+                        &node_str,
+                        "pending",
+                    )
+                    .await
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                logd!(4, "Queued scenario '{}' failed: {}", scenario_name, e);
+            }
+        }
+    }
+
+    /// CPU and memory requested by the models of a package, once per replica
+    ///
+    /// Models whose spec cannot be read request nothing.
+    async fn package_demands(&self, package: &Package) -> Vec<Demand> {
+        let mut demands = Vec::new();
+        for mi in package.get_replicas() {
+            let model_name = mi.get_name();
+            let key = format!("{}/{}", ETCD_MODEL_PREFIX, model_name);
+            let request = match common::etcd::get(&key).await {
+                Ok(model_str) => match serde_yaml::from_str::<Model>(&model_str) {
+                    Ok(model) => {
+                        let podspec = model.get_podspec();
+                        Resources {
+                            cpu_millis: podspec.cpu_request_millis(),
+                            memory_mb: podspec.memory_request_mb(),
+                        }
+                    }
+                    Err(e) => {
+                        logd!(4, "Failed to parse model '{}': {}", model_name, e);
+                        Resources::default()
+                    }
+                },
+                Err(_) => Resources::default(),
+            };
+            demands.push(Demand {
+                model: mi.get_pod_name(),
+                node: mi.get_node(),
+                request,
+            });
+        }
+        demands
+    }
+
+    /// Nodes of a package that take no new models
+    async fn cordoned_nodes(&self, package: &Package) -> Vec<String> {
+        let mut cordoned = Vec::new();
+        for mi in package.get_replicas() {
+            let node = mi.get_node();
+            if !cordoned.contains(&node) && !self.is_schedulable(&node).await {
+                cordoned.push(node);
+            }
+        }
+        cordoned
+    }
+
+    /// Why a model of a package may not be placed on its node, for the first
+    /// one that may not
+    ///
+    /// The node has to carry the labels the `nodeSelector` of the model
+    /// selects and the model has to tolerate its `NoSchedule` taints. Nodes
+    /// that are not registered and models that cannot be read are not checked.
+    async fn misplaced_model(&self, package: &Package) -> Option<String> {
+        for mi in package.get_replicas() {
+            let node = mi.get_node();
+            let Some(info) = self.node_info(&node).await else {
+                continue;
+            };
+            let Some(podspec) = self.model_podspec(&mi.get_name()).await else {
+                continue;
+            };
+            if let Some(reason) = podspec.placement_error(&info.labels, &info.taints) {
+                return Some(format!(
+                    "model '{}' may not run on node '{}': {}",
+                    mi.get_pod_name(),
+                    node,
+                    reason
+                ));
+            }
+        }
+        None
+    }
+
+    /// Pull the images of models on their NodeAgent nodes ahead of the action
+    ///
+    /// The nodes pull concurrently. A node that fails to pull an image only
+    /// logs it, the launch of the model pulls it again or fails itself.
+    async fn prefetch_images(
+        &self,
+        model_infos: &[ModelInfo],
+        node_roles: &HashMap<String, String>,
+    ) {
+        let mut podspecs = Vec::new();
+        for mi in model_infos {
+            let node = mi.get_node();
+            if node_roles.get(&node).map(String::as_str) != Some(NODE_TYPE_NODEAGENT) {
+                continue;
+            }
+            if let Some(podspec) = self.model_podspec(&mi.get_name()).await {
+                podspecs.push((node, podspec));
+            }
+        }
+
+        let mut pulls = tokio::task::JoinSet::new();
+        for (node, images) in images_by_node(&podspecs) {
+            let correlation_id = common::correlation::current_or_empty();
+            pulls.spawn(common::correlation::scope(correlation_id, async move {
+                let result = crate::runtime::nodeagent::prefetch_images(images, &node)
+                    .await
+                    .map_err(|e| e.to_string());
+                (node, result)
+            }));
+        }
+        while let Some(Ok((node, result))) = pulls.join_next().await {
+            match result {
+                Ok(response) => {
+                    logd!(
+                        2,
+                        "Node '{}' pulled {} image(s), {} already cached",
+                        node,
+                        response.pulled.len(),
+                        response.cached.len()
+                    );
+                    for (image, reason) in &response.failed {
+                        logd!(
+                            4,
+                            "Node '{}' failed to pull image '{}': {}",
+                            node,
+                            image,
+                            reason
+                        );
+                    }
+                }
+                Err(e) => logd!(4, "Images not prefetched on node '{}': {}", node, e),
+            }
+        }
+    }
+
+    /// Pod spec of a stored model, `None` if it cannot be read
+    async fn model_podspec(&self, model_name: &str) -> Option<PodSpec> {
+        let key = format!("{}/{}", ETCD_MODEL_PREFIX, model_name);
+        let model_str = common::etcd::get(&key).await.ok()?;
+        serde_yaml::from_str::<Model>(&model_str)
+            .ok()
+            .map(|model| model.get_podspec())
+    }
+
+    /// Whether new models may be placed on a node
+    ///
+    /// Nodes cordoned through the apiserver are not schedulable, nodes that
+    /// are not registered are.
+    async fn is_schedulable(&self, node_name: &str) -> bool {
+        let key = format!("{}/{}", ETCD_CLUSTER_NODES_PREFIX, node_name);
+        common::etcd::get(&key)
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_str::<common::apiserver::NodeInfo>(&json).ok())
+            .is_none_or(|info| !info.unschedulable)
+    }
+
+    /// Node as registered through the apiserver, `None` if it is not
+    async fn node_info(&self, node_name: &str) -> Option<common::apiserver::NodeInfo> {
+        let key = format!("{}/{}", ETCD_CLUSTER_NODES_PREFIX, node_name);
+        common::etcd::get(&key)
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    /// Capacity the nodes of a package reported when registering
+    ///
+    /// Nodes that are not registered or reported no resources are left out.
+    async fn node_capacities(&self, package: &Package) -> HashMap<String, Resources> {
+        let mut capacities = HashMap::new();
+        for mi in package.get_replicas() {
+            let node = mi.get_node();
+            if capacities.contains_key(&node) {
+                continue;
+            }
+            let resources = self.node_info(&node).await.and_then(|info| info.resources);
+            if let Some(resources) = resources {
+                capacities.insert(
+                    node,
+                    Resources {
+                        cpu_millis: resources.cpu_cores.max(0) as u64 * 1000,
+                        memory_mb: resources.memory_mb.max(0) as u64,
+                    },
+                );
+            }
+        }
+        capacities
+    }
+
+    /// Free memory the nodes of a package reported last
+    ///
+    /// Nodes without a current report are left out.
+    async fn node_free_memory(&self, package: &Package) -> HashMap<String, u64> {
+        let now = crate::admission::now();
+        let mut free_memory = HashMap::new();
+        for mi in package.get_replicas() {
+            let node = mi.get_node();
+            if free_memory.contains_key(&node) {
+                continue;
+            }
+            let usage = self.node_info(&node).await.and_then(|info| info.usage);
+            if let Some(usage) = crate::admission::current(usage, now) {
+                free_memory.insert(node, usage.free_memory_mb.max(0) as u64);
+            }
+        }
+        free_memory
+    }
+
+    /// Reconciles current and desired states for a scenario
+    ///
+    /// Compares the current state with the desired state for a given scenario
+    /// and performs the necessary actions to align them: the models of its
+    /// package are started for a desired `Running` and stopped for `Done`.
+    ///
+    /// # Arguments
+    ///
+    /// * `scenario_name` - Name of the scenario
+    /// * `current` - Current state value
+    /// * `desired` - Desired state value
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the reconciliation was successful
+    /// * `Err(...)` if the reconciliation failed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The scenario does not exist
+    /// - Another reconciliation of the scenario is in progress
+    /// - The reconciliation action fails
+    pub async fn reconcile_do(
+        &self,
+        scenario_name: String,
+        current: Status,
+        desired: Status,
+    ) -> Result<()> {
+        if current == desired {
+            return Ok(());
+        }
+
+        if matches!(current, Status::None | Status::Failed | Status::Unknown) {
+            return Err(Error::InvalidStateTransition(format!(
+                "Invalid current status: {:?}. Cannot reconcile from this state",
+                current
+            )));
+        }
+
+        if matches!(desired, Status::None | Status::Failed | Status::Unknown) {
+            return Err(Error::InvalidStateTransition(format!(
+                "Invalid desired status: {:?}. Cannot set this as target state",
+                desired
+            )));
+        }
+
+        let etcd_scenario_key = format!("{}/{}", ETCD_SCENARIO_PREFIX, scenario_name);
+        let scenario_str = common::etcd::get(&etcd_scenario_key)
+            .await
+            .map_err(common::etcd::error)?;
+        let scenario: Scenario = serde_yaml::from_str(&scenario_str)?;
+
+        let etcd_package_key = format!("{}/{}", ETCD_PACKAGE_PREFIX, scenario.get_targets());
+        let package_str = common::etcd::get(&etcd_package_key)
+            .await
+            .map_err(common::etcd::error)?;
+        let package: Package = serde_yaml::from_str(&package_str)?;
+        let resources = crate::operation::resources(&scenario_name, &package.get_qualified_name());
+        let _operation = self
+            .operations
+            .acquire(&resources, &format!("reconcile {}", scenario_name))
+            .await?;
+
+        for mi in package.get_replicas() {
+            let model_name = format!("{}.service", mi.get_pod_name());
+            let model_node = mi.get_node();
+            let node_type = if self.nodeagent_nodes.contains(&model_node) {
+                "nodeagent"
+            } else {
+                // Log warning for unknown node types and skip processing
+                logd!(
+                    4,
+                    "Warning: Node '{}' is not explicitly configured. Skipping deployment.",
+                    model_node
+                );
+                continue;
+            };
+
+            match desired {
+                Status::Running => {
+                    self.start_workload(&model_name, &model_node, node_type)
+                        .await?
+                }
+                Status::Done => {
+                    self.stop_workload(&model_name, &model_node, node_type)
+                        .await?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restarts the failing models of the package of a failed scenario
+    ///
+    /// Only the replicas of `failing_models` are restarted, the other models
+    /// of the package keep running. A failing model with a standby fails
+    /// over to it instead, see [`Self::promote_standby`]. Without failing
+    /// models, every model of the package is started.
+    ///
+    /// # Arguments
+    ///
+    /// * `scenario_name` - Name of the scenario
+    /// * `package_name` - Target package of the scenario
+    /// * `failing_models` - Models of the package to restart
+    /// * `reason` - Why the reconcile is requested
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The scenario or package does not exist
+    /// - The package is not the target of the scenario
+    /// - Another operation on the scenario or package is in progress
+    /// - A model fails to restart
+    pub async fn reconcile_package(
+        &self,
+        scenario_name: &str,
+        package_name: &str,
+        failing_models: &[String],
+        reason: &str,
+    ) -> Result<()> {
+        logd!(
+            3,
+            "Reconciling package '{}' of scenario '{}': {}",
+            package_name,
+            scenario_name,
+            reason
+        );
+        let scenario_key = format!("{}/{}", ETCD_SCENARIO_PREFIX, scenario_name);
+        let scenario: Scenario = serde_yaml::from_str(
+            &common::etcd::get(&scenario_key)
+                .await
+                .map_err(common::etcd::error)?,
+        )?;
+        let package_key = format!("{}/{}", ETCD_PACKAGE_PREFIX, scenario.get_targets());
+        let package: Package = serde_yaml::from_str(
+            &common::etcd::get(&package_key)
+                .await
+                .map_err(common::etcd::error)?,
+        )?;
+        if package.get_qualified_name() != package_name && scenario.get_targets() != package_name {
+            return Err(Error::InvalidRequest(format!(
+                "Package '{}' is not the target of scenario '{}'",
+                package_name, scenario_name
+            )));
+        }
+        let resources = crate::operation::resources(scenario_name, &package.get_qualified_name());
+        let _operation = self
+            .operations
+            .acquire(&resources, &format!("reconcile {}", scenario_name))
+            .await?;
+
+        for mi in reconcile_replicas(&package, failing_models) {
+            let model_name = format!("{}.service", mi.get_pod_name());
+            let model_node = mi.get_node();
+            if !self.nodeagent_nodes.contains(&model_node) {
+                logd!(
+                    4,
+                    "Warning: Node '{}' is not explicitly configured. Skipping deployment.",
+                    model_node
+                );
+                continue;
+            }
+            if failing_models.is_empty() {
+                self.start_workload(&model_name, &model_node, "nodeagent")
+                    .await?
+            } else if mi.get_standby_node().is_none() {
+                self.restart_workload(&model_name, &model_node, "nodeagent")
+                    .await?
+            } else if let Err(e) = self.promote_standby(&package_key, &mi).await {
+                logd!(
+                    4,
+                    "Standby of model '{}' not promoted, restarting the model: {}",
+                    mi.get_name(),
+                    e
+                );
+                self.restart_workload(&model_name, &model_node, "nodeagent")
+                    .await?
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the standby Pod `pod_name` on `node` and pauses it there
+    ///
+    /// The model keeps running without a standby that cannot be launched.
+    async fn launch_standby(&self, pod_name: &str, node: &str) {
+        let launched = async {
+            let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, pod_name)).await?;
+            let node_type = self.get_node_role_from_etcd(node).await?;
+            self.start_workload(&pod, node, &node_type).await?;
+            self.execute_workload_operation("pause", &pod, node, &node_type)
+                .await
+        };
+        match launched.await {
+            Ok(()) => logd!(3, "Standby '{}' waiting paused on '{}'", pod_name, node),
+            Err(e) => logd!(
+                4,
+                "Standby '{}' not launched on '{}': {}",
+                pod_name,
+                node,
+                e
+            ),
+        }
+    }
+
+    /// Promotes the paused standby of a failed model to run the model
+    ///
+    /// The standby is resumed before the failed primary is stopped. The two
+    /// then swap places: the package places the model on the node of the
+    /// standby and the standby on the old node, the Pod records are renamed
+    /// and relabelled to match, the autostart record follows, and the old
+    /// node gets a new paused standby.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model has no standby or it cannot be resumed,
+    /// the primary is left as it is then.
+    async fn promote_standby(&self, package_key: &str, mi: &ModelInfo) -> Result<()> {
+        let model_name = mi.get_name();
+        let Some(standby_node) = mi.get_standby_node() else {
+            return Err(Error::InvalidRequest(format!(
+                "Model '{}' has no standby",
+                model_name
+            )));
+        };
+        let node = mi.get_node();
+        let primary_key = format!("{}/{}", ETCD_POD_PREFIX, mi.get_pod_name());
+        let standby_key = format!("{}/{}", ETCD_POD_PREFIX, mi.get_standby_pod_name());
+        let primary = common::etcd::get(&primary_key)
+            .await
+            .map_err(common::etcd::error)?;
+        let standby = common::etcd::get(&standby_key)
+            .await
+            .map_err(common::etcd::error)?;
+        let node_type = self.get_node_role_from_etcd(&standby_node).await?;
+        self.execute_workload_operation("unpause", &standby, &standby_node, &node_type)
+            .await?;
+        logd!(
+            3,
+            "Standby of model '{}' promoted on '{}'",
+            model_name,
+            standby_node
+        );
+
+        // What is left of the failed primary is removed
+        if let Err(e) = self.stop_workload(&primary, &node, "nodeagent").await {
+            logd!(
+                4,
+                "Failed model '{}' not stopped on '{}': {}",
+                model_name,
+                node,
+                e
+            );
+        }
+
+        let package_str = common::etcd::get(package_key)
+            .await
+            .map_err(common::etcd::error)?;
+        let mut document: serde_yaml::Value = serde_yaml::from_str(&package_str)?;
+        let (_, name) = common::namespace::split(&model_name);
+        for model in document["spec"]["models"]
+            .as_sequence_mut()
+            .into_iter()
+            .flatten()
+        {
+            if model["name"].as_str() == Some(name) {
+                swap_standby(model);
+            }
+        }
+        common::etcd::put(package_key, &serde_yaml::to_string(&document)?)
+            .await
+            .map_err(common::etcd::error)?;
+        // The standby takes the name of the model and the failed primary
+        // comes back as the standby, each named and labelled as such
+        let mut promoted: Pod = serde_yaml::from_str(&standby)?;
+        promoted.set_primary();
+        common::etcd::put(&primary_key, &serde_yaml::to_string(&promoted)?)
+            .await
+            .map_err(common::etcd::error)?;
+        let mut demoted: Pod = serde_yaml::from_str(&primary)?;
+        demoted.set_standby();
+        common::etcd::put(&standby_key, &serde_yaml::to_string(&demoted)?)
+            .await
+            .map_err(common::etcd::error)?;
+        if let Err(e) = crate::autostart::moved(&node, &standby_node, &mi.get_pod_name()).await {
+            logd!(
+                4,
+                "Autostart record of model '{}' not moved: {}",
+                model_name,
+                e
+            );
+        }
+
+        self.launch_standby(&mi.get_standby_pod_name(), &node).await;
+        Ok(())
+    }
+
+    /// Moves every model placed on a failed node onto healthy nodes
+    ///
+    /// Walks all stored packages, starts each affected model on one of the
+    /// healthy nodes (round-robin) and rewrites the package so that later
+    /// actions target the new node. Cordoned nodes take no models.
+    ///
+    /// # Arguments
+    ///
+    /// * `failed_node` - Hostname of the node that stopped responding
+    /// * `healthy_nodes` - Hostnames of nodes that may take over its models
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<String>)` with the names of the relocated models
+    /// * `Err(...)` if no healthy node is available or etcd access fails
+    pub async fn relocate_node_models(
+        &self,
+        failed_node: &str,
+        healthy_nodes: &[String],
+    ) -> Result<Vec<String>> {
+        let (relocated, _) = self
+            .move_node_models(failed_node, healthy_nodes, false)
+            .await?;
+        Ok(relocated)
+    }
+
+    /// Re-places every model of a cordoned node before its maintenance
+    ///
+    /// Each model is started on one of the target nodes before it is
+    /// stopped on the drained node, so that it keeps running during the
+    /// move. The StateManager is told when the drain starts and whether it
+    /// left the node without models (drained) or not (cordoned).
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - Hostname of the cordoned node
+    /// * `target_nodes` - Hostnames of nodes that may take over its models
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((relocated, failed))` with the names of the moved models and of
+    ///   the models left on the node
+    /// * `Err(...)` if no target node is available or etcd access fails
+    pub async fn drain_node(
+        &self,
+        node: &str,
+        target_nodes: &[String],
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        self.notify_resource_state(ResourceType::Node, node, "cordoned", "draining")
+            .await;
+        // The error is not Send, keep its message across the notification
+        let result = self
+            .move_node_models(node, target_nodes, true)
+            .await
+            .map_err(|e| e.to_string());
+        let drained = matches!(&result, Ok((_, failed)) if failed.is_empty());
+        let target = if drained { "drained" } else { "cordoned" };
+        self.notify_resource_state(ResourceType::Node, node, "draining", target)
+            .await;
+        Ok(result?)
+    }
+
+    /// Moves the models placed on `node` onto `candidates`, round-robin
+    ///
+    /// The replicas of a model placed on `node` move together. With
+    /// `graceful`, a model is stopped on `node` once it was started on its
+    /// new node. A failed node is not asked to stop anything.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((moved, failed))` with the names of the models moved and of the
+    ///   models that could not be
+    async fn move_node_models(
+        &self,
+        node: &str,
+        candidates: &[String],
+        graceful: bool,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let now = crate::admission::now();
+        let mut schedulable = Vec::new();
+        let mut registered = HashMap::new();
+        for candidate in candidates {
+            if candidate != node && self.is_schedulable(candidate).await {
+                let info = self.node_info(candidate).await;
+                let usage = info.as_ref().and_then(|info| info.usage);
+                if let Some(info) = info {
+                    registered.insert(candidate.clone(), info);
+                }
+                schedulable.push((candidate.clone(), crate::admission::current(usage, now)));
+            }
+        }
+        // The least loaded nodes take the first models
+        let schedulable = crate::admission::by_load(schedulable);
+        if schedulable.is_empty() {
+            return Err(Error::Unavailable(format!(
+                "No healthy node available to relocate '{}'",
+                node
+            )));
+        }
+
+        let packages = common::etcd::get_all_with_prefix(&format!("{}/", ETCD_PACKAGE_PREFIX))
+            .await
+            .map_err(common::etcd::error)?;
+        let mut moved = Vec::new();
+        let mut failed = Vec::new();
+
+        for (key, package_str) in packages {
+            let mut package: serde_yaml::Value = match serde_yaml::from_str(&package_str) {
+                Ok(value) => value,
+                Err(e) => {
+                    logd!(4, "Warning: Failed to parse package '{}': {}", key, e);
+                    continue;
+                }
+            };
+            let Some(models) = package["spec"]["models"].as_sequence_mut() else {
+                continue;
+            };
+
+            let mut changed = false;
+            for model in models.iter_mut() {
+                let Ok(info) = serde_yaml::from_value::<ModelInfo>(model.clone()) else {
+                    continue;
+                };
+                let pods: Vec<String> = info
+                    .get_replicas()
+                    .iter()
+                    .filter(|replica| replica.get_node() == node)
+                    .map(ModelInfo::get_pod_name)
+                    .collect();
+                if pods.is_empty() {
+                    continue;
+                }
+                let model_name = info.get_name();
+
+                let podspec = self.model_podspec(&model_name).await;
+                let first = moved.len() + failed.len();
+                let Some(target) =
+                    relocation_target(&schedulable, first, podspec.as_ref(), &registered)
+                else {
+                    logd!(
+                        5,
+                        "No node may take over model '{}' from '{}'",
+                        model_name,
+                        node
+                    );
+                    failed.push(model_name);
+                    continue;
+                };
+                let mut started = Ok(());
+                for pod in &pods {
+                    started = self.start_model_on_node(pod, target).await;
+                    if started.is_err() {
+                        break;
+                    }
+                }
+                if let Err(e) = started {
+                    logd!(
+                        5,
+                        "Failed to relocate model '{}' to node '{}': {}",
+                        model_name,
+                        target,
+                        e
+                    );
+                    failed.push(model_name);
+                    continue;
+                }
+                for pod in &pods {
+                    if graceful {
+                        if let Err(e) = self.stop_model_on_node(pod, node).await {
+                            // The new copy runs, the old one is left to the maintenance
+                            logd!(
+                                4,
+                                "Failed to stop model '{}' on drained node '{}': {}",
+                                pod,
+                                node,
+                                e
+                            );
+                        }
+                    }
+                    if let Err(e) = crate::autostart::moved(node, target, pod).await {
+                        logd!(4, "Autostart record of model '{}' not moved: {}", pod, e);
+                    }
+                }
+
+                logd!(
+                    3,
+                    "Relocated model '{}' from '{}' to '{}'",
+                    model_name,
+                    node,
+                    target
+                );
+                place_on(model, node, target);
+                moved.push(model_name);
+                changed = true;
+            }
+
+            if changed {
+                common::etcd::put(&key, &serde_yaml::to_string(&package)?)
+                    .await
+                    .map_err(common::etcd::error)?;
+            }
+        }
+
+        Ok((moved, failed))
+    }
+
+    /// Spreads the models of all packages more evenly over `nodes`
+    ///
+    /// Plans the moves with [`crate::rebalance::plan`] from the load the
+    /// schedulable nodes reported, each model only to nodes whose labels and
+    /// taints it accepts. Unless `dry_run`, the moves run one at a time, each
+    /// holding its package like any other operation on it: the model is
+    /// started on its new node, has to pass the health gate of the update
+    /// strategy of its package there, and is stopped on its old node. The
+    /// package is reported as updating during the move and rewritten to the
+    /// new node. A model that does not run on its new node is stopped there
+    /// and stays on its old one.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - Hostnames of nodes to spread the models over
+    /// * `dry_run` - Only plan the moves
+    /// * `max_moves` - Moves planned at most, unlimited when 0
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((moves, failed))` with the moves planned, or done unless a dry
+    ///   run, and the moves that failed
+    /// * `Err(...)` if no node is schedulable or etcd access fails
+    pub async fn rebalance(
+        &self,
+        nodes: &[String],
+        dry_run: bool,
+        max_moves: usize,
+    ) -> Result<(Vec<ModelMove>, Vec<ModelMove>)> {
+        let now = crate::admission::now();
+        let mut schedulable = Vec::new();
+        let mut registered = HashMap::new();
+        let mut cpu_loads = HashMap::new();
+        for node in nodes {
+            if schedulable.contains(node) || !self.is_schedulable(node).await {
+                continue;
+            }
+            if let Some(info) = self.node_info(node).await {
+                if let Some(usage) = crate::admission::current(info.usage, now) {
+                    cpu_loads.insert(node.clone(), usage.cpu_load);
+                }
+                registered.insert(node.clone(), info);
+            }
+            schedulable.push(node.clone());
+        }
+        if schedulable.is_empty() {
+            return Err(Error::Unavailable(
+                "No schedulable node to rebalance the models over".to_string(),
+            ));
+        }
+
+        let packages = common::etcd::get_all_with_prefix(&format!("{}/", ETCD_PACKAGE_PREFIX))
+            .await
+            .map_err(common::etcd::error)?;
+        let mut package_keys = HashMap::new();
+        let mut placements = Vec::new();
+        let mut podspecs = HashMap::new();
+        for (key, package_str) in packages {
+            let Ok(package) = serde_yaml::from_str::<Package>(&package_str) else {
+                logd!(4, "Warning: Failed to parse package '{}'", key);
+                continue;
+            };
+            let package_name = package.get_qualified_name();
+            let document: serde_yaml::Value = serde_yaml::from_str(&package_str)?;
+            for model in document["spec"]["models"]
+                .as_sequence()
+                .into_iter()
+                .flatten()
+            {
+                let Ok(info) = serde_yaml::from_value::<ModelInfo>(model.clone()) else {
+                    continue;
+                };
+                let mut by_node: Vec<crate::rebalance::Placement> = Vec::new();
+                for replica in info.get_replicas() {
+                    let node = replica.get_node();
+                    match by_node.iter_mut().find(|placement| placement.node == node) {
+                        Some(placement) => placement.pods.push(replica.get_pod_name()),
+                        None => by_node.push(crate::rebalance::Placement {
+                            package: package_name.clone(),
+                            model: info.get_name(),
+                            node,
+                            pods: vec![replica.get_pod_name()],
+                        }),
+                    }
+                }
+                podspecs.insert(info.get_name(), self.model_podspec(&info.get_name()).await);
+                placements.extend(by_node);
+            }
+            package_keys.insert(package_name, key);
+        }
+
+        let allowed = |placement: &crate::rebalance::Placement, node: &str| {
+            let (Some(Some(podspec)), Some(info)) =
+                (podspecs.get(&placement.model), registered.get(node))
+            else {
+                return true;
+            };
+            podspec
+                .placement_error(&info.labels, &info.taints)
+                .is_none()
+                && !podspec.avoids(&info.taints)
+        };
+        let moves =
+            crate::rebalance::plan(&placements, &schedulable, &cpu_loads, max_moves, allowed);
+        logd!(
+            3,
+            "Rebalance of {} model(s) over {} node(s) plans {} move(s){}",
+            placements.len(),
+            schedulable.len(),
+            moves.len(),
+            if dry_run { " (dry run)" } else { "" }
+        );
+        if dry_run {
+            return Ok((moves, Vec::new()));
+        }
+
+        let mut failed = Vec::new();
+        for model_move in &moves {
+            let Some(key) = package_keys.get(&model_move.package) else {
+                continue;
+            };
+            let pods: Vec<String> = placements
+                .iter()
+                .find(|p| {
+                    p.package == model_move.package
+                        && p.model == model_move.model
+                        && p.node == model_move.from_node
+                })
+                .map(|p| p.pods.clone())
+                .unwrap_or_default();
+            // The error is not Send, keep its message only
+            let result = self
+                .move_model(key, model_move, &pods)
+                .await
+                .map_err(|e| e.to_string());
+            if let Err(e) = result {
+                logd!(
+                    5,
+                    "Failed to move model '{}' from '{}' to '{}': {}",
+                    model_move.model,
+                    model_move.from_node,
+                    model_move.to_node,
+                    e
+                );
+                failed.push(model_move.clone());
+            }
+        }
+        Ok((moves, failed))
+    }
 
-        let etcd_package_key = format!("package/{}", scenario.get_targets());
-        let package_str = common::etcd::get(&etcd_package_key).await?;
+    /// Moves the `pods` of a model of the package stored at `key` as planned
+    /// by a rebalance
+    async fn move_model(&self, key: &str, model_move: &ModelMove, pods: &[String]) -> Result<()> {
+        let package_name = &model_move.package;
+        let _operation = self
+            .operations
+            .acquire(
+                &[crate::operation::package_resource(package_name)],
+                &format!("rebalance {}", model_move.model),
+            )
+            .await?;
+        let (from, to) = (&model_move.from_node, &model_move.to_node);
+        let package_str = common::etcd::get(key).await.map_err(common::etcd::error)?;
         let package: Package = serde_yaml::from_str(&package_str)?;
+        let gate = HealthGate::for_strategy(package.get_strategy());
 
-        for mi in package.get_models() {
-            let model_name = format!("{}.service", mi.get_name());
-            let model_node = mi.get_node();
-            let node_type = if self.nodeagent_nodes.contains(&model_node) {
-                "nodeagent"
-            } else {
-                // Log warning for unknown node types and skip processing
+        let current = common::etcd::get(&format!("/package/{}/state", package_name))
+            .await
+            .unwrap_or_else(|_| "idle".to_string());
+        let step = format!("rebalance: moving '{}' to {}", model_move.model, to);
+        self.send_resource_state(
+            ResourceType::Package,
+            package_name,
+            &current,
+            "updating",
+            &step,
+            None,
+        )
+        .await;
+
+        let mut started = Vec::new();
+        let mut result = Ok(());
+        for pod in pods {
+            result = self.start_model_on_node(pod, to).await;
+            if result.is_err() {
+                break;
+            }
+            started.push(pod);
+            // The model keeps running on `from` meanwhile, only the new
+            // instance tells whether the move worked
+            result = gate.wait(pod, || update::instance_state(pod, to)).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            for pod in pods {
+                if let Err(e) = self.stop_model_on_node(pod, from).await {
+                    logd!(
+                        4,
+                        "Failed to stop moved model '{}' on '{}': {}",
+                        pod,
+                        from,
+                        e
+                    );
+                }
+                if let Err(e) = crate::autostart::moved(from, to, pod).await {
+                    logd!(4, "Autostart record of model '{}' not moved: {}", pod, e);
+                }
+            }
+            let mut document: serde_yaml::Value = serde_yaml::from_str(&package_str)?;
+            for model in document["spec"]["models"]
+                .as_sequence_mut()
+                .into_iter()
+                .flatten()
+            {
+                if model["name"].as_str() == Some(model_move.model.as_str()) {
+                    place_on(model, from, to);
+                }
+            }
+            result = common::etcd::put(key, &serde_yaml::to_string(&document)?)
+                .await
+                .map_err(Into::into);
+        } else {
+            for pod in started {
+                if let Err(e) = self.stop_model_on_node(pod, to).await {
+                    logd!(4, "Failed to stop model '{}' on '{}': {}", pod, to, e);
+                }
+            }
+        }
+
+        // The models keep running either way, on the new or on the old node
+        let outcome = match &result {
+            Ok(()) => format!("rebalance: moved '{}' to {}", model_move.model, to),
+            Err(e) => format!("rebalance: '{}' stays on {}: {}", model_move.model, from, e),
+        };
+        self.send_resource_state(
+            ResourceType::Package,
+            package_name,
+            "updating",
+            &current,
+            &outcome,
+            None,
+        )
+        .await;
+        result
+    }
+
+    /// Starts the models recorded as running on a node that registered again
+    ///
+    /// After a reboot the containers of a node are gone. Every model recorded
+    /// for the node is started again, and the package of each scenario moves
+    /// to running, or to error when one of its models could not be started.
+    /// A model whose containers still exist on the node, e.g. after only its
+    /// NodeAgent restarted, is left as it is and counted as started. Records
+    /// of scenarios that no longer exist are dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - Hostname of the node
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((started, failed))` with the names of the started models and of
+    ///   the models that could not be started
+    /// * `Err(...)` if the records or the role of the node cannot be read
+    pub async fn autostart_node(&self, node: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let records = crate::autostart::for_node(node).await?;
+        let (mut started, mut failed) = (Vec::new(), Vec::new());
+        if records.is_empty() {
+            return Ok((started, failed));
+        }
+        let node_type = self.get_node_role_from_etcd(node).await?;
+
+        let mut by_scenario: Vec<(String, Vec<String>)> = Vec::new();
+        for (model, record) in records {
+            match by_scenario.iter_mut().find(|(s, _)| *s == record.scenario) {
+                Some((_, models)) => models.push(model),
+                None => by_scenario.push((record.scenario, vec![model])),
+            }
+        }
+
+        for (scenario_name, models) in by_scenario {
+            let scenario =
+                common::etcd::get(&format!("{}/{}", ETCD_SCENARIO_PREFIX, scenario_name))
+                    .await
+                    .ok()
+                    .and_then(|yaml| serde_yaml::from_str::<Scenario>(&yaml).ok());
+            let Some(scenario) = scenario else {
                 logd!(
-                    4,
-                    "Warning: Node '{}' is not explicitly configured. Skipping deployment.",
-                    model_node
+                    3,
+                    "Dropping autostart of {:?}: scenario '{}' is gone",
+                    models,
+                    scenario_name
                 );
+                for model in &models {
+                    let _ = crate::autostart::forget(node, model).await;
+                }
                 continue;
             };
 
-            if desired == Status::Running {
-                self.start_workload(&model_name, &model_node, node_type)
-                    .await?;
+            let mut errors = Vec::new();
+            for model in models {
+                if let Some(state) = crate::update::instance_state(&model, node).await {
+                    logd!(
+                        2,
+                        "Autostart of model '{}' on '{}' skipped, its containers exist ({})",
+                        model,
+                        node,
+                        state.as_str_name()
+                    );
+                    started.push(model);
+                    continue;
+                }
+                let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, model)).await;
+                // The error is not Send, keep its message only
+                let result = match pod {
+                    Ok(pod) => self
+                        .start_workload(&pod, node, &node_type)
+                        .await
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(()) => started.push(model),
+                    Err(e) => {
+                        logd!(
+                            4,
+                            "Autostart of model '{}' on '{}' failed: {}",
+                            model,
+                            node,
+                            e
+                        );
+                        errors.push(format!("{}: {}", model, e));
+                        failed.push(model);
+                    }
+                }
+            }
+
+            let package_name = scenario.get_targets();
+            let current = common::etcd::get(&format!("/package/{}/state", package_name))
+                .await
+                .unwrap_or_else(|_| "idle".to_string());
+            let (target, sub_state) = if errors.is_empty() {
+                ("running", format!("autostarted on {}", node))
+            } else {
+                (
+                    "error",
+                    format!("autostart on {} failed: {}", node, errors.join(", ")),
+                )
+            };
+            if current != target || !errors.is_empty() {
+                self.send_resource_state(
+                    ResourceType::Package,
+                    &package_name,
+                    &current,
+                    target,
+                    &sub_state,
+                    None,
+                )
+                .await;
             }
         }
 
-        Ok(())
+        logd!(
+            3,
+            "Autostart of node '{}': started {:?}, failed {:?}",
+            node,
+            started,
+            failed
+        );
+        Ok((started, failed))
+    }
+
+    /// Start the stored pod of a model on the given node
+    async fn start_model_on_node(&self, model_name: &str, node_name: &str) -> Result<()> {
+        let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, model_name))
+            .await
+            .map_err(common::etcd::error)?;
+        let node_type = self.get_node_role_from_etcd(node_name).await?;
+        self.start_workload(&pod, node_name, &node_type).await
+    }
+
+    /// Stop the stored pod of a model on the given node
+    async fn stop_model_on_node(&self, model_name: &str, node_name: &str) -> Result<()> {
+        let pod = common::etcd::get(&format!("{}/{}", ETCD_POD_PREFIX, model_name))
+            .await
+            .map_err(common::etcd::error)?;
+        let node_type = self.get_node_role_from_etcd(node_name).await?;
+        self.stop_workload(&pod, node_name, &node_type).await
     }
 
     /// Creates a new workload for the specified scenario
@@ -642,14 +2695,232 @@ impl ActionControllerManager {
 
 //UNIT TEST SKELTON
 
+/// Models the last apply of a package changed, once
+///
+/// The diff is removed when read, so that only the action following the
+/// apply is limited to it.
+async fn take_package_diff(package_name: &str) -> Option<PackageDiff> {
+    let key = PackageDiff::key(package_name);
+    let diff = common::etcd::get(&key).await.ok()?;
+    if let Err(e) = common::etcd::delete(&key).await {
+        logd!(4, "Diff of package '{}' not removed: {}", package_name, e);
+    }
+    match serde_json::from_str(&diff) {
+        Ok(diff) => Some(diff),
+        Err(e) => {
+            logd!(4, "Invalid diff of package '{}': {}", package_name, e);
+            None
+        }
+    }
+}
+
+/// Refuse a scenario that is not permitted in the given vehicle mode
+///
+/// An unspecified mode never satisfies a mode restricted scenario.
+fn check_vehicle_mode(scenario: &Scenario, mode: VehicleMode) -> Result<()> {
+    if scenario.is_allowed_in_mode(mode) {
+        return Ok(());
+    }
+    Err(Error::PreconditionFailed(format!(
+        "Scenario '{}' is not permitted in vehicle mode '{}' (allowed: {})",
+        scenario.get_qualified_name(),
+        mode.name(),
+        scenario.get_allowed_modes().join(", ")
+    )))
+}
+
+/// Node a relocated model moves to, `None` if none may take it
+///
+/// The nodes are tried round-robin from the `first`, those the model avoids
+/// for a `PreferNoSchedule` taint after the others. Registered nodes have
+/// to match the placement constraints of the model, unregistered ones and
+/// models without a readable spec are not checked.
+fn relocation_target<'a>(
+    schedulable: &'a [String],
+    first: usize,
+    podspec: Option<&PodSpec>,
+    registered: &HashMap<String, common::apiserver::NodeInfo>,
+) -> Option<&'a String> {
+    let mut rotation =
+        (0..schedulable.len()).map(|i| &schedulable[(first + i) % schedulable.len()]);
+    let Some(podspec) = podspec else {
+        return rotation.next();
+    };
+    let (avoided, preferred): (Vec<&String>, Vec<&String>) = rotation
+        .filter(|node| {
+            registered.get(*node).is_none_or(|info| {
+                podspec
+                    .placement_error(&info.labels, &info.taints)
+                    .is_none()
+            })
+        })
+        .partition(|node| {
+            registered
+                .get(*node)
+                .is_some_and(|info| podspec.avoids(&info.taints))
+        });
+    preferred.into_iter().chain(avoided).next()
+}
+
+/// Images of the pod specs by the node they run on, each image once per node
+fn images_by_node(podspecs: &[(String, PodSpec)]) -> HashMap<String, Vec<String>> {
+    let mut images: HashMap<String, Vec<String>> = HashMap::new();
+    for (node, podspec) in podspecs {
+        let node_images = images.entry(node.clone()).or_default();
+        for image in podspec.get_images() {
+            if !node_images.iter().any(|known| known == image) {
+                node_images.push(image.to_string());
+            }
+        }
+    }
+    images
+}
+
+/// Place every replica of a model of a package document on `node` on
+/// `target` instead
+fn place_on(model: &mut serde_yaml::Value, node: &str, target: &str) {
+    let target_value = serde_yaml::Value::String(target.to_string());
+    if model["node"].as_str() == Some(node) {
+        model["node"] = target_value.clone();
+    }
+    if let Some(nodes) = model["nodes"].as_sequence_mut() {
+        for placed in nodes.iter_mut() {
+            if placed.as_str() == Some(node) {
+                *placed = target_value.clone();
+            }
+        }
+    }
+}
+
+/// Swap the node and the standby node of a model of a package document
+fn swap_standby(model: &mut serde_yaml::Value) {
+    let node = model["node"].clone();
+    model["node"] = model["standby"].clone();
+    model["standby"] = node;
+}
+
+/// Replicas of a package a package reconcile acts on, those of the
+/// `failing_models` or all of them when none is given
+fn reconcile_replicas(package: &Package, failing_models: &[String]) -> Vec<ModelInfo> {
+    package
+        .get_replicas()
+        .into_iter()
+        .filter(|mi| failing_models.is_empty() || failing_models.contains(&mi.get_name()))
+        .collect()
+}
+
+/// Result of the action queued by `transition_id`, failed with `error` if given
+fn action_result(
+    scenario_name: &str,
+    transition_id: &str,
+    action: &str,
+    error: Option<String>,
+    rejected: bool,
+) -> ActionResult {
+    ActionResult {
+        transition_id: transition_id.to_string(),
+        resource_type: ResourceType::Scenario as i32,
+        resource_name: scenario_name.to_string(),
+        action: action.to_string(),
+        success: error.is_none(),
+        message: error.unwrap_or_default(),
+        timestamp_ns: common::clock::now_ns(),
+        rejected,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::manager::Status;
+    use common::nodeagent::fromactioncontroller::{
+        HandleWorkloadRequest, HandleWorkloadResponse, PrefetchImagesRequest,
+        PrefetchImagesResponse, WorkloadCommand,
+    };
+    use common::nodeagent::fromapiserver::{
+        ConfigRequest, ConfigResponse, ContainerExecRequest, ContainerExecResponse,
+        ContainerLogChunk, ContainerLogsRequest, HandleYamlRequest, HandleYamlResponse,
+        HeartbeatRequest, HeartbeatResponse, ListImagesRequest, ListImagesResponse,
+        NodeRegistrationRequest, NodeRegistrationResponse, PruneImagesRequest, PruneImagesResponse,
+        ReloadConfigRequest, ReloadConfigResponse, StatusAck, StatusReport,
+    };
+    use common::nodeagent::node_agent_connection_server::{
+        NodeAgentConnection, NodeAgentConnectionServer,
+    };
+    use common::spec::k8s::pod::{LABEL_MODEL, LABEL_STANDBY};
+    use common::version::{ApiVersionRequest, ApiVersionResponse};
     use std::error::Error;
+    use tonic::{Request, Response};
+
+    #[test]
+    fn test_images_by_node() {
+        let podspec = |images: &[&str]| -> PodSpec {
+            let containers: Vec<String> = images
+                .iter()
+                .enumerate()
+                .map(|(i, image)| format!("  - name: c{}\n    image: {}\n", i, image))
+                .collect();
+            serde_yaml::from_str(&format!("containers:\n{}", containers.concat())).unwrap()
+        };
+        let podspecs = vec![
+            ("node-a".to_string(), podspec(&["app:1", "sidecar:2"])),
+            ("node-a".to_string(), podspec(&["sidecar:2", "db:3"])),
+            ("node-b".to_string(), podspec(&["app:1"])),
+        ];
+        let images = images_by_node(&podspecs);
+        assert_eq!(images["node-a"], vec!["app:1", "sidecar:2", "db:3"]);
+        assert_eq!(images["node-b"], vec!["app:1"]);
+    }
+
+    #[test]
+    fn test_check_vehicle_mode() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: ota-update
+spec:
+  action: update
+  target: ota
+  allowedModes:
+    - parked
+    - charging
+"#,
+        )
+        .unwrap();
+
+        assert!(check_vehicle_mode(&scenario, VehicleMode::Parked).is_ok());
+        assert!(check_vehicle_mode(&scenario, VehicleMode::Charging).is_ok());
+        for mode in [VehicleMode::Driving, VehicleMode::Unspecified] {
+            let err = check_vehicle_mode(&scenario, mode).unwrap_err().to_string();
+            assert!(err.contains("not permitted in vehicle mode"));
+            assert!(err.contains("parked, charging"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_package_diff_is_read_once() {
+        common::etcd::use_in_memory_store();
+        let diff = PackageDiff {
+            package: "diffed".to_string(),
+            changed: vec!["diffed-core".to_string()],
+            ..Default::default()
+        };
+        common::etcd::put(
+            &PackageDiff::key("diffed"),
+            &serde_json::to_string(&diff).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(take_package_diff("diffed").await, Some(diff));
+        assert_eq!(take_package_diff("diffed").await, None);
+    }
 
     #[tokio::test]
     async fn test_get_node_role_from_etcd_invalid_json() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert nodes/{name} and invalid JSON in cluster/nodes/{name}
         common::etcd::put("nodes/TestInvalid", "192.168.1.103")
             .await
@@ -671,6 +2942,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_node_role_from_etcd_etcd_missing_cluster_info() {
+        common::etcd::use_in_memory_store();
         // Setup: Only nodes/{hostname} exists but not cluster/nodes/{hostname}
         // This should fallback to settings.yaml
         common::etcd::put("nodes/TestMissing", "192.168.1.104")
@@ -692,6 +2964,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_manager_action_empty_scenario_name() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager.trigger_manager_action("").await;
 
@@ -701,6 +2974,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_manager_action_whitespace_scenario_name() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager.trigger_manager_action("   ").await;
 
@@ -710,6 +2984,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_manager_action_scenario_not_found() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager
             .trigger_manager_action("nonexistent_scenario_xyz")
@@ -721,6 +2996,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_manager_action_invalid_scenario_yaml() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert invalid YAML for scenario
         common::etcd::put("Scenario/invalid-yaml", "{ invalid: yaml: ]")
             .await
@@ -741,6 +3017,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_trigger_manager_action_package_not_found() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert scenario but no corresponding package
         common::etcd::put(
             "Scenario/test-scenario",
@@ -772,6 +3049,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_invalid_package_yaml() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert valid scenario and invalid package
         common::etcd::put(
             "Scenario/test-scenario",
@@ -811,6 +3089,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_launch_success() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert valid scenario and package
         common::etcd::put(
             "Scenario/launch-test",
@@ -853,6 +3132,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("launch-test").await;
@@ -866,6 +3148,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_terminate_success() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert valid scenario with terminate action
         common::etcd::put(
             "Scenario/terminate-test",
@@ -903,6 +3186,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("terminate-test").await;
@@ -917,6 +3203,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_update_success() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert valid scenario with update action
         common::etcd::put(
             "Scenario/update-test",
@@ -955,6 +3242,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("update-test").await;
@@ -967,6 +3257,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_rollback_success() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert valid scenario with rollback action
         common::etcd::put(
             "Scenario/rollback-test",
@@ -1004,6 +3295,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("rollback-test").await;
@@ -1018,6 +3312,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_unknown_node() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert scenario with unknown node
         common::etcd::put(
             "Scenario/unknown-node-test",
@@ -1054,6 +3349,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("unknown-node-test").await;
@@ -1072,6 +3370,7 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_nodeagent_workload() {
+        common::etcd::use_in_memory_store();
         // Setup: Insert scenario with nodeagent node
         common::etcd::put(
             "Scenario/nodeagent-test",
@@ -1108,6 +3407,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec!["ZONE".to_string()],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("nodeagent-test").await;
@@ -1124,6 +3426,7 @@ spec:
 
     #[tokio::test]
     async fn test_reconcile_do_same_status() {
+        common::etcd::use_in_memory_store();
         // Test: Current and desired status are the same
         let manager = ActionControllerManager::new();
         let result = manager
@@ -1135,6 +3438,7 @@ spec:
 
     #[tokio::test]
     async fn test_reconcile_do_invalid_current_status_none() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager
             .reconcile_do("test".into(), Status::None, Status::Running)
@@ -1149,35 +3453,214 @@ spec:
 
     #[tokio::test]
     async fn test_reconcile_do_invalid_current_status_failed() {
+        common::etcd::use_in_memory_store();
+        let manager = ActionControllerManager::new();
+        let result = manager
+            .reconcile_do("test".into(), Status::Failed, Status::Running)
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_do_invalid_desired_status_none() {
+        common::etcd::use_in_memory_store();
+        let manager = ActionControllerManager::new();
+        let result = manager
+            .reconcile_do("test".into(), Status::Running, Status::None)
+            .await;
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid desired status"));
+    }
+
+    // ==================== relocate_node_models Tests ====================
+
+    #[tokio::test]
+    async fn test_relocate_node_models_no_healthy_nodes() {
+        common::etcd::use_in_memory_store();
+        let manager = ActionControllerManager::new();
+
+        let result = manager.relocate_node_models("failed-node", &[]).await;
+        assert!(result.is_err());
+
+        // The failed node itself is never a relocation target
+        let result = manager
+            .relocate_node_models("failed-node", &["failed-node".to_string()])
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No healthy node available"));
+    }
+
+    #[tokio::test]
+    async fn test_relocate_node_models_without_models_on_node() {
+        common::etcd::use_in_memory_store();
+        let manager = ActionControllerManager::new();
+
+        // No package references the node
+        let relocated = manager
+            .relocate_node_models("node-without-models", &["HPC".to_string()])
+            .await
+            .unwrap();
+        assert!(relocated.is_empty());
+
+        // The failed node itself cannot take over its models
+        let result = manager
+            .relocate_node_models("node-without-models", &["node-without-models".to_string()])
+            .await;
+        assert!(matches!(result, Err(common::error::Error::Unavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn test_drain_node_skips_cordoned_targets() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
+        let cordoned = common::apiserver::NodeInfo {
+            hostname: "cordoned-target".to_string(),
+            unschedulable: true,
+            ..Default::default()
+        };
+        common::etcd::put(
+            "cluster/nodes/cordoned-target",
+            &serde_json::to_string(&cordoned).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert!(!manager.is_schedulable("cordoned-target").await);
+        assert!(manager.is_schedulable("unregistered-node").await);
+
+        // The only target is cordoned
         let result = manager
-            .reconcile_do("test".into(), Status::Failed, Status::Running)
+            .drain_node("drained-node", &["cordoned-target".to_string()])
             .await;
-
         assert!(result.is_err());
+
+        let (relocated, failed) = manager
+            .drain_node("drained-node", &["unregistered-node".to_string()])
+            .await
+            .unwrap();
+        assert!(relocated.is_empty());
+        assert!(failed.is_empty());
+        common::etcd::delete("cluster/nodes/cordoned-target")
+            .await
+            .unwrap();
     }
 
-    #[tokio::test]
-    async fn test_reconcile_do_invalid_desired_status_none() {
-        let manager = ActionControllerManager::new();
-        let result = manager
-            .reconcile_do("test".into(), Status::Running, Status::None)
-            .await;
+    #[test]
+    fn test_swap_standby() {
+        let mut model: serde_yaml::Value =
+            serde_yaml::from_str("{name: web, node: HPC, standby: ZONE}").unwrap();
+        swap_standby(&mut model);
+        assert_eq!(model["node"].as_str(), Some("ZONE"));
+        assert_eq!(model["standby"].as_str(), Some("HPC"));
+    }
 
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("Invalid desired status"));
+    #[test]
+    fn test_reconcile_replicas_of_failing_models() {
+        let package: Package = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Package
+metadata:
+  name: reconcile-pkg
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: ok-model
+      node: node-a
+      resources:
+        volume:
+        network:
+    - name: dead-model
+      node: node-a
+      replicas: 2
+      nodes: [node-b]
+      resources:
+        volume:
+        network:
+"#,
+        )
+        .unwrap();
+        let pods = |failing: &[&str]| -> Vec<String> {
+            let failing: Vec<String> = failing.iter().map(|m| m.to_string()).collect();
+            reconcile_replicas(&package, &failing)
+                .iter()
+                .map(|mi| format!("{}@{}", mi.get_pod_name(), mi.get_node()))
+                .collect()
+        };
+        assert_eq!(pods(&["dead-model"]).len(), 2);
+        assert!(pods(&["dead-model"])
+            .iter()
+            .all(|pod| pod.starts_with("dead-model")));
+        assert_eq!(pods(&[]).len(), 3);
+        assert!(pods(&["unknown-model"]).is_empty());
+    }
+
+    #[test]
+    fn test_relocation_target_follows_placement() {
+        let podspec: PodSpec = serde_yaml::from_str(
+            "containers:\n  - name: c\n    image: image-1\nnodeSelector:\n  gpu: \"true\"\n",
+        )
+        .unwrap();
+        let node = |gpu: &str, taint: Option<&str>| common::apiserver::NodeInfo {
+            labels: HashMap::from([("gpu".to_string(), gpu.to_string())]),
+            taints: taint
+                .map(|effect| common::nodeagent::fromapiserver::Taint {
+                    key: "dedicated".to_string(),
+                    value: "x".to_string(),
+                    effect: effect.to_string(),
+                })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let registered = HashMap::from([
+            ("no-gpu".to_string(), node("false", None)),
+            (
+                "gpu-preferred-not".to_string(),
+                node("true", Some("PreferNoSchedule")),
+            ),
+            ("gpu-tainted".to_string(), node("true", Some("NoSchedule"))),
+            ("gpu".to_string(), node("true", None)),
+        ]);
+        let nodes = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let schedulable = nodes(&["no-gpu", "gpu-preferred-not", "gpu-tainted", "gpu"]);
+        let target = relocation_target(&schedulable, 0, Some(&podspec), &registered);
+        assert_eq!(target.map(String::as_str), Some("gpu"));
+        let schedulable = nodes(&["no-gpu", "gpu-preferred-not", "gpu-tainted"]);
+        let target = relocation_target(&schedulable, 0, Some(&podspec), &registered);
+        assert_eq!(target.map(String::as_str), Some("gpu-preferred-not"));
+        let schedulable = nodes(&["no-gpu", "gpu-tainted"]);
+        assert_eq!(
+            relocation_target(&schedulable, 0, Some(&podspec), &registered),
+            None
+        );
+        // Unregistered nodes and unreadable models are not checked
+        let schedulable = nodes(&["no-gpu", "unregistered"]);
+        let target = relocation_target(&schedulable, 0, Some(&podspec), &registered);
+        assert_eq!(target.map(String::as_str), Some("unregistered"));
+        let target = relocation_target(&schedulable, 0, None, &registered);
+        assert_eq!(target.map(String::as_str), Some("no-gpu"));
     }
 
     // ==================== start_workload Tests ====================
 
     #[tokio::test]
     async fn test_start_workload_nodeagent_node() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec!["ZONE".to_string()],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager
@@ -1189,6 +3672,7 @@ spec:
 
     #[tokio::test]
     async fn test_start_workload_invalid_node_type() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager
             .start_workload("test-service", "node", "invalid_type")
@@ -1203,9 +3687,13 @@ spec:
 
     #[tokio::test]
     async fn test_stop_workload_nodeagent_node() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec!["ZONE".to_string()],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager
@@ -1217,6 +3705,7 @@ spec:
 
     #[tokio::test]
     async fn test_stop_workload_invalid_node_type() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager
             .stop_workload("test-service", "node", "invalid_type")
@@ -1227,6 +3716,7 @@ spec:
 
     #[tokio::test]
     async fn test_reload_all_node() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager::new();
         let result = manager.reload_all_node("test-service", "HPC").await;
 
@@ -1235,9 +3725,13 @@ spec:
 
     #[tokio::test]
     async fn test_reconcile_do_with_valid_status() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
         let result = manager
             .reconcile_do("antipinch-enable".into(), Status::Running, Status::Running)
@@ -1245,8 +3739,76 @@ spec:
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_reconcile_reads_artifacts_as_the_apiserver_writes_them() {
+        common::etcd::use_in_memory_store();
+        common::etcd::put(
+            "Scenario/reconcile-test",
+            r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: reconcile-test
+spec:
+  condition:
+  action: launch
+  target: reconcile-pkg
+"#,
+        )
+        .await
+        .unwrap();
+        common::etcd::put(
+            "Package/reconcile-pkg",
+            r#"
+apiVersion: v1
+kind: Package
+metadata:
+  label: null
+  name: reconcile-pkg
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: reconcile-service
+      node: HPC
+      resources:
+        volume:
+        network:
+"#,
+        )
+        .await
+        .unwrap();
+
+        // Without NodeAgent nodes the models are skipped once both are read
+        let manager = ActionControllerManager {
+            nodeagent_nodes: vec![],
+            state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
+        };
+        manager
+            .reconcile_do("reconcile-test".into(), Status::Running, Status::Done)
+            .await
+            .unwrap();
+        manager
+            .reconcile_package("reconcile-test", "reconcile-pkg", &[], "test")
+            .await
+            .unwrap();
+        assert!(manager
+            .reconcile_package("reconcile-test", "other-pkg", &[], "test")
+            .await
+            .is_err());
+
+        common::etcd::delete("Scenario/reconcile-test")
+            .await
+            .unwrap();
+        common::etcd::delete("Package/reconcile-pkg").await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_trigger_manager_action_with_valid_data() {
+        common::etcd::use_in_memory_store();
         common::etcd::put(
             "Scenario/antipinch-enable",
             r#"
@@ -1288,6 +3850,9 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("antipinch-enable").await;
@@ -1310,9 +3875,13 @@ spec:
 
     #[tokio::test]
     async fn test_trigger_manager_action_invalid_scenario() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager.trigger_manager_action("invalid_scenario").await;
@@ -1321,9 +3890,13 @@ spec:
 
     #[tokio::test]
     async fn test_reconcile_do_invalid_scenario_key() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager
@@ -1334,12 +3907,16 @@ spec:
 
     #[tokio::test]
     async fn test_start_workload_invalid_node_type_legacy() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
-        let result: std::result::Result<(), Box<dyn Error>> = manager
+        let result: common::Result<()> = manager
             .start_workload("antipinch-enable", "HPC", "invalid_type")
             .await;
         assert!(result.is_err());
@@ -1347,9 +3924,13 @@ spec:
 
     #[tokio::test]
     async fn test_stop_workload_invalid_node_type_legacy() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         let result = manager
@@ -1367,9 +3948,13 @@ spec:
 
     #[tokio::test]
     async fn test_create_delete_restart_pause_are_noops() {
+        common::etcd::use_in_memory_store();
         let manager = ActionControllerManager {
             nodeagent_nodes: vec![],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         assert!(manager.create_workload("test".into()).await.is_ok());
@@ -1382,8 +3967,529 @@ spec:
         let manager = ActionControllerManager {
             nodeagent_nodes: vec!["ZONE".to_string()],
             state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
         };
 
         assert!(manager.nodeagent_nodes.contains(&"ZONE".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_complete_network_setting_tracks_requests() {
+        common::etcd::use_in_memory_store();
+        let manager = ActionControllerManager::new();
+        assert!(manager
+            .complete_network_setting("unknown", true, "")
+            .await
+            .is_err());
+
+        let request = NetworkRequest {
+            model_name: "net-model".to_string(),
+            node_yaml: String::new(),
+            pod_name: "net-scenario".to_string(),
+            network_yaml: String::new(),
+            attempts: 1,
+        };
+        manager
+            .network_requests
+            .lock()
+            .await
+            .insert("req-1".to_string(), request.clone());
+        assert!(manager
+            .complete_network_setting("req-1", true, "")
+            .await
+            .is_ok());
+        assert!(manager.network_requests.lock().await.is_empty());
+
+        // A failure after the last attempt is not retried
+        manager.network_requests.lock().await.insert(
+            "req-2".to_string(),
+            NetworkRequest {
+                attempts: NETWORK_SETUP_ATTEMPTS,
+                ..request
+            },
+        );
+        assert!(manager
+            .complete_network_setting("req-2", false, "timeout")
+            .await
+            .is_ok());
+        assert!(manager.network_requests.lock().await.is_empty());
+    }
+
+    /// NodeAgent of a node that records the workload commands it receives
+    struct MockNodeAgent {
+        node: String,
+        calls: std::sync::Arc<std::sync::Mutex<Vec<(String, WorkloadCommand, String)>>>,
+    }
+
+    #[tonic::async_trait]
+    impl NodeAgentConnection for MockNodeAgent {
+        async fn handle_yaml(
+            &self,
+            _request: Request<HandleYamlRequest>,
+        ) -> std::result::Result<Response<HandleYamlResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("handle_yaml"))
+        }
+
+        async fn register_node(
+            &self,
+            _request: Request<NodeRegistrationRequest>,
+        ) -> std::result::Result<Response<NodeRegistrationResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("register_node"))
+        }
+
+        async fn report_status(
+            &self,
+            _request: Request<StatusReport>,
+        ) -> std::result::Result<Response<StatusAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("report_status"))
+        }
+
+        async fn heartbeat(
+            &self,
+            _request: Request<HeartbeatRequest>,
+        ) -> std::result::Result<Response<HeartbeatResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("heartbeat"))
+        }
+
+        async fn receive_config(
+            &self,
+            _request: Request<ConfigRequest>,
+        ) -> std::result::Result<Response<ConfigResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("receive_config"))
+        }
+
+        async fn reload_config(
+            &self,
+            _request: Request<ReloadConfigRequest>,
+        ) -> std::result::Result<Response<ReloadConfigResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("reload_config"))
+        }
+
+        async fn negotiate_api_version(
+            &self,
+            _request: Request<ApiVersionRequest>,
+        ) -> std::result::Result<Response<ApiVersionResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("negotiate_api_version"))
+        }
+
+        type GetContainerLogsStream = tonic::codegen::BoxStream<ContainerLogChunk>;
+
+        async fn get_container_logs(
+            &self,
+            _request: Request<ContainerLogsRequest>,
+        ) -> std::result::Result<Response<Self::GetContainerLogsStream>, tonic::Status> {
+            Err(tonic::Status::unimplemented("get_container_logs"))
+        }
+
+        async fn exec_in_container(
+            &self,
+            _request: Request<ContainerExecRequest>,
+        ) -> std::result::Result<Response<ContainerExecResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("exec_in_container"))
+        }
+
+        async fn list_images(
+            &self,
+            _request: Request<ListImagesRequest>,
+        ) -> std::result::Result<Response<ListImagesResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("list_images"))
+        }
+
+        async fn prune_images(
+            &self,
+            _request: Request<PruneImagesRequest>,
+        ) -> std::result::Result<Response<PruneImagesResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("prune_images"))
+        }
+
+        async fn handle_workload(
+            &self,
+            request: Request<HandleWorkloadRequest>,
+        ) -> std::result::Result<Response<HandleWorkloadResponse>, tonic::Status> {
+            let req = request.into_inner();
+            let pod: Pod = serde_yaml::from_str(&req.pod)
+                .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+            self.calls.lock().unwrap().push((
+                self.node.clone(),
+                req.workload_command(),
+                pod.get_name(),
+            ));
+            Ok(Response::new(HandleWorkloadResponse {
+                status: true,
+                desc: String::new(),
+            }))
+        }
+
+        async fn prefetch_images(
+            &self,
+            _request: Request<PrefetchImagesRequest>,
+        ) -> std::result::Result<Response<PrefetchImagesResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("prefetch_images"))
+        }
+    }
+
+    /// Registers `node` at `ip` and serves a [`MockNodeAgent`] for it there
+    async fn start_mock_nodeagent(
+        node: &str,
+        ip: &str,
+        calls: &std::sync::Arc<std::sync::Mutex<Vec<(String, WorkloadCommand, String)>>>,
+    ) {
+        let info = common::apiserver::NodeInfo {
+            hostname: node.to_string(),
+            node_role: NODE_ROLE_NODEAGENT,
+            ..Default::default()
+        };
+        common::etcd::put(&format!("{}/{}", ETCD_NODES_PREFIX, node), ip)
+            .await
+            .unwrap();
+        common::etcd::put(
+            &format!("{}/{}", ETCD_CLUSTER_NODES_PREFIX, node),
+            &serde_json::to_string(&info).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let addr = format!(
+            "{}:{}",
+            ip,
+            common::address::port(common::address::Service::NodeAgent)
+        )
+        .parse()
+        .unwrap();
+        let agent = MockNodeAgent {
+            node: node.to_string(),
+            calls: calls.clone(),
+        };
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(NodeAgentConnectionServer::new(agent))
+                .serve(addr)
+                .await
+                .unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn test_move_waits_for_the_instance_on_the_target_node() {
+        common::etcd::use_in_memory_store();
+        let calls = Default::default();
+        start_mock_nodeagent("rebalance-from", "127.0.0.63", &calls).await;
+        start_mock_nodeagent("rebalance-to", "127.0.0.64", &calls).await;
+        common::etcd::put(
+            "Package/rebalance-pkg",
+            r#"
+apiVersion: v1
+kind: Package
+metadata:
+  label: null
+  name: rebalance-pkg
+spec:
+  pattern:
+    - type: plain
+  strategy:
+    healthTimeout: 1
+  models:
+    - name: rebalance-core
+      node: rebalance-from
+      resources:
+        volume:
+        network:
+"#,
+        )
+        .await
+        .unwrap();
+        let podspec: PodSpec =
+            serde_yaml::from_str("containers:\n  - name: core\n    image: core\n").unwrap();
+        let pod = Pod::new("rebalance-core", podspec);
+        common::etcd::put("Pod/rebalance-core", &serde_yaml::to_string(&pod).unwrap())
+            .await
+            .unwrap();
+        // The model runs on its old node, the new instance never starts
+        common::etcd::put("/model/rebalance-core/state", "Running")
+            .await
+            .unwrap();
+        for (id, node, status) in [
+            ("rebalance-old", "rebalance-from", "running"),
+            ("rebalance-new", "rebalance-to", "created"),
+        ] {
+            let container = serde_json::json!({
+                "id": id,
+                "node": node,
+                "annotation": { LABEL_MODEL: "rebalance-core" },
+                "state": { "Status": status },
+            });
+            common::etcd::put(
+                &format!("/piccolo/metrics/containers/{}", id),
+                &container.to_string(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let manager = ActionControllerManager {
+            nodeagent_nodes: vec!["rebalance-from".to_string()],
+            state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
+        };
+        let model_move = ModelMove {
+            package: "rebalance-pkg".to_string(),
+            model: "rebalance-core".to_string(),
+            from_node: "rebalance-from".to_string(),
+            to_node: "rebalance-to".to_string(),
+        };
+        let result = manager
+            .move_model(
+                "Package/rebalance-pkg",
+                &model_move,
+                &["rebalance-core".to_string()],
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("not running"));
+
+        // The new instance is stopped again and the model stays where it was
+        let call = |node: &str, command, pod: &str| (node.to_string(), command, pod.to_string());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                call("rebalance-to", WorkloadCommand::Start, "rebalance-core"),
+                call("rebalance-to", WorkloadCommand::Stop, "rebalance-core"),
+            ]
+        );
+        let package: Package =
+            serde_yaml::from_str(&common::etcd::get("Package/rebalance-pkg").await.unwrap())
+                .unwrap();
+        assert_eq!(package.get_models()[0].get_node(), "rebalance-from");
+
+        for key in [
+            "Package/rebalance-pkg",
+            "Pod/rebalance-core",
+            "/model/rebalance-core/state",
+            "/piccolo/metrics/containers/rebalance-old",
+            "/piccolo/metrics/containers/rebalance-new",
+        ] {
+            common::etcd::delete(key).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_autostart_skips_models_whose_containers_exist() {
+        common::etcd::use_in_memory_store();
+        let calls = Default::default();
+        start_mock_nodeagent("autostart-node", "127.0.0.65", &calls).await;
+        common::etcd::put(
+            "Scenario/autostart-test",
+            "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: autostart-test\nspec:\n  action: launch\n  target: autostart-pkg\n",
+        )
+        .await
+        .unwrap();
+        let podspec: PodSpec =
+            serde_yaml::from_str("containers:\n  - name: core\n    image: core\n").unwrap();
+        for model in ["autostart-kept", "autostart-gone"] {
+            let pod = Pod::new(model, podspec.clone());
+            common::etcd::put(
+                &format!("Pod/{}", model),
+                &serde_yaml::to_string(&pod).unwrap(),
+            )
+            .await
+            .unwrap();
+            crate::autostart::record("autostart-node", model, "autostart-test")
+                .await
+                .unwrap();
+        }
+        // Only the NodeAgent restarted, the containers of one model still run
+        let container = serde_json::json!({
+            "id": "autostart-kept-1",
+            "node": "autostart-node",
+            "annotation": { LABEL_MODEL: "autostart-kept" },
+            "state": { "Status": "running" },
+        });
+        common::etcd::put(
+            "/piccolo/metrics/containers/autostart-kept-1",
+            &container.to_string(),
+        )
+        .await
+        .unwrap();
+
+        let manager = ActionControllerManager {
+            nodeagent_nodes: vec!["autostart-node".to_string()],
+            state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
+        };
+        let (started, failed) = manager.autostart_node("autostart-node").await.unwrap();
+        assert_eq!(started, ["autostart-gone", "autostart-kept"]);
+        assert!(failed.is_empty());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![(
+                "autostart-node".to_string(),
+                WorkloadCommand::Start,
+                "autostart-gone".to_string()
+            )]
+        );
+
+        for key in [
+            "Scenario/autostart-test",
+            "Pod/autostart-kept",
+            "Pod/autostart-gone",
+            "/piccolo/metrics/containers/autostart-kept-1",
+        ] {
+            common::etcd::delete(key).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_primary_death_promotes_the_standby() {
+        common::etcd::use_in_memory_store();
+        let calls = Default::default();
+        start_mock_nodeagent("failover-primary", "127.0.0.61", &calls).await;
+        start_mock_nodeagent("failover-standby", "127.0.0.62", &calls).await;
+        common::etcd::put(
+            "Scenario/failover-test",
+            r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: failover-test
+spec:
+  condition:
+  action: launch
+  target: failover-pkg
+"#,
+        )
+        .await
+        .unwrap();
+        common::etcd::put(
+            "Package/failover-pkg",
+            r#"
+apiVersion: v1
+kind: Package
+metadata:
+  label: null
+  name: failover-pkg
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: failover-core
+      node: failover-primary
+      standby: failover-standby
+      resources:
+        volume:
+        network:
+"#,
+        )
+        .await
+        .unwrap();
+        let podspec: PodSpec =
+            serde_yaml::from_str("containers:\n  - name: core\n    image: core\n").unwrap();
+        let primary = Pod::new("failover-core", podspec);
+        let mut standby = primary.clone();
+        standby.set_standby();
+        common::etcd::put(
+            "Pod/failover-core",
+            &serde_yaml::to_string(&primary).unwrap(),
+        )
+        .await
+        .unwrap();
+        common::etcd::put(
+            "Pod/failover-core-standby",
+            &serde_yaml::to_string(&standby).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        // The StateManager asks for a reconcile once the primary died
+        let manager = ActionControllerManager {
+            nodeagent_nodes: vec!["failover-primary".to_string()],
+            state_sender: StateManagerSender::new(),
+            network_requests: Default::default(),
+            admission: Default::default(),
+            operations: Default::default(),
+        };
+        manager
+            .reconcile_package(
+                "failover-test",
+                "failover-pkg",
+                &["failover-core".to_string()],
+                "model failover-core died",
+            )
+            .await
+            .unwrap();
+
+        // The standby resumes before the primary is stopped, then the old
+        // node gets a new paused standby
+        let call = |node: &str, command, pod: &str| (node.to_string(), command, pod.to_string());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                call(
+                    "failover-standby",
+                    WorkloadCommand::Unpause,
+                    "failover-core-standby"
+                ),
+                call("failover-primary", WorkloadCommand::Stop, "failover-core"),
+                call(
+                    "failover-primary",
+                    WorkloadCommand::Start,
+                    "failover-core-standby"
+                ),
+                call(
+                    "failover-primary",
+                    WorkloadCommand::Pause,
+                    "failover-core-standby"
+                ),
+            ]
+        );
+
+        let package: Package =
+            serde_yaml::from_str(&common::etcd::get("Package/failover-pkg").await.unwrap())
+                .unwrap();
+        let model = &package.get_models()[0];
+        assert_eq!(model.get_node(), "failover-standby");
+        assert_eq!(
+            model.get_standby_node(),
+            Some("failover-primary".to_string())
+        );
+
+        let promoted: Pod =
+            serde_yaml::from_str(&common::etcd::get("Pod/failover-core").await.unwrap()).unwrap();
+        assert_eq!(promoted.get_name(), "failover-core");
+        assert!(!promoted.get_labels().contains_key(LABEL_STANDBY));
+        let demoted: Pod = serde_yaml::from_str(
+            &common::etcd::get("Pod/failover-core-standby")
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(demoted.get_name(), "failover-core-standby");
+        assert_eq!(demoted.get_labels()[LABEL_STANDBY], "true");
+
+        for key in [
+            "Scenario/failover-test",
+            "Package/failover-pkg",
+            "Pod/failover-core",
+            "Pod/failover-core-standby",
+        ] {
+            common::etcd::delete(key).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_action_result_names_its_transition() {
+        let result = action_result("s", "policymanager-7", "launch", None, false);
+        assert_eq!(result.transition_id, "policymanager-7");
+        assert!(result.success);
+
+        let result = action_result("s", "policymanager-8", "launch", Some("busy".into()), true);
+        assert_eq!(result.transition_id, "policymanager-8");
+        assert_eq!(result.message, "busy");
+        assert!(!result.success && result.rejected);
+    }
 }
@@ -0,0 +1,270 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Records of the networks Pharos set up for models
+//!
+//! The network of a launched model is requested from Pharos and recorded
+//! under `NetworkSetup/{model}` with the yamls it was requested with and the
+//! status of the setup. A terminated model has its network torn down again
+//! with the same yamls, and the record is dropped once Pharos accepted the
+//! teardown. A teardown that fails while Pharos is unreachable stays
+//! recorded as failed and is sent again by the periodic retry, up to
+//! [`MAX_TEARDOWN_ATTEMPTS`] times. One refused with an error that cannot
+//! go away by sending it again, e.g. `Unimplemented`, or that used up its
+//! attempts is recorded as abandoned and no longer retried.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Key prefix of the records
+pub const PREFIX: &str = "NetworkSetup";
+
+/// Interval of the retry of failed teardowns
+pub const TEARDOWN_RETRY_INTERVAL_SECS: u64 = 60;
+
+/// Teardown requests sent for a network before it is given up
+pub const MAX_TEARDOWN_ATTEMPTS: u32 = 10;
+
+/// Step of the network of a model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Setup,
+    Teardown,
+}
+
+/// Outcome of the last request of a phase
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Accepted by Pharos, or being sent
+    Requested,
+    /// Completed by Pharos
+    Done,
+    /// Refused, or Pharos could not be reached
+    Failed,
+    /// Failed for good, no longer retried
+    Abandoned,
+}
+
+/// Network of a model as requested from Pharos
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub phase: Phase,
+    pub status: Status,
+    pub node_yaml: String,
+    pub pod_name: String,
+    pub network_yaml: String,
+    /// Teardown requests that failed so far
+    #[serde(default)]
+    pub teardown_attempts: u32,
+}
+
+fn key(model: &str) -> String {
+    format!("{}/{}", PREFIX, model)
+}
+
+/// Record of the network of `model`, `None` if it has none
+pub async fn get(model: &str) -> Option<Record> {
+    let value = common::etcd::get(&key(model)).await.ok()?;
+    serde_json::from_str(&value).ok()
+}
+
+/// Record the network of `model`
+pub async fn put(model: &str, record: &Record) -> Result<(), String> {
+    let value = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    common::etcd::put(&key(model), &value).await
+}
+
+/// Set the phase and status of the network of `model`, if it has a record
+pub async fn set(model: &str, phase: Phase, status: Status) -> Result<(), String> {
+    match get(model).await {
+        Some(record) => {
+            put(
+                model,
+                &Record {
+                    phase,
+                    status,
+                    ..record
+                },
+            )
+            .await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Drop the record of a torn down network
+pub async fn forget(model: &str) -> Result<(), String> {
+    common::etcd::delete(&key(model)).await
+}
+
+/// Tear down the network of a terminated model
+///
+/// ### Returns
+/// * `Ok(true)` - Pharos accepted the teardown, or the model has no network
+/// * `Ok(false)` - the teardown failed and is retried later, unless it was
+///   abandoned
+pub async fn tear_down(model: &str) -> Result<bool, String> {
+    let Some(record) = get(model).await else {
+        return Ok(true);
+    };
+    let result = crate::grpc::sender::pharos::release_network_pod(
+        record.node_yaml.clone(),
+        record.pod_name.clone(),
+        record.network_yaml.clone(),
+    )
+    .await;
+    let (error, retryable) = match result {
+        Ok(response) => {
+            let response = response.into_inner();
+            if response.accepted {
+                common::logd!(2, "Network of model '{}' torn down", model);
+                forget(model).await?;
+                return Ok(true);
+            }
+            (response.message, true)
+        }
+        Err(e) => (
+            e.message().to_string(),
+            common::grpc::retry::is_retryable(&e),
+        ),
+    };
+    let record = failed_teardown(record, retryable);
+    if record.status == Status::Abandoned {
+        common::logd!(
+            5,
+            "Network teardown of model '{}' abandoned after {} attempt(s): {}",
+            model,
+            record.teardown_attempts,
+            error
+        );
+    } else {
+        common::logd!(
+            4,
+            "Network teardown of model '{}' failed, retried later: {}",
+            model,
+            error
+        );
+    }
+    put(model, &record).await?;
+    Ok(false)
+}
+
+/// Record of a network after one more failed teardown
+fn failed_teardown(record: Record, retryable: bool) -> Record {
+    let teardown_attempts = record.teardown_attempts + 1;
+    let status = if retryable && teardown_attempts < MAX_TEARDOWN_ATTEMPTS {
+        Status::Failed
+    } else {
+        Status::Abandoned
+    };
+    Record {
+        phase: Phase::Teardown,
+        status,
+        teardown_attempts,
+        ..record
+    }
+}
+
+/// Models whose teardown failed
+pub async fn failed_teardowns() -> Result<Vec<String>, String> {
+    let kvs = common::etcd::get_all_with_prefix(&format!("{}/", PREFIX)).await?;
+    Ok(parse_failed_teardowns(kvs))
+}
+
+fn parse_failed_teardowns(kvs: Vec<(String, String)>) -> Vec<String> {
+    let prefix = format!("{}/", PREFIX);
+    let mut models: Vec<String> = kvs
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let model = key.strip_prefix(&prefix)?.to_string();
+            let record: Record = serde_json::from_str(&value).ok()?;
+            (record.phase == Phase::Teardown && record.status == Status::Failed).then_some(model)
+        })
+        .collect();
+    models.sort();
+    models
+}
+
+/// Retry the failed teardowns periodically, e.g. after a Pharos outage
+pub async fn run_retry(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let models = match failed_teardowns().await {
+            Ok(models) => models,
+            Err(e) => {
+                common::logd!(4, "Failed network teardowns not read: {}", e);
+                continue;
+            }
+        };
+        for model in models {
+            if let Err(e) = tear_down(&model).await {
+                common::logd!(4, "Network record of model '{}' not updated: {}", model, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(phase: Phase, status: Status) -> String {
+        serde_json::to_string(&Record {
+            phase,
+            status,
+            node_yaml: String::new(),
+            pod_name: "s".to_string(),
+            network_yaml: String::new(),
+            teardown_attempts: 0,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_failed_teardowns() {
+        let kvs = vec![
+            (key("team/radar"), record(Phase::Teardown, Status::Failed)),
+            (key("camera"), record(Phase::Teardown, Status::Failed)),
+            (key("lidar"), record(Phase::Setup, Status::Failed)),
+            (key("gps"), record(Phase::Setup, Status::Done)),
+            (key("broken"), "not json".to_string()),
+            (key("wifi"), record(Phase::Teardown, Status::Abandoned)),
+        ];
+        assert_eq!(
+            parse_failed_teardowns(kvs),
+            vec!["camera".to_string(), "team/radar".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_failed_teardowns_are_bounded() {
+        let done: Record = serde_json::from_str(&record(Phase::Setup, Status::Done)).unwrap();
+        let mut network = done.clone();
+        for _ in 1..MAX_TEARDOWN_ATTEMPTS {
+            network = failed_teardown(network, true);
+            assert_eq!(network.status, Status::Failed);
+        }
+        network = failed_teardown(network, true);
+        assert_eq!(network.status, Status::Abandoned);
+        assert_eq!(network.teardown_attempts, MAX_TEARDOWN_ATTEMPTS);
+
+        // Pharos does not implement the teardown, sending it again is useless
+        let network = failed_teardown(done, false);
+        assert_eq!(network.phase, Phase::Teardown);
+        assert_eq!(network.status, Status::Abandoned);
+        assert_eq!(network.teardown_attempts, 1);
+    }
+
+    #[test]
+    fn test_record_format() {
+        let value = record(Phase::Setup, Status::Requested);
+        assert!(value.contains(r#""phase":"setup""#));
+        assert!(value.contains(r#""status":"requested""#));
+    }
+}
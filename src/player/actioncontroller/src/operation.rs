@@ -0,0 +1,243 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Serialization of the operations on scenarios and packages
+//!
+//! Two triggers of the same scenario, or an update and a terminate of the
+//! same package, must not run their actions at the same time. An operation
+//! holds the lock of every resource it acts on, its scenario and the package
+//! of the scenario, until it is done:
+//! * an operation on a busy resource waits for it, in arrival order
+//! * an operation already running or waiting is rejected as in progress,
+//!   the running or waiting one settles its resources
+//! * an operation finding [`MAX_WAITING`] others waiting already is turned
+//!   away as unavailable, nothing else settles it
+//!
+//! The locks are taken in the order of the resource names, so that two
+//! operations sharing several resources cannot wait for each other.
+
+use common::error::Error;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+/// Operations waiting for a resource before new ones are rejected
+pub const MAX_WAITING: usize = 4;
+
+/// Operations of a resource, the first one is running
+#[derive(Default)]
+struct Slot {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    operations: Vec<String>,
+}
+
+/// Locks of the resources with operations running or waiting
+#[derive(Default)]
+pub struct Operations {
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+/// Resources held by an operation, released when dropped
+pub struct OperationGuard<'a> {
+    operations: &'a Operations,
+    resources: Vec<String>,
+    operation: String,
+    _locks: Vec<OwnedMutexGuard<()>>,
+}
+
+impl Operations {
+    /// Wait until `operation` holds every resource of `resources`
+    ///
+    /// # Errors
+    ///
+    /// * `Error::PreconditionFailed` if the same operation is running or
+    ///   waiting already
+    /// * `Error::Unavailable` if too many operations wait for a resource
+    pub async fn acquire(
+        &self,
+        resources: &[String],
+        operation: &str,
+    ) -> common::Result<OperationGuard<'_>> {
+        let mut resources = resources.to_vec();
+        resources.sort();
+        resources.dedup();
+
+        let locks: Vec<Arc<tokio::sync::Mutex<()>>> = {
+            let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+            for resource in &resources {
+                let Some(slot) = slots.get(resource) else {
+                    continue;
+                };
+                if slot.operations.iter().any(|queued| queued == operation) {
+                    return Err(in_progress(operation, resource, &slot.operations[0]));
+                }
+                if slot.operations.len() > MAX_WAITING {
+                    return Err(Error::Unavailable(format!(
+                        "Too many operations waiting on {}: '{}' is running, '{}' rejected",
+                        resource, slot.operations[0], operation
+                    )));
+                }
+            }
+            resources
+                .iter()
+                .map(|resource| {
+                    let slot = slots.entry(resource.clone()).or_default();
+                    slot.operations.push(operation.to_string());
+                    slot.lock.clone()
+                })
+                .collect()
+        };
+
+        // The guard releases what was taken if the caller stops waiting
+        let mut guard = OperationGuard {
+            operations: self,
+            resources,
+            operation: operation.to_string(),
+            _locks: Vec::new(),
+        };
+        for lock in locks {
+            guard._locks.push(lock.lock_owned().await);
+        }
+        Ok(guard)
+    }
+
+    /// Operations running or waiting on a resource, the running one first
+    pub fn of(&self, resource: &str) -> Vec<String> {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots
+            .get(resource)
+            .map(|slot| slot.operations.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        let mut slots = self
+            .operations
+            .slots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        for resource in &self.resources {
+            let Some(slot) = slots.get_mut(resource) else {
+                continue;
+            };
+            if let Some(index) = slot.operations.iter().position(|o| *o == self.operation) {
+                slot.operations.remove(index);
+            }
+            if slot.operations.is_empty() {
+                slots.remove(resource);
+            }
+        }
+    }
+}
+
+fn in_progress(operation: &str, resource: &str, running: &str) -> Error {
+    Error::PreconditionFailed(format!(
+        "Operation in progress on {}: '{}' is running, '{}' rejected",
+        resource, running, operation
+    ))
+}
+
+/// Resource names of a scenario and of its package, locked by its operations
+pub fn resources(scenario_name: &str, package_name: &str) -> Vec<String> {
+    vec![
+        format!("Scenario/{}", scenario_name),
+        package_resource(package_name),
+    ]
+}
+
+/// Resource name of a package, locked by the operations on it
+pub fn package_resource(package_name: &str) -> String {
+    format!("Package/{}", package_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_conflicting_operations_run_one_after_the_other() {
+        let operations = Operations::default();
+        let update = operations
+            .acquire(&resources("s1", "p"), "update s1")
+            .await
+            .unwrap();
+
+        // A terminate through another scenario of the same package waits
+        let s2 = resources("s2", "p");
+        let terminate = operations.acquire(&s2, "terminate s2");
+        tokio::pin!(terminate);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut terminate)
+                .await
+                .is_err()
+        );
+        assert_eq!(operations.of("Package/p"), ["update s1", "terminate s2"]);
+
+        drop(update);
+        let terminate = terminate.await.unwrap();
+        assert_eq!(operations.of("Package/p"), ["terminate s2"]);
+        assert!(operations.of("Scenario/s1").is_empty());
+        drop(terminate);
+        assert!(operations.of("Package/p").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_operation_is_rejected() {
+        let operations = Operations::default();
+        let _launch = operations
+            .acquire(&resources("s1", "p"), "launch s1")
+            .await
+            .unwrap();
+
+        let err = operations
+            .acquire(&resources("s1", "p"), "launch s1")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::PreconditionFailed(_)));
+        assert!(err.to_string().contains("Operation in progress"));
+        // The rejected operation left nothing behind
+        assert_eq!(operations.of("Scenario/s1"), ["launch s1"]);
+    }
+
+    #[tokio::test]
+    async fn test_too_many_waiting_operations_are_rejected() {
+        let operations = Operations::default();
+        let _running = operations
+            .acquire(&resources("s0", "p"), "launch s0")
+            .await
+            .unwrap();
+
+        let requests: Vec<(Vec<String>, String)> = (1..=MAX_WAITING)
+            .map(|index| {
+                let scenario = format!("s{}", index);
+                (resources(&scenario, "p"), format!("launch {}", scenario))
+            })
+            .collect();
+        let mut waiting = Vec::new();
+        for (resources, operation) in &requests {
+            let mut acquire = Box::pin(operations.acquire(resources, operation));
+            let wait = tokio::time::timeout(Duration::from_millis(1), &mut acquire);
+            assert!(wait.await.is_err());
+            waiting.push(acquire);
+        }
+        assert_eq!(operations.of("Package/p").len(), MAX_WAITING + 1);
+
+        let err = operations
+            .acquire(&resources("s9", "p"), "launch s9")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Unavailable(_)));
+        assert!(err.to_string().contains("'launch s0' is running"));
+
+        // Operations that stop waiting leave the queue
+        drop(waiting);
+        assert_eq!(operations.of("Package/p"), ["launch s0"]);
+    }
+}
@@ -0,0 +1,220 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Rebalancing of the models over the nodes
+//!
+//! After failovers and drains the models gather on the nodes that took them
+//! over. A rebalance plans moves from the most loaded node to less loaded
+//! ones for as long as a move narrows the gap between them, then runs the
+//! moves one at a time: a model is started on its new node and has to run
+//! there before it is stopped on its old node, see
+//! `ActionControllerManager::rebalance`.
+//!
+//! The load of a node is the CPU load it reported last, shared evenly by the
+//! pods placed on it. When a node has no current report, every node is
+//! weighed by the number of pods placed on it instead. A model moves with all
+//! its replicas on the node, and only to nodes it may be placed on.
+
+use common::actioncontroller::ModelMove;
+use std::collections::{HashMap, HashSet};
+
+/// Loads closer than this are equal
+const LOAD_EPSILON: f32 = 1e-3;
+
+/// Model of a package placed on a node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub package: String,
+    pub model: String,
+    pub node: String,
+    /// Pods of the replicas of the model placed on the node
+    pub pods: Vec<String>,
+}
+
+/// Moves spreading the `placements` over `nodes`, at most `max_moves` of
+/// them unless it is 0
+///
+/// `cpu_loads` are the current CPU loads reported by the nodes and `allowed`
+/// tells whether a placement may move to a node. Placements on other nodes
+/// than `nodes` are left where they are.
+pub fn plan(
+    placements: &[Placement],
+    nodes: &[String],
+    cpu_loads: &HashMap<String, f32>,
+    max_moves: usize,
+    allowed: impl Fn(&Placement, &str) -> bool,
+) -> Vec<ModelMove> {
+    let mut pods: HashMap<&str, usize> = nodes.iter().map(|n| (n.as_str(), 0)).collect();
+    for placement in placements {
+        if let Some(count) = pods.get_mut(placement.node.as_str()) {
+            *count += placement.pods.len();
+        }
+    }
+    let by_cpu = nodes.iter().all(|node| cpu_loads.contains_key(node));
+    let mut loads: HashMap<&str, f32> = pods
+        .iter()
+        .map(|(node, count)| {
+            let load = if by_cpu {
+                cpu_loads[*node]
+            } else {
+                *count as f32
+            };
+            (*node, load)
+        })
+        .collect();
+    // Weight of a placement, what it takes off its node when it moves
+    let weights: Vec<f32> = placements
+        .iter()
+        .map(|placement| {
+            let per_pod = match pods.get(placement.node.as_str()) {
+                Some(count) if by_cpu => loads[placement.node.as_str()] / (*count).max(1) as f32,
+                _ => 1.0,
+            };
+            per_pod * placement.pods.len() as f32
+        })
+        .collect();
+
+    let mut moved = HashSet::new();
+    let mut moves = Vec::new();
+    while max_moves == 0 || moves.len() < max_moves {
+        // The most loaded node, the first one given of equally loaded ones
+        let Some(from) = nodes.iter().map(String::as_str).reduce(|most, node| {
+            if loads[node] > loads[most] + LOAD_EPSILON {
+                node
+            } else {
+                most
+            }
+        }) else {
+            break;
+        };
+
+        // The move leaving the smallest load on the busier of both nodes
+        let mut best: Option<(usize, &str, f32)> = None;
+        for (index, placement) in placements.iter().enumerate() {
+            if placement.node != from || moved.contains(&index) || weights[index] <= 0.0 {
+                continue;
+            }
+            for to in nodes.iter().map(String::as_str) {
+                let after = loads[to] + weights[index];
+                if to == from || after + LOAD_EPSILON >= loads[from] || !allowed(placement, to) {
+                    continue;
+                }
+                let peak = after.max(loads[from] - weights[index]);
+                if best.is_none_or(|(_, _, best_peak)| peak + LOAD_EPSILON < best_peak) {
+                    best = Some((index, to, peak));
+                }
+            }
+        }
+        let Some((index, to, _)) = best else {
+            break;
+        };
+
+        *loads.get_mut(from).unwrap() -= weights[index];
+        *loads.get_mut(to).unwrap() += weights[index];
+        moved.insert(index);
+        let placement = &placements[index];
+        moves.push(ModelMove {
+            package: placement.package.clone(),
+            model: placement.model.clone(),
+            from_node: from.to_string(),
+            to_node: to.to_string(),
+        });
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(model: &str, node: &str, pods: usize) -> Placement {
+        Placement {
+            package: "package".to_string(),
+            model: model.to_string(),
+            node: node.to_string(),
+            pods: (0..pods).map(|i| format!("{}-{}", model, i)).collect(),
+        }
+    }
+
+    fn nodes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn moved(moves: &[ModelMove]) -> Vec<(&str, &str, &str)> {
+        moves
+            .iter()
+            .map(|m| (m.model.as_str(), m.from_node.as_str(), m.to_node.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_by_pod_count() {
+        let placements = vec![
+            placement("a", "node-1", 1),
+            placement("b", "node-1", 1),
+            placement("c", "node-1", 1),
+            placement("d", "node-1", 1),
+            placement("e", "node-3", 1),
+        ];
+        let all = nodes(&["node-1", "node-2", "node-3"]);
+        let moves = plan(&placements, &all, &HashMap::new(), 0, |_, _| true);
+        assert_eq!(
+            moved(&moves),
+            vec![("a", "node-1", "node-2"), ("b", "node-1", "node-2")]
+        );
+
+        // Balanced nodes keep their models
+        let balanced = vec![placement("a", "node-1", 1), placement("b", "node-2", 1)];
+        assert!(plan(&balanced, &all[..2], &HashMap::new(), 0, |_, _| true).is_empty());
+    }
+
+    #[test]
+    fn test_plan_limits() {
+        let placements = vec![
+            placement("a", "node-1", 2),
+            placement("b", "node-1", 1),
+            placement("c", "node-1", 1),
+        ];
+        let all = nodes(&["node-1", "node-2"]);
+        // A model moves with its replicas
+        let moves = plan(&placements, &all, &HashMap::new(), 0, |_, _| true);
+        assert_eq!(moved(&moves), vec![("a", "node-1", "node-2")]);
+        // Only allowed nodes take a model
+        let moves = plan(&placements, &all, &HashMap::new(), 0, |p, _| p.model != "a");
+        assert_eq!(
+            moved(&moves),
+            vec![("b", "node-1", "node-2"), ("c", "node-1", "node-2")]
+        );
+        let moves = plan(&placements, &all, &HashMap::new(), 1, |p, _| p.model != "a");
+        assert_eq!(moves.len(), 1);
+        // Models on other nodes stay
+        let moves = plan(&placements, &all[1..], &HashMap::new(), 0, |_, _| true);
+        assert!(moves.is_empty());
+    }
+
+    #[test]
+    fn test_plan_by_cpu_load() {
+        let placements = vec![
+            placement("a", "node-1", 1),
+            placement("b", "node-1", 1),
+            placement("c", "node-2", 1),
+            placement("d", "node-2", 1),
+        ];
+        let all = nodes(&["node-1", "node-2", "node-3"]);
+        let loads = HashMap::from([
+            ("node-1".to_string(), 80.0),
+            ("node-2".to_string(), 30.0),
+            ("node-3".to_string(), 10.0),
+        ]);
+        let moves = plan(&placements, &all, &loads, 0, |_, _| true);
+        // node-1 moves a model of 40 to node-3, which is the busiest node
+        // then and has nothing to move but the model it just took
+        assert_eq!(moved(&moves), vec![("a", "node-1", "node-3")]);
+
+        // Without a report of every node, pods are counted
+        let partial = HashMap::from([("node-1".to_string(), 80.0)]);
+        assert_eq!(plan(&placements, &all, &partial, 0, |_, _| true).len(), 1);
+    }
+}
@@ -3,7 +3,10 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 use common::logd;
-use common::nodeagent::fromactioncontroller::{HandleWorkloadRequest, WorkloadCommand};
+use common::nodeagent::fromactioncontroller::{
+    HandleWorkloadRequest, PrefetchImagesRequest, PrefetchImagesResponse, WorkloadCommand,
+};
+use common::nodeagent::fromapiserver::{ContainerExecRequest, ContainerExecResponse};
 use common::Result;
 /// Runtime implementation for NodeAgent API interactions
 ///
@@ -24,6 +27,7 @@ pub async fn handle_workload(cmd: WorkloadCommand, pod: &str, node_name: &str) -
         let request = HandleWorkloadRequest {
             workload_command: cmd.into(),
             pod: pod.to_string(),
+            correlation_id: common::correlation::current_or_empty(),
         };
         crate::grpc::sender::nodeagent::send_workload_handle_request(&addr, request).await?;
     } else {
@@ -52,6 +56,51 @@ pub async fn restart_workload(pod: &str, node_name: &str) -> Result<()> {
     Ok(())
 }
 
+pub async fn pause_workload(pod: &str, node_name: &str) -> Result<()> {
+    let cmd = WorkloadCommand::Pause;
+    handle_workload(cmd, pod, node_name).await?;
+    Ok(())
+}
+
+pub async fn unpause_workload(pod: &str, node_name: &str) -> Result<()> {
+    let cmd = WorkloadCommand::Unpause;
+    handle_workload(cmd, pod, node_name).await?;
+    Ok(())
+}
+
+/// Pull `images` on a node ahead of the launch of its models
+pub async fn prefetch_images(
+    images: Vec<String>,
+    node_name: &str,
+) -> Result<PrefetchImagesResponse> {
+    let Some(addr) = get_node_name_from_hostname(node_name).await else {
+        return Err(format!("Node {} not found in DB", node_name).into());
+    };
+    let request = PrefetchImagesRequest {
+        images,
+        correlation_id: common::correlation::current_or_empty(),
+    };
+    Ok(crate::grpc::sender::nodeagent::send_prefetch_images_request(&addr, request).await?)
+}
+
+/// Run a canary probe command in a container of a workload on its node
+pub async fn exec_in_workload(
+    target: crate::update::ProbeTarget,
+    command: Vec<String>,
+) -> Result<ContainerExecResponse> {
+    let Some(addr) = get_node_name_from_hostname(&target.node).await else {
+        return Err(format!("Node {} not found in DB", target.node).into());
+    };
+    let request = ContainerExecRequest {
+        pod: target.pod,
+        container: target.container,
+        command,
+        caller: "actioncontroller".to_string(),
+        ..Default::default()
+    };
+    Ok(crate::grpc::sender::nodeagent::send_exec_request(&addr, request).await?)
+}
+
 /// Find a node by IP address from simplified node keys
 async fn get_node_name_from_hostname(hostname: &str) -> Option<String> {
     logd!(2, "Checking node keys in etcd...");
@@ -77,12 +126,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_workload_returns_ok() {
+        common::etcd::use_in_memory_store();
         let result = create_workload("test_model", "test_node").await;
         assert!(result.is_ok(), "create_workload() should return Ok");
     }
 
     #[tokio::test]
     async fn test_create_workload_invalid_scenario_should_fail() {
+        common::etcd::use_in_memory_store();
         let result = create_workload("", "").await; // Empty scenario = invalid
         assert!(
             result.is_ok(),
@@ -94,12 +145,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_restart_workload_returns_ok() {
+        common::etcd::use_in_memory_store();
         let result = restart_workload("test_model", "test_node").await;
         assert!(result.is_ok(), "restart_workload() should return Ok");
     }
 
     #[tokio::test]
     async fn test_restart_workload_nonexistent_should_fail() {
+        common::etcd::use_in_memory_store();
         let result = restart_workload("nonexistent_scenario", "test_node").await;
         assert!(
             result.is_ok(),
@@ -111,12 +164,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_start_workload_returns_ok() {
+        common::etcd::use_in_memory_store();
         let result = start_workload("test_model", "test_node").await;
         assert!(result.is_ok(), "start_workload() should return Ok");
     }
 
     #[tokio::test]
     async fn test_start_workload_nonexistent_should_fail() {
+        common::etcd::use_in_memory_store();
         let result = start_workload("nonexistent_model", "test_node").await;
         assert!(
             result.is_ok(),
@@ -128,12 +183,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_stop_workload_returns_ok() {
+        common::etcd::use_in_memory_store();
         let result = stop_workload("test_model", "test_node").await;
         assert!(result.is_ok(), "stop_workload() should return Ok");
     }
 
     #[tokio::test]
     async fn test_stop_workload_nonexistent_should_fail() {
+        common::etcd::use_in_memory_store();
         let result = stop_workload("nonexistent_model", "test_node").await;
         assert!(
             result.is_ok(),
@@ -0,0 +1,541 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Package updates with rolling and blue-green strategies
+//!
+//! The `strategy` of a Package spec decides how an update replaces its
+//! running models:
+//! * `recreate` restarts every model at once, as before strategies existed
+//! * `rolling` restarts one model at a time and passes the next one only
+//!   once the updated model runs
+//! * `blueGreen` launches a copy of every model under a `-green` name,
+//!   switches the models to the new version once all copies run, and
+//!   retires the copies
+//! * `canary` launches a copy of every model under a `-canary` name and
+//!   evaluates it for a bake time. A canary that keeps running within its
+//!   restart budget and passes its probe is promoted, the models switch to
+//!   the new version. Otherwise it is rolled back, the models keep their
+//!   previous version. Either way the copies are retired.
+//!
+//! A model passes its health gate when StateManager reports it running on
+//! consecutive reads within the `healthTimeout` of the strategy. While the
+//! update runs the package is reported as updating, with the step in
+//! progress as sub-state. An update stops at the first model that does not
+//! run, the models not reached yet keep their previous version.
+
+use common::nodeagent::fromapiserver::ContainerExecResponse;
+use common::spec::artifact::package::UpdateStrategy;
+use common::spec::k8s::pod::{LABEL_MODEL, LABEL_REPLICA, LABEL_STANDBY};
+use common::spec::k8s::Pod;
+use common::state_mapping::StateName;
+use common::statemanager::ModelState;
+use common::Result;
+use std::future::Future;
+use std::time::Duration;
+
+/// Suffix of the copies of the models run during a blue-green update
+pub const GREEN_SUFFIX: &str = "-green";
+
+/// Suffix of the copies of the models run during a canary update
+pub const CANARY_SUFFIX: &str = "-canary";
+
+/// Sub-states of a blue-green update
+pub const LAUNCHING_GREEN: &str = "blue-green: launching green";
+pub const SWITCHING: &str = "blue-green: switching";
+pub const RETIRING_GREEN: &str = "blue-green: retiring green";
+
+/// Sub-states of a canary update
+pub const LAUNCHING_CANARY: &str = "canary: launching";
+pub const PROMOTING: &str = "canary: promoting";
+pub const ROLLING_BACK: &str = "canary: rolling back";
+
+/// Interval between reads of the state of an updated model
+const HEALTH_POLL_MS: u64 = 500;
+
+/// Consecutive reads an updated model must be running to pass its gate
+const HEALTH_STABLE_READS: u32 = 3;
+
+/// Waits for updated models to run
+pub struct HealthGate {
+    timeout: Duration,
+    poll: Duration,
+}
+
+impl HealthGate {
+    pub fn new(timeout: Duration, poll: Duration) -> Self {
+        Self { timeout, poll }
+    }
+
+    /// Gate with the health timeout of an update strategy
+    pub fn for_strategy(strategy: &UpdateStrategy) -> Self {
+        Self::new(
+            Duration::from_secs(strategy.health_timeout),
+            Duration::from_millis(HEALTH_POLL_MS),
+        )
+    }
+
+    /// Wait until `read` reports the model running on consecutive reads
+    ///
+    /// A single running read may still be the state from before the update,
+    /// so the model has to stay running for a few polls.
+    pub async fn wait<F, Fut>(&self, model_name: &str, mut read: F) -> Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Option<ModelState>>,
+    {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let mut running = 0;
+        loop {
+            match read().await {
+                Some(ModelState::Running) => running += 1,
+                _ => running = 0,
+            }
+            if running >= HEALTH_STABLE_READS {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(format!(
+                    "model '{}' is not running after {}s",
+                    model_name,
+                    self.timeout.as_secs()
+                )
+                .into());
+            }
+            tokio::time::sleep(self.poll).await;
+        }
+    }
+}
+
+/// State of a model as stored by StateManager, `None` if it is unknown
+pub async fn model_state(model_name: &str) -> Option<ModelState> {
+    let state = common::etcd::get(&format!("/model/{}/state", model_name))
+        .await
+        .ok()?;
+    ModelState::migrate(&state)
+}
+
+/// Containers the MonitoringServer stores, with the node they run on
+const CONTAINERS_PREFIX: &str = "/piccolo/metrics/containers/";
+
+/// State of the instance of the Pod `pod_name` on `node`, `None` if none of
+/// its containers is reported there
+///
+/// `/model/{pod}/state` is the state of the model wherever it runs, so
+/// while a model moves the instance still running on its old node would
+/// pass for the new one. The containers of the Pod are the ones on `node`
+/// whose model, replica and standby annotations are the ones of the Pod.
+pub async fn instance_state(pod_name: &str, node: &str) -> Option<ModelState> {
+    let pod_yaml = common::etcd::get(&format!("Pod/{}", pod_name)).await.ok()?;
+    let pod: Pod = serde_yaml::from_str(&pod_yaml).ok()?;
+    let annotations = pod.piccolo_annotations();
+    let containers = common::etcd::get_all_with_prefix(CONTAINERS_PREFIX)
+        .await
+        .ok()?;
+
+    let mut running = None;
+    for (_, container) in containers {
+        let Ok(container) = serde_json::from_str::<serde_json::Value>(&container) else {
+            continue;
+        };
+        let of_pod = [LABEL_MODEL, LABEL_REPLICA, LABEL_STANDBY]
+            .iter()
+            .all(|key| {
+                container["annotation"][key].as_str() == annotations.get(*key).map(String::as_str)
+            });
+        if container["node"].as_str() != Some(node) || !of_pod {
+            continue;
+        }
+        let status = container["state"]["Status"].as_str().unwrap_or_default();
+        running = Some(running.unwrap_or(true) && status.eq_ignore_ascii_case("running"));
+    }
+    running.map(|running| {
+        if running {
+            ModelState::Running
+        } else {
+            ModelState::Created
+        }
+    })
+}
+
+/// Evaluates the canary of an update for its bake time
+pub struct CanaryBake {
+    bake: Duration,
+    poll: Duration,
+    max_restarts: u32,
+}
+
+impl CanaryBake {
+    pub fn new(bake: Duration, poll: Duration, max_restarts: u32) -> Self {
+        Self {
+            bake,
+            poll,
+            max_restarts,
+        }
+    }
+
+    /// Bake with the canary settings of an update strategy
+    pub fn for_strategy(strategy: &UpdateStrategy) -> Self {
+        Self::new(
+            Duration::from_secs(strategy.canary.bake_time),
+            Duration::from_millis(HEALTH_POLL_MS),
+            strategy.canary.max_restarts,
+        )
+    }
+
+    /// Sub-state of a canary update while it bakes
+    pub fn sub_state(&self) -> String {
+        format!("canary: baking for {}s", self.bake.as_secs())
+    }
+
+    /// Poll the canary models and the probe until the bake time is over
+    ///
+    /// A canary model leaving running counts as a restart. The canary fails
+    /// once a model restarts more often than allowed, the probe fails, or a
+    /// model is not running when the bake time is over.
+    pub async fn evaluate<R, RFut, P, PFut>(
+        &self,
+        canaries: &[String],
+        mut read: R,
+        mut probe: P,
+    ) -> Result<()>
+    where
+        R: FnMut(String) -> RFut,
+        RFut: Future<Output = Option<ModelState>>,
+        P: FnMut() -> PFut,
+        PFut: Future<Output = Result<()>>,
+    {
+        let deadline = tokio::time::Instant::now() + self.bake;
+        let mut restarts = vec![0u32; canaries.len()];
+        let mut running = vec![true; canaries.len()];
+        loop {
+            for (index, canary) in canaries.iter().enumerate() {
+                let now_running = read(canary.clone()).await == Some(ModelState::Running);
+                if running[index] && !now_running {
+                    restarts[index] += 1;
+                    if restarts[index] > self.max_restarts {
+                        return Err(format!(
+                            "canary '{}' restarted {} time(s), {} allowed",
+                            canary, restarts[index], self.max_restarts
+                        )
+                        .into());
+                    }
+                }
+                running[index] = now_running;
+            }
+            probe()
+                .await
+                .map_err(|e| format!("canary probe failed: {}", e))?;
+
+            if tokio::time::Instant::now() >= deadline {
+                return match canaries.iter().zip(&running).find(|(_, up)| !**up) {
+                    Some((canary, _)) => Err(format!(
+                        "canary '{}' is not running after its bake time",
+                        canary
+                    )
+                    .into()),
+                    None => Ok(()),
+                };
+            }
+            tokio::time::sleep(self.poll).await;
+        }
+    }
+}
+
+/// Container of a canary a probe runs in
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeTarget {
+    pub pod: String,
+    pub container: String,
+    pub node: String,
+}
+
+/// Probe target of the copy of a model running on `node`, its first
+/// container
+pub fn probe_target(copy_yaml: &str, node: &str) -> Result<ProbeTarget> {
+    let pod: Pod = serde_yaml::from_str(copy_yaml)?;
+    let container = pod
+        .get_spec()
+        .containers
+        .first()
+        .ok_or_else(|| format!("pod '{}' has no container", pod.get_name()))?;
+    Ok(ProbeTarget {
+        pod: pod.get_name(),
+        container: container.get_name().to_string(),
+        node: node.to_string(),
+    })
+}
+
+/// Run the probe command of a canary in every canary, no command always
+/// passes
+///
+/// The command comes from the artifact, so it is only run inside the
+/// canaries, through `exec` on their nodes, never on this host.
+pub async fn run_probe<E, EFut>(command: &[String], targets: &[ProbeTarget], exec: E) -> Result<()>
+where
+    E: Fn(ProbeTarget, Vec<String>) -> EFut,
+    EFut: Future<Output = Result<ContainerExecResponse>>,
+{
+    if command.is_empty() {
+        return Ok(());
+    }
+    for target in targets {
+        let output = exec(target.clone(), command.to_vec()).await?;
+        if output.exit_code != 0 {
+            return Err(format!(
+                "'{}' exited with {} in {}: {}",
+                command[0],
+                output.exit_code,
+                target.pod,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Name of the copy of a model run during an update, see [`copy_pod`]
+pub fn copy_name(model_name: &str, suffix: &str) -> String {
+    format!("{}{}", model_name, suffix)
+}
+
+/// Pod yaml of the copy of a model run during a blue-green or canary update
+///
+/// The copy reports its containers as a model of its own name, so its state
+/// is tracked apart from the model it replaces.
+pub fn copy_pod(pod_yaml: &str, suffix: &str) -> Result<String> {
+    let mut pod: Pod = serde_yaml::from_str(pod_yaml)?;
+    pod.rename(&copy_name(&pod.get_name(), suffix));
+    Ok(serde_yaml::to_string(&pod)?)
+}
+
+/// Sub-state of a rolling update restarting the `step`th of `total` models
+pub fn rolling_step(step: usize, total: usize, model_name: &str) -> String {
+    format!("rolling {}/{}: {}", step, total, model_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn gate(timeout_ms: u64) -> HealthGate {
+        HealthGate::new(Duration::from_millis(timeout_ms), Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_health_gate_needs_consecutive_running_reads() {
+        let reads = AtomicUsize::new(0);
+        // Running before the restart, dead while restarting, then running
+        let states = [
+            Some(ModelState::Running),
+            Some(ModelState::Dead),
+            None,
+            Some(ModelState::Running),
+            Some(ModelState::Running),
+            Some(ModelState::Running),
+        ];
+        let result = gate(1000)
+            .wait("m", || {
+                let read = reads.fetch_add(1, Ordering::SeqCst);
+                async move { states[read.min(states.len() - 1)] }
+            })
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(reads.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_health_gate_times_out() {
+        let result = gate(20)
+            .wait("crashing", || async { Some(ModelState::Exited) })
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("'crashing' is not running"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_instance_state_is_the_one_on_the_node() {
+        common::etcd::use_in_memory_store();
+        let podspec = serde_yaml::from_str("containers:\n  - name: core\n    image: core\n");
+        let pod = Pod::new("instance-core", podspec.unwrap());
+        common::etcd::put("Pod/instance-core", &serde_yaml::to_string(&pod).unwrap())
+            .await
+            .unwrap();
+        let container = |id: &str, node: &str, model: &str, status: &str| {
+            let key = format!("{CONTAINERS_PREFIX}{id}");
+            let container = serde_json::json!({
+                "id": id,
+                "node": node,
+                "annotation": { LABEL_MODEL: model },
+                "state": { "Status": status },
+            })
+            .to_string();
+            async move { common::etcd::put(&key, &container).await.unwrap() }
+        };
+        container("instance-old", "instance-from", "instance-core", "running").await;
+        container("instance-new", "instance-to", "instance-core", "created").await;
+        container("instance-other", "instance-to", "other-core", "running").await;
+
+        assert_eq!(
+            instance_state("instance-core", "instance-from").await,
+            Some(ModelState::Running)
+        );
+        assert_eq!(
+            instance_state("instance-core", "instance-to").await,
+            Some(ModelState::Created)
+        );
+        assert_eq!(instance_state("instance-core", "instance-idle").await, None);
+
+        container("instance-new", "instance-to", "instance-core", "running").await;
+        assert_eq!(
+            instance_state("instance-core", "instance-to").await,
+            Some(ModelState::Running)
+        );
+    }
+
+    #[test]
+    fn test_copy_pod_is_a_model_of_its_own() {
+        let pod = r#"
+apiVersion: v1
+kind: Pod
+metadata:
+  name: hello-core
+  labels:
+    io.piccolo.model: hello-core
+spec:
+  containers:
+    - name: hello
+      image: hello:2
+"#;
+        let green: Pod = serde_yaml::from_str(&copy_pod(pod, GREEN_SUFFIX).unwrap()).unwrap();
+        assert_eq!(green.get_name(), "hello-core-green");
+        assert_eq!(green.get_labels()[LABEL_MODEL], "hello-core-green");
+        assert_eq!(green.get_spec().get_image(), Some("hello:2"));
+        assert!(copy_pod("not: [a pod", CANARY_SUFFIX).is_err());
+    }
+
+    fn bake(bake_ms: u64, max_restarts: u32) -> CanaryBake {
+        CanaryBake::new(
+            Duration::from_millis(bake_ms),
+            Duration::from_millis(1),
+            max_restarts,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_canary_bake_counts_restarts() {
+        let reads = AtomicUsize::new(0);
+        // Restarts once, then keeps running
+        let read = |_: String| {
+            let read = reads.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match read {
+                    1 => Some(ModelState::Dead),
+                    _ => Some(ModelState::Running),
+                }
+            }
+        };
+        let canaries = vec!["m-canary".to_string()];
+
+        let result = bake(20, 0)
+            .evaluate(&canaries, read, || async { Ok(()) })
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains("restarted 1 time(s), 0 allowed"),
+            "{}",
+            error
+        );
+
+        reads.store(0, Ordering::SeqCst);
+        let result = bake(20, 1)
+            .evaluate(&canaries, read, || async { Ok(()) })
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_canary_bake_fails_on_probe_or_down_canary() {
+        let canaries = vec!["m-canary".to_string()];
+        let result = bake(1000, 0)
+            .evaluate(
+                &canaries,
+                |_| async { Some(ModelState::Running) },
+                || async { Err("unhealthy".into()) },
+            )
+            .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "canary probe failed: unhealthy"
+        );
+
+        let result = bake(0, 5)
+            .evaluate(&canaries, |_| async { None }, || async { Ok(()) })
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("'m-canary' is not running"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_run_probe_runs_in_every_canary() {
+        let target = |pod: &str| ProbeTarget {
+            pod: pod.to_string(),
+            container: "app".to_string(),
+            node: "HPC".to_string(),
+        };
+        let targets = vec![target("a-canary"), target("b-canary")];
+        let execs = std::sync::Mutex::new(Vec::new());
+        let exec = |target: ProbeTarget, command: Vec<String>| {
+            execs.lock().unwrap().push((target.pod.clone(), command));
+            async move {
+                Ok(ContainerExecResponse {
+                    exit_code: if target.pod == "b-canary" { 3 } else { 0 },
+                    stderr: b"not ready\n".to_vec(),
+                    ..Default::default()
+                })
+            }
+        };
+
+        assert!(run_probe(&[], &targets, exec).await.is_ok());
+        assert!(execs.lock().unwrap().is_empty());
+
+        let check = vec!["check".to_string(), "-q".to_string()];
+        let error = run_probe(&check, &targets, exec).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'check' exited with 3 in b-canary: not ready"
+        );
+        assert_eq!(
+            *execs.lock().unwrap(),
+            vec![
+                ("a-canary".to_string(), check.clone()),
+                ("b-canary".to_string(), check)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probe_target_is_the_first_container() {
+        let copy = copy_pod(
+            "apiVersion: v1\nkind: Pod\nmetadata:\n  name: m\nspec:\n  containers:\n    - name: app\n      image: app\n    - name: sidecar\n      image: sidecar\n",
+            CANARY_SUFFIX,
+        )
+        .unwrap();
+        assert_eq!(
+            probe_target(&copy, "HPC").unwrap(),
+            ProbeTarget {
+                pod: "m-canary".to_string(),
+                container: "app".to_string(),
+                node: "HPC".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rolling_step() {
+        assert_eq!(rolling_step(2, 3, "m2"), "rolling 2/3: m2");
+    }
+}
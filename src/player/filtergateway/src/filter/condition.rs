@@ -0,0 +1,455 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Condition evaluation engine for scenario filters
+//!
+//! A scenario condition, including its allOf/anyOf/not blocks, is compiled
+//! into a [`ConditionExpr`] tree whose leaves compare one field of a signal
+//! of a vehicle bus, see [`crate::vehicle::source`], against a target value.
+//! The latest value of every referenced field is kept in a [`SignalCache`],
+//! so a condition spanning several topics can be re-evaluated whenever any
+//! of them changes.
+
+use crate::vehicle::dds::DdsData;
+use crate::vehicle::source::SourceKind;
+use common::spec::artifact::scenario::{Condition, ConditionKind};
+use common::Result;
+use std::collections::HashMap;
+
+/// Comparison operator of a condition leaf
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+impl CompareOp {
+    /// Parse the `express` field of a scenario condition
+    pub fn parse(express: &str) -> Result<Self> {
+        match express.to_lowercase().as_str() {
+            "eq" => Ok(CompareOp::Eq),
+            "ne" => Ok(CompareOp::Ne),
+            "lt" => Ok(CompareOp::Lt),
+            "le" => Ok(CompareOp::Le),
+            "gt" => Ok(CompareOp::Gt),
+            "ge" => Ok(CompareOp::Ge),
+            "contains" => Ok(CompareOp::Contains),
+            "startswith" => Ok(CompareOp::StartsWith),
+            "endswith" => Ok(CompareOp::EndsWith),
+            _ => Err("wrong expression in condition".into()),
+        }
+    }
+
+    /// Compare a received value against the target value
+    ///
+    /// `eq` and `ne` compare numerically when both sides are numbers and
+    /// case-insensitively otherwise. Ordering operators require numbers and
+    /// string matching operators ignore case.
+    pub fn compare(&self, current: &str, target: &str) -> Result<bool> {
+        match self {
+            CompareOp::Contains | CompareOp::StartsWith | CompareOp::EndsWith => {
+                let current = current.to_lowercase();
+                let target = target.to_lowercase();
+                Ok(match self {
+                    CompareOp::Contains => current.contains(&target),
+                    CompareOp::StartsWith => current.starts_with(&target),
+                    _ => current.ends_with(&target),
+                })
+            }
+            CompareOp::Eq | CompareOp::Ne => {
+                let equal = match (current.parse::<f64>(), target.parse::<f64>()) {
+                    (Ok(c), Ok(t)) => c == t,
+                    _ => current.to_lowercase() == target.to_lowercase(),
+                };
+                Ok(equal == (*self == CompareOp::Eq))
+            }
+            _ => {
+                let target_v = target
+                    .parse::<f64>()
+                    .map_err(|_| "target_value parse error")?;
+                let current_v = current
+                    .parse::<f64>()
+                    .map_err(|_| "field_value parse error")?;
+                Ok(match self {
+                    CompareOp::Lt => current_v < target_v,
+                    CompareOp::Le => current_v <= target_v,
+                    CompareOp::Gt => current_v > target_v,
+                    _ => current_v >= target_v,
+                })
+            }
+        }
+    }
+}
+
+/// Compiled scenario condition
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionExpr {
+    /// Compare `field` of the signal `topic` against `value`
+    ///
+    /// The topic is named after its source, see [`SourceKind::qualify`].
+    Compare {
+        topic: String,
+        field: String,
+        op: CompareOp,
+        value: String,
+    },
+    /// True when every child is true
+    AllOf(Vec<ConditionExpr>),
+    /// True when at least one child is true
+    AnyOf(Vec<ConditionExpr>),
+    /// Negation of the child
+    Not(Box<ConditionExpr>),
+}
+
+impl ConditionExpr {
+    /// Compile a scenario condition
+    ///
+    /// ### Errors
+    /// * the condition is malformed
+    /// * an operand is not a signal of a known source
+    pub fn from_condition(condition: &Condition) -> Result<Self> {
+        condition.validate()?;
+        Self::compile(condition)
+    }
+
+    fn compile(condition: &Condition) -> Result<Self> {
+        let compile_all = |children: &[Condition]| -> Result<Vec<ConditionExpr>> {
+            children.iter().map(Self::compile).collect()
+        };
+        match condition.kind() {
+            ConditionKind::AllOf(children) => {
+                return Ok(ConditionExpr::AllOf(compile_all(children)?))
+            }
+            ConditionKind::AnyOf(children) => {
+                return Ok(ConditionExpr::AnyOf(compile_all(children)?))
+            }
+            ConditionKind::Not(child) => {
+                return Ok(ConditionExpr::Not(Box::new(Self::compile(child)?)))
+            }
+            ConditionKind::Compare => {}
+        }
+
+        let operand_type = condition.get_operand_type();
+        let Some(source) = SourceKind::from_operand_type(&operand_type) else {
+            return Err(format!("unsupported operand type '{}'", operand_type).into());
+        };
+
+        Ok(ConditionExpr::Compare {
+            topic: source.qualify(&condition.get_operand_value())?,
+            field: condition.get_operand_name(),
+            op: CompareOp::parse(&condition.get_express())?,
+            value: condition.get_value(),
+        })
+    }
+
+    /// Signals referenced anywhere in the expression, without duplicates
+    pub fn topics(&self) -> Vec<String> {
+        let mut topics = Vec::new();
+        self.collect_topics(&mut topics);
+        topics
+    }
+
+    fn collect_topics(&self, topics: &mut Vec<String>) {
+        match self {
+            ConditionExpr::Compare { topic, .. } => {
+                if !topics.contains(topic) {
+                    topics.push(topic.clone());
+                }
+            }
+            ConditionExpr::AllOf(children) | ConditionExpr::AnyOf(children) => {
+                for child in children {
+                    child.collect_topics(topics);
+                }
+            }
+            ConditionExpr::Not(child) => child.collect_topics(topics),
+        }
+    }
+
+    /// Evaluate the expression against the latest received signals
+    ///
+    /// ### Returns
+    /// * `Ok(Some(result))` - the expression could be decided
+    /// * `Ok(None)` - a signal needed for the decision has not been received yet
+    pub fn evaluate(&self, signals: &SignalCache) -> Result<Option<bool>> {
+        match self {
+            ConditionExpr::Compare {
+                topic,
+                field,
+                op,
+                value,
+            } => match signals.get(topic, field) {
+                Some(current) => Ok(Some(op.compare(current, value)?)),
+                None => Ok(None),
+            },
+            ConditionExpr::AllOf(children) => {
+                let mut undecided = false;
+                for child in children {
+                    match child.evaluate(signals)? {
+                        Some(false) => return Ok(Some(false)),
+                        Some(true) => {}
+                        None => undecided = true,
+                    }
+                }
+                Ok(if undecided { None } else { Some(true) })
+            }
+            ConditionExpr::AnyOf(children) => {
+                let mut undecided = false;
+                for child in children {
+                    match child.evaluate(signals)? {
+                        Some(true) => return Ok(Some(true)),
+                        Some(false) => {}
+                        None => undecided = true,
+                    }
+                }
+                Ok(if undecided { None } else { Some(false) })
+            }
+            ConditionExpr::Not(child) => Ok(child.evaluate(signals)?.map(|v| !v)),
+        }
+    }
+}
+
+/// Latest field values received on each signal
+#[derive(Debug, Default, Clone)]
+pub struct SignalCache {
+    topics: HashMap<String, HashMap<String, String>>,
+}
+
+impl SignalCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the fields of a received DDS sample
+    pub fn update(&mut self, data: &DdsData) {
+        let fields = self.topics.entry(data.name.clone()).or_default();
+        for (key, value) in &data.fields {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+
+    /// Latest value of `field` on `topic`, if any
+    pub fn get(&self, topic: &str, field: &str) -> Option<&String> {
+        self.topics.get(topic).and_then(|fields| fields.get(field))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds(topic: &str, field: &str, value: &str) -> DdsData {
+        let mut fields = HashMap::new();
+        fields.insert(field.to_string(), value.to_string());
+        DdsData {
+            name: topic.to_string(),
+            value: value.to_string(),
+            fields,
+        }
+    }
+
+    fn compare(topic: &str, field: &str, op: CompareOp, value: &str) -> ConditionExpr {
+        ConditionExpr::Compare {
+            topic: topic.to_string(),
+            field: field.to_string(),
+            op,
+            value: value.to_string(),
+        }
+    }
+
+    fn condition(express: &str, operand_type: &str) -> Condition {
+        let yaml = format!(
+            r#"
+express: {express}
+value: "parking"
+operands:
+  type: {operand_type}
+  name: gear
+  value: /rt/piccolo/Gear_State
+"#
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn condition_with(operand_type: &str, value: &str) -> Condition {
+        let yaml = format!(
+            "{{express: eq, value: '1', operands: {{type: {operand_type}, name: data, value: {value}}}}}"
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn test_compare_op_parse() {
+        assert_eq!(CompareOp::parse("eq").unwrap(), CompareOp::Eq);
+        assert_eq!(CompareOp::parse("NE").unwrap(), CompareOp::Ne);
+        assert_eq!(CompareOp::parse("ge").unwrap(), CompareOp::Ge);
+        assert_eq!(
+            CompareOp::parse("startsWith").unwrap(),
+            CompareOp::StartsWith
+        );
+        assert!(CompareOp::parse("between").is_err());
+    }
+
+    #[test]
+    fn test_compare_values() {
+        assert!(CompareOp::Eq.compare("Parking", "parking").unwrap());
+        assert!(CompareOp::Eq.compare("10.0", "10").unwrap());
+        assert!(CompareOp::Ne.compare("drive", "parking").unwrap());
+        assert!(CompareOp::Lt.compare("9.5", "10").unwrap());
+        assert!(CompareOp::Le.compare("10", "10").unwrap());
+        assert!(CompareOp::Gt.compare("11", "10").unwrap());
+        assert!(!CompareOp::Ge.compare("9", "10").unwrap());
+        assert!(CompareOp::Contains.compare("Cell Fault", "fault").unwrap());
+        assert!(CompareOp::EndsWith
+            .compare("BulkCharging", "charging")
+            .unwrap());
+        assert!(!CompareOp::StartsWith.compare("drive", "park").unwrap());
+        assert_eq!(
+            CompareOp::Gt.compare("abc", "10").unwrap_err().to_string(),
+            "field_value parse error"
+        );
+    }
+
+    #[test]
+    fn test_from_condition() {
+        let expr = ConditionExpr::from_condition(&condition("eq", "DDS")).unwrap();
+        assert_eq!(
+            expr,
+            compare("/rt/piccolo/Gear_State", "gear", CompareOp::Eq, "parking")
+        );
+        assert_eq!(expr.topics(), vec!["/rt/piccolo/Gear_State".to_string()]);
+
+        assert!(ConditionExpr::from_condition(&condition("eq", "pod")).is_err());
+        assert!(ConditionExpr::from_condition(&condition("like", "DDS")).is_err());
+    }
+
+    #[test]
+    fn test_conditions_on_other_buses() {
+        let condition: Condition = serde_yaml::from_str(
+            r#"
+allOf:
+  - express: gt
+    value: "100"
+    operands: {type: CAN, name: byte0, value: "0x1a0"}
+  - express: eq
+    value: "1"
+    operands: {type: SOME/IP, name: byte0, value: 0x1234/0x8001}
+  - express: eq
+    value: "on"
+    operands: {type: gRPC, name: state, value: test/door}
+"#,
+        )
+        .unwrap();
+
+        let expr = ConditionExpr::from_condition(&condition).unwrap();
+        assert_eq!(
+            expr.topics(),
+            vec![
+                "CAN:0x1A0".to_string(),
+                "SOMEIP:0x1234/0x8001".to_string(),
+                "GRPC:test/door".to_string()
+            ]
+        );
+
+        let mut signals = SignalCache::new();
+        signals.update(&crate::vehicle::source::can::frame_data(0x1A0, &[120]));
+        signals.update(&crate::vehicle::source::someip::message_data(
+            0x1234,
+            0x8001,
+            &[1],
+        ));
+        assert_eq!(expr.evaluate(&signals).unwrap(), None);
+        signals.update(&dds("GRPC:test/door", "state", "on"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(true));
+
+        let bad_id = condition_with("CAN", "speed");
+        assert!(ConditionExpr::from_condition(&bad_id).is_err());
+    }
+
+    #[test]
+    fn test_from_composite_condition() {
+        let condition: Condition = serde_yaml::from_str(
+            r#"
+anyOf:
+  - express: startsWith
+    value: "park"
+    operands: {type: DDS, name: gear, value: gear_topic}
+  - not:
+      express: ge
+      value: "5"
+      operands: {type: DDS, name: speed, value: speed_topic}
+"#,
+        )
+        .unwrap();
+
+        let expr = ConditionExpr::from_condition(&condition).unwrap();
+        assert_eq!(
+            expr,
+            ConditionExpr::AnyOf(vec![
+                compare("gear_topic", "gear", CompareOp::StartsWith, "park"),
+                ConditionExpr::Not(Box::new(compare(
+                    "speed_topic",
+                    "speed",
+                    CompareOp::Ge,
+                    "5"
+                ))),
+            ])
+        );
+
+        let mut signals = SignalCache::new();
+        signals.update(&dds("speed_topic", "speed", "12"));
+        signals.update(&dds("gear_topic", "gear", "Drive"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(false));
+        signals.update(&dds("gear_topic", "gear", "PARKING"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(true));
+    }
+
+    #[test]
+    fn test_evaluate_waits_for_signals() {
+        let expr = ConditionExpr::AllOf(vec![
+            compare("gear", "state", CompareOp::Eq, "parking"),
+            compare("battery", "soc", CompareOp::Gt, "20"),
+        ]);
+        let mut signals = SignalCache::new();
+        assert_eq!(expr.evaluate(&signals).unwrap(), None);
+
+        signals.update(&dds("gear", "state", "parking"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), None);
+
+        signals.update(&dds("battery", "soc", "80"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(true));
+
+        signals.update(&dds("gear", "state", "drive"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(false));
+    }
+
+    #[test]
+    fn test_evaluate_any_of_and_not() {
+        let expr = ConditionExpr::AnyOf(vec![
+            compare("gear", "state", CompareOp::Eq, "parking"),
+            ConditionExpr::Not(Box::new(compare("speed", "value", CompareOp::Gt, "0"))),
+        ]);
+        let mut signals = SignalCache::new();
+
+        // One decided true child is enough
+        signals.update(&dds("gear", "state", "parking"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(true));
+
+        signals.update(&dds("gear", "state", "drive"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), None);
+
+        signals.update(&dds("speed", "value", "30"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(false));
+
+        signals.update(&dds("speed", "value", "0"));
+        assert_eq!(expr.evaluate(&signals).unwrap(), Some(true));
+        assert_eq!(expr.topics(), vec!["gear".to_string(), "speed".to_string()]);
+    }
+}
@@ -2,6 +2,8 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
+pub mod condition;
+
 use crate::grpc::sender::actioncontroller::FilterGatewaySender;
 use crate::grpc::sender::statemanager::StateManagerSender;
 use crate::vehicle::dds::DdsData;
@@ -9,6 +11,7 @@ use common::logd;
 use common::spec::artifact::Scenario;
 use common::statemanager::{ResourceType, StateChange};
 use common::Result;
+use condition::{ConditionExpr, SignalCache};
 // use dust_dds::infrastructure::wait_set::Condition;
 // use std::sync::Arc;
 // use tokio::sync::{mpsc, Mutex};
@@ -26,6 +29,12 @@ pub struct Filter {
     sender: FilterGatewaySender,
     /// gRPC sender for state manager
     state_sender: StateManagerSender,
+    /// Compiled scenario condition, `None` if it could not be compiled
+    expression: Option<ConditionExpr>,
+    /// Latest values of the signals referenced by the condition
+    signals: SignalCache,
+    /// Result of the previous evaluation, used to fire only on a rising edge
+    last_result: bool,
 }
 
 #[allow(dead_code)]
@@ -48,19 +57,44 @@ impl Filter {
         is_active: bool,
         sender: FilterGatewaySender,
     ) -> Self {
+        let expression = scenario.get_conditions().and_then(|condition| {
+            ConditionExpr::from_condition(&condition)
+                .map_err(|e| {
+                    logd!(
+                        5,
+                        "Invalid condition in scenario {}: {:?}",
+                        scenario_name,
+                        e
+                    )
+                })
+                .ok()
+        });
+
         Self {
             scenario_name,
             scenario,
             is_active,
             sender,
             state_sender: StateManagerSender::new(),
+            expression,
+            signals: SignalCache::new(),
+            last_result: false,
         }
     }
 
+    /// DDS topics the scenario condition depends on
+    pub fn topics(&self) -> Vec<String> {
+        self.expression
+            .as_ref()
+            .map(|expr| expr.topics())
+            .unwrap_or_default()
+    }
+
     /// Check if scenario conditions are met
     ///
-    /// Evaluates if the received vehicle data meets the scenario conditions.
-    /// If conditions are met, triggers an action through ActionController.
+    /// Records the received vehicle data and re-evaluates the scenario
+    /// condition. When the condition turns from unmet to met, notifies
+    /// StateManager and triggers an action through ActionController.
     ///
     /// # Arguments
     ///
@@ -73,144 +107,127 @@ impl Filter {
         use std::time::Instant;
         let start = Instant::now();
 
-        let condition = self.scenario.get_conditions().unwrap();
-        let topic = condition.get_operand_value();
-        let value_name = condition.get_operand_name();
-        let target_value = condition.get_value();
-        let express = condition.get_express();
+        let expression = match &self.expression {
+            Some(expr) => expr,
+            None => return Err("wrong expression in condition".into()),
+        };
 
-        logd!(1,
-        "Checking condition for scenario: {}\nTopic: {}\nTarget Value: {}\nValue Name: {}\nExpression: {}\n",
-        self.scenario_name, topic, target_value, value_name, express
-    );
+        logd!(
+            1,
+            "Checking condition for scenario: {}\nTopic: {}\nExpression: {:?}\n",
+            self.scenario_name,
+            data.name,
+            expression
+        );
 
-        if !data.name.eq(&topic) {
+        if !expression.topics().contains(&data.name) {
             let elapsed = start.elapsed();
             logd!(1, "meet_scenario_condition: elapsed = {:?}", elapsed);
             return Err("data topic does not match".into());
         }
 
-        let field_value = match data.fields.get(&value_name) {
-            Some(v) => v,
-            None => {
-                let elapsed = start.elapsed();
-                logd!(3, "meet_scenario_condition: elapsed = {:?}", elapsed);
-                return Err(format!("field '{}' not found in data.fields", value_name).into());
-            }
-        };
+        self.signals.update(data);
+        // The evaluation error is not Send, it must not live across the await below
+        let check = {
+            let result = expression.evaluate(&self.signals);
 
-        let check: bool = match express.as_str() {
-            "eq" => target_value.to_lowercase() == field_value.to_lowercase(),
-            "lt" => {
-                let target_v = target_value
-                    .parse::<f32>()
-                    .map_err(|_| "target_value parse error")?;
-                let current_v = field_value
-                    .parse::<f32>()
-                    .map_err(|_| "field_value parse error")?;
-                current_v < target_v
-            }
-            "le" => {
-                let target_v = target_value
-                    .parse::<f32>()
-                    .map_err(|_| "target_value parse error")?;
-                let current_v = field_value
-                    .parse::<f32>()
-                    .map_err(|_| "field_value parse error")?;
-                current_v <= target_v
-            }
-            "ge" => {
-                let target_v = target_value
-                    .parse::<f32>()
-                    .map_err(|_| "target_value parse error")?;
-                let current_v = field_value
-                    .parse::<f32>()
-                    .map_err(|_| "field_value parse error")?;
-                current_v >= target_v
-            }
-            "gt" => {
-                let target_v = target_value
-                    .parse::<f32>()
-                    .map_err(|_| "target_value parse error")?;
-                let current_v = field_value
-                    .parse::<f32>()
-                    .map_err(|_| "field_value parse error")?;
-                current_v > target_v
-            }
-            _ => {
-                let elapsed = start.elapsed();
-                logd!(3, "meet_scenario_condition: elapsed = {:?}", elapsed);
-                return Err("wrong expression in condition".into());
+            let elapsed = start.elapsed();
+            logd!(1, "meet_scenario_condition: elapsed = {:?}", elapsed);
+
+            match result {
+                Ok(Some(check)) => check,
+                // Some referenced signals have not been received yet
+                Ok(None) => false,
+                Err(e) => {
+                    self.last_result = false;
+                    return Err(e);
+                }
             }
         };
 
-        let elapsed = start.elapsed();
-        logd!(1, "meet_scenario_condition: elapsed = {:?}", elapsed);
-
-        if check {
-            logd!(1, "Condition met for scenario: {}", self.scenario_name);
-            logd!(1, "🔄 SCENARIO STATE TRANSITION: FilterGateway Processing");
-            logd!(1, "   📋 Scenario: {}", self.scenario_name);
-            logd!(1, "   🔄 State Change: idle → waiting");
-            logd!(1, "   🔍 Reason: Scenario condition satisfied");
-
-            // 🔍 COMMENT 1: FilterGateway condition registration
-            // When scenario condition is met, FilterGateway triggers ActionController
-            // via gRPC call. This initiates the scenario processing workflow.
-            // The ActionController will then handle state changes with StateManager.
-
-            // Send state change to StateManager: waiting -> satisfied
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos() as i64;
-
-            let state_change = StateChange {
-                resource_type: ResourceType::Scenario as i32,
-                resource_name: self.scenario_name.clone(),
-                current_state: "waiting".to_string(),
-                target_state: "satisfied".to_string(),
-                transition_id: format!("filtergateway-condition-satisfied-{}", timestamp),
-                timestamp_ns: timestamp,
-                source: "filtergateway".to_string(),
-            };
-
-            logd!(1, "   📤 Sending StateChange to StateManager:");
-            logd!(1, "      • Resource Type: SCENARIO");
-            logd!(1, "      • Resource Name: {}", state_change.resource_name);
-            logd!(1, "      • Current State: {}", state_change.current_state);
-            logd!(1, "      • Target State: {}", state_change.target_state);
-            logd!(1, "      • Transition ID: {}", state_change.transition_id);
-            logd!(1, "      • Source: {}", state_change.source);
-
-            if let Err(e) = self
-                .state_sender
-                .clone()
-                .send_state_change(state_change)
-                .await
-            {
-                logd!(
-                    5,
-                    "   ❌ Failed to send state change to StateManager: {:?}",
-                    e
-                );
-            } else {
-                logd!(
-                    1,
-                    "   ✅ Successfully notified StateManager: scenario {} waiting → satisfied",
-                    self.scenario_name
-                );
-            }
+        let was_met = std::mem::replace(&mut self.last_result, check);
+        if !check {
+            return Err("cannot meet condition".into());
+        }
+        if was_met {
+            logd!(
+                1,
+                "Condition still met for scenario: {}, not triggering again",
+                self.scenario_name
+            );
+            return Ok(());
+        }
 
-            logd!(1, "   📤 Triggering ActionController via gRPC...");
-            self.sender
-                .trigger_action(self.scenario_name.clone())
-                .await?;
-            logd!(2, "   ✅ ActionController triggered successfully");
-            Ok(())
+        // Everything the activation causes is traced with one correlation ID
+        common::correlation::scope(common::correlation::new_id(), self.activate()).await
+    }
+
+    /// Report the satisfied condition and trigger the ActionController
+    async fn activate(&mut self) -> Result<()> {
+        logd!(1, "Condition met for scenario: {}", self.scenario_name);
+        logd!(1, "🔄 SCENARIO STATE TRANSITION: FilterGateway Processing");
+        logd!(1, "   📋 Scenario: {}", self.scenario_name);
+        logd!(1, "   🔄 State Change: waiting → satisfied");
+        logd!(1, "   🔍 Reason: Scenario condition satisfied");
+
+        // 🔍 COMMENT 1: FilterGateway condition registration
+        // When scenario condition is met, FilterGateway triggers ActionController
+        // via gRPC call. This initiates the scenario processing workflow.
+        // The ActionController will then handle state changes with StateManager.
+
+        // Send state change to StateManager: waiting -> satisfied
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i64;
+
+        let state_change = StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: self.scenario_name.clone(),
+            current_state: "waiting".to_string(),
+            target_state: "satisfied".to_string(),
+            transition_id: common::correlation::transition_id("filtergateway"),
+            timestamp_ns: timestamp,
+            source: "filtergateway".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+
+        logd!(1, "   📤 Sending StateChange to StateManager:");
+        logd!(1, "      • Resource Type: SCENARIO");
+        logd!(1, "      • Resource Name: {}", state_change.resource_name);
+        logd!(1, "      • Current State: {}", state_change.current_state);
+        logd!(1, "      • Target State: {}", state_change.target_state);
+        logd!(1, "      • Transition ID: {}", state_change.transition_id);
+        logd!(1, "      • Source: {}", state_change.source);
+
+        if let Err(e) = self
+            .state_sender
+            .clone()
+            .send_state_change(state_change)
+            .await
+        {
+            logd!(
+                5,
+                "   ❌ Failed to send state change to StateManager: {:?}",
+                e
+            );
         } else {
-            Err("cannot meet condition".into())
+            logd!(
+                1,
+                "   ✅ Successfully notified StateManager: scenario {} waiting → satisfied",
+                self.scenario_name
+            );
         }
+
+        logd!(1, "   📤 Triggering ActionController via gRPC...");
+        self.sender
+            .trigger_action(self.scenario_name.clone())
+            .await?;
+        logd!(2, "   ✅ ActionController triggered successfully");
+        Ok(())
     }
 
     /// Pause the filter processing
@@ -283,15 +300,9 @@ impl Filter {
             data.fields
         );
 
-        // Check if topic matches filter condition
-        let condition = match self.scenario.get_conditions() {
-            Some(c) => c,
-            None => return Ok(()), // No conditions case (already handled)
-        };
-
-        let topic = condition.get_operand_value();
-        if !data.name.eq(&topic) {
-            return Ok(()); // Ignore unrelated topics
+        // Check if topic is referenced by the filter condition
+        if !self.topics().contains(&data.name) {
+            return Ok(()); // Ignore unrelated topics and scenarios without conditions
         }
 
         // Perform condition check
@@ -17,7 +17,7 @@ use tonic::{Request, Response, Status};
 // Import the generated protobuf code from filtergateway.proto
 use common::filtergateway::{
     filter_gateway_connection_server::{FilterGatewayConnection, FilterGatewayConnectionServer},
-    HandleScenarioRequest, HandleScenarioResponse,
+    HandleScenarioRequest, HandleScenarioResponse, PushSignalRequest, PushSignalResponse,
 };
 
 /// FilterGateway gRPC service handler
@@ -110,6 +110,27 @@ impl FilterGatewayConnection for FilterGatewayReceiver {
             desc: "Successfully handled scenario".to_string(),
         }))
     }
+
+    async fn push_signal(
+        &self,
+        request: Request<PushSignalRequest>,
+    ) -> std::result::Result<Response<PushSignalResponse>, Status> {
+        if !common::setting::get_config().filtergateway.push_signals {
+            return Err(Status::failed_precondition(
+                "PushSignal is disabled, set filtergateway.push_signals to accept it",
+            ));
+        }
+        let req = request.into_inner();
+        logd!(1, "Received pushed signal {}", req.name);
+
+        crate::vehicle::source::push::push(&req.name, req.fields.into_iter().collect())
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(PushSignalResponse {
+            status: true,
+            desc: format!("Signal {} delivered", req.name),
+        }))
+    }
 }
 //Unit Test Cases
 #[cfg(test)]
@@ -49,7 +49,10 @@ impl FilterGatewaySender {
             .await
             .unwrap();
 
-        let request = TriggerActionRequest { scenario_name };
+        let request = TriggerActionRequest {
+            scenario_name,
+            correlation_id: common::correlation::current_or_empty(),
+        };
 
         client.trigger_action(request).await.map_err(|e| {
             common::logd!(5, "Failed to trigger action: {:?}", e);
@@ -70,7 +73,10 @@ mod tests {
         action_controller_connection_server::{
             ActionControllerConnection, ActionControllerConnectionServer,
         },
-        ReconcileRequest, ReconcileResponse, TriggerActionRequest, TriggerActionResponse,
+        AutostartNodeRequest, AutostartNodeResponse, DrainNodeRequest, DrainNodeResponse,
+        RebalanceRequest, RebalanceResponse, ReconcileRequest, ReconcileResponse,
+        RelocateNodeModelsRequest, RelocateNodeModelsResponse, TriggerActionRequest,
+        TriggerActionResponse,
     };
     use std::net::SocketAddr;
     use std::panic::{catch_unwind, AssertUnwindSafe};
@@ -117,6 +123,34 @@ mod tests {
                 acknowledged: true, // or false, depending on test needs
             }))
         }
+
+        async fn relocate_node_models(
+            &self,
+            _request: Request<RelocateNodeModelsRequest>,
+        ) -> std::result::Result<Response<RelocateNodeModelsResponse>, Status> {
+            Ok(Response::new(RelocateNodeModelsResponse::default()))
+        }
+
+        async fn drain_node(
+            &self,
+            _request: Request<DrainNodeRequest>,
+        ) -> std::result::Result<Response<DrainNodeResponse>, Status> {
+            Ok(Response::new(DrainNodeResponse::default()))
+        }
+
+        async fn autostart_node(
+            &self,
+            _request: Request<AutostartNodeRequest>,
+        ) -> std::result::Result<Response<AutostartNodeResponse>, Status> {
+            Ok(Response::new(AutostartNodeResponse::default()))
+        }
+
+        async fn rebalance(
+            &self,
+            _request: Request<RebalanceRequest>,
+        ) -> std::result::Result<Response<RebalanceResponse>, Status> {
+            Ok(Response::new(RebalanceResponse::default()))
+        }
     }
 
     async fn spawn_mock_server(
@@ -15,9 +15,9 @@
 
 use common::statemanager::{
     connect_server, state_manager_connection_client::StateManagerConnectionClient, ResourceType,
-    StateChange, StateChangeResponse,
+    StateChange, StateChangeResponse, VehicleMode, VehicleModeRequest, VehicleModeResponse,
 };
-use tonic::{Request, Status};
+use tonic::Status;
 
 /// StateManager gRPC client for FilterGateway component.
 ///
@@ -160,13 +160,46 @@ impl StateManagerSender {
 
         if let Some(client) = &mut self.client {
             // Send the state change message via gRPC
-            client.send_state_change(Request::new(state_change)).await
+            client
+                .send_state_change(common::auth::request(state_change))
+                .await
         } else {
             // This should never happen due to ensure_connected, but provide safety fallback
             Err(Status::unknown("Client not connected"))
         }
     }
 
+    /// Reports the vehicle operational mode received from the vehicle network.
+    ///
+    /// # Arguments
+    /// * `mode` - Current vehicle mode
+    /// * `source` - Identifier of the mode source, used for auditing
+    ///
+    /// # Returns
+    /// * `Result<tonic::Response<VehicleModeResponse>, Status>` - Mode recorded by StateManager
+    ///
+    /// # Errors
+    /// * `Status::unknown` - Connection failure or client not connected
+    /// * `Status::invalid_argument` - Unspecified mode or empty source
+    pub async fn set_vehicle_mode(
+        &mut self,
+        mode: VehicleMode,
+        source: &str,
+    ) -> Result<tonic::Response<VehicleModeResponse>, Status> {
+        self.ensure_connected().await?;
+
+        if let Some(client) = &mut self.client {
+            client
+                .set_vehicle_mode(common::auth::request(VehicleModeRequest {
+                    mode: mode as i32,
+                    source: source.to_string(),
+                }))
+                .await
+        } else {
+            Err(Status::unknown("Client not connected"))
+        }
+    }
+
     /// Reports policy enforcement decision to StateManager.
     ///
     /// This convenience method creates and sends a StateChange message indicating
@@ -202,6 +235,10 @@ impl StateManagerSender {
             transition_id: format!("policy-{}", policy_id),
             timestamp_ns: timestamp,
             source: "filtergateway".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
@@ -242,6 +279,10 @@ impl StateManagerSender {
             transition_id: format!("access-{}", access_control_id),
             timestamp_ns: timestamp,
             source: "filtergateway".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
@@ -282,6 +323,10 @@ impl StateManagerSender {
             transition_id: format!("violation-{}", violation_id),
             timestamp_ns: timestamp,
             source: "filtergateway".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
@@ -322,6 +367,10 @@ impl StateManagerSender {
             transition_id: format!("filter-{}", filter_id),
             timestamp_ns: timestamp,
             source: "filtergateway".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         self.send_state_change(state_change).await
@@ -370,9 +419,13 @@ mod tests {
             resource_name: "brake-system-scenario".to_string(),
             current_state: "requested".to_string(),
             target_state: "allowed".to_string(),
-            transition_id: format!("policy-decision-{}", timestamp),
+            transition_id: common::correlation::transition_id("filtergateway"),
             timestamp_ns: timestamp,
             source: "filtergateway".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         // Send the message and verify successful response
@@ -0,0 +1,192 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Injection of synthetic condition signals for end-to-end scenario tests
+//!
+//! Built with the `injector` feature only, for CI and bench setups without
+//! vehicle buses. The filtergateway then serves `POST /api/inject` on
+//! [`common::filtergateway::open_injector_server`], whose body sets fields of
+//! a signal of any operand type as if they were received from its bus:
+//!
+//! ```json
+//! {"type": "DDS", "signal": "/rt/piccolo/ADAS",
+//!  "fields": {"ADASObstacleDetectionIsWarning": "true"}}
+//! ```
+//!
+//! The sample goes to the filters directly, no bus is read and no
+//! subscription is needed, so a scenario is triggered exactly like by a real
+//! signal. Injecting the `mode` of the vehicle mode topic sets the vehicle
+//! mode as well.
+
+use crate::vehicle::dds::DdsData;
+use crate::vehicle::source::SourceKind;
+use axum::{http::StatusCode, response::IntoResponse, routing::post, Json, Router};
+use common::error::Error;
+use common::logd;
+use common::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::mpsc::Sender;
+
+/// Synthetic sample of a condition signal
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Injection {
+    /// Operand type of the signal, `DDS` when not given
+    #[serde(default = "default_type", rename = "type")]
+    pub r#type: String,
+    /// Operand value naming the signal, e.g. the DDS topic or the CAN id
+    pub signal: String,
+    /// Fields of the sample, compared with the operand names
+    pub fields: HashMap<String, String>,
+}
+
+fn default_type() -> String {
+    SourceKind::Dds.name().to_string()
+}
+
+fn filters() -> &'static Mutex<Option<Sender<DdsData>>> {
+    static FILTERS: OnceLock<Mutex<Option<Sender<DdsData>>>> = OnceLock::new();
+    FILTERS.get_or_init(|| Mutex::new(None))
+}
+
+/// Deliver the injected samples on `tx`, the channel of the filters
+pub fn register(tx: Sender<DdsData>) {
+    *filters().lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+}
+
+/// Send a synthetic sample to the filters registered by [`register`]
+///
+/// ### Returns
+/// * the name the sample was delivered under
+/// ### Errors
+/// * `Error::InvalidRequest` - unknown operand type or malformed signal
+/// * `Error::Unavailable` - the filters do not receive signals
+pub async fn inject(injection: Injection) -> Result<String> {
+    let tx = filters().lock().unwrap_or_else(|e| e.into_inner()).clone();
+    inject_into(tx, injection).await
+}
+
+async fn inject_into(tx: Option<Sender<DdsData>>, injection: Injection) -> Result<String> {
+    let source = SourceKind::from_operand_type(&injection.r#type).ok_or_else(|| {
+        Error::InvalidRequest(format!("unsupported operand type '{}'", injection.r#type))
+    })?;
+    let name = source
+        .qualify(&injection.signal)
+        .map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    if injection.fields.is_empty() {
+        return Err(Error::InvalidRequest("no field to inject".to_string()));
+    }
+
+    let tx = tx.ok_or_else(|| Error::Unavailable("filters are not running".to_string()))?;
+    let mut fields: Vec<(&String, &String)> = injection.fields.iter().collect();
+    fields.sort();
+    let data = DdsData {
+        name: name.clone(),
+        value: fields[0].1.clone(),
+        fields: injection.fields.clone(),
+    };
+    tx.send(data)
+        .await
+        .map_err(|_| Error::Unavailable("filters are not running".to_string()))?;
+    logd!(2, "Injected {:?} into signal {}", injection.fields, name);
+    Ok(name)
+}
+
+async fn inject_signal(Json(injection): Json<Injection>) -> impl IntoResponse {
+    match inject(injection).await {
+        Ok(name) => (StatusCode::OK, Json(name)),
+        Err(e) => {
+            let code = match e {
+                Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (code, Json(e.to_string()))
+        }
+    }
+}
+
+/// Route of `POST /api/inject`
+pub fn router() -> Router {
+    Router::new().route("/api/inject", post(inject_signal))
+}
+
+/// Serve [`router`] on its own HTTP listener
+pub async fn serve(addr: String) {
+    match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => {
+            logd!(4, "Signal injector listening on {}, not for vehicles", addr);
+            if let Err(e) = axum::serve(listener, router()).await {
+                logd!(5, "Signal injector stopped: {}", e);
+            }
+        }
+        Err(e) => logd!(5, "Failed to bind signal injector on {}: {}", addr, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn injection(r#type: &str, signal: &str, fields: &[(&str, &str)]) -> Injection {
+        Injection {
+            r#type: r#type.to_string(),
+            signal: signal.to_string(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injected_samples_reach_the_filters() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+
+        let warning = injection(
+            "DDS",
+            "/rt/piccolo/ADAS",
+            &[("ADASObstacleDetectionIsWarning", "true")],
+        );
+        let name = inject_into(Some(tx.clone()), warning).await.unwrap();
+        assert_eq!(name, "/rt/piccolo/ADAS");
+        let data = rx.recv().await.unwrap();
+        assert_eq!(data.fields["ADASObstacleDetectionIsWarning"], "true");
+
+        let frame = injection("can", "0x1a0", &[("byte0", "120")]);
+        let name = inject_into(Some(tx.clone()), frame).await.unwrap();
+        assert_eq!(name, "CAN:0x1A0");
+        assert_eq!(rx.recv().await.unwrap().name, "CAN:0x1A0");
+
+        for invalid in [
+            injection("pod", "x", &[("a", "1")]),
+            injection("CAN", "speed", &[("a", "1")]),
+            injection("DDS", "topic", &[]),
+        ] {
+            let err = inject_into(Some(tx.clone()), invalid).await.unwrap_err();
+            assert!(matches!(err, Error::InvalidRequest(_)), "{:?}", err);
+        }
+        let gear = injection("DDS", "topic", &[("gear", "parking")]);
+        let err = inject_into(None, gear).await.unwrap_err();
+        assert!(matches!(err, Error::Unavailable(_)));
+    }
+
+    #[tokio::test]
+    async fn test_inject_route_refuses_invalid_signals() {
+        let body = r#"{"type": "pod", "signal": "x", "fields": {"a": "1"}}"#;
+        let response = router()
+            .oneshot(
+                Request::post("/api/inject")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -4,6 +4,8 @@
 */
 pub mod filter;
 pub mod grpc;
+#[cfg(feature = "injector")]
+pub mod injector;
 pub mod manager;
 pub mod vehicle;
 
@@ -24,6 +26,7 @@ pub async fn launch_manager(rx_grpc: Receiver<ScenarioParameter>) {
     match manager.initialize().await {
         Ok(_) => {
             println!("FilterGatewayManager successfully initialized");
+            common::health::set_started();
             // Only proceed to run if initialization was successful
             if let Err(e) = manager.run().await {
                 eprintln!("Error running FilterGatewayManager: {:?}", e);
@@ -58,8 +61,14 @@ pub async fn initialize(tx_grpc: Sender<manager::ScenarioParameter>) {
 
     println!("Piccolod gateway listening on {}", addr);
 
+    #[cfg(feature = "injector")]
+    tokio::spawn(crate::injector::serve(
+        common::filtergateway::open_injector_server(),
+    ));
+
     let _ = Server::builder()
         .add_service(FilterGatewayConnectionServer::new(server))
+        .add_service(common::health::grpc_service())
         .serve(addr)
         .await;
 }
@@ -4,6 +4,8 @@
 */
 mod filter;
 mod grpc;
+#[cfg(feature = "injector")]
+mod injector;
 mod manager;
 mod vehicle;
 
@@ -30,6 +32,11 @@ use common::logd::logger;
 async fn main() {
     let _ = logger::init_async_logger("filtergateway").await;
     logd!(1, "Initializing FilterGateway");
+    common::health::init("filtergateway", false);
+    common::address::validate_or_exit("filtergateway");
+    tokio::spawn(common::health::serve(
+        common::filtergateway::open_health_server(),
+    ));
 
     // Initialize tracing subscriber for logging
     let (tx_grpc, rx_grpc): (Sender<ScenarioParameter>, Receiver<ScenarioParameter>) = channel(100);
@@ -2,6 +2,7 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
+use crate::filter::condition::ConditionExpr;
 use crate::filter::Filter;
 use crate::grpc::sender::actioncontroller::FilterGatewaySender;
 use crate::grpc::sender::statemanager::StateManagerSender;
@@ -9,12 +10,29 @@ use crate::vehicle::dds::DdsData;
 use crate::vehicle::VehicleManager;
 use common::logd;
 use common::spec::artifact::Scenario;
-use common::statemanager::{ResourceType, StateChange};
+use common::statemanager::{ResourceType, StateChange, VehicleMode};
 use common::{spec::artifact::Artifact, Result};
 // use dust_dds::infrastructure::wait_set::Condition;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 
+/// DDS topic publishing the vehicle operational mode
+pub const VEHICLE_MODE_TOPIC: &str = "/rt/piccolo/Vehicle_Mode";
+/// Field of [`VEHICLE_MODE_TOPIC`] holding the mode name
+pub const VEHICLE_MODE_FIELD: &str = "mode";
+
+/// Extract the vehicle mode from a sample of [`VEHICLE_MODE_TOPIC`]
+///
+/// Returns `None` for other topics and for unknown mode names.
+pub fn vehicle_mode_from_dds(data: &DdsData) -> Option<VehicleMode> {
+    if data.name != VEHICLE_MODE_TOPIC {
+        return None;
+    }
+    data.fields
+        .get(VEHICLE_MODE_FIELD)
+        .and_then(|mode| VehicleMode::from_name(mode))
+}
+
 /// Manager for FilterGateway
 ///
 /// Responsible for:
@@ -56,6 +74,8 @@ impl FilterGatewayManager {
     /// A new FilterGatewayManager instance
     pub async fn new(rx_grpc: mpsc::Receiver<ScenarioParameter>) -> Self {
         let (tx_dds, rx_dds) = mpsc::channel::<DdsData>(10);
+        #[cfg(feature = "injector")]
+        crate::injector::register(tx_dds.clone());
         let mut vehicle_manager = VehicleManager::new(tx_dds);
 
         // Improved error handling: explicit error handling instead of unwrap()
@@ -91,29 +111,71 @@ impl FilterGatewayManager {
         for scenario in etcd_scenario {
             let scenario: Scenario = serde_yaml::from_str(&scenario)?;
             logd!(3, "Scenario: {:?}", scenario);
-            let topic_name = scenario
-                .get_conditions()
-                .as_ref()
-                .map(|cond| cond.get_operand_value())
-                .unwrap_or_default();
-            let data_type_name = scenario
-                .get_conditions()
-                .as_ref()
-                .map(|cond| cond.get_operand_value())
-                .unwrap_or_default();
-            let mut vehicle_manager = self.vehicle_manager.lock().await;
-            if let Err(e) = vehicle_manager
-                .subscribe_topic(topic_name, data_type_name)
-                .await
-            {
-                logd!(5, "Error subscribing to vehicle data: {:?}", e);
-            }
+            self.subscribe_condition_topics(&scenario).await;
             self.launch_scenario_filter(scenario).await?;
         }
 
+        // The vehicle mode is forwarded to StateManager regardless of scenarios,
+        // the filtergateway holds it so that no withdraw releases it
+        if let Err(e) = self
+            .vehicle_manager
+            .lock()
+            .await
+            .subscribe_signal(VEHICLE_MODE_TOPIC, "filtergateway")
+            .await
+        {
+            logd!(5, "Error subscribing to vehicle mode: {:?}", e);
+        }
+
         Ok(())
     }
 
+    /// Forward a vehicle mode sample to StateManager
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Mode reported on the vehicle network
+    async fn forward_vehicle_mode(&self, mode: VehicleMode) {
+        let mut state_sender = StateManagerSender::new();
+        if let Err(e) = state_sender.set_vehicle_mode(mode, "filtergateway").await {
+            logd!(5, "Failed to report vehicle mode {}: {:?}", mode.name(), e);
+        }
+    }
+
+    /// Subscribe to every signal referenced by a scenario condition
+    ///
+    /// Each signal is subscribed through the backend of its operand type,
+    /// see [`crate::vehicle::source`].
+    ///
+    /// # Arguments
+    ///
+    /// * `scenario` - Scenario whose condition topics are subscribed
+    async fn subscribe_condition_topics(&self, scenario: &Scenario) {
+        let topics = match scenario.get_conditions() {
+            Some(condition) => match ConditionExpr::from_condition(&condition) {
+                Ok(expr) => expr.topics(),
+                Err(e) => {
+                    logd!(
+                        5,
+                        "Invalid condition in scenario {}: {:?}",
+                        scenario.get_qualified_name(),
+                        e
+                    );
+                    return;
+                }
+            },
+            None => return,
+        };
+
+        let holder = scenario.get_qualified_name();
+        let mut vehicle_manager = self.vehicle_manager.lock().await;
+        for topic in topics {
+            if let Err(e) = vehicle_manager.subscribe_signal(&topic, &holder).await {
+                logd!(5, "Error subscribing to vehicle data: {:?}", e);
+            }
+        }
+    }
+
     /// Function to receive subscribed DDS data and pass it to filters
     ///
     /// This function runs as a separate task to continuously receive and process DDS data.
@@ -142,6 +204,10 @@ impl FilterGatewayManager {
                         );
                     }
 
+                    if let Some(mode) = vehicle_mode_from_dds(&dds_data) {
+                        self.forward_vehicle_mode(mode).await;
+                    }
+
                     // Forward data to all active filters
                     let mut filters = self.filters.lock().await;
                     for filter in filters.iter_mut() {
@@ -191,39 +257,25 @@ impl FilterGatewayManager {
                         0 => {
                             // Allow
                             // Subscribe to vehicle data
-                            let topic_name = param
-                                .scenario
-                                .get_conditions()
-                                .as_ref()
-                                .map(|cond| cond.get_operand_value())
-                                .unwrap_or_default();
-                            let data_type_name = param
-                                .scenario
-                                .get_conditions()
-                                .as_ref()
-                                .map(|cond| cond.get_operand_value())
-                                .unwrap_or_default();
-                            let mut vehicle_manager = self.vehicle_manager.lock().await;
-                            if let Err(e) = vehicle_manager
-                                .subscribe_topic(topic_name, data_type_name)
-                                .await
-                            {
-                                logd!(5, "Error subscribing to vehicle data: {:?}", e);
-                            }
+                            self.subscribe_condition_topics(&param.scenario).await;
                             self.launch_scenario_filter(param.scenario).await?;
                         }
                         1 => {
                             // Withdraw
-                            // Unsubscribe from vehicle data
-                            let mut vehicle_manager = self.vehicle_manager.lock().await;
-                            if let Err(e) = vehicle_manager
-                                .unsubscribe_topic(param.scenario.get_name().clone())
+                            // Release the signals no other scenario subscribed to
+                            if let Err(e) = self
+                                .vehicle_manager
+                                .lock()
+                                .await
+                                .release_signals(&param.scenario.get_qualified_name())
                                 .await
                             {
                                 logd!(5, "Error unsubscribing from vehicle data: {:?}", e);
                             }
-                            self.remove_scenario_filter(param.scenario.get_name().clone())
-                                .await?;
+                            self.remove_scenario_filter(
+                                param.scenario.get_qualified_name().clone(),
+                            )
+                            .await?;
                         }
                         _ => {}
                     }
@@ -344,9 +396,24 @@ impl FilterGatewayManager {
 
         // Check if the scenario has conditions
         if scenario.get_conditions().is_none() {
-            logd!(3, "No conditions for scenario: {}", scenario.get_name());
+            // Scheduled scenarios are triggered by the StateManager scheduler
+            if scenario.get_schedule().is_some() {
+                logd!(
+                    3,
+                    "Scenario {} is triggered by its schedule",
+                    scenario.get_qualified_name()
+                );
+                return Ok(());
+            }
+            logd!(
+                3,
+                "No conditions for scenario: {}",
+                scenario.get_qualified_name()
+            );
             let mut sender = self.sender.lock().await;
-            sender.trigger_action(scenario.get_name().clone()).await?;
+            sender
+                .trigger_action(scenario.get_qualified_name().clone())
+                .await?;
             let elapsed = start.elapsed();
             logd!(1, "launch_scenario_filter: elapsed = {:?}", elapsed);
             return Ok(());
@@ -357,7 +424,7 @@ impl FilterGatewayManager {
             1,
             "🔄 SCENARIO STATE TRANSITION: FilterGateway Condition Registration"
         );
-        logd!(1, "   📋 Scenario: {}", scenario.get_name());
+        logd!(1, "   📋 Scenario: {}", scenario.get_qualified_name());
         logd!(1, "   🔄 State Change: idle → waiting");
         logd!(
             1,
@@ -371,12 +438,16 @@ impl FilterGatewayManager {
 
         let state_change = StateChange {
             resource_type: ResourceType::Scenario as i32,
-            resource_name: scenario.get_name().clone(),
+            resource_name: scenario.get_qualified_name().clone(),
             current_state: "idle".to_string(),
             target_state: "waiting".to_string(),
-            transition_id: format!("filtergateway-condition-registered-{}", timestamp),
+            transition_id: common::correlation::transition_id("filtergateway"),
             timestamp_ns: timestamp,
             source: "filtergateway".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         logd!(1, "   📤 Sending StateChange to StateManager:");
@@ -398,7 +469,7 @@ impl FilterGatewayManager {
             logd!(
                 2,
                 "   ✅ Successfully notified StateManager: scenario {} idle → waiting",
-                scenario.get_name()
+                scenario.get_qualified_name()
             );
         }
 
@@ -406,7 +477,12 @@ impl FilterGatewayManager {
             let sender_guard = self.sender.lock().await;
             sender_guard.clone()
         };
-        let filter = Filter::new(scenario.get_name().to_string(), scenario, true, sender);
+        let filter = Filter::new(
+            scenario.get_qualified_name().to_string(),
+            scenario,
+            true,
+            sender,
+        );
 
         // Add the filter to our managed collection
         {
@@ -464,7 +540,9 @@ impl FilterGatewayManager {
     /// ### Return
     /// * `Result<Vec<String>>` - `Ok(_)` contains scenario yaml string vector
     async fn read_all_scenario_from_etcd() -> common::Result<Vec<String>> {
-        let kv_scenario = common::etcd::get_all_with_prefix("Scenario").await?;
+        let kv_scenario = common::etcd::get_all_with_prefix("Scenario")
+            .await
+            .map_err(common::etcd::error)?;
         let values = kv_scenario.into_iter().map(|kv| kv.1).collect();
 
         Ok(values)
@@ -1496,9 +1574,13 @@ mod tests {
                 resource_name: scenario.get_name(),
                 current_state: "idle".to_string(),
                 target_state: "waiting".to_string(),
-                transition_id: format!("filtergateway-condition-registered-{}", timestamp),
+                transition_id: common::correlation::transition_id("filtergateway"),
                 timestamp_ns: timestamp,
                 source: "filtergateway".to_string(),
+                correlation_id: String::new(),
+                sub_state: String::new(),
+                denial: None,
+                metadata: Default::default(),
             };
 
             if let Err(e) = state_sender.send_state_change(state_change).await {
@@ -1547,6 +1629,10 @@ mod tests {
             transition_id: "test-transition".to_string(),
             timestamp_ns: 123456789,
             source: "filtergateway".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         // Test error handling path (line 264)
@@ -1658,4 +1744,32 @@ spec:
             "Vehicle manager error should be triggered"
         );
     }
+
+    #[test]
+    fn test_vehicle_mode_from_dds() {
+        use super::{vehicle_mode_from_dds, VEHICLE_MODE_TOPIC};
+        use crate::vehicle::dds::DdsData;
+        use common::statemanager::VehicleMode;
+
+        let sample = |topic: &str, mode: &str| DdsData {
+            name: topic.to_string(),
+            value: String::new(),
+            fields: [("mode".to_string(), mode.to_string())]
+                .into_iter()
+                .collect(),
+        };
+
+        assert_eq!(
+            vehicle_mode_from_dds(&sample(VEHICLE_MODE_TOPIC, "Parked")),
+            Some(VehicleMode::Parked)
+        );
+        assert_eq!(
+            vehicle_mode_from_dds(&sample(VEHICLE_MODE_TOPIC, "towing")),
+            None
+        );
+        assert_eq!(
+            vehicle_mode_from_dds(&sample("/rt/piccolo/Gear_State", "parked")),
+            None
+        );
+    }
 }
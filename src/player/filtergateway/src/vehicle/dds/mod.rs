@@ -254,6 +254,24 @@ impl DdsManager {
     }
 }
 
+#[async_trait::async_trait]
+impl crate::vehicle::source::ConditionSource for DdsManager {
+    fn kind(&self) -> crate::vehicle::source::SourceKind {
+        crate::vehicle::source::SourceKind::Dds
+    }
+
+    /// The topic name doubles as the data type name, as registered by the
+    /// DDS type registry
+    async fn subscribe(&mut self, signal: &str) -> Result<()> {
+        self.create_typed_listener(signal.to_string(), signal.to_string())
+            .await
+    }
+
+    async fn unsubscribe(&mut self, signal: &str) -> Result<()> {
+        self.remove_listener(signal).await
+    }
+}
+
 // Include generated DDS types at runtime
 #[allow(unused)]
 #[allow(non_snake_case)]
@@ -3,19 +3,32 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 pub mod dds;
+pub mod source;
 
 use common::logd;
 use common::Result;
 use dds::DdsData;
+use source::{ConditionSource, SourceKind};
+use std::collections::{BTreeSet, HashMap};
 use tokio::sync::mpsc::Sender;
 
 /// Vehicle data management module
 ///
-/// Manages vehicle data through DDS communication
+/// Manages vehicle data through DDS communication and the other
+/// [`ConditionSource`] backends
 #[allow(dead_code)]
 pub struct VehicleManager {
     /// DDS Manager instance
     dds_manager: dds::DdsManager,
+    /// CAN frames of the configured interface
+    can: source::can::CanSource,
+    /// SOME/IP messages of the configured address
+    someip: source::someip::SomeIpSource,
+    /// Signals pushed through gRPC
+    push: source::push::PushSource,
+    /// Holders of every subscribed signal, a signal is released with its
+    /// last holder
+    holders: HashMap<String, BTreeSet<String>>,
 }
 #[allow(dead_code)]
 impl VehicleManager {
@@ -25,8 +38,13 @@ impl VehicleManager {
     ///
     /// A new VehicleManager instance
     pub fn new(tx: Sender<DdsData>) -> Self {
+        let settings = &common::setting::get_config().filtergateway;
         Self {
+            can: source::can::CanSource::new(settings.can_interface.clone(), tx.clone()),
+            someip: source::someip::SomeIpSource::new(settings.someip_address.clone(), tx.clone()),
+            push: source::push::PushSource::new(tx.clone()),
             dds_manager: dds::DdsManager::new(tx),
+            holders: HashMap::new(),
         }
     }
 
@@ -78,6 +96,103 @@ impl VehicleManager {
         Ok(())
     }
 
+    /// Backend of a source
+    fn source(&mut self, kind: SourceKind) -> &mut dyn ConditionSource {
+        match kind {
+            SourceKind::Dds => &mut self.dds_manager,
+            SourceKind::Can => &mut self.can,
+            SourceKind::SomeIp => &mut self.someip,
+            SourceKind::Grpc => &mut self.push,
+        }
+    }
+
+    /// Subscribes to a signal of a scenario condition
+    ///
+    /// The backend is subscribed for the first holder of the signal only.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - Name of the signal as made by [`SourceKind::qualify`],
+    ///   which selects the backend
+    /// * `holder` - Scenario, or component, the signal is subscribed for
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success or error result
+    pub async fn subscribe_signal(&mut self, signal: &str, holder: &str) -> Result<()> {
+        if !self.holders.contains_key(signal) {
+            let (kind, name) = SourceKind::of(signal);
+            let source = self.source(kind);
+            logd!(
+                2,
+                "Subscribing to {} signal '{}'",
+                source.kind().name(),
+                name
+            );
+            source.subscribe(name).await?;
+        }
+        self.holders
+            .entry(signal.to_string())
+            .or_default()
+            .insert(holder.to_string());
+        Ok(())
+    }
+
+    /// Unsubscribes a holder from a signal of a scenario condition
+    ///
+    /// The backend is unsubscribed once the signal has no holder left.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - Name of the signal as made by [`SourceKind::qualify`]
+    /// * `holder` - Scenario, or component, the signal was subscribed for
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success or error result
+    pub async fn unsubscribe_signal(&mut self, signal: &str, holder: &str) -> Result<()> {
+        let Some(holders) = self.holders.get_mut(signal) else {
+            return Ok(());
+        };
+        holders.remove(holder);
+        if !holders.is_empty() {
+            return Ok(());
+        }
+        self.holders.remove(signal);
+        let (kind, name) = SourceKind::of(signal);
+        logd!(2, "Unsubscribing from {} signal '{}'", kind.name(), name);
+        self.source(kind).unsubscribe(name).await
+    }
+
+    /// Unsubscribes a holder from every signal it subscribed to
+    ///
+    /// # Arguments
+    ///
+    /// * `holder` - Scenario, or component, the signals were subscribed for
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - The first error of the signals that could not be
+    ///   released, after trying all of them
+    pub async fn release_signals(&mut self, holder: &str) -> Result<()> {
+        let signals: Vec<String> = self
+            .holders
+            .iter()
+            .filter(|(_, holders)| holders.contains(holder))
+            .map(|(signal, _)| signal.clone())
+            .collect();
+        let mut result = Ok(());
+        for signal in signals {
+            if let Err(e) = self.unsubscribe_signal(&signal, holder).await {
+                logd!(5, "Error unsubscribing from signal '{}': {:?}", signal, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
     /// Get list of available DDS types
     pub fn list_available_types(&self) -> Vec<String> {
         self.dds_manager.list_available_types()
@@ -165,6 +280,53 @@ mod tests {
         assert!(!types.is_empty());
     }
 
+    #[tokio::test] // Test signals are subscribed through the backend of their source
+    async fn test_vehicle_manager_subscribe_signal() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let mut vehicle_manager = VehicleManager::new(tx);
+        let signal = SourceKind::Grpc.qualify("test/gear").unwrap();
+        vehicle_manager
+            .subscribe_signal(&signal, "s1")
+            .await
+            .unwrap();
+
+        let fields = std::collections::HashMap::from([("gear".to_string(), "P".to_string())]);
+        source::push::push("test/gear", fields.clone())
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().name, signal);
+
+        vehicle_manager
+            .unsubscribe_signal(&signal, "s1")
+            .await
+            .unwrap();
+        assert!(source::push::push("test/gear", fields).await.is_err());
+    }
+
+    #[tokio::test] // Test a signal shared by scenarios stays until the last one releases it
+    async fn test_vehicle_manager_shared_signal() {
+        let (tx, _rx) = mpsc::channel(10);
+        let mut vehicle_manager = VehicleManager::new(tx);
+        let signal = SourceKind::Grpc.qualify("test/shared").unwrap();
+        vehicle_manager
+            .subscribe_signal(&signal, "s1")
+            .await
+            .unwrap();
+        vehicle_manager
+            .subscribe_signal(&signal, "s2")
+            .await
+            .unwrap();
+        let fields = std::collections::HashMap::from([("door".to_string(), "open".to_string())]);
+
+        vehicle_manager.release_signals("s1").await.unwrap();
+        assert!(source::push::push("test/shared", fields.clone())
+            .await
+            .is_ok());
+
+        vehicle_manager.release_signals("s2").await.unwrap();
+        assert!(source::push::push("test/shared", fields).await.is_err());
+    }
+
     #[test] // Test setting the domain ID for VehicleManager
     fn test_vehicle_manager_set_domain_id() {
         let (tx, _rx) = mpsc::channel(10);
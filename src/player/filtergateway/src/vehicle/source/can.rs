@@ -0,0 +1,256 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! CAN frames read from a SocketCAN interface
+//!
+//! The frames of `filtergateway.can_interface` are read by a thread started
+//! with the first subscription and stopped after the last one. Only the
+//! frames of subscribed ids are sent, with their payload as `data` in hex,
+//! their length as `dlc` and every byte as `byte0` to `byte7`.
+
+use super::{byte_fields, hex, ConditionSource, SourceKind};
+use crate::vehicle::dds::DdsData;
+use async_trait::async_trait;
+use common::logd;
+use common::Result;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
+
+/// Time a read waits for a frame before the thread checks it should stop
+const READ_TIMEOUT_MICROS: libc::suseconds_t = 200_000;
+
+/// Parse a CAN id, in hex with a `0x` prefix or in decimal
+pub fn parse_id(value: &str) -> Result<u32> {
+    let value = value.trim();
+    let id = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse::<u32>(),
+    }
+    .map_err(|_| format!("invalid CAN id '{}'", value))?;
+    if id > libc::CAN_EFF_MASK {
+        return Err(format!("CAN id '{}' is out of range", value).into());
+    }
+    Ok(id)
+}
+
+/// Signal name of the frames of a CAN id
+pub fn signal(id: u32) -> String {
+    format!("0x{:X}", id)
+}
+
+/// Sample of a received frame
+pub fn frame_data(id: u32, data: &[u8]) -> DdsData {
+    let mut fields: HashMap<String, String> = byte_fields(data).collect();
+    fields.insert("data".to_string(), hex(data));
+    fields.insert("dlc".to_string(), data.len().to_string());
+    DdsData {
+        name: format!("{}:{}", SourceKind::Can.name(), signal(id)),
+        value: hex(data),
+        fields,
+    }
+}
+
+/// CAN backend of the scenario conditions
+pub struct CanSource {
+    interface: String,
+    tx: Sender<DdsData>,
+    ids: Arc<Mutex<HashSet<u32>>>,
+    /// Cleared to stop the reading thread
+    running: Arc<AtomicBool>,
+}
+
+impl CanSource {
+    pub fn new(interface: String, tx: Sender<DdsData>) -> Self {
+        Self {
+            interface,
+            tx,
+            ids: Arc::new(Mutex::new(HashSet::new())),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let socket = open(&self.interface)
+            .map_err(|e| format!("cannot open CAN interface '{}': {}", self.interface, e))?;
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = running.clone();
+        let (interface, ids, tx) = (self.interface.clone(), self.ids.clone(), self.tx.clone());
+        std::thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                let (id, data) = match read_frame(&socket) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        logd!(5, "Error reading CAN interface '{}': {}", interface, e);
+                        break;
+                    }
+                };
+                let subscribed = ids.lock().unwrap_or_else(|e| e.into_inner()).contains(&id);
+                if subscribed && tx.blocking_send(frame_data(id, &data)).is_err() {
+                    break;
+                }
+            }
+            running.store(false, Ordering::Relaxed);
+        });
+        logd!(2, "Reading CAN frames of interface '{}'", self.interface);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConditionSource for CanSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Can
+    }
+
+    async fn subscribe(&mut self, signal: &str) -> Result<()> {
+        let id = parse_id(signal)?;
+        self.ids
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id);
+        if !self.running.load(Ordering::Relaxed) {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, signal: &str) -> Result<()> {
+        let id = parse_id(signal)?;
+        let mut ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        ids.remove(&id);
+        if ids.is_empty() {
+            self.running.store(false, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Open a raw CAN socket bound to `interface`
+fn open(interface: &str) -> io::Result<OwnedFd> {
+    let name = std::ffi::CString::new(interface)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `name` is a valid NUL terminated string
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: plain socket creation, the descriptor is owned right after
+    let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW, libc::CAN_RAW) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a new descriptor nothing else owns
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let timeout = libc::timeval {
+        tv_sec: 0,
+        tv_usec: READ_TIMEOUT_MICROS,
+    };
+    // SAFETY: the option value is a timeval of the given size
+    let set = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const libc::timeval as *const libc::c_void,
+            std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+    if set < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: an all zero sockaddr_can is valid
+    let mut address: libc::sockaddr_can = unsafe { std::mem::zeroed() };
+    address.can_family = libc::AF_CAN as libc::sa_family_t;
+    address.can_ifindex = index as libc::c_int;
+    // SAFETY: the address is a sockaddr_can of the given size
+    let bound = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            &address as *const libc::sockaddr_can as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+        )
+    };
+    if bound < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+/// Read the next data frame, `None` on timeout and for other frames
+fn read_frame(socket: &OwnedFd) -> io::Result<Option<(u32, Vec<u8>)>> {
+    // SAFETY: an all zero can_frame is valid
+    let mut frame: libc::can_frame = unsafe { std::mem::zeroed() };
+    // SAFETY: at most the size of `frame` is written into it
+    let read = unsafe {
+        libc::read(
+            socket.as_raw_fd(),
+            &mut frame as *mut libc::can_frame as *mut libc::c_void,
+            std::mem::size_of::<libc::can_frame>(),
+        )
+    };
+    if read < 0 {
+        let e = io::Error::last_os_error();
+        return match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted => {
+                Ok(None)
+            }
+            _ => Err(e),
+        };
+    }
+    if frame.can_id & (libc::CAN_ERR_FLAG | libc::CAN_RTR_FLAG) != 0 {
+        return Ok(None);
+    }
+    let id = if frame.can_id & libc::CAN_EFF_FLAG != 0 {
+        frame.can_id & libc::CAN_EFF_MASK
+    } else {
+        frame.can_id & libc::CAN_SFF_MASK
+    };
+    let len = usize::from(frame.can_dlc).min(libc::CAN_MAX_DLEN);
+    Ok(Some((id, frame.data[..len].to_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_id() {
+        assert_eq!(parse_id("0x1A0").unwrap(), 0x1A0);
+        assert_eq!(parse_id("0X1a0").unwrap(), 0x1A0);
+        assert_eq!(parse_id("416").unwrap(), 0x1A0);
+        assert_eq!(parse_id("0x1FFFFFFF").unwrap(), libc::CAN_EFF_MASK);
+        assert!(parse_id("0x20000000").is_err());
+        assert!(parse_id("speed").is_err());
+    }
+
+    #[test]
+    fn test_frame_data() {
+        let data = frame_data(0x1A0, &[0x01, 0xFF]);
+        assert_eq!(data.name, "CAN:0x1A0");
+        assert_eq!(data.value, "01FF");
+        assert_eq!(data.fields["data"], "01FF");
+        assert_eq!(data.fields["dlc"], "2");
+        assert_eq!(data.fields["byte0"], "1");
+        assert_eq!(data.fields["byte1"], "255");
+        assert!(!data.fields.contains_key("byte2"));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_missing_interface_fails() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let mut source = CanSource::new("pullpiri-no-can".to_string(), tx);
+        assert!(source.subscribe("0x1A0").await.is_err());
+        assert!(source.unsubscribe("0x1A0").await.is_ok());
+    }
+}
@@ -0,0 +1,161 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Vehicle buses scenario conditions are evaluated on
+//!
+//! The `type` of a condition operand selects the [`ConditionSource`] its
+//! signal is received from, `value` names the signal and `name` the field
+//! compared:
+//! * `DDS` - a DDS topic, `name` is a field of its samples
+//! * `CAN` - a CAN id like `0x1A0`, `name` is `data`, `dlc` or `byte0` to `byte7`
+//! * `SOMEIP` - a `service/method` pair like `0x1234/0x8001`, `name` is
+//!   `payload`, `length` or `byte0` onwards
+//! * `GRPC` - any name, `name` is a field of the signals pushed for it
+//!   through the `PushSignal` call of the filtergateway
+//!
+//! Every source sends its samples as [`DdsData`](super::dds::DdsData) on the
+//! channel of the filters, named by [`SourceKind::qualify`] so that equally
+//! named signals of two buses do not mix. DDS topics keep their plain name.
+
+pub mod can;
+pub mod push;
+pub mod someip;
+
+use async_trait::async_trait;
+use common::Result;
+
+/// Bus a condition operand is received from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceKind {
+    Dds,
+    Can,
+    SomeIp,
+    Grpc,
+}
+
+impl SourceKind {
+    const ALL: [SourceKind; 4] = [
+        SourceKind::Dds,
+        SourceKind::Can,
+        SourceKind::SomeIp,
+        SourceKind::Grpc,
+    ];
+
+    /// Source of an operand `type`, ignoring case
+    pub fn from_operand_type(operand_type: &str) -> Option<Self> {
+        let operand_type = operand_type.replace('/', "");
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(&operand_type))
+    }
+
+    /// Operand `type` of the source
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceKind::Dds => "DDS",
+            SourceKind::Can => "CAN",
+            SourceKind::SomeIp => "SOMEIP",
+            SourceKind::Grpc => "GRPC",
+        }
+    }
+
+    /// Name the samples of the operand `value` are received under
+    ///
+    /// ### Errors
+    /// * `value` is not a signal of the source, e.g. not a CAN id
+    pub fn qualify(&self, value: &str) -> Result<String> {
+        Ok(match self {
+            SourceKind::Dds => value.to_string(),
+            SourceKind::Can => {
+                let id = can::parse_id(value)?;
+                format!("{}:{}", self.name(), can::signal(id))
+            }
+            SourceKind::SomeIp => {
+                let (service, method) = someip::parse_signal(value)?;
+                format!("{}:{}", self.name(), someip::signal(service, method))
+            }
+            SourceKind::Grpc => format!("{}:{}", self.name(), value),
+        })
+    }
+
+    /// Source and signal of a name made by [`SourceKind::qualify`]
+    pub fn of(qualified: &str) -> (Self, &str) {
+        qualified
+            .split_once(':')
+            .and_then(|(prefix, signal)| {
+                Self::ALL
+                    .into_iter()
+                    .find(|kind| *kind != SourceKind::Dds && kind.name() == prefix)
+                    .map(|kind| (kind, signal))
+            })
+            .unwrap_or((SourceKind::Dds, qualified))
+    }
+}
+
+/// Backend receiving the signals of one bus for the scenario filters
+#[async_trait]
+pub trait ConditionSource: Send + Sync {
+    /// Bus of the backend
+    fn kind(&self) -> SourceKind;
+    /// Start sending the samples of `signal`, nothing if already sent
+    async fn subscribe(&mut self, signal: &str) -> Result<()>;
+    /// Stop sending the samples of `signal`
+    async fn unsubscribe(&mut self, signal: &str) -> Result<()>;
+}
+
+/// `byte0` onwards fields of the bytes of a sample, as decimal numbers
+pub(crate) fn byte_fields(bytes: &[u8]) -> impl Iterator<Item = (String, String)> + '_ {
+    bytes
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| (format!("byte{}", index), byte.to_string()))
+}
+
+/// Bytes of a sample as an upper case hex string
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_kind_from_operand_type() {
+        assert_eq!(SourceKind::from_operand_type("DDS"), Some(SourceKind::Dds));
+        assert_eq!(SourceKind::from_operand_type("can"), Some(SourceKind::Can));
+        assert_eq!(
+            SourceKind::from_operand_type("SOME/IP"),
+            Some(SourceKind::SomeIp)
+        );
+        assert_eq!(
+            SourceKind::from_operand_type("gRPC"),
+            Some(SourceKind::Grpc)
+        );
+        assert_eq!(SourceKind::from_operand_type("pod"), None);
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let dds = SourceKind::Dds.qualify("/rt/piccolo/Gear_State").unwrap();
+        assert_eq!(dds, "/rt/piccolo/Gear_State");
+        assert_eq!(SourceKind::of(&dds), (SourceKind::Dds, dds.as_str()));
+
+        let can = SourceKind::Can.qualify("0x1a0").unwrap();
+        assert_eq!(can, "CAN:0x1A0");
+        assert_eq!(SourceKind::Can.qualify("416").unwrap(), can);
+        assert_eq!(SourceKind::of(&can), (SourceKind::Can, "0x1A0"));
+        assert!(SourceKind::Can.qualify("speed").is_err());
+
+        let someip = SourceKind::SomeIp.qualify("0x1234/32769").unwrap();
+        assert_eq!(someip, "SOMEIP:0x1234/0x8001");
+        assert_eq!(
+            SourceKind::of(&someip),
+            (SourceKind::SomeIp, "0x1234/0x8001")
+        );
+
+        let pushed = SourceKind::Grpc.qualify("test/speed").unwrap();
+        assert_eq!(SourceKind::of(&pushed), (SourceKind::Grpc, "test/speed"));
+    }
+}
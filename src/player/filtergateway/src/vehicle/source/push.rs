@@ -0,0 +1,107 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Signals pushed through the gRPC API of the filtergateway
+//!
+//! Meant for tests and simulators: a client calls `PushSignal` with a signal
+//! name and its fields, which are sent to the filters as a sample of the
+//! `GRPC` operand of that name. Signals no scenario is subscribed to are
+//! refused, so that a typo in a test does not go unnoticed. The call is
+//! refused altogether unless `filtergateway.push_signals` is set.
+
+use super::{ConditionSource, SourceKind};
+use crate::vehicle::dds::DdsData;
+use async_trait::async_trait;
+use common::error::Error;
+use common::Result;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::mpsc::Sender;
+
+/// Channel of the filters by subscribed signal, shared with the gRPC receiver
+fn pushed() -> &'static Mutex<HashMap<String, Sender<DdsData>>> {
+    static PUSHED: OnceLock<Mutex<HashMap<String, Sender<DdsData>>>> = OnceLock::new();
+    PUSHED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Send a pushed signal to the filters
+///
+/// ### Errors
+/// * `Error::NotFound` - no scenario is subscribed to the signal
+/// * `Error::Unavailable` - the filters do not receive signals
+pub async fn push(signal: &str, fields: HashMap<String, String>) -> Result<()> {
+    let tx = pushed()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(signal)
+        .cloned()
+        .ok_or_else(|| Error::NotFound(format!("no scenario condition on signal '{}'", signal)))?;
+    let data = DdsData {
+        name: format!("{}:{}", SourceKind::Grpc.name(), signal),
+        value: fields.values().next().cloned().unwrap_or_default(),
+        fields,
+    };
+    tx.send(data)
+        .await
+        .map_err(|_| Error::Unavailable("filters are not running".to_string()))
+}
+
+/// gRPC push backend of the scenario conditions
+pub struct PushSource {
+    tx: Sender<DdsData>,
+}
+
+impl PushSource {
+    /// Deliver the pushed signals subscribed to on `tx`
+    pub fn new(tx: Sender<DdsData>) -> Self {
+        Self { tx }
+    }
+}
+
+#[async_trait]
+impl ConditionSource for PushSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Grpc
+    }
+
+    async fn subscribe(&mut self, signal: &str) -> Result<()> {
+        pushed()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(signal.to_string(), self.tx.clone());
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, signal: &str) -> Result<()> {
+        pushed()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(signal);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pushed_signals_reach_the_filters() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let mut source = PushSource::new(tx);
+        let fields = HashMap::from([("speed".to_string(), "30".to_string())]);
+
+        let err = push("test/unknown", fields.clone()).await.unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)));
+
+        source.subscribe("test/speed").await.unwrap();
+        push("test/speed", fields.clone()).await.unwrap();
+        let data = rx.recv().await.unwrap();
+        assert_eq!(data.name, "GRPC:test/speed");
+        assert_eq!(data.fields["speed"], "30");
+
+        source.unsubscribe("test/speed").await.unwrap();
+        assert!(push("test/speed", fields).await.is_err());
+    }
+}
@@ -0,0 +1,244 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! SOME/IP messages received over UDP
+//!
+//! The messages sent to `filtergateway.someip_address` are received by a task
+//! started with the first subscription and stopped after the last one. Only
+//! the messages of subscribed `service/method` pairs without an error return
+//! code are sent, with their payload as `payload` in hex, its length as
+//! `length` and every byte as `byte0` onwards.
+
+use super::{byte_fields, hex, ConditionSource, SourceKind};
+use crate::vehicle::dds::DdsData;
+use async_trait::async_trait;
+use common::logd;
+use common::Result;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+/// Bytes of the SOME/IP header, the length field counts the last 8 of them
+const HEADER_LEN: usize = 16;
+/// Largest UDP datagram received
+const MAX_DATAGRAM: usize = 65_535;
+
+/// Parse a `service/method` pair, each in hex with a `0x` prefix or in decimal
+pub fn parse_signal(value: &str) -> Result<(u16, u16)> {
+    let invalid = || {
+        format!(
+            "invalid SOME/IP signal '{}', expected service/method",
+            value
+        )
+    };
+    let (service, method) = value.trim().split_once('/').ok_or_else(invalid)?;
+    let parse = |id: &str| {
+        let id = id.trim();
+        match id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => id.parse::<u16>(),
+        }
+    };
+    match (parse(service), parse(method)) {
+        (Ok(service), Ok(method)) => Ok((service, method)),
+        _ => Err(invalid().into()),
+    }
+}
+
+/// Signal name of the messages of a method of a service
+pub fn signal(service: u16, method: u16) -> String {
+    format!("0x{:04X}/0x{:04X}", service, method)
+}
+
+/// Service, method and payload of a SOME/IP message
+///
+/// `None` for a datagram too short for its header or length and for a
+/// message with an error return code.
+pub fn parse_message(datagram: &[u8]) -> Option<(u16, u16, &[u8])> {
+    if datagram.len() < HEADER_LEN {
+        return None;
+    }
+    let service = u16::from_be_bytes([datagram[0], datagram[1]]);
+    let method = u16::from_be_bytes([datagram[2], datagram[3]]);
+    let length = u32::from_be_bytes([datagram[4], datagram[5], datagram[6], datagram[7]]) as usize;
+    let return_code = datagram[15];
+    let end = length.checked_add(8)?;
+    if length < 8 || end > datagram.len() || return_code != 0 {
+        return None;
+    }
+    Some((service, method, &datagram[HEADER_LEN..end]))
+}
+
+/// Sample of a received message
+pub fn message_data(service: u16, method: u16, payload: &[u8]) -> DdsData {
+    let mut fields: HashMap<String, String> = byte_fields(payload).collect();
+    fields.insert("payload".to_string(), hex(payload));
+    fields.insert("length".to_string(), payload.len().to_string());
+    DdsData {
+        name: format!("{}:{}", SourceKind::SomeIp.name(), signal(service, method)),
+        value: hex(payload),
+        fields,
+    }
+}
+
+/// SOME/IP backend of the scenario conditions
+pub struct SomeIpSource {
+    address: String,
+    tx: Sender<DdsData>,
+    signals: Arc<Mutex<HashSet<(u16, u16)>>>,
+    receiver: Option<JoinHandle<()>>,
+}
+
+impl SomeIpSource {
+    pub fn new(address: String, tx: Sender<DdsData>) -> Self {
+        Self {
+            address,
+            tx,
+            signals: Arc::new(Mutex::new(HashSet::new())),
+            receiver: None,
+        }
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let socket = UdpSocket::bind(&self.address)
+            .await
+            .map_err(|e| format!("cannot bind SOME/IP address '{}': {}", self.address, e))?;
+        let (signals, tx) = (self.signals.clone(), self.tx.clone());
+        self.receiver = Some(tokio::spawn(async move {
+            let mut datagram = vec![0u8; MAX_DATAGRAM];
+            loop {
+                let len = match socket.recv(&mut datagram).await {
+                    Ok(len) => len,
+                    Err(e) => {
+                        logd!(5, "Error receiving SOME/IP message: {}", e);
+                        break;
+                    }
+                };
+                let Some((service, method, payload)) = parse_message(&datagram[..len]) else {
+                    continue;
+                };
+                let subscribed = signals
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .contains(&(service, method));
+                if subscribed
+                    && tx
+                        .send(message_data(service, method, payload))
+                        .await
+                        .is_err()
+                {
+                    break;
+                }
+            }
+        }));
+        logd!(2, "Receiving SOME/IP messages on {}", self.address);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConditionSource for SomeIpSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::SomeIp
+    }
+
+    async fn subscribe(&mut self, signal: &str) -> Result<()> {
+        let pair = parse_signal(signal)?;
+        self.signals
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(pair);
+        if self.receiver.as_ref().is_none_or(|task| task.is_finished()) {
+            self.start().await?;
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, signal: &str) -> Result<()> {
+        let pair = parse_signal(signal)?;
+        let mut signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        signals.remove(&pair);
+        if signals.is_empty() {
+            if let Some(task) = self.receiver.take() {
+                task.abort();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(service: u16, method: u16, return_code: u8, payload: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend(service.to_be_bytes());
+        message.extend(method.to_be_bytes());
+        message.extend((payload.len() as u32 + 8).to_be_bytes());
+        // client, session, protocol and interface versions, notification
+        message.extend([0, 1, 0, 1, 1, 1, 0x02, return_code]);
+        message.extend(payload);
+        message
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("0x1234/0x8001").unwrap(), (0x1234, 0x8001));
+        assert_eq!(parse_signal("4660 / 32769").unwrap(), (0x1234, 0x8001));
+        assert!(parse_signal("0x1234").is_err());
+        assert!(parse_signal("0x12345/1").is_err());
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let notification = message(0x1234, 0x8001, 0, &[7, 8]);
+        assert_eq!(
+            parse_message(&notification),
+            Some((0x1234, 0x8001, &[7u8, 8][..]))
+        );
+        assert_eq!(parse_message(&message(0x1234, 0x8001, 1, &[7])), None);
+        assert_eq!(parse_message(&notification[..17]), None);
+        assert_eq!(parse_message(&notification[..8]), None);
+
+        let data = message_data(0x1234, 0x8001, &[7, 8]);
+        assert_eq!(data.name, "SOMEIP:0x1234/0x8001");
+        assert_eq!(data.fields["payload"], "0708");
+        assert_eq!(data.fields["length"], "2");
+        assert_eq!(data.fields["byte1"], "8");
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_messages_are_sent() {
+        // A free port for the source to bind
+        let address = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let mut source = SomeIpSource::new(address.to_string(), tx);
+        source.subscribe("0x1234/0x8001").await.unwrap();
+
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender
+            .send_to(&message(0x1234, 0x9999, 0, &[1]), address)
+            .await
+            .unwrap();
+        sender
+            .send_to(&message(0x1234, 0x8001, 0, &[42]), address)
+            .await
+            .unwrap();
+        let data = tokio::time::timeout(std::time::Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.name, "SOMEIP:0x1234/0x8001");
+        assert_eq!(data.fields["byte0"], "42");
+
+        source.unsubscribe("0x1234/0x8001").await.unwrap();
+        assert!(source.receiver.is_none());
+    }
+}
@@ -0,0 +1,230 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Package states cascading from model states
+//!
+//! When a model changes state, every package containing it is evaluated
+//! again. The packages are found with one prefix read, then the states they
+//! are evaluated from are read in one batch: each model state once even when
+//! the model belongs to several packages, concurrently with at most
+//! `statemanager.cascade_concurrency` reads in flight. The packages are
+//! independent of each other and are evaluated from that batch without
+//! further reads, so that the latency of a cascade stays flat as the number
+//! of packages grows.
+
+use crate::state_machine::StateMachine;
+use crate::storage::Transaction;
+use common::logd;
+use common::spec::artifact::{Artifact, Package};
+use common::statemanager::PackageState;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Packages containing one of `models`, in the order of their keys
+pub async fn packages_containing(models: &[&str]) -> Result<Vec<Package>, String> {
+    let entries = crate::storage::storage()
+        .get_all_with_prefix("Package/")
+        .await
+        .map_err(|e| format!("Failed to get packages from ETCD: {:?}", e))?;
+    let mut packages = Vec::new();
+    for (key, yaml) in entries {
+        match serde_yaml::from_str::<Package>(&yaml) {
+            Ok(package) => {
+                if package
+                    .get_models()
+                    .iter()
+                    .any(|model| models.contains(&model.get_name().as_str()))
+                {
+                    packages.push(package);
+                }
+            }
+            Err(e) => logd!(4, "    Failed to parse package {}: {:?}", key, e),
+        }
+    }
+    Ok(packages)
+}
+
+/// Values of `keys` as they will be once `transaction` is committed, read
+/// with at most `limit` reads in flight
+///
+/// Keys that are not stored or could not be read are left out.
+pub async fn read_all(
+    transaction: &Transaction,
+    keys: BTreeSet<String>,
+    limit: usize,
+) -> HashMap<String, String> {
+    let transaction = Arc::new(transaction.clone());
+    let mut reads = JoinSet::new();
+    let mut values = HashMap::new();
+    let mut collect = |read: Result<(String, Result<String, String>), _>| match read {
+        Ok((key, Ok(value))) => {
+            values.insert(key, value);
+        }
+        Ok((_, Err(_))) => {}
+        Err(e) => logd!(4, "    State read task failed: {:?}", e),
+    };
+    for key in keys {
+        if reads.len() >= limit.max(1) {
+            if let Some(read) = reads.join_next().await {
+                collect(read);
+            }
+        }
+        let transaction = transaction.clone();
+        reads.spawn(async move {
+            let value = transaction.get(&key).await;
+            (key, value)
+        });
+    }
+    while let Some(read) = reads.join_next().await {
+        collect(read);
+    }
+    values
+}
+
+fn model_key(model_name: &str) -> String {
+    format!("/model/{}/state", model_name)
+}
+
+fn package_key(package_name: &str) -> String {
+    format!("/package/{}/state", package_name)
+}
+
+/// Evaluates `packages` against the states in `transaction`
+///
+/// Returns the packages whose state changed, with their new state, in the
+/// order of `packages`.
+pub async fn evaluate(
+    transaction: &Transaction,
+    packages: &[Package],
+) -> Vec<(String, PackageState)> {
+    let mut keys = BTreeSet::new();
+    for package in packages {
+        keys.insert(package_key(&package.get_qualified_name()));
+        for model in package.get_models() {
+            keys.insert(model_key(&model.get_name()));
+        }
+    }
+    let limit = common::setting::get_config()
+        .statemanager
+        .cascade_concurrency;
+    let stored = read_all(transaction, keys, limit).await;
+
+    let mut changed = Vec::new();
+    for package in packages {
+        let package_name = package.get_qualified_name();
+        logd!(2, "    Evaluating package state for: {}", package_name);
+        let model_states: Vec<_> = package
+            .get_models()
+            .into_iter()
+            .map(|model| {
+                let model_name = model.get_name();
+                let state = StateMachine::stored_model_state(
+                    &model_name,
+                    stored.get(&model_key(&model_name)).map(String::as_str),
+                );
+                (model_name, state)
+            })
+            .collect();
+        let current = StateMachine::stored_package_state(
+            &package_name,
+            stored.get(&package_key(&package_name)).map(String::as_str),
+        );
+        if let (true, new_state) =
+            StateMachine::package_state_change(&package_name, &model_states, current)
+        {
+            changed.push((package_name, new_state));
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, models: &[&str]) -> String {
+        let models: Vec<String> = models
+            .iter()
+            .map(|m| format!(
+                    r#"{{"name":"{}","node":"n","resources":{{"volume":"","network":"","realtime":false}}}}"#,
+                    m
+                ))
+            .collect();
+        format!(
+            r#"{{"apiVersion":"v1","kind":"Package","metadata":{{"name":"{}"}},"spec":{{"pattern":[],"models":[{}]}}}}"#,
+            name,
+            models.join(",")
+        )
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_packages_sharing_a_model() {
+        common::etcd::use_in_memory_store();
+        for (name, models) in [
+            ("cascade-a", vec!["cascade-m1", "cascade-m2"]),
+            ("cascade-b", vec!["cascade-m1"]),
+            ("cascade-c", vec!["cascade-m3"]),
+        ] {
+            common::etcd::put(&format!("Package/{}", name), &package(name, &models))
+                .await
+                .unwrap();
+        }
+        common::etcd::put("/model/cascade-m2/state", "Running")
+            .await
+            .unwrap();
+        common::etcd::put("/package/cascade-b/state", "PACKAGE_STATE_ERROR")
+            .await
+            .unwrap();
+
+        let packages = packages_containing(&["cascade-m1"]).await.unwrap();
+        let names: Vec<String> = packages.iter().map(|p| p.get_qualified_name()).collect();
+        assert_eq!(names, vec!["cascade-a", "cascade-b"]);
+
+        let mut transaction = Transaction::default();
+        transaction.put("/model/cascade-m1/state", "Dead");
+        let changed = evaluate(&transaction, &packages).await;
+        assert_eq!(
+            changed,
+            vec![("cascade-a".to_string(), PackageState::Degraded)]
+        );
+
+        transaction.put("/model/cascade-m1/state", "Running");
+        let changed = evaluate(&transaction, &packages).await;
+        assert_eq!(
+            changed,
+            vec![
+                ("cascade-a".to_string(), PackageState::Running),
+                ("cascade-b".to_string(), PackageState::Running),
+            ]
+        );
+
+        for name in ["cascade-a", "cascade-b", "cascade-c"] {
+            common::etcd::delete(&format!("Package/{}", name))
+                .await
+                .unwrap();
+        }
+        common::etcd::delete("/model/cascade-m2/state")
+            .await
+            .unwrap();
+        common::etcd::delete("/package/cascade-b/state")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_all_is_bounded_and_skips_missing_keys() {
+        common::etcd::use_in_memory_store();
+        let mut transaction = Transaction::default();
+        let keys: BTreeSet<String> = (0..20).map(|i| format!("/cascade/read/{}", i)).collect();
+        for key in keys.iter().step_by(2) {
+            transaction.put(key, "v");
+        }
+        let values = read_all(&transaction, keys, 3).await;
+        assert_eq!(values.len(), 10);
+        assert!(values.contains_key("/cascade/read/0"));
+        assert!(!values.contains_key("/cascade/read/1"));
+    }
+}
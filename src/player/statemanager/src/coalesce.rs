@@ -0,0 +1,489 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Coalescing of state writes
+//!
+//! The manager writes the state a resource ends in after every transition
+//! it processes, even when the state did not change, and every write is a
+//! new etcd revision. [`CoalescingStorage`] sits in front of the backend
+//! selected in settings.yaml and remembers the `/state` values it wrote:
+//! * a state written with the value it already has is skipped
+//! * a state written again within `statemanager.write_coalesce_window_ms`
+//!   of its last write is held back until the window ends, then only the
+//!   last value is written, nothing if the state flipped back meanwhile
+//!
+//! Held back values are returned by reads. A held back value that cannot be
+//! written is kept and written again every window. Until then the key is
+//! dirty: reads of it fail with the error of the write, and the next write
+//! of it goes straight to the backend and returns its result.
+//!
+//! A remembered value is trusted
+//! for `statemanager.write_cache_ttl_secs` only, as the apiserver also
+//! writes states when it restores a snapshot or collects garbage. Writes
+//! that never reach the backend are counted in
+//! `piccolo_state_writes_suppressed_total` on `/metrics`.
+
+use crate::storage::{StateStorage, Transaction};
+use async_trait::async_trait;
+use common::logd;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Writes suppressed by every coalescing storage
+static SUPPRESSED: AtomicU64 = AtomicU64::new(0);
+
+/// Writes that did not reach a backend since the start
+pub fn suppressed() -> u64 {
+    SUPPRESSED.load(Ordering::Relaxed)
+}
+
+/// Counter of the suppressed writes in the Prometheus text format
+pub fn render() -> String {
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "# HELP piccolo_state_writes_suppressed_total State writes skipped as unchanged or coalesced"
+    );
+    let _ = writeln!(text, "# TYPE piccolo_state_writes_suppressed_total counter");
+    let _ = writeln!(
+        text,
+        "piccolo_state_writes_suppressed_total {}",
+        suppressed()
+    );
+    text
+}
+
+/// Whether writes of a key are coalesced
+fn is_state(key: &str) -> bool {
+    key.ends_with("/state")
+}
+
+/// Last write of a state key
+#[derive(Debug)]
+struct Entry {
+    /// Value the backend holds
+    stored: String,
+    /// When it was written
+    written: Instant,
+    /// Value held back until the window of the last write ends
+    pending: Option<String>,
+    /// Error of the last attempt to write the held back value
+    failed: Option<String>,
+}
+
+/// What a put does with the backend
+enum Put {
+    Write,
+    Skip,
+    /// Write the held back value at the given time
+    Hold(Instant),
+}
+
+struct Shared {
+    inner: Arc<dyn StateStorage>,
+    entries: Mutex<HashMap<String, Entry>>,
+    suppressed: AtomicU64,
+}
+
+impl Shared {
+    fn with_entries<R>(&self, f: impl FnOnce(&mut HashMap<String, Entry>) -> R) -> R {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut entries)
+    }
+
+    fn suppress(&self, count: u64) {
+        self.suppressed.fetch_add(count, Ordering::Relaxed);
+        SUPPRESSED.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn remember(&self, key: &str, value: &str) {
+        self.with_entries(|entries| {
+            entries.insert(
+                key.to_string(),
+                Entry {
+                    stored: value.to_string(),
+                    written: common::clock::instant(),
+                    pending: None,
+                    failed: None,
+                },
+            )
+        });
+    }
+
+    fn forget(&self, key: &str) {
+        self.with_entries(|entries| entries.remove(key));
+    }
+
+    /// Write the value held back for a key, if it still differs
+    ///
+    /// The value stays held back until it is written. Returns whether a
+    /// value is still held back, because the write failed or a newer value
+    /// arrived meanwhile.
+    async fn flush(&self, key: &str) -> bool {
+        let pending = self.with_entries(|entries| {
+            let entry = entries.get_mut(key)?;
+            if entry.pending.as_ref()? == &entry.stored {
+                entry.pending = None;
+                return Some(None);
+            }
+            entry.pending.clone().map(Some)
+        });
+        let value = match pending {
+            Some(Some(value)) => value,
+            Some(None) => {
+                self.suppress(1);
+                return false;
+            }
+            None => return false,
+        };
+
+        let result = self.inner.put(key, &value).await;
+        self.with_entries(|entries| {
+            // Deleted or written directly meanwhile
+            let Some(entry) = entries.get_mut(key) else {
+                return false;
+            };
+            match result {
+                Ok(()) => {
+                    entry.stored = value.clone();
+                    entry.written = common::clock::instant();
+                    entry.failed = None;
+                    if entry.pending.as_ref() == Some(&value) {
+                        entry.pending = None;
+                    }
+                }
+                Err(e) => {
+                    logd!(
+                        4,
+                        "Failed to write coalesced state {}, retrying: {}",
+                        key,
+                        e
+                    );
+                    entry.failed = Some(e);
+                }
+            }
+            entry.pending.is_some()
+        })
+    }
+
+    /// Error of the last write of a held back value that failed
+    fn failure(&self, key: &str) -> Option<String> {
+        self.with_entries(|entries| entries.get(key)?.failed.clone())
+    }
+}
+
+/// Backend whose state writes are skipped when unchanged and coalesced
+pub struct CoalescingStorage {
+    shared: Arc<Shared>,
+    window: Duration,
+    ttl: Duration,
+}
+
+impl CoalescingStorage {
+    pub fn new(inner: Arc<dyn StateStorage>, window: Duration, ttl: Duration) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                inner,
+                entries: Mutex::new(HashMap::new()),
+                suppressed: AtomicU64::new(0),
+            }),
+            window,
+            ttl,
+        }
+    }
+
+    /// Writes of this storage that did not reach the backend
+    pub fn suppressed(&self) -> u64 {
+        self.shared.suppressed.load(Ordering::Relaxed)
+    }
+
+    fn decide(&self, key: &str, value: &str) -> Put {
+        let now = common::clock::instant();
+        let (put, suppressed) = self.shared.with_entries(|entries| {
+            let Some(entry) = entries.get_mut(key) else {
+                return (Put::Write, 0);
+            };
+            // A held back value replaced by a newer one is never written
+            let replaced = entry.pending.take().is_some() as u64;
+            if entry.failed.is_some() || now.duration_since(entry.written) >= self.ttl {
+                return (Put::Write, replaced);
+            }
+            let deadline = entry.written + self.window;
+            if entry.stored == value {
+                (Put::Skip, replaced + 1)
+            } else if now < deadline {
+                entry.pending = Some(value.to_string());
+                // The flush is already scheduled for a replaced value
+                match replaced {
+                    0 => (Put::Hold(deadline), 0),
+                    _ => (Put::Skip, replaced),
+                }
+            } else {
+                (Put::Write, replaced)
+            }
+        });
+        self.shared.suppress(suppressed);
+        put
+    }
+}
+
+#[async_trait]
+impl StateStorage for CoalescingStorage {
+    fn name(&self) -> &'static str {
+        self.shared.inner.name()
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        if let Some(e) = self.shared.failure(key) {
+            return Err(format!("State {} is not written yet: {}", key, e));
+        }
+        let pending = self
+            .shared
+            .with_entries(|entries| entries.get(key).and_then(|entry| entry.pending.clone()));
+        match pending {
+            Some(value) => Ok(value),
+            None => self.shared.inner.get(key).await,
+        }
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        if !is_state(key) || self.ttl.is_zero() {
+            return self.shared.inner.put(key, value).await;
+        }
+        match self.decide(key, value) {
+            Put::Write => {
+                let result = self.shared.inner.put(key, value).await;
+                match &result {
+                    Ok(()) => self.shared.remember(key, value),
+                    Err(_) => self.shared.forget(key),
+                }
+                result
+            }
+            Put::Skip => Ok(()),
+            Put::Hold(deadline) => {
+                let shared = Arc::clone(&self.shared);
+                let key = key.to_string();
+                let window = self.window;
+                tokio::spawn(async move {
+                    tokio::time::sleep_until(deadline.into()).await;
+                    while shared.flush(&key).await {
+                        tokio::time::sleep(window).await;
+                    }
+                });
+                Ok(())
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.shared.forget(key);
+        self.shared.inner.delete(key).await
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        let mut pairs: BTreeMap<String, String> = self
+            .shared
+            .inner
+            .get_all_with_prefix(prefix)
+            .await?
+            .into_iter()
+            .collect();
+        self.shared.with_entries(|entries| {
+            for (key, entry) in entries.iter().filter(|(key, _)| key.starts_with(prefix)) {
+                if let Some(e) = &entry.failed {
+                    return Err(format!("State {} is not written yet: {}", key, e));
+                }
+                if let Some(value) = &entry.pending {
+                    pairs.insert(key.clone(), value.clone());
+                }
+            }
+            Ok(())
+        })?;
+        Ok(pairs.into_iter().collect())
+    }
+
+    /// Commit the writes that change a state, held back values of the
+    /// committed keys are superseded
+    async fn commit(&self, transaction: Transaction) -> Result<(), String> {
+        if self.ttl.is_zero() {
+            return self.shared.inner.commit(transaction).await;
+        }
+        let now = common::clock::instant();
+        let mut changed = Transaction::default();
+        let suppressed = self.shared.with_entries(|entries| {
+            let mut suppressed = 0;
+            for (key, value) in transaction.writes() {
+                let unchanged = match entries.get_mut(key) {
+                    Some(entry) if is_state(key) => {
+                        suppressed += entry.pending.take().is_some() as u64;
+                        entry.failed.is_none()
+                            && now.duration_since(entry.written) < self.ttl
+                            && entry.stored == *value
+                    }
+                    _ => false,
+                };
+                if unchanged {
+                    suppressed += 1;
+                } else {
+                    changed.put(key, value);
+                }
+            }
+            suppressed
+        });
+        self.shared.suppress(suppressed);
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let writes = changed.writes().to_vec();
+        let result = self.shared.inner.commit(changed).await;
+        for (key, value) in writes.iter().filter(|(key, _)| is_state(key)) {
+            match &result {
+                Ok(()) => self.shared.remember(key, value),
+                Err(_) => self.shared.forget(key),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::{Fault, FaultScript, FaultyStorage, Operation, Rule};
+    use crate::storage::InMemoryStateStorage;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    fn coalescing(backend: &Arc<InMemoryStateStorage>) -> CoalescingStorage {
+        CoalescingStorage::new(backend.clone(), WINDOW, Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_state_is_not_written() {
+        let backend = Arc::new(InMemoryStateStorage::default());
+        let storage = coalescing(&backend);
+
+        storage.put("/model/m/state", "Running").await.unwrap();
+        storage.put("/model/m/state", "Running").await.unwrap();
+        assert_eq!(storage.suppressed(), 1);
+
+        // Other keys are always written
+        storage.put("/model/m/node", "HPC").await.unwrap();
+        storage.put("/model/m/node", "HPC").await.unwrap();
+        assert_eq!(storage.suppressed(), 1);
+
+        let mut transaction = Transaction::default();
+        transaction.put("/model/m/state", "Running");
+        transaction.put("/package/p/state", "running");
+        storage.commit(transaction).await.unwrap();
+        assert_eq!(storage.suppressed(), 2);
+        assert_eq!(backend.get("/package/p/state").await.unwrap(), "running");
+    }
+
+    #[tokio::test]
+    async fn test_rapid_changes_are_coalesced() {
+        let backend = Arc::new(InMemoryStateStorage::default());
+        let storage = coalescing(&backend);
+
+        storage.put("/model/m/state", "Running").await.unwrap();
+        storage.put("/model/m/state", "Dead").await.unwrap();
+        storage.put("/model/m/state", "Exited").await.unwrap();
+        assert_eq!(storage.get("/model/m/state").await.unwrap(), "Exited");
+        assert_eq!(backend.get("/model/m/state").await.unwrap(), "Running");
+        assert_eq!(
+            storage.get_all_with_prefix("/model/").await.unwrap(),
+            vec![("/model/m/state".to_string(), "Exited".to_string())]
+        );
+
+        tokio::time::sleep(WINDOW * 2).await;
+        assert_eq!(backend.get("/model/m/state").await.unwrap(), "Exited");
+        assert_eq!(storage.suppressed(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flip_flop_is_not_written() {
+        let backend = Arc::new(InMemoryStateStorage::default());
+        let storage = coalescing(&backend);
+
+        storage.put("/model/m/state", "Running").await.unwrap();
+        storage.put("/model/m/state", "Dead").await.unwrap();
+        storage.put("/model/m/state", "Running").await.unwrap();
+        tokio::time::sleep(WINDOW * 2).await;
+        assert_eq!(backend.get("/model/m/state").await.unwrap(), "Running");
+        assert_eq!(storage.suppressed(), 2);
+
+        // The window has passed, a change is written at once
+        storage.put("/model/m/state", "Dead").await.unwrap();
+        assert_eq!(backend.get("/model/m/state").await.unwrap(), "Dead");
+    }
+
+    #[tokio::test]
+    async fn test_expired_or_deleted_state_is_written() {
+        let backend = Arc::new(InMemoryStateStorage::default());
+        let storage = CoalescingStorage::new(backend.clone(), Duration::ZERO, WINDOW);
+
+        storage.put("/model/m/state", "Running").await.unwrap();
+        backend.put("/model/m/state", "Dead").await.unwrap();
+        tokio::time::sleep(WINDOW).await;
+        storage.put("/model/m/state", "Running").await.unwrap();
+        assert_eq!(backend.get("/model/m/state").await.unwrap(), "Running");
+
+        storage.delete("/model/m/state").await.unwrap();
+        storage.put("/model/m/state", "Running").await.unwrap();
+        assert_eq!(backend.get("/model/m/state").await.unwrap(), "Running");
+        assert_eq!(storage.suppressed(), 0);
+    }
+
+    fn failing_once(key: &str) -> CoalescingStorage {
+        // The first write of the key goes through, the second one fails
+        let script = FaultScript::new(vec![Rule::new(
+            Operation::Put,
+            Fault::Error("backend down".to_string()),
+        )
+        .on(key)
+        .after(1)
+        .times(1)]);
+        let backend = FaultyStorage::new(Box::new(InMemoryStateStorage::default()), script);
+        CoalescingStorage::new(Arc::new(backend), WINDOW, Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_is_retried() {
+        let storage = failing_once("/model/m/state");
+
+        storage.put("/model/m/state", "Running").await.unwrap();
+        storage.put("/model/m/state", "Dead").await.unwrap();
+        tokio::time::sleep(WINDOW * 3 / 2).await;
+        // The held back value was not written
+        let error = storage.get("/model/m/state").await.unwrap_err();
+        assert!(error.contains("backend down"));
+        assert!(storage.get_all_with_prefix("/model/").await.is_err());
+
+        tokio::time::sleep(WINDOW).await;
+        assert_eq!(storage.get("/model/m/state").await.unwrap(), "Dead");
+        assert_eq!(storage.suppressed(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_write_after_failed_flush_goes_to_the_backend() {
+        let storage = failing_once("/model/m/state");
+
+        storage.put("/model/m/state", "Running").await.unwrap();
+        storage.put("/model/m/state", "Dead").await.unwrap();
+        tokio::time::sleep(WINDOW * 3 / 2).await;
+        assert!(storage.get("/model/m/state").await.is_err());
+
+        // Written at once, the failed value is superseded
+        storage.put("/model/m/state", "Exited").await.unwrap();
+        assert_eq!(storage.get("/model/m/state").await.unwrap(), "Exited");
+        tokio::time::sleep(WINDOW * 2).await;
+        assert_eq!(storage.get("/model/m/state").await.unwrap(), "Exited");
+        assert_eq!(storage.suppressed(), 1);
+    }
+}
@@ -0,0 +1,437 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Conformance of the state machine to the LLD transition tables
+//!
+//! The transitions of StateManager_Scenario.md, StateManager_Package.md and
+//! the network setup are kept below as a plain table. The state machine is
+//! driven through every row and every pair of states of these types, then
+//! through random interleavings of state changes, checking that:
+//! - exactly the transitions of the table succeed, with their actions
+//! - terminal states are only left on a recovery event
+//! - failed transitions are counted and mark a resource unhealthy after
+//!   [`MAX_CONSECUTIVE_FAILURES`], until a transition succeeds again
+//! - package states follow the states of their models, crash looping models
+//!   count as dead and models do not run before their network is ready
+
+use crate::state_machine::StateMachine;
+use crate::types::ActionCommand;
+use common::monitoringserver::ContainerInfo;
+use common::state_mapping;
+use common::statemanager::{
+    ErrorCode, ModelState, NetworkState, PackageState, ResourceType, ScenarioState, StateChange,
+    CANARY_PROMOTED, CANARY_ROLLED_BACK,
+};
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+/// Transitions of the LLD: resource type, from state, event, to state, action
+const LLD_TRANSITIONS: &str = "
+scenario  Idle         scenario_activation          Waiting    start_condition_evaluation
+scenario  Waiting      condition_met                Satisfied  start_policy_verification
+scenario  Satisfied    policy_verification_success  Allowed    execute_action_on_target_package
+scenario  Satisfied    activation_deferred          Waiting    start_condition_evaluation
+scenario  Satisfied    policy_verification_failure  Denied     log_denial_generate_alert
+scenario  Allowed      scenario_completion          Completed  finalize_scenario
+scenario  Allowed      admission_deferred           Pending    wait_for_capacity
+scenario  Allowed      admission_denied             Denied     log_denial_generate_alert
+scenario  Pending      scenario_completion          Completed  finalize_scenario
+scenario  Pending      admission_denied             Denied     log_denial_generate_alert
+scenario  Completed    schedule_rearmed             Waiting    start_condition_evaluation
+scenario  Denied       schedule_rearmed             Waiting    start_condition_evaluation
+scenario  Idle         scenario_retrigger           Satisfied  start_policy_verification
+scenario  Allowed      scenario_retrigger           Satisfied  start_policy_verification
+scenario  Pending      scenario_retrigger           Satisfied  start_policy_verification
+scenario  Completed    scenario_retrigger           Satisfied  start_policy_verification
+scenario  Denied       scenario_retrigger           Satisfied  start_policy_verification
+scenario  Unspecified  scenario_deactivation        Idle       stop_condition_evaluation
+scenario  Waiting      scenario_deactivation        Idle       stop_condition_evaluation
+scenario  Satisfied    scenario_deactivation        Idle       stop_condition_evaluation
+scenario  Allowed      scenario_deactivation        Idle       stop_condition_evaluation
+scenario  Pending      scenario_deactivation        Idle       stop_condition_evaluation
+scenario  Completed    scenario_deactivation        Idle       stop_condition_evaluation
+scenario  Denied       scenario_deactivation        Idle       stop_condition_evaluation
+
+network   Unspecified  network_setup_requested      Requested  wait_for_network_setup
+network   Requested    network_setup_succeeded      Ready      release_dependent_models
+network   Requested    network_setup_failed         Failed     log_network_failure
+network   Failed       network_setup_retry          Requested  wait_for_network_setup
+network   Ready        network_setup_requested      Requested  wait_for_network_setup
+
+package   Unspecified  update_started               Updating   track_update_progress
+package   Idle         update_started               Updating   track_update_progress
+package   Running      update_started               Updating   track_update_progress
+package   Degraded     update_started               Updating   track_update_progress
+package   Error        update_started               Updating   track_update_progress
+package   Paused       update_started               Updating   track_update_progress
+package   Exited       update_started               Updating   track_update_progress
+package   Updating     update_completed             Running    update_state_announce_availability
+package   Updating     canary_promoted              Running    announce_canary_promotion
+package   Updating     canary_rolled_back           Running    alert_canary_rollback
+package   Updating     update_failed                Error      log_update_failure
+";
+
+/// States a resource does not leave on its own
+const TERMINAL_STATES: [(&str, &str); 4] = [
+    ("scenario", "Completed"),
+    ("scenario", "Denied"),
+    ("network", "Failed"),
+    ("package", "Error"),
+];
+
+/// Events allowed to move a resource out of a terminal state
+const RECOVERY_EVENTS: [&str; 5] = [
+    "network_setup_retry",
+    "update_started",
+    "scenario_deactivation",
+    "schedule_rearmed",
+    "scenario_retrigger",
+];
+
+/// Same as the state machine, failures before a resource is unhealthy
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// Resource types whose transitions are requested through StateChange
+const TYPES: [ResourceType; 3] = [
+    ResourceType::Scenario,
+    ResourceType::Package,
+    ResourceType::Network,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    resource_type: ResourceType,
+    from: i32,
+    event: String,
+    to: i32,
+    action: String,
+}
+
+fn parse_type(name: &str) -> ResourceType {
+    match name {
+        "scenario" => ResourceType::Scenario,
+        "package" => ResourceType::Package,
+        "network" => ResourceType::Network,
+        _ => panic!("unknown resource type {name}"),
+    }
+}
+
+fn parse_state(resource_type: ResourceType, name: &str) -> i32 {
+    if name == "Unspecified" {
+        return 0;
+    }
+    state_mapping::parse_state(resource_type, name)
+        .unwrap_or_else(|| panic!("unknown {resource_type:?} state {name}"))
+}
+
+fn name(resource_type: ResourceType, state: i32) -> &'static str {
+    state_mapping::state_name(resource_type, state).unwrap()
+}
+
+fn lld_table() -> Vec<Row> {
+    LLD_TRANSITIONS
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let [resource_type, from, event, to, action] = columns[..] else {
+                panic!("malformed transition: {line}");
+            };
+            let resource_type = parse_type(resource_type);
+            Row {
+                resource_type,
+                from: parse_state(resource_type, from),
+                event: event.to_string(),
+                to: parse_state(resource_type, to),
+                action: action.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Every state of a type, the unspecified one included
+fn states(resource_type: ResourceType) -> Vec<i32> {
+    (0..16)
+        .filter(|state| state_mapping::state_name(resource_type, *state).is_some())
+        .collect()
+}
+
+fn is_terminal(resource_type: ResourceType, state: i32) -> bool {
+    TERMINAL_STATES.iter().any(|(type_name, state_name)| {
+        parse_type(type_name) == resource_type && parse_state(resource_type, state_name) == state
+    })
+}
+
+/// Sub-state telling the state machine which update outcome is reported
+fn sub_state(event: &str) -> String {
+    match event {
+        "canary_promoted" => CANARY_PROMOTED.to_string(),
+        "canary_rolled_back" => format!("{CANARY_ROLLED_BACK}: probe failed"),
+        _ => String::new(),
+    }
+}
+
+fn change(resource_type: ResourceType, resource: &str, from: i32, to: i32) -> StateChange {
+    StateChange {
+        resource_type: resource_type as i32,
+        resource_name: resource.to_string(),
+        current_state: name(resource_type, from).to_string(),
+        target_state: name(resource_type, to).to_string(),
+        transition_id: format!("conformance-{resource}-{from}-{to}"),
+        source: "conformance".to_string(),
+        ..Default::default()
+    }
+}
+
+fn container(status: &str, crash_looping: bool) -> ContainerInfo {
+    let mut state = HashMap::from([("Status".to_string(), status.to_string())]);
+    if crash_looping {
+        state.insert("CrashLoopBackOff".to_string(), "true".to_string());
+    }
+    ContainerInfo {
+        id: "c".to_string(),
+        names: vec!["m_c".to_string()],
+        image: "img".to_string(),
+        state,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_lld_rows_succeed_with_their_action() {
+    for row in lld_table() {
+        let mut state_machine = StateMachine::new();
+        let mut actions = state_machine.initialize_action_executor();
+        let mut state_change = change(row.resource_type, "lld", row.from, row.to);
+        state_change.sub_state = sub_state(&row.event);
+
+        let result = state_machine.process_state_change(state_change);
+        assert!(result.is_success(), "{row:?}: {}", result.message);
+        assert_eq!(result.new_state, row.to, "{row:?}");
+        let ActionCommand { action, .. } = actions.try_recv().unwrap();
+        assert_eq!(action, row.action, "{row:?}");
+    }
+}
+
+#[test]
+fn test_every_state_pair_follows_lld_table() {
+    let table = lld_table();
+    for resource_type in TYPES {
+        for from in states(resource_type) {
+            for to in states(resource_type).into_iter().filter(|to| *to != from) {
+                let legal = table.iter().any(|row| {
+                    row.resource_type == resource_type && (row.from, row.to) == (from, to)
+                });
+                let mut state_machine = StateMachine::new();
+                let result =
+                    state_machine.process_state_change(change(resource_type, "pair", from, to));
+                let pair = format!(
+                    "{resource_type:?} {} -> {}",
+                    name(resource_type, from),
+                    name(resource_type, to)
+                );
+
+                assert_eq!(result.is_success(), legal, "{pair}: {}", result.message);
+                if legal {
+                    assert_eq!(result.new_state, to, "{pair}");
+                } else {
+                    assert_eq!(
+                        result.error_code,
+                        ErrorCode::InvalidStateTransition,
+                        "{pair}"
+                    );
+                    assert_eq!(result.new_state, from, "{pair}");
+                    assert!(
+                        state_machine
+                            .get_resource_state("pair", resource_type)
+                            .is_none(),
+                        "{pair}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_terminal_states_are_only_left_on_recovery() {
+    let table = lld_table();
+    for row in &table {
+        if is_terminal(row.resource_type, row.from) {
+            assert!(RECOVERY_EVENTS.contains(&row.event.as_str()), "{row:?}");
+        }
+    }
+    for state in ["Completed", "Denied"] {
+        let state = parse_state(ResourceType::Scenario, state);
+        // Only operators or the scheduler move a finished scenario
+        assert!(table
+            .iter()
+            .filter(|row| row.resource_type == ResourceType::Scenario && row.from == state)
+            .all(|row| [
+                "scenario_deactivation",
+                "schedule_rearmed",
+                "scenario_retrigger"
+            ]
+            .contains(&row.event.as_str())));
+    }
+}
+
+/// Expected state of a resource driven through random state changes
+#[derive(Debug, Clone, Copy)]
+struct Expected {
+    state: i32,
+    tracked: bool,
+    failures: u32,
+}
+
+fn initial_state(resource_type: ResourceType) -> i32 {
+    match resource_type {
+        ResourceType::Scenario => ScenarioState::Idle as i32,
+        ResourceType::Package => PackageState::Running as i32,
+        _ => NetworkState::Unspecified as i32,
+    }
+}
+
+/// Resource type, one of three resources, and an index into the states
+fn state_change_op() -> impl Strategy<Value = (usize, usize, usize)> {
+    (0..TYPES.len(), 0..3usize, 0..8usize)
+}
+
+proptest! {
+    #[test]
+    fn interleaved_changes_follow_lld_table(ops in prop::collection::vec(state_change_op(), 1..64)) {
+        let table = lld_table();
+        let mut state_machine = StateMachine::new();
+        let mut expected: HashMap<(usize, usize), Expected> = HashMap::new();
+
+        for (type_index, resource, state_index) in ops {
+            let resource_type = TYPES[type_index];
+            let type_states = states(resource_type);
+            let target = type_states[state_index % type_states.len()];
+            let resource_name = format!("r{resource}");
+            let before = *expected.entry((type_index, resource)).or_insert(Expected {
+                state: initial_state(resource_type),
+                tracked: false,
+                failures: 0,
+            });
+
+            let result = state_machine.process_state_change(change(
+                resource_type,
+                &resource_name,
+                before.state,
+                target,
+            ));
+            let row = table.iter().find(|row| {
+                row.resource_type == resource_type && (row.from, row.to) == (before.state, target)
+            });
+            let after = if target == before.state {
+                prop_assert_eq!(result.error_code, ErrorCode::InvalidRequest);
+                before
+            } else if let Some(row) = row {
+                prop_assert!(result.is_success(), "{:?}: {}", row, result.message);
+                prop_assert!(
+                    !is_terminal(resource_type, before.state)
+                        || RECOVERY_EVENTS.contains(&row.event.as_str())
+                );
+                Expected { state: target, tracked: true, failures: 0 }
+            } else {
+                prop_assert_eq!(result.error_code, ErrorCode::InvalidStateTransition);
+                Expected {
+                    failures: before.failures + u32::from(before.tracked),
+                    ..before
+                }
+            };
+            prop_assert_eq!(result.new_state, after.state);
+            expected.insert((type_index, resource), after);
+
+            let tracked = state_machine.get_resource_state(&resource_name, resource_type);
+            prop_assert_eq!(tracked.is_some(), after.tracked);
+            if let Some(tracked) = tracked {
+                prop_assert_eq!(tracked.current_state, after.state);
+                prop_assert_eq!(tracked.health_status.consecutive_failures, after.failures);
+                prop_assert_eq!(
+                    tracked.health_status.healthy,
+                    after.failures < MAX_CONSECUTIVE_FAILURES
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn package_state_follows_model_states(models in prop::collection::vec(1..7i32, 0..8)) {
+        let state_machine = StateMachine::new();
+        let models: Vec<(String, ModelState)> = models
+            .into_iter()
+            .enumerate()
+            .map(|(i, state)| (format!("m{i}"), ModelState::try_from(state).unwrap()))
+            .collect();
+        let count = |f: fn(&ModelState) -> bool| models.iter().filter(|(_, s)| f(s)).count();
+        let dead = count(|s| matches!(s, ModelState::Dead | ModelState::CrashLoopBackOff));
+        let paused = count(|s| *s == ModelState::Paused);
+        let exited = count(|s| *s == ModelState::Exited);
+
+        let expected = if models.is_empty() {
+            PackageState::Idle
+        } else if dead == models.len() {
+            PackageState::Error
+        } else if dead > 0 {
+            PackageState::Degraded
+        } else if paused == models.len() {
+            PackageState::Paused
+        } else if exited == models.len() {
+            PackageState::Exited
+        } else {
+            PackageState::Running
+        };
+        prop_assert_eq!(state_machine.evaluate_package_state_from_models(&models), expected);
+    }
+
+    #[test]
+    fn models_follow_containers_and_network(
+        ops in prop::collection::vec((any::<bool>(), 0..5usize, any::<bool>()), 1..48)
+    ) {
+        let mut state_machine = StateMachine::new();
+        let mut network = NetworkState::Unspecified as i32;
+
+        for (is_network, index, crash_looping) in ops {
+            if is_network {
+                let target = states(ResourceType::Network)[index % 4];
+                if target != network
+                    && state_machine
+                        .process_state_change(change(ResourceType::Network, "m", network, target))
+                        .is_success()
+                {
+                    network = target;
+                }
+                continue;
+            }
+
+            let was_running = state_machine
+                .get_resource_state("m", ResourceType::Model)
+                .is_some_and(|rs| rs.current_state == ModelState::Running as i32);
+            let status = ["running", "paused", "exited", "dead", "running"][index];
+            let container = container(status, crash_looping);
+            let result = state_machine.process_model_state_update("m", &[&container]);
+            prop_assert!(result.is_success());
+
+            let model = ModelState::try_from(result.new_state).unwrap();
+            if crash_looping {
+                prop_assert_eq!(model, ModelState::CrashLoopBackOff);
+            }
+            if model == ModelState::Running && !was_running {
+                prop_assert!(
+                    network == NetworkState::Unspecified as i32
+                        || network == NetworkState::Ready as i32
+                );
+            }
+            let package = state_machine
+                .evaluate_package_state_from_models(&[("m".to_string(), model)]);
+            prop_assert_eq!(
+                package == PackageState::Error,
+                matches!(model, ModelState::Dead | ModelState::CrashLoopBackOff)
+            );
+        }
+    }
+}
@@ -0,0 +1,294 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Last known containers of every node
+//!
+//! NodeAgents report full ContainerList snapshots while single container
+//! events arrive as `UpdateContainerStateRequest`s. Both are merged here so
+//! that model evaluation always sees every container of a model with its
+//! latest state.
+//!
+//! Replacing a node's snapshot reports which containers changed, so that only
+//! the models of those containers have to be re-evaluated.
+
+use common::monitoringserver::ContainerInfo;
+use common::spec::k8s::pod::{model_of, LABEL_MODEL};
+use common::statemanager::UpdateContainerStateRequest;
+use std::collections::HashMap;
+
+/// Containers that differ between two snapshots of a node
+#[derive(Debug, Default, PartialEq)]
+pub struct NodeDiff {
+    pub added: Vec<ContainerInfo>,
+    /// New version of containers whose state, identity or configuration changed
+    pub changed: Vec<ContainerInfo>,
+    pub removed: Vec<ContainerInfo>,
+}
+
+impl NodeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Every container of the diff, removed ones in their last known version
+    pub fn into_containers(self) -> Vec<ContainerInfo> {
+        let mut containers = self.added;
+        containers.extend(self.changed);
+        containers.extend(self.removed);
+        containers
+    }
+}
+
+/// Whether two versions of a container differ in anything but their statistics
+///
+/// Resource statistics change with every report and do not affect states.
+fn differs(old: &ContainerInfo, new: &ContainerInfo) -> bool {
+    old.state != new.state
+        || old.annotation != new.annotation
+        || old.names != new.names
+        || old.image != new.image
+        || old.config != new.config
+}
+
+/// Containers indexed by node name, then container id
+#[derive(Debug, Default)]
+pub struct ContainerCache {
+    nodes: HashMap<String, HashMap<String, ContainerInfo>>,
+}
+
+impl ContainerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the containers of a node with a full snapshot
+    ///
+    /// ### Returns
+    /// * `NodeDiff` - containers added, changed or removed since the previous snapshot
+    pub fn replace_node(&mut self, node_name: &str, containers: &[ContainerInfo]) -> NodeDiff {
+        let snapshot: HashMap<String, ContainerInfo> = containers
+            .iter()
+            .map(|c| (c.id.clone(), c.clone()))
+            .collect();
+        let previous = self
+            .nodes
+            .insert(node_name.to_string(), snapshot.clone())
+            .unwrap_or_default();
+
+        let mut diff = NodeDiff::default();
+        for (id, container) in snapshot {
+            match previous.get(&id) {
+                None => diff.added.push(container),
+                Some(old) if differs(old, &container) => diff.changed.push(container),
+                Some(_) => {}
+            }
+        }
+        diff.removed = previous
+            .into_iter()
+            .filter(|(id, _)| !self.nodes[node_name].contains_key(id))
+            .map(|(_, container)| container)
+            .collect();
+        diff
+    }
+
+    /// Merge a single container update into the cache
+    ///
+    /// The reported state fields overwrite the cached ones, other fields are
+    /// kept. A container that is not cached yet is added if the update names
+    /// its model.
+    ///
+    /// ### Returns
+    /// * `Ok(ContainerInfo)` - the container with the update applied
+    /// * `Err(String)` - the container is unknown and the update has no model name
+    pub fn apply_update(
+        &mut self,
+        update: &UpdateContainerStateRequest,
+    ) -> Result<ContainerInfo, String> {
+        let node = self.nodes.entry(update.node_name.clone()).or_default();
+        let container = match node.get_mut(&update.container_id) {
+            Some(container) => container,
+            None if !update.model_name.is_empty() => node
+                .entry(update.container_id.clone())
+                .or_insert_with(|| ContainerInfo {
+                    id: update.container_id.clone(),
+                    names: Vec::new(),
+                    image: String::new(),
+                    state: HashMap::new(),
+                    config: HashMap::new(),
+                    annotation: HashMap::new(),
+                    stats: HashMap::new(),
+                }),
+            None => {
+                return Err(format!(
+                    "container {} on node {} is unknown and no model name was given",
+                    update.container_id, update.node_name
+                ))
+            }
+        };
+
+        container
+            .state
+            .extend(update.state.iter().map(|(k, v)| (k.clone(), v.clone())));
+        // The model a container was reported with is kept
+        if !update.model_name.is_empty() && model_of(&container.annotation).is_none() {
+            container
+                .annotation
+                .insert(LABEL_MODEL.to_string(), update.model_name.clone());
+        }
+        Ok(container.clone())
+    }
+
+    /// Cached container by node and id
+    pub fn get(&self, node_name: &str, container_id: &str) -> Option<&ContainerInfo> {
+        self.nodes.get(node_name)?.get(container_id)
+    }
+
+    /// Whether a node has reported its containers
+    pub fn has_node(&self, node_name: &str) -> bool {
+        self.nodes.contains_key(node_name)
+    }
+
+    /// All cached containers of every node
+    pub fn containers(&self) -> Vec<ContainerInfo> {
+        self.nodes
+            .values()
+            .flat_map(|node| node.values().cloned())
+            .collect()
+    }
+
+    /// All cached containers of every node, with the node running them
+    pub fn node_containers(&self) -> Vec<(String, ContainerInfo)> {
+        self.nodes
+            .iter()
+            .flat_map(|(node_name, node)| {
+                node.values()
+                    .map(|container| (node_name.clone(), container.clone()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(id: &str, model: &str, status: &str) -> ContainerInfo {
+        ContainerInfo {
+            id: id.to_string(),
+            names: vec![format!("{}-{}", model, id)],
+            image: "sample:latest".to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::from([(LABEL_MODEL.to_string(), model.to_string())]),
+            stats: HashMap::new(),
+        }
+    }
+
+    fn update(node: &str, id: &str, model: &str, status: &str) -> UpdateContainerStateRequest {
+        UpdateContainerStateRequest {
+            node_name: node.to_string(),
+            container_id: id.to_string(),
+            model_name: model.to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            timestamp_ns: 1,
+        }
+    }
+
+    #[test]
+    fn test_apply_update_merges_state_of_known_container() {
+        let mut cache = ContainerCache::new();
+        cache.replace_node("node1", &[container("c1", "m1", "running")]);
+
+        let merged = cache
+            .apply_update(&update("node1", "c1", "", "exited"))
+            .unwrap();
+        assert_eq!(merged.state["Status"], "exited");
+        assert_eq!(merged.image, "sample:latest");
+        assert_eq!(cache.get("node1", "c1").unwrap().state["Status"], "exited");
+    }
+
+    #[test]
+    fn test_apply_update_adds_container_with_model_name() {
+        let mut cache = ContainerCache::new();
+        assert!(cache
+            .apply_update(&update("node1", "c2", "", "running"))
+            .is_err());
+
+        let added = cache
+            .apply_update(&update("node1", "c2", "m2", "running"))
+            .unwrap();
+        assert_eq!(added.annotation[LABEL_MODEL], "m2");
+        assert!(added.image.is_empty());
+        assert_eq!(cache.containers().len(), 1);
+
+        // A later update naming another model does not move the container
+        let moved = cache
+            .apply_update(&update("node1", "c2", "m3", "exited"))
+            .unwrap();
+        assert_eq!(model_of(&moved.annotation).unwrap(), "m2");
+    }
+
+    #[test]
+    fn test_apply_update_keeps_legacy_model_annotation() {
+        let mut cache = ContainerCache::new();
+        let mut legacy = container("c1", "m1", "running");
+        legacy.annotation = HashMap::from([("model".to_string(), "m1".to_string())]);
+        legacy.names.clear();
+        cache.replace_node("node1", &[legacy]);
+
+        let merged = cache
+            .apply_update(&update("node1", "c1", "m1", "exited"))
+            .unwrap();
+        assert_eq!(model_of(&merged.annotation).unwrap(), "m1");
+        assert!(!merged.annotation.contains_key(LABEL_MODEL));
+    }
+
+    #[test]
+    fn test_replace_node_reports_diff() {
+        let mut cache = ContainerCache::new();
+        let diff = cache.replace_node(
+            "node1",
+            &[
+                container("c1", "m1", "running"),
+                container("c2", "m1", "running"),
+            ],
+        );
+        assert_eq!(diff.added.len(), 2);
+
+        // Statistics alone are not a change
+        let mut c1 = container("c1", "m1", "running");
+        c1.stats.insert("CpuUsage".to_string(), "12.5".to_string());
+        let diff = cache.replace_node("node1", &[c1.clone(), container("c2", "m1", "running")]);
+        assert!(diff.is_empty());
+
+        let diff = cache.replace_node("node1", &[c1, container("c3", "m2", "running")]);
+        assert_eq!(diff.added[0].id, "c3");
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.removed[0].id, "c2");
+
+        let diff = cache.replace_node(
+            "node1",
+            &[
+                container("c1", "m1", "exited"),
+                container("c3", "m2", "running"),
+            ],
+        );
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].state["Status"], "exited");
+        assert_eq!(diff.into_containers().len(), 1);
+    }
+
+    #[test]
+    fn test_replace_node_keeps_other_nodes() {
+        let mut cache = ContainerCache::new();
+        cache.replace_node("node1", &[container("c1", "m1", "running")]);
+        cache.replace_node("node2", &[container("c2", "m1", "running")]);
+        cache.replace_node("node1", &[]);
+
+        assert!(cache.get("node1", "c1").is_none());
+        assert!(cache.get("node2", "c2").is_some());
+        assert_eq!(cache.containers().len(), 1);
+    }
+}
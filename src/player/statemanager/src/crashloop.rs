@@ -0,0 +1,260 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Crash loop detection from the restart counts of the containers
+//!
+//! The NodeAgent reports in the stats of every container how often it was
+//! restarted ("RestartCount") and killed for running out of memory
+//! ("OOMKillCount"). A container restarted `statemanager.crash_loop_restarts`
+//! times, or killed `statemanager.crash_loop_oom_kills` times, within the
+//! last `statemanager.crash_loop_window_secs` is crash looping even while
+//! the NodeAgent still restarts it.
+//!
+//! The counts seen first for a container are where it starts from, its
+//! earlier restarts are not dated. A count going down belongs to a Pod that
+//! was redeployed and starts over.
+
+use common::logd;
+use common::monitoringserver::ContainerInfo;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Containers whose counts are kept before the ones not seen are dropped
+pub const MAX_CONTAINERS: usize = 4096;
+
+/// Crash loop thresholds, 0 disables a threshold
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub restarts: u32,
+    pub oom_kills: u32,
+    pub window: Duration,
+}
+
+impl Thresholds {
+    fn from_settings() -> Self {
+        let settings = &common::setting::get_config().statemanager;
+        Self {
+            restarts: settings.crash_loop_restarts,
+            oom_kills: settings.crash_loop_oom_kills,
+            window: Duration::from_secs(settings.crash_loop_window_secs),
+        }
+    }
+}
+
+/// Increases of a count within the window
+#[derive(Default)]
+struct Counter {
+    last: Option<u64>,
+    increases: VecDeque<Instant>,
+}
+
+impl Counter {
+    /// Record `count` seen at `now`, returns the increases within `window`
+    fn observe(&mut self, count: u64, now: Instant, window: Duration) -> usize {
+        match self.last {
+            Some(last) if count > last => {
+                let added = (count - last).min(u64::from(u32::MAX)) as usize;
+                self.increases.extend(std::iter::repeat_n(now, added));
+            }
+            Some(last) if count < last => self.increases.clear(),
+            _ => {}
+        }
+        self.last = Some(count);
+        while self
+            .increases
+            .front()
+            .is_some_and(|at| now.saturating_duration_since(*at) > window)
+        {
+            self.increases.pop_front();
+        }
+        self.increases.len()
+    }
+}
+
+#[derive(Default)]
+struct History {
+    restarts: Counter,
+    oom_kills: Counter,
+    seen: Option<Instant>,
+    looping: bool,
+}
+
+#[derive(Default)]
+struct Detector {
+    containers: HashMap<String, History>,
+}
+
+impl Detector {
+    /// Record the counts of a container, returns whether it crash loops
+    fn observe(&mut self, container: &ContainerInfo, thresholds: Thresholds, now: Instant) -> bool {
+        let count = |stat: &str| {
+            container
+                .stats
+                .get(stat)
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+        let (restarts, oom_kills) = (count("RestartCount"), count("OOMKillCount"));
+        if container.id.is_empty() || (restarts.is_none() && oom_kills.is_none()) {
+            return false;
+        }
+        if !self.containers.contains_key(&container.id) && self.containers.len() >= MAX_CONTAINERS {
+            self.evict(thresholds.window, now);
+        }
+
+        let history = self.containers.entry(container.id.clone()).or_default();
+        history.seen = Some(now);
+        let recent_restarts =
+            history
+                .restarts
+                .observe(restarts.unwrap_or_default(), now, thresholds.window);
+        let recent_oom_kills =
+            history
+                .oom_kills
+                .observe(oom_kills.unwrap_or_default(), now, thresholds.window);
+        let looping = exceeds(recent_restarts, thresholds.restarts)
+            || exceeds(recent_oom_kills, thresholds.oom_kills);
+        if looping && !history.looping {
+            logd!(
+                4,
+                "Container {} crash loops: {} restarts and {} OOM kills within {}s",
+                container.names.first().unwrap_or(&container.id),
+                recent_restarts,
+                recent_oom_kills,
+                thresholds.window.as_secs()
+            );
+        }
+        history.looping = looping;
+        looping
+    }
+
+    /// Drop the containers not seen within `window`, then the least recently
+    /// seen ones until a new container fits
+    fn evict(&mut self, window: Duration, now: Instant) {
+        self.containers.retain(|_, history| {
+            history
+                .seen
+                .is_some_and(|seen| now.saturating_duration_since(seen) <= window)
+        });
+        if self.containers.len() < MAX_CONTAINERS {
+            return;
+        }
+        let mut by_seen: Vec<(Option<Instant>, String)> = self
+            .containers
+            .iter()
+            .map(|(id, history)| (history.seen, id.clone()))
+            .collect();
+        by_seen.sort();
+        for (_, id) in by_seen
+            .into_iter()
+            .take(self.containers.len() + 1 - MAX_CONTAINERS)
+        {
+            self.containers.remove(&id);
+        }
+    }
+}
+
+fn exceeds(recent: usize, threshold: u32) -> bool {
+    threshold > 0 && recent >= threshold as usize
+}
+
+fn detector() -> &'static Mutex<Detector> {
+    static DETECTOR: OnceLock<Mutex<Detector>> = OnceLock::new();
+    DETECTOR.get_or_init(|| Mutex::new(Detector::default()))
+}
+
+/// Whether a container restarts or is OOM killed more often than allowed
+pub fn crash_looping(container: &ContainerInfo) -> bool {
+    detector()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .observe(
+            container,
+            Thresholds::from_settings(),
+            common::clock::instant(),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        restarts: 3,
+        oom_kills: 2,
+        window: Duration::from_secs(60),
+    };
+
+    fn container(id: &str, restarts: u64, oom_kills: u64) -> ContainerInfo {
+        ContainerInfo {
+            id: id.to_string(),
+            names: vec![format!("pod_{}", id)],
+            stats: HashMap::from([
+                ("RestartCount".to_string(), restarts.to_string()),
+                ("OOMKillCount".to_string(), oom_kills.to_string()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restarts_within_the_window_crash_loop() {
+        let mut detector = Detector::default();
+        let start = Instant::now();
+        // Restarts before the container was first seen are not counted
+        assert!(!detector.observe(&container("c1", 10, 0), THRESHOLDS, start));
+        assert!(!detector.observe(&container("c1", 12, 0), THRESHOLDS, start));
+        // Seeing the same counts again adds nothing
+        assert!(!detector.observe(&container("c1", 12, 0), THRESHOLDS, start));
+
+        let later = start + Duration::from_secs(30);
+        assert!(detector.observe(&container("c1", 13, 0), THRESHOLDS, later));
+
+        // The first two restarts leave the window
+        let quiet = start + Duration::from_secs(61);
+        assert!(!detector.observe(&container("c1", 13, 0), THRESHOLDS, quiet));
+    }
+
+    #[tokio::test]
+    async fn test_oom_kills_crash_loop_before_restarts() {
+        let mut detector = Detector::default();
+        let start = Instant::now();
+        assert!(!detector.observe(&container("c1", 0, 0), THRESHOLDS, start));
+        assert!(!detector.observe(&container("c1", 1, 1), THRESHOLDS, start));
+        assert!(detector.observe(&container("c1", 2, 2), THRESHOLDS, start));
+        // Other containers have their own counts
+        assert!(!detector.observe(&container("c2", 2, 2), THRESHOLDS, start));
+    }
+
+    #[tokio::test]
+    async fn test_counts_going_down_start_over() {
+        let mut detector = Detector::default();
+        let start = Instant::now();
+        assert!(!detector.observe(&container("c1", 0, 0), THRESHOLDS, start));
+        assert!(detector.observe(&container("c1", 3, 0), THRESHOLDS, start));
+        assert!(!detector.observe(&container("c1", 0, 0), THRESHOLDS, start));
+        assert!(!detector.observe(&container("c1", 2, 0), THRESHOLDS, start));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_thresholds_and_missing_counts() {
+        let mut detector = Detector::default();
+        let start = Instant::now();
+        let disabled = Thresholds {
+            restarts: 0,
+            oom_kills: 0,
+            ..THRESHOLDS
+        };
+        assert!(!detector.observe(&container("c1", 0, 0), disabled, start));
+        assert!(!detector.observe(&container("c1", 50, 50), disabled, start));
+
+        let without_counts = ContainerInfo {
+            id: "c2".to_string(),
+            ..Default::default()
+        };
+        assert!(!detector.observe(&without_counts, THRESHOLDS, start));
+        assert!(!detector.containers.contains_key("c2"));
+    }
+}
@@ -0,0 +1,341 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! StateChange deduplication by transition_id
+//!
+//! Components retry `send_state_change` when a response is lost, which must
+//! not process the same transition twice. Accepted transitions are remembered
+//! in a bounded LRU cache for fast lookups and recorded in etcd so that a
+//! retry arriving after eviction or a StateManager restart still receives the
+//! original response. A record is honored for
+//! [`TRANSITION_RECORD_WINDOW_NS`] and removed by [`run_cleanup`] once it is
+//! older.
+
+use common::logd;
+use common::statemanager::StateChangeResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Number of transition_ids kept in memory
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+/// etcd key prefix of the transition records
+pub const TRANSITION_RECORD_PREFIX: &str = "/transition";
+/// How long an etcd transition record is honored, in nanoseconds (24 hours)
+pub const TRANSITION_RECORD_WINDOW_NS: i64 = 24 * 60 * 60 * 1_000_000_000;
+/// How often the expired transition records are removed
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Response fields persisted for a transition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransitionRecord {
+    message: String,
+    timestamp_ns: i64,
+    error_code: i32,
+    error_details: String,
+}
+
+impl TransitionRecord {
+    fn from_response(response: &StateChangeResponse) -> Self {
+        Self {
+            message: response.message.clone(),
+            timestamp_ns: response.timestamp_ns,
+            error_code: response.error_code,
+            error_details: response.error_details.clone(),
+        }
+    }
+
+    fn into_response(self, transition_id: &str) -> StateChangeResponse {
+        StateChangeResponse {
+            message: self.message,
+            transition_id: transition_id.to_string(),
+            timestamp_ns: self.timestamp_ns,
+            error_code: self.error_code,
+            error_details: self.error_details,
+            metadata: Default::default(),
+        }
+    }
+}
+
+/// Least recently used map of transition_id to its original response
+struct LruCache {
+    capacity: usize,
+    entries: HashMap<String, StateChangeResponse>,
+    /// transition_ids from least to most recently used
+    order: VecDeque<String>,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn touch(&mut self, transition_id: &str) {
+        if let Some(pos) = self.order.iter().position(|id| id == transition_id) {
+            if let Some(id) = self.order.remove(pos) {
+                self.order.push_back(id);
+            }
+        }
+    }
+
+    fn get(&mut self, transition_id: &str) -> Option<StateChangeResponse> {
+        let response = self.entries.get(transition_id).cloned()?;
+        self.touch(transition_id);
+        Some(response)
+    }
+
+    fn insert(&mut self, response: StateChangeResponse) {
+        let transition_id = response.transition_id.clone();
+        if self
+            .entries
+            .insert(transition_id.clone(), response)
+            .is_some()
+        {
+            self.touch(&transition_id);
+            return;
+        }
+        self.order.push_back(transition_id);
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    fn remove(&mut self, transition_id: &str) {
+        if self.entries.remove(transition_id).is_some() {
+            self.order.retain(|id| id != transition_id);
+        }
+    }
+}
+
+/// Registry of accepted transitions shared by all gRPC handlers
+#[derive(Clone)]
+pub struct TransitionDedup {
+    cache: Arc<Mutex<LruCache>>,
+}
+
+impl Default for TransitionDedup {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl TransitionDedup {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    fn record_key(transition_id: &str) -> String {
+        format!("{}/{}", TRANSITION_RECORD_PREFIX, transition_id)
+    }
+
+    /// Load a transition record from etcd, ignoring records outside the window
+    async fn load(transition_id: &str) -> Option<StateChangeResponse> {
+        let value = crate::storage::storage()
+            .get(&Self::record_key(transition_id))
+            .await
+            .ok()?;
+        let record: TransitionRecord = serde_yaml::from_str(&value).ok()?;
+
+        let now = common::clock::now_ns();
+        if now - record.timestamp_ns > TRANSITION_RECORD_WINDOW_NS {
+            return None;
+        }
+        Some(record.into_response(transition_id))
+    }
+
+    /// Claim a transition_id for a new transition
+    ///
+    /// `response` is remembered as the result of the transition before it is
+    /// processed, so that concurrent retries already see it.
+    ///
+    /// ### Returns
+    /// * `Some(original)` - the transition_id was seen before, with its original response
+    /// * `None` - the transition is new and has been recorded
+    pub async fn begin(&self, response: &StateChangeResponse) -> Option<StateChangeResponse> {
+        let transition_id = &response.transition_id;
+        if let Some(original) = self.cache.lock().unwrap().get(transition_id) {
+            return Some(original);
+        }
+
+        if let Some(original) = Self::load(transition_id).await {
+            self.cache.lock().unwrap().insert(original.clone());
+            return Some(original);
+        }
+
+        {
+            // Re-check under the lock, a concurrent retry may have won the race
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(original) = cache.get(transition_id) {
+                return Some(original);
+            }
+            cache.insert(response.clone());
+        }
+
+        let record = TransitionRecord::from_response(response);
+        match serde_yaml::to_string(&record) {
+            Ok(value) => {
+                if let Err(e) = crate::storage::storage()
+                    .put(&Self::record_key(transition_id), &value)
+                    .await
+                {
+                    logd!(4, "Failed to record transition {}: {}", transition_id, e);
+                }
+            }
+            Err(e) => logd!(4, "Failed to serialize transition record: {}", e),
+        }
+        None
+    }
+
+    /// Forget a transition that could not be processed, so that a retry is accepted
+    pub async fn abort(&self, transition_id: &str) {
+        self.cache.lock().unwrap().remove(transition_id);
+        if let Err(e) = crate::storage::storage()
+            .delete(&Self::record_key(transition_id))
+            .await
+        {
+            logd!(
+                4,
+                "Failed to remove transition record {}: {}",
+                transition_id,
+                e
+            );
+        }
+    }
+}
+
+/// Keys of the records older than the window at `now_ns`, and of the
+/// records that cannot be parsed
+fn expired(records: &[(String, String)], now_ns: i64) -> Vec<String> {
+    records
+        .iter()
+        .filter(|(_, value)| {
+            serde_yaml::from_str::<TransitionRecord>(value).map_or(true, |record| {
+                now_ns - record.timestamp_ns > TRANSITION_RECORD_WINDOW_NS
+            })
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// Remove the transition records that are no longer honored
+///
+/// ### Returns
+/// * `Ok(usize)` - the number of removed records
+pub async fn remove_expired(now_ns: i64) -> Result<usize, String> {
+    let storage = crate::storage::storage();
+    let records = storage
+        .get_all_with_prefix(&format!("{}/", TRANSITION_RECORD_PREFIX))
+        .await?;
+    let expired = expired(&records, now_ns);
+    for key in &expired {
+        storage.delete(key).await?;
+    }
+    Ok(expired.len())
+}
+
+/// Remove the expired transition records every hour while this instance is
+/// the leader
+pub async fn run_cleanup() {
+    loop {
+        crate::leader::elected().await;
+        tokio::time::sleep(CLEANUP_INTERVAL).await;
+        match remove_expired(common::clock::now_ns()).await {
+            Ok(0) => {}
+            Ok(removed) => logd!(2, "Removed {} expired transition records", removed),
+            Err(e) => logd!(4, "Failed to remove expired transition records: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(transition_id: &str, message: &str) -> StateChangeResponse {
+        StateChangeResponse {
+            message: message.to_string(),
+            transition_id: transition_id.to_string(),
+            timestamp_ns: 1,
+            error_code: 0,
+            error_details: String::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert(response("a", "first"));
+        cache.insert(response("b", "second"));
+        assert!(cache.get("a").is_some());
+
+        cache.insert(response("c", "third"));
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+
+        cache.remove("a");
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.order.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_begin_returns_original_response() {
+        common::etcd::use_in_memory_store();
+        let dedup = TransitionDedup::new(8);
+        assert!(dedup
+            .begin(&response("dedup-t1", "original"))
+            .await
+            .is_none());
+
+        let duplicate = dedup.begin(&response("dedup-t1", "retry")).await.unwrap();
+        assert_eq!(duplicate.message, "original");
+        assert_eq!(duplicate.transition_id, "dedup-t1");
+
+        dedup.abort("dedup-t1").await;
+        assert!(dedup.begin(&response("dedup-t1", "retry")).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_records_are_removed() {
+        common::etcd::use_in_memory_store();
+        let dedup = TransitionDedup::new(8);
+        let mut old = response("dedup-old", "queued");
+        old.timestamp_ns = 1_000;
+        let mut recent = response("dedup-recent", "queued");
+        recent.timestamp_ns = 1_000 + TRANSITION_RECORD_WINDOW_NS;
+        assert!(dedup.begin(&old).await.is_none());
+        assert!(dedup.begin(&recent).await.is_none());
+        crate::storage::storage()
+            .put("/transition/dedup-broken", "not a record")
+            .await
+            .unwrap();
+
+        let now = 2_000 + TRANSITION_RECORD_WINDOW_NS;
+        assert!(remove_expired(now).await.unwrap() >= 2);
+        let storage = crate::storage::storage();
+        assert!(storage.get("/transition/dedup-old").await.is_err());
+        assert!(storage.get("/transition/dedup-broken").await.is_err());
+        assert!(storage.get("/transition/dedup-recent").await.is_ok());
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let original = response("dedup-t2", "queued");
+        let record = TransitionRecord::from_response(&original);
+        let value = serde_yaml::to_string(&record).unwrap();
+        let parsed: TransitionRecord = serde_yaml::from_str(&value).unwrap();
+        assert_eq!(parsed.into_response("dedup-t2"), original);
+    }
+}
@@ -0,0 +1,139 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Reasons of denied scenarios
+//!
+//! The PolicyManager and the ActionController send a [`DenialReason`] with
+//! the StateChange that moves a scenario to Denied. It is kept next to the
+//! scenario state as `/scenario/{name}/denial` and dropped once the scenario
+//! leaves Denied, so the apiserver can show why a scenario was refused.
+//! Scenarios denied because they timed out get a reason from the
+//! StateManager itself.
+
+use common::logd;
+use common::statemanager::DenialReason;
+
+/// Error of the storage for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Rule of the denials of scenarios stuck in a state
+pub const RULE_TIMEOUT: &str = "state-timeout";
+
+/// Key of the denial reason of a scenario
+pub fn key(scenario_name: &str) -> String {
+    format!("/scenario/{}/denial", scenario_name)
+}
+
+/// Reason to keep for a scenario refused at `timestamp_ns`
+///
+/// Denials sent without a reason are kept with an `unknown` rule, the
+/// time of the StateChange fills in a missing timestamp.
+pub fn complete(denial: Option<&DenialReason>, source: &str, timestamp_ns: i64) -> DenialReason {
+    let mut denial = denial.cloned().unwrap_or_else(|| DenialReason {
+        rule: "unknown".to_string(),
+        message: "no reason reported".to_string(),
+        ..Default::default()
+    });
+    if denial.source.is_empty() {
+        denial.source = source.to_string();
+    }
+    if denial.timestamp_ns == 0 {
+        denial.timestamp_ns = timestamp_ns;
+    }
+    denial
+}
+
+/// Alert text of a denial
+pub fn describe(denial: &DenialReason) -> String {
+    format!(
+        "denied by rule {} of {}: {}",
+        denial.rule, denial.source, denial.message
+    )
+}
+
+/// Denial reason of a scenario, `None` if it is not denied
+pub async fn get(scenario_name: &str) -> Result<Option<DenialReason>, String> {
+    match crate::storage::storage().get(&key(scenario_name)).await {
+        Ok(value) => serde_json::from_str(&value)
+            .map(Some)
+            .map_err(|e| format!("Invalid denial of {}: {}", scenario_name, e)),
+        Err(e) if e == NOT_FOUND => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Keep the reason of a denied scenario
+pub async fn record(scenario_name: &str, denial: &DenialReason) {
+    let value = match serde_json::to_string(denial) {
+        Ok(value) => value,
+        Err(e) => {
+            logd!(5, "Cannot serialize the denial of {}: {}", scenario_name, e);
+            return;
+        }
+    };
+    if let Err(e) = crate::storage::storage()
+        .put(&key(scenario_name), &value)
+        .await
+    {
+        logd!(
+            4,
+            "Failed to save the denial of scenario {}: {}",
+            scenario_name,
+            e
+        );
+    }
+}
+
+/// Drop the reason of a scenario that is no longer denied
+pub async fn clear(scenario_name: &str) {
+    match crate::storage::storage().delete(&key(scenario_name)).await {
+        Ok(()) => {}
+        Err(e) if e == NOT_FOUND => {}
+        Err(e) => logd!(
+            4,
+            "Failed to remove the denial of scenario {}: {}",
+            scenario_name,
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_fills_missing_fields() {
+        let sent = DenialReason {
+            rule: "asil-level".to_string(),
+            message: "requires ASIL-B".to_string(),
+            ..Default::default()
+        };
+        let denial = complete(Some(&sent), "policymanager", 42);
+        assert_eq!(denial.rule, "asil-level");
+        assert_eq!(denial.source, "policymanager");
+        assert_eq!(denial.timestamp_ns, 42);
+        assert_eq!(
+            describe(&denial),
+            "denied by rule asil-level of policymanager: requires ASIL-B"
+        );
+
+        let unknown = complete(None, "actioncontroller", 7);
+        assert_eq!(unknown.rule, "unknown");
+        assert_eq!(unknown.source, "actioncontroller");
+    }
+
+    #[tokio::test]
+    async fn test_record_and_clear() {
+        common::etcd::use_in_memory_store();
+        let denial = complete(None, "test", 1);
+        record("denial-test", &denial).await;
+        assert_eq!(get("denial-test").await.unwrap(), Some(denial));
+        clear("denial-test").await;
+        assert_eq!(get("denial-test").await.unwrap(), None);
+        // Clearing a scenario that is not denied is fine
+        clear("denial-test").await;
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Dead-letter queue of failed StateChanges
+//!
+//! `SendStateChange` answers before the state machine processes a change,
+//! so the sender of a change the state machine refuses never learns about
+//! it. Every failed change is kept in the storage under [`PREFIX`] with the
+//! error of the state machine, keyed by its transition_id.
+//!
+//! `ListDeadLetters` shows the queue. After the cause is fixed,
+//! `RedriveDeadLetters` queues the changes again with their original
+//! transition_id: a change that succeeds leaves the queue, one that fails
+//! again stays with one more failure counted. The same RPC can discard
+//! changes that will never succeed.
+//!
+//! An alert is raised when the queue grows beyond
+//! `statemanager.dead_letter_alert_threshold` changes.
+
+use crate::types::TransitionResult;
+use common::error::Error;
+use common::logd;
+use common::statemanager::{DeadLetter, ResourceType, StateChange};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+
+/// Key prefix of the dead letters
+pub const PREFIX: &str = "/statemanager/dead-letter/";
+
+/// Error of the storage for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Key of the dead letter of a transition
+pub fn key(transition_id: &str) -> String {
+    format!("{}{}", PREFIX, transition_id)
+}
+
+/// Transitions queued again by a re-drive and not processed yet
+fn redriven() -> &'static Mutex<HashSet<String>> {
+    static REDRIVEN: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    REDRIVEN.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Dead letter of a transition, `None` if it is not in the queue
+pub async fn get(transition_id: &str) -> common::Result<Option<DeadLetter>> {
+    match crate::storage::storage().get(&key(transition_id)).await {
+        Ok(value) => serde_json::from_str(&value)
+            .map(Some)
+            .map_err(|e| Error::Internal(format!("Invalid dead letter {}: {}", transition_id, e))),
+        Err(e) if e == NOT_FOUND => Ok(None),
+        Err(e) => Err(common::etcd::error(e)),
+    }
+}
+
+/// All dead letters, ordered by transition_id
+///
+/// Entries that cannot be parsed are logged and left out.
+pub async fn list() -> common::Result<Vec<DeadLetter>> {
+    let entries = crate::storage::storage()
+        .get_all_with_prefix(PREFIX)
+        .await
+        .map_err(common::etcd::error)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(key, value)| match serde_json::from_str(&value) {
+            Ok(letter) => Some(letter),
+            Err(e) => {
+                logd!(4, "Ignoring invalid dead letter {}: {}", key, e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Whether a dead letter is selected by the filter of a list request
+///
+/// `Unspecified` and an empty name select every type and every resource.
+pub fn selects(letter: &DeadLetter, resource_type: ResourceType, resource_name: &str) -> bool {
+    let Some(change) = &letter.state_change else {
+        return false;
+    };
+    (resource_type == ResourceType::Unspecified || change.resource_type == resource_type as i32)
+        && (resource_name.is_empty() || change.resource_name == resource_name)
+}
+
+/// Dead letter of a failed transition, counting the earlier failures
+pub fn dead_letter(
+    state_change: &StateChange,
+    result: &TransitionResult,
+    earlier: Option<&DeadLetter>,
+    failed_ns: i64,
+) -> DeadLetter {
+    DeadLetter {
+        state_change: Some(state_change.clone()),
+        error_code: result.error_code as i32,
+        message: result.message.clone(),
+        error_details: result.error_details.clone(),
+        failed_ns,
+        failures: earlier.map_or(1, |letter| letter.failures + 1),
+    }
+}
+
+/// Keep a failed transition in the queue
+///
+/// A transition failing again after a re-drive replaces its dead letter.
+pub async fn record(state_change: &StateChange, result: &TransitionResult) {
+    redriven()
+        .lock()
+        .unwrap()
+        .remove(&state_change.transition_id);
+    let earlier = get(&state_change.transition_id).await.unwrap_or_else(|e| {
+        logd!(4, "{}", e);
+        None
+    });
+    let letter = dead_letter(
+        state_change,
+        result,
+        earlier.as_ref(),
+        common::clock::now_ns(),
+    );
+    let value = match serde_json::to_string(&letter) {
+        Ok(value) => value,
+        Err(e) => {
+            logd!(5, "Cannot serialize the dead letter: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = crate::storage::storage()
+        .put(&key(&state_change.transition_id), &value)
+        .await
+    {
+        logd!(
+            5,
+            "Failed to keep transition {} in the dead-letter queue: {}",
+            state_change.transition_id,
+            e
+        );
+        return;
+    }
+    logd!(
+        4,
+        "Transition {} kept in the dead-letter queue ({} failure(s))",
+        state_change.transition_id,
+        letter.failures
+    );
+    if earlier.is_none() {
+        check_threshold(state_change).await;
+    }
+}
+
+/// Alert once the queue grows beyond the threshold
+async fn check_threshold(state_change: &StateChange) {
+    let threshold = common::setting::get_config()
+        .statemanager
+        .dead_letter_alert_threshold;
+    let size = match crate::storage::storage().get_all_with_prefix(PREFIX).await {
+        Ok(entries) => entries.len(),
+        Err(e) => {
+            logd!(4, "Cannot count the dead letters: {}", e);
+            return;
+        }
+    };
+    if size > threshold {
+        crate::events::alert(
+            ResourceType::try_from(state_change.resource_type).unwrap_or_default(),
+            &state_change.resource_name,
+            format!(
+                "Dead-letter queue holds {} failed transition(s), more than {}",
+                size, threshold
+            ),
+        );
+    }
+}
+
+/// Mark a dead letter as queued again
+pub fn redrive(transition_id: &str) {
+    redriven().lock().unwrap().insert(transition_id.to_string());
+}
+
+/// Remove the dead letter of a re-driven transition that succeeded
+pub async fn settle(transition_id: &str) {
+    if !redriven().lock().unwrap().remove(transition_id) {
+        return;
+    }
+    if let Err(e) = crate::storage::storage().delete(&key(transition_id)).await {
+        logd!(
+            4,
+            "Failed to remove transition {} from the dead-letter queue: {}",
+            transition_id,
+            e
+        );
+        return;
+    }
+    logd!(
+        3,
+        "Re-driven transition {} succeeded, removed from the dead-letter queue",
+        transition_id
+    );
+}
+
+/// Drop a dead letter without re-driving it
+pub async fn discard(transition_id: &str) -> common::Result<()> {
+    redriven().lock().unwrap().remove(transition_id);
+    Ok(crate::storage::storage()
+        .delete(&key(transition_id))
+        .await
+        .map_err(common::etcd::error)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::statemanager::ErrorCode;
+
+    fn change(transition_id: &str, resource_name: &str) -> StateChange {
+        StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: resource_name.to_string(),
+            current_state: "idle".to_string(),
+            target_state: "running".to_string(),
+            transition_id: transition_id.to_string(),
+            source: "test".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn refused() -> TransitionResult {
+        TransitionResult {
+            new_state: 0,
+            error_code: ErrorCode::InvalidStateTransition,
+            message: "No valid transition".to_string(),
+            actions_to_execute: Vec::new(),
+            transition_id: String::new(),
+            error_details: "idle -> running".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_dead_letter_counts_failures_and_selects() {
+        let first = dead_letter(&change("t1", "hello"), &refused(), None, 10);
+        assert_eq!(first.failures, 1);
+        assert_eq!(first.error_code, ErrorCode::InvalidStateTransition as i32);
+        assert_eq!(first.error_details, "idle -> running");
+        let again = dead_letter(&change("t1", "hello"), &refused(), Some(&first), 20);
+        assert_eq!(again.failures, 2);
+        assert_eq!(again.failed_ns, 20);
+
+        assert!(selects(&first, ResourceType::Unspecified, ""));
+        assert!(selects(&first, ResourceType::Scenario, "hello"));
+        assert!(!selects(&first, ResourceType::Package, ""));
+        assert!(!selects(&first, ResourceType::Scenario, "other"));
+        assert!(!selects(
+            &DeadLetter::default(),
+            ResourceType::Unspecified,
+            ""
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_redriven_transition_leaves_the_queue_on_success() {
+        common::etcd::use_in_memory_store();
+        let state_change = change("dlq-test-1", "dlq-hello");
+        record(&state_change, &refused()).await;
+        record(&state_change, &refused()).await;
+        let letter = get("dlq-test-1").await.unwrap().unwrap();
+        assert_eq!(letter.failures, 2);
+        assert_eq!(letter.state_change, Some(state_change));
+        assert!(list().await.unwrap().iter().any(|letter| selects(
+            letter,
+            ResourceType::Scenario,
+            "dlq-hello"
+        )));
+
+        // Only a re-driven transition is removed when it succeeds
+        settle("dlq-test-1").await;
+        assert!(get("dlq-test-1").await.unwrap().is_some());
+        redrive("dlq-test-1");
+        settle("dlq-test-1").await;
+        assert!(get("dlq-test-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_discard() {
+        common::etcd::use_in_memory_store();
+        record(&change("dlq-test-2", "dlq-hello"), &refused()).await;
+        discard("dlq-test-2").await.unwrap();
+        assert!(get("dlq-test-2").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,413 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Drift between desired and actual states
+//!
+//! Once the action of a scenario completed, the models of its package should
+//! be in the `desiredState` declared by the scenario, or else by the package.
+//! Specs without one keep the state implied by the action: running after a
+//! launch, update or rollback, stopped after a terminate. The drift detector
+//! compares that desired state with the model state evaluated from the
+//! containers reported by the NodeAgents, and with the model state stored by
+//! the StateManager:
+//! * runtime drift - the model is not in its desired state, e.g. does not run
+//!   or has no container on a node that reported its containers; corrected
+//!   by an ActionController reconcile of the scenario to its desired state
+//! * stored drift - the stored model state disagrees with the containers,
+//!   e.g. after a lost write; corrected by saving the actual state
+//!
+//! Models of nodes that have not reported yet are skipped, so that a
+//! restarted StateManager does not see every model as missing.
+//!
+//! Several completed scenarios may target one package, e.g. a launch and a
+//! terminate. The package follows the scenario whose action completed last,
+//! its owner recorded under `/package/{name}/owner`. Without a recorded
+//! owner, a package claimed with different desired states is skipped rather
+//! than reconciled back and forth.
+
+use common::spec::artifact::scenario::DesiredState;
+use common::spec::artifact::{Artifact, Package, Scenario};
+use common::state_mapping::StateName;
+use common::statemanager::{ModelState, ResourceType, ScenarioState};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Actual state of a model without any container
+pub const MISSING: &str = "Missing";
+
+/// Model with its desired state and the scenario that deployed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesiredModel {
+    pub scenario: String,
+    pub package: String,
+    pub model: String,
+    pub node: String,
+    pub state: DesiredState,
+}
+
+impl DesiredModel {
+    /// Whether a resource of a synchronization request covers the model
+    ///
+    /// An unspecified type or an empty name covers every model.
+    pub fn in_scope(&self, resource_type: ResourceType, resource_name: &str) -> bool {
+        if resource_name.is_empty() {
+            return true;
+        }
+        match resource_type {
+            ResourceType::Scenario => self.scenario == resource_name,
+            ResourceType::Package => self.package == resource_name,
+            ResourceType::Model => self.model == resource_name,
+            ResourceType::Unspecified => true,
+            _ => false,
+        }
+    }
+}
+
+/// Kind of a drift, see the module documentation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriftKind {
+    Runtime,
+    Stored,
+}
+
+/// Difference between the desired or stored state of a model and its containers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub kind: DriftKind,
+    pub scenario: String,
+    pub model: String,
+    pub desired: String,
+    pub actual: String,
+    /// Desired state the scenario is reconciled to
+    pub target: DesiredState,
+}
+
+/// Drifts of one model
+///
+/// ### Parameters
+/// * `actual` - state evaluated from the containers, `None` without containers
+/// * `stored` - state saved by the StateManager, `None` if nothing is stored
+pub fn compare(
+    desired: &DesiredModel,
+    actual: Option<ModelState>,
+    stored: Option<ModelState>,
+) -> Vec<Drift> {
+    let drift = |kind, desired_state: &str, actual_state: &str| Drift {
+        kind,
+        scenario: desired.scenario.clone(),
+        model: desired.model.clone(),
+        desired: desired_state.to_string(),
+        actual: actual_state.to_string(),
+        target: desired.state,
+    };
+
+    let wanted = desired.state.model_state().name();
+    let mut drifts = Vec::new();
+    match (desired.state, actual) {
+        // Created models are still starting
+        (DesiredState::Running, Some(ModelState::Running | ModelState::Created)) => {}
+        (DesiredState::Stopped, None | Some(ModelState::Exited | ModelState::Dead)) => {}
+        (_, Some(state)) => drifts.push(drift(DriftKind::Runtime, wanted, state.name())),
+        (_, None) => drifts.push(drift(DriftKind::Runtime, wanted, MISSING)),
+    }
+    if let (Some(actual), Some(stored)) = (actual, stored) {
+        if actual != stored {
+            drifts.push(drift(DriftKind::Stored, stored.name(), actual.name()));
+        }
+    }
+    drifts
+}
+
+/// Desired state of the package of a scenario, see the module documentation
+pub fn desired_state(scenario: &Scenario, package: Option<&Package>) -> Option<DesiredState> {
+    scenario
+        .get_desired_state()
+        .or_else(|| package.and_then(Package::get_desired_state))
+        .or_else(|| DesiredState::from_action(&scenario.get_actions()))
+}
+
+/// Key of the scenario whose action completed last on a package
+pub fn owner_key(package: &str) -> String {
+    format!("/package/{}/owner", package)
+}
+
+/// Make a scenario whose action completed the owner of its package
+pub async fn record_owner(scenario_name: &str) -> Result<(), String> {
+    let storage = crate::storage::storage();
+    let yaml = storage.get(&format!("Scenario/{}", scenario_name)).await?;
+    let scenario = serde_yaml::from_str::<Scenario>(&yaml).map_err(|e| e.to_string())?;
+    storage
+        .put(&owner_key(&scenario.get_targets()), scenario_name)
+        .await
+}
+
+/// Keep the models of one scenario per package, see the module documentation
+///
+/// ### Parameters
+/// * `owners` - recorded owner scenario by package
+pub fn resolve_owners(
+    desired: Vec<DesiredModel>,
+    owners: &HashMap<String, String>,
+) -> Vec<DesiredModel> {
+    let mut by_package: BTreeMap<String, Vec<DesiredModel>> = BTreeMap::new();
+    for model in desired {
+        by_package
+            .entry(model.package.clone())
+            .or_default()
+            .push(model);
+    }
+
+    let mut resolved = Vec::new();
+    for (package, models) in by_package {
+        let owner = match owners.get(&package) {
+            Some(owner) if models.iter().any(|m| &m.scenario == owner) => owner.clone(),
+            _ if models.iter().all(|m| m.state == models[0].state) => {
+                let mut scenarios: Vec<&String> = models.iter().map(|m| &m.scenario).collect();
+                scenarios.sort();
+                scenarios[0].clone()
+            }
+            _ => {
+                common::logd!(
+                    4,
+                    "[Drift] Skipping {}, its scenarios want different states",
+                    package
+                );
+                continue;
+            }
+        };
+        resolved.extend(models.into_iter().filter(|m| m.scenario == owner));
+    }
+    resolved
+}
+
+/// Models of the packages of every deployed scenario
+///
+/// A package targeted by several scenarios keeps the models of its owner,
+/// see [`resolve_owners`].
+pub async fn desired_models() -> Result<Vec<DesiredModel>, String> {
+    let storage = crate::storage::storage();
+    let scenarios = storage.get_all_with_prefix("Scenario/").await?;
+
+    let mut desired = Vec::new();
+    for (key, yaml) in scenarios {
+        let Ok(scenario) = serde_yaml::from_str::<Scenario>(&yaml) else {
+            continue;
+        };
+        let state_key = format!("/scenario/{}/state", scenario.get_qualified_name());
+        let completed = storage
+            .get(&state_key)
+            .await
+            .ok()
+            .and_then(|state| ScenarioState::parse(&state))
+            == Some(ScenarioState::Completed);
+        if !completed {
+            continue;
+        }
+
+        let package_key = format!("Package/{}", scenario.get_targets());
+        let package = match storage.get(&package_key).await {
+            Ok(yaml) => serde_yaml::from_str::<Package>(&yaml).map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        let package = match package {
+            Ok(package) => package,
+            Err(e) => {
+                common::logd!(4, "[Drift] Skipping {}, no package: {}", key, e);
+                continue;
+            }
+        };
+        let Some(state) = desired_state(&scenario, Some(&package)) else {
+            continue;
+        };
+        desired.extend(package.get_models().iter().map(|model| DesiredModel {
+            scenario: scenario.get_qualified_name(),
+            package: package.get_qualified_name(),
+            model: model.get_name(),
+            node: model.get_node(),
+            state,
+        }));
+    }
+
+    let mut owners = HashMap::new();
+    for model in &desired {
+        if owners.contains_key(&model.package) {
+            continue;
+        }
+        if let Ok(owner) = storage.get(&owner_key(&model.package)).await {
+            owners.insert(model.package.clone(), owner);
+        }
+    }
+    Ok(resolve_owners(desired, &owners))
+}
+
+/// Counters of the drift detector
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DriftStats {
+    /// Drift checks since start
+    pub checks: u64,
+    /// Drifts found by the last check
+    pub drifted: usize,
+    /// Drifts found since start
+    pub detected: u64,
+    /// Reconcile requests and state corrections issued since start
+    pub corrected: u64,
+}
+
+static STATS: Mutex<DriftStats> = Mutex::new(DriftStats {
+    checks: 0,
+    drifted: 0,
+    detected: 0,
+    corrected: 0,
+});
+
+/// Count a finished drift check
+pub fn record_check(drifts: &[Drift], corrected: usize) {
+    let mut stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    stats.checks += 1;
+    stats.drifted = drifts.len();
+    stats.detected += drifts.len() as u64;
+    stats.corrected += corrected as u64;
+}
+
+pub fn stats() -> DriftStats {
+    STATS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desired() -> DesiredModel {
+        DesiredModel {
+            scenario: "antipinch".to_string(),
+            package: "antipinch-pkg".to_string(),
+            model: "antipinch-core".to_string(),
+            node: "HPC".to_string(),
+            state: DesiredState::Running,
+        }
+    }
+
+    #[test]
+    fn test_compare_running_and_starting_models_do_not_drift() {
+        let model = desired();
+        assert!(compare(&model, Some(ModelState::Running), Some(ModelState::Running)).is_empty());
+        assert!(compare(&model, Some(ModelState::Created), None).is_empty());
+    }
+
+    #[test]
+    fn test_compare_reports_runtime_and_stored_drift() {
+        let model = desired();
+
+        let missing = compare(&model, None, Some(ModelState::Running));
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].kind, DriftKind::Runtime);
+        assert_eq!(missing[0].actual, MISSING);
+
+        let dead = compare(&model, Some(ModelState::Dead), Some(ModelState::Running));
+        assert_eq!(
+            dead,
+            vec![
+                Drift {
+                    kind: DriftKind::Runtime,
+                    scenario: "antipinch".to_string(),
+                    model: "antipinch-core".to_string(),
+                    desired: "Running".to_string(),
+                    actual: "Dead".to_string(),
+                    target: DesiredState::Running,
+                },
+                Drift {
+                    kind: DriftKind::Stored,
+                    scenario: "antipinch".to_string(),
+                    model: "antipinch-core".to_string(),
+                    desired: "Running".to_string(),
+                    actual: "Dead".to_string(),
+                    target: DesiredState::Running,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_stopped_models() {
+        let model = DesiredModel {
+            state: DesiredState::Stopped,
+            ..desired()
+        };
+        assert!(compare(&model, None, None).is_empty());
+        assert!(compare(&model, Some(ModelState::Exited), Some(ModelState::Exited)).is_empty());
+
+        let running = compare(&model, Some(ModelState::Running), Some(ModelState::Running));
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].desired, "Exited");
+        assert_eq!(running[0].target, DesiredState::Stopped);
+    }
+
+    #[test]
+    fn test_declared_desired_state_overrides_the_action() {
+        let scenario = |extra: &str| -> Scenario {
+            serde_yaml::from_str(&format!(
+                "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: s\nspec:\n  action: launch\n  target: p\n{}",
+                extra
+            ))
+            .unwrap()
+        };
+        let package: Package = serde_yaml::from_str(
+            "apiVersion: v1\nkind: Package\nmetadata:\n  name: p\nspec:\n  pattern: []\n  models: []\n  desiredState: stopped\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            desired_state(&scenario(""), None),
+            Some(DesiredState::Running)
+        );
+        assert_eq!(
+            desired_state(&scenario(""), Some(&package)),
+            Some(DesiredState::Stopped)
+        );
+        assert_eq!(
+            desired_state(&scenario("  desiredState: running\n"), Some(&package)),
+            Some(DesiredState::Running)
+        );
+    }
+
+    #[test]
+    fn test_resolve_owners_of_shared_packages() {
+        let launch = desired();
+        let terminate = DesiredModel {
+            scenario: "antipinch-stop".to_string(),
+            state: DesiredState::Stopped,
+            ..desired()
+        };
+        let both = vec![launch.clone(), terminate.clone()];
+
+        let owners = HashMap::from([("antipinch-pkg".to_string(), "antipinch-stop".to_string())]);
+        assert_eq!(resolve_owners(both.clone(), &owners), vec![terminate]);
+
+        // Without an owner, conflicting claims are not reconciled
+        assert!(resolve_owners(both, &HashMap::new()).is_empty());
+
+        // Agreeing claims keep the models of one scenario
+        let update = DesiredModel {
+            scenario: "antipinch-update".to_string(),
+            ..desired()
+        };
+        assert_eq!(
+            resolve_owners(vec![update, launch.clone()], &HashMap::new()),
+            vec![launch]
+        );
+    }
+
+    #[test]
+    fn test_in_scope() {
+        let model = desired();
+        assert!(model.in_scope(ResourceType::Unspecified, ""));
+        assert!(model.in_scope(ResourceType::Scenario, "antipinch"));
+        assert!(model.in_scope(ResourceType::Package, "antipinch-pkg"));
+        assert!(model.in_scope(ResourceType::Model, "antipinch-core"));
+        assert!(model.in_scope(ResourceType::Model, ""));
+        assert!(!model.in_scope(ResourceType::Model, "other"));
+        assert!(!model.in_scope(ResourceType::Network, "antipinch"));
+    }
+}
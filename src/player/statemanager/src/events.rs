@@ -0,0 +1,140 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Live stream of state transitions and alerts
+//!
+//! Every transition and alert of the StateManager goes through this module:
+//! it is broadcast to the `SubscribeToStateChanges` subscribers and recorded
+//! for the fleet backend by the exporter. Subscribers that fall more than
+//! [`CAPACITY`] events behind miss the oldest ones.
+
+use common::statemanager::{
+    state_change_event::Event, AlertRecord, ResourceType, StateChangeEvent, TransitionRecord,
+};
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+/// Events kept for subscribers that are slow to read
+const CAPACITY: usize = 1024;
+
+fn sender() -> &'static broadcast::Sender<StateChangeEvent> {
+    static SENDER: OnceLock<broadcast::Sender<StateChangeEvent>> = OnceLock::new();
+    SENDER.get_or_init(|| broadcast::channel(CAPACITY).0)
+}
+
+/// Receive the events published from now on
+pub fn subscribe() -> broadcast::Receiver<StateChangeEvent> {
+    sender().subscribe()
+}
+
+/// Publish a state transition
+pub fn transition(record: TransitionRecord) {
+    // Sending only fails when nobody is subscribed
+    let _ = sender().send(StateChangeEvent {
+        event: Some(Event::Transition(record.clone())),
+    });
+    crate::safety_log::record_transition(&record);
+    crate::exporter::record_transition(record);
+}
+
+/// Publish an alert raised for a resource
+pub fn alert(resource_type: ResourceType, resource_name: &str, message: String) {
+    let record = AlertRecord {
+        resource_type: crate::notifier::type_name(resource_type).to_string(),
+        resource_name: resource_name.to_string(),
+        message,
+        timestamp_ns: common::clock::now_ns(),
+    };
+    let _ = sender().send(StateChangeEvent {
+        event: Some(Event::Alert(record.clone())),
+    });
+    crate::safety_log::record_alert(&record);
+    crate::exporter::record_alert(record);
+}
+
+/// Whether an event passes the filter of a subscription
+///
+/// `resource_type` is the lowercase type name, `None` for all types.
+pub fn selects(event: &StateChangeEvent, resource_type: Option<&str>, alerts: bool) -> bool {
+    let event_type = match &event.event {
+        Some(Event::Transition(record)) => &record.resource_type,
+        Some(Event::Alert(record)) if alerts => &record.resource_type,
+        _ => return false,
+    };
+    resource_type.is_none_or(|resource_type| resource_type == event_type)
+}
+
+/// Namespace of the resource of an event
+pub fn namespace(event: &StateChangeEvent) -> &str {
+    match &event.event {
+        Some(Event::Transition(record)) => common::namespace::of(&record.resource_name),
+        Some(Event::Alert(record)) => common::namespace::of(&record.resource_name),
+        None => common::namespace::DEFAULT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition_event(resource_type: &str) -> StateChangeEvent {
+        StateChangeEvent {
+            event: Some(Event::Transition(TransitionRecord {
+                resource_type: resource_type.to_string(),
+                resource_name: "hello".to_string(),
+                to_state: "Running".to_string(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    #[test]
+    fn test_selects_by_type_and_alerts() {
+        let alert = StateChangeEvent {
+            event: Some(Event::Alert(AlertRecord {
+                resource_type: "model".to_string(),
+                ..Default::default()
+            })),
+        };
+        assert!(selects(&transition_event("package"), None, false));
+        assert!(selects(
+            &transition_event("package"),
+            Some("package"),
+            false
+        ));
+        assert!(!selects(&transition_event("package"), Some("model"), true));
+        assert!(!selects(&alert, Some("model"), false));
+        assert!(selects(&alert, Some("model"), true));
+        assert!(!selects(&StateChangeEvent::default(), None, true));
+    }
+
+    #[test]
+    fn test_namespace_of_event() {
+        let mut event = transition_event("model");
+        assert_eq!(namespace(&event), common::namespace::DEFAULT);
+        if let Some(Event::Transition(record)) = &mut event.event {
+            record.resource_name = "team-a/hello".to_string();
+        }
+        assert_eq!(namespace(&event), "team-a");
+    }
+
+    #[tokio::test]
+    async fn test_transition_reaches_subscribers() {
+        let mut receiver = subscribe();
+        transition(TransitionRecord {
+            resource_type: "scenario".to_string(),
+            resource_name: "events-test".to_string(),
+            ..Default::default()
+        });
+        loop {
+            let event = receiver.recv().await.unwrap();
+            if let Some(Event::Transition(record)) = event.event {
+                if record.resource_name == "events-test" {
+                    break;
+                }
+            }
+        }
+    }
+}
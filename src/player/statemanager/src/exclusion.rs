@@ -0,0 +1,214 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Exclusion groups and activation windows of scenarios
+//!
+//! A scenario may declare `exclusionGroups`, of the scenarios sharing a
+//! group only one runs at a time, and `activationWindows`, the times of day
+//! it may start in. Both are checked when a scenario is about to run, on its
+//! StateChange to Allowed. A scenario outside its windows, or sharing a group
+//! with a scenario that is Allowed, Pending or Completed, goes back to
+//! Waiting instead, with a [`DenialReason`] naming the rule and what it
+//! found. Its condition is evaluated again, and the rules are checked again
+//! once it is met, so that the scenario runs when its window opens or its
+//! group is free.
+//!
+//! The group members are read before the transition, their states are
+//! checked under the lock of the state machine so that two scenarios of a
+//! group allowed at the same time cannot both pass.
+
+use common::logd;
+use common::spec::artifact::{Artifact, Scenario};
+use common::statemanager::{DenialReason, ScenarioState};
+
+/// Rule of the denials of scenarios whose group is taken
+pub const RULE_EXCLUSION_GROUP: &str = "exclusion-group";
+
+/// Rule of the denials of scenarios outside their activation windows
+pub const RULE_ACTIVATION_WINDOW: &str = "activation-window";
+
+/// States in which a scenario holds its exclusion groups
+const HOLDING_STATES: [ScenarioState; 3] = [
+    ScenarioState::Allowed,
+    ScenarioState::Pending,
+    ScenarioState::Completed,
+];
+
+/// What a scenario has to satisfy to run
+#[derive(Debug, Default)]
+pub struct Constraints {
+    scenario: Option<Scenario>,
+    /// Other scenarios sharing a group with it, with the shared group
+    members: Vec<(String, String)>,
+}
+
+impl Constraints {
+    /// Constraints of the scenario named `scenario_name`, none if it cannot
+    /// be read
+    pub async fn load(scenario_name: &str) -> Self {
+        let scenarios = match crate::storage::storage()
+            .get_all_with_prefix("Scenario/")
+            .await
+        {
+            Ok(scenarios) => scenarios,
+            Err(e) => {
+                logd!(4, "Exclusion groups of {} not read: {}", scenario_name, e);
+                return Self::default();
+            }
+        };
+        let scenarios: Vec<Scenario> = scenarios
+            .iter()
+            .filter_map(|(_, yaml)| serde_yaml::from_str(yaml).ok())
+            .collect();
+        Self::of(scenario_name, scenarios)
+    }
+
+    /// Constraints of the scenario named `scenario_name` among `scenarios`
+    pub fn of(scenario_name: &str, scenarios: Vec<Scenario>) -> Self {
+        let Some(index) = scenarios
+            .iter()
+            .position(|s| s.get_qualified_name() == scenario_name)
+        else {
+            return Self::default();
+        };
+        let groups = scenarios[index].get_exclusion_groups();
+        let mut members = Vec::new();
+        for (other_index, other) in scenarios.iter().enumerate() {
+            if other_index == index {
+                continue;
+            }
+            let other_groups = other.get_exclusion_groups();
+            if let Some(group) = groups.iter().find(|g| other_groups.contains(g)) {
+                members.push((other.get_qualified_name(), group.clone()));
+            }
+        }
+        Self {
+            scenario: scenarios.into_iter().nth(index),
+            members,
+        }
+    }
+
+    /// Why the scenario may not run at `now_ns`, `None` if it may
+    ///
+    /// `state_of` gives the current state of a scenario, `None` if it is
+    /// not tracked.
+    pub fn check(
+        &self,
+        now_ns: i64,
+        state_of: impl Fn(&str) -> Option<i32>,
+    ) -> Option<DenialReason> {
+        let scenario = self.scenario.as_ref()?;
+        let denial = |rule: &str, message: String| DenialReason {
+            rule: rule.to_string(),
+            message,
+            source: "statemanager".to_string(),
+            timestamp_ns: now_ns,
+        };
+
+        let now = chrono::DateTime::from_timestamp_nanos(now_ns);
+        if !scenario.is_in_activation_window(now) {
+            let windows: Vec<String> = scenario
+                .get_activation_windows()
+                .iter()
+                .map(|w| serde_json::to_string(w).unwrap_or_default())
+                .collect();
+            return Some(denial(
+                RULE_ACTIVATION_WINDOW,
+                format!(
+                    "{} is outside the activation windows {}",
+                    now.format("%a %H:%M UTC"),
+                    windows.join(", ")
+                ),
+            ));
+        }
+
+        for (member, group) in &self.members {
+            let Some(state) = state_of(member) else {
+                continue;
+            };
+            if let Some(holding) = HOLDING_STATES.iter().find(|s| **s as i32 == state) {
+                return Some(denial(
+                    RULE_EXCLUSION_GROUP,
+                    format!(
+                        "scenario {} of exclusion group {} is {}",
+                        member,
+                        group,
+                        holding.as_str_name()
+                    ),
+                ));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(name: &str, extra: &str) -> Scenario {
+        serde_yaml::from_str(&format!(
+            "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: {}\nspec:\n  action: launch\n  target: {}\n{}",
+            name, name, extra
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_exclusion_group() {
+        let scenarios = vec![
+            scenario("diagnostics", "  exclusionGroups: [bus]\n"),
+            scenario("normal", "  exclusionGroups: [bus, other]\n"),
+            scenario("unrelated", ""),
+        ];
+        let constraints = Constraints::of("diagnostics", scenarios);
+        assert_eq!(
+            constraints.members,
+            vec![("normal".to_string(), "bus".to_string())]
+        );
+
+        assert_eq!(constraints.check(0, |_| None), None);
+        let waiting = ScenarioState::Waiting as i32;
+        assert_eq!(constraints.check(0, |_| Some(waiting)), None);
+        let completed = ScenarioState::Completed as i32;
+        let denial = constraints.check(7, |_| Some(completed)).unwrap();
+        assert_eq!(denial.rule, RULE_EXCLUSION_GROUP);
+        assert_eq!(denial.source, "statemanager");
+        assert_eq!(denial.timestamp_ns, 7);
+        assert!(denial.message.contains("normal"), "{}", denial.message);
+
+        // Scenarios that are not stored have no constraints
+        let unknown = Constraints::of("unknown", vec![scenario("normal", "")]);
+        assert_eq!(unknown.check(0, |_| Some(completed)), None);
+    }
+
+    #[test]
+    fn test_activation_window() {
+        let scenarios = vec![scenario(
+            "night",
+            "  activationWindows:\n    - start: \"22:00\"\n      end: \"06:00\"\n",
+        )];
+        let constraints = Constraints::of("night", scenarios);
+        let at = |s: &str| {
+            s.parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap()
+        };
+        assert_eq!(
+            constraints.check(at("2024-01-01T23:00:00Z"), |_| None),
+            None
+        );
+        let denial = constraints
+            .check(at("2024-01-01T12:00:00Z"), |_| None)
+            .unwrap();
+        assert_eq!(denial.rule, RULE_ACTIVATION_WINDOW);
+        assert!(
+            denial.message.starts_with("Mon 12:00 UTC"),
+            "{}",
+            denial.message
+        );
+    }
+}
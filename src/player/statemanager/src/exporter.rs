@@ -0,0 +1,425 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Export of state history and alerts to a fleet backend
+//!
+//! When `telemetry.endpoint` is set, state transitions and alerts are
+//! recorded and posted every `telemetry.interval_secs` as a
+//! [`TelemetryBatch`], gzip compressed protobuf or JSON per
+//! `telemetry.format`. At most `telemetry.batch_size` records of each kind
+//! wait for the next batch.
+//!
+//! Vehicles are only connected now and then: a batch the backend does not
+//! take is spooled to `telemetry.spool_path` and uploaded, oldest first,
+//! before any newer batch once the backend answers again. Beyond
+//! `telemetry.spool_limit` spooled batches the oldest ones are deleted.
+
+use common::logd;
+use common::setting::TelemetrySettings;
+use common::statemanager::{AlertRecord, TelemetryBatch, TransitionRecord};
+use prost::Message;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Time allowed for a single upload
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Encoding of the uploaded batches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Gzip compressed protobuf
+    Protobuf,
+    Json,
+}
+
+impl Format {
+    fn parse(value: &str) -> Self {
+        if value.eq_ignore_ascii_case("json") {
+            Format::Json
+        } else {
+            Format::Protobuf
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Protobuf => "pb.gz",
+            Format::Json => "json",
+        }
+    }
+
+    /// Format of a spooled batch, by the extension of its file
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        [Format::Protobuf, Format::Json]
+            .into_iter()
+            .find(|format| name.ends_with(&format!(".{}", format.extension())))
+    }
+}
+
+/// Encode a batch for upload
+pub fn encode(batch: &TelemetryBatch, format: Format) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Protobuf => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(&batch.encode_to_vec())
+                .and_then(|_| encoder.finish())
+                .map_err(|e| e.to_string())
+        }
+        Format::Json => serde_json::to_vec(batch).map_err(|e| e.to_string()),
+    }
+}
+
+/// Counters of the exporter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportStats {
+    /// Records waiting for the next batch
+    pub pending: usize,
+    /// Batches the backend took
+    pub uploaded: u64,
+    /// Batches written to the spool
+    pub spooled: u64,
+    /// Records dropped because the next batch was full
+    pub dropped: u64,
+    /// Spooled batches deleted because the spool was full
+    pub discarded: u64,
+}
+
+/// Records of the next batch
+#[derive(Debug, Default)]
+struct Records {
+    capacity: usize,
+    transitions: VecDeque<TransitionRecord>,
+    alerts: VecDeque<AlertRecord>,
+    stats: ExportStats,
+}
+
+impl Records {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn push<T>(queue: &mut VecDeque<T>, record: T, capacity: usize, stats: &mut ExportStats) {
+        if queue.len() >= capacity {
+            queue.pop_front();
+            stats.dropped += 1;
+        }
+        if capacity > 0 {
+            queue.push_back(record);
+        }
+    }
+
+    fn push_transition(&mut self, record: TransitionRecord) {
+        Self::push(
+            &mut self.transitions,
+            record,
+            self.capacity,
+            &mut self.stats,
+        );
+    }
+
+    fn push_alert(&mut self, record: AlertRecord) {
+        Self::push(&mut self.alerts, record, self.capacity, &mut self.stats);
+    }
+
+    /// Take the records as a batch, `None` when there are none
+    fn take(&mut self, node: &str, created_ns: i64) -> Option<TelemetryBatch> {
+        if self.transitions.is_empty() && self.alerts.is_empty() {
+            return None;
+        }
+        Some(TelemetryBatch {
+            node: node.to_string(),
+            created_ns,
+            transitions: self.transitions.drain(..).collect(),
+            alerts: self.alerts.drain(..).collect(),
+        })
+    }
+
+    fn stats(&self) -> ExportStats {
+        ExportStats {
+            pending: self.transitions.len() + self.alerts.len(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+fn settings() -> &'static TelemetrySettings {
+    &common::setting::get_config().telemetry
+}
+
+fn enabled() -> bool {
+    !settings().endpoint.is_empty()
+}
+
+fn records() -> &'static Mutex<Records> {
+    static RECORDS: OnceLock<Mutex<Records>> = OnceLock::new();
+    RECORDS.get_or_init(|| Mutex::new(Records::new(settings().batch_size)))
+}
+
+fn with_records<R>(f: impl FnOnce(&mut Records) -> R) -> R {
+    let mut records = records().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut records)
+}
+
+fn now_ns() -> i64 {
+    common::clock::now_ns()
+}
+
+/// Record a state transition for export
+pub fn record_transition(record: TransitionRecord) {
+    if enabled() {
+        with_records(|records| records.push_transition(record));
+    }
+}
+
+/// Record an alert for export
+pub fn record_alert(record: AlertRecord) {
+    if enabled() {
+        with_records(|records| records.push_alert(record));
+    }
+}
+
+/// Counters of the exporter
+pub fn stats() -> ExportStats {
+    with_records(|records| records.stats())
+}
+
+/// Batches waiting on disk for the backend
+struct Spool {
+    dir: PathBuf,
+    limit: usize,
+}
+
+impl Spool {
+    /// Spooled batches, oldest first
+    fn pending(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| Format::of(path).is_some())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        // File names start with the zero padded creation time of the batch
+        paths.sort();
+        paths
+    }
+
+    fn store(&self, created_ns: i64, body: &[u8], format: Format) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self
+            .dir
+            .join(format!("{:020}.{}", created_ns, format.extension()));
+        std::fs::write(path, body)
+    }
+
+    /// Delete the oldest batches beyond the limit, returning how many
+    fn prune(&self) -> usize {
+        let pending = self.pending();
+        let excess = pending.len().saturating_sub(self.limit);
+        pending
+            .iter()
+            .take(excess)
+            .filter(|path| std::fs::remove_file(path).is_ok())
+            .count()
+    }
+}
+
+async fn upload(endpoint: &str, body: Vec<u8>, format: Format) -> Result<(), String> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let client = CLIENT.get_or_init(reqwest::Client::new);
+    let request = client.post(endpoint).timeout(UPLOAD_TIMEOUT).body(body);
+    let request = match format {
+        Format::Protobuf => request
+            .header(reqwest::header::CONTENT_TYPE, "application/x-protobuf")
+            .header(reqwest::header::CONTENT_ENCODING, "gzip"),
+        Format::Json => request.header(reqwest::header::CONTENT_TYPE, "application/json"),
+    };
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("answered {}", response.status()))
+    }
+}
+
+/// Upload the spooled batches and then the pending records, spooling the
+/// records if the backend cannot be reached
+async fn export(endpoint: &str, format: Format, spool: &Spool) {
+    let mut online = true;
+    for path in spool.pending() {
+        let (Ok(body), Some(spooled_format)) = (std::fs::read(&path), Format::of(&path)) else {
+            continue;
+        };
+        match upload(endpoint, body, spooled_format).await {
+            Ok(()) => {
+                let _ = std::fs::remove_file(&path);
+                with_records(|records| records.stats.uploaded += 1);
+            }
+            Err(e) => {
+                logd!(2, "[Telemetry] Backend unavailable, keeping spool: {}", e);
+                online = false;
+                break;
+            }
+        }
+    }
+
+    let created_ns = now_ns();
+    let node = &common::setting::get_config().host.name;
+    let Some(batch) = with_records(|records| records.take(node, created_ns)) else {
+        return;
+    };
+    let body = match encode(&batch, format) {
+        Ok(body) => body,
+        Err(e) => {
+            logd!(4, "[Telemetry] Failed to encode batch: {}", e);
+            return;
+        }
+    };
+    if online {
+        match upload(endpoint, body.clone(), format).await {
+            Ok(()) => {
+                with_records(|records| records.stats.uploaded += 1);
+                return;
+            }
+            Err(e) => logd!(2, "[Telemetry] Backend unavailable, spooling batch: {}", e),
+        }
+    }
+
+    if let Err(e) = spool.store(created_ns, &body, format) {
+        logd!(4, "[Telemetry] Failed to spool batch: {}", e);
+        return;
+    }
+    let discarded = spool.prune();
+    if discarded > 0 {
+        logd!(
+            4,
+            "[Telemetry] Spool full, deleted {} oldest batch(es)",
+            discarded
+        );
+    }
+    with_records(|records| {
+        records.stats.spooled += 1;
+        records.stats.discarded += discarded as u64;
+    });
+}
+
+/// Upload the state history every `telemetry.interval_secs`, if an endpoint
+/// is configured
+pub async fn run_exporter() {
+    let settings = settings();
+    if settings.endpoint.is_empty() {
+        return;
+    }
+    let format = Format::parse(&settings.format);
+    let spool = Spool {
+        dir: PathBuf::from(&settings.spool_path),
+        limit: settings.spool_limit,
+    };
+    logd!(
+        3,
+        "[Telemetry] Exporting state history to {} as {:?}",
+        settings.endpoint,
+        format
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        export(&settings.endpoint, format, &spool).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn transition(name: &str) -> TransitionRecord {
+        TransitionRecord {
+            resource_type: "package".to_string(),
+            resource_name: name.to_string(),
+            from_state: "Running".to_string(),
+            to_state: "Error".to_string(),
+            transition_id: "t-1".to_string(),
+            timestamp_ns: 1,
+            health: None,
+            metadata: Default::default(),
+        }
+    }
+
+    fn spool(name: &str, limit: usize) -> Spool {
+        let dir =
+            std::env::temp_dir().join(format!("piccolo-telemetry-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Spool { dir, limit }
+    }
+
+    #[test]
+    fn test_records_keep_the_newest_per_kind() {
+        let mut records = Records::new(2);
+        for name in ["a", "b", "c"] {
+            records.push_transition(transition(name));
+        }
+        records.push_alert(AlertRecord::default());
+        assert_eq!(records.stats().pending, 3);
+        assert_eq!(records.stats().dropped, 1);
+
+        let batch = records.take("HPC", 5).unwrap();
+        let names: Vec<&str> = batch
+            .transitions
+            .iter()
+            .map(|t| t.resource_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["b", "c"]);
+        assert_eq!(batch.alerts.len(), 1);
+        assert!(records.take("HPC", 6).is_none());
+    }
+
+    #[test]
+    fn test_encode_gzip_protobuf_and_json() {
+        let batch = TelemetryBatch {
+            node: "HPC".to_string(),
+            created_ns: 7,
+            transitions: vec![transition("p")],
+            alerts: Vec::new(),
+        };
+
+        let body = encode(&batch, Format::Protobuf).unwrap();
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(body.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(TelemetryBatch::decode(decoded.as_slice()).unwrap(), batch);
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&encode(&batch, Format::Json).unwrap()).unwrap();
+        assert_eq!(json["node"], "HPC");
+        assert_eq!(json["transitions"][0]["to_state"], "Error");
+    }
+
+    #[test]
+    fn test_spool_keeps_the_newest_batches_in_order() {
+        let spool = spool("order", 2);
+        spool.store(30, b"c", Format::Json).unwrap();
+        spool.store(10, b"a", Format::Protobuf).unwrap();
+        spool.store(20, b"b", Format::Json).unwrap();
+        assert_eq!(spool.prune(), 1);
+
+        let pending = spool.pending();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(std::fs::read(&pending[0]).unwrap(), b"b");
+        assert_eq!(Format::of(&pending[0]), Some(Format::Json));
+        assert_eq!(std::fs::read(&pending[1]).unwrap(), b"c");
+        std::fs::remove_dir_all(&spool.dir).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Transient and permanent failures of containers
+//!
+//! A container reported dead, or exited when the restart policy of its Pod
+//! restarts it, is not necessarily gone: the NodeAgent restarts it, often
+//! within a second. A failure is permanent when
+//! - the restart policy of the Pod leaves the container stopped,
+//! - its exit code says the command cannot run at all (126, 127), or
+//! - the container fails again within the grace window after it recovered.
+//!
+//! Any other failure is transient. A transiently failed container is given
+//! `statemanager.failure_grace_secs` to recover before it counts as dead,
+//! so that a restart does not fail its model. Containers restarted too often
+//! are left to the crash loop detection of [`crate::crashloop`].
+//!
+//! Each state machine keeps the failures of its containers in [`Failures`],
+//! measured with the clock it is given.
+
+use common::clock::Clock;
+use common::logd;
+use common::monitoringserver::ContainerInfo;
+use common::spec::k8s::pod::{RestartPolicy, ANNOTATION_RESTART_POLICY};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Containers whose failures are kept before the ones not seen are dropped
+pub const MAX_CONTAINERS: usize = 4096;
+
+/// Exit codes of a command that could not be executed or was not found
+const CANNOT_RUN: [&str; 2] = ["126", "127"];
+
+/// How a failed container is taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// Failed transiently, within its grace window
+    Recovering,
+    /// Failed for good, or for longer than its grace window
+    Dead,
+}
+
+/// Failure and recovery of a container
+#[derive(Clone, Default)]
+struct History {
+    /// When the current failure was first seen
+    failed_at: Option<Instant>,
+    /// When the container last recovered from a failure
+    recovered_at: Option<Instant>,
+    seen: Option<Instant>,
+}
+
+#[derive(Clone, Default)]
+struct Classifier {
+    containers: HashMap<String, History>,
+}
+
+impl Classifier {
+    /// Record a failure of a container at `now`
+    fn failed(&mut self, container: &ContainerInfo, grace: Duration, now: Instant) -> Verdict {
+        if grace.is_zero() || container.id.is_empty() {
+            return Verdict::Dead;
+        }
+        if !self.containers.contains_key(&container.id) && self.containers.len() >= MAX_CONTAINERS {
+            self.evict(grace, now);
+        }
+        let history = self.containers.entry(container.id.clone()).or_default();
+        history.seen = Some(now);
+        let failed_at = *history.failed_at.get_or_insert(now);
+
+        let relapsed = history
+            .recovered_at
+            .is_some_and(|at| failed_at.saturating_duration_since(at) <= grace);
+        let permanent = permanent(container);
+        if permanent || relapsed {
+            if failed_at == now {
+                logd!(
+                    4,
+                    "Container {} failed permanently: {}",
+                    name(container),
+                    if permanent {
+                        "it is not restarted"
+                    } else {
+                        "it failed again right after it recovered"
+                    }
+                );
+            }
+            return Verdict::Dead;
+        }
+        if now.saturating_duration_since(failed_at) > grace {
+            return Verdict::Dead;
+        }
+        Verdict::Recovering
+    }
+
+    /// Record that a container is not failed at `now`
+    fn healthy(&mut self, container: &ContainerInfo, now: Instant) {
+        if let Some(history) = self.containers.get_mut(&container.id) {
+            if history.failed_at.take().is_some() {
+                history.recovered_at = Some(now);
+            }
+            history.seen = Some(now);
+        }
+    }
+
+    /// Drop the containers not seen within `grace`, then the least recently
+    /// seen ones until a new container fits
+    fn evict(&mut self, grace: Duration, now: Instant) {
+        self.containers.retain(|_, history| {
+            history
+                .seen
+                .is_some_and(|seen| now.saturating_duration_since(seen) <= grace)
+        });
+        if self.containers.len() < MAX_CONTAINERS {
+            return;
+        }
+        let mut by_seen: Vec<(Option<Instant>, String)> = self
+            .containers
+            .iter()
+            .map(|(id, history)| (history.seen, id.clone()))
+            .collect();
+        by_seen.sort();
+        for (_, id) in by_seen
+            .into_iter()
+            .take(self.containers.len() + 1 - MAX_CONTAINERS)
+        {
+            self.containers.remove(&id);
+        }
+    }
+}
+
+fn restart_policy(container: &ContainerInfo) -> RestartPolicy {
+    container
+        .annotation
+        .get(ANNOTATION_RESTART_POLICY)
+        .and_then(|policy| RestartPolicy::from_str_name(policy))
+        .unwrap_or_default()
+}
+
+fn exit_code(container: &ContainerInfo) -> Option<&str> {
+    container.state.get("ExitCode").map(String::as_str)
+}
+
+/// Whether a failed container stays failed whatever the time given to it
+fn permanent(container: &ContainerInfo) -> bool {
+    restart_policy(container) == RestartPolicy::Never
+        || exit_code(container).is_some_and(|code| CANNOT_RUN.contains(&code))
+}
+
+/// Whether the NodeAgent restarts a container that exited, by the restart
+/// policy of its Pod and the exit code, unknown ones counting as failures
+pub fn restarted(container: &ContainerInfo) -> bool {
+    match restart_policy(container) {
+        RestartPolicy::Always => true,
+        RestartPolicy::OnFailure => exit_code(container) != Some("0"),
+        RestartPolicy::Never => false,
+    }
+}
+
+fn name(container: &ContainerInfo) -> &str {
+    container.names.first().unwrap_or(&container.id)
+}
+
+fn grace() -> Duration {
+    Duration::from_secs(
+        common::setting::get_config()
+            .statemanager
+            .failure_grace_secs,
+    )
+}
+
+/// Failures of the containers a state machine evaluated
+#[derive(Clone)]
+pub struct Failures {
+    classifier: Classifier,
+    clock: Arc<dyn Clock>,
+}
+
+impl Failures {
+    /// No failures yet, measured with `clock`
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            classifier: Classifier::default(),
+            clock,
+        }
+    }
+
+    /// Classify a failure of a container
+    pub fn failed(&mut self, container: &ContainerInfo) -> Verdict {
+        self.classifier
+            .failed(container, grace(), self.clock.instant())
+    }
+
+    /// Note that a container is not failed, ending its current failure
+    pub fn healthy(&mut self, container: &ContainerInfo) {
+        self.classifier.healthy(container, self.clock.instant())
+    }
+}
+
+impl Default for Failures {
+    /// No failures yet, measured with the installed clock
+    fn default() -> Self {
+        Self::new(common::clock::get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRACE: Duration = Duration::from_secs(10);
+
+    fn container(id: &str, exit_code: &str, policy: Option<&str>) -> ContainerInfo {
+        ContainerInfo {
+            id: id.to_string(),
+            names: vec![format!("pod_{}", id)],
+            state: HashMap::from([
+                ("Status".to_string(), "dead".to_string()),
+                ("ExitCode".to_string(), exit_code.to_string()),
+            ]),
+            annotation: policy
+                .map(|policy| {
+                    HashMap::from([(ANNOTATION_RESTART_POLICY.to_string(), policy.to_string())])
+                })
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transient_failures_get_a_grace_window() {
+        let mut classifier = Classifier::default();
+        let start = Instant::now();
+        let c1 = container("c1", "1", None);
+        assert_eq!(classifier.failed(&c1, GRACE, start), Verdict::Recovering);
+        assert_eq!(
+            classifier.failed(&c1, GRACE, start + Duration::from_secs(5)),
+            Verdict::Recovering
+        );
+        assert_eq!(
+            classifier.failed(&c1, GRACE, start + Duration::from_secs(11)),
+            Verdict::Dead
+        );
+
+        // Recovered in time, then failing again at once
+        let c2 = container("c2", "1", None);
+        assert_eq!(classifier.failed(&c2, GRACE, start), Verdict::Recovering);
+        classifier.healthy(&c2, start + Duration::from_secs(2));
+        assert_eq!(
+            classifier.failed(&c2, GRACE, start + Duration::from_secs(5)),
+            Verdict::Dead
+        );
+        // A failure long after the recovery is transient again
+        classifier.healthy(&c2, start + Duration::from_secs(6));
+        assert_eq!(
+            classifier.failed(&c2, GRACE, start + Duration::from_secs(60)),
+            Verdict::Recovering
+        );
+    }
+
+    #[test]
+    fn test_permanent_failures() {
+        let mut classifier = Classifier::default();
+        let start = Instant::now();
+        for permanent in [
+            container("never", "1", Some("Never")),
+            container("missing", "127", None),
+            container("not-executable", "126", Some("Always")),
+        ] {
+            assert_eq!(
+                classifier.failed(&permanent, GRACE, start),
+                Verdict::Dead,
+                "{}",
+                permanent.id
+            );
+        }
+        // Without a grace window every failure counts at once
+        let c1 = container("c1", "1", Some("OnFailure"));
+        assert_eq!(classifier.failed(&c1, Duration::ZERO, start), Verdict::Dead);
+        assert_eq!(classifier.failed(&c1, GRACE, start), Verdict::Recovering);
+    }
+
+    #[test]
+    fn test_restarted_exits() {
+        assert!(restarted(&container("always", "0", None)));
+        assert!(restarted(&container("failed", "1", Some("OnFailure"))));
+        assert!(!restarted(&container("completed", "0", Some("OnFailure"))));
+        assert!(!restarted(&container("never", "1", Some("Never"))));
+    }
+
+    #[test]
+    fn test_failures_follow_their_clock() {
+        let clock = Arc::new(common::clock::MockClock::new(0));
+        let mut failures = Failures::new(clock.clone());
+        let c1 = container("c1", "1", None);
+        assert_eq!(failures.failed(&c1), Verdict::Recovering);
+        clock.advance(grace() + Duration::from_secs(1));
+        assert_eq!(failures.failed(&c1), Verdict::Dead);
+        // Another state machine keeps failures of its own
+        assert_eq!(
+            Failures::new(clock.clone()).failed(&c1),
+            Verdict::Recovering
+        );
+    }
+}
@@ -0,0 +1,478 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Scripted failures of the storage and the ActionController
+//!
+//! Only built in tests and with the `fault-injection` feature. The storage
+//! backend and the ActionController sender are then wrapped in
+//! [`FaultyStorage`] and [`FaultySender`], which consult the [`script`]
+//! before every call. A script is a list of [`Rule`]s, installed by tests
+//! with [`install`] or read at start from the YAML file named by
+//! `STATEMANAGER_FAULT_SCRIPT`:
+//!
+//! ```yaml
+//! - operation: put          # get, put, delete, list, commit, reconcile, trigger_action
+//!   target: /package/       # part of the key or scenario name, any if omitted
+//!   fault: !timeout 3000    # or !error "...", !partial 1, !delay 200
+//!   after: 2                # matching calls let through first
+//!   times: 5                # calls failed, all of them if omitted
+//! ```
+//!
+//! The first rule matching a call decides its fault. Every injected fault
+//! is logged and kept for [`FaultScript::injected`].
+
+use crate::grpc::sender::ActionControllerSender;
+use crate::storage::{StateStorage, Transaction};
+use async_trait::async_trait;
+use common::actioncontroller::{ReconcileRequest, ReconcileResponse, TriggerActionResponse};
+use common::logd;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tonic::{Response, Status};
+
+/// Environment variable naming the script loaded at start
+pub const SCRIPT_ENV: &str = "STATEMANAGER_FAULT_SCRIPT";
+
+/// Call a rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Get,
+    Put,
+    Delete,
+    List,
+    Commit,
+    Reconcile,
+    TriggerAction,
+}
+
+/// Failure injected into a call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// Fail with a timeout after that many milliseconds
+    Timeout(u64),
+    /// Fail at once with the message
+    Error(String),
+    /// Write only that many keys of a committed transaction, then fail
+    Partial(usize),
+    /// Answer normally after that many milliseconds
+    Delay(u64),
+}
+
+/// Fault of the calls of one operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub operation: Operation,
+    /// Part of the key, prefix or scenario name, empty for any call
+    #[serde(default)]
+    pub target: String,
+    pub fault: Fault,
+    /// Matching calls let through before the first fault
+    #[serde(default)]
+    pub after: u32,
+    /// Calls failed, `None` for all of them
+    #[serde(default)]
+    pub times: Option<u32>,
+}
+
+impl Rule {
+    /// Fault every call of `operation`
+    pub fn new(operation: Operation, fault: Fault) -> Self {
+        Self {
+            operation,
+            target: String::new(),
+            fault,
+            after: 0,
+            times: None,
+        }
+    }
+
+    /// Only calls whose key or scenario name contains `target`
+    pub fn on(mut self, target: &str) -> Self {
+        self.target = target.to_string();
+        self
+    }
+
+    /// Let the first `calls` matching calls through
+    pub fn after(mut self, calls: u32) -> Self {
+        self.after = calls;
+        self
+    }
+
+    /// Fault `calls` calls, then let the others through
+    pub fn times(mut self, calls: u32) -> Self {
+        self.times = Some(calls);
+        self
+    }
+
+    fn matches(&self, operation: Operation, target: &str) -> bool {
+        self.operation == operation && target.contains(&self.target)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    rules: Vec<Rule>,
+    injected: Vec<String>,
+}
+
+/// Rules shared by the wrappers, with the faults they injected
+#[derive(Debug, Clone, Default)]
+pub struct FaultScript {
+    state: Arc<Mutex<State>>,
+}
+
+impl FaultScript {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let script = Self::default();
+        script.add(rules);
+        script
+    }
+
+    /// Read a YAML list of rules
+    pub fn load(path: &str) -> Result<Self, String> {
+        let yaml = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read fault script {}: {}", path, e))?;
+        let rules = serde_yaml::from_str(&yaml)
+            .map_err(|e| format!("Invalid fault script {}: {}", path, e))?;
+        Ok(Self::new(rules))
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state)
+    }
+
+    /// Append rules, after the ones already installed
+    pub fn add(&self, rules: Vec<Rule>) {
+        self.with_state(|state| state.rules.extend(rules));
+    }
+
+    /// Remove the rules matching a target, e.g. once a test is done
+    pub fn remove(&self, target: &str) {
+        self.with_state(|state| state.rules.retain(|rule| rule.target != target));
+    }
+
+    /// Faults injected so far, as `operation target: fault`
+    pub fn injected(&self) -> Vec<String> {
+        self.with_state(|state| state.injected.clone())
+    }
+
+    /// Fault of a call, counting it against the rule that matches
+    pub fn next(&self, operation: Operation, target: &str) -> Option<Fault> {
+        self.with_state(|state| {
+            let index = state
+                .rules
+                .iter()
+                .position(|rule| rule.matches(operation, target))?;
+            let rule = &mut state.rules[index];
+            if rule.after > 0 {
+                rule.after -= 1;
+                return None;
+            }
+            let fault = rule.fault.clone();
+            match rule.times {
+                Some(1) => {
+                    state.rules.remove(index);
+                }
+                Some(times) => rule.times = Some(times - 1),
+                None => {}
+            }
+            let injected = format!("{:?} {}: {:?}", operation, target, fault);
+            logd!(4, "Injected fault: {}", injected);
+            state.injected.push(injected);
+            Some(fault)
+        })
+    }
+}
+
+/// Script consulted by the wrapped storage and sender
+///
+/// Empty unless `STATEMANAGER_FAULT_SCRIPT` names a valid script.
+pub fn script() -> &'static FaultScript {
+    static SCRIPT: OnceLock<FaultScript> = OnceLock::new();
+    SCRIPT.get_or_init(|| match std::env::var(SCRIPT_ENV) {
+        Ok(path) => FaultScript::load(&path).unwrap_or_else(|e| {
+            logd!(5, "{}", e);
+            FaultScript::default()
+        }),
+        Err(_) => FaultScript::default(),
+    })
+}
+
+/// Add rules to the active script
+pub fn install(rules: Vec<Rule>) {
+    script().add(rules);
+}
+
+/// Storage failing as the script says
+pub struct FaultyStorage {
+    inner: Box<dyn StateStorage>,
+    script: FaultScript,
+}
+
+impl FaultyStorage {
+    pub fn new(inner: Box<dyn StateStorage>, script: FaultScript) -> Self {
+        Self { inner, script }
+    }
+
+    /// Wait or fail as the fault of a call says, `Ok` to go on
+    async fn inject(&self, operation: Operation, target: &str) -> Result<Option<Fault>, String> {
+        match self.script.next(operation, target) {
+            Some(Fault::Timeout(ms)) => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Err(format!(
+                    "{:?} {} timed out after {}ms",
+                    operation, target, ms
+                ))
+            }
+            Some(Fault::Error(message)) => Err(message),
+            Some(Fault::Delay(ms)) => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok(None)
+            }
+            fault => Ok(fault),
+        }
+    }
+}
+
+#[async_trait]
+impl StateStorage for FaultyStorage {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        self.inject(Operation::Get, key).await?;
+        self.inner.get(key).await
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        self.inject(Operation::Put, key).await?;
+        self.inner.put(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.inject(Operation::Delete, key).await?;
+        self.inner.delete(key).await
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        self.inject(Operation::List, prefix).await?;
+        self.inner.get_all_with_prefix(prefix).await
+    }
+
+    async fn commit(&self, transaction: Transaction) -> Result<(), String> {
+        // A transaction is matched by the keys it writes
+        let keys: Vec<&str> = transaction
+            .writes()
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect();
+        let Some(Fault::Partial(count)) = self.inject(Operation::Commit, &keys.join(",")).await?
+        else {
+            return self.inner.commit(transaction).await;
+        };
+        for (key, value) in transaction.writes().iter().take(count) {
+            self.inner.put(key, value).await?;
+        }
+        Err(format!(
+            "Commit interrupted after {} of {} writes",
+            count.min(keys.len()),
+            keys.len()
+        ))
+    }
+}
+
+/// ActionController sender failing as the script says
+pub struct FaultySender {
+    inner: Box<dyn ActionControllerSender>,
+    script: FaultScript,
+}
+
+impl FaultySender {
+    pub fn new(inner: Box<dyn ActionControllerSender>, script: FaultScript) -> Self {
+        Self { inner, script }
+    }
+
+    async fn inject(&self, operation: Operation, scenario_name: &str) -> Result<(), Status> {
+        match self.script.next(operation, scenario_name) {
+            Some(Fault::Timeout(ms)) => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Err(Status::deadline_exceeded(format!(
+                    "ActionController did not answer within {}ms",
+                    ms
+                )))
+            }
+            Some(Fault::Delay(ms)) => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok(())
+            }
+            Some(Fault::Error(message)) => Err(Status::unavailable(message)),
+            Some(Fault::Partial(_)) | None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionControllerSender for FaultySender {
+    async fn reconcile(
+        &self,
+        request: ReconcileRequest,
+    ) -> Result<Response<ReconcileResponse>, Status> {
+        self.inject(Operation::Reconcile, &request.scenario_name)
+            .await?;
+        self.inner.reconcile(request).await
+    }
+
+    async fn trigger_action(
+        &self,
+        scenario_name: String,
+    ) -> Result<Response<TriggerActionResponse>, Status> {
+        self.inject(Operation::TriggerAction, &scenario_name)
+            .await?;
+        self.inner.trigger_action(scenario_name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStateStorage;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn storage(rules: Vec<Rule>) -> FaultyStorage {
+        FaultyStorage::new(
+            Box::new(InMemoryStateStorage::default()),
+            FaultScript::new(rules),
+        )
+    }
+
+    /// ActionController answering every request
+    #[derive(Default)]
+    struct Answering {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl ActionControllerSender for Arc<Answering> {
+        async fn reconcile(
+            &self,
+            _request: ReconcileRequest,
+        ) -> Result<Response<ReconcileResponse>, Status> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Response::new(ReconcileResponse::default()))
+        }
+
+        async fn trigger_action(
+            &self,
+            _scenario_name: String,
+        ) -> Result<Response<TriggerActionResponse>, Status> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Response::new(TriggerActionResponse::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_storage_timeout_after_successful_calls() {
+        let storage = storage(vec![Rule::new(Operation::Put, Fault::Timeout(50))
+            .on("/package/")
+            .after(1)
+            .times(2)]);
+
+        storage.put("/package/p1/state", "running").await.unwrap();
+        storage.put("/model/m1/state", "Running").await.unwrap();
+        let started = tokio::time::Instant::now();
+        assert!(storage.put("/package/p1/state", "error").await.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(storage.put("/package/p1/state", "error").await.is_err());
+        storage.put("/package/p1/state", "degraded").await.unwrap();
+
+        assert_eq!(storage.get("/package/p1/state").await.unwrap(), "degraded");
+        assert_eq!(storage.script.injected().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_partial_commit_tears_transaction() {
+        let storage = storage(vec![
+            Rule::new(Operation::Commit, Fault::Partial(1)).times(1)
+        ]);
+        let mut transaction = Transaction::default();
+        transaction.put("/model/m1/state", "Dead");
+        transaction.put("/package/p1/state", "error");
+
+        let error = storage.commit(transaction.clone()).await.unwrap_err();
+        assert_eq!(error, "Commit interrupted after 1 of 2 writes");
+        assert_eq!(storage.get("/model/m1/state").await.unwrap(), "Dead");
+        assert!(storage.get("/package/p1/state").await.is_err());
+
+        // Committing again repairs the torn write
+        storage.commit(transaction).await.unwrap();
+        assert_eq!(storage.get("/package/p1/state").await.unwrap(), "error");
+    }
+
+    #[tokio::test]
+    async fn test_action_controller_outage_and_recovery() {
+        let answering = Arc::new(Answering::default());
+        let sender = FaultySender::new(
+            Box::new(answering.clone()),
+            FaultScript::new(vec![Rule::new(
+                Operation::Reconcile,
+                Fault::Error("connection refused".to_string()),
+            )
+            .times(2)]),
+        );
+        let request = ReconcileRequest {
+            scenario_name: "hello".to_string(),
+            ..Default::default()
+        };
+
+        for _ in 0..2 {
+            let status = sender.reconcile(request.clone()).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unavailable);
+        }
+        sender.reconcile(request).await.unwrap();
+        sender.trigger_action("hello".to_string()).await.unwrap();
+        assert_eq!(answering.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_script_from_yaml() {
+        let rules: Vec<Rule> = serde_yaml::from_str(
+            "- operation: trigger_action\n  target: hello\n  fault: !error down\n  times: 1\n- operation: list\n  fault: !delay 200\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                Rule::new(Operation::TriggerAction, Fault::Error("down".to_string()))
+                    .on("hello")
+                    .times(1),
+                Rule::new(Operation::List, Fault::Delay(200)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manager_reports_action_controller_outage() {
+        install(vec![Rule::new(
+            Operation::Reconcile,
+            Fault::Error("connection refused".to_string()),
+        )
+        .on("fault-test-outage")]);
+        let (_tx_container, rx_container) = tokio::sync::mpsc::channel(1);
+        let (_tx_state_change, rx_state_change) = tokio::sync::mpsc::channel(1);
+        let manager = crate::manager::StateManagerManager::new(rx_container, rx_state_change).await;
+
+        let error = manager
+            .send_reconcile_request("fault-test-outage")
+            .await
+            .unwrap_err();
+        script().remove("fault-test-outage");
+        assert!(error.contains("connection refused"), "{}", error);
+    }
+}
@@ -13,35 +13,97 @@
 //! including state changes, resource queries, recovery management, and event notifications.
 pub mod timpani;
 
+use crate::dedup::TransitionDedup;
+use crate::queue::{BoundedSender, EnqueueError, QueueStats};
+use crate::types::SimulationJob;
+use crate::vehicle_mode::{VehicleModeState, VehicleModeStore};
+use common::auth::Role;
 use common::logd;
 use common::monitoringserver::{ContainerList, SendContainerListResponse};
+use common::state_mapping;
 use common::statemanager::{
+    state_change_event::Event,
     state_manager_connection_server::StateManagerConnection,
     Action,
+    ActionResult,
+    ActionResultResponse,
+    DeadLetter,
     ErrorCode,
+    ForceSynchronizationRequest,
+    GetVehicleModeRequest,
+    ListDeadLettersRequest,
+    ListDeadLettersResponse,
+    PendingWorkRequest,
+    PendingWorkResponse,
+    RedriveDeadLettersRequest,
+    RedriveDeadLettersResponse,
+    ReloadConfigRequest,
+    ReloadConfigResponse,
     // // State Query API message types
     // ResourceStateRequest, ResourceStateResponse,
     // ResourceStateHistoryRequest, ResourceStateHistoryResponse,
     // ListResourcesByStateRequest, ListResourcesByStateResponse,
 
     // // State Management API message types
-    // UpdateDesiredStateRequest, TriggerStateTransitionRequest, ForceSynchronizationRequest,
+    // UpdateDesiredStateRequest, TriggerStateTransitionRequest,
 
     // // Recovery Management API message types
     // TriggerRecoveryRequest, AbortRecoveryRequest, RecoveryStatusRequest,
     // RecoveryResponse, RecoveryStatusResponse,
 
     // // Event and Notification API message types
-    // StateChangeSubscriptionRequest, StateChangeEvent,
     // AcknowledgeAlertRequest, AlertResponse,
     // GetPendingAlertsRequest, GetPendingAlertsResponse,
     ResourceType,
+    SimulationRequest,
+    SimulationResponse,
     StateChange,
+    StateChangeEvent,
     StateChangeResponse,
+    StateChangeSubscriptionRequest,
+    TransitionRecord,
+    UpdateContainerStateRequest,
+    UpdateContainerStateResponse,
+    VehicleMode,
+    VehicleModeRequest,
+    VehicleModeResponse,
+    VerifySafetyLogRequest,
+    VerifySafetyLogResponse,
 };
-use tokio::sync::mpsc;
+use common::version::{ApiVersionRequest, ApiVersionResponse};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Status};
 
+/// Events buffered for a state change subscriber
+const SUBSCRIPTION_BUFFER: usize = 256;
+
+/// Metadata entries accepted on a StateChange
+const MAX_METADATA_ENTRIES: usize = 32;
+
+/// Bytes accepted for a metadata key or value of a StateChange
+const MAX_METADATA_LENGTH: usize = 256;
+
+/// RPCs of the StateManager reported by `NegotiateApiVersion`
+const CAPABILITIES: &[&str] = &[
+    "SendStateChange",
+    "ForceSynchronization",
+    "SimulateStateChanges",
+    "ListDeadLetters",
+    "RedriveDeadLetters",
+    "ReportActionResult",
+    "GetPendingWork",
+    "VerifySafetyLog",
+    "SubscribeToStateChanges",
+    "SetVehicleMode",
+    "GetVehicleMode",
+    "ReloadConfig",
+    "NegotiateApiVersion",
+    "SendAction",
+    "SendChangedContainerList",
+    "UpdateContainerState",
+];
+
 /// StateManager gRPC service handler.
 ///
 /// This struct implements the StateManagerConnection gRPC service and acts as the
@@ -57,18 +119,39 @@ use tonic::{Request, Status};
 pub struct StateManagerReceiver {
     /// Channel sender for ContainerList messages from nodeagent.
     /// Used to forward container status updates to the StateManager for processing.
-    pub tx: mpsc::Sender<ContainerList>,
+    pub tx: BoundedSender<ContainerList>,
 
     /// Channel sender for StateChange messages from various components.
     /// Used to forward state transition requests to the StateManager's state machine engine.
-    pub tx_state_change: mpsc::Sender<StateChange>,
+    pub tx_state_change: BoundedSender<StateChange>,
+
+    /// Channel sender for single container state updates.
+    /// Used to merge container events into the StateManager's container cache.
+    pub tx_container_update: BoundedSender<UpdateContainerStateRequest>,
+
+    /// Channel sender for ForceSynchronization requests.
+    /// Used to have the StateManager detect and correct drift of the requested resources.
+    pub tx_sync: BoundedSender<ForceSynchronizationRequest>,
+
+    /// Channel sender for state changes to simulate.
+    /// Used to have the StateManager dry run state changes against a snapshot of its state machine.
+    pub tx_simulation: BoundedSender<SimulationJob>,
+
+    /// Current vehicle operational mode, reported by a mode source and read
+    /// before running mode restricted scenarios.
+    pub vehicle_mode: VehicleModeStore,
+
+    /// Accepted transition_ids with their original responses.
+    /// Used to answer retried StateChange requests without processing them twice.
+    pub dedup: TransitionDedup,
 }
 
 #[tonic::async_trait]
 impl StateManagerConnection for StateManagerReceiver {
     /// Stream type for state change event subscriptions.
     /// Uses ReceiverStream to provide async streaming of state change events to subscribers.
-    /// type SubscribeToStateChangesStream = ReceiverStream<Result<StateChangeEvent, Status>>;
+    type SubscribeToStateChangesStream = ReceiverStream<Result<StateChangeEvent, Status>>;
+
     /// Handles action requests (legacy implementation).
     ///
     /// # Arguments
@@ -84,6 +167,7 @@ impl StateManagerConnection for StateManagerReceiver {
         &self,
         request: Request<Action>,
     ) -> Result<tonic::Response<common::statemanager::Response>, Status> {
+        common::auth::authorize(&request, "SendAction", Role::Operator)?;
         let req = request.into_inner();
         let command = req.action;
 
@@ -116,7 +200,17 @@ impl StateManagerConnection for StateManagerReceiver {
         &'life self,
         request: Request<ContainerList>,
     ) -> Result<tonic::Response<SendContainerListResponse>, Status> {
-        let req: ContainerList = request.into_inner();
+        crate::leader::require_leader("SendChangedContainerList")?;
+        let principal =
+            common::auth::authorize(&request, "SendChangedContainerList", Role::Operator)?;
+        let remote = request.remote_addr();
+        let mut req: ContainerList = request.into_inner();
+        crate::ratelimit::check(
+            "SendChangedContainerList",
+            &crate::ratelimit::peer(principal.as_ref(), remote),
+        )?;
+        let version = common::version::accept("SendChangedContainerList", req.api_version)?;
+        common::version::upgrade_container_list(&mut req, version);
 
         match self.tx.send(req).await {
             Ok(_) => Ok(tonic::Response::new(SendContainerListResponse {
@@ -169,9 +263,30 @@ impl StateManagerConnection for StateManagerReceiver {
         &self,
         request: Request<StateChange>,
     ) -> Result<tonic::Response<StateChangeResponse>, Status> {
+        let principal = common::auth::authorize(&request, "SendStateChange", Role::Operator)?;
+        crate::leader::require_leader("SendStateChange")?;
+        let remote = request.remote_addr();
         let req = request.into_inner();
+        crate::ratelimit::check(
+            "SendStateChange",
+            &crate::ratelimit::peer(principal.as_ref(), remote),
+        )?;
         let transition_id = req.transition_id.clone();
 
+        // Package transitions follow the actions executed by the ActionController
+        if req.resource_type == ResourceType::Package as i32 {
+            common::auth::require_caller(
+                principal.as_ref(),
+                "SendStateChange(Package)",
+                &["actioncontroller"],
+            )?;
+        }
+        common::auth::check_namespace(
+            principal.as_ref(),
+            "SendStateChange",
+            common::namespace::of(&req.resource_name),
+        )?;
+
         // 🔍 COMMENT 5: StateManager receiving scenario state change requests
         // This method receives state change requests from multiple components:
         // - FilterGateway: when scenario conditions are registered and met
@@ -184,12 +299,10 @@ impl StateManagerConnection for StateManagerReceiver {
             return Ok(tonic::Response::new(StateChangeResponse {
                 message: format!("StateChange validation failed: {validation_error}"),
                 transition_id, // Preserve original ID even for validation failures
-                timestamp_ns: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_nanos() as i64,
+                timestamp_ns: common::clock::now_ns(),
                 error_code: ErrorCode::InvalidRequest as i32,
                 error_details: validation_error,
+                metadata: Default::default(),
             }));
         }
 
@@ -210,41 +323,772 @@ impl StateManagerConnection for StateManagerReceiver {
         );
         logd!(1, "  ID: {}, Source: {}", req.transition_id, req.source);
 
-        // Forward StateChange to StateManager's state machine engine
+        // Generate ASIL-compliant success response
+        let accepted = StateChangeResponse {
+            message: "StateChange successfully received and queued for processing".to_string(),
+            transition_id: transition_id.clone(), // Preserve original ID for tracking
+            timestamp_ns: common::clock::now_ns(), // Nanosecond precision for ASIL
+            error_code: ErrorCode::Success as i32,
+            error_details: String::new(), // No error details for success
+            metadata: req.metadata.clone(),
+        };
+
+        // Retried transitions get their original response and are not processed again
+        if let Some(original) = self.dedup.begin(&accepted).await {
+            logd!(
+                2,
+                "Duplicate StateChange {}, returning original response",
+                transition_id
+            );
+            return Ok(tonic::Response::new(original));
+        }
+
+        // Forward StateChange to StateManager's state machine engine, the
+        // timing budget runs from here to the persistence of the new state
+        crate::timing::received(&transition_id);
+        crate::pending::queued(&req);
         match self.tx_state_change.send(req).await {
-            Ok(_) => {
-                // Generate ASIL-compliant success response
-                Ok(tonic::Response::new(StateChangeResponse {
-                    message: "StateChange successfully received and queued for processing"
-                        .to_string(),
-                    transition_id, // Preserve original ID for tracking
-                    timestamp_ns: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_nanos() as i64, // Nanosecond precision for ASIL
-                    error_code: ErrorCode::Success as i32,
-                    error_details: String::new(), // No error details for success
-                }))
-            }
+            Ok(_) => Ok(tonic::Response::new(accepted)),
             Err(e) => {
-                // Channel send failed - StateManager unavailable or overloaded
+                // Queue full (reject policy) or StateManager engine stopped
                 logd!(5, "Failed to forward StateChange to StateManager: {e}");
+                crate::timing::discard(&transition_id);
+                crate::pending::discard(&transition_id);
+                self.dedup.abort(&transition_id).await;
+                let message = match e {
+                    EnqueueError::Full => "StateManager queue full, retry later",
+                    EnqueueError::Closed => "StateManager service unavailable",
+                };
                 Ok(tonic::Response::new(StateChangeResponse {
-                    message: "StateManager service unavailable".to_string(),
+                    message: message.to_string(),
                     transition_id, // Preserve original ID for tracking
-                    timestamp_ns: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_nanos() as i64,
+                    timestamp_ns: common::clock::now_ns(),
                     error_code: ErrorCode::ResourceUnavailable as i32,
                     error_details: format!("Cannot forward StateChange to StateManager: {e}"),
+                    metadata: Default::default(),
+                }))
+            }
+        }
+    }
+
+    /// Handles the state change of a single container.
+    ///
+    /// The update is merged into the container cache of the StateManager,
+    /// which re-evaluates the container's model and package without waiting
+    /// for the next ContainerList snapshot of the node.
+    ///
+    /// # Returns
+    /// * `ERROR_CODE_INVALID_REQUEST` - node name, container id or state is missing
+    /// * `ERROR_CODE_RESOURCE_UNAVAILABLE` - the update could not be queued
+    async fn update_container_state(
+        &self,
+        request: Request<UpdateContainerStateRequest>,
+    ) -> Result<tonic::Response<UpdateContainerStateResponse>, Status> {
+        let principal = common::auth::authorize(&request, "UpdateContainerState", Role::Operator)?;
+        crate::leader::require_leader("UpdateContainerState")?;
+        let remote = request.remote_addr();
+        let req = request.into_inner();
+        crate::ratelimit::check(
+            "UpdateContainerState",
+            &crate::ratelimit::peer(principal.as_ref(), remote),
+        )?;
+
+        if let Err(validation_error) = Self::validate_container_update(&req) {
+            return Ok(tonic::Response::new(UpdateContainerStateResponse {
+                message: format!("UpdateContainerState validation failed: {validation_error}"),
+                error_code: ErrorCode::InvalidRequest as i32,
+            }));
+        }
+
+        logd!(
+            1,
+            "UpdateContainerState received: {} on {}",
+            req.container_id,
+            req.node_name
+        );
+
+        match self.tx_container_update.send(req).await {
+            Ok(_) => Ok(tonic::Response::new(UpdateContainerStateResponse {
+                message: "Container state update queued for processing".to_string(),
+                error_code: ErrorCode::Success as i32,
+            })),
+            Err(e) => {
+                logd!(5, "Failed to forward container update to StateManager: {e}");
+                let message = match e {
+                    EnqueueError::Full => "StateManager queue full, retry later",
+                    EnqueueError::Closed => "StateManager service unavailable",
+                };
+                Ok(tonic::Response::new(UpdateContainerStateResponse {
+                    message: message.to_string(),
+                    error_code: ErrorCode::ResourceUnavailable as i32,
                 }))
             }
         }
     }
+
+    /// Synchronizes desired and actual states of resources.
+    ///
+    /// The StateManager compares the desired states of the deployed scenarios
+    /// covered by the request with the reported containers, alerts on drift
+    /// and reconciles the drifted scenarios. A deep synchronization also
+    /// corrects stored model and package states. An unspecified resource type
+    /// or an empty resource name synchronizes every resource.
+    ///
+    /// # Returns
+    /// * `ERROR_CODE_SUCCESS` - the synchronization was queued
+    /// * `ERROR_CODE_INVALID_REQUEST` - the resource type has no desired state
+    /// * `ERROR_CODE_RESOURCE_UNAVAILABLE` - the request could not be queued
+    async fn force_synchronization(
+        &self,
+        request: Request<ForceSynchronizationRequest>,
+    ) -> Result<tonic::Response<StateChangeResponse>, Status> {
+        common::auth::authorize(&request, "ForceSynchronization", Role::Operator)?;
+        crate::leader::require_leader("ForceSynchronization")?;
+        let req = request.into_inner();
+        let timestamp_ns = common::clock::now_ns();
+        let transition_id = format!(
+            "sync-{}-{}",
+            if req.resource_name.is_empty() {
+                "all"
+            } else {
+                &req.resource_name
+            },
+            timestamp_ns
+        );
+        let response = |message: String, error_code: ErrorCode, error_details: String| {
+            tonic::Response::new(StateChangeResponse {
+                message,
+                transition_id: transition_id.clone(),
+                timestamp_ns,
+                error_code: error_code as i32,
+                error_details,
+                metadata: Default::default(),
+            })
+        };
+
+        if !matches!(
+            ResourceType::try_from(req.resource_type),
+            Ok(ResourceType::Unspecified
+                | ResourceType::Scenario
+                | ResourceType::Package
+                | ResourceType::Model)
+        ) {
+            return Ok(response(
+                "ForceSynchronization validation failed".to_string(),
+                ErrorCode::InvalidRequest,
+                format!(
+                    "Cannot synchronize resource type {}",
+                    self.resource_type_to_string(req.resource_type)
+                ),
+            ));
+        }
+
+        logd!(
+            3,
+            "ForceSynchronization received: {} '{}' (deep: {})",
+            self.resource_type_to_string(req.resource_type),
+            req.resource_name,
+            req.deep_sync
+        );
+
+        match self.tx_sync.send(req).await {
+            Ok(_) => Ok(response(
+                "Synchronization queued for processing".to_string(),
+                ErrorCode::Success,
+                String::new(),
+            )),
+            Err(e) => {
+                logd!(
+                    5,
+                    "Failed to forward ForceSynchronization to StateManager: {e}"
+                );
+                let message = match e {
+                    EnqueueError::Full => "StateManager queue full, retry later",
+                    EnqueueError::Closed => "StateManager service unavailable",
+                };
+                Ok(response(
+                    message.to_string(),
+                    ErrorCode::ResourceUnavailable,
+                    format!("Cannot forward ForceSynchronization to StateManager: {e}"),
+                ))
+            }
+        }
+    }
+
+    /// Lists the state changes kept in the dead-letter queue.
+    ///
+    /// Dead letters of namespaces the caller cannot access are left out.
+    async fn list_dead_letters(
+        &self,
+        request: Request<ListDeadLettersRequest>,
+    ) -> Result<tonic::Response<ListDeadLettersResponse>, Status> {
+        let principal = common::auth::authorize(&request, "ListDeadLetters", Role::ReadOnly)?;
+        let req = request.into_inner();
+        let resource_type = ResourceType::try_from(req.resource_type).map_err(|_| {
+            Status::invalid_argument(format!("Invalid resource type: {}", req.resource_type))
+        })?;
+        let dead_letters = crate::dlq::list()
+            .await?
+            .into_iter()
+            .filter(|letter| {
+                crate::dlq::selects(letter, resource_type, &req.resource_name)
+                    && Self::can_access(principal.as_ref(), letter)
+            })
+            .collect();
+        Ok(tonic::Response::new(ListDeadLettersResponse {
+            dead_letters,
+        }))
+    }
+
+    /// Queues dead letters again after the cause of their failure was fixed,
+    /// or discards them.
+    ///
+    /// Re-driven changes keep their transition_id and leave the queue once
+    /// they succeed. Without transition_ids every dead letter the caller can
+    /// access is re-driven.
+    ///
+    /// # Errors
+    /// * `Status::permission_denied` - a requested dead letter belongs to a
+    ///   namespace the caller cannot access
+    async fn redrive_dead_letters(
+        &self,
+        request: Request<RedriveDeadLettersRequest>,
+    ) -> Result<tonic::Response<RedriveDeadLettersResponse>, Status> {
+        let principal = common::auth::authorize(&request, "RedriveDeadLetters", Role::Operator)?;
+        crate::leader::require_leader("RedriveDeadLetters")?;
+        let req = request.into_inner();
+        let letters = crate::dlq::list().await?;
+
+        let mut not_found = Vec::new();
+        let selected: Vec<StateChange> = if req.transition_ids.is_empty() {
+            letters
+                .into_iter()
+                .filter(|letter| Self::can_access(principal.as_ref(), letter))
+                .filter_map(|letter| letter.state_change)
+                .collect()
+        } else {
+            let mut selected = Vec::new();
+            for transition_id in &req.transition_ids {
+                let change = letters
+                    .iter()
+                    .filter_map(|letter| letter.state_change.as_ref())
+                    .find(|change| &change.transition_id == transition_id);
+                match change {
+                    Some(change) => {
+                        common::auth::check_namespace(
+                            principal.as_ref(),
+                            "RedriveDeadLetters",
+                            common::namespace::of(&change.resource_name),
+                        )?;
+                        selected.push(change.clone());
+                    }
+                    None => not_found.push(transition_id.clone()),
+                }
+            }
+            selected
+        };
+
+        let total = selected.len();
+        let mut redriven = Vec::new();
+        for change in selected {
+            let transition_id = change.transition_id.clone();
+            if req.discard {
+                crate::dlq::discard(&transition_id).await?;
+                redriven.push(transition_id);
+                continue;
+            }
+            crate::dlq::redrive(&transition_id);
+            crate::timing::received(&transition_id);
+            crate::pending::queued(&change);
+            if let Err(e) = self.tx_state_change.send(change).await {
+                logd!(5, "Failed to re-drive StateChange {transition_id}: {e}");
+                crate::timing::discard(&transition_id);
+                crate::pending::discard(&transition_id);
+                return Ok(tonic::Response::new(RedriveDeadLettersResponse {
+                    message: format!(
+                        "Re-drove {} of {} dead letter(s), then: {}",
+                        redriven.len(),
+                        total,
+                        e
+                    ),
+                    redriven,
+                    not_found,
+                }));
+            }
+            redriven.push(transition_id);
+        }
+
+        logd!(
+            3,
+            "RedriveDeadLetters: {} dead letter(s) {}",
+            redriven.len(),
+            if req.discard {
+                "discarded"
+            } else {
+                "re-driven"
+            }
+        );
+        Ok(tonic::Response::new(RedriveDeadLettersResponse {
+            message: format!(
+                "{} dead letter(s) {}",
+                redriven.len(),
+                if req.discard {
+                    "discarded"
+                } else {
+                    "re-driven"
+                }
+            ),
+            redriven,
+            not_found,
+        }))
+    }
+
+    /// Settles an action run by the ActionController and queues the
+    /// transition that follows its outcome, see [`crate::inflight`].
+    ///
+    /// # Errors
+    /// * `Status::invalid_argument` - resource type or name is missing
+    /// * `Status::unavailable` - the follow-up transition could not be queued
+    async fn report_action_result(
+        &self,
+        request: Request<ActionResult>,
+    ) -> Result<tonic::Response<ActionResultResponse>, Status> {
+        let principal = common::auth::authorize(&request, "ReportActionResult", Role::Operator)?;
+        crate::leader::require_leader("ReportActionResult")?;
+        common::auth::require_caller(
+            principal.as_ref(),
+            "ReportActionResult",
+            &["actioncontroller"],
+        )?;
+        let result = request.into_inner();
+        if matches!(
+            ResourceType::try_from(result.resource_type),
+            Err(_) | Ok(ResourceType::Unspecified)
+        ) || result.resource_name.trim().is_empty()
+        {
+            return Err(Status::invalid_argument(
+                "resource_type and resource_name are required",
+            ));
+        }
+        common::auth::check_namespace(
+            principal.as_ref(),
+            "ReportActionResult",
+            common::namespace::of(&result.resource_name),
+        )?;
+
+        // The operation in progress on the resource settles its transition
+        if result.rejected {
+            logd!(
+                4,
+                "ActionResult: {} {} {} rejected: {}",
+                self.resource_type_to_string(result.resource_type),
+                result.resource_name,
+                result.action,
+                result.message
+            );
+            crate::events::alert(
+                ResourceType::try_from(result.resource_type).unwrap_or(ResourceType::Scenario),
+                &result.resource_name,
+                format!("Action {} rejected: {}", result.action, result.message),
+            );
+            return Ok(tonic::Response::new(ActionResultResponse {
+                matched: false,
+                transition_id: String::new(),
+                message: "Rejected action leaves the resource to the running operation".to_string(),
+            }));
+        }
+
+        let settled = crate::inflight::finish(&result);
+        logd!(
+            if result.success { 2 } else { 4 },
+            "ActionResult: {} {} {} {}{}",
+            self.resource_type_to_string(result.resource_type),
+            result.resource_name,
+            result.action,
+            if result.success {
+                "succeeded"
+            } else {
+                "failed"
+            },
+            match &settled {
+                Some(action) => format!(
+                    ", {} of {} settled after {}ms",
+                    action.action,
+                    action.transition_id,
+                    common::clock::instant()
+                        .saturating_duration_since(action.since)
+                        .as_millis()
+                ),
+                None => String::new(),
+            }
+        );
+
+        let transition_id = common::correlation::transition_id("action-result");
+        let Some(change) = crate::inflight::follow_up(&result, transition_id.clone()) else {
+            return Ok(tonic::Response::new(ActionResultResponse {
+                matched: settled.is_some(),
+                transition_id: String::new(),
+                message: "No transition follows the action".to_string(),
+            }));
+        };
+        crate::timing::received(&transition_id);
+        crate::pending::queued(&change);
+        if let Err(e) = self.tx_state_change.send(change).await {
+            logd!(5, "Failed to queue the transition after an action: {e}");
+            crate::timing::discard(&transition_id);
+            crate::pending::discard(&transition_id);
+            return Err(Status::unavailable(format!(
+                "Cannot queue the transition after the action: {e}"
+            )));
+        }
+        Ok(tonic::Response::new(ActionResultResponse {
+            matched: settled.is_some(),
+            transition_id,
+            message: "Transition after the action queued".to_string(),
+        }))
+    }
+
+    /// Reports the work the StateManager has accepted and not finished.
+    ///
+    /// Lists the queued and processed StateChanges, the actions running in
+    /// the action executor or awaited from the ActionController, and the
+    /// target state each resource is headed for, see [`crate::pending`].
+    /// Resources of namespaces the caller cannot access are left out, the
+    /// queue count and age cover every namespace as the queue is shared.
+    async fn get_pending_work(
+        &self,
+        request: Request<PendingWorkRequest>,
+    ) -> Result<tonic::Response<PendingWorkResponse>, Status> {
+        let principal = common::auth::authorize(&request, "GetPendingWork", Role::ReadOnly)?;
+        let req = request.into_inner();
+        let resource_type = ResourceType::try_from(req.resource_type).map_err(|_| {
+            Status::invalid_argument(format!("Invalid resource type: {}", req.resource_type))
+        })?;
+        let mut work = crate::pending::report(resource_type);
+        if let Some(principal) = principal.as_ref() {
+            let visible = |name: &str| principal.can_access(common::namespace::of(name));
+            work.state_changes.retain(|c| visible(&c.resource_name));
+            work.actions.retain(|a| visible(&a.resource_key));
+            work.desired_states.retain(|d| visible(&d.resource_name));
+        }
+        Ok(tonic::Response::new(work))
+    }
+
+    /// Checks the hash chain of the safety event log.
+    ///
+    /// Reports the first record that was modified, removed or reordered, or
+    /// that the log was truncated at its end, see [`crate::safety_log`].
+    async fn verify_safety_log(
+        &self,
+        request: Request<VerifySafetyLogRequest>,
+    ) -> Result<tonic::Response<VerifySafetyLogResponse>, Status> {
+        common::auth::authorize(&request, "VerifySafetyLog", Role::ReadOnly)?;
+        let result = crate::safety_log::verify().await?;
+        if !result.valid {
+            logd!(5, "[SafetyLog] Verification failed: {}", result.message);
+        }
+        Ok(tonic::Response::new(result))
+    }
+
+    /// Simulates state changes without persisting or dispatching anything.
+    ///
+    /// The changes are applied in order to a snapshot of the state machine,
+    /// including condition evaluation and cascaded model transitions. The
+    /// response lists the transitions they would make with the actions that
+    /// would be dispatched and the states that would be stored. Missing
+    /// transition ids, sources and timestamps are filled in.
+    async fn simulate_state_changes(
+        &self,
+        request: Request<SimulationRequest>,
+    ) -> Result<tonic::Response<SimulationResponse>, Status> {
+        common::auth::authorize(&request, "SimulateStateChanges", Role::ReadOnly)?;
+        crate::leader::require_leader("SimulateStateChanges")?;
+        let timestamp_ns = common::clock::now_ns();
+        let changes = request
+            .into_inner()
+            .changes
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut change)| {
+                if change.transition_id.trim().is_empty() {
+                    change.transition_id = format!("simulation-{timestamp_ns}-{index}");
+                }
+                if change.source.trim().is_empty() {
+                    change.source = "simulation".to_string();
+                }
+                if change.timestamp_ns <= 0 {
+                    change.timestamp_ns = timestamp_ns;
+                }
+                change
+            })
+            .collect::<Vec<_>>();
+        logd!(
+            3,
+            "SimulateStateChanges received: {} change(s)",
+            changes.len()
+        );
+
+        let (reply, answer) = tokio::sync::oneshot::channel();
+        if let Err(e) = self
+            .tx_simulation
+            .send(SimulationJob { changes, reply })
+            .await
+        {
+            logd!(5, "Failed to forward simulation to StateManager: {e}");
+            return Err(Status::unavailable(format!(
+                "StateManager cannot simulate now: {e}"
+            )));
+        }
+        match answer.await {
+            Ok(transitions) => Ok(tonic::Response::new(SimulationResponse { transitions })),
+            Err(_) => Err(Status::unavailable(
+                "StateManager stopped before simulating",
+            )),
+        }
+    }
+
+    /// Records the vehicle operational mode reported by a mode source.
+    ///
+    /// # Errors
+    /// * `Status::invalid_argument` - unknown or unspecified mode, or empty source
+    async fn set_vehicle_mode(
+        &self,
+        request: Request<VehicleModeRequest>,
+    ) -> Result<tonic::Response<VehicleModeResponse>, Status> {
+        common::auth::authorize(&request, "SetVehicleMode", Role::Operator)?;
+        crate::leader::require_leader("SetVehicleMode")?;
+        let req = request.into_inner();
+        let mode = VehicleMode::try_from(req.mode)
+            .map_err(|_| Status::invalid_argument(format!("Invalid vehicle mode: {}", req.mode)))?;
+
+        match self.vehicle_mode.set(mode, &req.source).await {
+            Ok(state) => Ok(tonic::Response::new(Self::vehicle_mode_response(&state))),
+            Err(e) => Err(Status::invalid_argument(e)),
+        }
+    }
+
+    /// Returns the current vehicle operational mode.
+    ///
+    /// The mode is `VEHICLE_MODE_UNSPECIFIED` until a mode source reports one.
+    async fn get_vehicle_mode(
+        &self,
+        request: Request<GetVehicleModeRequest>,
+    ) -> Result<tonic::Response<VehicleModeResponse>, Status> {
+        common::auth::authorize(&request, "GetVehicleMode", Role::ReadOnly)?;
+        let state = self.vehicle_mode.get().await;
+        Ok(tonic::Response::new(Self::vehicle_mode_response(&state)))
+    }
+
+    /// Re-reads settings.yaml and applies the settings that do not need a
+    /// restart, listing the changed ones that do.
+    async fn reload_config(
+        &self,
+        request: Request<ReloadConfigRequest>,
+    ) -> Result<tonic::Response<ReloadConfigResponse>, Status> {
+        common::auth::authorize(&request, "ReloadConfig", Role::Admin)?;
+        let report = common::setting::reload().map_err(Status::failed_precondition)?;
+        Ok(tonic::Response::new(ReloadConfigResponse {
+            applied: report.applied,
+            restart_required: report.restart_required,
+        }))
+    }
+
+    /// Agrees on the API version with a client and lists the RPCs of the
+    /// StateManager.
+    async fn negotiate_api_version(
+        &self,
+        request: Request<ApiVersionRequest>,
+    ) -> Result<tonic::Response<ApiVersionResponse>, Status> {
+        common::auth::authorize(&request, "NegotiateApiVersion", Role::ReadOnly)?;
+        let req = request.into_inner();
+        logd!(
+            2,
+            "{} negotiates API versions {} to {}",
+            req.component,
+            req.min_api_version,
+            req.max_api_version
+        );
+        common::version::respond("statemanager", &req, CAPABILITIES).map(tonic::Response::new)
+    }
+
+    /// Streams state transitions, and alerts if asked, as they happen.
+    ///
+    /// With `snapshot` the stream starts with the stored state of every
+    /// resource as transitions with the `snapshot` transition id. A
+    /// subscriber that reads too slowly misses the oldest events, which is
+    /// logged. The stream ends when the subscriber goes away.
+    async fn subscribe_to_state_changes(
+        &self,
+        request: Request<StateChangeSubscriptionRequest>,
+    ) -> Result<tonic::Response<Self::SubscribeToStateChangesStream>, Status> {
+        let principal =
+            common::auth::authorize(&request, "SubscribeToStateChanges", Role::ReadOnly)?;
+        let req = request.into_inner();
+        let namespace = (!req.namespace.is_empty()).then(|| req.namespace.clone());
+        if let Some(namespace) = &namespace {
+            common::auth::check_namespace(
+                principal.as_ref(),
+                "SubscribeToStateChanges",
+                namespace,
+            )?;
+        }
+        // Events of the namespaces the caller cannot access are left out
+        let visible = move |event: &StateChangeEvent| {
+            let event_namespace = crate::events::namespace(event);
+            namespace.as_deref().is_none_or(|n| n == event_namespace)
+                && principal
+                    .as_ref()
+                    .is_none_or(|p| p.can_access(event_namespace))
+        };
+        let resource_type = match ResourceType::try_from(req.resource_type) {
+            Ok(ResourceType::Unspecified) => None,
+            Ok(resource_type) => Some(crate::notifier::type_name(resource_type)),
+            Err(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid resource type: {}",
+                    req.resource_type
+                )))
+            }
+        };
+        logd!(
+            3,
+            "SubscribeToStateChanges: type {}, namespace {}, alerts {}, snapshot {}",
+            resource_type.unwrap_or("all"),
+            if req.namespace.is_empty() {
+                "all"
+            } else {
+                &req.namespace
+            },
+            req.include_alerts,
+            req.snapshot
+        );
+
+        // Subscribe before reading the snapshot so no transition falls between
+        let mut events = crate::events::subscribe();
+        let (tx, rx) = tokio::sync::mpsc::channel(SUBSCRIPTION_BUFFER);
+        tokio::spawn(async move {
+            if req.snapshot {
+                for record in Self::snapshot(resource_type).await {
+                    let event = StateChangeEvent {
+                        event: Some(Event::Transition(record)),
+                    };
+                    if visible(&event) && tx.send(Ok(event)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if crate::events::selects(&event, resource_type, req.include_alerts)
+                            && visible(&event)
+                            && tx.send(Ok(event)).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        logd!(4, "State change subscriber missed {} event(s)", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+    }
 }
 
 impl StateManagerReceiver {
+    /// Whether the caller may see a dead letter, anyone without authentication
+    fn can_access(principal: Option<&common::auth::Principal>, letter: &DeadLetter) -> bool {
+        let resource_name = letter
+            .state_change
+            .as_ref()
+            .map_or("", |change| change.resource_name.as_str());
+        principal.is_none_or(|p| p.can_access(common::namespace::of(resource_name)))
+    }
+
+    /// Depth and overflow counters of the ContainerList, StateChange and
+    /// container update channels.
+    pub fn queue_stats(&self) -> (QueueStats, QueueStats, QueueStats) {
+        (
+            self.tx.stats(),
+            self.tx_state_change.stats(),
+            self.tx_container_update.stats(),
+        )
+    }
+
+    /// Stored state of every resource of a type, all types for `None`.
+    async fn snapshot(resource_type: Option<&str>) -> Vec<TransitionRecord> {
+        let timestamp_ns = common::clock::now_ns();
+        let mut records = Vec::new();
+        for rt in [
+            ResourceType::Scenario,
+            ResourceType::Package,
+            ResourceType::Model,
+        ] {
+            let type_name = crate::notifier::type_name(rt);
+            if resource_type.is_some_and(|resource_type| resource_type != type_name) {
+                continue;
+            }
+            let entries = match crate::storage::storage()
+                .get_all_with_prefix(&format!("/{}/", type_name))
+                .await
+            {
+                Ok(entries) => entries,
+                Err(e) => {
+                    logd!(
+                        4,
+                        "Failed to read {} states for a snapshot: {}",
+                        type_name,
+                        e
+                    );
+                    continue;
+                }
+            };
+            records.extend(entries.into_iter().filter_map(|(key, value)| {
+                let name = key
+                    .strip_prefix(&format!("/{}/", type_name))?
+                    .strip_suffix("/state")?;
+                let state = state_mapping::parse_state(rt, &value)?;
+                Some(TransitionRecord {
+                    resource_type: type_name.to_string(),
+                    resource_name: name.to_string(),
+                    from_state: String::new(),
+                    to_state: state_mapping::state_name(rt, state)?.to_string(),
+                    transition_id: "snapshot".to_string(),
+                    timestamp_ns,
+                    health: crate::health::of(rt, name),
+                    metadata: Default::default(),
+                })
+            }));
+        }
+        records
+    }
+
+    /// Checks that a container update identifies a container and carries state.
+    fn validate_container_update(update: &UpdateContainerStateRequest) -> Result<(), String> {
+        if update.node_name.trim().is_empty() {
+            return Err("node_name cannot be empty".to_string());
+        }
+        if update.container_id.trim().is_empty() {
+            return Err("container_id cannot be empty".to_string());
+        }
+        if update.state.is_empty() {
+            return Err("state cannot be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// Converts the stored vehicle mode into its gRPC response.
+    fn vehicle_mode_response(state: &VehicleModeState) -> VehicleModeResponse {
+        VehicleModeResponse {
+            mode: state.vehicle_mode() as i32,
+            source: state.source.clone(),
+            updated_ns: state.updated_ns,
+        }
+    }
+
     /// Validates a StateChange message according to PICCOLO specifications.
     ///
     /// This method performs comprehensive validation of StateChange messages
@@ -294,8 +1138,23 @@ impl StateManagerReceiver {
             return Err("timestamp_ns must be positive".to_string());
         }
 
-        // Additional validation can be added here when more fields are available
-        // in the proto file (metadata, dependencies, constraints, etc.)
+        // Validate metadata, it is copied into every action and history record
+        if state_change.metadata.len() > MAX_METADATA_ENTRIES {
+            return Err(format!(
+                "metadata has {} entries, at most {MAX_METADATA_ENTRIES} are allowed",
+                state_change.metadata.len()
+            ));
+        }
+        for (key, value) in &state_change.metadata {
+            if key.trim().is_empty() {
+                return Err("metadata keys cannot be empty".to_string());
+            }
+            if key.len() > MAX_METADATA_LENGTH || value.len() > MAX_METADATA_LENGTH {
+                return Err(format!(
+                    "metadata entry {key} exceeds {MAX_METADATA_LENGTH} bytes"
+                ));
+            }
+        }
 
         Ok(())
     }
@@ -323,17 +1182,36 @@ impl StateManagerReceiver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::queue::OverflowPolicy;
     use common::monitoringserver::ContainerList;
     use common::statemanager::{ErrorCode, ResourceType, StateChange};
+    use tokio::sync::mpsc;
     use tonic::Request;
 
+    #[test]
+    fn test_capabilities_list_every_rpc() {
+        let proto = include_str!("../../../../../common/proto/statemanager.proto");
+        for line in proto.lines().map(str::trim) {
+            let Some(rpc) = line.strip_prefix("rpc ") else {
+                continue;
+            };
+            let name = rpc.split([' ', '(']).next().unwrap();
+            assert!(CAPABILITIES.contains(&name), "{} is not listed", name);
+        }
+    }
+
     #[test]
     fn test_validate_state_change_and_resource_type_to_string() {
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx,
-            tx_state_change,
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         // Valid state change
@@ -345,6 +1223,10 @@ mod tests {
             transition_id: "t1".to_string(),
             timestamp_ns: 1,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
         assert!(receiver.validate_state_change(&sc).is_ok());
 
@@ -358,6 +1240,26 @@ mod tests {
         sc3.resource_name = "".to_string();
         assert!(receiver.validate_state_change(&sc3).is_err());
 
+        // Metadata within the limits
+        let mut sc4 = sc.clone();
+        sc4.metadata
+            .insert("approved_by".to_string(), "operator".to_string());
+        assert!(receiver.validate_state_change(&sc4).is_ok());
+
+        // Empty, oversized and too many metadata entries
+        let mut sc5 = sc.clone();
+        sc5.metadata.insert(" ".to_string(), "x".to_string());
+        assert!(receiver.validate_state_change(&sc5).is_err());
+        let mut sc6 = sc.clone();
+        sc6.metadata
+            .insert("k".to_string(), "x".repeat(MAX_METADATA_LENGTH + 1));
+        assert!(receiver.validate_state_change(&sc6).is_err());
+        let mut sc7 = sc.clone();
+        sc7.metadata = (0..=MAX_METADATA_ENTRIES)
+            .map(|i| (format!("k{i}"), String::new()))
+            .collect();
+        assert!(receiver.validate_state_change(&sc7).is_err());
+
         // resource_type_to_string checks
         assert_eq!(
             receiver.resource_type_to_string(ResourceType::Scenario as i32),
@@ -368,17 +1270,24 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_changed_container_list_success_and_failure() {
+        common::etcd::use_in_memory_store();
         // Success path: receiver present
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx: tx.clone(),
-            tx_state_change: tx_state_change.clone(),
+            tx: tx.clone().into(),
+            tx_state_change: tx_state_change.clone().into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         let cl = ContainerList {
             node_name: "n1".to_string(),
             containers: vec![],
+            api_version: common::version::API_VERSION,
         };
         let resp = receiver.send_changed_container_list(Request::new(cl)).await;
         assert!(resp.is_ok());
@@ -387,12 +1296,18 @@ mod tests {
         let (bad_tx, bad_rx) = mpsc::channel::<ContainerList>(1);
         drop(bad_rx);
         let receiver2 = StateManagerReceiver {
-            tx: bad_tx,
-            tx_state_change: tx_state_change.clone(),
+            tx: bad_tx.into(),
+            tx_state_change: tx_state_change.clone().into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
         let cl2 = ContainerList {
             node_name: "n2".to_string(),
             containers: vec![],
+            api_version: common::version::API_VERSION,
         };
         let resp2 = receiver2
             .send_changed_container_list(Request::new(cl2))
@@ -400,18 +1315,75 @@ mod tests {
         assert!(resp2.is_err());
     }
 
+    #[tokio::test]
+    async fn test_send_changed_container_list_requires_operator() {
+        common::etcd::use_in_memory_store();
+        let (tx, mut rx) = mpsc::channel::<ContainerList>(1);
+        let receiver = StateManagerReceiver {
+            tx: tx.into(),
+            tx_state_change: mpsc::channel::<StateChange>(1).0.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+        let request = |role: Option<Role>| {
+            let mut request = Request::new(ContainerList {
+                node_name: "n1".to_string(),
+                containers: vec![],
+                api_version: common::version::API_VERSION,
+            });
+            if let Some(role) = role {
+                request.extensions_mut().insert(common::auth::Principal {
+                    name: "nodeagent".to_string(),
+                    role,
+                    namespaces: vec![],
+                });
+            }
+            request
+        };
+        let settings = common::setting::AuthSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        common::auth::scope(settings, async {
+            let anonymous = receiver.send_changed_container_list(request(None)).await;
+            assert_eq!(anonymous.unwrap_err().code(), tonic::Code::Unauthenticated);
+            let viewer = receiver
+                .send_changed_container_list(request(Some(Role::ReadOnly)))
+                .await;
+            assert_eq!(viewer.unwrap_err().code(), tonic::Code::PermissionDenied);
+            assert!(rx.try_recv().is_err());
+
+            let operator = receiver
+                .send_changed_container_list(request(Some(Role::Operator)))
+                .await;
+            assert!(operator.is_ok());
+            assert!(rx.try_recv().is_ok());
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_send_changed_container_list_response_content() {
+        common::etcd::use_in_memory_store();
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx: tx.clone(),
-            tx_state_change: tx_state_change.clone(),
+            tx: tx.clone().into(),
+            tx_state_change: tx_state_change.clone().into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         let cl = ContainerList {
             node_name: "n1".to_string(),
             containers: vec![],
+            api_version: common::version::API_VERSION,
         };
         let resp = receiver
             .send_changed_container_list(Request::new(cl))
@@ -424,12 +1396,18 @@ mod tests {
         let (bad_tx, bad_rx) = mpsc::channel::<ContainerList>(1);
         drop(bad_rx);
         let receiver2 = StateManagerReceiver {
-            tx: bad_tx,
-            tx_state_change,
+            tx: bad_tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
         let cl2 = ContainerList {
             node_name: "n2".to_string(),
             containers: vec![],
+            api_version: common::version::API_VERSION,
         };
         let resp2 = receiver2
             .send_changed_container_list(Request::new(cl2))
@@ -444,12 +1422,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_state_change_success_and_unavailable() {
+        common::etcd::use_in_memory_store();
         // Success: tx_state_change has receiver
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, mut rx_state_change) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx: tx.clone(),
-            tx_state_change: tx_state_change.clone(),
+            tx: tx.clone().into(),
+            tx_state_change: tx_state_change.clone().into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         let sc = StateChange {
@@ -460,23 +1444,33 @@ mod tests {
             transition_id: "t2".to_string(),
             timestamp_ns: 1,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: [("retry".to_string(), "2".to_string())].into(),
         };
 
         let resp = receiver.send_state_change(Request::new(sc.clone())).await;
         assert!(resp.is_ok());
         let body = resp.unwrap().into_inner();
         assert_eq!(body.error_code, ErrorCode::Success as i32);
+        assert_eq!(body.metadata, sc.metadata);
 
-        // ensure message was forwarded
+        // ensure message was forwarded with its metadata
         let forwarded = rx_state_change.recv().await;
-        assert!(forwarded.is_some());
+        assert_eq!(forwarded.unwrap().metadata, sc.metadata);
 
         // Failure: tx_state_change cannot send (receiver dropped)
         let (bad_tx, bad_rx) = mpsc::channel::<StateChange>(1);
         drop(bad_rx);
         let receiver2 = StateManagerReceiver {
-            tx: tx.clone(),
-            tx_state_change: bad_tx,
+            tx: tx.clone().into(),
+            tx_state_change: bad_tx.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         let sc2 = StateChange {
@@ -493,11 +1487,17 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_action_returns_unavailable() {
+        common::etcd::use_in_memory_store();
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx,
-            tx_state_change,
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         let action = common::statemanager::Action {
@@ -512,12 +1512,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_state_change_validation_failure_returns_invalid_request() {
+        common::etcd::use_in_memory_store();
         // Create receiver; validation should fail before attempting to forward
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx,
-            tx_state_change,
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         // Build an invalid StateChange (timestamp_ns <= 0)
@@ -529,6 +1535,10 @@ mod tests {
             transition_id: "bad-tid".to_string(),
             timestamp_ns: 0,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         let resp = receiver.send_state_change(Request::new(sc)).await;
@@ -539,11 +1549,17 @@ mod tests {
 
     #[tokio::test]
     async fn test_send_state_change_invalid_resource_type_returns_invalid_request() {
+        common::etcd::use_in_memory_store();
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx,
-            tx_state_change,
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         let sc = StateChange {
@@ -554,6 +1570,10 @@ mod tests {
             transition_id: "tid-invalid".to_string(),
             timestamp_ns: 1,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         let resp = receiver.send_state_change(Request::new(sc)).await;
@@ -567,8 +1587,13 @@ mod tests {
         let (tx, _rx) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
         let receiver = StateManagerReceiver {
-            tx,
-            tx_state_change,
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
         };
 
         assert_eq!(
@@ -597,6 +1622,455 @@ mod tests {
         );
         assert_eq!(receiver.resource_type_to_string(9999), "Unknown");
     }
+
+    #[tokio::test]
+    async fn test_send_state_change_duplicate_transition_id() {
+        common::etcd::use_in_memory_store();
+        let (tx, _rx) = mpsc::channel::<ContainerList>(1);
+        let (tx_state_change, mut rx_state_change) = mpsc::channel::<StateChange>(4);
+        let receiver = StateManagerReceiver {
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let sc = StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "res-dup".to_string(),
+            current_state: "idle".to_string(),
+            target_state: "waiting".to_string(),
+            transition_id: "receiver-dup-t1".to_string(),
+            timestamp_ns: 1,
+            source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+
+        let first = receiver
+            .send_state_change(Request::new(sc.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        let retry = receiver
+            .send_state_change(Request::new(sc))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(retry, first);
+
+        // Only the first request reaches the state machine
+        assert!(rx_state_change.recv().await.is_some());
+        assert!(rx_state_change.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_state_change_rejected_when_queue_full() {
+        common::etcd::use_in_memory_store();
+        let (tx, _rx) = mpsc::channel::<ContainerList>(1);
+        let (tx_state_change, _rx_state_change) =
+            crate::queue::channel::<StateChange>("StateChange", 1, OverflowPolicy::Reject);
+        let receiver = StateManagerReceiver {
+            tx: tx.into(),
+            tx_state_change,
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let sc = StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "res-full".to_string(),
+            current_state: "idle".to_string(),
+            target_state: "waiting".to_string(),
+            transition_id: "receiver-full-t1".to_string(),
+            timestamp_ns: 1,
+            source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+        let first = receiver
+            .send_state_change(Request::new(sc.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(first.error_code, ErrorCode::Success as i32);
+
+        let second = receiver
+            .send_state_change(Request::new(StateChange {
+                transition_id: "receiver-full-t2".to_string(),
+                ..sc
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(second.error_code, ErrorCode::ResourceUnavailable as i32);
+
+        let (_, state_change_stats, _) = receiver.queue_stats();
+        assert_eq!(state_change_stats.depth, 1);
+        assert_eq!(state_change_stats.rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn test_set_and_get_vehicle_mode() {
+        common::etcd::use_in_memory_store();
+        let (tx, _rx) = mpsc::channel::<ContainerList>(1);
+        let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
+        let receiver = StateManagerReceiver {
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let resp = receiver
+            .get_vehicle_mode(Request::new(GetVehicleModeRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(resp.mode, VehicleMode::Unspecified as i32);
+
+        let resp = receiver
+            .set_vehicle_mode(Request::new(VehicleModeRequest {
+                mode: VehicleMode::Charging as i32,
+                source: "unittest".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(resp.mode, VehicleMode::Charging as i32);
+        assert_eq!(resp.source, "unittest");
+
+        let resp = receiver
+            .get_vehicle_mode(Request::new(GetVehicleModeRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(resp.mode, VehicleMode::Charging as i32);
+
+        for mode in [VehicleMode::Unspecified as i32, 42] {
+            let status = receiver
+                .set_vehicle_mode(Request::new(VehicleModeRequest {
+                    mode,
+                    source: "unittest".to_string(),
+                }))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_container_state() {
+        common::etcd::use_in_memory_store();
+        let (tx, _rx) = mpsc::channel::<ContainerList>(1);
+        let (tx_state_change, _rx2) = mpsc::channel::<StateChange>(1);
+        let (tx_container_update, mut rx_container_update) =
+            mpsc::channel::<UpdateContainerStateRequest>(1);
+        let receiver = StateManagerReceiver {
+            tx: tx.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: tx_container_update.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let update = UpdateContainerStateRequest {
+            node_name: "node1".to_string(),
+            container_id: "c1".to_string(),
+            model_name: "m1".to_string(),
+            state: std::collections::HashMap::from([("Status".to_string(), "exited".to_string())]),
+            timestamp_ns: 1,
+        };
+
+        let invalid = receiver
+            .update_container_state(Request::new(UpdateContainerStateRequest {
+                container_id: String::new(),
+                ..update.clone()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(invalid.error_code, ErrorCode::InvalidRequest as i32);
+
+        let accepted = receiver
+            .update_container_state(Request::new(update.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(accepted.error_code, ErrorCode::Success as i32);
+        assert_eq!(rx_container_update.recv().await, Some(update.clone()));
+
+        drop(rx_container_update);
+        let unavailable = receiver
+            .update_container_state(Request::new(update))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            unavailable.error_code,
+            ErrorCode::ResourceUnavailable as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn test_force_synchronization() {
+        common::etcd::use_in_memory_store();
+        let (tx_sync, mut rx_sync) = mpsc::channel::<ForceSynchronizationRequest>(1);
+        let receiver = StateManagerReceiver {
+            tx: mpsc::channel::<ContainerList>(1).0.into(),
+            tx_state_change: mpsc::channel::<StateChange>(1).0.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: tx_sync.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let invalid = receiver
+            .force_synchronization(Request::new(ForceSynchronizationRequest {
+                resource_type: ResourceType::Network as i32,
+                resource_name: "net".to_string(),
+                deep_sync: false,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(invalid.error_code, ErrorCode::InvalidRequest as i32);
+
+        let request = ForceSynchronizationRequest {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "antipinch".to_string(),
+            deep_sync: true,
+        };
+        let accepted = receiver
+            .force_synchronization(Request::new(request.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(accepted.error_code, ErrorCode::Success as i32);
+        assert!(accepted.transition_id.starts_with("sync-antipinch-"));
+        assert_eq!(rx_sync.recv().await, Some(request.clone()));
+
+        drop(rx_sync);
+        let unavailable = receiver
+            .force_synchronization(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            unavailable.error_code,
+            ErrorCode::ResourceUnavailable as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn test_report_action_result_queues_the_follow_up() {
+        common::etcd::use_in_memory_store();
+        let (tx_state_change, mut rx_state_change) = mpsc::channel::<StateChange>(1);
+        let receiver = StateManagerReceiver {
+            tx: mpsc::channel::<ContainerList>(1).0.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let missing = receiver
+            .report_action_result(Request::new(ActionResult::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::InvalidArgument);
+
+        crate::inflight::start(
+            "t-allowed",
+            ResourceType::Scenario,
+            "report-result",
+            &["execute_action_on_target_package".to_string()],
+        );
+        let response = receiver
+            .report_action_result(Request::new(ActionResult {
+                resource_type: ResourceType::Scenario as i32,
+                resource_name: "report-result".to_string(),
+                action: "launch".to_string(),
+                success: false,
+                message: "image pull failed".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.matched);
+        let change = rx_state_change.recv().await.unwrap();
+        assert_eq!(change.transition_id, response.transition_id);
+        assert_eq!(change.target_state, "denied");
+        assert_eq!(
+            change.denial.map(|d| d.rule),
+            Some(crate::inflight::RULE_ACTION_FAILED.to_string())
+        );
+
+        // A rejected action neither settles nor denies the transition
+        crate::inflight::start(
+            "t-rejected",
+            ResourceType::Scenario,
+            "report-rejected",
+            &["execute_action_on_target_package".to_string()],
+        );
+        let response = receiver
+            .report_action_result(Request::new(ActionResult {
+                resource_type: ResourceType::Scenario as i32,
+                resource_name: "report-rejected".to_string(),
+                action: "launch".to_string(),
+                success: false,
+                rejected: true,
+                message: "Operation in progress".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!response.matched);
+        assert!(response.transition_id.is_empty());
+        assert!(rx_state_change.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_redrive_dead_letters() {
+        common::etcd::use_in_memory_store();
+        let (tx_state_change, mut rx_state_change) = mpsc::channel::<StateChange>(1);
+        let receiver = StateManagerReceiver {
+            tx: mpsc::channel::<ContainerList>(1).0.into(),
+            tx_state_change: tx_state_change.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: mpsc::channel::<SimulationJob>(1).0.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+        let refused = crate::types::TransitionResult {
+            new_state: 0,
+            error_code: ErrorCode::InvalidStateTransition,
+            message: "No valid transition".to_string(),
+            actions_to_execute: Vec::new(),
+            transition_id: String::new(),
+            error_details: String::new(),
+        };
+        for transition_id in ["receiver-dlq-1", "receiver-dlq-2"] {
+            let change = StateChange {
+                resource_type: ResourceType::Package as i32,
+                resource_name: "receiver-dlq".to_string(),
+                transition_id: transition_id.to_string(),
+                ..Default::default()
+            };
+            crate::dlq::record(&change, &refused).await;
+        }
+
+        let listed = receiver
+            .list_dead_letters(Request::new(ListDeadLettersRequest {
+                resource_type: ResourceType::Package as i32,
+                resource_name: "receiver-dlq".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(listed.dead_letters.len(), 2);
+
+        let redriven = receiver
+            .redrive_dead_letters(Request::new(RedriveDeadLettersRequest {
+                transition_ids: vec!["receiver-dlq-1".to_string(), "missing".to_string()],
+                discard: false,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(redriven.redriven, vec!["receiver-dlq-1"]);
+        assert_eq!(redriven.not_found, vec!["missing"]);
+        let requeued = rx_state_change.recv().await.unwrap();
+        assert_eq!(requeued.transition_id, "receiver-dlq-1");
+
+        let discarded = receiver
+            .redrive_dead_letters(Request::new(RedriveDeadLettersRequest {
+                transition_ids: vec!["receiver-dlq-2".to_string()],
+                discard: true,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(discarded.redriven, vec!["receiver-dlq-2"]);
+        assert!(crate::dlq::get("receiver-dlq-2").await.unwrap().is_none());
+        // A re-driven change stays in the queue until it succeeds
+        assert!(crate::dlq::get("receiver-dlq-1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_simulate_state_changes_fills_defaults_and_returns_transitions() {
+        common::etcd::use_in_memory_store();
+        let (tx_simulation, mut rx_simulation) = mpsc::channel::<SimulationJob>(1);
+        let receiver = StateManagerReceiver {
+            tx: mpsc::channel::<ContainerList>(1).0.into(),
+            tx_state_change: mpsc::channel::<StateChange>(1).0.into(),
+            tx_container_update: mpsc::channel::<UpdateContainerStateRequest>(1).0.into(),
+            tx_sync: mpsc::channel::<ForceSynchronizationRequest>(1).0.into(),
+            tx_simulation: tx_simulation.into(),
+            vehicle_mode: VehicleModeStore::new(),
+            dedup: TransitionDedup::default(),
+        };
+
+        let engine = tokio::spawn(async move {
+            let job = rx_simulation.recv().await.unwrap();
+            assert_eq!(job.changes.len(), 1);
+            let change = &job.changes[0];
+            assert!(change.transition_id.starts_with("simulation-"));
+            assert_eq!(change.source, "simulation");
+            assert!(change.timestamp_ns > 0);
+            let transition = common::statemanager::SimulatedTransition {
+                resource_name: change.resource_name.clone(),
+                ..Default::default()
+            };
+            job.reply.send(vec![transition]).unwrap();
+        });
+
+        let request = SimulationRequest {
+            changes: vec![StateChange {
+                resource_type: ResourceType::Scenario as i32,
+                resource_name: "antipinch".to_string(),
+                current_state: "idle".to_string(),
+                target_state: "waiting".to_string(),
+                ..Default::default()
+            }],
+        };
+        let response = receiver
+            .simulate_state_changes(Request::new(request.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        engine.await.unwrap();
+        assert_eq!(response.transitions.len(), 1);
+        assert_eq!(response.transitions[0].resource_name, "antipinch");
+
+        // The engine is gone, nothing can answer the simulation
+        let status = receiver
+            .simulate_state_changes(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+    }
 }
 
 // ========================================
@@ -633,11 +2107,6 @@ mod tests {
 //   * Performance constraint enforcement and timing validation
 //   * Emergency override capabilities for safety-critical scenarios
 //
-// - force_synchronization(ForceSynchronizationRequest) -> StateChangeResponse
-//   * Reconcile state drift between desired and actual states
-//   * Deep synchronization with dependency cascade updates
-//   * Health check integration and validation
-//
 // RECOVERY MANAGEMENT API:
 // - trigger_recovery(TriggerRecoveryRequest) -> RecoveryResponse
 //   * Initiate recovery procedures with strategy selection
@@ -655,16 +2124,6 @@ mod tests {
 //   * Failure analysis and retry strategy reporting
 //
 // EVENT AND NOTIFICATION API:
-// - type SubscribeToStateChangesStream = ReceiverStream<Result<StateChangeEvent, Status>>
-//   * Real-time event streaming with filtering capabilities
-//   * Subscription management and event routing
-//   * Health and recovery event integration
-//
-// - subscribe_to_state_changes(StateChangeSubscriptionRequest) -> SubscribeToStateChangesStream
-//   * Event subscription with comprehensive filtering options
-//   * Resource type and severity level filtering
-//   * Metadata-based event routing and delivery
-//
 // - acknowledge_alert(AcknowledgeAlertRequest) -> AlertResponse
 //   * Alert lifecycle management and acknowledgment tracking
 //   * Escalation prevention and status updates
@@ -3,13 +3,70 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use async_trait::async_trait;
 use common::actioncontroller::{
     action_controller_connection_client::ActionControllerConnectionClient, connect_server,
-    ReconcileRequest, ReconcileResponse,
+    ReconcileRequest, ReconcileResponse, TriggerActionRequest, TriggerActionResponse,
 };
 use std::env;
+use std::sync::OnceLock;
 use tonic::{Request, Response, Status};
 
+/// Requests of the StateManager to the ActionController
+#[async_trait]
+pub trait ActionControllerSender: Send + Sync {
+    async fn reconcile(
+        &self,
+        request: ReconcileRequest,
+    ) -> Result<Response<ReconcileResponse>, Status>;
+    async fn trigger_action(
+        &self,
+        scenario_name: String,
+    ) -> Result<Response<TriggerActionResponse>, Status>;
+}
+
+/// The ActionController of settings.yaml, over gRPC
+pub struct GrpcSender;
+
+#[async_trait]
+impl ActionControllerSender for GrpcSender {
+    async fn reconcile(
+        &self,
+        request: ReconcileRequest,
+    ) -> Result<Response<ReconcileResponse>, Status> {
+        _send(request).await
+    }
+
+    async fn trigger_action(
+        &self,
+        scenario_name: String,
+    ) -> Result<Response<TriggerActionResponse>, Status> {
+        trigger_action(scenario_name).await
+    }
+}
+
+/// Sender used by the manager and the scheduler
+pub fn action_controller() -> &'static dyn ActionControllerSender {
+    static SENDER: OnceLock<Box<dyn ActionControllerSender>> = OnceLock::new();
+    SENDER
+        .get_or_init(|| instrument(Box::new(GrpcSender)))
+        .as_ref()
+}
+
+/// Route the requests through the active fault script
+#[cfg(any(test, feature = "fault-injection"))]
+fn instrument(sender: Box<dyn ActionControllerSender>) -> Box<dyn ActionControllerSender> {
+    Box::new(crate::fault::FaultySender::new(
+        sender,
+        crate::fault::script().clone(),
+    ))
+}
+
+#[cfg(not(any(test, feature = "fault-injection")))]
+fn instrument(sender: Box<dyn ActionControllerSender>) -> Box<dyn ActionControllerSender> {
+    sender
+}
+
 pub async fn _send(condition: ReconcileRequest) -> Result<Response<ReconcileResponse>, Status> {
     // Test mode bypass: return a fake successful response when env var is set
     if env::var("PULLPIRI_TEST_MODE").is_ok() {
@@ -19,10 +76,41 @@ pub async fn _send(condition: ReconcileRequest) -> Result<Response<ReconcileResp
         };
         return Ok(Response::new(resp));
     }
-    let mut client = ActionControllerConnectionClient::connect(connect_server())
+    // Reconcile requests carry the full desired state, so repeating one is harmless
+    let condition = &condition;
+    common::grpc::retry::call(&connect_server(), |channel| async move {
+        ActionControllerConnectionClient::new(channel)
+            .reconcile(Request::new(condition.clone()))
+            .await
+    })
+    .await
+}
+
+/// Ask the ActionController to run the action of a scenario
+///
+/// Sent once, an action must not run twice because of a retry.
+pub async fn trigger_action(
+    scenario_name: String,
+) -> Result<Response<TriggerActionResponse>, Status> {
+    if env::var("PULLPIRI_TEST_MODE").is_ok() {
+        let resp = TriggerActionResponse {
+            status: 0,
+            desc: "mock".to_string(),
+        };
+        return Ok(Response::new(resp));
+    }
+    let addr = connect_server();
+    let channel = common::grpc::channel(&addr).await?;
+    match ActionControllerConnectionClient::new(channel)
+        .trigger_action(Request::new(TriggerActionRequest {
+            scenario_name,
+            correlation_id: common::correlation::current_or_empty(),
+        }))
         .await
-        .unwrap();
-    client.reconcile(Request::new(condition)).await
+    {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
 }
 
 #[cfg(test)]
@@ -38,6 +126,7 @@ mod tests {
             scenario_name: "s1".to_string(),
             current: 0,
             desired: 0,
+            ..Default::default()
         };
 
         let res = _send(req).await;
@@ -0,0 +1,200 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Health of each resource tracked by the state machine
+//!
+//! The state machine counts the failed transitions of a resource and marks
+//! it unhealthy after several in a row, before it ever reaches an error
+//! state. The manager publishes that health here after every transition it
+//! processes, so that it travels with the transition events, raises an
+//! alert when a resource turns unhealthy, and is served as the
+//! `piccolo_resource_healthy` and `piccolo_resource_consecutive_failures`
+//! gauges on `/metrics` of the health endpoints.
+
+use crate::types::HealthStatus;
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use common::logd;
+use common::statemanager::{ResourceHealth, ResourceType};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// Health by lowercase resource type and name
+type Registry = BTreeMap<(String, String), ResourceHealth>;
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut registry)
+}
+
+fn key(resource_type: ResourceType, resource_name: &str) -> (String, String) {
+    (
+        crate::notifier::type_name(resource_type).to_string(),
+        resource_name.to_string(),
+    )
+}
+
+/// Published health of a resource, `None` until the state machine tracks it
+pub fn of(resource_type: ResourceType, resource_name: &str) -> Option<ResourceHealth> {
+    with_registry(|registry| registry.get(&key(resource_type, resource_name)).cloned())
+}
+
+/// Publish the health of a resource, alerting when it turns unhealthy
+///
+/// ### Returns
+/// * `true` - the resource was healthy or unknown and is unhealthy now
+pub fn update(resource_type: ResourceType, resource_name: &str, status: &HealthStatus) -> bool {
+    let health = ResourceHealth {
+        healthy: status.healthy,
+        consecutive_failures: status.consecutive_failures,
+        status_message: status.status_message.clone(),
+    };
+    let was_healthy = with_registry(|registry| {
+        registry
+            .insert(key(resource_type, resource_name), health)
+            .is_none_or(|previous| previous.healthy)
+    });
+
+    let turned_unhealthy = was_healthy && !status.healthy;
+    if turned_unhealthy {
+        logd!(
+            5,
+            "ALERT: {:?} '{}' unhealthy after {} consecutive failure(s): {}",
+            resource_type,
+            resource_name,
+            status.consecutive_failures,
+            status.status_message
+        );
+        crate::events::alert(
+            resource_type,
+            resource_name,
+            format!(
+                "unhealthy after {} consecutive failure(s): {}",
+                status.consecutive_failures, status.status_message
+            ),
+        );
+    } else if !was_healthy && status.healthy {
+        logd!(
+            3,
+            "{:?} '{}' is healthy again",
+            resource_type,
+            resource_name
+        );
+    }
+    turned_unhealthy
+}
+
+/// Escape a Prometheus label value
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Gauges of every published resource in the Prometheus text format
+pub fn render() -> String {
+    let mut text = String::new();
+    with_registry(|registry| {
+        let _ = writeln!(
+            text,
+            "# HELP piccolo_resource_healthy Whether the resource is healthy (1) or not (0)"
+        );
+        let _ = writeln!(text, "# TYPE piccolo_resource_healthy gauge");
+        for ((resource_type, name), health) in registry.iter() {
+            let _ = writeln!(
+                text,
+                "piccolo_resource_healthy{{type=\"{}\",name=\"{}\"}} {}",
+                resource_type,
+                label(name),
+                u8::from(health.healthy)
+            );
+        }
+        let _ = writeln!(
+            text,
+            "# HELP piccolo_resource_consecutive_failures Failed transitions since the last success"
+        );
+        let _ = writeln!(text, "# TYPE piccolo_resource_consecutive_failures gauge");
+        for ((resource_type, name), health) in registry.iter() {
+            let _ = writeln!(
+                text,
+                "piccolo_resource_consecutive_failures{{type=\"{}\",name=\"{}\"}} {}",
+                resource_type,
+                label(name),
+                health.consecutive_failures
+            );
+        }
+    });
+    text
+}
+
+/// Route of `/metrics`, served next to the health endpoints
+///
+/// The counter of suppressed state writes is served with the gauges.
+pub fn router() -> Router {
+    Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                render() + &crate::coalesce::render(),
+            )
+                .into_response()
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(healthy: bool, consecutive_failures: u32) -> HealthStatus {
+        HealthStatus {
+            healthy,
+            status_message: if healthy { "Healthy" } else { "no transition" }.to_string(),
+            last_check_ns: common::clock::now_ns(),
+            consecutive_failures,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_alerts_once_when_turning_unhealthy() {
+        let name = "health-test/alerts";
+        assert!(!update(ResourceType::Model, name, &status(true, 0)));
+        assert!(!update(ResourceType::Model, name, &status(true, 1)));
+        assert!(update(ResourceType::Model, name, &status(false, 3)));
+        assert!(!update(ResourceType::Model, name, &status(false, 4)));
+        assert_eq!(
+            of(ResourceType::Model, name).map(|h| h.consecutive_failures),
+            Some(4)
+        );
+
+        assert!(!update(ResourceType::Model, name, &status(true, 0)));
+        assert!(update(ResourceType::Model, name, &status(false, 3)));
+        assert_eq!(of(ResourceType::Model, "health-test/unknown"), None);
+    }
+
+    #[tokio::test]
+    async fn test_render_gauges() {
+        update(
+            ResourceType::Package,
+            "health-test/\"render\"",
+            &status(false, 5),
+        );
+        let text = render();
+        assert!(text.contains("# TYPE piccolo_resource_healthy gauge"));
+        assert!(text.contains(
+            "piccolo_resource_healthy{type=\"package\",name=\"health-test/\\\"render\\\"\"} 0"
+        ));
+        assert!(text.contains(
+            "piccolo_resource_consecutive_failures{type=\"package\",name=\"health-test/\\\"render\\\"\"} 5"
+        ));
+    }
+}
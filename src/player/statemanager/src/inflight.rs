@@ -0,0 +1,239 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Actions in flight at the ActionController
+//!
+//! Some actions queued by the state machine are carried out by the
+//! ActionController, such as `execute_action_on_target_package` once a
+//! scenario is allowed. They are kept here by the transition that queued
+//! them until the ActionController reports their outcome with
+//! `ReportActionResult`, and the outcome drives the transition after them:
+//! a scenario whose action succeeded is completed, one whose action failed
+//! is denied with the [`RULE_ACTION_FAILED`] rule.
+//!
+//! A result names the transition when the ActionController knows it and
+//! otherwise settles the oldest action in flight of its resource. A result
+//! that arrives before its transition was processed settles the action as
+//! soon as it is queued.
+
+use common::statemanager::{ActionResult, DenialReason, ResourceType, StateChange};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// Actions the ActionController reports the outcome of
+pub const AWAITED: &[&str] = &["execute_action_on_target_package"];
+
+/// Rule of the denials of scenarios whose action failed
+pub const RULE_ACTION_FAILED: &str = "action-failed";
+
+/// Results kept for actions not queued yet
+const EARLY_CAPACITY: usize = 64;
+
+/// Action waiting for its outcome
+#[derive(Debug, Clone, PartialEq)]
+pub struct InFlight {
+    pub transition_id: String,
+    pub resource_type: ResourceType,
+    pub resource_name: String,
+    pub action: String,
+    pub since: Instant,
+}
+
+#[derive(Default)]
+struct Registry {
+    in_flight: Vec<InFlight>,
+    /// Resources with a result that matched no action yet
+    early: Vec<(ResourceType, String)>,
+}
+
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    let mut registry = REGISTRY
+        .get_or_init(|| Mutex::new(Registry::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    f(&mut registry)
+}
+
+/// Keep the awaited actions queued by a transition until their outcome
+pub fn start(
+    transition_id: &str,
+    resource_type: ResourceType,
+    resource_name: &str,
+    actions: &[String],
+) {
+    for action in actions {
+        if !AWAITED.contains(&action.as_str()) {
+            continue;
+        }
+        with_registry(|registry| {
+            let early = registry
+                .early
+                .iter()
+                .position(|(rt, name)| *rt == resource_type && name == resource_name);
+            match early {
+                // The outcome is known already
+                Some(index) => {
+                    registry.early.remove(index);
+                }
+                None => registry.in_flight.push(InFlight {
+                    transition_id: transition_id.to_string(),
+                    resource_type,
+                    resource_name: resource_name.to_string(),
+                    action: action.clone(),
+                    since: common::clock::instant(),
+                }),
+            }
+        });
+    }
+}
+
+/// Actions waiting for their outcome, oldest first
+pub fn in_flight() -> Vec<InFlight> {
+    with_registry(|registry| registry.in_flight.clone())
+}
+
+/// Settle the action a result reports on
+///
+/// ### Returns
+/// * `Some(InFlight)` - the action that was waiting for the result
+/// * `None` - no action was in flight, the result settles the next one
+pub fn finish(result: &ActionResult) -> Option<InFlight> {
+    let resource_type = ResourceType::try_from(result.resource_type).ok()?;
+    with_registry(|registry| {
+        let by_id = registry.in_flight.iter().position(|a| {
+            !result.transition_id.is_empty() && a.transition_id == result.transition_id
+        });
+        let index = by_id.or_else(|| {
+            registry.in_flight.iter().position(|a| {
+                a.resource_type == resource_type && a.resource_name == result.resource_name
+            })
+        });
+        match index {
+            Some(index) => Some(registry.in_flight.remove(index)),
+            None => {
+                if registry.early.len() >= EARLY_CAPACITY {
+                    registry.early.remove(0);
+                }
+                registry
+                    .early
+                    .push((resource_type, result.resource_name.clone()));
+                None
+            }
+        }
+    })
+}
+
+/// StateChange that follows the outcome of an action
+///
+/// The state machine moves the resource from the state it tracks, so the
+/// change is valid while the scenario is allowed or pending. Resources of
+/// other types have no transition after their actions yet.
+pub fn follow_up(result: &ActionResult, transition_id: String) -> Option<StateChange> {
+    if result.resource_type != ResourceType::Scenario as i32 {
+        return None;
+    }
+    let (target_state, denial) = if result.success {
+        ("completed", None)
+    } else {
+        (
+            "denied",
+            Some(DenialReason {
+                rule: RULE_ACTION_FAILED.to_string(),
+                message: if result.message.is_empty() {
+                    format!("action '{}' failed", result.action)
+                } else {
+                    result.message.clone()
+                },
+                source: "actioncontroller".to_string(),
+                timestamp_ns: result.timestamp_ns,
+            }),
+        )
+    };
+    Some(StateChange {
+        resource_type: result.resource_type,
+        resource_name: result.resource_name.clone(),
+        current_state: "allowed".to_string(),
+        target_state: target_state.to_string(),
+        transition_id,
+        timestamp_ns: result.timestamp_ns,
+        source: "actioncontroller".to_string(),
+        correlation_id: common::correlation::current_or_empty(),
+        sub_state: String::new(),
+        denial,
+        metadata: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(transition_id: &str, name: &str, success: bool) -> ActionResult {
+        ActionResult {
+            transition_id: transition_id.to_string(),
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: name.to_string(),
+            action: "launch".to_string(),
+            success,
+            message: String::new(),
+            timestamp_ns: 7,
+            rejected: false,
+        }
+    }
+
+    fn awaited() -> Vec<String> {
+        vec![
+            "execute_action_on_target_package".to_string(),
+            "finalize_scenario".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_finish_by_transition_then_by_resource() {
+        start("t-1", ResourceType::Scenario, "inflight-a", &awaited());
+        start("t-2", ResourceType::Scenario, "inflight-a", &awaited());
+        start(
+            "t-3",
+            ResourceType::Scenario,
+            "inflight-b",
+            &["finalize_scenario".to_string()],
+        );
+
+        let settled = finish(&result("t-2", "inflight-a", true)).unwrap();
+        assert_eq!(settled.transition_id, "t-2");
+        let settled = finish(&result("", "inflight-a", true)).unwrap();
+        assert_eq!(settled.transition_id, "t-1");
+        assert!(finish(&result("t-3", "inflight-b", true)).is_none());
+    }
+
+    #[test]
+    fn test_early_result_settles_the_next_action() {
+        assert!(finish(&result("", "inflight-early", false)).is_none());
+        start("t-4", ResourceType::Scenario, "inflight-early", &awaited());
+        // Nothing is left in flight for the next result
+        assert!(finish(&result("t-4", "inflight-early", true)).is_none());
+    }
+
+    #[test]
+    fn test_follow_up_completes_or_denies_scenarios() {
+        let change = follow_up(&result("t-5", "s", true), "r-1".to_string()).unwrap();
+        assert_eq!(change.target_state, "completed");
+        assert_eq!(change.transition_id, "r-1");
+        assert!(change.denial.is_none());
+
+        let mut failed = result("t-5", "s", false);
+        failed.message = "image pull failed".to_string();
+        let change = follow_up(&failed, "r-2".to_string()).unwrap();
+        assert_eq!(change.target_state, "denied");
+        let denial = change.denial.unwrap();
+        assert_eq!(denial.rule, RULE_ACTION_FAILED);
+        assert_eq!(denial.message, "image pull failed");
+
+        let mut package = result("t-6", "p", true);
+        package.resource_type = ResourceType::Package as i32;
+        assert!(follow_up(&package, "r-3".to_string()).is_none());
+    }
+}
@@ -0,0 +1,320 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Leader election among StateManager instances sharing one etcd
+//!
+//! With `statemanager.leader_election` enabled, instances compete for a lease
+//! stored at [`LEADER_KEY`]. The holder of an unexpired lease is the leader:
+//! it alone processes the request channels and writes states. The other
+//! instances stand by and only answer read-only queries, see
+//! [`require_leader`].
+//!
+//! The leader renews its lease every third of `statemanager.lease_ttl_secs`
+//! and steps down as soon as it cannot. Each round of the election is cut
+//! off after a third of the TTL, so that a leader whose renewal does not
+//! succeed has stopped before the lease expires for the standbys. A standby
+//! claims an expired lease. Claims and renewals are compare-and-swap writes
+//! against the lease that was read, so that of two instances writing at the
+//! same time only one gets the lease.
+//!
+//! The lease is read and written straight through `common::etcd`: a lease
+//! renewal must never wait in the write buffer of [`crate::store`].
+//!
+//! Without election every instance is the leader, as before.
+
+use common::logd;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::watch;
+use tonic::Status;
+
+/// Key of the lease of the leader
+pub const LEADER_KEY: &str = "/statemanager/leader";
+
+/// Error of etcd for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Lease of the leader as stored in etcd
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Instance holding the lease
+    pub holder: String,
+    /// End of the lease, in milliseconds since the epoch
+    pub expires_ms: i64,
+}
+
+/// What an instance does after reading the lease
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// Write the lease, to take it or to renew it
+    Write,
+    /// Another instance holds the lease
+    Wait,
+}
+
+/// Decide whether `instance` writes the lease at `now_ms`
+pub fn claim(lease: Option<&Lease>, instance: &str, now_ms: i64) -> Claim {
+    match lease {
+        Some(lease) if lease.holder != instance && lease.expires_ms > now_ms => Claim::Wait,
+        _ => Claim::Write,
+    }
+}
+
+/// Leadership of this instance and the last known leader
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Leadership {
+    leader: bool,
+    holder: Option<String>,
+}
+
+static LEADERSHIP: OnceLock<watch::Sender<Leadership>> = OnceLock::new();
+
+fn leadership() -> &'static watch::Sender<Leadership> {
+    LEADERSHIP.get_or_init(|| {
+        let enabled = common::setting::get_config().statemanager.leader_election;
+        watch::Sender::new(Leadership {
+            leader: !enabled,
+            holder: None,
+        })
+    })
+}
+
+fn set_leadership(leader: bool, holder: Option<String>) {
+    leadership().send_if_modified(|current| {
+        let next = Leadership { leader, holder };
+        if *current == next {
+            return false;
+        }
+        *current = next;
+        true
+    });
+}
+
+/// Whether this instance processes requests and writes states
+pub fn is_leader() -> bool {
+    leadership().borrow().leader
+}
+
+/// Wait until this instance is the leader
+pub async fn elected() {
+    let mut receiver = leadership().subscribe();
+    let _ = receiver.wait_for(|leadership| leadership.leader).await;
+}
+
+/// Wait until this instance is no longer the leader
+pub async fn lost() {
+    let mut receiver = leadership().subscribe();
+    let _ = receiver.wait_for(|leadership| !leadership.leader).await;
+}
+
+/// Refuse a request that only the leader may serve
+///
+/// The status is `unavailable` so that the retrying clients try again,
+/// possibly on the new leader after a failover.
+#[allow(clippy::result_large_err)]
+pub fn require_leader(rpc: &str) -> Result<(), Status> {
+    check(&leadership().borrow(), rpc)
+}
+
+#[allow(clippy::result_large_err)]
+fn check(leadership: &Leadership, rpc: &str) -> Result<(), Status> {
+    if leadership.leader {
+        return Ok(());
+    }
+    Err(Status::unavailable(format!(
+        "{} is served by the leader StateManager{}",
+        rpc,
+        leadership
+            .holder
+            .as_ref()
+            .map(|holder| format!(" {}", holder))
+            .unwrap_or_default()
+    )))
+}
+
+/// Name of this instance in the election
+pub fn instance_id() -> String {
+    let configured = &common::setting::get_config().statemanager.instance_id;
+    if !configured.is_empty() {
+        return configured.clone();
+    }
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "statemanager".to_string());
+    format!("{}-{}", host, std::process::id())
+}
+
+/// The lease as stored and parsed, `None` if no lease is stored
+async fn read_lease() -> Result<Option<(String, Lease)>, String> {
+    match common::etcd::get(LEADER_KEY).await {
+        Ok(value) => serde_json::from_str(&value)
+            .map(|lease| Some((value, lease)))
+            .map_err(|e| format!("Invalid leader lease: {}", e)),
+        Err(e) if e == NOT_FOUND => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// One round of the election, returns the holder of the lease afterwards
+///
+/// An error means the lease could not be read or written.
+pub async fn campaign(instance: &str, ttl: Duration) -> Result<Option<String>, String> {
+    let stored = read_lease().await?;
+    let lease = stored.as_ref().map(|(_, lease)| lease);
+    let now_ms = common::clock::now_ms();
+    if claim(lease, instance, now_ms) == Claim::Wait {
+        return Ok(lease.map(|lease| lease.holder.clone()));
+    }
+
+    let claimed = Lease {
+        holder: instance.to_string(),
+        expires_ms: now_ms + ttl.as_millis() as i64,
+    };
+    let value = serde_json::to_string(&claimed).map_err(|e| e.to_string())?;
+    let expected = stored.as_ref().map(|(value, _)| value.as_str());
+    if common::etcd::compare_and_swap(LEADER_KEY, expected, &value).await? {
+        return Ok(Some(claimed.holder));
+    }
+
+    // Another instance wrote the lease since it was read
+    Ok(read_lease().await?.map(|(_, lease)| lease.holder))
+}
+
+/// Take part in the election until the process ends
+///
+/// Does nothing when `statemanager.leader_election` is disabled.
+pub async fn run_elector() {
+    let settings = &common::setting::get_config().statemanager;
+    if !settings.leader_election {
+        return;
+    }
+    let instance = instance_id();
+    let ttl = Duration::from_secs(settings.lease_ttl_secs.max(1));
+    logd!(
+        3,
+        "Leader election of {} with a lease of {:?}",
+        instance,
+        ttl
+    );
+
+    let round = ttl / 3;
+    let mut rounds = tokio::time::interval(round);
+    rounds.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        rounds.tick().await;
+        let result = match tokio::time::timeout(round, campaign(&instance, ttl)).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("no answer within {:?}", round)),
+        };
+        match result {
+            Ok(holder) => {
+                let leader = holder.as_deref() == Some(instance.as_str());
+                if leader != is_leader() {
+                    if leader {
+                        logd!(4, "{} is now the leader StateManager", instance);
+                    } else {
+                        logd!(
+                            4,
+                            "{} stands by, the leader is {}",
+                            instance,
+                            holder.as_deref().unwrap_or("unknown")
+                        );
+                    }
+                }
+                set_leadership(leader, holder);
+            }
+            Err(e) => {
+                // The lease may expire for the others before it can be renewed
+                if is_leader() {
+                    logd!(
+                        5,
+                        "{} steps down, its lease cannot be renewed: {}",
+                        instance,
+                        e
+                    );
+                }
+                set_leadership(false, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim() {
+        let lease = Lease {
+            holder: "sm-a".to_string(),
+            expires_ms: 1000,
+        };
+        assert_eq!(claim(None, "sm-b", 0), Claim::Write);
+        assert_eq!(claim(Some(&lease), "sm-b", 500), Claim::Wait);
+        // Expired lease
+        assert_eq!(claim(Some(&lease), "sm-b", 1000), Claim::Write);
+        // Renewal
+        assert_eq!(claim(Some(&lease), "sm-a", 500), Claim::Write);
+    }
+
+    #[tokio::test]
+    async fn test_campaign_takes_over_an_expired_lease() {
+        common::etcd::use_in_memory_store();
+        let ttl = Duration::from_secs(10);
+        assert_eq!(
+            campaign("sm-a", ttl).await.unwrap().as_deref(),
+            Some("sm-a")
+        );
+        // The lease of sm-a holds
+        assert_eq!(
+            campaign("sm-b", ttl).await.unwrap().as_deref(),
+            Some("sm-a")
+        );
+        assert_eq!(
+            campaign("sm-a", ttl).await.unwrap().as_deref(),
+            Some("sm-a")
+        );
+
+        let expired = Lease {
+            holder: "sm-a".to_string(),
+            expires_ms: chrono::Utc::now().timestamp_millis() - 1,
+        };
+        common::etcd::put(LEADER_KEY, &serde_json::to_string(&expired).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            campaign("sm-b", ttl).await.unwrap().as_deref(),
+            Some("sm-b")
+        );
+
+        // Of two standbys claiming the expired lease at once, one gets it
+        let expired = Lease {
+            holder: "sm-b".to_string(),
+            expires_ms: chrono::Utc::now().timestamp_millis() - 1,
+        };
+        common::etcd::put(LEADER_KEY, &serde_json::to_string(&expired).unwrap())
+            .await
+            .unwrap();
+        let (c, d) = tokio::join!(campaign("sm-c", ttl), campaign("sm-d", ttl));
+        let (c, d) = (c.unwrap(), d.unwrap());
+        assert_eq!(c, d);
+        assert!(matches!(c.as_deref(), Some("sm-c") | Some("sm-d")));
+    }
+
+    #[test]
+    fn test_standby_refuses_writes() {
+        let standby = Leadership {
+            leader: false,
+            holder: Some("sm-a".to_string()),
+        };
+        let status = check(&standby, "SendStateChange").unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert!(status.message().contains("sm-a"));
+        let leader = Leadership {
+            leader: true,
+            holder: Some("sm-b".to_string()),
+        };
+        assert!(check(&leader, "SendStateChange").is_ok());
+    }
+}
@@ -15,17 +15,64 @@
 use common::logd;
 use common::logd::logger;
 use common::monitoringserver::ContainerList;
+use common::startup::Dependency;
 use common::statemanager::{
-    state_manager_connection_server::StateManagerConnectionServer, StateChange,
+    state_manager_connection_server::StateManagerConnectionServer, ForceSynchronizationRequest,
+    StateChange, UpdateContainerStateRequest,
 };
+use queue::{BoundedSender, OverflowPolicy};
 use std::env;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::Mutex;
 use tonic::transport::Server;
+use types::SimulationJob;
 
+pub mod cascade;
+pub mod coalesce;
+#[cfg(test)]
+mod conformance;
+pub mod container_cache;
+pub mod crashloop;
+pub mod dedup;
+pub mod denial;
+pub mod dlq;
+pub mod drift;
+pub mod events;
+pub mod exclusion;
+pub mod exporter;
+pub mod failure;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault;
 pub mod grpc;
+pub mod health;
+pub mod inflight;
+pub mod leader;
 pub mod manager;
+pub mod notifier;
+pub mod pending;
+pub mod queue;
+pub mod ratelimit;
+pub mod replay;
+pub mod rollup;
+pub mod safety_log;
+pub mod scheduler;
 pub mod state_machine;
+pub mod storage;
+pub mod store;
+pub mod timing;
+pub mod transitions;
 pub mod types;
+pub mod vehicle_mode;
+
+/// Buffered ForceSynchronization requests, each one checks every deployed scenario
+const SYNC_BUFFER: usize = 10;
+
+/// Buffered simulations, each one copies the state machine
+const SIMULATION_BUFFER: usize = 10;
+
+/// Interval between channel statistics reports
+const QUEUE_STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 
 /// Launches the StateManagerManager in an asynchronous task.
 ///
@@ -36,6 +83,9 @@ pub mod types;
 /// # Arguments
 /// * `rx_container` - Channel receiver for ContainerList messages from nodeagent
 /// * `rx_state_change` - Channel receiver for StateChange messages from various components
+/// * `rx_container_update` - Channel receiver for single container state updates
+/// * `rx_sync` - Channel receiver for ForceSynchronization requests
+/// * `rx_simulation` - Channel receiver for state changes to simulate
 ///
 /// # Processing Flow
 /// 1. Create StateManagerManager instance with provided channels
@@ -48,8 +98,11 @@ pub mod types;
 /// - Continues operation even if some initialization steps fail
 /// - Provides comprehensive error reporting for debugging
 async fn launch_manager(
-    rx_container: Receiver<ContainerList>,
-    rx_state_change: Receiver<StateChange>,
+    rx_container: Arc<Mutex<Receiver<ContainerList>>>,
+    rx_state_change: Arc<Mutex<Receiver<StateChange>>>,
+    rx_container_update: Arc<Mutex<Receiver<UpdateContainerStateRequest>>>,
+    rx_sync: Arc<Mutex<Receiver<ForceSynchronizationRequest>>>,
+    rx_simulation: Arc<Mutex<Receiver<SimulationJob>>>,
 ) {
     // In test mode we short-circuit heavy startup to keep unit tests fast
     // In test builds or when `PULLPIRI_TEST_MODE` is set we short-circuit heavy startup
@@ -59,37 +112,56 @@ async fn launch_manager(
     }
     logd!(3, "=== StateManagerManager Starting ===");
 
-    // Create the StateManager engine with async channel receivers
-    let mut manager = manager::StateManagerManager::new(rx_container, rx_state_change).await;
+    // A standby waits for the leadership, the manager of a former leader is
+    // dropped and a new one reloads the states once it is elected again
+    loop {
+        leader::elected().await;
 
-    // Initialize the manager with configuration and persistent state
-    match manager.initialize().await {
-        Ok(_) => {
-            logd!(
-                3,
-                "StateManagerManager initialization completed successfully"
-            );
+        // Create the StateManager engine with async channel receivers
+        let mut manager = manager::StateManagerManager::with_shared_receivers(
+            Arc::clone(&rx_container),
+            Arc::clone(&rx_state_change),
+        )
+        .with_container_updates(Arc::clone(&rx_container_update))
+        .with_sync_requests(Arc::clone(&rx_sync))
+        .with_simulation_requests(Arc::clone(&rx_simulation));
 
-            // Run the main processing loop
-            logd!(3, "Starting StateManagerManager main processing loop...");
-            if let Err(e) = manager.run().await {
-                logd!(5, "StateManagerManager stopped with error: {e:?}");
+        // Initialize the manager with configuration and persistent state
+        match manager.initialize().await {
+            Ok(_) => {
+                logd!(
+                    3,
+                    "StateManagerManager initialization completed successfully"
+                );
+                common::health::set_started();
+
+                // Run the main processing loop
+                logd!(3, "Starting StateManagerManager main processing loop...");
+                match manager.run().await {
+                    Err(e) => {
+                        logd!(5, "StateManagerManager stopped with error: {e:?}");
+                        logd!(
+                            5,
+                            "This may indicate a critical system failure or shutdown request"
+                        );
+                    }
+                    Ok(_) if !leader::is_leader() => {
+                        logd!(4, "StateManagerManager stopped, leadership lost");
+                        continue;
+                    }
+                    Ok(_) => logd!(4, "StateManagerManager stopped gracefully"),
+                }
+            }
+            Err(e) => {
+                logd!(5, "Failed to initialize StateManagerManager: {e:?}");
                 logd!(
                     5,
-                    "This may indicate a critical system failure or shutdown request"
+                    "StateManager service cannot start - check configuration and dependencies"
                 );
-            } else {
-                logd!(4, "StateManagerManager stopped gracefully");
+                // Don't panic - allow graceful shutdown of other components
             }
         }
-        Err(e) => {
-            logd!(5, "Failed to initialize StateManagerManager: {e:?}");
-            logd!(
-                5,
-                "StateManager service cannot start - check configuration and dependencies"
-            );
-            // Don't panic - allow graceful shutdown of other components
-        }
+        break;
     }
 
     logd!(4, "=== StateManagerManager Stopped ===");
@@ -104,6 +176,9 @@ async fn launch_manager(
 /// # Arguments
 /// * `tx_container` - Channel sender for ContainerList messages to StateManager engine
 /// * `tx_state_change` - Channel sender for StateChange messages to StateManager engine
+/// * `tx_container_update` - Channel sender for single container state updates
+/// * `tx_sync` - Channel sender for ForceSynchronization requests
+/// * `tx_simulation` - Channel sender for state changes to simulate
 ///
 /// # Server Configuration
 /// - Binds to address specified in common::statemanager::open_server()
@@ -117,8 +192,11 @@ async fn launch_manager(
 /// - Logs server startup and shutdown events
 /// - Provides comprehensive error reporting for network issues
 async fn initialize_grpc_server(
-    tx_container: Sender<ContainerList>,
-    tx_state_change: Sender<StateChange>,
+    tx_container: BoundedSender<ContainerList>,
+    tx_state_change: BoundedSender<StateChange>,
+    tx_container_update: BoundedSender<UpdateContainerStateRequest>,
+    tx_sync: BoundedSender<ForceSynchronizationRequest>,
+    tx_simulation: BoundedSender<SimulationJob>,
 ) {
     // Allow tests to opt-out of starting the actual gRPC server
     // Skip starting the real gRPC server when running tests or explicitly requested
@@ -129,12 +207,41 @@ async fn initialize_grpc_server(
     logd!(3, "=== StateManager gRPC Server Starting ===");
 
     // Create the gRPC service handler with async channels
+    let vehicle_mode = vehicle_mode::VehicleModeStore::new();
+    vehicle_mode.restore().await;
     let server = grpc::receiver::StateManagerReceiver {
         tx: tx_container,
         tx_state_change,
+        tx_container_update,
+        tx_sync,
+        tx_simulation,
+        vehicle_mode,
+        dedup: dedup::TransitionDedup::default(),
     };
     logd!(3, "StateManagerReceiver instance created successfully");
 
+    // Periodically report channel depth, overflow counters and circuit breakers
+    let stats_source = server.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(QUEUE_STATS_INTERVAL);
+        loop {
+            interval.tick().await;
+            let (container, state_change, container_update) = stats_source.queue_stats();
+            logd!(2, "ContainerList queue: {container:?}");
+            logd!(2, "StateChange queue: {state_change:?}");
+            logd!(2, "Container update queue: {container_update:?}");
+            logd!(2, "Etcd write buffer: {:?}", store::stats());
+            logd!(2, "Drift detector: {:?}", drift::stats());
+            logd!(2, "Event hooks: {:?}", notifier::stats());
+            logd!(2, "Telemetry export: {:?}", exporter::stats());
+            logd!(2, "Timing budgets: {:?}", timing::stats());
+            logd!(2, "Rate limiter: {:?}", ratelimit::stats());
+            for breaker in common::grpc::retry::breaker_stats() {
+                logd!(2, "Circuit breaker: {breaker:?}");
+            }
+        }
+    });
+
     // Parse the server address from configuration
     let addr = match common::statemanager::open_server().parse() {
         Ok(addr) => {
@@ -153,8 +260,19 @@ async fn initialize_grpc_server(
 
     // Start the gRPC server with comprehensive error handling
     logd!(3, "Starting StateManager gRPC server...");
-    match Server::builder()
-        .add_service(StateManagerConnectionServer::new(server))
+    let mut builder = match common::tls::server() {
+        Ok(builder) => builder,
+        Err(e) => {
+            logd!(5, "StateManager gRPC server not started: {e}");
+            return;
+        }
+    };
+    match builder
+        .add_service(StateManagerConnectionServer::with_interceptor(
+            server,
+            common::auth::authenticate,
+        ))
+        .add_service(common::health::grpc_service())
         .serve(addr)
         .await
     {
@@ -225,6 +343,17 @@ async fn initialize_timpani_server() {
     logd!(4, "=== Timpani gRPC Server Stopped ===");
 }
 
+/// Parses a configured overflow policy, falling back to `default` when invalid.
+fn overflow_policy(value: &str, default: OverflowPolicy) -> OverflowPolicy {
+    value.parse().unwrap_or_else(|e| {
+        logd!(
+            4,
+            "Invalid overflow policy in settings ({e}), using {default:?}"
+        );
+        default
+    })
+}
+
 /// Main entry point for the StateManager service.
 ///
 /// This function orchestrates the complete StateManager service startup:
@@ -240,8 +369,12 @@ async fn initialize_timpani_server() {
 /// - Supports graceful termination handling
 ///
 /// # Channel Configuration
-/// - ContainerList channel: 100 message buffer for nodeagent communication
-/// - StateChange channel: 100 message buffer for component communication
+/// - ContainerList channel: 100 message buffer, drops the oldest snapshot when full
+/// - StateChange channel: 100 message buffer, rejects new requests when full
+/// - Container update channel: 100 message buffer, rejects new updates when full
+/// - ForceSynchronization channel: 10 request buffer, rejects new requests when full
+/// - Simulation channel: 10 request buffer, rejects new requests when full
+/// - Sizes and policies can be changed in the statemanager section of settings.yaml
 /// - Async processing prevents blocking between message types
 ///
 /// # Error Handling
@@ -251,18 +384,95 @@ async fn initialize_timpani_server() {
 #[tokio::main]
 async fn main() {
     let _ = logger::init_async_logger("statemanager").await;
+
+    // Replay a recording instead of serving, see the replay module
+    let args: Vec<String> = env::args().collect();
+    if let Some(options) = replay::ReplayOptions::from_args(&args) {
+        if let Err(e) = match options {
+            Ok(options) => replay::run(&options).await,
+            Err(e) => Err(e),
+        } {
+            eprintln!("statemanager: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     logd!(1, "initiailize statemanager...");
+    common::health::init("statemanager", true);
+    common::address::validate_or_exit("statemanager");
+    tokio::spawn(common::health::serve_with(
+        common::statemanager::open_health_server(),
+        health::router(),
+    ));
+    // Artifacts are read from etcd, open no listener before it answers
+    if let Err(e) = common::startup::wait("statemanager", &[Dependency::Etcd]).await {
+        logd!(6, "{e}");
+        std::process::exit(1);
+    }
+    // Keep state writes while etcd is briefly unavailable and write them back
+    tokio::spawn(common::setting::watch());
+    tokio::spawn(leader::run_elector());
+    tokio::spawn(store::run_flusher());
+    tokio::spawn(notifier::run_dispatcher());
+    tokio::spawn(exporter::run_exporter());
+    tokio::spawn(safety_log::run_writer());
+    tokio::spawn(dedup::run_cleanup());
+    let settings = &common::setting::get_config().statemanager;
+    if !settings.record_path.is_empty() {
+        if let Err(e) = replay::start_recording(&settings.record_path).await {
+            logd!(5, "{e}");
+        }
+    }
 
-    // Create async channels for communication between gRPC server and processing engine
-    // Buffer size of 100 provides good throughput while preventing excessive memory usage
-    let (tx_container, rx_container) = channel::<ContainerList>(100);
-    let (tx_state_change, rx_state_change) = channel::<StateChange>(100);
+    // Create bounded channels for communication between gRPC server and processing engine
+    // Sizes and overflow policies come from the statemanager section of settings.yaml
+    let (tx_container, rx_container) = queue::channel::<ContainerList>(
+        "ContainerList",
+        settings.container_buffer,
+        overflow_policy(&settings.container_overflow, OverflowPolicy::DropOldest),
+    );
+    let (tx_state_change, rx_state_change) = queue::channel::<StateChange>(
+        "StateChange",
+        settings.state_change_buffer,
+        overflow_policy(&settings.state_change_overflow, OverflowPolicy::Reject),
+    );
+    // A change dropped to make room is no longer pending
+    let tx_state_change =
+        tx_state_change.on_drop(|change: StateChange| pending::discard(&change.transition_id));
+    let (tx_container_update, rx_container_update) = queue::channel::<UpdateContainerStateRequest>(
+        "ContainerUpdate",
+        settings.container_update_buffer,
+        overflow_policy(&settings.container_update_overflow, OverflowPolicy::Reject),
+    );
+    let (tx_sync, rx_sync) = queue::channel::<ForceSynchronizationRequest>(
+        "ForceSynchronization",
+        SYNC_BUFFER,
+        OverflowPolicy::Reject,
+    );
+    let (tx_simulation, rx_simulation) =
+        queue::channel::<SimulationJob>("Simulation", SIMULATION_BUFFER, OverflowPolicy::Reject);
+
+    // Trigger scenarios with a time based schedule
+    tokio::spawn(scheduler::Scheduler::new(tx_state_change.clone()).run());
 
     // Launch StateManager processing engine
-    let manager_task = launch_manager(rx_container, rx_state_change);
+    let manager_task = launch_manager(
+        rx_container,
+        rx_state_change,
+        rx_container_update,
+        rx_sync,
+        rx_simulation,
+    );
 
     // Launch gRPC server for external communication
-    let grpc_task = initialize_grpc_server(tx_container, tx_state_change);
+    let grpc_task = initialize_grpc_server(
+        tx_container,
+        tx_state_change,
+        tx_container_update,
+        tx_sync,
+        tx_simulation,
+    );
 
     // Launch gRPC server for timpani deadline miss
     let timpani_task = initialize_timpani_server();
@@ -286,13 +496,34 @@ mod tests {
             std::env::set_var("PULLPIRI_TEST_MODE", "1");
         }
 
-        let (_tx_container, rx_container) = channel::<ContainerList>(10);
-        let (_tx_state_change, rx_state_change) = channel::<StateChange>(10);
+        let (_tx_container, rx_container) =
+            queue::channel::<ContainerList>("ContainerList", 10, OverflowPolicy::Block);
+        let (_tx_state_change, rx_state_change) =
+            queue::channel::<StateChange>("StateChange", 10, OverflowPolicy::Block);
+        let (_tx_container_update, rx_container_update) =
+            queue::channel::<UpdateContainerStateRequest>(
+                "ContainerUpdate",
+                10,
+                OverflowPolicy::Block,
+            );
+        let (_tx_sync, rx_sync) = queue::channel::<ForceSynchronizationRequest>(
+            "ForceSynchronization",
+            10,
+            OverflowPolicy::Block,
+        );
+        let (_tx_simulation, rx_simulation) =
+            queue::channel::<SimulationJob>("Simulation", 10, OverflowPolicy::Block);
 
         // Should return quickly because test mode short-circuits startup
         let res = timeout(
             Duration::from_secs(1),
-            launch_manager(rx_container, rx_state_change),
+            launch_manager(
+                rx_container,
+                rx_state_change,
+                rx_container_update,
+                rx_sync,
+                rx_simulation,
+            ),
         )
         .await;
         assert!(res.is_ok(), "launch_manager did not return in test mode");
@@ -308,13 +539,34 @@ mod tests {
             std::env::set_var("PULLPIRI_TEST_MODE", "1");
         }
 
-        let (tx_container, _rx_container) = channel::<ContainerList>(10);
-        let (tx_state_change, _rx_state_change) = channel::<StateChange>(10);
+        let (tx_container, _rx_container) =
+            queue::channel::<ContainerList>("ContainerList", 10, OverflowPolicy::Block);
+        let (tx_state_change, _rx_state_change) =
+            queue::channel::<StateChange>("StateChange", 10, OverflowPolicy::Block);
+        let (tx_container_update, _rx_container_update) =
+            queue::channel::<UpdateContainerStateRequest>(
+                "ContainerUpdate",
+                10,
+                OverflowPolicy::Block,
+            );
+        let (tx_sync, _rx_sync) = queue::channel::<ForceSynchronizationRequest>(
+            "ForceSynchronization",
+            10,
+            OverflowPolicy::Block,
+        );
+        let (tx_simulation, _rx_simulation) =
+            queue::channel::<SimulationJob>("Simulation", 10, OverflowPolicy::Block);
 
         // Should return quickly because test mode short-circuits server startup
         let res = timeout(
             Duration::from_secs(1),
-            initialize_grpc_server(tx_container, tx_state_change),
+            initialize_grpc_server(
+                tx_container,
+                tx_state_change,
+                tx_container_update,
+                tx_sync,
+                tx_simulation,
+            ),
         )
         .await;
         assert!(
@@ -354,14 +606,41 @@ mod tests {
             std::env::remove_var("PULLPIRI_TEST_MODE");
         }
 
-        let (tx_container, rx_container) = channel::<ContainerList>(10);
-        let (tx_state_change, rx_state_change) = channel::<StateChange>(10);
+        let (tx_container, rx_container) =
+            queue::channel::<ContainerList>("ContainerList", 10, OverflowPolicy::Block);
+        let (tx_state_change, rx_state_change) =
+            queue::channel::<StateChange>("StateChange", 10, OverflowPolicy::Block);
+        let (tx_container_update, rx_container_update) =
+            queue::channel::<UpdateContainerStateRequest>(
+                "ContainerUpdate",
+                10,
+                OverflowPolicy::Block,
+            );
+        let (tx_sync, rx_sync) = queue::channel::<ForceSynchronizationRequest>(
+            "ForceSynchronization",
+            10,
+            OverflowPolicy::Block,
+        );
+        let (tx_simulation, rx_simulation) =
+            queue::channel::<SimulationJob>("Simulation", 10, OverflowPolicy::Block);
 
         // Both futures should return quickly because cfg!(test) is true
         let fut = async move {
             tokio::join!(
-                launch_manager(rx_container, rx_state_change),
-                initialize_grpc_server(tx_container, tx_state_change),
+                launch_manager(
+                    rx_container,
+                    rx_state_change,
+                    rx_container_update,
+                    rx_sync,
+                    rx_simulation
+                ),
+                initialize_grpc_server(
+                    tx_container,
+                    tx_state_change,
+                    tx_container_update,
+                    tx_sync,
+                    tx_simulation
+                ),
             );
         };
 
@@ -376,14 +655,41 @@ mod tests {
             std::env::set_var("PULLPIRI_TEST_MODE", "1");
         }
 
-        let (tx_container, rx_container) = channel::<ContainerList>(10);
-        let (tx_state_change, rx_state_change) = channel::<StateChange>(10);
+        let (tx_container, rx_container) =
+            queue::channel::<ContainerList>("ContainerList", 10, OverflowPolicy::Block);
+        let (tx_state_change, rx_state_change) =
+            queue::channel::<StateChange>("StateChange", 10, OverflowPolicy::Block);
+        let (tx_container_update, rx_container_update) =
+            queue::channel::<UpdateContainerStateRequest>(
+                "ContainerUpdate",
+                10,
+                OverflowPolicy::Block,
+            );
+        let (tx_sync, rx_sync) = queue::channel::<ForceSynchronizationRequest>(
+            "ForceSynchronization",
+            10,
+            OverflowPolicy::Block,
+        );
+        let (tx_simulation, rx_simulation) =
+            queue::channel::<SimulationJob>("Simulation", 10, OverflowPolicy::Block);
 
         // Run manager, grpc server and timpani concurrently and ensure they all return quickly
         let fut = async move {
             tokio::join!(
-                launch_manager(rx_container, rx_state_change),
-                initialize_grpc_server(tx_container, tx_state_change),
+                launch_manager(
+                    rx_container,
+                    rx_state_change,
+                    rx_container_update,
+                    rx_sync,
+                    rx_simulation
+                ),
+                initialize_grpc_server(
+                    tx_container,
+                    tx_state_change,
+                    tx_container_update,
+                    tx_sync,
+                    tx_simulation
+                ),
                 initialize_timpani_server(),
             );
         };
@@ -401,6 +707,18 @@ mod tests {
 
     // Call the generated `main()` function (synchronous entry created by `#[tokio::main]`)
     // to exercise the startup logging, channel creation and join logic in test builds.
+    #[tokio::test]
+    async fn test_overflow_policy_falls_back_to_default() {
+        assert_eq!(
+            overflow_policy("drop_oldest", OverflowPolicy::Reject),
+            OverflowPolicy::DropOldest
+        );
+        assert_eq!(
+            overflow_policy("unbounded", OverflowPolicy::Reject),
+            OverflowPolicy::Reject
+        );
+    }
+
     #[test]
     fn test_main_invocation_without_env() {
         // Ensure the env var is not set and call main(); in test builds `cfg!(test)`
@@ -415,6 +733,7 @@ mod tests {
 
     #[test]
     fn test_main_invocation_with_env() {
+        common::etcd::use_in_memory_store();
         // Explicit test-mode via env var should also keep startup light
         unsafe {
             std::env::set_var("PULLPIRI_TEST_MODE", "1");
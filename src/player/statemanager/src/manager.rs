@@ -13,14 +13,22 @@
 //! state transitions, monitoring, reconciliation, and recovery for all resource types
 //! (Scenario, Package, Model, Volume, Network, Node).
 
+use crate::container_cache::ContainerCache;
+use crate::drift::{Drift, DriftKind};
 use crate::grpc::sender;
-use crate::state_machine::StateMachine;
-use crate::types::{ActionCommand, TransitionResult};
+use crate::state_machine::{ModelInstances, StateMachine};
+use crate::storage::Transaction;
+use crate::types::{ActionCommand, SimulationJob, TimeoutEvent, TransitionResult};
 use common::monitoringserver::ContainerList;
+use common::spec::artifact::scenario::DesiredState;
 use common::spec::artifact::Artifact;
+use common::spec::k8s::pod::{model_of, LABEL_REPLICA, LABEL_STANDBY};
+use common::state_mapping::{self, StateName};
 
 use common::statemanager::{
-    ErrorCode, ModelState, PackageState, ResourceType, ScenarioState, StateChange,
+    DenialReason, ErrorCode, ForceSynchronizationRequest, ModelState, NetworkState, NodeState,
+    PackageState, ResourceType, ScenarioState, SimulatedTransition, StateChange,
+    UpdateContainerStateRequest,
 };
 
 use common::logd;
@@ -29,6 +37,9 @@ use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tokio::task;
 
+/// Interval between checks of the drift check setting while it is disabled
+const DISABLED_DRIFT_POLL: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// Core state management engine for the StateManager service.
 ///
 /// This struct orchestrates all state management operations by receiving messages
@@ -64,6 +75,29 @@ pub struct StateManagerManager {
     /// - FilterGateway: Policy-driven state transitions and filtering decisions
     /// - ActionController: Action execution results and state confirmations
     rx_state_change: Arc<Mutex<mpsc::Receiver<StateChange>>>,
+
+    /// Channel receiver for single container state updates.
+    ///
+    /// Updates are merged into the container cache and only re-evaluate the
+    /// model of the updated container. `None` until set with
+    /// [`StateManagerManager::with_container_updates`].
+    rx_container_update: Option<Arc<Mutex<mpsc::Receiver<UpdateContainerStateRequest>>>>,
+
+    /// Channel receiver for ForceSynchronization requests.
+    ///
+    /// `None` until set with [`StateManagerManager::with_sync_requests`].
+    rx_sync: Option<Arc<Mutex<mpsc::Receiver<ForceSynchronizationRequest>>>>,
+
+    /// Channel receiver for state changes to simulate.
+    ///
+    /// `None` until set with [`StateManagerManager::with_simulation_requests`].
+    rx_simulation: Option<Arc<Mutex<mpsc::Receiver<SimulationJob>>>>,
+
+    /// Last known containers of every node, fed by ContainerLists and updates
+    container_cache: Arc<Mutex<ContainerCache>>,
+
+    /// Clock the activation windows of scenarios are checked against
+    clock: Arc<dyn common::clock::Clock>,
 }
 
 impl StateManagerManager {
@@ -82,13 +116,70 @@ impl StateManagerManager {
         rx_container: mpsc::Receiver<ContainerList>,
         rx_state_change: mpsc::Receiver<StateChange>,
     ) -> Self {
+        Self::with_shared_receivers(
+            Arc::new(Mutex::new(rx_container)),
+            Arc::new(Mutex::new(rx_state_change)),
+        )
+    }
+
+    /// Creates a StateManagerManager from receivers shared with their senders.
+    ///
+    /// Senders using the drop-oldest overflow policy need access to the receiver
+    /// to discard queued messages, see [`crate::queue::channel`].
+    pub fn with_shared_receivers(
+        rx_container: Arc<Mutex<mpsc::Receiver<ContainerList>>>,
+        rx_state_change: Arc<Mutex<mpsc::Receiver<StateChange>>>,
+    ) -> Self {
+        let mut state_machine = StateMachine::new();
+        state_machine
+            .load_state_timeouts(&common::setting::get_config().statemanager.state_timeouts);
+
         Self {
-            state_machine: Arc::new(Mutex::new(StateMachine::new())),
-            rx_container: Arc::new(Mutex::new(rx_container)),
-            rx_state_change: Arc::new(Mutex::new(rx_state_change)),
+            state_machine: Arc::new(Mutex::new(state_machine)),
+            rx_container,
+            rx_state_change,
+            rx_container_update: None,
+            rx_sync: None,
+            rx_simulation: None,
+            container_cache: Arc::new(Mutex::new(ContainerCache::new())),
+            clock: common::clock::get(),
         }
     }
 
+    /// Sets the receiver of single container state updates.
+    pub fn with_container_updates(
+        mut self,
+        rx_container_update: Arc<Mutex<mpsc::Receiver<UpdateContainerStateRequest>>>,
+    ) -> Self {
+        self.rx_container_update = Some(rx_container_update);
+        self
+    }
+
+    /// Sets the receiver of ForceSynchronization requests.
+    pub fn with_sync_requests(
+        mut self,
+        rx_sync: Arc<Mutex<mpsc::Receiver<ForceSynchronizationRequest>>>,
+    ) -> Self {
+        self.rx_sync = Some(rx_sync);
+        self
+    }
+
+    /// Sets the receiver of state changes to simulate.
+    pub fn with_simulation_requests(
+        mut self,
+        rx_simulation: Arc<Mutex<mpsc::Receiver<SimulationJob>>>,
+    ) -> Self {
+        self.rx_simulation = Some(rx_simulation);
+        self
+    }
+
+    /// Sets the clock activation windows are checked against, the installed
+    /// one by default.
+    pub fn with_clock(mut self, clock: Arc<dyn common::clock::Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Initializes the StateManagerManager's internal state and resources.
     ///
     /// Performs startup operations required before beginning message processing:
@@ -111,6 +202,10 @@ impl StateManagerManager {
         // Initialize the state machine with async action executor
         let action_receiver = {
             let mut state_machine = self.state_machine.lock().await;
+            // Tables from the settings override the built-in ones
+            if let Some(tables) = crate::transitions::load().await {
+                state_machine.apply_transition_tables(tables);
+            }
             state_machine.initialize_action_executor()
         };
 
@@ -120,6 +215,16 @@ impl StateManagerManager {
         });
 
         logd!(3, "State machine initialized with transition tables for Scenario, Package, and Model resources");
+
+        // States stored by an older version are read with the current names
+        match crate::storage::migrate_states(crate::storage::storage()).await {
+            Ok(0) => {}
+            Ok(count) => logd!(4, "Migrated {} state(s) stored with former names", count),
+            Err(e) => logd!(4, "Skipping state migration: {}", e),
+        }
+
+        // Fix package states torn from their model states by an earlier crash
+        self.repair_package_states().await;
         logd!(
             3,
             "Async action executor started for non-blocking action processing"
@@ -176,7 +281,7 @@ impl StateManagerManager {
     /// # Thread Safety
     /// This method is async and uses internal locking for state machine access.
     /// Multiple concurrent calls are safe but will be serialized at the state machine level.
-    async fn process_state_change(&self, state_change: StateChange) {
+    async fn process_state_change(&self, mut state_change: StateChange) {
         // ========================================
         // STEP 1: RESOURCE TYPE VALIDATION
         // ========================================
@@ -190,13 +295,13 @@ impl StateManagerManager {
                     state_change.resource_type,
                     state_change.resource_name
                 );
+                crate::timing::discard(&state_change.transition_id);
                 return; // Early return - cannot process invalid resource types
             }
         };
 
-        // NOTE: ASIL level parsing is commented out pending implementation of ASILLevel enum
-        // This will be needed for safety-critical processing validation
-        // let asil_level = match state_change.asil_level { ... };
+        // NOTE: The ASIL level of a resource comes from the annotation of its
+        // scenario, see crate::timing for the timing budget it selects
 
         // ========================================
         // STEP 2: COMPREHENSIVE REQUEST LOGGING
@@ -304,11 +409,51 @@ impl StateManagerManager {
         // - Condition evaluation for conditional transitions
         // - Action scheduling for follow-up operations
         // - Error detection and reporting
+        // A scenario about to run has to be inside its activation windows and
+        // alone among the scenarios of its exclusion groups, it waits for its
+        // condition again otherwise, see crate::exclusion
+        let constraints = if resource_type == ResourceType::Scenario
+            && state_mapping::parse_state(resource_type, &state_change.target_state)
+                == Some(ScenarioState::Allowed as i32)
+        {
+            Some(crate::exclusion::Constraints::load(&state_change.resource_name).await)
+        } else {
+            None
+        };
+
         let result = {
             // Acquire exclusive lock on the state machine for this transition
             // Note: This serializes all state transitions to maintain consistency
             let mut state_machine = self.state_machine.lock().await;
-            state_machine.process_state_change(state_change.clone())
+            let denial = constraints.as_ref().and_then(|constraints| {
+                constraints.check(self.clock.now_ns(), |name| {
+                    state_machine
+                        .get_resource_state(name, ResourceType::Scenario)
+                        .map(|resource| resource.current_state)
+                })
+            });
+            if let Some(denial) = denial {
+                logd!(
+                    3,
+                    "  Scenario {} {}",
+                    state_change.resource_name,
+                    crate::denial::describe(&denial)
+                );
+                state_change.target_state = "waiting".to_string();
+                state_change.denial = Some(denial);
+            }
+            let result = state_machine.process_state_change(state_change.clone());
+            // Published before the transition event, which carries it
+            if let Some(resource) =
+                state_machine.get_resource_state(&state_change.resource_name, resource_type)
+            {
+                crate::health::update(
+                    resource_type,
+                    &state_change.resource_name,
+                    &resource.health_status,
+                );
+            }
+            result
         }; // Lock is automatically released here
 
         // ========================================
@@ -322,21 +467,25 @@ impl StateManagerManager {
             // ========================================
             logd!(1, "  ✓ State transition completed successfully");
             // Convert new_state to string representation based on resource type only for logs
-            let new_state_str = match resource_type {
-                ResourceType::Scenario => ScenarioState::try_from(result.new_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Package => PackageState::try_from(result.new_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Model => ModelState::try_from(result.new_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                _ => "UNKNOWN",
-            };
+            let new_state_str = state_mapping::proto_state_name(resource_type, result.new_state)
+                .unwrap_or("UNKNOWN");
             logd!(2, "    Final State: {new_state_str}");
             logd!(2, "    Success Message: {}", result.message);
             logd!(1, "    Transition ID: {}", result.transition_id);
+            crate::inflight::start(
+                &result.transition_id,
+                resource_type,
+                &state_change.resource_name,
+                &result.actions_to_execute,
+            );
+            crate::notifier::notify(
+                resource_type,
+                &state_change.resource_name,
+                state_mapping::parse_state(resource_type, &state_change.current_state),
+                result.new_state,
+                &result.transition_id,
+                &state_change.metadata,
+            );
 
             // 🔍 COMMENT 6: Save scenario state changes to ETCD
             // StateManager receives state change requests from FilterGateway, ActionController, and PolicyManager
@@ -356,9 +505,9 @@ impl StateManagerManager {
                 logd!(1, "   📤 Saving to ETCD:");
                 logd!(1, "      • Key: {}", etcd_key);
                 logd!(1, "      • Value: {}", etcd_value);
-                logd!(1, "      • Operation: common::etcd::put()");
+                logd!(1, "      • Operation: StateStorage::put()");
 
-                if let Err(e) = common::etcd::put(&etcd_key, etcd_value).await {
+                if let Err(e) = crate::storage::storage().put(&etcd_key, etcd_value).await {
                     logd!(4, "   ❌ Failed to save scenario state to ETCD: {:?}", e);
                 } else {
                     logd!(
@@ -368,8 +517,93 @@ impl StateManagerManager {
                         etcd_value
                     );
                 }
+                // The reason of a denial is kept while the scenario is denied,
+                // or waits again after its activation was deferred
+                if result.new_state == ScenarioState::Denied as i32 || state_change.denial.is_some()
+                {
+                    let denial = crate::denial::complete(
+                        state_change.denial.as_ref(),
+                        &state_change.source,
+                        state_change.timestamp_ns,
+                    );
+                    crate::denial::record(&state_change.resource_name, &denial).await;
+                } else {
+                    crate::denial::clear(&state_change.resource_name).await;
+                }
+                // The scenario completed last decides the desired state of
+                // its package, see crate::drift
+                if result.new_state == ScenarioState::Completed as i32 {
+                    if let Err(e) = crate::drift::record_owner(&state_change.resource_name).await {
+                        logd!(
+                            4,
+                            "   Failed to record {} as package owner: {}",
+                            state_change.resource_name,
+                            e
+                        );
+                    }
+                }
+                self.refresh_scenario_status(&state_change.resource_name)
+                    .await;
+            }
+
+            // Network states are persisted by model name, the resource name of
+            // the network of a model
+            if resource_type == ResourceType::Network {
+                let etcd_key = format!("/network/{}/state", state_change.resource_name);
+                if let Err(e) = crate::storage::storage()
+                    .put(&etcd_key, new_state_str)
+                    .await
+                {
+                    logd!(4, "   Failed to save network state to ETCD: {:?}", e);
+                }
+
+                // The model held back by its network may be running now
+                if result.new_state == NetworkState::Ready as i32 {
+                    self.evaluate_cached_model(&state_change.resource_name)
+                        .await;
+                }
+            }
+
+            // Node states are persisted by hostname for the ActionController,
+            // which places no new models on a node that is not schedulable
+            if resource_type == ResourceType::Node {
+                let etcd_key = format!("/node/{}/state", state_change.resource_name);
+                if let Err(e) = crate::storage::storage()
+                    .put(&etcd_key, new_state_str)
+                    .await
+                {
+                    logd!(4, "   Failed to save node state to ETCD: {:?}", e);
+                }
+            }
+
+            // Package updates are reported by the ActionController together
+            // with the update step in progress, empty once it is over unless
+            // a canary update records its outcome
+            if resource_type == ResourceType::Package {
+                let mut transaction = Transaction::default();
+                transaction.put(
+                    &format!("/package/{}/state", state_change.resource_name),
+                    new_state_str,
+                );
+                transaction.put(
+                    &format!("/package/{}/update", state_change.resource_name),
+                    &state_change.sub_state,
+                );
+                if let Err(e) = crate::storage::storage().commit(transaction).await {
+                    logd!(4, "   Failed to save package state to ETCD: {:?}", e);
+                }
+                self.refresh_package_scenarios(&state_change.resource_name)
+                    .await;
             }
 
+            crate::timing::persisted(
+                resource_type,
+                &state_change.resource_name,
+                &state_change.transition_id,
+            )
+            .await;
+            crate::dlq::settle(&state_change.transition_id).await;
+
             // Log any actions that were queued for asynchronous execution
             // Actions are processed separately to keep state transitions fast
             if !result.actions_to_execute.is_empty() {
@@ -393,18 +627,8 @@ impl StateManagerManager {
             // ========================================
             logd!(4, "  ✗ State transition failed");
             // Convert new_state to string representation based on resource type only for logs
-            let new_state_str = match resource_type {
-                ResourceType::Scenario => ScenarioState::try_from(result.new_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Package => PackageState::try_from(result.new_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Model => ModelState::try_from(result.new_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                _ => "UNKNOWN",
-            };
+            let new_state_str = state_mapping::proto_state_name(resource_type, result.new_state)
+                .unwrap_or("UNKNOWN");
             logd!(4, "    Error Code: {:?}", result.error_code);
             logd!(4, "    Error Message: {}", result.message);
             logd!(4, "    Error Details: {}", result.error_details);
@@ -413,6 +637,7 @@ impl StateManagerManager {
 
             // Delegate to specialized failure handling logic
             // This method will analyze the failure type and determine appropriate recovery actions
+            crate::timing::discard(&state_change.transition_id);
             self.handle_transition_failure(&state_change, &result).await;
 
             logd!(4, "  Status: State change processing completed with errors");
@@ -459,9 +684,11 @@ impl StateManagerManager {
             }
         }
 
-        // In a real implementation, this would:
-        // - Log to audit trail
-        // - Generate alerts
+        // The sender was answered before processing, keep the change for an
+        // operator to inspect and re-drive
+        crate::dlq::record(state_change, result).await;
+
+        // In a real implementation, this would also:
         // - Trigger recovery procedures
         // - Update monitoring metrics
     }
@@ -475,82 +702,171 @@ impl StateManagerManager {
     /// * `container_list` - ContainerList message with node and container status
     ///
     /// # Processing Steps
-    /// 1. Analyze container health and status changes
-    /// 2. Identify models affected by container changes  
-    /// 3. Evaluate model state based on container states
+    /// 1. Diff the node's containers against the container cache
+    /// 2. Identify models of added, changed and removed containers
+    /// 3. Evaluate those models from all of their cached containers
     /// 4. Update model states in ETCD if transitions occur
+    ///
+    /// Models without container changes are not evaluated again, so repeated
+    /// identical snapshots cause no state machine work and no ETCD access.
     async fn process_container_list(&self, container_list: ContainerList) {
         logd!(2, "=== PROCESSING CONTAINER LIST ===");
         logd!(2, "  Node Name: {}", container_list.node_name);
         logd!(2, "  Container Count: {}", container_list.containers.len());
 
-        // Process containers and group by model
-        let model_containers = self
-            .group_containers_by_model(&container_list.containers)
-            .await;
+        let (diff, cached) = {
+            let mut cache = self.container_cache.lock().await;
+            let diff = cache.replace_node(&container_list.node_name, &container_list.containers);
+            (diff, cache.node_containers())
+        };
+        if diff.is_empty() {
+            logd!(2, "  No container changes since the last snapshot");
+            return;
+        }
+        logd!(
+            2,
+            "  Changes: {} added, {} changed, {} removed",
+            diff.added.len(),
+            diff.changed.len(),
+            diff.removed.len()
+        );
 
-        // Process each model's container states
-        for (model_name, containers) in model_containers {
-            logd!(2, "  Processing model: {}", model_name);
+        // Only the models of containers that differ need a new evaluation
+        let changed_containers = diff.into_containers();
+        let affected_models = self.group_containers_by_model(&changed_containers).await;
+        let mut model_instances = self.group_instances_by_model(&cached).await;
+
+        for model_name in affected_models.keys() {
+            match model_instances.remove(model_name) {
+                Some(instances) => self.evaluate_model_instances(model_name, &instances).await,
+                None => {
+                    logd!(2, "  Model {} has no containers left", model_name);
+                    self.evaluate_model_instances(model_name, &ModelInstances::new())
+                        .await
+                }
+            }
+        }
 
-            // Process the state evaluation and transition through the state machine
-            let mut state_machine = self.state_machine.lock().await;
-            let transition_result =
-                state_machine.process_model_state_update(&model_name, &containers);
+        logd!(2, "  Status: Container list processing completed");
+        logd!(2, "=====================================");
+    }
 
-            if transition_result.is_success() {
-                // Check if state actually changed by looking at actions_to_execute
-                let state_changed = !transition_result.actions_to_execute.is_empty();
+    /// Merges a single container update into the container cache and
+    /// re-evaluates the model of that container.
+    ///
+    /// The model is evaluated with all of its cached containers, across nodes,
+    /// so that the update does not hide the state of its sibling containers.
+    async fn process_container_update(&self, update: UpdateContainerStateRequest) {
+        logd!(
+            2,
+            "=== PROCESSING CONTAINER UPDATE: {} on {} ===",
+            update.container_id,
+            update.node_name
+        );
 
-                if state_changed {
-                    logd!(
-                        1,
-                        "    State transition successful: {}",
-                        transition_result.message
-                    );
+        let container = {
+            let mut cache = self.container_cache.lock().await;
+            match cache.apply_update(&update) {
+                Ok(container) => container,
+                Err(e) => {
+                    logd!(4, "  Ignoring container update: {}", e);
+                    return;
+                }
+            }
+        };
 
-                    // Extract the new model state from the transition result
-                    let new_model_state = match transition_result.new_state {
-                        1 => common::statemanager::ModelState::Created,
-                        2 => common::statemanager::ModelState::Paused,
-                        3 => common::statemanager::ModelState::Exited,
-                        4 => common::statemanager::ModelState::Dead,
-                        5 => common::statemanager::ModelState::Running,
-                        _ => common::statemanager::ModelState::Running,
-                    };
+        let Some(model_name) = self.extract_model_name_from_container(&container).await else {
+            logd!(
+                4,
+                "  Container {} does not belong to a model",
+                update.container_id
+            );
+            return;
+        };
 
-                    // Save the new model state to ETCD
-                    drop(state_machine); // Release the lock before async operation
-                    if let Err(e) = self
-                        .save_model_state_to_etcd(&model_name, new_model_state)
-                        .await
-                    {
-                        logd!(4, "    Failed to save model state to ETCD: {:?}", e);
-                    } else {
-                        logd!(1, "    Successfully saved model state to ETCD");
-
-                        // Trigger package state evaluation based on model state change
-                        // This implements the chain reaction described in the Korean documentation
-                        self.trigger_package_state_evaluation(&model_name).await;
-                    }
+        self.evaluate_cached_model(&model_name).await;
+    }
+
+    /// Re-evaluates a model from all of its cached containers
+    async fn evaluate_cached_model(&self, model_name: &str) {
+        let cached = self.container_cache.lock().await.node_containers();
+        let model_instances = self.group_instances_by_model(&cached).await;
+        if let Some(instances) = model_instances.get(model_name) {
+            self.evaluate_model_instances(model_name, instances).await;
+        }
+    }
+
+    /// Evaluates a model from its containers on every node and propagates a
+    /// state change to ETCD and to the packages containing the model.
+    ///
+    /// A model without instances had all of its containers removed.
+    async fn evaluate_model_instances(&self, model_name: &str, instances: &ModelInstances<'_>) {
+        logd!(
+            2,
+            "  Processing model: {} on instance(s) {}",
+            model_name,
+            instances.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+
+        // Process the state evaluation and transition through the state machine
+        let mut state_machine = self.state_machine.lock().await;
+        let previous_state = state_machine
+            .get_resource_state(model_name, ResourceType::Model)
+            .map(|state| state.current_state);
+        let transition_result = if instances.is_empty() {
+            state_machine.process_model_removed(model_name)
+        } else {
+            state_machine.process_model_instances_update(model_name, instances)
+        };
+
+        if transition_result.is_success() {
+            // Check if state actually changed by looking at actions_to_execute
+            let state_changed = !transition_result.actions_to_execute.is_empty();
+
+            if state_changed {
+                logd!(
+                    1,
+                    "    State transition successful: {}",
+                    transition_result.message
+                );
+
+                // Extract the new model state from the transition result
+                let new_model_state = ModelState::try_from(transition_result.new_state)
+                    .unwrap_or(ModelState::Unspecified);
+
+                // Save the new model state with the package states it changes
+                // This implements the chain reaction described in the Korean documentation
+                drop(state_machine); // Release the lock before async operation
+                if let Err(e) = self
+                    .save_model_state_cascade(model_name, new_model_state)
+                    .await
+                {
+                    logd!(4, "    {}", e);
                 } else {
-                    logd!(
-                        2,
-                        "    Model state unchanged: {}",
-                        transition_result.message
+                    logd!(1, "    Successfully saved model state to ETCD");
+                    crate::notifier::notify(
+                        ResourceType::Model,
+                        model_name,
+                        previous_state,
+                        transition_result.new_state,
+                        &transition_result.transition_id,
+                        &Default::default(),
                     );
                 }
             } else {
                 logd!(
-                    4,
-                    "    State evaluation failed: {}",
+                    2,
+                    "    Model state unchanged: {}",
                     transition_result.message
                 );
             }
+        } else {
+            logd!(
+                4,
+                "    State evaluation failed: {}",
+                transition_result.message
+            );
         }
-
-        logd!(2, "  Status: Container list processing completed");
-        logd!(2, "=====================================");
     }
 
     /// Groups containers by their associated model based on annotations or naming conventions
@@ -573,64 +889,53 @@ impl StateManagerManager {
         model_containers
     }
 
-    /// Extracts model name from container annotations or configuration
-    async fn extract_model_name_from_container(
+    /// Groups the containers of every node by model, then by instance
+    ///
+    /// A model placed on several nodes gets one instance per node, and one
+    /// per replica on a node running several, its standby is an instance of
+    /// its own. Its instances are evaluated separately, so that containers
+    /// of the same local name on different nodes are never taken for one
+    /// instance.
+    async fn group_instances_by_model<'a>(
         &self,
-        container: &common::monitoringserver::ContainerInfo,
-    ) -> Option<String> {
-        // Check annotations for model information
-        if let Some(model_name) = container.annotation.get("model") {
-            return Some(model_name.clone());
-        }
-
-        if let Some(model_name) = container.annotation.get("pullpiri.model") {
-            return Some(model_name.clone());
-        }
+        containers: &'a [(String, common::monitoringserver::ContainerInfo)],
+    ) -> std::collections::HashMap<String, ModelInstances<'a>> {
+        let mut model_instances: std::collections::HashMap<String, ModelInstances<'a>> =
+            std::collections::HashMap::new();
 
-        // Check config for model information
-        if let Some(model_name) = container.config.get("model") {
-            return Some(model_name.clone());
-        }
-
-        // Try to extract from container names (as fallback)
-        for name in &container.names {
-            if name.contains("model-") {
-                if let Some(model_name) = name.strip_prefix("model-") {
-                    return Some(model_name.to_string());
-                }
+        for (node_name, container) in containers {
+            if let Some(model_name) = self.extract_model_name_from_container(container).await {
+                let instance = if container.annotation.contains_key(LABEL_STANDBY) {
+                    format!("{}#standby", node_name)
+                } else {
+                    match container.annotation.get(LABEL_REPLICA) {
+                        Some(replica) => format!("{}#{}", node_name, replica),
+                        None => node_name.clone(),
+                    }
+                };
+                model_instances
+                    .entry(model_name)
+                    .or_default()
+                    .entry(instance)
+                    .or_default()
+                    .push(container);
             }
         }
 
-        None
+        model_instances
     }
 
-    /// Saves model state to ETCD using the format specified in the documentation
-    async fn save_model_state_to_etcd(
+    /// Extracts model name from the piccolo annotations of a container
+    ///
+    /// The `io.piccolo.model` annotation set at container creation is
+    /// authoritative. Containers created before it was set are mapped by
+    /// their legacy `model` or `pullpiri.model` annotation, the others
+    /// belong to no model.
+    async fn extract_model_name_from_container(
         &self,
-        model_name: &str,
-        model_state: common::statemanager::ModelState,
-    ) -> std::result::Result<(), String> {
-        let key = format!("/model/{}/state", model_name);
-        let value = match model_state {
-            common::statemanager::ModelState::Created => "Created",
-            common::statemanager::ModelState::Paused => "Paused",
-            common::statemanager::ModelState::Exited => "Exited",
-            common::statemanager::ModelState::Dead => "Dead",
-            common::statemanager::ModelState::Running => "Running",
-            _ => "Unknown",
-        };
-
-        logd!(1, "    Saving to ETCD - Key: {}, Value: {}", key, value);
-
-        if let Err(e) = common::etcd::put(&key, value).await {
-            logd!(5, "    Failed to save model state: {:?}", e);
-            return Err(format!(
-                "Failed to save model state for {}: {:?}",
-                model_name, e
-            ));
-        }
-
-        Ok(())
+        container: &common::monitoringserver::ContainerInfo,
+    ) -> Option<String> {
+        model_of(&container.annotation)
     }
 
     /// Saves package state to ETCD using the format specified in the Korean documentation
@@ -651,7 +956,7 @@ impl StateManagerManager {
             value
         );
 
-        if let Err(e) = common::etcd::put(&key, value).await {
+        if let Err(e) = crate::storage::storage().put(&key, value).await {
             logd!(5, "    Failed to save package state: {:?}", e);
             return Err(format!(
                 "Failed to save package state for {}: {:?}",
@@ -662,87 +967,250 @@ impl StateManagerManager {
         Ok(())
     }
 
-    /// Triggers package state evaluation and update based on model state changes
+    /// Saves a model state together with the package states it changes
     ///
-    /// This function implements the chain reaction described in the Korean documentation:
-    /// When a model state changes, it triggers package state evaluation to see if the
-    /// package state should also change based on the states of all models in the package.
-    async fn trigger_package_state_evaluation(&self, changed_model_name: &str) {
+    /// The package states are evaluated as if the model state was already
+    /// saved, and all states are committed in one transaction so that a crash
+    /// cannot leave a model state without the package states derived from it.
+    /// Packages that became error or degraded are reconciled afterwards.
+    /// The unspecified state is not a state to save and is refused.
+    async fn save_model_state_cascade(
+        &self,
+        model_name: &str,
+        model_state: common::statemanager::ModelState,
+    ) -> std::result::Result<(), String> {
+        if model_state == ModelState::Unspecified {
+            return Err(format!(
+                "Model {} has no state to save, keeping the stored one",
+                model_name
+            ));
+        }
+        let mut transaction = Transaction::default();
+        transaction.put(&format!("/model/{}/state", model_name), model_state.name());
+        let changed = self
+            .evaluate_packages_of_model(&mut transaction, model_name)
+            .await;
+
         logd!(
-            2,
-            "  Triggering package state evaluation for model: {}",
-            changed_model_name
+            1,
+            "    Saving model {} state {} with {} package state(s)",
+            model_name,
+            model_state.name(),
+            changed.len()
         );
+        // Read before the commit replaces them, for the event hooks
+        let mut previous_states = Vec::new();
+        for (package_name, _) in &changed {
+            previous_states.push(
+                crate::storage::storage()
+                    .get(&format!("/package/{}/state", package_name))
+                    .await
+                    .ok()
+                    .and_then(|state| PackageState::migrate(&state)),
+            );
+        }
+        crate::storage::storage()
+            .commit(transaction)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Failed to save state of model {} and its packages: {:?}",
+                    model_name, e
+                )
+            })?;
+
+        for ((package_name, new_state), previous_state) in changed.iter().zip(previous_states) {
+            crate::notifier::notify(
+                ResourceType::Package,
+                package_name,
+                previous_state.map(|state| state as i32),
+                *new_state as i32,
+                "",
+                &Default::default(),
+            );
+            self.refresh_package_scenarios(package_name).await;
+        }
+        self.reconcile_failed_packages(&changed).await;
+        Ok(())
+    }
 
-        // Find all packages that contain this model using StateMachine
-        let packages = match StateMachine::find_packages_containing_model(changed_model_name).await
-        {
-            Ok(pkgs) => pkgs,
+    /// Recomputes the status of a scenario, see [`crate::rollup`], and
+    /// announces a change as event
+    async fn refresh_scenario_status(&self, scenario_name: &str) {
+        match crate::rollup::refresh(scenario_name).await {
+            Ok(Some((previous, status))) => logd!(
+                3,
+                "EVENT: scenario '{}' status {} -> {}",
+                scenario_name,
+                if previous.is_empty() {
+                    "none"
+                } else {
+                    &previous
+                },
+                status.as_str()
+            ),
+            Ok(None) => {}
+            Err(e) => logd!(
+                4,
+                "    Failed to refresh status of scenario {}: {}",
+                scenario_name,
+                e
+            ),
+        }
+    }
+
+    /// Recomputes the status of every scenario targeting a package
+    async fn refresh_package_scenarios(&self, package_name: &str) {
+        match crate::rollup::scenarios_targeting(package_name).await {
+            Ok(scenarios) => {
+                for scenario_name in scenarios {
+                    self.refresh_scenario_status(&scenario_name).await;
+                }
+            }
+            Err(e) => logd!(
+                4,
+                "    Failed to find scenarios of package {}: {}",
+                package_name,
+                e
+            ),
+        }
+    }
+
+    /// Evaluates the packages containing a model against the states in
+    /// `transaction` and adds the changed package states to it
+    ///
+    /// Returns the packages whose state changed, with their new state.
+    async fn evaluate_packages_of_model(
+        &self,
+        transaction: &mut Transaction,
+        model_name: &str,
+    ) -> Vec<(String, common::statemanager::PackageState)> {
+        let packages = match crate::cascade::packages_containing(&[model_name]).await {
+            Ok(packages) => packages,
             Err(e) => {
                 logd!(
                     4,
                     "    Failed to find packages for model {}: {:?}",
-                    changed_model_name,
+                    model_name,
                     e
                 );
-                return;
+                return Vec::new();
             }
         };
 
-        // Evaluate and update state for each package using state machine
-        for package_name in packages {
-            let state_machine = self.state_machine.lock().await;
-            match state_machine
-                .evaluate_and_update_package_state(&package_name)
-                .await
-            {
-                Ok((state_changed, new_state)) => {
-                    drop(state_machine); // Release lock before async operations
-
-                    if state_changed {
-                        // Save new state to ETCD
-                        if let Err(e) = self
-                            .save_package_state_to_etcd(&package_name, new_state)
-                            .await
-                        {
-                            logd!(5, "      Failed to save package state: {:?}", e);
-                            continue;
-                        }
-
-                        // If package is in error or degraded state, trigger ActionController reconcile
-                        if new_state == common::statemanager::PackageState::Error
-                            || new_state == common::statemanager::PackageState::Degraded
-                        {
-                            if let Err(e) = self
-                                .trigger_action_controller_reconcile_internal(&package_name)
-                                .await
-                            {
-                                logd!(
-                                    5,
-                                    "      Failed to trigger ActionController reconcile: {:?}",
-                                    e
-                                );
-                            }
-                        }
+        // The packages are evaluated from one batch of reads, see crate::cascade
+        let changed = crate::cascade::evaluate(transaction, &packages).await;
+        for (package_name, new_state) in &changed {
+            transaction.put(
+                &format!("/package/{}/state", package_name),
+                new_state.as_str_name(),
+            );
+        }
+        changed
+    }
 
-                        logd!(
-                            1,
-                            "      Successfully updated package {} state to {}",
-                            package_name,
-                            new_state.as_str_name()
-                        );
-                    }
-                }
-                Err(e) => {
+    /// Requests ActionController reconcile for packages whose new state is
+    /// error or degraded
+    async fn reconcile_failed_packages(
+        &self,
+        changed: &[(String, common::statemanager::PackageState)],
+    ) {
+        for (package_name, new_state) in changed {
+            if *new_state == common::statemanager::PackageState::Error
+                || *new_state == common::statemanager::PackageState::Degraded
+            {
+                let reason = format!("package {} became {}", package_name, new_state.name());
+                if let Err(e) = self
+                    .trigger_action_controller_reconcile_internal(package_name, &reason)
+                    .await
+                {
                     logd!(
-                        4,
-                        "    Failed to evaluate package state for {}: {:?}",
-                        package_name,
+                        5,
+                        "      Failed to trigger ActionController reconcile: {:?}",
                         e
                     );
                 }
             }
+
+            logd!(
+                1,
+                "      Successfully updated package {} state to {}",
+                package_name,
+                new_state.as_str_name()
+            );
+        }
+    }
+
+    /// Corrects stored package states that disagree with their model states
+    ///
+    /// Before cascading writes were transactional, a crash between the model
+    /// and the package write could leave such entries behind. Packages none
+    /// of whose models has a state yet are left alone.
+    async fn repair_package_states(&self) -> usize {
+        let packages = match crate::storage::storage()
+            .get_all_with_prefix("Package/")
+            .await
+        {
+            Ok(packages) => packages,
+            Err(e) => {
+                logd!(4, "Skipping package state repair: {:?}", e);
+                return 0;
+            }
+        };
+
+        let mut transaction = Transaction::default();
+        let mut repaired = Vec::new();
+        for (key, yaml) in packages {
+            let package = match serde_yaml::from_str::<common::spec::artifact::Package>(&yaml) {
+                Ok(package) => package,
+                Err(e) => {
+                    logd!(4, "    Failed to parse package {}: {:?}", key, e);
+                    continue;
+                }
+            };
+            let package_name = package.get_qualified_name();
+
+            let mut has_state = StateMachine::get_current_package_state(&package_name)
+                .await
+                .is_some();
+            for model in package.get_models() {
+                if has_state {
+                    break;
+                }
+                let model_key = format!("/model/{}/state", model.get_name());
+                has_state = crate::storage::storage().get(&model_key).await.is_ok();
+            }
+            if !has_state {
+                continue;
+            }
+
+            let state_machine = self.state_machine.lock().await;
+            if let Ok((true, state)) = state_machine
+                .evaluate_package_state_in(&transaction, &package_name)
+                .await
+            {
+                transaction.put(
+                    &format!("/package/{}/state", package_name),
+                    state.as_str_name(),
+                );
+                repaired.push((package_name, state));
+            }
         }
+
+        if repaired.is_empty() {
+            return 0;
+        }
+        if let Err(e) = crate::storage::storage().commit(transaction).await {
+            logd!(5, "Failed to repair package states: {:?}", e);
+            return 0;
+        }
+        logd!(
+            4,
+            "Repaired {} package state(s) inconsistent with their models",
+            repaired.len()
+        );
+        self.reconcile_failed_packages(&repaired).await;
+        repaired.len()
     }
 
     /// Trigger ActionController reconcile request for dead/error package state
@@ -754,14 +1222,18 @@ impl StateManagerManager {
         &self,
         package_name: &str,
     ) -> std::result::Result<(), String> {
-        self.trigger_action_controller_reconcile_internal(package_name)
+        self.trigger_action_controller_reconcile_internal(package_name, "requested")
             .await
     }
 
     /// Internal implementation of ActionController reconcile trigger
+    ///
+    /// The request names the package and its failing models, so that
+    /// ActionController restarts only those and leaves the others running.
     async fn trigger_action_controller_reconcile_internal(
         &self,
         package_name: &str,
+        reason: &str,
     ) -> std::result::Result<(), String> {
         logd!(
             3,
@@ -787,14 +1259,75 @@ impl StateManagerManager {
             }
         };
 
-        // Create reconcile request using the gRPC sender
-        let reconcile_request = common::actioncontroller::ReconcileRequest {
-            scenario_name: scenario_name.clone(),
+        let failing_models = match StateMachine::get_models_for_package(package_name).await {
+            Ok(model_states) => StateMachine::failing_models(&model_states),
+            Err(e) => {
+                logd!(4, "      Failed to get models of {}: {}", package_name, e);
+                Vec::new()
+            }
+        };
+        self.request_reconcile(common::actioncontroller::ReconcileRequest {
+            scenario_name,
+            current: common::actioncontroller::PodStatus::Failed.into(),
+            desired: common::actioncontroller::PodStatus::Running.into(),
+            resource_type: ResourceType::Package as i32,
+            package_name: package_name.to_string(),
+            failing_models,
+            reason: reason.to_string(),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Ask ActionController to bring a failed scenario back to running
+    pub async fn send_reconcile_request(
+        &self,
+        scenario_name: &str,
+    ) -> std::result::Result<(), String> {
+        self.request_reconcile(common::actioncontroller::ReconcileRequest {
+            scenario_name: scenario_name.to_string(),
             current: common::actioncontroller::PodStatus::Failed.into(),
             desired: common::actioncontroller::PodStatus::Running.into(),
+            resource_type: ResourceType::Scenario as i32,
+            reason: format!("scenario {} failed", scenario_name),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Ask ActionController to bring a drifted scenario to its desired state
+    pub async fn reconcile_to_desired_state(
+        &self,
+        scenario_name: &str,
+        desired: DesiredState,
+    ) -> std::result::Result<(), String> {
+        use common::actioncontroller::PodStatus;
+        let (current, desired) = match desired {
+            DesiredState::Running => (PodStatus::Done, PodStatus::Running),
+            DesiredState::Stopped => (PodStatus::Running, PodStatus::Done),
         };
+        self.request_reconcile(common::actioncontroller::ReconcileRequest {
+            scenario_name: scenario_name.to_string(),
+            current: current.into(),
+            desired: desired.into(),
+            resource_type: ResourceType::Scenario as i32,
+            reason: format!("scenario {} drifted", scenario_name),
+            ..Default::default()
+        })
+        .await
+    }
 
-        match sender::_send(reconcile_request).await {
+    async fn request_reconcile(
+        &self,
+        mut reconcile_request: common::actioncontroller::ReconcileRequest,
+    ) -> std::result::Result<(), String> {
+        reconcile_request.correlation_id = common::correlation::current_or_empty();
+        let scenario_name = reconcile_request.scenario_name.clone();
+
+        match sender::action_controller()
+            .reconcile(reconcile_request)
+            .await
+        {
             Ok(response) => {
                 logd!(
                     2,
@@ -819,20 +1352,514 @@ impl StateManagerManager {
         }
     }
 
+    /// Moves resources stuck in a transitional state to their failure state.
+    ///
+    /// Every timed out resource raises an alert, has its new state persisted
+    /// to ETCD and goes through the recovery path of its type:
+    /// - Scenario: ActionController reconcile of the scenario
+    /// - Package: ActionController reconcile of the owning scenario
+    /// - Model: package state re-evaluation, which reconciles failed packages
+    ///
+    /// # Returns
+    /// * `Vec<TimeoutEvent>` - Resources that timed out during this check
+    pub async fn check_state_timeouts(&self) -> Vec<TimeoutEvent> {
+        let events = {
+            let mut state_machine = self.state_machine.lock().await;
+            let events = state_machine.check_timeouts();
+            for event in &events {
+                if let Some(resource) =
+                    state_machine.get_resource_state(&event.resource_name, event.resource_type)
+                {
+                    crate::health::update(
+                        event.resource_type,
+                        &event.resource_name,
+                        &resource.health_status,
+                    );
+                }
+            }
+            events
+        };
+
+        for event in &events {
+            self.handle_state_timeout(event).await;
+        }
+        events
+    }
+
+    /// Replace the state timeouts with the ones of the current settings
+    pub async fn reload_state_timeouts(&self) {
+        self.state_machine
+            .lock()
+            .await
+            .load_state_timeouts(&common::setting::get_config().statemanager.state_timeouts);
+        logd!(3, "State timeouts reloaded from the settings");
+    }
+
+    /// Alert, persist and recover a single timed out resource
+    async fn handle_state_timeout(&self, event: &TimeoutEvent) {
+        logd!(
+            5,
+            "ALERT: {:?} '{}' stuck in state {} for {}s, forcing transition to {} ({})",
+            event.resource_type,
+            event.resource_name,
+            event.from_state,
+            event.elapsed.as_secs(),
+            event.to_state,
+            event.transition_id
+        );
+        crate::events::alert(
+            event.resource_type,
+            &event.resource_name,
+            format!(
+                "stuck in state {} for {}s, forced to {}",
+                state_mapping::state_name(event.resource_type, event.from_state)
+                    .unwrap_or("Unknown"),
+                event.elapsed.as_secs(),
+                state_mapping::state_name(event.resource_type, event.to_state).unwrap_or("Unknown")
+            ),
+        );
+        crate::notifier::notify(
+            event.resource_type,
+            &event.resource_name,
+            Some(event.from_state),
+            event.to_state,
+            &event.transition_id,
+            &Default::default(),
+        );
+
+        let recovery = match event.resource_type {
+            ResourceType::Scenario => {
+                let state = ScenarioState::try_from(event.to_state)
+                    .map(|s| s.as_str_name())
+                    .unwrap_or("UNKNOWN");
+                let key = format!("/scenario/{}/state", event.resource_name);
+                if let Err(e) = crate::storage::storage().put(&key, state).await {
+                    logd!(4, "    Failed to save scenario state to ETCD: {:?}", e);
+                }
+                if event.to_state == ScenarioState::Denied as i32 {
+                    let denial = DenialReason {
+                        rule: crate::denial::RULE_TIMEOUT.to_string(),
+                        message: format!(
+                            "stuck in state {} for {}s",
+                            state_mapping::state_name(event.resource_type, event.from_state)
+                                .unwrap_or("Unknown"),
+                            event.elapsed.as_secs()
+                        ),
+                        source: "statemanager".to_string(),
+                        timestamp_ns: common::clock::now_ns(),
+                    };
+                    crate::denial::record(&event.resource_name, &denial).await;
+                }
+                self.refresh_scenario_status(&event.resource_name).await;
+                self.send_reconcile_request(&event.resource_name).await
+            }
+            ResourceType::Package => {
+                let state = PackageState::try_from(event.to_state).unwrap_or(PackageState::Error);
+                if let Err(e) = self
+                    .save_package_state_to_etcd(&event.resource_name, state)
+                    .await
+                {
+                    logd!(4, "    {}", e);
+                }
+                self.refresh_package_scenarios(&event.resource_name).await;
+                let reason = format!(
+                    "package {} stuck in state {} for {}s",
+                    event.resource_name,
+                    state_mapping::state_name(event.resource_type, event.from_state)
+                        .unwrap_or("Unknown"),
+                    event.elapsed.as_secs()
+                );
+                self.trigger_action_controller_reconcile_internal(&event.resource_name, &reason)
+                    .await
+            }
+            ResourceType::Model => {
+                let state = ModelState::try_from(event.to_state).unwrap_or(ModelState::Dead);
+                self.save_model_state_cascade(&event.resource_name, state)
+                    .await
+            }
+            ResourceType::Network => {
+                let key = format!("/network/{}/state", event.resource_name);
+                crate::storage::storage()
+                    .put(&key, NetworkState::Failed.as_str_name())
+                    .await
+                    .map_err(|e| format!("Failed to save network state to ETCD: {:?}", e))
+            }
+            ResourceType::Node => {
+                let key = format!("/node/{}/state", event.resource_name);
+                crate::storage::storage()
+                    .put(&key, NodeState::Cordoned.as_str_name())
+                    .await
+                    .map_err(|e| format!("Failed to save node state to ETCD: {:?}", e))
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(e) = recovery {
+            logd!(
+                5,
+                "    Recovery of timed out {:?} '{}' failed: {}",
+                event.resource_type,
+                event.resource_name,
+                e
+            );
+        }
+    }
+
+    /// Compares the desired states of deployed scenarios with the cached
+    /// containers and stored model states, see [`crate::drift`].
+    ///
+    /// The desired states are kept in the tracked resource states of the
+    /// models and packages.
+    ///
+    /// Only models covered by `resource_type` and `resource_name` are
+    /// compared. Every drift raises an alert.
+    pub async fn detect_drift(
+        &self,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> std::result::Result<Vec<Drift>, String> {
+        let desired = crate::drift::desired_models().await?;
+        let (cached, reported_nodes): (_, std::collections::HashSet<String>) = {
+            let cache = self.container_cache.lock().await;
+            let nodes = desired
+                .iter()
+                .filter(|model| cache.has_node(&model.node))
+                .map(|model| model.node.clone())
+                .collect();
+            (cache.node_containers(), nodes)
+        };
+        let model_instances = self.group_instances_by_model(&cached).await;
+        {
+            let mut state_machine = self.state_machine.lock().await;
+            for model in &desired {
+                state_machine.set_desired_state(
+                    &model.model,
+                    ResourceType::Model,
+                    model.state.model_state() as i32,
+                );
+                state_machine.set_desired_state(
+                    &model.package,
+                    ResourceType::Package,
+                    model.state.package_state() as i32,
+                );
+            }
+        }
+
+        let mut drifts = Vec::new();
+        for model in desired
+            .iter()
+            .filter(|model| model.in_scope(resource_type, resource_name))
+        {
+            let instances = model_instances.get(&model.model);
+            if instances.is_none() && !reported_nodes.contains(&model.node) {
+                continue;
+            }
+            let actual = {
+                let state_machine = self.state_machine.lock().await;
+                instances.map(|i| state_machine.evaluate_model_state_from_instances(i))
+            };
+            let stored = crate::storage::storage()
+                .get(&format!("/model/{}/state", model.model))
+                .await
+                .ok()
+                .and_then(|state| ModelState::migrate(&state));
+            drifts.extend(crate::drift::compare(model, actual, stored));
+        }
+
+        for drift in &drifts {
+            logd!(
+                5,
+                "ALERT: {:?} drift of model '{}' in scenario '{}': desired {}, actual {}",
+                drift.kind,
+                drift.model,
+                drift.scenario,
+                drift.desired,
+                drift.actual
+            );
+            crate::events::alert(
+                ResourceType::Model,
+                &drift.model,
+                format!(
+                    "{:?} drift in scenario {}: desired {}, actual {}",
+                    drift.kind, drift.scenario, drift.desired, drift.actual
+                ),
+            );
+        }
+        Ok(drifts)
+    }
+
+    /// Corrects drifts, returning the number of corrections issued
+    ///
+    /// Scenarios with runtime drift are reconciled once each. Stored drift
+    /// is only corrected if `fix_stored` is set, by saving the actual model
+    /// state together with the package states it changes.
+    async fn correct_drift(&self, drifts: &[Drift], fix_stored: bool) -> usize {
+        let mut corrected = 0;
+        let mut reconciled = std::collections::HashSet::new();
+        for drift in drifts {
+            let result = match drift.kind {
+                DriftKind::Runtime if reconciled.insert(drift.scenario.clone()) => {
+                    self.reconcile_to_desired_state(&drift.scenario, drift.target)
+                        .await
+                }
+                DriftKind::Stored if fix_stored => {
+                    let state = ModelState::parse(&drift.actual).unwrap_or(ModelState::Dead);
+                    self.save_model_state_cascade(&drift.model, state).await
+                }
+                _ => continue,
+            };
+            match result {
+                Ok(()) => corrected += 1,
+                Err(e) => logd!(4, "    Failed to correct drift of {}: {}", drift.model, e),
+            }
+        }
+        corrected
+    }
+
+    /// Periodic drift check, reconciling drifted scenarios if
+    /// `statemanager.drift_auto_correct` is set
+    pub async fn check_drift(&self) -> Vec<Drift> {
+        let drifts = match self.detect_drift(ResourceType::Unspecified, "").await {
+            Ok(drifts) => drifts,
+            Err(e) => {
+                logd!(4, "Skipping drift check: {}", e);
+                return Vec::new();
+            }
+        };
+        let corrected = if common::setting::get_config()
+            .statemanager
+            .drift_auto_correct
+        {
+            self.correct_drift(&drifts, false).await
+        } else {
+            0
+        };
+        crate::drift::record_check(&drifts, corrected);
+        drifts
+    }
+
+    /// Detects and corrects the drift of the resources of a
+    /// ForceSynchronization request
+    ///
+    /// Drifted scenarios are always reconciled, stored model states are
+    /// corrected as well for a deep synchronization.
+    pub async fn synchronize(&self, request: ForceSynchronizationRequest) -> Vec<Drift> {
+        let resource_type =
+            ResourceType::try_from(request.resource_type).unwrap_or(ResourceType::Unspecified);
+        logd!(
+            3,
+            "=== FORCE SYNCHRONIZATION: {:?} '{}' (deep: {}) ===",
+            resource_type,
+            request.resource_name,
+            request.deep_sync
+        );
+
+        let drifts = match self
+            .detect_drift(resource_type, &request.resource_name)
+            .await
+        {
+            Ok(drifts) => drifts,
+            Err(e) => {
+                logd!(5, "  Synchronization failed: {}", e);
+                return Vec::new();
+            }
+        };
+        let corrected = self.correct_drift(&drifts, request.deep_sync).await;
+        crate::drift::record_check(&drifts, corrected);
+        logd!(
+            3,
+            "  {} drift(s) found, {} correction(s) issued",
+            drifts.len(),
+            corrected
+        );
+        drifts
+    }
+
+    /// Feeds a recording of the manager inputs back in, one at a time
+    ///
+    /// The snapshot entries are stored as they are, then the StateChange and
+    /// ContainerList messages are processed in the recorded order, so that a
+    /// recording always leads to the same states. `clock`, the clock of the
+    /// manager, is moved to the recorded offset of each message before it
+    /// is processed. With a `speed` above 0 the messages keep their
+    /// recorded pace, sped up by that factor.
+    pub async fn replay(
+        &self,
+        recording: Vec<crate::replay::Recorded>,
+        speed: f64,
+        clock: &common::clock::MockClock,
+    ) {
+        let start = tokio::time::Instant::now();
+        let total = recording.len();
+        let mut offset_ns = 0;
+        for recorded in recording {
+            if let Some(delay) = crate::replay::delay(recorded.offset_ns, start.elapsed(), speed) {
+                tokio::time::sleep(delay).await;
+            }
+            clock.advance(std::time::Duration::from_nanos(
+                recorded.offset_ns.saturating_sub(offset_ns),
+            ));
+            offset_ns = offset_ns.max(recorded.offset_ns);
+            match recorded.message {
+                crate::replay::Message::Start { .. } => {}
+                crate::replay::Message::Snapshot { key, value } => {
+                    if let Err(e) = crate::storage::storage().put(&key, &value).await {
+                        logd!(4, "Replay cannot store {}: {}", key, e);
+                    }
+                }
+                crate::replay::Message::StateChange(state_change) => {
+                    let correlation_id = state_change.correlation_id.clone();
+                    common::correlation::scope(
+                        correlation_id,
+                        self.process_state_change(*state_change),
+                    )
+                    .await;
+                }
+                crate::replay::Message::ContainerList(container_list) => {
+                    self.process_container_list(container_list).await;
+                }
+            }
+        }
+        logd!(3, "Replayed {} recorded message(s)", total);
+    }
+
+    /// Simulates state changes against a snapshot of the state machine
+    ///
+    /// The changes go through the transition rules, conditions and the
+    /// network to model cascade of [`Self::process_state_change`] in order,
+    /// each one seeing the states left by the previous ones. The states they
+    /// would persist are collected instead of written and no action is
+    /// queued, so the tracked states, ETCD and the ActionController are left
+    /// untouched. Every change yields its transition followed by the model
+    /// transition it cascades to, if any.
+    pub async fn simulate(&self, changes: Vec<StateChange>) -> Vec<SimulatedTransition> {
+        let mut snapshot = self.state_machine.lock().await.snapshot();
+        let mut transitions = Vec::new();
+
+        for change in changes {
+            let resource_type =
+                ResourceType::try_from(change.resource_type).unwrap_or(ResourceType::Unspecified);
+            let from_state = snapshot
+                .get_resource_state(&change.resource_name, resource_type)
+                .and_then(|rs| state_mapping::proto_state_name(resource_type, rs.current_state))
+                .map(str::to_string)
+                .unwrap_or_else(|| change.current_state.clone());
+
+            let result = snapshot.process_state_change(change.clone());
+            let to_state = state_mapping::proto_state_name(resource_type, result.new_state)
+                .map(str::to_string)
+                .unwrap_or_else(|| change.current_state.clone());
+
+            let mut writes = std::collections::HashMap::new();
+            if result.is_success() {
+                if let Some(key) = Self::state_key(resource_type, &change.resource_name) {
+                    writes.insert(key, to_state.clone());
+                }
+            }
+            let network_ready = result.is_success()
+                && resource_type == ResourceType::Network
+                && result.new_state == NetworkState::Ready as i32;
+
+            transitions.push(SimulatedTransition {
+                resource_type: change.resource_type,
+                resource_name: change.resource_name.clone(),
+                from_state,
+                to_state,
+                error_code: result.error_code as i32,
+                message: result.message,
+                actions: result.actions_to_execute,
+                writes,
+            });
+
+            // The model held back by its network would be evaluated again
+            if network_ready {
+                if let Some(transition) = self
+                    .simulate_cached_model(&mut snapshot, &change.resource_name)
+                    .await
+                {
+                    transitions.push(transition);
+                }
+            }
+        }
+        transitions
+    }
+
+    /// Simulates [`Self::evaluate_cached_model`] on a snapshot
+    ///
+    /// Returns the model transition with the model and package states it
+    /// would save, `None` if the model has no cached containers or its state
+    /// would not change.
+    async fn simulate_cached_model(
+        &self,
+        snapshot: &mut StateMachine,
+        model_name: &str,
+    ) -> Option<SimulatedTransition> {
+        let cached = self.container_cache.lock().await.node_containers();
+        let model_instances = self.group_instances_by_model(&cached).await;
+        let instances = model_instances.get(model_name)?;
+
+        let from_state = snapshot
+            .get_resource_state(model_name, ResourceType::Model)
+            .map(|rs| rs.current_state)
+            .unwrap_or(ModelState::Created as i32);
+        let result = snapshot.process_model_instances_update(model_name, instances);
+        if !result.is_success() || result.actions_to_execute.is_empty() {
+            return None;
+        }
+
+        let model_state = ModelState::try_from(result.new_state).unwrap_or(ModelState::Unspecified);
+        if model_state == ModelState::Unspecified {
+            return None;
+        }
+        let mut transaction = Transaction::default();
+        transaction.put(&format!("/model/{}/state", model_name), model_state.name());
+        self.evaluate_packages_of_model(&mut transaction, model_name)
+            .await;
+
+        Some(SimulatedTransition {
+            resource_type: ResourceType::Model as i32,
+            resource_name: model_name.to_string(),
+            from_state: state_mapping::proto_state_name(ResourceType::Model, from_state)
+                .unwrap_or("UNKNOWN")
+                .to_string(),
+            to_state: model_state.as_str_name().to_string(),
+            error_code: ErrorCode::Success as i32,
+            message: result.message,
+            // Model state updates are saved, not dispatched as actions
+            actions: Vec::new(),
+            writes: transaction.writes().iter().cloned().collect(),
+        })
+    }
+
+    /// Key under which [`Self::process_state_change`] persists the state of
+    /// a resource, `None` for resource types whose state is not persisted there
+    fn state_key(resource_type: ResourceType, resource_name: &str) -> Option<String> {
+        match resource_type {
+            ResourceType::Scenario => Some(format!("/scenario/{}/state", resource_name)),
+            ResourceType::Network => Some(format!("/network/{}/state", resource_name)),
+            ResourceType::Node => Some(format!("/node/{}/state", resource_name)),
+            ResourceType::Package => Some(format!("/package/{}/state", resource_name)),
+            _ => None,
+        }
+    }
+
     /// Find scenario that contains the given package
     async fn find_scenario_for_package(
         &self,
         package_name: &str,
     ) -> std::result::Result<Option<String>, String> {
         // Get all scenarios from ETCD
-        match common::etcd::get_all_with_prefix("Scenario/").await {
+        match crate::storage::storage()
+            .get_all_with_prefix("Scenario/")
+            .await
+        {
             Ok(scenario_entries) => {
                 for kv in scenario_entries {
                     match serde_yaml::from_str::<common::spec::artifact::Scenario>(&kv.1) {
                         Ok(scenario) => {
                             // Check if this scenario references the package
                             if scenario.get_targets() == package_name {
-                                return Ok(Some(scenario.get_name()));
+                                return Ok(Some(scenario.get_qualified_name()));
                             }
                         }
                         Err(e) => {
@@ -854,6 +1881,8 @@ impl StateManagerManager {
     /// Spawns dedicated async tasks for processing different message types:
     /// 1. Container status processing task
     /// 2. State change processing task
+    /// 3. Single container update processing task, if a receiver was set
+    /// 4. ForceSynchronization processing task, if a receiver was set
     ///
     /// Each task runs independently to ensure optimal throughput and prevent
     /// blocking between different message types.
@@ -870,69 +1899,193 @@ impl StateManagerManager {
         let rx_state_change = Arc::clone(&self.rx_state_change);
 
         // ========================================
-        // CONTAINER STATUS PROCESSING TASK
+        // CONTAINER STATUS PROCESSING TASK
+        // ========================================
+        // Handles ContainerList messages from nodeagent for container monitoring
+        let container_task = {
+            let state_manager = self.clone_for_task();
+            tokio::spawn(async move {
+                loop {
+                    let container_list_opt = {
+                        let mut rx = rx_container.lock().await;
+                        rx.recv().await
+                    };
+                    match container_list_opt {
+                        Some(container_list) => {
+                            // Process container status update with comprehensive analysis
+                            let _busy = common::health::busy("ContainerList");
+                            crate::replay::record_container_list(&container_list);
+                            state_manager.process_container_list(container_list).await;
+                        }
+                        None => {
+                            // Channel closed - graceful shutdown
+                            logd!(
+                                4,
+                                "Container channel closed - shutting down container processing"
+                            );
+                            common::health::closed("ContainerList");
+                            break;
+                        }
+                    }
+                }
+                logd!(4, "ContainerList processing task stopped");
+            })
+        };
+
+        // ========================================
+        // STATE CHANGE PROCESSING TASK
+        // ========================================
+        // Handles StateChange messages from ApiServer, FilterGateway, ActionController
+        let state_change_task = {
+            let state_manager = self.clone_for_task();
+            tokio::spawn(async move {
+                loop {
+                    let state_change_opt = {
+                        let mut rx = rx_state_change.lock().await;
+                        rx.recv().await
+                    };
+                    match state_change_opt {
+                        Some(state_change) => {
+                            // Process state change with comprehensive PICCOLO compliance
+                            let _busy = common::health::busy("StateChange");
+                            crate::replay::record_state_change(&state_change);
+                            crate::pending::processing(&state_change);
+                            let transition_id = state_change.transition_id.clone();
+                            let correlation_id = state_change.correlation_id.clone();
+                            common::correlation::scope(
+                                correlation_id,
+                                state_manager.process_state_change(state_change),
+                            )
+                            .await;
+                            crate::pending::processed(&transition_id);
+                        }
+                        None => {
+                            // Channel closed - graceful shutdown
+                            logd!(
+                                4,
+                                "StateChange channel closed - shutting down state processing"
+                            );
+                            common::health::closed("StateChange");
+                            break;
+                        }
+                    }
+                }
+                logd!(4, "StateChange processing task stopped");
+            })
+        };
+
+        // ========================================
+        // CONTAINER UPDATE PROCESSING TASK
+        // ========================================
+        // Handles single container state updates merged into the container cache
+        let container_update_task = {
+            let state_manager = self.clone_for_task();
+            let rx_container_update = self.rx_container_update.clone();
+            tokio::spawn(async move {
+                let Some(rx_container_update) = rx_container_update else {
+                    return;
+                };
+                loop {
+                    let update_opt = {
+                        let mut rx = rx_container_update.lock().await;
+                        rx.recv().await
+                    };
+                    match update_opt {
+                        Some(update) => {
+                            let _busy = common::health::busy("ContainerUpdate");
+                            state_manager.process_container_update(update).await;
+                        }
+                        None => {
+                            // Channel closed - graceful shutdown
+                            logd!(
+                                4,
+                                "Container update channel closed - shutting down update processing"
+                            );
+                            common::health::closed("ContainerUpdate");
+                            break;
+                        }
+                    }
+                }
+                logd!(4, "Container update processing task stopped");
+            })
+        };
+
+        // ========================================
+        // FORCE SYNCHRONIZATION TASK
         // ========================================
-        // Handles ContainerList messages from nodeagent for container monitoring
-        let container_task = {
+        // Handles ForceSynchronization requests one at a time
+        let sync_task = {
             let state_manager = self.clone_for_task();
+            let rx_sync = self.rx_sync.clone();
             tokio::spawn(async move {
+                let Some(rx_sync) = rx_sync else {
+                    return;
+                };
                 loop {
-                    let container_list_opt = {
-                        let mut rx = rx_container.lock().await;
+                    let request_opt = {
+                        let mut rx = rx_sync.lock().await;
                         rx.recv().await
                     };
-                    match container_list_opt {
-                        Some(container_list) => {
-                            // Process container status update with comprehensive analysis
-                            state_manager.process_container_list(container_list).await;
+                    match request_opt {
+                        Some(request) => {
+                            let _busy = common::health::busy("ForceSynchronization");
+                            state_manager.synchronize(request).await;
                         }
                         None => {
                             // Channel closed - graceful shutdown
                             logd!(
                                 4,
-                                "Container channel closed - shutting down container processing"
+                                "Synchronization channel closed - shutting down synchronization"
                             );
+                            common::health::closed("ForceSynchronization");
                             break;
                         }
                     }
                 }
-                logd!(4, "ContainerList processing task stopped");
+                logd!(4, "Synchronization processing task stopped");
             })
         };
 
         // ========================================
-        // STATE CHANGE PROCESSING TASK
+        // SIMULATION TASK
         // ========================================
-        // Handles StateChange messages from ApiServer, FilterGateway, ActionController
-        let state_change_task = {
+        // Answers dry runs of state changes, nothing is persisted or dispatched
+        let simulation_task = {
             let state_manager = self.clone_for_task();
+            let rx_simulation = self.rx_simulation.clone();
             tokio::spawn(async move {
+                let Some(rx_simulation) = rx_simulation else {
+                    return;
+                };
                 loop {
-                    let state_change_opt = {
-                        let mut rx = rx_state_change.lock().await;
+                    let job_opt = {
+                        let mut rx = rx_simulation.lock().await;
                         rx.recv().await
                     };
-                    match state_change_opt {
-                        Some(state_change) => {
-                            // Process state change with comprehensive PICCOLO compliance
-                            state_manager.process_state_change(state_change).await;
+                    match job_opt {
+                        Some(job) => {
+                            let transitions = state_manager.simulate(job.changes).await;
+                            // The requester may have given up waiting
+                            let _ = job.reply.send(transitions);
                         }
                         None => {
-                            // Channel closed - graceful shutdown
-                            logd!(
-                                4,
-                                "StateChange channel closed - shutting down state processing"
-                            );
+                            logd!(4, "Simulation channel closed - shutting down simulation");
                             break;
                         }
                     }
                 }
-                logd!(4, "StateChange processing task stopped");
+                logd!(4, "Simulation processing task stopped");
             })
         };
 
-        // Wait for both tasks to complete (typically on shutdown)
-        let result = tokio::try_join!(container_task, state_change_task);
+        // Wait for all tasks to complete (typically on shutdown)
+        let result = tokio::try_join!(
+            container_task,
+            state_change_task,
+            container_update_task,
+            sync_task,
+            simulation_task
+        );
         match result {
             Ok(_) => {
                 logd!(3, "All processing tasks completed successfully");
@@ -957,6 +2110,11 @@ impl StateManagerManager {
             state_machine: Arc::clone(&self.state_machine),
             rx_container: Arc::clone(&self.rx_container),
             rx_state_change: Arc::clone(&self.rx_state_change),
+            rx_container_update: self.rx_container_update.clone(),
+            rx_sync: self.rx_sync.clone(),
+            rx_simulation: self.rx_simulation.clone(),
+            container_cache: Arc::clone(&self.container_cache),
+            clock: Arc::clone(&self.clock),
         }
     }
 
@@ -971,8 +2129,11 @@ impl StateManagerManager {
     /// # Lifecycle
     /// 1. Wraps self in Arc for shared ownership across tasks
     /// 2. Spawns the gRPC message processing task
-    /// 3. Waits for processing completion (typically on shutdown)
-    /// 4. Performs cleanup and logs final status
+    /// 3. Spawns the stuck-state watchdog, see [`Self::check_state_timeouts`]
+    /// 4. Spawns the drift detector, see [`Self::check_drift`]
+    /// 5. Waits for processing completion (typically on shutdown) or the loss
+    ///    of the leadership, see [`crate::leader`]
+    /// 6. Stops the watchdog and the drift detector and logs final status
     ///
     /// # Error Handling
     /// - Logs processing errors without panicking
@@ -984,14 +2145,60 @@ impl StateManagerManager {
         let grpc_manager = Arc::clone(&arc_self);
 
         // Spawn the main gRPC processing task
-        let grpc_processor = tokio::spawn(async move {
+        let mut grpc_processor = tokio::spawn(async move {
             if let Err(e) = grpc_manager.process_grpc_requests().await {
                 logd!(5, "Error in gRPC processor: {e:?}");
             }
         });
 
-        // Wait for the processing task to complete
-        let result = grpc_processor.await;
+        // Spawn the stuck-state watchdog, its interval and the timeouts are
+        // read again after a settings reload
+        let watchdog_manager = Arc::clone(&arc_self);
+        let watchdog = tokio::spawn(async move {
+            let mut generation = common::setting::generation();
+            loop {
+                let check_interval = common::setting::get_config()
+                    .statemanager
+                    .timeout_check_interval
+                    .max(1);
+                tokio::time::sleep(std::time::Duration::from_secs(check_interval)).await;
+                if generation != common::setting::generation() {
+                    generation = common::setting::generation();
+                    watchdog_manager.reload_state_timeouts().await;
+                }
+                watchdog_manager.check_state_timeouts().await;
+            }
+        });
+
+        // Spawn the drift detector, disabled with an interval of 0
+        let drift_manager = Arc::clone(&arc_self);
+        let drift_detector = tokio::spawn(async move {
+            loop {
+                let drift_interval = common::setting::get_config()
+                    .statemanager
+                    .drift_check_interval_secs;
+                if drift_interval == 0 {
+                    // A settings reload may enable it later
+                    tokio::time::sleep(DISABLED_DRIFT_POLL).await;
+                    continue;
+                }
+                // Sleeping first skips the check before any NodeAgent reported
+                tokio::time::sleep(std::time::Duration::from_secs(drift_interval)).await;
+                drift_manager.check_drift().await;
+            }
+        });
+
+        // Wait for the processing task to complete, or stop it when another
+        // instance takes over the leadership
+        let result = tokio::select! {
+            result = &mut grpc_processor => result,
+            _ = crate::leader::lost() => {
+                grpc_processor.abort();
+                Ok(())
+            }
+        };
+        watchdog.abort();
+        drift_detector.abort();
         match result {
             Ok(_) => {
                 logd!(4, "StateManagerManager stopped gracefully");
@@ -1018,7 +2225,9 @@ pub async fn run_action_executor(mut receiver: mpsc::UnboundedReceiver<ActionCom
     while let Some(action_command) = receiver.recv().await {
         // Execute action asynchronously without blocking state transitions
         task::spawn(async move {
+            let running = crate::pending::action_started(&action_command);
             execute_action(action_command).await;
+            crate::pending::action_finished(running);
         });
     }
 
@@ -1043,6 +2252,13 @@ async fn execute_action(command: ActionCommand) {
             );
             // Would integrate with policy engine or condition evaluator
         }
+        "stop_condition_evaluation" => {
+            logd!(
+                2,
+                " Stopping condition evaluation for deactivated scenario: {}",
+                command.resource_key
+            );
+        }
         "start_policy_verification" => {
             logd!(
                 2,
@@ -1065,7 +2281,24 @@ async fn execute_action(command: ActionCommand) {
                 " Logging denial and generating alert for scenario: {}",
                 command.resource_key
             );
-            // Would integrate with alerting system
+            let context = |key: &str| command.context.get(key).cloned().unwrap_or_default();
+            let sent = command
+                .context
+                .contains_key("denial_rule")
+                .then(|| DenialReason {
+                    rule: context("denial_rule"),
+                    message: context("denial_message"),
+                    source: context("denial_source"),
+                    timestamp_ns: 0,
+                });
+            let denial = crate::denial::complete(sent.as_ref(), &context("source"), 0);
+            let resource_name = command
+                .context
+                .get("resource_name")
+                .unwrap_or(&command.resource_key);
+            let message = crate::denial::describe(&denial);
+            logd!(4, "Scenario {} {}", resource_name, message);
+            crate::events::alert(ResourceType::Scenario, resource_name, message);
         }
         "start_model_creation_allocate_resources" => {
             logd!(
@@ -1195,6 +2428,73 @@ async fn execute_action(command: ActionCommand) {
             );
             // Would start complete model recreation process
         }
+        "wait_for_network_setup" => {
+            logd!(
+                2,
+                " Holding model until its network is ready: {}",
+                command.resource_key
+            );
+        }
+        "release_dependent_models" => {
+            logd!(
+                2,
+                " Network ready, model may start running: {}",
+                command.resource_key
+            );
+        }
+        "log_network_failure" => {
+            logd!(
+                4,
+                " Network setup failed, model stays created: {}",
+                command.resource_key
+            );
+        }
+        "track_update_progress" => {
+            logd!(
+                2,
+                " Update started, package state follows its steps: {}",
+                command.resource_key
+            );
+        }
+        "announce_canary_promotion" => {
+            logd!(
+                2,
+                " Canary promoted, package runs the new version: {}",
+                command.resource_key
+            );
+        }
+        "alert_canary_rollback" => {
+            let sub_state = command
+                .context
+                .get("sub_state")
+                .map(String::as_str)
+                .unwrap_or_default();
+            logd!(
+                5,
+                "ALERT: Canary of {} failed its evaluation and was rolled back ({})",
+                command.resource_key,
+                sub_state
+            );
+            let package_name = command
+                .resource_key
+                .split_once("::")
+                .map_or(command.resource_key.as_str(), |(_, name)| name);
+            crate::events::alert(
+                command.resource_type,
+                package_name,
+                format!(
+                    "canary failed its evaluation and was rolled back ({})",
+                    sub_state
+                ),
+            );
+        }
+        "log_update_failure" => {
+            logd!(
+                4,
+                " Update failed, package needs reconciliation: {}",
+                command.resource_key
+            );
+        }
         _ => {
             logd!(
                 4,
@@ -1278,8 +2578,10 @@ mod integration_tests {
         action_controller_connection_server::{
             ActionControllerConnection, ActionControllerConnectionServer,
         },
-        CompleteNetworkSettingRequest, CompleteNetworkSettingResponse, ReconcileRequest,
-        ReconcileResponse, TriggerActionRequest, TriggerActionResponse,
+        AutostartNodeRequest, AutostartNodeResponse, CompleteNetworkSettingRequest,
+        CompleteNetworkSettingResponse, DrainNodeRequest, DrainNodeResponse, RebalanceRequest,
+        RebalanceResponse, ReconcileRequest, ReconcileResponse, RelocateNodeModelsRequest,
+        RelocateNodeModelsResponse, TriggerActionRequest, TriggerActionResponse,
     };
     use std::sync::Arc;
     use tonic::{transport::Server, Request, Response, Status};
@@ -1341,10 +2643,39 @@ mod integration_tests {
                 acknowledged: true,
             }))
         }
+
+        async fn relocate_node_models(
+            &self,
+            _request: Request<RelocateNodeModelsRequest>,
+        ) -> std::result::Result<Response<RelocateNodeModelsResponse>, Status> {
+            Ok(Response::new(RelocateNodeModelsResponse::default()))
+        }
+
+        async fn drain_node(
+            &self,
+            _request: Request<DrainNodeRequest>,
+        ) -> std::result::Result<Response<DrainNodeResponse>, Status> {
+            Ok(Response::new(DrainNodeResponse::default()))
+        }
+
+        async fn autostart_node(
+            &self,
+            _request: Request<AutostartNodeRequest>,
+        ) -> std::result::Result<Response<AutostartNodeResponse>, Status> {
+            Ok(Response::new(AutostartNodeResponse::default()))
+        }
+
+        async fn rebalance(
+            &self,
+            _request: Request<RebalanceRequest>,
+        ) -> std::result::Result<Response<RebalanceResponse>, Status> {
+            Ok(Response::new(RebalanceResponse::default()))
+        }
     }
 
     #[tokio::test]
     async fn test_statemanager_actioncontroller_communication() {
+        common::etcd::use_in_memory_store();
         println!("🧪 Testing StateManager → ActionController Communication");
         println!("=========================================================");
 
@@ -1451,6 +2782,7 @@ spec:
 
     #[tokio::test]
     async fn test_statemanager_error_handling() {
+        common::etcd::use_in_memory_store();
         println!("🧪 Testing StateManager Error Handling");
         println!("======================================");
 
@@ -1484,6 +2816,7 @@ mod unit_tests {
     use super::*;
     use crate::types::ActionCommand;
     use common::monitoringserver::{ContainerInfo, ContainerList};
+    use common::spec::k8s::pod::{ANNOTATION_RESTART_POLICY, LABEL_MODEL};
     use std::collections::HashMap;
     use tokio::sync::mpsc;
     use tokio::time::{timeout, Duration};
@@ -1582,6 +2915,39 @@ mod unit_tests {
         assert_eq!(extracted, None);
     }
 
+    #[tokio::test]
+    async fn test_extract_model_name_prefers_model_label() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        let container = ContainerInfo {
+            id: "clabel".to_string(),
+            names: vec!["/model-from-name".to_string()],
+            image: "img".to_string(),
+            state: HashMap::new(),
+            config: HashMap::new(),
+            annotation: HashMap::from([
+                (LABEL_MODEL.to_string(), "labelled".to_string()),
+                ("model".to_string(), "legacy".to_string()),
+            ]),
+            stats: HashMap::new(),
+        };
+        let extracted = manager.extract_model_name_from_container(&container).await;
+        assert_eq!(extracted.as_deref(), Some("labelled"));
+
+        // The name of a container does not make it one of a model
+        let unlabelled = ContainerInfo {
+            annotation: HashMap::new(),
+            names: vec!["/legacy-core_app".to_string()],
+            ..container
+        };
+        let extracted = manager.extract_model_name_from_container(&unlabelled).await;
+        assert_eq!(extracted, None);
+    }
+
     #[tokio::test]
     async fn test_group_containers_by_model_multiple_models() {
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
@@ -1707,11 +3073,90 @@ mod unit_tests {
         assert!(grouped.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_group_instances_by_model_keeps_nodes_apart() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        // Both nodes run the model with the same local container name
+        let container = |status: &str| ContainerInfo {
+            id: format!("id-{}", status),
+            names: vec!["shared_main".to_string()],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::from([
+                ("model".to_string(), "shared".to_string()),
+                (ANNOTATION_RESTART_POLICY.to_string(), "Never".to_string()),
+            ]),
+            stats: HashMap::new(),
+        };
+        let containers = vec![
+            ("node-a".to_string(), container("paused")),
+            ("node-b".to_string(), container("exited")),
+        ];
+
+        let grouped = manager.group_instances_by_model(&containers).await;
+        let instances = &grouped["shared"];
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances["node-a"][0].state["Status"], "paused");
+        assert_eq!(
+            manager
+                .state_machine
+                .lock()
+                .await
+                .evaluate_model_state_from_instances(instances),
+            ModelState::Paused
+        );
+    }
+
+    #[tokio::test]
+    async fn test_group_instances_by_model_keeps_replicas_apart() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        // Two replicas of the model run on the same node
+        let replica = |index: &str, status: &str| ContainerInfo {
+            id: format!("id-{}", index),
+            names: vec![format!("web-{}_main", index)],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::from([
+                (LABEL_MODEL.to_string(), "web".to_string()),
+                (LABEL_REPLICA.to_string(), index.to_string()),
+            ]),
+            stats: HashMap::new(),
+        };
+        let containers = vec![
+            ("node-a".to_string(), replica("0", "running")),
+            ("node-a".to_string(), replica("1", "dead")),
+        ];
+
+        let grouped = manager.group_instances_by_model(&containers).await;
+        let instances = &grouped["web"];
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances["node-a#1"][0].state["Status"], "dead");
+        assert_eq!(
+            manager
+                .state_machine
+                .lock()
+                .await
+                .evaluate_model_state_from_instances(instances),
+            ModelState::Degraded
+        );
+    }
+
     #[tokio::test]
     async fn test_execute_action_many_variants() {
         // Call a selection of known action strings to cover match arms
         let actions = vec![
             "start_condition_evaluation",
+            "stop_condition_evaluation",
             "start_policy_verification",
             "execute_action_on_target_package",
             "log_denial_generate_alert",
@@ -1759,6 +3204,10 @@ mod unit_tests {
             transition_id: "tid".to_string(),
             source: "test".to_string(),
             timestamp_ns: 0,
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         use common::statemanager::ErrorCode;
@@ -1809,12 +3258,141 @@ mod unit_tests {
         let cl = ContainerList {
             node_name: "node1".to_string(),
             containers: vec![c],
+            api_version: common::version::API_VERSION,
         };
 
         // Should run without panic and process the single model
         manager.process_container_list(cl).await;
     }
 
+    #[tokio::test]
+    async fn test_process_container_update_evaluates_model_with_cached_containers() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        let container = |id: &str| common::monitoringserver::ContainerInfo {
+            id: id.to_string(),
+            names: vec![format!("/mupd-{}", id)],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), "running".to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::from([
+                ("model".to_string(), "mupd".to_string()),
+                (ANNOTATION_RESTART_POLICY.to_string(), "Never".to_string()),
+            ]),
+            stats: HashMap::new(),
+        };
+        manager
+            .process_container_list(ContainerList {
+                node_name: "node1".to_string(),
+                containers: vec![container("c1"), container("c2")],
+                api_version: common::version::API_VERSION,
+            })
+            .await;
+
+        let exited = |id: &str| UpdateContainerStateRequest {
+            node_name: "node1".to_string(),
+            container_id: id.to_string(),
+            model_name: String::new(),
+            state: HashMap::from([("Status".to_string(), "exited".to_string())]),
+            timestamp_ns: 1,
+        };
+        let model_state = |manager: &StateManagerManager| {
+            let manager = manager.clone_for_task();
+            async move {
+                manager
+                    .state_machine
+                    .lock()
+                    .await
+                    .get_resource_state("mupd", ResourceType::Model)
+                    .map(|rs| rs.current_state)
+            }
+        };
+
+        // The sibling container is still running
+        manager.process_container_update(exited("c1")).await;
+        assert_eq!(
+            model_state(&manager).await,
+            Some(ModelState::Running as i32)
+        );
+
+        manager.process_container_update(exited("c2")).await;
+        assert_eq!(model_state(&manager).await, Some(ModelState::Exited as i32));
+
+        // Unknown containers without a model name are ignored
+        manager.process_container_update(exited("c3")).await;
+        assert!(manager
+            .container_cache
+            .lock()
+            .await
+            .get("node1", "c3")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_process_container_list_skips_unchanged_models() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        let container = |status: &str| common::monitoringserver::ContainerInfo {
+            id: "c1".to_string(),
+            names: vec!["/mdiff-c1".to_string()],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::from([
+                ("model".to_string(), "mdiff".to_string()),
+                (ANNOTATION_RESTART_POLICY.to_string(), "Never".to_string()),
+            ]),
+            stats: HashMap::new(),
+        };
+        let snapshot = |status: &str| ContainerList {
+            node_name: "node-diff".to_string(),
+            containers: vec![container(status)],
+            api_version: common::version::API_VERSION,
+        };
+        let model_state = || async {
+            manager
+                .state_machine
+                .lock()
+                .await
+                .get_resource_state("mdiff", ResourceType::Model)
+                .map(|rs| rs.current_state)
+        };
+
+        manager.process_container_list(snapshot("running")).await;
+        assert_eq!(model_state().await, Some(ModelState::Running as i32));
+
+        // Move the model away from what the cached containers say
+        let exited = container("exited");
+        manager
+            .state_machine
+            .lock()
+            .await
+            .process_model_state_update("mdiff", &[&exited]);
+
+        // An identical snapshot is not evaluated again
+        manager.process_container_list(snapshot("running")).await;
+        assert_eq!(model_state().await, Some(ModelState::Exited as i32));
+
+        manager.process_container_list(snapshot("paused")).await;
+        assert_eq!(model_state().await, Some(ModelState::Paused as i32));
+
+        // A snapshot without the containers of the model exits it
+        manager
+            .process_container_list(ContainerList {
+                node_name: "node-diff".to_string(),
+                containers: Vec::new(),
+                api_version: common::version::API_VERSION,
+            })
+            .await;
+        assert_eq!(model_state().await, Some(ModelState::Exited as i32));
+    }
+
     #[tokio::test]
     async fn test_process_state_change_invalid_resource_type_returns_early() {
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
@@ -1832,6 +3410,10 @@ mod unit_tests {
             transition_id: "t".to_string(),
             source: "s".to_string(),
             timestamp_ns: 0,
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         manager.process_state_change(bad).await;
@@ -1847,11 +3429,11 @@ mod unit_tests {
 
         // Attempt to save a model state (success path)
         let res = manager
-            .save_model_state_to_etcd("test-model", common::statemanager::ModelState::Running)
+            .save_model_state_cascade("test-model", common::statemanager::ModelState::Running)
             .await;
         assert!(
             res.is_ok(),
-            "save_model_state_to_etcd should succeed: {:?}",
+            "save_model_state_cascade should succeed: {:?}",
             res
         );
 
@@ -1867,7 +3449,7 @@ mod unit_tests {
     }
 
     #[tokio::test]
-    async fn test_save_model_state_to_etcd_failure_on_long_key() {
+    async fn test_save_model_state_cascade_failure_on_long_key() {
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, rx_state_change) =
             mpsc::channel::<common::statemanager::StateChange>(1);
@@ -1878,15 +3460,33 @@ mod unit_tests {
         let long_name = "a".repeat(2000);
 
         let res = manager
-            .save_model_state_to_etcd(&long_name, common::statemanager::ModelState::Running)
+            .save_model_state_cascade(&long_name, common::statemanager::ModelState::Running)
             .await;
 
         assert!(
             res.is_err(),
-            "Expected save_model_state_to_etcd to fail for long key"
+            "Expected save_model_state_cascade to fail for long key"
         );
     }
 
+    #[tokio::test]
+    async fn test_save_model_state_cascade_skips_unspecified() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+        let key = "/model/unspecified-model/state";
+        crate::storage::storage().put(key, "Running").await.unwrap();
+
+        let res = manager
+            .save_model_state_cascade("unspecified-model", ModelState::Unspecified)
+            .await;
+
+        assert!(res.is_err(), "Expected the unspecified state to be refused");
+        assert_eq!(crate::storage::storage().get(key).await.unwrap(), "Running");
+    }
+
     #[tokio::test]
     async fn test_save_package_state_to_etcd_failure_on_long_key() {
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
@@ -1944,6 +3544,7 @@ mod unit_tests {
         let c = ContainerList {
             node_name: "node-x".to_string(),
             containers: Vec::new(),
+            api_version: common::version::API_VERSION,
         };
         tx_container
             .send(c)
@@ -1958,6 +3559,10 @@ mod unit_tests {
             transition_id: "t1".to_string(),
             source: "test".to_string(),
             timestamp_ns: 0,
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         tx_state_change
@@ -1976,6 +3581,7 @@ mod unit_tests {
 
     #[tokio::test]
     async fn test_manager_process_state_change_scenario_saves_etcd() {
+        common::etcd::use_in_memory_store();
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, rx_state_change) =
             mpsc::channel::<common::statemanager::StateChange>(1);
@@ -1991,6 +3597,10 @@ mod unit_tests {
             transition_id: "t-etcd".to_string(),
             timestamp_ns: 1,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         manager.process_state_change(sc.clone()).await;
@@ -2004,7 +3614,184 @@ mod unit_tests {
     }
 
     #[tokio::test]
-    async fn test_trigger_package_state_evaluation_no_packages() {
+    async fn test_simulate_chains_changes_without_touching_state_machine() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        let change = |current: &str, target: &str| StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "simulated-scenario".to_string(),
+            current_state: current.to_string(),
+            target_state: target.to_string(),
+            transition_id: format!("t-{target}"),
+            timestamp_ns: 1,
+            source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+        let transitions = manager
+            .simulate(vec![
+                change("Idle", "Waiting"),
+                // The tracked state is Waiting now, whatever the request claims
+                change("Idle", "Satisfied"),
+                change("Satisfied", "Completed"),
+            ])
+            .await;
+
+        assert_eq!(transitions.len(), 3);
+        assert_eq!(transitions[0].from_state, "Idle");
+        assert_eq!(transitions[0].to_state, "SCENARIO_STATE_WAITING");
+        assert_eq!(transitions[0].actions, vec!["start_condition_evaluation"]);
+        assert_eq!(
+            transitions[0]
+                .writes
+                .get("/scenario/simulated-scenario/state"),
+            Some(&"SCENARIO_STATE_WAITING".to_string())
+        );
+        assert_eq!(transitions[1].from_state, "SCENARIO_STATE_WAITING");
+        assert_eq!(transitions[1].to_state, "SCENARIO_STATE_SATISFIED");
+        assert_eq!(
+            transitions[2].error_code,
+            ErrorCode::InvalidStateTransition as i32
+        );
+        assert_eq!(transitions[2].to_state, "SCENARIO_STATE_SATISFIED");
+        assert!(transitions[2].actions.is_empty());
+        assert!(transitions[2].writes.is_empty());
+
+        let state_machine = manager.state_machine.lock().await;
+        assert!(state_machine
+            .get_resource_state("simulated-scenario", ResourceType::Scenario)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scenario_outside_its_window_waits_until_it_opens() {
+        common::etcd::use_in_memory_store();
+        common::etcd::put(
+            "Scenario/night-scenario",
+            "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: night-scenario\nspec:\n  action: launch\n  target: night-scenario\n  activationWindows:\n    - start: \"22:00\"\n      end: \"06:00\"\n",
+        )
+        .await
+        .unwrap();
+        let noon = "2024-01-01T12:00:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap();
+        let clock = Arc::new(common::clock::MockClock::new(noon));
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change)
+            .await
+            .with_clock(clock.clone());
+
+        let change = |current: &str, target: &str| StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "night-scenario".to_string(),
+            current_state: current.to_string(),
+            target_state: target.to_string(),
+            transition_id: format!("t-night-{}", target),
+            timestamp_ns: 1,
+            source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+        let state = || async {
+            manager
+                .state_machine
+                .lock()
+                .await
+                .get_resource_state("night-scenario", ResourceType::Scenario)
+                .map(|rs| rs.current_state)
+        };
+
+        // At noon the scenario goes back to waiting for its condition
+        manager
+            .process_state_change(change("Idle", "Waiting"))
+            .await;
+        manager
+            .process_state_change(change("Waiting", "Satisfied"))
+            .await;
+        manager
+            .process_state_change(change("Satisfied", "Allowed"))
+            .await;
+        assert_eq!(state().await, Some(ScenarioState::Waiting as i32));
+        let denial = crate::denial::get("night-scenario").await.unwrap().unwrap();
+        assert_eq!(denial.rule, crate::exclusion::RULE_ACTIVATION_WINDOW);
+
+        // Met again once the window is open, the scenario is allowed
+        clock.advance(std::time::Duration::from_secs(11 * 3600));
+        manager
+            .process_state_change(change("Waiting", "Satisfied"))
+            .await;
+        manager
+            .process_state_change(change("Satisfied", "Allowed"))
+            .await;
+        assert_eq!(state().await, Some(ScenarioState::Allowed as i32));
+        assert_eq!(crate::denial::get("night-scenario").await.unwrap(), None);
+
+        common::etcd::delete("Scenario/night-scenario")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_state_timeouts_fails_stuck_scenario() {
+        let (_tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
+        let (_tx_state_change, rx_state_change) =
+            mpsc::channel::<common::statemanager::StateChange>(1);
+        let manager = StateManagerManager::new(rx_container, rx_state_change).await;
+
+        manager
+            .process_state_change(StateChange {
+                resource_type: ResourceType::Scenario as i32,
+                resource_name: "timeout-scenario".to_string(),
+                current_state: "Idle".to_string(),
+                target_state: "Waiting".to_string(),
+                transition_id: "t-timeout".to_string(),
+                timestamp_ns: 1,
+                source: "unittest".to_string(),
+                correlation_id: String::new(),
+                sub_state: String::new(),
+                denial: None,
+                metadata: Default::default(),
+            })
+            .await;
+        assert!(manager.check_state_timeouts().await.is_empty());
+
+        manager.state_machine.lock().await.set_state_timeout(
+            ResourceType::Scenario,
+            ScenarioState::Waiting as i32,
+            std::time::Duration::ZERO,
+        );
+        let events = manager.check_state_timeouts().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].to_state, ScenarioState::Denied as i32);
+
+        let state_machine = manager.state_machine.lock().await;
+        let state = state_machine
+            .get_resource_state("timeout-scenario", ResourceType::Scenario)
+            .unwrap();
+        assert_eq!(state.current_state, ScenarioState::Denied as i32);
+        drop(state_machine);
+
+        let denial = crate::denial::get("timeout-scenario")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(denial.rule, crate::denial::RULE_TIMEOUT);
+        assert_eq!(denial.source, "statemanager");
+    }
+
+    #[tokio::test]
+    async fn test_save_model_state_cascade_no_packages() {
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, rx_state_change) =
             mpsc::channel::<common::statemanager::StateChange>(1);
@@ -2014,14 +3801,16 @@ mod unit_tests {
         // Ensure no packages exist for this test model
         let _ = common::etcd::delete("Package/no-packages").await;
 
-        // Should run without panic even if no packages found
-        manager
-            .trigger_package_state_evaluation("no-packages")
+        // Should save the model state alone if no packages found
+        let res = manager
+            .save_model_state_cascade("no-packages", common::statemanager::ModelState::Running)
             .await;
+        assert!(res.is_ok(), "{:?}", res);
     }
 
     #[tokio::test]
-    async fn test_trigger_package_state_evaluation_updates_and_attempts_reconcile() {
+    async fn test_save_model_state_cascade_updates_and_attempts_reconcile() {
+        common::etcd::use_in_memory_store();
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, rx_state_change) =
             mpsc::channel::<common::statemanager::StateChange>(1);
@@ -2033,13 +3822,13 @@ mod unit_tests {
         let pkg_yaml = r#"{"apiVersion":"v1","kind":"Package","metadata":{"name":"pkg-update"},"spec":{"pattern":[],"models":[{"name":"mup","node":"n","resources":{"volume":"","network":"","realtime":false}}]}}"#;
         let _ = common::etcd::put(pkg_key, pkg_yaml).await;
 
-        // Set model state to Dead
-        let _ = common::etcd::put("/model/mup/state", "Dead").await;
         // Set current package state to running so a change is detected
         let _ = common::etcd::put("/package/pkg-update/state", "running").await;
 
-        // Trigger evaluation
-        manager.trigger_package_state_evaluation("mup").await;
+        // Save the model as Dead, the package state is saved with it
+        let _ = manager
+            .save_model_state_cascade("mup", common::statemanager::ModelState::Dead)
+            .await;
 
         // After evaluation, the package state should be updated (Error expected)
         let state = StateMachine::get_current_package_state("pkg-update").await;
@@ -2049,6 +3838,7 @@ mod unit_tests {
 
     #[tokio::test]
     async fn test_find_scenario_for_package_no_scenarios() {
+        common::etcd::use_in_memory_store();
         let (tx_container, rx_container) = mpsc::channel::<ContainerList>(1);
         let (tx_state_change, rx_state_change) =
             mpsc::channel::<common::statemanager::StateChange>(1);
@@ -7,10 +7,14 @@
 //!
 //! This module provides the public interface for the StateManager component
 
+pub mod container_cache;
+pub mod dedup;
 pub mod grpc;
 pub mod manager;
+pub mod queue;
 pub mod state_machine;
 pub mod types;
+pub mod vehicle_mode;
 
 // Re-export main types for easier access
 pub use manager::StateManagerManager;
@@ -0,0 +1,778 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Event hooks notifying external systems of state transitions
+//!
+//! The hooks of `statemanager.hooks` in settings.yaml select transitions by
+//! resource type and new state, e.g. `package:error`, and optionally by
+//! resource name. A selected transition is posted to the webhook of a hook
+//! and/or published to its MQTT topic, as JSON or rendered from the template
+//! of the hook.
+//!
+//! Deliveries are queued and sent by [`run_dispatcher`], so that a slow
+//! endpoint never holds up a transition. Failed deliveries are retried with a
+//! back-off doubling from [`BACKOFF_BASE`], up to
+//! `statemanager.hook_retry_attempts` attempts; once
+//! `statemanager.hook_retry_buffer` deliveries wait, the oldest is dropped.
+
+use common::logd;
+use common::setting::HookSettings;
+use common::state_mapping;
+use common::statemanager::{ResourceType, TransitionRecord};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+/// Transitions of hooks that do not select any
+const DEFAULT_EVENTS: [&str; 3] = ["package:error", "scenario:denied", "model:crashloopbackoff"];
+
+/// Time allowed for a single delivery
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait before the first retry of a delivery, doubled for every retry
+pub const BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Longest wait between retries of a delivery
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Prefix of the client identifier of the StateManager at MQTT brokers
+const MQTT_CLIENT_PREFIX: &str = "piccolo-statemanager";
+
+/// Identifier of the only PUBLISH sent over an MQTT connection
+const MQTT_PACKET_ID: u16 = 1;
+
+/// A state transition announced to the hooks
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookEvent {
+    pub resource_type: &'static str,
+    pub resource_name: String,
+    /// Empty when the previous state is not known
+    pub from_state: &'static str,
+    pub to_state: &'static str,
+    pub transition_id: String,
+    /// RFC 3339 time the transition was announced
+    pub timestamp: String,
+}
+
+impl HookEvent {
+    /// Payload of the event, rendered from a template or as JSON
+    ///
+    /// A template that is JSON has the placeholders of its strings filled
+    /// in and is serialized again, so that the fields are escaped.
+    fn payload(&self, template: Option<&str>) -> String {
+        match template {
+            Some(template) => match serde_json::from_str::<serde_json::Value>(template) {
+                Ok(mut value) => {
+                    self.fill(&mut value);
+                    value.to_string()
+                }
+                Err(_) => self.render(template),
+            },
+            None => serde_json::json!({
+                "resource_type": self.resource_type,
+                "resource_name": self.resource_name,
+                "from_state": self.from_state,
+                "to_state": self.to_state,
+                "transition_id": self.transition_id,
+                "timestamp": self.timestamp,
+            })
+            .to_string(),
+        }
+    }
+
+    /// Text with the placeholders of the event filled in
+    fn render(&self, text: &str) -> String {
+        text.replace("{resource_type}", self.resource_type)
+            .replace("{resource_name}", &self.resource_name)
+            .replace("{from_state}", self.from_state)
+            .replace("{to_state}", self.to_state)
+            .replace("{transition_id}", &self.transition_id)
+            .replace("{timestamp}", &self.timestamp)
+    }
+
+    /// Fill in the placeholders of every string of a JSON template
+    fn fill(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => *text = self.render(text),
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.fill(item)),
+            serde_json::Value::Object(fields) => {
+                fields.values_mut().for_each(|field| self.fill(field))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Name of a resource type in event filters and exported records
+pub fn type_name(resource_type: ResourceType) -> &'static str {
+    match resource_type {
+        ResourceType::Scenario => "scenario",
+        ResourceType::Package => "package",
+        ResourceType::Model => "model",
+        ResourceType::Volume => "volume",
+        ResourceType::Network => "network",
+        ResourceType::Node => "node",
+        ResourceType::Unspecified => "unspecified",
+    }
+}
+
+/// Lowercase without separators, so that `crash_loop_back_off` selects
+/// `CrashLoopBackOff`
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether a hook selects an event
+fn selects(hook: &HookSettings, event: &HookEvent) -> bool {
+    if !hook.resources.is_empty() && !hook.resources.contains(&event.resource_name) {
+        return false;
+    }
+    let resource_type = normalize(event.resource_type);
+    let state = normalize(event.to_state);
+    let selected = |filter: &str| {
+        let (filter_type, filter_state) = filter.split_once(':').unwrap_or((filter, "*"));
+        (filter_type == "*" || normalize(filter_type) == resource_type)
+            && (filter_state == "*" || normalize(filter_state) == state)
+    };
+    if hook.events.is_empty() {
+        DEFAULT_EVENTS.iter().any(|filter| selected(filter))
+    } else {
+        hook.events.iter().any(|filter| selected(filter))
+    }
+}
+
+/// Where a payload is sent
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Webhook(String),
+    Mqtt { broker: String, topic: String },
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Webhook(url) => write!(f, "{}", url),
+            Target::Mqtt { broker, topic } => write!(f, "mqtt://{}/{}", broker, topic),
+        }
+    }
+}
+
+/// Payload waiting to be sent to one target of a hook
+#[derive(Debug)]
+struct Delivery {
+    hook: String,
+    target: Target,
+    payload: String,
+    /// Failed attempts so far
+    attempts: u32,
+    due: Instant,
+}
+
+/// Deliveries of the hooks selecting an event
+fn deliveries(hooks: &[HookSettings], event: &HookEvent, now: Instant) -> Vec<Delivery> {
+    let mut deliveries = Vec::new();
+    for hook in hooks.iter().filter(|hook| selects(hook, event)) {
+        let payload = event.payload(hook.template.as_deref());
+        let mut targets = Vec::new();
+        if let Some(url) = &hook.webhook {
+            targets.push(Target::Webhook(url.clone()));
+        }
+        if let Some(broker) = &hook.mqtt_broker {
+            targets.push(Target::Mqtt {
+                broker: broker.clone(),
+                topic: hook.mqtt_topic.clone(),
+            });
+        }
+        for target in targets {
+            deliveries.push(Delivery {
+                hook: hook.name.clone(),
+                target,
+                payload: payload.clone(),
+                attempts: 0,
+                due: now,
+            });
+        }
+    }
+    deliveries
+}
+
+/// Counters of the hook deliveries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HookStats {
+    pub capacity: usize,
+    /// Deliveries waiting to be sent or retried
+    pub pending: usize,
+    pub delivered: u64,
+    /// Failed attempts that were queued for retry
+    pub retried: u64,
+    /// Deliveries given up after their last attempt failed
+    pub failed: u64,
+    /// Deliveries dropped because the queue was full
+    pub dropped: u64,
+}
+
+/// Deliveries waiting for their turn
+#[derive(Debug)]
+struct Outbox {
+    max_attempts: u32,
+    pending: VecDeque<Delivery>,
+    stats: HookStats,
+}
+
+impl Outbox {
+    fn new(capacity: usize, max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            pending: VecDeque::new(),
+            stats: HookStats {
+                capacity,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Queue a delivery, dropping the oldest one of a full queue
+    fn push(&mut self, delivery: Delivery) {
+        if self.pending.len() >= self.stats.capacity {
+            if let Some(dropped) = self.pending.pop_front() {
+                self.stats.dropped += 1;
+                logd!(
+                    4,
+                    "[Hooks] Queue full, dropped delivery of hook {} to {}",
+                    dropped.hook,
+                    dropped.target
+                );
+            }
+        }
+        if self.stats.capacity > 0 {
+            self.pending.push_back(delivery);
+        } else {
+            self.stats.dropped += 1;
+        }
+    }
+
+    /// Take the deliveries that are due, in order
+    fn take_due(&mut self, now: Instant) -> Vec<Delivery> {
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|delivery| delivery.due <= now);
+        self.pending = waiting.into();
+        due
+    }
+
+    /// When the next delivery is due
+    fn next_due(&self) -> Option<Instant> {
+        self.pending.iter().map(|delivery| delivery.due).min()
+    }
+
+    /// Record a failed attempt, queueing the delivery for retry unless it
+    /// has no attempts left
+    ///
+    /// Returns whether the delivery was queued again.
+    fn retry(&mut self, mut delivery: Delivery, now: Instant) -> bool {
+        delivery.attempts += 1;
+        if delivery.attempts >= self.max_attempts {
+            self.stats.failed += 1;
+            return false;
+        }
+        let backoff = BACKOFF_BASE.saturating_mul(1 << (delivery.attempts - 1).min(16));
+        delivery.due = now + backoff.min(BACKOFF_MAX);
+        self.stats.retried += 1;
+        self.push(delivery);
+        true
+    }
+
+    fn stats(&self) -> HookStats {
+        HookStats {
+            pending: self.pending.len(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+fn outbox() -> &'static Mutex<Outbox> {
+    static OUTBOX: OnceLock<Mutex<Outbox>> = OnceLock::new();
+    OUTBOX.get_or_init(|| {
+        let settings = &common::setting::get_config().statemanager;
+        Mutex::new(Outbox::new(
+            settings.hook_retry_buffer,
+            settings.hook_retry_attempts,
+        ))
+    })
+}
+
+fn with_outbox<R>(f: impl FnOnce(&mut Outbox) -> R) -> R {
+    let mut outbox = outbox().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut outbox)
+}
+
+/// Current point in time of the [`common::clock`], for delivery timers
+fn now() -> Instant {
+    Instant::from_std(common::clock::instant())
+}
+
+/// Wakes the dispatcher when deliveries are queued
+fn queued() -> &'static Notify {
+    static QUEUED: OnceLock<Notify> = OnceLock::new();
+    QUEUED.get_or_init(Notify::new)
+}
+
+/// Announce a state transition to the hooks selecting it, and record it
+/// for the telemetry export
+///
+/// ### Parameters
+/// * `resource_type: ResourceType` - type of the resource
+/// * `resource_name: &str` - name of the resource
+/// * `from_state: Option<i32>` - previous proto state, if known
+/// * `to_state: i32` - new proto state
+/// * `transition_id: &str` - identifier of the transition
+/// * `metadata: &HashMap<String, String>` - metadata of the StateChange
+///   that caused the transition, empty for internal transitions
+pub fn notify(
+    resource_type: ResourceType,
+    resource_name: &str,
+    from_state: Option<i32>,
+    to_state: i32,
+    transition_id: &str,
+    metadata: &HashMap<String, String>,
+) {
+    if from_state == Some(to_state) {
+        return;
+    }
+    let timestamp = common::clock::now_utc();
+    let event = HookEvent {
+        resource_type: type_name(resource_type),
+        resource_name: resource_name.to_string(),
+        from_state: from_state
+            .and_then(|state| state_mapping::state_name(resource_type, state))
+            .unwrap_or_default(),
+        to_state: state_mapping::state_name(resource_type, to_state).unwrap_or("Unknown"),
+        transition_id: transition_id.to_string(),
+        timestamp: timestamp.to_rfc3339(),
+    };
+    crate::events::transition(TransitionRecord {
+        resource_type: event.resource_type.to_string(),
+        resource_name: event.resource_name.clone(),
+        from_state: event.from_state.to_string(),
+        to_state: event.to_state.to_string(),
+        transition_id: event.transition_id.clone(),
+        timestamp_ns: timestamp.timestamp_nanos_opt().unwrap_or_default(),
+        health: crate::health::of(resource_type, resource_name),
+        metadata: metadata.clone(),
+    });
+
+    let hooks = &common::setting::get_config().statemanager.hooks;
+
+    let deliveries = deliveries(hooks, &event, now());
+    if deliveries.is_empty() {
+        return;
+    }
+    logd!(
+        2,
+        "[Hooks] {} '{}' {} -> {}, {} delivery(ies) queued",
+        event.resource_type,
+        event.resource_name,
+        event.from_state,
+        event.to_state,
+        deliveries.len()
+    );
+    with_outbox(|outbox| {
+        for delivery in deliveries {
+            outbox.push(delivery);
+        }
+    });
+    queued().notify_one();
+}
+
+/// Send the queued deliveries as they become due
+pub async fn run_dispatcher() {
+    loop {
+        let due = with_outbox(|outbox| outbox.take_due(now()));
+        for delivery in due {
+            let result = tokio::time::timeout(
+                DELIVERY_TIMEOUT,
+                deliver(&delivery.target, &delivery.payload),
+            )
+            .await
+            .unwrap_or_else(|_| Err("timed out".to_string()));
+
+            match result {
+                Ok(()) => with_outbox(|outbox| outbox.stats.delivered += 1),
+                Err(e) => {
+                    let (hook, target) = (delivery.hook.clone(), delivery.target.to_string());
+                    if with_outbox(|outbox| outbox.retry(delivery, now())) {
+                        logd!(
+                            4,
+                            "[Hooks] Delivery of hook {} to {} failed, retrying: {}",
+                            hook,
+                            target,
+                            e
+                        );
+                    } else {
+                        logd!(
+                            5,
+                            "[Hooks] Delivery of hook {} to {} failed, giving up: {}",
+                            hook,
+                            target,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+
+        match with_outbox(|outbox| outbox.next_due()) {
+            Some(due) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(due) => {}
+                    _ = queued().notified() => {}
+                }
+            }
+            None => queued().notified().await,
+        }
+    }
+}
+
+/// Counters of the hook deliveries
+pub fn stats() -> HookStats {
+    with_outbox(|outbox| outbox.stats())
+}
+
+async fn deliver(target: &Target, payload: &str) -> Result<(), String> {
+    match target {
+        Target::Webhook(url) => post(url, payload).await,
+        Target::Mqtt { broker, topic } => publish(broker, topic, payload.as_bytes()).await,
+    }
+}
+
+/// Post a payload to a webhook, which must answer with a 2xx status
+async fn post(url: &str, payload: &str) -> Result<(), String> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let client = CLIENT.get_or_init(reqwest::Client::new);
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("answered {}", response.status()))
+    }
+}
+
+/// Append the MQTT variable length encoding of the length of a packet
+fn push_remaining_length(packet: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+/// Append a length prefixed MQTT string
+fn push_string(body: &mut Vec<u8>, value: &str) {
+    body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    body.extend_from_slice(value.as_bytes());
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    push_remaining_length(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// MQTT 3.1.1 CONNECT with a clean session and no credentials
+fn connect_packet(client_id: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, "MQTT");
+    body.push(4); // protocol level 3.1.1
+    body.push(0x02); // clean session
+    body.extend_from_slice(&60u16.to_be_bytes()); // keep alive
+    push_string(&mut body, client_id);
+    packet(0x10, &body)
+}
+
+/// Client identifier of this instance, a broker drops the older of two
+/// connections with the same identifier
+fn mqtt_client_id() -> String {
+    format!("{}-{}", MQTT_CLIENT_PREFIX, crate::leader::instance_id())
+}
+
+/// MQTT PUBLISH at QoS 1, which the broker acknowledges with a PUBACK
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, topic);
+    body.extend_from_slice(&MQTT_PACKET_ID.to_be_bytes());
+    body.extend_from_slice(payload);
+    packet(0x32, &body)
+}
+
+/// Publish a payload to a topic of an MQTT broker over a short lived
+/// connection, which succeeds once the broker acknowledged it
+async fn publish(broker: &str, topic: &str, payload: &[u8]) -> Result<(), String> {
+    let mut stream = tokio::net::TcpStream::connect(broker)
+        .await
+        .map_err(|e| e.to_string())?;
+    stream
+        .write_all(&connect_packet(&mqtt_client_id()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut connack = [0u8; 4];
+    stream
+        .read_exact(&mut connack)
+        .await
+        .map_err(|e| e.to_string())?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(format!("broker refused connection (code {})", connack[3]));
+    }
+    stream
+        .write_all(&publish_packet(topic, payload))
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut puback = [0u8; 4];
+    stream
+        .read_exact(&mut puback)
+        .await
+        .map_err(|e| format!("broker did not acknowledge the publish: {}", e))?;
+    if puback[0] != 0x40 || puback[2..] != MQTT_PACKET_ID.to_be_bytes() {
+        return Err(format!("broker answered {:02X?} to the publish", puback));
+    }
+    stream
+        .write_all(&[0xE0, 0x00]) // DISCONNECT
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(resource_type: &'static str, name: &str, to_state: &'static str) -> HookEvent {
+        HookEvent {
+            resource_type,
+            resource_name: name.to_string(),
+            from_state: "Running",
+            to_state,
+            transition_id: "t-1".to_string(),
+            timestamp: "2024-01-01T00:00:00+00:00".to_string(),
+        }
+    }
+
+    fn hook(events: &[&str], resources: &[&str]) -> HookSettings {
+        HookSettings {
+            name: "fleet".to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+            resources: resources.iter().map(|r| r.to_string()).collect(),
+            webhook: Some("http://fleet/events".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_events_and_filters() {
+        let all = hook(&[], &[]);
+        assert!(selects(&all, &event("package", "p", "Error")));
+        assert!(selects(&all, &event("model", "m", "CrashLoopBackOff")));
+        assert!(!selects(&all, &event("model", "m", "Dead")));
+
+        let models = hook(&["model:*", "scenario:crash_loop_back_off"], &["m"]);
+        assert!(selects(&models, &event("model", "m", "Dead")));
+        assert!(!selects(&models, &event("model", "other", "Dead")));
+        assert!(!selects(&models, &event("scenario", "m", "Denied")));
+
+        let errors = hook(&["*:Error"], &[]);
+        assert!(selects(&errors, &event("package", "p", "Error")));
+        assert!(!selects(&errors, &event("package", "p", "Running")));
+    }
+
+    #[test]
+    fn test_payload_from_template_or_json() {
+        let event = event("package", "p", "Error");
+        assert_eq!(
+            event.payload(Some(
+                "{resource_type}/{resource_name}: {from_state} -> {to_state}"
+            )),
+            "package/p: Running -> Error"
+        );
+        let json: serde_json::Value = serde_json::from_str(&event.payload(None)).unwrap();
+        assert_eq!(json["resource_name"], "p");
+        assert_eq!(json["to_state"], "Error");
+        assert_eq!(json["transition_id"], "t-1");
+
+        let mut quoted = event.clone();
+        quoted.resource_name = "p\"}, \"x\": \"y".to_string();
+        let payload = quoted.payload(Some(
+            r#"{"name": "{resource_name}", "tags": ["{to_state}"]}"#,
+        ));
+        let json: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(json["name"], quoted.resource_name);
+        assert_eq!(json["tags"][0], "Error");
+        assert!(json.get("x").is_none());
+    }
+
+    #[test]
+    fn test_deliveries_per_target() {
+        let mut both = hook(&["package:error"], &[]);
+        both.mqtt_broker = Some("broker:1883".to_string());
+        both.mqtt_topic = "vehicle/events".to_string();
+        let now = Instant::now();
+        let deliveries = deliveries(
+            &[both, hook(&["model:*"], &[])],
+            &event("package", "p", "Error"),
+            now,
+        );
+        let targets: Vec<String> = deliveries.iter().map(|d| d.target.to_string()).collect();
+        assert_eq!(
+            targets,
+            vec!["http://fleet/events", "mqtt://broker:1883/vehicle/events"]
+        );
+    }
+
+    #[test]
+    fn test_retries_back_off_until_attempts_run_out() {
+        let now = Instant::now();
+        let mut outbox = Outbox::new(10, 3);
+        let delivery = deliveries(&[hook(&[], &[])], &event("package", "p", "Error"), now)
+            .pop()
+            .unwrap();
+        outbox.push(delivery);
+
+        let delivery = outbox.take_due(now).pop().unwrap();
+        assert!(outbox.retry(delivery, now));
+        assert_eq!(outbox.next_due(), Some(now + BACKOFF_BASE));
+        assert!(outbox.take_due(now).is_empty());
+
+        let later = now + BACKOFF_BASE;
+        let delivery = outbox.take_due(later).pop().unwrap();
+        assert!(outbox.retry(delivery, later));
+        assert_eq!(outbox.next_due(), Some(later + BACKOFF_BASE * 2));
+
+        let delivery = outbox.take_due(later + BACKOFF_BASE * 2).pop().unwrap();
+        assert!(!outbox.retry(delivery, later));
+        let stats = outbox.stats();
+        assert_eq!((stats.pending, stats.retried, stats.failed), (0, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_full_outbox_drops_the_oldest() {
+        let now = Instant::now();
+        let mut outbox = Outbox::new(1, 3);
+        for name in ["a", "b"] {
+            for delivery in deliveries(&[hook(&[], &[])], &event("package", name, "Error"), now) {
+                outbox.push(delivery);
+            }
+        }
+        let due = outbox.take_due(now);
+        assert_eq!(due.len(), 1);
+        assert!(due[0].payload.contains("\"b\""));
+        assert_eq!(outbox.stats().dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_transition_record_carries_metadata() {
+        let mut events = crate::events::subscribe();
+        let metadata = HashMap::from([("approved_by".to_string(), "operator".to_string())]);
+        notify(
+            ResourceType::Scenario,
+            "notifier-metadata",
+            None,
+            common::statemanager::ScenarioState::Waiting as i32,
+            "t-metadata",
+            &metadata,
+        );
+
+        loop {
+            let event = events.recv().await.unwrap();
+            if let Some(common::statemanager::state_change_event::Event::Transition(record)) =
+                event.event
+            {
+                if record.transition_id == "t-metadata" {
+                    assert_eq!(record.metadata, metadata);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mqtt_remaining_length() {
+        let encode = |len| {
+            let mut bytes = Vec::new();
+            push_remaining_length(&mut bytes, len);
+            bytes
+        };
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(127), vec![0x7F]);
+        assert_eq!(encode(128), vec![0x80, 0x01]);
+        assert_eq!(encode(16_383), vec![0xFF, 0x7F]);
+    }
+
+    /// Broker accepting one connection, answering the publish with `puback`
+    /// or closing the connection when it is empty
+    async fn fake_broker(
+        puback: &'static [u8],
+    ) -> (String, tokio::task::JoinHandle<(Vec<u8>, Vec<u8>, Vec<u8>)>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let broker = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut connect = vec![0u8; connect_packet(&mqtt_client_id()).len()];
+            stream.read_exact(&mut connect).await.unwrap();
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+            let mut publish = vec![0u8; publish_packet("vehicle/events", b"{}").len()];
+            stream.read_exact(&mut publish).await.unwrap();
+            if puback.is_empty() {
+                return (connect, publish, Vec::new());
+            }
+            stream.write_all(puback).await.unwrap();
+            let mut rest = Vec::new();
+            let _ = stream.read_to_end(&mut rest).await;
+            (connect, publish, rest)
+        });
+        (broker, server)
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_mqtt_broker() {
+        let (broker, server) = fake_broker(&[0x40, 0x02, 0x00, 0x01]).await;
+
+        publish(&broker, "vehicle/events", b"{}").await.unwrap();
+        let (connect, publish, rest) = server.await.unwrap();
+        assert_eq!(connect, connect_packet(&mqtt_client_id()));
+        assert_eq!(publish, publish_packet("vehicle/events", b"{}"));
+        assert_eq!(rest, vec![0xE0, 0x00]);
+        // Every instance connects with its own identifier
+        assert!(mqtt_client_id().ends_with(&crate::leader::instance_id()));
+    }
+
+    #[tokio::test]
+    async fn test_publish_without_puback_fails() {
+        // The broker closes the connection instead of acknowledging
+        let (broker, server) = fake_broker(&[]).await;
+        assert!(publish(&broker, "vehicle/events", b"{}").await.is_err());
+        server.await.unwrap();
+
+        let (broker, server) = fake_broker(&[0x40, 0x02, 0x00, 0x02]).await;
+        let error = publish(&broker, "vehicle/events", b"{}").await.unwrap_err();
+        assert!(error.contains("answered"));
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Work the StateManager has accepted and not finished
+//!
+//! A StateChange is tracked from the moment it is queued for the engine,
+//! whether it comes from the gRPC server, a re-drive, an action result or
+//! the scheduler, until the engine has processed it. The actions its
+//! transition queues are tracked while the action executor runs them, and
+//! those the ActionController carries out while their result is awaited, see
+//! [`crate::inflight`].
+//!
+//! `GetPendingWork` reports them with their ages, the number of changes
+//! still queued with the age of the oldest, and for every resource the
+//! target state its pending changes lead to.
+
+use crate::types::ActionCommand;
+use common::statemanager::{
+    PendingAction, PendingDesiredState, PendingStateChange, PendingWorkResponse, ResourceType,
+    StateChange,
+};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Changes kept at most, the oldest are forgotten beyond
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct TrackedChange {
+    transition_id: String,
+    resource_type: i32,
+    resource_name: String,
+    target_state: String,
+    source: String,
+    since: Instant,
+    processing: bool,
+}
+
+#[derive(Debug, Clone)]
+struct RunningAction {
+    id: u64,
+    transition_id: String,
+    resource_type: ResourceType,
+    resource_key: String,
+    action: String,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// In the order they were queued
+    changes: Vec<TrackedChange>,
+    actions: Vec<RunningAction>,
+    next_action: u64,
+}
+
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    let mut registry = REGISTRY
+        .get_or_init(|| Mutex::new(Registry::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    f(&mut registry)
+}
+
+fn tracked(change: &StateChange, processing: bool) -> TrackedChange {
+    TrackedChange {
+        transition_id: change.transition_id.clone(),
+        resource_type: change.resource_type,
+        resource_name: change.resource_name.clone(),
+        target_state: change.target_state.clone(),
+        source: change.source.clone(),
+        since: common::clock::instant(),
+        processing,
+    }
+}
+
+/// Track a change about to be queued for the engine
+pub fn queued(change: &StateChange) {
+    with_registry(|registry| {
+        registry
+            .changes
+            .retain(|c| c.transition_id != change.transition_id);
+        if registry.changes.len() >= MAX_TRACKED {
+            registry.changes.remove(0);
+        }
+        registry.changes.push(tracked(change, false));
+    });
+}
+
+/// Forget a change that could not be queued
+pub fn discard(transition_id: &str) {
+    with_registry(|registry| {
+        registry
+            .changes
+            .retain(|c| c.transition_id != transition_id)
+    });
+}
+
+/// Mark a change taken from the queue by the engine
+pub fn processing(change: &StateChange) {
+    with_registry(|registry| {
+        match registry
+            .changes
+            .iter_mut()
+            .find(|c| c.transition_id == change.transition_id)
+        {
+            Some(tracked) => tracked.processing = true,
+            None => registry.changes.push(tracked(change, true)),
+        }
+    });
+}
+
+/// Forget a change the engine has processed
+pub fn processed(transition_id: &str) {
+    discard(transition_id);
+}
+
+/// Track an action the executor starts, see [`action_finished`]
+pub fn action_started(command: &ActionCommand) -> u64 {
+    with_registry(|registry| {
+        registry.next_action += 1;
+        let id = registry.next_action;
+        registry.actions.push(RunningAction {
+            id,
+            transition_id: command.transition_id.clone(),
+            resource_type: command.resource_type,
+            resource_key: command.resource_key.clone(),
+            action: command.action.clone(),
+            since: common::clock::instant(),
+        });
+        id
+    })
+}
+
+/// Forget an action returned by [`action_started`] once it has run
+pub fn action_finished(id: u64) {
+    with_registry(|registry| registry.actions.retain(|a| a.id != id));
+}
+
+fn age_ms(since: Instant, now: Instant) -> i64 {
+    now.saturating_duration_since(since).as_millis() as i64
+}
+
+/// Pending work of the resources of a type, every type for `Unspecified`
+pub fn report(resource_type: ResourceType) -> PendingWorkResponse {
+    let awaited = crate::inflight::in_flight();
+    let now = common::clock::instant();
+    with_registry(|registry| report_at(registry, &awaited, resource_type, now))
+}
+
+fn report_at(
+    registry: &Registry,
+    awaited: &[crate::inflight::InFlight],
+    resource_type: ResourceType,
+    now: Instant,
+) -> PendingWorkResponse {
+    let selected =
+        |rt: i32| resource_type == ResourceType::Unspecified || rt == resource_type as i32;
+    let changes: Vec<&TrackedChange> = registry
+        .changes
+        .iter()
+        .filter(|c| selected(c.resource_type))
+        .collect();
+
+    let waiting: Vec<&&TrackedChange> = changes.iter().filter(|c| !c.processing).collect();
+    let oldest = waiting
+        .iter()
+        .map(|c| now.saturating_duration_since(c.since))
+        .max()
+        .unwrap_or(Duration::ZERO);
+
+    let mut desired_states: Vec<PendingDesiredState> = Vec::new();
+    for change in &changes {
+        let desired = PendingDesiredState {
+            resource_type: change.resource_type,
+            resource_name: change.resource_name.clone(),
+            target_state: change.target_state.clone(),
+            transition_id: change.transition_id.clone(),
+        };
+        // A later change of the resource overrides the target of an earlier one
+        match desired_states.iter_mut().find(|d| {
+            d.resource_type == change.resource_type && d.resource_name == change.resource_name
+        }) {
+            Some(previous) => *previous = desired,
+            None => desired_states.push(desired),
+        }
+    }
+
+    let running = registry
+        .actions
+        .iter()
+        .filter(|a| selected(a.resource_type as i32))
+        .map(|a| PendingAction {
+            transition_id: a.transition_id.clone(),
+            resource_type: a.resource_type as i32,
+            resource_key: a.resource_key.clone(),
+            action: a.action.clone(),
+            age_ms: age_ms(a.since, now),
+            awaiting_result: false,
+        });
+    let awaited = awaited
+        .iter()
+        .filter(|a| selected(a.resource_type as i32))
+        .map(|a| PendingAction {
+            transition_id: a.transition_id.clone(),
+            resource_type: a.resource_type as i32,
+            resource_key: a.resource_name.clone(),
+            action: a.action.clone(),
+            age_ms: age_ms(a.since, now),
+            awaiting_result: true,
+        });
+
+    PendingWorkResponse {
+        queued: waiting.len() as u32,
+        oldest_queued_age_ms: oldest.as_millis() as i64,
+        state_changes: changes
+            .iter()
+            .map(|c| PendingStateChange {
+                transition_id: c.transition_id.clone(),
+                resource_type: c.resource_type,
+                resource_name: c.resource_name.clone(),
+                target_state: c.target_state.clone(),
+                source: c.source.clone(),
+                age_ms: age_ms(c.since, now),
+                processing: c.processing,
+            })
+            .collect(),
+        actions: running.chain(awaited).collect(),
+        desired_states,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn change(id: &str, rt: ResourceType, name: &str, target: &str) -> StateChange {
+        StateChange {
+            resource_type: rt as i32,
+            resource_name: name.to_string(),
+            target_state: target.to_string(),
+            transition_id: id.to_string(),
+            source: "test".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_pending_changes_and_actions() {
+        let mut registry = Registry::default();
+        let start = Instant::now();
+        let mut track = |c: &StateChange, at: Duration, processing: bool| {
+            let mut t = tracked(c, processing);
+            t.since = start + at;
+            registry.changes.push(t);
+        };
+        track(
+            &change("t1", ResourceType::Scenario, "s", "allowed"),
+            Duration::ZERO,
+            true,
+        );
+        track(
+            &change("t2", ResourceType::Scenario, "s", "completed"),
+            Duration::from_millis(100),
+            false,
+        );
+        track(
+            &change("t3", ResourceType::Package, "p", "running"),
+            Duration::from_millis(300),
+            false,
+        );
+        registry.actions.push(RunningAction {
+            id: 1,
+            transition_id: "t1".to_string(),
+            resource_type: ResourceType::Scenario,
+            resource_key: "s".to_string(),
+            action: "start_condition_evaluation".to_string(),
+            since: start,
+        });
+        let awaited = vec![crate::inflight::InFlight {
+            transition_id: "t0".to_string(),
+            resource_type: ResourceType::Scenario,
+            resource_name: "s".to_string(),
+            action: "execute_action_on_target_package".to_string(),
+            since: start,
+        }];
+
+        let now = start + Duration::from_millis(1000);
+        let report = report_at(&registry, &awaited, ResourceType::Unspecified, now);
+        assert_eq!(report.queued, 2);
+        assert_eq!(report.oldest_queued_age_ms, 900);
+        assert_eq!(report.state_changes.len(), 3);
+        assert!(report.state_changes[0].processing);
+        let desired: HashMap<&str, (&str, &str)> = report
+            .desired_states
+            .iter()
+            .map(|d| {
+                (
+                    d.resource_name.as_str(),
+                    (d.target_state.as_str(), d.transition_id.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(desired["s"], ("completed", "t2"));
+        assert_eq!(desired["p"], ("running", "t3"));
+        assert_eq!(report.actions.len(), 2);
+        assert!(!report.actions[0].awaiting_result);
+        assert!(report.actions[1].awaiting_result);
+        assert_eq!(report.actions[1].age_ms, 1000);
+
+        let packages = report_at(&registry, &awaited, ResourceType::Package, now);
+        assert_eq!(packages.queued, 1);
+        assert_eq!(packages.oldest_queued_age_ms, 700);
+        assert!(packages.actions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_tracked_until_processed() {
+        let c = change("pending-t1", ResourceType::Model, "pending-m", "Running");
+        queued(&c);
+        let ids = || -> Vec<(String, bool)> {
+            report(ResourceType::Model)
+                .state_changes
+                .into_iter()
+                .filter(|c| c.resource_name == "pending-m")
+                .map(|c| (c.transition_id, c.processing))
+                .collect()
+        };
+        assert_eq!(ids(), vec![("pending-t1".to_string(), false)]);
+        processing(&c);
+        assert_eq!(ids(), vec![("pending-t1".to_string(), true)]);
+        processed("pending-t1");
+        assert!(ids().is_empty());
+
+        let command = ActionCommand {
+            action: "pending-action".to_string(),
+            resource_key: "pending-m".to_string(),
+            resource_type: ResourceType::Model,
+            transition_id: "pending-t1".to_string(),
+            context: HashMap::new(),
+        };
+        let id = action_started(&command);
+        let running = || {
+            report(ResourceType::Model)
+                .actions
+                .iter()
+                .any(|a| a.action == "pending-action")
+        };
+        assert!(running());
+        action_finished(id);
+        assert!(!running());
+    }
+}
@@ -0,0 +1,302 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Bounded channels between the gRPC server and the StateManager engine
+//!
+//! Each channel has an explicit overflow policy deciding what happens when
+//! the engine falls behind and the buffer is full, and keeps counters so that
+//! queue depth and losses under burst load can be observed.
+
+use common::logd;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+/// Depth, in percent of the capacity, above which enqueueing logs a warning
+const HIGH_DEPTH_PERCENT: usize = 80;
+
+/// What to do with a message when the channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until the engine makes room
+    Block,
+    /// Refuse the new message
+    Reject,
+    /// Discard the oldest queued message to make room, for snapshot data
+    DropOldest,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "block" => Ok(OverflowPolicy::Block),
+            "reject" => Ok(OverflowPolicy::Reject),
+            "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            _ => Err(format!("unknown overflow policy '{}'", s)),
+        }
+    }
+}
+
+/// Why a message could not be queued
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnqueueError {
+    /// The channel is full and the policy rejects new messages
+    Full,
+    /// The engine stopped receiving
+    Closed,
+}
+
+impl std::fmt::Display for EnqueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnqueueError::Full => write!(f, "queue is full"),
+            EnqueueError::Closed => write!(f, "queue is closed"),
+        }
+    }
+}
+
+/// Snapshot of a channel's counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub capacity: usize,
+    /// Messages currently waiting for the engine
+    pub depth: usize,
+    /// Highest depth observed
+    pub high_watermark: u64,
+    pub enqueued: u64,
+    pub rejected: u64,
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    high_watermark: AtomicU64,
+    enqueued: AtomicU64,
+    rejected: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Sending side of a bounded channel with an overflow policy
+pub struct BoundedSender<T> {
+    name: &'static str,
+    tx: mpsc::Sender<T>,
+    policy: OverflowPolicy,
+    /// Receiver shared with the engine, used to discard the oldest message
+    drain: Option<Arc<Mutex<mpsc::Receiver<T>>>>,
+    /// Called with every message discarded to make room
+    on_drop: Option<Arc<dyn Fn(T) + Send + Sync>>,
+    counters: Arc<Counters>,
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            tx: self.tx.clone(),
+            policy: self.policy,
+            drain: self.drain.clone(),
+            on_drop: self.on_drop.clone(),
+            counters: Arc::clone(&self.counters),
+        }
+    }
+}
+
+impl<T> From<mpsc::Sender<T>> for BoundedSender<T> {
+    /// Wrap a plain sender, waiting for room when the channel is full
+    fn from(tx: mpsc::Sender<T>) -> Self {
+        Self::new("channel", tx, OverflowPolicy::Block)
+    }
+}
+
+/// Create a bounded channel whose receiver can be shared with the engine
+///
+/// The receiver is returned behind the same lock the sender uses to apply
+/// [`OverflowPolicy::DropOldest`].
+pub fn channel<T>(
+    name: &'static str,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (BoundedSender<T>, Arc<Mutex<mpsc::Receiver<T>>>) {
+    let (tx, rx) = mpsc::channel(capacity.max(1));
+    let rx = Arc::new(Mutex::new(rx));
+    let sender = BoundedSender {
+        name,
+        tx,
+        policy,
+        drain: (policy == OverflowPolicy::DropOldest).then(|| Arc::clone(&rx)),
+        on_drop: None,
+        counters: Arc::new(Counters::default()),
+    };
+    (sender, rx)
+}
+
+impl<T> BoundedSender<T> {
+    /// Wrap a sender without access to its receiver
+    ///
+    /// [`OverflowPolicy::DropOldest`] needs the receiver and falls back to
+    /// [`OverflowPolicy::Reject`] here; use [`channel`] instead.
+    pub fn new(name: &'static str, tx: mpsc::Sender<T>, policy: OverflowPolicy) -> Self {
+        let policy = match policy {
+            OverflowPolicy::DropOldest => OverflowPolicy::Reject,
+            other => other,
+        };
+        Self {
+            name,
+            tx,
+            policy,
+            drain: None,
+            on_drop: None,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Hand every message [`OverflowPolicy::DropOldest`] discards to `f`,
+    /// e.g. to forget what was tracked for it
+    pub fn on_drop(mut self, f: impl Fn(T) + Send + Sync + 'static) -> Self {
+        self.on_drop = Some(Arc::new(f));
+        self
+    }
+
+    fn depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    /// Queue a message according to the overflow policy
+    pub async fn send(&self, item: T) -> Result<(), EnqueueError> {
+        match self.policy {
+            OverflowPolicy::Block => {
+                self.tx.send(item).await.map_err(|_| EnqueueError::Closed)?;
+            }
+            OverflowPolicy::Reject => match self.tx.try_send(item) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+                    logd!(4, "{} queue full, rejecting message", self.name);
+                    return Err(EnqueueError::Full);
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => return Err(EnqueueError::Closed),
+            },
+            OverflowPolicy::DropOldest => {
+                let mut item = item;
+                loop {
+                    match self.tx.try_send(item) {
+                        Ok(()) => break,
+                        Err(mpsc::error::TrySendError::Full(returned)) => {
+                            item = returned;
+                            if let Some(drain) = &self.drain {
+                                // The engine only holds the lock while the queue is empty
+                                let dropped = drain.lock().await.try_recv();
+                                if let Ok(dropped) = dropped {
+                                    self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                                    logd!(4, "{} queue full, dropped oldest message", self.name);
+                                    if let Some(on_drop) = &self.on_drop {
+                                        on_drop(dropped);
+                                    }
+                                }
+                            }
+                        }
+                        Err(mpsc::error::TrySendError::Closed(_)) => {
+                            return Err(EnqueueError::Closed)
+                        }
+                    }
+                }
+            }
+        }
+
+        self.counters.enqueued.fetch_add(1, Ordering::Relaxed);
+        let depth = self.depth();
+        let previous = self
+            .counters
+            .high_watermark
+            .fetch_max(depth as u64, Ordering::Relaxed);
+        let threshold = self.tx.max_capacity() * HIGH_DEPTH_PERCENT / 100;
+        if depth as u64 > previous && depth >= threshold.max(1) {
+            logd!(
+                4,
+                "{} queue depth {} of {} reached a new high",
+                self.name,
+                depth,
+                self.tx.max_capacity()
+            );
+        }
+        Ok(())
+    }
+
+    /// Current counters of the channel
+    pub fn stats(&self) -> QueueStats {
+        QueueStats {
+            capacity: self.tx.max_capacity(),
+            depth: self.depth(),
+            high_watermark: self.counters.high_watermark.load(Ordering::Relaxed),
+            enqueued: self.counters.enqueued.load(Ordering::Relaxed),
+            rejected: self.counters.rejected.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow_policy_from_str() {
+        assert_eq!("block".parse(), Ok(OverflowPolicy::Block));
+        assert_eq!("Reject".parse(), Ok(OverflowPolicy::Reject));
+        assert_eq!("drop-oldest".parse(), Ok(OverflowPolicy::DropOldest));
+        assert!("drop_newest".parse::<OverflowPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reject_when_full() {
+        let (tx, rx) = channel::<u32>("test", 2, OverflowPolicy::Reject);
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        assert_eq!(tx.send(3).await, Err(EnqueueError::Full));
+
+        let stats = tx.stats();
+        assert_eq!(stats.depth, 2);
+        assert_eq!(stats.high_watermark, 2);
+        assert_eq!(stats.enqueued, 2);
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(rx.lock().await.recv().await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_when_full() {
+        let dropped = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (tx, rx) = channel::<u32>("test", 2, OverflowPolicy::DropOldest);
+        let tx = tx.on_drop({
+            let dropped = Arc::clone(&dropped);
+            move |i| dropped.lock().unwrap().push(i)
+        });
+        for i in 1..=4 {
+            tx.send(i).await.unwrap();
+        }
+
+        let stats = tx.stats();
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(*dropped.lock().unwrap(), [1, 2]);
+        assert_eq!(stats.enqueued, 4);
+        let mut rx = rx.lock().await;
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_closed_channel() {
+        let (tx, rx) = channel::<u32>("test", 1, OverflowPolicy::Block);
+        drop(rx);
+        assert_eq!(tx.send(1).await, Err(EnqueueError::Closed));
+
+        let (plain_tx, plain_rx) = mpsc::channel::<u32>(1);
+        drop(plain_rx);
+        let tx: BoundedSender<u32> = plain_tx.into();
+        assert_eq!(tx.send(1).await, Err(EnqueueError::Closed));
+    }
+}
@@ -0,0 +1,262 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Per-peer rate limiting of the StateManager gRPC endpoints
+//!
+//! Every peer has a token bucket refilled at `statemanager.rate_limit_per_sec`
+//! and holding up to `statemanager.rate_limit_burst` requests, so that a
+//! NodeAgent flooding container lists cannot starve the other peers. A
+//! request without a token is refused with `resource_exhausted`, the peer
+//! is expected to back off.
+//!
+//! A peer is the IP address the request came from, along with the
+//! authenticated principal when authentication is enabled, so that callers
+//! sharing a principal, e.g. the NodeAgents of several nodes, do not share a
+//! bucket. The node or component named in the request is not used, a peer
+//! could name another one.
+//!
+//! A bucket that refilled to its capacity is dropped once
+//! [`MAX_BUCKETS`] peers are tracked, it is the same as a new one. When
+//! still full, the least recently used buckets are dropped. The refusals
+//! counted for a peer are dropped with its bucket, so that a flood of
+//! peers cannot grow them without bound, the total is kept.
+
+use common::logd;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tonic::Status;
+
+/// Peers whose bucket is kept
+pub const MAX_BUCKETS: usize = 1024;
+
+/// Counters of the rate limiter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThrottleStats {
+    /// Requests let through since start
+    pub allowed: u64,
+    /// Requests refused since start
+    pub throttled: u64,
+    /// Refused requests by peer, of the peers that still have a bucket
+    pub throttled_by_peer: BTreeMap<String, u64>,
+}
+
+/// Requests a peer may still send
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+#[derive(Default)]
+struct Limiter {
+    buckets: HashMap<String, Bucket>,
+    stats: ThrottleStats,
+}
+
+impl Limiter {
+    /// Take a token of `peer` at `now`, returns whether one was left
+    fn acquire(&mut self, peer: &str, rate: f64, burst: u32, now: Instant) -> bool {
+        let capacity = f64::from(burst.max(1));
+        if !self.buckets.contains_key(peer) && self.buckets.len() >= MAX_BUCKETS {
+            self.evict(rate, capacity, now);
+        }
+        let bucket = self.buckets.entry(peer.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            refilled: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            self.stats.allowed += 1;
+            true
+        } else {
+            self.stats.throttled += 1;
+            *self
+                .stats
+                .throttled_by_peer
+                .entry(peer.to_string())
+                .or_default() += 1;
+            false
+        }
+    }
+
+    /// Drop the buckets that are full at `now`, then the least recently used
+    /// ones until a new bucket fits, with the refusals of their peers
+    fn evict(&mut self, rate: f64, capacity: f64, now: Instant) {
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+            bucket.tokens + elapsed * rate < capacity
+        });
+        if self.buckets.len() >= MAX_BUCKETS {
+            self.evict_least_recently_used();
+        }
+        let buckets = &self.buckets;
+        self.stats
+            .throttled_by_peer
+            .retain(|peer, _| buckets.contains_key(peer));
+    }
+
+    /// Drop the least recently used buckets until a new bucket fits
+    fn evict_least_recently_used(&mut self) {
+        let mut by_use: Vec<(Instant, String)> = self
+            .buckets
+            .iter()
+            .map(|(peer, bucket)| (bucket.refilled, peer.clone()))
+            .collect();
+        by_use.sort();
+        for (_, peer) in by_use
+            .into_iter()
+            .take(self.buckets.len() + 1 - MAX_BUCKETS)
+        {
+            self.buckets.remove(&peer);
+        }
+    }
+}
+
+fn limiter() -> &'static Mutex<Limiter> {
+    static LIMITER: OnceLock<Mutex<Limiter>> = OnceLock::new();
+    LIMITER.get_or_init(|| Mutex::new(Limiter::default()))
+}
+
+/// Peer a request is accounted to
+///
+/// ### Parameters
+/// * `principal` - caller resolved by [`common::auth::authorize`]
+/// * `remote` - address the request came from
+pub fn peer(principal: Option<&common::auth::Principal>, remote: Option<SocketAddr>) -> String {
+    match (principal, remote) {
+        (Some(principal), Some(remote)) => format!("{}@{}", principal.name, remote.ip()),
+        (Some(principal), None) => principal.name.clone(),
+        (None, Some(remote)) => remote.ip().to_string(),
+        (None, None) => "anonymous".to_string(),
+    }
+}
+
+/// Let a request of `peer` through, or refuse it when the peer is over its rate
+#[allow(clippy::result_large_err)]
+pub fn check(rpc: &str, peer: &str) -> Result<(), Status> {
+    let settings = &common::setting::get_config().statemanager;
+    if settings.rate_limit_per_sec <= 0.0 {
+        return Ok(());
+    }
+    let allowed = limiter().lock().unwrap_or_else(|e| e.into_inner()).acquire(
+        peer,
+        settings.rate_limit_per_sec,
+        settings.rate_limit_burst,
+        common::clock::instant(),
+    );
+    if allowed {
+        return Ok(());
+    }
+    logd!(4, "Throttled {} from {}", rpc, peer);
+    Err(Status::resource_exhausted(format!(
+        "{} rate limit of {}/s exceeded by {}, retry later",
+        rpc, settings.rate_limit_per_sec, peer
+    )))
+}
+
+pub fn stats() -> ThrottleStats {
+    limiter()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .stats
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_allows_the_burst_then_refills() {
+        let mut limiter = Limiter::default();
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.acquire("flooding-node", 2.0, 3, start));
+        }
+        assert!(!limiter.acquire("flooding-node", 2.0, 3, start));
+        // Other peers keep their own bucket
+        assert!(limiter.acquire("quiet-node", 2.0, 3, start));
+
+        // Two tokens per second
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.acquire("flooding-node", 2.0, 3, later));
+        assert!(!limiter.acquire("flooding-node", 2.0, 3, later));
+
+        assert_eq!(limiter.stats.allowed, 5);
+        assert_eq!(limiter.stats.throttled, 2);
+        assert_eq!(limiter.stats.throttled_by_peer["flooding-node"], 2);
+    }
+
+    #[test]
+    fn test_peer() {
+        let principal = common::auth::Principal {
+            name: "nodeagent".to_string(),
+            role: common::auth::Role::Operator,
+            namespaces: Vec::new(),
+        };
+        let remote: SocketAddr = "10.0.0.7:50123".parse().unwrap();
+        assert_eq!(peer(Some(&principal), Some(remote)), "nodeagent@10.0.0.7");
+        assert_eq!(
+            peer(Some(&principal), "10.0.0.8:50123".parse().ok()),
+            "nodeagent@10.0.0.8"
+        );
+        assert_eq!(peer(Some(&principal), None), "nodeagent");
+        // Connections of one host share a bucket
+        assert_eq!(peer(None, Some(remote)), "10.0.0.7");
+        assert_eq!(peer(None, "10.0.0.7:50124".parse().ok()), "10.0.0.7");
+        assert_eq!(peer(None, None), "anonymous");
+    }
+
+    #[test]
+    fn test_idle_buckets_are_evicted() {
+        let mut limiter = Limiter::default();
+        let start = Instant::now();
+        for i in 0..MAX_BUCKETS {
+            assert!(limiter.acquire(&format!("peer-{}", i), 1.0, 2, start));
+        }
+        // peer-0 is drained and used last
+        let used = start + Duration::from_millis(1);
+        assert!(limiter.acquire("peer-0", 1.0, 2, used));
+        assert!(!limiter.acquire("peer-0", 1.0, 2, used));
+
+        // Nothing refilled yet, the least recently used bucket makes room
+        assert!(limiter.acquire("new-peer", 1.0, 2, start));
+        assert_eq!(limiter.buckets.len(), MAX_BUCKETS);
+        assert!(limiter.buckets.contains_key("peer-0"));
+
+        // One second later the buckets used once are full again and dropped
+        let later = used + Duration::from_secs(1);
+        assert!(limiter.acquire("another-peer", 1.0, 2, later));
+        assert!(limiter.buckets.len() < 4);
+        // The drained peer keeps its bucket and its rate
+        assert!(limiter.acquire("peer-0", 1.0, 2, later));
+        assert!(!limiter.acquire("peer-0", 1.0, 2, later));
+    }
+
+    #[test]
+    fn test_refusals_are_evicted_with_their_bucket() {
+        let mut limiter = Limiter::default();
+        let start = Instant::now();
+        for i in 0..MAX_BUCKETS {
+            let peer = format!("flood-{}", i);
+            assert!(limiter.acquire(&peer, 1.0, 1, start));
+            assert!(!limiter.acquire(&peer, 1.0, 1, start));
+        }
+        assert_eq!(limiter.stats.throttled_by_peer.len(), MAX_BUCKETS);
+
+        // The full buckets are dropped with the refusals of their peers
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.acquire("new-peer", 1.0, 1, later));
+        assert!(limiter.stats.throttled_by_peer.is_empty());
+        assert_eq!(limiter.stats.throttled, MAX_BUCKETS as u64);
+    }
+}
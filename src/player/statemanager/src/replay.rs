@@ -0,0 +1,328 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Recording and deterministic replay of the StateManager inputs
+//!
+//! When `statemanager.record_path` is set, the file starts with the time
+//! the recording started and a snapshot of the stored artifacts and states,
+//! followed by every StateChange and
+//! ContainerList in the order the manager processes them. Each line is a
+//! JSON [`Recorded`] message with its offset from the start of the
+//! recording.
+//!
+//! `statemanager --replay <file> [--speed <factor>]` feeds a recording back
+//! into a manager, one message at a time. Nothing stored by the running
+//! system is read: the StateManager storage and `common::etcd` are replaced
+//! by in-memory stores seeded with the snapshot, and a [`MockClock`] that
+//! stands at the recorded time of each message is installed. The actions
+//! of the transitions are not executed, but reconcile requests the manager
+//! sends on its own, e.g. for failed packages, still go to the
+//! ActionController of settings.yaml. The stored states are printed at the
+//! end, so that two runs of the same recording can be compared. A speed of
+//! 0, the default, replays without waiting between messages.
+
+use common::clock::MockClock;
+use common::logd;
+use common::monitoringserver::ContainerList;
+use common::statemanager::StateChange;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Key prefixes of the artifacts and states in the snapshot
+pub const SNAPSHOT_PREFIXES: [&str; 8] = [
+    "Scenario/",
+    "Package/",
+    "Model/",
+    "/scenario/",
+    "/package/",
+    "/model/",
+    "/network/",
+    "/node/",
+];
+
+/// Input of the manager
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Message {
+    /// Nanoseconds since the Unix epoch when the recording started
+    Start {
+        timestamp_ns: i64,
+    },
+    /// Stored key and value when the recording started
+    Snapshot {
+        key: String,
+        value: String,
+    },
+    StateChange(Box<StateChange>),
+    ContainerList(ContainerList),
+}
+
+/// Line of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recorded {
+    /// Nanoseconds since the start of the recording
+    pub offset_ns: u64,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+struct Recorder {
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+fn recorder() -> &'static Mutex<Option<Recorder>> {
+    static RECORDER: OnceLock<Mutex<Option<Recorder>>> = OnceLock::new();
+    RECORDER.get_or_init(|| Mutex::new(None))
+}
+
+fn write(recorder: &mut Recorder, message: Message) -> std::io::Result<()> {
+    let recorded = Recorded {
+        offset_ns: common::clock::instant()
+            .saturating_duration_since(recorder.start)
+            .as_nanos() as u64,
+        message,
+    };
+    serde_json::to_writer(&mut recorder.file, &recorded)?;
+    recorder.file.write_all(b"\n")?;
+    recorder.file.flush()
+}
+
+fn record(message: Message) {
+    let mut recorder = recorder().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(active) = recorder.as_mut() {
+        if let Err(e) = write(active, message) {
+            logd!(4, "Recording stopped, cannot write: {}", e);
+            *recorder = None;
+        }
+    }
+}
+
+/// Start recording to `path` with a snapshot of the stored states
+pub async fn start_recording(path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Cannot record to {}: {}", path, e))?;
+    let mut active = Recorder {
+        file: BufWriter::new(file),
+        start: common::clock::instant(),
+    };
+    write(
+        &mut active,
+        Message::Start {
+            timestamp_ns: common::clock::now_ns(),
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    for prefix in SNAPSHOT_PREFIXES {
+        let entries = crate::storage::storage()
+            .get_all_with_prefix(prefix)
+            .await
+            .map_err(|e| format!("Cannot snapshot {}: {}", prefix, e))?;
+        for (key, value) in entries {
+            write(&mut active, Message::Snapshot { key, value }).map_err(|e| e.to_string())?;
+        }
+    }
+    *recorder().lock().unwrap_or_else(|e| e.into_inner()) = Some(active);
+    logd!(3, "Recording StateManager inputs to {}", path);
+    Ok(())
+}
+
+/// Record a StateChange about to be processed
+pub fn record_state_change(state_change: &StateChange) {
+    record(Message::StateChange(Box::new(state_change.clone())));
+}
+
+/// Record a ContainerList about to be processed
+pub fn record_container_list(container_list: &ContainerList) {
+    record(Message::ContainerList(container_list.clone()));
+}
+
+/// Read a recording
+pub fn load(path: &str) -> Result<Vec<Recorded>, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(index, line)| {
+            let line = line.map_err(|e| format!("Cannot read {}: {}", path, e))?;
+            serde_json::from_str(&line).map_err(|e| format!("{} line {}: {}", path, index + 1, e))
+        })
+        .collect()
+}
+
+/// Time the recording started, the Unix epoch for a recording without it
+pub fn start_ns(recording: &[Recorded]) -> i64 {
+    recording
+        .iter()
+        .find_map(|recorded| match recorded.message {
+            Message::Start { timestamp_ns } => Some(timestamp_ns),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// How long to wait before a message replayed at `speed` times the
+/// recorded pace, `None` when it is due
+pub fn delay(offset_ns: u64, elapsed: Duration, speed: f64) -> Option<Duration> {
+    if speed <= 0.0 {
+        return None;
+    }
+    Duration::from_secs_f64(offset_ns as f64 / 1e9 / speed)
+        .checked_sub(elapsed)
+        .filter(|delay| !delay.is_zero())
+}
+
+/// Options of a replay run, from the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOptions {
+    pub path: String,
+    pub speed: f64,
+}
+
+impl ReplayOptions {
+    /// `--replay <file> [--speed <factor>]`, `None` without `--replay`
+    pub fn from_args(args: &[String]) -> Option<Result<Self, String>> {
+        let position = args.iter().position(|arg| arg == "--replay")?;
+        let Some(path) = args.get(position + 1) else {
+            return Some(Err("--replay needs the path of a recording".to_string()));
+        };
+        let speed = match args.iter().position(|arg| arg == "--speed") {
+            Some(position) => match args.get(position + 1).map(|speed| speed.parse::<f64>()) {
+                Some(Ok(speed)) if speed >= 0.0 => speed,
+                _ => return Some(Err("--speed needs a factor of 0 or more".to_string())),
+            },
+            None => 0.0,
+        };
+        Some(Ok(Self {
+            path: path.clone(),
+            speed,
+        }))
+    }
+}
+
+/// Replay a recording against in-memory stores and print the stored
+/// states it ends with
+pub async fn run(options: &ReplayOptions) -> Result<(), String> {
+    let recording = load(&options.path)?;
+    common::etcd::set_store(Arc::new(common::etcd::InMemoryStore::default()));
+    crate::storage::set_storage(Box::new(crate::storage::InMemoryStateStorage::default()))?;
+    let clock = Arc::new(MockClock::new(start_ns(&recording)));
+    common::clock::set(clock.clone());
+
+    let (_tx_container, rx_container) = tokio::sync::mpsc::channel(1);
+    let (_tx_state_change, rx_state_change) = tokio::sync::mpsc::channel(1);
+    let manager = crate::manager::StateManagerManager::new(rx_container, rx_state_change).await;
+    manager.replay(recording, options.speed, &clock).await;
+
+    for prefix in ["/scenario/", "/package/", "/model/", "/network/", "/node/"] {
+        for (key, value) in crate::storage::storage()
+            .get_all_with_prefix(prefix)
+            .await?
+        {
+            println!("{} = {}", key, value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_round_trip() {
+        let recorded = Recorded {
+            offset_ns: 42,
+            message: Message::StateChange(Box::new(StateChange {
+                resource_type: 1,
+                resource_name: "hello".to_string(),
+                current_state: "Idle".to_string(),
+                target_state: "Waiting".to_string(),
+                transition_id: "t-1".to_string(),
+                ..Default::default()
+            })),
+        };
+        let line = serde_json::to_string(&recorded).unwrap();
+        assert!(line.starts_with("{\"offset_ns\":42,\"state_change\":"));
+        assert_eq!(serde_json::from_str::<Recorded>(&line).unwrap(), recorded);
+
+        let snapshot: Recorded =
+            serde_json::from_str(r#"{"offset_ns":0,"snapshot":{"key":"k","value":"v"}}"#).unwrap();
+        assert_eq!(
+            snapshot.message,
+            Message::Snapshot {
+                key: "k".to_string(),
+                value: "v".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_start_ns() {
+        let start: Recorded =
+            serde_json::from_str(r#"{"offset_ns":0,"start":{"timestamp_ns":1700000000}}"#).unwrap();
+        let snapshot = Recorded {
+            offset_ns: 0,
+            message: Message::Snapshot {
+                key: "k".to_string(),
+                value: "v".to_string(),
+            },
+        };
+        assert_eq!(start_ns(&[start, snapshot.clone()]), 1_700_000_000);
+        assert_eq!(start_ns(&[snapshot]), 0);
+    }
+
+    #[test]
+    fn test_delay_follows_speed() {
+        let second = 1_000_000_000;
+        assert_eq!(delay(second, Duration::ZERO, 0.0), None);
+        assert_eq!(
+            delay(second, Duration::ZERO, 2.0),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            delay(second, Duration::from_millis(200), 1.0),
+            Some(Duration::from_millis(800))
+        );
+        assert_eq!(delay(second, Duration::from_secs(2), 1.0), None);
+    }
+
+    #[test]
+    fn test_replay_options() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(str::to_string).collect() };
+        assert_eq!(ReplayOptions::from_args(&args("statemanager")), None);
+        assert_eq!(
+            ReplayOptions::from_args(&args("statemanager --replay in.jsonl --speed 10")),
+            Some(Ok(ReplayOptions {
+                path: "in.jsonl".to_string(),
+                speed: 10.0
+            }))
+        );
+        assert!(matches!(
+            ReplayOptions::from_args(&args("statemanager --replay in.jsonl --speed -1")),
+            Some(Err(_))
+        ));
+        assert!(matches!(
+            ReplayOptions::from_args(&args("statemanager --replay")),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_reports_bad_line() {
+        let path = std::env::temp_dir().join(format!("replay-test-{}.jsonl", std::process::id()));
+        std::fs::write(
+            &path,
+            "{\"offset_ns\":0,\"snapshot\":{\"key\":\"k\",\"value\":\"v\"}}\n\nnot json\n",
+        )
+        .unwrap();
+        let error = load(path.to_str().unwrap()).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.contains("line 3"), "{}", error);
+    }
+}
@@ -0,0 +1,170 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Scenario status rollup
+//!
+//! Users think in scenarios, while the state machine tracks the scenario
+//! condition and policy flow separately from the package it deploys. The
+//! rollup combines both into one status for displays:
+//! * `inactive` - the condition of the scenario is not met yet
+//! * `activating` - condition met, waiting for policy, capacity or the
+//!   package to start
+//! * `running` - the package runs, or is being updated
+//! * `degraded` - some models of the package are dead, or it is paused
+//! * `failed` - the scenario was denied or its package is in error
+//! * `completed` - the models of the package have exited
+//!
+//! The status is saved as `/scenario/{name}/status` whenever the scenario or
+//! its target package changes state.
+
+use common::spec::artifact::{Artifact, Scenario};
+use common::state_mapping::StateName;
+use common::statemanager::{PackageState, ScenarioState};
+
+/// Display status of a scenario
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScenarioStatus {
+    Inactive,
+    Activating,
+    Running,
+    Degraded,
+    Failed,
+    Completed,
+}
+
+impl ScenarioStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScenarioStatus::Inactive => "inactive",
+            ScenarioStatus::Activating => "activating",
+            ScenarioStatus::Running => "running",
+            ScenarioStatus::Degraded => "degraded",
+            ScenarioStatus::Failed => "failed",
+            ScenarioStatus::Completed => "completed",
+        }
+    }
+}
+
+/// Status of a scenario from its state and the state of its target package
+///
+/// The package only counts once the action of the scenario has completed.
+pub fn rollup(scenario: Option<ScenarioState>, package: Option<PackageState>) -> ScenarioStatus {
+    match scenario {
+        None | Some(ScenarioState::Unspecified | ScenarioState::Idle | ScenarioState::Waiting) => {
+            ScenarioStatus::Inactive
+        }
+        Some(ScenarioState::Satisfied | ScenarioState::Allowed | ScenarioState::Pending) => {
+            ScenarioStatus::Activating
+        }
+        Some(ScenarioState::Denied) => ScenarioStatus::Failed,
+        Some(ScenarioState::Completed) => match package {
+            Some(PackageState::Running | PackageState::Updating) => ScenarioStatus::Running,
+            Some(PackageState::Degraded | PackageState::Paused) => ScenarioStatus::Degraded,
+            Some(PackageState::Error) => ScenarioStatus::Failed,
+            Some(PackageState::Exited) => ScenarioStatus::Completed,
+            None | Some(PackageState::Idle | PackageState::Unspecified) => {
+                ScenarioStatus::Activating
+            }
+        },
+    }
+}
+
+/// Key of the rolled up status of a scenario
+pub fn status_key(scenario: &str) -> String {
+    format!("/scenario/{}/status", scenario)
+}
+
+/// Names of the applied scenarios whose target is a package
+pub async fn scenarios_targeting(package: &str) -> Result<Vec<String>, String> {
+    let scenarios = crate::storage::storage()
+        .get_all_with_prefix("Scenario/")
+        .await?;
+    Ok(scenarios
+        .iter()
+        .filter_map(|(_, yaml)| serde_yaml::from_str::<Scenario>(yaml).ok())
+        .filter(|scenario| scenario.get_targets() == package)
+        .map(|scenario| scenario.get_qualified_name())
+        .collect())
+}
+
+/// Recompute and save the status of a scenario
+///
+/// ### Returns
+/// * `Ok(Some((old, new)))` - the status changed, `old` is empty for a
+///   scenario without status yet
+/// * `Ok(None)` - the status did not change
+pub async fn refresh(scenario: &str) -> Result<Option<(String, ScenarioStatus)>, String> {
+    let storage = crate::storage::storage();
+    let state = storage
+        .get(&format!("/scenario/{}/state", scenario))
+        .await
+        .ok()
+        .and_then(|state| ScenarioState::parse(&state));
+    let package = match storage.get(&format!("Scenario/{}", scenario)).await {
+        Ok(yaml) => serde_yaml::from_str::<Scenario>(&yaml)
+            .map(|s| s.get_targets())
+            .map_err(|e| e.to_string())?,
+        Err(_) => String::new(),
+    };
+    let package_state = if package.is_empty() {
+        None
+    } else {
+        storage
+            .get(&format!("/package/{}/state", package))
+            .await
+            .ok()
+            .and_then(|state| PackageState::migrate(&state))
+    };
+
+    let status = rollup(state, package_state);
+    let key = status_key(scenario);
+    let previous = storage.get(&key).await.unwrap_or_default();
+    if previous == status.as_str() {
+        return Ok(None);
+    }
+    storage.put(&key, status.as_str()).await?;
+    Ok(Some((previous, status)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup_before_the_action_completes() {
+        assert_eq!(rollup(None, None), ScenarioStatus::Inactive);
+        assert_eq!(
+            rollup(Some(ScenarioState::Waiting), Some(PackageState::Running)),
+            ScenarioStatus::Inactive
+        );
+        assert_eq!(
+            rollup(Some(ScenarioState::Pending), None),
+            ScenarioStatus::Activating
+        );
+        assert_eq!(
+            rollup(Some(ScenarioState::Denied), Some(PackageState::Running)),
+            ScenarioStatus::Failed
+        );
+    }
+
+    #[test]
+    fn test_rollup_follows_the_package_once_completed() {
+        let completed = |package| rollup(Some(ScenarioState::Completed), package);
+        assert_eq!(completed(None), ScenarioStatus::Activating);
+        assert_eq!(
+            completed(Some(PackageState::Updating)),
+            ScenarioStatus::Running
+        );
+        assert_eq!(
+            completed(Some(PackageState::Degraded)),
+            ScenarioStatus::Degraded
+        );
+        assert_eq!(completed(Some(PackageState::Error)), ScenarioStatus::Failed);
+        assert_eq!(
+            completed(Some(PackageState::Exited)),
+            ScenarioStatus::Completed
+        );
+    }
+}
@@ -0,0 +1,742 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Tamper-evident safety event log
+//!
+//! For ISO 26262 safety audits every state transition and alert of the
+//! StateManager is appended to a log in the storage, one record per event
+//! under [`PREFIX`] keyed by its sequence number. The records form a chain:
+//! each one stores the hash of the record before it and its own hash over
+//! its fields and that previous hash, the first one follows [`GENESIS`].
+//! The number of records, the hash of the last one and the first record
+//! still stored are kept under [`HEAD`], written together with every record.
+//!
+//! The hashes are HMAC-SHA256 with the key in the file of
+//! `statemanager.safety_log_key_path`, which is kept outside the storage,
+//! so that whoever can write the storage cannot rehash a modified record
+//! or rewrite the head. The key must stay the same for the life of the log.
+//! Without a key the hashes are plain SHA-256 and only detect accidental
+//! changes. Rolling the log back to an earlier head is only detected
+//! against a head kept elsewhere, such as the `head_hash` reported by a
+//! previous verification.
+//!
+//! Only the last `statemanager.safety_log_retention` records are kept.
+//! Older ones are pruned in batches of [`PRUNE_BATCH`], the head then
+//! records the first stored record and the hash it follows, so the chain is
+//! verified from there on.
+//!
+//! `VerifySafetyLog` walks the chain with [`verify`], [`PAGE`] records at a
+//! time: a modified record no longer matches its hash, a removed or
+//! reordered record breaks the sequence or the chain, and a log truncated
+//! at its end no longer reaches the head.
+//!
+//! Events are appended in the order they happened by [`run_writer`]. While
+//! the storage is unavailable they wait in memory, at most
+//! [`MAX_PENDING`] of them.
+
+use crate::storage::{StateStorage, Transaction};
+use common::logd;
+use common::statemanager::{AlertRecord, TransitionRecord, VerifySafetyLogResponse};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Key prefix of the records
+pub const PREFIX: &str = "/statemanager/safety-log/record/";
+
+/// Key of the [`Head`] of the log
+pub const HEAD: &str = "/statemanager/safety-log/head";
+
+/// Previous hash of the first record
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Events kept in memory while the storage is unavailable
+pub const MAX_PENDING: usize = 10_000;
+
+/// Records beyond the retention pruned at once
+pub const PRUNE_BATCH: u64 = 100;
+
+/// Records read at once while the log is verified, the records whose key
+/// shares all but the last [`PAGE_DIGITS`] digits
+pub const PAGE: u64 = 10u64.pow(PAGE_DIGITS as u32);
+
+const PAGE_DIGITS: usize = 3;
+
+/// Digits of the sequence in the key of a record
+const SEQUENCE_DIGITS: usize = 20;
+
+/// Error of the storage for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Delay before a failed append is tried again
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Event to append to the log
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyEvent {
+    pub timestamp_ns: i64,
+    /// `transition` or `alert`
+    pub kind: String,
+    pub resource_type: String,
+    pub resource_name: String,
+    pub transition_id: String,
+    /// States of a transition, message of an alert
+    pub detail: String,
+}
+
+/// Record of the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SafetyRecord {
+    pub sequence: u64,
+    pub timestamp_ns: i64,
+    pub kind: String,
+    pub resource_type: String,
+    pub resource_name: String,
+    pub transition_id: String,
+    pub detail: String,
+    /// Hash of the previous record, [`GENESIS`] for the first one
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl SafetyRecord {
+    /// Record of `event` following the record hashed `prev_hash`
+    pub fn chain(event: SafetyEvent, sequence: u64, prev_hash: &str, key: Option<&[u8]>) -> Self {
+        let mut record = Self {
+            sequence,
+            timestamp_ns: event.timestamp_ns,
+            kind: event.kind,
+            resource_type: event.resource_type,
+            resource_name: event.resource_name,
+            transition_id: event.transition_id,
+            detail: event.detail,
+            prev_hash: prev_hash.to_string(),
+            hash: String::new(),
+        };
+        record.hash = record.digest(key);
+        record
+    }
+
+    /// Hash of every field but the hash
+    pub fn digest(&self, key: Option<&[u8]>) -> String {
+        digest(
+            key,
+            &[
+                &self.sequence.to_string(),
+                &self.timestamp_ns.to_string(),
+                &self.kind,
+                &self.resource_type,
+                &self.resource_name,
+                &self.transition_id,
+                &self.detail,
+                &self.prev_hash,
+            ],
+        )
+    }
+}
+
+/// End of the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Head {
+    /// Number of records, the sequence of the next one
+    pub records: u64,
+    /// Hash of the last record, [`GENESIS`] without any
+    pub hash: String,
+    /// Sequence of the first stored record, the ones before were pruned
+    pub first: u64,
+    /// Hash the first stored record follows
+    pub first_prev_hash: String,
+    /// Hash of the other fields
+    pub mac: String,
+}
+
+impl Head {
+    /// Head of an empty log
+    pub fn empty(key: Option<&[u8]>) -> Self {
+        Self {
+            records: 0,
+            hash: GENESIS.to_string(),
+            first: 0,
+            first_prev_hash: GENESIS.to_string(),
+            mac: String::new(),
+        }
+        .seal(key)
+    }
+
+    /// The head with the hash of its fields
+    pub fn seal(mut self, key: Option<&[u8]>) -> Self {
+        self.mac = self.digest(key);
+        self
+    }
+
+    /// Hash of every field but the mac
+    pub fn digest(&self, key: Option<&[u8]>) -> String {
+        digest(
+            key,
+            &[
+                &self.records.to_string(),
+                &self.hash,
+                &self.first.to_string(),
+                &self.first_prev_hash,
+            ],
+        )
+    }
+}
+
+/// HMAC-SHA256 of the fields with `key`, SHA-256 without one, as lowercase
+/// hex
+///
+/// Fields are prefixed with their length so that no two records hash the
+/// same content.
+fn digest(key: Option<&[u8]>, fields: &[&str]) -> String {
+    let mut message = Vec::new();
+    for field in fields {
+        message.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        message.extend_from_slice(field.as_bytes());
+    }
+    let hash = match key {
+        Some(key) => hmac_sha256(key, &message),
+        None => Sha256::digest(&message).into(),
+    };
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// HMAC-SHA256 of RFC 2104
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// Key of the hashes, read once from `statemanager.safety_log_key_path`
+///
+/// ### Returns
+/// * `Ok(None)` - no key file is set
+/// * `Err(String)` - the key file is set but cannot be read or is empty
+fn key() -> Result<Option<&'static [u8]>, String> {
+    static KEY: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+    if let Some(key) = KEY.get() {
+        return Ok(key.as_deref());
+    }
+    let path = &common::setting::get_config()
+        .statemanager
+        .safety_log_key_path;
+    let key = if path.is_empty() {
+        None
+    } else {
+        let key = std::fs::read(path).map_err(|e| format!("Key {} not read: {}", path, e))?;
+        if key.is_empty() {
+            return Err(format!("Key {} is empty", path));
+        }
+        Some(key)
+    };
+    Ok(KEY.get_or_init(|| key).as_deref())
+}
+
+/// Key of the record of a sequence, zero padded so that keys sort by sequence
+pub fn key_of(sequence: u64) -> String {
+    format!("{}{:0width$}", PREFIX, sequence, width = SEQUENCE_DIGITS)
+}
+
+/// Common prefix of the keys of the records of a page
+fn page_prefix(page: u64) -> String {
+    format!(
+        "{}{:0width$}",
+        PREFIX,
+        page,
+        width = SEQUENCE_DIGITS - PAGE_DIGITS
+    )
+}
+
+fn pending() -> &'static Mutex<VecDeque<SafetyEvent>> {
+    static PENDING: OnceLock<Mutex<VecDeque<SafetyEvent>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Wakes the writer when events are queued
+fn queued() -> &'static Notify {
+    static QUEUED: OnceLock<Notify> = OnceLock::new();
+    QUEUED.get_or_init(Notify::new)
+}
+
+/// Queue an event for the log
+pub fn record(event: SafetyEvent) {
+    {
+        let mut pending = pending().lock().unwrap_or_else(|e| e.into_inner());
+        if pending.len() >= MAX_PENDING {
+            if let Some(dropped) = pending.pop_front() {
+                logd!(
+                    5,
+                    "[SafetyLog] Event of {} at {} ns not logged, {} events wait",
+                    dropped.resource_name,
+                    dropped.timestamp_ns,
+                    MAX_PENDING
+                );
+            }
+        }
+        pending.push_back(event);
+    }
+    queued().notify_one();
+}
+
+/// Queue a state transition for the log
+pub fn record_transition(record: &TransitionRecord) {
+    self::record(SafetyEvent {
+        timestamp_ns: record.timestamp_ns,
+        kind: "transition".to_string(),
+        resource_type: record.resource_type.clone(),
+        resource_name: record.resource_name.clone(),
+        transition_id: record.transition_id.clone(),
+        detail: format!("{} -> {}", record.from_state, record.to_state),
+    });
+}
+
+/// Queue an alert for the log
+pub fn record_alert(record: &AlertRecord) {
+    self::record(SafetyEvent {
+        timestamp_ns: record.timestamp_ns,
+        kind: "alert".to_string(),
+        resource_type: record.resource_type.clone(),
+        resource_name: record.resource_name.clone(),
+        transition_id: String::new(),
+        detail: record.message.clone(),
+    });
+}
+
+/// Head of the stored log
+async fn read_head(storage: &dyn StateStorage, key: Option<&[u8]>) -> Result<Head, String> {
+    match storage.get(HEAD).await {
+        Ok(value) => serde_json::from_str(&value).map_err(|e| format!("Invalid head: {}", e)),
+        Err(e) if e == NOT_FOUND => Ok(Head::empty(key)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Store a record following `head` together with the head it leads to
+async fn append(
+    storage: &dyn StateStorage,
+    key: Option<&[u8]>,
+    head: &Head,
+    record: &SafetyRecord,
+) -> Result<Head, String> {
+    let next = Head {
+        records: record.sequence + 1,
+        hash: record.hash.clone(),
+        ..head.clone()
+    }
+    .seal(key);
+    let mut transaction = Transaction::default();
+    transaction.put(
+        &key_of(record.sequence),
+        &serde_json::to_string(record).map_err(|e| e.to_string())?,
+    );
+    transaction.put(
+        HEAD,
+        &serde_json::to_string(&next).map_err(|e| e.to_string())?,
+    );
+    storage.commit(transaction).await?;
+    Ok(next)
+}
+
+/// Prune the records of `head` beyond the last `retention`, none for 0
+///
+/// The head moves past the pruned records before they are removed, so a
+/// removal that fails leaves records verification no longer reads.
+async fn prune(
+    storage: &dyn StateStorage,
+    key: Option<&[u8]>,
+    head: &Head,
+    retention: u64,
+) -> Result<Head, String> {
+    if retention == 0 || head.records - head.first < retention + PRUNE_BATCH {
+        return Ok(head.clone());
+    }
+    let first = head.records - retention;
+    let record: SafetyRecord = serde_json::from_str(&storage.get(&key_of(first)).await?)
+        .map_err(|e| format!("Record {} cannot be read: {}", first, e))?;
+    if record.digest(key) != record.hash {
+        return Err(format!("Record {} was modified", first));
+    }
+    let pruned = Head {
+        first,
+        first_prev_hash: record.prev_hash,
+        ..head.clone()
+    }
+    .seal(key);
+    storage
+        .put(
+            HEAD,
+            &serde_json::to_string(&pruned).map_err(|e| e.to_string())?,
+        )
+        .await?;
+    for sequence in head.first..first {
+        storage.delete(&key_of(sequence)).await?;
+    }
+    Ok(pruned)
+}
+
+/// Append the queued events to the log, in order
+pub async fn run_writer() {
+    let key = loop {
+        match key() {
+            Ok(key) => break key,
+            Err(e) => {
+                logd!(6, "[SafetyLog] {}, no event is logged", e);
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+    };
+    if key.is_none() {
+        logd!(
+            5,
+            "[SafetyLog] No statemanager.safety_log_key_path, records are hashed without a key"
+        );
+    }
+    let storage = crate::storage::storage();
+    let retention = common::setting::get_config()
+        .statemanager
+        .safety_log_retention;
+    let mut head = loop {
+        match read_head(storage, key).await {
+            Ok(head) => break head,
+            Err(e) => {
+                logd!(4, "[SafetyLog] Head not read: {}", e);
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+    };
+    logd!(2, "[SafetyLog] Appending after {} records", head.records);
+    loop {
+        let event = pending()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front();
+        let Some(event) = event else {
+            queued().notified().await;
+            continue;
+        };
+        let record = SafetyRecord::chain(event, head.records, &head.hash, key);
+        head = loop {
+            match append(storage, key, &head, &record).await {
+                Ok(head) => break head,
+                Err(e) => {
+                    logd!(
+                        4,
+                        "[SafetyLog] Record {} not stored: {}",
+                        record.sequence,
+                        e
+                    );
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        };
+        match prune(storage, key, &head, retention).await {
+            Ok(pruned) => head = pruned,
+            Err(e) => logd!(
+                4,
+                "[SafetyLog] Records before {} not pruned: {}",
+                head.records - retention,
+                e
+            ),
+        }
+    }
+}
+
+/// Walk along the chain of a log from its first stored record
+struct Chain<'a> {
+    key: Option<&'a [u8]>,
+    head: &'a Head,
+    /// Sequence of the next record
+    next: u64,
+    prev_hash: String,
+}
+
+impl<'a> Chain<'a> {
+    fn new(key: Option<&'a [u8]>, head: &'a Head) -> Self {
+        Self {
+            key,
+            head,
+            next: head.first,
+            prev_hash: head.first_prev_hash.clone(),
+        }
+    }
+
+    fn invalid(&self, sequence: u64, message: String) -> VerifySafetyLogResponse {
+        VerifySafetyLogResponse {
+            valid: false,
+            records: self.next - self.head.first,
+            head_hash: self.head.hash.clone(),
+            first_invalid: Some(sequence),
+            message,
+        }
+    }
+
+    fn check_head(&self) -> Result<(), VerifySafetyLogResponse> {
+        if self.head.digest(self.key) != self.head.mac || self.head.first > self.head.records {
+            return Err(self.invalid(self.head.first, "Head was modified".to_string()));
+        }
+        Ok(())
+    }
+
+    fn follow(&mut self, record: &SafetyRecord) -> Result<(), VerifySafetyLogResponse> {
+        let expected = self.next;
+        if record.sequence != expected {
+            return Err(self.invalid(
+                expected,
+                format!("Record {} is missing, found {}", expected, record.sequence),
+            ));
+        }
+        if record.prev_hash != self.prev_hash {
+            return Err(self.invalid(
+                expected,
+                format!("Record {} does not follow the record before it", expected),
+            ));
+        }
+        if record.digest(self.key) != record.hash {
+            return Err(self.invalid(expected, format!("Record {} was modified", expected)));
+        }
+        self.next += 1;
+        self.prev_hash = record.hash.clone();
+        Ok(())
+    }
+
+    fn finish(self) -> VerifySafetyLogResponse {
+        if self.next != self.head.records || self.prev_hash != self.head.hash {
+            return self.invalid(
+                self.next.min(self.head.records),
+                format!(
+                    "Log has {} records, its head expects {}",
+                    self.next, self.head.records
+                ),
+            );
+        }
+        let count = self.next - self.head.first;
+        VerifySafetyLogResponse {
+            valid: true,
+            records: count,
+            head_hash: self.head.hash.clone(),
+            first_invalid: None,
+            message: format!("{} records verified", count),
+        }
+    }
+}
+
+/// Check the chain of `records`, the stored ones ordered by key, against
+/// `head`
+pub fn check(records: &[SafetyRecord], head: &Head, key: Option<&[u8]>) -> VerifySafetyLogResponse {
+    let mut chain = Chain::new(key, head);
+    if let Err(invalid) = chain.check_head() {
+        return invalid;
+    }
+    for record in records {
+        if let Err(invalid) = chain.follow(record) {
+            return invalid;
+        }
+    }
+    chain.finish()
+}
+
+/// Check the log of a storage, a page of records at a time
+async fn verify_in(
+    storage: &dyn StateStorage,
+    key: Option<&[u8]>,
+) -> Result<VerifySafetyLogResponse, String> {
+    let head = read_head(storage, key).await?;
+    let mut chain = Chain::new(key, &head);
+    if let Err(invalid) = chain.check_head() {
+        return Ok(invalid);
+    }
+    let first_key = key_of(head.first);
+    let mut page = head.first / PAGE;
+    while page * PAGE < head.records {
+        for (stored, value) in storage.get_all_with_prefix(&page_prefix(page)).await? {
+            // Left over by a pruning that did not finish
+            if stored < first_key {
+                continue;
+            }
+            let record = match serde_json::from_str(&value) {
+                Ok(record) => record,
+                Err(e) => {
+                    return Ok(chain.invalid(
+                        chain.next,
+                        format!("Record {} cannot be read: {}", stored, e),
+                    ))
+                }
+            };
+            if let Err(invalid) = chain.follow(&record) {
+                return Ok(invalid);
+            }
+        }
+        page += 1;
+    }
+    Ok(chain.finish())
+}
+
+/// Check the stored log
+pub async fn verify() -> common::Result<VerifySafetyLogResponse> {
+    Ok(verify_in(crate::storage::storage(), key()?)
+        .await
+        .map_err(common::etcd::error)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStateStorage;
+
+    const KEY: Option<&[u8]> = Some(b"safety-log-test-key");
+
+    fn event(sequence: u64) -> SafetyEvent {
+        SafetyEvent {
+            timestamp_ns: sequence as i64,
+            kind: "transition".to_string(),
+            resource_type: "scenario".to_string(),
+            resource_name: format!("scenario-{}", sequence),
+            transition_id: format!("t-{}", sequence),
+            detail: "Idle -> Waiting".to_string(),
+        }
+    }
+
+    fn log(count: u64) -> (Vec<SafetyRecord>, Head) {
+        let mut head = Head::empty(KEY);
+        let mut records = Vec::new();
+        for sequence in 0..count {
+            let record = SafetyRecord::chain(event(sequence), head.records, &head.hash, KEY);
+            head = Head {
+                records: record.sequence + 1,
+                hash: record.hash.clone(),
+                ..head
+            }
+            .seal(KEY);
+            records.push(record);
+        }
+        (records, head)
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // Test case 2 of RFC 4231
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = mac.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_intact_log_is_valid() {
+        let (records, head) = log(3);
+        let result = check(&records, &head, KEY);
+        assert!(result.valid, "{}", result.message);
+        assert_eq!(result.records, 3);
+        assert_eq!(result.first_invalid, None);
+        assert_eq!(records[1].prev_hash, records[0].hash);
+
+        assert!(check(&[], &Head::empty(KEY), KEY).valid);
+        // Verified with another key, nothing matches
+        assert!(!check(&records, &head, Some(b"other")).valid);
+    }
+
+    #[test]
+    fn test_modified_record_is_detected() {
+        let (mut records, head) = log(3);
+        records[1].detail = "Idle -> Allowed".to_string();
+        let result = check(&records, &head, KEY);
+        assert!(!result.valid);
+        assert_eq!(result.first_invalid, Some(1));
+
+        // Rehashing the record breaks the link of the next one
+        records[1].hash = records[1].digest(KEY);
+        assert_eq!(check(&records, &head, KEY).first_invalid, Some(2));
+    }
+
+    #[test]
+    fn test_rewrite_without_the_key_is_detected() {
+        let (mut records, head) = log(3);
+        // Whoever writes the storage can rehash, but not with the key
+        records[2].detail = "Idle -> Allowed".to_string();
+        records[2].hash = records[2].digest(None);
+        let forged = Head {
+            hash: records[2].hash.clone(),
+            ..head.clone()
+        }
+        .seal(None);
+        let result = check(&records, &forged, KEY);
+        assert!(!result.valid);
+        assert_eq!(result.message, "Head was modified");
+
+        // A head moved back to cut the end of the log off
+        let cut = Head {
+            records: 2,
+            hash: records[1].hash.clone(),
+            ..head
+        };
+        assert_eq!(check(&records[..2], &cut, KEY).message, "Head was modified");
+    }
+
+    #[test]
+    fn test_removed_records_are_detected() {
+        let (records, head) = log(4);
+
+        let mut gap = records.clone();
+        gap.remove(1);
+        assert_eq!(check(&gap, &head, KEY).first_invalid, Some(1));
+
+        let truncated = &records[..2];
+        let result = check(truncated, &head, KEY);
+        assert!(!result.valid);
+        assert_eq!(result.first_invalid, Some(2));
+        assert!(result.message.contains("expects 4"), "{}", result.message);
+
+        assert_eq!(check(&[], &head, KEY).first_invalid, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_pruned_log_is_verified_by_page() {
+        let storage = InMemoryStateStorage::default();
+        let retention = 100;
+        let mut head = read_head(&storage, KEY).await.unwrap();
+        for sequence in 0..1_050 {
+            let record = SafetyRecord::chain(event(sequence), head.records, &head.hash, KEY);
+            head = append(&storage, KEY, &head, &record).await.unwrap();
+            head = prune(&storage, KEY, &head, retention).await.unwrap();
+        }
+
+        // Pruned at 1000 records down to the last 100, spanning two pages
+        assert_eq!(head.first, 900);
+        assert!(storage.get(&key_of(899)).await.is_err());
+        assert!(storage.get(&key_of(900)).await.is_ok());
+        let result = verify_in(&storage, KEY).await.unwrap();
+        assert!(result.valid, "{}", result.message);
+        assert_eq!(result.records, 150);
+
+        let mut record: SafetyRecord =
+            serde_json::from_str(&storage.get(&key_of(1_020)).await.unwrap()).unwrap();
+        record.detail = "Idle -> Allowed".to_string();
+        storage
+            .put(&key_of(1_020), &serde_json::to_string(&record).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            verify_in(&storage, KEY).await.unwrap().first_invalid,
+            Some(1_020)
+        );
+    }
+}
@@ -0,0 +1,425 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Time based triggers of scenarios
+//!
+//! A Scenario whose spec has a `schedule` (cron expression, one-shot delay or
+//! interval) is triggered by the StateManager. When the scheduler first sees
+//! the scenario it moves it from idle to waiting; whenever the schedule is due
+//! it emits the `waiting -> satisfied` StateChange through the engine channel
+//! and triggers the ActionController, as FilterGateway does for a met
+//! condition. Once the run of a cron or interval schedule ended with the
+//! scenario completed or denied, the scheduler re-arms it by moving it back
+//! to waiting, ready for the next time the schedule is due.
+//!
+//! When each schedule was registered and last fired is persisted under
+//! `/schedule/{scenario}`. After a restart a delay keeps its deadline, and a
+//! cron or interval schedule that was missed while the StateManager was down
+//! fires once.
+//!
+//! With leader election only the leader runs the schedules, see
+//! [`crate::leader`].
+
+use crate::queue::BoundedSender;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::logd;
+use common::spec::artifact::scenario::{Schedule, Trigger};
+use common::spec::artifact::{Artifact, Scenario};
+use common::state_mapping::StateName;
+use common::statemanager::{ResourceType, ScenarioState, StateChange};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Storage prefix of the schedule records
+const RECORD_PREFIX: &str = "/schedule/";
+/// Resolution of the scheduler
+const TICK: Duration = Duration::from_secs(1);
+
+/// Persisted state of one schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRecord {
+    /// The schedule the times refer to, a changed schedule starts over
+    pub schedule: Schedule,
+    pub registered_at: DateTime<Utc>,
+    pub last_fired: Option<DateTime<Utc>>,
+    /// The schedule recurs and fired, the scenario waits to be re-armed
+    #[serde(default)]
+    pub rearm: bool,
+}
+
+/// Next time a trigger is due, none once a delay fired
+fn next_fire(trigger: &Trigger, record: &ScheduleRecord) -> Option<DateTime<Utc>> {
+    let since = record.last_fired.unwrap_or(record.registered_at);
+    let after = |period: &Duration| {
+        TimeDelta::from_std(*period)
+            .ok()
+            .and_then(|period| since.checked_add_signed(period))
+    };
+    match trigger {
+        Trigger::Delay(delay) if record.last_fired.is_none() => after(delay),
+        Trigger::Delay(_) => None,
+        Trigger::Interval(interval) => after(interval),
+        Trigger::Cron(schedule) => schedule.after(&since).next(),
+    }
+}
+
+fn record_key(scenario_name: &str) -> String {
+    format!("{}{}", RECORD_PREFIX, scenario_name)
+}
+
+/// Scheduled scenario
+struct Entry {
+    trigger: Trigger,
+    record: ScheduleRecord,
+}
+
+/// Scheduler of the scenarios with a `schedule`
+pub struct Scheduler {
+    tx_state_change: BoundedSender<StateChange>,
+    entries: HashMap<String, Entry>,
+}
+
+impl Scheduler {
+    pub fn new(tx_state_change: BoundedSender<StateChange>) -> Self {
+        Self {
+            tx_state_change,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Reload the scheduled scenarios, keeping the record of known schedules
+    ///
+    /// Returns the scenarios that were newly registered.
+    async fn refresh(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let storage = crate::storage::storage();
+        let scenarios = match storage.get_all_with_prefix("Scenario/").await {
+            Ok(scenarios) => scenarios,
+            Err(e) => {
+                logd!(4, "[Scheduler] Failed to load scenarios: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut scheduled = HashMap::new();
+        for (key, yaml) in scenarios {
+            let Ok(scenario) = serde_yaml::from_str::<Scenario>(&yaml) else {
+                continue;
+            };
+            let Some(schedule) = scenario.get_schedule() else {
+                continue;
+            };
+            match schedule.trigger() {
+                Ok(trigger) => {
+                    scheduled.insert(scenario.get_qualified_name(), (schedule, trigger));
+                }
+                Err(e) => logd!(4, "[Scheduler] Ignoring schedule of {}: {}", key, e),
+            }
+        }
+
+        // Scenarios that were deleted or lost their schedule
+        let removed: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|name| !scheduled.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in removed {
+            self.entries.remove(&name);
+            let _ = storage.delete(&record_key(&name)).await;
+            logd!(2, "[Scheduler] Unscheduled scenario {}", name);
+        }
+
+        let mut registered = Vec::new();
+        for (name, (schedule, trigger)) in scheduled {
+            if self
+                .entries
+                .get(&name)
+                .is_some_and(|entry| entry.record.schedule == schedule)
+            {
+                continue;
+            }
+
+            let stored = storage
+                .get(&record_key(&name))
+                .await
+                .ok()
+                .and_then(|value| serde_yaml::from_str::<ScheduleRecord>(&value).ok())
+                .filter(|record| record.schedule == schedule);
+            let record = match stored {
+                Some(record) => record,
+                None => {
+                    let record = ScheduleRecord {
+                        schedule,
+                        registered_at: now,
+                        last_fired: None,
+                        rearm: false,
+                    };
+                    Self::persist(&name, &record).await;
+                    registered.push(name.clone());
+                    record
+                }
+            };
+            logd!(
+                2,
+                "[Scheduler] Scheduled scenario {}, next at {:?}",
+                name,
+                next_fire(&trigger, &record)
+            );
+            self.entries.insert(name, Entry { trigger, record });
+        }
+        registered
+    }
+
+    /// Mark the schedules that are due as fired
+    ///
+    /// Returns the scenarios to trigger.
+    async fn take_due(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let mut due = Vec::new();
+        for (name, entry) in self.entries.iter_mut() {
+            if next_fire(&entry.trigger, &entry.record).is_some_and(|next| next <= now) {
+                entry.record.last_fired = Some(now);
+                entry.record.rearm = !matches!(entry.trigger, Trigger::Delay(_));
+                Self::persist(name, &entry.record).await;
+                due.push(name.clone());
+            }
+        }
+        due.sort();
+        due
+    }
+
+    /// Mark the fired recurring schedules whose run ended as re-armed
+    ///
+    /// Returns the scenarios to move back to waiting, with the state their
+    /// run ended in.
+    async fn take_rearmable(&mut self) -> Vec<(String, String)> {
+        let mut rearmable = Vec::new();
+        for (name, entry) in self.entries.iter_mut() {
+            if !entry.record.rearm {
+                continue;
+            }
+            let state = crate::storage::storage()
+                .get(&format!("/scenario/{}/state", name))
+                .await
+                .ok()
+                .and_then(|state| ScenarioState::parse(&state));
+            let Some(state @ (ScenarioState::Completed | ScenarioState::Denied)) = state else {
+                continue;
+            };
+            entry.record.rearm = false;
+            Self::persist(name, &entry.record).await;
+            rearmable.push((name.clone(), state.name().to_string()));
+        }
+        rearmable.sort();
+        rearmable
+    }
+
+    async fn persist(name: &str, record: &ScheduleRecord) {
+        match serde_yaml::to_string(record) {
+            Ok(value) => {
+                if let Err(e) = crate::storage::storage()
+                    .put(&record_key(name), &value)
+                    .await
+                {
+                    logd!(4, "[Scheduler] Failed to save schedule of {}: {}", name, e);
+                }
+            }
+            Err(e) => logd!(4, "[Scheduler] Failed to serialize schedule: {}", e),
+        }
+    }
+
+    /// Send a scenario StateChange to the engine
+    async fn send_state_change(&self, scenario_name: &str, current: &str, target: &str) {
+        let timestamp = common::clock::now_ns();
+        let state_change = StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: scenario_name.to_string(),
+            current_state: current.to_string(),
+            target_state: target.to_string(),
+            transition_id: common::correlation::transition_id("scheduler"),
+            timestamp_ns: timestamp,
+            source: "scheduler".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+        let transition_id = state_change.transition_id.clone();
+        crate::pending::queued(&state_change);
+        if let Err(e) = self.tx_state_change.send(state_change).await {
+            logd!(
+                4,
+                "[Scheduler] Failed to queue StateChange of {}: {}",
+                scenario_name,
+                e
+            );
+            crate::pending::discard(&transition_id);
+        }
+    }
+
+    /// Activate a scenario whose schedule is due
+    async fn fire(&self, scenario_name: &str) {
+        logd!(
+            3,
+            "[Scheduler] Schedule of scenario {} is due",
+            scenario_name
+        );
+        self.send_state_change(scenario_name, "waiting", "satisfied")
+            .await;
+        if let Err(e) = crate::grpc::sender::action_controller()
+            .trigger_action(scenario_name.to_string())
+            .await
+        {
+            logd!(
+                5,
+                "[Scheduler] Failed to trigger ActionController for {}: {:?}",
+                scenario_name,
+                e
+            );
+        }
+    }
+
+    /// Run the scheduler until the process stops
+    pub async fn run(mut self) {
+        let mut last_refresh: Option<DateTime<Utc>> = None;
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            // Only the leader fires schedules, it reloads them once elected
+            if !crate::leader::is_leader() {
+                self.entries.clear();
+                last_refresh = None;
+                continue;
+            }
+            let refresh_secs = common::setting::get_config()
+                .statemanager
+                .schedule_refresh_secs
+                .max(1);
+            let refresh = TimeDelta::seconds(refresh_secs as i64);
+            let now = common::clock::now_utc();
+            if last_refresh.is_none_or(|last| now - last >= refresh) {
+                last_refresh = Some(now);
+                for name in self.refresh(now).await {
+                    self.send_state_change(&name, "idle", "waiting").await;
+                }
+            }
+            for (name, state) in self.take_rearmable().await {
+                logd!(2, "[Scheduler] Re-arming scenario {} after its run", name);
+                self.send_state_change(&name, &state, "waiting").await;
+            }
+            for name in self.take_due(now).await {
+                self.fire(&name).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(schedule: Schedule, last_fired: Option<i64>) -> (Trigger, ScheduleRecord) {
+        let at = |secs: i64| Utc.timestamp_opt(secs, 0).unwrap();
+        (
+            schedule.trigger().unwrap(),
+            ScheduleRecord {
+                schedule,
+                registered_at: at(1_000),
+                last_fired: last_fired.map(at),
+                rearm: false,
+            },
+        )
+    }
+
+    #[test]
+    fn test_next_fire() {
+        let at = |secs: i64| Some(Utc.timestamp_opt(secs, 0).unwrap());
+
+        let (trigger, delay) = record(Schedule::delay(30), None);
+        assert_eq!(next_fire(&trigger, &delay), at(1_030));
+        let (trigger, fired) = record(Schedule::delay(30), Some(1_030));
+        assert_eq!(next_fire(&trigger, &fired), None);
+
+        let (trigger, interval) = record(Schedule::interval(60), Some(1_100));
+        assert_eq!(next_fire(&trigger, &interval), at(1_160));
+
+        // Every minute on the minute, counted from the last fire
+        let (trigger, cron) = record(Schedule::cron("* * * * *"), Some(1_090));
+        assert_eq!(next_fire(&trigger, &cron), at(1_140));
+    }
+
+    #[tokio::test]
+    async fn test_take_due_fires_once_and_persists() {
+        common::etcd::use_in_memory_store();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let mut scheduler = Scheduler::new(tx.into());
+        let (trigger, record) = record(Schedule::delay(30), None);
+        scheduler
+            .entries
+            .insert("delayed".to_string(), Entry { trigger, record });
+
+        let now = Utc.timestamp_opt(1_020, 0).unwrap();
+        assert!(scheduler.take_due(now).await.is_empty());
+        let now = Utc.timestamp_opt(1_031, 0).unwrap();
+        assert_eq!(scheduler.take_due(now).await, vec!["delayed".to_string()]);
+        assert!(scheduler.take_due(now).await.is_empty());
+        assert_eq!(scheduler.entries["delayed"].record.last_fired, Some(now));
+
+        scheduler
+            .send_state_change("delayed", "waiting", "satisfied")
+            .await;
+        let state_change = rx.recv().await.unwrap();
+        assert_eq!(state_change.resource_name, "delayed");
+        assert_eq!(state_change.target_state, "satisfied");
+        assert_eq!(state_change.source, "scheduler");
+    }
+
+    #[tokio::test]
+    async fn test_recurring_schedule_is_rearmed_after_its_run() {
+        common::etcd::use_in_memory_store();
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let mut scheduler = Scheduler::new(tx.into());
+        let (trigger, interval) = record(Schedule::interval(60), None);
+        scheduler.entries.insert(
+            "recurring".to_string(),
+            Entry {
+                trigger,
+                record: interval,
+            },
+        );
+        let (trigger, delay) = record(Schedule::delay(30), None);
+        scheduler.entries.insert(
+            "once".to_string(),
+            Entry {
+                trigger,
+                record: delay,
+            },
+        );
+
+        let now = Utc.timestamp_opt(1_100, 0).unwrap();
+        assert_eq!(scheduler.take_due(now).await, ["once", "recurring"]);
+        assert!(scheduler.entries["recurring"].record.rearm);
+        assert!(!scheduler.entries["once"].record.rearm);
+
+        // Not re-armed while the run goes on
+        let storage = crate::storage::storage();
+        storage
+            .put("/scenario/recurring/state", "SCENARIO_STATE_ALLOWED")
+            .await
+            .unwrap();
+        assert!(scheduler.take_rearmable().await.is_empty());
+
+        storage
+            .put("/scenario/recurring/state", "SCENARIO_STATE_COMPLETED")
+            .await
+            .unwrap();
+        assert_eq!(
+            scheduler.take_rearmable().await,
+            [("recurring".to_string(), "Completed".to_string())]
+        );
+        assert!(scheduler.take_rearmable().await.is_empty());
+    }
+}
@@ -26,17 +26,29 @@
 //! let result = state_machine.process_state_change(state_change);
 //! ```
 
+use crate::failure::{Failures, Verdict};
+use crate::storage::Transaction;
 use crate::types::{
-    ActionCommand, ContainerState, HealthStatus, ResourceState, StateTransition, TransitionResult,
+    ActionCommand, ContainerState, HealthStatus, ResourceState, StateTransition, TimeoutEvent,
+    TransitionResult, ANY_EVENT, ANY_STATE,
 };
+use common::clock::Clock;
 use common::logd;
 use common::spec::artifact::Artifact;
+use common::spec::k8s::pod::LABEL_STANDBY;
+use common::state_mapping::{self, StateName};
 use common::statemanager::{
-    ErrorCode, ModelState, PackageState, ResourceType, ScenarioState, StateChange,
+    ErrorCode, ModelState, NetworkState, NodeState, PackageState, ResourceType, ScenarioState,
+    StateChange, CANARY_PROMOTED, CANARY_ROLLED_BACK,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
-use tokio::time::Instant;
+
+/// Containers of a model by the instance running them, its node and, for a
+/// replicated model, its replica
+pub type ModelInstances<'a> = BTreeMap<String, Vec<&'a common::monitoringserver::ContainerInfo>>;
 
 // ========================================
 // CONSTANTS AND CONFIGURATION
@@ -61,12 +73,10 @@ impl TransitionResult {
         common::statemanager::StateChangeResponse {
             message: self.message.clone(),
             transition_id: self.transition_id.clone(),
-            timestamp_ns: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_nanos() as i64,
+            timestamp_ns: common::clock::now_ns(),
             error_code: self.error_code as i32,
             error_details: self.error_details.clone(),
+            metadata: Default::default(),
         }
     }
 }
@@ -101,6 +111,18 @@ pub struct StateMachine {
 
     /// Action command sender for async execution
     action_sender: Option<mpsc::UnboundedSender<ActionCommand>>,
+
+    /// Maximum time a resource may stay in a transitional state
+    ///
+    /// Resources exceeding it are moved to the failure state of their type
+    /// by [`StateMachine::check_timeouts`].
+    state_timeouts: HashMap<(ResourceType, i32), Duration>,
+
+    /// Failures of the containers evaluated, see [`crate::failure`]
+    failures: std::sync::Mutex<Failures>,
+
+    /// Clock of the transition timestamps and state timeouts
+    clock: Arc<dyn Clock>,
 }
 
 impl StateMachine {
@@ -121,10 +143,16 @@ impl StateMachine {
             transition_tables: HashMap::new(),
             resource_states: HashMap::new(),
             action_sender: None,
+            state_timeouts: HashMap::new(),
+            failures: Default::default(),
+            clock: common::clock::get(),
         };
 
         // Initialize transition tables for each resource type
         state_machine.initialize_scenario_transitions();
+        state_machine.initialize_network_transitions();
+        state_machine.initialize_node_transitions();
+        state_machine.initialize_package_transitions();
 
         state_machine
     }
@@ -136,6 +164,34 @@ impl StateMachine {
         receiver
     }
 
+    /// Copy of the tracked states and transition rules that queues no actions
+    ///
+    /// State changes processed by the copy leave this state machine untouched,
+    /// which lets them be simulated before they are requested.
+    pub fn snapshot(&self) -> Self {
+        StateMachine {
+            transition_tables: self.transition_tables.clone(),
+            resource_states: self.resource_states.clone(),
+            action_sender: None,
+            state_timeouts: self.state_timeouts.clone(),
+            failures: std::sync::Mutex::new(self.failures().clone()),
+            clock: self.clock.clone(),
+        }
+    }
+
+    /// State machine taking its timestamps and measuring the failures of
+    /// containers with `clock`
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        let mut state_machine = Self::new();
+        *state_machine.failures() = Failures::new(clock.clone());
+        state_machine.clock = clock;
+        state_machine
+    }
+
+    fn failures(&self) -> std::sync::MutexGuard<'_, Failures> {
+        self.failures.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     // ========================================
     // STATE TRANSITION TABLE INITIALIZATION
     // ========================================
@@ -155,7 +211,7 @@ impl StateMachine {
     /// - "Active" -> "Inactive" on "deactivate" event
     /// - Any state -> "Failed" on "error" event
     fn initialize_scenario_transitions(&mut self) {
-        let scenario_transitions = vec![
+        let mut scenario_transitions = vec![
             StateTransition {
                 from_state: ScenarioState::Idle as i32,
                 event: "scenario_activation".to_string(),
@@ -177,6 +233,13 @@ impl StateMachine {
                 condition: None,
                 action: "execute_action_on_target_package".to_string(),
             },
+            StateTransition {
+                from_state: ScenarioState::Satisfied as i32,
+                event: "activation_deferred".to_string(),
+                to_state: ScenarioState::Waiting as i32,
+                condition: None,
+                action: "start_condition_evaluation".to_string(),
+            },
             StateTransition {
                 from_state: ScenarioState::Satisfied as i32,
                 event: "policy_verification_failure".to_string(),
@@ -191,11 +254,242 @@ impl StateMachine {
                 condition: None,
                 action: "finalize_scenario".to_string(),
             },
+            StateTransition {
+                from_state: ScenarioState::Allowed as i32,
+                event: "admission_deferred".to_string(),
+                to_state: ScenarioState::Pending as i32,
+                condition: None,
+                action: "wait_for_capacity".to_string(),
+            },
+            StateTransition {
+                from_state: ScenarioState::Allowed as i32,
+                event: "admission_denied".to_string(),
+                to_state: ScenarioState::Denied as i32,
+                condition: None,
+                action: "log_denial_generate_alert".to_string(),
+            },
+            StateTransition {
+                from_state: ScenarioState::Pending as i32,
+                event: "scenario_completion".to_string(),
+                to_state: ScenarioState::Completed as i32,
+                condition: None,
+                action: "finalize_scenario".to_string(),
+            },
+            StateTransition {
+                from_state: ScenarioState::Pending as i32,
+                event: "admission_denied".to_string(),
+                to_state: ScenarioState::Denied as i32,
+                condition: None,
+                action: "log_denial_generate_alert".to_string(),
+            },
         ];
+        // The scheduler re-arms a recurring scenario once its run ended
+        let rearms = [ScenarioState::Completed, ScenarioState::Denied]
+            .into_iter()
+            .map(|from| StateTransition {
+                from_state: from as i32,
+                event: "schedule_rearmed".to_string(),
+                to_state: ScenarioState::Waiting as i32,
+                condition: None,
+                action: "start_condition_evaluation".to_string(),
+            });
+        scenario_transitions.extend(rearms);
+        // Operators deactivate a scenario from any state but idle
+        scenario_transitions.push(StateTransition {
+            from_state: ANY_STATE,
+            event: "scenario_deactivation".to_string(),
+            to_state: ScenarioState::Idle as i32,
+            condition: None,
+            action: "stop_condition_evaluation".to_string(),
+        });
+        // Operators re-trigger a scenario from any state, its conditions are
+        // taken as met; a waiting one meets them the usual way
+        let retriggers = [
+            ScenarioState::Idle,
+            ScenarioState::Allowed,
+            ScenarioState::Pending,
+            ScenarioState::Completed,
+            ScenarioState::Denied,
+        ]
+        .into_iter()
+        .map(|from| StateTransition {
+            from_state: from as i32,
+            event: "scenario_retrigger".to_string(),
+            to_state: ScenarioState::Satisfied as i32,
+            condition: None,
+            action: "start_policy_verification".to_string(),
+        });
+        scenario_transitions.extend(retriggers);
         self.transition_tables
             .insert(ResourceType::Scenario, scenario_transitions);
     }
 
+    /// Initialize network state transitions
+    ///
+    /// Networks of models are set up by Pharos on request of the
+    /// ActionController, which reports every request and its outcome. A
+    /// failed or outdated setup can be requested again.
+    fn initialize_network_transitions(&mut self) {
+        let network_transitions = vec![
+            StateTransition {
+                from_state: NetworkState::Unspecified as i32,
+                event: "network_setup_requested".to_string(),
+                to_state: NetworkState::Requested as i32,
+                condition: None,
+                action: "wait_for_network_setup".to_string(),
+            },
+            StateTransition {
+                from_state: NetworkState::Requested as i32,
+                event: "network_setup_succeeded".to_string(),
+                to_state: NetworkState::Ready as i32,
+                condition: None,
+                action: "release_dependent_models".to_string(),
+            },
+            StateTransition {
+                from_state: NetworkState::Requested as i32,
+                event: "network_setup_failed".to_string(),
+                to_state: NetworkState::Failed as i32,
+                condition: None,
+                action: "log_network_failure".to_string(),
+            },
+            StateTransition {
+                from_state: NetworkState::Failed as i32,
+                event: "network_setup_retry".to_string(),
+                to_state: NetworkState::Requested as i32,
+                condition: None,
+                action: "wait_for_network_setup".to_string(),
+            },
+            StateTransition {
+                from_state: NetworkState::Ready as i32,
+                event: "network_setup_requested".to_string(),
+                to_state: NetworkState::Requested as i32,
+                condition: None,
+                action: "wait_for_network_setup".to_string(),
+            },
+        ];
+        self.transition_tables
+            .insert(ResourceType::Network, network_transitions);
+    }
+
+    /// Initialize node maintenance transitions
+    ///
+    /// The apiserver cordons and uncordons nodes on request of an operator.
+    /// A drain is run by the ActionController, which reports its start and
+    /// whether every model of the node could be re-placed. A node whose drain
+    /// failed stays cordoned with the models that are left.
+    fn initialize_node_transitions(&mut self) {
+        let node_transitions = vec![
+            StateTransition {
+                from_state: NodeState::Schedulable as i32,
+                event: "node_cordoned".to_string(),
+                to_state: NodeState::Cordoned as i32,
+                condition: None,
+                action: "block_new_placements".to_string(),
+            },
+            StateTransition {
+                from_state: NodeState::Cordoned as i32,
+                event: "node_drain_started".to_string(),
+                to_state: NodeState::Draining as i32,
+                condition: None,
+                action: "relocate_node_models".to_string(),
+            },
+            StateTransition {
+                from_state: NodeState::Drained as i32,
+                event: "node_drain_started".to_string(),
+                to_state: NodeState::Draining as i32,
+                condition: None,
+                action: "relocate_node_models".to_string(),
+            },
+            StateTransition {
+                from_state: NodeState::Draining as i32,
+                event: "node_drain_completed".to_string(),
+                to_state: NodeState::Drained as i32,
+                condition: None,
+                action: "node_ready_for_maintenance".to_string(),
+            },
+            StateTransition {
+                from_state: NodeState::Draining as i32,
+                event: "node_drain_failed".to_string(),
+                to_state: NodeState::Cordoned as i32,
+                condition: None,
+                action: "log_drain_failure".to_string(),
+            },
+            StateTransition {
+                from_state: NodeState::Cordoned as i32,
+                event: "node_uncordoned".to_string(),
+                to_state: NodeState::Schedulable as i32,
+                condition: None,
+                action: "allow_new_placements".to_string(),
+            },
+            StateTransition {
+                from_state: NodeState::Drained as i32,
+                event: "node_uncordoned".to_string(),
+                to_state: NodeState::Schedulable as i32,
+                condition: None,
+                action: "allow_new_placements".to_string(),
+            },
+        ];
+        self.transition_tables
+            .insert(ResourceType::Node, node_transitions);
+    }
+
+    /// Initialize package update transitions
+    ///
+    /// Package states are otherwise derived from the states of their models.
+    /// The ActionController reports the start and the outcome of an update,
+    /// and its steps as sub-states of updating. A canary update ends in
+    /// running either way, with its promotion or rollback as sub-state.
+    fn initialize_package_transitions(&mut self) {
+        let package_transitions = vec![
+            StateTransition {
+                from_state: ANY_STATE,
+                event: "update_started".to_string(),
+                to_state: PackageState::Updating as i32,
+                condition: None,
+                action: "track_update_progress".to_string(),
+            },
+            StateTransition {
+                from_state: PackageState::Updating as i32,
+                event: "update_completed".to_string(),
+                to_state: PackageState::Running as i32,
+                condition: None,
+                action: "update_state_announce_availability".to_string(),
+            },
+            StateTransition {
+                from_state: PackageState::Updating as i32,
+                event: "canary_promoted".to_string(),
+                to_state: PackageState::Running as i32,
+                condition: None,
+                action: "announce_canary_promotion".to_string(),
+            },
+            StateTransition {
+                from_state: PackageState::Updating as i32,
+                event: "canary_rolled_back".to_string(),
+                to_state: PackageState::Running as i32,
+                condition: None,
+                action: "alert_canary_rollback".to_string(),
+            },
+            StateTransition {
+                from_state: PackageState::Updating as i32,
+                event: "update_failed".to_string(),
+                to_state: PackageState::Error as i32,
+                condition: None,
+                action: "log_update_failure".to_string(),
+            },
+        ];
+        self.transition_tables
+            .insert(ResourceType::Package, package_transitions);
+    }
+
+    /// Whether the network of a model is tracked and not ready yet
+    ///
+    /// Models without a tracked network do not wait for one.
+    pub fn is_waiting_for_network(&self, model_name: &str) -> bool {
+        self.resource_states
+            .get(&self.generate_resource_key(ResourceType::Network, model_name))
+            .is_some_and(|rs| rs.current_state != NetworkState::Ready as i32)
+    }
+
     // ========================================
     // CORE STATE PROCESSING
     // ========================================
@@ -248,6 +542,27 @@ impl StateMachine {
             ),
         };
 
+        // Progress within the current state, e.g. the steps of an update
+        if !state_change.sub_state.is_empty()
+            && current_state
+                == Self::state_str_to_enum(
+                    state_change.target_state.as_str(),
+                    state_change.resource_type,
+                )
+        {
+            self.update_resource_state(&resource_key, &state_change, current_state, resource_type);
+            let state_str =
+                state_mapping::proto_state_name(resource_type, current_state).unwrap_or("UNKNOWN");
+            return TransitionResult {
+                new_state: current_state,
+                error_code: ErrorCode::Success,
+                message: format!("{state_str}: {}", state_change.sub_state),
+                actions_to_execute: vec![],
+                transition_id: state_change.transition_id.clone(),
+                error_details: String::new(),
+            };
+        }
+
         // Special state-specific handling removed - using simplified state model
 
         // Find valid transition
@@ -259,6 +574,15 @@ impl StateMachine {
             ),
             resource_type,
         );
+        let transition_event = match transition_event.as_str() {
+            "update_completed" if state_change.sub_state == CANARY_PROMOTED => {
+                "canary_promoted".to_string()
+            }
+            "update_completed" if state_change.sub_state.starts_with(CANARY_ROLLED_BACK) => {
+                "canary_rolled_back".to_string()
+            }
+            _ => transition_event,
+        };
 
         if let Some(transition) = self.find_valid_transition(
             resource_type,
@@ -307,18 +631,9 @@ impl StateMachine {
                 }
             }
 
-            let transitioned_state_str = match resource_type {
-                ResourceType::Scenario => ScenarioState::try_from(transition.to_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Package => PackageState::try_from(transition.to_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Model => ModelState::try_from(transition.to_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                _ => "UNKNOWN",
-            };
+            let transitioned_state_str =
+                state_mapping::proto_state_name(resource_type, transition.to_state)
+                    .unwrap_or("UNKNOWN");
 
             // Create successful transition result
             let transition_result = TransitionResult {
@@ -336,61 +651,13 @@ impl StateMachine {
 
             transition_result
         } else {
-            let current_state_str = match resource_type {
-                ResourceType::Scenario => ScenarioState::try_from(current_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Package => PackageState::try_from(current_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                ResourceType::Model => ModelState::try_from(current_state)
-                    .map(|s| s.as_str_name())
-                    .unwrap_or("UNKNOWN"),
-                _ => "UNKNOWN",
-            };
+            let current_state_str =
+                state_mapping::proto_state_name(resource_type, current_state).unwrap_or("UNKNOWN");
 
-            let target_state_str = match resource_type {
-                ResourceType::Scenario => {
-                    let normalized = format!(
-                        "SCENARIO_STATE_{}",
-                        state_change
-                            .target_state
-                            .trim()
-                            .to_ascii_uppercase()
-                            .replace('-', "_")
-                    );
-                    ScenarioState::from_str_name(&normalized)
-                        .map(|s| s.as_str_name())
-                        .unwrap_or("UNKNOWN")
-                }
-                ResourceType::Package => {
-                    let normalized = format!(
-                        "PACKAGE_STATE_{}",
-                        state_change
-                            .target_state
-                            .trim()
-                            .to_ascii_uppercase()
-                            .replace('-', "_")
-                    );
-                    PackageState::from_str_name(&normalized)
-                        .map(|s| s.as_str_name())
-                        .unwrap_or("UNKNOWN")
-                }
-                ResourceType::Model => {
-                    let normalized = format!(
-                        "MODEL_STATE_{}",
-                        state_change
-                            .target_state
-                            .trim()
-                            .to_ascii_uppercase()
-                            .replace('-', "_")
-                    );
-                    ModelState::from_str_name(&normalized)
-                        .map(|s| s.as_str_name())
-                        .unwrap_or("UNKNOWN")
-                }
-                _ => "UNKNOWN",
-            };
+            let target_state_str =
+                state_mapping::parse_state(resource_type, &state_change.target_state)
+                    .and_then(|state| state_mapping::proto_state_name(resource_type, state))
+                    .unwrap_or("UNKNOWN");
 
             let transition_result = TransitionResult {
                 new_state: current_state,
@@ -426,15 +693,53 @@ impl StateMachine {
         &mut self,
         model_name: &str,
         containers: &[&common::monitoringserver::ContainerInfo],
+    ) -> TransitionResult {
+        let new_model_state = self.evaluate_model_state_from_containers(containers);
+        self.transition_model(model_name, new_model_state)
+    }
+
+    /// Process model state update based on the containers of each node
+    ///
+    /// Like [`Self::process_model_state_update`] for a model with instances
+    /// on several nodes, `instances` maps each node to its containers of the
+    /// model.
+    pub fn process_model_instances_update(
+        &mut self,
+        model_name: &str,
+        instances: &ModelInstances,
+    ) -> TransitionResult {
+        let new_model_state = self.evaluate_model_state_from_instances(instances);
+        self.transition_model(model_name, new_model_state)
+    }
+
+    /// Process the removal of the last containers of a model
+    ///
+    /// A model whose containers are all gone no longer runs anywhere, so it
+    /// has exited.
+    pub fn process_model_removed(&mut self, model_name: &str) -> TransitionResult {
+        self.transition_model(model_name, ModelState::Exited)
+    }
+
+    /// Move a model to the state evaluated from its containers
+    fn transition_model(
+        &mut self,
+        model_name: &str,
+        mut new_model_state: ModelState,
     ) -> TransitionResult {
         let resource_key = self.generate_resource_key(ResourceType::Model, model_name);
-        let timestamp_ns = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as i64;
+        let timestamp_ns = self.clock.now_ns();
 
-        // Evaluate the new model state based on container states
-        let new_model_state = self.evaluate_model_state_from_containers(containers);
+        // A model is not running before its network is set up
+        let was_running = self
+            .resource_states
+            .get(&resource_key)
+            .is_some_and(|rs| rs.current_state == ModelState::Running as i32);
+        if new_model_state == ModelState::Running
+            && !was_running
+            && self.is_waiting_for_network(model_name)
+        {
+            new_model_state = ModelState::Created;
+        }
 
         // Create a pseudo state change for internal processing
         let state_change = StateChange {
@@ -446,9 +751,13 @@ impl StateMachine {
                 .map(|rs| self.state_enum_to_str(rs.current_state, ResourceType::Model))
                 .unwrap_or_else(|| "Created".to_string()),
             target_state: self.model_state_to_str(new_model_state),
-            transition_id: format!("model_update_{}_{}", model_name, timestamp_ns),
+            transition_id: common::correlation::transition_id("statemanager"),
             timestamp_ns,
             source: "container_analysis".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         // Get current state from existing resource or default to Created
@@ -496,7 +805,7 @@ impl StateMachine {
     }
 
     /// Evaluates the model state based on container states according to the state transition rules
-    fn evaluate_model_state_from_containers(
+    pub fn evaluate_model_state_from_containers(
         &self,
         containers: &[&common::monitoringserver::ContainerInfo],
     ) -> ModelState {
@@ -505,6 +814,8 @@ impl StateMachine {
         }
 
         let mut _running_count = 0;
+        let mut not_ready_count = 0;
+        let mut crash_loop_count = 0;
         let mut paused_count = 0;
         let mut exited_count = 0;
         let mut dead_count = 0;
@@ -515,6 +826,9 @@ impl StateMachine {
         for container in containers {
             match self.parse_container_state(container) {
                 ContainerState::Running => _running_count += 1,
+                // Neither running nor dead while it recovers from a transient failure
+                ContainerState::NotReady | ContainerState::Recovering => not_ready_count += 1,
+                ContainerState::CrashLoopBackOff => crash_loop_count += 1,
                 ContainerState::Paused => paused_count += 1,
                 ContainerState::Exited => exited_count += 1,
                 ContainerState::Dead => dead_count += 1,
@@ -527,11 +841,23 @@ impl StateMachine {
         let total_containers = containers.len();
 
         // Apply state transition rules from documentation
+        // The NodeAgent gave up restarting a container of the model, or
+        // restarts it too often
+        if crash_loop_count > 0 {
+            return ModelState::CrashLoopBackOff;
+        }
+
         // Rule 1: Dead - if one or more containers are dead or unknown
         if dead_count > 0 {
             return ModelState::Dead;
         }
 
+        // A model is not running while a container is not ready to serve or
+        // recovers
+        if not_ready_count > 0 {
+            return ModelState::Created;
+        }
+
         // Rule 2: Paused - if all containers are paused
         if paused_count == total_containers {
             return ModelState::Paused;
@@ -546,13 +872,64 @@ impl StateMachine {
         ModelState::Running
     }
 
+    /// Evaluates the model state from its instances on several nodes
+    ///
+    /// The containers of every instance are evaluated on their own, so that
+    /// the containers of different instances are not mixed: a model paused
+    /// on one node and exited on another is not running anywhere. The
+    /// instance states are then combined, the first of these wins:
+    /// - degraded when some instances run and others are dead or crash loop
+    /// - crash loop back-off, dead or created (not ready) on any instance
+    /// - running on any instance
+    /// - paused on any instance
+    /// - exited everywhere
+    ///
+    /// A paused standby is left out, once promoted it counts like any other
+    /// instance. It makes a model whose other instances are down degraded,
+    /// for the ActionController to promote it.
+    pub fn evaluate_model_state_from_instances(&self, instances: &ModelInstances) -> ModelState {
+        let mut standby_ready = false;
+        let mut states = Vec::new();
+        for containers in instances.values() {
+            let state = self.evaluate_model_state_from_containers(containers);
+            let standby = containers
+                .iter()
+                .any(|container| container.annotation.contains_key(LABEL_STANDBY));
+            if standby && state == ModelState::Paused {
+                standby_ready = true;
+                continue;
+            }
+            states.push(state);
+        }
+        if states.is_empty() {
+            return ModelState::Created;
+        }
+        let down = states
+            .iter()
+            .any(|state| matches!(state, ModelState::Dead | ModelState::CrashLoopBackOff));
+        if down && (standby_ready || states.contains(&ModelState::Running)) {
+            return ModelState::Degraded;
+        }
+        [
+            ModelState::CrashLoopBackOff,
+            ModelState::Dead,
+            ModelState::Created,
+            ModelState::Running,
+            ModelState::Paused,
+        ]
+        .into_iter()
+        .find(|state| states.contains(state))
+        .unwrap_or(ModelState::Exited)
+    }
+
     /// Evaluates package state based on model states according to Korean documentation requirements
     ///
     /// This function implements the package state transition rules defined in StateManager_Package.md:
     /// - idle: Initial package state (creation default)
     /// - paused: All models are in paused state
     /// - exited: All models are in exited state
-    /// - degraded: Some (1+) models are in dead state, but not all models are dead
+    /// - degraded: Some (1+) models are in dead state, but not all models are dead,
+    ///   or a model has replicas down
     /// - error: All models are in dead state
     /// - running: Default state when none of the above conditions are met
     ///
@@ -565,6 +942,12 @@ impl StateMachine {
         &self,
         model_states: &[(String, ModelState)],
     ) -> PackageState {
+        Self::package_state_from_models(model_states)
+    }
+
+    /// Package state of the given model states, see
+    /// [`Self::evaluate_package_state_from_models`]
+    pub fn package_state_from_models(model_states: &[(String, ModelState)]) -> PackageState {
         if model_states.is_empty() {
             return PackageState::Idle;
         }
@@ -573,13 +956,16 @@ impl StateMachine {
         let mut paused_count = 0;
         let mut exited_count = 0;
         let mut dead_count = 0;
+        let mut degraded_count = 0;
 
         // Count models in each relevant state
         for (_, model_state) in model_states {
             match model_state {
                 ModelState::Paused => paused_count += 1,
                 ModelState::Exited => exited_count += 1,
-                ModelState::Dead => dead_count += 1,
+                // A crash looping model is dead until it is redeployed
+                ModelState::Dead | ModelState::CrashLoopBackOff => dead_count += 1,
+                ModelState::Degraded => degraded_count += 1,
                 _ => {} // Other states don't directly impact package state rules
             }
         }
@@ -590,8 +976,9 @@ impl StateMachine {
             return PackageState::Error;
         }
 
-        // Rule 2: degraded - Some (1+) models are in dead state, but not all
-        if dead_count > 0 && dead_count < total_models {
+        // Rule 2: degraded - Some (1+) models are in dead state, but not all,
+        // or replicas of a model are down
+        if (dead_count > 0 && dead_count < total_models) || degraded_count > 0 {
             return PackageState::Degraded;
         }
 
@@ -609,16 +996,39 @@ impl StateMachine {
         PackageState::Running
     }
 
+    /// Models whose state makes their package error or degraded, the ones
+    /// to restart to recover it
+    pub fn failing_models(model_states: &[(String, ModelState)]) -> Vec<String> {
+        model_states
+            .iter()
+            .filter(|(_, state)| {
+                matches!(
+                    state,
+                    ModelState::Dead | ModelState::CrashLoopBackOff | ModelState::Degraded
+                )
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
     /// Retrieves all model states for models that belong to a given package
     ///
     /// This function queries ETCD to get all model states and filters them
     /// to find models that belong to the specified package.
     pub async fn get_models_for_package(
         package_name: &str,
+    ) -> std::result::Result<Vec<(String, common::statemanager::ModelState)>, String> {
+        Self::get_models_for_package_in(&Transaction::default(), package_name).await
+    }
+
+    /// Model states of a package as they will be once `transaction` is committed
+    pub async fn get_models_for_package_in(
+        transaction: &Transaction,
+        package_name: &str,
     ) -> std::result::Result<Vec<(String, common::statemanager::ModelState)>, String> {
         // Get package definition from ETCD to find its models
         let package_key = format!("Package/{}", package_name);
-        let package_yaml = match common::etcd::get(&package_key).await {
+        let package_yaml = match transaction.get(&package_key).await {
             Ok(yaml) => yaml,
             Err(e) => {
                 logd!(4, "    Failed to get package definition: {:?}", e);
@@ -642,139 +1052,131 @@ impl StateMachine {
             let model_name = model_info.get_name();
             let model_state_key = format!("/model/{}/state", model_name);
 
-            match common::etcd::get(&model_state_key).await {
-                Ok(state_str) => {
-                    let model_state = match state_str.as_str() {
-                        "Created" => common::statemanager::ModelState::Created,
-                        "Paused" => common::statemanager::ModelState::Paused,
-                        "Exited" => common::statemanager::ModelState::Exited,
-                        "Dead" => common::statemanager::ModelState::Dead,
-                        "Running" => common::statemanager::ModelState::Running,
-                        _ => common::statemanager::ModelState::Running, // Default to Running
-                    };
-                    model_states.push((model_name, model_state));
-                }
-                Err(_) => {
-                    // If model state not found, assume it's in Created state
-                    model_states.push((model_name, common::statemanager::ModelState::Created));
-                }
-            }
+            let stored = transaction.get(&model_state_key).await.ok();
+            let model_state = Self::stored_model_state(&model_name, stored.as_deref());
+            model_states.push((model_name, model_state));
         }
 
         Ok(model_states)
     }
 
+    /// State of a model from its stored value, `Created` when it has none
+    pub fn stored_model_state(model_name: &str, stored: Option<&str>) -> ModelState {
+        match stored {
+            Some(state_str) => ModelState::migrate(state_str).unwrap_or_else(|| {
+                // An unreadable state is not evidence that the model runs
+                logd!(
+                    4,
+                    "    Unknown state '{}' for model {}, assuming Created",
+                    state_str,
+                    model_name
+                );
+                ModelState::Created
+            }),
+            None => ModelState::Created,
+        }
+    }
+
     /// Find all packages that contain the given model
     pub async fn find_packages_containing_model(
         model_name: &str,
     ) -> std::result::Result<Vec<String>, String> {
-        let mut packages = Vec::new();
-
-        // Get all packages from ETCD with prefix
-        match common::etcd::get_all_with_prefix("Package/").await {
-            Ok(package_entries) => {
-                for kv in package_entries {
-                    match serde_yaml::from_str::<common::spec::artifact::Package>(&kv.1) {
-                        Ok(package) => {
-                            // Check if this package contains the model
-                            for model_info in package.get_models() {
-                                if model_info.get_name() == model_name {
-                                    packages.push(package.get_name());
-                                    break;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            logd!(4, "    Failed to parse package {}: {:?}", kv.0, e);
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                logd!(5, "    Failed to get packages from ETCD: {:?}", e);
-                return Err(format!("Failed to get packages from ETCD: {:?}", e));
-            }
-        }
-
-        Ok(packages)
+        let packages = crate::cascade::packages_containing(&[model_name]).await?;
+        Ok(packages
+            .iter()
+            .map(|package| package.get_qualified_name())
+            .collect())
     }
 
     /// Get current package state from ETCD
     pub async fn get_current_package_state(
         package_name: &str,
+    ) -> Option<common::statemanager::PackageState> {
+        Self::get_current_package_state_in(&Transaction::default(), package_name).await
+    }
+
+    /// Package state as it will be once `transaction` is committed
+    pub async fn get_current_package_state_in(
+        transaction: &Transaction,
+        package_name: &str,
     ) -> Option<common::statemanager::PackageState> {
         let key = format!("/package/{}/state", package_name);
-        match common::etcd::get(&key).await {
-            Ok(state_str) => match state_str.as_str() {
-                "PACKAGE_STATE_IDLE" | "idle" => Some(common::statemanager::PackageState::Idle),
-                "PACKAGE_STATE_PAUSED" | "paused" => {
-                    Some(common::statemanager::PackageState::Paused)
-                }
-                "PACKAGE_STATE_EXITED" | "exited" => {
-                    Some(common::statemanager::PackageState::Exited)
-                }
-                "PACKAGE_STATE_DEGRADED" | "degraded" => {
-                    Some(common::statemanager::PackageState::Degraded)
-                }
-                "PACKAGE_STATE_ERROR" | "error" => Some(common::statemanager::PackageState::Error),
-                "PACKAGE_STATE_RUNNING" | "running" => {
-                    Some(common::statemanager::PackageState::Running)
-                }
-                _ => Some(common::statemanager::PackageState::Idle),
-            },
-            Err(_) => None,
+        let stored = transaction.get(&key).await.ok();
+        Self::stored_package_state(package_name, stored.as_deref())
+    }
+
+    /// State of a package from its stored value
+    pub fn stored_package_state(
+        package_name: &str,
+        stored: Option<&str>,
+    ) -> Option<common::statemanager::PackageState> {
+        let state_str = stored?;
+        let state = PackageState::migrate(state_str);
+        if state.is_none() {
+            logd!(
+                4,
+                "    Unknown state '{}' for package {}",
+                state_str,
+                package_name
+            );
         }
+        state
     }
 
     /// Evaluate and update package state based on current model states
     pub async fn evaluate_and_update_package_state(
         &self,
         package_name: &str,
+    ) -> std::result::Result<(bool, common::statemanager::PackageState), String> {
+        self.evaluate_package_state_in(&Transaction::default(), package_name)
+            .await
+    }
+
+    /// Evaluate package state from the model states as they will be once
+    /// `transaction` is committed
+    pub async fn evaluate_package_state_in(
+        &self,
+        transaction: &Transaction,
+        package_name: &str,
     ) -> std::result::Result<(bool, common::statemanager::PackageState), String> {
         logd!(2, "    Evaluating package state for: {}", package_name);
 
         // Get model states for this package
-        let model_states = Self::get_models_for_package(package_name).await?;
+        let model_states = Self::get_models_for_package_in(transaction, package_name).await?;
+
+        // Get current package state
+        let current_package_state =
+            Self::get_current_package_state_in(transaction, package_name).await;
+        Ok(Self::package_state_change(
+            package_name,
+            &model_states,
+            current_package_state,
+        ))
+    }
 
+    /// Whether a package changes state with the given model states, and the
+    /// state it is in then
+    pub fn package_state_change(
+        package_name: &str,
+        model_states: &[(String, ModelState)],
+        current_package_state: Option<common::statemanager::PackageState>,
+    ) -> (bool, common::statemanager::PackageState) {
         if model_states.is_empty() {
             logd!(4, "      No models found for package {}", package_name);
-            return Ok((false, common::statemanager::PackageState::Idle));
+            return (false, common::statemanager::PackageState::Idle);
+        }
+        let current_package_state =
+            current_package_state.unwrap_or(common::statemanager::PackageState::Idle);
+
+        // Models restart while their package is updated, the ActionController
+        // reports the outcome of the update instead
+        if current_package_state == common::statemanager::PackageState::Updating {
+            logd!(1, "      Package {} is updating, state kept", package_name);
+            return (false, current_package_state);
         }
-
-        // Convert to format expected by state machine
-        let model_states_for_evaluation: Vec<(String, ModelState)> = model_states
-            .iter()
-            .map(|(name, state)| {
-                let converted_state = match state {
-                    common::statemanager::ModelState::Created => ModelState::Created,
-                    common::statemanager::ModelState::Paused => ModelState::Paused,
-                    common::statemanager::ModelState::Exited => ModelState::Exited,
-                    common::statemanager::ModelState::Dead => ModelState::Dead,
-                    common::statemanager::ModelState::Running => ModelState::Running,
-                    _ => ModelState::Running,
-                };
-                (name.clone(), converted_state)
-            })
-            .collect();
-
-        // Get current package state
-        let current_package_state = Self::get_current_package_state(package_name)
-            .await
-            .unwrap_or(common::statemanager::PackageState::Idle);
 
         // Evaluate new package state using state machine
-        let evaluated_state = self.evaluate_package_state_from_models(&model_states_for_evaluation);
-
-        // Convert back to common::statemanager::PackageState
-        let new_package_state = match evaluated_state {
-            PackageState::Idle => common::statemanager::PackageState::Idle,
-            PackageState::Paused => common::statemanager::PackageState::Paused,
-            PackageState::Exited => common::statemanager::PackageState::Exited,
-            PackageState::Degraded => common::statemanager::PackageState::Degraded,
-            PackageState::Error => common::statemanager::PackageState::Error,
-            PackageState::Running => common::statemanager::PackageState::Running,
-            _ => common::statemanager::PackageState::Running,
-        };
+        let new_package_state = Self::package_state_from_models(model_states);
 
         // Check if package state changed
         let state_changed = new_package_state != current_package_state;
@@ -794,17 +1196,66 @@ impl StateMachine {
             );
         }
 
-        Ok((state_changed, new_package_state))
+        (state_changed, new_package_state)
     }
 
     /// Parses container state from the state HashMap
+    ///
+    /// The NodeAgent adds "Live" and "Ready" for containers with liveness
+    /// and readiness probes, a running container failing them is reported
+    /// dead or not ready. It sets "CrashLoopBackOff" once it stopped
+    /// restarting a container, a container it still restarts crash loops
+    /// when restarted or OOM killed too often, see [`crate::crashloop`].
+    ///
+    /// A dead container, or an exited one the NodeAgent restarts, is
+    /// recovering while its failure is transient and dead after it, see
+    /// [`crate::failure`].
     fn parse_container_state(
         &self,
         container: &common::monitoringserver::ContainerInfo,
     ) -> ContainerState {
+        match self.reported_container_state(container) {
+            ContainerState::Dead => self.classify_failure(container),
+            ContainerState::Exited if crate::failure::restarted(container) => {
+                self.classify_failure(container)
+            }
+            state => {
+                self.failures().healthy(container);
+                state
+            }
+        }
+    }
+
+    fn classify_failure(
+        &self,
+        container: &common::monitoringserver::ContainerInfo,
+    ) -> ContainerState {
+        match self.failures().failed(container) {
+            Verdict::Recovering => ContainerState::Recovering,
+            Verdict::Dead => ContainerState::Dead,
+        }
+    }
+
+    /// Container state as reported, see [`Self::parse_container_state`]
+    fn reported_container_state(
+        &self,
+        container: &common::monitoringserver::ContainerInfo,
+    ) -> ContainerState {
+        let probe_failed = |probe: &str| container.state.get(probe).is_some_and(|v| v == "false");
+        if container
+            .state
+            .get("CrashLoopBackOff")
+            .is_some_and(|v| v == "true")
+            || crate::crashloop::crash_looping(container)
+        {
+            return ContainerState::CrashLoopBackOff;
+        }
+
         // Check the "Status" field first
         if let Some(status) = container.state.get("Status") {
             match status.to_lowercase().as_str() {
+                "running" if probe_failed("Live") => return ContainerState::Dead,
+                "running" if probe_failed("Ready") => return ContainerState::NotReady,
                 "running" => return ContainerState::Running,
                 "paused" => return ContainerState::Paused,
                 "exited" => return ContainerState::Exited,
@@ -829,14 +1280,7 @@ impl StateMachine {
 
     /// Convert ModelState enum to string representation
     fn model_state_to_str(&self, state: ModelState) -> String {
-        match state {
-            ModelState::Created => "Created".to_string(),
-            ModelState::Paused => "Paused".to_string(),
-            ModelState::Exited => "Exited".to_string(),
-            ModelState::Dead => "Dead".to_string(),
-            ModelState::Running => "Running".to_string(),
-            _ => "Unknown".to_string(),
-        }
+        state.name().to_string()
     }
 
     // ========================================
@@ -859,8 +1303,10 @@ impl StateMachine {
     /// - `None`: If no valid transition exists for the given parameters
     ///
     /// # Implementation Details
-    /// This method performs exact matching on all transition parameters.
-    /// Wildcard or pattern matching is not currently supported.
+    /// Rules from [`ANY_STATE`] or on [`ANY_EVENT`] match too. The most
+    /// specific matching rule is taken (see [`StateTransition::specificity`]),
+    /// the first of equally specific ones, and returned with the concrete
+    /// state and event filled in.
     fn find_valid_transition(
         &self,
         resource_type: ResourceType,
@@ -868,17 +1314,16 @@ impl StateMachine {
         event: &str,
         to_state: i32,
     ) -> Option<StateTransition> {
-        if let Some(transitions) = self.transition_tables.get(&resource_type) {
-            for transition in transitions {
-                if transition.from_state == from_state
-                    && transition.event == event
-                    && transition.to_state == to_state
-                {
-                    return Some(transition.clone());
-                }
+        let mut found: Option<(u8, &StateTransition)> = None;
+        for transition in self.transition_tables.get(&resource_type)? {
+            let Some(specificity) = transition.specificity(from_state, event, to_state) else {
+                continue;
+            };
+            if found.is_none_or(|(best, _)| specificity > best) {
+                found = Some((specificity, transition));
             }
         }
-        None
+        found.map(|(_, transition)| transition.resolved(from_state, event))
     }
 
     /// Validate state change request parameters
@@ -891,7 +1336,9 @@ impl StateMachine {
             return Err("Transition ID cannot be empty".to_string());
         }
 
-        if state_change.current_state == state_change.target_state {
+        if state_change.current_state == state_change.target_state
+            && state_change.sub_state.is_empty()
+        {
             return Err("Current and target states cannot be the same".to_string());
         }
 
@@ -920,31 +1367,11 @@ impl StateMachine {
             Err(_) => ResourceType::Scenario, // fallback, adjust as needed
         };
 
-        let from_state_str = match resource_type {
-            ResourceType::Scenario => ScenarioState::try_from(transition.from_state)
-                .map(|s| s.as_str_name())
-                .unwrap_or("UNKNOWN"),
-            ResourceType::Package => PackageState::try_from(transition.from_state)
-                .map(|s| s.as_str_name())
-                .unwrap_or("UNKNOWN"),
-            ResourceType::Model => ModelState::try_from(transition.from_state)
-                .map(|s| s.as_str_name())
-                .unwrap_or("UNKNOWN"),
-            _ => "UNKNOWN",
-        };
+        let from_state_str = state_mapping::proto_state_name(resource_type, transition.from_state)
+            .unwrap_or("UNKNOWN");
 
-        let to_state_str = match resource_type {
-            ResourceType::Scenario => ScenarioState::try_from(transition.to_state)
-                .map(|s| s.as_str_name())
-                .unwrap_or("UNKNOWN"),
-            ResourceType::Package => PackageState::try_from(transition.to_state)
-                .map(|s| s.as_str_name())
-                .unwrap_or("UNKNOWN"),
-            ResourceType::Model => ModelState::try_from(transition.to_state)
-                .map(|s| s.as_str_name())
-                .unwrap_or("UNKNOWN"),
-            _ => "UNKNOWN",
-        };
+        let to_state_str = state_mapping::proto_state_name(resource_type, transition.to_state)
+            .unwrap_or("UNKNOWN");
 
         context.insert("from_state".to_string(), from_state_str.to_string());
         context.insert("to_state".to_string(), to_state_str.to_string());
@@ -958,14 +1385,25 @@ impl StateMachine {
             "timestamp_ns".to_string(),
             state_change.timestamp_ns.to_string(),
         );
+        if !state_change.sub_state.is_empty() {
+            context.insert("sub_state".to_string(), state_change.sub_state.clone());
+        }
+        if let Some(denial) = &state_change.denial {
+            context.insert("denial_rule".to_string(), denial.rule.clone());
+            context.insert("denial_message".to_string(), denial.message.clone());
+            context.insert("denial_source".to_string(), denial.source.clone());
+        }
+        // Prefixed so that metadata cannot replace the entries above
+        for (key, value) in &state_change.metadata {
+            context.insert(format!("metadata.{key}"), value.clone());
+        }
         context
     }
 
     /// Updates health status based on transition result
     fn update_health_status(&mut self, resource_key: &str, transition_result: &TransitionResult) {
         if let Some(resource_state) = self.resource_states.get_mut(resource_key) {
-            let now = Instant::now();
-            resource_state.health_status.last_check = now;
+            resource_state.health_status.last_check_ns = self.clock.now_ns();
 
             if transition_result.is_success() {
                 resource_state.health_status.healthy = true;
@@ -1026,6 +1464,12 @@ impl StateMachine {
                 {
                     "policy_verification_success".to_string()
                 }
+                (x, y)
+                    if x == ScenarioState::Satisfied as i32
+                        && y == ScenarioState::Waiting as i32 =>
+                {
+                    "activation_deferred".to_string()
+                }
                 (x, y)
                     if x == ScenarioState::Satisfied as i32
                         && y == ScenarioState::Denied as i32 =>
@@ -1033,14 +1477,56 @@ impl StateMachine {
                     "policy_verification_failure".to_string()
                 }
                 (x, y)
-                    if x == ScenarioState::Allowed as i32
+                    if (x == ScenarioState::Allowed as i32
+                        || x == ScenarioState::Pending as i32)
                         && y == ScenarioState::Completed as i32 =>
                 {
                     "scenario_completion".to_string()
                 }
+                (x, y)
+                    if x == ScenarioState::Allowed as i32 && y == ScenarioState::Pending as i32 =>
+                {
+                    "admission_deferred".to_string()
+                }
+                (x, y)
+                    if (x == ScenarioState::Allowed as i32
+                        || x == ScenarioState::Pending as i32)
+                        && y == ScenarioState::Denied as i32 =>
+                {
+                    "admission_denied".to_string()
+                }
+                (x, y)
+                    if (x == ScenarioState::Completed as i32
+                        || x == ScenarioState::Denied as i32)
+                        && y == ScenarioState::Waiting as i32 =>
+                {
+                    "schedule_rearmed".to_string()
+                }
+                (x, y) if x != ScenarioState::Idle as i32 && y == ScenarioState::Idle as i32 => {
+                    "scenario_deactivation".to_string()
+                }
+                (x, y)
+                    if x != ScenarioState::Waiting as i32
+                        && y == ScenarioState::Satisfied as i32 =>
+                {
+                    "scenario_retrigger".to_string()
+                }
                 _ => format!("transition_{current_state}_{target_state}"),
             },
             ResourceType::Package => match (current_state, target_state) {
+                (x, y)
+                    if x != PackageState::Updating as i32 && y == PackageState::Updating as i32 =>
+                {
+                    "update_started".to_string()
+                }
+                (x, y)
+                    if x == PackageState::Updating as i32 && y == PackageState::Running as i32 =>
+                {
+                    "update_completed".to_string()
+                }
+                (x, y) if x == PackageState::Updating as i32 && y == PackageState::Error as i32 => {
+                    "update_failed".to_string()
+                }
                 (x, y)
                     if x == PackageState::Unspecified as i32 && y == PackageState::Idle as i32 =>
                 {
@@ -1132,6 +1618,52 @@ impl StateMachine {
                 (x, y) if x == ModelState::Dead as i32 && y == ModelState::Created as i32 => {
                     "manual_automatic_recovery".to_string()
                 }
+                (_, y) if y == ModelState::CrashLoopBackOff as i32 => {
+                    "restart_limit_exceeded".to_string()
+                }
+                (_, y) if y == ModelState::Degraded as i32 => "replicas_down".to_string(),
+                (x, y) if x == ModelState::Degraded as i32 && y == ModelState::Running as i32 => {
+                    "replicas_recovered".to_string()
+                }
+                _ => format!("transition_{current_state}_{target_state}"),
+            },
+            ResourceType::Network => match (current_state, target_state) {
+                (x, y)
+                    if (x == NetworkState::Unspecified as i32
+                        || x == NetworkState::Ready as i32)
+                        && y == NetworkState::Requested as i32 =>
+                {
+                    "network_setup_requested".to_string()
+                }
+                (x, y)
+                    if x == NetworkState::Requested as i32 && y == NetworkState::Ready as i32 =>
+                {
+                    "network_setup_succeeded".to_string()
+                }
+                (x, y)
+                    if x == NetworkState::Requested as i32 && y == NetworkState::Failed as i32 =>
+                {
+                    "network_setup_failed".to_string()
+                }
+                (x, y)
+                    if x == NetworkState::Failed as i32 && y == NetworkState::Requested as i32 =>
+                {
+                    "network_setup_retry".to_string()
+                }
+                _ => format!("transition_{current_state}_{target_state}"),
+            },
+            ResourceType::Node => match (current_state, target_state) {
+                (x, y) if x == NodeState::Schedulable as i32 && y == NodeState::Cordoned as i32 => {
+                    "node_cordoned".to_string()
+                }
+                (_, y) if y == NodeState::Draining as i32 => "node_drain_started".to_string(),
+                (x, y) if x == NodeState::Draining as i32 && y == NodeState::Drained as i32 => {
+                    "node_drain_completed".to_string()
+                }
+                (x, y) if x == NodeState::Draining as i32 && y == NodeState::Cordoned as i32 => {
+                    "node_drain_failed".to_string()
+                }
+                (_, y) if y == NodeState::Schedulable as i32 => "node_uncordoned".to_string(),
                 _ => format!("transition_{current_state}_{target_state}"),
             },
             _ => format!("transition_{current_state}_{target_state}"),
@@ -1213,7 +1745,7 @@ impl StateMachine {
         new_state: i32,
         resource_type: ResourceType,
     ) {
-        let now = Instant::now();
+        let now = self.clock.now_ns();
 
         let resource_state = self
             .resource_states
@@ -1229,19 +1761,19 @@ impl StateMachine {
                     state_change.target_state.as_str(),
                     state_change.resource_type,
                 )),
-                last_transition_time: now,
+                last_transition_ns: now,
                 transition_count: 0,
                 metadata: HashMap::new(),
                 health_status: HealthStatus {
                     healthy: true,
                     status_message: "Healthy".to_string(),
-                    last_check: now,
+                    last_check_ns: now,
                     consecutive_failures: 0,
                 },
             });
 
         resource_state.current_state = new_state;
-        resource_state.last_transition_time = now;
+        resource_state.last_transition_ns = now;
         resource_state.transition_count += 1;
         resource_state.metadata.insert(
             "last_transition_id".to_string(),
@@ -1250,6 +1782,13 @@ impl StateMachine {
         resource_state
             .metadata
             .insert("source".to_string(), state_change.source.clone());
+        if state_change.sub_state.is_empty() {
+            resource_state.metadata.remove("sub_state");
+        } else {
+            resource_state
+                .metadata
+                .insert("sub_state".to_string(), state_change.sub_state.clone());
+        }
     }
 
     // ========================================
@@ -1283,6 +1822,26 @@ impl StateMachine {
         self.resource_states.get(&resource_key)
     }
 
+    /// Record the desired state declared for a tracked resource
+    ///
+    /// The desired state otherwise is the target of the first transition of
+    /// the resource. Returns whether the resource is tracked.
+    pub fn set_desired_state(
+        &mut self,
+        resource_name: &str,
+        resource_type: ResourceType,
+        desired_state: i32,
+    ) -> bool {
+        let resource_key = self.generate_resource_key(resource_type, resource_name);
+        match self.resource_states.get_mut(&resource_key) {
+            Some(resource_state) => {
+                resource_state.desired_state = Some(desired_state);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// List all resources currently in a specific state
     ///
     /// Provides a filtered view of all managed resources based on their
@@ -1306,78 +1865,200 @@ impl StateMachine {
         &self,
         resource_type: Option<ResourceType>,
 
-        state: i32,
-    ) -> Vec<&ResourceState> {
-        self.resource_states
-            .values()
-            .filter(|resource| {
-                resource.current_state == state
-                    && (resource_type.is_none() || resource_type == Some(resource.resource_type))
-            })
-            .collect()
+        state: i32,
+    ) -> Vec<&ResourceState> {
+        self.resource_states
+            .values()
+            .filter(|resource| {
+                resource.current_state == state
+                    && (resource_type.is_none() || resource_type == Some(resource.resource_type))
+            })
+            .collect()
+    }
+
+    /// Apply transition tables loaded from the settings, see
+    /// [`crate::transitions`]
+    ///
+    /// A replaced table is made of the loaded transitions only. Otherwise a
+    /// loaded transition takes the place of the one with the same states
+    /// and event, or is added. A transition from any state without an event
+    /// is taken on any event.
+    pub fn apply_transition_tables(
+        &mut self,
+        tables: HashMap<ResourceType, crate::transitions::Table>,
+    ) {
+        for (resource_type, table) in tables {
+            let transitions: Vec<StateTransition> = table
+                .transitions
+                .into_iter()
+                .map(|transition| {
+                    let event = transition.event.clone().unwrap_or_else(|| {
+                        if transition.from_state == ANY_STATE {
+                            ANY_EVENT.to_string()
+                        } else {
+                            self.infer_event_from_states(
+                                transition.from_state,
+                                transition.to_state,
+                                resource_type,
+                            )
+                        }
+                    });
+                    transition.with_event(event)
+                })
+                .collect();
+            let current = self.transition_tables.entry(resource_type).or_default();
+            if table.replace {
+                current.clear();
+            }
+            for transition in transitions {
+                match current.iter_mut().find(|t| {
+                    t.from_state == transition.from_state
+                        && t.event == transition.event
+                        && t.to_state == transition.to_state
+                }) {
+                    Some(existing) => *existing = transition,
+                    None => current.push(transition),
+                }
+            }
+        }
+    }
+
+    // ========================================
+    // STUCK-STATE DETECTION
+    // ========================================
+
+    /// Set the maximum time a resource may stay in `state`
+    pub fn set_state_timeout(
+        &mut self,
+        resource_type: ResourceType,
+        state: i32,
+        timeout: Duration,
+    ) {
+        self.state_timeouts.insert((resource_type, state), timeout);
+    }
+
+    /// Load state timeouts from the statemanager settings
+    ///
+    /// `timeouts` maps a resource type name to state names and timeouts in
+    /// seconds, replacing the timeouts loaded before. Unknown resource types
+    /// or states and zero timeouts are skipped with a warning.
+    pub fn load_state_timeouts(&mut self, timeouts: &HashMap<String, HashMap<String, u64>>) {
+        self.state_timeouts.clear();
+        for (type_name, states) in timeouts {
+            let resource_type = match crate::transitions::parse_resource_type(type_name) {
+                Some(resource_type) => resource_type,
+                None => {
+                    logd!(
+                        4,
+                        "Ignoring state timeouts of unknown resource type '{}'",
+                        type_name
+                    );
+                    continue;
+                }
+            };
+            for (state_name, seconds) in states {
+                let state = Self::state_str_to_enum(state_name, resource_type as i32);
+                if state == 0 || *seconds == 0 {
+                    logd!(
+                        4,
+                        "Ignoring invalid state timeout {}/{}: {}s",
+                        type_name,
+                        state_name,
+                        seconds
+                    );
+                    continue;
+                }
+                self.set_state_timeout(resource_type, state, Duration::from_secs(*seconds));
+            }
+        }
+    }
+
+    /// State a resource of the given type is moved to when it times out
+    fn timeout_state(resource_type: ResourceType) -> Option<i32> {
+        match resource_type {
+            ResourceType::Scenario => Some(ScenarioState::Denied as i32),
+            ResourceType::Package => Some(PackageState::Error as i32),
+            ResourceType::Model => Some(ModelState::Dead as i32),
+            ResourceType::Network => Some(NetworkState::Failed as i32),
+            // A drain that never finishes leaves the node cordoned
+            ResourceType::Node => Some(NodeState::Cordoned as i32),
+            _ => None,
+        }
+    }
+
+    /// Move every resource stuck in a state longer than its timeout to the
+    /// failure state of its type
+    ///
+    /// The transition is recorded like any other one and the resource is
+    /// marked unhealthy. Persisting the new state, alerting and recovery are
+    /// left to the caller.
+    ///
+    /// # Returns
+    /// One event per resource that timed out
+    pub fn check_timeouts(&mut self) -> Vec<TimeoutEvent> {
+        let now_ns = self.clock.now_ns();
+        let mut events = Vec::new();
+
+        for resource in self.resource_states.values_mut() {
+            let Some(timeout) = self
+                .state_timeouts
+                .get(&(resource.resource_type, resource.current_state))
+            else {
+                continue;
+            };
+            let elapsed = common::clock::elapsed(resource.last_transition_ns, now_ns);
+            if elapsed < *timeout {
+                continue;
+            }
+            let Some(to_state) = Self::timeout_state(resource.resource_type) else {
+                continue;
+            };
+
+            let transition_id = format!("timeout_{}_{}", resource.resource_name, now_ns);
+            events.push(TimeoutEvent {
+                resource_type: resource.resource_type,
+                resource_name: resource.resource_name.clone(),
+                from_state: resource.current_state,
+                to_state,
+                elapsed,
+                transition_id: transition_id.clone(),
+            });
+
+            resource.current_state = to_state;
+            resource.last_transition_ns = now_ns;
+            resource.transition_count += 1;
+            resource
+                .metadata
+                .insert("last_transition_id".to_string(), transition_id);
+            resource
+                .metadata
+                .insert("source".to_string(), "state_timeout".to_string());
+            resource.health_status.healthy = false;
+            resource.health_status.status_message = format!(
+                "Timed out after {}s in a transitional state",
+                elapsed.as_secs()
+            );
+            resource.health_status.last_check_ns = now_ns;
+            resource.health_status.consecutive_failures += 1;
+        }
+
+        events
     }
 
     // Utility: Convert state string to proto enum value
     fn state_str_to_enum(state: &str, resource_type: i32) -> i32 {
-        // Map "idle" -> "SCENARIO_STATE_IDLE", etc.
-        let normalized = match ResourceType::try_from(resource_type) {
-            Ok(ResourceType::Scenario) => format!(
-                "SCENARIO_STATE_{}",
-                state.trim().to_ascii_uppercase().replace('-', "_")
-            ),
-            Ok(ResourceType::Package) => format!(
-                "PACKAGE_STATE_{}",
-                state.trim().to_ascii_uppercase().replace('-', "_")
-            ),
-            Ok(ResourceType::Model) => format!(
-                "MODEL_STATE_{}",
-                state.trim().to_ascii_uppercase().replace('-', "_")
-            ),
-            _ => state.trim().to_ascii_uppercase().replace('-', "_"),
-        };
-        match ResourceType::try_from(resource_type) {
-            Ok(ResourceType::Scenario) => ScenarioState::from_str_name(&normalized)
-                .map(|s| s as i32)
-                .unwrap_or(ScenarioState::Unspecified as i32),
-            Ok(ResourceType::Package) => PackageState::from_str_name(&normalized)
-                .map(|s| s as i32)
-                .unwrap_or(PackageState::Unspecified as i32),
-            Ok(ResourceType::Model) => ModelState::from_str_name(&normalized)
-                .map(|s| s as i32)
-                .unwrap_or(ModelState::Unspecified as i32),
-            _ => 0,
-        }
+        // Unknown names map to the unspecified state (0) of every type
+        ResourceType::try_from(resource_type)
+            .ok()
+            .and_then(|rt| state_mapping::parse_state(rt, state))
+            .unwrap_or(0)
     }
 
     // Utility: Convert proto enum value to state string
     fn state_enum_to_str(&self, state: i32, resource_type: ResourceType) -> String {
-        match resource_type {
-            ResourceType::Scenario => ScenarioState::try_from(state)
-                .map(|s| {
-                    s.as_str_name()
-                        .strip_prefix("SCENARIO_STATE_")
-                        .unwrap_or(s.as_str_name())
-                        .to_string()
-                })
-                .unwrap_or_else(|_| "Unknown".to_string()),
-            ResourceType::Package => PackageState::try_from(state)
-                .map(|s| {
-                    s.as_str_name()
-                        .strip_prefix("PACKAGE_STATE_")
-                        .unwrap_or(s.as_str_name())
-                        .to_string()
-                })
-                .unwrap_or_else(|_| "Unknown".to_string()),
-            ResourceType::Model => ModelState::try_from(state)
-                .map(|s| {
-                    s.as_str_name()
-                        .strip_prefix("MODEL_STATE_")
-                        .unwrap_or(s.as_str_name())
-                        .to_string()
-                })
-                .unwrap_or_else(|_| "Unknown".to_string()),
-            _ => "Unknown".to_string(),
-        }
+        state_mapping::state_name(resource_type, state)
+            .unwrap_or("Unknown")
+            .to_string()
     }
 }
 
@@ -1394,8 +2075,22 @@ impl Default for StateMachine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::spec::k8s::pod::ANNOTATION_RESTART_POLICY;
     use common::statemanager::{ModelState, PackageState};
 
+    #[test]
+    fn test_failing_models() {
+        let models = vec![
+            ("a".to_string(), ModelState::Running),
+            ("b".to_string(), ModelState::Dead),
+            ("c".to_string(), ModelState::CrashLoopBackOff),
+            ("d".to_string(), ModelState::Degraded),
+            ("e".to_string(), ModelState::Exited),
+        ];
+        assert_eq!(StateMachine::failing_models(&models), vec!["b", "c", "d"]);
+        assert!(StateMachine::failing_models(&models[..1]).is_empty());
+    }
+
     #[test]
     fn test_evaluate_package_state_from_models_empty() {
         let state_machine = StateMachine::new();
@@ -1426,6 +2121,17 @@ mod tests {
         assert_eq!(result, PackageState::Degraded);
     }
 
+    #[test]
+    fn test_evaluate_package_state_degraded_model() {
+        let state_machine = StateMachine::new();
+        let model_states = vec![
+            ("model1".to_string(), ModelState::Degraded),
+            ("model2".to_string(), ModelState::Running),
+        ];
+        let result = state_machine.evaluate_package_state_from_models(&model_states);
+        assert_eq!(result, PackageState::Degraded);
+    }
+
     #[test]
     fn test_evaluate_package_state_all_paused() {
         let state_machine = StateMachine::new();
@@ -1554,6 +2260,10 @@ mod tests {
             transition_id: "t-1".to_string(),
             timestamp_ns: 1,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: [("to_state".to_string(), "Spoofed".to_string())].into(),
         };
 
         let result = state_machine.process_state_change(state_change.clone());
@@ -1563,6 +2273,9 @@ mod tests {
             .try_recv()
             .expect("expected an action queued");
         assert_eq!(action.action, "start_condition_evaluation");
+        // Metadata is carried in the context without replacing its entries
+        assert_eq!(action.context["metadata.to_state"], "Spoofed");
+        assert_ne!(action.context["to_state"], "Spoofed");
         // Resource state should now exist
         let rs = state_machine.get_resource_state("test-scenario", ResourceType::Scenario);
         assert!(
@@ -1571,6 +2284,103 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_scenario_is_rearmed_once_its_run_ended() {
+        let mut state_machine = StateMachine::new();
+        let change = |from: &str, to: &str, id: &str| StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "rearmed".to_string(),
+            current_state: from.to_string(),
+            target_state: to.to_string(),
+            transition_id: id.to_string(),
+            timestamp_ns: 1,
+            source: "scheduler".to_string(),
+            ..Default::default()
+        };
+
+        assert!(state_machine
+            .process_state_change(change("Allowed", "Waiting", "t-1"))
+            .is_failure());
+        assert!(state_machine
+            .process_state_change(change("Allowed", "Completed", "t-2"))
+            .is_success());
+        let result = state_machine.process_state_change(change("Completed", "Waiting", "t-3"));
+        assert!(result.is_success());
+        assert_eq!(result.new_state, ScenarioState::Waiting as i32);
+        assert_eq!(result.actions_to_execute, ["start_condition_evaluation"]);
+    }
+
+    #[test]
+    fn test_idle_scenario_is_retriggered() {
+        let mut state_machine = StateMachine::new();
+        let change = |from: &str, to: &str, id: &str| StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "retriggered".to_string(),
+            current_state: from.to_string(),
+            target_state: to.to_string(),
+            transition_id: id.to_string(),
+            timestamp_ns: 1,
+            source: "apiserver".to_string(),
+            ..Default::default()
+        };
+
+        let result = state_machine.process_state_change(change("Idle", "Satisfied", "t-1"));
+        assert!(result.is_success(), "{}", result.message);
+        assert_eq!(result.new_state, ScenarioState::Satisfied as i32);
+        assert_eq!(result.actions_to_execute, ["start_policy_verification"]);
+
+        // Once its run ended, it can be re-triggered again
+        assert!(state_machine
+            .process_state_change(change("Satisfied", "Denied", "t-2"))
+            .is_success());
+        let result = state_machine.process_state_change(change("Idle", "Satisfied", "t-3"));
+        assert!(result.is_success(), "{}", result.message);
+        assert_eq!(
+            state_machine
+                .get_resource_state("retriggered", ResourceType::Scenario)
+                .unwrap()
+                .health_status
+                .consecutive_failures,
+            0
+        );
+    }
+
+    #[test]
+    fn test_snapshot_queues_no_action_and_leaves_original_untouched() {
+        use common::statemanager::ResourceType;
+
+        let mut state_machine = StateMachine::new();
+        let mut action_receiver = state_machine.initialize_action_executor();
+
+        let mut snapshot = state_machine.snapshot();
+        let result = snapshot.process_state_change(StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: "snapshot-scenario".to_string(),
+            current_state: "Idle".to_string(),
+            target_state: "Waiting".to_string(),
+            transition_id: "t-snapshot".to_string(),
+            timestamp_ns: 1,
+            source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        });
+
+        assert!(result.is_success());
+        assert_eq!(
+            result.actions_to_execute,
+            vec!["start_condition_evaluation"]
+        );
+        assert!(snapshot
+            .get_resource_state("snapshot-scenario", ResourceType::Scenario)
+            .is_some());
+        assert!(action_receiver.try_recv().is_err());
+        assert!(state_machine
+            .get_resource_state("snapshot-scenario", ResourceType::Scenario)
+            .is_none());
+    }
+
     #[test]
     fn test_process_state_change_invalid_transition_returns_error() {
         use common::statemanager::{ErrorCode, ResourceType};
@@ -1586,6 +2396,10 @@ mod tests {
             transition_id: "t-2".to_string(),
             timestamp_ns: 2,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         let result = state_machine.process_state_change(state_change);
@@ -1601,19 +2415,19 @@ mod tests {
         // Prepare a resource state with 2 consecutive failures already
         let resource_key =
             state_machine.generate_resource_key(ResourceType::Scenario, "h-scenario");
-        let now = Instant::now();
+        let now = common::clock::now_ns();
         let rs = ResourceState {
             resource_type: ResourceType::Scenario,
             resource_name: "h-scenario".to_string(),
             current_state: ScenarioState::Idle as i32,
             desired_state: Some(ScenarioState::Waiting as i32),
-            last_transition_time: now,
+            last_transition_ns: now,
             transition_count: 0,
             metadata: HashMap::new(),
             health_status: HealthStatus {
                 healthy: true,
                 status_message: "ok".to_string(),
-                last_check: now,
+                last_check_ns: now,
                 consecutive_failures: 2,
             },
         };
@@ -1651,7 +2465,7 @@ mod tests {
         let res = state_machine.evaluate_model_state_from_containers(&[]);
         assert_eq!(res, ModelState::Created);
 
-        // One dead -> Dead
+        // One dead for good -> Dead
         let mut state_map = HashMap::new();
         state_map.insert("Status".to_string(), "dead".to_string());
         let container_dead = ContainerInfo {
@@ -1660,12 +2474,25 @@ mod tests {
             image: "img".to_string(),
             state: state_map,
             config: HashMap::new(),
-            annotation: HashMap::new(),
+            annotation: HashMap::from([(
+                ANNOTATION_RESTART_POLICY.to_string(),
+                "Never".to_string(),
+            )]),
             stats: HashMap::new(),
         };
         let res = state_machine.evaluate_model_state_from_containers(&[&container_dead]);
         assert_eq!(res, ModelState::Dead);
 
+        // One dead and restarted -> neither running nor dead during its
+        // grace window
+        let container_restarted = ContainerInfo {
+            id: "restarted-d2".to_string(),
+            annotation: HashMap::new(),
+            ..container_dead.clone()
+        };
+        let res = state_machine.evaluate_model_state_from_containers(&[&container_restarted]);
+        assert_eq!(res, ModelState::Created);
+
         // All paused -> Paused
         let mut s1 = HashMap::new();
         s1.insert("Status".to_string(), "paused".to_string());
@@ -1692,27 +2519,33 @@ mod tests {
         let res = state_machine.evaluate_model_state_from_containers(&[&c1, &c2]);
         assert_eq!(res, ModelState::Paused);
 
-        // All exited -> Exited
+        // All exited and not restarted -> Exited
+        let completed = HashMap::from([(
+            ANNOTATION_RESTART_POLICY.to_string(),
+            "OnFailure".to_string(),
+        )]);
         let mut e1 = HashMap::new();
         e1.insert("Status".to_string(), "exited".to_string());
+        e1.insert("ExitCode".to_string(), "0".to_string());
         let ce1 = ContainerInfo {
             id: "e1".to_string(),
             names: vec!["e1".to_string()],
             image: "img".to_string(),
             state: e1,
             config: HashMap::new(),
-            annotation: HashMap::new(),
+            annotation: completed.clone(),
             stats: HashMap::new(),
         };
         let mut e2 = HashMap::new();
         e2.insert("Status".to_string(), "exited".to_string());
+        e2.insert("ExitCode".to_string(), "0".to_string());
         let ce2 = ContainerInfo {
             id: "e2".to_string(),
             names: vec!["e2".to_string()],
             image: "img".to_string(),
             state: e2,
             config: HashMap::new(),
-            annotation: HashMap::new(),
+            annotation: completed.clone(),
             stats: HashMap::new(),
         };
         let res = state_machine.evaluate_model_state_from_containers(&[&ce1, &ce2]);
@@ -1745,6 +2578,59 @@ mod tests {
         assert_eq!(res, ModelState::Running);
     }
 
+    #[test]
+    fn test_exited_containers_that_are_restarted_recover() {
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let clock = Arc::new(common::clock::MockClock::new(0));
+        let state_machine = StateMachine::with_clock(clock.clone());
+        // Podman reports a crashed container exited until it is restarted
+        let crashed = ContainerInfo {
+            id: "crashed".to_string(),
+            names: vec!["m_crashed".to_string()],
+            state: HashMap::from([
+                ("Status".to_string(), "exited".to_string()),
+                ("ExitCode".to_string(), "1".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            state_machine.parse_container_state(&crashed),
+            ContainerState::Recovering
+        );
+        assert_eq!(
+            state_machine.evaluate_model_state_from_containers(&[&crashed]),
+            ModelState::Created
+        );
+        let grace = common::setting::get_config()
+            .statemanager
+            .failure_grace_secs;
+        clock.advance(Duration::from_secs(grace + 1));
+        assert_eq!(
+            state_machine.evaluate_model_state_from_containers(&[&crashed]),
+            ModelState::Dead
+        );
+
+        // Exited with code 0 under OnFailure, the container completed
+        let completed = ContainerInfo {
+            id: "completed".to_string(),
+            state: HashMap::from([
+                ("Status".to_string(), "exited".to_string()),
+                ("ExitCode".to_string(), "0".to_string()),
+            ]),
+            annotation: HashMap::from([(
+                ANNOTATION_RESTART_POLICY.to_string(),
+                "OnFailure".to_string(),
+            )]),
+            ..crashed.clone()
+        };
+        assert_eq!(
+            state_machine.parse_container_state(&completed),
+            ContainerState::Exited
+        );
+    }
+
     #[test]
     fn test_process_model_state_update_transitions() {
         use common::monitoringserver::ContainerInfo;
@@ -1774,6 +2660,231 @@ mod tests {
         assert!(rs.is_some());
     }
 
+    #[test]
+    fn test_model_instances_are_evaluated_per_node() {
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let state_machine = StateMachine::new();
+        // Not restarted, a dead container is dead for good
+        let container = |status: &str| ContainerInfo {
+            id: status.to_string(),
+            names: vec![status.to_string()],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), status.to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::from([(
+                ANNOTATION_RESTART_POLICY.to_string(),
+                "Never".to_string(),
+            )]),
+            stats: HashMap::new(),
+        };
+        let (paused, exited, running, dead) = (
+            container("paused"),
+            container("exited"),
+            container("running"),
+            container("dead"),
+        );
+        fn instances<'a>(nodes: &[(&str, &'a ContainerInfo)]) -> ModelInstances<'a> {
+            nodes
+                .iter()
+                .map(|(node, c)| (node.to_string(), vec![*c]))
+                .collect()
+        }
+
+        // Mixed in one list the containers would read as running
+        assert_eq!(
+            state_machine.evaluate_model_state_from_containers(&[&paused, &exited]),
+            ModelState::Running
+        );
+        assert_eq!(
+            state_machine
+                .evaluate_model_state_from_instances(&instances(&[("a", &paused), ("b", &exited)])),
+            ModelState::Paused
+        );
+        assert_eq!(
+            state_machine.evaluate_model_state_from_instances(&instances(&[
+                ("a", &exited),
+                ("b", &running)
+            ])),
+            ModelState::Running
+        );
+        // Replicas down while others run degrade the model
+        assert_eq!(
+            state_machine
+                .evaluate_model_state_from_instances(&instances(&[("a", &running), ("b", &dead)])),
+            ModelState::Degraded
+        );
+        assert_eq!(
+            state_machine
+                .evaluate_model_state_from_instances(&instances(&[("a", &exited), ("b", &dead)])),
+            ModelState::Dead
+        );
+        assert_eq!(
+            state_machine.evaluate_model_state_from_instances(&ModelInstances::new()),
+            ModelState::Created
+        );
+    }
+
+    #[test]
+    fn test_paused_standby_instances() {
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let state_machine = StateMachine::new();
+        let container = |status: &str, standby: bool| {
+            let mut annotation =
+                HashMap::from([(ANNOTATION_RESTART_POLICY.to_string(), "Never".to_string())]);
+            if standby {
+                annotation.insert(LABEL_STANDBY.to_string(), "true".to_string());
+            }
+            ContainerInfo {
+                id: format!("{}-{}", status, standby),
+                names: vec![status.to_string()],
+                image: "img".to_string(),
+                state: HashMap::from([("Status".to_string(), status.to_string())]),
+                config: HashMap::new(),
+                annotation,
+                stats: HashMap::new(),
+            }
+        };
+        let (running, dead) = (container("running", false), container("dead", false));
+        let (standby, promoted) = (container("paused", true), container("running", true));
+        fn instances<'a>(nodes: &[(&str, &'a ContainerInfo)]) -> ModelInstances<'a> {
+            nodes
+                .iter()
+                .map(|(node, c)| (node.to_string(), vec![*c]))
+                .collect()
+        }
+
+        // A paused standby does not count as an instance of the model
+        assert_eq!(
+            state_machine.evaluate_model_state_from_instances(&instances(&[
+                ("a", &running),
+                ("b#standby", &standby)
+            ])),
+            ModelState::Running
+        );
+        // The primary is dead, the standby is ready to be promoted
+        assert_eq!(
+            state_machine.evaluate_model_state_from_instances(&instances(&[
+                ("a", &dead),
+                ("b#standby", &standby)
+            ])),
+            ModelState::Degraded
+        );
+        // Promoted, the standby runs the model
+        assert_eq!(
+            state_machine.evaluate_model_state_from_instances(&instances(&[
+                ("b#standby", &promoted),
+                ("a#standby", &standby)
+            ])),
+            ModelState::Running
+        );
+        assert_eq!(
+            state_machine.evaluate_model_state_from_instances(&instances(&[("a", &dead)])),
+            ModelState::Dead
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_waits_for_network_ready() {
+        common::etcd::use_in_memory_store();
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let mut state_machine = StateMachine::new();
+        let network_change = |current: &str, target: &str, id: &str| StateChange {
+            resource_type: ResourceType::Network as i32,
+            resource_name: "model-net".to_string(),
+            current_state: current.to_string(),
+            target_state: target.to_string(),
+            transition_id: id.to_string(),
+            timestamp_ns: 1,
+            source: "actioncontroller".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+        let container = ContainerInfo {
+            id: "c1".to_string(),
+            names: vec!["model-net".to_string()],
+            image: "img".to_string(),
+            state: HashMap::from([("Status".to_string(), "running".to_string())]),
+            config: HashMap::new(),
+            annotation: HashMap::new(),
+            stats: HashMap::new(),
+        };
+
+        let result =
+            state_machine.process_state_change(network_change("unspecified", "requested", "n1"));
+        assert!(result.is_success());
+        assert!(state_machine.is_waiting_for_network("model-net"));
+
+        // Running containers do not make the model running yet
+        let result = state_machine.process_model_state_update("model-net", &[&container]);
+        assert!(result.actions_to_execute.is_empty());
+        assert_eq!(result.new_state, ModelState::Created as i32);
+
+        // A failed setup can be retried
+        let result =
+            state_machine.process_state_change(network_change("requested", "failed", "n2"));
+        assert!(result.is_success());
+        let result =
+            state_machine.process_state_change(network_change("failed", "requested", "n3"));
+        assert!(result.is_success());
+        let result = state_machine.process_state_change(network_change("requested", "ready", "n4"));
+        assert_eq!(result.new_state, NetworkState::Ready as i32);
+        assert!(!state_machine.is_waiting_for_network("model-net"));
+
+        let result = state_machine.process_model_state_update("model-net", &[&container]);
+        assert_eq!(result.new_state, ModelState::Running as i32);
+
+        // Ready cannot fail without being requested again
+        let result = state_machine.process_state_change(network_change("ready", "failed", "n5"));
+        assert_eq!(result.error_code, ErrorCode::InvalidStateTransition);
+    }
+
+    #[test]
+    fn test_node_cordon_and_drain() {
+        let mut state_machine = StateMachine::new();
+        let node_change = |current: &str, target: &str, id: &str| StateChange {
+            resource_type: ResourceType::Node as i32,
+            resource_name: "edge-1".to_string(),
+            current_state: current.to_string(),
+            target_state: target.to_string(),
+            transition_id: id.to_string(),
+            timestamp_ns: 1,
+            source: "apiserver".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        };
+
+        // A schedulable node cannot be drained before it is cordoned
+        let result =
+            state_machine.process_state_change(node_change("schedulable", "draining", "d0"));
+        assert_eq!(result.error_code, ErrorCode::InvalidStateTransition);
+
+        let result =
+            state_machine.process_state_change(node_change("schedulable", "cordoned", "d1"));
+        assert_eq!(result.new_state, NodeState::Cordoned as i32);
+        let result = state_machine.process_state_change(node_change("cordoned", "draining", "d2"));
+        assert!(result.is_success());
+        // A failed drain leaves the node cordoned
+        let result = state_machine.process_state_change(node_change("draining", "cordoned", "d3"));
+        assert_eq!(result.new_state, NodeState::Cordoned as i32);
+        let result = state_machine.process_state_change(node_change("cordoned", "draining", "d4"));
+        assert!(result.is_success());
+        let result = state_machine.process_state_change(node_change("draining", "drained", "d5"));
+        assert_eq!(result.new_state, NodeState::Drained as i32);
+        let result =
+            state_machine.process_state_change(node_change("drained", "schedulable", "d6"));
+        assert_eq!(result.new_state, NodeState::Schedulable as i32);
+    }
+
     #[test]
     fn test_parse_container_state_running_fallback() {
         use common::monitoringserver::ContainerInfo;
@@ -1797,6 +2908,117 @@ mod tests {
         assert_eq!(res, ContainerState::Running);
     }
 
+    #[test]
+    fn test_probe_results_feed_model_state() {
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let state_machine = StateMachine::new();
+        let container = |probes: &[(&str, &str)]| {
+            let mut state = HashMap::from([("Status".to_string(), "running".to_string())]);
+            for (probe, result) in probes {
+                state.insert(probe.to_string(), result.to_string());
+            }
+            // Not restarted, a container failing its liveness probe is dead
+            ContainerInfo {
+                id: "c".to_string(),
+                names: vec!["m_c".to_string()],
+                image: "img".to_string(),
+                state,
+                config: HashMap::new(),
+                annotation: HashMap::from([(
+                    ANNOTATION_RESTART_POLICY.to_string(),
+                    "Never".to_string(),
+                )]),
+                stats: HashMap::new(),
+            }
+        };
+        let model_state = |containers: &[ContainerInfo]| {
+            let refs: Vec<&ContainerInfo> = containers.iter().collect();
+            state_machine.evaluate_model_state_from_containers(&refs)
+        };
+
+        let ready = container(&[("Ready", "true"), ("Live", "true")]);
+        let not_ready = container(&[("Ready", "false")]);
+        let not_live = container(&[("Ready", "true"), ("Live", "false")]);
+        assert_eq!(
+            model_state(std::slice::from_ref(&ready)),
+            ModelState::Running
+        );
+        assert_eq!(
+            model_state(&[ready.clone(), not_ready]),
+            ModelState::Created
+        );
+        assert_eq!(model_state(&[ready, not_live]), ModelState::Dead);
+    }
+
+    #[test]
+    fn test_crash_loop_back_off_escalates_model_and_package() {
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let state_machine = StateMachine::new();
+        let container = |state: &[(&str, &str)]| ContainerInfo {
+            id: "c".to_string(),
+            names: vec!["m_c".to_string()],
+            image: "img".to_string(),
+            state: state
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            config: HashMap::new(),
+            annotation: HashMap::new(),
+            stats: HashMap::new(),
+        };
+        let running = container(&[("Status", "running"), ("RestartCount", "1")]);
+        let crash_looping = container(&[
+            ("Status", "exited"),
+            ("RestartCount", "5"),
+            ("CrashLoopBackOff", "true"),
+        ]);
+        assert_eq!(
+            state_machine.evaluate_model_state_from_containers(&[&running, &crash_looping]),
+            ModelState::CrashLoopBackOff
+        );
+        assert_eq!(
+            state_machine.evaluate_package_state_from_models(&[
+                ("m1".to_string(), ModelState::CrashLoopBackOff),
+                ("m2".to_string(), ModelState::Running),
+            ]),
+            PackageState::Degraded
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restarting_too_often_crash_loops() {
+        common::etcd::use_in_memory_store();
+        use common::monitoringserver::ContainerInfo;
+        use std::collections::HashMap;
+
+        let state_machine = StateMachine::new();
+        let container = |restarts: u32| ContainerInfo {
+            id: "restarting-too-often".to_string(),
+            names: vec!["m_restarting".to_string()],
+            state: HashMap::from([("Status".to_string(), "running".to_string())]),
+            stats: HashMap::from([
+                ("RestartCount".to_string(), restarts.to_string()),
+                ("OOMKillCount".to_string(), "0".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            state_machine.evaluate_model_state_from_containers(&[&container(0)]),
+            ModelState::Running
+        );
+        let limit = common::setting::get_config()
+            .statemanager
+            .crash_loop_restarts;
+        assert_eq!(
+            state_machine.evaluate_model_state_from_containers(&[&container(limit)]),
+            ModelState::CrashLoopBackOff
+        );
+    }
+
     #[test]
     fn test_get_resource_state_and_list_resources_by_state() {
         use common::statemanager::{ResourceType, ScenarioState};
@@ -1812,6 +3034,10 @@ mod tests {
             transition_id: "lt-1".to_string(),
             timestamp_ns: 1,
             source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
 
         let _ = state_machine.process_state_change(state_change);
@@ -1850,6 +3076,10 @@ mod tests {
                 transition_id: "t".to_string(),
                 timestamp_ns: 0,
                 source: "test".to_string(),
+                correlation_id: String::new(),
+                sub_state: String::new(),
+                denial: None,
+                metadata: Default::default(),
             }
         ));
 
@@ -1864,6 +3094,10 @@ mod tests {
                 transition_id: "t".to_string(),
                 timestamp_ns: 0,
                 source: "test".to_string(),
+                correlation_id: String::new(),
+                sub_state: String::new(),
+                denial: None,
+                metadata: Default::default(),
             }
         ));
     }
@@ -1879,6 +3113,10 @@ mod tests {
             transition_id: "t".to_string(),
             timestamp_ns: 0,
             source: "test".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
         };
         assert!(!sm.evaluate_condition("critical_models_failed", &sc));
         assert!(!sm.evaluate_condition("timeout_or_error", &sc));
@@ -1930,6 +3168,66 @@ mod tests {
         assert_eq!(m3, "container_dead_or_info_failure");
     }
 
+    #[test]
+    fn test_package_update_reports_steps_as_sub_states() {
+        let mut sm = StateMachine::new();
+        let change = |current: &str, target: &str, sub_state: &str| StateChange {
+            resource_type: ResourceType::Package as i32,
+            resource_name: "pkg".to_string(),
+            current_state: current.to_string(),
+            target_state: target.to_string(),
+            transition_id: format!("t-{target}-{sub_state}"),
+            timestamp_ns: 1,
+            source: "actioncontroller".to_string(),
+            correlation_id: String::new(),
+            sub_state: sub_state.to_string(),
+            denial: None,
+            metadata: Default::default(),
+        };
+
+        let started = sm.process_state_change(change("running", "updating", "rolling 0/2"));
+        assert!(started.is_success(), "{}", started.message);
+        assert_eq!(started.actions_to_execute, vec!["track_update_progress"]);
+
+        let step = sm.process_state_change(change("updating", "updating", "rolling 1/2: m1"));
+        assert!(step.is_success(), "{}", step.message);
+        assert!(step.actions_to_execute.is_empty());
+        let tracked = sm.get_resource_state("pkg", ResourceType::Package).unwrap();
+        assert_eq!(tracked.current_state, PackageState::Updating as i32);
+        assert_eq!(tracked.metadata["sub_state"], "rolling 1/2: m1");
+
+        // Without a sub-state staying in a state is no transition
+        let same = sm.process_state_change(change("updating", "updating", ""));
+        assert_eq!(same.error_code, ErrorCode::InvalidRequest);
+
+        let done = sm.process_state_change(change("updating", "running", ""));
+        assert!(done.is_success(), "{}", done.message);
+        let tracked = sm.get_resource_state("pkg", ResourceType::Package).unwrap();
+        assert_eq!(tracked.current_state, PackageState::Running as i32);
+        assert!(!tracked.metadata.contains_key("sub_state"));
+
+        let canary = |outcome: &str| {
+            let mut sm = StateMachine::new();
+            sm.process_state_change(change("running", "updating", "canary: launching"));
+            sm.process_state_change(change("updating", "running", outcome))
+        };
+        let promoted = canary(CANARY_PROMOTED);
+        assert_eq!(
+            promoted.actions_to_execute,
+            vec!["announce_canary_promotion"]
+        );
+        let rolled_back = canary(&format!("{CANARY_ROLLED_BACK}: probe failed"));
+        assert_eq!(rolled_back.new_state, PackageState::Running as i32);
+        assert_eq!(
+            rolled_back.actions_to_execute,
+            vec!["alert_canary_rollback"]
+        );
+
+        // Only updates are reported, other package states follow the models
+        let paused = sm.process_state_change(change("running", "paused", ""));
+        assert_eq!(paused.error_code, ErrorCode::InvalidStateTransition);
+    }
+
     #[test]
     fn test_state_str_to_enum_hyphen_and_case() {
         // Test various normalizations
@@ -1958,6 +3256,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_current_package_state_reads_etcd() {
+        common::etcd::use_in_memory_store();
         // Put a package state into etcd and verify mapping
         let key = "/package/testpkg/state";
         let _ = common::etcd::put(key, "running").await;
@@ -1968,6 +3267,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_evaluate_and_update_package_state_all_dead_in_etcd() {
+        common::etcd::use_in_memory_store();
         // Create a package with two models and set both models' states to Dead in ETCD
         let pkg_key = "Package/pkg-dead";
         let pkg_yaml = r#"{"apiVersion":"v1","kind":"Package","metadata":{"name":"pkg-dead"},"spec":{"pattern":[],"models":[{"name":"mdead1","node":"n","resources":{"volume":"","network":"","realtime":false}},{"name":"mdead2","node":"n","resources":{"volume":"","network":"","realtime":false}}]}}"#;
@@ -1992,6 +3292,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_evaluate_and_update_package_state_degraded_in_etcd() {
+        common::etcd::use_in_memory_store();
         // Create a package with two models and set one model Dead and one Running
         let pkg_key = "Package/pkg-degraded";
         let pkg_yaml = r#"{"apiVersion":"v1","kind":"Package","metadata":{"name":"pkg-degraded"},"spec":{"pattern":[],"models":[{"name":"mdeg1","node":"n","resources":{"volume":"","network":"","realtime":false}},{"name":"mdeg2","node":"n","resources":{"volume":"","network":"","realtime":false}}]}}"#;
@@ -2015,6 +3316,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_models_for_package_missing_returns_empty() {
+        common::etcd::use_in_memory_store();
         // Ensure package key is absent
         let _ = common::etcd::delete("Package/missing-package").await;
         let res = StateMachine::get_models_for_package("missing-package").await;
@@ -2031,6 +3333,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_models_for_package_invalid_yaml_returns_empty() {
+        common::etcd::use_in_memory_store();
         // Put an invalid YAML string into etcd under the package key
         let pkg_key = "Package/pkg-invalid-yaml";
         let _ = common::etcd::put(pkg_key, "::: not valid yaml :::").await;
@@ -2048,6 +3351,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_find_packages_containing_model_success() {
+        common::etcd::use_in_memory_store();
         // Create two packages, one containing the target model
         let pkg_a_key = "Package/pkg-with-model";
         let pkg_a_yaml = r#"{"apiVersion":"v1","kind":"Package","metadata":{"name":"pkg-with-model"},"spec":{"pattern":[],"models":[{"name":"target_model","node":"n","resources":{"volume":"","network":"","realtime":false}}]}}"#;
@@ -2068,6 +3372,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_current_package_state_none_when_missing() {
+        common::etcd::use_in_memory_store();
         // Ensure no state key exists for this package
         let _ = common::etcd::delete("/package/no-state/state").await;
         let res = StateMachine::get_current_package_state("no-state").await;
@@ -2094,6 +3399,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_evaluate_and_update_package_state_no_models() {
+        common::etcd::use_in_memory_store();
         let sm = StateMachine::new();
         // Ensure no package data is present in etcd for this test package
         let _ = common::etcd::delete("Package/nonexistent-package").await;
@@ -2104,4 +3410,267 @@ mod tests {
         assert!(!changed);
         assert_eq!(state, common::statemanager::PackageState::Idle);
     }
+
+    fn scenario_change(name: &str, from: &str, to: &str) -> StateChange {
+        StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: name.to_string(),
+            current_state: from.to_string(),
+            target_state: to.to_string(),
+            transition_id: format!("{}-{}", name, to),
+            timestamp_ns: 1,
+            source: "unittest".to_string(),
+            correlation_id: String::new(),
+            sub_state: String::new(),
+            denial: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scenario_admission_transitions() {
+        common::etcd::use_in_memory_store();
+        let mut sm = StateMachine::new();
+        for (from, to) in [
+            ("idle", "waiting"),
+            ("waiting", "satisfied"),
+            ("satisfied", "allowed"),
+            ("allowed", "pending"),
+            ("pending", "completed"),
+        ] {
+            let result = sm.process_state_change(scenario_change("queued", from, to));
+            assert!(
+                result.is_success(),
+                "{} -> {}: {}",
+                from,
+                to,
+                result.message
+            );
+        }
+
+        for (from, to) in [
+            ("idle", "waiting"),
+            ("waiting", "satisfied"),
+            ("satisfied", "allowed"),
+            ("allowed", "denied"),
+        ] {
+            let result = sm.process_state_change(scenario_change("too-large", from, to));
+            assert!(
+                result.is_success(),
+                "{} -> {}: {}",
+                from,
+                to,
+                result.message
+            );
+        }
+        let state = sm
+            .get_resource_state("too-large", ResourceType::Scenario)
+            .unwrap();
+        assert_eq!(state.current_state, ScenarioState::Denied as i32);
+    }
+
+    #[tokio::test]
+    async fn test_check_timeouts_moves_stuck_scenario_to_denied() {
+        common::etcd::use_in_memory_store();
+        let clock = Arc::new(common::clock::MockClock::new(1_000_000_000));
+        let mut sm = StateMachine::with_clock(clock.clone());
+        sm.set_state_timeout(
+            ResourceType::Scenario,
+            ScenarioState::Satisfied as i32,
+            Duration::from_secs(30),
+        );
+        sm.process_state_change(scenario_change("stuck", "idle", "waiting"));
+        sm.process_state_change(scenario_change("stuck", "waiting", "satisfied"));
+
+        clock.advance(Duration::from_secs(29));
+        assert!(sm.check_timeouts().is_empty());
+
+        clock.advance(Duration::from_secs(2));
+        let events = sm.check_timeouts();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].resource_name, "stuck");
+        assert_eq!(events[0].from_state, ScenarioState::Satisfied as i32);
+        assert_eq!(events[0].to_state, ScenarioState::Denied as i32);
+        assert_eq!(events[0].elapsed, Duration::from_secs(31));
+
+        let state = sm
+            .get_resource_state("stuck", ResourceType::Scenario)
+            .unwrap();
+        assert_eq!(state.current_state, ScenarioState::Denied as i32);
+        assert!(!state.health_status.healthy);
+        assert_eq!(state.metadata["source"], "state_timeout");
+
+        // Denied has no timeout, the resource is reported once
+        clock.advance(Duration::from_secs(600));
+        assert!(sm.check_timeouts().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_transition_tables() {
+        common::etcd::use_in_memory_store();
+        let mut sm = StateMachine::new();
+        let tables = crate::transitions::parse(
+            r#"
+scenario:
+  transitions:
+    - from: satisfied
+      to: allowed
+      condition: operator_approved
+      action: execute_action_on_target_package
+    - from: satisfied
+      to: completed
+      event: scenario_skipped
+      action: log_skipped
+network:
+  replace: true
+  transitions:
+    - from: requested
+      to: ready
+      action: connect
+"#,
+            config::FileFormat::Yaml,
+        )
+        .unwrap();
+        let scenarios = sm.transition_tables[&ResourceType::Scenario].len();
+        sm.apply_transition_tables(tables);
+
+        let event = sm.infer_event_from_states(
+            ScenarioState::Satisfied as i32,
+            ScenarioState::Allowed as i32,
+            ResourceType::Scenario,
+        );
+        let overridden = sm
+            .find_valid_transition(
+                ResourceType::Scenario,
+                ScenarioState::Satisfied as i32,
+                &event,
+                ScenarioState::Allowed as i32,
+            )
+            .unwrap();
+        assert_eq!(overridden.condition.as_deref(), Some("operator_approved"));
+        assert_eq!(
+            sm.transition_tables[&ResourceType::Scenario].len(),
+            scenarios + 1
+        );
+        assert!(sm
+            .find_valid_transition(
+                ResourceType::Scenario,
+                ScenarioState::Satisfied as i32,
+                "scenario_skipped",
+                ScenarioState::Completed as i32,
+            )
+            .is_some());
+        assert_eq!(sm.transition_tables[&ResourceType::Network].len(), 1);
+        assert!(!sm.transition_tables[&ResourceType::Package].is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_transitions_yield_to_exact_ones() {
+        let mut sm = StateMachine::new();
+        // Built in, an update may start from any state
+        let started = sm
+            .find_valid_transition(
+                ResourceType::Package,
+                PackageState::Degraded as i32,
+                "update_started",
+                PackageState::Updating as i32,
+            )
+            .unwrap();
+        assert_eq!(started.from_state, PackageState::Degraded as i32);
+        assert_eq!(started.action, "track_update_progress");
+        // but not from the state it leads to
+        assert!(sm
+            .find_valid_transition(
+                ResourceType::Package,
+                PackageState::Updating as i32,
+                "update_started",
+                PackageState::Updating as i32,
+            )
+            .is_none());
+        // as is a deactivation, but from idle
+        let deactivated = sm
+            .find_valid_transition(
+                ResourceType::Scenario,
+                ScenarioState::Pending as i32,
+                "scenario_deactivation",
+                ScenarioState::Idle as i32,
+            )
+            .unwrap();
+        assert_eq!(deactivated.from_state, ScenarioState::Pending as i32);
+        assert_eq!(deactivated.action, "stop_condition_evaluation");
+        assert!(sm
+            .find_valid_transition(
+                ResourceType::Scenario,
+                ScenarioState::Idle as i32,
+                "scenario_deactivation",
+                ScenarioState::Idle as i32,
+            )
+            .is_none());
+
+        let tables = crate::transitions::parse(
+            r#"
+package:
+  transitions:
+    - {from: "*", to: error, action: log_failure}
+    - {from: running, to: error, event: "*", action: alert_failure}
+    - {from: running, to: error, event: model_crashed, action: restart_models}
+"#,
+            config::FileFormat::Yaml,
+        )
+        .unwrap();
+        sm.apply_transition_tables(tables);
+        let action = |from: PackageState, event: &str| {
+            sm.find_valid_transition(
+                ResourceType::Package,
+                from as i32,
+                event,
+                PackageState::Error as i32,
+            )
+            .map(|transition| transition.action)
+        };
+        assert_eq!(
+            action(PackageState::Running, "model_crashed").as_deref(),
+            Some("restart_models")
+        );
+        assert_eq!(
+            action(PackageState::Running, "model_failed").as_deref(),
+            Some("alert_failure")
+        );
+        assert_eq!(
+            action(PackageState::Paused, "model_failed").as_deref(),
+            Some("log_failure")
+        );
+        // Updating -> Error keeps its built-in rule on its own event
+        assert_eq!(
+            action(PackageState::Updating, "update_failed").as_deref(),
+            Some("log_update_failure")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_state_timeouts_skips_invalid_entries() {
+        common::etcd::use_in_memory_store();
+        let mut sm = StateMachine::new();
+        let timeouts = HashMap::from([
+            (
+                "model".to_string(),
+                HashMap::from([("created".to_string(), 60), ("running".to_string(), 0)]),
+            ),
+            (
+                "volume".to_string(),
+                HashMap::from([("bound".to_string(), 5)]),
+            ),
+            (
+                "scenario".to_string(),
+                HashMap::from([("launching".to_string(), 5)]),
+            ),
+        ]);
+        sm.load_state_timeouts(&timeouts);
+
+        assert_eq!(sm.state_timeouts.len(), 1);
+        assert_eq!(
+            sm.state_timeouts[&(ResourceType::Model, ModelState::Created as i32)],
+            Duration::from_secs(60)
+        );
+    }
 }
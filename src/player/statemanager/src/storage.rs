@@ -0,0 +1,568 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Storage of states and artifacts read by the StateManager
+//!
+//! The state machine and the manager only see the [`StateStorage`] trait.
+//! The backend is chosen with `statemanager.storage_backend`:
+//! * `etcd` (default) - the shared RocksDB service, with writes buffered by
+//!   [`crate::store`] while it is unavailable
+//! * `embedded` - a local sled database in `statemanager.storage_path`, for
+//!   small single-board deployments that do not run the service
+//!
+//! The artifacts are written by the API server to the RocksDB service only,
+//! so with the embedded backend they are still read from there, see
+//! [`SplitStorage`].
+//!
+//! Both backends report a missing key with the same `Key not found` error.
+//! Tests and replays that need a storage of their own use an
+//! [`InMemoryStateStorage`].
+//!
+//! Writes that must not be torn apart by a crash, such as a model state and
+//! the package states it changes, are collected in a [`Transaction`] and
+//! committed atomically with [`StateStorage::commit`].
+//!
+//! Model and package states stored by a version with the former state set
+//! are rewritten to the current names by [`migrate_states`] at startup.
+//!
+//! State writes to the selected backend are skipped when unchanged and
+//! coalesced when rapid by [`crate::coalesce`].
+
+use async_trait::async_trait;
+use common::logd;
+use common::state_mapping::StateName;
+use common::statemanager::{ModelState, PackageState};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Error of a read of a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Key prefixes of the artifacts written by the API server
+const ARTIFACT_PREFIXES: [&str; 7] = [
+    "Scenario/",
+    "Package/",
+    "Model/",
+    "Pod/",
+    "Network/",
+    "Volume/",
+    "Node/",
+];
+
+fn is_artifact(key: &str) -> bool {
+    ARTIFACT_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+}
+
+/// Writes committed together by [`StateStorage::commit`]
+///
+/// Reads through the transaction see its own writes before the storage.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Transaction {
+    writes: Vec<(String, String)>,
+}
+
+impl Transaction {
+    /// Add a write, replacing an earlier write of the same key
+    pub fn put(&mut self, key: &str, value: &str) {
+        match self.writes.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.writes.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    pub fn writes(&self) -> &[(String, String)] {
+        &self.writes
+    }
+
+    /// Value of a key as it will be once the transaction is committed
+    pub async fn get(&self, key: &str) -> Result<String, String> {
+        match self.writes.iter().find(|(k, _)| k == key) {
+            Some((_, value)) => Ok(value.clone()),
+            None => storage().get(key).await,
+        }
+    }
+}
+
+/// Key-value storage of the StateManager
+#[async_trait]
+pub trait StateStorage: Send + Sync {
+    /// Name of the backend, for logs
+    fn name(&self) -> &'static str;
+    /// Value of a key, `Key not found` if it is not stored
+    async fn get(&self, key: &str) -> Result<String, String>;
+    async fn put(&self, key: &str, value: &str) -> Result<(), String>;
+    async fn delete(&self, key: &str) -> Result<(), String>;
+    /// All pairs whose key starts with `prefix`, ordered by key
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String>;
+    /// Apply all writes of a transaction or none of them
+    async fn commit(&self, transaction: Transaction) -> Result<(), String>;
+}
+
+/// The RocksDB service through `common::etcd`
+pub struct EtcdStorage;
+
+#[async_trait]
+impl StateStorage for EtcdStorage {
+    fn name(&self) -> &'static str {
+        "etcd"
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        crate::store::get(key).await
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        crate::store::put(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        crate::store::delete(key).await
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        common::etcd::get_all_with_prefix(prefix).await
+    }
+
+    async fn commit(&self, transaction: Transaction) -> Result<(), String> {
+        if transaction.is_empty() {
+            return Ok(());
+        }
+        crate::store::put_all(transaction.writes).await
+    }
+}
+
+/// Local sled database
+pub struct EmbeddedStorage {
+    db: sled::Db,
+}
+
+impl EmbeddedStorage {
+    /// Open or create the database in a directory
+    pub fn open(path: &str) -> Result<Self, String> {
+        sled::open(path)
+            .map(|db| Self { db })
+            .map_err(|e| format!("Failed to open embedded storage at {}: {}", path, e))
+    }
+
+    /// Database discarded when dropped
+    pub fn temporary() -> Result<Self, String> {
+        sled::Config::new()
+            .temporary(true)
+            .open()
+            .map(|db| Self { db })
+            .map_err(|e| format!("Failed to open temporary embedded storage: {}", e))
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, String> {
+    String::from_utf8(bytes.to_vec()).map_err(|e| format!("Stored data is not UTF-8: {}", e))
+}
+
+#[async_trait]
+impl StateStorage for EmbeddedStorage {
+    fn name(&self) -> &'static str {
+        "embedded"
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        match self.db.get(key).map_err(|e| e.to_string())? {
+            Some(value) => utf8(&value),
+            None => Err(NOT_FOUND.to_string()),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        if key.is_empty() {
+            return Err("Key cannot be empty".to_string());
+        }
+        self.db
+            .insert(key, value.as_bytes())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.db.remove(key).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        self.db
+            .scan_prefix(prefix)
+            .map(|item| {
+                let (key, value) = item.map_err(|e| e.to_string())?;
+                Ok((utf8(&key)?, utf8(&value)?))
+            })
+            .collect()
+    }
+
+    async fn commit(&self, transaction: Transaction) -> Result<(), String> {
+        let mut batch = sled::Batch::default();
+        for (key, value) in transaction.writes {
+            if key.is_empty() {
+                return Err("Key cannot be empty".to_string());
+            }
+            batch.insert(key.as_bytes(), value.as_bytes());
+        }
+        self.db.apply_batch(batch).map_err(|e| e.to_string())
+    }
+}
+
+/// States in one storage and the artifacts in another
+///
+/// Every key under an artifact prefix goes to `artifacts`, the others to
+/// `states`. A transaction is committed to each storage its writes go to.
+pub struct SplitStorage {
+    states: Arc<dyn StateStorage>,
+    artifacts: Arc<dyn StateStorage>,
+}
+
+impl SplitStorage {
+    pub fn new(states: Arc<dyn StateStorage>, artifacts: Arc<dyn StateStorage>) -> Self {
+        Self { states, artifacts }
+    }
+
+    fn of(&self, key: &str) -> &dyn StateStorage {
+        if is_artifact(key) {
+            self.artifacts.as_ref()
+        } else {
+            self.states.as_ref()
+        }
+    }
+}
+
+#[async_trait]
+impl StateStorage for SplitStorage {
+    fn name(&self) -> &'static str {
+        self.states.name()
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        self.of(key).get(key).await
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        self.of(key).put(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.of(key).delete(key).await
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        self.of(prefix).get_all_with_prefix(prefix).await
+    }
+
+    async fn commit(&self, transaction: Transaction) -> Result<(), String> {
+        let (artifacts, states): (Vec<_>, Vec<_>) = transaction
+            .writes
+            .into_iter()
+            .partition(|(key, _)| is_artifact(key));
+        if !states.is_empty() {
+            self.states.commit(Transaction { writes: states }).await?;
+        }
+        if !artifacts.is_empty() {
+            self.artifacts
+                .commit(Transaction { writes: artifacts })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// States kept in memory, for tests that do not need a database
+#[derive(Debug, Default)]
+pub struct InMemoryStateStorage {
+    pairs: Mutex<BTreeMap<String, String>>,
+}
+
+impl InMemoryStateStorage {
+    fn with_pairs<R>(&self, f: impl FnOnce(&mut BTreeMap<String, String>) -> R) -> R {
+        let mut pairs = self.pairs.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut pairs)
+    }
+}
+
+#[async_trait]
+impl StateStorage for InMemoryStateStorage {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn get(&self, key: &str) -> Result<String, String> {
+        self.with_pairs(|pairs| pairs.get(key).cloned())
+            .ok_or_else(|| NOT_FOUND.to_string())
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        if key.is_empty() {
+            return Err("Key cannot be empty".to_string());
+        }
+        self.with_pairs(|pairs| pairs.insert(key.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.with_pairs(|pairs| pairs.remove(key));
+        Ok(())
+    }
+
+    async fn get_all_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        Ok(self.with_pairs(|pairs| {
+            pairs
+                .range(prefix.to_string()..)
+                .take_while(|(key, _)| key.starts_with(prefix))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        }))
+    }
+
+    async fn commit(&self, transaction: Transaction) -> Result<(), String> {
+        if transaction.writes.iter().any(|(key, _)| key.is_empty()) {
+            return Err("Key cannot be empty".to_string());
+        }
+        self.with_pairs(|pairs| pairs.extend(transaction.writes));
+        Ok(())
+    }
+}
+
+/// Rewrite the model and package states stored with former names
+///
+/// Models keep their short name and packages their proto name. States
+/// that are neither current nor former names are left for the readers to
+/// report.
+///
+/// ### Returns
+/// * `Ok(count)` - number of states rewritten
+pub async fn migrate_states(storage: &dyn StateStorage) -> Result<usize, String> {
+    let mut transaction = Transaction::default();
+    for (key, value) in storage.get_all_with_prefix("/model/").await? {
+        if key.ends_with("/state") && ModelState::parse(&value).is_none() {
+            if let Some(state) = ModelState::migrate(&value) {
+                transaction.put(&key, state.name());
+            }
+        }
+    }
+    for (key, value) in storage.get_all_with_prefix("/package/").await? {
+        if key.ends_with("/state") && PackageState::parse(&value).is_none() {
+            if let Some(state) = PackageState::migrate(&value) {
+                transaction.put(&key, state.as_str_name());
+            }
+        }
+    }
+
+    let count = transaction.writes().len();
+    if count > 0 {
+        storage.commit(transaction).await?;
+    }
+    Ok(count)
+}
+
+static STORAGE: OnceLock<Box<dyn StateStorage>> = OnceLock::new();
+
+/// Use `storage` instead of the backend selected in settings.yaml
+///
+/// Fails once the storage is in use, so it must be called before anything
+/// reads or writes a state, e.g. to replay a recording against a
+/// storage in memory. Every write reaches `storage`, none is coalesced.
+pub fn set_storage(storage: Box<dyn StateStorage>) -> Result<(), String> {
+    let name = storage.name();
+    STORAGE
+        .set(instrument(storage))
+        .map_err(|_| "State storage is already in use".to_string())?;
+    logd!(3, "State storage backend: {}", name);
+    Ok(())
+}
+
+/// Backend selected in settings.yaml, opened on first use
+///
+/// An embedded database that cannot be opened is logged and etcd is used
+/// instead, so that the StateManager still starts.
+pub fn storage() -> &'static dyn StateStorage {
+    STORAGE
+        .get_or_init(|| {
+            let settings = &common::setting::get_config().statemanager;
+            let storage: Box<dyn StateStorage> = match settings.storage_backend.as_str() {
+                "embedded" => match EmbeddedStorage::open(&settings.storage_path) {
+                    Ok(storage) => {
+                        Box::new(SplitStorage::new(Arc::new(storage), Arc::new(EtcdStorage)))
+                    }
+                    Err(e) => {
+                        logd!(5, "{}, using etcd", e);
+                        Box::new(EtcdStorage)
+                    }
+                },
+                "etcd" => Box::new(EtcdStorage),
+                other => {
+                    logd!(5, "Unknown storage backend '{}', using etcd", other);
+                    Box::new(EtcdStorage)
+                }
+            };
+            logd!(3, "State storage backend: {}", storage.name());
+            Box::new(crate::coalesce::CoalescingStorage::new(
+                Arc::from(instrument(storage)),
+                Duration::from_millis(settings.write_coalesce_window_ms),
+                Duration::from_secs(settings.write_cache_ttl_secs),
+            ))
+        })
+        .as_ref()
+}
+
+/// Route the backend through the active fault script
+#[cfg(any(test, feature = "fault-injection"))]
+fn instrument(storage: Box<dyn StateStorage>) -> Box<dyn StateStorage> {
+    Box::new(crate::fault::FaultyStorage::new(
+        storage,
+        crate::fault::script().clone(),
+    ))
+}
+
+#[cfg(not(any(test, feature = "fault-injection")))]
+fn instrument(storage: Box<dyn StateStorage>) -> Box<dyn StateStorage> {
+    storage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embedded_storage_round_trip() {
+        let storage = EmbeddedStorage::temporary().unwrap();
+
+        storage.put("/model/m1/state", "Running").await.unwrap();
+        storage.put("/model/m2/state", "Dead").await.unwrap();
+        storage.put("/package/p1/state", "running").await.unwrap();
+        assert_eq!(storage.get("/model/m1/state").await.unwrap(), "Running");
+
+        let models = storage.get_all_with_prefix("/model/").await.unwrap();
+        assert_eq!(
+            models,
+            vec![
+                ("/model/m1/state".to_string(), "Running".to_string()),
+                ("/model/m2/state".to_string(), "Dead".to_string()),
+            ]
+        );
+
+        storage.delete("/model/m1/state").await.unwrap();
+        assert_eq!(storage.get("/model/m1/state").await.unwrap_err(), NOT_FOUND);
+        assert!(storage.put("", "value").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_embedded_commit_applies_all_or_nothing() {
+        let storage = EmbeddedStorage::temporary().unwrap();
+
+        let mut transaction = Transaction::default();
+        transaction.put("/model/m1/state", "Dead");
+        transaction.put("/package/p1/state", "error");
+        transaction.put("/model/m1/state", "Running");
+        assert_eq!(transaction.writes().len(), 2);
+        storage.commit(transaction).await.unwrap();
+        assert_eq!(storage.get("/model/m1/state").await.unwrap(), "Running");
+        assert_eq!(storage.get("/package/p1/state").await.unwrap(), "error");
+
+        let mut transaction = Transaction::default();
+        transaction.put("/model/m2/state", "Dead");
+        transaction.put("", "invalid");
+        assert!(storage.commit(transaction).await.is_err());
+        assert!(storage.get("/model/m2/state").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_split_storage_keeps_artifacts_apart() {
+        let states = Arc::new(InMemoryStateStorage::default());
+        let artifacts = Arc::new(InMemoryStateStorage::default());
+        artifacts
+            .put("Scenario/s1", "kind: Scenario")
+            .await
+            .unwrap();
+        let storage = SplitStorage::new(states.clone(), artifacts.clone());
+
+        assert_eq!(storage.get("Scenario/s1").await.unwrap(), "kind: Scenario");
+        assert_eq!(
+            storage
+                .get_all_with_prefix("Scenario/")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let mut transaction = Transaction::default();
+        transaction.put("/scenario/s1/state", "Completed");
+        transaction.put("Package/p1", "kind: Package");
+        storage.commit(transaction).await.unwrap();
+        assert_eq!(states.get("/scenario/s1/state").await.unwrap(), "Completed");
+        assert!(states.get("Package/p1").await.is_err());
+        assert_eq!(artifacts.get("Package/p1").await.unwrap(), "kind: Package");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_storage_matches_embedded() {
+        let storage = InMemoryStateStorage::default();
+
+        storage.put("/model/m1/state", "Running").await.unwrap();
+        storage.put("/modelx/m2/state", "Dead").await.unwrap();
+        assert_eq!(
+            storage.get_all_with_prefix("/model/").await.unwrap(),
+            vec![("/model/m1/state".to_string(), "Running".to_string())]
+        );
+
+        let mut transaction = Transaction::default();
+        transaction.put("/model/m2/state", "Dead");
+        transaction.put("", "invalid");
+        assert!(storage.commit(transaction).await.is_err());
+        assert_eq!(storage.get("/model/m2/state").await.unwrap_err(), NOT_FOUND);
+
+        storage.delete("/model/m1/state").await.unwrap();
+        assert!(storage.get("/model/m1/state").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_states_rewrites_former_names() {
+        let storage = InMemoryStateStorage::default();
+        storage.put("/model/m1/state", "Succeeded").await.unwrap();
+        storage.put("/model/m2/state", "Running").await.unwrap();
+        storage.put("/model/m3/state", "Unknown").await.unwrap();
+        storage.put("/model/m4/node", "Failed").await.unwrap();
+        storage
+            .put("/package/p1/state", "PACKAGE_STATE_FAILED")
+            .await
+            .unwrap();
+
+        assert_eq!(migrate_states(&storage).await.unwrap(), 2);
+        assert_eq!(storage.get("/model/m1/state").await.unwrap(), "Exited");
+        assert_eq!(storage.get("/model/m2/state").await.unwrap(), "Running");
+        assert_eq!(storage.get("/model/m3/state").await.unwrap(), "Unknown");
+        assert_eq!(storage.get("/model/m4/node").await.unwrap(), "Failed");
+        assert_eq!(
+            storage.get("/package/p1/state").await.unwrap(),
+            "PACKAGE_STATE_ERROR"
+        );
+        assert_eq!(migrate_states(&storage).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_reads_its_own_writes() {
+        let mut transaction = Transaction::default();
+        transaction.put("/model/pending/state", "Running");
+        assert_eq!(
+            transaction.get("/model/pending/state").await.unwrap(),
+            "Running"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_backend_is_etcd() {
+        assert_eq!(storage().name(), "etcd");
+    }
+}
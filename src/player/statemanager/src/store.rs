@@ -0,0 +1,397 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! State writes of the StateManager, buffered while etcd is unavailable
+//!
+//! Writes go straight to etcd while it answers. When no etcd endpoint can be
+//! reached the StateManager enters degraded mode: writes are queued in
+//! memory, in order and with the last write of a key replacing the earlier
+//! ones, and reads of a queued key are answered from the queue. A periodic
+//! [`flush`] writes the queue back once etcd recovers. Only the leader
+//! flushes, see [`crate::leader`]: an instance that stepped down keeps its
+//! queue until it is elected again instead of overwriting the states of the
+//! new leader.
+//!
+//! The queue holds at most `statemanager.write_buffer` keys; a write of a new
+//! key to a full queue fails like the etcd write it replaces.
+
+use common::logd;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Error of etcd for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+/// Longest key the RocksDB service accepts
+const MAX_KEY_LEN: usize = 1024;
+
+/// Buffered write of one key
+#[derive(Debug, Clone, PartialEq)]
+enum Write {
+    Put(String),
+    Delete,
+}
+
+/// Queued write with its position in the order of writes
+#[derive(Debug)]
+struct Pending {
+    seq: u64,
+    key: String,
+    write: Write,
+}
+
+/// Counters of the write buffer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoreStats {
+    pub capacity: usize,
+    /// Writes waiting for etcd
+    pub pending: usize,
+    /// Writes queued since start
+    pub buffered: u64,
+    /// Queued writes written to etcd
+    pub flushed: u64,
+    /// Writes refused because the buffer was full
+    pub rejected: u64,
+    /// Queued writes etcd refused and which were discarded
+    pub discarded: u64,
+}
+
+/// Ordered writes waiting for etcd
+#[derive(Debug)]
+struct WriteBuffer {
+    capacity: usize,
+    next_seq: u64,
+    pending: VecDeque<Pending>,
+    stats: StoreStats,
+}
+
+impl WriteBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            next_seq: 0,
+            pending: VecDeque::new(),
+            stats: StoreStats {
+                capacity,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn is_degraded(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Queue a write, replacing a queued write of the same key
+    fn enqueue(&mut self, key: &str, write: Write) -> Result<(), String> {
+        let existing = self.pending.iter().position(|p| p.key == key);
+        if existing.is_none() && self.pending.len() >= self.capacity {
+            self.stats.rejected += 1;
+            return Err(format!(
+                "etcd is unavailable and the write buffer is full ({} keys)",
+                self.capacity
+            ));
+        }
+        if let Some(index) = existing {
+            self.pending.remove(index);
+        }
+        self.next_seq += 1;
+        self.pending.push_back(Pending {
+            seq: self.next_seq,
+            key: key.to_string(),
+            write,
+        });
+        self.stats.buffered += 1;
+        Ok(())
+    }
+
+    /// Queue writes together, or none of them if they do not all fit
+    fn enqueue_all(&mut self, writes: Vec<(String, Write)>) -> Result<(), String> {
+        let new_keys = writes
+            .iter()
+            .filter(|(key, _)| self.lookup(key).is_none())
+            .count();
+        if self.pending.len() + new_keys > self.capacity {
+            self.stats.rejected += writes.len() as u64;
+            return Err(format!(
+                "etcd is unavailable and the write buffer is full ({} keys)",
+                self.capacity
+            ));
+        }
+        for (key, write) in writes {
+            self.enqueue(&key, write)?;
+        }
+        Ok(())
+    }
+
+    /// Queued write of a key, if any
+    fn lookup(&self, key: &str) -> Option<&Write> {
+        self.pending.iter().find(|p| p.key == key).map(|p| &p.write)
+    }
+
+    /// Oldest queued write
+    fn front(&self) -> Option<(u64, String, Write)> {
+        self.pending
+            .front()
+            .map(|p| (p.seq, p.key.clone(), p.write.clone()))
+    }
+
+    /// Remove a write once handled, unless a newer write replaced it meanwhile
+    fn complete(&mut self, seq: u64, written: bool) {
+        if let Some(index) = self.pending.iter().position(|p| p.seq == seq) {
+            self.pending.remove(index);
+            if written {
+                self.stats.flushed += 1;
+            } else {
+                self.stats.discarded += 1;
+            }
+        }
+    }
+
+    fn stats(&self) -> StoreStats {
+        StoreStats {
+            pending: self.pending.len(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+fn buffer() -> &'static Mutex<WriteBuffer> {
+    static BUFFER: OnceLock<Mutex<WriteBuffer>> = OnceLock::new();
+    BUFFER.get_or_init(|| {
+        let capacity = common::setting::get_config().statemanager.write_buffer;
+        Mutex::new(WriteBuffer::new(capacity))
+    })
+}
+
+fn with_buffer<R>(f: impl FnOnce(&mut WriteBuffer) -> R) -> R {
+    let mut buffer = buffer().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut buffer)
+}
+
+/// Refuse keys the RocksDB service would refuse, so that a buffered write
+/// fails now rather than when it is flushed
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        Err("Key cannot be empty".to_string())
+    } else if key.len() > MAX_KEY_LEN {
+        Err(format!(
+            "Key exceeds maximum allowed length of {} characters",
+            MAX_KEY_LEN
+        ))
+    } else if key.contains(['<', '>', '?', '{', '}']) {
+        Err("Key contains invalid special characters".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Queue a write that etcd could not take
+fn defer(key: &str, write: Write, error: &str) -> Result<(), String> {
+    validate_key(key)?;
+    with_buffer(|b| {
+        let entering = !b.is_degraded();
+        b.enqueue(key, write)?;
+        if entering {
+            logd!(
+                4,
+                "[Store] etcd unavailable, buffering state writes: {}",
+                error
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Store a value, queued while etcd is unavailable
+pub async fn put(key: &str, value: &str) -> Result<(), String> {
+    // Keep the order of writes while older ones wait for etcd
+    if with_buffer(|b| b.is_degraded()) {
+        validate_key(key)?;
+        return with_buffer(|b| b.enqueue(key, Write::Put(value.to_string())));
+    }
+    match common::etcd::put(key, value).await {
+        Err(e) if common::etcd::is_unavailable(&e) => defer(key, Write::Put(value.to_string()), &e),
+        result => result,
+    }
+}
+
+/// Delete a key, queued while etcd is unavailable
+pub async fn delete(key: &str) -> Result<(), String> {
+    if with_buffer(|b| b.is_degraded()) {
+        validate_key(key)?;
+        return with_buffer(|b| b.enqueue(key, Write::Delete));
+    }
+    match common::etcd::delete(key).await {
+        Err(e) if common::etcd::is_unavailable(&e) => defer(key, Write::Delete, &e),
+        result => result,
+    }
+}
+
+/// Store values together with one batch, queued while etcd is unavailable
+///
+/// The batch is atomic while etcd answers. Queued writes are flushed one by
+/// one once it recovers.
+pub async fn put_all(items: Vec<(String, String)>) -> Result<(), String> {
+    for (key, _) in &items {
+        validate_key(key)?;
+    }
+    let deferred = |items: &[(String, String)]| -> Vec<(String, Write)> {
+        items
+            .iter()
+            .map(|(key, value)| (key.clone(), Write::Put(value.clone())))
+            .collect()
+    };
+    if with_buffer(|b| b.is_degraded()) {
+        return with_buffer(|b| b.enqueue_all(deferred(&items)));
+    }
+    match common::etcd::batch_put(items.clone()).await {
+        Err(e) if common::etcd::is_unavailable(&e) => {
+            let entering = with_buffer(|b| !b.is_degraded());
+            with_buffer(|b| b.enqueue_all(deferred(&items)))?;
+            if entering {
+                logd!(4, "[Store] etcd unavailable, buffering state writes: {}", e);
+            }
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Read a value, seeing writes that still wait for etcd
+pub async fn get(key: &str) -> Result<String, String> {
+    match with_buffer(|b| b.lookup(key).cloned()) {
+        Some(Write::Put(value)) => Ok(value),
+        Some(Write::Delete) => Err(NOT_FOUND.to_string()),
+        None => common::etcd::get(key).await,
+    }
+}
+
+/// Write queued writes to etcd in order, until etcd fails again
+///
+/// Returns the number of writes flushed, none unless this instance is the
+/// leader.
+pub async fn flush() -> usize {
+    if !crate::leader::is_leader() {
+        return 0;
+    }
+    let mut flushed = 0;
+    while let Some((seq, key, write)) = with_buffer(|b| b.front()) {
+        let result = match &write {
+            Write::Put(value) => common::etcd::put(&key, value).await,
+            Write::Delete => common::etcd::delete(&key).await,
+        };
+        match result {
+            Ok(()) => {
+                with_buffer(|b| b.complete(seq, true));
+                flushed += 1;
+            }
+            Err(e) if common::etcd::is_unavailable(&e) => break,
+            Err(e) => {
+                logd!(5, "[Store] Discarding buffered write of '{}': {}", key, e);
+                with_buffer(|b| b.complete(seq, false));
+            }
+        }
+    }
+    if flushed > 0 && !with_buffer(|b| b.is_degraded()) {
+        logd!(
+            3,
+            "[Store] etcd recovered, flushed {} buffered writes",
+            flushed
+        );
+    }
+    flushed
+}
+
+/// Flush the buffer every `statemanager.write_flush_interval_ms` while
+/// this instance is the leader
+pub async fn run_flusher() {
+    loop {
+        crate::leader::elected().await;
+        let interval_ms = common::setting::get_config()
+            .statemanager
+            .write_flush_interval_ms
+            .max(1);
+        tokio::time::sleep(Duration::from_millis(interval_ms)).await;
+        if with_buffer(|b| b.is_degraded()) {
+            flush().await;
+        }
+    }
+}
+
+/// Counters of the write buffer
+pub fn stats() -> StoreStats {
+    with_buffer(|b| b.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_write_of_a_key_wins_and_moves_to_the_back() {
+        let mut buffer = WriteBuffer::new(10);
+        buffer.enqueue("a", Write::Put("1".into())).unwrap();
+        buffer.enqueue("b", Write::Put("1".into())).unwrap();
+        buffer.enqueue("a", Write::Delete).unwrap();
+
+        assert_eq!(buffer.lookup("a"), Some(&Write::Delete));
+        assert_eq!(buffer.lookup("c"), None);
+        let (_, key, _) = buffer.front().unwrap();
+        assert_eq!(key, "b");
+        assert_eq!(buffer.stats().pending, 2);
+    }
+
+    #[test]
+    fn test_full_buffer_rejects_new_keys_only() {
+        let mut buffer = WriteBuffer::new(1);
+        buffer.enqueue("a", Write::Put("1".into())).unwrap();
+        assert!(buffer.enqueue("b", Write::Put("1".into())).is_err());
+        assert!(buffer.enqueue("a", Write::Put("2".into())).is_ok());
+        assert_eq!(buffer.lookup("a"), Some(&Write::Put("2".into())));
+        assert_eq!(buffer.stats().rejected, 1);
+    }
+
+    #[test]
+    fn test_enqueue_all_is_all_or_nothing() {
+        let mut buffer = WriteBuffer::new(2);
+        buffer.enqueue("a", Write::Put("1".into())).unwrap();
+        let writes = |keys: &[&str]| {
+            keys.iter()
+                .map(|key| (key.to_string(), Write::Put("2".into())))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(buffer.enqueue_all(writes(&["b", "c"])).is_err());
+        assert_eq!(buffer.lookup("b"), None);
+        assert!(buffer.enqueue_all(writes(&["a", "b"])).is_ok());
+        assert_eq!(buffer.lookup("a"), Some(&Write::Put("2".into())));
+        assert_eq!(buffer.stats().pending, 2);
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("/model/m1/state").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key(&"a".repeat(MAX_KEY_LEN + 1)).is_err());
+        assert!(validate_key("/model/{m1}/state").is_err());
+    }
+
+    #[test]
+    fn test_complete_skips_replaced_writes() {
+        let mut buffer = WriteBuffer::new(10);
+        buffer.enqueue("a", Write::Put("1".into())).unwrap();
+        let (seq, _, _) = buffer.front().unwrap();
+        // Written again while the flush of the first value was in flight
+        buffer.enqueue("a", Write::Put("2".into())).unwrap();
+
+        buffer.complete(seq, true);
+        assert_eq!(buffer.lookup("a"), Some(&Write::Put("2".into())));
+        let (seq, _, _) = buffer.front().unwrap();
+        buffer.complete(seq, true);
+        assert!(!buffer.is_degraded());
+        assert_eq!(buffer.stats().flushed, 1);
+    }
+}
@@ -0,0 +1,305 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Timing budgets of state transitions
+//!
+//! The time from the receipt of a StateChange by the gRPC server to the
+//! persistence of the new state is measured for every transition and checked
+//! against `statemanager.timing_budgets_ms`, keyed by resource type then ASIL
+//! level. The level of a resource is the `io.piccolo.annotations.asil-level`
+//! annotation of the scenario owning it, QM when the scenario has none.
+//!
+//! A transition over its budget raises an alert and is counted per resource
+//! type and level for safety audits. The level is only looked up when a
+//! transition takes longer than the strictest budget of its type.
+
+use common::logd;
+use common::spec::artifact::{Artifact, Package, Scenario};
+use common::statemanager::ResourceType;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Scenario annotation holding the required ASIL level
+pub const ASIL_LEVEL_ANNOTATION: &str = "io.piccolo.annotations.asil-level";
+
+/// Receipts kept for transitions that are not persisted yet
+const MAX_PENDING: usize = 10_000;
+
+/// Receipts older than this are assumed lost when the limit is reached
+const STALE: Duration = Duration::from_secs(600);
+
+/// Counters of the timing budget checks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimingStats {
+    /// Received transitions not persisted yet
+    pub pending: usize,
+    /// Transitions measured up to persistence
+    pub measured: u64,
+    /// Transitions over their budget
+    pub violations: u64,
+    /// Violations keyed by `type/level`, e.g. `package/d`
+    pub violations_by_level: BTreeMap<String, u64>,
+    /// Longest time to persistence seen, in milliseconds
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct Timing {
+    received: HashMap<String, Instant>,
+    stats: TimingStats,
+}
+
+fn timing() -> &'static Mutex<Timing> {
+    static TIMING: OnceLock<Mutex<Timing>> = OnceLock::new();
+    TIMING.get_or_init(|| Mutex::new(Timing::default()))
+}
+
+fn with_timing<R>(f: impl FnOnce(&mut Timing) -> R) -> R {
+    let mut timing = timing().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut timing)
+}
+
+/// Budget key of an ASIL level: `qm` or `a` to `d`
+///
+/// Accepts "QM" and "A" to "D", with an optional "ASIL-" or "ASIL_" prefix.
+pub fn level_key(level: &str) -> Option<&'static str> {
+    let upper = level.trim().to_uppercase();
+    let level = upper
+        .strip_prefix("ASIL-")
+        .or_else(|| upper.strip_prefix("ASIL_"))
+        .unwrap_or(&upper);
+    match level {
+        "QM" => Some("qm"),
+        "A" => Some("a"),
+        "B" => Some("b"),
+        "C" => Some("c"),
+        "D" => Some("d"),
+        _ => None,
+    }
+}
+
+/// Budget of a resource type at an ASIL level, if one is configured
+pub fn budget(
+    budgets: &HashMap<String, HashMap<String, u64>>,
+    resource_type: &str,
+    level: &str,
+) -> Option<Duration> {
+    budgets
+        .get(resource_type)?
+        .get(level)
+        .map(|ms| Duration::from_millis(*ms))
+}
+
+/// Smallest budget of a resource type, whatever the level
+fn strictest(
+    budgets: &HashMap<String, HashMap<String, u64>>,
+    resource_type: &str,
+) -> Option<Duration> {
+    budgets
+        .get(resource_type)?
+        .values()
+        .min()
+        .map(|ms| Duration::from_millis(*ms))
+}
+
+/// Note the receipt of a StateChange queued for processing
+pub fn received(transition_id: &str) {
+    with_timing(|timing| {
+        if timing.received.len() >= MAX_PENDING {
+            let now = common::clock::instant();
+            timing
+                .received
+                .retain(|_, received| now.saturating_duration_since(*received) < STALE);
+        }
+        if timing.received.len() < MAX_PENDING {
+            timing
+                .received
+                .insert(transition_id.to_string(), common::clock::instant());
+        }
+    });
+}
+
+/// Forget a StateChange that will not be persisted
+pub fn discard(transition_id: &str) {
+    with_timing(|timing| timing.received.remove(transition_id));
+}
+
+/// Check the time to persistence of a transition against its budget
+///
+/// Transitions that were not received through the gRPC server, such as
+/// the ones raised by the StateManager itself, are not measured.
+pub async fn persisted(resource_type: ResourceType, resource_name: &str, transition_id: &str) {
+    let Some(elapsed) = with_timing(|timing| {
+        let elapsed = common::clock::instant()
+            .saturating_duration_since(timing.received.remove(transition_id)?);
+        timing.stats.measured += 1;
+        timing.stats.max_ms = timing.stats.max_ms.max(elapsed.as_millis() as u64);
+        Some(elapsed)
+    }) else {
+        return;
+    };
+
+    let budgets = &common::setting::get_config().statemanager.timing_budgets_ms;
+    let type_name = crate::notifier::type_name(resource_type);
+    if strictest(budgets, type_name).is_none_or(|strictest| elapsed <= strictest) {
+        return;
+    }
+    let level = asil_level(resource_type, resource_name).await;
+    let Some(budget) = budget(budgets, type_name, level) else {
+        return;
+    };
+    if elapsed <= budget {
+        return;
+    }
+
+    logd!(
+        5,
+        "ALERT: {:?} '{}' transition {} took {}ms to persist, over its {} budget of {}ms",
+        resource_type,
+        resource_name,
+        transition_id,
+        elapsed.as_millis(),
+        level.to_uppercase(),
+        budget.as_millis()
+    );
+    crate::events::alert(
+        resource_type,
+        resource_name,
+        format!(
+            "transition {} took {}ms, over the {} budget of {}ms",
+            transition_id,
+            elapsed.as_millis(),
+            level.to_uppercase(),
+            budget.as_millis()
+        ),
+    );
+    with_timing(|timing| {
+        timing.stats.violations += 1;
+        *timing
+            .stats
+            .violations_by_level
+            .entry(format!("{}/{}", type_name, level))
+            .or_default() += 1;
+    });
+}
+
+/// ASIL level of the scenario owning a resource, QM if unknown
+async fn asil_level(resource_type: ResourceType, resource_name: &str) -> &'static str {
+    let storage = crate::storage::storage();
+    let scenarios: Vec<Scenario> = match storage.get_all_with_prefix("Scenario/").await {
+        Ok(entries) => entries
+            .iter()
+            .filter_map(|(_, yaml)| serde_yaml::from_str(yaml).ok())
+            .collect(),
+        Err(e) => {
+            logd!(4, "Failed to read scenarios for an ASIL level: {}", e);
+            return "qm";
+        }
+    };
+    let packages: Vec<String> = match resource_type {
+        ResourceType::Scenario => {
+            return scenarios
+                .iter()
+                .find(|scenario| scenario.get_qualified_name() == resource_name)
+                .map_or("qm", scenario_level)
+        }
+        ResourceType::Package => vec![resource_name.to_string()],
+        ResourceType::Model => match storage.get_all_with_prefix("Package/").await {
+            Ok(entries) => entries
+                .iter()
+                .filter_map(|(_, yaml)| serde_yaml::from_str::<Package>(yaml).ok())
+                .filter(|package| {
+                    package
+                        .get_models()
+                        .iter()
+                        .any(|model| model.get_name() == resource_name)
+                })
+                .map(|package| package.get_qualified_name())
+                .collect(),
+            Err(e) => {
+                logd!(4, "Failed to read packages for an ASIL level: {}", e);
+                return "qm";
+            }
+        },
+        _ => return "qm",
+    };
+    // A resource shared by several scenarios gets the strictest level
+    scenarios
+        .iter()
+        .filter(|scenario| packages.contains(&scenario.get_targets()))
+        .map(scenario_level)
+        .max_by_key(|level| ["qm", "a", "b", "c", "d"].iter().position(|l| l == level))
+        .unwrap_or("qm")
+}
+
+fn scenario_level(scenario: &Scenario) -> &'static str {
+    scenario
+        .get_annotation(ASIL_LEVEL_ANNOTATION)
+        .and_then(|level| level_key(&level))
+        .unwrap_or("qm")
+}
+
+/// Counters of the timing budget checks
+pub fn stats() -> TimingStats {
+    with_timing(|timing| TimingStats {
+        pending: timing.received.len(),
+        ..timing.stats.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_key() {
+        assert_eq!(level_key("QM"), Some("qm"));
+        assert_eq!(level_key("ASIL-D"), Some("d"));
+        assert_eq!(level_key("asil_b"), Some("b"));
+        assert_eq!(level_key(" c "), Some("c"));
+        assert_eq!(level_key("E"), None);
+    }
+
+    #[test]
+    fn test_budget_lookup() {
+        let budgets = HashMap::from([(
+            "package".to_string(),
+            HashMap::from([("qm".to_string(), 1000), ("d".to_string(), 50)]),
+        )]);
+        assert_eq!(
+            budget(&budgets, "package", "d"),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(budget(&budgets, "package", "b"), None);
+        assert_eq!(budget(&budgets, "model", "qm"), None);
+        assert_eq!(
+            strictest(&budgets, "package"),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(strictest(&budgets, "model"), None);
+    }
+
+    #[tokio::test]
+    async fn test_fast_transition_is_measured_without_violation() {
+        common::etcd::use_in_memory_store();
+        received("timing-test-fast");
+        let before = stats();
+        persisted(ResourceType::Scenario, "timing-test", "timing-test-fast").await;
+        assert!(stats().measured > before.measured);
+        assert!(!with_timing(|timing| timing
+            .received
+            .contains_key("timing-test-fast")));
+    }
+
+    #[test]
+    fn test_discard_forgets_receipt() {
+        received("timing-test-discard");
+        discard("timing-test-discard");
+        assert!(!with_timing(|timing| timing
+            .received
+            .contains_key("timing-test-discard")));
+    }
+}
@@ -0,0 +1,311 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Transition tables loaded at startup over the built-in ones
+//!
+//! The tables are read from the `statemanager.transition_table_key` etcd
+//! key, or when it is not set from the `statemanager.transition_table_path`
+//! file, in YAML, TOML or JSON by its extension. They are keyed by resource type:
+//!
+//! ```yaml
+//! scenario:
+//!   transitions:
+//!     - from: satisfied
+//!       to: allowed
+//!       action: execute_action_on_target_package
+//!       condition: sufficient_resources
+//! package:
+//!   replace: true
+//!   transitions: [...]
+//! ```
+//!
+//! A transition overrides the built-in one with the same states and event,
+//! or is added to the table. With `replace` the table of the type is made
+//! of the listed transitions only. The event defaults to the one the
+//! StateManager raises between the two states.
+//!
+//! `from: "*"` matches any state and `event: "*"` any event, a transition
+//! from any state without an event being taken on any event. A transition
+//! naming the state is preferred over one from any state, and one naming
+//! the event over one on any event:
+//!
+//! ```yaml
+//! package:
+//!   transitions:
+//!     - {from: "*", to: error, event: "*", action: log_failure}
+//!     - {from: running, to: error, action: alert_failure}
+//! ```
+//!
+//! Types that are not listed keep their built-in table, and a source with an
+//! unknown type or state is rejected as a whole.
+
+use crate::types::{StateTransition, ANY_STATE};
+use common::logd;
+use common::state_mapping;
+use common::statemanager::ResourceType;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Table of a resource type as written in the source
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TableConfig {
+    /// Drop the built-in transitions of the type
+    pub replace: bool,
+    pub transitions: Vec<TransitionConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransitionConfig {
+    pub from: String,
+    pub to: String,
+    pub event: Option<String>,
+    pub condition: Option<String>,
+    pub action: String,
+}
+
+/// Transition of a loaded table, see [`TransitionConfig`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableTransition {
+    pub from_state: i32,
+    pub to_state: i32,
+    /// `None` for the event raised between the states
+    pub event: Option<String>,
+    pub condition: Option<String>,
+    pub action: String,
+}
+
+impl TableTransition {
+    /// Rule of the state machine, raised by `event`
+    pub fn with_event(self, event: String) -> StateTransition {
+        StateTransition {
+            from_state: self.from_state,
+            event,
+            to_state: self.to_state,
+            condition: self.condition,
+            action: self.action,
+        }
+    }
+}
+
+/// Validated table of a resource type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub replace: bool,
+    pub transitions: Vec<TableTransition>,
+}
+
+/// Resource type of a name used in the settings, e.g. `scenario`
+pub fn parse_resource_type(name: &str) -> Option<ResourceType> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "scenario" => Some(ResourceType::Scenario),
+        "package" => Some(ResourceType::Package),
+        "model" => Some(ResourceType::Model),
+        "network" => Some(ResourceType::Network),
+        "node" => Some(ResourceType::Node),
+        _ => None,
+    }
+}
+
+/// Check the tables of a source against the known types and states
+pub fn validate(
+    tables: HashMap<String, TableConfig>,
+) -> Result<HashMap<ResourceType, Table>, String> {
+    let mut validated = HashMap::new();
+    for (type_name, table) in tables {
+        let resource_type = parse_resource_type(&type_name)
+            .ok_or_else(|| format!("unknown resource type '{}'", type_name))?;
+        let state = |name: &str| {
+            state_mapping::parse_state(resource_type, name)
+                .ok_or_else(|| format!("unknown {} state '{}'", type_name, name))
+        };
+        let mut transitions = Vec::new();
+        for transition in table.transitions {
+            if transition.action.trim().is_empty() {
+                return Err(format!(
+                    "{} transition {} -> {} has no action",
+                    type_name, transition.from, transition.to
+                ));
+            }
+            let from_state = if transition.from.trim() == "*" {
+                ANY_STATE
+            } else {
+                state(&transition.from)?
+            };
+            transitions.push(TableTransition {
+                from_state,
+                to_state: state(&transition.to)?,
+                event: transition.event.filter(|event| !event.trim().is_empty()),
+                condition: transition.condition,
+                action: transition.action,
+            });
+        }
+        validated.insert(
+            resource_type,
+            Table {
+                replace: table.replace,
+                transitions,
+            },
+        );
+    }
+    Ok(validated)
+}
+
+/// Tables of a source, in the format of its extension, YAML by default
+pub fn parse(
+    content: &str,
+    format: config::FileFormat,
+) -> Result<HashMap<ResourceType, Table>, String> {
+    let tables = config::Config::builder()
+        .add_source(config::File::from_str(content, format))
+        .build()
+        .and_then(|source| source.try_deserialize::<HashMap<String, TableConfig>>())
+        .map_err(|e| e.to_string())?;
+    validate(tables)
+}
+
+fn format_of(path: &str) -> config::FileFormat {
+    match std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("toml") => config::FileFormat::Toml,
+        Some("json") => config::FileFormat::Json,
+        _ => config::FileFormat::Yaml,
+    }
+}
+
+/// Tables overriding the built-in ones, `None` when none are configured or
+/// the configured ones are invalid
+pub async fn load() -> Option<HashMap<ResourceType, Table>> {
+    let settings = &common::setting::get_config().statemanager;
+    let (origin, content, format) = match crate::storage::storage()
+        .get(&settings.transition_table_key)
+        .await
+    {
+        Ok(content) => (
+            settings.transition_table_key.clone(),
+            content,
+            config::FileFormat::Yaml,
+        ),
+        Err(_) => match std::fs::read_to_string(&settings.transition_table_path) {
+            Ok(content) => (
+                settings.transition_table_path.clone(),
+                content,
+                format_of(&settings.transition_table_path),
+            ),
+            Err(_) => return None,
+        },
+    };
+    match parse(&content, format) {
+        Ok(tables) => {
+            logd!(3, "Transition tables loaded from {}", origin);
+            Some(tables)
+        }
+        Err(e) => {
+            logd!(
+                5,
+                "Ignoring transition tables of {}, using the built-in ones: {}",
+                origin,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ANY_EVENT;
+    use common::statemanager::{PackageState, ScenarioState};
+
+    #[test]
+    fn test_parse_yaml_and_toml() {
+        let yaml = r#"
+scenario:
+  transitions:
+    - from: satisfied
+      to: allowed
+      action: execute_action_on_target_package
+      condition: sufficient_resources
+package:
+  replace: true
+  transitions:
+    - from: idle
+      to: running
+      event: launch
+      action: start_models
+"#;
+        let tables = parse(yaml, config::FileFormat::Yaml).unwrap();
+        let scenario = &tables[&ResourceType::Scenario];
+        assert!(!scenario.replace);
+        assert_eq!(
+            scenario.transitions,
+            vec![TableTransition {
+                from_state: ScenarioState::Satisfied as i32,
+                to_state: ScenarioState::Allowed as i32,
+                event: None,
+                condition: Some("sufficient_resources".to_string()),
+                action: "execute_action_on_target_package".to_string(),
+            }]
+        );
+        let package = &tables[&ResourceType::Package];
+        assert!(package.replace);
+        assert_eq!(package.transitions[0].from_state, PackageState::Idle as i32);
+        assert_eq!(package.transitions[0].event.as_deref(), Some("launch"));
+
+        let toml = r#"
+[[node.transitions]]
+from = "schedulable"
+to = "cordoned"
+action = "stop_scheduling"
+"#;
+        let tables = parse(toml, format_of("/etc/piccolo/transitions.toml")).unwrap();
+        assert_eq!(tables[&ResourceType::Node].transitions.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_wildcards() {
+        let yaml = r#"
+package:
+  transitions:
+    - {from: "*", to: error, event: "*", action: log_failure}
+    - {from: "*", to: updating, event: update_started, action: track}
+"#;
+        let tables = parse(yaml, config::FileFormat::Yaml).unwrap();
+        let transitions = &tables[&ResourceType::Package].transitions;
+        assert_eq!(transitions[0].from_state, ANY_STATE);
+        assert_eq!(transitions[0].event.as_deref(), Some(ANY_EVENT));
+        assert_eq!(transitions[1].from_state, ANY_STATE);
+        assert_eq!(transitions[1].event.as_deref(), Some("update_started"));
+        // A target state is always named
+        assert_eq!(
+            parse(
+                "package:\n  transitions:\n    - {from: idle, to: '*', action: go}\n",
+                config::FileFormat::Yaml
+            )
+            .unwrap_err(),
+            "unknown package state '*'"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_types_and_states() {
+        let parse_yaml = |yaml: &str| parse(yaml, config::FileFormat::Yaml).unwrap_err();
+        assert_eq!(
+            parse_yaml("vehicle:\n  transitions: []\n"),
+            "unknown resource type 'vehicle'"
+        );
+        assert_eq!(
+            parse_yaml("scenario:\n  transitions:\n    - {from: idle, to: launched, action: go}\n"),
+            "unknown scenario state 'launched'"
+        );
+        assert!(parse_yaml(
+            "scenario:\n  transitions:\n    - {from: idle, to: waiting, action: ''}\n"
+        )
+        .contains("has no action"));
+    }
+}
@@ -2,9 +2,9 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
-use common::statemanager::{ErrorCode, ResourceType};
+use common::statemanager::{ErrorCode, ResourceType, SimulatedTransition, StateChange};
 use std::collections::HashMap;
-use tokio::time::Instant;
+use std::time::Duration;
 // ========================================
 // CORE DATA STRUCTURES
 // ========================================
@@ -19,7 +19,16 @@ pub struct ActionCommand {
     pub context: HashMap<String, String>,
 }
 
+/// `from_state` of a transition taken from any state but its `to_state`
+pub const ANY_STATE: i32 = -1;
+
+/// `event` of a transition taken on any event
+pub const ANY_EVENT: &str = "*";
+
 /// Represents a state transition in the state machine
+///
+/// `from_state` may be [`ANY_STATE`] and `event` [`ANY_EVENT`], for a rule
+/// that holds for every state or event.
 #[derive(Debug, Clone, PartialEq)]
 pub struct StateTransition {
     pub from_state: i32,
@@ -29,12 +38,48 @@ pub struct StateTransition {
     pub action: String,
 }
 
+impl StateTransition {
+    /// How specifically the transition matches a move, `None` if it does not
+    ///
+    /// A transition naming the state and the event matches most
+    /// specifically, then one naming the state on any event, then one from
+    /// any state naming the event, then one from any state on any event.
+    /// A transition from any state does not match a move that stays in its
+    /// `to_state`.
+    pub fn specificity(&self, from_state: i32, event: &str, to_state: i32) -> Option<u8> {
+        if self.to_state != to_state {
+            return None;
+        }
+        let state = match self.from_state {
+            state if state == from_state => 2,
+            ANY_STATE if from_state != to_state => 0,
+            _ => return None,
+        };
+        let event = match self.event.as_str() {
+            named if named == event => 1,
+            ANY_EVENT => 0,
+            _ => return None,
+        };
+        Some(state + event)
+    }
+
+    /// The transition as taken from `from_state` on `event`
+    pub fn resolved(&self, from_state: i32, event: &str) -> StateTransition {
+        StateTransition {
+            from_state,
+            event: event.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
 /// Health status tracking for resources
 #[derive(Debug, Clone)]
 pub struct HealthStatus {
     pub healthy: bool,
     pub status_message: String,
-    pub last_check: Instant,
+    /// Nanoseconds since the Unix epoch, see [`common::clock`]
+    pub last_check_ns: i64,
     pub consecutive_failures: u32,
 }
 
@@ -45,12 +90,32 @@ pub struct ResourceState {
     pub resource_name: String,
     pub current_state: i32,
     pub desired_state: Option<i32>,
-    pub last_transition_time: Instant,
+    /// Nanoseconds since the Unix epoch, see [`common::clock`]
+    pub last_transition_ns: i64,
     pub transition_count: u64,
     pub metadata: HashMap<String, String>,
     pub health_status: HealthStatus,
 }
 
+/// A resource forced out of a state it stayed in for longer than its timeout
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeoutEvent {
+    pub resource_type: ResourceType,
+    pub resource_name: String,
+    pub from_state: i32,
+    pub to_state: i32,
+    /// Time spent in `from_state`
+    pub elapsed: Duration,
+    pub transition_id: String,
+}
+
+/// State changes to simulate, answered with the transitions they would make
+#[derive(Debug)]
+pub struct SimulationJob {
+    pub changes: Vec<StateChange>,
+    pub reply: tokio::sync::oneshot::Sender<Vec<SimulatedTransition>>,
+}
+
 /// Result of a state transition attempt - aligned with proto StateChangeResponse
 #[derive(Debug, Clone)]
 pub struct TransitionResult {
@@ -68,17 +133,54 @@ pub enum ContainerState {
     Created,
     Initialized,
     Running,
+    /// Running, but its readiness probe does not pass
+    NotReady,
+    /// Stopped after exceeding the restarts its restart policy allows
+    CrashLoopBackOff,
     Paused,
     Exited,
     Unknown,
     Dead,
+    /// Dead, or exited to be restarted, but failed transiently and within its
+    /// grace window
+    Recovering,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashMap;
-    use tokio::time::Instant;
+
+    #[test]
+    fn test_wildcard_transitions_match_less_specifically() {
+        let transition = |from_state: i32, event: &str| StateTransition {
+            from_state,
+            event: event.to_string(),
+            to_state: 5,
+            condition: None,
+            action: "act".to_string(),
+        };
+        let specificity =
+            |from_state, event| transition(from_state, event).specificity(1, "fail", 5);
+        assert_eq!(specificity(1, "fail"), Some(3));
+        assert_eq!(specificity(1, ANY_EVENT), Some(2));
+        assert_eq!(specificity(ANY_STATE, "fail"), Some(1));
+        assert_eq!(specificity(ANY_STATE, ANY_EVENT), Some(0));
+        assert_eq!(specificity(2, ANY_EVENT), None);
+        assert_eq!(specificity(ANY_STATE, "retry"), None);
+        assert_eq!(
+            transition(ANY_STATE, ANY_EVENT).specificity(1, "fail", 4),
+            None
+        );
+        assert_eq!(
+            transition(ANY_STATE, "fail").specificity(5, "fail", 5),
+            None
+        );
+        assert_eq!(transition(5, "fail").specificity(5, "fail", 5), Some(3));
+
+        let resolved = transition(ANY_STATE, ANY_EVENT).resolved(1, "fail");
+        assert_eq!(resolved, transition(1, "fail"));
+    }
 
     #[test]
     fn test_state_transition_equality() {
@@ -143,11 +245,11 @@ mod tests {
     fn test_resource_state_construction() {
         use common::statemanager::ScenarioState;
 
-        let now = Instant::now();
+        let now = common::clock::now_ns();
         let hs = HealthStatus {
             healthy: true,
             status_message: "ok".to_string(),
-            last_check: now,
+            last_check_ns: now,
             consecutive_failures: 0,
         };
 
@@ -156,7 +258,7 @@ mod tests {
             resource_name: "rname".to_string(),
             current_state: ScenarioState::Idle as i32,
             desired_state: Some(ScenarioState::Waiting as i32),
-            last_transition_time: now,
+            last_transition_ns: now,
             transition_count: 0,
             metadata: HashMap::new(),
             health_status: hs.clone(),
@@ -0,0 +1,143 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Vehicle operational mode state
+//!
+//! The current mode (driving, parked, charging) is reported by a mode source
+//! such as FilterGateway forwarding a DDS topic, and read by ActionController
+//! and PolicyManager before running mode restricted scenarios. The last known
+//! mode is persisted in etcd so that it survives a StateManager restart.
+
+use common::logd;
+use common::statemanager::VehicleMode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// etcd key holding the last reported vehicle mode
+pub const VEHICLE_MODE_KEY: &str = "/vehicle/mode";
+
+/// Snapshot of the vehicle mode and who reported it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VehicleModeState {
+    /// Lowercase mode name, empty while the mode is unknown
+    pub mode: String,
+    pub source: String,
+    pub updated_ns: i64,
+}
+
+impl VehicleModeState {
+    pub fn vehicle_mode(&self) -> VehicleMode {
+        VehicleMode::from_name(&self.mode).unwrap_or(VehicleMode::Unspecified)
+    }
+}
+
+/// Shared store of the current vehicle mode
+#[derive(Clone, Default)]
+pub struct VehicleModeStore {
+    state: Arc<RwLock<VehicleModeState>>,
+}
+
+impl VehicleModeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the last persisted mode from etcd, keeping the mode unknown if none exists
+    pub async fn restore(&self) {
+        let Ok(value) = crate::storage::storage().get(VEHICLE_MODE_KEY).await else {
+            return;
+        };
+        match serde_yaml::from_str::<VehicleModeState>(&value) {
+            Ok(state) => {
+                logd!(3, "Restored vehicle mode '{}' from etcd", state.mode);
+                *self.state.write().await = state;
+            }
+            Err(e) => logd!(4, "Ignoring invalid persisted vehicle mode: {}", e),
+        }
+    }
+
+    pub async fn get(&self) -> VehicleModeState {
+        self.state.read().await.clone()
+    }
+
+    /// Record a new mode reported by `source` and persist it
+    ///
+    /// ### Returns
+    /// * `Err(String)` - the mode is unspecified or the source is empty
+    pub async fn set(&self, mode: VehicleMode, source: &str) -> Result<VehicleModeState, String> {
+        if mode == VehicleMode::Unspecified {
+            return Err("vehicle mode must be specified".to_string());
+        }
+        if source.trim().is_empty() {
+            return Err("source cannot be empty".to_string());
+        }
+
+        let state = VehicleModeState {
+            mode: mode.name().to_string(),
+            source: source.to_string(),
+            updated_ns: common::clock::now_ns(),
+        };
+        {
+            let mut current = self.state.write().await;
+            if current.mode != state.mode {
+                logd!(
+                    3,
+                    "Vehicle mode changed: '{}' -> '{}' (source: {})",
+                    current.mode,
+                    state.mode,
+                    source
+                );
+            }
+            *current = state.clone();
+        }
+
+        // The in-memory mode stays authoritative if etcd is unavailable
+        match serde_yaml::to_string(&state) {
+            Ok(value) => {
+                if let Err(e) = crate::storage::storage()
+                    .put(VEHICLE_MODE_KEY, &value)
+                    .await
+                {
+                    logd!(4, "Failed to persist vehicle mode: {}", e);
+                }
+            }
+            Err(e) => logd!(4, "Failed to serialize vehicle mode: {}", e),
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_and_get_mode() {
+        common::etcd::use_in_memory_store();
+        let store = VehicleModeStore::new();
+        assert_eq!(store.get().await.vehicle_mode(), VehicleMode::Unspecified);
+
+        let state = store.set(VehicleMode::Parked, "unittest").await.unwrap();
+        assert_eq!(state.mode, "parked");
+        assert!(state.updated_ns > 0);
+
+        let current = store.get().await;
+        assert_eq!(current.vehicle_mode(), VehicleMode::Parked);
+        assert_eq!(current.source, "unittest");
+    }
+
+    #[tokio::test]
+    async fn test_set_rejects_invalid_input() {
+        common::etcd::use_in_memory_store();
+        let store = VehicleModeStore::new();
+        assert!(store
+            .set(VehicleMode::Unspecified, "unittest")
+            .await
+            .is_err());
+        assert!(store.set(VehicleMode::Driving, " ").await.is_err());
+        assert_eq!(store.get().await, VehicleModeState::default());
+    }
+}
@@ -0,0 +1,527 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Apply many artifact files in one request
+//!
+//! A bulk request is a tarball (optionally gzip compressed) of YAML files, or
+//! a JSON object mapping file names to YAML content. All files are parsed and
+//! their cross-references checked before anything is stored: a Scenario must
+//! target a Package, and a Package must use Models, Volumes and Networks that
+//! are part of the request or already stored. Files are then applied in
+//! dependency order, resources first, then Packages, then Scenarios, and
+//! the result is reported per file.
+//!
+//! A compressed tarball is decompressed up to `artifact.max_body_bytes`, the
+//! limit of an uncompressed body, so that a small body cannot expand into an
+//! unbounded one. The documents of all files together are limited by
+//! `artifact.max_documents`, as are the documents of a single artifact body.
+
+use super::{
+    parse_artifact_info, process_artifact_document, save_pod_yaml_from_package,
+    validate_artifact_documents, KIND_MODEL, KIND_NETWORK, KIND_PACKAGE, KIND_SCENARIO,
+    KIND_VOLUME,
+};
+use common::spec::artifact::{Package, Scenario};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Offset and value of the ustar magic in a tar header
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// One manifest file of a bulk request
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestFile {
+    pub name: String,
+    pub content: String,
+}
+
+/// Outcome of one file of a bulk request
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FileResult {
+    pub file: String,
+    /// Applied artifacts as `Kind/name`
+    pub applied: Vec<String>,
+    /// Why the file was not, or only partially, applied
+    pub error: Option<String>,
+    /// Documents of the file not matching the schema of their kind
+    pub violations: Vec<super::schema::Violation>,
+}
+
+/// Artifact document found in a file
+struct Manifest {
+    file: usize,
+    kind: String,
+    name: String,
+    doc: String,
+    references: Vec<String>,
+}
+
+impl Manifest {
+    fn key(&self) -> String {
+        format!("{}/{}", self.kind, self.name)
+    }
+}
+
+/// Split a bulk request body into its manifest files
+///
+/// ### Parameters
+/// * `body: &[u8]` - tar, tar.gz, or JSON object of file name to content
+/// ### Returns
+/// * `Vec<ManifestFile>` - `.yaml`/`.yml` files sorted by name, an error
+///   if they have more artifact documents together than allowed
+pub fn unpack(body: &[u8]) -> common::Result<Vec<ManifestFile>> {
+    let files = if body.starts_with(&GZIP_MAGIC) {
+        let limit = common::setting::get_config().artifact.max_body_bytes;
+        read_tar(decompress(body, limit)?.as_slice())?
+    } else if body.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        read_tar(body)?
+    } else {
+        let files: BTreeMap<String, String> = serde_json::from_slice(body)
+            .map_err(|e| format!("Bulk body is neither a tarball nor a JSON file map: {}", e))?;
+        files
+            .into_iter()
+            .map(|(name, content)| ManifestFile { name, content })
+            .collect()
+    };
+    super::limits::check_total(files.iter().map(|file| file.content.as_str()))?;
+    Ok(files)
+}
+
+/// Decompress a gzip body, an error once it exceeds `limit` bytes
+fn decompress(body: &[u8], limit: usize) -> common::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(body)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > limit {
+        return Err(common::error::Error::InvalidRequest(format!(
+            "Decompressed body exceeds the limit of {} bytes",
+            limit
+        )));
+    }
+    Ok(decompressed)
+}
+
+fn read_tar<R: Read>(reader: R) -> common::Result<Vec<ManifestFile>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        if !(name.ends_with(".yaml") || name.ends_with(".yml")) {
+            continue;
+        }
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .map_err(|e| format!("{} is not valid UTF-8 text: {}", name, e))?;
+        files.push(ManifestFile { name, content });
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+/// Position of an artifact kind in the apply order
+fn apply_rank(kind: &str) -> u8 {
+    match kind {
+        KIND_SCENARIO => 2,
+        KIND_PACKAGE => 1,
+        _ => 0,
+    }
+}
+
+/// Artifacts an artifact document refers to, as `Kind/name` with the name
+/// qualified with the namespace of the document
+pub(crate) fn references(kind: &str, value: &serde_yaml::Value) -> common::Result<Vec<String>> {
+    let mut refs = Vec::new();
+    match kind {
+        KIND_SCENARIO => {
+            let scenario: Scenario = serde_yaml::from_value(value.clone())?;
+            refs.push(format!("{}/{}", KIND_PACKAGE, scenario.get_targets()));
+        }
+        KIND_PACKAGE => {
+            let package: Package = serde_yaml::from_value(value.clone())?;
+            for model in package.get_models() {
+                refs.push(format!("{}/{}", KIND_MODEL, model.get_name()));
+                let resources = model.get_resources();
+                if let Some(volume) = resources.get_volume() {
+                    refs.push(format!("{}/{}", KIND_VOLUME, volume));
+                }
+                if let Some(network) = resources.get_network() {
+                    refs.push(format!("{}/{}", KIND_NETWORK, network));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(refs)
+}
+
+/// Parse the artifact documents of every file
+///
+/// Files that cannot be parsed get their error set and contribute no manifests.
+fn parse_files(files: &[ManifestFile], results: &mut [FileResult]) -> Vec<Manifest> {
+    let mut manifests = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let violations = super::schema::validate(&file.content);
+        if !violations.is_empty() {
+            results[index].error = Some(format!(
+                "{} document(s) do not match the schema of their kind",
+                violations.len()
+            ));
+            results[index].violations = violations;
+            continue;
+        }
+        match parse_file(index, &file.content) {
+            Ok(found) => manifests.extend(found),
+            Err(e) => results[index].error = Some(e.to_string()),
+        }
+    }
+    manifests
+}
+
+fn parse_file(index: usize, content: &str) -> common::Result<Vec<Manifest>> {
+    let docs = super::limits::split(content)?;
+    validate_artifact_documents(&docs)?;
+
+    let mut manifests = Vec::new();
+    for (position, doc) in docs.iter().enumerate() {
+        let value: serde_yaml::Value = serde_yaml::from_str(doc)?;
+        if value.is_null() {
+            continue;
+        }
+        let (kind, name) = parse_artifact_info(&value)
+            .ok_or_else(|| format!("Document {} is an unknown or invalid artifact", position))?;
+        let references = references(&kind, &value)?;
+        manifests.push(Manifest {
+            file: index,
+            kind,
+            name,
+            doc: doc.to_string(),
+            references,
+        });
+    }
+    Ok(manifests)
+}
+
+/// Fail files that define an artifact already defined by an earlier file
+fn check_duplicates(manifests: &[Manifest], files: &[ManifestFile], results: &mut [FileResult]) {
+    let mut defined: HashMap<String, usize> = HashMap::new();
+    for manifest in manifests {
+        match defined.get(&manifest.key()) {
+            Some(&first) if first != manifest.file => {
+                results[manifest.file].error = Some(format!(
+                    "{} is also defined in {}",
+                    manifest.key(),
+                    files[first].name
+                ));
+            }
+            _ => {
+                defined.insert(manifest.key(), manifest.file);
+            }
+        }
+    }
+}
+
+/// Fail files whose artifacts refer to something neither applied with them
+/// nor already stored
+///
+/// Repeats until stable, since a failed file also withdraws its artifacts
+/// from the ones other files may refer to.
+fn check_references(manifests: &[Manifest], stored: &HashSet<String>, results: &mut [FileResult]) {
+    loop {
+        let available: HashSet<String> = manifests
+            .iter()
+            .filter(|m| results[m.file].error.is_none())
+            .map(Manifest::key)
+            .collect();
+
+        let mut changed = false;
+        for manifest in manifests {
+            if results[manifest.file].error.is_some() {
+                continue;
+            }
+            if let Some(missing) = manifest
+                .references
+                .iter()
+                .find(|r| !available.contains(*r) && !stored.contains(*r))
+            {
+                results[manifest.file].error =
+                    Some(format!("{} refers to missing {}", manifest.key(), missing));
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+    }
+}
+
+/// Apply the manifest files of a bulk request
+///
+/// ### Returns
+/// * `Vec<FileResult>` - outcome of each file, in request order
+/// * `Vec<(usize, String)>` - stored scenarios with the index of their file,
+///   to be handed to the FilterGateway
+pub async fn apply_files(files: &[ManifestFile]) -> (Vec<FileResult>, Vec<(usize, String)>) {
+    let mut results: Vec<FileResult> = files
+        .iter()
+        .map(|file| FileResult {
+            file: file.name.clone(),
+            applied: Vec::new(),
+            error: None,
+            violations: Vec::new(),
+        })
+        .collect();
+
+    let mut manifests = parse_files(files, &mut results);
+    check_duplicates(&manifests, files, &mut results);
+
+    // References outside the request must already be stored
+    let defined: HashSet<String> = manifests.iter().map(Manifest::key).collect();
+    let mut stored = HashSet::new();
+    let outside: HashSet<&String> = manifests
+        .iter()
+        .flat_map(|m| m.references.iter())
+        .filter(|r| !defined.contains(*r))
+        .collect();
+    for reference in outside {
+        if common::etcd::get(reference).await.is_ok() {
+            stored.insert(reference.clone());
+        }
+    }
+    check_references(&manifests, &stored, &mut results);
+
+    manifests.retain(|m| results[m.file].error.is_none());
+    manifests.sort_by_key(|m| apply_rank(&m.kind));
+
+    let mut scenarios = Vec::new();
+    for manifest in &manifests {
+        let result = &mut results[manifest.file];
+        if result.error.is_some() {
+            continue;
+        }
+        let previous = match manifest.kind.as_str() {
+            KIND_PACKAGE => super::diff::deployed(&manifest.name).await,
+            _ => super::diff::Pods::new(),
+        };
+        let processed = process_artifact_document(&manifest.doc)
+            .await
+            .map_err(|e| e.to_string());
+        match processed {
+            Ok(Some((kind, artifact_str))) => {
+                if kind == KIND_PACKAGE {
+                    let scenario = manifests
+                        .iter()
+                        .find(|m| m.kind == KIND_SCENARIO && m.references.contains(&manifest.key()))
+                        .map(|m| m.name.as_str());
+                    let saved = match save_pod_yaml_from_package(&artifact_str, scenario).await {
+                        Ok(pods) => super::diff::record(&manifest.name, &previous, &pods).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = saved {
+                        result.error = Some(format!("{}: {}", manifest.key(), e));
+                        continue;
+                    }
+                } else if kind == KIND_SCENARIO {
+                    scenarios.push((manifest.file, artifact_str));
+                }
+                result.applied.push(manifest.key());
+            }
+            Ok(None) => {}
+            Err(e) => result.error = Some(format!("{}: {}", manifest.key(), e)),
+        }
+    }
+
+    (results, scenarios)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = r#"
+apiVersion: v1
+kind: Model
+metadata:
+  name: hello-core
+spec:
+  hostNetwork: true
+  containers:
+    - name: hello
+      image: hello
+"#;
+
+    const PACKAGE: &str = r#"
+apiVersion: v1
+kind: Package
+metadata:
+  name: hello
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: hello-core
+      node: HPC
+      resources:
+        volume:
+        network:
+"#;
+
+    const SCENARIO: &str = r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: hello
+spec:
+  condition:
+  action: update
+  target: hello
+"#;
+
+    fn file(name: &str, content: &str) -> ManifestFile {
+        ManifestFile {
+            name: name.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn results_for(files: &[ManifestFile]) -> Vec<FileResult> {
+        files
+            .iter()
+            .map(|f| FileResult {
+                file: f.name.clone(),
+                applied: Vec::new(),
+                error: None,
+                violations: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unpack_tar_gz_and_json() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [
+            ("b/scenario.yaml", SCENARIO),
+            ("a/model.yml", MODEL),
+            ("README", "ignored"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        let tarball = builder.into_inner().unwrap();
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &tarball).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        for body in [&tarball, &gzipped] {
+            let files = unpack(body).unwrap();
+            assert_eq!(
+                files,
+                vec![
+                    file("a/model.yml", MODEL),
+                    file("b/scenario.yaml", SCENARIO)
+                ]
+            );
+        }
+
+        let json = serde_json::json!({ "package.yaml": PACKAGE }).to_string();
+        assert_eq!(
+            unpack(json.as_bytes()).unwrap(),
+            vec![file("package.yaml", PACKAGE)]
+        );
+        assert!(unpack(b"not an archive").is_err());
+    }
+
+    #[test]
+    fn test_decompress_is_capped() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        std::io::Write::write_all(&mut encoder, &[0u8; 4096]).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        assert!(gzipped.len() < 100);
+
+        assert_eq!(decompress(&gzipped, 4096).unwrap().len(), 4096);
+        let err = decompress(&gzipped, 4095).unwrap_err();
+        assert!(err.to_string().contains("4095 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_and_order_manifests() {
+        let files = vec![
+            file("scenario.yaml", SCENARIO),
+            file("package.yaml", &format!("{}---{}", PACKAGE, MODEL)),
+            file("broken.yaml", "kind: Unknown\nmetadata:\n  name: x\n"),
+            file("malformed.yaml", "kind: Model\nmetadata: 5\n"),
+        ];
+        let mut results = results_for(&files);
+        let mut manifests = parse_files(&files, &mut results);
+
+        assert!(results[0].error.is_none());
+        assert!(results[1].error.is_none());
+        assert!(results[2].error.is_some());
+        assert!(results[2].violations.is_empty());
+        assert!(results[3].error.is_some());
+        assert_eq!(results[3].violations[0].path, "metadata");
+        assert_eq!(results[3].violations[0].line, Some(2));
+        assert_eq!(manifests[0].references, vec!["Package/hello"]);
+
+        manifests.sort_by_key(|m| apply_rank(&m.kind));
+        let order: Vec<String> = manifests.iter().map(Manifest::key).collect();
+        assert_eq!(
+            order,
+            vec!["Model/hello-core", "Package/hello", "Scenario/hello"]
+        );
+    }
+
+    #[test]
+    fn test_missing_references_fail_dependent_files() {
+        // The package misses its model, so the scenario loses its target too
+        let files = vec![
+            file("scenario.yaml", SCENARIO),
+            file("package.yaml", PACKAGE),
+        ];
+        let mut results = results_for(&files);
+        let manifests = parse_files(&files, &mut results);
+        check_references(&manifests, &HashSet::new(), &mut results);
+        assert_eq!(
+            results[1].error.as_deref(),
+            Some("Package/hello refers to missing Model/hello-core")
+        );
+        assert_eq!(
+            results[0].error.as_deref(),
+            Some("Scenario/hello refers to missing Package/hello")
+        );
+
+        // A model that is already stored satisfies the reference
+        let mut results = results_for(&files);
+        let stored = HashSet::from(["Model/hello-core".to_string()]);
+        check_references(&manifests, &stored, &mut results);
+        assert!(results.iter().all(|r| r.error.is_none()));
+    }
+
+    #[test]
+    fn test_duplicate_artifacts_fail_later_file() {
+        let files = vec![file("one.yaml", MODEL), file("two.yaml", MODEL)];
+        let mut results = results_for(&files);
+        let manifests = parse_files(&files, &mut results);
+        check_duplicates(&manifests, &files, &mut results);
+        assert!(results[0].error.is_none());
+        assert_eq!(
+            results[1].error.as_deref(),
+            Some("Model/hello-core is also defined in one.yaml")
+        );
+    }
+}
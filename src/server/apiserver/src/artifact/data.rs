@@ -13,9 +13,10 @@ use common::logd;
 /// * `artifact_name: &str` - name of the newly released artifact
 /// ### Return
 /// * `Result<(String)>` - `Ok()` contains yaml string if success
-#[allow(dead_code)]
 pub async fn read_from_etcd(artifact_name: &str) -> common::Result<String> {
-    let raw = common::etcd::get(artifact_name).await?;
+    let raw = common::etcd::get(artifact_name)
+        .await
+        .map_err(common::etcd::error)?;
     Ok(raw)
 }
 
@@ -26,7 +27,9 @@ pub async fn read_from_etcd(artifact_name: &str) -> common::Result<String> {
 /// ### Return
 /// * `Result<Vec<String>>` - `Ok(_)` contains scenario yaml string vector
 pub async fn read_all_scenario_from_etcd() -> common::Result<Vec<String>> {
-    let kv_scenario = common::etcd::get_all_with_prefix("Scenario").await?;
+    let kv_scenario = common::etcd::get_all_with_prefix("Scenario")
+        .await
+        .map_err(common::etcd::error)?;
     let values = kv_scenario.into_iter().map(|kv| kv.1).collect();
 
     Ok(values)
@@ -47,7 +50,7 @@ pub async fn write_to_etcd(key: &str, artifact_str: &str) -> common::Result<()>
 
     logd!(1, "write_to_etcd: elapsed = {:?}", elapsed);
 
-    result?;
+    result.map_err(common::etcd::error)?;
     Ok(())
 }
 
@@ -58,7 +61,9 @@ pub async fn write_to_etcd(key: &str, artifact_str: &str) -> common::Result<()>
 /// ### Return
 /// * `Result<()>` - `Ok` if success, `Err` otherwise
 pub async fn delete_at_etcd(key: &str) -> common::Result<()> {
-    common::etcd::delete(key).await?;
+    common::etcd::delete(key)
+        .await
+        .map_err(common::etcd::error)?;
     Ok(())
 }
 
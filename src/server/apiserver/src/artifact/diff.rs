@@ -0,0 +1,178 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! What a re-applied package changes in the Pods of its models
+//!
+//! Before a package is written, the Pods of its stored version are read
+//! with the node each one runs on. Once the Pods of the new version are
+//! written, both are compared into a [`PackageDiff`] that is reported to
+//! the client and kept for the ActionController, whose next update of the
+//! package restarts only the added and changed Pods. A diff the
+//! ActionController has not taken yet is merged with the next one, so that
+//! applies in a row redeploy what any of them changed.
+
+use super::{data, KIND_PACKAGE};
+use common::logd;
+use common::spec::artifact::package::PackageDiff;
+use common::spec::artifact::{Artifact, Package};
+use std::collections::BTreeMap;
+
+/// Pods by name, with the node they run on and their yaml
+pub type Pods = BTreeMap<String, (String, String)>;
+
+/// Pods of the stored version of a package, none when it is not stored
+pub async fn deployed(package_name: &str) -> Pods {
+    let Ok(package_str) = data::read_from_etcd(&format!("{}/{}", KIND_PACKAGE, package_name)).await
+    else {
+        return Pods::new();
+    };
+    let Ok(package) = serde_yaml::from_str::<Package>(&package_str) else {
+        return Pods::new();
+    };
+    let mut pods = Pods::new();
+    for replica in package.get_replicas() {
+        let pod_name = replica.get_pod_name();
+        let pod_yaml = data::read_from_etcd(&format!("Pod/{}", pod_name))
+            .await
+            .unwrap_or_default();
+        pods.insert(pod_name, (replica.get_node(), pod_yaml));
+    }
+    pods
+}
+
+/// Name of the package of the artifact documents, if there is one
+pub fn package_of(docs: &[&str]) -> Option<String> {
+    docs.iter().find_map(|doc| {
+        let value: serde_yaml::Value = serde_yaml::from_str(doc).ok()?;
+        if value.get("kind")?.as_str()? != KIND_PACKAGE {
+            return None;
+        }
+        let package: Package = serde_yaml::from_value(value).ok()?;
+        Some(package.get_qualified_name())
+    })
+}
+
+/// Compare the Pods of a package before and after it was applied and keep
+/// the diff for the next update of the package
+///
+/// Returns the diff of this apply, while the kept one also holds the
+/// changes of the applies whose update has not run yet.
+pub async fn record(
+    package_name: &str,
+    previous: &Pods,
+    next: &Pods,
+) -> common::Result<PackageDiff> {
+    let diff = PackageDiff::between(package_name, previous, next);
+    let key = PackageDiff::key(package_name);
+    let pending = data::read_from_etcd(&key)
+        .await
+        .ok()
+        .and_then(|pending| serde_json::from_str::<PackageDiff>(&pending).ok());
+    let kept = match &pending {
+        Some(pending) => pending.then(&diff),
+        None => diff.clone(),
+    };
+    data::write_to_etcd(&key, &serde_json::to_string(&kept)?).await?;
+    logd!(
+        2,
+        "Package {} applied: {} added, {} changed, {} removed, {} unchanged Pod(s)",
+        package_name,
+        diff.added.len(),
+        diff.changed.len(),
+        diff.removed.len(),
+        diff.unchanged.len()
+    );
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = r#"
+apiVersion: v1
+kind: Package
+metadata:
+  name: diffed
+  namespace: team
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: diffed-core
+      node: HPC
+      resources:
+        volume:
+        network:
+"#;
+
+    #[test]
+    fn test_package_of() {
+        let scenario = "kind: Scenario\nmetadata:\n  name: s\n";
+        assert_eq!(
+            package_of(&[scenario, PACKAGE]),
+            Some("team/diffed".to_string())
+        );
+        assert_eq!(package_of(&[scenario]), None);
+    }
+
+    #[tokio::test]
+    async fn test_record_compares_with_the_stored_pods() {
+        common::etcd::use_in_memory_store();
+        data::write_to_etcd("Package/team/diffed", PACKAGE)
+            .await
+            .unwrap();
+        data::write_to_etcd("Pod/team/diffed-core", "image: v1")
+            .await
+            .unwrap();
+
+        let previous = deployed("team/diffed").await;
+        assert_eq!(
+            previous["team/diffed-core"],
+            ("HPC".to_string(), "image: v1".to_string())
+        );
+        assert!(deployed("team/unknown").await.is_empty());
+
+        let mut next = previous.clone();
+        next.insert(
+            "team/diffed-core".to_string(),
+            ("HPC".to_string(), "image: v2".to_string()),
+        );
+        let diff = record("team/diffed", &previous, &next).await.unwrap();
+        assert_eq!(diff.changed, ["team/diffed-core"]);
+
+        let stored = data::read_from_etcd(&PackageDiff::key("team/diffed"))
+            .await
+            .unwrap();
+        assert_eq!(serde_json::from_str::<PackageDiff>(&stored).unwrap(), diff);
+    }
+
+    #[tokio::test]
+    async fn test_applies_in_a_row_keep_both_changes() {
+        common::etcd::use_in_memory_store();
+        let pods = |core: &str, sub: &str| -> Pods {
+            [("team/rowed-core", core), ("team/rowed-sub", sub)]
+                .into_iter()
+                .map(|(pod, yaml)| (pod.to_string(), ("HPC".to_string(), yaml.to_string())))
+                .collect()
+        };
+        let v1 = pods("image: v1", "image: v1");
+        let v2 = pods("image: v2", "image: v1");
+        let v3 = pods("image: v2", "image: v2");
+
+        // The update of the first apply has not taken its diff yet
+        let first = record("team/rowed", &v1, &v2).await.unwrap();
+        let second = record("team/rowed", &v2, &v3).await.unwrap();
+        assert_eq!(first.changed, ["team/rowed-core"]);
+        assert_eq!(second.changed, ["team/rowed-sub"]);
+
+        let stored = data::read_from_etcd(&PackageDiff::key("team/rowed"))
+            .await
+            .unwrap();
+        let kept: PackageDiff = serde_json::from_str(&stored).unwrap();
+        assert_eq!(kept.changed, ["team/rowed-core", "team/rowed-sub"]);
+        assert!(kept.unchanged.is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Garbage collection of keys left behind by removed artifacts
+//!
+//! Stored artifacts derive further keys: the `Pod/{model}` documents, one
+//! per replica of the model, the `/model/{name}/…`, `/package/{name}/…`
+//! and `/scenario/{name}/…` states, the `Autostart/{node}/{model}` records
+//! of the ActionController and the dead letters of the StateManager.
+//! Withdrawing an artifact removes the artifact only. Every
+//! `gc.interval_secs` the derived keys are compared with the stored
+//! `Model/`, `Package/` and `Scenario/` artifacts, and a key whose artifact
+//! has stayed missing for `gc.grace_secs` is deleted. The grace period keeps
+//! keys of artifacts being applied or re-applied.
+//!
+//! The `/transition/{id}` records deduplicating StateChanges belong to no
+//! artifact. They are collected once older than the window the StateManager
+//! honors them for.
+
+use common::logd;
+use common::statemanager::{DeadLetter, ResourceType};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Prefix of the derived keys, with the prefix of the artifact they belong to
+const DERIVED: [(&str, &str); 5] = [
+    ("Pod/", "Model/"),
+    ("/model/", "Model/"),
+    ("/package/", "Package/"),
+    ("/scenario/", "Scenario/"),
+    (AUTOSTART, "Model/"),
+];
+
+/// Prefix of the autostart records of the ActionController
+const AUTOSTART: &str = "Autostart/";
+
+/// Prefix of the dead letters of the StateManager
+const DEAD_LETTERS: &str = "/statemanager/dead-letter/";
+
+/// Prefix of the transition records of the StateManager
+const TRANSITIONS: &str = "/transition/";
+
+/// How long the StateManager honors a transition record, in nanoseconds
+const TRANSITION_WINDOW_NS: i64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Counters of the collector
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct GcStats {
+    /// Collections since start
+    pub runs: u64,
+    /// Keys deleted since start
+    pub collected: u64,
+    /// Orphaned keys waiting for their grace period
+    pub pending: usize,
+}
+
+/// Artifact a derived key belongs to, e.g. `Model/a` for `/model/a/state`
+/// or `Autostart/HPC/a`
+fn owner(key: &str) -> Option<String> {
+    DERIVED.iter().find_map(|(prefix, artifact)| {
+        let rest = key.strip_prefix(prefix)?;
+        let name = if *prefix == AUTOSTART {
+            rest.split_once('/')?.1
+        } else if prefix.starts_with('/') {
+            rest.rsplit_once('/')?.0
+        } else {
+            rest
+        };
+        (!name.is_empty()).then(|| format!("{}{}", artifact, name))
+    })
+}
+
+/// Pod a Pod key or an autostart record is about
+fn pod_of(key: &str) -> Option<&str> {
+    match key.strip_prefix("Pod/") {
+        Some(pod) => Some(pod),
+        None => Some(key.strip_prefix(AUTOSTART)?.split_once('/')?.1),
+    }
+}
+
+/// Model a replica or standby Pod key belongs to, e.g. `Model/a` for
+/// `Pod/a--1` or `Pod/a-standby`
+fn replica_owner(key: &str) -> Option<String> {
+    use common::spec::k8s::pod::{replica_base, standby_base};
+    let name = pod_of(key)?;
+    let model = match standby_base(name) {
+        Some(model) => model,
+        None => replica_base(name)?,
+    };
+    Some(format!("Model/{}", model))
+}
+
+/// Derived keys whose artifact is not among `artifacts`
+///
+/// The Pods of the replicas and the standby of a model are kept with the
+/// model.
+pub fn orphans<'a>(artifacts: &HashSet<String>, keys: &'a [String]) -> Vec<&'a String> {
+    keys.iter()
+        .filter(|key| owner(key).is_some_and(|owner| !artifacts.contains(&owner)))
+        .filter(|key| replica_owner(key).is_none_or(|model| !artifacts.contains(&model)))
+        .collect()
+}
+
+/// Dead letters whose resource is a scenario, package or model that is not
+/// among `artifacts`
+pub fn orphaned_dead_letters<'a>(
+    artifacts: &HashSet<String>,
+    letters: &'a [(String, String)],
+) -> Vec<&'a String> {
+    letters
+        .iter()
+        .filter(|(_, value)| {
+            let Some(change) = serde_json::from_str::<DeadLetter>(value)
+                .ok()
+                .and_then(|letter| letter.state_change)
+            else {
+                return false;
+            };
+            let artifact = match ResourceType::try_from(change.resource_type) {
+                Ok(ResourceType::Scenario) => "Scenario/",
+                Ok(ResourceType::Package) => "Package/",
+                Ok(ResourceType::Model) => "Model/",
+                _ => return false,
+            };
+            !artifacts.contains(&format!("{}{}", artifact, change.resource_name))
+        })
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Transition records older than the window they are honored for at `now_ns`
+pub fn expired_transitions(records: &[(String, String)], now_ns: i64) -> Vec<&String> {
+    records
+        .iter()
+        .filter(|(_, value)| {
+            serde_json::from_str::<serde_json::Value>(value)
+                .ok()
+                .and_then(|record| record["timestamp_ns"].as_i64())
+                .is_some_and(|timestamp_ns| now_ns - timestamp_ns > TRANSITION_WINDOW_NS)
+        })
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Orphaned keys by the time they were first seen orphaned
+#[derive(Default)]
+struct Collector {
+    first_seen: HashMap<String, Instant>,
+    stats: GcStats,
+}
+
+impl Collector {
+    /// Keys orphaned for longer than `grace`, forgets keys no longer orphaned
+    fn due(&mut self, orphans: &[&String], now: Instant, grace: Duration) -> Vec<String> {
+        let current: HashSet<&String> = orphans.iter().copied().collect();
+        self.first_seen.retain(|key, _| current.contains(key));
+        for key in orphans {
+            self.first_seen.entry((*key).clone()).or_insert(now);
+        }
+        let mut due: Vec<String> = self
+            .first_seen
+            .iter()
+            .filter(|(_, seen)| now.duration_since(**seen) >= grace)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.sort();
+        due
+    }
+}
+
+fn collector() -> &'static Mutex<Collector> {
+    static COLLECTOR: OnceLock<Mutex<Collector>> = OnceLock::new();
+    COLLECTOR.get_or_init(|| Mutex::new(Collector::default()))
+}
+
+pub fn stats() -> GcStats {
+    collector()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .stats
+        .clone()
+}
+
+/// Keys stored under a prefix
+async fn keys(prefix: &str) -> common::Result<Vec<String>> {
+    let kvs = common::etcd::get_all_with_prefix(prefix)
+        .await
+        .map_err(common::etcd::error)?;
+    Ok(kvs.into_iter().map(|(key, _)| key).collect())
+}
+
+/// Delete the derived keys orphaned for longer than `grace`
+///
+/// ### Returns
+/// * `Ok(Vec<String>)` - the deleted keys
+pub async fn collect(grace: Duration) -> common::Result<Vec<String>> {
+    let mut artifacts = HashSet::new();
+    for prefix in ["Model/", "Package/", "Scenario/"] {
+        artifacts.extend(keys(prefix).await?);
+    }
+    let mut derived = Vec::new();
+    for (prefix, _) in DERIVED {
+        derived.extend(keys(prefix).await?);
+    }
+    let letters = common::etcd::get_all_with_prefix(DEAD_LETTERS)
+        .await
+        .map_err(common::etcd::error)?;
+    let transitions = common::etcd::get_all_with_prefix(TRANSITIONS)
+        .await
+        .map_err(common::etcd::error)?;
+
+    let due = {
+        let mut orphans = orphans(&artifacts, &derived);
+        orphans.extend(orphaned_dead_letters(&artifacts, &letters));
+        orphans.extend(expired_transitions(&transitions, common::clock::now_ns()));
+        let mut collector = collector().lock().unwrap_or_else(|e| e.into_inner());
+        let due = collector.due(&orphans, Instant::now(), grace);
+        collector.stats.pending = orphans.len();
+        due
+    };
+
+    let mut collected = Vec::new();
+    for key in due {
+        match common::etcd::delete(&key).await {
+            Ok(()) => collected.push(key),
+            Err(e) => logd!(4, "GC failed to delete {}: {}", key, e),
+        }
+    }
+
+    let mut collector = collector().lock().unwrap_or_else(|e| e.into_inner());
+    for key in &collected {
+        collector.first_seen.remove(key);
+    }
+    collector.stats.runs += 1;
+    collector.stats.collected += collected.len() as u64;
+    collector.stats.pending -= collected.len();
+    Ok(collected)
+}
+
+/// Collect orphaned keys every `gc.interval_secs`, if `gc.enabled` is set
+pub async fn run() {
+    let settings = common::setting::get_config().gc.clone();
+    if !settings.enabled {
+        return;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_secs.max(1)));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match collect(Duration::from_secs(settings.grace_secs)).await {
+            Ok(collected) if !collected.is_empty() => logd!(
+                3,
+                "GC deleted {} orphaned key(s): {}",
+                collected.len(),
+                collected.join(", ")
+            ),
+            Ok(_) => logd!(1, "GC found nothing to delete, {:?}", stats()),
+            Err(e) => logd!(4, "GC skipped: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn test_orphans() {
+        let artifacts: HashSet<String> = keys(&["Model/a", "Package/p", "Scenario/team/s"])
+            .into_iter()
+            .collect();
+        let derived = keys(&[
+            "Pod/a",
+            "Pod/a--1",
+            "Pod/a-standby",
+            "Pod/gone",
+            "Pod/gone--1",
+            "Pod/gone-standby",
+            "/model/a/state",
+            "/model/gone/state",
+            "/package/p/state",
+            "/package/gone/update",
+            "/scenario/team/s/denial",
+            "/scenario/team/gone/state",
+            "Autostart/HPC/a--1",
+            "Autostart/HPC/gone",
+        ]);
+        assert_eq!(
+            orphans(&artifacts, &derived),
+            vec![
+                "Pod/gone",
+                "Pod/gone--1",
+                "Pod/gone-standby",
+                "/model/gone/state",
+                "/package/gone/update",
+                "/scenario/team/gone/state",
+                "Autostart/HPC/gone"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_orphaned_dead_letters_and_expired_transitions() {
+        let artifacts: HashSet<String> = keys(&["Scenario/s"]).into_iter().collect();
+        let letter = |resource_type: ResourceType, name: &str| {
+            let letter = DeadLetter {
+                state_change: Some(common::statemanager::StateChange {
+                    resource_type: resource_type as i32,
+                    resource_name: name.to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            serde_json::to_string(&letter).unwrap()
+        };
+        let letters = vec![
+            (
+                "/statemanager/dead-letter/1".to_string(),
+                letter(ResourceType::Scenario, "s"),
+            ),
+            (
+                "/statemanager/dead-letter/2".to_string(),
+                letter(ResourceType::Scenario, "gone"),
+            ),
+            (
+                "/statemanager/dead-letter/3".to_string(),
+                letter(ResourceType::Node, "HPC"),
+            ),
+        ];
+        assert_eq!(
+            orphaned_dead_letters(&artifacts, &letters),
+            vec!["/statemanager/dead-letter/2"]
+        );
+
+        let now_ns = 2 * TRANSITION_WINDOW_NS;
+        let records = vec![
+            (
+                "/transition/old".to_string(),
+                r#"{"timestamp_ns":1}"#.to_string(),
+            ),
+            (
+                "/transition/new".to_string(),
+                format!(r#"{{"timestamp_ns":{}}}"#, now_ns - 1),
+            ),
+        ];
+        assert_eq!(
+            expired_transitions(&records, now_ns),
+            vec!["/transition/old"]
+        );
+    }
+
+    #[test]
+    fn test_due_after_the_grace_period() {
+        let grace = Duration::from_secs(60);
+        let start = Instant::now();
+        let (a, b) = ("Pod/a".to_string(), "Pod/b".to_string());
+        let mut collector = Collector::default();
+
+        assert!(collector.due(&[&a, &b], start, grace).is_empty());
+        // b got its artifact back in between
+        assert_eq!(collector.due(&[&a], start + grace, grace), vec![a.clone()]);
+        assert!(collector.due(&[&a, &b], start + grace, grace).contains(&a));
+        assert!(!collector.due(&[&a, &b], start + grace, grace).contains(&b));
+    }
+}
@@ -0,0 +1,410 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Dependency graph of the applied artifacts
+//!
+//! Every scenario points to the package it targets, every package to its
+//! models and every model to the nodes its replicas are placed on. Each
+//! vertex carries the state last stored for it: the rolled up status of a
+//! scenario, the states of packages and models saved by the StateManager and
+//! the status of a registered node.
+//!
+//! A vertex in a failing state such as `Degraded`, `Dead` or `NotReady`
+//! whose dependencies are all healthy is an origin: the failure starts there
+//! and the failing vertices depending on it only inherit it. The graph is
+//! exported as JSON and as DOT for Graphviz, where failing vertices are
+//! filled red and origins drawn with a bold border.
+
+use super::query::{QueryError, Scope};
+use super::{KIND_MODEL, KIND_NODE, KIND_PACKAGE, KIND_SCENARIO};
+use common::apiserver::NodeInfo;
+use common::nodeagent::fromapiserver::NodeStatus;
+use common::spec::artifact::{Artifact, Package, Scenario};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// States, in any case and without separators, of a vertex that fails
+const FAILING: [&str; 8] = [
+    "degraded",
+    "error",
+    "failed",
+    "dead",
+    "crashloopbackoff",
+    "denied",
+    "notready",
+    "terminating",
+];
+
+/// Artifact or node of the graph
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Vertex {
+    /// `Kind/qualified name`, unique in the graph
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+    /// Last stored state, none before the first one
+    pub state: Option<String>,
+    /// Whether the state is a failing one
+    pub failing: bool,
+}
+
+/// Dependency of a vertex on another one
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+}
+
+/// Scenario, package, model and node dependencies with their states
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct Graph {
+    pub vertices: Vec<Vertex>,
+    pub edges: Vec<Edge>,
+    /// Failing vertices none of whose dependencies fail
+    pub origins: Vec<String>,
+}
+
+/// Whether a stored state is one of [`FAILING`]
+fn is_failing(state: &str) -> bool {
+    let normalized: String = state
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    FAILING.contains(&normalized.as_str())
+}
+
+/// Readable status of a registered node, e.g. `READY`
+fn node_status(node: &NodeInfo) -> Option<String> {
+    NodeStatus::try_from(node.status).ok().map(|status| {
+        status
+            .as_str_name()
+            .trim_start_matches("NODE_STATUS_")
+            .to_string()
+    })
+}
+
+/// Builder of a graph, keeping vertices and edges ordered and unique
+#[derive(Default)]
+struct Builder {
+    vertices: BTreeMap<String, Vertex>,
+    edges: BTreeSet<Edge>,
+}
+
+impl Builder {
+    fn vertex(&mut self, kind: &str, name: &str, state: Option<String>) -> String {
+        let id = format!("{}/{}", kind, name);
+        let vertex = self.vertices.entry(id.clone()).or_insert_with(|| Vertex {
+            id: id.clone(),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            state: None,
+            failing: false,
+        });
+        if state.is_some() {
+            vertex.failing = state.as_deref().is_some_and(is_failing);
+            vertex.state = state;
+        }
+        id
+    }
+
+    fn edge(&mut self, from: &str, to: &str) {
+        self.edges.insert(Edge {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+
+    fn build(self) -> Graph {
+        let origins = self
+            .vertices
+            .values()
+            .filter(|vertex| vertex.failing)
+            .filter(|vertex| {
+                !self
+                    .edges
+                    .iter()
+                    .filter(|edge| edge.from == vertex.id)
+                    .any(|edge| self.vertices.get(&edge.to).is_some_and(|to| to.failing))
+            })
+            .map(|vertex| vertex.id.clone())
+            .collect();
+        Graph {
+            vertices: self.vertices.into_values().collect(),
+            edges: self.edges.into_iter().collect(),
+            origins,
+        }
+    }
+}
+
+/// Graph of the stored artifacts, states and nodes
+///
+/// ### Parameters
+/// * `artifacts` - stored `(Kind/name, yaml)` pairs of scenarios and packages
+/// * `states` - stored `(key, state)` pairs of `/scenario/`, `/package/` and
+///   `/model/` keys
+/// * `nodes` - registered nodes
+/// * `scope` - namespaces of the artifacts to include
+fn build(
+    artifacts: &[(String, String)],
+    states: &[(String, String)],
+    nodes: &[NodeInfo],
+    scope: Scope,
+) -> Graph {
+    let states: BTreeMap<&str, &str> = states
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let state = |key: String| states.get(key.as_str()).map(|s| s.to_string());
+    let nodes: BTreeMap<&str, &NodeInfo> = nodes
+        .iter()
+        .map(|node| (node.hostname.as_str(), node))
+        .collect();
+
+    let mut builder = Builder::default();
+    for (key, yaml) in artifacts {
+        if key.starts_with(&format!("{}/", KIND_PACKAGE)) {
+            let Ok(package) = serde_yaml::from_str::<Package>(yaml) else {
+                continue;
+            };
+            if !scope.contains(&package.get_namespace()) {
+                continue;
+            }
+            let name = package.get_qualified_name();
+            let id = builder.vertex(
+                KIND_PACKAGE,
+                &name,
+                state(format!("/package/{}/state", name)),
+            );
+            for model in package.get_models() {
+                let model_name = model.get_name();
+                let model_id = builder.vertex(
+                    KIND_MODEL,
+                    &model_name,
+                    state(format!("/model/{}/state", model_name)),
+                );
+                builder.edge(&id, &model_id);
+                for replica in model.get_replicas() {
+                    let node = replica.get_node();
+                    let status = nodes.get(node.as_str()).and_then(|n| node_status(n));
+                    let node_id = builder.vertex(KIND_NODE, &node, status);
+                    builder.edge(&model_id, &node_id);
+                }
+            }
+        } else if key.starts_with(&format!("{}/", KIND_SCENARIO)) {
+            let Ok(scenario) = serde_yaml::from_str::<Scenario>(yaml) else {
+                continue;
+            };
+            if !scope.contains(&scenario.get_namespace()) {
+                continue;
+            }
+            let name = scenario.get_qualified_name();
+            let status = state(format!("/scenario/{}/status", name))
+                .or_else(|| state(format!("/scenario/{}/state", name)));
+            let id = builder.vertex(KIND_SCENARIO, &name, status);
+            let package_id = builder.vertex(KIND_PACKAGE, &scenario.get_targets(), None);
+            builder.edge(&id, &package_id);
+        }
+    }
+    builder.build()
+}
+
+/// Graph of the artifacts applied in the namespaces of `scope`
+pub async fn export(scope: Scope<'_>) -> Result<Graph, QueryError> {
+    scope.check()?;
+    let mut artifacts = Vec::new();
+    for kind in [KIND_SCENARIO, KIND_PACKAGE] {
+        let stored = common::etcd::get_all_with_prefix(&format!("{}/", kind))
+            .await
+            .map_err(QueryError::Storage)?;
+        artifacts.extend(stored);
+    }
+    let mut states = Vec::new();
+    for prefix in ["/scenario/", "/package/", "/model/"] {
+        states.extend(
+            common::etcd::get_all_with_prefix(prefix)
+                .await
+                .unwrap_or_default(),
+        );
+    }
+    let nodes: Vec<NodeInfo> = common::etcd::get_all_with_prefix("cluster/nodes/")
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|(_, json)| serde_json::from_str(json).ok())
+        .collect();
+    Ok(build(&artifacts, &states, &nodes, scope))
+}
+
+impl Graph {
+    /// The graph in the DOT language of Graphviz
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph piccolo {\n    rankdir=LR;\n");
+        for vertex in &self.vertices {
+            let label = match &vertex.state {
+                Some(state) => format!("{}\\n{}\\n{}", vertex.kind, vertex.name, state),
+                None => format!("{}\\n{}", vertex.kind, vertex.name),
+            };
+            let shape = match vertex.kind.as_str() {
+                KIND_SCENARIO => "ellipse",
+                KIND_NODE => "box3d",
+                _ => "box",
+            };
+            let mut attributes = format!("label=\"{}\", shape={}", escape(&label), shape);
+            if vertex.failing {
+                attributes.push_str(", style=filled, fillcolor=\"#f4cccc\"");
+            }
+            if self.origins.contains(&vertex.id) {
+                attributes.push_str(", penwidth=3, color=red");
+            }
+            dot.push_str(&format!(
+                "    \"{}\" [{}];\n",
+                escape(&vertex.id),
+                attributes
+            ));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\";\n",
+                escape(&edge.from),
+                escape(&edge.to)
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escape the quotes of a DOT string, keeping the `\n` line breaks
+fn escape(value: &str) -> String {
+    value.replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"apiVersion: v1
+kind: Scenario
+metadata:
+  name: antipinch
+spec:
+  condition:
+  action: update
+  target: antipinch
+"#;
+
+    const PACKAGE: &str = r#"apiVersion: v1
+kind: Package
+metadata:
+  name: antipinch
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: antipinch-core
+      node: HPC
+      resources:
+        volume:
+        network:
+    - name: antipinch-ui
+      node: ZONE
+      resources:
+        volume:
+        network:
+"#;
+
+    fn node(name: &str, status: NodeStatus) -> NodeInfo {
+        NodeInfo {
+            hostname: name.to_string(),
+            status: status.into(),
+            ..Default::default()
+        }
+    }
+
+    fn stored(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_is_failing() {
+        assert!(is_failing("Degraded"));
+        assert!(is_failing("CrashLoopBackOff"));
+        assert!(is_failing("NOT_READY"));
+        assert!(!is_failing("running"));
+        assert!(!is_failing("READY"));
+    }
+
+    #[test]
+    fn test_build_links_scenarios_to_nodes_and_finds_origins() {
+        let artifacts = stored(&[
+            ("Scenario/antipinch", SCENARIO),
+            ("Package/antipinch", PACKAGE),
+        ]);
+        let states = stored(&[
+            ("/scenario/antipinch/status", "degraded"),
+            ("/package/antipinch/state", "degraded"),
+            ("/model/antipinch-core/state", "Running"),
+            ("/model/antipinch-ui/state", "Dead"),
+        ]);
+        let nodes = [
+            node("HPC", NodeStatus::Ready),
+            node("ZONE", NodeStatus::Ready),
+        ];
+        let graph = build(&artifacts, &states, &nodes, Scope::default());
+
+        let ids: Vec<&str> = graph.vertices.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "Model/antipinch-core",
+                "Model/antipinch-ui",
+                "Node/HPC",
+                "Node/ZONE",
+                "Package/antipinch",
+                "Scenario/antipinch",
+            ]
+        );
+        assert_eq!(graph.edges.len(), 5);
+        assert!(graph.edges.contains(&Edge {
+            from: "Model/antipinch-ui".to_string(),
+            to: "Node/ZONE".to_string(),
+        }));
+        assert_eq!(graph.vertices[2].state.as_deref(), Some("READY"));
+        assert_eq!(graph.origins, vec!["Model/antipinch-ui"]);
+
+        // A node going down is where the failure of its model starts
+        let nodes = [
+            node("HPC", NodeStatus::Ready),
+            node("ZONE", NodeStatus::NotReady),
+        ];
+        let graph = build(&artifacts, &states, &nodes, Scope::default());
+        assert_eq!(graph.origins, vec!["Node/ZONE"]);
+
+        let other = Scope {
+            namespace: Some("team-a"),
+            principal: None,
+        };
+        assert_eq!(build(&artifacts, &states, &nodes, other), Graph::default());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let artifacts = stored(&[("Package/antipinch", PACKAGE)]);
+        let states = stored(&[("/model/antipinch-ui/state", "Dead")]);
+        let graph = build(&artifacts, &states, &[], Scope::default());
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph piccolo {"));
+        assert!(dot.contains("\"Package/antipinch\" -> \"Model/antipinch-ui\";"));
+        assert!(dot.contains(
+            "\"Model/antipinch-ui\" [label=\"Model\\nantipinch-ui\\nDead\", shape=box, \
+             style=filled, fillcolor=\"#f4cccc\", penwidth=3, color=red];"
+        ));
+        assert!(dot.contains("\"Node/HPC\" [label=\"Node\\nHPC\", shape=box3d];"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Size limits of the artifacts of a request
+//!
+//! The body of a request, the number of its documents and the size of every
+//! document are limited by the `artifact` settings, so that a huge request
+//! cannot stall the ApiServer or etcd. The size of the body is only checked
+//! by the router, before the body is read, see [`crate::route::api`].
+//! [`documents`] splits the documents off the body one at a time and stops
+//! at the first one over a limit, before the rest of the body is split or
+//! anything is parsed. The files of a bulk request share one document
+//! limit, see [`check_total`].
+
+use super::YAML_SEPARATOR;
+use common::error::Error;
+use common::setting::ArtifactSettings;
+use std::str::Split;
+
+/// Documents of a request body, checked against the limits
+pub struct Documents<'a> {
+    limits: ArtifactSettings,
+    docs: Split<'a, &'static str>,
+    count: usize,
+    failed: bool,
+}
+
+impl<'a> Iterator for Documents<'a> {
+    type Item = common::Result<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let doc = self.docs.next()?;
+        // Empty documents, as before a leading separator, are not counted
+        if !doc.trim().is_empty() {
+            self.count += 1;
+        }
+        let result = if self.count > self.limits.max_documents {
+            Err(format!(
+                "Request has more than {} artifact documents",
+                self.limits.max_documents
+            ))
+        } else if doc.len() > self.limits.max_document_bytes {
+            Err(format!(
+                "Artifact document {} of {} bytes exceeds the limit of {} bytes",
+                self.count,
+                doc.len(),
+                self.limits.max_document_bytes
+            ))
+        } else {
+            Ok(doc)
+        };
+        self.failed = result.is_err();
+        Some(result.map_err(Error::InvalidRequest))
+    }
+}
+
+/// Documents of `body` within the limits of the settings
+pub fn documents(body: &str) -> Documents<'_> {
+    documents_with(body, common::setting::get_config().artifact.clone())
+}
+
+fn documents_with(body: &str, limits: ArtifactSettings) -> Documents<'_> {
+    Documents {
+        limits,
+        docs: body.split(YAML_SEPARATOR),
+        count: 0,
+        failed: false,
+    }
+}
+
+/// Documents of `body`, an error if it exceeds a limit of the settings
+pub fn split(body: &str) -> common::Result<Vec<&str>> {
+    documents(body).collect()
+}
+
+/// Reject a body exceeding a limit of the settings, without keeping its
+/// documents
+pub fn check(body: &str) -> common::Result<()> {
+    documents(body).try_for_each(|doc| doc.map(|_| ()))
+}
+
+/// Reject the bodies of one request having more documents together than
+/// the settings allow
+pub fn check_total<'a>(bodies: impl IntoIterator<Item = &'a str>) -> common::Result<()> {
+    check_total_with(bodies, common::setting::get_config().artifact.max_documents)
+}
+
+fn check_total_with<'a>(
+    bodies: impl IntoIterator<Item = &'a str>,
+    max_documents: usize,
+) -> common::Result<()> {
+    let count = bodies
+        .into_iter()
+        .flat_map(|body| body.split(YAML_SEPARATOR))
+        .filter(|doc| !doc.trim().is_empty())
+        .take(max_documents + 1)
+        .count();
+    if count > max_documents {
+        return Err(Error::InvalidRequest(format!(
+            "Request has more than {} artifact documents",
+            max_documents
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(documents: usize, document: usize) -> ArtifactSettings {
+        ArtifactSettings {
+            max_documents: documents,
+            max_document_bytes: document,
+            ..Default::default()
+        }
+    }
+
+    fn split_with(body: &str, limits: ArtifactSettings) -> common::Result<Vec<&str>> {
+        documents_with(body, limits).collect()
+    }
+
+    #[test]
+    fn test_documents_within_limits() {
+        let body = "---\nkind: Scenario\n---\nkind: Package\n";
+        let docs = split_with(body, limits(2, 20)).unwrap();
+        assert_eq!(docs, vec!["", "\nkind: Scenario\n", "\nkind: Package\n"]);
+    }
+
+    #[test]
+    fn test_documents_over_limits() {
+        let body = "kind: Scenario\n---\nkind: Package\n---\nkind: Model\n";
+        let err = split_with(body, limits(2, 100)).unwrap_err();
+        assert!(err.to_string().contains("more than 2"), "{}", err);
+
+        let body = "kind: Model\n---\nkind: Scenario\n";
+        let err = split_with(body, limits(5, 14)).unwrap_err();
+        assert!(err.to_string().contains("document 2 of 16"), "{}", err);
+
+        // Nothing is split after the first document over a limit
+        let body = "kind: Scenario\n---\nkind: Package\n---\nkind: Model\n";
+        let mut docs = documents_with(body, limits(1, 100));
+        assert!(docs.next().unwrap().is_ok());
+        assert!(docs.next().unwrap().is_err());
+        assert!(docs.next().is_none());
+    }
+
+    #[test]
+    fn test_documents_counted_across_bodies() {
+        let bodies = ["kind: Scenario\n---\nkind: Package\n", "---\nkind: Model\n"];
+        assert!(check_total_with(bodies, 3).is_ok());
+        let err = check_total_with(bodies, 2).unwrap_err();
+        assert!(err.to_string().contains("more than 2"), "{}", err);
+    }
+}
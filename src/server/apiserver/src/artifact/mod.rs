@@ -5,10 +5,20 @@
 
 //! Convert string-type artifacts to struct and access etcd
 
+pub mod bulk;
 pub mod data;
-
+pub mod diff;
+pub mod gc;
+pub mod graph;
+pub mod limits;
+pub mod query;
+pub mod schema;
+
+use common::error::Error;
 use common::logd;
+use common::spec::artifact::package::PackageDiff;
 use common::spec::artifact::{Artifact, Model, Network, Node, Package, Scenario, Volume};
+use common::spec::k8s::pod::{LABEL_PACKAGE, LABEL_SCENARIO};
 use common::spec::k8s::Pod;
 
 // Artifact kind constants
@@ -22,35 +32,188 @@ const KIND_MODEL: &str = "Model";
 // YAML document separator
 const YAML_SEPARATOR: &str = "---";
 
-/// Parse artifact kind and name from YAML value
+/// Parse artifact kind and qualified name from YAML value
+///
+/// The name is qualified with the namespace of the artifact, see
+/// [`common::namespace`].
 fn parse_artifact_info(value: &serde_yaml::Value) -> Option<(String, String)> {
     let kind = value.get("kind")?.as_str()?;
 
     let name = match kind {
         KIND_SCENARIO => serde_yaml::from_value::<Scenario>(value.clone())
             .ok()?
-            .get_name(),
+            .get_qualified_name(),
         KIND_PACKAGE => serde_yaml::from_value::<Package>(value.clone())
             .ok()?
-            .get_name(),
+            .get_qualified_name(),
         KIND_VOLUME => serde_yaml::from_value::<Volume>(value.clone())
             .ok()?
-            .get_name(),
+            .get_qualified_name(),
         KIND_NETWORK => serde_yaml::from_value::<Network>(value.clone())
             .ok()?
-            .get_name(),
+            .get_qualified_name(),
         KIND_NODE => serde_yaml::from_value::<Node>(value.clone())
             .ok()?
-            .get_name(),
+            .get_qualified_name(),
         KIND_MODEL => serde_yaml::from_value::<Model>(value.clone())
             .ok()?
-            .get_name(),
+            .get_qualified_name(),
         _ => return None,
     };
 
     Some((kind.to_string(), name))
 }
 
+/// `metadata.namespace` of an artifact document, the default namespace
+/// without it
+pub fn namespace_of(value: &serde_yaml::Value) -> String {
+    value
+        .get("metadata")
+        .and_then(|metadata| metadata.get("namespace"))
+        .and_then(|namespace| namespace.as_str())
+        .filter(|namespace| !namespace.is_empty())
+        .unwrap_or(common::namespace::DEFAULT)
+        .to_string()
+}
+
+/// Namespaces of the artifact documents of a request body
+pub fn namespaces(body: &str) -> common::Result<Vec<String>> {
+    let mut namespaces = Vec::new();
+    for doc in limits::documents(body) {
+        let doc = doc?;
+        let value: serde_yaml::Value = serde_yaml::from_str(doc)?;
+        if value.is_null() {
+            continue;
+        }
+        let namespace = namespace_of(&value);
+        if !namespaces.contains(&namespace) {
+            namespaces.push(namespace);
+        }
+    }
+    Ok(namespaces)
+}
+
+/// Reject scenarios with malformed conditions, models with malformed resource
+/// limits or probes or selecting unknown node labels and packages with
+/// invalid replicas or infeasible realtime scheduling before anything is
+/// stored
+fn validate_artifact_documents(docs: &[&str]) -> common::Result<()> {
+    for doc in docs {
+        let value: serde_yaml::Value = serde_yaml::from_str(doc)?;
+        common::namespace::validate(&namespace_of(&value)).map_err(Error::InvalidRequest)?;
+        let kind = value.get("kind").and_then(|kind| kind.as_str());
+        if let Some(kind @ (KIND_SCENARIO | KIND_PACKAGE | KIND_MODEL)) = kind {
+            let name = value
+                .get("metadata")
+                .and_then(|metadata| metadata.get("name"))
+                .and_then(|name| name.as_str())
+                .unwrap_or_default();
+            common::namespace::validate_name(name)
+                .map_err(|e| Error::InvalidRequest(format!("Invalid {}: {}", kind, e)))?;
+        }
+        if kind == Some(KIND_MODEL) {
+            let model: Model = serde_yaml::from_value(value)
+                .map_err(|e| Error::InvalidRequest(format!("Invalid model: {}", e)))?;
+            model.get_podspec().validate_resources().map_err(|e| {
+                Error::InvalidRequest(format!(
+                    "Invalid resources in model {}: {}",
+                    model.get_name(),
+                    e
+                ))
+            })?;
+            model.get_podspec().validate_probes().map_err(|e| {
+                Error::InvalidRequest(format!(
+                    "Invalid probe in model {}: {}",
+                    model.get_name(),
+                    e
+                ))
+            })?;
+            let selector = model.get_podspec().node_selector();
+            let unknown = common::setting::get_config()
+                .placement
+                .unknown_keys(selector.keys());
+            if !unknown.is_empty() {
+                return Err(Error::InvalidRequest(format!(
+                    "Unknown node label keys in model {}: {}",
+                    model.get_name(),
+                    unknown.join(", ")
+                )));
+            }
+            continue;
+        }
+        if kind == Some(KIND_PACKAGE) {
+            let package: Package = serde_yaml::from_value(value)
+                .map_err(|e| Error::InvalidRequest(format!("Invalid package: {}", e)))?;
+            for model in package.get_models() {
+                common::namespace::validate_name(&model.get_name()).map_err(|e| {
+                    Error::InvalidRequest(format!(
+                        "Invalid model of package {}: {}",
+                        package.get_qualified_name(),
+                        e
+                    ))
+                })?;
+                model.validate_replicas().map_err(|e| {
+                    Error::InvalidRequest(format!(
+                        "Invalid replicas of model {} in package {}: {}",
+                        model.get_name(),
+                        package.get_qualified_name(),
+                        e
+                    ))
+                })?;
+                model.validate_standby().map_err(|e| {
+                    Error::InvalidRequest(format!(
+                        "Invalid standby of model {} in package {}: {}",
+                        model.get_name(),
+                        package.get_qualified_name(),
+                        e
+                    ))
+                })?;
+                if let Some(realtime) = model.get_resources().get_realtime_spec() {
+                    realtime.validate().map_err(|e| {
+                        Error::InvalidRequest(format!(
+                            "Invalid realtime scheduling of model {} in package {}: {}",
+                            model.get_name(),
+                            package.get_qualified_name(),
+                            e
+                        ))
+                    })?;
+                }
+            }
+            continue;
+        }
+        if kind != Some(KIND_SCENARIO) {
+            continue;
+        }
+
+        let scenario: Scenario = serde_yaml::from_value(value)
+            .map_err(|e| Error::InvalidRequest(format!("Invalid scenario: {}", e)))?;
+        common::namespace::validate_name(&scenario.get_target_name()).map_err(|e| {
+            Error::InvalidRequest(format!(
+                "Invalid target of scenario {}: {}",
+                scenario.get_qualified_name(),
+                e
+            ))
+        })?;
+        if let Some(condition) = scenario.get_conditions() {
+            condition.validate().map_err(|e| {
+                Error::InvalidRequest(format!(
+                    "Invalid condition in scenario {}: {}",
+                    scenario.get_qualified_name(),
+                    e
+                ))
+            })?;
+        }
+        scenario.validate().map_err(|e| {
+            Error::InvalidRequest(format!(
+                "Invalid scenario {}: {}",
+                scenario.get_qualified_name(),
+                e
+            ))
+        })?;
+    }
+    Ok(())
+}
+
 /// Send initial state change notification to StateManager
 async fn notify_scenario_state(scenario_name: &str, target_state: &str) {
     let timestamp = std::time::SystemTime::now()
@@ -63,9 +226,13 @@ async fn notify_scenario_state(scenario_name: &str, target_state: &str) {
         resource_name: scenario_name.to_string(),
         current_state: String::new(),
         target_state: target_state.to_string(),
-        transition_id: format!("apiserver-scenario-init-{}", timestamp),
+        transition_id: common::correlation::transition_id("apiserver"),
         timestamp_ns: timestamp,
         source: "apiserver".to_string(),
+        correlation_id: common::correlation::current_or_empty(),
+        sub_state: String::new(),
+        denial: None,
+        metadata: Default::default(),
     };
 
     logd!(
@@ -136,14 +303,24 @@ async fn process_artifact_document(doc: &str) -> common::Result<Option<(String,
 /// ### Parametets
 /// * `body: &str` - whole yaml string of piccolo artifact
 /// ### Returns
-/// * `Result(String, String)` - scenario and package yaml in downloaded artifact
+/// * `Result(String, PackageDiff)` - scenario yaml in downloaded artifact and
+///   the Pods of the package it added, changed or removed
 /// ### Description
-/// Write artifact in etcd
-pub async fn apply(body: &str) -> common::Result<String> {
+/// Write artifact in etcd. Bodies over the limits of the `artifact`
+/// settings are rejected before anything is parsed, see [`limits`].
+pub async fn apply(body: &str) -> common::Result<(String, PackageDiff)> {
     use std::time::Instant;
     let total_start = Instant::now();
 
-    let docs: Vec<&str> = body.split(YAML_SEPARATOR).collect();
+    let docs = limits::split(body)?;
+    schema::check(body)?;
+    validate_artifact_documents(&docs)?;
+    let package_name = diff::package_of(&docs);
+    let previous = match &package_name {
+        Some(package_name) => diff::deployed(package_name).await,
+        None => diff::Pods::new(),
+    };
+
     let mut scenario_str = String::new();
     let mut package_str = String::new();
 
@@ -160,12 +337,20 @@ pub async fn apply(body: &str) -> common::Result<String> {
     logd!(1, "apply: total elapsed = {:?}", total_start.elapsed());
 
     if scenario_str.is_empty() {
-        Err("There is not any scenario in yaml string".into())
+        Err(Error::InvalidRequest(
+            "There is not any scenario in yaml string".to_string(),
+        ))
     } else if package_str.is_empty() {
-        Err("There is not any package in yaml string".into())
+        Err(Error::InvalidRequest(
+            "There is not any package in yaml string".to_string(),
+        ))
     } else {
-        save_pod_yaml_from_package(&package_str).await?;
-        Ok(scenario_str)
+        let scenario: Scenario = serde_yaml::from_str(&scenario_str)?;
+        let pods =
+            save_pod_yaml_from_package(&package_str, Some(&scenario.get_qualified_name())).await?;
+        let package_name = package_name.unwrap_or_default();
+        let diff = diff::record(&package_name, &previous, &pods).await?;
+        Ok((scenario_str, diff))
     }
 }
 
@@ -178,9 +363,8 @@ pub async fn apply(body: &str) -> common::Result<String> {
 /// ### Description
 /// Delete scenario yaml only, because other scenario can use a package with same name
 pub async fn withdraw(body: &str) -> common::Result<String> {
-    let docs: Vec<&str> = body.split(YAML_SEPARATOR).collect();
-
-    for doc in docs {
+    for doc in limits::documents(body) {
+        let doc = doc?;
         let value: serde_yaml::Value = serde_yaml::from_str(doc)?;
 
         if let Some((kind, name)) = parse_artifact_info(&value) {
@@ -193,19 +377,27 @@ pub async fn withdraw(body: &str) -> common::Result<String> {
         }
     }
 
-    Err("There is not any scenario in yaml string".into())
+    Err(Error::InvalidRequest(
+        "There is not any scenario in yaml string".to_string(),
+    ))
 }
 
 /// Load model with optional volume and network resources
+///
+/// The names of the model info are qualified with the namespace of its package.
 async fn load_model_with_resources(
     model_info: &common::spec::artifact::package::ModelInfo,
 ) -> common::Result<Model> {
-    let model_str = common::etcd::get(&format!("{}/{}", KIND_MODEL, model_info.get_name())).await?;
+    let model_str = common::etcd::get(&format!("{}/{}", KIND_MODEL, model_info.get_name()))
+        .await
+        .map_err(common::etcd::error)?;
     let mut model: Model = serde_yaml::from_str(&model_str)?;
 
     // Load volume if specified
     if let Some(volume_name) = model_info.get_resources().get_volume() {
-        let volume_str = common::etcd::get(&format!("{}/{}", KIND_VOLUME, volume_name)).await?;
+        let volume_str = common::etcd::get(&format!("{}/{}", KIND_VOLUME, volume_name))
+            .await
+            .map_err(common::etcd::error)?;
         let volume: Volume = serde_yaml::from_str(&volume_str)?;
 
         if let Some(volume_spec) = volume.get_spec() {
@@ -218,7 +410,9 @@ async fn load_model_with_resources(
 
     // Load network if specified
     if let Some(network_name) = model_info.get_resources().get_network() {
-        let network_str = common::etcd::get(&format!("{}/{}", KIND_NETWORK, network_name)).await?;
+        let network_str = common::etcd::get(&format!("{}/{}", KIND_NETWORK, network_name))
+            .await
+            .map_err(common::etcd::error)?;
         let _network: Network = serde_yaml::from_str(&network_str)?;
         // TODO: Apply network configuration
     }
@@ -227,24 +421,56 @@ async fn load_model_with_resources(
 }
 
 /// Save Pod YAML for all models in a package
-async fn save_pod_yaml_from_package(package_str: &str) -> common::Result<()> {
+///
+/// The Pods are labeled with `scenario` when the package is applied with one.
+/// Returns the saved Pods with the node of their model.
+async fn save_pod_yaml_from_package(
+    package_str: &str,
+    scenario: Option<&str>,
+) -> common::Result<diff::Pods> {
     let package: Package = serde_yaml::from_str(package_str)?;
     let mut models = Vec::new();
 
     for model_info in package.get_models() {
         let model = load_model_with_resources(&model_info).await?;
-        models.push(model);
+        models.push((model, model_info.get_replicas()));
     }
 
-    let pods: Vec<Pod> = models.into_iter().map(Pod::from).collect();
+    // Package labels reach the containers through the Pod, below the model's own
+    let mut package_labels = package.get_labels();
+    package_labels.insert(LABEL_PACKAGE.to_string(), package.get_qualified_name());
+    if let Some(scenario) = scenario {
+        package_labels.insert(LABEL_SCENARIO.to_string(), scenario.to_string());
+    }
 
-    for pod in pods {
-        let pod_yaml = serde_yaml::to_string(&pod)?;
-        let key = format!("{}/{}", "Pod", pod.get_name());
-        data::write_to_etcd(&key, &pod_yaml).await?;
+    // Every replica of a model gets its own Pod, the first one the Pod of the model
+    let mut pods = diff::Pods::new();
+    for (model, replicas) in models {
+        let mut pod = Pod::from(model);
+        pod.add_labels(package_labels.clone());
+        for replica in replicas {
+            let mut replica_pod = pod.clone();
+            if let Some(index) = replica.get_replica() {
+                replica_pod.set_replica(index);
+            }
+            let key = format!("{}/{}", "Pod", replica.get_pod_name());
+            let pod_yaml = serde_yaml::to_string(&replica_pod)?;
+            data::write_to_etcd(&key, &pod_yaml).await?;
+            pods.insert(replica.get_pod_name(), (replica.get_node(), pod_yaml));
+
+            // The standby gets a Pod of its own, launched paused on its node
+            if let Some(standby_node) = replica.get_standby_node() {
+                let mut standby_pod = replica_pod.clone();
+                standby_pod.set_standby();
+                let key = format!("{}/{}", "Pod", replica.get_standby_pod_name());
+                let pod_yaml = serde_yaml::to_string(&standby_pod)?;
+                data::write_to_etcd(&key, &pod_yaml).await?;
+                pods.insert(replica.get_standby_pod_name(), (standby_node, pod_yaml));
+            }
+        }
     }
 
-    Ok(())
+    Ok(pods)
 }
 
 //UNIT TEST CASES
@@ -313,6 +539,65 @@ spec:
         network: []
 "#;
 
+    /// Valid Scenario with nested allOf/not condition
+    const VALID_COMPOSITE_CONDITION_YAML: &str = r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: helloworld
+spec:
+  condition:
+    allOf:
+      - express: eq
+        value: "parking"
+        operands:
+          type: DDS
+          name: gear
+          value: /rt/piccolo/Gear_State
+      - not:
+          express: contains
+          value: "fault"
+          operands:
+            type: DDS
+            name: status
+            value: /rt/piccolo/Battery
+  action: update
+  target: helloworld
+"#;
+
+    /// Invalid YAML — `gt` threshold is not a number
+    const INVALID_YAML_MALFORMED_CONDITION: &str = r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: helloworld
+spec:
+  condition:
+    allOf:
+      - express: gt
+        value: "high"
+        operands:
+          type: DDS
+          name: speed
+          value: /rt/piccolo/Speed
+  action: update
+  target: helloworld
+---
+apiVersion: v1
+kind: Package
+metadata:
+  name: helloworld
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: helloworld-core
+      node: HPC
+      resources:
+        volume:
+        network:
+"#;
+
     /// Invalid YAML — only unknown artifact
     const INVALID_YAML_UNKNOWN_ARTIFACT: &str = r#"
 apiVersion: v1
@@ -368,7 +653,7 @@ spec:
         );
 
         // Assert: scenario and package strings should not be empty
-        let scenario = result.unwrap();
+        let (scenario, _) = result.unwrap();
         assert!(!scenario.is_empty(), "Scenario YAML should not be empty");
 
         // Cleanup: Remove the created Model
@@ -387,6 +672,237 @@ spec:
         );
     }
 
+    /// Test apply() rejects a malformed condition before writing to etcd
+    #[tokio::test]
+    async fn test_apply_invalid_malformed_condition() {
+        let result = apply(INVALID_YAML_MALFORMED_CONDITION).await;
+
+        let err = result.expect_err("apply() accepted a malformed condition");
+        assert!(
+            err.to_string()
+                .contains("Invalid condition in scenario helloworld"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    /// Test validation accepts composite conditions
+    #[test]
+    fn test_validate_artifact_documents_composite_condition() {
+        let docs: Vec<&str> = VALID_COMPOSITE_CONDITION_YAML
+            .split(YAML_SEPARATOR)
+            .collect();
+        assert!(validate_artifact_documents(&docs).is_ok());
+
+        let docs: Vec<&str> = INVALID_YAML_MALFORMED_CONDITION
+            .split(YAML_SEPARATOR)
+            .collect();
+        assert!(validate_artifact_documents(&docs).is_err());
+    }
+
+    /// Test validation rejects names that would leave their namespace
+    #[test]
+    fn test_validate_artifact_documents_names() {
+        let scenario = |name: &str, target: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: {}
+  namespace: team-a
+spec:
+  action: update
+  target: {}
+"#,
+                name, target
+            )
+        };
+        let package = |model: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Package
+metadata:
+  name: hello
+  namespace: team-a
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: {}
+      node: HPC
+      resources:
+        volume:
+        network:
+"#,
+                model
+            )
+        };
+        let error = |doc: String| validate_artifact_documents(&[doc.as_str()]).unwrap_err();
+
+        assert!(validate_artifact_documents(&[scenario("hello", "hello").as_str()]).is_ok());
+        assert!(validate_artifact_documents(&[package("hello-core").as_str()]).is_ok());
+        assert!(error(scenario("team-b/hello", "hello"))
+            .to_string()
+            .contains("Invalid Scenario: name 'team-b/hello'"));
+        assert!(error(scenario("hello", "team-b/hello"))
+            .to_string()
+            .contains("Invalid target of scenario team-a/hello"));
+        let err = error(package("team-b/hello-core"));
+        assert!(err
+            .to_string()
+            .contains("Invalid model of package team-a/hello"));
+        assert!(matches!(err, Error::InvalidRequest(_)));
+    }
+
+    /// Test validation rejects unknown vehicle modes
+    #[test]
+    fn test_validate_artifact_documents_allowed_modes() {
+        let scenario = |mode: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: helloworld
+spec:
+  action: update
+  target: helloworld
+  allowedModes:
+    - {}
+"#,
+                mode
+            )
+        };
+
+        assert!(validate_artifact_documents(&[scenario("parked").as_str()]).is_ok());
+        let err = validate_artifact_documents(&[scenario("towing").as_str()]).unwrap_err();
+        assert!(err.to_string().contains("unknown vehicle mode 'towing'"));
+        assert!(matches!(err, Error::InvalidRequest(_)));
+    }
+
+    /// Test validation rejects models with malformed resource limits
+    #[test]
+    fn test_validate_artifact_documents_model_resources() {
+        let model = |memory: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Model
+metadata:
+  name: helloworld-core
+spec:
+  containers:
+    - name: helloworld
+      image: helloworld
+      resources:
+        limits:
+          memory: {}
+"#,
+                memory
+            )
+        };
+
+        assert!(validate_artifact_documents(&[model("256Mi").as_str()]).is_ok());
+        let err = validate_artifact_documents(&[model("lots").as_str()]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid resources in model helloworld-core"));
+    }
+
+    /// Test validation rejects models selecting unknown node labels
+    #[test]
+    fn test_validate_artifact_documents_node_selector() {
+        let model = |key: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Model
+metadata:
+  name: helloworld-core
+spec:
+  nodeSelector:
+    {}: "true"
+  containers:
+    - name: helloworld
+      image: helloworld
+"#,
+                key
+            )
+        };
+
+        assert!(validate_artifact_documents(&[model("gpu").as_str()]).is_ok());
+        let err = validate_artifact_documents(&[model("gpus").as_str()]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unknown node label keys in model helloworld-core: gpus"));
+    }
+
+    /// Test validation rejects infeasible realtime scheduling
+    #[test]
+    fn test_validate_artifact_documents_realtime() {
+        let package = |realtime: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Package
+metadata:
+  name: helloworld
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: helloworld-core
+      node: HPC
+      resources:
+        realtime: {}
+"#,
+                realtime
+            )
+        };
+
+        assert!(validate_artifact_documents(&[package("true").as_str()]).is_ok());
+        assert!(
+            validate_artifact_documents(&[package("{priority: 90, runtime: 2000}").as_str()])
+                .is_ok()
+        );
+        let err = validate_artifact_documents(&[package("{runtime: 20000}").as_str()]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid realtime scheduling of model helloworld-core"));
+    }
+
+    /// Test validation rejects models without replicas
+    #[test]
+    fn test_validate_artifact_documents_replicas() {
+        let package = |replicas: &str| {
+            format!(
+                r#"
+apiVersion: v1
+kind: Package
+metadata:
+  name: helloworld
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: helloworld-core
+      node: HPC
+      replicas: {}
+      resources: {{}}
+"#,
+                replicas
+            )
+        };
+
+        assert!(validate_artifact_documents(&[package("3").as_str()]).is_ok());
+        let err = validate_artifact_documents(&[package("0").as_str()]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid replicas of model helloworld-core"));
+    }
+
     /// Test apply() with unknown artifact (no Scenario, no Package)
     #[tokio::test]
     async fn test_apply_invalid_unknown_artifact() {
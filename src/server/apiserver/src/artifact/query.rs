@@ -0,0 +1,532 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! List and read applied artifacts
+//!
+//! Artifacts are stored in etcd as `Kind/name`, with the name qualified by
+//! the namespace of the artifact, so listing a kind is a prefix scan. Results
+//! are ordered by qualified name and paged with a `limit` and the qualified
+//! name of the last artifact of the previous page. A query covers one
+//! namespace or all of them, but only the namespaces the caller may access.
+//!
+//! Scenarios come with the status rolled up by the StateManager from their
+//! state and the state of their package, see `/scenario/{name}/status`, and
+//! a denied scenario with the rule that refused it, `/scenario/{name}/denial`.
+
+use super::{KIND_MODEL, KIND_NETWORK, KIND_NODE, KIND_PACKAGE, KIND_SCENARIO, KIND_VOLUME};
+use common::auth::Principal;
+use common::statemanager::DenialReason;
+use std::collections::BTreeMap;
+
+const KINDS: [&str; 6] = [
+    KIND_SCENARIO,
+    KIND_PACKAGE,
+    KIND_MODEL,
+    KIND_VOLUME,
+    KIND_NETWORK,
+    KIND_NODE,
+];
+
+/// Error of etcd for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Why a query failed
+#[derive(Debug, PartialEq)]
+pub enum QueryError {
+    /// Unknown kind or malformed label selector
+    Invalid(String),
+    /// Namespace the caller may not access
+    Forbidden(String),
+    NotFound(String),
+    Storage(String),
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Invalid(msg)
+            | QueryError::Forbidden(msg)
+            | QueryError::NotFound(msg)
+            | QueryError::Storage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Applied artifact as shown in a list
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ArtifactSummary {
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    /// Rolled up status of a scenario
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Why a denied scenario was refused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub denial: Option<DenialReason>,
+}
+
+/// One page of artifacts
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct ArtifactList {
+    pub items: Vec<ArtifactSummary>,
+    /// Qualified name to pass as `continue` for the next page, none on the
+    /// last page
+    #[serde(rename = "continue")]
+    pub next: Option<String>,
+}
+
+/// Stored artifact with the Pod generated for a Model
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct ArtifactDetail {
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub yaml: String,
+    pub pod: Option<String>,
+    /// Rolled up status of a scenario
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Why a denied scenario was refused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub denial: Option<DenialReason>,
+}
+
+/// Namespaces covered by a query
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scope<'a> {
+    /// Namespace asked for, all of them when none
+    pub namespace: Option<&'a str>,
+    /// Caller of the query, which only sees the namespaces it may access
+    pub principal: Option<&'a Principal>,
+}
+
+impl Scope<'_> {
+    pub(super) fn contains(&self, namespace: &str) -> bool {
+        self.namespace.is_none_or(|asked| asked == namespace)
+            && self.principal.is_none_or(|p| p.can_access(namespace))
+    }
+
+    /// Refuse a query of a namespace the caller may not access
+    pub(super) fn check(&self) -> Result<(), QueryError> {
+        match (self.namespace, self.principal) {
+            (Some(namespace), Some(p)) if !p.can_access(namespace) => Err(QueryError::Forbidden(
+                format!("{} is not allowed in namespace {}", p.name, namespace),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Requirement of a label selector
+#[derive(Debug, PartialEq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+}
+
+/// Label selector such as `app=hello,tier!=debug,critical`
+#[derive(Debug, Default, PartialEq)]
+pub struct LabelSelector(Vec<Requirement>);
+
+impl std::str::FromStr for LabelSelector {
+    type Err = QueryError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut requirements = Vec::new();
+        for term in value.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let requirement = if let Some((key, value)) = term.split_once("!=") {
+                Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some((key, value)) = term.split_once('=') {
+                Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else {
+                Requirement::Exists(term.to_string())
+            };
+            let key = match &requirement {
+                Requirement::Equals(key, _)
+                | Requirement::NotEquals(key, _)
+                | Requirement::Exists(key) => key,
+            };
+            if key.is_empty() {
+                return Err(QueryError::Invalid(format!(
+                    "Invalid label selector term '{}'",
+                    term
+                )));
+            }
+            requirements.push(requirement);
+        }
+        Ok(Self(requirements))
+    }
+}
+
+impl LabelSelector {
+    fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.0.iter().all(|requirement| match requirement {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+        })
+    }
+}
+
+/// Stored kind name for a kind given in any case, e.g. `scenario`
+fn resolve_kind(kind: &str) -> Result<&'static str, QueryError> {
+    KINDS
+        .iter()
+        .find(|k| k.eq_ignore_ascii_case(kind))
+        .copied()
+        .ok_or_else(|| QueryError::Invalid(format!("Unknown artifact kind '{}'", kind)))
+}
+
+/// `metadata.labels` of an artifact yaml
+fn labels_of(yaml: &str) -> BTreeMap<String, String> {
+    serde_yaml::from_str::<serde_yaml::Value>(yaml)
+        .ok()
+        .and_then(|value| {
+            value
+                .get("metadata")
+                .and_then(|metadata| metadata.get("labels"))
+                .and_then(|labels| serde_yaml::from_value(labels.clone()).ok())
+        })
+        .unwrap_or_default()
+}
+
+/// Artifacts of the stored `(key, yaml)` pairs of a kind in the scope and
+/// matching the selector, by qualified name
+fn select(
+    kind: &str,
+    stored: Vec<(String, String)>,
+    scope: Scope,
+    selector: &LabelSelector,
+) -> Vec<(String, ArtifactSummary)> {
+    let prefix = format!("{}/", kind);
+    let mut items: Vec<(String, ArtifactSummary)> = stored
+        .into_iter()
+        .filter_map(|(key, yaml)| {
+            let qualified = key.strip_prefix(&prefix)?.to_string();
+            let (namespace, name) = common::namespace::split(&qualified);
+            let item = ArtifactSummary {
+                kind: kind.to_string(),
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                labels: labels_of(&yaml),
+                status: None,
+                denial: None,
+            };
+            Some((qualified, item))
+        })
+        .filter(|(_, item)| scope.contains(&item.namespace))
+        .filter(|(_, item)| selector.matches(&item.labels))
+        .collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    items
+}
+
+/// First `limit` of the selected artifacts, all of them when `limit` is 0
+fn page(mut items: Vec<(String, ArtifactSummary)>, limit: usize) -> ArtifactList {
+    let mut next = None;
+    if limit > 0 && items.len() > limit {
+        items.truncate(limit);
+        next = items.last().map(|(qualified, _)| qualified.clone());
+    }
+    ArtifactList {
+        items: items.into_iter().map(|(_, item)| item).collect(),
+        next,
+    }
+}
+
+/// List the applied artifacts of a kind
+///
+/// The store is read in batches of one page from the `continue` of the
+/// previous page, until a page of artifacts in the scope and matching the
+/// selector is found or the kind has no more artifacts.
+///
+/// ### Parameters
+/// * `kind: &str` - artifact kind, case insensitive
+/// * `scope: Scope` - namespaces to list
+/// * `selector: &str` - label selector, empty to match all
+/// * `limit: usize` - page size, 0 for no limit
+/// * `after: Option<&str>` - `continue` of the previous page
+pub async fn list(
+    kind: &str,
+    scope: Scope<'_>,
+    selector: &str,
+    limit: usize,
+    after: Option<&str>,
+) -> Result<ArtifactList, QueryError> {
+    let kind = resolve_kind(kind)?;
+    scope.check()?;
+    let selector: LabelSelector = selector.parse()?;
+    let prefix = format!("{}/", kind);
+    // One more than the page tells whether there is a next page
+    let batch = if limit == 0 { 0 } else { limit + 1 };
+    let mut cursor = after.map(|after| format!("{}{}", prefix, after));
+    let mut items = Vec::new();
+    loop {
+        let stored = common::etcd::get_page_with_prefix(&prefix, cursor.as_deref(), batch)
+            .await
+            .map_err(QueryError::Storage)?;
+        let last_batch = batch == 0 || stored.len() < batch;
+        cursor = stored.last().map(|(key, _)| key.clone());
+        items.extend(select(kind, stored, scope, &selector));
+        if last_batch || items.len() > limit {
+            break;
+        }
+    }
+    let mut list = page(items, limit);
+    if kind == KIND_SCENARIO {
+        let statuses = common::etcd::get_all_with_prefix("/scenario/")
+            .await
+            .unwrap_or_default();
+        add_statuses(&mut list, &statuses);
+    }
+    Ok(list)
+}
+
+/// Set the status and denial of the scenarios of a list from the stored
+/// `/scenario/{name}/status` and `/scenario/{name}/denial` keys
+fn add_statuses(list: &mut ArtifactList, stored: &[(String, String)]) {
+    let of = |suffix: &str| -> BTreeMap<&str, &str> {
+        stored
+            .iter()
+            .filter_map(|(key, value)| {
+                let name = key.strip_prefix("/scenario/")?.strip_suffix(suffix)?;
+                Some((name, value.as_str()))
+            })
+            .collect()
+    };
+    let statuses = of("/status");
+    let denials = of("/denial");
+    for item in &mut list.items {
+        let qualified = common::namespace::qualify(&item.namespace, &item.name);
+        item.status = statuses.get(qualified.as_str()).map(|s| s.to_string());
+        item.denial = denials
+            .get(qualified.as_str())
+            .and_then(|denial| serde_json::from_str(denial).ok());
+    }
+}
+
+/// Read an applied artifact, with its generated Pod yaml for a Model
+///
+/// The artifact is looked up in the namespace of `scope`, the default
+/// namespace when it has none.
+pub async fn get(kind: &str, scope: Scope<'_>, name: &str) -> Result<ArtifactDetail, QueryError> {
+    let kind = resolve_kind(kind)?;
+    let namespace = scope.namespace.unwrap_or(common::namespace::DEFAULT);
+    Scope {
+        namespace: Some(namespace),
+        ..scope
+    }
+    .check()?;
+    let qualified = common::namespace::qualify(namespace, name);
+    let key = format!("{}/{}", kind, qualified);
+    let yaml = super::data::read_from_etcd(&key)
+        .await
+        .map_err(|e| match e.to_string() {
+            msg if msg == NOT_FOUND => QueryError::NotFound(format!("{} is not applied", key)),
+            msg => QueryError::Storage(msg),
+        })?;
+    let pod = if kind == KIND_MODEL {
+        super::data::read_from_etcd(&format!("Pod/{}", qualified))
+            .await
+            .ok()
+    } else {
+        None
+    };
+    let (status, denial) = if kind == KIND_SCENARIO {
+        let status = common::etcd::get(&format!("/scenario/{}/status", qualified))
+            .await
+            .ok();
+        let denial = common::etcd::get(&format!("/scenario/{}/denial", qualified))
+            .await
+            .ok()
+            .and_then(|denial| serde_json::from_str(&denial).ok());
+        (status, denial)
+    } else {
+        (None, None)
+    };
+
+    Ok(ArtifactDetail {
+        kind: kind.to_string(),
+        namespace: namespace.to_string(),
+        name: name.to_string(),
+        yaml,
+        pod,
+        status,
+        denial,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(name: &str, labels: &str) -> (String, String) {
+        (
+            format!("Scenario/{}", name),
+            format!(
+                "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: {}\n  labels: {}\n",
+                name, labels
+            ),
+        )
+    }
+
+    fn names(list: &ArtifactList) -> Vec<&str> {
+        list.items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_resolve_kind() {
+        assert_eq!(resolve_kind("scenario"), Ok(KIND_SCENARIO));
+        assert_eq!(resolve_kind("Model"), Ok(KIND_MODEL));
+        assert!(matches!(resolve_kind("Pod"), Err(QueryError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_label_selector() {
+        let labels = BTreeMap::from([
+            ("app".to_string(), "hello".to_string()),
+            ("tier".to_string(), "front".to_string()),
+        ]);
+        let matches = |s: &str| s.parse::<LabelSelector>().unwrap().matches(&labels);
+        assert!(matches(""));
+        assert!(matches("app=hello, tier"));
+        assert!(matches("tier!=back"));
+        assert!(!matches("app=bye"));
+        assert!(!matches("critical"));
+        assert!("=hello".parse::<LabelSelector>().is_err());
+    }
+
+    #[test]
+    fn test_select_filters_sorts_and_pages() {
+        let all = vec![
+            stored("c", "{app: hello}"),
+            stored("a", "{app: hello}"),
+            stored("b", "{app: bye}"),
+            stored("d", "null"),
+        ];
+        let any = LabelSelector::default();
+
+        let all_scope = Scope::default();
+        let first = page(select(KIND_SCENARIO, all.clone(), all_scope, &any), 2);
+        assert_eq!(names(&first), vec!["a", "b"]);
+        assert_eq!(first.next.as_deref(), Some("b"));
+        let whole = page(select(KIND_SCENARIO, all.clone(), all_scope, &any), 4);
+        assert_eq!(names(&whole), vec!["a", "b", "c", "d"]);
+        assert_eq!(whole.next, None);
+
+        let hello = "app=hello".parse().unwrap();
+        let selected = page(select(KIND_SCENARIO, all, all_scope, &hello), 0);
+        assert_eq!(names(&selected), vec!["a", "c"]);
+        assert_eq!(selected.items[0].labels["app"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_list_pages_at_the_store() {
+        common::etcd::use_in_memory_store();
+        for (name, labels) in [
+            ("a", "{app: hello}"),
+            ("b", "{app: bye}"),
+            ("c", "{app: hello}"),
+            ("d", "{app: hello}"),
+        ] {
+            let (key, yaml) = stored(&format!("paged/{}", name), labels);
+            common::etcd::put(&key, &yaml).await.unwrap();
+        }
+        let paged = Scope {
+            namespace: Some("paged"),
+            principal: None,
+        };
+
+        let first = list("scenario", paged, "app=hello", 2, None).await.unwrap();
+        assert_eq!(names(&first), vec!["a", "c"]);
+        assert_eq!(first.next.as_deref(), Some("paged/c"));
+        let second = list("scenario", paged, "app=hello", 2, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(names(&second), vec!["d"]);
+        assert_eq!(second.next, None);
+    }
+
+    #[test]
+    fn test_add_statuses() {
+        let any = LabelSelector::default();
+        let mut list = page(
+            select(
+                KIND_SCENARIO,
+                vec![stored("a", "{}"), stored("b", "{}")],
+                Scope::default(),
+                &any,
+            ),
+            0,
+        );
+        add_statuses(
+            &mut list,
+            &[
+                ("/scenario/a/state".to_string(), "Completed".to_string()),
+                ("/scenario/a/status".to_string(), "running".to_string()),
+                (
+                    "/scenario/b/denial".to_string(),
+                    r#"{"rule":"asil-level","message":"requires ASIL-D","source":"policymanager","timestamp_ns":1}"#
+                        .to_string(),
+                ),
+                ("/scenario/b/status".to_string(), "failed".to_string()),
+            ],
+        );
+        assert_eq!(list.items[0].status.as_deref(), Some("running"));
+        assert_eq!(list.items[0].denial, None);
+        assert_eq!(list.items[1].status.as_deref(), Some("failed"));
+        assert_eq!(list.items[1].denial.as_ref().unwrap().rule, "asil-level");
+    }
+
+    #[test]
+    fn test_page_is_scoped_by_namespace() {
+        let all = vec![
+            stored("hello", "{}"),
+            stored("team-a/hello", "{}"),
+            stored("team-b/hello", "{}"),
+        ];
+        let any = LabelSelector::default();
+        let namespaces = |list: &ArtifactList| -> Vec<String> {
+            list.items
+                .iter()
+                .map(|item| item.namespace.clone())
+                .collect()
+        };
+
+        let everything = page(
+            select(KIND_SCENARIO, all.clone(), Scope::default(), &any),
+            0,
+        );
+        assert_eq!(namespaces(&everything), vec!["default", "team-a", "team-b"]);
+        assert_eq!(names(&everything), vec!["hello", "hello", "hello"]);
+
+        let team_a = Scope {
+            namespace: Some("team-a"),
+            principal: None,
+        };
+        let list = page(select(KIND_SCENARIO, all.clone(), team_a, &any), 0);
+        assert_eq!(namespaces(&list), vec!["team-a"]);
+
+        let caller = Principal {
+            name: "team-b-ci".to_string(),
+            role: common::auth::Role::Operator,
+            namespaces: vec!["team-b".to_string()],
+        };
+        let own = Scope {
+            namespace: None,
+            principal: Some(&caller),
+        };
+        let list = page(select(KIND_SCENARIO, all, own, &any), 0);
+        assert_eq!(namespaces(&list), vec!["team-b"]);
+        let other = Scope {
+            namespace: Some("team-a"),
+            principal: Some(&caller),
+        };
+        assert!(matches!(other.check(), Err(QueryError::Forbidden(_))));
+    }
+}
@@ -0,0 +1,246 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Schema check of the artifact documents of a request
+//!
+//! Every document is deserialized into the spec struct of its kind before
+//! anything is stored, so that a malformed request is refused as a whole
+//! and the client learns where each document is wrong: the index of the
+//! document, the path of the field and its line and column in the request.
+//! Documents of unknown kinds are not checked, they are skipped when applied.
+
+use super::{
+    KIND_MODEL, KIND_NETWORK, KIND_NODE, KIND_PACKAGE, KIND_SCENARIO, KIND_VOLUME, YAML_SEPARATOR,
+};
+use common::error::Error;
+use common::spec::artifact::{Model, Network, Node, Package, Scenario, Volume};
+use serde::de::DeserializeOwned;
+
+/// Where and why an artifact document does not match the schema of its kind
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Violation {
+    /// Index of the document in the request, from 0
+    pub document: usize,
+    /// Kind of the document, empty when it has none
+    pub kind: String,
+    /// Path of the field, e.g. `spec.models[0].name`, empty for the document
+    pub path: String,
+    /// Line in the request, from 1, unknown for some syntax errors
+    pub line: Option<usize>,
+    /// Column in the line, from 1
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "document {}", self.document)?;
+        if !self.kind.is_empty() {
+            write!(f, " ({})", self.kind)?;
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " at line {} column {}", line, column)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, ", {}", self.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check every document of a request against the schema of its kind
+///
+/// ### Parameters
+/// * `body: &str` - whole yaml string of piccolo artifact
+/// ### Returns
+/// * `Vec<Violation>` - the first violation of every malformed document
+pub fn validate(body: &str) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut start = 0;
+    for (document, doc) in body.split(YAML_SEPARATOR).enumerate() {
+        if let Err(violation) = validate_document(doc) {
+            violations.push(violation.locate(document, body, start));
+        }
+        start += doc.len() + YAML_SEPARATOR.len();
+    }
+    violations
+}
+
+/// Refuse a request with a document not matching the schema of its kind
+pub fn check(body: &str) -> common::Result<()> {
+    let violations = validate(body);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let messages: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    Err(Error::InvalidRequest(format!(
+        "Invalid artifact: {}",
+        messages.join("; ")
+    )))
+}
+
+/// Violation found in a document, located within the document
+struct Found {
+    kind: String,
+    path: String,
+    index: Option<usize>,
+    message: String,
+}
+
+impl Found {
+    /// Locate the violation of the document at byte `start` of `body`
+    fn locate(self, document: usize, body: &str, start: usize) -> Violation {
+        let position = self.index.map(|index| {
+            let before = &body[..(start + index).min(body.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            (line, column)
+        });
+        Violation {
+            document,
+            kind: self.kind,
+            path: self.path,
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            message: self.message,
+        }
+    }
+}
+
+fn validate_document(doc: &str) -> Result<(), Found> {
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(doc).map_err(|e| found(String::new(), String::new(), &e))?;
+    if value.is_null() {
+        return Ok(());
+    }
+    let Some(kind) = value.get("kind").and_then(|kind| kind.as_str()) else {
+        return Err(Found {
+            kind: String::new(),
+            path: "kind".to_string(),
+            index: None,
+            message: "missing field `kind`".to_string(),
+        });
+    };
+    match kind {
+        KIND_SCENARIO => validate_as::<Scenario>(kind, doc),
+        KIND_PACKAGE => validate_as::<Package>(kind, doc),
+        KIND_VOLUME => validate_as::<Volume>(kind, doc),
+        KIND_NETWORK => validate_as::<Network>(kind, doc),
+        KIND_NODE => validate_as::<Node>(kind, doc),
+        KIND_MODEL => validate_as::<Model>(kind, doc),
+        _ => Ok(()),
+    }
+}
+
+fn validate_as<T: DeserializeOwned>(kind: &str, doc: &str) -> Result<(), Found> {
+    let deserializer = serde_yaml::Deserializer::from_str(doc);
+    serde_path_to_error::deserialize::<_, T>(deserializer)
+        .map(|_| ())
+        .map_err(|e| {
+            let path = e.path().to_string();
+            found(kind.to_string(), path, e.inner())
+        })
+}
+
+fn found(kind: String, path: String, error: &serde_yaml::Error) -> Found {
+    let path = if path == "." { String::new() } else { path };
+    let location = error.location();
+    // The message without the path and location serde_yaml adds to it
+    let mut message = error.to_string();
+    if let Some(location) = &location {
+        let suffix = format!(" at line {} column {}", location.line(), location.column());
+        if let Some(stripped) = message.strip_suffix(&suffix) {
+            message = stripped.to_string();
+        }
+    }
+    if let Some(stripped) = message.strip_prefix(&format!("{}: ", path)) {
+        message = stripped.to_string();
+    }
+    Found {
+        kind,
+        path,
+        index: location.map(|location| location.index()),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"apiVersion: v1
+kind: Scenario
+metadata:
+  name: helloworld
+spec:
+  action: update
+  target: helloworld
+"#;
+
+    #[test]
+    fn test_valid_documents_have_no_violations() {
+        let body = format!("{}---\n{}", SCENARIO, "kind: Unknown\nspec: 1\n");
+        assert!(validate(&body).is_empty());
+        assert!(check(&body).is_ok());
+        assert!(validate("").is_empty());
+    }
+
+    #[test]
+    fn test_violation_has_document_path_line_and_column() {
+        let package = r#"apiVersion: v1
+kind: Package
+metadata:
+  name: helloworld
+spec:
+  pattern:
+    - type: plain
+  models:
+    - name: helloworld-core
+      node: HPC
+      resources: 7
+"#;
+        let body = format!("{}---\n{}", SCENARIO, package);
+        let violations = validate(&body);
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        let violation = &violations[0];
+        assert_eq!(violation.document, 1);
+        assert_eq!(violation.kind, KIND_PACKAGE);
+        assert_eq!(violation.path, "spec.models[0].resources");
+        // Line 11 of the package, after the 7 lines of the scenario and the separator
+        assert_eq!(violation.line, Some(19));
+        assert_eq!(violation.column, Some(18));
+        assert!(violation.message.starts_with("invalid type"));
+        assert!(!violation.message.contains("at line"));
+
+        let err = check(&body).unwrap_err();
+        assert!(matches!(err, Error::InvalidRequest(_)));
+        assert!(err
+            .to_string()
+            .contains("document 1 (Package) at line 19 column 18, spec.models[0].resources"));
+    }
+
+    #[test]
+    fn test_every_malformed_document_is_reported() {
+        let missing_target = SCENARIO.replace("  target: helloworld\n", "");
+        let body = format!(
+            "{}---\nkind: Volume\nmetadata: [\n---\nmetadata:\n  name: x\n",
+            missing_target
+        );
+        let violations = validate(&body);
+        assert_eq!(violations.len(), 3, "{:?}", violations);
+
+        assert_eq!(violations[0].document, 0);
+        assert_eq!(violations[0].kind, KIND_SCENARIO);
+        assert!(violations[0].message.contains("missing field `target`"));
+
+        // A syntax error is located but has no kind
+        assert_eq!(violations[1].document, 1);
+        assert!(violations[1].kind.is_empty());
+        assert!(violations[1].line.is_some());
+
+        assert_eq!(violations[2].document, 2);
+        assert_eq!(violations[2].path, "kind");
+    }
+}
@@ -0,0 +1,328 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Access to the containers of applied models for debugging
+//!
+//! A model runs as the Pod of the same name on the node its Package places
+//! it on. Requests for its containers are proxied to the NodeAgent of that
+//! node, whose address is registered as `nodes/{hostname}`.
+//!
+//! Commands run in a container are written to the audit log with the
+//! principal that ran them, here and on the node.
+
+use crate::grpc::sender::nodeagent;
+use common::logd;
+use common::nodeagent::fromapiserver::{
+    ContainerExecRequest, ContainerLogChunk, ContainerLogsRequest,
+};
+use common::spec::artifact::Package;
+use common::spec::k8s::Pod;
+use tonic::Streaming;
+
+/// Error of etcd for a key that is not stored
+const NOT_FOUND: &str = "Key not found";
+
+/// Why a container could not be reached
+#[derive(Debug, PartialEq)]
+pub enum ContainerError {
+    /// Missing or ambiguous container name
+    Invalid(String),
+    NotFound(String),
+    /// Storage or node could not be reached
+    Unavailable(String),
+}
+
+impl std::fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerError::Invalid(msg)
+            | ContainerError::NotFound(msg)
+            | ContainerError::Unavailable(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Where the containers of a model run
+#[derive(Debug, PartialEq)]
+struct Placement {
+    node: String,
+    node_ip: String,
+    containers: Vec<String>,
+}
+
+/// Which logs of a container to read
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    /// Container of the model, may be left out for single container models
+    pub container: Option<String>,
+    /// Number of lines from the end, 0 for all of them
+    pub tail: i64,
+    /// Unix time in seconds of the oldest line, 0 for no limit
+    pub since: i64,
+    /// Keep streaming new lines until the client disconnects
+    pub follow: bool,
+}
+
+/// Diagnostic command to run in a container
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct ExecQuery {
+    /// Container of the model, may be left out for single container models
+    pub container: Option<String>,
+    pub command: Vec<String>,
+    /// Seconds to wait for the command, 0 for the default of the node
+    pub timeout: u32,
+}
+
+/// Output of a diagnostic command
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i64,
+}
+
+/// Node of the first stored Package placing the model
+fn node_of(packages: &[(String, String)], model: &str) -> Option<String> {
+    packages
+        .iter()
+        .filter_map(|(_, yaml)| serde_yaml::from_str::<Package>(yaml).ok())
+        .find_map(|package| {
+            package
+                .get_models()
+                .iter()
+                .find(|info| info.get_name() == model)
+                .map(|info| info.get_node())
+        })
+}
+
+/// Container to access, the only one of the model when none is requested
+fn pick_container(
+    model: &str,
+    containers: &[String],
+    requested: Option<&str>,
+) -> Result<String, ContainerError> {
+    match requested {
+        Some(name) if containers.iter().any(|c| c == name) => Ok(name.to_string()),
+        Some(name) => Err(ContainerError::NotFound(format!(
+            "Model {} has no container {}",
+            model, name
+        ))),
+        None if containers.len() == 1 => Ok(containers[0].clone()),
+        None => Err(ContainerError::Invalid(format!(
+            "Model {} has containers {}, select one with `container`",
+            model,
+            containers.join(", ")
+        ))),
+    }
+}
+
+/// Find the node and containers of an applied model
+async fn locate(model: &str) -> Result<Placement, ContainerError> {
+    let pod_yaml = crate::artifact::data::read_from_etcd(&format!("Pod/{}", model))
+        .await
+        .map_err(|e| match e.to_string() {
+            msg if msg == NOT_FOUND => {
+                ContainerError::NotFound(format!("Model {} is not applied", model))
+            }
+            msg => ContainerError::Unavailable(msg),
+        })?;
+    let pod: Pod = serde_yaml::from_str(&pod_yaml)
+        .map_err(|e| ContainerError::Unavailable(format!("Invalid Pod {}: {}", model, e)))?;
+    let containers = pod
+        .get_spec()
+        .containers
+        .iter()
+        .map(|c| c.get_name().to_string())
+        .collect();
+
+    let packages = common::etcd::get_all_with_prefix("Package/")
+        .await
+        .map_err(ContainerError::Unavailable)?;
+    let node = node_of(&packages, model).ok_or_else(|| {
+        ContainerError::NotFound(format!("Model {} is not placed by any package", model))
+    })?;
+    let node_ip = match common::etcd::get(&format!("nodes/{}", node)).await {
+        Ok(ip) => ip,
+        Err(_) => crate::node::node_lookup::find_node_by_hostname(&node)
+            .await
+            .map(|info| info.ip_address)
+            .ok_or_else(|| ContainerError::NotFound(format!("Node {} is not registered", node)))?,
+    };
+
+    Ok(Placement {
+        node,
+        node_ip,
+        containers,
+    })
+}
+
+/// Open the log stream of a container of a model
+///
+/// ### Parameters
+/// * `model: &str` - name of the applied model
+/// * `query: LogQuery` - container and lines to read
+pub async fn logs(
+    model: &str,
+    query: LogQuery,
+) -> Result<Streaming<ContainerLogChunk>, ContainerError> {
+    let placement = locate(model).await?;
+    let container = pick_container(model, &placement.containers, query.container.as_deref())?;
+    logd!(
+        2,
+        "Reading logs of {}/{} on node {}",
+        model,
+        container,
+        placement.node
+    );
+
+    let request = ContainerLogsRequest {
+        pod: model.to_string(),
+        container,
+        tail: query.tail,
+        since: query.since,
+        follow: query.follow,
+    };
+    nodeagent::get_container_logs(request, &placement.node_ip)
+        .await
+        .map_err(node_error)
+}
+
+/// Run a one-shot diagnostic command in a container of a model
+///
+/// ### Parameters
+/// * `model: &str` - name of the applied model
+/// * `query: ExecQuery` - container and command to run
+/// * `caller: &str` - principal running the command, for the audit log
+pub async fn exec(
+    model: &str,
+    query: ExecQuery,
+    caller: &str,
+) -> Result<ExecResult, ContainerError> {
+    if query.command.is_empty() {
+        return Err(ContainerError::Invalid("command is required".to_string()));
+    }
+    let placement = locate(model).await?;
+    let container = pick_container(model, &placement.containers, query.container.as_deref())?;
+    common::auth::audit_granted(
+        "exec",
+        caller,
+        &format!(
+            "{:?} in {}/{} on node {}",
+            query.command, model, container, placement.node
+        ),
+    );
+
+    let request = ContainerExecRequest {
+        pod: model.to_string(),
+        container,
+        command: query.command,
+        timeout_seconds: query.timeout,
+        caller: caller.to_string(),
+    };
+    let response = nodeagent::exec_in_container(request, &placement.node_ip)
+        .await
+        .map_err(node_error)?;
+    logd!(
+        3,
+        "[AUDIT] exec by {} in {} exited with {}",
+        caller,
+        model,
+        response.exit_code
+    );
+
+    Ok(ExecResult {
+        stdout: String::from_utf8_lossy(&response.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&response.stderr).to_string(),
+        exit_code: response.exit_code,
+    })
+}
+
+/// Error of a request the NodeAgent answered with a status
+fn node_error(status: tonic::Status) -> ContainerError {
+    let message = status.message().to_string();
+    match status.code() {
+        tonic::Code::NotFound => ContainerError::NotFound(message),
+        tonic::Code::InvalidArgument => ContainerError::Invalid(message),
+        _ => ContainerError::Unavailable(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, models: &[(&str, &str)]) -> (String, String) {
+        let models: String = models
+            .iter()
+            .map(|(model, node)| {
+                format!(
+                    "    - name: {}\n      node: {}\n      resources: {{}}\n",
+                    model, node
+                )
+            })
+            .collect();
+        (
+            format!("Package/{}", name),
+            format!(
+                "apiVersion: v1\nkind: Package\nmetadata:\n  name: {}\nspec:\n  pattern:\n    - type: plain\n  models:\n{}",
+                name, models
+            ),
+        )
+    }
+
+    #[test]
+    fn test_node_of() {
+        let packages = vec![
+            package("a", &[("a-core", "HPC")]),
+            package("b", &[("b-core", "ZONE"), ("b-side", "HPC")]),
+        ];
+        assert_eq!(node_of(&packages, "b-core"), Some("ZONE".to_string()));
+        assert_eq!(node_of(&packages, "a-core"), Some("HPC".to_string()));
+        assert_eq!(node_of(&packages, "c-core"), None);
+    }
+
+    #[test]
+    fn test_node_error() {
+        assert!(matches!(
+            node_error(tonic::Status::not_found("gone")),
+            ContainerError::NotFound(_)
+        ));
+        assert!(matches!(
+            node_error(tonic::Status::deadline_exceeded("slow")),
+            ContainerError::Unavailable(_)
+        ));
+    }
+
+    #[test]
+    fn test_exec_query_from_json() {
+        let query: ExecQuery =
+            serde_json::from_str(r#"{"command": ["cat", "/etc/hostname"]}"#).unwrap();
+        assert_eq!(query.container, None);
+        assert_eq!(query.command, vec!["cat", "/etc/hostname"]);
+        assert_eq!(query.timeout, 0);
+    }
+
+    #[test]
+    fn test_pick_container() {
+        let one = vec!["main".to_string()];
+        let two = vec!["main".to_string(), "sidecar".to_string()];
+        assert_eq!(pick_container("m", &one, None), Ok("main".to_string()));
+        assert_eq!(
+            pick_container("m", &two, Some("sidecar")),
+            Ok("sidecar".to_string())
+        );
+        assert!(matches!(
+            pick_container("m", &two, None),
+            Err(ContainerError::Invalid(_))
+        ));
+        assert!(matches!(
+            pick_container("m", &one, Some("other")),
+            Err(ContainerError::NotFound(_))
+        ));
+    }
+}
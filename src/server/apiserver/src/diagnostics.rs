@@ -32,7 +32,11 @@ pub async fn check_service_connectivity(ip: &str, port: u16) -> bool {
 
 /// Check if NodeAgent is reachable at the given IP
 pub async fn check_node_agent_connectivity(ip: &str) -> bool {
-    check_service_connectivity(ip, 47004).await
+    check_service_connectivity(
+        ip,
+        common::address::port(common::address::Service::NodeAgent),
+    )
+    .await
 }
 
 #[cfg(test)]
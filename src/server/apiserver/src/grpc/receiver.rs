@@ -11,14 +11,29 @@ use common::apiserver::{
     GetTopologyRequest, GetTopologyResponse, TopologyType, UpdateTopologyRequest,
     UpdateTopologyResponse,
 };
+use common::auth::Role;
 use common::etcd;
 use common::logd;
 use common::nodeagent::fromapiserver::{
-    NodeRegistrationRequest, NodeRegistrationResponse, NodeStatus,
+    ClusterConfig, HeartbeatRequest, HeartbeatResponse, NodeRegistrationRequest,
+    NodeRegistrationResponse, NodeStatus, StatusAck, StatusReport,
 };
+use common::version::{ApiVersionRequest, ApiVersionResponse};
 use prost::Message;
 use tonic::{Request, Response, Status};
 
+/// RPCs of the API server reported by `NegotiateApiVersion`
+const CAPABILITIES: &[&str] = &[
+    "GetNodes",
+    "GetNode",
+    "RegisterNode",
+    "Heartbeat",
+    "ReportStatus",
+    "GetTopology",
+    "UpdateTopology",
+    "NegotiateApiVersion",
+];
+
 /// Simple registry embedded in receiver
 #[derive(Clone)]
 struct NodeRegistry;
@@ -100,6 +115,7 @@ impl ApiServerConnection for ApiServerReceiver {
         request: Request<GetNodesRequest>,
     ) -> Result<Response<GetNodesResponse>, Status> {
         logd!(1, "Received GetNodes request");
+        common::auth::authorize(&request, "GetNodes", Role::ReadOnly)?;
         let _req = request.into_inner();
 
         match self.node_manager.get_nodes().await {
@@ -121,6 +137,7 @@ impl ApiServerConnection for ApiServerReceiver {
         request: Request<GetNodeRequest>,
     ) -> Result<Response<GetNodeResponse>, Status> {
         logd!(1, "Received GetNode request");
+        common::auth::authorize(&request, "GetNode", Role::ReadOnly)?;
         let req = request.into_inner();
 
         match self.node_manager.get_node(&req.node_id).await {
@@ -147,7 +164,10 @@ impl ApiServerConnection for ApiServerReceiver {
         request: Request<NodeRegistrationRequest>,
     ) -> Result<Response<NodeRegistrationResponse>, Status> {
         logd!(1, "Received RegisterNode request");
-        let req = request.into_inner();
+        common::auth::authorize(&request, "RegisterNode", Role::Operator)?;
+        let mut req = request.into_inner();
+        let version = common::version::accept("RegisterNode", req.api_version)?;
+        common::version::upgrade_registration(&mut req, version);
 
         logd!(
             2,
@@ -176,6 +196,10 @@ impl ApiServerConnection for ApiServerReceiver {
                     last_heartbeat: chrono::Utc::now().timestamp(),
                     created_at: chrono::Utc::now().timestamp(),
                     metadata: req.metadata.clone(),
+                    unschedulable: false,
+                    usage: req.usage,
+                    labels: req.labels.clone(),
+                    taints: req.taints.clone(),
                 };
 
                 // 인코딩을 제거하고 json string으로 저장
@@ -209,6 +233,7 @@ impl ApiServerConnection for ApiServerReceiver {
                     "Node registration successful, cluster token: {}",
                     cluster_token
                 );
+                tokio::spawn(crate::node::autostart::request(req.hostname.clone()));
                 Ok(Response::new(NodeRegistrationResponse {
                     success: true,
                     message: "Node registered successfully".to_string(),
@@ -232,10 +257,81 @@ impl ApiServerConnection for ApiServerReceiver {
         }
     }
 
+    async fn heartbeat(
+        &self,
+        request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        common::auth::authorize(&request, "Heartbeat", Role::Operator)?;
+        let mut req = request.into_inner();
+        let version = common::version::accept("Heartbeat", req.api_version)?;
+        common::version::upgrade_heartbeat(&mut req, version);
+        logd!(1, "Received Heartbeat from node {}", req.node_id);
+
+        let ack = match self.node_manager.update_heartbeat(&req.node_id).await {
+            Ok(()) => true,
+            Err(e) => {
+                logd!(4, "Failed to record heartbeat for {}: {}", req.node_id, e);
+                false
+            }
+        };
+
+        Ok(Response::new(HeartbeatResponse {
+            ack,
+            updated_config: Some(ClusterConfig {
+                master_endpoint: common::apiserver::connect_grpc_server(),
+                heartbeat_interval: common::setting::get_config().liveness.heartbeat_interval
+                    as i32,
+                settings: std::collections::HashMap::new(),
+            }),
+        }))
+    }
+
+    async fn report_status(
+        &self,
+        request: Request<StatusReport>,
+    ) -> Result<Response<StatusAck>, Status> {
+        common::auth::authorize(&request, "ReportStatus", Role::Operator)?;
+        let req = request.into_inner();
+        logd!(1, "Received StatusReport from node {}", req.node_id);
+
+        let Some(usage) = req.usage else {
+            return Ok(Response::new(StatusAck {
+                received: false,
+                message: "Status report carries no usage".to_string(),
+            }));
+        };
+        let (received, message) = match self.node_manager.update_usage(&req.node_id, usage).await {
+            Ok(true) => (true, "Status report recorded".to_string()),
+            Ok(false) => (false, format!("Node {} is not registered", req.node_id)),
+            Err(e) => {
+                logd!(4, "Failed to record status of {}: {}", req.node_id, e);
+                (false, format!("Failed to record status: {}", e))
+            }
+        };
+        Ok(Response::new(StatusAck { received, message }))
+    }
+
+    async fn negotiate_api_version(
+        &self,
+        request: Request<ApiVersionRequest>,
+    ) -> Result<Response<ApiVersionResponse>, Status> {
+        common::auth::authorize(&request, "NegotiateApiVersion", Role::ReadOnly)?;
+        let req = request.into_inner();
+        logd!(
+            2,
+            "{} negotiates API versions {} to {}",
+            req.component,
+            req.min_api_version,
+            req.max_api_version
+        );
+        common::version::respond("apiserver", &req, CAPABILITIES).map(Response::new)
+    }
+
     async fn get_topology(
         &self,
-        _request: Request<GetTopologyRequest>,
+        request: Request<GetTopologyRequest>,
     ) -> Result<Response<GetTopologyResponse>, Status> {
+        common::auth::authorize(&request, "GetTopology", Role::ReadOnly)?;
         match self.registry.get_topology().await {
             Ok(topology) => Ok(Response::new(GetTopologyResponse {
                 topology: Some(topology),
@@ -254,6 +350,7 @@ impl ApiServerConnection for ApiServerReceiver {
         &self,
         request: Request<UpdateTopologyRequest>,
     ) -> Result<Response<UpdateTopologyResponse>, Status> {
+        common::auth::authorize(&request, "UpdateTopology", Role::Operator)?;
         let req = request.into_inner();
 
         if let Some(topology) = req.topology {
@@ -314,6 +411,10 @@ mod tests {
             node_role: NodeRole::Nodeagent.into(),
             resources: Some(create_test_resource_info()),
             metadata,
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -329,6 +430,10 @@ mod tests {
             last_heartbeat: chrono::Utc::now().timestamp(),
             created_at: chrono::Utc::now().timestamp(),
             metadata: HashMap::new(),
+            unschedulable: false,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -629,6 +734,10 @@ mod tests {
             node_role: NodeRole::Bluechi.into(),
             resources: Some(create_test_resource_info()),
             metadata,
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         };
 
         let request = Request::new(registration_request);
@@ -663,4 +772,39 @@ mod tests {
         // Response should be successful if node was registered successfully
         assert!(!response.message.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_heartbeat_returns_cluster_config() {
+        let receiver = ApiServerReceiver::new();
+        let request = Request::new(HeartbeatRequest {
+            node_id: "heartbeat-node-001".to_string(),
+            timestamp: chrono::Utc::now().timestamp(),
+            api_version: common::version::API_VERSION,
+        });
+
+        let result = receiver.heartbeat(request).await;
+        assert!(result.is_ok());
+
+        let response = result.unwrap().into_inner();
+        let config = response.updated_config.expect("cluster config expected");
+        assert_eq!(
+            config.heartbeat_interval as u64,
+            common::setting::get_config().liveness.heartbeat_interval
+        );
+        assert!(config.master_endpoint.ends_with(":47098"));
+    }
+
+    #[tokio::test]
+    async fn test_report_status_without_usage_is_refused() {
+        let receiver = ApiServerReceiver::new();
+        let request = Request::new(StatusReport {
+            node_id: "status-node-001".to_string(),
+            status: NodeStatus::Ready.into(),
+            ..Default::default()
+        });
+
+        let response = receiver.report_status(request).await.unwrap().into_inner();
+        assert!(!response.received);
+        assert!(response.message.contains("no usage"));
+    }
 }
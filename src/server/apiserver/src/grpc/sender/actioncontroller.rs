@@ -0,0 +1,114 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Running gRPC message sending to actioncontroller
+
+use common::actioncontroller::{
+    action_controller_connection_client::ActionControllerConnectionClient, connect_server,
+    AutostartNodeRequest, AutostartNodeResponse, DrainNodeRequest, DrainNodeResponse,
+    RebalanceRequest, RebalanceResponse, RelocateNodeModelsRequest, RelocateNodeModelsResponse,
+    TriggerActionRequest, TriggerActionResponse,
+};
+use tonic::{Request, Response, Status};
+
+/// Ask ActionController to move the models of a failed node onto healthy nodes
+///
+/// ### Parameters
+/// * `request: RelocateNodeModelsRequest` - failed node and relocation candidates
+/// ### Description
+/// Called by the node liveness monitor once a node misses too many heartbeats.
+pub async fn relocate_node_models(
+    request: RelocateNodeModelsRequest,
+) -> Result<Response<RelocateNodeModelsResponse>, Status> {
+    let addr = connect_server();
+    let mut client = ActionControllerConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.relocate_node_models(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
+}
+
+/// Ask ActionController to move the models of a cordoned node onto other nodes
+///
+/// ### Parameters
+/// * `request: DrainNodeRequest` - cordoned node and the nodes that may take over its models
+/// ### Description
+/// Called when an operator drains a node for maintenance.
+pub async fn drain_node(request: DrainNodeRequest) -> Result<Response<DrainNodeResponse>, Status> {
+    let addr = connect_server();
+    let mut client = ActionControllerConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.drain_node(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
+}
+
+/// Ask ActionController to spread the models more evenly over the nodes
+///
+/// ### Parameters
+/// * `request: RebalanceRequest` - schedulable nodes, dry run and move limit
+/// ### Description
+/// Called when an operator rebalances the models, e.g. after failovers.
+pub async fn rebalance(request: RebalanceRequest) -> Result<Response<RebalanceResponse>, Status> {
+    let addr = connect_server();
+    let mut client = ActionControllerConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.rebalance(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
+}
+
+/// Ask ActionController to start the models recorded for a node again
+///
+/// ### Parameters
+/// * `request: AutostartNodeRequest` - node that registered again
+/// ### Description
+/// Called when a node registers, e.g. after a reboot.
+pub async fn autostart_node(
+    request: AutostartNodeRequest,
+) -> Result<Response<AutostartNodeResponse>, Status> {
+    let addr = connect_server();
+    let mut client = ActionControllerConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.autostart_node(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
+}
+
+/// Ask ActionController to run the action of a scenario on its target package
+///
+/// ### Parameters
+/// * `request: TriggerActionRequest` - scenario to run
+/// ### Description
+/// Called when an operator re-triggers a scenario whatever its conditions.
+pub async fn trigger_action(
+    request: TriggerActionRequest,
+) -> Result<Response<TriggerActionResponse>, Status> {
+    let addr = connect_server();
+    let mut client = ActionControllerConnectionClient::new(common::grpc::channel(&addr).await?);
+    match client.trigger_action(Request::new(request)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_relocate_node_models_without_server() {
+        let request = RelocateNodeModelsRequest {
+            failed_node: "failed-node".to_string(),
+            healthy_nodes: vec!["healthy-node".to_string()],
+        };
+
+        // ActionController is not running during unit tests
+        let result = relocate_node_models(request).await;
+        if let Err(status) = result {
+            assert_eq!(status.code(), tonic::Code::Unavailable);
+        }
+    }
+}
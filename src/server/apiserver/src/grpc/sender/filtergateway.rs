@@ -24,10 +24,12 @@ pub async fn send(
     use std::time::Instant;
     let start = Instant::now();
 
-    let mut client = FilterGatewayConnectionClient::connect(connect_server())
-        .await
-        .map_err(|e| Status::unavailable(format!("Failed to connect to FilterGateway: {}", e)))?;
-    let response = client.handle_scenario(Request::new(scenario)).await;
+    let addr = connect_server();
+    let mut client = FilterGatewayConnectionClient::new(common::grpc::channel(&addr).await?);
+    let response = match client.handle_scenario(Request::new(scenario)).await {
+        Err(status) => Err(common::grpc::release_on_failure(&addr, status).await),
+        response => response,
+    };
 
     let elapsed = start.elapsed();
     common::logd!(1, "send: elapsed = {:?}", elapsed);
@@ -122,6 +124,13 @@ spec:
                 desc: format!("Mock handled: {:?}", req.action),
             }))
         }
+
+        async fn push_signal(
+            &self,
+            _request: Request<common::filtergateway::PushSignalRequest>,
+        ) -> Result<Response<common::filtergateway::PushSignalResponse>, Status> {
+            Err(Status::unimplemented("push_signal"))
+        }
     }
 
     /// Starts a mock gRPC server on a random available port
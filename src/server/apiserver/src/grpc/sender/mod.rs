@@ -5,6 +5,7 @@
 
 //! Running gRPC message sending
 
+pub mod actioncontroller;
 pub mod filtergateway;
 pub mod nodeagent;
 pub mod statemanager;
@@ -3,9 +3,13 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 use common::logd;
-use common::nodeagent::fromapiserver::{HandleYamlRequest, HandleYamlResponse};
+use common::nodeagent::fromapiserver::{
+    ContainerExecRequest, ContainerExecResponse, ContainerLogChunk, ContainerLogsRequest,
+    HandleYamlRequest, HandleYamlResponse,
+};
 use common::nodeagent::node_agent_connection_client::NodeAgentConnectionClient;
-use tonic::{Request, Response, Status};
+use tonic::transport::Channel;
+use tonic::{Response, Status, Streaming};
 
 // Send to a specific node using its IP address
 pub async fn send_to_node(
@@ -18,23 +22,24 @@ pub async fn send_to_node(
     } else {
         node_ip.clone()
     };
-    let addr = format!("http://{}:47004", fixed_ip);
+    let addr = common::address::connect_server_at(common::address::Service::NodeAgent, &fixed_ip);
 
     logd!(2, "Attempting to connect to NodeAgent at: {}", addr);
 
     // Attempting to connect with a timeout
     let client_result = tokio::time::timeout(
         std::time::Duration::from_secs(5),
-        NodeAgentConnectionClient::connect(addr.clone()),
+        common::grpc::channel(&addr),
     )
     .await;
 
     match client_result {
-        Ok(Ok(mut client)) => {
+        Ok(Ok(channel)) => {
+            let mut client = NodeAgentConnectionClient::new(channel);
             logd!(2, "Successfully connected to NodeAgent, sending request...");
             match tokio::time::timeout(
                 std::time::Duration::from_secs(1),
-                client.handle_yaml(Request::new(action)),
+                client.handle_yaml(common::auth::request(action)),
             )
             .await
             {
@@ -44,6 +49,7 @@ pub async fn send_to_node(
                         Ok(response)
                     }
                     Err(e) => {
+                        let e = common::grpc::release_on_failure(&addr, e).await;
                         logd!(5, "Error calling NodeAgent handle_yaml: {}", e);
                         Err(Status::internal(format!(
                             "Error calling NodeAgent handle_yaml: {}",
@@ -130,6 +136,63 @@ pub async fn send_guest(
     }
 }
 
+/// Connect to the NodeAgent of a node, waiting at most 5 seconds
+async fn connect(node_ip: &str) -> Result<(String, NodeAgentConnectionClient<Channel>), Status> {
+    let fixed_ip = if node_ip == "0.0.0.0" {
+        "127.0.0.1"
+    } else {
+        node_ip
+    };
+    let addr = common::address::connect_server_at(common::address::Service::NodeAgent, fixed_ip);
+
+    match tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        common::grpc::channel(&addr),
+    )
+    .await
+    {
+        Ok(Ok(channel)) => Ok((addr, NodeAgentConnectionClient::new(channel))),
+        Ok(Err(e)) => Err(Status::unavailable(format!(
+            "Failed to connect to NodeAgent at {}: {}",
+            addr, e
+        ))),
+        Err(_) => Err(Status::deadline_exceeded(format!(
+            "Timeout while connecting to NodeAgent at {}",
+            addr
+        ))),
+    }
+}
+
+/// Open the log stream of a container on a node
+pub async fn get_container_logs(
+    request: ContainerLogsRequest,
+    node_ip: &str,
+) -> Result<Streaming<ContainerLogChunk>, Status> {
+    let (addr, mut client) = connect(node_ip).await?;
+    match client
+        .get_container_logs(common::auth::request(request))
+        .await
+    {
+        Ok(response) => Ok(response.into_inner()),
+        Err(e) => Err(common::grpc::release_on_failure(&addr, e).await),
+    }
+}
+
+/// Run a diagnostic command in a container on a node
+pub async fn exec_in_container(
+    request: ContainerExecRequest,
+    node_ip: &str,
+) -> Result<ContainerExecResponse, Status> {
+    let (addr, mut client) = connect(node_ip).await?;
+    match client
+        .exec_in_container(common::auth::request(request))
+        .await
+    {
+        Ok(response) => Ok(response.into_inner()),
+        Err(e) => Err(common::grpc::release_on_failure(&addr, e).await),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,6 +253,10 @@ spec:
             last_heartbeat: chrono::Utc::now().timestamp(),
             created_at: chrono::Utc::now().timestamp(),
             metadata: HashMap::new(),
+            unschedulable: false,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -229,8 +296,11 @@ spec:
         match error.code() {
             Code::Unavailable => {
                 assert!(error.message().contains(&format!(
-                    "Failed to connect to NodeAgent at http://{}:47004",
-                    node_ip
+                    "Failed to connect to NodeAgent at {}",
+                    common::address::connect_server_at(
+                        common::address::Service::NodeAgent,
+                        &node_ip
+                    )
                 )));
             }
             Code::DeadlineExceeded => {
@@ -13,22 +13,25 @@
 //! and comprehensive error handling to ensure reliable communication with the
 //! StateManager in the PICCOLO framework.
 
+use common::grpc::outbox::Outbox;
 use common::statemanager::{
-    connect_server, state_manager_connection_client::StateManagerConnectionClient, StateChange,
-    StateChangeResponse,
+    connect_server, state_manager_connection_client::StateManagerConnectionClient,
+    SimulationRequest, SimulationResponse, StateChange, StateChangeResponse,
 };
-use tonic::{Request, Status};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tonic::Status;
 
 /// StateManager gRPC client for ApiServer component.
 ///
-/// This client manages the gRPC connection to the StateManager service and provides
-/// methods for sending state change requests. It implements lazy connection establishment
-/// to optimize resource usage and provides automatic reconnection capabilities.
+/// This client sends state change requests to the StateManager service over the
+/// process-wide channel pool of `common::grpc`.
 ///
 /// # Connection Management
 /// - Establishes connections on first use (lazy initialization)
-/// - Reuses existing connections for multiple requests
-/// - Handles connection failures gracefully with proper error reporting
+/// - Reuses the pooled connection for multiple requests
+/// - Retries transient failures with backoff and stops calling an unreachable
+///   StateManager through the circuit breaker of `common::grpc::retry`
 /// - Provides thread-safe access through cloning capability
 ///
 /// # ASIL Compliance
@@ -37,14 +40,7 @@ use tonic::{Request, Status};
 /// - Provides comprehensive tracking through transition IDs
 /// - Includes context information for safety analysis and audit trails
 #[derive(Clone)]
-pub struct StateManagerSender {
-    /// Cached gRPC client connection to the StateManager service.
-    ///
-    /// This connection is established lazily on the first request and reused
-    /// for subsequent requests to optimize performance. Set to None initially
-    /// and populated when ensure_connected() is called.
-    client: Option<StateManagerConnectionClient<tonic::transport::Channel>>,
-}
+pub struct StateManagerSender {}
 
 impl Default for StateManagerSender {
     /// Creates a new StateManagerSender with default settings.
@@ -66,47 +62,7 @@ impl StateManagerSender {
     /// # Returns
     /// * `Self` - New StateManagerSender instance ready for use
     pub fn new() -> Self {
-        Self { client: None }
-    }
-
-    /// Ensures a gRPC connection to the StateManager exists and is ready for use.
-    ///
-    /// This method implements lazy connection establishment by checking if a connection
-    /// already exists and creating one if necessary. It uses the common::statemanager
-    /// configuration to determine the StateManager's network location.
-    ///
-    /// # Connection Process
-    /// 1. Check if a connection already exists
-    /// 2. If not, attempt to establish a new gRPC connection
-    /// 3. Store the connection for reuse in subsequent requests
-    /// 4. Return success or detailed error information
-    ///
-    /// # Returns
-    /// * `Result<(), Status>` - Success if connection is available, error otherwise
-    ///
-    /// # Errors
-    /// * `Status::unknown` - Connection establishment failed (network, service unavailable, etc.)
-    ///
-    /// # Future Enhancements
-    /// - Add connection health checking and automatic reconnection
-    /// - Implement exponential backoff for connection retries
-    /// - Add connection pooling for high-throughput scenarios
-    async fn ensure_connected(&mut self) -> Result<(), Status> {
-        if self.client.is_none() {
-            match StateManagerConnectionClient::connect(connect_server()).await {
-                Ok(client) => {
-                    self.client = Some(client);
-                    Ok(())
-                }
-                Err(e) => Err(Status::unknown(format!(
-                    "Failed to connect to StateManager: {}",
-                    e
-                ))),
-            }
-        } else {
-            // Connection already exists and ready for use
-            Ok(())
-        }
+        Self {}
     }
 
     /// Sends a state change message to the StateManager service.
@@ -116,11 +72,16 @@ impl StateManagerSender {
     /// management, request transmission, and response processing.
     ///
     /// # Request Processing Flow
-    /// 1. Ensure gRPC connection is established and ready
+    /// 1. Take the pooled gRPC channel to the StateManager
     /// 2. Create gRPC request wrapper with StateChange message
-    /// 3. Send request to StateManager via gRPC
+    /// 3. Send request to StateManager via gRPC, retrying transient failures
     /// 4. Receive and return StateChangeResponse with tracking information
     ///
+    /// A change the StateManager cannot be reached for is stored in the outbox
+    /// of `common::grpc::outbox` and delivered by [`run_outbox`] once it is
+    /// back, later changes queue behind it. The response of a queued change
+    /// carries `ERROR_CODE_RESOURCE_UNAVAILABLE`.
+    ///
     /// # Arguments
     /// * `state_change` - Complete StateChange message containing:
     ///   - Resource identification (type enum and name)
@@ -135,8 +96,8 @@ impl StateManagerSender {
     ///   - Error codes and details if applicable
     ///
     /// # Errors
-    /// * `Status::unknown` - Connection failure or client not connected
-    /// * `Status::unavailable` - StateManager service unavailable
+    /// * `Status::unavailable` - StateManager service unavailable after all retries,
+    ///   or its circuit breaker is open
     /// * `Status::invalid_argument` - Malformed StateChange message
     /// * `Status::deadline_exceeded` - Request timeout (ASIL timing violation)
     ///
@@ -149,19 +110,58 @@ impl StateManagerSender {
         &mut self,
         state_change: StateChange,
     ) -> Result<tonic::Response<StateChangeResponse>, Status> {
-        // Ensure we have an active gRPC connection before sending
-        self.ensure_connected().await?;
+        outbox().send(state_change, deliver).await
+    }
 
-        if let Some(client) = &mut self.client {
-            // Send the state change message via gRPC
-            client.send_state_change(Request::new(state_change)).await
-        } else {
-            // This should never happen due to ensure_connected, but provide safety fallback
-            Err(Status::unknown("Client not connected"))
-        }
+    /// Asks the StateManager which transitions state changes would make.
+    ///
+    /// Nothing is persisted or dispatched by a simulation, so failed calls
+    /// are retried like state changes.
+    ///
+    /// # Arguments
+    /// * `request` - State changes applied in order to a snapshot of the state machine
+    ///
+    /// # Returns
+    /// * `Result<tonic::Response<SimulationResponse>, Status>` - Transitions the
+    ///   changes would make, including cascaded model transitions
+    pub async fn simulate_state_changes(
+        &mut self,
+        request: SimulationRequest,
+    ) -> Result<tonic::Response<SimulationResponse>, Status> {
+        let request = &request;
+        common::grpc::retry::call(&connect_server(), |channel| async move {
+            StateManagerConnectionClient::new(channel)
+                .simulate_state_changes(common::auth::request(request.clone()))
+                .await
+        })
+        .await
     }
 }
 
+/// Outbox of the StateChanges of the ApiServer
+fn outbox() -> &'static Outbox {
+    static OUTBOX: OnceLock<Outbox> = OnceLock::new();
+    OUTBOX.get_or_init(|| Outbox::new("apiserver"))
+}
+
+/// Send a StateChange to the StateManager, retrying transient failures
+async fn deliver(
+    state_change: StateChange,
+) -> Result<tonic::Response<StateChangeResponse>, Status> {
+    let state_change = &state_change;
+    common::grpc::retry::call(&connect_server(), |channel| async move {
+        StateManagerConnectionClient::new(channel)
+            .send_state_change(common::auth::request(state_change.clone()))
+            .await
+    })
+    .await
+}
+
+/// Deliver the StateChanges queued while the StateManager was unreachable
+pub async fn run_outbox(interval: Duration) {
+    outbox().run(interval, deliver).await
+}
+
 // ========================================
 // UNIT TESTS
 // ========================================
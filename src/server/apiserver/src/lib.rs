@@ -3,8 +3,11 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 pub mod artifact;
+pub mod container;
 pub mod diagnostics;
 pub mod grpc;
 pub mod manager;
 pub mod node;
 pub mod route;
+pub mod scenario;
+pub mod snapshot;
@@ -15,13 +15,17 @@
 //!   that a filter can be created.
 
 mod artifact;
+mod container;
 mod grpc;
 mod manager;
 mod node;
 mod route;
+mod scenario;
+mod snapshot;
 
 use common::logd;
 use common::logd::logger;
+use common::startup::Dependency;
 
 /// Main function of Piccolo API Server
 #[cfg(feature = "tarpaulin_include")]
@@ -34,6 +38,13 @@ fn main() {
 async fn main() {
     let _ = logger::init_async_logger("apiserver").await;
     logd!(1, "initiailize api server");
+    common::health::init("apiserver", true);
+    common::address::validate_or_exit("apiserver");
+    // Nodes are registered in etcd, open no listener before it answers
+    if let Err(e) = common::startup::wait("apiserver", &[Dependency::Etcd]).await {
+        logd!(6, "{e}");
+        std::process::exit(1);
+    }
 
     manager::initialize().await
 }
@@ -9,7 +9,8 @@ use common::apiserver::api_server_connection_server::ApiServerConnectionServer;
 use common::filtergateway::{Action, HandleScenarioRequest};
 use common::logd;
 use common::nodeagent::fromapiserver::HandleYamlRequest;
-use tonic::transport::Server;
+use common::spec::artifact::package::PackageDiff;
+use common::statemanager::{ResourceType, SimulatedTransition, SimulationRequest, StateChange};
 
 /// Launch REST API listener, gRPC server, and reload scenario data in etcd
 pub async fn initialize() {
@@ -19,14 +20,32 @@ pub async fn initialize() {
     } else {
         logd!(2, "Host node registered successfully");
     }
+    common::health::set_started();
 
     tokio::join!(
         crate::route::launch_tcp_listener(),
         start_grpc_server(),
-        reload()
+        reload(),
+        start_liveness_monitor(),
+        crate::artifact::gc::run(),
+        crate::grpc::sender::statemanager::run_outbox(std::time::Duration::from_secs(
+            common::grpc::outbox::FLUSH_INTERVAL_SECS
+        ))
     );
 }
 
+/// Track node heartbeats and trigger failover for unresponsive nodes
+async fn start_liveness_monitor() {
+    match crate::node::NodeManager::new() {
+        Ok(node_manager) => {
+            crate::node::liveness::NodeLivenessMonitor::new(node_manager)
+                .run()
+                .await
+        }
+        Err(e) => logd!(5, "Failed to start node liveness monitor: {:?}", e),
+    }
+}
+
 /// Start gRPC server for node communications
 async fn start_grpc_server() {
     let addr = common::apiserver::open_grpc_server()
@@ -37,8 +56,19 @@ async fn start_grpc_server() {
 
     logd!(3, "ApiServer gRPC listening on {}", addr);
 
-    let _ = Server::builder()
-        .add_service(ApiServerConnectionServer::new(grpc_service))
+    let mut server = match common::tls::server() {
+        Ok(server) => server,
+        Err(e) => {
+            logd!(5, "ApiServer gRPC not started: {}", e);
+            return;
+        }
+    };
+    let _ = server
+        .add_service(ApiServerConnectionServer::with_interceptor(
+            grpc_service,
+            common::auth::authenticate,
+        ))
+        .add_service(common::health::grpc_service())
         .serve(addr)
         .await;
 }
@@ -87,6 +117,10 @@ async fn register_host_node() -> Result<(), Box<dyn std::error::Error + Send + S
         resources: None,
         node_type,
         node_role,
+        api_version: common::version::API_VERSION,
+        usage: None,
+        labels: std::collections::HashMap::new(),
+        taints: Vec::new(),
     };
 
     // NodeManager를 사용하여 노드 등록
@@ -113,20 +147,22 @@ async fn register_host_node() -> Result<(), Box<dyn std::error::Error + Send + S
 /// ### Description
 /// This function is called once when the apiserver starts.
 async fn reload() {
-    let scenarios_result = crate::artifact::data::read_all_scenario_from_etcd().await;
-
-    if let Ok(scenarios) = scenarios_result {
-        for scenario in scenarios {
-            let req = HandleScenarioRequest {
-                action: Action::Apply.into(),
-                scenario,
-            };
-            if let Err(status) = crate::grpc::sender::filtergateway::send(req).await {
-                logd!(4, "{:#?}", status);
-            }
+    let scenarios = match crate::artifact::data::read_all_scenario_from_etcd().await {
+        Ok(scenarios) => scenarios,
+        Err(e) => {
+            logd!(2, "{:#?}", e);
+            return;
+        }
+    };
+
+    for scenario in scenarios {
+        let req = HandleScenarioRequest {
+            action: Action::Apply.into(),
+            scenario,
+        };
+        if let Err(status) = crate::grpc::sender::filtergateway::send(req).await {
+            logd!(4, "{:#?}", status);
         }
-    } else {
-        logd!(2, "{:#?}", scenarios_result);
     }
 }
 
@@ -138,15 +174,43 @@ async fn reload() {
 /// write artifact in etcd
 /// (optional) make yaml, kube files for Bluechi
 /// send a gRPC message to gateway
-pub async fn apply_artifact(body: &str) -> common::Result<()> {
-    let scenario = crate::artifact::apply(body).await?;
+/// ### Returns
+/// * `PackageDiff` - Pods of the package the artifact added, changed or removed
+pub async fn apply_artifact(body: &str) -> common::Result<PackageDiff> {
+    let (scenario, diff) = crate::artifact::apply(body).await?;
 
     let req: HandleScenarioRequest = HandleScenarioRequest {
         action: Action::Apply.into(),
         scenario,
     };
     crate::grpc::sender::filtergateway::send(req).await?;
-    Ok(())
+    Ok(diff)
+}
+
+/// Apply many artifact files at once
+///
+/// ### Parameters
+/// * `files: &[ManifestFile]` - yaml files unpacked from the request body
+/// ### Description
+/// write the artifacts of every valid file in etcd in dependency order
+/// send a gRPC message to gateway for each applied scenario
+/// ### Returns
+/// * `Vec<FileResult>` - outcome of each file
+pub async fn apply_bulk(
+    files: &[crate::artifact::bulk::ManifestFile],
+) -> Vec<crate::artifact::bulk::FileResult> {
+    let (mut results, scenarios) = crate::artifact::bulk::apply_files(files).await;
+
+    for (file, scenario) in scenarios {
+        let req = HandleScenarioRequest {
+            action: Action::Apply.into(),
+            scenario,
+        };
+        if let Err(e) = crate::grpc::sender::filtergateway::send(req).await {
+            results[file].error = Some(format!("Failed to hand over scenario: {}", e));
+        }
+    }
+    results
 }
 
 /// Withdraw downloaded artifact
@@ -169,6 +233,77 @@ pub async fn withdraw_artifact(body: &str) -> common::Result<()> {
     Ok(())
 }
 
+/// Restore a snapshot of the system state
+///
+/// ### Parameters
+/// * `archive: &[u8]` - tar.gz archive of a snapshot
+/// * `options` - whether to overwrite existing artifacts and restore nodes
+/// ### Description
+/// write the keys of the snapshot in etcd
+/// send a gRPC message to gateway for each restored scenario
+pub async fn restore_snapshot(
+    archive: &[u8],
+    options: &crate::snapshot::RestoreOptions,
+) -> common::Result<crate::snapshot::RestoreResult> {
+    let result = crate::snapshot::restore(archive, options).await?;
+    reload().await;
+    Ok(result)
+}
+
+/// State change of a simulation request
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct SimulatedChange {
+    /// `scenario`, `package`, `model`, `volume`, `network` or `node`
+    resource_type: String,
+    resource_name: String,
+    /// State assumed for resources the StateManager does not track yet
+    #[serde(default)]
+    current_state: String,
+    target_state: String,
+}
+
+/// Build the StateManager simulation request of a list of state changes
+///
+/// ### Parameters
+/// * `body: &str` - yaml or json list of state changes
+fn simulation_request(body: &str) -> common::Result<SimulationRequest> {
+    let changes: Vec<SimulatedChange> = serde_yaml::from_str(body)?;
+    let changes = changes
+        .into_iter()
+        .map(|change| {
+            let name = format!("RESOURCE_TYPE_{}", change.resource_type.to_uppercase());
+            let resource_type = ResourceType::from_str_name(&name)
+                .ok_or_else(|| format!("unknown resource type '{}'", change.resource_type))?;
+            Ok(StateChange {
+                resource_type: resource_type as i32,
+                resource_name: change.resource_name,
+                current_state: change.current_state,
+                target_state: change.target_state,
+                source: "apiserver".to_string(),
+                ..Default::default()
+            })
+        })
+        .collect::<common::Result<Vec<_>>>()?;
+    Ok(SimulationRequest { changes })
+}
+
+/// Simulate state changes without persisting or dispatching anything
+///
+/// ### Parameters
+/// * `body: &str` - yaml or json list of state changes
+/// ### Description
+/// send the changes to StateManager, which applies them in order
+/// to a snapshot of its state machine
+/// ### Returns
+/// * `Vec<SimulatedTransition>` - transitions the changes would make
+pub async fn simulate(body: &str) -> common::Result<Vec<SimulatedTransition>> {
+    let request = simulation_request(body)?;
+    let mut sender = crate::grpc::sender::statemanager::StateManagerSender::new();
+    let response = sender.simulate_state_changes(request).await?;
+    Ok(response.into_inner().transitions)
+}
+
 //UNIT Test Cases
 #[cfg(test)]
 mod tests {
@@ -631,6 +766,13 @@ spec:
                 desc: "Success".to_string(),
             }))
         }
+
+        async fn push_signal(
+            &self,
+            _request: Request<common::filtergateway::PushSignalRequest>,
+        ) -> Result<Response<common::filtergateway::PushSignalResponse>, Status> {
+            Err(Status::unimplemented("push_signal"))
+        }
     }
 
     /// Starts the mock gRPC server asynchronously on a random port.
@@ -674,7 +816,7 @@ spec:
         body: &str,
         grpc_addr: SocketAddr,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let scenario = crate::artifact::apply(body).await?;
+        let (scenario, _) = crate::artifact::apply(body).await?;
 
         // Prepare the gRPC request with Apply action
         let req = HandleScenarioRequest {
@@ -940,6 +1082,37 @@ spec:
         assert!(result.is_ok(), "send_download_request() failed to execute");
     }
 
+    #[test]
+    fn test_simulation_request() {
+        let body = r#"
+- resourceType: scenario
+  resourceName: antipinch
+  currentState: idle
+  targetState: waiting
+- resourceType: NETWORK
+  resourceName: antipinch-core
+  targetState: ready
+"#;
+        let request = simulation_request(body).unwrap();
+        assert_eq!(request.changes.len(), 2);
+        assert_eq!(
+            request.changes[0].resource_type,
+            common::statemanager::ResourceType::Scenario as i32
+        );
+        assert_eq!(request.changes[0].current_state, "idle");
+        assert_eq!(
+            request.changes[1].resource_type,
+            common::statemanager::ResourceType::Network as i32
+        );
+        assert!(request.changes[1].current_state.is_empty());
+        assert_eq!(request.changes[1].source, "apiserver");
+
+        let unknown = "[{resourceType: robot, resourceName: r, targetState: running}]";
+        assert!(simulation_request(unknown).is_err());
+        let misspelled = "[{resourceType: model, resourceName: m, target: running}]";
+        assert!(simulation_request(misspelled).is_err());
+    }
+
     // Test for `reload()` - successful case
     #[tokio::test]
     async fn test_reload_success() {
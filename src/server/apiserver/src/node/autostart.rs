@@ -0,0 +1,51 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Restart of the models of a node that registered again
+//!
+//! A NodeAgent registers whenever it starts, so also after its node rebooted
+//! and lost its containers. ActionController is asked to start the models
+//! recorded as running on the node. The node's gRPC server and, when the
+//! whole vehicle boots, ActionController may come up after the
+//! registration, so the request waits a moment and is retried while
+//! ActionController is unavailable.
+
+use common::actioncontroller::AutostartNodeRequest;
+use common::logd;
+use std::time::Duration;
+
+/// Wait after the registration before the first request
+const DELAY: Duration = Duration::from_secs(5);
+
+/// Requests sent before giving up
+const ATTEMPTS: u32 = 6;
+
+/// Ask ActionController to start the models recorded for `node`
+pub async fn request(node: String) {
+    for attempt in 1..=ATTEMPTS {
+        tokio::time::sleep(DELAY * attempt).await;
+        let request = AutostartNodeRequest { node: node.clone() };
+        match crate::grpc::sender::actioncontroller::autostart_node(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                logd!(
+                    3,
+                    "Autostart of {}: started {:?}, failed {:?}",
+                    node,
+                    response.started_models,
+                    response.failed_models
+                );
+                return;
+            }
+            Err(status) if status.code() == tonic::Code::Unavailable && attempt < ATTEMPTS => {
+                logd!(2, "Autostart of {} postponed: {}", node, status.message());
+            }
+            Err(status) => {
+                logd!(5, "Autostart of {} failed: {}", node, status.message());
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Labels and taints of the nodes
+//!
+//! NodeAgents register with the labels and taints of their configuration.
+//! Models are placed by them: the ActionController only places a model on
+//! a node carrying every label of its `nodeSelector` and whose `NoSchedule`
+//! taints it tolerates. Label keys are expected among the
+//! `placement.label_keys` of the settings: a model selecting another key is
+//! rejected, so that a typo does not silently keep it off every node, while
+//! a node registering with one is only warned about, so that nodes
+//! configured before a key was listed keep registering.
+
+use crate::node::NodeManager;
+use common::apiserver::NodeInfo;
+use common::logd;
+use common::nodeagent::fromapiserver::{NodeStatus, Taint};
+use common::spec::k8s::pod::{TAINT_NO_SCHEDULE, TAINT_PREFER_NO_SCHEDULE};
+use std::collections::HashMap;
+
+/// A node with what models are placed by
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct NodeLabels {
+    pub node: String,
+    pub status: String,
+    pub unschedulable: bool,
+    pub labels: HashMap<String, String>,
+    pub taints: Vec<Taint>,
+}
+
+impl From<NodeInfo> for NodeLabels {
+    fn from(node: NodeInfo) -> Self {
+        let status = NodeStatus::try_from(node.status)
+            .unwrap_or_default()
+            .as_str_name()
+            .trim_start_matches("NODE_STATUS_")
+            .to_string();
+        NodeLabels {
+            node: node.hostname,
+            status,
+            unschedulable: node.unschedulable,
+            labels: node.labels,
+            taints: node.taints,
+        }
+    }
+}
+
+/// Check the labels and taints a node registers with
+///
+/// Unknown label keys are logged and returned, only invalid taints are
+/// rejected.
+pub fn validate(
+    hostname: &str,
+    labels: &HashMap<String, String>,
+    taints: &[Taint],
+) -> Result<Vec<String>, String> {
+    let placement = &common::setting::get_config().placement;
+    let unknown = placement.unknown_keys(labels.keys());
+    if !unknown.is_empty() {
+        logd!(
+            4,
+            "Node {} registers with unknown label keys {}",
+            hostname,
+            unknown.join(", ")
+        );
+    }
+    for taint in taints {
+        if taint.key.is_empty() {
+            return Err("taint without key".to_string());
+        }
+        if ![TAINT_NO_SCHEDULE, TAINT_PREFER_NO_SCHEDULE].contains(&taint.effect.as_str()) {
+            return Err(format!(
+                "taint {} has unknown effect '{}'",
+                taint.key, taint.effect
+            ));
+        }
+    }
+    Ok(unknown)
+}
+
+/// Labels of a `key=value,key=value` selector, all nodes for an empty one
+pub fn parse_selector(selector: &str) -> Result<HashMap<String, String>, String> {
+    selector
+        .split(',')
+        .map(str::trim)
+        .filter(|term| !term.is_empty())
+        .map(|term| match term.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(format!("invalid selector term '{}'", term)),
+        })
+        .collect()
+}
+
+/// Registered nodes carrying every label of `selector`, by name
+pub async fn list(selector: &HashMap<String, String>) -> Result<Vec<NodeLabels>, String> {
+    let nodes = NodeManager
+        .get_all_nodes()
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut listed: Vec<NodeLabels> = nodes
+        .into_iter()
+        .filter(|node| {
+            selector
+                .iter()
+                .all(|(key, value)| node.labels.get(key) == Some(value))
+        })
+        .map(NodeLabels::from)
+        .collect();
+    listed.sort_by(|a, b| a.node.cmp(&b.node));
+    Ok(listed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn taint(key: &str, effect: &str) -> Taint {
+        Taint {
+            key: key.to_string(),
+            value: "x".to_string(),
+            effect: effect.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let labels = HashMap::from([("gpu".to_string(), "true".to_string())]);
+        assert_eq!(
+            validate("n", &labels, &[taint("safety-domain", "NoSchedule")]),
+            Ok(Vec::new())
+        );
+
+        let unknown = HashMap::from([("gpux".to_string(), "true".to_string())]);
+        assert_eq!(validate("n", &unknown, &[]), Ok(vec!["gpux".to_string()]));
+        assert!(validate("n", &labels, &[taint("", "NoSchedule")]).is_err());
+        assert!(validate("n", &labels, &[taint("gpu", "NoExecute")]).is_err());
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        assert_eq!(
+            parse_selector("gpu=true, safety-domain=ASIL-B").unwrap(),
+            HashMap::from([
+                ("gpu".to_string(), "true".to_string()),
+                ("safety-domain".to_string(), "ASIL-B".to_string()),
+            ])
+        );
+        assert!(parse_selector("").unwrap().is_empty());
+        assert!(parse_selector("gpu").is_err());
+        assert!(parse_selector("=true").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_by_selector() {
+        common::etcd::use_in_memory_store();
+        for (name, gpu) in [
+            ("labels-b", "true"),
+            ("labels-a", "true"),
+            ("labels-c", "false"),
+        ] {
+            let node = NodeInfo {
+                hostname: name.to_string(),
+                status: NodeStatus::Ready as i32,
+                labels: HashMap::from([
+                    ("gpu".to_string(), gpu.to_string()),
+                    ("zone".to_string(), "labels-test".to_string()),
+                ]),
+                ..Default::default()
+            };
+            common::etcd::put(
+                &format!("cluster/nodes/{}", name),
+                &serde_json::to_string(&node).unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let selector = parse_selector("zone=labels-test,gpu=true").unwrap();
+        let listed = list(&selector).await.unwrap();
+        let names: Vec<&str> = listed.iter().map(|n| n.node.as_str()).collect();
+        assert_eq!(names, vec!["labels-a", "labels-b"]);
+        assert_eq!(listed[0].status, "READY");
+
+        for name in ["labels-a", "labels-b", "labels-c"] {
+            common::etcd::delete(&format!("cluster/nodes/{}", name))
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,261 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Heartbeat-driven node liveness tracking and failover
+
+use crate::node::status::NodeStatusManager;
+use crate::node::NodeManager;
+use common::actioncontroller::RelocateNodeModelsRequest;
+use common::apiserver::NodeInfo;
+use common::logd;
+use common::nodeagent::fromapiserver::NodeStatus;
+use std::time::Duration;
+
+/// Periodically checks node heartbeats and hands failed nodes over for failover
+pub struct NodeLivenessMonitor {
+    node_manager: NodeManager,
+    status_manager: NodeStatusManager,
+    /// Expected interval between heartbeats, in seconds
+    heartbeat_interval: u64,
+    /// Number of missed heartbeats tolerated before a node is NotReady
+    miss_threshold: u32,
+}
+
+impl NodeLivenessMonitor {
+    /// Create a monitor using the liveness section of settings.yaml
+    pub fn new(node_manager: NodeManager) -> Self {
+        let liveness = &common::setting::get_config().liveness;
+        Self::with_params(
+            node_manager,
+            liveness.heartbeat_interval,
+            liveness.miss_threshold,
+        )
+    }
+
+    /// Create a monitor with explicit heartbeat parameters
+    pub fn with_params(
+        node_manager: NodeManager,
+        heartbeat_interval: u64,
+        miss_threshold: u32,
+    ) -> Self {
+        Self {
+            node_manager,
+            status_manager: NodeStatusManager,
+            heartbeat_interval: heartbeat_interval.max(1),
+            miss_threshold: miss_threshold.max(1),
+        }
+    }
+
+    /// Seconds without a heartbeat after which a node is considered failed
+    pub fn heartbeat_timeout(&self) -> u64 {
+        self.heartbeat_interval * self.miss_threshold as u64
+    }
+
+    /// Ready nodes whose last heartbeat is older than the timeout
+    pub fn find_failed_nodes<'a>(&self, nodes: &'a [NodeInfo]) -> Vec<&'a NodeInfo> {
+        nodes
+            .iter()
+            .filter(|node| node.status == NodeStatus::Ready as i32)
+            .filter(|node| {
+                !self
+                    .status_manager
+                    .is_node_healthy(node, self.heartbeat_timeout())
+            })
+            .collect()
+    }
+
+    /// Hostnames of Ready nodes that are still sending heartbeats and are
+    /// not cordoned, which may take over models of other nodes
+    pub fn find_healthy_nodes(&self, nodes: &[NodeInfo]) -> Vec<String> {
+        nodes
+            .iter()
+            .filter(|node| node.status == NodeStatus::Ready as i32 && !node.unschedulable)
+            .filter(|node| {
+                self.status_manager
+                    .is_node_healthy(node, self.heartbeat_timeout())
+            })
+            .map(|node| node.hostname.clone())
+            .collect()
+    }
+
+    /// Run a single liveness check over all registered nodes
+    ///
+    /// ### Returns
+    /// * hostnames of the nodes that were newly marked NotReady
+    pub async fn check_once(
+        &self,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let nodes = self.node_manager.get_all_nodes().await?;
+        let healthy_nodes = self.find_healthy_nodes(&nodes);
+        let mut failed = Vec::new();
+
+        for node in self.find_failed_nodes(&nodes) {
+            logd!(
+                4,
+                "Node {} missed {} heartbeats (last at {}), marking NotReady",
+                node.hostname,
+                self.miss_threshold,
+                node.last_heartbeat
+            );
+            if !self.node_manager.mark_not_ready(&node.hostname).await? {
+                continue;
+            }
+            failed.push(node.hostname.clone());
+            self.request_failover(&node.hostname, &healthy_nodes).await;
+        }
+
+        Ok(failed)
+    }
+
+    /// Ask ActionController to re-place the models of a failed node
+    async fn request_failover(&self, failed_node: &str, healthy_nodes: &[String]) {
+        if healthy_nodes.is_empty() {
+            logd!(
+                5,
+                "No healthy node available to take over models of {}",
+                failed_node
+            );
+            return;
+        }
+
+        let request = RelocateNodeModelsRequest {
+            failed_node: failed_node.to_string(),
+            healthy_nodes: healthy_nodes.to_vec(),
+        };
+        match crate::grpc::sender::actioncontroller::relocate_node_models(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                logd!(
+                    3,
+                    "Relocated models of {}: {:?} ({})",
+                    failed_node,
+                    response.relocated_models,
+                    response.desc
+                );
+            }
+            Err(e) => logd!(5, "Failed to relocate models of {}: {}", failed_node, e),
+        }
+    }
+
+    /// Check node liveness every heartbeat interval until the task is dropped
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.heartbeat_interval));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.check_once().await {
+                logd!(4, "Node liveness check failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::nodeagent::fromapiserver::NodeRole;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn create_test_node(hostname: &str, last_heartbeat: i64, status: NodeStatus) -> NodeInfo {
+        NodeInfo {
+            node_id: hostname.to_string(),
+            hostname: hostname.to_string(),
+            ip_address: "192.168.1.100".to_string(),
+            node_type: 2,
+            node_role: NodeRole::Nodeagent.into(),
+            status: status.into(),
+            resources: None,
+            last_heartbeat,
+            created_at: 1234567890,
+            metadata: std::collections::HashMap::new(),
+            unschedulable: false,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
+        }
+    }
+
+    fn create_monitor() -> NodeLivenessMonitor {
+        NodeLivenessMonitor::with_params(NodeManager::new().unwrap(), 10, 3)
+    }
+
+    #[test]
+    fn test_heartbeat_timeout() {
+        assert_eq!(create_monitor().heartbeat_timeout(), 30);
+
+        // Zero values are clamped so the monitor never spins
+        let monitor = NodeLivenessMonitor::with_params(NodeManager::new().unwrap(), 0, 0);
+        assert_eq!(monitor.heartbeat_timeout(), 1);
+    }
+
+    #[test]
+    fn test_find_failed_and_healthy_nodes() {
+        let monitor = create_monitor();
+        let nodes = vec![
+            create_test_node("alive", now() - 5, NodeStatus::Ready),
+            create_test_node("missed-few", now() - 25, NodeStatus::Ready),
+            create_test_node("dead", now() - 31, NodeStatus::Ready),
+            create_test_node("already-down", now() - 300, NodeStatus::NotReady),
+            create_test_node("maintenance", now() - 300, NodeStatus::Maintenance),
+            NodeInfo {
+                unschedulable: true,
+                ..create_test_node("cordoned", now() - 5, NodeStatus::Ready)
+            },
+        ];
+
+        let failed: Vec<&str> = monitor
+            .find_failed_nodes(&nodes)
+            .iter()
+            .map(|n| n.hostname.as_str())
+            .collect();
+        assert_eq!(failed, vec!["dead"]);
+
+        let healthy = monitor.find_healthy_nodes(&nodes);
+        assert_eq!(healthy, vec!["alive".to_string(), "missed-few".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_check_once_marks_failed_nodes_not_ready() {
+        common::etcd::use_in_memory_store();
+        let nodes = [
+            create_test_node("liveness-alive", now() - 5, NodeStatus::Ready),
+            create_test_node("liveness-dead", now() - 31, NodeStatus::Ready),
+            create_test_node("liveness-down", now() - 300, NodeStatus::NotReady),
+        ];
+        for node in &nodes {
+            let key = format!("cluster/nodes/{}", node.hostname);
+            common::etcd::put(&key, &serde_json::to_string(node).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let monitor = create_monitor();
+        let failed = monitor.check_once().await.unwrap();
+        assert!(failed.contains(&"liveness-dead".to_string()));
+        assert!(!failed.contains(&"liveness-alive".to_string()));
+        assert!(!failed.contains(&"liveness-down".to_string()));
+
+        let node_manager = NodeManager::new().unwrap();
+        let status = |node: Option<NodeInfo>| node.unwrap().status;
+        assert_eq!(
+            status(node_manager.get_node("liveness-dead").await.unwrap()),
+            NodeStatus::NotReady as i32
+        );
+        assert_eq!(
+            status(node_manager.get_node("liveness-alive").await.unwrap()),
+            NodeStatus::Ready as i32
+        );
+
+        // A node already marked NotReady is not reported again
+        let failed = monitor.check_once().await.unwrap();
+        assert!(!failed.contains(&"liveness-dead".to_string()));
+    }
+}
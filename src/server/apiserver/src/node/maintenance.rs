@@ -0,0 +1,317 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Cordon and drain of nodes for maintenance
+//!
+//! A cordoned node is marked `unschedulable` in its `cluster/nodes/{hostname}`
+//! entry. The ActionController places no new models on it and the liveness
+//! monitor does not fail models over to it, while the models it runs keep
+//! running. Draining cordons the node and has the ActionController move its
+//! models to the schedulable Ready nodes, each started on its new node
+//! before it is stopped on the drained one. Uncordoning makes the node
+//! schedulable again, models moved away stay where they are.
+//!
+//! Every change is reported to the StateManager as the Node state of the
+//! node: schedulable, cordoned, draining and drained.
+//!
+//! Rebalancing has the ActionController spread the models over the
+//! schedulable Ready nodes again, e.g. once the nodes that failed or were
+//! drained came back. A dry run only reports the moves it would make.
+
+use crate::grpc::sender::statemanager::StateManagerSender;
+use crate::node::liveness::NodeLivenessMonitor;
+use crate::node::NodeManager;
+use common::actioncontroller::{DrainNodeRequest, ModelMove, RebalanceRequest};
+use common::logd;
+use common::statemanager::{ResourceType, StateChange};
+
+/// Why a maintenance operation could not be done
+#[derive(Debug, PartialEq)]
+pub enum MaintenanceError {
+    NotFound(String),
+    /// No node may take over the models of a drained node
+    Failed(String),
+    /// Storage or ActionController could not be reached
+    Unavailable(String),
+}
+
+impl std::fmt::Display for MaintenanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaintenanceError::NotFound(msg)
+            | MaintenanceError::Failed(msg)
+            | MaintenanceError::Unavailable(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Scheduling of a node after a cordon or uncordon
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Cordon {
+    pub node: String,
+    pub unschedulable: bool,
+    /// Whether the request changed the node
+    pub changed: bool,
+}
+
+/// Outcome of a drain
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrainReport {
+    pub node: String,
+    pub relocated_models: Vec<String>,
+    /// Models that could not be moved and still run on the node
+    pub failed_models: Vec<String>,
+}
+
+impl DrainReport {
+    /// Whether no model is left on the node
+    pub fn drained(&self) -> bool {
+        self.failed_models.is_empty()
+    }
+}
+
+/// Stop placing new models on a node
+pub async fn cordon(node: &str) -> Result<Cordon, MaintenanceError> {
+    set_unschedulable(node, true).await
+}
+
+/// Place new models on a cordoned or drained node again
+pub async fn uncordon(node: &str) -> Result<Cordon, MaintenanceError> {
+    set_unschedulable(node, false).await
+}
+
+/// Cordon a node and move its models onto the other schedulable nodes
+pub async fn drain(node: &str) -> Result<DrainReport, MaintenanceError> {
+    cordon(node).await?;
+
+    let node_manager = NodeManager;
+    let nodes = node_manager
+        .get_all_nodes()
+        .await
+        .map_err(|e| MaintenanceError::Unavailable(e.to_string()))?;
+    let target_nodes: Vec<String> = NodeLivenessMonitor::new(node_manager)
+        .find_healthy_nodes(&nodes)
+        .into_iter()
+        .filter(|hostname| hostname != node)
+        .collect();
+    if target_nodes.is_empty() {
+        return Err(MaintenanceError::Failed(format!(
+            "No schedulable node can take over the models of {}",
+            node
+        )));
+    }
+
+    let request = DrainNodeRequest {
+        node: node.to_string(),
+        target_nodes,
+    };
+    let response = crate::grpc::sender::actioncontroller::drain_node(request)
+        .await
+        .map_err(|status| match status.code() {
+            tonic::Code::FailedPrecondition => {
+                MaintenanceError::Failed(status.message().to_string())
+            }
+            _ => MaintenanceError::Unavailable(status.message().to_string()),
+        })?
+        .into_inner();
+    logd!(3, "Drained node {}: {}", node, response.desc);
+
+    Ok(DrainReport {
+        node: node.to_string(),
+        relocated_models: response.relocated_models,
+        failed_models: response.failed_models,
+    })
+}
+
+/// Outcome of a rebalance
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebalanceReport {
+    pub dry_run: bool,
+    /// Moves planned, or done unless a dry run
+    pub moves: Vec<Move>,
+    /// Moves not done, the model stays on its node
+    pub failed_moves: Vec<Move>,
+}
+
+/// Move of a model with its replicas on a node
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Move {
+    pub package: String,
+    pub model: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl From<ModelMove> for Move {
+    fn from(model_move: ModelMove) -> Self {
+        Move {
+            package: model_move.package,
+            model: model_move.model,
+            from: model_move.from_node,
+            to: model_move.to_node,
+        }
+    }
+}
+
+/// Spread the models over the schedulable nodes, at most `max_moves` of
+/// them unless it is 0
+pub async fn rebalance(dry_run: bool, max_moves: u32) -> Result<RebalanceReport, MaintenanceError> {
+    let node_manager = NodeManager;
+    let nodes = node_manager
+        .get_all_nodes()
+        .await
+        .map_err(|e| MaintenanceError::Unavailable(e.to_string()))?;
+    let nodes = NodeLivenessMonitor::new(node_manager).find_healthy_nodes(&nodes);
+    if nodes.is_empty() {
+        return Err(MaintenanceError::Failed(
+            "No schedulable node to rebalance the models over".to_string(),
+        ));
+    }
+
+    let request = RebalanceRequest {
+        nodes,
+        dry_run,
+        max_moves,
+    };
+    let response = crate::grpc::sender::actioncontroller::rebalance(request)
+        .await
+        .map_err(|status| match status.code() {
+            tonic::Code::FailedPrecondition => {
+                MaintenanceError::Failed(status.message().to_string())
+            }
+            _ => MaintenanceError::Unavailable(status.message().to_string()),
+        })?
+        .into_inner();
+    logd!(3, "Rebalanced the models: {}", response.desc);
+
+    Ok(RebalanceReport {
+        dry_run,
+        moves: response.moves.into_iter().map(Move::from).collect(),
+        failed_moves: response.failed_moves.into_iter().map(Move::from).collect(),
+    })
+}
+
+async fn set_unschedulable(node: &str, unschedulable: bool) -> Result<Cordon, MaintenanceError> {
+    let node_manager = NodeManager;
+    let exists = node_manager
+        .get_node(node)
+        .await
+        .map_err(|e| MaintenanceError::Unavailable(e.to_string()))?
+        .is_some();
+    if !exists {
+        return Err(MaintenanceError::NotFound(format!(
+            "Node {} is not registered",
+            node
+        )));
+    }
+    let changed = node_manager
+        .set_unschedulable(node, unschedulable)
+        .await
+        .map_err(|e| MaintenanceError::Unavailable(e.to_string()))?;
+
+    if changed {
+        let (current, target) = if unschedulable {
+            ("schedulable", "cordoned")
+        } else {
+            ("cordoned", "schedulable")
+        };
+        report_state(node, current, target).await;
+    }
+    Ok(Cordon {
+        node: node.to_string(),
+        unschedulable,
+        changed,
+    })
+}
+
+/// Tell the StateManager about the new scheduling of a node
+///
+/// The StateManager keeps the state it tracks over `current`, e.g. a drained
+/// node is uncordoned from drained.
+async fn report_state(node: &str, current: &str, target: &str) {
+    let state_change = StateChange {
+        resource_type: ResourceType::Node as i32,
+        resource_name: node.to_string(),
+        current_state: current.to_string(),
+        target_state: target.to_string(),
+        transition_id: common::correlation::transition_id("apiserver"),
+        timestamp_ns: chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        source: "apiserver".to_string(),
+        ..Default::default()
+    };
+    if let Err(e) = StateManagerSender::new()
+        .send_state_change(state_change)
+        .await
+    {
+        logd!(4, "Failed to report node {} as {}: {}", node, target, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::apiserver::NodeInfo;
+
+    #[tokio::test]
+    async fn test_cordon_and_uncordon() {
+        common::etcd::use_in_memory_store();
+        let node = NodeInfo {
+            node_id: "maintenance-node".to_string(),
+            hostname: "maintenance-node".to_string(),
+            ..Default::default()
+        };
+        common::etcd::put(
+            "cluster/nodes/maintenance-node",
+            &serde_json::to_string(&node).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let cordoned = cordon("maintenance-node").await.unwrap();
+        assert!(cordoned.unschedulable);
+        assert!(cordoned.changed);
+        let stored = NodeManager.get_node("maintenance-node").await.unwrap();
+        assert!(stored.unwrap().unschedulable);
+        // Cordoning again changes nothing
+        assert!(!cordon("maintenance-node").await.unwrap().changed);
+
+        let uncordoned = uncordon("maintenance-node").await.unwrap();
+        assert!(!uncordoned.unschedulable);
+        assert!(uncordoned.changed);
+
+        // A heartbeat at the time of a cordon does not undo it
+        let (cordoned, heartbeat) = tokio::join!(
+            cordon("maintenance-node"),
+            NodeManager.update_heartbeat("maintenance-node")
+        );
+        assert!(cordoned.unwrap().changed);
+        heartbeat.unwrap();
+        NodeManager
+            .update_heartbeat("maintenance-node")
+            .await
+            .unwrap();
+        let stored = NodeManager.get_node("maintenance-node").await.unwrap();
+        assert!(stored.unwrap().unschedulable);
+
+        common::etcd::delete("cluster/nodes/maintenance-node")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unknown_node() {
+        common::etcd::use_in_memory_store();
+        assert!(matches!(
+            cordon("unregistered-node").await,
+            Err(MaintenanceError::NotFound(_))
+        ));
+        assert!(matches!(
+            drain("unregistered-node").await,
+            Err(MaintenanceError::NotFound(_))
+        ));
+    }
+}
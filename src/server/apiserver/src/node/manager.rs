@@ -8,7 +8,10 @@
 use common::apiserver::NodeInfo;
 use common::etcd;
 use common::logd;
-use common::nodeagent::fromapiserver::{NodeRegistrationRequest, NodeStatus};
+use common::nodeagent::fromapiserver::{NodeRegistrationRequest, NodeStatus, NodeUsage};
+
+/// Attempts of an update of a node that keeps being changed concurrently
+const UPDATE_ATTEMPTS: u32 = 5;
 
 /// Node manager for handling cluster node operations
 #[derive(Clone)]
@@ -25,26 +28,51 @@ impl NodeManager {
         &self,
         request: NodeRegistrationRequest,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        crate::node::labels::validate(&request.hostname, &request.labels, &request.taints)
+            .map_err(|e| format!("Node {} not registered: {}", request.hostname, e))?;
+
         // node_id 대신 hostname(node_name)을 키로 사용합니다
         let node_key = format!("cluster/nodes/{}", request.hostname);
-
-        // Create node info
-        let node_info = NodeInfo {
-            node_id: request.node_id.clone(),
-            hostname: request.hostname.clone(),
-            ip_address: request.ip_address.clone(),
-            node_type: request.node_type,
-            node_role: request.node_role,
-            status: NodeStatus::Pending.into(),
-            resources: request.resources,
-            last_heartbeat: chrono::Utc::now().timestamp(),
-            created_at: chrono::Utc::now().timestamp(),
-            metadata: request.metadata,
-        };
-
         // 1. cluster/nodes/{hostname}: 노드 정보(json string)
-        let node_json = serde_json::to_string(&node_info)?;
-        etcd::put(&node_key, &node_json).await?;
+        let mut attempts = 0;
+        loop {
+            let stored = etcd::get(&node_key).await.ok();
+            // A node registering again after its maintenance stays cordoned
+            let unschedulable = stored
+                .as_deref()
+                .and_then(|stored| serde_json::from_str::<NodeInfo>(stored).ok())
+                .is_some_and(|node| node.unschedulable);
+
+            // Create node info
+            let node_info = NodeInfo {
+                node_id: request.node_id.clone(),
+                hostname: request.hostname.clone(),
+                ip_address: request.ip_address.clone(),
+                node_type: request.node_type,
+                node_role: request.node_role,
+                status: NodeStatus::Pending.into(),
+                resources: request.resources.clone(),
+                last_heartbeat: chrono::Utc::now().timestamp(),
+                created_at: chrono::Utc::now().timestamp(),
+                metadata: request.metadata.clone(),
+                unschedulable,
+                usage: request.usage,
+                labels: request.labels.clone(),
+                taints: request.taints.clone(),
+            };
+            let node_json = serde_json::to_string(&node_info)?;
+            if etcd::compare_and_swap(&node_key, stored.as_deref(), &node_json).await? {
+                break;
+            }
+            attempts += 1;
+            if attempts == UPDATE_ATTEMPTS {
+                return Err(format!(
+                    "Node {} kept changing during its registration",
+                    request.hostname
+                )
+                .into());
+            }
+        }
 
         // 2. nodes/{ip_address}: hostname(plain string)
         let ip_key = format!("nodes/{}", request.ip_address);
@@ -102,45 +130,152 @@ impl NodeManager {
         }
     }
 
+    /// Apply `update` to the stored node and write it back
+    ///
+    /// The node is written only if it was not changed since it was read,
+    /// otherwise it is read again, so that concurrent updates of other fields
+    /// are kept. `update` returns `false` when the node needs no write.
+    ///
+    /// Returns `false` if the node is not registered or was not written.
+    async fn modify_node(
+        &self,
+        node_id: &str,
+        mut update: impl FnMut(&mut NodeInfo) -> bool,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let node_key = format!("cluster/nodes/{}", node_id);
+        for _ in 0..UPDATE_ATTEMPTS {
+            let stored = match etcd::get(&node_key).await {
+                Ok(stored) => stored,
+                Err(_) => return Ok(false), // Node not found
+            };
+            let mut node = serde_json::from_str::<NodeInfo>(&stored)?;
+            if !update(&mut node) {
+                return Ok(false);
+            }
+            let node_json = serde_json::to_string(&node)?;
+            if etcd::compare_and_swap(&node_key, Some(&stored), &node_json).await? {
+                return Ok(true);
+            }
+        }
+        Err(format!("Node {} kept changing during its update", node_id).into())
+    }
+
     /// Update node heartbeat
+    ///
+    /// A node in maintenance stays in maintenance and a cordoned node stays
+    /// cordoned.
     pub async fn update_heartbeat(
         &self,
         node_id: &str,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if let Some(mut node) = self.get_node(node_id).await? {
-            node.last_heartbeat = chrono::Utc::now().timestamp();
-            node.status = NodeStatus::Ready.into();
-
-            // node_name으로 키 생성
-            let node_key = format!("cluster/nodes/{}", node.hostname);
-            let node_json = serde_json::to_string(&node)?;
-            etcd::put(&node_key, &node_json).await?;
-
+        let updated = self
+            .modify_node(node_id, |node| {
+                node.last_heartbeat = chrono::Utc::now().timestamp();
+                if node.status != NodeStatus::Maintenance as i32 {
+                    node.status = NodeStatus::Ready.into();
+                }
+                true
+            })
+            .await?;
+        if updated {
             logd!(1, "Updated heartbeat for node {}", node_id);
         }
         Ok(())
     }
 
+    /// Record the load a node reported in its status report
+    ///
+    /// Returns `false` if the node is not registered.
+    pub async fn update_usage(
+        &self,
+        node_id: &str,
+        usage: NodeUsage,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let updated = self
+            .modify_node(node_id, |node| {
+                node.usage = Some(usage);
+                true
+            })
+            .await?;
+        if updated {
+            logd!(1, "Updated usage of node {}", node_id);
+        }
+        Ok(updated)
+    }
+
     /// Update node status
     pub async fn update_status(
         &self,
         node_id: &str,
         status: NodeStatus,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if let Some(mut node) = self.get_node(node_id).await? {
-            node.status = status.into();
-            node.last_heartbeat = chrono::Utc::now().timestamp();
-
-            // node.hostname을 사용하여 키 생성 (node_id 대신)
-            let node_key = format!("cluster/nodes/{}", node.hostname);
-            let node_json = serde_json::to_string(&node)?;
-            etcd::put(&node_key, &node_json).await?;
-
+        let updated = self
+            .modify_node(node_id, |node| {
+                node.status = status.into();
+                node.last_heartbeat = chrono::Utc::now().timestamp();
+                true
+            })
+            .await?;
+        if updated {
             logd!(1, "Updated status for node {} to {:?}", node_id, status);
         }
         Ok(())
     }
 
+    /// Mark a node NotReady while keeping its last heartbeat timestamp
+    ///
+    /// Returns `true` if the node existed and its status was changed.
+    pub async fn mark_not_ready(
+        &self,
+        node_id: &str,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let updated = self
+            .modify_node(node_id, |node| {
+                if node.status == NodeStatus::NotReady as i32 {
+                    return false;
+                }
+                node.status = NodeStatus::NotReady.into();
+                true
+            })
+            .await?;
+        if updated {
+            logd!(4, "Marked node {} as NotReady", node_id);
+        }
+        Ok(updated)
+    }
+
+    /// Cordon or uncordon a node, a cordoned node takes no new models
+    ///
+    /// Returns `true` if the node existed and its flag was changed.
+    pub async fn set_unschedulable(
+        &self,
+        node_id: &str,
+        unschedulable: bool,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let updated = self
+            .modify_node(node_id, |node| {
+                if node.unschedulable == unschedulable {
+                    return false;
+                }
+                node.unschedulable = unschedulable;
+                true
+            })
+            .await?;
+        if updated {
+            logd!(
+                3,
+                "Node {} is {}",
+                node_id,
+                if unschedulable {
+                    "cordoned"
+                } else {
+                    "schedulable"
+                }
+            );
+        }
+        Ok(updated)
+    }
+
     /// Remove a node from the cluster
     pub async fn remove_node(
         &self,
@@ -193,6 +328,10 @@ mod tests {
             node_role: NodeRole::Nodeagent.into(),
             resources: Some(create_test_resource_info()),
             metadata,
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -211,6 +350,10 @@ mod tests {
                 os_version: "Ubuntu 22.04".to_string(),
             }),
             metadata: HashMap::new(),
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -223,6 +366,10 @@ mod tests {
             node_role: NodeRole::Master.into(), // Use Master instead of BluechiManager
             resources: Some(create_test_resource_info()),
             metadata: HashMap::new(),
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -437,6 +584,16 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_mark_not_ready_nonexistent_node() {
+        let manager = NodeManager::new().expect("Failed to create NodeManager");
+
+        match manager.mark_not_ready("nonexistent-node-999").await {
+            Ok(changed) => assert!(!changed),
+            Err(e) => println!("Expected etcd connection error: {}", e),
+        }
+    }
+
     #[tokio::test]
     async fn test_update_node_status() {
         let manager = NodeManager::new().expect("Failed to create NodeManager");
@@ -587,6 +744,10 @@ mod tests {
             node_role: NodeRole::Nodeagent.into(),
             resources: None, // Test with no resources
             metadata: HashMap::new(),
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         };
 
         match manager.register_node(edge_case_request).await {
@@ -699,6 +860,10 @@ mod tests {
             node_role: NodeRole::Master.into(),
             resources: Some(create_test_resource_info()),
             metadata: complex_metadata.clone(),
+            api_version: common::version::API_VERSION,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         };
 
         assert_eq!(request.metadata.len(), 5);
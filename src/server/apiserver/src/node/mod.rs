@@ -5,6 +5,10 @@
 
 //! Node management modules
 
+pub mod autostart;
+pub mod labels;
+pub mod liveness;
+pub mod maintenance;
 pub mod manager;
 pub mod node_lookup;
 pub mod registry;
@@ -114,6 +114,10 @@ mod tests {
             last_heartbeat: chrono::Utc::now().timestamp(),
             created_at: chrono::Utc::now().timestamp(),
             metadata: HashMap::new(),
+            unschedulable: false,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
@@ -144,6 +144,10 @@ mod tests {
             last_heartbeat,
             created_at: 1234567890,
             metadata: std::collections::HashMap::new(),
+            unschedulable: false,
+            usage: None,
+            labels: std::collections::HashMap::new(),
+            taints: Vec::new(),
         }
     }
 
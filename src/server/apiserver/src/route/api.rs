@@ -5,21 +5,56 @@
 
 //! Handler functions of Piccolo REST API
 
+use crate::artifact::query::Scope;
+use crate::container::{ContainerError, ExecQuery, LogQuery};
+use crate::node::maintenance::{self, MaintenanceError};
+use crate::scenario::{self, LifecycleError};
 use axum::{
-    response::Response,
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Extension, Path, Query},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
     routing::{delete, get, post},
-    Router,
+    Json, Router,
 };
+use common::auth::{Principal, Role};
 
 /// Make router type for composing handler and Piccolo service
 ///
 /// ### Parametets
 /// None
 pub fn router() -> Router {
+    // The only check of the body size of the artifact requests
+    let max_body_bytes = common::setting::get_config().artifact.max_body_bytes;
+    let body_limit = || DefaultBodyLimit::max(max_body_bytes);
     Router::new()
         .route("/api/notify", get(notify))
-        .route("/api/artifact", post(apply_artifact))
-        .route("/api/artifact", delete(withdraw_artifact))
+        .route("/api/artifact", post(apply_artifact).layer(body_limit()))
+        .route(
+            "/api/artifact",
+            delete(withdraw_artifact).layer(body_limit()),
+        )
+        .route("/api/artifact/bulk", post(apply_bulk).layer(body_limit()))
+        .route("/api/artifact/:kind", get(list_artifacts))
+        .route("/api/artifact/:kind/:name", get(get_artifact))
+        .route("/api/health", get(health))
+        .route("/api/graph", get(export_graph))
+        .route("/api/simulate", post(simulate))
+        .route("/api/scenario/:name/activate", post(activate_scenario))
+        .route("/api/scenario/:name/deactivate", post(deactivate_scenario))
+        .route("/api/scenario/:name/trigger", post(trigger_scenario))
+        .route("/api/logs/:model", get(container_logs))
+        .route("/api/exec/:model", post(container_exec))
+        .route("/api/nodes", get(list_nodes))
+        .route("/api/node/:name/cordon", post(cordon_node))
+        .route("/api/node/:name/uncordon", post(uncordon_node))
+        .route("/api/node/:name/drain", post(drain_node))
+        .route("/api/rebalance", post(rebalance))
+        .route("/api/admin/snapshot", get(export_snapshot))
+        .route("/api/admin/restore", post(restore_snapshot))
 }
 
 /// Notify of new artifact release in the cloud
@@ -32,26 +67,553 @@ async fn notify(artifact_name: String) -> Response {
     super::status(Ok(()))
 }
 
+/// Refuse artifacts in a namespace the caller may not access
+///
+/// ### Parameters
+/// * `principal` - caller, none when authentication is disabled
+/// * `rpc` - name of the request for the audit log
+/// * `body` - the artifacts in yaml format
+#[allow(clippy::result_large_err)]
+fn check_namespaces(principal: Option<&Principal>, rpc: &str, body: &str) -> Result<(), Response> {
+    let namespaces = crate::artifact::namespaces(body).map_err(|e| super::status(Err(e)))?;
+    for namespace in namespaces {
+        common::auth::check_namespace(principal, rpc, &namespace).map_err(|status| {
+            (StatusCode::FORBIDDEN, Json(status.message().to_string())).into_response()
+        })?;
+    }
+    Ok(())
+}
+
 /// Apply the new artifacts (scenario, package, etc...)
 ///
 /// ### Parameters
 /// * `body: String` - the string in yaml format
-async fn apply_artifact(body: String) -> Response {
-    let result = crate::manager::apply_artifact(&body).await;
+/// ### Description
+/// Responds with the Pods of the package the artifacts added, changed or
+/// removed, see [`common::spec::artifact::package::PackageDiff`]. Responds
+/// with `413 Payload Too Large` when the body exceeds the size limit of the
+/// router or the document limits of the `artifact` settings, see
+/// [`crate::artifact::limits`], and with
+/// `422 Unprocessable Entity` and the list of schema violations when a
+/// document does not match the schema of its kind.
+async fn apply_artifact(principal: Option<Extension<Principal>>, body: String) -> Response {
+    if let Err(e) = crate::artifact::limits::check(&body) {
+        return (StatusCode::PAYLOAD_TOO_LARGE, Json(e.to_string())).into_response();
+    }
+    let violations = crate::artifact::schema::validate(&body);
+    if !violations.is_empty() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(violations)).into_response();
+    }
+    let principal = principal.map(|p| p.0);
+    if let Err(response) = check_namespaces(principal.as_ref(), "POST /api/artifact", &body) {
+        return response;
+    }
+    match crate::manager::apply_artifact(&body).await {
+        Ok(diff) => (StatusCode::OK, Json(diff)).into_response(),
+        Err(e) => super::status(Err(e)),
+    }
+}
 
-    super::status(result)
+/// Apply a bundle of artifact files
+///
+/// ### Parameters
+/// * `body: Bytes` - tar, tar.gz, or JSON object of file name to yaml
+/// ### Description
+/// Responds with the result of each file, `200 OK` when all of them were
+/// applied and `422 Unprocessable Entity` otherwise. Nothing is applied when
+/// a file has artifacts in a namespace the caller may not access. A body
+/// over `artifact.max_body_bytes` is refused with `413 Payload Too Large`.
+async fn apply_bulk(principal: Option<Extension<Principal>>, body: Bytes) -> Response {
+    let files = match crate::artifact::bulk::unpack(&body) {
+        Ok(files) => files,
+        Err(e) => return super::status(Err(e)),
+    };
+    let principal = principal.map(|p| p.0);
+    for file in &files {
+        if let Err(response) =
+            check_namespaces(principal.as_ref(), "POST /api/artifact/bulk", &file.content)
+        {
+            return response;
+        }
+    }
+    bulk_response(crate::manager::apply_bulk(&files).await)
+}
+
+fn bulk_response(results: Vec<crate::artifact::bulk::FileResult>) -> Response {
+    let code = if results.iter().all(|r| r.error.is_none()) {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    (code, Json(results)).into_response()
+}
+
+/// Query parameters of an artifact list
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ListQuery {
+    /// Label selector, e.g. `app=hello,tier!=debug`
+    selector: String,
+    /// Page size, 0 for all
+    limit: usize,
+    /// `continue` of the previous page
+    #[serde(rename = "continue")]
+    after: Option<String>,
+    /// Namespace to list, all the ones the caller may access when none
+    namespace: Option<String>,
+}
+
+/// Query parameters of an artifact read
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct GetQuery {
+    /// Namespace of the artifact, the default namespace when none
+    namespace: Option<String>,
+}
+
+/// List applied artifacts of a kind
+///
+/// ### Parameters
+/// * `kind` - artifact kind such as `scenario` or `model`
+/// * `?selector=&limit=&continue=&namespace=` - label filter, paging and
+///   namespace
+async fn list_artifacts(
+    Path(kind): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let scope = Scope {
+        namespace: query.namespace.as_deref(),
+        principal: principal.as_ref(),
+    };
+    let result = crate::artifact::query::list(
+        &kind,
+        scope,
+        &query.selector,
+        query.limit,
+        query.after.as_deref(),
+    )
+    .await;
+    query_response(result)
+}
+
+/// Get the stored yaml of an applied artifact
+///
+/// ### Parameters
+/// * `kind`, `name` - artifact to read, Models include their Pod yaml
+/// * `?namespace=` - namespace of the artifact
+async fn get_artifact(
+    Path((kind, name)): Path<(String, String)>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<GetQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let scope = Scope {
+        namespace: query.namespace.as_deref(),
+        principal: principal.as_ref(),
+    };
+    let result = crate::artifact::query::get(&kind, scope, &name).await;
+    query_response(result)
+}
+
+fn query_response<T: serde::Serialize>(
+    result: Result<T, crate::artifact::query::QueryError>,
+) -> Response {
+    use crate::artifact::query::QueryError;
+    match result {
+        Ok(body) => Json(body).into_response(),
+        Err(e) => {
+            let code = match e {
+                QueryError::Invalid(_) => StatusCode::BAD_REQUEST,
+                QueryError::Forbidden(_) => StatusCode::FORBIDDEN,
+                QueryError::NotFound(_) => StatusCode::NOT_FOUND,
+                QueryError::Storage(_) => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (code, Json(e.to_string())).into_response()
+        }
+    }
+}
+
+/// Query parameters of a dependency graph export
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct GraphQuery {
+    /// `json` or `dot`, json when none
+    format: Option<String>,
+    /// Namespace to export, all the ones the caller may access when none
+    namespace: Option<String>,
+}
+
+/// Export the scenario, package, model and node dependency graph
+///
+/// ### Parameters
+/// * `?format=&namespace=` - `dot` for Graphviz instead of JSON, namespace
+///   of the artifacts
+/// ### Description
+/// Vertices carry their current states, the failing ones none of whose
+/// dependencies fail are listed as `origins`, see
+/// [`crate::artifact::graph`].
+async fn export_graph(
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<GraphQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let scope = Scope {
+        namespace: query.namespace.as_deref(),
+        principal: principal.as_ref(),
+    };
+    match query.format.as_deref() {
+        None | Some("json") => query_response(crate::artifact::graph::export(scope).await),
+        Some("dot") => match crate::artifact::graph::export(scope).await {
+            Ok(graph) => (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "text/vnd.graphviz")],
+                graph.to_dot(),
+            )
+                .into_response(),
+            Err(e) => query_response::<()>(Err(e)),
+        },
+        Some(format) => (
+            StatusCode::BAD_REQUEST,
+            Json(format!(
+                "Unknown graph format '{}', expected json or dot",
+                format
+            )),
+        )
+            .into_response(),
+    }
+}
+
+/// Query parameters of a node listing
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct NodesQuery {
+    /// `key=value,key=value` labels the listed nodes carry, all when none
+    selector: Option<String>,
+}
+
+/// List the registered nodes with their labels and taints
+///
+/// ### Parameters
+/// * `?selector=` - labels the nodes have to carry, e.g. `gpu=true`
+/// ### Description
+/// Answers 400 for a malformed selector.
+async fn list_nodes(Query(query): Query<NodesQuery>) -> Response {
+    let selector =
+        match crate::node::labels::parse_selector(query.selector.as_deref().unwrap_or_default()) {
+            Ok(selector) => selector,
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(e)).into_response(),
+        };
+    match crate::node::labels::list(&selector).await {
+        Ok(nodes) => Json(nodes).into_response(),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, Json(e)).into_response(),
+    }
 }
 
 /// Withdraw the applied scenario
 ///
 /// ### Parameters
 /// * `body: String` - name of the artifact to be deleted
-async fn withdraw_artifact(body: String) -> Response {
+async fn withdraw_artifact(principal: Option<Extension<Principal>>, body: String) -> Response {
+    let principal = principal.map(|p| p.0);
+    if let Err(response) = check_namespaces(principal.as_ref(), "DELETE /api/artifact", &body) {
+        return response;
+    }
     let result = crate::manager::withdraw_artifact(&body).await;
 
     super::status(result)
 }
 
+/// Simulate state changes without persisting or dispatching anything
+///
+/// ### Parameters
+/// * `body: String` - yaml or json list of state changes
+/// ### Description
+/// Responds with the transitions, actions and stored states the changes
+/// would lead to, in the order they would happen.
+async fn simulate(body: String) -> Response {
+    match crate::manager::simulate(&body).await {
+        Ok(transitions) => Json(transitions).into_response(),
+        Err(e) => super::status(Err(e)),
+    }
+}
+
+/// Activate an idle scenario without applying its yaml again
+///
+/// ### Parameters
+/// * `name` - name of the applied scenario
+/// * `?namespace=` - namespace of the scenario
+/// ### Description
+/// Responds with `202 Accepted` once the StateManager took the state change
+/// and FilterGateway registered the conditions of the scenario.
+async fn activate_scenario(
+    Path(name): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<GetQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let scope = Scope {
+        namespace: query.namespace.as_deref(),
+        principal: principal.as_ref(),
+    };
+    lifecycle_response(scenario::activate(scope, &name).await)
+}
+
+/// Move a scenario back to idle and stop evaluating its conditions
+///
+/// ### Parameters
+/// * `name` - name of the applied scenario
+/// * `?namespace=` - namespace of the scenario
+async fn deactivate_scenario(
+    Path(name): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<GetQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let scope = Scope {
+        namespace: query.namespace.as_deref(),
+        principal: principal.as_ref(),
+    };
+    lifecycle_response(scenario::deactivate(scope, &name).await)
+}
+
+/// Force the action of a scenario to run again on its target package
+///
+/// ### Parameters
+/// * `name` - name of the applied scenario
+/// * `?namespace=` - namespace of the scenario
+/// ### Description
+/// The conditions of the scenario are not evaluated, its policy still is.
+async fn trigger_scenario(
+    Path(name): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<GetQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let scope = Scope {
+        namespace: query.namespace.as_deref(),
+        principal: principal.as_ref(),
+    };
+    lifecycle_response(scenario::trigger(scope, &name).await)
+}
+
+fn lifecycle_response(result: Result<scenario::Lifecycle, LifecycleError>) -> Response {
+    match result {
+        Ok(lifecycle) => (StatusCode::ACCEPTED, Json(lifecycle)).into_response(),
+        Err(e) => {
+            let code = match e {
+                LifecycleError::Forbidden(_) => StatusCode::FORBIDDEN,
+                LifecycleError::NotFound(_) => StatusCode::NOT_FOUND,
+                LifecycleError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (code, Json(e.to_string())).into_response()
+        }
+    }
+}
+
+/// Read the logs of a container of a model from its node
+///
+/// ### Parameters
+/// * `model` - name of the applied model
+/// * `?container=&tail=&since=&follow=` - container, which may be left out
+///   for single container models, and the lines to read
+/// ### Description
+/// Responds with the stdout and stderr of the container as plain text. With
+/// `follow=true` new lines are streamed until the client disconnects.
+async fn container_logs(Path(model): Path<String>, Query(query): Query<LogQuery>) -> Response {
+    use tokio_stream::StreamExt;
+    match crate::container::logs(&model, query).await {
+        Ok(stream) => {
+            let body = Body::from_stream(stream.map(|chunk| {
+                chunk
+                    .map(|c| Bytes::from(c.data))
+                    .map_err(axum::BoxError::from)
+            }));
+            ([(CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+        }
+        Err(e) => container_error(e),
+    }
+}
+
+/// Run a one-shot diagnostic command in a container of a model
+///
+/// ### Parameters
+/// * `model` - name of the applied model
+/// * `body` - JSON with the `command` to run, the `container` which may be
+///   left out for single container models and a `timeout` in seconds
+/// ### Description
+/// Responds with the stdout, stderr and exit code of the command. Who ran
+/// which command is written to the audit log.
+///
+/// Only authenticated admins may run commands, so exec is refused with
+/// `401 Unauthorized` while authentication is disabled.
+async fn container_exec(
+    Path(model): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Json(query): Json<ExecQuery>,
+) -> Response {
+    let principal = principal.map(|p| p.0);
+    let caller = match common::auth::check_role(principal.as_ref(), "POST /api/exec", Role::Admin) {
+        Ok(caller) => caller,
+        Err(status) => return super::denied(status),
+    };
+    match crate::container::exec(&model, query, &caller.name).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => container_error(e),
+    }
+}
+
+fn container_error(e: ContainerError) -> Response {
+    let code = match e {
+        ContainerError::Invalid(_) => StatusCode::BAD_REQUEST,
+        ContainerError::NotFound(_) => StatusCode::NOT_FOUND,
+        ContainerError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (code, Json(e.to_string())).into_response()
+}
+
+/// Stop placing new models on a node
+///
+/// ### Parameters
+/// * `name` - hostname of the node
+/// ### Description
+/// Models running on the node keep running.
+async fn cordon_node(Path(name): Path<String>) -> Response {
+    match maintenance::cordon(&name).await {
+        Ok(cordon) => Json(cordon).into_response(),
+        Err(e) => maintenance_error(e),
+    }
+}
+
+/// Place new models on a cordoned or drained node again
+///
+/// ### Parameters
+/// * `name` - hostname of the node
+async fn uncordon_node(Path(name): Path<String>) -> Response {
+    match maintenance::uncordon(&name).await {
+        Ok(cordon) => Json(cordon).into_response(),
+        Err(e) => maintenance_error(e),
+    }
+}
+
+/// Cordon a node and move its models to other nodes
+///
+/// ### Parameters
+/// * `name` - hostname of the node
+/// ### Description
+/// Responds with the relocated models and the ones left on the node, with
+/// 422 when some could not be moved.
+async fn drain_node(Path(name): Path<String>) -> Response {
+    match maintenance::drain(&name).await {
+        Ok(report) => {
+            let code = if report.drained() {
+                StatusCode::OK
+            } else {
+                StatusCode::UNPROCESSABLE_ENTITY
+            };
+            (code, Json(report)).into_response()
+        }
+        Err(e) => maintenance_error(e),
+    }
+}
+
+/// Query parameters of a rebalance
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RebalanceQuery {
+    /// Only report the moves
+    dry_run: bool,
+    /// Moves at most, unlimited when 0
+    max_moves: u32,
+}
+
+/// Spread the models more evenly over the schedulable nodes
+///
+/// ### Parameters
+/// * `?dryRun=&maxMoves=` - only plan the moves, and how many at most
+/// ### Description
+/// Responds with the planned or done moves and the ones that failed, with
+/// 422 when some model could not be moved.
+async fn rebalance(Query(query): Query<RebalanceQuery>) -> Response {
+    match maintenance::rebalance(query.dry_run, query.max_moves).await {
+        Ok(report) => {
+            let code = if report.failed_moves.is_empty() {
+                StatusCode::OK
+            } else {
+                StatusCode::UNPROCESSABLE_ENTITY
+            };
+            (code, Json(report)).into_response()
+        }
+        Err(e) => maintenance_error(e),
+    }
+}
+
+fn maintenance_error(e: MaintenanceError) -> Response {
+    let code = match e {
+        MaintenanceError::NotFound(_) => StatusCode::NOT_FOUND,
+        MaintenanceError::Failed(_) => StatusCode::CONFLICT,
+        MaintenanceError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (code, Json(e.to_string())).into_response()
+}
+
+/// Export the whole system state as a tar.gz archive
+///
+/// ### Description
+/// Serves as backup, for migration to another cluster and as support bundle.
+async fn export_snapshot() -> Response {
+    match crate::snapshot::export().await {
+        Ok(archive) => (
+            StatusCode::OK,
+            [
+                (CONTENT_TYPE, "application/gzip"),
+                (
+                    CONTENT_DISPOSITION,
+                    "attachment; filename=\"piccolo-snapshot.tar.gz\"",
+                ),
+            ],
+            archive,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, Json(e.to_string())).into_response(),
+    }
+}
+
+/// Import a snapshot taken by `GET /api/admin/snapshot`
+///
+/// ### Parameters
+/// * `options` - `force=true` to overwrite existing artifacts, `nodes=true`
+///   to restore the node registrations
+/// * `body: Bytes` - the tar.gz archive
+async fn restore_snapshot(
+    Query(options): Query<crate::snapshot::RestoreOptions>,
+    body: Bytes,
+) -> Response {
+    match crate::manager::restore_snapshot(&body, &options).await {
+        Ok(result) => (StatusCode::OK, Json(result)).into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(e.to_string())).into_response(),
+    }
+}
+
+/// Report the health of the connections to other components
+///
+/// ### Description
+/// Lists the circuit breakers of the gRPC servers called by the apiserver.
+/// The status is `degraded` while any of them is not closed.
+async fn health() -> Response {
+    let breakers = common::grpc::retry::breaker_stats();
+    let degraded = breakers
+        .iter()
+        .any(|b| b.state != common::grpc::retry::BreakerState::Closed);
+    Json(serde_json::json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "breakers": breakers,
+        "gc": crate::artifact::gc::stats(),
+    }))
+    .into_response()
+}
+
 //UNIT TEST CASES
 #[cfg(test)]
 mod tests {
@@ -178,6 +740,261 @@ spec:
         assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
 
+    /// Negative test: POST /api/artifact/bulk rejects a body that is no bundle
+    #[tokio::test]
+    async fn test_apply_bulk_invalid_body() {
+        let app = Router::new().route("/api/artifact/bulk", post(super::apply_bulk));
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/artifact/bulk")
+            .body(Body::from("not a bundle"))
+            .unwrap();
+
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    /// Bodies of artifact requests over the limit are refused, bulk ones too
+    #[tokio::test]
+    async fn test_artifact_bodies_over_limit() {
+        let max = common::setting::get_config().artifact.max_body_bytes;
+        for (method, uri) in [
+            ("POST", "/api/artifact"),
+            ("DELETE", "/api/artifact"),
+            ("POST", "/api/artifact/bulk"),
+        ] {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from(vec![b'a'; max + 1]))
+                .unwrap();
+            let response = super::router().oneshot(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{uri}");
+        }
+    }
+
+    /// A malformed artifact is refused with where it is wrong
+    #[tokio::test]
+    async fn test_apply_artifact_reports_schema_violations() {
+        let app = Router::new().route("/api/artifact", post(super::apply_artifact));
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/artifact")
+            .body(Body::from("kind: Scenario\nmetadata:\n  name: [x]\n"))
+            .unwrap();
+
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let violations: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(violations[0]["document"], 0);
+        assert_eq!(violations[0]["kind"], "Scenario");
+        assert_eq!(violations[0]["path"], "metadata.name");
+        assert_eq!(violations[0]["line"], 3);
+    }
+
+    /// An artifact with too many documents is refused before it is parsed
+    #[tokio::test]
+    async fn test_apply_artifact_over_document_limit() {
+        let app = Router::new().route("/api/artifact", post(super::apply_artifact));
+        let max = common::setting::get_config().artifact.max_documents;
+        let body = vec!["kind: Model"; max + 1].join("\n---\n");
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/artifact")
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    /// Exec is refused to anonymous callers and to roles below admin
+    #[tokio::test]
+    async fn test_container_exec_requires_admin() {
+        use common::auth::{Principal, Role};
+
+        let exec = |principal: Option<Principal>| {
+            let app = Router::new().route("/api/exec/:model", post(super::container_exec));
+            let app = match principal {
+                Some(principal) => app.layer(axum::Extension(principal)),
+                None => app,
+            };
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/exec/helloworld")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"command":["ls"]}"#))
+                .unwrap();
+            app.oneshot(req)
+        };
+
+        let response = exec(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let operator = Principal {
+            name: "dashboard".to_string(),
+            role: Role::Operator,
+            namespaces: Vec::new(),
+        };
+        let response = exec(Some(operator)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    /// A bulk response is only OK when every file was applied
+    #[test]
+    fn test_bulk_response_status() {
+        use crate::artifact::bulk::FileResult;
+        let ok = FileResult {
+            file: "a.yaml".to_string(),
+            applied: vec!["Model/a".to_string()],
+            error: None,
+            violations: Vec::new(),
+        };
+        let failed = FileResult {
+            error: Some("Scenario/b refers to missing Package/b".to_string()),
+            ..ok.clone()
+        };
+        assert_eq!(
+            super::bulk_response(vec![ok.clone()]).status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            super::bulk_response(vec![ok, failed]).status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+
+    /// Negative test: GET /api/artifact/{kind} rejects unknown kinds and selectors
+    #[tokio::test]
+    async fn test_list_artifacts_invalid_query() {
+        let app = super::router();
+        for uri in ["/api/artifact/pod", "/api/artifact/scenario?selector=%3Dx"] {
+            let req = Request::builder()
+                .method("GET")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/artifact/pod/hello")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Positive test: GET /api/health reports the circuit breakers
+    #[tokio::test]
+    async fn test_health_reports_breakers() {
+        let app = Router::new().route("/api/health", get(super::health));
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/health")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let health: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        // Other tests may have tripped a breaker while StateManager is absent
+        assert!(matches!(
+            health["status"].as_str(),
+            Some("ok") | Some("degraded")
+        ));
+        assert!(health["breakers"].is_array());
+    }
+
+    /// Positive and negative test: GET /api/graph in DOT, refusing other formats
+    #[tokio::test]
+    async fn test_export_graph_formats() {
+        common::etcd::use_in_memory_store();
+        let app = Router::new().route("/api/graph", get(super::export_graph));
+        let get_graph = |uri: &str| {
+            Request::builder()
+                .method("GET")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(get_graph("/api/graph?format=dot"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).starts_with("digraph piccolo {"));
+
+        let response = app
+            .oneshot(get_graph("/api/graph?format=png"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Positive and negative test: GET /api/nodes by label selector
+    #[tokio::test]
+    async fn test_list_nodes() {
+        common::etcd::use_in_memory_store();
+        let app = Router::new().route("/api/nodes", get(super::list_nodes));
+        let list = |uri: &str| {
+            Request::builder()
+                .method("GET")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(list("/api/nodes?selector=gpu=true"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(serde_json::from_slice::<serde_json::Value>(&body)
+            .unwrap()
+            .is_array());
+
+        let response = app.oneshot(list("/api/nodes?selector=gpu")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Negative test: POST /api/node/{name}/... answers 404 for unknown nodes
+    #[tokio::test]
+    async fn test_node_maintenance_unknown_node() {
+        common::etcd::use_in_memory_store();
+        let app = super::router();
+        for action in ["cordon", "uncordon", "drain"] {
+            let req = Request::builder()
+                .method("POST")
+                .uri(format!("/api/node/route-unknown-node/{}", action))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
     // -------------------
     // Apply Artifact Tests (POST)
     // -------------------
@@ -8,10 +8,13 @@
 pub mod api;
 
 use axum::{
-    http::StatusCode,
+    extract::Request,
+    http::{header::AUTHORIZATION, Method, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     Json, Router,
 };
+use common::auth::Role;
 use common::logd;
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
@@ -29,7 +32,11 @@ pub async fn launch_tcp_listener() {
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
-    let app = Router::new().merge(api::router()).layer(cors);
+    let app = Router::new()
+        .merge(api::router())
+        .layer(middleware::from_fn(authorize))
+        .merge(common::health::router())
+        .layer(cors);
 
     logd!(
         2,
@@ -44,6 +51,50 @@ pub async fn launch_tcp_listener() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// Check the bearer token of a REST request when authentication is enabled
+///
+/// ### Description
+/// GET requests need the read-only role, all others the operator role.
+/// The `/api/admin/` and `/api/exec/` requests need the admin role.
+/// The principal of an authorized request is passed on to the handler as
+/// request extension.
+async fn authorize(mut request: Request, next: Next) -> Response {
+    let settings = &common::setting::get_config().auth;
+    if settings.enabled {
+        let principal = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(common::auth::bearer_token)
+            .and_then(|token| common::auth::principal_for_token(settings, token));
+        let path = request.uri().path();
+        let role = if path.starts_with("/api/admin/") || path.starts_with("/api/exec/") {
+            Role::Admin
+        } else if request.method() == Method::GET {
+            Role::ReadOnly
+        } else {
+            Role::Operator
+        };
+        let rpc = format!("{} {}", request.method(), request.uri().path());
+        if let Err(status) = common::auth::check_role(principal.as_ref(), &rpc, role) {
+            return denied(status);
+        }
+        if let Some(principal) = principal {
+            request.extensions_mut().insert(principal);
+        }
+    }
+    next.run(request).await
+}
+
+/// Response to a request refused by [`common::auth`]
+pub fn denied(status: tonic::Status) -> Response {
+    let code = match status.code() {
+        tonic::Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        _ => StatusCode::FORBIDDEN,
+    };
+    (code, Json(status.message().to_string())).into_response()
+}
+
 /// Generate appropriate API response based on handler execution result
 ///
 /// ### Parametets
@@ -82,7 +133,7 @@ mod tests {
 
         // Negative case: Error response
         let err = Box::new(std::io::Error::other("test error")) as Box<dyn StdError + Send + Sync>;
-        let err_response = status(Err(err));
+        let err_response = status(Err(err.into()));
         assert_eq!(err_response.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
 
@@ -169,6 +220,27 @@ mod tests {
         }
     }
 
+    // Requests pass the auth middleware while authentication is disabled
+    #[tokio::test]
+    async fn test_authorize_passes_when_auth_disabled() {
+        let app = Router::new()
+            .route("/api/artifact", post(|| async { StatusCode::OK }))
+            .layer(middleware::from_fn(authorize));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/artifact")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     // ❌ Negative test: Invalid method on /api/notify
     #[tokio::test]
     async fn test_invalid_method_notify() {
@@ -0,0 +1,189 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Activation, deactivation and re-trigger of applied scenarios
+//!
+//! An applied scenario is idle until FilterGateway registers its conditions
+//! and activates it. Deactivating a scenario moves it back to idle from any
+//! state and withdraws its conditions from FilterGateway, activating it
+//! moves it from idle to waiting and registers its conditions again, so
+//! that neither needs its yaml to be applied again.
+//!
+//! A re-trigger runs the action of a scenario on its target package right
+//! away, whatever its conditions. The scenario is moved to satisfied first,
+//! from idle or the end of its last run alike, so that the policy check and
+//! the outcome of the action move it on as if its conditions had been met.
+
+use crate::artifact::query::{QueryError, Scope};
+use crate::grpc::sender::statemanager::StateManagerSender;
+use common::actioncontroller::TriggerActionRequest;
+use common::filtergateway::{Action, HandleScenarioRequest};
+use common::logd;
+use common::statemanager::{ResourceType, StateChange};
+
+/// Why a scenario could not be activated, deactivated or triggered
+#[derive(Debug, PartialEq)]
+pub enum LifecycleError {
+    /// Namespace the caller may not access
+    Forbidden(String),
+    NotFound(String),
+    /// Storage, StateManager, FilterGateway or ActionController could not be
+    /// reached
+    Unavailable(String),
+}
+
+impl std::fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifecycleError::Forbidden(msg)
+            | LifecycleError::NotFound(msg)
+            | LifecycleError::Unavailable(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<QueryError> for LifecycleError {
+    fn from(e: QueryError) -> Self {
+        match e {
+            QueryError::Forbidden(msg) => LifecycleError::Forbidden(msg),
+            QueryError::NotFound(msg) => LifecycleError::NotFound(msg),
+            QueryError::Invalid(msg) | QueryError::Storage(msg) => LifecycleError::Unavailable(msg),
+        }
+    }
+}
+
+/// State a scenario was asked to move to
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Lifecycle {
+    pub scenario: String,
+    /// Target of the last state change sent to the StateManager
+    pub state: String,
+    /// Whether the action of the scenario was run
+    pub triggered: bool,
+}
+
+/// Move an idle scenario to waiting and register its conditions again
+pub async fn activate(scope: Scope<'_>, name: &str) -> Result<Lifecycle, LifecycleError> {
+    let (scenario, yaml) = applied(scope, name).await?;
+    change(&scenario, "idle", "waiting").await?;
+    hand_over(Action::Apply, yaml).await?;
+    Ok(Lifecycle {
+        scenario,
+        state: "waiting".to_string(),
+        triggered: false,
+    })
+}
+
+/// Move a scenario back to idle and withdraw its conditions
+pub async fn deactivate(scope: Scope<'_>, name: &str) -> Result<Lifecycle, LifecycleError> {
+    let (scenario, yaml) = applied(scope, name).await?;
+    change(&scenario, "", "idle").await?;
+    hand_over(Action::Withdraw, yaml).await?;
+    Ok(Lifecycle {
+        scenario,
+        state: "idle".to_string(),
+        triggered: false,
+    })
+}
+
+/// Run the action of a scenario on its target package again
+pub async fn trigger(scope: Scope<'_>, name: &str) -> Result<Lifecycle, LifecycleError> {
+    let (scenario, _) = applied(scope, name).await?;
+    change(&scenario, "idle", "satisfied").await?;
+    let request = TriggerActionRequest {
+        scenario_name: scenario.clone(),
+        correlation_id: common::correlation::current_or_empty(),
+    };
+    let response = crate::grpc::sender::actioncontroller::trigger_action(request)
+        .await
+        .map_err(|status| LifecycleError::Unavailable(status.message().to_string()))?
+        .into_inner();
+    logd!(3, "Re-triggered scenario {}: {}", scenario, response.desc);
+    Ok(Lifecycle {
+        scenario,
+        state: "satisfied".to_string(),
+        triggered: true,
+    })
+}
+
+/// Qualified name and stored yaml of an applied scenario
+async fn applied(scope: Scope<'_>, name: &str) -> Result<(String, String), LifecycleError> {
+    let detail = crate::artifact::query::get("scenario", scope, name).await?;
+    let qualified = common::namespace::qualify(&detail.namespace, &detail.name);
+    Ok((qualified, detail.yaml))
+}
+
+/// Ask the StateManager to move a scenario from `current` to `target`
+///
+/// The StateManager keeps the state it tracks over `current`.
+async fn change(scenario: &str, current: &str, target: &str) -> Result<(), LifecycleError> {
+    let state_change = state_change(scenario, current, target);
+    StateManagerSender::new()
+        .send_state_change(state_change)
+        .await
+        .map_err(|status| LifecycleError::Unavailable(status.message().to_string()))?;
+    logd!(2, "Asked to move scenario {} to {}", scenario, target);
+    Ok(())
+}
+
+fn state_change(scenario: &str, current: &str, target: &str) -> StateChange {
+    StateChange {
+        resource_type: ResourceType::Scenario as i32,
+        resource_name: scenario.to_string(),
+        current_state: current.to_string(),
+        target_state: target.to_string(),
+        transition_id: common::correlation::transition_id("apiserver"),
+        timestamp_ns: chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        source: "apiserver".to_string(),
+        correlation_id: common::correlation::current_or_empty(),
+        ..Default::default()
+    }
+}
+
+/// Register or withdraw the conditions of a scenario in FilterGateway
+async fn hand_over(action: Action, scenario: String) -> Result<(), LifecycleError> {
+    let request = HandleScenarioRequest {
+        action: action.into(),
+        scenario,
+    };
+    crate::grpc::sender::filtergateway::send(request)
+        .await
+        .map_err(|status| LifecycleError::Unavailable(status.message().to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_change() {
+        let change = state_change("team/s", "idle", "satisfied");
+        assert_eq!(change.resource_type, ResourceType::Scenario as i32);
+        assert_eq!(change.resource_name, "team/s");
+        assert_eq!(
+            (change.current_state.as_str(), change.target_state.as_str()),
+            ("idle", "satisfied")
+        );
+        assert_eq!(change.source, "apiserver");
+        assert!(change.transition_id.starts_with("apiserver"));
+    }
+
+    #[test]
+    fn test_query_errors() {
+        assert_eq!(
+            LifecycleError::from(QueryError::NotFound("Scenario/s is not applied".into())),
+            LifecycleError::NotFound("Scenario/s is not applied".into())
+        );
+        assert!(matches!(
+            LifecycleError::from(QueryError::Forbidden(String::new())),
+            LifecycleError::Forbidden(_)
+        ));
+        assert!(matches!(
+            LifecycleError::from(QueryError::Storage("down".into())),
+            LifecycleError::Unavailable(_)
+        ));
+    }
+}
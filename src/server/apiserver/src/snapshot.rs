@@ -0,0 +1,436 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Snapshot and restore of the whole system state
+//!
+//! A snapshot is a gzip compressed tarball holding
+//! * `manifest.json` - format version, creation time and key count
+//! * `store.json` - every stored artifact, resource state, node registration,
+//!   setting history and log key with its value
+//! * `relationships.json` - the artifacts each Scenario and Package refers to
+//!
+//! The store has no transactions, so the keys are read until two passes in a
+//! row agree. Restoring writes the keys of a snapshot into a store without
+//! artifacts, node registrations are left out unless asked for since they
+//! belong to the hosts the snapshot was taken on. Keys under the restored
+//! prefixes that the snapshot does not have are removed first, so that the
+//! store holds the state of the snapshot and nothing else.
+//!
+//! Neither the state taken nor the archive restored may exceed
+//! [`MAX_SNAPSHOT_BYTES`] once uncompressed.
+
+use common::error::Error;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Version of the snapshot format
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Key prefixes of the system state
+///
+/// The leader lease of the StateManager is left out, it belongs to the
+/// instance holding it.
+pub const PREFIXES: &[&str] = &[
+    "Scenario/",
+    "Package/",
+    "Model/",
+    "Volume/",
+    "Network/",
+    "Node/",
+    "Pod/",
+    "Autostart/",
+    "NetworkSetup/",
+    "/scenario/",
+    "/package/",
+    "/model/",
+    "/volume/",
+    "/network/",
+    "/node/",
+    "nodes/",
+    "cluster/",
+    "/transition/",
+    "/schedule/",
+    "/vehicle/",
+    "/statemanager/dead-letter/",
+    "/statemanager/safety-log/",
+    "/outbox/",
+    "/outbox-dead/",
+    "/piccolo/settings/",
+    "/piccolo/metadata/",
+    "/piccolo/metrics/",
+    "/piccolo/logs/",
+];
+
+/// Bytes of keys and values a snapshot holds at most, uncompressed
+pub const MAX_SNAPSHOT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Prefixes of the artifacts, a store holding one of them is not fresh
+const ARTIFACT_PREFIXES: [&str; 3] = ["Scenario/", "Package/", "Model/"];
+
+/// Prefixes of the node registrations
+const NODE_PREFIXES: [&str; 3] = ["nodes/", "cluster/", "/node/"];
+
+/// Passes over the store before giving up on a consistent read
+const READ_ATTEMPTS: usize = 5;
+
+/// Description of a snapshot
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// RFC 3339 time the snapshot was taken
+    pub created: String,
+    pub keys: usize,
+}
+
+/// Keys written by a restore
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct RestoreResult {
+    pub restored: usize,
+    /// Node registrations left out
+    pub skipped: usize,
+    /// Stored keys the snapshot does not have, removed
+    pub cleared: usize,
+}
+
+/// Options of a restore
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct RestoreOptions {
+    /// Restore into a store that already has artifacts
+    pub force: bool,
+    /// Restore the node registrations as well
+    pub nodes: bool,
+}
+
+/// Bytes of the keys and values of a store
+fn size(store: &BTreeMap<String, String>) -> u64 {
+    store
+        .iter()
+        .map(|(key, value)| (key.len() + value.len()) as u64)
+        .sum()
+}
+
+/// Read every key of the system state
+async fn read_store() -> common::Result<BTreeMap<String, String>> {
+    let mut store = BTreeMap::new();
+    for prefix in PREFIXES {
+        store.extend(
+            common::etcd::get_all_with_prefix(prefix)
+                .await
+                .map_err(common::etcd::error)?,
+        );
+        if size(&store) > MAX_SNAPSHOT_BYTES {
+            return Err(Error::PreconditionFailed(format!(
+                "State exceeds the snapshot limit of {} bytes",
+                MAX_SNAPSHOT_BYTES
+            )));
+        }
+    }
+    Ok(store)
+}
+
+/// Read the system state until two passes agree
+async fn read_consistent() -> common::Result<BTreeMap<String, String>> {
+    let mut previous = read_store().await?;
+    for _ in 1..READ_ATTEMPTS {
+        let current = read_store().await?;
+        if current == previous {
+            return Ok(current);
+        }
+        previous = current;
+    }
+    Err(Error::Unavailable(format!(
+        "State kept changing over {} reads, retry the snapshot",
+        READ_ATTEMPTS
+    )))
+}
+
+/// Artifacts each stored Scenario and Package refers to, as `Kind/name`
+pub fn relationships(store: &BTreeMap<String, String>) -> BTreeMap<String, Vec<String>> {
+    store
+        .iter()
+        .filter_map(|(key, value)| {
+            let (kind, _) = key.split_once('/')?;
+            if kind != "Scenario" && kind != "Package" {
+                return None;
+            }
+            let value: serde_yaml::Value = serde_yaml::from_str(value).ok()?;
+            let refs = crate::artifact::bulk::references(kind, &value).ok()?;
+            Some((key.clone(), refs))
+        })
+        .collect()
+}
+
+fn append(builder: &mut tar::Builder<Vec<u8>>, name: &str, content: &[u8]) -> common::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, name, content)?;
+    Ok(())
+}
+
+/// Pack a store into a snapshot archive
+pub fn pack(store: &BTreeMap<String, String>) -> common::Result<Vec<u8>> {
+    let manifest = Manifest {
+        version: FORMAT_VERSION,
+        created: chrono::Utc::now().to_rfc3339(),
+        keys: store.len(),
+    };
+    let mut builder = tar::Builder::new(Vec::new());
+    append(
+        &mut builder,
+        "manifest.json",
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    append(
+        &mut builder,
+        "store.json",
+        &serde_json::to_vec_pretty(store)?,
+    )?;
+    append(
+        &mut builder,
+        "relationships.json",
+        &serde_json::to_vec_pretty(&relationships(store))?,
+    )?;
+    let tarball = builder.into_inner()?;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, &tarball)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decompress an archive, refusing one larger than `limit` bytes
+fn decompress(archive: &[u8], limit: u64) -> common::Result<Vec<u8>> {
+    let mut tarball = Vec::new();
+    flate2::read::GzDecoder::new(archive)
+        .take(limit + 1)
+        .read_to_end(&mut tarball)?;
+    if tarball.len() as u64 > limit {
+        return Err(Error::InvalidRequest(format!(
+            "Snapshot exceeds the limit of {} bytes",
+            limit
+        )));
+    }
+    Ok(tarball)
+}
+
+/// Unpack the manifest and store of a snapshot archive
+pub fn unpack(archive: &[u8]) -> common::Result<(Manifest, BTreeMap<String, String>)> {
+    // The tarball holds the store twice, as store.json and relationships.json
+    let tarball = decompress(archive, 2 * MAX_SNAPSHOT_BYTES)?;
+    let mut tar = tar::Archive::new(tarball.as_slice());
+    let (mut manifest, mut store) = (None, None);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        match name.as_str() {
+            "manifest.json" => manifest = Some(serde_json::from_slice::<Manifest>(&content)?),
+            "store.json" => store = Some(serde_json::from_slice(&content)?),
+            _ => {}
+        }
+    }
+    let manifest = manifest
+        .ok_or_else(|| Error::InvalidRequest("Snapshot has no manifest.json".to_string()))?;
+    if manifest.version != FORMAT_VERSION {
+        return Err(Error::InvalidRequest(format!(
+            "Snapshot format {} is not supported, expected {}",
+            manifest.version, FORMAT_VERSION
+        )));
+    }
+    Ok((
+        manifest,
+        store.ok_or_else(|| Error::InvalidRequest("Snapshot has no store.json".to_string()))?,
+    ))
+}
+
+/// Take a snapshot of the system state
+///
+/// ### Returns
+/// * `Ok(Vec<u8>)` - the tar.gz archive
+pub async fn export() -> common::Result<Vec<u8>> {
+    let store = read_consistent().await?;
+    common::logd!(3, "Snapshot of {} keys taken", store.len());
+    pack(&store)
+}
+
+/// Keys of a snapshot to restore, the node registrations left out unless `nodes`
+fn restorable(store: BTreeMap<String, String>, nodes: bool) -> (Vec<(String, String)>, usize) {
+    let total = store.len();
+    let items: Vec<(String, String)> = store
+        .into_iter()
+        .filter(|(key, _)| nodes || !NODE_PREFIXES.iter().any(|p| key.starts_with(p)))
+        .collect();
+    let skipped = total - items.len();
+    (items, skipped)
+}
+
+/// Prefixes a restore writes, the node registrations only with `nodes`
+fn restored_prefixes(nodes: bool) -> impl Iterator<Item = &'static str> {
+    PREFIXES
+        .iter()
+        .copied()
+        .filter(move |prefix| nodes || !NODE_PREFIXES.contains(prefix))
+}
+
+/// Stored keys missing from the restored ones
+fn stale(stored: Vec<(String, String)>, items: &[(String, String)]) -> Vec<String> {
+    let restored: HashSet<&str> = items.iter().map(|(key, _)| key.as_str()).collect();
+    stored
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|key| !restored.contains(key.as_str()))
+        .collect()
+}
+
+/// Remove the keys under the restored prefixes the snapshot does not have
+async fn clear(items: &[(String, String)], nodes: bool) -> common::Result<usize> {
+    let mut cleared = 0;
+    for prefix in restored_prefixes(nodes) {
+        let stored = common::etcd::get_all_with_prefix(prefix)
+            .await
+            .map_err(common::etcd::error)?;
+        for key in stale(stored, items) {
+            common::etcd::delete(&key)
+                .await
+                .map_err(common::etcd::error)?;
+            cleared += 1;
+        }
+    }
+    Ok(cleared)
+}
+
+/// Write the keys of a snapshot into the store
+///
+/// ### Parameters
+/// * `archive` - tar.gz archive made by [`export`]
+/// * `options` - whether to overwrite existing artifacts and restore nodes
+pub async fn restore(archive: &[u8], options: &RestoreOptions) -> common::Result<RestoreResult> {
+    let (manifest, store) = unpack(archive)?;
+    if !options.force {
+        for prefix in ARTIFACT_PREFIXES {
+            if !common::etcd::get_all_with_prefix(prefix)
+                .await
+                .map_err(common::etcd::error)?
+                .is_empty()
+            {
+                return Err(Error::PreconditionFailed(format!(
+                    "Store already has {} artifacts, restore with force to overwrite",
+                    prefix.trim_end_matches('/')
+                )));
+            }
+        }
+    }
+
+    let (items, skipped) = restorable(store, options.nodes);
+    let restored = items.len();
+    let cleared = clear(&items, options.nodes).await?;
+    common::etcd::batch_put(items)
+        .await
+        .map_err(common::etcd::error)?;
+    common::logd!(
+        3,
+        "Restored {} keys of the snapshot taken {}, {} node keys skipped, {} stale keys removed",
+        restored,
+        manifest.created,
+        skipped,
+        cleared
+    );
+    Ok(RestoreResult {
+        restored,
+        skipped,
+        cleared,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> BTreeMap<String, String> {
+        [
+            ("Scenario/s", "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: s\nspec:\n  condition:\n  action: update\n  target: p\n"),
+            ("/scenario/s/state", "playing"),
+            ("nodes/HPC", "10.0.0.1"),
+            ("cluster/nodes/HPC", "{}"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_pack_and_unpack() {
+        let store = store();
+        let archive = pack(&store).unwrap();
+        let (manifest, unpacked) = unpack(&archive).unwrap();
+        assert_eq!(manifest.version, FORMAT_VERSION);
+        assert_eq!(manifest.keys, 4);
+        assert_eq!(unpacked, store);
+
+        assert!(unpack(b"not a snapshot").is_err());
+    }
+
+    #[test]
+    fn test_archives_over_the_limit_are_refused() {
+        let archive = pack(&store()).unwrap();
+        assert!(decompress(&archive, 1 << 20).is_ok());
+        let err = decompress(&archive, 64).unwrap_err();
+        assert!(matches!(err, Error::InvalidRequest(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_every_key_prefix_is_covered() {
+        let keys = [
+            common::spec::artifact::package::PackageDiff::key("p"),
+            "NetworkSetup/m".to_string(),
+            "/transition/t1".to_string(),
+            "/schedule/s".to_string(),
+            "/vehicle/mode".to_string(),
+            "/statemanager/dead-letter/t1".to_string(),
+            "/outbox-dead/apiserver/1".to_string(),
+        ];
+        for key in keys {
+            assert!(PREFIXES.iter().any(|p| key.starts_with(p)), "{}", key);
+        }
+        assert!(!PREFIXES
+            .iter()
+            .any(|p| "/statemanager/leader".starts_with(p)));
+    }
+
+    #[test]
+    fn test_stale_keys_are_cleared() {
+        let (items, _) = restorable(store(), false);
+        let stored = vec![
+            ("Scenario/s".to_string(), "old".to_string()),
+            ("Scenario/gone".to_string(), "old".to_string()),
+        ];
+        assert_eq!(stale(stored, &items), vec!["Scenario/gone"]);
+        assert!(restored_prefixes(false).all(|p| !NODE_PREFIXES.contains(&p)));
+        assert_eq!(restored_prefixes(true).count(), PREFIXES.len());
+    }
+
+    #[test]
+    fn test_relationships() {
+        let relationships = relationships(&store());
+        assert_eq!(relationships["Scenario/s"], vec!["Package/p"]);
+        assert_eq!(relationships.len(), 1);
+    }
+
+    #[test]
+    fn test_restorable_skips_nodes() {
+        let (items, skipped) = restorable(store(), false);
+        assert_eq!(skipped, 2);
+        assert_eq!(
+            items.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            vec!["/scenario/s/state", "Scenario/s"]
+        );
+        assert_eq!(restorable(store(), true).1, 0);
+    }
+}
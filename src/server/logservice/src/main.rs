@@ -25,8 +25,12 @@ pub const LOG_HISTORY_CAPACITY: usize = 2000;
 /// Entry point: Open a Unix socket and run the receiving task
 /// and HTTP server task in parallel. Pressing Ctrl+C stops
 /// both tasks and cleans up the socket file.
+///
+/// The aggregator is ready once the socket and the HTTP listener are bound.
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    common::address::validate_or_exit("logservice");
+    common::health::init("logservice", false);
     let logd_path = common::logd::LOGD_SOCKET_PATH;
     let logd = bind_sock(logd_path)?;
     println!("[aggregator] sockets ready");
@@ -241,14 +241,19 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
 "#;
 
 /// Launch the HTTP server and keep serving until the task is cancelled.
+///
+/// The log socket is bound before, so the aggregator is marked started once
+/// the listener is bound, which makes `/readyz` answer `200 OK`.
 pub async fn run_http_server(state: WebState, addr: SocketAddr) {
     let app = Router::new()
         .route("/", get(serve_index))
         .route("/logs", get(stream_logs))
-        .with_state(state);
+        .with_state(state)
+        .merge(common::health::router());
 
     match TcpListener::bind(addr).await {
         Ok(listener) => {
+            common::health::set_started();
             if let Err(err) = axum::serve(listener, app.into_make_service()).await {
                 eprintln!("[aggregator] http server error: {err}");
             }
@@ -267,7 +267,9 @@ impl DataStore {
             .insert(container_id.clone(), node_name.clone());
 
         // Store to etcd
-        if let Err(e) = crate::etcd_storage::store_container_info(&container_info).await {
+        if let Err(e) =
+            crate::etcd_storage::store_container_info_on_node(&container_info, &node_name).await
+        {
             eprintln!(
                 "[ETCD] Warning: Failed to store ContainerInfo to etcd: {}",
                 e
@@ -20,7 +20,9 @@ async fn store_info<T: Serialize>(
     let json_data = serde_json::to_string(info)
         .map_err(|e| format!("Failed to serialize {}: {}", resource_type, e))?;
 
-    common::etcd::put(&key, &json_data).await?;
+    common::etcd::put(&key, &json_data)
+        .await
+        .map_err(common::etcd::error)?;
     println!(
         "[ETCD] Stored the metrics for {}: {}",
         resource_type, resource_id
@@ -34,7 +36,7 @@ async fn get_info<T: DeserializeOwned>(
     resource_id: &str,
 ) -> common::Result<T> {
     let key = format!("/piccolo/metrics/{}/{}", resource_type, resource_id);
-    let json_data = common::etcd::get(&key).await?;
+    let json_data = common::etcd::get(&key).await.map_err(common::etcd::error)?;
 
     let info: T = serde_json::from_str(&json_data)
         .map_err(|e| format!("Failed to deserialize {}: {}", resource_type, e))?;
@@ -45,7 +47,9 @@ async fn get_info<T: DeserializeOwned>(
 /// Generic function to delete info from etcd
 async fn delete_info(resource_type: &str, resource_id: &str) -> common::Result<()> {
     let key = format!("/piccolo/metrics/{}/{}", resource_type, resource_id);
-    common::etcd::delete(&key).await?;
+    common::etcd::delete(&key)
+        .await
+        .map_err(common::etcd::error)?;
     println!(
         "[ETCD] Deleted the metrics for {}: {}",
         resource_type, resource_id
@@ -56,7 +60,9 @@ async fn delete_info(resource_type: &str, resource_id: &str) -> common::Result<(
 /// Generic function to get all items of a type from etcd
 async fn get_all_info<T: DeserializeOwned>(resource_type: &str) -> common::Result<Vec<T>> {
     let prefix = format!("/piccolo/metrics/{}/", resource_type);
-    let kv_pairs = common::etcd::get_all_with_prefix(&prefix).await?;
+    let kv_pairs = common::etcd::get_all_with_prefix(&prefix)
+        .await
+        .map_err(common::etcd::error)?;
 
     let mut items = Vec::new();
     for kv in kv_pairs {
@@ -91,8 +97,30 @@ pub async fn store_board_info(board_info: &BoardInfo) -> common::Result<()> {
 
 /// Store ContainerInfo in etcd - Using same pattern as others
 pub async fn store_container_info(container_info: &ContainerInfo) -> common::Result<()> {
-    // Convert protobuf ContainerInfo to JSON for storage using the same pattern
-    let json_value = serde_json::json!({
+    store_info(
+        "containers",
+        &container_info.id,
+        &container_json(container_info),
+    )
+    .await
+}
+
+/// Store ContainerInfo in etcd with the node it runs on
+///
+/// The node lets the ActionController tell the instance of a model on one
+/// node from the one on another while the model moves.
+pub async fn store_container_info_on_node(
+    container_info: &ContainerInfo,
+    node_name: &str,
+) -> common::Result<()> {
+    let mut json_value = container_json(container_info);
+    json_value["node"] = serde_json::json!(node_name);
+    store_info("containers", &container_info.id, &json_value).await
+}
+
+/// Convert protobuf ContainerInfo to JSON for storage using the same pattern
+fn container_json(container_info: &ContainerInfo) -> Value {
+    serde_json::json!({
         "id": container_info.id,
         "names": container_info.names,
         "image": container_info.image,
@@ -100,9 +128,7 @@ pub async fn store_container_info(container_info: &ContainerInfo) -> common::Res
         "config": container_info.config,
         "annotation": container_info.annotation,
         "stats": container_info.stats,
-    });
-
-    store_info("containers", &container_info.id, &json_value).await
+    })
 }
 
 /// Retrieve NodeInfo from etcd
@@ -181,7 +207,9 @@ pub async fn get_all_boards() -> common::Result<Vec<BoardInfo>> {
 /// Get all containers from etcd
 pub async fn get_all_containers() -> common::Result<Vec<ContainerInfo>> {
     let prefix = "/piccolo/metrics/containers/".to_string();
-    let kv_pairs = common::etcd::get_all_with_prefix(&prefix).await?;
+    let kv_pairs = common::etcd::get_all_with_prefix(&prefix)
+        .await
+        .map_err(common::etcd::error)?;
 
     let mut containers = Vec::new();
     for kv in kv_pairs {
@@ -459,6 +487,25 @@ mod tests {
         assert!(result.is_ok() || result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_store_container_info_on_node() {
+        common::etcd::use_in_memory_store();
+        let container = sample_container("c-on-node", "container-on-node");
+        store_container_info_on_node(&container, "ZONE")
+            .await
+            .unwrap();
+
+        let stored = common::etcd::get("/piccolo/metrics/containers/c-on-node")
+            .await
+            .unwrap();
+        let stored: Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored["node"], "ZONE");
+        assert_eq!(
+            get_container_info("c-on-node").await.unwrap().names,
+            container.names
+        );
+    }
+
     #[tokio::test]
     async fn test_get_node_info_not_found() {
         let result = get_node_info("notfound").await;
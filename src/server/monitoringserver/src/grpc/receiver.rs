@@ -4,8 +4,8 @@
 */
 use common::monitoringserver::monitoring_server_connection_server::MonitoringServerConnection;
 use common::monitoringserver::{
-    ContainerList, NodeInfo, SendContainerListResponse, SendNodeInfoResponse,
-    StressMonitoringMetric, StressMonitoringMetricResponse,
+    ClusterMetrics, ClusterMetricsRequest, ContainerList, NodeInfo, SendContainerListResponse,
+    SendNodeInfoResponse, StressMonitoringMetric, StressMonitoringMetricResponse,
 };
 use tokio::sync::mpsc;
 use tonic::{Request, Response, Status};
@@ -85,7 +85,9 @@ impl MonitoringServerConnection for MonitoringServerReceiver {
         &'life self,
         request: Request<ContainerList>,
     ) -> Result<Response<SendContainerListResponse>, Status> {
-        let req: ContainerList = request.into_inner();
+        let mut req: ContainerList = request.into_inner();
+        let version = common::version::accept("SendContainerList", req.api_version)?;
+        common::version::upgrade_container_list(&mut req, version);
 
         match self.tx_container.send(req).await {
             Ok(_) => Ok(tonic::Response::new(SendContainerListResponse {
@@ -140,6 +142,16 @@ impl MonitoringServerConnection for MonitoringServerReceiver {
             )),
         }
     }
+
+    /// Handle a query of the cluster-wide rollup
+    ///
+    /// Answers with the latest reports of all nodeagents summarized per node and per package.
+    async fn get_cluster_metrics<'life>(
+        &'life self,
+        request: Request<ClusterMetricsRequest>,
+    ) -> Result<Response<ClusterMetrics>, Status> {
+        Ok(Response::new(crate::rollup::query(&request.into_inner())))
+    }
 }
 
 #[cfg(test)]
@@ -173,6 +185,7 @@ mod tests {
         ContainerList {
             node_name: node_name.to_string(),
             containers: vec![],
+            api_version: common::version::API_VERSION,
         }
     }
 
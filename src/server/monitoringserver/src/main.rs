@@ -12,6 +12,7 @@ pub mod data_structures;
 pub mod etcd_storage;
 pub mod grpc;
 pub mod manager;
+pub mod rollup;
 
 use common::logd;
 use common::logd::logger;
@@ -32,6 +33,7 @@ async fn launch_manager(
     match manager.initialize().await {
         Ok(_) => {
             logd!(3, "MonitoringServerManager successfully initialized");
+            common::health::set_started();
             if let Err(e) = manager.run().await {
                 logd!(5, "Error running MonitoringServerManager: {:?}", e);
             }
@@ -65,6 +67,7 @@ async fn initialize(
 
     if let Err(e) = Server::builder()
         .add_service(MonitoringServerConnectionServer::new(server))
+        .add_service(common::health::grpc_service())
         .serve(addr)
         .await
     {
@@ -76,6 +79,12 @@ async fn initialize(
 async fn main() {
     let _ = logger::init_async_logger("monitoringserver").await;
     logd!(1, "initiailize monitoring server");
+    common::health::init("monitoringserver", true);
+    common::address::validate_or_exit("monitoringserver");
+    tokio::spawn(common::health::serve_with(
+        common::monitoringserver::open_health_server(),
+        rollup::router(),
+    ));
 
     let (tx_container, rx_container) = channel::<ContainerList>(100);
     let (tx_node, rx_node) = channel::<NodeInfo>(100);
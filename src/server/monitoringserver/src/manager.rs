@@ -67,6 +67,10 @@ impl MonitoringServerManager {
             container_list.node_name,
             container_list.containers.len()
         );
+        crate::rollup::record_containers(
+            &container_list.node_name,
+            container_list.containers.clone(),
+        );
 
         let current_container_ids: Vec<String> = container_list
             .containers
@@ -265,6 +269,7 @@ impl MonitoringServerManager {
     async fn handle_node_info(&self, node_info: NodeInfo) {
         // Print detailed NodeInfo first
         self.print_node_info(&node_info);
+        crate::rollup::record_node(node_info.clone());
 
         // Store NodeInfo and update SocInfo/BoardInfo with etcd storage
         {
@@ -882,6 +887,7 @@ mod tests {
         ContainerList {
             node_name: node_name.to_string(),
             containers,
+            api_version: common::version::API_VERSION,
         }
     }
 
@@ -0,0 +1,475 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Cluster-wide rollup of the nodeagent reports
+//!
+//! The manager feeds every NodeInfo and ContainerList it receives into one
+//! [`Cluster`]. A query summarizes it per node and per package, from the
+//! `io.piccolo.package` label of the containers, as a [`ClusterMetrics`].
+//! It is served by the `GetClusterMetrics` RPC and as Prometheus metrics on
+//! `/metrics` of the health listener.
+//!
+//! A node whose last NodeInfo is older than [`STALE_AFTER`] is unhealthy,
+//! and so are the packages with a container on it.
+
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use common::monitoringserver::{
+    ClusterMetrics, ClusterMetricsRequest, ContainerInfo, NodeInfo, NodeMetrics, PackageMetrics,
+};
+use common::spec::k8s::pod::{model_of, LABEL_PACKAGE};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+/// Age of the last NodeInfo after which a node is unhealthy
+pub const STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// Latest reports of every node
+#[derive(Debug, Default)]
+pub struct Cluster {
+    nodes: HashMap<String, (NodeInfo, SystemTime)>,
+    /// Containers of each node, by node name
+    containers: HashMap<String, Vec<ContainerInfo>>,
+}
+
+impl Cluster {
+    pub fn update_node(&mut self, node: NodeInfo, at: SystemTime) {
+        self.nodes.insert(node.node_name.clone(), (node, at));
+    }
+
+    /// Replace the containers of a node with its latest ContainerList
+    pub fn update_containers(&mut self, node_name: &str, containers: Vec<ContainerInfo>) {
+        self.containers.insert(node_name.to_string(), containers);
+    }
+
+    /// Summarize the cluster at `now`, narrowed to the node and package of
+    /// the request when they are set
+    pub fn rollup(&self, request: &ClusterMetricsRequest, now: SystemTime) -> ClusterMetrics {
+        let mut names: BTreeSet<&String> = self.nodes.keys().collect();
+        names.extend(self.containers.keys());
+
+        let mut metrics = ClusterMetrics::default();
+        let mut packages: BTreeMap<String, PackageRollup> = BTreeMap::new();
+        for name in names {
+            if !request.node_name.is_empty() && &request.node_name != name {
+                continue;
+            }
+            let containers = self.containers.get(name).map(Vec::as_slice).unwrap_or(&[]);
+            let node = self.node_metrics(name, containers, now);
+
+            for container in containers {
+                let Some(package) = package_of(container) else {
+                    continue;
+                };
+                if !request.package.is_empty() && &request.package != package {
+                    continue;
+                }
+                packages
+                    .entry(package.clone())
+                    .or_default()
+                    .add(name, container, node.healthy);
+            }
+
+            metrics.node_count += 1;
+            if node.healthy {
+                metrics.healthy_nodes += 1;
+                metrics.cpu_usage += node.cpu_usage;
+            }
+            metrics.cpu_count += node.cpu_count;
+            metrics.used_memory += node.used_memory;
+            metrics.total_memory += node.total_memory;
+            metrics.containers += node.containers;
+            metrics.running_containers += node.running_containers;
+            metrics.nodes.push(node);
+        }
+        if metrics.healthy_nodes > 0 {
+            metrics.cpu_usage /= metrics.healthy_nodes as f64;
+        }
+        metrics.packages = packages
+            .into_iter()
+            .map(|(package, rollup)| rollup.finish(package))
+            .collect();
+        metrics
+    }
+
+    fn node_metrics(
+        &self,
+        name: &str,
+        containers: &[ContainerInfo],
+        now: SystemTime,
+    ) -> NodeMetrics {
+        let mut metrics = NodeMetrics {
+            node_name: name.to_string(),
+            containers: containers.len() as u32,
+            running_containers: containers.iter().filter(|c| is_running(c)).count() as u32,
+            ..Default::default()
+        };
+        if let Some((info, at)) = self.nodes.get(name) {
+            let age = now.duration_since(*at).unwrap_or_default();
+            metrics.ip = info.ip.clone();
+            metrics.cpu_usage = info.cpu_usage;
+            metrics.cpu_count = info.cpu_count;
+            metrics.used_memory = info.used_memory;
+            metrics.total_memory = info.total_memory;
+            metrics.mem_usage = info.mem_usage;
+            metrics.rx_bytes = info.rx_bytes;
+            metrics.tx_bytes = info.tx_bytes;
+            metrics.healthy = age <= STALE_AFTER;
+            metrics.last_report_ns = at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as i64)
+                .unwrap_or_default();
+        }
+        metrics
+    }
+}
+
+/// Package totals while the containers are summed up
+#[derive(Default)]
+struct PackageRollup {
+    models: BTreeSet<String>,
+    nodes: BTreeSet<String>,
+    containers: u32,
+    running_containers: u32,
+    cpu_total_usage: u64,
+    memory_usage: u64,
+    /// Containers not running or on an unhealthy node
+    unhealthy: u32,
+}
+
+impl PackageRollup {
+    fn add(&mut self, node: &str, container: &ContainerInfo, node_healthy: bool) {
+        let running = is_running(container);
+        if let Some(model) = model_of(&container.annotation) {
+            self.models.insert(model);
+        }
+        self.nodes.insert(node.to_string());
+        self.containers += 1;
+        self.running_containers += running as u32;
+        self.cpu_total_usage += stat(container, "CpuTotalUsage");
+        self.memory_usage += stat(container, "MemoryUsage");
+        self.unhealthy += !(running && node_healthy) as u32;
+    }
+
+    fn finish(self, package: String) -> PackageMetrics {
+        PackageMetrics {
+            package,
+            models: self.models.into_iter().collect(),
+            nodes: self.nodes.into_iter().collect(),
+            containers: self.containers,
+            running_containers: self.running_containers,
+            cpu_total_usage: self.cpu_total_usage,
+            memory_usage: self.memory_usage,
+            healthy: self.unhealthy == 0,
+        }
+    }
+}
+
+fn package_of(container: &ContainerInfo) -> Option<&String> {
+    [LABEL_PACKAGE, "package"]
+        .iter()
+        .find_map(|key| container.annotation.get(*key))
+}
+
+fn is_running(container: &ContainerInfo) -> bool {
+    container
+        .state
+        .get("Status")
+        .is_some_and(|status| status.eq_ignore_ascii_case("running"))
+}
+
+/// Numeric container stat, 0 when it is missing or unavailable
+fn stat(container: &ContainerInfo, key: &str) -> u64 {
+    container
+        .stats
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+fn cluster() -> &'static Mutex<Cluster> {
+    static CLUSTER: OnceLock<Mutex<Cluster>> = OnceLock::new();
+    CLUSTER.get_or_init(|| Mutex::new(Cluster::default()))
+}
+
+/// Record the latest NodeInfo of a node
+pub fn record_node(node: NodeInfo) {
+    let mut cluster = cluster().lock().unwrap_or_else(|e| e.into_inner());
+    cluster.update_node(node, SystemTime::now());
+}
+
+/// Record the latest ContainerList of a node
+pub fn record_containers(node_name: &str, containers: Vec<ContainerInfo>) {
+    let mut cluster = cluster().lock().unwrap_or_else(|e| e.into_inner());
+    cluster.update_containers(node_name, containers);
+}
+
+/// Rollup of the recorded reports
+pub fn query(request: &ClusterMetricsRequest) -> ClusterMetrics {
+    let cluster = cluster().lock().unwrap_or_else(|e| e.into_inner());
+    cluster.rollup(request, SystemTime::now())
+}
+
+/// Escape a Prometheus label value
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render a rollup in the Prometheus text exposition format
+pub fn prometheus(metrics: &ClusterMetrics) -> String {
+    let mut out = String::new();
+    let mut metric = |kind: &str, name: &str, help: &str, samples: Vec<(String, f64)>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    let mut gauge =
+        |name: &str, help: &str, samples: Vec<(String, f64)>| metric("gauge", name, help, samples);
+
+    gauge(
+        "pullpiri_cluster_nodes",
+        "Nodes that reported to the monitoring server",
+        vec![(String::new(), metrics.node_count as f64)],
+    );
+    gauge(
+        "pullpiri_cluster_healthy_nodes",
+        "Nodes that reported within the staleness window",
+        vec![(String::new(), metrics.healthy_nodes as f64)],
+    );
+    gauge(
+        "pullpiri_cluster_cpu_usage",
+        "Mean CPU usage of the healthy nodes in percent",
+        vec![(String::new(), metrics.cpu_usage)],
+    );
+    gauge(
+        "pullpiri_cluster_memory_used_bytes",
+        "Memory used on all nodes",
+        vec![(String::new(), metrics.used_memory as f64)],
+    );
+    gauge(
+        "pullpiri_cluster_memory_total_bytes",
+        "Memory of all nodes",
+        vec![(String::new(), metrics.total_memory as f64)],
+    );
+
+    let per_node = |value: fn(&NodeMetrics) -> f64| -> Vec<(String, f64)> {
+        metrics
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    format!("{{node=\"{}\"}}", label(&node.node_name)),
+                    value(node),
+                )
+            })
+            .collect()
+    };
+    gauge(
+        "pullpiri_node_healthy",
+        "Whether the node reported within the staleness window",
+        per_node(|n| n.healthy as u8 as f64),
+    );
+    gauge(
+        "pullpiri_node_cpu_usage",
+        "CPU usage of the node in percent",
+        per_node(|n| n.cpu_usage),
+    );
+    gauge(
+        "pullpiri_node_memory_used_bytes",
+        "Memory used on the node",
+        per_node(|n| n.used_memory as f64),
+    );
+    gauge(
+        "pullpiri_node_memory_total_bytes",
+        "Memory of the node",
+        per_node(|n| n.total_memory as f64),
+    );
+    gauge(
+        "pullpiri_node_containers",
+        "Containers on the node",
+        per_node(|n| n.containers as f64),
+    );
+    gauge(
+        "pullpiri_node_running_containers",
+        "Running containers on the node",
+        per_node(|n| n.running_containers as f64),
+    );
+
+    let per_package = |value: fn(&PackageMetrics) -> f64| -> Vec<(String, f64)> {
+        metrics
+            .packages
+            .iter()
+            .map(|package| {
+                (
+                    format!("{{package=\"{}\"}}", label(&package.package)),
+                    value(package),
+                )
+            })
+            .collect()
+    };
+    gauge(
+        "pullpiri_package_healthy",
+        "Whether every container of the package runs on a healthy node",
+        per_package(|p| p.healthy as u8 as f64),
+    );
+    gauge(
+        "pullpiri_package_containers",
+        "Containers of the package",
+        per_package(|p| p.containers as f64),
+    );
+    gauge(
+        "pullpiri_package_running_containers",
+        "Running containers of the package",
+        per_package(|p| p.running_containers as f64),
+    );
+    gauge(
+        "pullpiri_package_memory_usage_bytes",
+        "Memory used by the containers of the package",
+        per_package(|p| p.memory_usage as f64),
+    );
+    // CPU time only grows, until a container of the package is replaced
+    metric(
+        "counter",
+        "pullpiri_package_cpu_usage_total",
+        "Total CPU time of the containers of the package",
+        per_package(|p| p.cpu_total_usage as f64),
+    );
+    out
+}
+
+/// Route of `/metrics`, served next to the health endpoints
+pub fn router() -> Router {
+    Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                prometheus(&query(&ClusterMetricsRequest::default())),
+            )
+                .into_response()
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::spec::k8s::pod::LABEL_MODEL;
+
+    fn node(name: &str, cpu_usage: f64) -> NodeInfo {
+        NodeInfo {
+            node_name: name.to_string(),
+            ip: "10.0.0.1".to_string(),
+            cpu_usage,
+            cpu_count: 4,
+            used_memory: 1024,
+            total_memory: 4096,
+            ..Default::default()
+        }
+    }
+
+    fn container(id: &str, package: &str, model: &str, status: &str) -> ContainerInfo {
+        let mut container = ContainerInfo {
+            id: id.to_string(),
+            ..Default::default()
+        };
+        container
+            .annotation
+            .insert(LABEL_PACKAGE.to_string(), package.to_string());
+        container
+            .annotation
+            .insert(LABEL_MODEL.to_string(), model.to_string());
+        container
+            .state
+            .insert("Status".to_string(), status.to_string());
+        container
+            .stats
+            .insert("MemoryUsage".to_string(), "100".to_string());
+        container
+    }
+
+    fn cluster(now: SystemTime) -> Cluster {
+        let mut cluster = Cluster::default();
+        cluster.update_node(node("node-a", 20.0), now);
+        cluster.update_node(node("node-b", 60.0), now);
+        cluster.update_node(node("node-c", 99.0), now - STALE_AFTER * 2);
+        cluster.update_containers(
+            "node-a",
+            vec![
+                container("a1", "nav", "route", "running"),
+                container("a2", "media", "player", "running"),
+            ],
+        );
+        cluster.update_containers("node-b", vec![container("b1", "nav", "map", "exited")]);
+        cluster.update_containers("node-c", vec![container("c1", "media", "radio", "running")]);
+        cluster
+    }
+
+    #[test]
+    fn test_rollup_per_node_and_package() {
+        let now = SystemTime::now();
+        let metrics = cluster(now).rollup(&ClusterMetricsRequest::default(), now);
+
+        assert_eq!(metrics.node_count, 3);
+        assert_eq!(metrics.healthy_nodes, 2);
+        assert_eq!(metrics.cpu_usage, 40.0);
+        assert_eq!(metrics.total_memory, 3 * 4096);
+        assert_eq!(metrics.containers, 4);
+        assert_eq!(metrics.running_containers, 3);
+        assert!(!metrics.nodes[2].healthy);
+
+        let nav = &metrics.packages[1];
+        assert_eq!(nav.package, "nav");
+        assert_eq!(nav.models, vec!["map", "route"]);
+        assert_eq!(nav.nodes, vec!["node-a", "node-b"]);
+        assert_eq!(nav.running_containers, 1);
+        assert_eq!(nav.memory_usage, 200);
+        assert!(!nav.healthy);
+        // Running, but partly on a stale node
+        assert!(!metrics.packages[0].healthy);
+    }
+
+    #[test]
+    fn test_rollup_filters() {
+        let now = SystemTime::now();
+        let cluster = cluster(now);
+        let request = ClusterMetricsRequest {
+            node_name: "node-a".to_string(),
+            ..Default::default()
+        };
+        let metrics = cluster.rollup(&request, now);
+        assert_eq!(metrics.nodes.len(), 1);
+        assert!(metrics.packages.iter().all(|p| p.healthy));
+
+        let request = ClusterMetricsRequest {
+            package: "media".to_string(),
+            ..Default::default()
+        };
+        let metrics = cluster.rollup(&request, now);
+        assert_eq!(metrics.node_count, 3);
+        assert_eq!(metrics.packages.len(), 1);
+        assert_eq!(metrics.packages[0].containers, 2);
+    }
+
+    #[test]
+    fn test_prometheus_text() {
+        let now = SystemTime::now();
+        let text = prometheus(&cluster(now).rollup(&ClusterMetricsRequest::default(), now));
+        assert!(text.contains("# TYPE pullpiri_cluster_nodes gauge\npullpiri_cluster_nodes 3\n"));
+        assert!(text.contains("pullpiri_node_healthy{node=\"node-c\"} 0\n"));
+        assert!(text.contains("pullpiri_package_containers{package=\"media\"} 2\n"));
+        assert!(text.contains("# TYPE pullpiri_package_cpu_usage_total counter\n"));
+        assert_eq!(label("a\"b\\"), "a\\\"b\\\\");
+    }
+}
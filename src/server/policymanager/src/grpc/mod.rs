@@ -2,5 +2,5 @@
 * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
 * SPDX-License-Identifier: Apache-2.0
 */
-mod receiver;
+pub mod receiver;
 pub mod sender;
@@ -4,23 +4,162 @@
  */
 
 use crate::grpc::sender::statemanager::StateManagerSender;
+use crate::policy::{Denial, PolicyContext, PolicyEngine};
 use common::policymanager::policy_manager_connection_server::PolicyManagerConnection;
 use common::policymanager::{CheckPolicyRequest, CheckPolicyResponse};
-use common::statemanager::{ResourceType, StateChange};
+use common::statemanager::{DenialReason, ResourceType, StateChange, VehicleMode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
 use tonic::Response;
 #[allow(dead_code)]
 pub struct PolicyManagerGrpcServer {
     /// StateManager sender for scenario state changes
     state_sender: StateManagerSender,
+    /// Rules deciding whether a satisfied scenario may run
+    engine: RwLock<PolicyEngine>,
+    /// Settings generation the engine was built from, `None` for a custom engine
+    generation: Option<AtomicU64>,
 }
+impl Default for PolicyManagerGrpcServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[allow(dead_code)]
 impl PolicyManagerGrpcServer {
     /// Creates a new PolicyManagerGrpcServer instance
+    ///
+    /// Its rules are built from the policy settings again whenever the
+    /// settings are reloaded.
     pub fn new() -> Self {
+        Self {
+            generation: Some(AtomicU64::new(common::setting::generation())),
+            ..Self::with_engine(PolicyEngine::from_settings())
+        }
+    }
+
+    /// Creates a PolicyManagerGrpcServer with a custom policy engine
+    pub fn with_engine(engine: PolicyEngine) -> Self {
         Self {
             state_sender: StateManagerSender::new(),
+            engine: RwLock::new(engine),
+            generation: None,
+        }
+    }
+
+    /// Evaluate the policy rules for a scenario context
+    ///
+    /// ### Returns
+    /// * `(status, desc)` - status 0 when the scenario is allowed, 1 otherwise
+    pub fn evaluate(&self, context: &PolicyContext) -> (i32, String) {
+        Self::status(&context.scenario_name, &self.verdict(context))
+    }
+
+    /// Evaluate the policy rules, rebuilt first if the settings were reloaded
+    fn verdict(&self, context: &PolicyContext) -> Result<(), Denial> {
+        if let Some(generation) = &self.generation {
+            let current = common::setting::generation();
+            if generation.swap(current, Ordering::AcqRel) != current {
+                println!("Settings reloaded, rebuilding the policy rules");
+                *self.engine.write().unwrap_or_else(|e| e.into_inner()) =
+                    PolicyEngine::from_settings();
+            }
+        }
+        self.engine
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .evaluate(context)
+    }
+
+    /// Status and description of the answer to a policy check
+    fn status(scenario_name: &str, verdict: &Result<(), Denial>) -> (i32, String) {
+        match verdict {
+            Ok(()) => (0, "Policy check passed".to_string()),
+            Err(denial) => (
+                1,
+                format!(
+                    "Policy check failed for scenario: {}: {}",
+                    scenario_name, denial
+                ),
+            ),
+        }
+    }
+
+    /// Current vehicle mode name from StateManager, `None` if unknown
+    async fn current_vehicle_mode(&self) -> Option<String> {
+        match self.state_sender.clone().get_vehicle_mode().await {
+            Ok(response) => match VehicleMode::try_from(response.into_inner().mode) {
+                Ok(VehicleMode::Unspecified) | Err(_) => None,
+                Ok(mode) => Some(mode.name().to_string()),
+            },
+            Err(e) => {
+                println!("Failed to query vehicle mode from StateManager: {:?}", e);
+                None
+            }
         }
     }
+
+    /// Send the policy verification result of a scenario to StateManager
+    ///
+    /// A denied scenario is sent with the rule that denied it. Returns the
+    /// id of the transition, which the ActionController reports the outcome
+    /// of the action of an allowed scenario with.
+    async fn notify_state_change(
+        &self,
+        scenario_name: &str,
+        target_state: &str,
+        denial: Option<&Denial>,
+    ) -> String {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i64;
+
+        let state_change = StateChange {
+            resource_type: ResourceType::Scenario as i32,
+            resource_name: scenario_name.to_string(),
+            current_state: "satisfied".to_string(),
+            target_state: target_state.to_string(),
+            transition_id: common::correlation::transition_id("policymanager"),
+            timestamp_ns: timestamp,
+            source: "policymanager".to_string(),
+            correlation_id: common::correlation::current_or_empty(),
+            sub_state: String::new(),
+            denial: denial.map(|denial| DenialReason {
+                rule: denial.rule.clone(),
+                message: denial.reason.clone(),
+                source: "policymanager".to_string(),
+                timestamp_ns: timestamp,
+            }),
+            metadata: Default::default(),
+        };
+
+        println!("   📤 Sending StateChange to StateManager:");
+        println!("      • Resource Type: SCENARIO");
+        println!("      • Resource Name: {}", state_change.resource_name);
+        println!("      • Current State: {}", state_change.current_state);
+        println!("      • Target State: {}", state_change.target_state);
+        println!("      • Transition ID: {}", state_change.transition_id);
+        println!("      • Source: {}", state_change.source);
+
+        let transition_id = state_change.transition_id.clone();
+
+        if let Err(e) = self
+            .state_sender
+            .clone()
+            .send_state_change(state_change)
+            .await
+        {
+            println!("   ❌ Failed to send state change to StateManager: {:?}", e);
+        } else {
+            println!(
+                "   ✅ Successfully notified StateManager: scenario {} satisfied → {}",
+                scenario_name, target_state
+            );
+        }
+        transition_id
+    }
 }
 
 #[tonic::async_trait]
@@ -32,22 +171,33 @@ impl PolicyManagerConnection for PolicyManagerGrpcServer {
         let req = request.into_inner();
         let scenario_name = req.scenario_name; // Renamed for clarity
 
-        // Simulate internal logic
-        let (status, desc) = if scenario_name.is_empty() {
-            (1, "Scenario name cannot be empty".to_string())
-        } else if scenario_name == "test_scenario" {
-            (0, "Policy check passed".to_string())
-        } else {
-            (
-                1,
-                format!("Policy check failed for scenario: {}", scenario_name),
-            )
+        if scenario_name.trim().is_empty() {
+            return Ok(Response::new(CheckPolicyResponse {
+                status: 1,
+                desc: "Scenario name cannot be empty".to_string(),
+                transition_id: String::new(),
+            }));
+        }
+
+        let verdict = match PolicyContext::load(&scenario_name).await {
+            Ok(mut context) => {
+                if !context.allowed_modes.is_empty() {
+                    context.operational_mode = self.current_vehicle_mode().await;
+                }
+                self.verdict(&context)
+            }
+            // A scenario that cannot be verified is denied
+            Err(e) => Err(Denial {
+                rule: "policy-context".to_string(),
+                reason: e,
+            }),
         };
+        let (status, desc) = Self::status(&scenario_name, &verdict);
 
         // 🔍 COMMENT 4: PolicyManager policy satisfaction
         // When PolicyManager determines that a scenario satisfies policy requirements
-        // (status == 0), it should notify StateManager of the scenario state change
-        // from "satisfied" to "allowed" state. This would be done via StateManagerSender.
+        // (status == 0), it notifies StateManager of the scenario state change
+        // from "satisfied" to "allowed" state, otherwise to "denied".
 
         println!("🔄 SCENARIO STATE TRANSITION: PolicyManager Processing");
         println!("   📋 Scenario: {}", scenario_name);
@@ -56,92 +206,37 @@ impl PolicyManagerConnection for PolicyManagerGrpcServer {
             if status == 0 { "PASSED" } else { "FAILED" }
         );
 
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as i64;
-
-        if status == 0 {
+        let target_state = if status == 0 {
             // Policy satisfied: satisfied -> allowed
             println!("   🔄 State Change: satisfied → allowed");
             println!("   🔍 Reason: Policy requirements satisfied");
-
-            let state_change = StateChange {
-                resource_type: ResourceType::Scenario as i32,
-                resource_name: scenario_name.clone(),
-                current_state: "satisfied".to_string(),
-                target_state: "allowed".to_string(),
-                transition_id: format!("policymanager-policy-allowed-{}", timestamp),
-                timestamp_ns: timestamp,
-                source: "policymanager".to_string(),
-            };
-
-            println!("   📤 Sending StateChange to StateManager:");
-            println!("      • Resource Type: SCENARIO");
-            println!("      • Resource Name: {}", state_change.resource_name);
-            println!("      • Current State: {}", state_change.current_state);
-            println!("      • Target State: {}", state_change.target_state);
-            println!("      • Transition ID: {}", state_change.transition_id);
-            println!("      • Source: {}", state_change.source);
-
-            if let Err(e) = self
-                .state_sender
-                .clone()
-                .send_state_change(state_change)
-                .await
-            {
-                println!("   ❌ Failed to send state change to StateManager: {:?}", e);
-            } else {
-                println!(
-                    "   ✅ Successfully notified StateManager: scenario {} satisfied → allowed",
-                    scenario_name
-                );
-            }
+            "allowed"
         } else {
             // Policy not satisfied: satisfied -> denied
             println!("   🔄 State Change: satisfied → denied");
-            println!("   🔍 Reason: Policy requirements not satisfied");
-
-            let state_change = StateChange {
-                resource_type: ResourceType::Scenario as i32,
-                resource_name: scenario_name.clone(),
-                current_state: "satisfied".to_string(),
-                target_state: "denied".to_string(),
-                transition_id: format!("policymanager-policy-denied-{}", timestamp),
-                timestamp_ns: timestamp,
-                source: "policymanager".to_string(),
-            };
-
-            println!("   📤 Sending StateChange to StateManager:");
-            println!("      • Resource Type: SCENARIO");
-            println!("      • Resource Name: {}", state_change.resource_name);
-            println!("      • Current State: {}", state_change.current_state);
-            println!("      • Target State: {}", state_change.target_state);
-            println!("      • Transition ID: {}", state_change.transition_id);
-            println!("      • Source: {}", state_change.source);
-
-            if let Err(e) = self
-                .state_sender
-                .clone()
-                .send_state_change(state_change)
-                .await
-            {
-                println!("   ❌ Failed to send state change to StateManager: {:?}", e);
-            } else {
-                println!(
-                    "   ✅ Successfully notified StateManager: scenario {} satisfied → denied",
-                    scenario_name
-                );
-            }
-        }
+            println!("   🔍 Reason: {}", desc);
+            "denied"
+        };
+        // The state change belongs to the activation that asked for the check
+        let transition_id = common::correlation::scope(
+            req.correlation_id,
+            self.notify_state_change(&scenario_name, target_state, verdict.as_ref().err()),
+        )
+        .await;
 
-        Ok(Response::new(CheckPolicyResponse { status, desc }))
+        Ok(Response::new(CheckPolicyResponse {
+            status,
+            desc,
+            transition_id,
+        }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::policy::rules::AsilLevelRule;
+    use crate::policy::AsilLevel;
     use tonic::Request;
 
     #[tokio::test]
@@ -149,30 +244,28 @@ mod tests {
         println!("🧪 Testing PolicyManager Scenario State Management");
         println!("=================================================");
 
-        let server = PolicyManagerGrpcServer::new();
-
-        println!("📋 Testing Policy Success Case:");
-        println!("   🔄 Expected State Change: satisfied → allowed");
+        let server = PolicyManagerGrpcServer::with_engine(PolicyEngine::from_settings());
 
-        // Test policy success (satisfied -> allowed)
+        println!("📋 Testing Empty Scenario Name:");
         let request = Request::new(CheckPolicyRequest {
-            scenario_name: "test_scenario".to_string(),
+            scenario_name: "".to_string(),
+            correlation_id: String::new(),
         });
 
         let response = server.check_policy(request).await.unwrap();
         let policy_response = response.into_inner();
 
-        assert_eq!(policy_response.status, 0);
-        assert_eq!(policy_response.desc, "Policy check passed");
-        println!("✅ Policy success state change completed");
+        assert_eq!(policy_response.status, 1);
+        assert_eq!(policy_response.desc, "Scenario name cannot be empty");
         println!("");
 
         println!("📋 Testing Policy Failure Case:");
         println!("   🔄 Expected State Change: satisfied → denied");
 
-        // Test policy failure (satisfied -> denied)
+        // Scenario is not stored, so it cannot be verified (satisfied -> denied)
         let request = Request::new(CheckPolicyRequest {
             scenario_name: "restricted_scenario".to_string(),
+            correlation_id: String::new(),
         });
 
         let response = server.check_policy(request).await.unwrap();
@@ -180,9 +273,55 @@ mod tests {
 
         assert_eq!(policy_response.status, 1);
         assert!(policy_response.desc.contains("Policy check failed"));
+        assert!(policy_response.transition_id.starts_with("policymanager"));
         println!("✅ Policy failure state change completed");
         println!("");
 
         println!("🎉 PolicyManager state management test completed successfully!");
     }
+
+    #[test]
+    fn test_evaluate_context() {
+        let mut engine = PolicyEngine::new();
+        engine.add_rule(Box::new(AsilLevelRule::new(AsilLevel::B)));
+        let server = PolicyManagerGrpcServer::with_engine(engine);
+
+        let mut context = PolicyContext {
+            scenario_name: "test_scenario".to_string(),
+            asil_level: AsilLevel::A,
+            ..Default::default()
+        };
+        assert_eq!(
+            server.evaluate(&context),
+            (0, "Policy check passed".to_string())
+        );
+
+        context.asil_level = AsilLevel::D;
+        let (status, desc) = server.evaluate(&context);
+        assert_eq!(status, 1);
+        assert!(desc.contains("asil-level"));
+    }
+
+    #[test]
+    fn test_rules_are_rebuilt_after_a_reload() {
+        let server = PolicyManagerGrpcServer::new();
+        let context = PolicyContext {
+            scenario_name: "test_scenario".to_string(),
+            asil_level: AsilLevel::D,
+            ..Default::default()
+        };
+        // Rules of older settings
+        let mut engine = PolicyEngine::new();
+        engine.add_rule(Box::new(AsilLevelRule::new(AsilLevel::Qm)));
+        *server.engine.write().unwrap() = engine;
+        assert_eq!(server.evaluate(&context).0, 1);
+
+        // The default settings allow ASIL D
+        server
+            .generation
+            .as_ref()
+            .unwrap()
+            .store(u64::MAX, Ordering::Release);
+        assert_eq!(server.evaluate(&context).0, 0);
+    }
 }
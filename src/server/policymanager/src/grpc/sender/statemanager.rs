@@ -6,10 +6,10 @@
 //! StateManager gRPC client for sending state change messages from PolicyManager.
 
 use common::statemanager::{
-    connect_server, state_manager_connection_client::StateManagerConnectionClient, StateChange,
-    StateChangeResponse,
+    connect_server, state_manager_connection_client::StateManagerConnectionClient,
+    GetVehicleModeRequest, StateChange, StateChangeResponse, VehicleModeResponse,
 };
-use tonic::{Request, Status};
+use tonic::Status;
 
 /// StateManager gRPC client for PolicyManager component.
 #[derive(Clone)]
@@ -31,32 +31,56 @@ impl StateManagerSender {
     }
 
     /// Ensures a gRPC connection to the StateManager exists and is ready for use.
+    ///
+    /// The channel is shared through the process-wide pool in `common::grpc`.
     async fn ensure_connected(&mut self) -> Result<(), Status> {
         if self.client.is_none() {
-            match StateManagerConnectionClient::connect(connect_server()).await {
-                Ok(client) => {
-                    self.client = Some(client);
-                    Ok(())
-                }
-                Err(e) => Err(Status::unknown(format!(
-                    "Failed to connect to StateManager: {}",
-                    e
-                ))),
-            }
+            let channel = common::grpc::channel(&connect_server()).await?;
+            self.client = Some(StateManagerConnectionClient::new(channel));
+            Ok(())
         } else {
             Ok(())
         }
     }
 
+    /// Drops the connection after a transport failure so the next request reconnects
+    async fn release_on_failure<T>(&mut self, result: Result<T, Status>) -> Result<T, Status> {
+        match result {
+            Err(status) => {
+                self.client = None;
+                Err(common::grpc::release_on_failure(&connect_server(), status).await)
+            }
+            ok => ok,
+        }
+    }
+
     /// Sends a state change message to the StateManager service.
+    ///
+    /// Transient failures are retried with backoff through `common::grpc::retry`.
     pub async fn send_state_change(
         &mut self,
         state_change: StateChange,
     ) -> Result<tonic::Response<StateChangeResponse>, Status> {
+        let state_change = &state_change;
+        common::grpc::retry::call(&connect_server(), |channel| async move {
+            StateManagerConnectionClient::new(channel)
+                .send_state_change(common::auth::request(state_change.clone()))
+                .await
+        })
+        .await
+    }
+
+    /// Queries the current vehicle operational mode from the StateManager service.
+    pub async fn get_vehicle_mode(
+        &mut self,
+    ) -> Result<tonic::Response<VehicleModeResponse>, Status> {
         self.ensure_connected().await?;
 
         if let Some(client) = &mut self.client {
-            client.send_state_change(Request::new(state_change)).await
+            let result = client
+                .get_vehicle_mode(common::auth::request(GetVehicleModeRequest {}))
+                .await;
+            self.release_on_failure(result).await
         } else {
             Err(Status::unknown("Client not connected"))
         }
@@ -3,10 +3,43 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 pub mod grpc;
+pub mod policy;
 
-fn main() {
-    println!("Hello, world!");
-    loop {
-        std::thread::sleep(std::time::Duration::from_secs(10));
+use common::policymanager::policy_manager_connection_server::PolicyManagerConnectionServer;
+use tonic::transport::Server;
+
+/// Serve CheckPolicy requests from ActionController
+async fn initialize_grpc_server() {
+    let server = grpc::receiver::PolicyManagerGrpcServer::new();
+    let addr = match common::policymanager::open_server().parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Failed to parse PolicyManager server address: {:?}", e);
+            return;
+        }
+    };
+
+    println!("PolicyManager gRPC server listening on {}", addr);
+    common::health::set_started();
+    if let Err(e) = Server::builder()
+        .add_service(PolicyManagerConnectionServer::new(server))
+        .add_service(common::health::grpc_service())
+        .serve(addr)
+        .await
+    {
+        eprintln!("PolicyManager gRPC server error: {:?}", e);
     }
 }
+
+#[tokio::main]
+async fn main() {
+    println!("Piccolo PolicyManager is starting...");
+    common::health::init("policymanager", false);
+    common::address::validate_or_exit("policymanager");
+    tokio::spawn(common::health::serve(
+        common::policymanager::open_health_server(),
+    ));
+    // Policy rules follow changes of settings.yaml
+    tokio::spawn(common::setting::watch());
+    initialize_grpc_server().await;
+}
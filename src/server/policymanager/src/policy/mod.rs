@@ -0,0 +1,299 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Local policy engine deciding whether a satisfied scenario may run
+//!
+//! The engine is a list of [`PolicyRule`]s evaluated in order against a
+//! [`PolicyContext`]. The first rule that rejects the scenario decides the
+//! denial reason. Additional rules can be plugged in with
+//! [`PolicyEngine::add_rule`].
+
+pub mod rules;
+
+use common::spec::artifact::{Artifact, Package, Scenario};
+use common::spec::k8s::Pod;
+use rules::{AsilLevelRule, OperationalModeRule, ResourceBudgetRule};
+use std::fmt;
+use std::str::FromStr;
+
+/// Scenario annotation holding the required ASIL level
+pub const ASIL_LEVEL_ANNOTATION: &str = "io.piccolo.annotations.asil-level";
+
+/// Automotive Safety Integrity Level, ordered from lowest to highest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum AsilLevel {
+    #[default]
+    Qm,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl FromStr for AsilLevel {
+    type Err = String;
+
+    /// Accepts "QM", "A".."D" with an optional "ASIL-" or "ASIL_" prefix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_uppercase();
+        let level = upper
+            .strip_prefix("ASIL-")
+            .or_else(|| upper.strip_prefix("ASIL_"))
+            .unwrap_or(&upper);
+        match level {
+            "QM" => Ok(AsilLevel::Qm),
+            "A" => Ok(AsilLevel::A),
+            "B" => Ok(AsilLevel::B),
+            "C" => Ok(AsilLevel::C),
+            "D" => Ok(AsilLevel::D),
+            _ => Err(format!("unknown ASIL level '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for AsilLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsilLevel::Qm => write!(f, "QM"),
+            AsilLevel::A => write!(f, "ASIL-A"),
+            AsilLevel::B => write!(f, "ASIL-B"),
+            AsilLevel::C => write!(f, "ASIL-C"),
+            AsilLevel::D => write!(f, "ASIL-D"),
+        }
+    }
+}
+
+/// Everything the rules need to know about a scenario
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolicyContext {
+    pub scenario_name: String,
+    /// ASIL level required by the scenario
+    pub asil_level: AsilLevel,
+    /// CPU requested by the target package models, in millicores
+    pub cpu_request_millis: u64,
+    /// Memory requested by the target package models, in MiB
+    pub memory_request_mb: u64,
+    /// Vehicle modes the scenario may run in, empty for any mode
+    pub allowed_modes: Vec<String>,
+    /// Current vehicle operational mode, `None` if unknown
+    pub operational_mode: Option<String>,
+}
+
+impl PolicyContext {
+    /// Build the context of a scenario from its definition and the pods of its package
+    pub fn from_artifacts(scenario: &Scenario, pods: &[Pod]) -> Result<Self, String> {
+        let asil_level = match scenario.get_annotation(ASIL_LEVEL_ANNOTATION) {
+            Some(level) => level.parse()?,
+            None => AsilLevel::default(),
+        };
+        let allowed_modes = scenario
+            .get_allowed_modes()
+            .iter()
+            .map(|mode| mode.trim().to_lowercase())
+            .collect();
+
+        Ok(Self {
+            scenario_name: scenario.get_qualified_name(),
+            asil_level,
+            cpu_request_millis: pods.iter().map(|p| p.get_spec().cpu_request_millis()).sum(),
+            memory_request_mb: pods.iter().map(|p| p.get_spec().memory_request_mb()).sum(),
+            allowed_modes,
+            operational_mode: None,
+        })
+    }
+
+    /// Load a scenario, its package and the package pods from etcd
+    pub async fn load(scenario_name: &str) -> Result<Self, String> {
+        let scenario_str = common::etcd::get(&format!("Scenario/{}", scenario_name))
+            .await
+            .map_err(|e| format!("Scenario '{}' not found: {}", scenario_name, e))?;
+        let scenario: Scenario = serde_yaml::from_str(&scenario_str)
+            .map_err(|e| format!("Failed to parse scenario '{}': {}", scenario_name, e))?;
+
+        let package_name = scenario.get_targets();
+        let package_str = common::etcd::get(&format!("Package/{}", package_name))
+            .await
+            .map_err(|e| format!("Package '{}' not found: {}", package_name, e))?;
+        let package: Package = serde_yaml::from_str(&package_str)
+            .map_err(|e| format!("Failed to parse package '{}': {}", package_name, e))?;
+
+        let mut pods = Vec::new();
+        for model in package.get_models() {
+            let pod_str = common::etcd::get(&format!("Pod/{}", model.get_name()))
+                .await
+                .map_err(|e| format!("Pod '{}' not found: {}", model.get_name(), e))?;
+            let pod: Pod = serde_yaml::from_str(&pod_str)
+                .map_err(|e| format!("Failed to parse pod '{}': {}", model.get_name(), e))?;
+            pods.push(pod);
+        }
+
+        Self::from_artifacts(&scenario, &pods)
+    }
+}
+
+/// A single policy check
+pub trait PolicyRule: Send + Sync {
+    /// Short name used in denial messages
+    fn name(&self) -> &str;
+
+    /// Return `Err(reason)` to deny the scenario
+    fn evaluate(&self, context: &PolicyContext) -> Result<(), String>;
+}
+
+/// Rule that denied a scenario and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denial {
+    pub rule: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.reason)
+    }
+}
+
+/// Ordered set of policy rules
+#[derive(Default)]
+pub struct PolicyEngine {
+    rules: Vec<Box<dyn PolicyRule>>,
+}
+
+impl PolicyEngine {
+    /// Create an engine without any rules, allowing every scenario
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an engine with the ASIL, resource budget and operational mode rules
+    /// configured from the policy section of settings.yaml
+    pub fn from_settings() -> Self {
+        let settings = &common::setting::get_config().policy;
+        let max_asil_level = settings.max_asil_level.parse().unwrap_or_else(|e| {
+            println!("Invalid max_asil_level in settings ({}), using QM", e);
+            AsilLevel::Qm
+        });
+
+        let mut engine = Self::new();
+        engine.add_rule(Box::new(AsilLevelRule::new(max_asil_level)));
+        engine.add_rule(Box::new(ResourceBudgetRule::new(
+            settings.cpu_budget_millis,
+            settings.memory_budget_mb,
+        )));
+        engine.add_rule(Box::new(OperationalModeRule));
+        engine
+    }
+
+    /// Append a rule, evaluated after the existing ones
+    pub fn add_rule(&mut self, rule: Box<dyn PolicyRule>) {
+        self.rules.push(rule);
+    }
+
+    /// Evaluate all rules, stopping at the first denial
+    ///
+    /// ### Returns
+    /// * `Err(Denial)` - the rule that denied the scenario and its reason
+    pub fn evaluate(&self, context: &PolicyContext) -> Result<(), Denial> {
+        for rule in &self.rules {
+            rule.evaluate(context).map_err(|reason| Denial {
+                rule: rule.name().to_string(),
+                reason,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DenyAll;
+
+    impl PolicyRule for DenyAll {
+        fn name(&self) -> &str {
+            "deny-all"
+        }
+
+        fn evaluate(&self, _context: &PolicyContext) -> Result<(), String> {
+            Err("denied for test".to_string())
+        }
+    }
+
+    #[test]
+    fn test_asil_level_parse_and_order() {
+        assert_eq!("qm".parse::<AsilLevel>().unwrap(), AsilLevel::Qm);
+        assert_eq!("ASIL-B".parse::<AsilLevel>().unwrap(), AsilLevel::B);
+        assert_eq!("asil_d".parse::<AsilLevel>().unwrap(), AsilLevel::D);
+        assert!("E".parse::<AsilLevel>().is_err());
+        assert!(AsilLevel::Qm < AsilLevel::A && AsilLevel::C < AsilLevel::D);
+        assert_eq!(AsilLevel::C.to_string(), "ASIL-C");
+    }
+
+    #[test]
+    fn test_context_from_artifacts() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Scenario
+metadata:
+  name: antipinch
+  annotations:
+    io.piccolo.annotations.asil-level: B
+spec:
+  action: launch
+  target: antipinch
+  allowedModes:
+    - Parked
+    - charging
+"#,
+        )
+        .unwrap();
+        let pod: Pod = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Pod
+metadata:
+  name: antipinch-core
+spec:
+  containers:
+    - name: antipinch
+      image: antipinch:latest
+      resources:
+        requests:
+          cpu: 250m
+          memory: 64Mi
+"#,
+        )
+        .unwrap();
+
+        let context = PolicyContext::from_artifacts(&scenario, &[pod.clone(), pod]).unwrap();
+        assert_eq!(context.scenario_name, "antipinch");
+        assert_eq!(context.asil_level, AsilLevel::B);
+        assert_eq!(context.cpu_request_millis, 500);
+        assert_eq!(context.memory_request_mb, 128);
+        assert_eq!(context.allowed_modes, vec!["parked", "charging"]);
+        assert_eq!(context.operational_mode, None);
+    }
+
+    #[test]
+    fn test_engine_reports_first_denial() {
+        let context = PolicyContext::default();
+        let mut engine = PolicyEngine::new();
+        assert!(engine.evaluate(&context).is_ok());
+
+        engine.add_rule(Box::new(AsilLevelRule::new(AsilLevel::D)));
+        engine.add_rule(Box::new(DenyAll));
+        let denial = engine.evaluate(&context).unwrap_err();
+        assert_eq!(denial.rule, "deny-all");
+        assert_eq!(denial.to_string(), "deny-all: denied for test");
+    }
+
+    #[tokio::test]
+    async fn test_load_missing_scenario() {
+        let result = PolicyContext::load("policy-missing-scenario").await;
+        assert!(result.is_err());
+    }
+}
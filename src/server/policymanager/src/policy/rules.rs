@@ -0,0 +1,156 @@
+/*
+ * SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Built-in policy rules
+
+use super::{AsilLevel, PolicyContext, PolicyRule};
+
+/// Deny scenarios requiring a higher ASIL level than the platform supports
+pub struct AsilLevelRule {
+    max_level: AsilLevel,
+}
+
+impl AsilLevelRule {
+    pub fn new(max_level: AsilLevel) -> Self {
+        Self { max_level }
+    }
+}
+
+impl PolicyRule for AsilLevelRule {
+    fn name(&self) -> &str {
+        "asil-level"
+    }
+
+    fn evaluate(&self, context: &PolicyContext) -> Result<(), String> {
+        if context.asil_level > self.max_level {
+            return Err(format!(
+                "scenario requires {} but platform supports up to {}",
+                context.asil_level, self.max_level
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Deny scenarios whose models request more than the configured budget
+///
+/// A budget of 0 disables the corresponding check.
+pub struct ResourceBudgetRule {
+    cpu_budget_millis: u64,
+    memory_budget_mb: u64,
+}
+
+impl ResourceBudgetRule {
+    pub fn new(cpu_budget_millis: u64, memory_budget_mb: u64) -> Self {
+        Self {
+            cpu_budget_millis,
+            memory_budget_mb,
+        }
+    }
+}
+
+impl PolicyRule for ResourceBudgetRule {
+    fn name(&self) -> &str {
+        "resource-budget"
+    }
+
+    fn evaluate(&self, context: &PolicyContext) -> Result<(), String> {
+        if self.cpu_budget_millis > 0 && context.cpu_request_millis > self.cpu_budget_millis {
+            return Err(format!(
+                "CPU request {}m exceeds budget {}m",
+                context.cpu_request_millis, self.cpu_budget_millis
+            ));
+        }
+        if self.memory_budget_mb > 0 && context.memory_request_mb > self.memory_budget_mb {
+            return Err(format!(
+                "memory request {}Mi exceeds budget {}Mi",
+                context.memory_request_mb, self.memory_budget_mb
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Deny scenarios restricted to vehicle modes other than the current one
+///
+/// Scenarios without mode restrictions are always allowed. A restricted
+/// scenario is denied while the current mode is unknown.
+pub struct OperationalModeRule;
+
+impl PolicyRule for OperationalModeRule {
+    fn name(&self) -> &str {
+        "operational-mode"
+    }
+
+    fn evaluate(&self, context: &PolicyContext) -> Result<(), String> {
+        if context.allowed_modes.is_empty() {
+            return Ok(());
+        }
+        match &context.operational_mode {
+            Some(mode) if context.allowed_modes.contains(&mode.to_lowercase()) => Ok(()),
+            Some(mode) => Err(format!(
+                "vehicle mode '{}' is not one of {:?}",
+                mode, context.allowed_modes
+            )),
+            None => Err("current vehicle mode is unknown".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asil_level_rule() {
+        let rule = AsilLevelRule::new(AsilLevel::B);
+        let mut context = PolicyContext {
+            asil_level: AsilLevel::B,
+            ..Default::default()
+        };
+        assert!(rule.evaluate(&context).is_ok());
+
+        context.asil_level = AsilLevel::C;
+        assert!(rule.evaluate(&context).unwrap_err().contains("ASIL-C"));
+    }
+
+    #[test]
+    fn test_resource_budget_rule() {
+        let mut context = PolicyContext {
+            cpu_request_millis: 1500,
+            memory_request_mb: 256,
+            ..Default::default()
+        };
+        assert!(ResourceBudgetRule::new(0, 0).evaluate(&context).is_ok());
+        assert!(ResourceBudgetRule::new(2000, 512)
+            .evaluate(&context)
+            .is_ok());
+        assert!(ResourceBudgetRule::new(1000, 0)
+            .evaluate(&context)
+            .unwrap_err()
+            .contains("CPU"));
+
+        context.cpu_request_millis = 0;
+        assert!(ResourceBudgetRule::new(1000, 128)
+            .evaluate(&context)
+            .unwrap_err()
+            .contains("memory"));
+    }
+
+    #[test]
+    fn test_operational_mode_rule() {
+        let mut context = PolicyContext::default();
+        assert!(OperationalModeRule.evaluate(&context).is_ok());
+
+        context.allowed_modes = vec!["parked".to_string()];
+        assert!(OperationalModeRule.evaluate(&context).is_err());
+
+        context.operational_mode = Some("Parked".to_string());
+        assert!(OperationalModeRule.evaluate(&context).is_ok());
+
+        context.operational_mode = Some("driving".to_string());
+        assert!(OperationalModeRule.evaluate(&context).is_err());
+    }
+}
@@ -4,7 +4,7 @@
  */
 
 use clap::Parser;
-use rocksdb::{IteratorMode, Options, WriteBatch, DB};
+use rocksdb::{Direction, IteratorMode, Options, WriteBatch, DB};
 use std::sync::{Arc, OnceLock};
 use tokio::sync::Mutex;
 use tonic::{transport::Server, Request, Response, Status};
@@ -13,9 +13,10 @@ use tracing::{error, info};
 // Import protobuf definitions
 use common::rocksdbservice::{
     rocks_db_service_server::{RocksDbService, RocksDbServiceServer},
-    BatchPutRequest, BatchPutResponse, DeleteRequest, DeleteResponse, GetByPrefixRequest,
-    GetByPrefixResponse, GetRequest, GetResponse, HealthRequest, HealthResponse, KeyValue,
-    ListKeysRequest, ListKeysResponse, PutRequest, PutResponse,
+    BatchPutRequest, BatchPutResponse, CompareAndSwapRequest, CompareAndSwapResponse,
+    DeleteRequest, DeleteResponse, GetByPrefixRequest, GetByPrefixResponse, GetRequest,
+    GetResponse, HealthRequest, HealthResponse, KeyValue, ListKeysRequest, ListKeysResponse,
+    PutRequest, PutResponse,
 };
 
 // Global RocksDB instance
@@ -281,7 +282,10 @@ impl RocksDbService for RocksDbServiceImpl {
         let db = get_db()?;
         let db_lock = db.lock().await;
         let mut results = Vec::new();
-        let iter = db_lock.iterator(IteratorMode::Start);
+        // Keys are sorted, the pairs of the prefix are read from the first
+        // one of the page and no further than the page
+        let start = req.start_after.as_str().max(req.prefix.as_str());
+        let iter = db_lock.iterator(IteratorMode::From(start.as_bytes(), Direction::Forward));
 
         for item in iter {
             match item {
@@ -291,8 +295,15 @@ impl RocksDbService for RocksDbServiceImpl {
                         String::from_utf8(value_bytes.to_vec()),
                     ) {
                         (Ok(key), Ok(value)) => {
-                            if key.starts_with(&req.prefix) {
-                                results.push(KeyValue { key, value });
+                            if !key.starts_with(&req.prefix) {
+                                break;
+                            }
+                            if key == req.start_after {
+                                continue;
+                            }
+                            results.push(KeyValue { key, value });
+                            if req.limit > 0 && results.len() >= req.limit as usize {
+                                break;
                             }
                         }
                         _ => continue, // Skip invalid UTF-8 entries
@@ -311,6 +322,55 @@ impl RocksDbService for RocksDbServiceImpl {
         }))
     }
 
+    async fn compare_and_swap(
+        &self,
+        request: Request<CompareAndSwapRequest>,
+    ) -> Result<Response<CompareAndSwapResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.key.is_empty() || req.key.len() > 1024 || req.key.contains(['<', '>', '?', '{', '}'])
+        {
+            return Err(Status::invalid_argument(format!(
+                "Invalid key: {}",
+                req.key
+            )));
+        }
+
+        // The lock is held from the read to the write, no other operation
+        // can change the key in between
+        let db = get_db()?;
+        let db_lock = db.lock().await;
+
+        let current = match db_lock.get(req.key.as_bytes()) {
+            Ok(current) => current,
+            Err(e) => {
+                error!("Failed to get key '{}': {}", req.key, e);
+                return Err(Status::internal(format!("RocksDB get error: {}", e)));
+            }
+        };
+        if current.as_deref() != req.expected.as_ref().map(|expected| expected.as_bytes()) {
+            info!("Key '{}' changed, not swapped", req.key);
+            return Ok(Response::new(CompareAndSwapResponse {
+                swapped: false,
+                error: String::new(),
+            }));
+        }
+
+        match db_lock.put(req.key.as_bytes(), req.value.as_bytes()) {
+            Ok(()) => {
+                info!("Successfully swapped key: '{}'", req.key);
+                Ok(Response::new(CompareAndSwapResponse {
+                    swapped: true,
+                    error: String::new(),
+                }))
+            }
+            Err(e) => {
+                error!("Failed to store key '{}': {}", req.key, e);
+                Err(Status::internal(format!("RocksDB put error: {}", e)))
+            }
+        }
+    }
+
     async fn list_keys(
         &self,
         request: Request<ListKeysRequest>,
@@ -363,6 +423,7 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
     let args = Args::parse();
+    common::address::validate_or_exit("rocksdbservice");
 
     // Initialize RocksDB
     init_db(&args.path)?;
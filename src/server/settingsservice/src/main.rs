@@ -97,10 +97,13 @@ async fn run_server_mode(args: Args) -> Result<()> {
     info!("  GET    /api/v1/metrics");
     info!("  GET    /api/v1/history");
     info!("  GET    /api/v1/system/health");
+    info!("  GET    /healthz, /readyz");
 
     // Start all services including the API server
     // This will start the HTTP server on the specified port
+    common::health::init("settingsservice", false);
     core_manager.start_services().await?;
+    common::health::set_started();
 
     info!("Settings Service started successfully");
 
@@ -253,6 +253,7 @@ impl ApiServer {
                 get(get_container_metric_by_id),
             )
             .with_state(self.state.clone())
+            .merge(common::health::router())
             .layer(CorsLayer::permissive())
     }
 }
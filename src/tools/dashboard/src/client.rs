@@ -0,0 +1,184 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Connections to the apiserver and the StateManager
+
+use crate::state::ScenarioTree;
+use common::statemanager::{
+    state_manager_connection_client::StateManagerConnectionClient, StateChangeEvent,
+    StateChangeSubscriptionRequest,
+};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Time allowed for a request to the apiserver
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait before subscribing again after the stream ended
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(3);
+
+/// What the subscription task reports to the dashboard
+#[derive(Debug)]
+pub enum Update {
+    Connected,
+    Event(StateChangeEvent),
+    Disconnected(String),
+}
+
+/// Name of the package a scenario targets, from the scenario yaml
+pub fn scenario_target(yaml: &str) -> Option<String> {
+    let doc: serde_yaml::Value = serde_yaml::from_str(yaml).ok()?;
+    doc["spec"]["target"].as_str().map(str::to_string)
+}
+
+/// Names of the models of a package, from the package yaml
+pub fn package_models(yaml: &str) -> Vec<String> {
+    serde_yaml::from_str::<serde_yaml::Value>(yaml)
+        .ok()
+        .and_then(|doc| {
+            doc["spec"]["models"].as_sequence().map(|models| {
+                models
+                    .iter()
+                    .filter_map(|model| model["name"].as_str().map(str::to_string))
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Client of the apiserver REST API, for the scenario hierarchy
+pub struct ApiClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    pub fn new(base_url: &str, token: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    async fn get(&self, endpoint: &str) -> Result<Value, String> {
+        let mut request = self
+            .client
+            .get(format!("{}{}", self.base_url, endpoint))
+            .timeout(REQUEST_TIMEOUT);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("apiserver {}: {}", endpoint, response.status()));
+        }
+        response.json().await.map_err(|e| e.to_string())
+    }
+
+    /// Applied scenarios with the package they target and its models
+    pub async fn hierarchy(&self) -> Result<Vec<ScenarioTree>, String> {
+        let mut scenarios = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let endpoint = match &after {
+                Some(after) => format!("/api/artifact/scenario?continue={}", after),
+                None => "/api/artifact/scenario".to_string(),
+            };
+            let page = self.get(&endpoint).await?;
+            for item in page["items"].as_array().into_iter().flatten() {
+                if let Some(name) = item["name"].as_str() {
+                    scenarios.push(self.scenario(name).await?);
+                }
+            }
+            match page["continue"].as_str() {
+                Some(next) => after = Some(next.to_string()),
+                None => return Ok(scenarios),
+            }
+        }
+    }
+
+    async fn scenario(&self, name: &str) -> Result<ScenarioTree, String> {
+        let scenario = self
+            .get(&format!("/api/artifact/scenario/{}", name))
+            .await?;
+        let package = scenario["yaml"].as_str().and_then(scenario_target);
+        let models = match &package {
+            // A package that is not applied yet has no models to show
+            Some(package) => match self
+                .get(&format!("/api/artifact/package/{}", package))
+                .await
+            {
+                Ok(package) => package["yaml"]
+                    .as_str()
+                    .map(package_models)
+                    .unwrap_or_default(),
+                Err(_) => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        Ok(ScenarioTree {
+            name: name.to_string(),
+            package,
+            models,
+        })
+    }
+}
+
+/// Subscribe to the StateManager and forward its events until the
+/// dashboard goes away, subscribing again whenever the stream ends
+pub async fn subscribe(address: String, token: Option<String>, tx: mpsc::Sender<Update>) {
+    loop {
+        let reason = match stream(&address, token.as_deref(), &tx).await {
+            Ok(()) => "stream ended".to_string(),
+            Err(e) => e,
+        };
+        if tx.send(Update::Disconnected(reason)).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+async fn stream(
+    address: &str,
+    token: Option<&str>,
+    tx: &mpsc::Sender<Update>,
+) -> Result<(), String> {
+    let mut client = StateManagerConnectionClient::connect(address.to_string())
+        .await
+        .map_err(|e| format!("cannot connect to {}: {}", address, e))?;
+    let mut request = tonic::Request::new(StateChangeSubscriptionRequest {
+        resource_type: 0,
+        include_alerts: true,
+        snapshot: true,
+        namespace: String::new(),
+    });
+    if let Some(token) = token {
+        let value = format!("Bearer {}", token)
+            .parse()
+            .map_err(|_| "token is not a valid header value".to_string())?;
+        request.metadata_mut().insert("authorization", value);
+    }
+    let mut events = client
+        .subscribe_to_state_changes(request)
+        .await
+        .map_err(|e| e.message().to_string())?
+        .into_inner();
+    if tx.send(Update::Connected).await.is_err() {
+        return Ok(());
+    }
+    while let Some(event) = events
+        .message()
+        .await
+        .map_err(|e| e.message().to_string())?
+    {
+        if tx.send(Update::Event(event)).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
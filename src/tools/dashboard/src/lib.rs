@@ -0,0 +1,16 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Terminal dashboard of the StateManager
+//!
+//! Shows the scenario, package and model hierarchy with the live state of
+//! every resource, the recent transitions and the alerts raised, as streamed
+//! by the `SubscribeToStateChanges` RPC.
+
+pub mod client;
+pub mod state;
+pub mod ui;
+
+pub use client::{ApiClient, Update};
+pub use state::{Dashboard, ScenarioTree};
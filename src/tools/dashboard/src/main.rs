@@ -0,0 +1,110 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! dashboard - Terminal status dashboard of the StateManager
+
+use clap::Parser;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use dashboard::{client, ui, ApiClient, Dashboard, Update};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Time between two draws, and between two looks at the keyboard
+const TICK: Duration = Duration::from_millis(200);
+
+#[derive(Parser)]
+#[command(name = "dashboard")]
+#[command(about = "Terminal status dashboard of the StateManager")]
+#[command(version)]
+struct Cli {
+    /// StateManager gRPC URL
+    #[arg(
+        long,
+        env = "PICCOLO_STATEMANAGER",
+        default_value = "http://localhost:47006"
+    )]
+    statemanager: String,
+
+    /// apiserver URL, for the scenario hierarchy
+    #[arg(
+        short,
+        long,
+        env = "PICCOLO_SERVER",
+        default_value = "http://localhost:47099"
+    )]
+    server: String,
+
+    /// Bearer token, needed when authentication is enabled
+    #[arg(long, env = "PICCOLO_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Seconds between two reads of the scenario hierarchy
+    #[arg(long, default_value = "30")]
+    refresh: u64,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let token = cli.token.filter(|token| !token.is_empty());
+
+    let (tx, mut updates) = mpsc::channel(256);
+    tokio::spawn(client::subscribe(cli.statemanager, token.clone(), tx));
+    let api = ApiClient::new(&cli.server, token);
+
+    let mut terminal = ratatui::init();
+    let mut dashboard = Dashboard::default();
+    let mut tick = tokio::time::interval(TICK);
+    let mut refresh = tokio::time::interval(Duration::from_secs(cli.refresh.max(1)));
+
+    let result: std::io::Result<()> = loop {
+        tokio::select! {
+            Some(update) = updates.recv() => match update {
+                Update::Connected => {
+                    dashboard.connected = true;
+                    dashboard.error = None;
+                }
+                Update::Event(event) => dashboard.apply(event),
+                Update::Disconnected(reason) => {
+                    dashboard.connected = false;
+                    dashboard.error = Some(reason);
+                }
+            },
+            _ = refresh.tick() => match api.hierarchy().await {
+                Ok(scenarios) => dashboard.scenarios = scenarios,
+                Err(e) => dashboard.error = Some(e),
+            },
+            _ = tick.tick() => {
+                if let Err(e) = terminal.draw(|frame| ui::draw(frame, &dashboard)) {
+                    break Err(e);
+                }
+                match read_key() {
+                    Ok(Some(KeyCode::Char('q') | KeyCode::Esc)) => break Ok(()),
+                    Ok(Some(KeyCode::Char('r'))) => refresh.reset_immediately(),
+                    Ok(Some(KeyCode::Char('c'))) => dashboard.alerts.clear(),
+                    Ok(_) => {}
+                    Err(e) => break Err(e),
+                }
+            }
+        }
+    };
+
+    ratatui::restore();
+    if let Err(e) = result {
+        eprintln!("dashboard: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Key pressed since the last tick, without waiting
+fn read_key() -> std::io::Result<Option<KeyCode>> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                return Ok(Some(key.code));
+            }
+        }
+    }
+    Ok(None)
+}
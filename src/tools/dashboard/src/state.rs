@@ -0,0 +1,130 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! What the dashboard shows, fed by the events of the StateManager
+
+use common::statemanager::{
+    state_change_event::Event, AlertRecord, StateChangeEvent, TransitionRecord,
+};
+use ratatui::style::Color;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Transitions and alerts kept for display
+pub const HISTORY: usize = 100;
+
+/// Scenario with the package it targets and the models of the package
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScenarioTree {
+    pub name: String,
+    pub package: Option<String>,
+    pub models: Vec<String>,
+}
+
+/// Line of the resource tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub depth: usize,
+    pub resource_type: &'static str,
+    pub name: String,
+    /// Last known state, empty until one is received
+    pub state: String,
+}
+
+/// Live state of the resources with their recent transitions and alerts
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    /// Scenario hierarchy read from the apiserver
+    pub scenarios: Vec<ScenarioTree>,
+    /// Last state of every resource by type and name
+    pub states: BTreeMap<(String, String), String>,
+    /// Newest first
+    pub transitions: VecDeque<TransitionRecord>,
+    /// Newest first
+    pub alerts: VecDeque<AlertRecord>,
+    /// Whether the subscription is up
+    pub connected: bool,
+    /// Last error of the subscription or the hierarchy refresh
+    pub error: Option<String>,
+}
+
+impl Dashboard {
+    /// Take an event of the subscription into account
+    pub fn apply(&mut self, event: StateChangeEvent) {
+        match event.event {
+            Some(Event::Transition(record)) => {
+                self.states.insert(
+                    (record.resource_type.clone(), record.resource_name.clone()),
+                    record.to_state.clone(),
+                );
+                // The snapshot only seeds the states
+                if record.transition_id != "snapshot" {
+                    push(&mut self.transitions, record);
+                }
+            }
+            Some(Event::Alert(record)) => push(&mut self.alerts, record),
+            None => {}
+        }
+    }
+
+    /// Last known state of a resource
+    pub fn state(&self, resource_type: &str, name: &str) -> &str {
+        self.states
+            .get(&(resource_type.to_string(), name.to_string()))
+            .map_or("", String::as_str)
+    }
+
+    /// Resource tree: scenarios, their package and its models, followed by
+    /// the resources with a state that are in none of the scenarios
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let row = |depth, resource_type, name: &str| Row {
+            depth,
+            resource_type,
+            name: name.to_string(),
+            state: self.state(resource_type, name).to_string(),
+        };
+        for scenario in &self.scenarios {
+            rows.push(row(0, "scenario", &scenario.name));
+            if let Some(package) = &scenario.package {
+                rows.push(row(1, "package", package));
+                for model in &scenario.models {
+                    rows.push(row(2, "model", model));
+                }
+            }
+        }
+        let listed: Vec<(&str, String)> = rows
+            .iter()
+            .map(|row| (row.resource_type, row.name.clone()))
+            .collect();
+        for (resource_type, name) in self.states.keys() {
+            let resource_type = match resource_type.as_str() {
+                "scenario" => "scenario",
+                "package" => "package",
+                "model" => "model",
+                _ => continue,
+            };
+            if !listed.contains(&(resource_type, name.clone())) {
+                rows.push(row(0, resource_type, name));
+            }
+        }
+        rows
+    }
+}
+
+fn push<T>(history: &mut VecDeque<T>, record: T) {
+    history.push_front(record);
+    history.truncate(HISTORY);
+}
+
+/// Color of a state: green when healthy, yellow while in between, red on
+/// failure and gray when unknown
+pub fn state_color(state: &str) -> Color {
+    match state {
+        "Running" | "Allowed" | "Completed" | "Satisfied" | "Ready" => Color::Green,
+        "Error" | "Denied" | "Dead" | "CrashLoopBackOff" | "Failed" => Color::Red,
+        "Idle" | "Waiting" | "Pending" | "Paused" | "Exited" | "Degraded" | "Updating"
+        | "Created" | "Requested" => Color::Yellow,
+        _ => Color::DarkGray,
+    }
+}
@@ -0,0 +1,136 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Drawing of the dashboard
+
+use crate::state::{state_color, Dashboard};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::Frame;
+
+/// Time of day of a timestamp in nanoseconds, UTC
+pub fn clock(timestamp_ns: i64) -> String {
+    let seconds = timestamp_ns.div_euclid(1_000_000_000).rem_euclid(86_400);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn state_span(state: &str) -> Span<'static> {
+    let text = if state.is_empty() { "-" } else { state };
+    Span::styled(text.to_string(), Style::default().fg(state_color(state)))
+}
+
+/// Draw the resource tree on the left, the transitions and alerts on the
+/// right and the status line at the bottom
+pub fn draw(frame: &mut Frame, dashboard: &Dashboard) {
+    let [main, status] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .areas(frame.area());
+    let [tree, side] = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .areas(main);
+    let [transitions, alerts] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .areas(side);
+
+    let rows: Vec<ListItem> = dashboard
+        .rows()
+        .into_iter()
+        .map(|row| {
+            ListItem::new(Line::from(vec![
+                Span::raw("  ".repeat(row.depth)),
+                Span::styled(
+                    format!("{:<9}", row.resource_type),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(format!("{} ", row.name)),
+                state_span(&row.state),
+            ]))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(rows).block(Block::default().borders(Borders::ALL).title(" Resources ")),
+        tree,
+    );
+
+    let items: Vec<ListItem> = dashboard
+        .transitions
+        .iter()
+        .map(|record| {
+            let from = if record.from_state.is_empty() {
+                "?"
+            } else {
+                &record.from_state
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} ", clock(record.timestamp_ns))),
+                Span::raw(format!(
+                    "{} {} ",
+                    record.resource_type, record.resource_name
+                )),
+                state_span(from),
+                Span::raw(" -> "),
+                state_span(&record.to_state),
+            ]))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Recent transitions "),
+        ),
+        transitions,
+    );
+
+    let items: Vec<ListItem> = dashboard
+        .alerts
+        .iter()
+        .map(|record| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} ", clock(record.timestamp_ns))),
+                Span::styled(
+                    format!("{} {} ", record.resource_type, record.resource_name),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(record.message.clone()),
+            ]))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Alerts ({}) ", dashboard.alerts.len())),
+        ),
+        alerts,
+    );
+
+    let connection = if dashboard.connected {
+        Span::styled("connected", Style::default().fg(Color::Green))
+    } else {
+        Span::styled("disconnected", Style::default().fg(Color::Red))
+    };
+    let mut line = vec![
+        Span::raw(" StateManager "),
+        connection,
+        Span::raw("  q: quit  r: refresh  c: clear alerts"),
+    ];
+    if let Some(error) = &dashboard.error {
+        line.push(Span::styled(
+            format!("  {}", error),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(line)), status);
+}
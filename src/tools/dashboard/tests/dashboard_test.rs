@@ -0,0 +1,97 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Dashboard state tests
+
+use common::statemanager::{
+    state_change_event::Event, AlertRecord, StateChangeEvent, TransitionRecord,
+};
+use dashboard::client::{package_models, scenario_target};
+use dashboard::state::{state_color, Row};
+use dashboard::ui::clock;
+use dashboard::{Dashboard, ScenarioTree};
+use ratatui::style::Color;
+
+fn transition(resource_type: &str, name: &str, to: &str, transition_id: &str) -> StateChangeEvent {
+    StateChangeEvent {
+        event: Some(Event::Transition(TransitionRecord {
+            resource_type: resource_type.to_string(),
+            resource_name: name.to_string(),
+            to_state: to.to_string(),
+            transition_id: transition_id.to_string(),
+            ..Default::default()
+        })),
+    }
+}
+
+#[test]
+fn test_state_colors() {
+    assert_eq!(state_color("Running"), Color::Green);
+    assert_eq!(state_color("CrashLoopBackOff"), Color::Red);
+    assert_eq!(state_color("Degraded"), Color::Yellow);
+    assert_eq!(state_color(""), Color::DarkGray);
+}
+
+#[test]
+fn test_snapshot_seeds_states_only() {
+    let mut dashboard = Dashboard::default();
+    dashboard.apply(transition("model", "hello", "Running", "snapshot"));
+    dashboard.apply(transition("model", "hello", "Dead", "t-1"));
+    dashboard.apply(StateChangeEvent {
+        event: Some(Event::Alert(AlertRecord {
+            resource_type: "model".to_string(),
+            resource_name: "hello".to_string(),
+            message: "stuck".to_string(),
+            timestamp_ns: 0,
+        })),
+    });
+    assert_eq!(dashboard.state("model", "hello"), "Dead");
+    assert_eq!(dashboard.transitions.len(), 1);
+    assert_eq!(dashboard.alerts.len(), 1);
+}
+
+#[test]
+fn test_rows_follow_hierarchy() {
+    let mut dashboard = Dashboard {
+        scenarios: vec![ScenarioTree {
+            name: "hello".to_string(),
+            package: Some("hello-pkg".to_string()),
+            models: vec!["hello-model".to_string()],
+        }],
+        ..Default::default()
+    };
+    dashboard.apply(transition("package", "hello-pkg", "Running", "t-1"));
+    dashboard.apply(transition("scenario", "other", "Waiting", "t-2"));
+
+    let row = |depth, resource_type, name: &str, state: &str| Row {
+        depth,
+        resource_type,
+        name: name.to_string(),
+        state: state.to_string(),
+    };
+    assert_eq!(
+        dashboard.rows(),
+        vec![
+            row(0, "scenario", "hello", ""),
+            row(1, "package", "hello-pkg", "Running"),
+            row(2, "model", "hello-model", ""),
+            row(0, "scenario", "other", "Waiting"),
+        ]
+    );
+}
+
+#[test]
+fn test_hierarchy_from_yaml() {
+    let scenario = "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: hello\nspec:\n  action: update\n  target: hello-pkg\n";
+    let package = "apiVersion: v1\nkind: Package\nmetadata:\n  name: hello-pkg\nspec:\n  models:\n    - name: a\n      node: n1\n    - name: b\n      node: n2\n";
+    assert_eq!(scenario_target(scenario), Some("hello-pkg".to_string()));
+    assert_eq!(package_models(package), vec!["a", "b"]);
+    assert!(package_models("not: [yaml").is_empty());
+}
+
+#[test]
+fn test_clock() {
+    assert_eq!(clock(0), "00:00:00");
+    assert_eq!(clock((3600 + 62) * 1_000_000_000), "01:01:02");
+}
@@ -0,0 +1,161 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! REST API client for the apiserver
+
+use crate::error::{CliError, Result};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use serde_json::Value;
+use std::time::Duration;
+
+/// Which logs of a container to read
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LogOptions {
+    /// Container of the model, may be left out for single container models
+    pub container: Option<String>,
+    /// Number of lines from the end, 0 for all of them
+    pub tail: i64,
+    /// Unix time in seconds of the oldest line, 0 for no limit
+    pub since: i64,
+    /// Keep streaming new lines
+    pub follow: bool,
+}
+
+impl LogOptions {
+    /// Query parameters of the logs route
+    pub fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        if let Some(container) = &self.container {
+            query.push(("container", container.clone()));
+        }
+        if self.tail > 0 {
+            query.push(("tail", self.tail.to_string()));
+        }
+        if self.since > 0 {
+            query.push(("since", self.since.to_string()));
+        }
+        if self.follow {
+            query.push(("follow", "true".to_string()));
+        }
+        query
+    }
+}
+
+/// HTTP client of the apiserver REST API
+pub struct PiccoloClient {
+    client: Client,
+    base_url: String,
+    timeout: Duration,
+    token: Option<String>,
+}
+
+impl PiccoloClient {
+    /// Create a new PiccoloClient
+    ///
+    /// # Arguments
+    /// * `base_url` - Base URL of the apiserver (e.g., "http://localhost:47099")
+    /// * `timeout` - Request timeout in seconds, followed logs are not limited
+    /// * `token` - Bearer token sent when authentication is enabled
+    pub fn new(base_url: &str, timeout: u64, token: Option<String>) -> Result<Self> {
+        let client = Client::builder().build().map_err(CliError::Http)?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            timeout: Duration::from_secs(timeout),
+            token: token.filter(|token| !token.is_empty()),
+        })
+    }
+
+    fn request(&self, method: Method, endpoint: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, endpoint));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Apply artifacts, `yaml` may hold several documents
+    pub async fn apply(&self, yaml: &str) -> Result<()> {
+        let request = self
+            .request(Method::POST, "/api/artifact")
+            .timeout(self.timeout)
+            .header("Content-Type", "text/plain")
+            .body(yaml.to_owned());
+        check(request.send().await?).await?;
+        Ok(())
+    }
+
+    /// Withdraw the scenario of the artifacts in `yaml`
+    pub async fn withdraw(&self, yaml: &str) -> Result<()> {
+        let request = self
+            .request(Method::DELETE, "/api/artifact")
+            .timeout(self.timeout)
+            .header("Content-Type", "text/plain")
+            .body(yaml.to_owned());
+        check(request.send().await?).await?;
+        Ok(())
+    }
+
+    /// List the applied artifacts of a kind, all pages of them
+    ///
+    /// # Arguments
+    /// * `kind` - artifact kind such as `scenario`
+    /// * `selector` - label selector, e.g. `app=hello`, empty for all
+    pub async fn list(&self, kind: &str, selector: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let mut query = vec![("selector", selector.to_string())];
+            if let Some(after) = &after {
+                query.push(("continue", after.clone()));
+            }
+            let request = self
+                .request(Method::GET, &format!("/api/artifact/{}", kind))
+                .timeout(self.timeout)
+                .query(&query);
+            let page: Value = check(request.send().await?).await?.json().await?;
+            if let Some(page_items) = page["items"].as_array() {
+                items.extend(page_items.iter().cloned());
+            }
+            match page["continue"].as_str() {
+                Some(next) => after = Some(next.to_string()),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    /// Get an applied artifact with its yaml
+    pub async fn get(&self, kind: &str, name: &str) -> Result<Value> {
+        let request = self
+            .request(Method::GET, &format!("/api/artifact/{}/{}", kind, name))
+            .timeout(self.timeout);
+        Ok(check(request.send().await?).await?.json().await?)
+    }
+
+    /// Open the logs of a container of a model, the body streams the lines
+    pub async fn logs(&self, model: &str, options: &LogOptions) -> Result<Response> {
+        let mut request = self
+            .request(Method::GET, &format!("/api/logs/{}", model))
+            .query(&options.query());
+        if !options.follow {
+            request = request.timeout(self.timeout);
+        }
+        check(request.send().await?).await
+    }
+}
+
+/// Turn an error status into an error with the message of the apiserver
+async fn check(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let text = response.text().await.unwrap_or_default();
+    // The apiserver answers errors with a JSON string
+    let message = serde_json::from_str::<String>(&text).unwrap_or(text);
+    Err(CliError::Api { status, message })
+}
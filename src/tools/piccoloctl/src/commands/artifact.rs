@@ -0,0 +1,188 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Apply, withdraw and inspect artifacts
+
+use crate::commands::{print_info, print_success, read_source, resolve_kind};
+use crate::{CliError, PiccoloClient, Result};
+use colored::Colorize;
+use serde_json::Value;
+
+/// Kind and name of the documents of an artifact file
+pub fn documents(yaml: &str) -> Vec<(String, String)> {
+    yaml.split("\n---")
+        .filter_map(|doc| {
+            let kind = top_level(doc, "kind")?;
+            let name = doc
+                .lines()
+                .skip_while(|line| !line.starts_with("metadata:"))
+                .skip(1)
+                .take_while(|line| line.starts_with(' '))
+                .find_map(|line| line.trim().strip_prefix("name:"))
+                .map(|name| name.trim().trim_matches('"').to_string())
+                .unwrap_or_default();
+            Some((kind, name))
+        })
+        .collect()
+}
+
+fn top_level(doc: &str, key: &str) -> Option<String> {
+    doc.lines()
+        .find_map(|line| line.strip_prefix(&format!("{}:", key)))
+        .map(|value| value.trim().trim_matches('"').to_string())
+}
+
+/// Apply the artifacts of a file, '-' for stdin
+pub async fn apply(client: &PiccoloClient, file: &str) -> Result<()> {
+    let yaml = read_source(file)?;
+    let documents = documents(&yaml);
+    if documents.is_empty() {
+        return Err(CliError::Custom(format!("No artifacts in {}", file)));
+    }
+    client.apply(&yaml).await?;
+    for (kind, name) in documents {
+        print_success(&format!("{}/{} applied", kind.to_lowercase(), name));
+    }
+    Ok(())
+}
+
+/// Withdraw the scenario of a file, or an applied scenario by name
+pub async fn withdraw(
+    client: &PiccoloClient,
+    file: Option<&str>,
+    name: Option<&str>,
+) -> Result<()> {
+    let yaml = match (file, name) {
+        (Some(file), _) => read_source(file)?,
+        (None, Some(name)) => {
+            let scenario = client.get("scenario", name).await?;
+            scenario["yaml"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| CliError::Custom(format!("Scenario {} has no yaml", name)))?
+        }
+        (None, None) => {
+            return Err(CliError::Custom(
+                "Give a scenario name or a file with -f".to_string(),
+            ))
+        }
+    };
+    client.withdraw(&yaml).await?;
+    let scenario = documents(&yaml)
+        .into_iter()
+        .find(|(kind, _)| kind == "Scenario")
+        .map(|(_, name)| name)
+        .unwrap_or_default();
+    print_success(&format!("scenario/{} withdrawn", scenario));
+    Ok(())
+}
+
+/// Table of artifacts, with the status column for scenarios
+pub fn table(kind: &str, items: &[Value]) -> String {
+    let with_status = kind == "scenario";
+    let rows: Vec<Vec<String>> = items
+        .iter()
+        .map(|item| {
+            let labels = item["labels"]
+                .as_object()
+                .map(|labels| {
+                    labels
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or_default()))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .unwrap_or_default();
+            let mut row = vec![item["name"].as_str().unwrap_or_default().to_string()];
+            if with_status {
+                row.push(item["status"].as_str().unwrap_or("-").to_string());
+            }
+            row.push(if labels.is_empty() {
+                "<none>".to_string()
+            } else {
+                labels
+            });
+            row
+        })
+        .collect();
+
+    let header: Vec<String> = if with_status {
+        vec!["NAME", "STATUS", "LABELS"]
+    } else {
+        vec!["NAME", "LABELS"]
+    }
+    .into_iter()
+    .map(str::to_string)
+    .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("   ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// List the applied artifacts of a kind
+pub async fn get(client: &PiccoloClient, kind: &str, selector: &str) -> Result<()> {
+    let kind = resolve_kind(kind)?;
+    let items = client.list(kind, selector).await?;
+    if items.is_empty() {
+        print_info(&format!("No {} artifacts found", kind));
+    } else {
+        println!("{}", table(kind, &items));
+    }
+    Ok(())
+}
+
+/// Show an applied artifact with its status and yaml
+///
+/// `resource` is `kind/name`, or the kind when the name is given apart.
+pub async fn describe(client: &PiccoloClient, resource: &str, name: Option<&str>) -> Result<()> {
+    let (kind, name) = match (resource.split_once('/'), name) {
+        (Some((kind, name)), None) => (kind, name),
+        (None, Some(name)) => (resource, name),
+        _ => {
+            return Err(CliError::Custom(
+                "Describe a resource as <kind>/<name> or <kind> <name>".to_string(),
+            ))
+        }
+    };
+    let kind = resolve_kind(kind)?;
+    let artifact = client.get(kind, name).await?;
+
+    println!(
+        "{} {}",
+        "Kind:".bold(),
+        artifact["kind"].as_str().unwrap_or(kind)
+    );
+    println!("{} {}", "Name:".bold(), name);
+    if let Some(status) = artifact["status"].as_str() {
+        println!("{} {}", "Status:".bold(), status);
+    }
+    println!(
+        "\n{}",
+        artifact["yaml"].as_str().unwrap_or_default().trim_end()
+    );
+    if let Some(pod) = artifact["pod"].as_str() {
+        println!("\n{}\n{}", "Pod:".bold(), pod.trim_end());
+    }
+    Ok(())
+}
@@ -0,0 +1,19 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Logs of the containers of a model
+
+use crate::{LogOptions, PiccoloClient, Result};
+use std::io::Write;
+
+/// Print the logs of a container of a model as they arrive
+pub async fn logs(client: &PiccoloClient, model: &str, options: &LogOptions) -> Result<()> {
+    let mut response = client.logs(model, options).await?;
+    let mut stdout = std::io::stdout();
+    while let Some(chunk) = response.chunk().await? {
+        stdout.write_all(&chunk)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Command implementations for piccoloctl
+
+pub mod artifact;
+pub mod logs;
+
+use crate::{CliError, Result};
+use colored::Colorize;
+use std::io::Read;
+
+/// Artifact kinds the apiserver stores
+const KINDS: [&str; 6] = ["scenario", "package", "model", "volume", "network", "node"];
+
+/// Artifact kind of a command argument, singular or plural in any case
+pub fn resolve_kind(kind: &str) -> Result<&'static str> {
+    let kind = kind.to_ascii_lowercase();
+    let singular = kind.strip_suffix('s').unwrap_or(&kind);
+    KINDS
+        .iter()
+        .find(|k| **k == kind || **k == singular)
+        .copied()
+        .ok_or_else(|| {
+            CliError::Custom(format!(
+                "Unknown kind '{}', expected one of {}",
+                kind,
+                KINDS.join(", ")
+            ))
+        })
+}
+
+/// Read a file, or stdin for '-'
+pub fn read_source(path: &str) -> Result<String> {
+    if path == "-" {
+        let mut buffer = String::new();
+        std::io::stdin().read_to_string(&mut buffer)?;
+        Ok(buffer)
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| CliError::Custom(format!("Cannot read {}: {}", path, e)))
+    }
+}
+
+/// Helper function to print success messages
+pub fn print_success(message: &str) {
+    println!("{} {}", "✓".green().bold(), message);
+}
+
+/// Helper function to print info messages
+pub fn print_info(message: &str) {
+    println!("{} {}", "ℹ".blue().bold(), message);
+}
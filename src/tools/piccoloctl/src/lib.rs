@@ -0,0 +1,15 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! piccoloctl Library
+//!
+//! This library provides the core functionality for the piccoloctl tool,
+//! which applies and inspects Piccolo artifacts through the apiserver REST API.
+
+pub mod client;
+pub mod commands;
+pub mod error;
+
+pub use client::{LogOptions, PiccoloClient};
+pub use error::{CliError, Result};
@@ -0,0 +1,135 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! piccoloctl - Command Line Interface for the Piccolo apiserver
+//!
+//! Applies and withdraws artifacts, lists and describes the applied ones and
+//! reads the logs of model containers through the apiserver REST API.
+
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use piccoloctl::commands::{artifact, logs};
+use piccoloctl::{LogOptions, PiccoloClient};
+
+#[derive(Parser)]
+#[command(name = "piccoloctl")]
+#[command(about = "CLI tool for the Piccolo apiserver")]
+#[command(version)]
+#[command(long_about = None)]
+struct Cli {
+    /// apiserver URL
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "PICCOLO_SERVER",
+        default_value = "http://localhost:47099"
+    )]
+    server: String,
+
+    /// Bearer token, needed when the apiserver has authentication enabled
+    #[arg(long, global = true, env = "PICCOLO_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Request timeout in seconds
+    #[arg(short, long, global = true, default_value = "30")]
+    timeout: u64,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Apply the artifacts of a yaml file
+    Apply {
+        /// Path to the yaml file or '-' for stdin
+        #[arg(short, long)]
+        file: String,
+    },
+    /// Withdraw a scenario, by name or from its yaml file
+    Withdraw {
+        /// Name of the applied scenario
+        #[arg(required_unless_present = "file")]
+        scenario: Option<String>,
+        /// Path to the yaml file or '-' for stdin
+        #[arg(short, long, conflicts_with = "scenario")]
+        file: Option<String>,
+    },
+    /// List applied artifacts of a kind
+    Get {
+        /// scenarios, packages, models, volumes, networks or nodes
+        kind: String,
+        /// Label selector, e.g. app=hello,tier!=debug
+        #[arg(short = 'l', long, default_value = "")]
+        selector: String,
+    },
+    /// Show an applied artifact with its status and yaml
+    Describe {
+        /// <kind>/<name>, or the kind followed by the name
+        resource: String,
+        name: Option<String>,
+    },
+    /// Print the logs of a container of a model
+    Logs {
+        /// Name of the model
+        model: String,
+        /// Container of the model, needed when it has several
+        #[arg(short, long)]
+        container: Option<String>,
+        /// Number of lines from the end
+        #[arg(long, default_value = "0")]
+        tail: i64,
+        /// Unix time in seconds of the oldest line
+        #[arg(long, default_value = "0")]
+        since: i64,
+        /// Keep printing new lines
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let client = match PiccoloClient::new(&cli.server, cli.timeout, cli.token) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{} Failed to create client: {}", "✗".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = match cli.command {
+        Commands::Apply { file } => artifact::apply(&client, &file).await,
+        Commands::Withdraw { scenario, file } => {
+            artifact::withdraw(&client, file.as_deref(), scenario.as_deref()).await
+        }
+        Commands::Get { kind, selector } => artifact::get(&client, &kind, &selector).await,
+        Commands::Describe { resource, name } => {
+            artifact::describe(&client, &resource, name.as_deref()).await
+        }
+        Commands::Logs {
+            model,
+            container,
+            tail,
+            since,
+            follow,
+        } => {
+            let options = LogOptions {
+                container,
+                tail,
+                since,
+                follow,
+            };
+            logs::logs(&client, &model, &options).await
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{} {}", "✗".red().bold(), e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,79 @@
+/*
+* SPDX-FileCopyrightText: Copyright 2024 LG Electronics Inc.
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! CLI-specific tests
+
+use piccoloctl::commands::artifact::{documents, table};
+use piccoloctl::commands::resolve_kind;
+use piccoloctl::{CliError, LogOptions, PiccoloClient};
+use serde_json::json;
+
+#[test]
+fn test_error_display() {
+    let error = CliError::Api {
+        status: 404,
+        message: "Scenario hello not found".to_string(),
+    };
+    assert_eq!(
+        format!("{}", error),
+        "apiserver (404): Scenario hello not found"
+    );
+}
+
+#[test]
+fn test_client_creation() {
+    assert!(PiccoloClient::new("http://localhost:47099/", 30, None).is_ok());
+}
+
+#[test]
+fn test_resolve_kind() {
+    assert_eq!(resolve_kind("scenarios").unwrap(), "scenario");
+    assert_eq!(resolve_kind("Model").unwrap(), "model");
+    assert_eq!(resolve_kind("nodes").unwrap(), "node");
+    assert!(resolve_kind("pods").is_err());
+}
+
+#[test]
+fn test_documents_of_artifact_file() {
+    let yaml = "apiVersion: v1\nkind: Scenario\nmetadata:\n  name: hello\nspec:\n  action: update\n---\napiVersion: v1\nkind: Package\nmetadata:\n  labels:\n    app: hello\n  name: hello-pkg\n";
+    assert_eq!(
+        documents(yaml),
+        vec![
+            ("Scenario".to_string(), "hello".to_string()),
+            ("Package".to_string(), "hello-pkg".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_table_of_scenarios() {
+    let items = vec![
+        json!({"name": "hello", "labels": {"app": "hello"}, "status": "running"}),
+        json!({"name": "a", "labels": {}}),
+    ];
+    assert_eq!(
+        table("scenario", &items),
+        "NAME    STATUS    LABELS\nhello   running   app=hello\na       -         <none>"
+    );
+    assert_eq!(table("model", &items[1..]), "NAME   LABELS\na      <none>");
+}
+
+#[test]
+fn test_log_options_query() {
+    assert!(LogOptions::default().query().is_empty());
+    let options = LogOptions {
+        container: Some("main".to_string()),
+        tail: 50,
+        since: 0,
+        follow: true,
+    };
+    assert_eq!(
+        options.query(),
+        vec![
+            ("container", "main".to_string()),
+            ("tail", "50".to_string()),
+            ("follow", "true".to_string()),
+        ]
+    );
+}